@@ -0,0 +1,324 @@
+//! 基准测试命令
+//!
+//! `atp bench input-latency` 在本地启动验证服务器, 等目标虚拟机的 Agent 连上后反复执行
+//! "QMP send-key + Agent 确认", 统计端到端输入延迟并写入数据库;
+//! `atp bench compare` 比较两次运行的结果。
+
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use atp_executor::benchmark::{run_input_latency, ComparisonRow, INPUT_LATENCY_KIND, INPUT_LATENCY_METRIC};
+use atp_executor::{BenchmarkComparison, InputLatencyOptions, InputLatencyRun, QmpInputProbe, SampleOutcome};
+use atp_storage::{BenchmarkFilter, EntityMetricSample, MetricEntity, Storage, StorageManager};
+use chrono::{Local, Utc};
+use colored::Colorize;
+use indicatif::{ProgressBar, ProgressStyle};
+use tokio_util::sync::CancellationToken;
+use verification_server::{Daemon, DaemonConfig};
+
+use crate::commands::common::{output_format, print_serialized, progress};
+use crate::commands::vm_target::VmTarget;
+
+pub async fn handle(action: crate::BenchAction, profile: Option<&str>) -> Result<()> {
+    match action {
+        crate::BenchAction::InputLatency {
+            target,
+            samples,
+            interval_ms,
+            key,
+            timeout_ms,
+            agent_id,
+            agent_wait_secs,
+            server_config,
+            label,
+            no_save,
+            format,
+        } => {
+            let options = InputLatencyOptions::new(samples, Duration::from_millis(interval_ms))
+                .with_key(&key)
+                .with_timeout(Duration::from_millis(timeout_ms));
+            let target = VmTarget::from_args(&target, profile).await?;
+            input_latency(
+                &target,
+                &options,
+                agent_id.as_deref(),
+                Duration::from_secs(agent_wait_secs),
+                server_config.as_deref(),
+                label,
+                !no_save,
+                &format,
+            )
+            .await
+        }
+        crate::BenchAction::Compare { base, other, format } => compare(base, other, &format).await,
+        crate::BenchAction::List { vm, limit } => list_runs(vm, limit).await,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn input_latency(
+    target: &VmTarget,
+    options: &InputLatencyOptions,
+    agent_id: Option<&str>,
+    agent_wait: Duration,
+    server_config: Option<&str>,
+    label: Option<String>,
+    save: bool,
+    format: &str,
+) -> Result<()> {
+    let format = output_format(Some(format))?;
+    // Agent 默认以虚拟机名称注册
+    let agent_id = agent_id.unwrap_or(target.vm());
+
+    let config = match server_config {
+        Some(path) => DaemonConfig::load(shellexpand::tilde(path).as_ref())?,
+        None => DaemonConfig::default(),
+    };
+    let daemon = Daemon::start(config).await.context("启动验证服务器失败")?;
+    let service = daemon.service().clone();
+
+    progress!(format, "{} 等待虚拟机 {} 的 Agent 连接 (最长 {} 秒)...", "⏳".cyan(), agent_id.yellow(), agent_wait.as_secs());
+    let deadline = tokio::time::Instant::now() + agent_wait;
+    while !service.list_clients().await.iter().any(|client| client.vm_id == agent_id) {
+        if tokio::time::Instant::now() >= deadline {
+            daemon.shutdown().await.ok();
+            anyhow::bail!("等待 Agent {} 连接超时, 请确认虚拟机中的 Agent 已指向本机的验证服务器", agent_id);
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    let qmp = target.qmp().await?;
+    let mut probe = QmpInputProbe::new(qmp, service, agent_id);
+
+    progress!(
+        format,
+        "{} 开始采样: {} 个样本, 间隔 {}ms, 按键 {}",
+        "▶".cyan(),
+        options.samples,
+        options.interval_ms,
+        options.key.green()
+    );
+
+    let bar = if format.is_table() { ProgressBar::new(options.samples as u64) } else { ProgressBar::hidden() };
+    bar.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("=>-"),
+    );
+
+    // Ctrl-C 停止采样, 已采集的样本照常统计与保存
+    let cancel = CancellationToken::new();
+    let ctrl_c = tokio::spawn({
+        let cancel = cancel.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                cancel.cancel();
+            }
+        }
+    });
+
+    // 观察到的样本同时记为虚拟机的时序指标, 可用 `atp report metrics` 查看
+    let entity = MetricEntity::vm(target.vm());
+    let mut points = Vec::new();
+    let mut run = run_input_latency(&mut probe, target.vm(), options, &cancel, |_, outcome| {
+        match outcome {
+            SampleOutcome::Observed { latency_ms } => {
+                points.push(EntityMetricSample::new(&entity, INPUT_LATENCY_METRIC, *latency_ms as f64, Utc::now()));
+                bar.set_message(format!("{}ms", latency_ms))
+            }
+            SampleOutcome::Mismatched => bar.set_message("不匹配".to_string()),
+            SampleOutcome::TimedOut => bar.set_message("超时".to_string()),
+            SampleOutcome::Failed(error) => bar.set_message(error.clone()),
+        }
+        bar.inc(1);
+    })
+    .await;
+    ctrl_c.abort();
+    bar.finish_and_clear();
+    daemon.shutdown().await.ok();
+
+    if cancel.is_cancelled() {
+        progress!(format, "{} 已中断, 共采集 {} 个样本", "⚠".yellow(), run.completed());
+    }
+    run.label = label;
+
+    if save && run.completed() > 0 {
+        let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+        let storage = Storage::from_manager(&storage_manager);
+        run.id = Some(storage.benchmarks().create(&run.to_record()).await?);
+        storage.metrics().insert_batch(&points).await?;
+    }
+
+    if !format.is_table() {
+        let summary = serde_json::json!({
+            "run": run,
+            "percentiles": run.percentiles(),
+            "histogram": run.histogram(),
+        });
+        return print_serialized(&summary, format);
+    }
+
+    print_run(&run);
+    Ok(())
+}
+
+/// 输出一次运行的统计与直方图
+fn print_run(run: &InputLatencyRun) {
+    println!("\n{} 输入延迟: {}", "📊".cyan(), run.target.yellow());
+    if let Some(label) = &run.label {
+        println!("  标签: {}", label);
+    }
+    println!(
+        "  样本: {} (观察到 {}, 超时 {}, 不匹配 {}, 发送失败 {})",
+        run.completed(),
+        run.latencies_ms.len().to_string().green(),
+        run.timeouts.to_string().yellow(),
+        run.mismatched,
+        run.failures
+    );
+    if let Some(error) = &run.last_error {
+        println!("  最近一次发送失败: {}", error.red());
+    }
+
+    match run.percentiles() {
+        Some(stats) => {
+            println!(
+                "  min {}ms / median {}ms / p95 {}ms / p99 {}ms / max {}ms (平均 {:.1}ms)",
+                stats.min_ms, stats.median_ms, stats.p95_ms, stats.p99_ms, stats.max_ms, stats.mean_ms
+            );
+            println!("\n{}", run.histogram().render(40));
+        }
+        None => println!("  {} 没有观察到的样本, 无法统计延迟", "ℹ".yellow()),
+    }
+
+    if let Some(id) = run.id {
+        println!("\n{} 结果已保存, ID: {}", "✓".green(), id.to_string().yellow());
+    }
+}
+
+/// 比较两次运行 (`atp bench compare`)
+async fn compare(base: i64, other: i64, format: &str) -> Result<()> {
+    let format = output_format(Some(format))?;
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let load = |id: i64| {
+        let storage = &storage;
+        async move {
+            storage
+                .benchmarks()
+                .get_by_id(id)
+                .await?
+                .with_context(|| format!("未找到基准测试运行 ID: {}", id))
+        }
+    };
+    let base = load(base).await?;
+    let other = load(other).await?;
+    if base.kind != other.kind {
+        anyhow::bail!("两次运行的类型不同 ({} / {}), 无法比较", base.kind, other.kind);
+    }
+    if base.target != other.target {
+        eprintln!("{} 两次运行的目标不同: {} / {}", "⚠".yellow(), base.target, other.target);
+    }
+
+    let comparison = BenchmarkComparison::new(&base, &other);
+    if !format.is_table() {
+        return print_serialized(&comparison, format);
+    }
+
+    let title = |id: i64, label: &Option<String>| match label {
+        Some(label) => format!("#{} ({})", id, label),
+        None => format!("#{}", id),
+    };
+    println!(
+        "\n{} {} → {}\n",
+        "📈".cyan(),
+        title(comparison.base_id, &comparison.base_label).yellow(),
+        title(comparison.other_id, &comparison.other_label).yellow()
+    );
+    println!("{:<22} {:>10} {:>10} {:>10} {:>9}", "指标".bold(), "基准".bold(), "对比".bold(), "差值".bold(), "变化".bold());
+    println!("{}", "-".repeat(66));
+    for row in &comparison.rows {
+        print_comparison_row(row);
+    }
+
+    Ok(())
+}
+
+fn print_comparison_row(row: &ComparisonRow) {
+    let value = |value: Option<f64>| value.map(|value| format!("{:.1}", value)).unwrap_or_else(|| "N/A".to_string());
+    let delta = row.delta().map(|delta| format!("{:+.1}", delta)).unwrap_or_else(|| "N/A".to_string());
+    let percent = row
+        .delta_percent()
+        .map(|percent| format!("{:+.1}%", percent))
+        .unwrap_or_else(|| "-".to_string());
+
+    // 延迟与超时率变大是退化, 观察到的样本数变少是退化
+    let worse = match row.delta() {
+        Some(delta) if row.metric == "observed" => delta < 0.0,
+        Some(delta) => delta > 0.0,
+        None => false,
+    };
+    let percent = if worse { percent.red() } else { percent.green() };
+
+    println!(
+        "{:<22} {:>10} {:>10} {:>10} {:>9}",
+        row.metric,
+        value(row.base),
+        value(row.other),
+        delta,
+        percent
+    );
+}
+
+/// 列出最近的运行 (`atp bench list`)
+async fn list_runs(vm: Option<String>, limit: i64) -> Result<()> {
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let runs = storage
+        .benchmarks()
+        .list(&BenchmarkFilter {
+            kind: Some(INPUT_LATENCY_KIND.to_string()),
+            target: vm,
+            limit: Some(limit),
+        })
+        .await?;
+
+    if runs.is_empty() {
+        println!("{} 没有基准测试记录", "ℹ".yellow());
+        return Ok(());
+    }
+
+    println!(
+        "{:<6} {:<20} {:<20} {:<16} {:>8} {:>8} {:>8} {:>8}",
+        "ID".bold(),
+        "时间".bold(),
+        "虚拟机".bold(),
+        "标签".bold(),
+        "样本".bold(),
+        "超时".bold(),
+        "median".bold(),
+        "p99".bold()
+    );
+    println!("{}", "-".repeat(100));
+
+    let ms = |ms: Option<i64>| ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "N/A".to_string());
+    for run in &runs {
+        println!(
+            "{:<6} {:<20} {:<20} {:<16} {:>8} {:>8} {:>8} {:>8}",
+            run.id,
+            run.started_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+            run.target,
+            run.label.as_deref().unwrap_or("-"),
+            run.samples,
+            run.timeouts,
+            ms(run.median_ms),
+            ms(run.p99_ms)
+        );
+    }
+
+    Ok(())
+}
@@ -1,32 +1,136 @@
 //! Command execution 命令处理
 
-use anyhow::Result;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use atp_executor::{Action, ExecutorError, PowerShellOutput, PowerShellScript, ScenarioRunner};
 use colored::Colorize;
+use serde::Serialize;
 
-use crate::config::CliConfig;
+use crate::commands::common::{output_format, print_rendered, print_serialized, progress, OutputFormat};
+use crate::commands::vm_target::{ActionResult, VmTarget};
+use crate::i18n::{t, MsgKey};
+use crate::VmTargetArgs;
 
-pub async fn handle(action: crate::CommandAction) -> Result<()> {
+pub async fn handle(action: crate::CommandAction, profile: Option<&str>) -> Result<()> {
     match action {
-        crate::CommandAction::Exec { host, vm, cmd } => {
-            exec_command(&host, &vm, &cmd).await
+        crate::CommandAction::Exec { target, cmd } => {
+            exec_command(&target, profile, &cmd).await
+        }
+        crate::CommandAction::Ps { target, script, inline, args, as_json, timeout } => {
+            let scripts = load_scripts(&script, &inline, &args, as_json)?;
+            exec_powershell(&target, profile, &scripts, Duration::from_secs(timeout)).await
         }
     }
 }
 
-async fn exec_command(host_id: &str, vm_name: &str, cmd: &str) -> Result<()> {
-    println!("{} 准备执行命令...", "⚙".cyan());
-    println!("  主机: {}", host_id.yellow());
-    println!("  虚拟机: {}", vm_name.yellow());
-    println!("  命令: {}", cmd.green());
+async fn exec_command(args: &VmTargetArgs, profile: Option<&str>, cmd: &str) -> Result<()> {
+    let format = output_format(None)?;
+    let target = VmTarget::from_args(args, profile).await?;
+
+    progress!(format, "{} {}", "⚙".cyan(), t(MsgKey::PreparingCommand));
+    progress!(format, "  {}: {}", t(MsgKey::LabelHost), target.host_id().yellow());
+    progress!(format, "  {}: {}", t(MsgKey::LabelVm), target.vm().yellow());
+    progress!(format, "  {}: {}", t(MsgKey::LabelCommand), cmd.green());
 
-    // 验证主机配置存在
-    let config = CliConfig::load()?;
-    let _host_config = config.get_host(host_id)?;
+    // 通过 QGA 执行, 与场景中的 exec_command 相同
+    let step = target.run(Action::ExecCommand { command: cmd.to_string() }).await?;
 
-    // TODO: 实现实际的命令执行
-    // 需要通过 QGA 协议执行命令
-    println!("\n{} 此功能需要通过场景文件使用", "ℹ".cyan());
-    println!("  提示: 使用 'atp scenario run <file>' 来执行完整的测试场景");
+    print_rendered(&ActionResult::executed(&target, "exec_command", step), format)
+}
+
+/// 读取脚本文件与内联脚本, 返回 (名称, 脚本) 列表
+fn load_scripts(
+    files: &[String],
+    inline: &[String],
+    args: &[String],
+    as_json: bool,
+) -> Result<Vec<(String, PowerShellScript)>> {
+    let mut scripts = Vec::new();
+    for file in files {
+        let source = std::fs::read_to_string(file).with_context(|| format!("读取脚本文件失败: {}", file))?;
+        scripts.push((file.clone(), source));
+    }
+    for (index, source) in inline.iter().enumerate() {
+        scripts.push((format!("inline-{}", index + 1), source.clone()));
+    }
+
+    Ok(scripts
+        .into_iter()
+        .map(|(name, source)| {
+            let script = PowerShellScript::new(source).with_args(args.to_vec()).with_json(as_json);
+            (name, script)
+        })
+        .collect())
+}
+
+/// 一个脚本的执行结果 (json / yaml 输出)
+#[derive(Serialize)]
+struct PsResult<'a> {
+    script: &'a str,
+    #[serde(flatten)]
+    output: &'a PowerShellOutput,
+}
 
+/// 依次执行 PowerShell 脚本, 每个脚本结束后立即输出结果
+///
+/// 所有脚本共用一个 QGA 连接; 任一脚本失败时停止, 并以对应的退出码结束进程。
+async fn exec_powershell(
+    args: &VmTargetArgs,
+    profile: Option<&str>,
+    scripts: &[(String, PowerShellScript)],
+    timeout: Duration,
+) -> Result<()> {
+    let format = output_format(None)?;
+    let target = VmTarget::from_args(args, profile).await?;
+
+    progress!(format, "{} {}", "⚙".cyan(), t(MsgKey::PreparingCommand));
+    progress!(format, "  {}: {}", t(MsgKey::LabelHost), target.host_id().yellow());
+    progress!(format, "  {}: {}", t(MsgKey::LabelVm), target.vm().yellow());
+
+    let mut runner = target.runner().await?;
+    runner.attach(Some(target.host_id()), target.vm()).await?;
+
+    let result = run_scripts(&runner, scripts, timeout, format).await;
+    runner.detach().await;
+
+    match result {
+        Err(e) => match e.downcast_ref::<ExecutorError>() {
+            Some(ExecutorError::PowerShell(err)) => {
+                eprintln!("{} {}", "✗".red(), err);
+                std::process::exit(err.exit_code());
+            }
+            _ => Err(e),
+        },
+        Ok(()) => Ok(()),
+    }
+}
+
+async fn run_scripts(
+    runner: &ScenarioRunner,
+    scripts: &[(String, PowerShellScript)],
+    timeout: Duration,
+    format: OutputFormat,
+) -> Result<()> {
+    for (name, script) in scripts {
+        progress!(format, "\n{} {}: {}", "▶".cyan(), t(MsgKey::LabelScript), name.green());
+        let output = runner.exec_powershell(script, timeout).await?;
+
+        match (format, &output.json) {
+            (OutputFormat::Table, _) => {
+                let stdout = output.stdout.trim_end();
+                if !stdout.is_empty() {
+                    println!("{}", stdout);
+                }
+                if !output.stderr.trim().is_empty() {
+                    eprintln!("{}", output.stderr.trim_end().yellow());
+                }
+                println!("{} {} {}", "✓".green(), t(MsgKey::OperationDone), format!("({} ms)", output.duration_ms).dimmed());
+            }
+            // --as-json 时只输出脚本结果本身, 便于直接交给 jq 等工具
+            (_, Some(json)) => print_serialized(json, format)?,
+            (_, None) => print_serialized(&PsResult { script: name, output: &output }, format)?,
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,364 @@
+//! 命令输出格式
+//!
+//! 全局 `--output` (或子命令的 `--format`) 选择结果的输出格式。各命令把最终结果包装成
+//! 实现 [`Render`] 的结构后统一输出: 表格给人看, json / yaml 给脚本用。
+//! 机器可读格式下进度信息写到标准错误, 标准输出只包含结果; 退出码与格式无关。
+//!
+//! 会删除或修改平台资源的命令统一经过 [`DestructiveGuard`]: 先列出解析出的目标,
+//! 再要求输入确认, `--yes` 跳过确认, `--dry-run` 只列出目标。
+
+use std::fmt;
+use std::io::BufRead;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// 输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+}
+
+impl OutputFormat {
+    /// 是否为人类可读的表格输出
+    pub fn is_table(self) -> bool {
+        self == Self::Table
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "table" => Ok(Self::Table),
+            "json" => Ok(Self::Json),
+            "yaml" | "yml" => Ok(Self::Yaml),
+            _ => Err(format!("不支持的输出格式: {} (可选: table, json, yaml)", s)),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Table => write!(f, "table"),
+            Self::Json => write!(f, "json"),
+            Self::Yaml => write!(f, "yaml"),
+        }
+    }
+}
+
+static OUTPUT: OnceLock<Option<OutputFormat>> = OnceLock::new();
+
+/// 设置全局 `--output` (启动时调用一次, 之后的调用被忽略)
+pub fn set_output(format: Option<OutputFormat>) {
+    let _ = OUTPUT.set(format);
+}
+
+/// 命令的输出格式: 指定了全局 `--output` 时以其为准, 否则使用子命令的 `--format`
+pub fn output_format(local: Option<&str>) -> Result<OutputFormat> {
+    resolve(OUTPUT.get().copied().flatten(), local)
+}
+
+fn resolve(global: Option<OutputFormat>, local: Option<&str>) -> Result<OutputFormat> {
+    match (global, local) {
+        (Some(format), _) => Ok(format),
+        (None, Some(local)) => local.parse().map_err(anyhow::Error::msg),
+        (None, None) => Ok(OutputFormat::Table),
+    }
+}
+
+/// 可按输出格式渲染的命令结果
+pub trait Render: Serialize {
+    /// 人类可读的表格 (可以带颜色)
+    fn to_table(&self) -> String;
+
+    fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    fn render(&self, format: OutputFormat) -> Result<String> {
+        match format {
+            OutputFormat::Table => Ok(self.to_table()),
+            OutputFormat::Json => self.to_json(),
+            OutputFormat::Yaml => self.to_yaml(),
+        }
+    }
+}
+
+/// 按格式输出命令结果
+pub fn print_rendered<R: Render + ?Sized>(value: &R, format: OutputFormat) -> Result<()> {
+    let output = value.render(format)?;
+    println!("{}", output.trim_end());
+    Ok(())
+}
+
+/// 以机器可读格式输出任意可序列化的结果 (表格输出由命令自行处理)
+pub fn print_serialized<T: Serialize + ?Sized>(value: &T, format: OutputFormat) -> Result<()> {
+    match format {
+        OutputFormat::Yaml => print!("{}", serde_yaml::to_string(value)?),
+        _ => println!("{}", serde_json::to_string_pretty(value)?),
+    }
+    Ok(())
+}
+
+/// 输出进度与提示信息: 表格输出时写到标准输出, json / yaml 输出时写到标准错误
+macro_rules! progress {
+    ($format:expr) => {
+        if $format.is_table() {
+            println!();
+        } else {
+            eprintln!();
+        }
+    };
+    ($format:expr, $($arg:tt)*) => {
+        if $format.is_table() {
+            println!($($arg)*);
+        } else {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use progress;
+
+/// 确认提示中最多列出的目标数
+const MAX_LISTED_TARGETS: usize = 20;
+
+/// 破坏性操作已确认的凭据
+///
+/// 只能由 [`DestructiveGuard::confirm`] 创建。执行破坏性调用的函数以它为参数,
+/// 未经确认的代码路径无法调用这些函数。
+#[derive(Debug, PartialEq, Eq)]
+pub struct Confirmed(());
+
+/// 破坏性操作的确认结论
+#[derive(Debug, PartialEq, Eq)]
+pub enum Confirmation {
+    /// 继续执行
+    Proceed(Confirmed),
+    /// 演练模式, 已列出目标, 不执行
+    DryRun,
+    /// 用户取消或没有目标
+    Cancelled,
+}
+
+impl Confirmation {
+    /// 确认继续时返回凭据
+    pub fn proceed(self) -> Option<Confirmed> {
+        match self {
+            Self::Proceed(confirmed) => Some(confirmed),
+            Self::DryRun | Self::Cancelled => None,
+        }
+    }
+}
+
+/// 演练模式的机器可读输出
+#[derive(Serialize)]
+struct DryRunOutput<'a> {
+    dry_run: bool,
+    action: &'a str,
+    total: usize,
+    targets: &'a [String],
+}
+
+/// 破坏性操作的确认守卫
+///
+/// 由命令分发处按子命令声明的确认参数统一创建 (见 `VdiAction::confirm_args`)。
+/// 确认通过时得到 [`Confirmed`], 破坏性调用以它为参数, 不会在确认之前执行。
+pub struct DestructiveGuard {
+    yes: bool,
+    dry_run: bool,
+    input: Mutex<Box<dyn BufRead + Send>>,
+}
+
+impl DestructiveGuard {
+    /// 从标准输入读取确认
+    pub fn new(yes: bool, dry_run: bool) -> Self {
+        Self::with_input(yes, dry_run, std::io::BufReader::new(std::io::stdin()))
+    }
+
+    /// 从指定输入读取确认 (测试时注入)
+    pub fn with_input(yes: bool, dry_run: bool, input: impl BufRead + Send + 'static) -> Self {
+        Self {
+            yes,
+            dry_run,
+            input: Mutex::new(Box::new(input)),
+        }
+    }
+
+    /// 机器可读输出用于脚本, 不能交互确认: 须指定 `--yes` 或 `--dry-run`
+    ///
+    /// 在解析目标之前调用, 避免做完耗时的查询才报错。
+    pub fn ensure_confirmable(&self, format: OutputFormat) -> Result<()> {
+        if !format.is_table() && !self.yes && !self.dry_run {
+            anyhow::bail!("{} 输出需要同时指定 --yes 或 --dry-run", format);
+        }
+        Ok(())
+    }
+
+    /// 列出目标 (前 20 个与总数) 并确认
+    ///
+    /// 需要输入 `yes` 或目标总数才继续; `--dry-run` 时只列出目标,
+    /// 机器可读格式下把目标列表输出到标准输出。
+    pub fn confirm(&self, format: OutputFormat, action: &str, targets: &[String]) -> Result<Confirmation> {
+        self.ensure_confirmable(format)?;
+
+        progress!(format, "📋 {}: 共 {} 个目标", action, targets.len());
+        for line in target_lines(targets) {
+            progress!(format, "   {}", line);
+        }
+        if targets.is_empty() {
+            progress!(format, "ℹ 没有匹配的目标");
+            return Ok(Confirmation::Cancelled);
+        }
+
+        if self.dry_run {
+            progress!(format, "\nℹ 演练模式, 未执行任何操作");
+            if !format.is_table() {
+                let output = DryRunOutput { dry_run: true, action, total: targets.len(), targets };
+                print_serialized(&output, format)?;
+            }
+            return Ok(Confirmation::DryRun);
+        }
+        if self.yes {
+            return Ok(Confirmation::Proceed(Confirmed(())));
+        }
+
+        println!("\n⚠ 即将对以上 {} 个目标执行{}, 输入 yes 或目标数量 {} 继续:", targets.len(), action, targets.len());
+        let mut answer = String::new();
+        self.input
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .read_line(&mut answer)?;
+
+        if is_confirmed(&answer, targets.len()) {
+            Ok(Confirmation::Proceed(Confirmed(())))
+        } else {
+            println!("\nℹ 已取消");
+            Ok(Confirmation::Cancelled)
+        }
+    }
+}
+
+/// 目标列表的显示行: 最多 [`MAX_LISTED_TARGETS`] 个, 其余折叠为一行
+fn target_lines(targets: &[String]) -> Vec<String> {
+    let mut lines: Vec<String> = targets
+        .iter()
+        .take(MAX_LISTED_TARGETS)
+        .map(|target| format!("- {}", target))
+        .collect();
+    if targets.len() > MAX_LISTED_TARGETS {
+        lines.push(format!("... 另有 {} 个 (共 {} 个)", targets.len() - MAX_LISTED_TARGETS, targets.len()));
+    }
+    lines
+}
+
+/// 输入 `yes` (不区分大小写) 或准确的目标数量视为确认, 单独的 `y` 不算
+fn is_confirmed(answer: &str, count: usize) -> bool {
+    let answer = answer.trim();
+    answer.eq_ignore_ascii_case("yes") || answer == count.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Sample {
+        name: String,
+        count: u32,
+    }
+
+    impl Render for Sample {
+        fn to_table(&self) -> String {
+            format!("{:<10} {}", self.name, self.count)
+        }
+    }
+
+    #[test]
+    fn test_resolve_output_format() {
+        assert_eq!(resolve(None, None).unwrap(), OutputFormat::Table);
+        assert_eq!(resolve(None, Some("JSON")).unwrap(), OutputFormat::Json);
+        assert_eq!(resolve(Some(OutputFormat::Yaml), Some("json")).unwrap(), OutputFormat::Yaml);
+        assert_eq!(resolve(Some(OutputFormat::Table), Some("xml")).unwrap(), OutputFormat::Table);
+
+        let err = resolve(None, Some("xml")).unwrap_err().to_string();
+        assert!(err.contains("table, json, yaml"), "{}", err);
+        assert_eq!("yml".parse::<OutputFormat>().unwrap().to_string(), "yaml");
+    }
+
+    #[test]
+    fn test_render() {
+        let sample = Sample { name: "vm-1".to_string(), count: 2 };
+
+        assert_eq!(sample.render(OutputFormat::Table).unwrap(), "vm-1       2");
+        let json: serde_json::Value = serde_json::from_str(&sample.render(OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(json, serde_json::json!({"name": "vm-1", "count": 2}));
+        assert_eq!(sample.render(OutputFormat::Yaml).unwrap(), "name: vm-1\ncount: 2\n");
+    }
+
+    fn targets(count: usize) -> Vec<String> {
+        (1..=count).map(|i| format!("vm-{:02}", i)).collect()
+    }
+
+    fn guard(yes: bool, dry_run: bool, input: &str) -> DestructiveGuard {
+        DestructiveGuard::with_input(yes, dry_run, std::io::Cursor::new(input.to_string()))
+    }
+
+    #[test]
+    fn test_target_lines_truncated() {
+        assert_eq!(target_lines(&targets(2)), vec!["- vm-01", "- vm-02"]);
+
+        let lines = target_lines(&targets(25));
+        assert_eq!(lines.len(), MAX_LISTED_TARGETS + 1);
+        assert_eq!(lines[19], "- vm-20");
+        assert_eq!(lines[20], "... 另有 5 个 (共 25 个)");
+    }
+
+    #[test]
+    fn test_guard_typed_confirmation() {
+        let targets = targets(3);
+        let format = OutputFormat::Table;
+
+        for (input, expected) in [
+            ("yes\n", Confirmation::Proceed(Confirmed(()))),
+            ("YES\n", Confirmation::Proceed(Confirmed(()))),
+            ("3\n", Confirmation::Proceed(Confirmed(()))),
+            ("y\n", Confirmation::Cancelled),
+            ("2\n", Confirmation::Cancelled),
+            ("", Confirmation::Cancelled),
+        ] {
+            let guard = guard(false, false, input);
+            assert_eq!(guard.confirm(format, "关机", &targets).unwrap(), expected, "{:?}", input);
+        }
+
+        // --yes 不读取输入, --dry-run 优先于 --yes
+        assert!(guard(true, false, "").confirm(format, "关机", &targets).unwrap().proceed().is_some());
+        assert_eq!(guard(true, true, "yes\n").confirm(format, "关机", &targets).unwrap(), Confirmation::DryRun);
+        assert!(Confirmation::DryRun.proceed().is_none());
+
+        // 没有目标时不提示
+        assert_eq!(guard(false, false, "yes\n").confirm(format, "关机", &[]).unwrap(), Confirmation::Cancelled);
+    }
+
+    #[test]
+    fn test_guard_machine_readable_requires_yes() {
+        let targets = targets(1);
+
+        let err = guard(false, false, "yes\n").confirm(OutputFormat::Json, "删除", &targets).unwrap_err();
+        assert!(err.to_string().contains("--yes"), "{}", err);
+        assert!(guard(false, true, "").ensure_confirmable(OutputFormat::Json).is_ok());
+        assert!(guard(true, false, "").confirm(OutputFormat::Yaml, "删除", &targets).unwrap().proceed().is_some());
+    }
+}
@@ -1,7 +1,10 @@
-use anyhow::Result;
-use atp_storage::BackupManager;
+use anyhow::{Context, Result};
+use atp_storage::{BackupInfo, BackupManager, SchemaMigration, StorageManager, LATEST_SCHEMA_VERSION};
+use serde::Serialize;
 use std::path::PathBuf;
 
+use crate::commands::common::{output_format, print_rendered, progress, Render};
+
 pub async fn handle(action: crate::DbAction) -> Result<()> {
     match action {
         crate::DbAction::Backup {
@@ -28,14 +31,207 @@ pub async fn handle(action: crate::DbAction) -> Result<()> {
             db_path,
             backup_dir,
         } => cleanup_backups(keep, &db_path, backup_dir).await,
+
+        crate::DbAction::Migrate { db_path } => migrate_database(&db_path).await,
+
+        crate::DbAction::Status { db_path } => show_status(&db_path).await,
+    }
+}
+
+/// 备份文件
+#[derive(Debug, Serialize)]
+struct BackupEntry {
+    path: String,
+    size_bytes: u64,
+    /// 人类可读的大小
+    size: String,
+    modified: String,
+}
+
+impl From<&BackupInfo> for BackupEntry {
+    fn from(backup: &BackupInfo) -> Self {
+        Self {
+            path: backup.path.display().to_string(),
+            size_bytes: backup.size,
+            size: backup.size_human_readable(),
+            modified: backup.modified.format("%Y-%m-%d %H:%M:%S").to_string(),
+        }
+    }
+}
+
+/// 新建的备份 (`atp db backup`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct BackupCreated {
+    backup: BackupEntry,
+}
+
+impl Render for BackupCreated {
+    fn to_table(&self) -> String {
+        [
+            format!("✅ 数据库已成功备份到: {}", self.backup.path),
+            format!("   大小: {}", self.backup.size),
+            format!("   时间: {}", self.backup.modified),
+        ]
+        .join("\n")
     }
 }
 
+/// 恢复结果 (`atp db restore`)
+#[derive(Debug, Serialize)]
+struct RestoreResult {
+    backup_path: String,
+    db_path: String,
+    safety_backup: bool,
+}
+
+impl Render for RestoreResult {
+    fn to_table(&self) -> String {
+        format!("✅ 数据库已从备份恢复: {}", self.backup_path)
+    }
+}
+
+/// 备份列表 (`atp db list`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct BackupList {
+    backups: Vec<BackupEntry>,
+}
+
+impl Render for BackupList {
+    fn to_table(&self) -> String {
+        if self.backups.is_empty() {
+            return "没有找到备份文件".to_string();
+        }
+
+        let mut lines = vec![
+            "📦 数据库备份列表:".to_string(),
+            String::new(),
+            format!("{:<50} {:<12} {:<20}", "文件路径", "大小", "备份时间"),
+            "-".repeat(85),
+        ];
+
+        for backup in &self.backups {
+            lines.push(format!("{:<50} {:<12} {:<20}", backup.path, backup.size, backup.modified));
+        }
+
+        let total_size: u64 = self.backups.iter().map(|b| b.size_bytes).sum();
+        lines.push(String::new());
+        lines.push(format!("总计: {} 个备份", self.backups.len()));
+        lines.push(format!("总大小: {:.2} MB", total_size as f64 / (1024.0 * 1024.0)));
+        lines.join("\n")
+    }
+}
+
+/// 删除的备份 (`atp db delete`)
+#[derive(Debug, Serialize)]
+struct DeletedBackup {
+    path: String,
+}
+
+impl Render for DeletedBackup {
+    fn to_table(&self) -> String {
+        "✅ 备份已删除".to_string()
+    }
+}
+
+/// 清理结果 (`atp db cleanup`)
+#[derive(Debug, Serialize)]
+struct CleanupResult {
+    keep: usize,
+    deleted: usize,
+    remaining: usize,
+}
+
+impl Render for CleanupResult {
+    fn to_table(&self) -> String {
+        if self.deleted > 0 {
+            format!("✅ 已删除 {} 个旧备份\n   剩余 {} 个备份", self.deleted, self.remaining)
+        } else {
+            "✅ 无需清理,备份数量未超过保留数量".to_string()
+        }
+    }
+}
+
+/// 迁移结果 (`atp db migrate`)
+#[derive(Debug, Serialize)]
+struct MigrateResult {
+    from_version: i64,
+    to_version: i64,
+    /// 本次执行的迁移 (`版本_名称`)
+    applied: Vec<String>,
+}
+
+impl Render for MigrateResult {
+    fn to_table(&self) -> String {
+        if self.applied.is_empty() {
+            return format!("✅ 数据库已是最新版本 ({})", LATEST_SCHEMA_VERSION);
+        }
+
+        let mut lines: Vec<String> = self.applied.iter().map(|migration| format!("   已执行: {}", migration)).collect();
+        lines.push(format!("✅ 已执行 {} 个迁移, 当前版本: {}", self.applied.len(), self.to_version));
+        lines.join("\n")
+    }
+}
+
+/// 数据库状态 (`atp db status`)
+#[derive(Debug, Serialize)]
+struct DbStatus {
+    db_path: String,
+    exists: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema_version: Option<i64>,
+    supported_version: i64,
+    /// 待执行的迁移 (`版本_名称`)
+    pending_migrations: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    integrity_ok: Option<bool>,
+    integrity_errors: Vec<String>,
+    foreign_key_violations: Vec<String>,
+}
+
+impl Render for DbStatus {
+    fn to_table(&self) -> String {
+        let Some(version) = self.schema_version else {
+            return format!("❌ 数据库不存在: {}", self.db_path);
+        };
+
+        let mut lines = vec![
+            format!("📊 数据库: {}", self.db_path),
+            format!("   schema 版本: {}", version),
+            format!("   程序支持版本: {}", self.supported_version),
+        ];
+
+        if version > self.supported_version {
+            lines.push("   ⚠️  数据库版本高于程序支持的版本, 请升级 atp".to_string());
+        } else if self.pending_migrations.is_empty() {
+            lines.push("   待执行迁移: 无".to_string());
+        } else {
+            lines.push(format!("   待执行迁移: {} 个 (运行 atp db migrate)", self.pending_migrations.len()));
+            lines.extend(self.pending_migrations.iter().map(|migration| format!("     - {}", migration)));
+        }
+
+        if self.integrity_ok == Some(true) {
+            lines.push("   完整性检查: ✅ 通过".to_string());
+        } else {
+            lines.push("   完整性检查: ❌ 发现问题".to_string());
+            lines.extend(self.integrity_errors.iter().map(|error| format!("     - {}", error)));
+            lines.extend(self.foreign_key_violations.iter().map(|violation| format!("     - 外键: {}", violation)));
+        }
+        lines.join("\n")
+    }
+}
+
+fn migration_name(migration: &SchemaMigration) -> String {
+    format!("{:03}_{}", migration.version, migration.name)
+}
+
 async fn backup_database(
     name: Option<&str>,
     db_path: &str,
     backup_dir: Option<String>,
 ) -> Result<()> {
+    let format = output_format(None)?;
     let expanded_db_path = shellexpand::tilde(db_path);
     let backup_dir = backup_dir.map(|p| {
         let expanded = shellexpand::tilde(&p);
@@ -44,19 +240,17 @@ async fn backup_database(
 
     let manager = BackupManager::new(expanded_db_path.as_ref(), backup_dir)?;
 
-    println!("🔄 正在备份数据库...");
+    progress!(format, "🔄 正在备份数据库...");
     let backup_path = manager.backup(name)?;
 
-    println!("✅ 数据库已成功备份到: {}", backup_path.display());
-
     // 显示备份信息
     let backups = manager.list_backups()?;
-    if let Some(backup) = backups.iter().find(|b| b.path == backup_path) {
-        println!("   大小: {}", backup.size_human_readable());
-        println!("   时间: {}", backup.modified.format("%Y-%m-%d %H:%M:%S"));
-    }
+    let backup = backups
+        .iter()
+        .find(|b| b.path == backup_path)
+        .with_context(|| format!("备份目录中找不到新建的备份: {}", backup_path.display()))?;
 
-    Ok(())
+    print_rendered(&BackupCreated { backup: backup.into() }, format)
 }
 
 async fn restore_database(
@@ -64,25 +258,30 @@ async fn restore_database(
     db_path: &str,
     safety_backup: bool,
 ) -> Result<()> {
+    let format = output_format(None)?;
     let expanded_db_path = shellexpand::tilde(db_path);
     let expanded_backup_path = shellexpand::tilde(backup_path);
 
     let manager = BackupManager::new(expanded_db_path.as_ref(), None)?;
 
-    println!("⚠️  警告: 此操作将覆盖当前数据库!");
+    progress!(format, "⚠️  警告: 此操作将覆盖当前数据库!");
 
     if safety_backup {
-        println!("🔄 正在创建安全备份...");
+        progress!(format, "🔄 正在创建安全备份...");
     }
 
     manager.restore(expanded_backup_path.as_ref(), safety_backup)?;
 
-    println!("✅ 数据库已从备份恢复: {}", backup_path);
-
-    Ok(())
+    let result = RestoreResult {
+        backup_path: backup_path.to_string(),
+        db_path: expanded_db_path.to_string(),
+        safety_backup,
+    };
+    print_rendered(&result, format)
 }
 
 async fn list_backups(db_path: &str, backup_dir: Option<String>) -> Result<()> {
+    let format = output_format(None)?;
     let expanded_db_path = shellexpand::tilde(db_path);
     let backup_dir = backup_dir.map(|p| {
         let expanded = shellexpand::tilde(&p);
@@ -92,40 +291,14 @@ async fn list_backups(db_path: &str, backup_dir: Option<String>) -> Result<()> {
     let manager = BackupManager::new(expanded_db_path.as_ref(), backup_dir)?;
     let backups = manager.list_backups()?;
 
-    if backups.is_empty() {
-        println!("没有找到备份文件");
-        return Ok(());
-    }
-
-    println!("📦 数据库备份列表:");
-    println!();
-    println!(
-        "{:<50} {:<12} {:<20}",
-        "文件路径", "大小", "备份时间"
-    );
-    println!("{}", "-".repeat(85));
-
-    for backup in &backups {
-        println!(
-            "{:<50} {:<12} {:<20}",
-            backup.path.display(),
-            backup.size_human_readable(),
-            backup.modified.format("%Y-%m-%d %H:%M:%S")
-        );
-    }
-
-    println!();
-    println!("总计: {} 个备份", backups.len());
-
-    // 显示总大小
-    let total_size: u64 = backups.iter().map(|b| b.size).sum();
-    let total_size_mb = total_size as f64 / (1024.0 * 1024.0);
-    println!("总大小: {:.2} MB", total_size_mb);
-
-    Ok(())
+    let list = BackupList {
+        backups: backups.iter().map(BackupEntry::from).collect(),
+    };
+    print_rendered(&list, format)
 }
 
 async fn delete_backup(backup_path: &str) -> Result<()> {
+    let format = output_format(None)?;
     let expanded_path = shellexpand::tilde(backup_path);
     let path = PathBuf::from(expanded_path.as_ref());
 
@@ -138,15 +311,14 @@ async fn delete_backup(backup_path: &str) -> Result<()> {
 
     let manager = BackupManager::new(db_path, None)?;
 
-    println!("🔄 正在删除备份: {}", path.display());
+    progress!(format, "🔄 正在删除备份: {}", path.display());
     manager.delete_backup(&path)?;
 
-    println!("✅ 备份已删除");
-
-    Ok(())
+    print_rendered(&DeletedBackup { path: path.display().to_string() }, format)
 }
 
 async fn cleanup_backups(keep: usize, db_path: &str, backup_dir: Option<String>) -> Result<()> {
+    let format = output_format(None)?;
     let expanded_db_path = shellexpand::tilde(db_path);
     let backup_dir = backup_dir.map(|p| {
         let expanded = shellexpand::tilde(&p);
@@ -156,22 +328,68 @@ async fn cleanup_backups(keep: usize, db_path: &str, backup_dir: Option<String>)
     let manager = BackupManager::new(expanded_db_path.as_ref(), backup_dir)?;
 
     let backups_before = manager.list_backups()?;
-    println!(
+    progress!(
+        format,
         "🔄 正在清理旧备份... (当前: {}, 保留: {})",
         backups_before.len(),
         keep
     );
 
     let deleted = manager.cleanup_old_backups(keep)?;
+    let remaining = manager.list_backups()?.len();
+
+    print_rendered(&CleanupResult { keep, deleted, remaining }, format)
+}
+
+async fn migrate_database(db_path: &str) -> Result<()> {
+    let format = output_format(None)?;
+    let manager = StorageManager::open(db_path).await?;
 
-    if deleted > 0 {
-        println!("✅ 已删除 {} 个旧备份", deleted);
+    let before = manager.schema_version().await?;
+    progress!(format, "🔄 正在迁移数据库... (当前版本: {})", before);
 
-        let backups_after = manager.list_backups()?;
-        println!("   剩余 {} 个备份", backups_after.len());
-    } else {
-        println!("✅ 无需清理,备份数量未超过保留数量");
+    let applied = manager.migrate().await?;
+    let result = MigrateResult {
+        from_version: before,
+        to_version: manager.schema_version().await?,
+        applied: applied.iter().map(migration_name).collect(),
+    };
+
+    manager.close().await;
+    print_rendered(&result, format)
+}
+
+async fn show_status(db_path: &str) -> Result<()> {
+    let format = output_format(None)?;
+    let expanded_db_path = shellexpand::tilde(db_path);
+    let mut status = DbStatus {
+        db_path: expanded_db_path.to_string(),
+        exists: std::path::Path::new(expanded_db_path.as_ref()).exists(),
+        schema_version: None,
+        supported_version: LATEST_SCHEMA_VERSION,
+        pending_migrations: Vec::new(),
+        integrity_ok: None,
+        integrity_errors: Vec::new(),
+        foreign_key_violations: Vec::new(),
+    };
+    if !status.exists {
+        return print_rendered(&status, format);
     }
 
-    Ok(())
+    let manager = StorageManager::open(db_path).await?;
+
+    let version = manager.schema_version().await?;
+    status.schema_version = Some(version);
+    if version <= LATEST_SCHEMA_VERSION {
+        let pending = manager.pending_migrations().await?;
+        status.pending_migrations = pending.iter().map(migration_name).collect();
+    }
+
+    let integrity = manager.verify_integrity().await?;
+    status.integrity_ok = Some(integrity.is_ok());
+    status.integrity_errors = integrity.integrity_errors;
+    status.foreign_key_violations = integrity.foreign_key_violations;
+
+    manager.close().await;
+    print_rendered(&status, format)
 }
@@ -1,91 +1,558 @@
 //! 主机管理命令
+//!
+//! 主机保存在数据库的主机仓储中 (地址、URI、标签与最近一次连接探测的结果),
+//! SSH 与默认主机等本地设置仍保存在配置文件中。添加主机时立即尝试连接 libvirt 并记录主机能力。
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use atp_storage::{HostProbeRecord, HostRecord, Storage, StorageError, StorageManager};
+use atp_transport::{ConnectionState, HostCapabilities, HostInfo, SshConfig, SshJump, TransportConfig, TransportManager};
+use chrono::{DateTime, Local, Utc};
 use colored::Colorize;
+use serde::Serialize;
+use crate::commands::common::{output_format, print_rendered, Render};
 use crate::config::CliConfig;
+use crate::i18n::{t, tr, MsgKey};
+
+const DB_PATH: &str = "~/.config/atp/data.db";
 
 pub async fn handle(action: crate::HostAction) -> Result<()> {
+    let mut hosts = HostCommands::open().await?;
+
     match action {
-        crate::HostAction::Add { id, host, uri } => add_host(&id, &host, uri).await,
-        crate::HostAction::List => list_hosts().await,
-        crate::HostAction::Remove { id } => remove_host(&id).await,
+        crate::HostAction::Add {
+            id,
+            host,
+            uri,
+            tags,
+            ssh_user,
+            ssh_port,
+            ssh_key,
+            ssh_jump,
+            ssh_jump_key,
+            ssh_sudo,
+            ssh_sudo_password,
+        } => {
+            let mut ssh = ssh_config(ssh_user, ssh_port, ssh_key, ssh_jump, ssh_jump_key);
+            if ssh_sudo || ssh_sudo_password.is_some() {
+                ssh = Some(ssh.unwrap_or_default().with_sudo(ssh_sudo_password));
+            }
+            hosts.add(&id, &host, uri, tags, ssh).await
+        }
+        crate::HostAction::List => hosts.list().await,
+        crate::HostAction::Remove { id } => hosts.remove(&id).await,
+        crate::HostAction::Probe { id } => hosts.probe_command(&id).await,
+        crate::HostAction::Tag { id, tags, remove } => hosts.tag(&id, &tags, remove).await,
+    }
+}
+
+/// 由 --ssh-* 参数构造 SSH 配置, 均未设置时返回 None
+fn ssh_config(
+    user: Option<String>,
+    port: Option<u16>,
+    key: Option<String>,
+    jump: Option<Box<SshJump>>,
+    jump_key: Option<String>,
+) -> Option<SshConfig> {
+    if user.is_none() && port.is_none() && key.is_none() && jump.is_none() {
+        return None;
+    }
+
+    let mut ssh = user.as_deref().map_or_else(SshConfig::default, SshConfig::new);
+    if let Some(port) = port {
+        ssh = ssh.with_port(port);
+    }
+    if let Some(key) = &key {
+        ssh = ssh.with_identity_file(key);
+    }
+    if let Some(mut jump) = jump {
+        if let Some(jump_key) = &jump_key {
+            jump.ssh = jump.ssh.with_identity_file(jump_key);
+        }
+        ssh = ssh.with_jump(*jump);
+    }
+    Some(ssh)
+}
+
+/// 跳板机链路, 按连接顺序排列 (最外层的跳板机在前)
+fn jump_chain(ssh: &SshConfig) -> Vec<String> {
+    let mut jumps = Vec::new();
+    let mut current = ssh;
+    while let Some(jump) = &current.jump {
+        jumps.push(format!("{}@{}:{}", jump.ssh.user, jump.host, jump.ssh.port));
+        current = &jump.ssh;
     }
+    jumps.reverse();
+    jumps
+}
+
+/// 主机命令的上下文: 本地配置、主机仓储, 以及本次命令建立的 libvirt 连接
+struct HostCommands {
+    config: CliConfig,
+    storage: Storage,
+    transport: TransportManager,
 }
 
-async fn add_host(id: &str, host: &str, uri: Option<String>) -> Result<()> {
-    let mut config = CliConfig::load()?;
+impl HostCommands {
+    async fn open() -> Result<Self> {
+        let storage_manager = StorageManager::new(DB_PATH)
+            .await
+            .context("打开数据库失败, 主机信息保存在数据库中")?;
+
+        Ok(Self {
+            config: CliConfig::load()?,
+            storage: Storage::from_manager(&storage_manager),
+            transport: TransportManager::new(TransportConfig::default()),
+        })
+    }
+
+    async fn add(&mut self, id: &str, host: &str, uri: Option<String>, tags: Vec<String>, ssh: Option<SshConfig>) -> Result<()> {
+        if self.storage.hosts().get_by_id(id).await?.is_some() {
+            anyhow::bail!(tr(MsgKey::HostExists, &[id]));
+        }
+
+        self.config.add_host(id, host, uri.clone())?;
+        self.config.set_host_ssh(id, ssh.clone())?;
+        let tags = normalize_tags(tags);
+        if let Some(host_config) = self.config.hosts.get_mut(id) {
+            host_config.tags = tags.clone();
+        }
+
+        let now = Utc::now();
+        let record = HostRecord {
+            id: id.to_string(),
+            host: host.to_string(),
+            uri: uri.unwrap_or_else(|| default_uri(host)),
+            tags: tags_json(&tags)?,
+            metadata: None,
+            created_at: now,
+            updated_at: now,
+        };
+        self.storage.hosts().upsert_by_host_id(&record).await?;
+        self.config.save()?;
+
+        println!("{} {}", "✓".green().bold(), tr(MsgKey::HostAdded, &[&id.cyan().bold().to_string()]));
+        println!("  {}: {}", t(MsgKey::LabelAddress), host.yellow());
+        println!("  URI:  {}", record.uri.yellow());
+
+        if let Some(ssh) = &ssh {
+            for line in HostSsh::from_config(ssh).lines("  ", host) {
+                println!("{}", line);
+            }
+        }
+
+        if !tags.is_empty() {
+            println!("  {}: {}", t(MsgKey::LabelTags), tags.join(", ").bright_black());
+        }
+
+        if self.config.default_host.as_deref() == Some(id) {
+            println!("  {}", t(MsgKey::HostSetDefault).green());
+        }
+
+        // 连接失败不影响添加, 只记录状态
+        println!("\n{}", tr(MsgKey::HostProbing, &[id]));
+        let status = HostStatus::from_probe(Some(&self.probe(&record).await?));
+        for line in status.lines("  ") {
+            println!("{}", line);
+        }
+
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<()> {
+        // 只在配置文件中的主机 (早期版本添加) 导入主机仓储
+        let hosts = self.storage.hosts();
+        for (id, host_config) in self.config.list_hosts() {
+            if hosts.get_by_id(id).await?.is_none() {
+                hosts.upsert_by_host_id(&config_record(id, host_config)?).await?;
+            }
+        }
+
+        let list = HostList::new(&self.config, hosts.list_all().await?, hosts.list_probes().await?);
+        print_rendered(&list, output_format(None)?)
+    }
+
+    async fn remove(&mut self, id: &str) -> Result<()> {
+        let in_config = self.config.hosts.contains_key(id);
+        let in_storage = match self.storage.hosts().delete(id).await {
+            Ok(()) => true,
+            Err(StorageError::NotFound(_)) => false,
+            Err(e) => return Err(e.into()),
+        };
+        if !in_config && !in_storage {
+            anyhow::bail!(tr(MsgKey::HostNotFound, &[id]));
+        }
+
+        if in_config {
+            self.config.remove_host(id)?;
+            self.config.save()?;
+        }
+
+        println!("{} {}", "✓".green().bold(), tr(MsgKey::HostRemoved, &[&id.cyan().bold().to_string()]));
+
+        Ok(())
+    }
+
+    /// `atp host probe`: 重新连接主机并刷新能力
+    async fn probe_command(&self, id: &str) -> Result<()> {
+        let record = self.record(id).await?;
+        let format = output_format(None)?;
+        if format.is_table() {
+            println!("{}", tr(MsgKey::HostProbing, &[id]));
+        }
+
+        let probe = self.probe(&record).await?;
+        let result = ProbeResult {
+            id: record.id.clone(),
+            host: record.host.clone(),
+            uri: record.uri.clone(),
+            status: HostStatus::from_probe(Some(&probe)),
+        };
+        print_rendered(&result, format)?;
+
+        if !probe.reachable {
+            std::process::exit(1);
+        }
+        Ok(())
+    }
 
-    config.add_host(id, host, uri)?;
-    config.save()?;
+    async fn tag(&mut self, id: &str, tags: &[String], remove: bool) -> Result<()> {
+        let record = self.record(id).await?;
 
-    println!("{} 主机 {} 添加成功", "✓".green().bold(), id.cyan().bold());
-    println!("  地址: {}", host.yellow());
+        let mut current = record_tags(&record);
+        for tag in normalize_tags(tags.to_vec()) {
+            if remove {
+                current.retain(|t| *t != tag);
+            } else if !current.contains(&tag) {
+                current.push(tag);
+            }
+        }
 
-    if let Ok(host_config) = config.get_host(id) {
-        if let Some(uri) = &host_config.uri {
-            println!("  URI:  {}", uri.yellow());
+        self.storage.hosts().set_tags(id, &current).await?;
+        if let Some(host_config) = self.config.hosts.get_mut(id) {
+            host_config.tags = current.clone();
+            self.config.save()?;
         }
+
+        let tags = if current.is_empty() { "-".to_string() } else { current.join(", ") };
+        println!("{} {}", "✓".green().bold(), tr(MsgKey::HostTagged, &[&id.cyan().bold().to_string(), &tags]));
+        Ok(())
     }
 
-    if config.default_host.as_deref() == Some(id) {
-        println!("  {}", "已设置为默认主机".green());
+    async fn record(&self, id: &str) -> Result<HostRecord> {
+        if let Some(record) = self.storage.hosts().get_by_id(id).await? {
+            return Ok(record);
+        }
+
+        // 只在配置文件中的主机
+        let host_config = self
+            .config
+            .hosts
+            .get(id)
+            .ok_or_else(|| anyhow::anyhow!(tr(MsgKey::HostNotFound, &[id])))?;
+        let record = config_record(id, host_config)?;
+        self.storage.hosts().upsert_by_host_id(&record).await?;
+        Ok(record)
+    }
+
+    /// 连接 libvirt 并读取主机能力, 结果写入主机仓储
+    ///
+    /// 返回仓储中的探测记录: 连接失败时保留上一次成功探测到的能力与在线时间。
+    async fn probe(&self, record: &HostRecord) -> Result<HostProbeRecord> {
+        if !self.transport.list_hosts().await.contains(&record.id) {
+            self.transport.add_host(self.host_info(record)).await?;
+        }
+
+        let result = self
+            .transport
+            .execute_on_host(&record.id, |conn| async move {
+                // 连接池在后台建立连接, 尚未连上时在这里直接连接
+                if conn.state().await != ConnectionState::Connected {
+                    conn.connect().await?;
+                }
+                conn.capabilities().await
+            })
+            .await;
+
+        let now = Utc::now();
+        let probe = match result {
+            Ok(capabilities) => HostProbeRecord {
+                host_id: record.id.clone(),
+                probed_at: now,
+                reachable: true,
+                last_seen_at: Some(now),
+                error: None,
+                libvirt_version: Some(capabilities.libvirt_version.clone()),
+                hypervisor: Some(capabilities.hypervisor.clone()),
+                hypervisor_version: capabilities.hypervisor_version.clone(),
+                cpu_model: capabilities.cpu_model.clone(),
+                max_vcpus: capabilities.max_vcpus.map(i64::from),
+                capabilities: Some(serde_json::to_string(&capabilities)?),
+            },
+            Err(e) => HostProbeRecord {
+                host_id: record.id.clone(),
+                probed_at: now,
+                reachable: false,
+                last_seen_at: None,
+                error: Some(e.to_string()),
+                libvirt_version: None,
+                hypervisor: None,
+                hypervisor_version: None,
+                cpu_model: None,
+                max_vcpus: None,
+                capabilities: None,
+            },
+        };
+
+        let hosts = self.storage.hosts();
+        hosts.record_probe(&probe).await?;
+        Ok(hosts.get_probe(&record.id).await?.unwrap_or(probe))
     }
 
-    Ok(())
+    /// 主机仓储中的主机转换为连接信息 (SSH 配置取自配置文件)
+    fn host_info(&self, record: &HostRecord) -> HostInfo {
+        let mut host_info = HostInfo::new(&record.id, &record.host)
+            .with_uri(&record.uri)
+            .with_tags(record_tags(record));
+        if let Some(ssh) = self.config.hosts.get(&record.id).and_then(|host| host.ssh.clone()) {
+            host_info = host_info.with_ssh(ssh);
+        }
+        host_info
+    }
 }
 
-async fn list_hosts() -> Result<()> {
-    let config = CliConfig::load()?;
+/// 未指定 URI 时默认通过 SSH 连接
+fn default_uri(host: &str) -> String {
+    format!("qemu+ssh://{}:22/system", host)
+}
 
-    if config.hosts.is_empty() {
-        println!("{}", "没有配置任何主机".yellow());
-        println!("\n使用以下命令添加主机:");
-        println!("  {} atp host add <ID> <HOST> [--uri <URI>]", "$".bright_black());
-        return Ok(());
+/// 去掉空白与重复的标签, 保持原有顺序
+fn normalize_tags(tags: Vec<String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_string();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
     }
+    normalized
+}
 
-    println!("{}\n", "配置的主机列表:".bold());
+fn tags_json(tags: &[String]) -> Result<Option<String>> {
+    Ok(if tags.is_empty() { None } else { Some(serde_json::to_string(tags)?) })
+}
 
-    let mut hosts: Vec<_> = config.list_hosts();
-    hosts.sort_by_key(|(id, _)| *id);
+/// 主机仓储中的标签 (JSON 数组)
+fn record_tags(record: &HostRecord) -> Vec<String> {
+    record
+        .tags
+        .as_deref()
+        .and_then(|tags| serde_json::from_str(tags).ok())
+        .unwrap_or_default()
+}
+
+/// 配置文件中的主机转换为主机仓储记录
+fn config_record(id: &str, host_config: &crate::config::HostConfig) -> Result<HostRecord> {
+    let now = Utc::now();
+    Ok(HostRecord {
+        id: id.to_string(),
+        host: host_config.host.clone(),
+        uri: host_config.uri.clone().unwrap_or_else(|| default_uri(&host_config.host)),
+        tags: tags_json(&host_config.tags)?,
+        metadata: None,
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+/// 主机最近一次连接探测的状态
+#[derive(Debug, Serialize)]
+struct HostStatus {
+    /// online / unreachable / unknown (从未探测)
+    state: &'static str,
+    probed_at: Option<DateTime<Utc>>,
+    last_seen_at: Option<DateTime<Utc>>,
+    error: Option<String>,
+    capabilities: Option<HostCapabilities>,
+}
+
+impl HostStatus {
+    fn from_probe(probe: Option<&HostProbeRecord>) -> Self {
+        match probe {
+            Some(probe) => Self {
+                state: if probe.reachable { "online" } else { "unreachable" },
+                probed_at: Some(probe.probed_at),
+                last_seen_at: probe.last_seen_at,
+                error: probe.error.clone(),
+                capabilities: probe.capabilities.as_deref().and_then(|json| serde_json::from_str(json).ok()),
+            },
+            None => Self {
+                state: "unknown",
+                probed_at: None,
+                last_seen_at: None,
+                error: None,
+                capabilities: None,
+            },
+        }
+    }
 
-    for (id, host_config) in hosts {
-        let is_default = config.default_host.as_deref() == Some(id);
-        let marker = if is_default {
-            "*".green().bold()
-        } else {
-            " ".into()
+    /// 状态与主机能力的显示行
+    fn lines(&self, indent: &str) -> Vec<String> {
+        let state = match self.state {
+            "online" => t(MsgKey::HostStatusOnline).green(),
+            "unreachable" => t(MsgKey::HostStatusUnreachable).red(),
+            _ => t(MsgKey::HostStatusUnknown).bright_black(),
         };
+        let last_seen = self
+            .last_seen_at
+            .map(|time| format!(" ({}: {})", t(MsgKey::LabelLastSeen), time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")))
+            .unwrap_or_default();
+        let mut lines = vec![format!("{}{}: {}{}", indent, t(MsgKey::LabelStatus), state, last_seen)];
+
+        if let Some(error) = &self.error {
+            lines.push(format!("{}{}: {}", indent, t(MsgKey::ErrorPrefix), error.red()));
+        }
 
-        println!(
-            "{} {} {}",
-            marker,
-            id.cyan().bold(),
-            if is_default { "(默认)".green() } else { "".into() }
-        );
-        println!("    地址: {}", host_config.host.yellow());
+        if let Some(capabilities) = &self.capabilities {
+            let hypervisor = match &capabilities.hypervisor_version {
+                Some(version) => format!("{} {}", capabilities.hypervisor, version),
+                None => capabilities.hypervisor.clone(),
+            };
+            lines.push(format!("{}libvirt: {} / {}", indent, capabilities.libvirt_version.yellow(), hypervisor.yellow()));
 
-        if let Some(uri) = &host_config.uri {
-            println!("    URI:  {}", uri.yellow());
+            let cpu = match (&capabilities.cpu_model, &capabilities.arch) {
+                (Some(model), Some(arch)) => format!("{} ({})", model, arch),
+                (Some(model), None) => model.clone(),
+                (None, Some(arch)) => arch.clone(),
+                (None, None) => "-".to_string(),
+            };
+            let max_vcpus = capabilities.max_vcpus.map(|n| n.to_string()).unwrap_or_else(|| "-".to_string());
+            lines.push(format!("{}CPU:  {}, {} vCPU: {}", indent, cpu.yellow(), t(MsgKey::LabelMax), max_vcpus.yellow()));
         }
+        lines
+    }
+}
+
+/// `atp host probe` 的结果
+#[derive(Debug, Serialize)]
+struct ProbeResult {
+    id: String,
+    host: String,
+    uri: String,
+    #[serde(flatten)]
+    status: HostStatus,
+}
+
+impl Render for ProbeResult {
+    fn to_table(&self) -> String {
+        let mut lines = vec![format!("{} {} ({})", self.id.cyan().bold(), self.host.yellow(), self.uri)];
+        lines.extend(self.status.lines("  "));
+        lines.join("\n")
+    }
+}
+
+/// 主机列表 (`atp host list`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct HostList {
+    hosts: Vec<HostEntry>,
+}
+
+/// 主机列表中的一台主机 (不包含 sudo 密码等敏感信息)
+#[derive(Debug, Serialize)]
+struct HostEntry {
+    id: String,
+    host: String,
+    uri: String,
+    default: bool,
+    tags: Vec<String>,
+    ssh: Option<HostSsh>,
+    status: HostStatus,
+}
+
+/// 主机的 SSH 连接信息
+#[derive(Debug, Serialize)]
+struct HostSsh {
+    user: String,
+    port: u16,
+    sudo: bool,
+    jump: Vec<String>,
+}
 
-        if !host_config.tags.is_empty() {
-            println!("    标签: {}", host_config.tags.join(", ").bright_black());
+impl HostSsh {
+    fn from_config(ssh: &SshConfig) -> Self {
+        Self {
+            user: ssh.user.clone(),
+            port: ssh.port,
+            sudo: ssh.sudo.is_some(),
+            jump: jump_chain(ssh),
         }
+    }
 
-        println!();
+    /// SSH 地址与跳板机链路的显示行
+    fn lines(&self, indent: &str, host: &str) -> Vec<String> {
+        let sudo = if self.sudo { " (sudo)" } else { "" };
+        let mut lines = vec![format!("{}SSH:  {}{}", indent, format!("{}@{}:{}", self.user, host, self.port).yellow(), sudo)];
+        if !self.jump.is_empty() {
+            lines.push(format!("{}{}: {}", indent, t(MsgKey::LabelJumpHost), self.jump.join(" -> ").yellow()));
+        }
+        lines
     }
+}
 
-    Ok(())
+impl HostList {
+    fn new(config: &CliConfig, records: Vec<HostRecord>, probes: Vec<HostProbeRecord>) -> Self {
+        let hosts = records
+            .into_iter()
+            .map(|record| HostEntry {
+                tags: record_tags(&record),
+                default: config.default_host.as_deref() == Some(record.id.as_str()),
+                ssh: config.hosts.get(&record.id).and_then(|host| host.ssh.as_ref()).map(HostSsh::from_config),
+                status: HostStatus::from_probe(probes.iter().find(|probe| probe.host_id == record.id)),
+                id: record.id,
+                host: record.host,
+                uri: record.uri,
+            })
+            .collect();
+        Self { hosts }
+    }
 }
 
-async fn remove_host(id: &str) -> Result<()> {
-    let mut config = CliConfig::load()?;
+impl Render for HostList {
+    fn to_table(&self) -> String {
+        if self.hosts.is_empty() {
+            return format!(
+                "{}\n\n{}\n  {} atp host add <ID> <HOST> [--uri <URI>] [--ssh-user <USER>] [--ssh-jump <USER@BASTION>]",
+                t(MsgKey::HostNoneConfigured).yellow(),
+                t(MsgKey::HostAddUsage),
+                "$".bright_black()
+            );
+        }
+
+        let mut lines = vec![format!("{}\n", t(MsgKey::HostListTitle).bold())];
+        for host in &self.hosts {
+            let marker = if host.default { "*".green().bold() } else { " ".into() };
+            lines.push(format!(
+                "{} {} {}",
+                marker,
+                host.id.cyan().bold(),
+                if host.default { t(MsgKey::HostDefaultMarker).green() } else { "".into() }
+            ));
+            lines.push(format!("    {}: {}", t(MsgKey::LabelAddress), host.host.yellow()));
+
+            lines.push(format!("    URI:  {}", host.uri.yellow()));
 
-    config.remove_host(id)?;
-    config.save()?;
+            if let Some(ssh) = &host.ssh {
+                lines.extend(ssh.lines("    ", &host.host));
+            }
 
-    println!("{} 主机 {} 已移除", "✓".green().bold(), id.cyan().bold());
+            if !host.tags.is_empty() {
+                lines.push(format!("    {}: {}", t(MsgKey::LabelTags), host.tags.join(", ").bright_black()));
+            }
 
-    Ok(())
+            lines.extend(host.status.lines("    "));
+
+            lines.push(String::new());
+        }
+        lines.join("\n")
+    }
 }
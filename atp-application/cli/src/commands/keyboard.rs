@@ -1,48 +1,57 @@
 //! Keyboard 命令处理
 
 use anyhow::Result;
+use atp_executor::Action;
+use atp_protocol::KeyCombo;
 use colored::Colorize;
 
-use crate::config::CliConfig;
+use crate::commands::common::{output_format, print_rendered, progress};
+use crate::commands::vm_target::{ActionResult, VmTarget};
+use crate::i18n::{t, MsgKey};
+use crate::VmTargetArgs;
 
-pub async fn handle(action: crate::KeyboardAction) -> Result<()> {
+pub async fn handle(action: crate::KeyboardAction, profile: Option<&str>) -> Result<()> {
     match action {
-        crate::KeyboardAction::Send { host, vm, key } => send_key(&host, &vm, &key).await,
-        crate::KeyboardAction::Text { host, vm, text } => send_text(&host, &vm, &text).await,
+        crate::KeyboardAction::Send { target, key } => send_key(&target, profile, &key).await,
+        crate::KeyboardAction::Text { target, text } => send_text(&target, profile, &text).await,
     }
 }
 
-async fn send_key(host_id: &str, vm_name: &str, key: &str) -> Result<()> {
-    println!("{} 准备发送按键...", "⌨".cyan());
-    println!("  主机: {}", host_id.yellow());
-    println!("  虚拟机: {}", vm_name.yellow());
-    println!("  按键: {}", key.green());
+async fn send_key(args: &VmTargetArgs, profile: Option<&str>, key: &str) -> Result<()> {
+    // 按键组合 (如 ctrl+alt+del) 与场景中的 send_key 使用同一解析规则
+    let strokes = KeyCombo::parse(key)?;
+    let qcodes: Vec<String> = strokes.iter().map(|stroke| stroke.qcodes().join("+")).collect();
 
-    // 验证主机配置存在
-    let config = CliConfig::load()?;
-    let _host_config = config.get_host(host_id)?;
+    let format = output_format(None)?;
+    let target = VmTarget::from_args(args, profile).await?;
 
-    // TODO: 实现实际的按键发送
-    // 需要连接到虚拟机并通过 SPICE 协议发送按键
-    println!("\n{} 此功能需要通过场景文件使用", "ℹ".cyan());
-    println!("  提示: 使用 'atp scenario run <file>' 来执行完整的测试场景");
+    progress!(format, "{} {}", "⌨".cyan(), t(MsgKey::PreparingKey));
+    progress!(format, "  {}: {}", t(MsgKey::LabelHost), target.host_id().yellow());
+    progress!(format, "  {}: {}", t(MsgKey::LabelVm), target.vm().yellow());
+    progress!(format, "  {}: {} ({})", t(MsgKey::LabelKey), key.green(), qcodes.join(" ").dimmed());
 
-    Ok(())
+    let action = Action::SendKey {
+        key: key.to_string(),
+        hold_ms: None,
+    };
+    let action_type = action.type_name();
+    let step = target.run(action).await?;
+
+    print_rendered(&ActionResult::executed(&target, &action_type, step), format)
 }
 
-async fn send_text(host_id: &str, vm_name: &str, text: &str) -> Result<()> {
-    println!("{} 准备发送文本...", "⌨".cyan());
-    println!("  主机: {}", host_id.yellow());
-    println!("  虚拟机: {}", vm_name.yellow());
-    println!("  文本: {}", text.green());
+async fn send_text(args: &VmTargetArgs, profile: Option<&str>, text: &str) -> Result<()> {
+    let format = output_format(None)?;
+    let target = VmTarget::from_args(args, profile).await?;
 
-    // 验证主机配置存在
-    let config = CliConfig::load()?;
-    let _host_config = config.get_host(host_id)?;
+    progress!(format, "{} {}", "⌨".cyan(), t(MsgKey::PreparingText));
+    progress!(format, "  {}: {}", t(MsgKey::LabelHost), target.host_id().yellow());
+    progress!(format, "  {}: {}", t(MsgKey::LabelVm), target.vm().yellow());
+    progress!(format, "  {}: {}", t(MsgKey::LabelText), text.green());
 
-    // TODO: 实现实际的文本发送
-    println!("\n{} 此功能需要通过场景文件使用", "ℹ".cyan());
-    println!("  提示: 使用 'atp scenario run <file>' 来执行完整的测试场景");
+    let action = Action::SendText { text: text.to_string() };
+    let action_type = action.type_name();
+    let step = target.run(action).await?;
 
-    Ok(())
+    print_rendered(&ActionResult::executed(&target, &action_type, step), format)
 }
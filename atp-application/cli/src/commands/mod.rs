@@ -1,10 +1,14 @@
 //! CLI 命令处理模块
 
+pub mod bench;
 pub mod command;
+pub mod common;
 pub mod db;
 pub mod host;
 pub mod keyboard;
 pub mod mouse;
 pub mod report; // 启用报告命令
 pub mod scenario;
+pub mod shell;
 pub mod vdi; // VDI 平台管理
+pub mod vm_target;
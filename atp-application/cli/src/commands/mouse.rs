@@ -1,52 +1,55 @@
 //! Mouse 命令处理
 
 use anyhow::Result;
+use atp_executor::Action;
 use colored::Colorize;
 
-use crate::config::CliConfig;
+use crate::commands::common::{output_format, print_rendered, progress};
+use crate::commands::vm_target::{ActionResult, VmTarget};
+use crate::i18n::{t, MsgKey};
+use crate::VmTargetArgs;
 
-pub async fn handle(action: crate::MouseAction) -> Result<()> {
+pub async fn handle(action: crate::MouseAction, profile: Option<&str>) -> Result<()> {
     match action {
-        crate::MouseAction::Click { host, vm, x, y, button } => {
-            click(&host, &vm, x, y, &button).await
+        crate::MouseAction::Click { target, x, y, button } => {
+            click(&target, profile, x, y, &button).await
         }
-        crate::MouseAction::Move { host, vm, x, y } => {
-            move_mouse(&host, &vm, x, y).await
+        crate::MouseAction::Move { target, x, y } => {
+            move_mouse(&target, profile, x, y).await
         }
     }
 }
 
-async fn click(host_id: &str, vm_name: &str, x: i32, y: i32, button: &str) -> Result<()> {
-    println!("{} 准备鼠标点击...", "🖱".cyan());
-    println!("  主机: {}", host_id.yellow());
-    println!("  虚拟机: {}", vm_name.yellow());
-    println!("  位置: ({}, {})", x.to_string().green(), y.to_string().green());
-    println!("  按钮: {}", button.green());
-
-    // 验证主机配置存在
-    let config = CliConfig::load()?;
-    let _host_config = config.get_host(host_id)?;
-
-    // TODO: 实现实际的鼠标点击
-    println!("\n{} 此功能需要通过场景文件使用", "ℹ".cyan());
-    println!("  提示: 使用 'atp scenario run <file>' 来执行完整的测试场景");
-
-    Ok(())
+async fn click(args: &VmTargetArgs, profile: Option<&str>, x: i32, y: i32, button: &str) -> Result<()> {
+    let format = output_format(None)?;
+    let target = VmTarget::from_args(args, profile).await?;
+
+    progress!(format, "{} {}", "🖱".cyan(), t(MsgKey::PreparingClick));
+    progress!(format, "  {}: {}", t(MsgKey::LabelHost), target.host_id().yellow());
+    progress!(format, "  {}: {}", t(MsgKey::LabelVm), target.vm().yellow());
+    progress!(format, "  {}: ({}, {})", t(MsgKey::LabelPosition), x.to_string().green(), y.to_string().green());
+    progress!(format, "  {}: {}", t(MsgKey::LabelButton), button.green());
+
+    let action = Action::MouseClick {
+        x,
+        y,
+        button: button.to_string(),
+    };
+    let action_type = action.type_name();
+    let step = target.run(action).await?;
+
+    print_rendered(&ActionResult::executed(&target, &action_type, step), format)
 }
 
-async fn move_mouse(host_id: &str, vm_name: &str, x: i32, y: i32) -> Result<()> {
-    println!("{} 准备移动鼠标...", "🖱".cyan());
-    println!("  主机: {}", host_id.yellow());
-    println!("  虚拟机: {}", vm_name.yellow());
-    println!("  位置: ({}, {})", x.to_string().green(), y.to_string().green());
-
-    // 验证主机配置存在
-    let config = CliConfig::load()?;
-    let _host_config = config.get_host(host_id)?;
+async fn move_mouse(args: &VmTargetArgs, profile: Option<&str>, x: i32, y: i32) -> Result<()> {
+    let format = output_format(None)?;
+    let target = VmTarget::from_args(args, profile).await?;
 
-    // TODO: 实现实际的鼠标移动
-    println!("\n{} 此功能需要通过场景文件使用", "ℹ".cyan());
-    println!("  提示: 使用 'atp scenario run <file>' 来执行完整的测试场景");
+    progress!(format, "{} {}", "🖱".cyan(), t(MsgKey::PreparingMove));
+    progress!(format, "  {}: {}", t(MsgKey::LabelHost), target.host_id().yellow());
+    progress!(format, "  {}: {}", t(MsgKey::LabelVm), target.vm().yellow());
+    progress!(format, "  {}: ({}, {})", t(MsgKey::LabelPosition), x.to_string().green(), y.to_string().green());
 
-    Ok(())
+    // 场景中没有单独的鼠标移动动作
+    print_rendered(&ActionResult::scenario_only(&target, "mouse_move"), format)
 }
@@ -1,23 +1,167 @@
 //! 测试报告管理命令
 
+use std::collections::BTreeMap;
+
 use anyhow::Result;
 use colored::Colorize;
-use chrono::Local;
-use atp_storage::{StorageManager, Storage, ReportFilter};
-
-pub async fn handle(action: crate::ReportAction) -> Result<()> {
+use chrono::{Duration, Local, Utc};
+use tracing::info;
+use atp_executor::html_report::render_comparison_html;
+use atp_executor::{DiffOptions, ExecutionReport, ReportDiff, StepChange, StepDiff, StepErrorKind, StepSide};
+use atp_storage::{
+    Anonymizer, BundleMetadata, ExecutionStepRecord, StorageManager, Storage, MetricBucket, MetricEntity, ReportBundle,
+    ReportFilter, ReportCleanupCriteria, ReportCleanupStats, RetentionPolicyRecord, RetentionResult, TestReportRecord,
+    VerificationFilter,
+};
+use serde::Serialize;
+
+use crate::commands::common::{output_format, print_rendered, print_serialized, progress, Render};
+use crate::config::CliConfig;
+
+pub async fn handle(action: crate::ReportAction, profile: Option<&str>) -> Result<()> {
     match action {
         crate::ReportAction::List {
             scenario,
             passed,
             failed,
+            failed_kind,
             limit,
-        } => list_reports(scenario, passed, failed, limit).await,
+        } => list_reports(scenario, passed, failed, failed_kind, limit).await,
         crate::ReportAction::Show { id } => show_report(id).await,
-        crate::ReportAction::Export { id, output, format } => export_report(id, &output, &format).await,
+        crate::ReportAction::Diff {
+            baseline,
+            current,
+            threshold,
+            all,
+            format,
+        } => diff_reports(baseline, current, threshold, all, &format).await,
+        crate::ReportAction::Export {
+            id,
+            ids,
+            output,
+            format,
+            bundle,
+            anonymize,
+            mapping,
+        } => {
+            // 匿名化时映射表的输出路径
+            let mapping = anonymize.then(|| mapping.unwrap_or_else(|| format!("{}.mapping.json", output)));
+            match id {
+                None => export_comparison(&ids, &output, &format, mapping.as_deref()).await,
+                Some(id) if bundle => export_bundle(id, &output, profile, mapping.as_deref()).await,
+                Some(id) => export_report(id, &output, &format, mapping.as_deref()).await,
+            }
+        }
+        crate::ReportAction::Import { file } => import_bundle(&file).await,
         crate::ReportAction::Delete { id } => delete_report(id).await,
-        crate::ReportAction::Stats { scenario, days } => show_stats(&scenario, days).await,
-        crate::ReportAction::Cleanup { days, force } => cleanup_reports(days, force).await,
+        crate::ReportAction::Stats {
+            scenario,
+            days,
+            min_runs,
+            limit,
+            format,
+        } => show_stats(&scenario, days, min_runs, limit, &format).await,
+        crate::ReportAction::Cleanup {
+            days,
+            force,
+            scenario,
+            tag,
+            dry_run,
+        } => cleanup_reports(days, force, scenario, tag, dry_run).await,
+        crate::ReportAction::Retention { action } => match action {
+            crate::RetentionAction::Add {
+                scenario,
+                keep_last,
+                keep_days,
+            } => add_retention_policy(&scenario, keep_last, keep_days).await,
+            crate::RetentionAction::List => list_retention_policies().await,
+            crate::RetentionAction::Remove { id } => remove_retention_policy(id).await,
+            crate::RetentionAction::Apply { force, dry_run } => apply_retention(force, dry_run).await,
+        },
+        crate::ReportAction::Metrics {
+            vm,
+            host,
+            metric,
+            since,
+            bucket,
+            format,
+        } => {
+            let entity = match (vm, host) {
+                (Some(vm), _) => MetricEntity::vm(&vm),
+                (None, Some(host)) => MetricEntity::host(&host),
+                (None, None) => anyhow::bail!("需要指定 --vm 或 --host"),
+            };
+            show_metrics(entity, &metric, &since, bucket.as_deref(), &format).await
+        }
+        crate::ReportAction::Verification {
+            vm,
+            since,
+            limit,
+            format,
+        } => show_verification_results(vm, &since, limit, &format).await,
+    }
+}
+
+/// 报告列表 (`atp report list`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct ReportList {
+    reports: Vec<TestReportRecord>,
+}
+
+impl Render for ReportList {
+    fn to_table(&self) -> String {
+        if self.reports.is_empty() {
+            return format!("\n{} 没有找到测试报告", "ℹ".yellow());
+        }
+
+        let mut lines = vec![
+            format!("\n{} 找到 {} 个报告:\n", "✓".green(), self.reports.len()),
+            format!(
+                "{:<6} {:<25} {:<20} {:<8} {:<10} {:<15}",
+                "ID".bold(),
+                "场景名称".bold(),
+                "执行时间".bold(),
+                "结果".bold(),
+                "步骤".bold(),
+                "耗时".bold()
+            ),
+            "-".repeat(90),
+        ];
+
+        for report in &self.reports {
+            let result_str = if report.passed {
+                "通过".green()
+            } else {
+                "失败".red()
+            };
+
+            let local_time = report.start_time.with_timezone(&Local);
+            let time_str = local_time.format("%Y-%m-%d %H:%M:%S").to_string();
+
+            let steps_str = format!(
+                "{}/{}",
+                report.success_count,
+                report.total_steps
+            );
+
+            let duration_str = if let Some(ms) = report.duration_ms {
+                format!("{:.2}s", ms as f64 / 1000.0)
+            } else {
+                "N/A".to_string()
+            };
+
+            lines.push(format!(
+                "{:<6} {:<25} {:<20} {:<8} {:<10} {:<15}",
+                report.id,
+                report.scenario_name,
+                time_str,
+                result_str,
+                steps_str,
+                duration_str
+            ));
+        }
+        lines.join("\n")
     }
 }
 
@@ -25,15 +169,18 @@ async fn list_reports(
     scenario: Option<String>,
     passed: bool,
     failed: bool,
+    failed_kind: Option<StepErrorKind>,
     limit: i64,
 ) -> Result<()> {
-    println!("{} 加载测试报告...", "⏳".cyan());
+    let format = output_format(None)?;
+    progress!(format, "{} 加载测试报告...", "⏳".cyan());
 
     let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
     let storage = Storage::from_manager(&storage_manager);
 
     let mut filter = ReportFilter {
         scenario_name: scenario,
+        failed_kind: failed_kind.map(|kind| kind.as_str().to_string()),
         limit: Some(limit),
         ..Default::default()
     };
@@ -45,136 +192,307 @@ async fn list_reports(
     }
 
     let reports = storage.reports().list(&filter).await?;
+    print_rendered(&ReportList { reports }, format)
+}
 
-    if reports.is_empty() {
-        println!("\n{} 没有找到测试报告", "ℹ".yellow());
-        return Ok(());
+/// 报告详情 (`atp report show`)
+#[derive(Debug, Serialize)]
+struct ReportDetail {
+    report: TestReportRecord,
+
+    /// 失败步骤按错误分类计数
+    failed_kinds: BTreeMap<String, usize>,
+
+    steps: Vec<ExecutionStepRecord>,
+}
+
+impl ReportDetail {
+    fn new(report: TestReportRecord, steps: Vec<ExecutionStepRecord>) -> Self {
+        let mut failed_kinds = BTreeMap::new();
+        for step in steps.iter().filter(|step| step.status == "Failed") {
+            let kind = step.error_kind.as_deref().unwrap_or("unknown");
+            *failed_kinds.entry(kind.to_string()).or_default() += 1;
+        }
+        Self { report, failed_kinds, steps }
     }
+}
 
-    println!("\n{} 找到 {} 个报告:\n", "✓".green(), reports.len());
+impl Render for ReportDetail {
+    fn to_table(&self) -> String {
+        let report = &self.report;
+        let mut lines = vec![
+            format!("\n{} 测试报告详情\n", "📊".cyan()),
+            format!("  ID: {}", report.id),
+            format!("  场景: {}", report.scenario_name.yellow()),
+        ];
+        if let Some(version) = report.scenario_version {
+            lines.push(format!("  场景版本: {}", version));
+        }
 
-    // 表头
-    println!(
-        "{:<6} {:<25} {:<20} {:<8} {:<10} {:<15}",
-        "ID".bold(),
-        "场景名称".bold(),
-        "执行时间".bold(),
-        "结果".bold(),
-        "步骤".bold(),
-        "耗时".bold()
-    );
-    println!("{}", "-".repeat(90));
+        if let Some(desc) = &report.description {
+            lines.push(format!("  描述: {}", desc));
+        }
 
-    for report in reports {
-        let result_str = if report.passed {
-            "通过".green()
+        lines.push(format!("  结果: {}", if report.passed {
+            "通过 ✓".green()
         } else {
-            "失败".red()
-        };
+            "失败 ✗".red()
+        }));
 
         let local_time = report.start_time.with_timezone(&Local);
-        let time_str = local_time.format("%Y-%m-%d %H:%M:%S").to_string();
+        lines.push(format!("  开始时间: {}", local_time.format("%Y-%m-%d %H:%M:%S")));
 
-        let steps_str = format!(
-            "{}/{}",
-            report.success_count,
-            report.total_steps
-        );
+        if let Some(duration_ms) = report.duration_ms {
+            lines.push(format!("  总耗时: {:.2} 秒", duration_ms as f64 / 1000.0));
+        }
 
-        let duration_str = if let Some(ms) = report.duration_ms {
-            format!("{:.2}s", ms as f64 / 1000.0)
-        } else {
-            "N/A".to_string()
-        };
+        lines.push("\n  步骤统计:".to_string());
+        lines.push(format!("    总步骤数: {}", report.total_steps));
+        lines.push(format!("    成功: {}", report.success_count.to_string().green()));
+        lines.push(format!("    失败: {}", report.failed_count.to_string().red()));
+        lines.push(format!("    跳过: {}", report.skipped_count));
+
+        if !self.failed_kinds.is_empty() {
+            let summary: Vec<String> = self
+                .failed_kinds
+                .iter()
+                .map(|(kind, count)| format!("{} {}", kind, count))
+                .collect();
+            lines.push(format!("    失败分类: {}", summary.join(", ")));
+        }
 
-        println!(
-            "{:<6} {:<25} {:<20} {:<8} {:<10} {:<15}",
-            report.id,
-            report.scenario_name,
-            time_str,
-            result_str,
-            steps_str,
-            duration_str
-        );
-    }
+        if !self.steps.is_empty() {
+            lines.push("\n  步骤详情:\n".to_string());
 
-    Ok(())
+            for step in &self.steps {
+                let status_icon = match step.status.as_str() {
+                    "Success" => "✓".green(),
+                    "Failed" => "✗".red(),
+                    "Skipped" => "⊘".yellow(),
+                    _ => "?".normal(),
+                };
+
+                lines.push(format!("    {} 步骤 {}: {}", status_icon, step.step_index + 1, step.description));
+
+                if let Some(error) = &step.error {
+                    lines.push(format!("      错误: {}", error.red()));
+                }
+
+                if let Some(kind) = &step.error_kind {
+                    lines.push(format!("      分类: {}", kind));
+                }
+
+                if let Some(duration_ms) = step.duration_ms {
+                    lines.push(format!("      耗时: {:.2} 秒", duration_ms as f64 / 1000.0));
+                }
+
+                if let Some(output) = &step.output {
+                    lines.push(format!("      输出: {}", output.dimmed()));
+                }
+            }
+        }
+
+        lines.join("\n")
+    }
 }
 
 async fn show_report(id: i64) -> Result<()> {
-    println!("{} 加载报告详情...", "⏳".cyan());
+    let format = output_format(None)?;
+    progress!(format, "{} 加载报告详情...", "⏳".cyan());
 
     let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
     let storage = Storage::from_manager(&storage_manager);
 
-    let report = storage.reports().get_by_id(id).await?;
+    let report = storage
+        .reports()
+        .get_by_id(id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("未找到报告 ID: {}", id))?;
+    let steps = storage.reports().get_steps(id).await?;
 
-    if report.is_none() {
-        println!("\n{} 未找到报告 ID: {}", "✗".red(), id);
-        return Ok(());
+    print_rendered(&ReportDetail::new(report, steps), format)
+}
+
+/// 创建匿名化器: 配置中的主机地址、主机 ID 与 SSH 用户名作为已知值, 并追加配置的规则
+fn create_anonymizer() -> Result<Anonymizer> {
+    let config = CliConfig::load()?;
+    let mut anonymizer = Anonymizer::new().with_rules(&config.anonymize_rules)?;
+
+    let mut host_ids: Vec<&String> = config.hosts.keys().collect();
+    host_ids.sort();
+    for id in host_ids {
+        let host = &config.hosts[id];
+        anonymizer.add_known("host", id);
+        anonymizer.add_known("host", &host.host);
+        if let Some(ssh) = &host.ssh {
+            anonymizer.add_known("user", &ssh.user);
+        }
     }
 
-    let report = report.unwrap();
-    let steps = storage.reports().get_steps(id).await?;
+    Ok(anonymizer)
+}
 
-    println!("\n{} 测试报告详情\n", "📊".cyan());
-    println!("  ID: {}", report.id);
-    println!("  场景: {}", report.scenario_name.yellow());
+/// 输出匿名化映射表
+fn write_mapping(anonymizer: &Anonymizer, path: &str) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(anonymizer.mapping())?)?;
 
-    if let Some(desc) = &report.description {
-        println!("  描述: {}", desc);
-    }
+    println!(
+        "{} 匿名化映射表已输出到: {} ({} 项, 仅供内部对照, 请勿随报告发送)",
+        "✓".green(),
+        path.yellow(),
+        anonymizer.mapping().len()
+    );
 
-    println!("  结果: {}", if report.passed {
-        "通过 ✓".green()
-    } else {
-        "失败 ✗".red()
-    });
+    Ok(())
+}
 
-    let local_time = report.start_time.with_timezone(&Local);
-    println!("  开始时间: {}", local_time.format("%Y-%m-%d %H:%M:%S"));
+/// 报告对比结果 (`atp report diff`)
+#[derive(Debug, Serialize)]
+struct ReportDiffView {
+    baseline_id: i64,
+    current_id: i64,
 
-    if let Some(duration_ms) = report.duration_ms {
-        println!("  总耗时: {:.2} 秒", duration_ms as f64 / 1000.0);
-    }
+    #[serde(flatten)]
+    diff: ReportDiff,
 
-    println!("\n  步骤统计:");
-    println!("    总步骤数: {}", report.total_steps);
-    println!("    成功: {}", report.success_count.to_string().green());
-    println!("    失败: {}", report.failed_count.to_string().red());
-    println!("    跳过: {}", report.skipped_count);
+    /// 表格输出时是否列出无变化的步骤
+    #[serde(skip)]
+    show_all: bool,
+}
 
-    if !steps.is_empty() {
-        println!("\n  步骤详情:\n");
+impl Render for ReportDiffView {
+    fn to_table(&self) -> String {
+        let diff = &self.diff;
+        let result = |passed: bool| if passed { "通过".green() } else { "失败".red() };
+        let seconds = |ms: u64| format!("{:.2}s", ms as f64 / 1000.0);
+
+        let mut lines = vec![
+            format!(
+                "\n{} 报告 #{} ({}) → #{} ({})\n",
+                "📊".cyan(),
+                self.baseline_id,
+                diff.baseline_scenario.yellow(),
+                self.current_id,
+                diff.current_scenario.yellow()
+            ),
+            format!("  结果: {} → {}", result(diff.baseline_passed), result(diff.current_passed)),
+            format!(
+                "  总耗时: {} → {}",
+                seconds(diff.baseline_duration_ms),
+                seconds(diff.current_duration_ms)
+            ),
+            format!("  {}\n", diff),
+        ];
+
+        let rows: Vec<&StepDiff> = if self.show_all { diff.steps.iter().collect() } else { diff.changed().collect() };
+        if rows.is_empty() {
+            lines.push(format!("{} 步骤没有变化", "✓".green()));
+            return lines.join("\n");
+        }
 
-        for step in steps {
-            let status_icon = match step.status.as_str() {
-                "Success" => "✓".green(),
-                "Failed" => "✗".red(),
-                "Skipped" => "⊘".yellow(),
-                _ => "?".normal(),
+        lines.push(format!(
+            "{:<10} {:<36} {:>10} {:>10} {:>9}",
+            "变化".bold(),
+            "步骤".bold(),
+            "基准".bold(),
+            "本次".bold(),
+            "耗时变化".bold()
+        ));
+        lines.push("-".repeat(80));
+
+        for step in rows {
+            let label = step.change.label();
+            let label = match step.change {
+                StepChange::NewlyFailed | StepChange::StillFailing => label.red(),
+                StepChange::DurationRegressed | StepChange::Removed => label.yellow(),
+                StepChange::NewlyPassed => label.green(),
+                StepChange::Unchanged | StepChange::Added => label.normal(),
+            };
+            let duration = |side: &Option<StepSide>| {
+                side.as_ref().map(|side| seconds(side.duration_ms)).unwrap_or_else(|| "-".to_string())
             };
+            let percent = step
+                .duration_delta_pct()
+                .map(|percent| format!("{:+.0}%", percent))
+                .unwrap_or_else(|| "-".to_string());
+
+            lines.push(format!(
+                "{:<10} {:<36} {:>10} {:>10} {:>9}",
+                label,
+                step.description,
+                duration(&step.baseline),
+                duration(&step.current),
+                percent
+            ));
+            if let Some(error) = step.current.as_ref().and_then(|current| current.error.as_deref()) {
+                if step.change == StepChange::NewlyFailed || step.change == StepChange::Added {
+                    lines.push(format!("{:<10} 错误: {}", "", error.red()));
+                }
+            }
+        }
 
-            println!("    {} 步骤 {}: {}", status_icon, step.step_index + 1, step.description);
+        lines.join("\n")
+    }
+}
 
-            if let Some(error) = &step.error {
-                println!("      错误: {}", error.red());
-            }
+/// 对比两次报告, 有新失败的步骤时以非零状态退出
+async fn diff_reports(baseline_id: i64, current_id: i64, threshold: f64, show_all: bool, format: &str) -> Result<()> {
+    let format = output_format(Some(format))?;
+    if threshold < 0.0 {
+        anyhow::bail!("耗时退化阈值不能为负数: {}", threshold);
+    }
 
-            if let Some(duration_ms) = step.duration_ms {
-                println!("      耗时: {:.2} 秒", duration_ms as f64 / 1000.0);
-            }
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
 
-            if let Some(output) = &step.output {
-                println!("      输出: {}", output.dimmed());
-            }
+    let load = |id: i64| {
+        let storage = &storage;
+        async move {
+            let record = storage
+                .reports()
+                .get_by_id(id)
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("未找到报告 ID: {}", id))?;
+            let steps = storage.reports().get_steps(id).await?;
+            anyhow::Ok(ExecutionReport::from_records(&record, &steps))
         }
+    };
+    let baseline = load(baseline_id).await?;
+    let current = load(current_id).await?;
+    if baseline.scenario_name != current.scenario_name {
+        eprintln!(
+            "{} 两次报告的场景不同: {} / {}",
+            "⚠".yellow(),
+            baseline.scenario_name,
+            current.scenario_name
+        );
+    }
+
+    let diff = current.diff_with(&baseline, &DiffOptions::default().with_threshold(threshold));
+    let has_new_failures = diff.has_new_failures();
+    print_rendered(
+        &ReportDiffView {
+            baseline_id,
+            current_id,
+            diff,
+            show_all,
+        },
+        format,
+    )?;
+
+    if has_new_failures {
+        std::process::exit(1);
     }
 
     Ok(())
 }
 
-async fn export_report(id: i64, output: &str, format: &str) -> Result<()> {
+async fn export_report(
+    id: i64,
+    output: &str,
+    format: &str,
+    mapping: Option<&str>,
+) -> Result<()> {
     println!("{} 导出报告...", "⏳".cyan());
 
     let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
@@ -189,16 +507,37 @@ async fn export_report(id: i64, output: &str, format: &str) -> Result<()> {
 
     let report = report.unwrap();
     let steps = storage.reports().get_steps(id).await?;
+    let step_metrics = storage.reports().get_step_metrics(id).await?;
+
+    let mut anonymizer = mapping.map(|_| create_anonymizer()).transpose()?;
 
     // 构建导出数据
-    let export_data = serde_json::json!({
+    let mut export_data = serde_json::json!({
         "report": report,
         "steps": steps,
+        "step_metrics": step_metrics,
     });
 
     let content = match format {
-        "json" => serde_json::to_string_pretty(&export_data)?,
-        "yaml" => serde_yaml::to_string(&export_data)?,
+        "json" | "yaml" => {
+            if let Some(anonymizer) = anonymizer.as_mut() {
+                anonymizer.anonymize_value(&mut export_data);
+            }
+            if format == "json" {
+                serde_json::to_string_pretty(&export_data)?
+            } else {
+                serde_yaml::to_string(&export_data)?
+            }
+        }
+        "html" => {
+            let mut execution_report = ExecutionReport::from_records(&report, &steps).with_step_metrics(&step_metrics);
+            if let Some(anonymizer) = anonymizer.as_mut() {
+                let mut value = serde_json::to_value(&execution_report)?;
+                anonymizer.anonymize_value(&mut value);
+                execution_report = serde_json::from_value(value)?;
+            }
+            execution_report.to_html()
+        }
         _ => anyhow::bail!("不支持的格式: {}", format),
     };
 
@@ -206,29 +545,192 @@ async fn export_report(id: i64, output: &str, format: &str) -> Result<()> {
 
     println!("\n{} 报告已导出到: {}", "✓".green(), output.yellow());
 
+    if let (Some(anonymizer), Some(mapping)) = (&anonymizer, mapping) {
+        write_mapping(anonymizer, mapping)?;
+    }
+
     Ok(())
 }
 
-async fn delete_report(id: i64) -> Result<()> {
-    println!("{} 删除报告 {}...", "⏳".cyan(), id);
+/// 导出多份报告的步骤对比矩阵 (HTML)
+async fn export_comparison(
+    ids: &[i64],
+    output: &str,
+    format: &str,
+    mapping: Option<&str>,
+) -> Result<()> {
+    if format != "html" {
+        anyhow::bail!("多报告对比只支持 html 格式, 当前格式: {}", format);
+    }
+
+    println!("{} 导出 {} 份报告的对比...", "⏳".cyan(), ids.len());
 
     let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
     let storage = Storage::from_manager(&storage_manager);
 
-    storage.reports().delete(id).await?;
+    let mut anonymizer = mapping.map(|_| create_anonymizer()).transpose()?;
+    let mut reports = Vec::with_capacity(ids.len());
+    for &id in ids {
+        let Some(report) = storage.reports().get_by_id(id).await? else {
+            anyhow::bail!("未找到报告 ID: {}", id);
+        };
+        let steps = storage.reports().get_steps(id).await?;
 
-    println!("\n{} 报告已删除", "✓".green());
+        let mut execution_report = ExecutionReport::from_records(&report, &steps);
+        if let Some(anonymizer) = anonymizer.as_mut() {
+            let mut value = serde_json::to_value(&execution_report)?;
+            anonymizer.anonymize_value(&mut value);
+            execution_report = serde_json::from_value(value)?;
+        }
+        reports.push((id, execution_report));
+    }
+
+    std::fs::write(output, render_comparison_html(&reports))?;
+
+    println!("\n{} 报告对比已导出到: {}", "✓".green(), output.yellow());
+
+    if let (Some(anonymizer), Some(mapping)) = (&anonymizer, mapping) {
+        write_mapping(anonymizer, mapping)?;
+    }
 
     Ok(())
 }
 
-async fn show_stats(scenario: &str, days: i32) -> Result<()> {
-    println!("{} 加载统计信息...", "⏳".cyan());
+async fn export_bundle(
+    id: i64,
+    output: &str,
+    profile: Option<&str>,
+    mapping: Option<&str>,
+) -> Result<()> {
+    println!("{} 导出报告包...", "⏳".cyan());
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    if storage.reports().get_by_id(id).await?.is_none() {
+        println!("\n{} 未找到报告 ID: {}", "✗".red(), id);
+        return Ok(());
+    }
+
+    let bundle = storage.reports().export_bundle(id, profile).await?;
+    let mut content = serde_json::to_value(&bundle)?;
+    let mut anonymizer = mapping.map(|_| create_anonymizer()).transpose()?;
+    if let Some(anonymizer) = anonymizer.as_mut() {
+        anonymizer.anonymize_value(&mut content);
+    }
+    std::fs::write(output, serde_json::to_string_pretty(&content)?)?;
+
+    println!(
+        "\n{} 报告包已导出到: {} ({} 个步骤)",
+        "✓".green(),
+        output.yellow(),
+        bundle.steps.len()
+    );
+
+    if let (Some(anonymizer), Some(mapping)) = (&anonymizer, mapping) {
+        write_mapping(anonymizer, mapping)?;
+    }
+
+    Ok(())
+}
+
+/// 导入的报告包 (`atp report import`)
+#[derive(Debug, Serialize)]
+struct ImportedBundle {
+    /// 导入后的报告 ID
+    id: i64,
+    scenario_name: String,
+    metadata: BundleMetadata,
+}
+
+impl Render for ImportedBundle {
+    fn to_table(&self) -> String {
+        let metadata = &self.metadata;
+        [
+            format!("\n{} 报告已导入, 新的报告 ID: {}", "✓".green(), self.id.to_string().yellow()),
+            format!("  场景: {}", self.scenario_name),
+            format!(
+                "  来源: {} (profile: {}, ATP {})",
+                metadata.hostname.as_deref().unwrap_or("未知主机"),
+                metadata.profile.as_deref().unwrap_or("-"),
+                metadata.atp_version
+            ),
+            format!(
+                "  导出时间: {}",
+                metadata.exported_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+            ),
+        ]
+        .join("\n")
+    }
+}
+
+async fn import_bundle(file: &str) -> Result<()> {
+    let format = output_format(None)?;
+    progress!(format, "{} 导入报告包...", "⏳".cyan());
+
+    let content = std::fs::read_to_string(file)?;
+    let bundle: ReportBundle = serde_json::from_str(&content)
+        .map_err(|e| anyhow::anyhow!("无效的报告包 {}: {}", file, e))?;
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let id = storage.reports().import_bundle(&bundle).await?;
+
+    let imported = ImportedBundle {
+        id,
+        scenario_name: bundle.report.scenario_name,
+        metadata: bundle.metadata,
+    };
+    print_rendered(&imported, format)
+}
+
+/// 已删除的报告 (`atp report delete`)
+#[derive(Debug, Serialize)]
+struct DeletedReport {
+    id: i64,
+}
+
+impl Render for DeletedReport {
+    fn to_table(&self) -> String {
+        format!("\n{} 报告已删除", "✓".green())
+    }
+}
+
+async fn delete_report(id: i64) -> Result<()> {
+    let format = output_format(None)?;
+    progress!(format, "{} 删除报告 {}...", "⏳".cyan(), id);
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    storage.reports().delete(id).await?;
+
+    print_rendered(&DeletedReport { id }, format)
+}
+
+async fn show_stats(scenario: &str, days: i32, min_runs: i64, limit: i64, format: &str) -> Result<()> {
+    let format = output_format(Some(format))?;
 
     let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
     let storage = Storage::from_manager(&storage_manager);
 
     let success_rate = storage.reports().get_success_rate(scenario, days).await?;
+    let trend = storage.reports().scenario_trend(scenario, days).await?;
+    let flaky = storage.reports().flaky_steps(scenario, days, min_runs).await?;
+    let slowest = storage.reports().slowest_steps(scenario, limit).await?;
+
+    if !format.is_table() {
+        let stats = serde_json::json!({
+            "scenario": scenario,
+            "days": days,
+            "success_rate": success_rate,
+            "trend": trend,
+            "flaky_steps": flaky,
+            "slowest_steps": slowest,
+        });
+        return print_serialized(&stats, format);
+    }
 
     println!("\n{} 场景统计: {}\n", "📈".cyan(), scenario.yellow());
     println!("  时间范围: 最近 {} 天", days);
@@ -242,10 +744,80 @@ async fn show_stats(scenario: &str, days: i32) -> Result<()> {
         println!("  评级: {} 需要改进", "★".red());
     }
 
+    println!("\n{} 每日趋势:\n", "📅".cyan());
+    if trend.is_empty() {
+        println!("  {} 没有执行记录", "ℹ".yellow());
+    } else {
+        println!(
+            "  {:<12} {:<8} {:<8} {:<10} {:<12}",
+            "日期".bold(),
+            "执行".bold(),
+            "通过".bold(),
+            "通过率".bold(),
+            "平均耗时".bold()
+        );
+        for day in &trend {
+            println!(
+                "  {:<12} {:<8} {:<8} {:<10} {:<12}",
+                day.date,
+                day.runs,
+                day.passed_runs,
+                format!("{:.1}%", day.pass_rate),
+                format_duration(day.avg_duration_ms)
+            );
+        }
+    }
+
+    println!("\n{} 不稳定步骤 (至少执行 {} 次):\n", "⚠".yellow(), min_runs);
+    if flaky.is_empty() {
+        println!("  {} 没有发现不稳定步骤", "✓".green());
+    } else {
+        println!("  {:<40} {:<8} {:<8} {:<10}", "步骤".bold(), "执行".bold(), "失败".bold(), "失败率".bold());
+        for step in &flaky {
+            println!(
+                "  {:<40} {:<8} {:<8} {:<10}",
+                step.description,
+                step.runs,
+                step.failures,
+                format!("{:.1}%", step.failure_rate).red()
+            );
+        }
+    }
+
+    println!("\n{} 最慢步骤:\n", "🐢".cyan());
+    if slowest.is_empty() {
+        println!("  {} 没有步骤耗时记录", "ℹ".yellow());
+    } else {
+        println!("  {:<40} {:<8} {:<12} {:<12}", "步骤".bold(), "执行".bold(), "平均耗时".bold(), "最长耗时".bold());
+        for step in &slowest {
+            println!(
+                "  {:<40} {:<8} {:<12} {:<12}",
+                step.description,
+                step.runs,
+                format_duration(Some(step.avg_duration_ms)),
+                format_duration(Some(step.max_duration_ms as f64))
+            );
+        }
+    }
+
     Ok(())
 }
 
-async fn cleanup_reports(days: i32, force: bool) -> Result<()> {
+/// 格式化毫秒耗时
+fn format_duration(ms: Option<f64>) -> String {
+    match ms {
+        Some(ms) => format!("{:.2}s", ms / 1000.0),
+        None => "N/A".to_string(),
+    }
+}
+
+async fn cleanup_reports(
+    days: i32,
+    force: bool,
+    scenario: Option<String>,
+    tag: Option<String>,
+    dry_run: bool,
+) -> Result<()> {
     println!("{} 准备清理旧报告...", "⏳".cyan());
 
     let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
@@ -254,13 +826,14 @@ async fn cleanup_reports(days: i32, force: bool) -> Result<()> {
     // 计算截止日期
     let cutoff_date = chrono::Utc::now() - chrono::Duration::days(days as i64);
 
-    // 查询要删除的报告
-    let filter = ReportFilter {
-        start_time_to: Some(cutoff_date),
-        ..Default::default()
+    let criteria = ReportCleanupCriteria {
+        before: Some(cutoff_date),
+        scenario_pattern: scenario.clone(),
+        tag: tag.clone(),
     };
 
-    let to_delete = storage.reports().list(&filter).await?;
+    // 查询要删除的报告
+    let to_delete = storage.reports().list_cleanup_candidates(&criteria).await?;
 
     if to_delete.is_empty() {
         println!("\n{} 没有需要清理的报告", "ℹ".yellow());
@@ -273,9 +846,36 @@ async fn cleanup_reports(days: i32, force: bool) -> Result<()> {
         cutoff_date.format("%Y-%m-%d")
     );
 
+    if let Some(pattern) = &scenario {
+        println!("  场景匹配: {}", pattern.yellow());
+    }
+
+    if let Some(tag) = &tag {
+        println!("  标签: {}", tag.yellow());
+    }
+
     // 显示统计
-    let total_steps: i32 = to_delete.iter().map(|r| r.total_steps).sum();
-    println!("  总步骤数: {}", total_steps);
+    let stats = storage.reports().cleanup(&criteria, true).await?;
+    println!("  总步骤数: {}", stats.step_count);
+    println!("  预计释放空间: {}", stats.estimated_size_human_readable());
+
+    if dry_run {
+        println!("\n{} 演练模式, 以下报告将被删除:\n", "ℹ".cyan());
+
+        for report in &to_delete {
+            let local_time = report.start_time.with_timezone(&Local);
+            println!(
+                "  {:<6} {:<25} {:<20} {}",
+                report.id,
+                report.scenario_name,
+                local_time.format("%Y-%m-%d %H:%M:%S"),
+                report.tags.as_deref().unwrap_or("-")
+            );
+        }
+
+        println!("\n{} 演练模式未删除任何报告", "ℹ".yellow());
+        return Ok(());
+    }
 
     // 确认删除(除非使用 --force)
     if !force {
@@ -292,20 +892,471 @@ async fn cleanup_reports(days: i32, force: bool) -> Result<()> {
 
     // 执行删除
     println!("\n{} 正在删除报告...", "🔄".cyan());
-    let mut deleted_count = 0;
+    let stats = storage.reports().cleanup(&criteria, false).await?;
 
-    for report in to_delete {
-        match storage.reports().delete(report.id).await {
-            Ok(_) => {
-                deleted_count += 1;
-            }
-            Err(e) => {
-                eprintln!("{} 删除报告 {} 失败: {}", "✗".red(), report.id, e);
-            }
+    println!(
+        "\n{} 已删除 {} 个报告, {} 个步骤, 释放约 {}",
+        "✓".green(),
+        stats.report_count,
+        stats.step_count,
+        stats.estimated_size_human_readable()
+    );
+
+    Ok(())
+}
+
+/// 保留规则的保留条件描述
+fn describe_retention(policy: &RetentionPolicyRecord) -> String {
+    let mut conditions = Vec::new();
+    if let Some(n) = policy.keep_last_n {
+        conditions.push(format!("最近 {} 次", n));
+    }
+    if let Some(days) = policy.keep_days {
+        conditions.push(format!("{} 天内", days));
+    }
+    conditions.join(" 或 ")
+}
+
+/// 新增的保留规则 (`atp report retention add`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct AddedRetentionPolicy {
+    policy: RetentionPolicyRecord,
+}
+
+impl Render for AddedRetentionPolicy {
+    fn to_table(&self) -> String {
+        format!(
+            "{} 已添加保留规则 {}: {} 保留 {}",
+            "✓".green(),
+            self.policy.id,
+            self.policy.scenario_pattern.cyan(),
+            describe_retention(&self.policy)
+        )
+    }
+}
+
+async fn add_retention_policy(scenario: &str, keep_last: Option<i64>, keep_days: Option<i64>) -> Result<()> {
+    let format = output_format(None)?;
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let mut policy = RetentionPolicyRecord::new(scenario, keep_last, keep_days);
+    policy.id = storage.retention().create(&policy).await?;
+
+    print_rendered(&AddedRetentionPolicy { policy }, format)
+}
+
+/// 保留规则列表 (`atp report retention list`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct RetentionPolicyList {
+    policies: Vec<RetentionPolicyRecord>,
+}
+
+impl Render for RetentionPolicyList {
+    fn to_table(&self) -> String {
+        if self.policies.is_empty() {
+            return format!("{} 没有保留规则, report retention apply 不会删除任何报告", "ℹ".yellow());
         }
+
+        let mut lines = vec![
+            format!("{:<6} {:<30} {}", "ID".bold(), "场景匹配".bold(), "保留".bold()),
+            "-".repeat(60),
+        ];
+        for policy in &self.policies {
+            lines.push(format!(
+                "{:<6} {:<30} {}",
+                policy.id,
+                policy.scenario_pattern,
+                describe_retention(policy)
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+async fn list_retention_policies() -> Result<()> {
+    let format = output_format(None)?;
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let policies = storage.retention().list_all().await?;
+    print_rendered(&RetentionPolicyList { policies }, format)
+}
+
+/// 已删除的保留规则 (`atp report retention remove`)
+#[derive(Debug, Serialize)]
+struct RemovedRetentionPolicy {
+    id: i64,
+}
+
+impl Render for RemovedRetentionPolicy {
+    fn to_table(&self) -> String {
+        format!("{} 已删除保留规则 {}", "✓".green(), self.id)
     }
+}
 
-    println!("\n{} 已删除 {} 个报告", "✓".green(), deleted_count);
+async fn remove_retention_policy(id: i64) -> Result<()> {
+    let format = output_format(None)?;
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    storage.retention().delete(id).await?;
+
+    print_rendered(&RemovedRetentionPolicy { id }, format)
+}
+
+/// 按保留规则清理的结果 (`atp report retention apply`)
+#[derive(Debug, Serialize)]
+struct RetentionApplyView {
+    dry_run: bool,
+    stats: ReportCleanupStats,
+
+    /// 已删除 (演练时为将被删除) 的报告
+    reports: Vec<TestReportRecord>,
+}
+
+impl RetentionApplyView {
+    fn new(dry_run: bool, result: RetentionResult) -> Self {
+        Self {
+            dry_run,
+            stats: result.stats,
+            reports: result.reports,
+        }
+    }
+}
+
+impl Render for RetentionApplyView {
+    fn to_table(&self) -> String {
+        if self.reports.is_empty() {
+            return format!("\n{} 没有需要清理的报告", "ℹ".yellow());
+        }
+
+        if !self.dry_run {
+            return format!(
+                "\n{} 已删除 {} 个报告, {} 个步骤, 释放约 {}",
+                "✓".green(),
+                self.stats.report_count,
+                self.stats.step_count,
+                self.stats.estimated_size_human_readable()
+            );
+        }
+
+        let mut lines = retention_preview_lines(&self.stats);
+        lines.push(format!("\n{} 演练模式, 以下报告将被删除:\n", "ℹ".cyan()));
+        for report in &self.reports {
+            let local_time = report.start_time.with_timezone(&Local);
+            lines.push(format!(
+                "  {:<6} {:<25} {}",
+                report.id,
+                report.scenario_name,
+                local_time.format("%Y-%m-%d %H:%M:%S")
+            ));
+        }
+        lines.push(format!("\n{} 演练模式未删除任何报告", "ℹ".yellow()));
+        lines.join("\n")
+    }
+}
+
+/// 将被清理的报告统计
+fn retention_preview_lines(stats: &ReportCleanupStats) -> Vec<String> {
+    vec![
+        format!("\n{} 找到 {} 个不受保留规则保护的报告", "⚠".yellow(), stats.report_count),
+        format!("  总步骤数: {}", stats.step_count),
+        format!("  预计释放空间: {}", stats.estimated_size_human_readable()),
+    ]
+}
+
+/// 按保留规则清理报告
+///
+/// json / yaml 输出不能交互确认, 须同时指定 `--force` 或 `--dry-run`。
+async fn apply_retention(force: bool, dry_run: bool) -> Result<()> {
+    let format = output_format(None)?;
+    if !format.is_table() && !force && !dry_run {
+        anyhow::bail!("{} 输出需要同时指定 --force 或 --dry-run", format);
+    }
+    progress!(format, "{} 准备按保留规则清理报告...", "⏳".cyan());
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let preview = storage.apply_retention(true).await?;
+    if dry_run || preview.reports.is_empty() {
+        return print_rendered(&RetentionApplyView::new(dry_run, preview), format);
+    }
+
+    for line in retention_preview_lines(&preview.stats) {
+        progress!(format, "{}", line);
+    }
+
+    // 确认删除(除非使用 --force)
+    if !force {
+        println!("\n是否继续删除? (y/N): ");
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+        let input = input.trim().to_lowercase();
+
+        if input != "y" && input != "yes" {
+            println!("\n{} 已取消", "ℹ".yellow());
+            return Ok(());
+        }
+    }
+
+    progress!(format, "\n{} 正在删除报告...", "🔄".cyan());
+    let result = storage.apply_retention(false).await?;
+    info!(
+        reports = result.stats.report_count,
+        steps = result.stats.step_count,
+        "按保留规则清理报告"
+    );
+
+    print_rendered(&RetentionApplyView::new(false, result), format)
+}
+
+/// 解析时间范围, 如 `90s`、`30m`、`24h`、`7d`
+fn parse_since(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value.len().saturating_sub(1);
+    let (amount, unit) = value.split_at(split);
+    let amount: i64 = amount
+        .parse()
+        .map_err(|_| anyhow::anyhow!("无效的时间范围: {} (示例: 30m, 24h, 7d)", value))?;
+
+    match unit {
+        "s" => Ok(Duration::seconds(amount)),
+        "m" => Ok(Duration::minutes(amount)),
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        _ => anyhow::bail!("无效的时间范围: {} (示例: 30m, 24h, 7d)", value),
+    }
+}
+
+async fn show_verification_results(
+    vm: Option<String>,
+    since: &str,
+    limit: Option<i64>,
+    format: &str,
+) -> Result<()> {
+    let format = output_format(Some(format))?;
+    let since = parse_since(since)?;
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let filter = VerificationFilter {
+        vm_id: vm,
+        from: Some(Utc::now() - since),
+        limit,
+        ..Default::default()
+    };
+    let results = storage.verifications().list(&filter).await?;
+
+    if !format.is_table() {
+        return print_serialized(&results, format);
+    }
+
+    if results.is_empty() {
+        println!("{} 没有找到验证结果", "ℹ".yellow());
+        return Ok(());
+    }
+
+    println!(
+        "{:<20} {:<20} {:<12} {:<12} {:<12} {:<12}",
+        "时间".bold(),
+        "虚拟机".bold(),
+        "事件类型".bold(),
+        "结果".bold(),
+        "Agent延迟".bold(),
+        "服务端延迟".bold()
+    );
+    println!("{}", "-".repeat(95));
+
+    let latency = |ms: Option<i64>| ms.map(|ms| format!("{}ms", ms)).unwrap_or_else(|| "N/A".to_string());
+    for result in &results {
+        let outcome = match result.outcome.as_str() {
+            "verified" => "通过".green(),
+            "mismatched" => "不匹配".red(),
+            _ => "超时".yellow(),
+        };
+        println!(
+            "{:<20} {:<20} {:<12} {:<12} {:<12} {:<12}",
+            result.created_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+            result.vm_id,
+            result.event_type,
+            outcome,
+            latency(result.latency_ms),
+            latency(result.server_latency_ms)
+        );
+    }
+
+    let verified = results.iter().filter(|result| result.verified).count();
+    let latencies: Vec<i64> = results.iter().filter_map(|result| result.server_latency_ms).collect();
+    println!(
+        "\n{} 共 {} 条, 通过 {} 条 ({:.1}%)",
+        "✓".green(),
+        results.len(),
+        verified,
+        verified as f64 * 100.0 / results.len() as f64
+    );
+    if !latencies.is_empty() {
+        println!(
+            "  平均服务端延迟: {:.1}ms",
+            latencies.iter().sum::<i64>() as f64 / latencies.len() as f64
+        );
+    }
 
     Ok(())
 }
+
+/// 默认把时间范围分为约 60 个桶 (至少 1 秒)
+fn default_bucket(since: Duration) -> Duration {
+    Duration::seconds((since.num_seconds() / 60).max(1))
+}
+
+/// 降采样后的时序指标 (`atp report metrics`)
+#[derive(Debug, Serialize)]
+struct MetricSeries {
+    entity: MetricEntity,
+    metric: String,
+    bucket_secs: i64,
+    buckets: Vec<MetricBucket>,
+}
+
+impl Render for MetricSeries {
+    fn to_table(&self) -> String {
+        let title = format!("{} {}", self.entity.entity_type, self.entity.entity_id);
+        if self.buckets.is_empty() {
+            return format!("{} {} 在该时间段内没有 {} 指标", "ℹ".yellow(), title, self.metric);
+        }
+
+        let peak = self.buckets.iter().map(|bucket| bucket.max).fold(f64::MIN, f64::max);
+        let mut lines = vec![
+            format!(
+                "\n{} {} / {} (每 {} 秒一个桶)\n",
+                "📈".cyan(),
+                title.yellow(),
+                self.metric.green(),
+                self.bucket_secs
+            ),
+            format!("{:<20} {:>10} {:>10} {:>6}", "时间".bold(), "平均".bold(), "最大".bold(), "样本".bold()),
+            "-".repeat(80),
+        ];
+
+        for bucket in &self.buckets {
+            // 条形长度按最大值的比例
+            let width = if peak > 0.0 { (bucket.avg / peak * 30.0).round().max(0.0) as usize } else { 0 };
+            lines.push(format!(
+                "{:<20} {:>10.2} {:>10.2} {:>6}  {}",
+                bucket.bucket_start.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                bucket.avg,
+                bucket.max,
+                bucket.count,
+                "█".repeat(width).cyan()
+            ));
+        }
+
+        let count: i64 = self.buckets.iter().map(|bucket| bucket.count).sum();
+        let avg = self.buckets.iter().map(|bucket| bucket.avg * bucket.count as f64).sum::<f64>() / count as f64;
+        lines.push(format!("\n{} 共 {} 个样本, 平均 {:.2}, 最大 {:.2}", "✓".green(), count, avg, peak));
+        lines.join("\n")
+    }
+}
+
+async fn show_metrics(
+    entity: MetricEntity,
+    metric: &str,
+    since: &str,
+    bucket: Option<&str>,
+    format: &str,
+) -> Result<()> {
+    let format = output_format(Some(format))?;
+    let since = parse_since(since)?;
+    let bucket = match bucket {
+        Some(bucket) => parse_since(bucket)?,
+        None => default_bucket(since),
+    };
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let now = Utc::now();
+    let buckets = storage.metrics().aggregate(&entity, metric, (now - since)..now, bucket).await?;
+
+    print_rendered(
+        &MetricSeries {
+            entity,
+            metric: metric.to_string(),
+            bucket_secs: bucket.num_seconds(),
+            buckets,
+        },
+        format,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_since() {
+        assert_eq!(parse_since("24h").unwrap(), Duration::hours(24));
+        assert_eq!(parse_since("30m").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_since("7d").unwrap(), Duration::days(7));
+        assert!(parse_since("24").is_err());
+        assert!(parse_since("h").is_err());
+        assert!(parse_since("1w").is_err());
+    }
+
+    #[test]
+    fn test_default_bucket() {
+        assert_eq!(default_bucket(Duration::hours(1)), Duration::minutes(1));
+        assert_eq!(default_bucket(Duration::days(1)), Duration::minutes(24));
+        assert_eq!(default_bucket(Duration::seconds(30)), Duration::seconds(1));
+    }
+
+    #[test]
+    fn test_render_retention_apply() {
+        let stats = ReportCleanupStats {
+            report_count: 1,
+            step_count: 3,
+            estimated_bytes: 2048,
+        };
+        let report = TestReportRecord {
+            id: 7,
+            scenario_name: "login".to_string(),
+            description: None,
+            start_time: Utc::now(),
+            end_time: None,
+            duration_ms: None,
+            total_steps: 3,
+            success_count: 3,
+            failed_count: 0,
+            skipped_count: 0,
+            passed: true,
+            tags: None,
+            created_at: Utc::now(),
+            scenario_version: None,
+        };
+        let result = || RetentionResult {
+            reports: vec![report.clone()],
+            stats: stats.clone(),
+        };
+
+        let preview = RetentionApplyView::new(true, result()).to_table();
+        assert!(preview.contains("找到 1 个不受保留规则保护的报告"), "{}", preview);
+        assert!(preview.contains("login") && preview.contains("演练模式未删除"), "{}", preview);
+
+        let applied = RetentionApplyView::new(false, result());
+        assert!(applied.to_table().contains("已删除 1 个报告, 3 个步骤, 释放约 2.00 KB"));
+        let value: serde_json::Value = serde_json::from_str(&applied.to_json().unwrap()).unwrap();
+        assert_eq!(value["dry_run"], false);
+        assert_eq!(value["stats"]["step_count"], 3);
+        assert_eq!(value["reports"][0]["scenario_name"], "login");
+
+        assert!(RetentionApplyView::new(true, RetentionResult::default()).to_table().contains("没有需要清理的报告"));
+    }
+}
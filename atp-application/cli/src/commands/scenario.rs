@@ -7,22 +7,218 @@ use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
-use atp_executor::{Scenario, ScenarioRunner};
-use atp_transport::{TransportManager, TransportConfig, HostInfo};
-use atp_protocol::ProtocolRegistry;
-use atp_storage::{StorageManager, Storage};
+use atp_executor::authoring::step_line;
+use atp_executor::scope::{VM_INDEX_VARIABLE, VM_NAME_VARIABLE};
+use atp_executor::step_groups;
+use atp_executor::{
+    ArtifactLayout, EnvironmentGuardMode, ExecutionObserver, ExecutionReport, FanOutReport, FanOutRunner,
+    IssueSeverity, JsonLinesObserver, LibvirtVmMetrics, PlannedStep, Scenario, ScenarioRunner, ScenarioTemplate,
+    SharedVariables, StepFilter, StepPhase, TestConfig, TracingObserver, ValidationIssue, VariableScope, VdiConfig,
+};
+use atp_transport::{TransportManager, TransportConfig};
+use atp_protocol::{qga::QgaMetrics, ProtocolRegistry};
+use atp_storage::{
+    CollectorConfig, MetricsCollector, MetricsSource, ScenarioFilter, ScenarioSummary, StorageManager, Storage,
+};
+use chrono::Local;
+use serde::Serialize;
 
+use crate::commands::common::{output_format, print_rendered, progress, OutputFormat, Render};
+use crate::commands::vdi::{create_vdi_client, load_config};
 use crate::config::CliConfig;
 
-pub async fn handle(action: crate::ScenarioAction) -> Result<()> {
+/// 数据库路径
+const DB_PATH: &str = "~/.config/atp/data.db";
+
+/// 被 Ctrl-C 强制中断时的退出码 (128 + SIGINT)
+const EXIT_INTERRUPTED: i32 = 130;
+
+pub async fn handle(action: crate::ScenarioAction, profile: Option<&str>) -> Result<()> {
     match action {
-        crate::ScenarioAction::Run { file } => run_scenario(&file).await,
-        crate::ScenarioAction::List => list_scenarios().await,
+        crate::ScenarioAction::Run {
+            file,
+            name,
+            dry_run,
+            tags,
+            skip_tags,
+            progress,
+            progress_file,
+            step_metrics_ms,
+            fan_out,
+        } => {
+            let filter = StepFilter::new()
+                .with_include_tags(tags)
+                .with_exclude_tags(skip_tags);
+            let observer = create_observer(progress.as_deref(), progress_file.as_deref())?;
+            let source = match (file, name) {
+                (Some(file), _) => ScenarioSource::File(file),
+                (None, Some(name)) => ScenarioSource::parse_stored(&name)?,
+                (None, None) => anyhow::bail!("请指定场景文件或 --name"),
+            };
+            let step_metrics = step_metrics_ms.map(Duration::from_millis);
+            let crate::ScenarioFanOutArgs { vars, config, vms, artifact_dir, concurrency } = *fan_out;
+            let test_config = config.as_deref().map(|path| load_config(path, profile)).transpose()?;
+            let variables = load_variables(test_config.as_ref(), &vars)?;
+            let vdi = test_config.and_then(|config| config.vdi);
+            let fan_out = (!vms.is_empty()).then_some(FanOutArgs {
+                vms,
+                artifact_dir,
+                concurrency: concurrency as usize,
+            });
+            run_scenario(&source, dry_run, &filter, observer, step_metrics, variables, vdi, fan_out).await
+        }
+        crate::ScenarioAction::List { files } => {
+            if files {
+                list_scenario_files().await
+            } else {
+                list_scenarios().await
+            }
+        }
+        crate::ScenarioAction::Save { file } => save_scenario(&file).await,
+        crate::ScenarioAction::Diff { name, from, to } => diff_scenario(&name, from, to).await,
+        crate::ScenarioAction::New { file, template, force } => new_scenario(&file, &template, force),
+        crate::ScenarioAction::Validate { file } => validate_scenario_file(&file).await,
+        crate::ScenarioAction::Explain { file } => explain_scenario(&file),
     }
 }
 
-async fn run_scenario(file: &str) -> Result<()> {
-    let path = Path::new(file);
+/// 场景来源
+enum ScenarioSource {
+    /// 场景文件
+    File(String),
+    /// 数据库中保存的场景, 未指定版本时使用最新版本
+    Stored { name: String, version: Option<i32> },
+}
+
+impl ScenarioSource {
+    /// 解析 `名称[@版本]`
+    fn parse_stored(spec: &str) -> Result<Self> {
+        let (name, version) = match spec.rsplit_once('@') {
+            Some((name, version)) => {
+                let version = version
+                    .parse()
+                    .with_context(|| format!("无效的场景版本: {}", version))?;
+                (name, Some(version))
+            }
+            None => (spec, None),
+        };
+
+        Ok(Self::Stored {
+            name: name.to_string(),
+            version,
+        })
+    }
+
+    /// 加载场景, 返回场景及其版本 (场景文件没有版本)
+    async fn load(&self) -> Result<(Scenario, Option<i32>)> {
+        match self {
+            Self::File(file) => Ok((load_scenario_file(Path::new(file))?, None)),
+            Self::Stored { name, version } => {
+                let storage_manager = StorageManager::new(DB_PATH).await
+                    .context("初始化数据库失败")?;
+                let storage = Storage::from_manager(&storage_manager);
+
+                let (definition, version) = match version {
+                    Some(version) => {
+                        let record = storage.scenarios().get_version(name, *version).await?
+                            .with_context(|| format!("场景 {} 没有版本 {}", name, version))?;
+                        (record.definition, record.version)
+                    }
+                    None => {
+                        let record = storage.scenarios().get_latest(name).await?
+                            .with_context(|| format!("未找到已保存的场景: {}", name))?;
+                        (record.definition, record.version)
+                    }
+                };
+
+                let scenario = Scenario::from_yaml_str(&definition)
+                    .with_context(|| format!("解析场景 {}@{} 失败", name, version))?;
+                Ok((scenario, Some(version)))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ScenarioSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File(file) => write!(f, "{}", file),
+            Self::Stored { name, version: Some(version) } => write!(f, "{}@{}", name, version),
+            Self::Stored { name, version: None } => write!(f, "{}", name),
+        }
+    }
+}
+
+/// 按扩展名加载场景文件
+fn load_scenario_file(path: &Path) -> Result<Scenario> {
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("yaml") | Some("yml") => Ok(Scenario::from_yaml_file(path)?),
+        Some("json") => Ok(Scenario::from_json_file(path)?),
+        _ => anyhow::bail!("不支持的场景文件格式，仅支持 .yaml/.yml 或 .json"),
+    }
+}
+
+/// 根据 --progress 参数创建进度观察者
+fn create_observer(
+    progress: Option<&str>,
+    progress_file: Option<&str>,
+) -> Result<Option<Arc<dyn ExecutionObserver>>> {
+    let observer: Arc<dyn ExecutionObserver> = match progress {
+        None => return Ok(None),
+        Some("jsonl") => match progress_file {
+            Some(path) => {
+                let file = std::fs::File::create(path)
+                    .with_context(|| format!("无法创建进度文件: {}", path))?;
+                Arc::new(JsonLinesObserver::new(std::io::BufWriter::new(file)))
+            }
+            None => Arc::new(JsonLinesObserver::new(std::io::stderr())),
+        },
+        Some("log") => Arc::new(TracingObserver),
+        Some(other) => anyhow::bail!("不支持的进度格式: {} (可选: jsonl, log)", other),
+    };
+
+    Ok(Some(observer))
+}
+
+/// 对多台虚拟机并行执行的参数
+struct FanOutArgs {
+    /// 目标虚拟机
+    vms: Vec<String>,
+
+    /// 工件根目录
+    artifact_dir: String,
+
+    /// 同时执行的数量
+    concurrency: usize,
+}
+
+/// 合并配置文件 [variables] 与 `--var` (后者优先) 为共享变量层
+fn load_variables(config: Option<&TestConfig>, vars: &[String]) -> Result<SharedVariables> {
+    let file_vars = config.map(|config| config.variables.clone()).unwrap_or_default();
+    let cli_vars = vars
+        .iter()
+        .map(|arg| SharedVariables::parse_cli_var(arg))
+        .collect::<atp_executor::Result<_>>()?;
+
+    Ok(SharedVariables::from_layers(file_vars, cli_vars))
+}
+
+/// 执行场景
+///
+/// `vdi` 来自 `--config` 的 `[vdi]` 配置段; 给出时先登录 VDI 平台,
+/// 供环境检查 (`environment_guard`) 与 VDI 步骤使用。
+#[allow(clippy::too_many_arguments)]
+async fn run_scenario(
+    source: &ScenarioSource,
+    dry_run: bool,
+    filter: &StepFilter,
+    observer: Option<Arc<dyn ExecutionObserver>>,
+    step_metrics: Option<Duration>,
+    variables: SharedVariables,
+    vdi: Option<VdiConfig>,
+    fan_out: Option<FanOutArgs>,
+) -> Result<()> {
+    let format = output_format(None)?;
 
     // 加载场景
     let spinner = ProgressBar::new_spinner();
@@ -31,30 +227,42 @@ async fn run_scenario(file: &str) -> Result<()> {
             .template("{spinner:.green} {msg}")
             .unwrap()
     );
-    spinner.set_message(format!("加载场景: {}", file));
+    spinner.set_message(format!("加载场景: {}", source));
     spinner.enable_steady_tick(Duration::from_millis(100));
 
-    let scenario = if path.extension().and_then(|s| s.to_str()) == Some("yaml") ||
-                      path.extension().and_then(|s| s.to_str()) == Some("yml") {
-        Scenario::from_yaml_file(path)?
-    } else if path.extension().and_then(|s| s.to_str()) == Some("json") {
-        Scenario::from_json_file(path)?
-    } else {
-        anyhow::bail!("不支持的场景文件格式，仅支持 .yaml/.yml 或 .json");
-    };
+    let (scenario, scenario_version) = source.load().await?;
 
     spinner.finish_with_message(format!("{} 场景加载成功: {}", "✓".green().bold(), scenario.name.cyan()));
 
     // 显示场景信息
-    println!();
+    progress!(format);
+    if let Some(version) = scenario_version {
+        progress!(format, "版本: {}", version.to_string().cyan());
+    }
     if let Some(desc) = &scenario.description {
-        println!("描述: {}", desc.bright_black());
+        progress!(format, "描述: {}", desc.bright_black());
+    }
+    progress!(format, "步骤数: {}", scenario.steps.len().to_string().yellow());
+    if let Some(fan_out) = &fan_out {
+        progress!(format, "目标虚拟机: {}", fan_out.vms.join(", ").cyan());
+    }
+    if !variables.is_empty() {
+        progress!(format, "变量数: {}", variables.len().to_string().cyan());
     }
-    println!("步骤数: {}", scenario.steps.len().to_string().yellow());
     if !scenario.tags.is_empty() {
-        println!("标签: {}", scenario.tags.join(", ").bright_black());
+        progress!(format, "标签: {}", scenario.tags.join(", ").bright_black());
+    }
+    if !filter.include_tags.is_empty() {
+        progress!(format, "只执行标签: {}", filter.include_tags.join(", ").cyan());
+    }
+    if !filter.exclude_tags.is_empty() {
+        progress!(format, "跳过标签: {}", filter.exclude_tags.join(", ").cyan());
+    }
+    progress!(format);
+
+    if dry_run {
+        return validate_scenario(&scenario, &variables, fan_out.is_some(), format).await;
     }
-    println!();
 
     // 初始化传输管理器和协议注册表
     let spinner = ProgressBar::new_spinner();
@@ -69,39 +277,108 @@ async fn run_scenario(file: &str) -> Result<()> {
     // 加载配置以获取主机信息
     let config = CliConfig::load()?;
 
-    // 创建传输管理器
-    let transport_config = TransportConfig::default();
-    let mut transport_manager = TransportManager::new(transport_config);
-
-    // 添加配置的主机（这里简化处理，实际场景中应该根据需要选择主机）
-    // 暂时添加所有配置的主机
-    for (id, host_config) in config.hosts.iter() {
-        let uri = host_config.uri.clone()
-            .unwrap_or_else(|| format!("qemu+ssh://{}:22/system", host_config.host));
-
-        let host_info = HostInfo::new(id, &host_config.host).with_uri(&uri);
-        transport_manager.add_host(host_info).await
-            .with_context(|| format!("添加主机 {} 失败", id))?;
-    }
+    // 创建传输管理器并注册配置的所有主机
+    let transport_manager = TransportManager::from_config(config.transport_config())
+        .await
+        .context("初始化传输管理器失败")?;
 
     let transport_manager = Arc::new(transport_manager);
     let protocol_registry = Arc::new(ProtocolRegistry::new());
 
     spinner.finish_with_message(format!("{} 传输管理器初始化完成", "✓".green().bold()));
 
+    // 登录 VDI 平台 (可选), 所有执行器共用同一个客户端
+    let vdi_client = match &vdi {
+        Some(vdi_config) => {
+            progress!(format, "登录 VDI 平台: {}", vdi_config.base_url.cyan());
+            Some(Arc::new(create_vdi_client(vdi_config).await?))
+        }
+        None => None,
+    };
+
     // 初始化数据库存储
-    let storage_manager = StorageManager::new("~/.config/atp/data.db").await
+    let storage_manager = StorageManager::new(DB_PATH).await
         .context("初始化数据库失败")?;
     let storage = Arc::new(Storage::from_manager(&storage_manager));
 
+    // 所有执行器的 QGA 命令统计累计到同一个指标源
+    let qga_metrics = Arc::new(QgaMetrics::default());
+
     // 创建场景执行器 (with数据库支持)
-    let mut runner = ScenarioRunner::new(
-        Arc::clone(&transport_manager),
-        Arc::clone(&protocol_registry),
-    ).with_storage(Arc::clone(&storage));
+    let build_runner = || {
+        let mut runner = ScenarioRunner::new(
+            Arc::clone(&transport_manager),
+            Arc::clone(&protocol_registry),
+        )
+        .with_storage(Arc::clone(&storage))
+        .with_qga_metrics(Arc::clone(&qga_metrics));
+
+        if let Some(client) = &vdi_client {
+            runner = runner.with_vdi_client(Arc::clone(client));
+        }
+        if let Some(version) = scenario_version {
+            runner = runner.with_scenario_version(version);
+        }
+        if let Some(observer) = &observer {
+            runner = runner.with_observer(Arc::clone(observer));
+        }
+        if let Some(interval) = step_metrics {
+            runner = runner.with_metrics_sampling(interval);
+        }
+        runner
+    };
+
+    // 挂载指标采集器 (可选)
+    let metrics_collector = match config.metrics_interval_secs {
+        Some(secs) if secs > 0 => {
+            let collector = Arc::new(MetricsCollector::new(
+                storage.metrics().clone(),
+                CollectorConfig {
+                    interval: Duration::from_secs(secs),
+                    ..Default::default()
+                },
+            ));
+            collector.add_source(Arc::clone(&transport_manager) as Arc<dyn MetricsSource>).await;
+            collector.add_source(Arc::clone(&qga_metrics) as Arc<dyn MetricsSource>).await;
+
+            // 场景指定了目标虚拟机时, 同时通过 libvirt 采样其资源使用情况
+            let target_host = scenario.target_host.as_ref().or(config.default_host.as_ref());
+            let domains = match &fan_out {
+                Some(fan_out) => fan_out.vms.iter().collect(),
+                None => scenario.target_domain.iter().collect::<Vec<_>>(),
+            };
+            if let (Some(host_id), false) = (target_host, domains.is_empty()) {
+                let vm_metrics = domains
+                    .into_iter()
+                    .fold(LibvirtVmMetrics::new(Arc::clone(&transport_manager)), |metrics, domain| {
+                        metrics.with_target(host_id, domain)
+                    });
+                collector.add_source(Arc::new(vm_metrics)).await;
+            }
+            collector.start().await;
+            Some(collector)
+        }
+        _ => None,
+    };
+
+    if let Some(fan_out) = &fan_out {
+        let result = run_fan_out(&scenario, filter, fan_out, variables, &build_runner, format).await;
+        if let Some(collector) = &metrics_collector {
+            if let Err(e) = collector.stop().await {
+                eprintln!("{} 写入指标失败: {}", "⚠".yellow(), e);
+            }
+        }
+        return result;
+    }
+
+    let mut runner = build_runner();
+    if !variables.is_empty() {
+        let target = scenario.target_domain.as_deref().unwrap_or_default();
+        runner = runner.with_variables(VariableScope::new(target, variables));
+    }
 
     // 执行场景
-    println!("\n{}\n", "开始执行场景...".bold());
+    progress!(format, "\n{}\n", "开始执行场景...".bold());
 
     let progress = ProgressBar::new(scenario.steps.len() as u64);
     progress.set_style(
@@ -111,95 +388,848 @@ async fn run_scenario(file: &str) -> Result<()> {
             .progress_chars("=>-")
     );
 
-    let report = runner.run(&scenario).await?;
+    // Ctrl-C 与场景超时走相同的取消路径: 跳过剩余步骤, 仍执行清理。
+    // 清理期间再次按下 Ctrl-C 时放弃清理, 以 130 (128 + SIGINT) 退出
+    let cancel_token = runner.cancellation_token();
+    let ctrl_c = tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\n{} 收到中断信号, 正在取消场景并执行清理... (再次按 Ctrl-C 强制退出)", "⚠".yellow());
+            cancel_token.cancel();
+        }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\n{} 再次收到中断信号, 跳过清理并退出", "✗".red());
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+    });
+
+    let report = runner.run_filtered(&scenario, filter).await;
+    ctrl_c.abort();
+
+    if let Some(collector) = &metrics_collector {
+        if let Err(e) = collector.stop().await {
+            eprintln!("{} 写入指标失败: {}", "⚠".yellow(), e);
+        }
+    }
+
+    let report = report?;
 
     progress.finish_with_message("完成".green().to_string());
 
-    // 显示执行报告
-    println!("\n{}", "=".repeat(60));
-    println!("{}", "执行报告".bold());
-    println!("{}", "=".repeat(60));
-    println!();
-
-    println!("场景名称: {}", report.scenario_name.cyan().bold());
-    if let Some(desc) = &report.description {
-        println!("场景描述: {}", desc.bright_black());
-    }
-    println!("执行时间: {} ms", report.duration_ms.to_string().yellow());
-    println!();
-
-    println!("步骤统计:");
-    println!("  总步骤: {}", report.steps_executed.to_string().bright_blue());
-    println!("  成功:   {}", report.passed_count.to_string().green());
-    println!("  失败:   {}", report.failed_count.to_string().red());
-    println!();
-
-    // 显示步骤详情
-    if !report.steps.is_empty() {
-        println!("步骤详情:");
-        println!();
-
-        for step in &report.steps {
-            let status_icon = match step.status {
-                atp_executor::StepStatus::Success => "✓".green(),
-                atp_executor::StepStatus::Failed => "✗".red(),
-                atp_executor::StepStatus::Skipped => "⊘".yellow(),
-            };
+    let result = RunResult { report };
+    print_rendered(&result, format)?;
 
-            println!(
-                "{} 步骤 {}: {}",
-                status_icon.bold(),
-                (step.step_index + 1).to_string().bright_black(),
-                step.description
-            );
+    if !result.passed() {
+        anyhow::bail!("场景执行失败");
+    }
+
+    Ok(())
+}
+
+/// 单目标执行结果 (`atp scenario run`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct RunResult {
+    report: ExecutionReport,
+}
+
+impl RunResult {
+    fn passed(&self) -> bool {
+        self.report.failed_count == 0 && self.report.passed
+    }
+}
+
+impl Render for RunResult {
+    fn to_table(&self) -> String {
+        let report = &self.report;
+        let mut lines = vec![
+            format!("\n{}", "=".repeat(60)),
+            "执行报告".bold().to_string(),
+            "=".repeat(60),
+            String::new(),
+            format!("场景名称: {}", report.scenario_name.cyan().bold()),
+        ];
+        if let Some(desc) = &report.description {
+            lines.push(format!("场景描述: {}", desc.bright_black()));
+        }
+        lines.push(format!("执行时间: {} ms", report.duration_ms.to_string().yellow()));
+        if report.timed_out {
+            lines.push("场景超过最大执行时间, 剩余步骤已跳过".red().to_string());
+        } else if report.cancelled {
+            lines.push("场景已被用户取消, 剩余步骤已跳过".yellow().to_string());
+        }
+        lines.push(String::new());
+
+        lines.push("步骤统计:".to_string());
+        lines.push(format!("  总步骤: {}", report.steps_executed.to_string().bright_blue()));
+        lines.push(format!("  成功:   {}", report.passed_count.to_string().green()));
+        lines.push(format!("  失败:   {}", report.failed_count.to_string().red()));
+        lines.push(format!("  跳过:   {}", report.skipped_count.to_string().yellow()));
+        lines.push(String::new());
+
+        // 步骤详情
+        if !report.steps.is_empty() {
+            lines.push("步骤详情:".to_string());
+            lines.push(String::new());
+
+            for step in &report.steps {
+                let status_icon = match step.status {
+                    atp_executor::StepStatus::Success => "✓".green(),
+                    atp_executor::StepStatus::Failed => "✗".red(),
+                    atp_executor::StepStatus::Skipped => "⊘".yellow(),
+                };
 
-            if let Some(output) = &step.output {
-                println!("   输出: {}", output.bright_black());
+                lines.push(format!(
+                    "{} 步骤 {}: {}",
+                    status_icon.bold(),
+                    (step.step_index + 1).to_string().bright_black(),
+                    step.description
+                ));
+                if let Some(output) = &step.output {
+                    lines.push(format!("   输出: {}", output.bright_black()));
+                }
+                if let Some(error) = &step.error {
+                    lines.push(format!("   错误: {}", error.red()));
+                }
+                lines.push(format!("   耗时: {} ms", step.duration_ms.to_string().bright_black()));
+                lines.push(String::new());
             }
+        }
 
-            if let Some(error) = &step.error {
-                println!("   错误: {}", error.red());
+        if !report.orphan_resources.is_empty() {
+            lines.push("环境检查发现未清理的新增资源:".yellow().to_string());
+            for orphan in &report.orphan_resources {
+                lines.push(format!(
+                    "  - {} {} ({})",
+                    orphan.kind,
+                    orphan.id,
+                    orphan.name.as_deref().unwrap_or("-")
+                ));
             }
+            lines.push("  可使用 atp vdi cleanup-orphans --from-report <id> 清理".to_string());
+            lines.push(String::new());
+        }
+
+        // 总结
+        lines.push("=".repeat(60));
+        lines.push(if self.passed() {
+            format!("{} 场景执行成功", "✓".green().bold())
+        } else {
+            format!("{} 场景执行失败", "✗".red().bold())
+        });
+        lines.push("=".repeat(60));
+        lines.join("\n")
+    }
+}
+
+/// 校验场景 (不连接虚拟机)
+///
+/// 共享变量与多目标执行时预置的 `vm_name`/`vm_index` 视为已定义。
+async fn validate_scenario(
+    scenario: &Scenario,
+    variables: &SharedVariables,
+    fan_out: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let mut scope = VariableScope::new(scenario.target_domain.as_deref().unwrap_or_default(), variables.clone());
+    if fan_out {
+        scope.set(VM_NAME_VARIABLE, "");
+        scope.set(VM_INDEX_VARIABLE, "");
+    }
+    let runner = offline_runner().with_variables(scope);
+    let issues = runner.validate(scenario).await;
+
+    print_validation(&ValidationResult::new(None, issues, None), format)
+}
 
-            println!("   耗时: {} ms", step.duration_ms.to_string().bright_black());
-            println!();
+/// 对多台虚拟机并行执行场景, 输出各虚拟机结果并把聚合报告写入工件根目录
+async fn run_fan_out(
+    scenario: &Scenario,
+    filter: &StepFilter,
+    args: &FanOutArgs,
+    variables: SharedVariables,
+    build_runner: &dyn Fn() -> ScenarioRunner,
+    format: OutputFormat,
+) -> Result<()> {
+    let fan_out = FanOutRunner::new(ArtifactLayout::new(&args.artifact_dir))
+        .with_shared_variables(variables)
+        .with_concurrency(args.concurrency);
+
+    progress!(
+        format,
+        "\n{}\n",
+        format!("开始对 {} 台虚拟机执行场景 (并发 {})...", args.vms.len(), args.concurrency).bold()
+    );
+
+    // 与单目标执行相同: 第一次 Ctrl-C 取消所有子运行并执行清理, 第二次直接退出
+    let cancel_token = fan_out.cancellation_token();
+    let ctrl_c = tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\n{} 收到中断信号, 正在取消所有子运行并执行清理... (再次按 Ctrl-C 强制退出)", "⚠".yellow());
+            cancel_token.cancel();
         }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("\n{} 再次收到中断信号, 跳过清理并退出", "✗".red());
+            std::process::exit(EXIT_INTERRUPTED);
+        }
+    });
+
+    let report = fan_out.run(scenario, &args.vms, filter, |_| build_runner()).await;
+    ctrl_c.abort();
+    let report = report?;
+
+    let report_path = Path::new(&report.artifact_root).join("fan_out_report.json");
+    std::fs::write(&report_path, report.to_json()?)
+        .with_context(|| format!("写入聚合报告失败: {}", report_path.display()))?;
+
+    let result = FanOutResult { report, report_file: report_path.display().to_string() };
+    print_rendered(&result, format)?;
+
+    let report = &result.report;
+    if !report.passed {
+        anyhow::bail!("{}/{} 台虚拟机执行失败", report.targets.len() - report.passed_count(), report.targets.len());
     }
+    Ok(())
+}
 
-    // 总结
-    println!("{}", "=".repeat(60));
-    let status = if report.failed_count == 0 {
-        format!("{} 场景执行成功", "✓".green().bold())
-    } else {
-        format!("{} 场景执行失败", "✗".red().bold())
-    };
-    println!("{}", status);
-    println!("{}", "=".repeat(60));
+/// 多目标执行结果 (`atp scenario run --vm`)
+#[derive(Debug, Serialize)]
+struct FanOutResult {
+    #[serde(flatten)]
+    report: FanOutReport,
 
-    if report.failed_count > 0 {
-        anyhow::bail!("场景执行失败");
+    /// 写入工件根目录的聚合报告
+    report_file: String,
+}
+
+impl Render for FanOutResult {
+    fn to_table(&self) -> String {
+        let report = &self.report;
+        let mut lines = vec![
+            format!("\n{}", "=".repeat(60)),
+            "执行报告".bold().to_string(),
+            "=".repeat(60),
+            String::new(),
+            format!("场景名称: {}", report.scenario_name.cyan().bold()),
+            format!("执行时间: {} ms", report.duration_ms.to_string().yellow()),
+            format!("通过: {}/{}", report.passed_count().to_string().green(), report.targets.len()),
+            String::new(),
+        ];
+
+        for target in &report.targets {
+            let status_icon = if target.passed { "✓".green() } else { "✗".red() };
+            let summary = match (&target.report, &target.error) {
+                (Some(run), _) => format!(
+                    "{} 成功 / {} 失败 / {} 跳过, {} ms",
+                    run.passed_count, run.failed_count, run.skipped_count, run.duration_ms
+                ),
+                (None, Some(error)) => error.red().to_string(),
+                (None, None) => String::new(),
+            };
+            lines.push(format!("{} {}: {}", status_icon.bold(), target.vm_name.cyan(), summary));
+            lines.push(format!("   工件: {}", target.artifact_dir.bright_black()));
+        }
+
+        lines.push(String::new());
+        lines.push(format!("聚合报告: {}", self.report_file.cyan()));
+        lines.join("\n")
+    }
+}
+
+/// 创建不连接任何基础设施的执行器 (用于校验与执行计划)
+fn offline_runner() -> ScenarioRunner {
+    let transport_manager = Arc::new(TransportManager::new(TransportConfig::default()));
+    let protocol_registry = Arc::new(ProtocolRegistry::new());
+    ScenarioRunner::new(transport_manager, protocol_registry)
+}
+
+/// 场景校验结果 (`atp scenario validate` / `atp scenario run --dry-run`)
+#[derive(Debug, Serialize)]
+struct ValidationResult<'a> {
+    /// 场景名称与步骤总数 (`run --dry-run` 已在进度信息中输出, 此处省略)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scenario: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    steps: Option<usize>,
+
+    /// 场景文件无法解析时的错误
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parse_error: Option<ParseError>,
+
+    passed: bool,
+    issues: Vec<ValidationIssue>,
+
+    /// 场景文件原文 (YAML), 表格输出时用于显示出错的行
+    #[serde(skip)]
+    content: Option<&'a str>,
+}
+
+/// 场景文件的解析错误
+#[derive(Debug, Serialize)]
+struct ParseError {
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+}
+
+impl<'a> ValidationResult<'a> {
+    /// 静态校验的结果; 提供 YAML 原文时, 步骤级问题附带该步骤在文件中的行号与内容
+    fn new(scenario: Option<&Scenario>, issues: Vec<ValidationIssue>, yaml: Option<&'a str>) -> Self {
+        Self {
+            scenario: scenario.map(|s| s.name.clone()),
+            steps: scenario.map(|s| s.setup.len() + s.steps.len() + s.teardown.len()),
+            parse_error: None,
+            passed: !issues.iter().any(|i| i.is_error()),
+            issues,
+            content: yaml,
+        }
+    }
+
+    /// 场景文件解析失败
+    fn parse_failed(message: String, content: &'a str) -> Self {
+        let line = error_line(&message);
+        Self {
+            scenario: None,
+            steps: None,
+            parse_error: Some(ParseError { message, line }),
+            passed: false,
+            issues: Vec::new(),
+            content: Some(content),
+        }
     }
+}
+
+impl Render for ValidationResult<'_> {
+    fn to_table(&self) -> String {
+        let content = self.content.unwrap_or_default();
+
+        if let Some(error) = &self.parse_error {
+            let mut lines = vec![format!("{} 场景解析失败: {}", "✗".red().bold(), error.message)];
+            lines.extend(error.line.and_then(|line| line_context(content, line)));
+            return lines.join("\n");
+        }
+
+        let mut lines = Vec::new();
+        if let (Some(name), Some(steps)) = (&self.scenario, self.steps) {
+            lines.push(format!("场景: {} ({} 个步骤)\n", name.cyan(), steps));
+        }
+
+        if self.issues.is_empty() {
+            lines.push(format!("{} 场景校验通过", "✓".green().bold()));
+            return lines.join("\n");
+        }
+
+        lines.push(format!("{}\n", "校验结果:".bold()));
+
+        for issue in &self.issues {
+            let severity = match issue.severity {
+                IssueSeverity::Error => "错误".red().bold(),
+                IssueSeverity::Warning => "警告".yellow().bold(),
+            };
+            let location = match issue.step_index {
+                Some(index) => format!("步骤 {}", index + 1),
+                None => "场景".to_string(),
+            };
 
+            lines.push(format!("  [{}] {}: {}", severity, location.bright_black(), issue.message));
+
+            let line = self.content.zip(issue.step_index).and_then(|(yaml, index)| step_line(yaml, index));
+            lines.extend(line.and_then(|line| line_context(content, line)));
+        }
+
+        let error_count = self.issues.iter().filter(|i| i.is_error()).count();
+        lines.push(String::new());
+        lines.push(format!(
+            "共 {} 个问题 ({} 个错误, {} 个警告)",
+            self.issues.len(),
+            error_count.to_string().red(),
+            (self.issues.len() - error_count).to_string().yellow()
+        ));
+        lines.join("\n")
+    }
+}
+
+/// 输出校验结果, 有错误时返回错误
+fn print_validation(result: &ValidationResult<'_>, format: OutputFormat) -> Result<()> {
+    print_rendered(result, format)?;
+
+    if !result.passed {
+        anyhow::bail!("场景校验失败");
+    }
     Ok(())
 }
 
+/// 文件中的一行 (行号从 1 开始), 带行号前缀
+fn line_context(content: &str, line: usize) -> Option<String> {
+    let text = content.lines().nth(line.saturating_sub(1))?;
+    Some(format!("      {} {}", format!("{:>4} |", line).bright_black(), text))
+}
+
+/// 从 serde_yaml 错误信息中提取行号 (`at line N column M`)
+fn error_line(message: &str) -> Option<usize> {
+    let rest = &message[message.find("line ")? + "line ".len()..];
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+/// 从模板创建场景文件
+fn new_scenario(file: &str, template: &str, force: bool) -> Result<()> {
+    let format = output_format(None)?;
+    let template: ScenarioTemplate = template.parse()?;
+    let path = Path::new(file);
+
+    if path.exists() && !force {
+        anyhow::bail!("文件已存在: {} (使用 --force 覆盖)", file);
+    }
+    match path.extension().and_then(|s| s.to_str()) {
+        Some("yaml") | Some("yml") => {}
+        _ => anyhow::bail!("模板为 YAML 格式, 文件扩展名应为 .yaml 或 .yml"),
+    }
+
+    std::fs::write(path, template.content()).with_context(|| format!("写入场景文件失败: {}", file))?;
+
+    let created = CreatedScenario { file: file.to_string(), template: template.name() };
+    print_rendered(&created, format)
+}
+
+/// 从模板创建的场景文件 (`atp scenario new`)
+#[derive(Debug, Serialize)]
+struct CreatedScenario {
+    file: String,
+    template: &'static str,
+}
+
+impl Render for CreatedScenario {
+    fn to_table(&self) -> String {
+        [
+            format!("{} 已从模板 {} 创建场景: {}", "✓".green().bold(), self.template.cyan(), self.file),
+            "\n按注释修改后可以通过以下命令检查:".to_string(),
+            format!("  atp scenario validate {}", self.file),
+            format!("  atp scenario explain {}", self.file),
+        ]
+        .join("\n")
+    }
+}
+
+/// 校验场景文件: 解析失败时指出所在行, 解析成功后执行静态校验
+async fn validate_scenario_file(file: &str) -> Result<()> {
+    let format = output_format(None)?;
+    let path = Path::new(file);
+    let content = std::fs::read_to_string(path).with_context(|| format!("读取场景文件失败: {}", file))?;
+    let is_yaml = !matches!(path.extension().and_then(|s| s.to_str()), Some("json"));
+
+    let result = match load_scenario_file(path) {
+        Ok(scenario) => {
+            let issues = offline_runner().validate_definition(&scenario).await;
+            ValidationResult::new(Some(&scenario), issues, is_yaml.then_some(content.as_str()))
+        }
+        Err(e) => ValidationResult::parse_failed(format!("{:#}", e), &content),
+    };
+
+    print_validation(&result, format)
+}
+
+/// 场景的执行计划 (`atp scenario explain`)
+#[derive(Debug, Serialize)]
+struct ExecutionPlan {
+    scenario: String,
+    description: Option<String>,
+    target_host: Option<String>,
+    target_domain: Option<String>,
+    max_duration_secs: Option<u64>,
+    environment_guard: Option<EnvironmentGuardMode>,
+    tags: Vec<String>,
+    steps: Vec<PlannedStep>,
+
+    /// 是否有前置 / 清理步骤 (表格末尾的说明)
+    #[serde(skip)]
+    has_setup: bool,
+    #[serde(skip)]
+    has_teardown: bool,
+}
+
+impl Render for ExecutionPlan {
+    fn to_table(&self) -> String {
+        let mut lines = vec![format!("{} {}", "场景:".bold(), self.scenario.cyan().bold())];
+        if let Some(description) = &self.description {
+            lines.push(format!("描述: {}", description.bright_black()));
+        }
+        lines.push(format!(
+            "目标: 主机 {} / 虚拟机 {}",
+            self.target_host.as_deref().unwrap_or("(默认主机)"),
+            self.target_domain.as_deref().unwrap_or("-")
+        ));
+        if let Some(secs) = self.max_duration_secs {
+            lines.push(format!("最长执行时间: {}s", secs));
+        }
+        if let Some(mode) = &self.environment_guard {
+            lines.push(format!("环境检查: {:?}", mode));
+        }
+        if !self.tags.is_empty() {
+            lines.push(format!("标签: {}", self.tags.join(", ")));
+        }
+        lines.push(String::new());
+
+        let header = ["#", "阶段", "步骤", "动作", "目标", "超时", "标签"];
+        lines.push(format!(
+            "{:<4} {:<8} {:<28} {:<28} {:<32} {:<10} {}",
+            header[0], header[1], header[2], header[3], header[4], header[5], header[6]
+        ));
+        lines.push("-".repeat(120));
+
+        for step in &self.steps {
+            let timeout = if step.default_timeout {
+                format!("{}s (默认)", step.timeout_secs)
+            } else {
+                format!("{}s", step.timeout_secs)
+            };
+            let phase = match step.phase {
+                StepPhase::Setup => "前置".yellow(),
+                StepPhase::Main => "测试".normal(),
+                StepPhase::Teardown => "清理".bright_black(),
+            };
+            lines.push(format!(
+                "{:<4} {:<8} {:<28} {:<28} {:<32} {:<10} {}",
+                step.step_index + 1,
+                phase,
+                step.description,
+                step.action.cyan(),
+                step.target.as_deref().unwrap_or("-"),
+                timeout,
+                step.tags.join(", ")
+            ));
+        }
+
+        if self.has_setup {
+            lines.push("\n前置步骤失败时跳过测试步骤; 清理步骤总是执行".to_string());
+        } else if self.has_teardown {
+            lines.push("\n清理步骤总是执行".to_string());
+        }
+        lines.join("\n")
+    }
+}
+
+/// 输出场景的执行计划
+fn explain_scenario(file: &str) -> Result<()> {
+    let format = output_format(None)?;
+    let scenario = load_scenario_file(Path::new(file))?;
+    let steps = offline_runner().plan(&scenario);
+
+    let plan = ExecutionPlan {
+        has_setup: !scenario.setup.is_empty(),
+        has_teardown: !scenario.teardown.is_empty(),
+        scenario: scenario.name,
+        description: scenario.description,
+        target_host: scenario.target_host,
+        target_domain: scenario.target_domain,
+        max_duration_secs: scenario.max_duration_secs,
+        environment_guard: scenario.environment_guard,
+        tags: scenario.tags,
+        steps,
+    };
+    print_rendered(&plan, format)
+}
+
+/// 已保存的场景列表 (`atp scenario list`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct ScenarioList {
+    scenarios: Vec<ScenarioSummary>,
+}
+
+impl Render for ScenarioList {
+    fn to_table(&self) -> String {
+        if self.scenarios.is_empty() {
+            return [
+                "没有已保存的场景".yellow().to_string(),
+                "\n可以通过 atp scenario save <file> 保存场景, 或使用 --files 列出场景目录中的文件".to_string(),
+            ]
+            .join("\n");
+        }
+
+        let mut lines = vec![format!("找到 {} 个场景:\n", self.scenarios.len().to_string().green())];
+
+        for scenario in &self.scenarios {
+            lines.push(format!("{} {}", scenario.name.cyan().bold(), format!("v{}", scenario.version).bright_black()));
+
+            if let Some(desc) = &scenario.description {
+                lines.push(format!("  描述: {}", desc.bright_black()));
+            }
+
+            lines.push(format!(
+                "  更新时间: {}",
+                scenario.updated_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S")
+            ));
+
+            match (scenario.last_run_at, scenario.last_run_passed) {
+                (Some(run_at), Some(passed)) => {
+                    let status = if passed { "通过 ✓".green() } else { "失败 ✗".red() };
+                    let version = scenario
+                        .last_run_version
+                        .map_or(String::new(), |version| format!(" v{}", version));
+                    lines.push(format!(
+                        "  最近执行: {} {}{} (报告 #{})",
+                        run_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                        status,
+                        version.bright_black(),
+                        scenario.last_report_id.unwrap_or_default()
+                    ));
+                }
+                _ => lines.push(format!("  最近执行: {}", "从未执行".bright_black())),
+            }
+
+            lines.push(String::new());
+        }
+
+        lines.join("\n")
+    }
+}
+
 async fn list_scenarios() -> Result<()> {
+    let format = output_format(None)?;
+    let storage_manager = StorageManager::new(DB_PATH).await
+        .context("初始化数据库失败")?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let scenarios = storage.scenarios().list_with_last_run(&ScenarioFilter::default()).await?;
+
+    print_rendered(&ScenarioList { scenarios }, format)
+}
+
+/// 保存的场景 (`atp scenario save`)
+#[derive(Debug, Serialize)]
+struct SavedScenario {
+    name: String,
+    version: i32,
+
+    /// 内容与最新版本相同时不会产生新版本
+    changed: bool,
+}
+
+impl Render for SavedScenario {
+    fn to_table(&self) -> String {
+        if !self.changed {
+            return format!(
+                "{} 场景 {} 未变化, 当前版本: {}",
+                "✓".green().bold(),
+                self.name.cyan(),
+                self.version
+            );
+        }
+
+        [
+            format!(
+                "{} 场景已保存: {} (版本 {})",
+                "✓".green().bold(),
+                self.name.cyan(),
+                self.version.to_string().yellow()
+            ),
+            format!("  运行: atp scenario run --name {}@{}", self.name, self.version),
+        ]
+        .join("\n")
+    }
+}
+
+/// 保存场景定义到数据库
+async fn save_scenario(file: &str) -> Result<()> {
+    let format = output_format(None)?;
+    let path = Path::new(file);
+    let scenario = load_scenario_file(path)?;
+    let mut definition = std::fs::read_to_string(path)
+        .with_context(|| format!("读取场景文件失败: {}", file))?;
+
+    // 引用了其他文件的场景保存展开后的形式, 之后按名称运行时不再依赖被引用的文件
+    if step_groups::uses_step_groups(&definition) {
+        definition = scenario.to_yaml()?;
+    }
+
+    let storage_manager = StorageManager::new(DB_PATH).await
+        .context("初始化数据库失败")?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let previous = storage.scenarios().get_latest(&scenario.name).await?.map(|s| s.version);
+    let version = storage.scenarios().save(&scenario.name, &definition).await?;
+
+    let saved = SavedScenario { name: scenario.name, version, changed: previous != Some(version) };
+    print_rendered(&saved, format)
+}
+
+/// 场景两个版本的对比 (`atp scenario diff`)
+#[derive(Debug, Serialize)]
+struct ScenarioDiff<'a> {
+    name: &'a str,
+    from: i32,
+    to: i32,
+    identical: bool,
+    lines: Vec<DiffLine<'a>>,
+}
+
+impl Render for ScenarioDiff<'_> {
+    fn to_table(&self) -> String {
+        let mut lines = vec![
+            format!("--- {}@{}", self.name, self.from).red().to_string(),
+            format!("+++ {}@{}", self.name, self.to).green().to_string(),
+        ];
+
+        if self.identical {
+            lines.push("两个版本内容相同".bright_black().to_string());
+            return lines.join("\n");
+        }
+
+        lines.extend(self.lines.iter().map(|line| match line {
+            DiffLine::Same(text) => format!(" {}", text.bright_black()),
+            DiffLine::Removed(text) => format!("-{}", text).red().to_string(),
+            DiffLine::Added(text) => format!("+{}", text).green().to_string(),
+        }));
+        lines.join("\n")
+    }
+}
+
+/// 对比已保存场景的两个版本
+async fn diff_scenario(name: &str, from: i32, to: i32) -> Result<()> {
+    let format = output_format(None)?;
+    let storage_manager = StorageManager::new(DB_PATH).await
+        .context("初始化数据库失败")?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    let old = storage.scenarios().get_version(name, from).await?
+        .with_context(|| format!("场景 {} 没有版本 {}", name, from))?;
+    let new = storage.scenarios().get_version(name, to).await?
+        .with_context(|| format!("场景 {} 没有版本 {}", name, to))?;
+
+    let lines = diff_lines(&old.definition, &new.definition);
+    let diff = ScenarioDiff {
+        name,
+        from,
+        to,
+        identical: lines.iter().all(|line| matches!(line, DiffLine::Same(_))),
+        lines,
+    };
+    print_rendered(&diff, format)
+}
+
+/// 逐行对比结果
+#[derive(Debug, Serialize)]
+#[serde(tag = "op", content = "text", rename_all = "snake_case")]
+enum DiffLine<'a> {
+    Same(&'a str),
+    Removed(&'a str),
+    Added(&'a str),
+}
+
+/// 基于最长公共子序列的逐行对比
+fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffLine<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j]: old[i..] 与 new[j..] 的最长公共子序列长度
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut diff = Vec::new();
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(DiffLine::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Removed(old[i]));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|line| DiffLine::Removed(line)));
+    diff.extend(new[j..].iter().map(|line| DiffLine::Added(line)));
+
+    diff
+}
+
+/// 场景目录中的场景文件 (`atp scenario list --files`)
+#[derive(Debug, Serialize)]
+struct ScenarioFileList {
+    dir: String,
+    exists: bool,
+    files: Vec<ScenarioFile>,
+}
+
+/// 可以解析的场景文件
+#[derive(Debug, Serialize)]
+struct ScenarioFile {
+    file: String,
+    name: String,
+    description: Option<String>,
+    steps: usize,
+    tags: Vec<String>,
+}
+
+impl Render for ScenarioFileList {
+    fn to_table(&self) -> String {
+        if !self.exists {
+            return [
+                format!("场景目录不存在: {:?}", self.dir).yellow().to_string(),
+                "\n可以通过设置配置文件中的 scenario_dir 来指定场景目录".to_string(),
+            ]
+            .join("\n");
+        }
+
+        let mut lines = vec![format!("{}\n", format!("场景目录: {:?}", self.dir).bold())];
+
+        if self.files.is_empty() {
+            lines.push("没有找到任何场景文件".yellow().to_string());
+            return lines.join("\n");
+        }
+
+        lines.push(format!("找到 {} 个场景:\n", self.files.len().to_string().green()));
+
+        for scenario in &self.files {
+            lines.push(scenario.file.cyan().bold().to_string());
+            lines.push(format!("  名称: {}", scenario.name));
+
+            if let Some(desc) = &scenario.description {
+                lines.push(format!("  描述: {}", desc.bright_black()));
+            }
+
+            lines.push(format!("  步骤: {}", scenario.steps.to_string().yellow()));
+
+            if !scenario.tags.is_empty() {
+                lines.push(format!("  标签: {}", scenario.tags.join(", ").bright_black()));
+            }
+
+            lines.push(String::new());
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// 列出场景目录中的场景文件
+async fn list_scenario_files() -> Result<()> {
+    let format = output_format(None)?;
     let config = CliConfig::load()?;
     let scenario_dir = config.get_scenario_dir();
 
-    if !scenario_dir.exists() {
-        println!("{}", format!("场景目录不存在: {:?}", scenario_dir).yellow());
-        println!("\n可以通过设置配置文件中的 scenario_dir 来指定场景目录");
-        return Ok(());
+    let mut list = ScenarioFileList {
+        dir: scenario_dir.display().to_string(),
+        exists: scenario_dir.exists(),
+        files: Vec::new(),
+    };
+    if !list.exists {
+        return print_rendered(&list, format);
     }
 
-    println!("{}\n", format!("场景目录: {:?}", scenario_dir).bold());
-
     let entries = std::fs::read_dir(&scenario_dir)
         .with_context(|| format!("读取场景目录失败: {:?}", scenario_dir))?;
 
-    let mut scenarios = Vec::new();
-
     for entry in entries {
         let entry = entry?;
         let path = entry.path();
@@ -221,33 +1251,76 @@ async fn list_scenarios() -> Result<()> {
         };
 
         if let Ok(scenario) = scenario_result {
-            scenarios.push((path, scenario));
+            list.files.push(ScenarioFile {
+                file: entry.file_name().to_string_lossy().into_owned(),
+                name: scenario.name,
+                description: scenario.description,
+                steps: scenario.steps.len(),
+                tags: scenario.tags,
+            });
         }
     }
 
-    if scenarios.is_empty() {
-        println!("{}", "没有找到任何场景文件".yellow());
-        return Ok(());
-    }
+    print_rendered(&list, format)
+}
 
-    println!("找到 {} 个场景:\n", scenarios.len().to_string().green());
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for (path, scenario) in scenarios {
-        println!("{}", path.file_name().unwrap().to_str().unwrap().cyan().bold());
-        println!("  名称: {}", scenario.name);
+    #[test]
+    fn test_fan_out_args() {
+        use clap::Parser;
 
-        if let Some(desc) = &scenario.description {
-            println!("  描述: {}", desc.bright_black());
-        }
+        let parse = |args: &[&str]| crate::Cli::try_parse_from([&["atp", "scenario", "run", "login.yaml"], args].concat());
 
-        println!("  步骤: {}", scenario.steps.len().to_string().yellow());
+        assert!(parse(&["--vm", "vm-1,vm-2", "--var", "user=admin", "--var", "pin=1234"]).is_ok());
+        assert!(parse(&["--var", "user=admin"]).is_ok());
+        // 工件目录与并发数只用于多虚拟机执行
+        assert!(parse(&["--artifact-dir", "out"]).is_err());
+        assert!(parse(&["--vm", "vm-1", "--concurrency", "0"]).is_err());
+    }
 
-        if !scenario.tags.is_empty() {
-            println!("  标签: {}", scenario.tags.join(", ").bright_black());
-        }
+    #[test]
+    fn test_load_variables() {
+        let vars = ["user=admin".to_string(), "cmd=a=b".to_string()];
+        let variables = load_variables(None, &vars).unwrap();
+        assert_eq!(variables.get("user"), Some("admin"));
+        assert_eq!(variables.get("cmd"), Some("a=b"));
 
-        println!();
+        assert!(load_variables(None, &["novalue".to_string()]).is_err());
     }
 
-    Ok(())
+    #[test]
+    fn test_validation_result_render() {
+        let yaml = "name: demo\nsteps:\n  - action:\n      type: wait\n";
+        let issues = vec![ValidationIssue::warning(Some(0), "缺少超时")];
+        let result = ValidationResult::new(None, issues, Some(yaml));
+        assert!(result.passed);
+        assert!(result.to_table().contains("   3 |"), "{}", result.to_table());
+
+        let json: serde_json::Value = serde_json::from_str(&result.to_json().unwrap()).unwrap();
+        assert_eq!(json["passed"], true);
+        assert_eq!(json["issues"][0]["step_index"], 0);
+        assert!(json.get("parse_error").is_none());
+
+        let result = ValidationResult::parse_failed("invalid type at line 2 column 3".to_string(), yaml);
+        assert!(!result.passed);
+        let json: serde_json::Value = serde_json::from_str(&result.to_json().unwrap()).unwrap();
+        assert_eq!(json["parse_error"]["line"], 2);
+    }
+
+    #[test]
+    fn test_diff_lines_serialize() {
+        let diff = diff_lines("a\nb\n", "a\nc\n");
+        let json = serde_json::to_value(&diff).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"op": "same", "text": "a"},
+                {"op": "removed", "text": "b"},
+                {"op": "added", "text": "c"},
+            ])
+        );
+    }
 }
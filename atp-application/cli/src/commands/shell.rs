@@ -0,0 +1,349 @@
+//! 交互式会话 (`atp shell`)
+//!
+//! 连接一台虚拟机的 QMP / QGA / SPICE 后保持连接, 逐行读取命令并执行。
+//! 按键、文本、命令与点击映射到场景中的同名动作, 由 `ScenarioRunner` 执行;
+//! 单条命令出错只显示错误, 不会结束会话。
+//!
+//! 会话输出只面向人, 不经过 `Render`; 指定 json / yaml 输出时直接报错, 脚本应改用
+//! `atp keyboard` / `atp command` 等单次命令或场景。
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use atp_executor::{Action, PowerShellScript, ScenarioRunner, SessionState, StepStatus};
+use colored::Colorize;
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use crate::commands::common::output_format;
+use crate::commands::vm_target::VmTarget;
+use crate::config::CliConfig;
+use crate::VmTargetArgs;
+
+/// `ps` 命令的超时时间
+const POWERSHELL_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 命令帮助
+const HELP: &str = "\
+可用命令:
+  key <按键>              发送按键或组合键, 如 key ctrl+alt+f2
+  text <文本>             输入文本, 可以用双引号包住, 如 text \"hello world\"
+  exec <命令>             通过 QGA 执行命令并显示输出, 如 exec uname -a
+  ps <脚本文件> [参数...]  执行本地的 PowerShell 脚本 (Windows 客户机), 如 ps check.ps1 app01
+  click <x> <y> [按钮]    鼠标点击 (left / right / middle, 默认 left)
+  wait <秒>               等待
+  state                   显示虚拟机运行状态与协议连接
+  screenshot <文件>       通过 QMP 截屏 (QEMU 所在主机上的路径, .png 输出 PNG, 否则 PPM)
+  help                    显示本帮助
+  quit / exit             退出";
+
+/// 一条会话命令
+#[derive(Debug, Clone)]
+enum ShellCommand {
+    /// 映射到场景动作的命令
+    Action(Action),
+    State,
+    Screenshot(PathBuf),
+    /// PowerShell 脚本文件与参数
+    PowerShell(PathBuf, Vec<String>),
+    Help,
+    Quit,
+}
+
+/// 解析一行输入, 空行与 `#` 开头的注释返回 None
+fn parse_command(line: &str) -> Result<Option<ShellCommand>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let require = |usage: &str| -> Result<()> {
+        if rest.is_empty() {
+            anyhow::bail!("缺少参数, 用法: {}", usage);
+        }
+        Ok(())
+    };
+
+    let command = match name.to_lowercase().as_str() {
+        "key" => {
+            require("key <按键>")?;
+            ShellCommand::Action(Action::SendKey {
+                key: rest.to_string(),
+                hold_ms: None,
+            })
+        }
+        "text" => {
+            require("text <文本>")?;
+            ShellCommand::Action(Action::SendText { text: unquote(rest)? })
+        }
+        "exec" => {
+            require("exec <命令>")?;
+            ShellCommand::Action(Action::ExecCommand { command: rest.to_string() })
+        }
+        "click" => {
+            let args: Vec<&str> = rest.split_whitespace().collect();
+            let (x, y, button) = match args.as_slice() {
+                [x, y] => (x, y, "left"),
+                [x, y, button] => (x, y, *button),
+                _ => anyhow::bail!("用法: click <x> <y> [left|right|middle]"),
+            };
+            if !["left", "right", "middle"].contains(&button) {
+                anyhow::bail!("未知的鼠标按钮: {} (可选: left, right, middle)", button);
+            }
+            ShellCommand::Action(Action::MouseClick {
+                x: x.parse().with_context(|| format!("无效的坐标: {}", x))?,
+                y: y.parse().with_context(|| format!("无效的坐标: {}", y))?,
+                button: button.to_string(),
+            })
+        }
+        "wait" => {
+            require("wait <秒>")?;
+            ShellCommand::Action(Action::Wait {
+                duration: rest.parse().with_context(|| format!("无效的秒数: {}", rest))?,
+            })
+        }
+        "ps" => {
+            require("ps <脚本文件> [参数...]")?;
+            let mut words = rest.split_whitespace().map(str::to_string);
+            let file = words.next().unwrap_or_default();
+            ShellCommand::PowerShell(PathBuf::from(file), words.collect())
+        }
+        "state" => ShellCommand::State,
+        "screenshot" => {
+            require("screenshot <文件>")?;
+            ShellCommand::Screenshot(PathBuf::from(unquote(rest)?))
+        }
+        "help" | "?" => ShellCommand::Help,
+        "quit" | "exit" => ShellCommand::Quit,
+        _ => anyhow::bail!("未知命令: {} (输入 help 查看可用命令)", name),
+    };
+
+    Ok(Some(command))
+}
+
+/// 去掉成对的双引号并处理 `\"`、`\\`、`\n`、`\t` 转义; 没有引号时原样返回
+fn unquote(value: &str) -> Result<String> {
+    let Some(inner) = value.strip_prefix('"') else {
+        return Ok(value.to_string());
+    };
+    let inner = inner.strip_suffix('"').context("缺少结束的双引号")?;
+
+    let mut result = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(c @ ('"' | '\\')) => result.push(c),
+            Some(c) => {
+                result.push('\\');
+                result.push(c);
+            }
+            None => result.push('\\'),
+        }
+    }
+    Ok(result)
+}
+
+pub async fn handle(args: &VmTargetArgs, history: Option<&str>, profile: Option<&str>) -> Result<()> {
+    let format = output_format(None)?;
+    if !format.is_table() {
+        anyhow::bail!("atp shell 是交互式会话, 不支持 {} 输出", format);
+    }
+
+    let target = VmTarget::from_args(args, profile).await?;
+    let mut runner = target.runner().await?;
+
+    println!("{} 连接虚拟机 {} (主机 {})...", "🔌".cyan(), target.vm().yellow(), target.host_id().yellow());
+    let state = runner.attach(Some(target.host_id()), target.vm()).await?;
+    print_state(&state);
+    println!("\n输入 help 查看可用命令, quit 退出\n");
+
+    let history_path = match history {
+        Some(path) => PathBuf::from(path),
+        None => CliConfig::config_path()?.with_file_name("shell_history"),
+    };
+    let result = repl(&mut runner, target.vm(), &history_path).await;
+
+    runner.detach().await;
+    result
+}
+
+/// 读取并执行命令, 直到 quit 或 Ctrl-D
+async fn repl(runner: &mut ScenarioRunner, vm: &str, history_path: &Path) -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    // 历史文件不存在时忽略
+    let _ = editor.load_history(history_path);
+
+    let prompt = format!("atp:{}> ", vm);
+    let mut index = 0;
+
+    loop {
+        // 读取输入会阻塞, 期间让出当前工作线程
+        let line = match tokio::task::block_in_place(|| editor.readline(&prompt)) {
+            Ok(line) => line,
+            // Ctrl-C 只清空当前行
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if !line.trim().is_empty() {
+            let _ = editor.add_history_entry(line.as_str());
+        }
+
+        let command = match parse_command(&line) {
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(e) => {
+                eprintln!("{} {:#}", "✗".red(), e);
+                continue;
+            }
+        };
+
+        match command {
+            ShellCommand::Action(action) => {
+                index += 1;
+                run_action(runner, &action, index).await;
+            }
+            ShellCommand::State => print_state(&runner.session_state().await),
+            ShellCommand::Screenshot(path) => match screenshot(runner, &path).await {
+                Ok(path) => println!("{} 截图已保存: {}", "✓".green(), path.display()),
+                Err(e) => eprintln!("{} {:#}", "✗".red(), e),
+            },
+            ShellCommand::PowerShell(path, args) => {
+                if let Err(e) = powershell(runner, &path, args).await {
+                    eprintln!("{} {:#}", "✗".red(), e);
+                }
+            }
+            ShellCommand::Help => println!("{}", HELP),
+            ShellCommand::Quit => break,
+        }
+    }
+
+    if let Some(dir) = history_path.parent() {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    if let Err(e) = editor.save_history(history_path) {
+        eprintln!("{} 保存历史记录失败: {}", "⚠".yellow(), e);
+    }
+    Ok(())
+}
+
+/// 执行一个动作并显示结果, 失败只显示错误
+async fn run_action(runner: &mut ScenarioRunner, action: &Action, index: usize) {
+    match runner.execute_interactive(action, index).await {
+        Ok(step) => {
+            let duration = format!("({} ms)", step.duration_ms).dimmed();
+            match step.status {
+                StepStatus::Failed => eprintln!(
+                    "{} {} {}",
+                    "✗".red(),
+                    step.error.as_deref().unwrap_or(&step.description),
+                    duration
+                ),
+                _ => println!("{} {} {}", "✓".green(), step.description, duration),
+            }
+            if let Some(output) = step.output.as_deref().filter(|output| !output.is_empty()) {
+                println!("{}", output.trim_end());
+            }
+        }
+        Err(e) => eprintln!("{} {}", "✗".red(), e),
+    }
+}
+
+/// 截屏, 相对路径按当前目录转换为绝对路径 (文件由 QEMU 进程写入)
+async fn screenshot(runner: &mut ScenarioRunner, path: &Path) -> Result<PathBuf> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()?.join(path)
+    };
+    runner.screenshot(&path).await?;
+    Ok(path)
+}
+
+/// 在当前 QGA 连接上执行 PowerShell 脚本文件
+async fn powershell(runner: &ScenarioRunner, path: &Path, args: Vec<String>) -> Result<()> {
+    let source = std::fs::read_to_string(path).with_context(|| format!("读取脚本文件失败: {}", path.display()))?;
+    let script = PowerShellScript::new(source).with_args(args);
+
+    let output = runner.exec_powershell(&script, POWERSHELL_TIMEOUT).await?;
+    let stdout = output.stdout.trim_end();
+    if !stdout.is_empty() {
+        println!("{}", stdout);
+    }
+    if !output.stderr.trim().is_empty() {
+        eprintln!("{}", output.stderr.trim_end().yellow());
+    }
+    println!("{} {}", "✓".green(), format!("({} ms)", output.duration_ms).dimmed());
+    Ok(())
+}
+
+/// 显示会话状态
+fn print_state(state: &SessionState) {
+    let connected = |ok: bool| if ok { "已连接".green() } else { "未连接".red() };
+
+    println!("  虚拟机: {}", state.domain.as_deref().unwrap_or("-").yellow());
+    println!("  运行状态: {}", state.run_state.as_deref().unwrap_or("未知"));
+    println!("  QMP: {}  QGA: {}  SPICE: {}", connected(state.qmp), connected(state.qga), connected(state.spice));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// 解析为动作并转换为场景中的写法, 便于比较
+    fn action(line: &str) -> serde_json::Value {
+        match parse_command(line).unwrap() {
+            Some(ShellCommand::Action(action)) => serde_json::to_value(action).unwrap(),
+            other => panic!("{}: {:?}", line, other),
+        }
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(action("key ctrl+alt+f2"), json!({"type": "send_key", "key": "ctrl+alt+f2"}));
+        assert_eq!(action("text \"hello \\\"atp\\\"\\n\""), json!({"type": "send_text", "text": "hello \"atp\"\n"}));
+        assert_eq!(action("TEXT  plain words "), json!({"type": "send_text", "text": "plain words"}));
+        assert_eq!(action("exec uname -a"), json!({"type": "exec_command", "command": "uname -a"}));
+        assert_eq!(action("click 100 200"), json!({"type": "mouse_click", "x": 100, "y": 200, "button": "left"}));
+        assert_eq!(action("wait 2"), json!({"type": "wait", "duration": 2}));
+
+        assert!(parse_command("  ").unwrap().is_none());
+        assert!(parse_command("# 注释").unwrap().is_none());
+        assert!(matches!(parse_command("state").unwrap(), Some(ShellCommand::State)));
+        assert!(matches!(
+            parse_command("screenshot out.png").unwrap(),
+            Some(ShellCommand::Screenshot(path)) if path == Path::new("out.png")
+        ));
+        assert!(matches!(
+            parse_command("ps check.ps1 C:\\ -Verbose").unwrap(),
+            Some(ShellCommand::PowerShell(path, args)) if path == Path::new("check.ps1") && args == ["C:\\", "-Verbose"]
+        ));
+        assert!(matches!(parse_command("exit").unwrap(), Some(ShellCommand::Quit)));
+    }
+
+    #[test]
+    fn test_parse_command_errors() {
+        for (line, expected) in [
+            ("key", "用法: key <按键>"),
+            ("ps", "用法: ps <脚本文件>"),
+            ("click 1", "用法: click"),
+            ("click 1 2 side", "未知的鼠标按钮"),
+            ("click a 2", "无效的坐标: a"),
+            ("text \"open", "缺少结束的双引号"),
+            ("reboot", "未知命令: reboot"),
+        ] {
+            let err = format!("{:#}", parse_command(line).unwrap_err());
+            assert!(err.contains(expected), "{}: {}", line, err);
+        }
+    }
+}
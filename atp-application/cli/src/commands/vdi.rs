@@ -1,13 +1,36 @@
 //! VDI 平台管理和验证命令
 
-use crate::VdiAction;
+use crate::commands::common::{
+    output_format, print_rendered, print_serialized, progress, Confirmation, Confirmed, DestructiveGuard, OutputFormat,
+    Render,
+};
+use crate::{BaselineAction, BatchTargetArgs, ConfirmArgs, VdiAction};
 use anyhow::{Context, Result};
-use atp_executor::{TestConfig, VdiConfig};
-use atp_transport::{HostConnection, HostInfo};
+use atp_executor::vdi_ops::parse_assign_mapping;
+use atp_executor::{
+    AssignItemResult, AssignMapping, BatchItemResult, CloneItemResult, CloneSource, NameTemplate, RestoreItemResult,
+    RestoreMethod, RestoreStatus, VmMatchResult,
+};
+use atp_executor::vm_cache::{domain_status_label, records_from_listing};
+use atp_executor::{
+    BaselineDiff, BaselineOps, BaselineSnapshot, BatchOperation, CacheMode, CleanupStatus, ResourceKind, Target, TestConfig, VdiBatchOps, VdiConfig,
+    VmCacheManager,
+};
+use atp_storage::{HostRecord, ReportResourceRecord, Storage, StorageManager, VmStatusHistoryRecord};
+use atp_transport::{
+    BrickStatus, ConnectionState, DomainFilter, GlusterClient, GlusterFileUsage, HealInfo, HostConnection, HostInfo, LibvirtDomainInfo, SplitBrainEntry,
+    TransportConfig, TransportManager,
+};
 use atp_vdiplatform::{VdiClient, client::VdiConfig as VdiClientConfig};
+use chrono::{Local, Utc};
+use futures_util::future::join_all;
+use serde::Serialize;
 use serde_json::json;
 use std::collections::HashMap;
-use tracing::{error, info};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
 
 /// VDI 虚拟机信息
 #[derive(Debug, Clone)]
@@ -17,44 +40,223 @@ struct VmInfo {
     host: String,
 }
 
-/// Libvirt 虚拟机信息
-#[derive(Debug, Clone)]
-struct LibvirtVmInfo {
-    name: String,
-    state: String,
-    cpu: u32,
-    memory_mb: u64,
-}
-
 /// 比对结果
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 struct CompareResult {
     vm_name: String,
+    host: String,
     vdi_status: String,
     libvirt_status: String,
     consistent: bool,
-    host: String,
 }
 
-pub async fn handle(action: VdiAction) -> Result<()> {
+/// 一致性验证结果 (json / yaml 输出为比对结果数组, 与 `--only-diff` 过滤一致)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct ConsistencyReport {
+    results: Vec<CompareResult>,
+    #[serde(skip)]
+    total: usize,
+    #[serde(skip)]
+    consistent: usize,
+}
+
+impl ConsistencyReport {
+    fn new(results: Vec<CompareResult>, only_diff: bool) -> Self {
+        let total = results.len();
+        let consistent = results.iter().filter(|result| result.consistent).count();
+        let results = results.into_iter().filter(|result| !only_diff || !result.consistent).collect();
+        Self { results, total, consistent }
+    }
+}
+
+impl Render for ConsistencyReport {
+    fn to_table(&self) -> String {
+        let inconsistent = self.total - self.consistent;
+        let rate = if self.total > 0 {
+            (self.consistent as f64 / self.total as f64) * 100.0
+        } else {
+            0.0
+        };
+
+        let mut lines = vec![
+            "╔════════════════════════════════════════════════════════════════╗".to_string(),
+            "║                      验证结果汇总                              ║".to_string(),
+            "╚════════════════════════════════════════════════════════════════╝\n".to_string(),
+            "📊 统计信息:".to_string(),
+            format!("   总虚拟机数: {}", self.total),
+            format!("   一致: {} ✅", self.consistent),
+            format!("   不一致: {} ❌", inconsistent),
+            format!("   一致性: {:.1}%\n", rate),
+            "📋 详细对比结果:\n".to_string(),
+            format!(
+                "{:<20} {:<15} {:<20} {:<15} {:<10}",
+                "虚拟机名称", "主机", "VDI状态", "libvirt状态", "一致性"
+            ),
+            "-".repeat(80),
+        ];
+
+        for result in &self.results {
+            let status_icon = if result.consistent { "✅" } else { "❌" };
+            lines.push(format!(
+                "{:<20} {:<15} {:<20} {:<20} {}",
+                result.vm_name, result.host, result.vdi_status, result.libvirt_status, status_icon
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+impl VdiAction {
+    /// 会删除或修改平台资源的子命令返回其确认参数
+    ///
+    /// 这里不用通配分支: 新增子命令时必须声明是否为破坏性操作。
+    fn confirm_args(&self) -> Option<&ConfirmArgs> {
+        match self {
+            VdiAction::CleanupOrphans { confirm, .. }
+            | VdiAction::Batch { confirm, .. }
+            | VdiAction::Assign { confirm, .. }
+            | VdiAction::Clone { confirm, .. }
+            | VdiAction::TestRestore { confirm, .. } => Some(confirm),
+            VdiAction::Verify { .. }
+            | VdiAction::ListHosts { .. }
+            | VdiAction::ListVms { .. }
+            | VdiAction::SyncHosts { .. }
+            | VdiAction::History { .. }
+            | VdiAction::DiskHealth { .. }
+            | VdiAction::Baseline { .. } => None,
+        }
+    }
+}
+
+pub async fn handle(action: VdiAction, profile: Option<&str>) -> Result<()> {
+    // 破坏性子命令统一经过确认守卫
+    let guard = action
+        .confirm_args()
+        .map(|args| DestructiveGuard::new(args.yes, args.dry_run));
+    let destructive = || guard.as_ref().context("子命令未声明确认参数");
+
     match action {
         VdiAction::Verify {
             config,
             only_diff,
             format,
-        } => verify_consistency(&config, only_diff, &format).await?,
-        VdiAction::ListHosts { config } => list_hosts(&config).await?,
-        VdiAction::ListVms { config, host } => list_vms(&config, host.as_deref()).await?,
+            watch,
+            interval,
+            until_consistent,
+            timeout,
+        } => {
+            if watch || until_consistent {
+                let options = WatchOptions {
+                    interval: Duration::from_secs(interval),
+                    until_consistent,
+                    timeout: timeout.map(Duration::from_secs),
+                };
+                watch_consistency(&config, profile, &format, options).await?
+            } else {
+                verify_consistency(&config, profile, only_diff, &format).await?
+            }
+        }
+        VdiAction::ListHosts { config } => list_hosts(&config, profile).await?,
+        VdiAction::ListVms {
+            config,
+            host,
+            status,
+            user,
+            sort,
+            columns,
+        } => {
+            let options = VmListOptions::parse(host, status.as_deref(), user, sort.as_deref(), &columns)?;
+            list_vms(&config, profile, &options).await?
+        }
         VdiAction::SyncHosts {
             config,
             test_connection,
-        } => sync_hosts(&config, test_connection).await?,
+        } => sync_hosts(&config, profile, test_connection).await?,
+        VdiAction::CleanupOrphans { from_report, config, .. } => {
+            cleanup_orphans(&config, profile, from_report, destructive()?).await?
+        }
+        VdiAction::Batch {
+            operation,
+            target,
+            balanced,
+            format,
+            config,
+            ..
+        } => batch_operation(&config, profile, &operation, target, balanced, destructive()?, &format).await?,
+        VdiAction::Assign {
+            mapping,
+            format,
+            config,
+            ..
+        } => assign_users(&config, profile, &mapping, destructive()?, &format).await?,
+        VdiAction::Clone {
+            source,
+            count,
+            name,
+            storage_pool,
+            start,
+            wait_qga,
+            clone_timeout,
+            qga_timeout,
+            format,
+            config,
+            ..
+        } => {
+            let options = CloneOptions {
+                source,
+                count,
+                name,
+                storage_pool,
+                start,
+                wait_qga,
+                clone_timeout: Duration::from_secs(clone_timeout),
+                qga_timeout: Duration::from_secs(qga_timeout),
+            };
+            clone_vms(&config, profile, &options, destructive()?, &format).await?
+        }
+        VdiAction::TestRestore {
+            target,
+            marker_path,
+            method,
+            timeout,
+            format,
+            config,
+            ..
+        } => {
+            let options = RestoreTestOptions {
+                marker_path,
+                method: parse_restore_method(&method)?,
+                timeout: Duration::from_secs(timeout),
+            };
+            test_restore_points(&config, profile, target, &options, destructive()?, &format).await?
+        }
+        VdiAction::History {
+            vm_name,
+            refresh,
+            config,
+        } => vm_history(&config, profile, &vm_name, refresh).await?,
+        VdiAction::DiskHealth { vm, host, format } => disk_health(&vm, host.as_deref(), &format).await?,
+        VdiAction::Baseline { action } => match action {
+            BaselineAction::Save { output, config } => save_baseline(&config, profile, &output).await?,
+            BaselineAction::Diff {
+                baseline,
+                format,
+                config,
+            } => diff_baseline(&config, profile, &baseline, &format).await?,
+        },
     }
+
     Ok(())
 }
 
+/// 加载测试配置 (指定 profile 时合并 [default] 与 [profile.<name>])
+pub(crate) fn load_config(config_path: &str, profile: Option<&str>) -> Result<TestConfig> {
+    TestConfig::load_with_env(Path::new(config_path), profile)
+}
+
 /// 创建并登录VDI客户端
-async fn create_vdi_client(vdi_config: &VdiConfig) -> Result<VdiClient> {
+pub(crate) async fn create_vdi_client(vdi_config: &VdiConfig) -> Result<VdiClient> {
     let client_config = VdiClientConfig {
         connect_timeout: vdi_config.connect_timeout,
         request_timeout: vdi_config.connect_timeout,
@@ -73,46 +275,173 @@ async fn create_vdi_client(vdi_config: &VdiConfig) -> Result<VdiClient> {
     Ok(client)
 }
 
+/// 一致性比对时同时列举的主机数
+const SNAPSHOT_CONCURRENCY: usize = 8;
+
 /// 验证 VDI 平台与 libvirt 虚拟机状态一致性
-async fn verify_consistency(config_path: &str, only_diff: bool, format: &str) -> Result<()> {
-    println!("╔════════════════════════════════════════════════════════════════╗");
-    println!("║         VDI 与 libvirt 虚拟机状态一致性验证                   ║");
-    println!("╚════════════════════════════════════════════════════════════════╝\n");
-
-    // 加载配置
-    let config = TestConfig::load_from_path(config_path)
-        .context(format!("无法加载配置文件: {}", config_path))?;
-    let vdi_config = config
-        .vdi
-        .as_ref()
-        .context("配置文件中未找到 VDI 平台配置")?;
-
-    // 1. 登录 VDI 平台
-    println!("📋 步骤 1/4: 登录 VDI 平台...");
-    let client = create_vdi_client(vdi_config).await?;
-    println!("   ✅ VDI 登录成功\n");
+/// VDI 平台与 libvirt 的一致性比对
+///
+/// 主机连接注册在同一个 `TransportManager` 中, `--watch` 的多轮比对之间复用, 不会每轮重新连接。
+struct ConsistencyChecker {
+    client: VdiClient,
+    transport: TransportManager,
+}
 
-    // 2. 从 VDI 获取主机列表
-    println!("📋 步骤 2/4: 获取 VDI 主机列表...");
-    let hosts = client.host().list_all().await?;
-    println!("   ✅ 找到 {} 个主机\n", hosts.len());
+impl ConsistencyChecker {
+    /// 加载配置并登录 VDI 平台
+    async fn connect(config_path: &str, profile: Option<&str>) -> Result<Self> {
+        let config = load_config(config_path, profile)
+            .context(format!("无法加载配置文件: {}", config_path))?;
+        let vdi_config = config
+            .vdi
+            .as_ref()
+            .context("配置文件中未找到 VDI 平台配置")?;
+
+        Ok(Self {
+            client: create_vdi_client(vdi_config).await?,
+            transport: TransportManager::new(TransportConfig::default()),
+        })
+    }
 
-    // 创建主机ID到主机名的映射
-    let mut host_id_to_name: HashMap<String, String> = HashMap::new();
-    for host in &hosts {
-        let host_id = host["id"].as_str().unwrap_or("").to_string();
-        let host_name = host["name"].as_str().unwrap_or("").to_string();
-        if !host_id.is_empty() && !host_name.is_empty() {
-            host_id_to_name.insert(host_id, host_name);
+    /// 执行一轮比对, `verbose` 时输出各步骤的进度
+    async fn check(&self, format: OutputFormat, verbose: bool) -> Result<Vec<CompareResult>> {
+        // 1. 从 VDI 获取主机列表
+        if verbose {
+            progress!(format, "📋 步骤 2/4: 获取 VDI 主机列表...");
+        }
+        let hosts = self.client.host().list_all().await?;
+        if verbose {
+            progress!(format, "   ✅ 找到 {} 个主机\n", hosts.len());
+        }
+
+        // 创建主机ID到主机名的映射
+        let mut host_id_to_name: HashMap<String, String> = HashMap::new();
+        for host in &hosts {
+            let host_id = host["id"].as_str().unwrap_or("").to_string();
+            let host_name = host["name"].as_str().unwrap_or("").to_string();
+            if !host_id.is_empty() && !host_name.is_empty() {
+                host_id_to_name.insert(host_id, host_name);
+            }
+        }
+
+        // 2. 从 VDI 获取虚拟机列表
+        if verbose {
+            progress!(format, "📋 步骤 3/4: 获取 VDI 虚拟机列表...");
+        }
+        let vdi_domains = self.client.domain().list_all().await?;
+        let vdi_vms = vdi_vm_infos(&vdi_domains, &host_id_to_name);
+        if verbose {
+            progress!(format, "   ✅ VDI 虚拟机数量: {}\n", vdi_vms.len());
+            progress!(format, "📋 步骤 4/4: 连接 libvirt 并比对虚拟机状态...\n");
+        }
+
+        // 3. 并发列举各在线主机上的 libvirt 虚拟机并比对
+        let mut online = Vec::new();
+        for host in &hosts {
+            let host_name = host["name"].as_str().unwrap_or("");
+            let host_ip = host["ip"].as_str().unwrap_or("");
+
+            if host["status"].as_i64().unwrap_or(-1) != 1 {
+                if verbose {
+                    progress!(format, "   ⚠️  主机 {} 离线，跳过", host_name);
+                }
+                continue;
+            }
+            online.push((host_name, host_ip));
+        }
+
+        let registrations = join_all(online.iter().map(|(host_name, host_ip)| async move {
+            if verbose {
+                progress!(format, "   🔗 连接主机: {} ({})", host_name, host_ip);
+            }
+            (host_name.to_string(), self.register_host(host_name, host_ip).await)
+        }))
+        .await;
+        let mut connected = Vec::new();
+        for (host_name, registration) in registrations {
+            match registration {
+                Ok(()) => connected.push(host_name),
+                Err(e) => error!("   ❌ {:#}", e),
+            }
+        }
+
+        let snapshot = self
+            .transport
+            .snapshot_all_domains(&DomainFilter::all().with_hosts(connected), SNAPSHOT_CONCURRENCY)
+            .await;
+        for failure in &snapshot.errors {
+            error!("   ❌ 列举主机 {} 上的虚拟机失败: {}", failure.host_id, failure.error);
         }
+
+        let mut results = Vec::new();
+        for host_name in &snapshot.hosts {
+            let domains: Vec<LibvirtDomainInfo> = snapshot.domains_on(host_name).cloned().collect();
+            if verbose {
+                progress!(format, "   📊 {} libvirt 虚拟机数量: {}", host_name, domains.len());
+            }
+            results.extend(compare_host_vms(host_name, &domains, &vdi_vms));
+        }
+        if verbose {
+            progress!(format, "");
+        }
+
+        Ok(results)
     }
 
-    // 3. 从 VDI 获取虚拟机列表
-    println!("📋 步骤 3/4: 获取 VDI 虚拟机列表...");
-    let vdi_domains = client.domain().list_all().await?;
+    /// 注册主机并建立 libvirt 连接
+    ///
+    /// 主机第一次出现时注册到 `TransportManager`, 依次尝试 qemu+tcp 与 qemu+ssh;
+    /// 之后沿用已注册的连接, 连接断开时由快照在原地重连。
+    async fn register_host(&self, host_name: &str, host_ip: &str) -> Result<()> {
+        if self.transport.list_hosts().await.iter().any(|id| id == host_name) {
+            return Ok(());
+        }
+
+        let uris = [
+            format!("qemu+tcp://{}/system", host_ip),
+            format!("qemu+ssh://root@{}/system", host_ip),
+        ];
+        let mut last_error = None;
+        for uri in &uris {
+            self.transport
+                .add_host(HostInfo::new(host_name, host_ip).with_uri(uri))
+                .await?;
+
+            let connected = self
+                .transport
+                .execute_on_host(host_name, |conn| async move {
+                    // 连接池在后台建立连接, 尚未连上时在这里直接连接
+                    if conn.state().await != ConnectionState::Connected {
+                        conn.connect().await?;
+                    }
+                    Ok(())
+                })
+                .await;
+            match connected {
+                Ok(()) => {
+                    info!("   ✅ 连接成功: {}", uri);
+                    return Ok(());
+                }
+                Err(e) => {
+                    info!("   ⚠️  连接失败 {}: {}", uri, e);
+                    self.transport.remove_host(host_name).await?;
+                    last_error = Some(e);
+                }
+            }
+        }
+
+        anyhow::bail!(
+            "无法连接到主机 {} 的 libvirtd: {}",
+            host_name,
+            last_error.map(|e| e.to_string()).unwrap_or_default()
+        )
+    }
+}
 
-    let mut vdi_vms: HashMap<String, VmInfo> = HashMap::new();
-    for domain in &vdi_domains {
+/// VDI 虚拟机列表按名称索引 (主机 ID 换成主机名)
+fn vdi_vm_infos(domains: &[serde_json::Value], host_id_to_name: &HashMap<String, String>) -> HashMap<String, VmInfo> {
+    let mut vdi_vms = HashMap::new();
+    for domain in domains {
         let name = domain["name"].as_str().unwrap_or("").to_string();
         let status = match domain["status"].as_i64().unwrap_or(-1) {
             0 => "关机".to_string(),
@@ -141,404 +470,2396 @@ async fn verify_consistency(config_path: &str, only_diff: bool, format: &str) ->
             );
         }
     }
+    vdi_vms
+}
 
-    println!("   ✅ VDI 虚拟机数量: {}\n", vdi_vms.len());
-
-    // 4. 连接 libvirt 并获取虚拟机信息
-    println!("📋 步骤 4/4: 连接 libvirt 并比对虚拟机状态...\n");
-
-    let mut all_results: Vec<CompareResult> = Vec::new();
-    let mut total_vms = 0;
-    let mut consistent_vms = 0;
-    let mut inconsistent_vms = 0;
-
-    for host in &hosts {
-        let host_name = host["name"].as_str().unwrap_or("");
-        let host_ip = host["ip"].as_str().unwrap_or("");
-        let status = host["status"].as_i64().unwrap_or(-1);
-
-        if status != 1 {
-            println!("   ⚠️  主机 {} 离线，跳过", host_name);
-            continue;
-        }
-
-        println!("   🔗 连接主机: {} ({})", host_name, host_ip);
-
-        // 尝试连接 libvirt
-        let uris = vec![
-            format!("qemu+tcp://{}/system", host_ip),
-            format!("qemu+ssh://root@{}/system", host_ip),
-        ];
-
-        let mut connected = false;
-        let mut libvirt_vms: HashMap<String, LibvirtVmInfo> = HashMap::new();
-
-        for uri in &uris {
-            let host_info = HostInfo {
-                id: host_name.to_string(),
-                host: host_name.to_string(),
-                uri: uri.clone(),
-                tags: vec![],
-                metadata: HashMap::new(),
-            };
-
-            let conn = HostConnection::new(host_info);
-            match conn.connect().await {
-                Ok(_) => {
-                    if conn.is_alive().await {
-                        info!("   ✅ 连接成功: {}", uri);
-
-                        // 获取虚拟机列表（包括所有状态的虚拟机）
-                        if let Ok(conn_mutex) = conn.get_connection().await {
-                            let conn_guard = conn_mutex.lock().await;
-                            if let Some(conn_ref) = conn_guard.as_ref() {
-                                // 获取所有域（包括关闭状态的）
-                                // flags: VIR_CONNECT_LIST_DOMAINS_ACTIVE | VIR_CONNECT_LIST_DOMAINS_INACTIVE = 3
-                                if let Ok(domains) = conn_ref.list_all_domains(3) {
-                                    for domain in &domains {
-                                        if let Ok(name) = domain.get_name() {
-                                            let state = if let Ok((st, _)) = domain.get_state() {
-                                                // 使用 Debug format 输出状态，然后解析为字符串
-                                                let state_debug = format!("{:?}", st);
-                                                // 状态值: Running, Shutoff, Paused, Shutdown, Crashed, PMSuspended, Blocked, NoState
-                                                state_debug
-                                            } else {
-                                                "Unknown".to_string()
-                                            };
-
-                                            let (cpu, memory) = if let Ok(info) = domain.get_info()
-                                            {
-                                                (info.nr_virt_cpu, info.memory / 1024)
-                                            } else {
-                                                (0, 0)
-                                            };
-
-                                            libvirt_vms.insert(
-                                                name.clone(),
-                                                LibvirtVmInfo {
-                                                    name,
-                                                    state,
-                                                    cpu,
-                                                    memory_mb: memory,
-                                                },
-                                            );
-                                        }
-                                    }
-                                }
-                            }
-                        }
-
-                        connected = true;
-                        break;
-                    }
-                }
-                Err(e) => {
-                    info!("   ⚠️  连接失败 {}: {}", uri, e);
-                }
-            }
-        }
-
-        if !connected {
-            error!("   ❌ 无法连接到主机 {} 的 libvirtd", host_name);
-            continue;
-        }
-
-        println!("   📊 libvirt 虚拟机数量: {}", libvirt_vms.len());
-
-        // 比对虚拟机状态
-        for (vm_name, libvirt_vm) in &libvirt_vms {
-            total_vms += 1;
+/// libvirt 虚拟机状态名称 (virDomainState)
+fn libvirt_state_name(state: u32) -> &'static str {
+    match state {
+        1 => "Running",
+        2 => "Blocked",
+        3 => "Paused",
+        4 => "Shutdown",
+        5 => "Shutoff",
+        6 => "Crashed",
+        7 => "PMSuspended",
+        _ => "NoState",
+    }
+}
 
-            if let Some(vdi_vm) = vdi_vms.get(vm_name) {
+/// 比对一台主机上的 libvirt 虚拟机与 VDI 记录, 结果按虚拟机名称排序
+fn compare_host_vms(host_name: &str, domains: &[LibvirtDomainInfo], vdi_vms: &HashMap<String, VmInfo>) -> Vec<CompareResult> {
+    let mut results: Vec<CompareResult> = domains
+        .iter()
+        .map(|domain| {
+            let libvirt_status = libvirt_state_name(domain.state);
+            match vdi_vms.get(&domain.name) {
                 // VDI 中存在该虚拟机，检查状态是否一致
-                let consistent = match (vdi_vm.status.as_str(), libvirt_vm.state.as_str()) {
-                    ("运行中", "1") | ("运行中", "Running") => true,
-                    ("挂起", "3") | ("挂起", "Paused") => true,
-                    ("关机", "5") | ("关机", "Shutoff") => true,
-                    _ => false,
-                };
-
-                if consistent {
-                    consistent_vms += 1;
-                } else {
-                    inconsistent_vms += 1;
-                }
-
-                all_results.push(CompareResult {
-                    vm_name: vm_name.clone(),
-                    vdi_status: vdi_vm.status.clone(),
-                    libvirt_status: libvirt_vm.state.clone(),
-                    consistent,
+                Some(vdi_vm) => CompareResult {
+                    vm_name: domain.name.clone(),
                     host: host_name.to_string(),
-                });
-            } else {
+                    vdi_status: vdi_vm.status.clone(),
+                    libvirt_status: libvirt_status.to_string(),
+                    consistent: matches!(
+                        (vdi_vm.status.as_str(), libvirt_status),
+                        ("运行中", "Running") | ("挂起", "Paused") | ("关机", "Shutoff")
+                    ),
+                },
                 // libvirt 上存在但 VDI 中不存在 - 不一致
-                inconsistent_vms += 1;
-                all_results.push(CompareResult {
-                    vm_name: vm_name.clone(),
+                None => CompareResult {
+                    vm_name: domain.name.clone(),
+                    host: host_name.to_string(),
                     vdi_status: "不存在".to_string(),
-                    libvirt_status: libvirt_vm.state.clone(),
+                    libvirt_status: libvirt_status.to_string(),
                     consistent: false,
-                    host: host_name.to_string(),
-                });
+                },
             }
-        }
-
-        println!();
-    }
+        })
+        .collect();
+    results.sort_by(|a, b| a.vm_name.cmp(&b.vm_name));
+    results
+}
 
-    // 输出结果
-    println!("╔════════════════════════════════════════════════════════════════╗");
-    println!("║                      验证结果汇总                              ║");
-    println!("╚════════════════════════════════════════════════════════════════╝\n");
+async fn verify_consistency(config_path: &str, profile: Option<&str>, only_diff: bool, format: &str) -> Result<()> {
+    let format = output_format(Some(format))?;
+    progress!(format, "╔════════════════════════════════════════════════════════════════╗");
+    progress!(format, "║         VDI 与 libvirt 虚拟机状态一致性验证                   ║");
+    progress!(format, "╚════════════════════════════════════════════════════════════════╝\n");
 
-    println!("📊 统计信息:");
-    println!("   总虚拟机数: {}", total_vms);
-    println!("   一致: {} ✅", consistent_vms);
-    println!("   不一致: {} ❌", inconsistent_vms);
-    println!(
-        "   一致性: {:.1}%\n",
-        if total_vms > 0 {
-            (consistent_vms as f64 / total_vms as f64) * 100.0
-        } else {
-            0.0
-        }
-    );
+    progress!(format, "📋 步骤 1/4: 登录 VDI 平台...");
+    let checker = ConsistencyChecker::connect(config_path, profile).await?;
+    progress!(format, "   ✅ VDI 登录成功\n");
 
-    // 根据格式输出详细结果
-    match format {
-        "json" => output_json(&all_results, only_diff)?,
-        "yaml" => output_yaml(&all_results, only_diff)?,
-        _ => output_table(&all_results, only_diff),
-    }
+    let report = ConsistencyReport::new(checker.check(format, true).await?, only_diff);
+    print_rendered(&report, format)?;
 
-    if inconsistent_vms > 0 {
+    if report.consistent < report.total {
         std::process::exit(1);
     }
 
     Ok(())
 }
 
-/// 表格格式输出
-fn output_table(results: &[CompareResult], only_diff: bool) {
-    println!("📋 详细对比结果:\n");
-    println!(
-        "{:<20} {:<15} {:<20} {:<15} {:<10}",
-        "虚拟机名称", "主机", "VDI状态", "libvirt状态", "一致性"
-    );
-    println!("{}", "-".repeat(80));
+/// `vdi verify --watch` 的参数
+#[derive(Debug, Clone, Copy)]
+struct WatchOptions {
+    /// 两轮比对之间的间隔
+    interval: Duration,
 
-    for result in results {
-        if only_diff && result.consistent {
-            continue;
-        }
+    /// 某一轮没有不一致的虚拟机时立即结束
+    until_consistent: bool,
 
-        let status_icon = if result.consistent { "✅" } else { "❌" };
-        println!(
-            "{:<20} {:<15} {:<20} {:<20} {}",
-            result.vm_name, result.host, result.vdi_status, result.libvirt_status, status_icon
-        );
-    }
+    /// 最长监视时间
+    timeout: Option<Duration>,
 }
 
-/// JSON 格式输出
-fn output_json(results: &[CompareResult], only_diff: bool) -> Result<()> {
-    let filtered: Vec<_> = if only_diff {
-        results.iter().filter(|r| !r.consistent).collect()
-    } else {
-        results.iter().collect()
+/// 与上一轮相比的变化 (按主机与虚拟机名称对应): 新出现的不一致, 以及恢复一致 (或已不存在) 的虚拟机
+fn consistency_delta(previous: &[CompareResult], current: &[CompareResult]) -> (Vec<CompareResult>, Vec<CompareResult>) {
+    let inconsistent = |results: &[CompareResult], result: &CompareResult| {
+        results
+            .iter()
+            .any(|other| !other.consistent && other.host == result.host && other.vm_name == result.vm_name)
     };
 
-    let json_data: Vec<serde_json::Value> = filtered
+    let newly_inconsistent = current
         .iter()
-        .map(|r| {
-            json!({
-                "vm_name": r.vm_name,
-                "host": r.host,
-                "vdi_status": r.vdi_status,
-                "libvirt_status": r.libvirt_status,
-                "consistent": r.consistent
-            })
+        .filter(|result| !result.consistent && !inconsistent(previous, result))
+        .cloned()
+        .collect();
+    let recovered = previous
+        .iter()
+        .filter(|result| !result.consistent && !inconsistent(current, result))
+        .map(|result| {
+            current
+                .iter()
+                .find(|other| other.host == result.host && other.vm_name == result.vm_name)
+                .unwrap_or(result)
+                .clone()
         })
         .collect();
 
-    println!("{}", serde_json::to_string_pretty(&json_data)?);
-    Ok(())
+    (newly_inconsistent, recovered)
 }
 
-/// YAML 格式输出
-fn output_yaml(results: &[CompareResult], only_diff: bool) -> Result<()> {
-    let filtered: Vec<_> = if only_diff {
-        results.iter().filter(|r| !r.consistent).collect()
-    } else {
-        results.iter().collect()
-    };
+/// 监视模式中一轮比对的变化
+#[derive(Debug, Serialize)]
+struct WatchPass {
+    pass: usize,
+    checked_at: chrono::DateTime<Utc>,
+    total: usize,
+    inconsistent: usize,
+    newly_inconsistent: Vec<CompareResult>,
+    recovered: Vec<CompareResult>,
+}
+
+impl Render for WatchPass {
+    fn to_table(&self) -> String {
+        let mut lines = vec![format!(
+            "[{}] 第 {} 轮: 共 {} 台, 不一致 {} 台{}",
+            self.checked_at.with_timezone(&Local).format("%H:%M:%S"),
+            self.pass,
+            self.total,
+            self.inconsistent,
+            if self.newly_inconsistent.is_empty() && self.recovered.is_empty() {
+                " (无变化)".to_string()
+            } else {
+                format!(" (新增 {}, 恢复 {})", self.newly_inconsistent.len(), self.recovered.len())
+            }
+        )];
 
-    for result in filtered {
-        println!("- vm_name: {}", result.vm_name);
-        println!("  host: {}", result.host);
-        println!("  vdi_status: {}", result.vdi_status);
-        println!("  libvirt_status: {}", result.libvirt_status);
-        println!("  consistent: {}", result.consistent);
-        println!();
+        for result in &self.newly_inconsistent {
+            lines.push(format!(
+                "   ❌ {} ({}): VDI {} / libvirt {}",
+                result.vm_name, result.host, result.vdi_status, result.libvirt_status
+            ));
+        }
+        for result in &self.recovered {
+            lines.push(format!(
+                "   ✅ {} ({}): VDI {} / libvirt {}",
+                result.vm_name, result.host, result.vdi_status, result.libvirt_status
+            ));
+        }
+        lines.join("\n")
     }
+}
 
-    Ok(())
+/// 监视结束时的汇总
+#[derive(Debug, Default, Serialize)]
+struct WatchSummary {
+    /// 完成的比对轮数
+    passes: usize,
+    /// 最后一轮的不一致数
+    inconsistent: usize,
+    /// 累计新出现的不一致次数
+    newly_inconsistent: usize,
+    /// 累计恢复一致的次数
+    recovered: usize,
+    /// 比对失败的轮数
+    failed_passes: usize,
+    elapsed_secs: u64,
+    /// 结束原因: interrupted / consistent / timeout
+    reason: &'static str,
 }
 
-/// 列出 VDI 平台的所有主机
-async fn list_hosts(config_path: &str) -> Result<()> {
-    println!("📋 VDI 平台主机列表\n");
+impl WatchSummary {
+    fn record(&mut self, pass: &WatchPass) {
+        self.passes = pass.pass;
+        self.inconsistent = pass.inconsistent;
+        self.newly_inconsistent += pass.newly_inconsistent.len();
+        self.recovered += pass.recovered.len();
+    }
+}
 
-    let config = TestConfig::load_from_path(config_path)?;
-    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+impl Render for WatchSummary {
+    fn to_table(&self) -> String {
+        let reason = match self.reason {
+            "consistent" => "全部一致",
+            "timeout" => "超时",
+            _ => "已中断",
+        };
+        [
+            format!("\n📊 监视结束 ({}), 用时 {} 秒", reason, self.elapsed_secs),
+            format!("   比对轮数: {} (失败 {})", self.passes, self.failed_passes),
+            format!("   累计新增不一致: {}", self.newly_inconsistent),
+            format!("   累计恢复一致: {}", self.recovered),
+            format!("   当前不一致: {}", self.inconsistent),
+        ]
+        .join("\n")
+    }
+}
 
-    let client = create_vdi_client(vdi_config).await?;
-    let hosts = client.host().list_all().await?;
+/// 定期重新比对, 只输出与上一轮相比的变化
+///
+/// Ctrl-C 或超过 `--timeout` 结束时输出汇总, 最后一轮有不一致时以退出码 1 退出;
+/// `--until-consistent` 在某一轮全部一致时以退出码 0 结束, 超时以退出码 1 结束。
+async fn watch_consistency(config_path: &str, profile: Option<&str>, format: &str, options: WatchOptions) -> Result<()> {
+    let format = output_format(Some(format))?;
+    if options.interval.is_zero() {
+        anyhow::bail!("--interval 必须大于 0");
+    }
 
-    println!(
-        "{:<20} {:<20} {:<10} {:<15} {:<15}",
-        "主机名", "IP地址", "状态", "CPU(核)", "内存(GB)"
+    let checker = ConsistencyChecker::connect(config_path, profile).await?;
+    progress!(
+        format,
+        "👀 监视 VDI 与 libvirt 一致性, 每 {} 秒比对一次 (Ctrl-C 结束)\n",
+        options.interval.as_secs()
     );
-    println!("{}", "-".repeat(80));
 
-    for host in &hosts {
-        let name = host["name"].as_str().unwrap_or("");
-        let ip = host["ip"].as_str().unwrap_or("");
-        let status = match host["status"].as_i64().unwrap_or(-1) {
-            1 => "在线 ✅",
-            _ => "离线 ❌",
+    let started = Instant::now();
+    let deadline = options.timeout.map(|timeout| started + timeout);
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    let mut summary = WatchSummary {
+        reason: "interrupted",
+        ..Default::default()
+    };
+    let mut previous: Vec<CompareResult> = Vec::new();
+
+    loop {
+        let result = tokio::select! {
+            result = checker.check(format, false) => result,
+            _ = &mut ctrl_c => break,
         };
-        let cpu = host["cpuSize"].as_i64().unwrap_or(0);
-        let memory_gb = host["memory"].as_f64().unwrap_or(0.0);
 
-        println!(
-            "{:<20} {:<20} {:<10} {:<15} {:<15.2}",
-            name, ip, status, cpu, memory_gb
-        );
+        match result {
+            Ok(results) => {
+                let (newly_inconsistent, recovered) = consistency_delta(&previous, &results);
+                let pass = WatchPass {
+                    pass: summary.passes + 1,
+                    checked_at: Utc::now(),
+                    total: results.len(),
+                    inconsistent: results.iter().filter(|result| !result.consistent).count(),
+                    newly_inconsistent,
+                    recovered,
+                };
+                summary.record(&pass);
+                print_rendered(&pass, format)?;
+                previous = results;
+
+                if options.until_consistent && pass.inconsistent == 0 {
+                    summary.reason = "consistent";
+                    break;
+                }
+            }
+            Err(e) => {
+                summary.failed_passes += 1;
+                progress!(format, "⚠️  比对失败: {:#}", e);
+            }
+        }
+
+        let mut wait = options.interval;
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                summary.reason = "timeout";
+                break;
+            }
+            wait = wait.min(remaining);
+        }
+
+        tokio::select! {
+            _ = tokio::time::sleep(wait) => {}
+            _ = &mut ctrl_c => break,
+        }
     }
 
-    println!("\n总计: {} 个主机", hosts.len());
+    summary.elapsed_secs = started.elapsed().as_secs();
+    print_rendered(&summary, format)?;
+
+    // --until-consistent 超时说明没有等到全部一致; 其余情况按最后一轮的结果
+    let failed = match summary.reason {
+        "consistent" => false,
+        "timeout" if options.until_consistent => true,
+        _ => summary.inconsistent > 0,
+    };
+    if failed {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
 
-/// 列出 VDI 平台的所有虚拟机
-async fn list_vms(config_path: &str, host_filter: Option<&str>) -> Result<()> {
-    println!("📋 VDI 平台虚拟机列表\n");
-
-    let config = TestConfig::load_from_path(config_path)?;
-    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
-
-    let client = create_vdi_client(vdi_config).await?;
+/// VDI 平台主机列表 (`atp vdi list-hosts`)
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct VdiHostList {
+    hosts: Vec<VdiHostRow>,
+}
 
-    let domains = client.domain().list_all().await?;
-    let hosts_vec = client.host().list_all().await?;
+/// VDI 平台主机列表中的一台主机
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct VdiHostRow {
+    id: String,
+    name: String,
+    ip: String,
+    online: bool,
+    cpu: i64,
+    memory_gb: f64,
+}
 
-    // 建立主机ID到名称的映射
-    let mut host_id_to_name: HashMap<String, String> = HashMap::new();
-    for host in &hosts_vec {
-        let host_id = host["id"].as_str().unwrap_or("").to_string();
-        let host_name = host["name"].as_str().unwrap_or("").to_string();
-        if !host_id.is_empty() && !host_name.is_empty() {
-            host_id_to_name.insert(host_id, host_name);
+impl VdiHostRow {
+    fn from_value(host: &serde_json::Value) -> Self {
+        Self {
+            id: host["id"].as_str().unwrap_or("").to_string(),
+            name: host["name"].as_str().unwrap_or("").to_string(),
+            ip: host["ip"].as_str().unwrap_or("").to_string(),
+            online: host["status"].as_i64() == Some(1),
+            cpu: host["cpuSize"].as_i64().unwrap_or(0),
+            memory_gb: host["memory"].as_f64().unwrap_or(0.0),
         }
     }
+}
 
-    println!(
-        "{:<25} {:<20} {:<15} {:<10} {:<15}",
-        "虚拟机名称", "主机", "状态", "CPU(核)", "内存(GB)"
-    );
-    println!("{}", "-".repeat(90));
-
-    let mut count = 0;
-    for domain in &domains {
-        let name = domain["name"].as_str().unwrap_or("");
-        let host_id = domain["hostId"].as_str().unwrap_or("");
-        let host_name = host_id_to_name.get(host_id).map(|s| s.as_str()).unwrap_or("");
+impl Render for VdiHostList {
+    fn to_table(&self) -> String {
+        let mut lines = vec![
+            "📋 VDI 平台主机列表\n".to_string(),
+            format!(
+                "{:<20} {:<20} {:<10} {:<15} {:<15}",
+                "主机名", "IP地址", "状态", "CPU(核)", "内存(GB)"
+            ),
+            "-".repeat(80),
+        ];
 
-        // 主机过滤
-        if let Some(filter) = host_filter {
-            if host_name != filter {
-                continue;
-            }
+        for host in &self.hosts {
+            let status = if host.online { "在线 ✅" } else { "离线 ❌" };
+            lines.push(format!(
+                "{:<20} {:<20} {:<10} {:<15} {:<15.2}",
+                host.name, host.ip, status, host.cpu, host.memory_gb
+            ));
         }
 
-        let status = match domain["status"].as_i64().unwrap_or(-1) {
-            0 => "关机 ⚪",
-            1 => "运行中 ✅",
-            2 => "挂起 🟡",
-            3 => "休眠 🌙",
-            5 => "操作中 ⚙️",
-            6 => "升级中 ⬆️",
-            _ => "未知 ⚠️",
-        };
-        let cpu = domain["cpuNum"].as_i64().unwrap_or(0);
-        let memory_gb = domain["memory"].as_f64().unwrap_or(0.0) / 1024.0;
-
-        println!(
-            "{:<25} {:<20} {:<15} {:<10} {:<15.2}",
-            name, host_name, status, cpu, memory_gb
-        );
-        count += 1;
+        lines.push(format!("\n总计: {} 个主机", self.hosts.len()));
+        lines.join("\n")
     }
-
-    println!("\n总计: {} 个虚拟机", count);
-
-    Ok(())
 }
 
-/// 同步 VDI 主机到本地配置
-async fn sync_hosts(config_path: &str, test_connection: bool) -> Result<()> {
-    println!("🔄 同步 VDI 主机到本地配置\n");
+/// 列出 VDI 平台的所有主机
+async fn list_hosts(config_path: &str, profile: Option<&str>) -> Result<()> {
+    let format = output_format(None)?;
 
-    let config = TestConfig::load_from_path(config_path)?;
+    let config = load_config(config_path, profile)?;
     let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
 
     let client = create_vdi_client(vdi_config).await?;
     let hosts = client.host().list_all().await?;
 
-    println!("📊 发现 {} 个主机:\n", hosts.len());
+    let list = VdiHostList {
+        hosts: hosts.iter().map(VdiHostRow::from_value).collect(),
+    };
+    print_rendered(&list, format)
+}
 
-    for (i, host) in hosts.iter().enumerate() {
+/// 单个孤儿资源的清理结果
+#[derive(Debug, Clone, Serialize)]
+struct OrphanCleanupItem {
+    resource_type: String,
+    resource_id: String,
+    name: Option<String>,
+
+    /// 删除失败时的错误
+    error: Option<String>,
+}
+
+/// 孤儿资源清理结果 (`atp vdi cleanup-orphans`)
+#[derive(Debug, Serialize)]
+struct OrphanCleanupSummary {
+    report_id: i64,
+    total: usize,
+    failed: usize,
+    results: Vec<OrphanCleanupItem>,
+}
+
+impl OrphanCleanupSummary {
+    fn new(report_id: i64, results: Vec<OrphanCleanupItem>) -> Self {
+        let failed = results.iter().filter(|result| result.error.is_some()).count();
+        Self {
+            report_id,
+            total: results.len(),
+            failed,
+            results,
+        }
+    }
+}
+
+impl Render for OrphanCleanupSummary {
+    fn to_table(&self) -> String {
+        if self.results.is_empty() {
+            return format!("ℹ 报告 {} 中没有待清理的孤儿资源", self.report_id);
+        }
+
+        let mut lines = vec![String::new()];
+        for result in &self.results {
+            lines.push(match &result.error {
+                None => format!("   ✅ 已删除 {} {}", result.resource_type, result.resource_id),
+                Some(error) => format!("   ❌ 删除 {} {} 失败: {}", result.resource_type, result.resource_id, error),
+            });
+        }
+        if self.failed == 0 {
+            lines.push("\n✅ 孤儿资源已全部清理".to_string());
+        } else {
+            lines.push(format!(
+                "\n孤儿资源清理完成: 成功 {} 个, 失败 {} 个",
+                self.total - self.failed,
+                self.failed
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// 按报告中的环境检查清单清理未清理的新增资源
+///
+/// 先删除虚拟机再删除桌面池; 删除成功的资源在报告中标记为已释放,
+/// 失败的保留孤儿状态并记录错误, 可以再次执行本命令重试。
+async fn cleanup_orphans(config_path: &str, profile: Option<&str>, report_id: i64, guard: &DestructiveGuard) -> Result<()> {
+    let format = output_format(None)?;
+    guard.ensure_confirmable(format)?;
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+
+    storage
+        .reports()
+        .get_by_id(report_id)
+        .await?
+        .with_context(|| format!("报告 {} 不存在", report_id))?;
+
+    let orphaned = CleanupStatus::Orphaned.as_str();
+    let mut orphans: Vec<_> = storage
+        .reports()
+        .get_resources(report_id)
+        .await?
+        .into_iter()
+        .filter(|resource| resource.cleanup_status == orphaned)
+        .collect();
+
+    // 桌面池排在虚拟机之后删除
+    orphans.sort_by_key(|resource| resource.resource_type == ResourceKind::DeskPool.as_str());
+
+    let targets: Vec<String> = orphans
+        .iter()
+        .map(|resource| {
+            format!(
+                "{} {} ({})",
+                resource.resource_type,
+                resource.resource_id,
+                resource.name.as_deref().unwrap_or("-")
+            )
+        })
+        .collect();
+    let action = format!("删除报告 {} 中的孤儿资源", report_id);
+    let confirmed = match guard.confirm(format, &action, &targets)? {
+        Confirmation::Proceed(confirmed) => confirmed,
+        Confirmation::DryRun => return Ok(()),
+        Confirmation::Cancelled if orphans.is_empty() => {
+            return print_rendered(&OrphanCleanupSummary::new(report_id, Vec::new()), format);
+        }
+        Confirmation::Cancelled => return Ok(()),
+    };
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+    let client = create_vdi_client(vdi_config).await?;
+
+    let summary = delete_orphans(confirmed, &client, &storage, report_id, &orphans).await?;
+    print_rendered(&summary, format)?;
+
+    if summary.failed > 0 {
+        anyhow::bail!("{} 个资源清理失败", summary.failed);
+    }
+
+    Ok(())
+}
+
+/// 逐个删除孤儿资源, 并在报告中更新资源的清理状态
+async fn delete_orphans(
+    _confirmed: Confirmed,
+    client: &VdiClient,
+    storage: &Storage,
+    report_id: i64,
+    orphans: &[ReportResourceRecord],
+) -> Result<OrphanCleanupSummary> {
+    let orphaned = CleanupStatus::Orphaned.as_str();
+    let mut results = Vec::with_capacity(orphans.len());
+    for resource in orphans {
+        let result = if resource.resource_type == ResourceKind::Domain.as_str() {
+            client.domain().delete(&resource.resource_id).await.map_err(|e| e.to_string())
+        } else if resource.resource_type == ResourceKind::DeskPool.as_str() {
+            client.desk_pool().delete(&resource.resource_id).await.map_err(|e| e.to_string())
+        } else {
+            Err(format!("不支持清理 {} 类型的资源", resource.resource_type))
+        };
+
+        let error = match result {
+            Ok(()) => {
+                storage
+                    .reports()
+                    .update_resource_status(resource.id, CleanupStatus::Released.as_str(), None)
+                    .await?;
+                None
+            }
+            Err(e) => {
+                error!("删除 {} {} 失败: {}", resource.resource_type, resource.resource_id, e);
+                storage
+                    .reports()
+                    .update_resource_status(resource.id, orphaned, Some(&e))
+                    .await?;
+                Some(e)
+            }
+        };
+        results.push(OrphanCleanupItem {
+            resource_type: resource.resource_type.clone(),
+            resource_id: resource.resource_id.clone(),
+            name: resource.name.clone(),
+            error,
+        });
+    }
+
+    Ok(OrphanCleanupSummary::new(report_id, results))
+}
+
+/// list-vms 可用的状态名称与 VDI 状态码
+const VM_STATUS_NAMES: &[(&str, i64)] = &[
+    ("shutoff", 0),
+    ("running", 1),
+    ("paused", 2),
+    ("hibernated", 3),
+    ("busy", 5),
+    ("upgrading", 6),
+];
+
+/// 解析状态过滤条件 (英文名称或中文状态名)
+fn parse_vm_status(value: &str) -> Result<i64> {
+    let value = value.trim();
+    VM_STATUS_NAMES
+        .iter()
+        .find(|(name, code)| name.eq_ignore_ascii_case(value) || domain_status_label(*code) == value)
+        .map(|(_, code)| *code)
+        .with_context(|| {
+            let names: Vec<_> = VM_STATUS_NAMES.iter().map(|(name, _)| *name).collect();
+            format!("未知的状态 '{}', 可选: {}", value, names.join(", "))
+        })
+}
+
+/// 带图标的状态名称
+fn vm_status_display(status: i64) -> &'static str {
+    match status {
+        0 => "关机 ⚪",
+        1 => "运行中 ✅",
+        2 => "挂起 🟡",
+        3 => "休眠 🌙",
+        5 => "操作中 ⚙️",
+        6 => "升级中 ⬆️",
+        _ => "未知 ⚠️",
+    }
+}
+
+/// list-vms 排序字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VmSortKey {
+    Name,
+    Status,
+    Host,
+    /// 内存从大到小
+    Memory,
+}
+
+impl VmSortKey {
+    const ALL: [(&'static str, VmSortKey); 4] = [
+        ("name", VmSortKey::Name),
+        ("status", VmSortKey::Status),
+        ("host", VmSortKey::Host),
+        ("memory", VmSortKey::Memory),
+    ];
+
+    fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        Self::ALL
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(value))
+            .map(|(_, key)| *key)
+            .with_context(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|(name, _)| *name).collect();
+                format!("未知的排序字段 '{}', 可选: {}", value, names.join(", "))
+            })
+    }
+}
+
+/// list-vms 表格列
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum VmColumn {
+    Name,
+    Host,
+    Status,
+    Cpu,
+    Memory,
+    Ip,
+    User,
+}
+
+impl VmColumn {
+    const ALL: [VmColumn; 7] = [
+        VmColumn::Name,
+        VmColumn::Host,
+        VmColumn::Status,
+        VmColumn::Cpu,
+        VmColumn::Memory,
+        VmColumn::Ip,
+        VmColumn::User,
+    ];
+
+    /// 未指定 --columns 时显示的列
+    const DEFAULT: [VmColumn; 5] = [
+        VmColumn::Name,
+        VmColumn::Host,
+        VmColumn::Status,
+        VmColumn::Cpu,
+        VmColumn::Memory,
+    ];
+
+    /// 命令行中的列名
+    fn key(self) -> &'static str {
+        match self {
+            VmColumn::Name => "name",
+            VmColumn::Host => "host",
+            VmColumn::Status => "status",
+            VmColumn::Cpu => "cpu",
+            VmColumn::Memory => "memory",
+            VmColumn::Ip => "ip",
+            VmColumn::User => "user",
+        }
+    }
+
+    fn header(self) -> &'static str {
+        match self {
+            VmColumn::Name => "虚拟机名称",
+            VmColumn::Host => "主机",
+            VmColumn::Status => "状态",
+            VmColumn::Cpu => "CPU(核)",
+            VmColumn::Memory => "内存(GB)",
+            VmColumn::Ip => "IP",
+            VmColumn::User => "用户",
+        }
+    }
+
+    fn width(self) -> usize {
+        match self {
+            VmColumn::Name => 25,
+            VmColumn::Host => 20,
+            VmColumn::Status => 15,
+            VmColumn::Cpu => 10,
+            VmColumn::Memory => 15,
+            VmColumn::Ip => 16,
+            VmColumn::User => 15,
+        }
+    }
+}
+
+/// 解析 --columns, 为空时使用默认列
+fn parse_vm_columns(columns: &[String]) -> Result<Vec<VmColumn>> {
+    if columns.is_empty() {
+        return Ok(VmColumn::DEFAULT.to_vec());
+    }
+
+    columns
+        .iter()
+        .map(|column| {
+            let column = column.trim();
+            VmColumn::ALL
+                .iter()
+                .find(|candidate| candidate.key().eq_ignore_ascii_case(column))
+                .copied()
+                .with_context(|| {
+                    let keys: Vec<_> = VmColumn::ALL.iter().map(|c| c.key()).collect();
+                    format!("未知的列 '{}', 可选: {}", column, keys.join(", "))
+                })
+        })
+        .collect()
+}
+
+/// list-vms 的过滤、排序与列选择
+#[derive(Debug, Clone, PartialEq)]
+struct VmListOptions {
+    host: Option<String>,
+    status: Option<i64>,
+    user: Option<String>,
+    sort: Option<VmSortKey>,
+    columns: Vec<VmColumn>,
+}
+
+impl VmListOptions {
+    /// 校验命令行参数 (在连接 VDI 平台之前完成)
+    fn parse(
+        host: Option<String>,
+        status: Option<&str>,
+        user: Option<String>,
+        sort: Option<&str>,
+        columns: &[String],
+    ) -> Result<Self> {
+        Ok(Self {
+            host,
+            status: status.map(parse_vm_status).transpose()?,
+            user,
+            sort: sort.map(VmSortKey::parse).transpose()?,
+            columns: parse_vm_columns(columns)?,
+        })
+    }
+}
+
+/// list-vms 表格中的一台虚拟机
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct VmRow {
+    name: String,
+    host: String,
+    status: i64,
+    cpu: i64,
+    memory_mb: f64,
+    ip: String,
+    user: String,
+}
+
+/// 把虚拟机列表转换为表格行 (主机 ID 换成主机名)
+fn vm_rows(domains: &[serde_json::Value], host_id_to_name: &HashMap<String, String>) -> Vec<VmRow> {
+    domains
+        .iter()
+        .map(|domain| {
+            let host_id = domain["hostId"].as_str().unwrap_or("");
+            VmRow {
+                name: domain["name"].as_str().unwrap_or("").to_string(),
+                host: host_id_to_name.get(host_id).cloned().unwrap_or_default(),
+                status: domain["status"].as_i64().unwrap_or(-1),
+                cpu: domain["cpuNum"].as_i64().unwrap_or(0),
+                memory_mb: domain["memory"].as_f64().unwrap_or(0.0),
+                ip: domain["ip"].as_str().unwrap_or("").to_string(),
+                user: domain["userName"].as_str().unwrap_or("").to_string(),
+            }
+        })
+        .collect()
+}
+
+/// 按主机、状态、用户过滤
+fn filter_vms(rows: Vec<VmRow>, options: &VmListOptions) -> Vec<VmRow> {
+    rows.into_iter()
+        .filter(|row| options.host.as_ref().is_none_or(|host| &row.host == host))
+        .filter(|row| options.status.is_none_or(|status| row.status == status))
+        .filter(|row| options.user.as_ref().is_none_or(|user| &row.user == user))
+        .collect()
+}
+
+/// 排序 (名称作为次要排序字段)
+fn sort_vms(rows: &mut [VmRow], key: VmSortKey) {
+    rows.sort_by(|a, b| {
+        let primary = match key {
+            VmSortKey::Name => std::cmp::Ordering::Equal,
+            VmSortKey::Status => a.status.cmp(&b.status),
+            VmSortKey::Host => a.host.cmp(&b.host),
+            VmSortKey::Memory => b.memory_mb.total_cmp(&a.memory_mb),
+        };
+        primary.then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+/// 某一列的显示内容
+fn vm_cell(row: &VmRow, column: VmColumn) -> String {
+    match column {
+        VmColumn::Name => row.name.clone(),
+        VmColumn::Host => row.host.clone(),
+        VmColumn::Status => vm_status_display(row.status).to_string(),
+        VmColumn::Cpu => row.cpu.to_string(),
+        VmColumn::Memory => format!("{:.2}", row.memory_mb / 1024.0),
+        VmColumn::Ip => row.ip.clone(),
+        VmColumn::User => row.user.clone(),
+    }
+}
+
+/// 按列宽拼接一行
+fn format_vm_line<'a>(columns: &[VmColumn], cells: impl IntoIterator<Item = &'a str>) -> String {
+    columns
+        .iter()
+        .zip(cells)
+        .map(|(column, cell)| format!("{:<width$}", cell, width = column.width()))
+        .collect::<Vec<_>>()
+        .join(" ")
+        .trim_end()
+        .to_string()
+}
+
+/// 虚拟机列表 (`atp vdi list-vms`), json / yaml 输出包含所有字段, 不受 `--columns` 影响
+#[derive(Debug, Serialize)]
+#[serde(transparent)]
+struct VmList {
+    rows: Vec<VmRow>,
+    #[serde(skip)]
+    columns: Vec<VmColumn>,
+}
+
+impl Render for VmList {
+    fn to_table(&self) -> String {
+        let columns = &self.columns;
+        let mut lines = vec![
+            "📋 VDI 平台虚拟机列表\n".to_string(),
+            format_vm_line(columns, columns.iter().map(|column| column.header())),
+            "-".repeat(columns.iter().map(|column| column.width() + 1).sum()),
+        ];
+
+        for row in &self.rows {
+            let cells: Vec<String> = columns.iter().map(|column| vm_cell(row, *column)).collect();
+            lines.push(format_vm_line(columns, cells.iter().map(String::as_str)));
+        }
+
+        lines.push(format!("\n总计: {} 个虚拟机", self.rows.len()));
+        lines.join("\n")
+    }
+}
+
+/// 列出 VDI 平台的所有虚拟机
+///
+/// VDI 平台的虚拟机列表接口不支持按状态、用户过滤, 过滤与排序都在本地完成。
+async fn list_vms(config_path: &str, profile: Option<&str>, options: &VmListOptions) -> Result<()> {
+    let format = output_format(None)?;
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+
+    let client = create_vdi_client(vdi_config).await?;
+
+    let domains = client.domain().list_all().await?;
+    let hosts_vec = client.host().list_all().await?;
+
+    // 顺便更新本地虚拟机缓存, 记录状态变化
+    if let Err(e) = update_vm_cache(&domains).await {
+        warn!("更新虚拟机缓存失败: {}", e);
+    }
+
+    // 建立主机ID到名称的映射
+    let mut host_id_to_name: HashMap<String, String> = HashMap::new();
+    for host in &hosts_vec {
+        let host_id = host["id"].as_str().unwrap_or("").to_string();
+        let host_name = host["name"].as_str().unwrap_or("").to_string();
+        if !host_id.is_empty() && !host_name.is_empty() {
+            host_id_to_name.insert(host_id, host_name);
+        }
+    }
+
+    let mut rows = filter_vms(vm_rows(&domains, &host_id_to_name), options);
+    if let Some(key) = options.sort {
+        sort_vms(&mut rows, key);
+    }
+
+    let list = VmList {
+        rows,
+        columns: options.columns.clone(),
+    };
+    print_rendered(&list, format)
+}
+
+/// 把虚拟机列表同步到本地缓存
+async fn update_vm_cache(domains: &[serde_json::Value]) -> Result<()> {
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Storage::from_manager(&storage_manager);
+    storage
+        .vm_cache()
+        .sync_all(&records_from_listing(domains, Utc::now()))
+        .await?;
+    Ok(())
+}
+
+/// 显示虚拟机的状态变更历史
+///
+/// 历史记录来自本地虚拟机缓存, 只有同步过的状态变化才会出现
+/// (`atp vdi list-vms` 或 `--refresh` 都会同步)。
+/// 解析批量操作名称
+fn parse_batch_operation(value: &str) -> Result<BatchOperation> {
+    match value.to_lowercase().as_str() {
+        "start" => Ok(BatchOperation::Start),
+        "shutdown" => Ok(BatchOperation::Shutdown),
+        "reboot" => Ok(BatchOperation::Reboot),
+        _ => anyhow::bail!("不支持的批量操作: {} (可选: start, shutdown, reboot)", value),
+    }
+}
+
+/// 批量操作结果 (`atp vdi batch`)
+#[derive(Debug, Serialize)]
+struct BatchSummary {
+    operation: BatchOperation,
+    total: usize,
+    failed: usize,
+    results: Vec<BatchItemResult>,
+}
+
+impl BatchSummary {
+    fn new(operation: BatchOperation, results: Vec<BatchItemResult>) -> Self {
+        let failed = results.iter().filter(|result| !result.is_success()).count();
+        Self {
+            operation,
+            total: results.len(),
+            failed,
+            results,
+        }
+    }
+}
+
+impl Render for BatchSummary {
+    fn to_table(&self) -> String {
+        if self.results.is_empty() {
+            return "ℹ 没有匹配的虚拟机".to_string();
+        }
+
+        let mut lines = vec![String::new()];
+        for result in &self.results {
+            lines.push(match &result.error {
+                None => format!("   ✅ {} ({})", result.vm.name, result.vm.id),
+                Some(error) => format!("   ❌ {} ({}): {}", result.vm.name, result.vm.id, error),
+            });
+        }
+        lines.push(format!(
+            "\n{} 完成: 成功 {} 台, 失败 {} 台",
+            self.operation.label(),
+            self.total - self.failed,
+            self.failed
+        ));
+        lines.join("\n")
+    }
+}
+
+/// 对按名称通配符、桌面池或 ID 列表选出的虚拟机执行批量操作
+///
+/// 执行前列出目标虚拟机并请求确认 (`--yes` 跳过); 有虚拟机失败时以退出码 1 退出。
+/// `--balanced` 启动时按批指定负载最低的主机, 放置决策记录在日志中。
+async fn batch_operation(
+    config_path: &str,
+    profile: Option<&str>,
+    operation: &str,
+    target: BatchTargetArgs,
+    balanced: bool,
+    guard: &DestructiveGuard,
+    format: &str,
+) -> Result<()> {
+    let format = output_format(Some(format))?;
+    let operation = parse_batch_operation(operation)?;
+    if balanced && operation != BatchOperation::Start {
+        anyhow::bail!("--balanced 只能用于 start");
+    }
+    let target = Target::from_args(target.pattern, target.pool, target.id)?;
+    guard.ensure_confirmable(format)?;
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+    let client = create_vdi_client(vdi_config).await?;
+    let ops = VdiBatchOps::new(Arc::new(client));
+
+    let vms = ops.resolve_target(&target, CacheMode::Fresh).await?;
+    let targets: Vec<String> = vms
+        .iter()
+        .map(|vm| format!("{} ({}) [{}]", vm.name, vm.id, vm.status))
+        .collect();
+    let confirmed = match guard.confirm(format, &format!("批量{} {}", operation.label(), target), &targets)? {
+        Confirmation::Proceed(confirmed) => confirmed,
+        Confirmation::DryRun => return Ok(()),
+        Confirmation::Cancelled if vms.is_empty() => {
+            return print_rendered(&BatchSummary::new(operation, Vec::new()), format);
+        }
+        Confirmation::Cancelled => return Ok(()),
+    };
+
+    let results = execute_batch(confirmed, &ops, operation, vms, balanced).await?;
+    let summary = BatchSummary::new(operation, results);
+    print_rendered(&summary, format)?;
+
+    if summary.failed > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// 对已确认的虚拟机执行批量操作
+async fn execute_batch(
+    _confirmed: Confirmed,
+    ops: &VdiBatchOps,
+    operation: BatchOperation,
+    vms: Vec<VmMatchResult>,
+    balanced: bool,
+) -> Result<Vec<BatchItemResult>> {
+    if balanced {
+        Ok(ops.batch_start_balanced(vms).await?)
+    } else {
+        Ok(ops.batch_vms(operation, vms).await)
+    }
+}
+
+/// 用户分配结果 (`atp vdi assign`)
+#[derive(Debug, Serialize)]
+struct AssignSummary {
+    total: usize,
+    failed: usize,
+    results: Vec<AssignItemResult>,
+}
+
+impl Render for AssignSummary {
+    fn to_table(&self) -> String {
+        let mut lines = vec![String::new()];
+        for result in &self.results {
+            lines.push(match &result.error {
+                None => format!("   ✅ {} -> {}", result.mapping.user, result.mapping.vm),
+                Some(error) => format!("   ❌ {} -> {}: {}", result.mapping.user, result.mapping.vm, error),
+            });
+        }
+        lines.push(format!("\n分配完成: 成功 {} 条, 失败 {} 条", self.total - self.failed, self.failed));
+        lines.join("\n")
+    }
+}
+
+/// 按 CSV 映射把用户分配到虚拟机, 有映射失败时以退出码 1 退出
+async fn assign_users(
+    config_path: &str,
+    profile: Option<&str>,
+    mapping_path: &str,
+    guard: &DestructiveGuard,
+    format: &str,
+) -> Result<()> {
+    let format = output_format(Some(format))?;
+    let content = std::fs::read_to_string(mapping_path)
+        .with_context(|| format!("读取分配映射失败: {}", mapping_path))?;
+    let mappings = parse_assign_mapping(&content)?;
+    guard.ensure_confirmable(format)?;
+
+    if mappings.is_empty() {
+        anyhow::bail!("分配映射为空: {}", mapping_path);
+    }
+    let targets: Vec<String> = mappings
+        .iter()
+        .map(|mapping| format!("{} -> {}", mapping.user, mapping.vm))
+        .collect();
+    let Some(confirmed) = guard.confirm(format, &format!("用户分配 {}", mapping_path), &targets)?.proceed() else {
+        return Ok(());
+    };
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+    let client = create_vdi_client(vdi_config).await?;
+    let ops = VdiBatchOps::new(Arc::new(client));
+
+    let results = execute_assign(confirmed, &ops, &mappings).await?;
+    let summary = AssignSummary {
+        total: results.len(),
+        failed: results.iter().filter(|result| !result.is_success()).count(),
+        results,
+    };
+    print_rendered(&summary, format)?;
+
+    if summary.failed > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// 按已确认的映射分配用户
+async fn execute_assign(_confirmed: Confirmed, ops: &VdiBatchOps, mappings: &[AssignMapping]) -> Result<Vec<AssignItemResult>> {
+    Ok(ops.batch_assign(mappings, CacheMode::Fresh).await?)
+}
+
+/// `atp vdi clone` 的参数
+struct CloneOptions {
+    source: String,
+    count: usize,
+    name: String,
+    storage_pool: Option<String>,
+    start: bool,
+    wait_qga: bool,
+    clone_timeout: Duration,
+    qga_timeout: Duration,
+}
+
+/// 批量克隆结果 (`atp vdi clone`)
+#[derive(Debug, Serialize)]
+struct CloneSummary {
+    source: CloneSource,
+    total: usize,
+    failed: usize,
+    results: Vec<CloneItemResult>,
+}
+
+impl CloneSummary {
+    fn new(source: CloneSource, results: Vec<CloneItemResult>) -> Self {
+        let failed = results.iter().filter(|result| !result.is_success()).count();
+        Self {
+            source,
+            total: results.len(),
+            failed,
+            results,
+        }
+    }
+}
+
+impl Render for CloneSummary {
+    fn to_table(&self) -> String {
+        let cell = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        let mut lines = vec![
+            String::new(),
+            format!("{:<24} {:<38} {:<38} {}", "名称", "ID", "主机", "QGA"),
+        ];
+        for result in &self.results {
+            let qga = match result.qga_ready {
+                Some(true) => "✅",
+                Some(false) => "❌",
+                None => "-",
+            };
+            lines.push(format!(
+                "{:<24} {:<38} {:<38} {}",
+                result.name,
+                cell(&result.id),
+                cell(&result.host_id),
+                qga
+            ));
+        }
+
+        let failures: Vec<&CloneItemResult> = self.results.iter().filter(|result| !result.is_success()).collect();
+        if !failures.is_empty() {
+            lines.push("\n失败:".to_string());
+            for result in failures {
+                let phase = result.failed_phase.map(|phase| phase.label()).unwrap_or("未知");
+                lines.push(format!(
+                    "   ❌ {} [{}]: {}",
+                    result.name,
+                    phase,
+                    result.error.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+
+        lines.push(format!(
+            "\n从 {} 克隆完成: 成功 {} 台, 失败 {} 台",
+            self.source.name,
+            self.total - self.failed,
+            self.failed
+        ));
+        lines.join("\n")
+    }
+}
+
+/// 从虚拟机或模板批量克隆虚拟机
+///
+/// 命名模板在调用任何 API 之前校验; 克隆完成后可选均衡启动并等待 QGA 就绪。
+/// 每台虚拟机记录失败的阶段, 有虚拟机失败时以退出码 1 退出。
+async fn clone_vms(
+    config_path: &str,
+    profile: Option<&str>,
+    options: &CloneOptions,
+    guard: &DestructiveGuard,
+    format: &str,
+) -> Result<()> {
+    let format = output_format(Some(format))?;
+    let names = NameTemplate::parse(&options.name)?.names(options.count)?;
+    guard.ensure_confirmable(format)?;
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+    let client = create_vdi_client(vdi_config).await?;
+    let ops = VdiBatchOps::new(Arc::new(client));
+
+    let source = ops.resolve_clone_source(&options.source).await?;
+    if source.template && options.storage_pool.is_none() {
+        anyhow::bail!("克隆源 {} 是模板, 需要指定 --storage-pool", source.name);
+    }
+    let kind = if source.template { "模板" } else { "虚拟机" };
+    let prompt = format!("从{} {} ({}) 克隆 {} 台虚拟机", kind, source.name, source.id, names.len());
+    let Some(confirmed) = guard.confirm(format, &prompt, &names)?.proceed() else {
+        return Ok(());
+    };
+
+    let results = execute_clone(confirmed, &ops, &source, &names, options, format).await?;
+    let summary = CloneSummary::new(source, results);
+    print_rendered(&summary, format)?;
+
+    if summary.failed > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// 按已确认的名称克隆虚拟机, 按选项启动并等待 QGA 就绪
+async fn execute_clone(
+    _confirmed: Confirmed,
+    ops: &VdiBatchOps,
+    source: &CloneSource,
+    names: &[String],
+    options: &CloneOptions,
+    format: OutputFormat,
+) -> Result<Vec<CloneItemResult>> {
+    // 先连接主机, 避免克隆完成后才发现无法检查 QGA
+    let transport = if options.wait_qga { Some(transport_from_cli_config().await?) } else { None };
+
+    progress!(format, "正在克隆 {} 台虚拟机...", names.len());
+    let mut results = ops
+        .clone_batch_tracked(source, names, options.storage_pool.as_deref(), options.clone_timeout)
+        .await?;
+
+    if options.start {
+        progress!(format, "正在启动克隆的虚拟机...");
+        ops.start_clones(&mut results).await;
+    }
+    if let Some(transport) = &transport {
+        progress!(format, "正在等待 QGA 就绪...");
+        ops.verify_clones_qga(transport, &mut results, options.qga_timeout).await;
+    }
+
+    Ok(results)
+}
+
+/// `atp vdi test-restore` 的参数
+struct RestoreTestOptions {
+    marker_path: Option<String>,
+    method: RestoreMethod,
+    timeout: Duration,
+}
+
+/// 解析触发还原的方式
+fn parse_restore_method(value: &str) -> Result<RestoreMethod> {
+    match value.to_lowercase().as_str() {
+        "reboot" => Ok(RestoreMethod::Reboot),
+        "rebase" => Ok(RestoreMethod::Rebase),
+        _ => anyhow::bail!("不支持的还原方式: {} (可选: reboot, rebase)", value),
+    }
+}
+
+/// 还原点验证结果 (`atp vdi test-restore`)
+#[derive(Debug, Serialize)]
+struct RestoreSummary {
+    method: RestoreMethod,
+    total: usize,
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    results: Vec<RestoreItemResult>,
+}
+
+impl RestoreSummary {
+    fn new(method: RestoreMethod, results: Vec<RestoreItemResult>) -> Self {
+        let count = |status: RestoreStatus| results.iter().filter(|result| result.status == status).count();
+        Self {
+            method,
+            total: results.len(),
+            passed: count(RestoreStatus::Passed),
+            failed: count(RestoreStatus::Failed),
+            skipped: count(RestoreStatus::SkippedNoQga),
+            results,
+        }
+    }
+}
+
+impl Render for RestoreSummary {
+    fn to_table(&self) -> String {
+        let mut lines = vec![String::new()];
+        for result in &self.results {
+            let reason = result.error.as_deref().unwrap_or_default();
+            lines.push(match result.status {
+                RestoreStatus::Passed => format!(
+                    "   ✅ {} ({}): {}",
+                    result.vm.name,
+                    result.vm.id,
+                    result.marker_path.as_deref().unwrap_or_default()
+                ),
+                RestoreStatus::Failed => {
+                    let phase = result.failed_phase.map(|phase| phase.label()).unwrap_or("未知");
+                    format!("   ❌ {} ({}) [{}]: {}", result.vm.name, result.vm.id, phase, reason)
+                }
+                RestoreStatus::SkippedNoQga => {
+                    format!("   ⏭ {} ({}) [QGA 不可用]: {}", result.vm.name, result.vm.id, reason)
+                }
+            });
+        }
+        lines.push(format!(
+            "\n还原点验证完成 ({}): 通过 {} 台, 失败 {} 台, 跳过 {} 台",
+            self.method.label(),
+            self.passed,
+            self.failed,
+            self.skipped
+        ));
+        lines.join("\n")
+    }
+}
+
+/// 验证目标虚拟机的还原点 (见 `VdiBatchOps::verify_restore_points`)
+///
+/// 验证期间会设置与取消还原点并重启或重置虚拟机, 因此经过确认守卫。
+/// 有虚拟机失败时以退出码 1 退出, QGA 不可用而跳过的虚拟机不影响退出码。
+async fn test_restore_points(
+    config_path: &str,
+    profile: Option<&str>,
+    target: BatchTargetArgs,
+    options: &RestoreTestOptions,
+    guard: &DestructiveGuard,
+    format: &str,
+) -> Result<()> {
+    let format = output_format(Some(format))?;
+    let target = Target::from_args(target.pattern, target.pool, target.id)?;
+    guard.ensure_confirmable(format)?;
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+    let client = create_vdi_client(vdi_config).await?;
+    let ops = VdiBatchOps::new(Arc::new(client));
+
+    let vms = ops.resolve_target(&target, CacheMode::Fresh).await?;
+    let targets: Vec<String> = vms
+        .iter()
+        .map(|vm| format!("{} ({}) [{}]", vm.name, vm.id, vm.status))
+        .collect();
+    let prompt = format!("验证还原点 {} (将{}虚拟机)", target, options.method.label());
+    let confirmed = match guard.confirm(format, &prompt, &targets)? {
+        Confirmation::Proceed(confirmed) => confirmed,
+        Confirmation::DryRun => return Ok(()),
+        Confirmation::Cancelled if vms.is_empty() => {
+            return print_rendered(&RestoreSummary::new(options.method, Vec::new()), format);
+        }
+        Confirmation::Cancelled => return Ok(()),
+    };
+
+    let results = execute_restore(confirmed, &ops, vms, options, format).await?;
+    let summary = RestoreSummary::new(options.method, results);
+    print_rendered(&summary, format)?;
+
+    if summary.failed > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// 验证已确认虚拟机的还原点 (会重启或重置虚拟机)
+async fn execute_restore(
+    _confirmed: Confirmed,
+    ops: &VdiBatchOps,
+    vms: Vec<VmMatchResult>,
+    options: &RestoreTestOptions,
+    format: OutputFormat,
+) -> Result<Vec<RestoreItemResult>> {
+    let transport = transport_from_cli_config().await?;
+    progress!(format, "正在验证 {} 台虚拟机的还原点...", vms.len());
+    Ok(ops
+        .verify_restore_points(&transport, vms, options.marker_path.as_deref(), options.method, options.timeout)
+        .await)
+}
+
+/// 基线保存结果 (`atp vdi baseline save`)
+#[derive(Debug, Serialize)]
+struct SavedBaseline {
+    path: String,
+    captured_at: chrono::DateTime<Utc>,
+    vm_count: usize,
+
+    /// 快照查询失败的虚拟机数 (对比时不检查其快照数)
+    unknown_snapshots: usize,
+}
+
+impl Render for SavedBaseline {
+    fn to_table(&self) -> String {
+        let mut lines = vec![format!("✅ 已保存 {} 台虚拟机的基线到 {}", self.vm_count, self.path)];
+        if self.unknown_snapshots > 0 {
+            lines.push(format!(
+                "⚠ {} 台虚拟机的快照查询失败, 对比时不检查其快照数",
+                self.unknown_snapshots
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// 保存当前环境基线
+async fn save_baseline(config_path: &str, profile: Option<&str>, output: &str) -> Result<()> {
+    let format = output_format(None)?;
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+    let client = create_vdi_client(vdi_config).await?;
+
+    progress!(format, "📋 采集环境基线...");
+    let baseline = BaselineOps::new(Arc::new(client)).capture().await?;
+    baseline
+        .save(output)
+        .with_context(|| format!("保存基线文件失败: {}", output))?;
+
+    let saved = SavedBaseline {
+        path: output.to_string(),
+        captured_at: baseline.captured_at,
+        vm_count: baseline.vms.len(),
+        unknown_snapshots: baseline.vms.iter().filter(|vm| vm.snapshot_count.is_none()).count(),
+    };
+    print_rendered(&saved, format)
+}
+
+/// 采集当前环境并与基线文件对比, 有差异时以退出码 1 退出
+async fn diff_baseline(config_path: &str, profile: Option<&str>, baseline_path: &str, format: &str) -> Result<()> {
+    let format = output_format(Some(format))?;
+    let before = BaselineSnapshot::load(baseline_path)
+        .with_context(|| format!("读取基线文件失败: {}", baseline_path))?;
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+    let client = create_vdi_client(vdi_config).await?;
+    let after = BaselineOps::new(Arc::new(client)).capture().await?;
+    let diff = before.diff(&after);
+
+    match format {
+        OutputFormat::Table => output_baseline_diff(&before, &after, &diff),
+        _ => {
+            let output = json!({
+                "baseline_captured_at": before.captured_at,
+                "captured_at": after.captured_at,
+                "added": diff.added,
+                "missing": diff.missing,
+                "changed": diff.changed,
+            });
+            print_serialized(&output, format)?;
+        }
+    }
+
+    if !diff.is_empty() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// 表格格式输出基线对比结果
+fn output_baseline_diff(before: &BaselineSnapshot, after: &BaselineSnapshot, diff: &BaselineDiff) {
+    println!(
+        "📋 基线对比: {} ({} 台) -> {} ({} 台)\n",
+        before.captured_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        before.vms.len(),
+        after.captured_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+        after.vms.len()
+    );
+
+    if diff.is_empty() {
+        println!("✅ 环境与基线一致");
+        return;
+    }
+
+    for vm in &diff.missing {
+        println!("   ❌ 丢失: {} ({}) [{}]", vm.name, vm.id, vm.status);
+    }
+    for vm in &diff.added {
+        println!("   ➕ 新增: {} ({}) [{}]", vm.name, vm.id, vm.status);
+    }
+    for vm in &diff.changed {
+        println!("   ⚠ 变化: {} ({})", vm.name, vm.id);
+        for change in &vm.changes {
+            println!("        {}", change);
+        }
+    }
+
+    println!("\n{}", diff);
+}
+
+/// 一台虚拟机的状态变更历史
+#[derive(Debug, Serialize)]
+struct VmHistoryEntry {
+    vm_id: String,
+    history: Vec<VmStatusHistoryRecord>,
+}
+
+/// 虚拟机状态变更历史 (`atp vdi history`)
+///
+/// 同名的虚拟机可能有多台, 按虚拟机 ID 分别列出。
+#[derive(Debug, Serialize)]
+struct VmHistory {
+    vm_name: String,
+    vms: Vec<VmHistoryEntry>,
+}
+
+impl Render for VmHistory {
+    fn to_table(&self) -> String {
+        if self.vms.is_empty() {
+            return format!("ℹ 没有虚拟机 {} 的状态记录, 可使用 --refresh 从 VDI 平台同步", self.vm_name);
+        }
+
+        let mut lines = Vec::new();
+        for vm in &self.vms {
+            lines.push(format!("📋 虚拟机 {} ({}) 状态变更历史:\n", self.vm_name, vm.vm_id));
+            lines.push(format!("{:<22} {:<10} {:<10}", "时间", "原状态", "新状态"));
+            lines.push("-".repeat(50));
+            for record in &vm.history {
+                lines.push(format!(
+                    "{:<22} {:<10} {:<10}",
+                    record.changed_at.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+                    record.old_status.as_deref().unwrap_or("-"),
+                    record.new_status
+                ));
+            }
+            lines.push(String::new());
+        }
+        lines.join("\n")
+    }
+}
+
+async fn vm_history(config_path: &str, profile: Option<&str>, vm_name: &str, refresh: bool) -> Result<()> {
+    let format = output_format(None)?;
+
+    let storage_manager = StorageManager::new("~/.config/atp/data.db").await?;
+    let storage = Arc::new(Storage::from_manager(&storage_manager));
+
+    if refresh {
+        let config = load_config(config_path, profile)?;
+        let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+        let client = create_vdi_client(vdi_config).await?;
+        VmCacheManager::new(Arc::new(client), storage.clone())
+            .force_refresh()
+            .await?;
+    }
+
+    // 先按名称查找, 找不到时把参数当作虚拟机 ID
+    let mut vm_ids: Vec<String> = storage
+        .vm_cache()
+        .find_by_name(vm_name)
+        .await?
+        .into_iter()
+        .map(|vm| vm.id)
+        .collect();
+    if vm_ids.is_empty() {
+        vm_ids.push(vm_name.to_string());
+    }
+
+    let mut vms = Vec::new();
+    for vm_id in vm_ids {
+        let history = storage.vm_cache().history(&vm_id).await?;
+        if !history.is_empty() {
+            vms.push(VmHistoryEntry { vm_id, history });
+        }
+    }
+
+    print_rendered(&VmHistory { vm_name: vm_name.to_string(), vms }, format)
+}
+
+/// 同步到本地的一台 VDI 主机
+#[derive(Debug, Serialize)]
+struct SyncedHost {
+    name: String,
+    ip: String,
+    online: bool,
+
+    /// libvirt 连接测试结果 (未指定 `--test-connection` 或主机离线时为空)
+    connected: Option<bool>,
+}
+
+/// 主机同步结果 (`atp vdi sync-hosts`)
+#[derive(Debug, Serialize)]
+struct SyncHostsSummary {
+    hosts: Vec<SyncedHost>,
+
+    /// 是否已保存到数据库 (数据库不可用时只输出主机列表)
+    saved: bool,
+    created: usize,
+    updated: usize,
+}
+
+impl Render for SyncHostsSummary {
+    fn to_table(&self) -> String {
+        let mut lines = vec![format!("📊 发现 {} 个主机:\n", self.hosts.len())];
+        for (i, host) in self.hosts.iter().enumerate() {
+            let status = match (host.online, host.connected) {
+                (false, _) => "离线 ❌",
+                (true, None) => "在线 ✅",
+                (true, Some(true)) => "连接成功 ✅",
+                (true, Some(false)) => "连接失败 ❌",
+            };
+            lines.push(format!("  {}. {} ({}) - {}", i + 1, host.name, host.ip, status));
+        }
+
+        if self.saved {
+            lines.push(format!("\n💾 已保存到数据库: 新增 {} 个, 更新 {} 个", self.created, self.updated));
+        }
+        lines.push("\n💡 提示: 主机信息已从 VDI 平台获取".to_string());
+        lines.push("   可以在测试配置中使用这些主机信息".to_string());
+        lines.join("\n")
+    }
+}
+
+/// 同步 VDI 主机到本地配置
+async fn sync_hosts(config_path: &str, profile: Option<&str>, test_connection: bool) -> Result<()> {
+    let format = output_format(None)?;
+    progress!(format, "🔄 同步 VDI 主机到本地配置\n");
+
+    let config = load_config(config_path, profile)?;
+    let vdi_config = config.vdi.as_ref().context("未配置 VDI 平台")?;
+
+    let client = create_vdi_client(vdi_config).await?;
+    let hosts = client.host().list_all().await?;
+
+    // 数据库不可用时仍然输出主机列表
+    let storage = match StorageManager::new("~/.config/atp/data.db").await {
+        Ok(manager) => Some(Storage::from_manager(&manager)),
+        Err(e) => {
+            warn!("打开数据库失败, 主机信息不会被保存: {}", e);
+            None
+        }
+    };
+
+    let (mut created, mut updated) = (0, 0);
+    let mut synced = Vec::with_capacity(hosts.len());
+    for host in &hosts {
         let name = host["name"].as_str().unwrap_or("");
         let ip = host["ip"].as_str().unwrap_or("");
-        let status = host["status"].as_i64().unwrap_or(-1);
+        let online = host["status"].as_i64() == Some(1);
 
-        print!("  {}. {} ({}) ", i + 1, name, ip);
-
-        if status != 1 {
-            println!("- 离线 ❌");
-            continue;
+        if let Some(storage) = &storage {
+            match storage.hosts().upsert_by_host_id(&host_record(host)).await {
+                Ok(true) => created += 1,
+                Ok(false) => updated += 1,
+                Err(e) => warn!("保存主机 {} 失败: {}", name, e),
+            }
         }
 
-        if test_connection {
+        let connected = if online && test_connection {
             // 测试连接
             let uri = format!("qemu+tcp://{}/system", ip);
             let host_info = HostInfo {
                 id: name.to_string(),
                 host: name.to_string(),
-                uri: uri.clone(),
+                uri,
                 tags: vec![],
                 metadata: HashMap::new(),
+                ssh: None,
             };
 
             let conn = HostConnection::new(host_info);
-            match conn.connect().await {
-                Ok(_) if conn.is_alive().await => {
-                    println!("- 连接成功 ✅");
-                }
-                _ => {
-                    println!("- 连接失败 ❌");
-                }
-            }
+            Some(conn.connect().await.is_ok() && conn.is_alive().await)
         } else {
-            println!("- 在线 ✅");
+            None
+        };
+
+        synced.push(SyncedHost {
+            name: name.to_string(),
+            ip: ip.to_string(),
+            online,
+            connected,
+        });
+    }
+
+    let summary = SyncHostsSummary {
+        hosts: synced,
+        saved: storage.is_some(),
+        created,
+        updated,
+    };
+    print_rendered(&summary, format)
+}
+
+/// 虚拟机磁盘 (`virsh domblklist --details` 中的一行)
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DomainDisk {
+    target: String,
+    source: String,
+}
+
+/// 磁盘在单个 brick 上的状态
+#[derive(Debug, Serialize)]
+struct BrickDiskHealth {
+    brick: String,
+    status: String,
+    /// brick 上的待自愈条目总数 (不可达时为 None)
+    pending_entries: Option<u64>,
+    /// 磁盘文件本身在待自愈列表中
+    disk_pending: bool,
+    /// 磁盘文件处于脑裂
+    split_brain: bool,
+    /// brick 所在文件系统的可用空间 (字节, brick 离线时为 None)
+    free_bytes: Option<u64>,
+    /// brick 所在文件系统的总空间 (字节)
+    total_bytes: Option<u64>,
+    /// 空间使用率 (百分比)
+    used_percent: Option<f64>,
+    /// inode 使用率 (百分比)
+    inode_used_percent: Option<f64>,
+}
+
+/// 单个磁盘的检查结果
+#[derive(Debug, Serialize)]
+struct DiskHealth {
+    target: String,
+    source: String,
+    volume: Option<String>,
+    bricks: Vec<BrickDiskHealth>,
+    /// 不在 GlusterFS 上时的说明
+    #[serde(skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    /// 查找磁盘所在 brick 失败 (如文件不存在)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl DiskHealth {
+    fn is_healthy(&self) -> bool {
+        self.error.is_none() && self.bricks.iter().all(|brick| {
+            brick.status.eq_ignore_ascii_case("connected") && !brick.disk_pending && !brick.split_brain
+        })
+    }
+}
+
+/// 解析 `virsh domblklist <vm> --details`, 只保留有源文件的磁盘 (跳过光驱与网络磁盘)
+fn parse_domblklist(output: &str) -> Vec<DomainDisk> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (disk_type, device, target) = (fields.next()?, fields.next()?, fields.next()?);
+            let source = fields.collect::<Vec<_>>().join(" ");
+            (disk_type == "file" && device == "disk" && source.starts_with('/')).then(|| DomainDisk {
+                target: target.to_string(),
+                source,
+            })
+        })
+        .collect()
+}
+
+/// 由卷的自愈状态、脑裂列表与 brick 容量得到磁盘在其各个 brick 上的状态
+fn brick_disk_health(usage: &GlusterFileUsage, heal: &HealInfo, split_brain: &[SplitBrainEntry]) -> Vec<BrickDiskHealth> {
+    let file = &usage.file;
+    file.bricks
+        .iter()
+        .zip(&usage.bricks)
+        .map(|(brick, status)| {
+            let info = heal.brick(brick);
+            BrickDiskHealth {
+                brick: brick.clone(),
+                status: info.map_or_else(|| "未出现在 heal info 中".to_string(), |info| info.status.clone()),
+                pending_entries: info.and_then(|info| info.pending),
+                disk_pending: info.is_some_and(|info| info.entries.iter().any(|entry| entry.path == file.path)),
+                split_brain: split_brain.iter().any(|entry| entry.brick == *brick && entry.path == file.path)
+                    || info.is_some_and(|info| {
+                        info.entries.iter().any(|entry| entry.path == file.path && entry.split_brain)
+                    }),
+                free_bytes: status.free_bytes,
+                total_bytes: status.total_bytes,
+                used_percent: status.used_percent(),
+                inode_used_percent: status.inode_used_percent(),
+            }
+        })
+        .collect()
+}
+
+/// 按本地主机配置创建传输管理器
+async fn transport_from_cli_config() -> Result<TransportManager> {
+    let config = crate::config::CliConfig::load()?;
+    Ok(TransportManager::from_config(config.transport_config()).await?)
+}
+
+/// 检查虚拟机磁盘所在 brick 的自愈与脑裂状态
+async fn disk_health(vm_name: &str, host: Option<&str>, format: &str) -> Result<()> {
+    let format = output_format(Some(format))?;
+    let manager = Arc::new(transport_from_cli_config().await?);
+    let host_id = match host {
+        Some(host) => host.to_string(),
+        None => manager
+            .find_domain(vm_name)
+            .await?
+            .map(|(host_id, _)| host_id)
+            .with_context(|| format!("在已配置的主机上没有找到虚拟机 {}", vm_name))?,
+    };
+
+    let argv: Vec<String> = ["virsh", "domblklist", vm_name, "--details"].iter().map(|s| s.to_string()).collect();
+    let output = manager.exec_host_command(&host_id, &argv, Duration::from_secs(30)).await?;
+    if !output.success() {
+        anyhow::bail!("获取虚拟机 {} 的磁盘列表失败: {}", vm_name, output.stderr.trim());
+    }
+
+    let gluster = GlusterClient::new(manager.clone(), &host_id);
+    let disks = parse_domblklist(&output.stdout);
+    let sources: Vec<String> = disks.iter().map(|disk| disk.source.clone()).collect();
+    let mut locations = gluster.get_file_locations_batch(&sources).await?;
+
+    // 同一卷上的多个磁盘只查询一次
+    let mut volumes: HashMap<String, (HealInfo, Vec<SplitBrainEntry>, Vec<BrickStatus>)> = HashMap::new();
+    let mut results = Vec::new();
+
+    for disk in disks {
+        let mut health = DiskHealth {
+            target: disk.target,
+            source: disk.source,
+            volume: None,
+            bricks: Vec::new(),
+            note: None,
+            error: None,
+        };
+
+        let file = match locations.remove(&health.source).unwrap_or(Ok(None)) {
+            Ok(Some(file)) => file,
+            Ok(None) => {
+                health.note = Some("不在 GlusterFS 挂载点上, 跳过".to_string());
+                results.push(health);
+                continue;
+            }
+            Err(e) => {
+                health.error = Some(e.to_string());
+                results.push(health);
+                continue;
+            }
+        };
+        if !volumes.contains_key(&file.volume) {
+            let heal = gluster.get_heal_info(&file.volume).await?;
+            let split_brain = gluster.detect_split_brain(&file.volume).await?;
+            let statuses = gluster.get_volume_status_detail(&file.volume).await?;
+            volumes.insert(file.volume.clone(), (heal, split_brain, statuses));
         }
+        let (heal, split_brain, statuses) = &volumes[&file.volume];
+
+        let usage = GlusterFileUsage::new(file, statuses);
+        health.bricks = brick_disk_health(&usage, heal, split_brain);
+        health.volume = Some(usage.file.volume);
+        results.push(health);
     }
 
-    println!("\n💡 提示: 主机信息已从 VDI 平台获取");
-    println!("   可以在测试配置中使用这些主机信息");
+    match format {
+        OutputFormat::Table => output_disk_health(vm_name, &host_id, &results),
+        _ => print_serialized(&results, format)?,
+    }
 
+    if results.iter().any(|disk| !disk.is_healthy()) {
+        std::process::exit(1);
+    }
     Ok(())
 }
+
+fn output_disk_health(vm_name: &str, host_id: &str, results: &[DiskHealth]) {
+    println!("💾 虚拟机 {} (主机 {}) 磁盘健康状态:\n", vm_name, host_id);
+    if results.is_empty() {
+        println!("ℹ 虚拟机没有文件磁盘");
+        return;
+    }
+
+    for disk in results {
+        let icon = if disk.is_healthy() { "✅" } else { "❌" };
+        println!("{} {} {}", icon, disk.target, disk.source);
+        if let Some(note) = &disk.note {
+            println!("   ℹ {}", note);
+            continue;
+        }
+        if let Some(error) = &disk.error {
+            println!("   ❌ {}", error);
+            continue;
+        }
+        println!("   卷: {}", disk.volume.as_deref().unwrap_or("-"));
+        println!(
+            "   {:<40} {:<24} {:<10} {:<10} {:<6} {:<20} {:<8} {:<8}",
+            "Brick", "状态", "待自愈", "本磁盘", "脑裂", "可用/总量", "已用", "inode"
+        );
+        for brick in &disk.bricks {
+            let percent = |value: Option<f64>| value.map_or_else(|| "-".to_string(), |p| format!("{:.1}%", p));
+            let capacity = match (brick.free_bytes, brick.total_bytes) {
+                (Some(free), Some(total)) => format!("{}/{}", format_gib(free), format_gib(total)),
+                _ => "-".to_string(),
+            };
+            println!(
+                "   {:<40} {:<24} {:<10} {:<10} {:<6} {:<20} {:<8} {:<8}",
+                brick.brick,
+                brick.status,
+                brick.pending_entries.map_or_else(|| "-".to_string(), |n| n.to_string()),
+                if brick.disk_pending { "待自愈" } else { "-" },
+                if brick.split_brain { "是" } else { "否" },
+                capacity,
+                percent(brick.used_percent),
+                percent(brick.inode_used_percent)
+            );
+        }
+    }
+}
+
+fn format_gib(bytes: u64) -> String {
+    format!("{:.1}G", bytes as f64 / (1024.0 * 1024.0 * 1024.0))
+}
+
+/// VDI 主机转换为数据库记录 (以主机名作为主机 ID, 与 libvirt 连接配置一致)
+fn host_record(host: &serde_json::Value) -> HostRecord {
+    let ip = host["ip"].as_str().unwrap_or("");
+    let now = Utc::now();
+    HostRecord {
+        id: host["name"].as_str().unwrap_or("").to_string(),
+        host: ip.to_string(),
+        uri: format!("qemu+tcp://{}/system", ip),
+        tags: None,
+        metadata: Some(
+            json!({
+                "vdi_host_id": host["id"],
+                "status": host["status"],
+            })
+            .to_string(),
+        ),
+        created_at: now,
+        updated_at: now,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(name: &str, host: &str, status: i64, memory_mb: f64, user: &str) -> VmRow {
+        VmRow {
+            name: name.to_string(),
+            host: host.to_string(),
+            status,
+            cpu: 2,
+            memory_mb,
+            ip: String::new(),
+            user: user.to_string(),
+        }
+    }
+
+    fn options(host: Option<&str>, status: Option<&str>, user: Option<&str>) -> VmListOptions {
+        VmListOptions::parse(host.map(String::from), status, user.map(String::from), None, &[]).unwrap()
+    }
+
+    #[test]
+    fn test_parse_domblklist() {
+        let output = " Type   Device   Target   Source
+------------------------------------------------------------------
+ file   disk     vda      /mnt/gv0/images/win10 data.qcow2
+ file   cdrom    sda      -
+ network disk    vdb      gv0/images/extra.qcow2
+ file   disk     vdc      /var/lib/libvirt/images/local.qcow2
+";
+        assert_eq!(
+            parse_domblklist(output),
+            vec![
+                DomainDisk { target: "vda".to_string(), source: "/mnt/gv0/images/win10 data.qcow2".to_string() },
+                DomainDisk { target: "vdc".to_string(), source: "/var/lib/libvirt/images/local.qcow2".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_brick_disk_health() {
+        let heal = atp_transport::gluster::parse_heal_info(
+            "gv0",
+            "Brick gfs1:/b/gv0\n/images/win10.qcow2 - Is in split-brain\nStatus: Connected\nNumber of entries: 1\n\n\
+             Brick gfs2:/b/gv0\n/images/other.qcow2\nStatus: Connected\nNumber of entries: 1\n\n\
+             Brick gfs3:/b/gv0\nStatus: Transport endpoint is not connected\nNumber of entries: -\n",
+        )
+        .unwrap();
+        let split_brain = vec![SplitBrainEntry { brick: "gfs2:/b/gv0".to_string(), path: "/images/win10.qcow2".to_string() }];
+
+        let statuses = atp_transport::gluster::parse_volume_status_detail(
+            "gv0",
+            "Brick : Brick gfs1:/b/gv0\nOnline : Y\nDisk Space Free : 25.0GB\nTotal Disk Space : 100.0GB\n\
+             Brick : Brick gfs2:/b/gv0\nOnline : Y\nDisk Space Free : 50.0GB\nTotal Disk Space : 100.0GB\n\
+             Brick : Brick gfs3:/b/gv0\nOnline : N\nDisk Space Free : N/A\n",
+        )
+        .unwrap();
+        let usage = |path: &str, bricks: &[usize]| GlusterFileUsage {
+            file: atp_transport::GlusterFile {
+                volume: "gv0".to_string(),
+                path: path.to_string(),
+                bricks: bricks.iter().map(|i| statuses[*i].brick.clone()).collect(),
+            },
+            bricks: bricks.iter().map(|i| statuses[*i].clone()).collect(),
+        };
+
+        // 只报告保存该磁盘的 brick
+        let bricks = brick_disk_health(&usage("/images/win10.qcow2", &[0, 1]), &heal, &split_brain);
+        assert_eq!(bricks.len(), 2);
+        assert!(bricks[0].disk_pending && bricks[0].split_brain);
+        assert!(!bricks[1].disk_pending && bricks[1].split_brain);
+        assert_eq!(bricks[1].pending_entries, Some(1));
+        assert_eq!(bricks[0].used_percent, Some(75.0));
+        assert_eq!(bricks[1].free_bytes, Some(50 * 1024 * 1024 * 1024));
+
+        let bricks = brick_disk_health(&usage("/images/other.qcow2", &[2]), &heal, &[]);
+        assert_eq!(bricks[0].pending_entries, None);
+        assert_eq!((bricks[0].free_bytes, bricks[0].used_percent), (None, None));
+        let disk = DiskHealth { target: "vda".to_string(), source: String::new(), volume: None, bricks, note: None, error: None };
+        assert!(!disk.is_healthy());
+    }
+
+    #[test]
+    fn test_batch_target_args_mutually_exclusive() {
+        use clap::Parser;
+
+        let parse = |args: &[&str]| crate::Cli::try_parse_from([&["atp", "vdi", "batch", "start"], args].concat());
+
+        assert!(parse(&["--pool", "财务部"]).is_ok());
+        assert!(parse(&["--id", "vm-1,vm-2"]).is_ok());
+        assert!(parse(&["--pattern", "win10-*", "--pool", "财务部"]).is_err());
+        assert!(parse(&["--pool", "财务部", "--id", "vm-1"]).is_err());
+
+        assert_eq!(parse_batch_operation("Shutdown").unwrap(), BatchOperation::Shutdown);
+        assert!(parse_batch_operation("delete").is_err());
+    }
+
+    #[test]
+    fn test_clone_args() {
+        use clap::Parser;
+
+        let parse = |args: &[&str]| {
+            crate::Cli::try_parse_from([&["atp", "vdi", "clone", "--source", "tpl", "--count", "20"], args].concat())
+        };
+
+        let cli = parse(&["--name", "lab-{:03}", "--storage-pool", "pool-1", "--start", "--wait-qga"]).unwrap();
+        match cli.command {
+            crate::Commands::Vdi {
+                action: VdiAction::Clone { count, storage_pool, start, wait_qga, qga_timeout, .. },
+            } => {
+                assert_eq!(count, 20);
+                assert_eq!(storage_pool.as_deref(), Some("pool-1"));
+                assert!(start && wait_qga);
+                assert_eq!(qga_timeout, 300);
+            }
+            _ => unreachable!(),
+        }
+
+        // 等待 QGA 需要先启动
+        assert!(parse(&["--name", "lab-{}", "--wait-qga"]).is_err());
+        assert!(parse(&[]).is_err());
+    }
+
+    #[test]
+    fn test_render_clone_summary() {
+        use atp_executor::ClonePhase;
+
+        let item = |name: &str, id: Option<&str>, qga_ready: Option<bool>| CloneItemResult {
+            name: name.to_string(),
+            id: id.map(String::from),
+            status: id.map(|_| "运行中".to_string()),
+            host_id: id.map(|_| "h-1".to_string()),
+            started: id.is_some(),
+            qga_ready,
+            failed_phase: None,
+            error: None,
+        };
+        let failed = CloneItemResult {
+            failed_phase: Some(ClonePhase::Clone),
+            error: Some("名称重复".to_string()),
+            ..item("lab-002", None, None)
+        };
+        let results = vec![item("lab-001", Some("vm-1"), Some(true)), failed];
+        let source = CloneSource { id: "tpl-1".to_string(), name: "win10".to_string(), template: true };
+        let summary = CloneSummary::new(source, results);
+        assert_eq!(summary.failed, 1);
+
+        let table = summary.to_table();
+        assert!(table.contains("lab-001") && table.contains("h-1") && table.contains("✅"), "{}", table);
+        assert!(table.contains("lab-002 [克隆]: 名称重复"), "{}", table);
+        assert!(table.contains("成功 1 台, 失败 1 台"), "{}", table);
+
+        let value: serde_json::Value = serde_json::from_str(&summary.to_json().unwrap()).unwrap();
+        assert_eq!(value["results"][1]["failed_phase"], "clone");
+        assert_eq!(value["source"]["template"], true);
+    }
+
+    #[test]
+    fn test_render_orphan_cleanup_summary() {
+        let item = |id: &str, error: Option<&str>| OrphanCleanupItem {
+            resource_type: ResourceKind::Domain.as_str().to_string(),
+            resource_id: id.to_string(),
+            name: None,
+            error: error.map(String::from),
+        };
+        let summary = OrphanCleanupSummary::new(42, vec![item("vm-1", None), item("vm-2", Some("不存在"))]);
+        assert_eq!(summary.failed, 1);
+
+        let table = summary.to_table();
+        assert!(table.contains("✅ 已删除 domain vm-1"), "{}", table);
+        assert!(table.contains("删除 domain vm-2 失败: 不存在"), "{}", table);
+        assert!(table.contains("成功 1 个, 失败 1 个"), "{}", table);
+
+        let value: serde_json::Value = serde_json::from_str(&summary.to_json().unwrap()).unwrap();
+        assert_eq!(value["report_id"], 42);
+        assert_eq!(value["results"][1]["error"], "不存在");
+        assert!(OrphanCleanupSummary::new(42, Vec::new()).to_table().contains("没有待清理"));
+    }
+
+    #[test]
+    fn test_render_sync_hosts_summary() {
+        let host = |name: &str, online: bool, connected: Option<bool>| SyncedHost {
+            name: name.to_string(),
+            ip: "10.0.0.1".to_string(),
+            online,
+            connected,
+        };
+        let summary = SyncHostsSummary {
+            hosts: vec![host("h-1", true, Some(false)), host("h-2", false, None)],
+            saved: true,
+            created: 1,
+            updated: 1,
+        };
+
+        let table = summary.to_table();
+        assert!(table.contains("1. h-1 (10.0.0.1) - 连接失败"), "{}", table);
+        assert!(table.contains("2. h-2 (10.0.0.1) - 离线"), "{}", table);
+        assert!(table.contains("新增 1 个, 更新 1 个"), "{}", table);
+
+        let value: serde_json::Value = serde_json::from_str(&summary.to_json().unwrap()).unwrap();
+        assert_eq!(value["hosts"][0]["connected"], false);
+        assert!(value["hosts"][1]["connected"].is_null());
+    }
+
+    #[test]
+    fn test_test_restore_args() {
+        use clap::Parser;
+
+        let parse = |args: &[&str]| crate::Cli::try_parse_from([&["atp", "vdi", "test-restore"], args].concat());
+
+        match parse(&["--pattern", "lab-*"]).unwrap().command {
+            crate::Commands::Vdi {
+                action: VdiAction::TestRestore { target, marker_path, method, timeout, .. },
+            } => {
+                assert_eq!(target.pattern.as_deref(), Some("lab-*"));
+                assert_eq!(marker_path, None);
+                assert_eq!(parse_restore_method(&method).unwrap(), RestoreMethod::Reboot);
+                assert_eq!(timeout, 600);
+            }
+            _ => unreachable!(),
+        }
+
+        assert!(parse(&["--pattern", "lab-*", "--pool", "财务部"]).is_err());
+        assert_eq!(parse_restore_method("Rebase").unwrap(), RestoreMethod::Rebase);
+        assert!(parse_restore_method("restore").is_err());
+    }
+
+    #[test]
+    fn test_render_restore_summary() {
+        use atp_executor::{RestorePhase, VmMatchResult};
+
+        let item = |id: &str, status: RestoreStatus, failed_phase: Option<RestorePhase>, error: Option<&str>| {
+            RestoreItemResult {
+                vm: VmMatchResult {
+                    id: id.to_string(),
+                    name: format!("lab-{}", id),
+                    status: "运行中".to_string(),
+                    host_id: "h-1".to_string(),
+                },
+                marker_path: (status != RestoreStatus::SkippedNoQga).then(|| "/var/tmp/atp-restore-marker".to_string()),
+                status,
+                failed_phase,
+                error: error.map(String::from),
+            }
+        };
+        let results = vec![
+            item("1", RestoreStatus::Passed, None, None),
+            item("2", RestoreStatus::Failed, Some(RestorePhase::VerifyMarker), Some("标记文件仍为修改后的内容")),
+            item("3", RestoreStatus::SkippedNoQga, None, Some("QGA ping 失败")),
+        ];
+        let summary = RestoreSummary::new(RestoreMethod::Reboot, results);
+        assert_eq!((summary.passed, summary.failed, summary.skipped), (1, 1, 1));
+
+        let table = summary.to_table();
+        assert!(table.contains("✅ lab-1 (1): /var/tmp/atp-restore-marker"), "{}", table);
+        assert!(table.contains("lab-2 (2) [校验标记文件]: 标记文件仍为修改后的内容"), "{}", table);
+        assert!(table.contains("lab-3 (3) [QGA 不可用]: QGA ping 失败"), "{}", table);
+        assert!(table.contains("通过 1 台, 失败 1 台, 跳过 1 台"), "{}", table);
+
+        let value: serde_json::Value = serde_json::from_str(&summary.to_json().unwrap()).unwrap();
+        assert_eq!(value["method"], "reboot");
+        assert_eq!(value["results"][2]["status"], "skipped_no_qga");
+    }
+
+    #[test]
+    fn test_destructive_subcommands_declare_confirm_args() {
+        use clap::Parser;
+
+        let action = |args: &[&str]| match crate::Cli::try_parse_from([&["atp", "vdi"], args].concat()).unwrap().command {
+            crate::Commands::Vdi { action } => action,
+            _ => unreachable!(),
+        };
+
+        let batch = action(&["batch", "shutdown", "--pool", "财务部", "--dry-run"]);
+        let confirm = batch.confirm_args().unwrap();
+        assert!(confirm.dry_run && !confirm.yes);
+
+        let clone = action(&["clone", "--source", "tpl", "--count", "2", "--name", "lab-{:02}", "--dry-run"]);
+        assert!(clone.confirm_args().unwrap().dry_run);
+
+        let restore = action(&["test-restore", "--pattern", "lab-*", "--dry-run"]);
+        assert!(restore.confirm_args().unwrap().dry_run);
+
+        let assign = action(&["assign", "--mapping", "users.csv", "-y"]);
+        assert!(assign.confirm_args().unwrap().yes);
+        assert!(action(&["cleanup-orphans", "--from-report", "1"]).confirm_args().is_some());
+
+        assert!(action(&["list-hosts"]).confirm_args().is_none());
+        assert!(action(&["history", "win10-01"]).confirm_args().is_none());
+    }
+
+    #[test]
+    fn test_parse_vm_columns() {
+        assert_eq!(parse_vm_columns(&[]).unwrap(), VmColumn::DEFAULT.to_vec());
+
+        let columns = parse_vm_columns(&["name".to_string(), " IP".to_string(), "user".to_string()]).unwrap();
+        assert_eq!(columns, vec![VmColumn::Name, VmColumn::Ip, VmColumn::User]);
+
+        let err = parse_vm_columns(&["name".to_string(), "disk".to_string()]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "未知的列 'disk', 可选: name, host, status, cpu, memory, ip, user"
+        );
+    }
+
+    #[test]
+    fn test_parse_status_and_sort() {
+        assert_eq!(parse_vm_status("running").unwrap(), 1);
+        assert_eq!(parse_vm_status("Shutoff").unwrap(), 0);
+        assert_eq!(parse_vm_status("挂起").unwrap(), 2);
+        assert!(parse_vm_status("stopped").unwrap_err().to_string().contains("可选: shutoff, running"));
+
+        assert_eq!(VmSortKey::parse("memory").unwrap(), VmSortKey::Memory);
+        assert!(VmSortKey::parse("cpu").unwrap_err().to_string().contains("可选: name, status, host, memory"));
+    }
+
+    #[test]
+    fn test_vm_rows_and_filter() {
+        let hosts = HashMap::from([("h1".to_string(), "node-1".to_string())]);
+        let domains = vec![
+            json!({"name": "win10-01", "hostId": "h1", "status": 1, "cpuNum": 4, "memory": 8192, "ip": "10.0.0.1", "userName": "alice"}),
+            json!({"name": "win10-02", "hostId": "h2", "status": 0}),
+        ];
+        let rows = vm_rows(&domains, &hosts);
+        assert_eq!(rows[0].host, "node-1");
+        assert_eq!(rows[0].ip, "10.0.0.1");
+        assert_eq!(rows[1].host, "");
+        assert_eq!(rows[1].cpu, 0);
+
+        let rows = vec![
+            row("a", "node-1", 1, 1024.0, "alice"),
+            row("b", "node-2", 0, 2048.0, "bob"),
+            row("c", "node-1", 0, 4096.0, "alice"),
+        ];
+        let names = |rows: Vec<VmRow>| rows.into_iter().map(|r| r.name).collect::<Vec<_>>();
+
+        assert_eq!(names(filter_vms(rows.clone(), &options(None, None, None))), ["a", "b", "c"]);
+        assert_eq!(names(filter_vms(rows.clone(), &options(Some("node-1"), None, None))), ["a", "c"]);
+        assert_eq!(names(filter_vms(rows.clone(), &options(None, Some("shutoff"), None))), ["b", "c"]);
+        assert_eq!(names(filter_vms(rows, &options(Some("node-1"), Some("shutoff"), Some("alice")))), ["c"]);
+    }
+
+    #[test]
+    fn test_sort_vms() {
+        let mut rows = vec![
+            row("c", "node-2", 1, 1024.0, ""),
+            row("a", "node-2", 0, 4096.0, ""),
+            row("b", "node-1", 1, 4096.0, ""),
+        ];
+        let names = |rows: &[VmRow]| rows.iter().map(|r| r.name.as_str()).collect::<Vec<_>>().join(",");
+
+        sort_vms(&mut rows, VmSortKey::Name);
+        assert_eq!(names(&rows), "a,b,c");
+        sort_vms(&mut rows, VmSortKey::Status);
+        assert_eq!(names(&rows), "a,b,c");
+        sort_vms(&mut rows, VmSortKey::Host);
+        assert_eq!(names(&rows), "b,a,c");
+        // 内存从大到小, 相同时按名称
+        sort_vms(&mut rows, VmSortKey::Memory);
+        assert_eq!(names(&rows), "a,b,c");
+    }
+
+    #[test]
+    fn test_host_record() {
+        let record = host_record(&json!({"id": "h-1", "name": "node-1", "ip": "10.0.0.1", "status": 1}));
+        assert_eq!(record.id, "node-1");
+        assert_eq!(record.host, "10.0.0.1");
+        assert_eq!(record.uri, "qemu+tcp://10.0.0.1/system");
+
+        let metadata: serde_json::Value = serde_json::from_str(record.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(metadata, json!({"vdi_host_id": "h-1", "status": 1}));
+    }
+
+    #[test]
+    fn test_column_cells() {
+        let vm = VmRow {
+            ip: "10.0.0.1".to_string(),
+            ..row("win10-01", "node-1", 2, 3072.0, "alice")
+        };
+        let columns = [VmColumn::Name, VmColumn::Status, VmColumn::Memory, VmColumn::User];
+        let cells: Vec<String> = columns.iter().map(|column| vm_cell(&vm, *column)).collect();
+        assert_eq!(cells, ["win10-01", "挂起 🟡", "3.00", "alice"]);
+
+        let line = format_vm_line(&[VmColumn::Ip, VmColumn::User], ["10.0.0.1", "alice"]);
+        assert_eq!(line, format!("{:<16} alice", "10.0.0.1"));
+    }
+
+    #[test]
+    fn test_render_consistency_report() {
+        let result = |vm_name: &str, consistent: bool| CompareResult {
+            vm_name: vm_name.to_string(),
+            host: "node-1".to_string(),
+            vdi_status: "运行中".to_string(),
+            libvirt_status: if consistent { "Running" } else { "Shutoff" }.to_string(),
+            consistent,
+        };
+
+        let report = ConsistencyReport::new(vec![result("vm-1", true), result("vm-2", false)], true);
+        assert_eq!((report.total, report.consistent), (2, 1));
+
+        let json: serde_json::Value = serde_json::from_str(&report.render(OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            json!([{
+                "vm_name": "vm-2",
+                "host": "node-1",
+                "vdi_status": "运行中",
+                "libvirt_status": "Shutoff",
+                "consistent": false,
+            }])
+        );
+        assert!(report.render(OutputFormat::Yaml).unwrap().starts_with("- vm_name: vm-2\n"));
+
+        let table = report.render(OutputFormat::Table).unwrap();
+        assert!(table.contains("一致性: 50.0%"), "{}", table);
+        assert!(!table.contains("vm-1"), "{}", table);
+    }
+
+    #[test]
+    fn test_compare_host_vms() {
+        let domain = |name: &str, state: u32| LibvirtDomainInfo {
+            name: name.to_string(),
+            uuid: String::new(),
+            state,
+            vcpus: 2,
+            memory_kb: 4 * 1024 * 1024,
+        };
+        let vm = |name: &str, status: &str| {
+            (
+                name.to_string(),
+                VmInfo {
+                    name: name.to_string(),
+                    status: status.to_string(),
+                    host: "node-1".to_string(),
+                },
+            )
+        };
+        let vdi_vms: HashMap<String, VmInfo> = [vm("vm-a", "运行中"), vm("vm-b", "关机"), vm("vm-c", "挂起")].into();
+
+        let results = compare_host_vms("node-1", &[domain("vm-c", 3), domain("vm-b", 1), domain("vm-a", 1), domain("vm-x", 5)], &vdi_vms);
+        let summary: Vec<_> = results
+            .iter()
+            .map(|r| (r.vm_name.as_str(), r.vdi_status.as_str(), r.libvirt_status.as_str(), r.consistent))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("vm-a", "运行中", "Running", true),
+                ("vm-b", "关机", "Running", false),
+                ("vm-c", "挂起", "Paused", true),
+                ("vm-x", "不存在", "Shutoff", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_consistency_delta() {
+        let result = |host: &str, vm_name: &str, consistent: bool| CompareResult {
+            vm_name: vm_name.to_string(),
+            host: host.to_string(),
+            vdi_status: "运行中".to_string(),
+            libvirt_status: if consistent { "Running" } else { "Shutoff" }.to_string(),
+            consistent,
+        };
+
+        // 第一轮: 所有不一致都是新增
+        let first = vec![result("node-1", "vm-1", false), result("node-1", "vm-2", true)];
+        let (added, recovered) = consistency_delta(&[], &first);
+        assert_eq!(added, vec![result("node-1", "vm-1", false)]);
+        assert!(recovered.is_empty());
+
+        // 没有变化
+        assert_eq!(consistency_delta(&first, &first), (vec![], vec![]));
+
+        // vm-1 恢复, vm-2 与另一台主机上的同名虚拟机变为不一致; 恢复项取本轮的状态
+        let second = vec![
+            result("node-1", "vm-1", true),
+            result("node-1", "vm-2", false),
+            result("node-2", "vm-1", false),
+        ];
+        let (added, recovered) = consistency_delta(&first, &second);
+        assert_eq!(added, vec![result("node-1", "vm-2", false), result("node-2", "vm-1", false)]);
+        assert_eq!(recovered, vec![result("node-1", "vm-1", true)]);
+
+        // 虚拟机已不存在也算恢复
+        let (added, recovered) = consistency_delta(&second, &[]);
+        assert!(added.is_empty());
+        assert_eq!(recovered.len(), 2);
+    }
+}
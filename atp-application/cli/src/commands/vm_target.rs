@@ -0,0 +1,311 @@
+//! 键盘 / 鼠标 / 命令子命令的目标虚拟机
+//!
+//! 指定 `--host` 时使用本地配置中的主机; 只指定 `--config` 与 `--vm` 时从 VDI 平台查找虚拟机,
+//! 按其所在主机的地址临时注册 libvirt 连接 (本地配置中有同名或同地址的主机时沿用其 URI 与 SSH 配置)。
+//! 操作以单步骤场景交给 `ScenarioRunner` 执行, 与场景中的同名动作行为一致。
+
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use atp_executor::scenario::similar_names;
+use atp_executor::vm_cache::records_from_listing;
+use atp_executor::{Action, Scenario, ScenarioRunner, ScenarioStep, StepReport, StepStatus, SCENARIO_SCHEMA_VERSION};
+use atp_protocol::qmp::QmpProtocol;
+use atp_protocol::{Protocol, ProtocolRegistry};
+use atp_storage::VmCacheRecord;
+use atp_transport::{HostEntry, TransportConfig, TransportManager};
+use chrono::Utc;
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::commands::common::Render;
+use crate::commands::vdi::{create_vdi_client, load_config};
+use crate::config::CliConfig;
+use crate::i18n::{t, tr, MsgKey};
+use crate::VmTargetArgs;
+
+/// 解析后的目标虚拟机
+pub struct VmTarget {
+    /// libvirt 主机
+    host: HostEntry,
+
+    /// 虚拟机名称 (libvirt 中的 domain 名称)
+    vm: String,
+}
+
+impl VmTarget {
+    /// 按命令行参数解析目标
+    pub async fn from_args(args: &VmTargetArgs, profile: Option<&str>) -> Result<Self> {
+        Self::resolve(args.host.as_deref(), &args.vm, args.config.as_deref(), profile).await
+    }
+
+    /// 解析目标: 优先使用 `--host`, 否则通过 `--config` 中的 VDI 平台查找虚拟机所在主机
+    pub async fn resolve(host: Option<&str>, vm: &str, config: Option<&str>, profile: Option<&str>) -> Result<Self> {
+        let cli_config = CliConfig::load()?;
+
+        if let Some(host_id) = host {
+            let host_config = cli_config.get_host(host_id)?;
+            return Ok(Self {
+                host: host_config.to_host_entry(host_id),
+                vm: vm.to_string(),
+            });
+        }
+
+        let config_path = config.context("需要指定 --host, 或用 --config 指定 VDI 平台配置")?;
+        println!("{} {}", "🔍".cyan(), t(MsgKey::ResolvingVm));
+
+        let test_config = load_config(config_path, profile)?;
+        let vdi_config = test_config.vdi.as_ref().context("配置文件中未找到 VDI 平台配置")?;
+        let client = create_vdi_client(vdi_config).await?;
+
+        let domains = client.domain().list_all().await?;
+        let record = find_vdi_vm(&records_from_listing(&domains, Utc::now()), vm)?;
+        let hosts = client.host().list_all().await?;
+        let (host_name, ip) = find_vdi_host(&hosts, &record.host_id)?;
+
+        let host = cli_config
+            .hosts
+            .iter()
+            .find(|(id, host_config)| *id == &host_name || host_config.host == ip)
+            .map(|(id, host_config)| host_config.to_host_entry(id))
+            .unwrap_or_else(|| HostEntry::new(&host_name, &ip).with_uri(&format!("qemu+tcp://{}/system", ip)));
+
+        println!("  {}", tr(MsgKey::ResolvedVm, &[&record.name, &host.id, &host.to_host_info().uri]));
+
+        Ok(Self {
+            host,
+            vm: record.name,
+        })
+    }
+
+    /// 主机 ID
+    pub fn host_id(&self) -> &str {
+        &self.host.id
+    }
+
+    /// 虚拟机名称
+    pub fn vm(&self) -> &str {
+        &self.vm
+    }
+
+    /// 只注册目标主机的传输管理器
+    async fn transport(&self) -> Result<TransportManager> {
+        TransportManager::from_config(TransportConfig::default().with_host(self.host.clone()))
+            .await
+            .with_context(|| format!("添加主机 {} 失败", self.host.id))
+    }
+
+    /// 连接目标主机的场景执行器
+    pub async fn runner(&self) -> Result<ScenarioRunner> {
+        let transport_manager = self.transport().await?;
+        Ok(ScenarioRunner::new(Arc::new(transport_manager), Arc::new(ProtocolRegistry::new())))
+    }
+
+    /// 直接连接目标虚拟机的 QMP (不经过场景执行器, 供基准测试等需要自行计时的命令使用)
+    pub async fn qmp(&self) -> Result<QmpProtocol> {
+        let transport_manager = self.transport().await?;
+
+        let vm = self.vm.clone();
+        let domain = transport_manager
+            .execute_on_host(&self.host.id, |conn| async move { conn.get_domain(&vm).await })
+            .await
+            .with_context(|| format!("获取虚拟机 {} 失败", self.vm))?;
+
+        let mut qmp = QmpProtocol::new();
+        qmp.connect(&domain)
+            .await
+            .with_context(|| format!("连接虚拟机 {} 的 QMP 失败", self.vm))?;
+        Ok(qmp)
+    }
+
+    /// 以单步骤场景执行动作, 步骤失败时返回错误
+    pub async fn run(&self, action: Action) -> Result<StepReport> {
+
+        let scenario = Scenario {
+            schema_version: SCENARIO_SCHEMA_VERSION,
+            name: format!("cli-{}", action.type_name()),
+            description: None,
+            target_host: Some(self.host.id.clone()),
+            target_domain: Some(self.vm.clone()),
+            setup: Vec::new(),
+            steps: vec![ScenarioStep {
+                name: None,
+                action,
+                verify: false,
+                timeout: None,
+                tags: Vec::new(),
+            }],
+            teardown: Vec::new(),
+            tags: Vec::new(),
+            max_duration_secs: None,
+            environment_guard: None,
+            keyboard_layout: None,
+            guest_os: None,
+        };
+
+        let report = self.runner().await?.run(&scenario).await?;
+        let step = report.steps.into_iter().next().context("步骤没有执行")?;
+
+        match step.status {
+            StepStatus::Success => Ok(step),
+            _ => anyhow::bail!("{}", step.error.as_deref().unwrap_or("步骤执行失败")),
+        }
+    }
+}
+
+/// 单个动作的执行结果 (`atp keyboard` / `atp mouse` / `atp command exec`)
+#[derive(Debug, Serialize)]
+pub struct ActionResult {
+    /// libvirt 主机
+    pub host: String,
+
+    /// 虚拟机名称
+    pub vm: String,
+
+    /// 动作类型 (与场景中的 type 一致)
+    pub action: String,
+
+    /// 是否实际执行 (只能在场景中使用的操作为 false)
+    pub executed: bool,
+
+    /// 动作输出
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+
+    /// 耗时 (毫秒)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+}
+
+impl ActionResult {
+    /// 已执行的动作
+    pub fn executed(target: &VmTarget, action: &str, step: StepReport) -> Self {
+        Self {
+            host: target.host_id().to_string(),
+            vm: target.vm().to_string(),
+            action: action.to_string(),
+            executed: true,
+            output: step.output.filter(|output| !output.is_empty()),
+            duration_ms: Some(step.duration_ms),
+        }
+    }
+
+    /// 命令行不支持、只能在场景中使用的动作
+    pub fn scenario_only(target: &VmTarget, action: &str) -> Self {
+        Self {
+            host: target.host_id().to_string(),
+            vm: target.vm().to_string(),
+            action: action.to_string(),
+            executed: false,
+            output: None,
+            duration_ms: None,
+        }
+    }
+}
+
+impl Render for ActionResult {
+    fn to_table(&self) -> String {
+        if !self.executed {
+            return format!(
+                "\n{} {}\n  {}: {}",
+                "ℹ".cyan(),
+                t(MsgKey::ScenarioOnly),
+                t(MsgKey::HintPrefix),
+                t(MsgKey::UseScenarioRun)
+            );
+        }
+
+        let mut table = format!("\n{} {}", "✓".green(), t(MsgKey::OperationDone));
+        if let Some(output) = &self.output {
+            table.push('\n');
+            table.push_str(output);
+        }
+        table
+    }
+}
+
+/// 按名称 (也可以是 ID) 查找 VDI 虚拟机
+///
+/// 名称对应多台虚拟机时要求使用 ID; 找不到时列出相近的名称。
+fn find_vdi_vm(vms: &[VmCacheRecord], name: &str) -> Result<VmCacheRecord> {
+    if let Some(vm) = vms.iter().find(|vm| vm.id == name) {
+        return Ok(vm.clone());
+    }
+
+    let matched: Vec<&VmCacheRecord> = vms.iter().filter(|vm| vm.name == name).collect();
+    match matched.as_slice() {
+        [vm] => Ok((*vm).clone()),
+        [] => {
+            let names: Vec<&str> = vms.iter().map(|vm| vm.name.as_str()).collect();
+            let candidates = similar_names(name, &names);
+            if candidates.is_empty() {
+                anyhow::bail!("VDI 平台上没有名为 {} 的虚拟机", name)
+            }
+            anyhow::bail!("VDI 平台上没有名为 {} 的虚拟机, 是否想使用: {}", name, candidates.join(", "))
+        }
+        vms => {
+            let ids: Vec<&str> = vms.iter().map(|vm| vm.id.as_str()).collect();
+            anyhow::bail!("虚拟机名称 {} 对应多台虚拟机 ({}), 请使用 ID", name, ids.join(", "))
+        }
+    }
+}
+
+/// 按 VDI 主机 ID 查找主机名称与地址
+fn find_vdi_host(hosts: &[serde_json::Value], host_id: &str) -> Result<(String, String)> {
+    let host = hosts
+        .iter()
+        .find(|host| host["id"].as_str() == Some(host_id))
+        .with_context(|| format!("VDI 平台上没有 ID 为 {} 的主机 (虚拟机可能未分配主机)", host_id))?;
+
+    let name = host["name"].as_str().unwrap_or_default();
+    let ip = host["ip"].as_str().filter(|ip| !ip.is_empty())
+        .with_context(|| format!("VDI 主机 {} 没有地址", host_id))?;
+
+    Ok((if name.is_empty() { ip } else { name }.to_string(), ip.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, name: &str) -> VmCacheRecord {
+        VmCacheRecord {
+            id: id.to_string(),
+            name: name.to_string(),
+            status: "运行中".to_string(),
+            host_id: "h-1".to_string(),
+            cpu: None,
+            memory: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_find_vdi_vm() {
+        let vms = vec![record("vm-1", "win10-01"), record("vm-2", "win10-02"), record("vm-3", "dup"), record("vm-4", "dup")];
+
+        assert_eq!(find_vdi_vm(&vms, "win10-02").unwrap().id, "vm-2");
+        assert_eq!(find_vdi_vm(&vms, "vm-3").unwrap().name, "dup");
+
+        let err = find_vdi_vm(&vms, "win10-1").unwrap_err().to_string();
+        assert!(err.contains("是否想使用: win10-01, win10-02"), "{}", err);
+        let err = find_vdi_vm(&vms, "dup").unwrap_err().to_string();
+        assert!(err.contains("vm-3, vm-4"), "{}", err);
+        let err = find_vdi_vm(&vms, "linux-server").unwrap_err().to_string();
+        assert!(!err.contains("是否想使用"), "{}", err);
+    }
+
+    #[test]
+    fn test_find_vdi_host() {
+        let hosts = vec![
+            serde_json::json!({"id": "h-1", "name": "node-1", "ip": "10.0.0.1"}),
+            serde_json::json!({"id": "h-2", "name": "", "ip": "10.0.0.2"}),
+            serde_json::json!({"id": "h-3", "name": "node-3", "ip": ""}),
+        ];
+
+        assert_eq!(find_vdi_host(&hosts, "h-1").unwrap(), ("node-1".to_string(), "10.0.0.1".to_string()));
+        assert_eq!(find_vdi_host(&hosts, "h-2").unwrap(), ("10.0.0.2".to_string(), "10.0.0.2".to_string()));
+        assert!(find_vdi_host(&hosts, "h-3").is_err());
+        assert!(find_vdi_host(&hosts, "h-9").is_err());
+    }
+}
@@ -8,11 +8,15 @@
 //! - 提供导入/导出功能保持兼容性
 
 use anyhow::{Context, Result};
+use atp_storage::AnonymizeRuleSpec;
+use atp_transport::{HostEntry, SshConfig, TransportConfig, SUDO_PASSWORD_ENV};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::i18n::{tr, MsgKey};
+
 /// CLI 配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CliConfig {
@@ -26,6 +30,14 @@ pub struct CliConfig {
     /// 场景目录
     pub scenario_dir: Option<String>,
 
+    /// 指标采集间隔 (秒), 设置后执行场景时定期将连接指标写入数据库
+    #[serde(default)]
+    pub metrics_interval_secs: Option<u64>,
+
+    /// 匿名化导出时追加的规则
+    #[serde(default)]
+    pub anonymize_rules: Vec<AnonymizeRuleSpec>,
+
     /// 配置版本
     #[serde(default = "default_version")]
     pub version: String,
@@ -51,6 +63,24 @@ pub struct HostConfig {
     /// 元数据
     #[serde(default)]
     pub metadata: HashMap<String, String>,
+
+    /// SSH 配置 (执行 virsh 等宿主机命令时使用)
+    #[serde(default)]
+    pub ssh: Option<SshConfig>,
+}
+
+impl HostConfig {
+    /// 转换为传输层的主机配置 (未配置 URI 时默认通过 SSH 连接)
+    pub fn to_host_entry(&self, id: &str) -> HostEntry {
+        HostEntry {
+            id: id.to_string(),
+            host: self.host.clone(),
+            uri: self.uri.clone(),
+            tags: self.tags.clone(),
+            metadata: self.metadata.clone(),
+            ssh: self.ssh.clone(),
+        }
+    }
 }
 
 impl Default for CliConfig {
@@ -59,6 +89,8 @@ impl Default for CliConfig {
             hosts: HashMap::new(),
             default_host: None,
             scenario_dir: Some("./scenarios".to_string()),
+            metrics_interval_secs: None,
+            anonymize_rules: Vec::new(),
             version: default_version(),
         }
     }
@@ -82,8 +114,20 @@ impl CliConfig {
         let content = fs::read_to_string(&path)
             .with_context(|| format!("读取配置文件失败: {:?}", path))?;
 
-        toml::from_str(&content)
-            .with_context(|| format!("解析配置文件失败: {:?}", path))
+        let mut config: Self = toml::from_str(&content)
+            .with_context(|| format!("解析配置文件失败: {:?}", path))?;
+        config.apply_sudo_password(std::env::var(SUDO_PASSWORD_ENV).ok());
+        Ok(config)
+    }
+
+    /// 为开启了 sudo 的主机设置运行时提供的密码 (密码不写入配置文件)
+    fn apply_sudo_password(&mut self, password: Option<String>) {
+        let Some(password) = password.filter(|password| !password.is_empty()) else {
+            return;
+        };
+        for ssh in self.hosts.values_mut().filter_map(|host| host.ssh.as_mut()) {
+            ssh.set_sudo_password(Some(password.clone()));
+        }
     }
 
     /// 保存配置
@@ -99,7 +143,7 @@ impl CliConfig {
         let content = toml::to_string_pretty(self)
             .context("序列化配置失败")?;
 
-        fs::write(&path, content)
+        write_private(&path, &content)
             .with_context(|| format!("写入配置文件失败: {:?}", path))?;
 
         Ok(())
@@ -108,7 +152,7 @@ impl CliConfig {
     /// 添加主机
     pub fn add_host(&mut self, id: &str, host: &str, uri: Option<String>) -> Result<()> {
         if self.hosts.contains_key(id) {
-            anyhow::bail!("主机 {} 已存在", id);
+            anyhow::bail!(tr(MsgKey::HostExists, &[id]));
         }
 
         let config = HostConfig {
@@ -116,6 +160,7 @@ impl CliConfig {
             uri,
             tags: Vec::new(),
             metadata: HashMap::new(),
+            ssh: None,
         };
 
         self.hosts.insert(id.to_string(), config);
@@ -128,10 +173,20 @@ impl CliConfig {
         Ok(())
     }
 
+    /// 设置主机的 SSH 配置
+    pub fn set_host_ssh(&mut self, id: &str, ssh: Option<SshConfig>) -> Result<()> {
+        let host = self
+            .hosts
+            .get_mut(id)
+            .ok_or_else(|| anyhow::anyhow!(tr(MsgKey::HostNotFound, &[id])))?;
+        host.ssh = ssh;
+        Ok(())
+    }
+
     /// 移除主机
     pub fn remove_host(&mut self, id: &str) -> Result<()> {
         if !self.hosts.contains_key(id) {
-            anyhow::bail!("主机 {} 不存在", id);
+            anyhow::bail!(tr(MsgKey::HostNotFound, &[id]));
         }
 
         self.hosts.remove(id);
@@ -159,10 +214,21 @@ impl CliConfig {
             .collect()
     }
 
+    /// 按配置的主机构造传输层配置 (主机按 ID 排序)
+    pub fn transport_config(&self) -> TransportConfig {
+        let mut hosts: Vec<HostEntry> = self.hosts.iter().map(|(id, config)| config.to_host_entry(id)).collect();
+        hosts.sort_by(|a, b| a.id.cmp(&b.id));
+
+        TransportConfig {
+            hosts,
+            ..TransportConfig::default()
+        }
+    }
+
     /// 设置默认主机
     pub fn set_default_host(&mut self, id: &str) -> Result<()> {
         if !self.hosts.contains_key(id) {
-            anyhow::bail!("主机 {} 不存在", id);
+            anyhow::bail!(tr(MsgKey::HostNotFound, &[id]));
         }
 
         self.default_host = Some(id.to_string());
@@ -175,6 +241,29 @@ impl CliConfig {
     }
 }
 
+/// 写入只有当前用户可以读写的文件 (Unix 上为 0600, 已有文件同样收紧权限)
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        file.write_all(content.as_bytes())
+    }
+
+    #[cfg(not(unix))]
+    {
+        fs::write(path, content)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -187,6 +276,22 @@ mod tests {
         assert_eq!(config.scenario_dir, Some("./scenarios".to_string()));
     }
 
+    #[test]
+    fn test_transport_config() {
+        let mut config = CliConfig::default();
+        config.add_host("node-2", "192.168.1.102", Some("qemu+tcp://192.168.1.102/system".to_string())).unwrap();
+        config.add_host("node-1", "192.168.1.101", None).unwrap();
+        config.hosts.get_mut("node-1").unwrap().tags = vec!["gpu".to_string()];
+
+        let transport = config.transport_config();
+        let hosts: Vec<_> = transport.hosts.iter().map(|host| host.to_host_info()).collect();
+        assert_eq!(hosts[0].id, "node-1");
+        assert_eq!(hosts[0].uri, "qemu+ssh://192.168.1.101:22/system");
+        assert_eq!(hosts[0].tags, vec!["gpu".to_string()]);
+        assert_eq!(hosts[1].uri, "qemu+tcp://192.168.1.102/system");
+        assert!(transport.validate().is_ok());
+    }
+
     #[test]
     fn test_add_remove_host() {
         let mut config = CliConfig::default();
@@ -220,4 +325,61 @@ mod tests {
         let result = config.add_host("host1", "192.168.1.101", None);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_host_ssh_through_jump() {
+        let mut config = CliConfig::default();
+        config.add_host("host1", "10.0.0.1", None).unwrap();
+
+        let mut jump: atp_transport::SshJump = "ops@bastion:2222".parse().unwrap();
+        jump.ssh = jump.ssh.with_identity_file("/keys/ops");
+        let ssh = SshConfig::new("root").with_jump(jump);
+        config.set_host_ssh("host1", Some(ssh.clone())).unwrap();
+        assert!(config.set_host_ssh("missing", None).is_err());
+
+        // 跳板机写入 [hosts.host1.ssh.jump], 读回后与原配置一致
+        let text = toml::to_string_pretty(&config).unwrap();
+        assert!(text.contains("[hosts.host1.ssh.jump]"), "{}", text);
+        let loaded: CliConfig = toml::from_str(&text).unwrap();
+        assert_eq!(loaded.get_host("host1").unwrap().ssh, Some(ssh));
+    }
+
+    #[test]
+    fn test_sudo_password_not_saved() {
+        let mut config = CliConfig::default();
+        config.add_host("host1", "10.0.0.1", None).unwrap();
+        config.add_host("host2", "10.0.0.2", None).unwrap();
+        config
+            .set_host_ssh("host1", Some(SshConfig::new("ops").with_sudo(Some("p@ss".to_string()))))
+            .unwrap();
+        config.set_host_ssh("host2", Some(SshConfig::new("root"))).unwrap();
+
+        let text = toml::to_string_pretty(&config).unwrap();
+        assert!(!text.contains("p@ss"), "{}", text);
+        assert!(text.contains("[hosts.host1.ssh.sudo]"), "{}", text);
+
+        // 读回后密码来自运行时 (环境变量), 只设置给开启了 sudo 的主机
+        let mut loaded: CliConfig = toml::from_str(&text).unwrap();
+        loaded.apply_sudo_password(Some("p@ss".to_string()));
+        assert_eq!(loaded.get_host("host1").unwrap().ssh, config.get_host("host1").unwrap().ssh);
+        assert_eq!(loaded.get_host("host2").unwrap().ssh, Some(SshConfig::new("root")));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("atp-config-{}.toml", std::process::id()));
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_private(&path, "new").unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(content, "new");
+        assert_eq!(mode, 0o600);
+    }
 }
@@ -0,0 +1,65 @@
+//! en-US 消息表
+
+use super::MsgKey;
+
+pub(super) const MESSAGES: &[(MsgKey, &str)] = &[
+    // 通用
+    (MsgKey::ErrorPrefix, "Error"),
+    (MsgKey::HintPrefix, "Hint"),
+    (MsgKey::LabelHost, "Host"),
+    (MsgKey::LabelVm, "VM"),
+    (MsgKey::LabelAddress, "Address"),
+    (MsgKey::LabelTags, "Tags"),
+    (MsgKey::LabelKey, "Key"),
+    (MsgKey::LabelText, "Text"),
+    (MsgKey::LabelPosition, "Position"),
+    (MsgKey::LabelButton, "Button"),
+    (MsgKey::LabelCommand, "Command"),
+    (MsgKey::LabelScript, "Script"),
+    (MsgKey::LabelJumpHost, "Jump host"),
+    (MsgKey::LabelStatus, "Status"),
+    (MsgKey::LabelLastSeen, "last seen"),
+    (MsgKey::LabelMax, "max"),
+    (MsgKey::ScenarioOnly, "This feature is only available through scenario files"),
+    (MsgKey::UseScenarioRun, "Use 'atp scenario run <file>' to run a complete test scenario"),
+
+    // 主机管理
+    (MsgKey::HostAdded, "Host {} added"),
+    (MsgKey::HostSetDefault, "Set as default host"),
+    (MsgKey::HostNoneConfigured, "No hosts configured"),
+    (MsgKey::HostAddUsage, "Add a host with:"),
+    (MsgKey::HostListTitle, "Configured hosts:"),
+    (MsgKey::HostDefaultMarker, "(default)"),
+    (MsgKey::HostRemoved, "Host {} removed"),
+    (MsgKey::HostExists, "Host {} already exists"),
+    (MsgKey::HostNotFound, "Host {} not found"),
+    (MsgKey::HostProbing, "Connecting to libvirt on host {}..."),
+    (MsgKey::HostStatusOnline, "online"),
+    (MsgKey::HostStatusUnreachable, "unreachable"),
+    (MsgKey::HostStatusUnknown, "not probed"),
+    (MsgKey::HostTagged, "Tags of host {}: {}"),
+
+    // 键盘 / 鼠标 / 命令
+    (MsgKey::PreparingKey, "Preparing to send key..."),
+    (MsgKey::PreparingText, "Preparing to send text..."),
+    (MsgKey::PreparingClick, "Preparing mouse click..."),
+    (MsgKey::PreparingMove, "Preparing mouse move..."),
+    (MsgKey::PreparingCommand, "Preparing to execute command..."),
+    (MsgKey::ResolvingVm, "Looking up the VM on the VDI platform..."),
+    (MsgKey::ResolvedVm, "VM {} is on host {} ({})"),
+    (MsgKey::OperationDone, "Done"),
+
+    // 已知错误的提示
+    (MsgKey::HintHostNotFound, "Run 'atp host list' to see configured hosts, or 'atp host add' to add one"),
+    (MsgKey::HintDomainNotFound, "Make sure the VM name is correct and its host is connected"),
+    (MsgKey::HintAmbiguousDomain, "The VM name exists on several hosts; set target_host in the scenario"),
+    (MsgKey::HintConnectionFailed, "Check that the host is reachable and libvirtd is running"),
+    (MsgKey::HintConnectionTimeout, "The host timed out; check the network or increase the timeout"),
+    (MsgKey::HintLibvirt, "Check the libvirt URI and access permissions"),
+    (MsgKey::HintVdiAuth, "Check the VDI username and password in the config file"),
+    (MsgKey::HintVdiUnreachable, "Check that the VDI platform address in the config file is reachable"),
+    (MsgKey::HintVdiNotFound, "Make sure the resource ID or name exists on the VDI platform"),
+    (MsgKey::HintDatabase, "Check the database path and permissions; use 'atp db restore' to recover from a backup"),
+    (MsgKey::HintScenarioLoad, "Check the scenario file path and YAML syntax; 'atp scenario run --dry-run' validates it"),
+    (MsgKey::HintStepTimeout, "The step timed out; increase its timeout in the scenario"),
+];
@@ -0,0 +1,293 @@
+//! CLI 输出本地化
+//!
+//! 内置 zh-CN 与 en-US 两套消息表 (`MsgKey` → 文案), 通过 `--lang` 或
+//! `ATP_LANG` 环境变量选择, 默认 zh-CN。文案中的 `{}` 按顺序替换为参数。
+//!
+//! 核心库的错误信息保持原样, 只在渲染时为已知错误附加本地化的提示。
+
+mod en_us;
+mod zh_cn;
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use atp_executor::ExecutorError;
+use atp_storage::StorageError;
+use atp_transport::TransportError;
+use atp_vdiplatform::VdiError;
+
+/// 输出语言
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Lang {
+    #[default]
+    ZhCn,
+    EnUs,
+}
+
+impl Lang {
+    fn table(self) -> &'static [(MsgKey, &'static str)] {
+        match self {
+            Self::ZhCn => zh_cn::MESSAGES,
+            Self::EnUs => en_us::MESSAGES,
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    /// 接受 `zh`、`zh-CN`、`en_US.UTF-8` 等写法
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let tag = s.split('.').next().unwrap_or_default().replace('_', "-").to_lowercase();
+        match tag.split('-').next() {
+            Some("zh") => Ok(Self::ZhCn),
+            Some("en") => Ok(Self::EnUs),
+            _ => Err(format!("不支持的语言: {} (可选: zh-CN, en-US)", s)),
+        }
+    }
+}
+
+impl fmt::Display for Lang {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZhCn => write!(f, "zh-CN"),
+            Self::EnUs => write!(f, "en-US"),
+        }
+    }
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+/// 设置输出语言 (启动时调用一次, 之后的调用被忽略)
+pub fn set_lang(lang: Lang) {
+    let _ = LANG.set(lang);
+}
+
+/// 当前输出语言
+pub fn lang() -> Lang {
+    LANG.get().copied().unwrap_or_default()
+}
+
+macro_rules! msg_keys {
+    ($($key:ident),* $(,)?) => {
+        /// 消息 key
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum MsgKey {
+            $($key,)*
+        }
+
+        #[cfg(test)]
+        impl MsgKey {
+            /// 全部 key, 用于校验消息表完整性
+            pub const ALL: &'static [MsgKey] = &[$(MsgKey::$key,)*];
+        }
+    };
+}
+
+msg_keys! {
+    // 通用
+    ErrorPrefix,
+    HintPrefix,
+    LabelHost,
+    LabelVm,
+    LabelAddress,
+    LabelTags,
+    LabelKey,
+    LabelText,
+    LabelPosition,
+    LabelButton,
+    LabelCommand,
+    LabelScript,
+    LabelJumpHost,
+    LabelStatus,
+    LabelLastSeen,
+    LabelMax,
+    ScenarioOnly,
+    UseScenarioRun,
+
+    // 主机管理
+    HostAdded,
+    HostSetDefault,
+    HostNoneConfigured,
+    HostAddUsage,
+    HostListTitle,
+    HostDefaultMarker,
+    HostRemoved,
+    HostExists,
+    HostNotFound,
+    HostProbing,
+    HostStatusOnline,
+    HostStatusUnreachable,
+    HostStatusUnknown,
+    HostTagged,
+
+    // 键盘 / 鼠标 / 命令
+    PreparingKey,
+    PreparingText,
+    PreparingClick,
+    PreparingMove,
+    PreparingCommand,
+    ResolvingVm,
+    ResolvedVm,
+    OperationDone,
+
+    // 已知错误的提示
+    HintHostNotFound,
+    HintDomainNotFound,
+    HintAmbiguousDomain,
+    HintConnectionFailed,
+    HintConnectionTimeout,
+    HintLibvirt,
+    HintVdiAuth,
+    HintVdiUnreachable,
+    HintVdiNotFound,
+    HintDatabase,
+    HintScenarioLoad,
+    HintStepTimeout,
+}
+
+/// 按当前语言取文案, 缺失时回退到 zh-CN
+pub fn t(key: MsgKey) -> &'static str {
+    lookup(lang(), key)
+}
+
+/// 取文案并按顺序替换 `{}` 占位符
+pub fn tr(key: MsgKey, args: &[&str]) -> String {
+    format_message(t(key), args)
+}
+
+fn lookup(lang: Lang, key: MsgKey) -> &'static str {
+    find(lang.table(), key)
+        .or_else(|| find(Lang::ZhCn.table(), key))
+        .unwrap_or("")
+}
+
+fn find(table: &'static [(MsgKey, &'static str)], key: MsgKey) -> Option<&'static str> {
+    table.iter().find(|(k, _)| *k == key).map(|(_, text)| *text)
+}
+
+fn format_message(template: &str, args: &[&str]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut args = args.iter();
+    let mut rest = template;
+    while let Some(pos) = rest.find("{}") {
+        out.push_str(&rest[..pos]);
+        out.push_str(args.next().copied().unwrap_or("{}"));
+        rest = &rest[pos + 2..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// 已知错误对应的提示 (沿错误链查找第一个可识别的核心库错误)
+pub fn hint_for(err: &anyhow::Error) -> Option<MsgKey> {
+    err.chain().find_map(|cause| {
+        if let Some(e) = cause.downcast_ref::<TransportError>() {
+            transport_hint(e)
+        } else if let Some(e) = cause.downcast_ref::<VdiError>() {
+            vdi_hint(e)
+        } else if let Some(e) = cause.downcast_ref::<ExecutorError>() {
+            executor_hint(e)
+        } else if let Some(e) = cause.downcast_ref::<StorageError>() {
+            storage_hint(e)
+        } else {
+            None
+        }
+    })
+}
+
+fn transport_hint(e: &TransportError) -> Option<MsgKey> {
+    match e {
+        TransportError::HostNotFound(_) => Some(MsgKey::HintHostNotFound),
+        TransportError::DomainNotFound(_) => Some(MsgKey::HintDomainNotFound),
+        TransportError::AmbiguousDomain(..) => Some(MsgKey::HintAmbiguousDomain),
+        TransportError::ConnectionFailed(_) | TransportError::Disconnected => {
+            Some(MsgKey::HintConnectionFailed)
+        }
+        TransportError::Timeout => Some(MsgKey::HintConnectionTimeout),
+        TransportError::LibvirtError(_) => Some(MsgKey::HintLibvirt),
+        // 带上下文的错误由错误链中的 source 决定
+        _ => None,
+    }
+}
+
+fn vdi_hint(e: &VdiError) -> Option<MsgKey> {
+    match e {
+        VdiError::AuthError(_) => Some(MsgKey::HintVdiAuth),
+        VdiError::HttpError(_) | VdiError::Timeout(_) => Some(MsgKey::HintVdiUnreachable),
+        VdiError::NotFound(_) => Some(MsgKey::HintVdiNotFound),
+        _ => None,
+    }
+}
+
+fn executor_hint(e: &ExecutorError) -> Option<MsgKey> {
+    match e {
+        ExecutorError::ScenarioLoadFailed(_) => Some(MsgKey::HintScenarioLoad),
+        ExecutorError::Timeout => Some(MsgKey::HintStepTimeout),
+        ExecutorError::DatabaseError(_) => Some(MsgKey::HintDatabase),
+        _ => None,
+    }
+}
+
+fn storage_hint(e: &StorageError) -> Option<MsgKey> {
+    match e {
+        StorageError::ConnectionError(_) | StorageError::MigrationError(_) => {
+            Some(MsgKey::HintDatabase)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn keys(table: &[(MsgKey, &str)]) -> HashSet<MsgKey> {
+        table.iter().map(|(k, _)| *k).collect()
+    }
+
+    #[test]
+    fn test_message_tables_complete() {
+        let all: HashSet<MsgKey> = MsgKey::ALL.iter().copied().collect();
+
+        for lang in [Lang::ZhCn, Lang::EnUs] {
+            let table = lang.table();
+            assert_eq!(keys(table), all, "{} 消息表与 MsgKey 不一致", lang);
+            assert_eq!(table.len(), all.len(), "{} 消息表存在重复 key", lang);
+            assert!(table.iter().all(|(_, text)| !text.is_empty()), "{} 存在空文案", lang);
+        }
+
+        // 同一 key 在两种语言中的占位符数量一致
+        for key in MsgKey::ALL {
+            assert_eq!(
+                lookup(Lang::ZhCn, *key).matches("{}").count(),
+                lookup(Lang::EnUs, *key).matches("{}").count(),
+                "{:?} 占位符数量不一致",
+                key
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_lang() {
+        assert_eq!("zh-CN".parse::<Lang>().unwrap(), Lang::ZhCn);
+        assert_eq!("zh".parse::<Lang>().unwrap(), Lang::ZhCn);
+        assert_eq!("en_US.UTF-8".parse::<Lang>().unwrap(), Lang::EnUs);
+        assert_eq!("EN".parse::<Lang>().unwrap(), Lang::EnUs);
+        assert!("fr-FR".parse::<Lang>().is_err());
+    }
+
+    #[test]
+    fn test_format_and_hint() {
+        assert_eq!(format_message("Host {} added", &["h1"]), "Host h1 added");
+        assert_eq!(lookup(Lang::EnUs, MsgKey::HostRemoved).replace("{}", "h1"), "Host h1 removed");
+
+        // 核心库错误经过 anyhow context 包装后仍能识别
+        let err = anyhow::Error::new(TransportError::DomainNotFound("vm-1".into()))
+            .context("执行场景失败");
+        assert_eq!(hint_for(&err), Some(MsgKey::HintDomainNotFound));
+        assert_eq!(hint_for(&anyhow::anyhow!("其他错误")), None);
+    }
+}
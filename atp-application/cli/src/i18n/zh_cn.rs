@@ -0,0 +1,65 @@
+//! zh-CN 消息表
+
+use super::MsgKey;
+
+pub(super) const MESSAGES: &[(MsgKey, &str)] = &[
+    // 通用
+    (MsgKey::ErrorPrefix, "错误"),
+    (MsgKey::HintPrefix, "提示"),
+    (MsgKey::LabelHost, "主机"),
+    (MsgKey::LabelVm, "虚拟机"),
+    (MsgKey::LabelAddress, "地址"),
+    (MsgKey::LabelTags, "标签"),
+    (MsgKey::LabelKey, "按键"),
+    (MsgKey::LabelText, "文本"),
+    (MsgKey::LabelPosition, "位置"),
+    (MsgKey::LabelButton, "按钮"),
+    (MsgKey::LabelCommand, "命令"),
+    (MsgKey::LabelScript, "脚本"),
+    (MsgKey::LabelJumpHost, "跳板机"),
+    (MsgKey::LabelStatus, "状态"),
+    (MsgKey::LabelLastSeen, "最近在线"),
+    (MsgKey::LabelMax, "最大"),
+    (MsgKey::ScenarioOnly, "此功能需要通过场景文件使用"),
+    (MsgKey::UseScenarioRun, "使用 'atp scenario run <file>' 来执行完整的测试场景"),
+
+    // 主机管理
+    (MsgKey::HostAdded, "主机 {} 添加成功"),
+    (MsgKey::HostSetDefault, "已设置为默认主机"),
+    (MsgKey::HostNoneConfigured, "没有配置任何主机"),
+    (MsgKey::HostAddUsage, "使用以下命令添加主机:"),
+    (MsgKey::HostListTitle, "配置的主机列表:"),
+    (MsgKey::HostDefaultMarker, "(默认)"),
+    (MsgKey::HostRemoved, "主机 {} 已移除"),
+    (MsgKey::HostExists, "主机 {} 已存在"),
+    (MsgKey::HostNotFound, "主机 {} 不存在"),
+    (MsgKey::HostProbing, "连接主机 {} 的 libvirt..."),
+    (MsgKey::HostStatusOnline, "在线"),
+    (MsgKey::HostStatusUnreachable, "无法连接"),
+    (MsgKey::HostStatusUnknown, "未探测"),
+    (MsgKey::HostTagged, "主机 {} 的标签: {}"),
+
+    // 键盘 / 鼠标 / 命令
+    (MsgKey::PreparingKey, "准备发送按键..."),
+    (MsgKey::PreparingText, "准备发送文本..."),
+    (MsgKey::PreparingClick, "准备鼠标点击..."),
+    (MsgKey::PreparingMove, "准备移动鼠标..."),
+    (MsgKey::PreparingCommand, "准备执行命令..."),
+    (MsgKey::ResolvingVm, "从 VDI 平台查找虚拟机..."),
+    (MsgKey::ResolvedVm, "虚拟机 {} 位于主机 {} ({})"),
+    (MsgKey::OperationDone, "执行完成"),
+
+    // 已知错误的提示
+    (MsgKey::HintHostNotFound, "使用 'atp host list' 查看已配置的主机, 或用 'atp host add' 添加"),
+    (MsgKey::HintDomainNotFound, "确认虚拟机名称正确且所在主机已连接"),
+    (MsgKey::HintAmbiguousDomain, "虚拟机名称在多个主机上重复, 请在场景中指定 target_host"),
+    (MsgKey::HintConnectionFailed, "检查主机地址是否可达, 以及 libvirtd 是否在运行"),
+    (MsgKey::HintConnectionTimeout, "主机响应超时, 检查网络或适当增大超时时间"),
+    (MsgKey::HintLibvirt, "检查 libvirt URI 与访问权限"),
+    (MsgKey::HintVdiAuth, "检查配置文件中的 VDI 用户名与密码"),
+    (MsgKey::HintVdiUnreachable, "检查配置文件中的 VDI 平台地址是否可达"),
+    (MsgKey::HintVdiNotFound, "确认 VDI 平台上的资源 ID 或名称正确"),
+    (MsgKey::HintDatabase, "检查数据库路径与权限, 必要时用 'atp db restore' 从备份恢复"),
+    (MsgKey::HintScenarioLoad, "检查场景文件路径与 YAML 格式, 可用 'atp scenario run --dry-run' 校验"),
+    (MsgKey::HintStepTimeout, "步骤执行超时, 可在场景中增大该步骤的 timeout"),
+];
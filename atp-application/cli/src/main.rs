@@ -1,11 +1,16 @@
 //! ATP CLI 应用
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use colored::Colorize;
 use tracing::{info, Level};
 
 mod commands;
 mod config;
+mod i18n;
+
+use commands::common::OutputFormat;
+use i18n::{t, Lang, MsgKey};
 
 #[derive(Parser)]
 #[command(name = "atp")]
@@ -16,6 +21,20 @@ struct Cli {
     #[arg(short, long, default_value = "info")]
     log_level: String,
 
+    /// 测试配置 profile (对应配置文件中的 [profile.<name>])
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// 输出语言 (zh-CN / en-US), 默认 zh-CN
+    #[arg(long, global = true, env = "ATP_LANG")]
+    lang: Option<Lang>,
+
+    /// 结果输出格式 (table / json / yaml), 放在子命令之前, 优先于子命令的 --format
+    ///
+    /// 例如 `atp --output json vdi list-vms ...`; json / yaml 输出时进度信息写到标准错误。
+    #[arg(long, env = "ATP_OUTPUT")]
+    output: Option<OutputFormat>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -69,6 +88,22 @@ enum Commands {
         #[command(subcommand)]
         action: VdiAction,
     },
+
+    /// 基准测试
+    Bench {
+        #[command(subcommand)]
+        action: BenchAction,
+    },
+
+    /// 交互式会话: 连接一台虚拟机后逐条执行按键、文本、命令等操作
+    Shell {
+        #[command(flatten)]
+        target: VmTargetArgs,
+
+        /// 历史记录文件 (默认 ~/.config/atp/shell_history)
+        #[arg(long)]
+        history: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -79,38 +114,67 @@ enum HostAction {
         id: String,
         /// 主机地址
         host: String,
-        /// Libvirt URI
+        /// Libvirt URI (默认 qemu+ssh://<HOST>:22/system)
         #[arg(long)]
         uri: Option<String>,
+        /// 主机标签 (可重复指定)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// SSH 登录用户 (设置任一 --ssh-* 参数后可执行 virsh 等宿主机命令, 默认 root)
+        #[arg(long)]
+        ssh_user: Option<String>,
+        /// SSH 端口
+        #[arg(long)]
+        ssh_port: Option<u16>,
+        /// SSH 私钥文件
+        #[arg(long)]
+        ssh_key: Option<String>,
+        /// 经由跳板机连接, 格式: [user@]host[:port]
+        #[arg(long, value_parser = |s: &str| s.parse::<atp_transport::SshJump>().map(Box::new))]
+        ssh_jump: Option<Box<atp_transport::SshJump>>,
+        /// 登录跳板机使用的私钥文件
+        #[arg(long, requires = "ssh_jump")]
+        ssh_jump_key: Option<String>,
+        /// 以 sudo 提权执行宿主机命令 (登录用户不是 root 时使用)
+        #[arg(long)]
+        ssh_sudo: bool,
+        /// sudo 密码 (设置后隐含 --ssh-sudo, 未设置时要求免密 sudo)。
+        /// 不写入配置文件, 之后的命令从环境变量 ATP_SSH_SUDO_PASSWORD 读取
+        #[arg(long, env = "ATP_SSH_SUDO_PASSWORD", hide_env_values = true)]
+        ssh_sudo_password: Option<String>,
     },
-    /// 列出主机
+    /// 列出主机及最近一次连接探测的状态
     List,
-    /// 移除主机
+    /// 移除主机 (已连接时先断开)
     Remove { id: String },
+    /// 重新连接主机, 刷新 libvirt 版本与主机能力
+    Probe { id: String },
+    /// 为主机添加标签
+    Tag {
+        id: String,
+        /// 标签 (可指定多个)
+        #[arg(required = true)]
+        tags: Vec<String>,
+        /// 移除这些标签
+        #[arg(long)]
+        remove: bool,
+    },
 }
 
 #[derive(Subcommand)]
 enum KeyboardAction {
     /// 发送按键
     Send {
-        /// 主机 ID
-        #[arg(long)]
-        host: String,
-        /// 虚拟机名称
-        #[arg(long)]
-        vm: String,
-        /// 按键
+        #[command(flatten)]
+        target: VmTargetArgs,
+        /// 按键或按键组合 (如 ret、ctrl+alt+del、shift+f5)
         #[arg(long)]
         key: String,
     },
     /// 发送文本
     Text {
-        /// 主机 ID
-        #[arg(long)]
-        host: String,
-        /// 虚拟机名称
-        #[arg(long)]
-        vm: String,
+        #[command(flatten)]
+        target: VmTargetArgs,
         /// 文本内容
         text: String,
     },
@@ -120,12 +184,8 @@ enum KeyboardAction {
 enum MouseAction {
     /// 鼠标点击
     Click {
-        /// 主机 ID
-        #[arg(long)]
-        host: String,
-        /// 虚拟机名称
-        #[arg(long)]
-        vm: String,
+        #[command(flatten)]
+        target: VmTargetArgs,
         /// X 坐标
         #[arg(long)]
         x: i32,
@@ -138,12 +198,8 @@ enum MouseAction {
     },
     /// 鼠标移动
     Move {
-        /// 主机 ID
-        #[arg(long)]
-        host: String,
-        /// 虚拟机名称
-        #[arg(long)]
-        vm: String,
+        #[command(flatten)]
+        target: VmTargetArgs,
         /// X 坐标
         #[arg(long)]
         x: i32,
@@ -157,26 +213,129 @@ enum MouseAction {
 enum CommandAction {
     /// 执行命令
     Exec {
-        /// 主机 ID
-        #[arg(long)]
-        host: String,
-        /// 虚拟机名称
-        #[arg(long)]
-        vm: String,
+        #[command(flatten)]
+        target: VmTargetArgs,
         /// 命令
         cmd: String,
     },
+
+    /// 执行 PowerShell 脚本 (Windows 客户机, 多个脚本复用同一个 QGA 连接)
+    #[command(group(clap::ArgGroup::new("source").args(["script", "inline"]).required(true).multiple(true)))]
+    Ps {
+        #[command(flatten)]
+        target: VmTargetArgs,
+
+        /// 脚本文件 (可重复, 按顺序执行)
+        #[arg(long)]
+        script: Vec<String>,
+
+        /// 内联脚本 (可重复, 在脚本文件之后执行)
+        #[arg(long)]
+        inline: Vec<String>,
+
+        /// 脚本参数 (传给每个脚本)
+        #[arg(long, num_args = 1.., allow_hyphen_values = true)]
+        args: Vec<String>,
+
+        /// 以 ConvertTo-Json 输出脚本结果并解析 (配合 --output json)
+        #[arg(long)]
+        as_json: bool,
+
+        /// 单个脚本的超时时间 (秒)
+        #[arg(long, default_value = "300")]
+        timeout: u64,
+    },
 }
 
 #[derive(Subcommand)]
 enum ScenarioAction {
     /// 运行场景
     Run {
+        /// 场景文件路径
+        #[arg(required_unless_present = "name", conflicts_with = "name")]
+        file: Option<String>,
+
+        /// 运行已保存的场景: 名称[@版本], 不指定版本时运行最新版本
+        #[arg(long)]
+        name: Option<String>,
+
+        /// 演练模式: 只校验场景定义, 不连接虚拟机
+        #[arg(long)]
+        dry_run: bool,
+
+        /// 只执行带有这些标签的步骤 (逗号分隔)
+        #[arg(long, value_delimiter = ',')]
+        tags: Vec<String>,
+
+        /// 跳过带有这些标签的步骤 (逗号分隔)
+        #[arg(long, value_delimiter = ',')]
+        skip_tags: Vec<String>,
+
+        /// 输出结构化进度事件 (jsonl/log)
+        #[arg(long)]
+        progress: Option<String>,
+
+        /// 进度事件输出文件 (默认写入标准错误)
+        #[arg(long, requires = "progress")]
+        progress_file: Option<String>,
+
+        /// 按此间隔 (毫秒) 采样每个步骤期间的虚拟机 CPU/内存/磁盘 I/O, 写入报告
+        #[arg(long, value_name = "MS", value_parser = clap::value_parser!(u64).range(1..))]
+        step_metrics_ms: Option<u64>,
+
+        #[command(flatten)]
+        fan_out: Box<ScenarioFanOutArgs>,
+    },
+    /// 列出已保存的场景及最近一次执行结果
+    List {
+        /// 列出场景目录中的场景文件
+        #[arg(long)]
+        files: bool,
+    },
+
+    /// 保存场景定义 (内容变化时版本加 1)
+    Save {
+        /// 场景文件路径
+        file: String,
+    },
+
+    /// 对比已保存场景的两个版本
+    Diff {
+        /// 场景名称
+        name: String,
+
+        /// 旧版本
+        from: i32,
+
+        /// 新版本
+        to: i32,
+    },
+
+    /// 从模板创建带注释的场景文件
+    New {
+        /// 输出的场景文件路径
+        file: String,
+
+        /// 模板 (vdi-lifecycle/input-verify/mixed)
+        #[arg(short, long, default_value = "input-verify")]
+        template: String,
+
+        /// 覆盖已存在的文件
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// 校验场景文件 (解析 + 静态校验, 不连接任何基础设施)
+    Validate {
+        /// 场景文件路径
+        file: String,
+    },
+
+    /// 显示场景的执行计划 (步骤、动作、目标、超时)
+    Explain {
         /// 场景文件路径
         file: String,
     },
-    /// 列出场景
-    List,
 }
 
 #[derive(Subcommand)]
@@ -195,6 +354,11 @@ pub enum ReportAction {
         #[arg(short, long)]
         failed: bool,
 
+        /// 只显示含该分类失败步骤的报告
+        /// (infrastructure/protocol/verification/timeout/configuration/unknown)
+        #[arg(long, value_name = "KIND", value_parser = |s: &str| s.parse::<atp_executor::StepErrorKind>())]
+        failed_kind: Option<atp_executor::StepErrorKind>,
+
         /// 限制数量
         #[arg(short, long, default_value = "10")]
         limit: i64,
@@ -206,18 +370,64 @@ pub enum ReportAction {
         id: i64,
     },
 
+    /// 对比两次报告, 列出新失败、新通过、持续失败与耗时退化的步骤
+    ///
+    /// 有新失败的步骤时以非零状态退出, 可用于 CI 判定回归。
+    Diff {
+        /// 基准报告 ID
+        baseline: i64,
+
+        /// 本次报告 ID
+        current: i64,
+
+        /// 耗时增加超过该百分比视为退化
+        #[arg(long, default_value = "30")]
+        threshold: f64,
+
+        /// 同时列出没有变化的步骤
+        #[arg(long)]
+        all: bool,
+
+        /// 输出格式 (table/json/yaml)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+    },
+
     /// 导出报告
     Export {
         /// 报告 ID
-        id: i64,
+        #[arg(required_unless_present = "ids", conflicts_with = "ids")]
+        id: Option<i64>,
+
+        /// 对比多份报告 (逗号分隔, 仅支持 html 格式, 输出按步骤对齐的通过/失败矩阵)
+        #[arg(long, value_delimiter = ',', num_args = 1.., conflicts_with = "bundle")]
+        ids: Vec<i64>,
 
         /// 输出文件路径
         #[arg(short, long)]
         output: String,
 
-        /// 输出格式(json/yaml)
+        /// 输出格式(json/yaml/html)
         #[arg(short, long, default_value = "json")]
         format: String,
+
+        /// 导出为可导入的报告包 (JSON, 含步骤、资源与导出环境信息)
+        #[arg(long)]
+        bundle: bool,
+
+        /// 匿名化导出: IP、主机名、用户名替换为一致的假名
+        #[arg(long)]
+        anonymize: bool,
+
+        /// 匿名化映射表输出路径 (默认: <输出文件>.mapping.json)
+        #[arg(long, requires = "anonymize")]
+        mapping: Option<String>,
+    },
+
+    /// 导入报告包 (由 export --bundle 生成)
+    Import {
+        /// 报告包文件路径
+        file: String,
     },
 
     /// 删除报告
@@ -234,6 +444,18 @@ pub enum ReportAction {
         /// 天数
         #[arg(short, long, default_value = "30")]
         days: i32,
+
+        /// 判定不稳定步骤所需的最少执行次数
+        #[arg(long, default_value = "3")]
+        min_runs: i64,
+
+        /// 最慢步骤显示数量
+        #[arg(short, long, default_value = "5")]
+        limit: i64,
+
+        /// 输出格式 (table/json/yaml)
+        #[arg(short, long, default_value = "table")]
+        format: String,
     },
 
     /// 清理旧报告
@@ -245,6 +467,183 @@ pub enum ReportAction {
         /// 强制删除不提示确认
         #[arg(short, long)]
         force: bool,
+
+        /// 场景名称匹配模式(支持 * 和 ? 通配符)
+        #[arg(short, long)]
+        scenario: Option<String>,
+
+        /// 只清理带有该标签的报告
+        #[arg(short, long)]
+        tag: Option<String>,
+
+        /// 演练模式: 只列出将被删除的报告, 不实际删除
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// 管理报告保留规则
+    Retention {
+        #[command(subcommand)]
+        action: RetentionAction,
+    },
+
+    /// 查看主机 / 虚拟机的时序指标 (步骤资源采样、基准测试写入), 按时间桶降采样
+    Metrics {
+        /// 虚拟机名称
+        #[arg(long, required_unless_present = "host", conflicts_with = "host")]
+        vm: Option<String>,
+
+        /// 主机 ID
+        #[arg(long)]
+        host: Option<String>,
+
+        /// 指标名称, 如 cpu、memory_mb、input_latency_ms
+        #[arg(short, long, default_value = "cpu")]
+        metric: String,
+
+        /// 时间范围, 如 30m、24h、7d
+        #[arg(long, default_value = "1h")]
+        since: String,
+
+        /// 时间桶长度, 如 10s、1m、1h (默认把时间范围分为约 60 个桶)
+        #[arg(long)]
+        bucket: Option<String>,
+
+        /// 输出格式 (table/json/yaml)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+    },
+
+    /// 查看 Guest 验证结果 (由 verification-server 写库)
+    Verification {
+        /// 虚拟机 ID 过滤
+        #[arg(long)]
+        vm: Option<String>,
+
+        /// 时间范围, 如 30m、24h、7d
+        #[arg(long, default_value = "24h")]
+        since: String,
+
+        /// 限制数量
+        #[arg(short, long)]
+        limit: Option<i64>,
+
+        /// 输出格式 (table/json/yaml)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BenchAction {
+    /// 端到端输入延迟: QMP send-key 到 Guest Agent 观察到按键的时间
+    ///
+    /// 在本地启动验证服务器, 等目标虚拟机的 Agent 连上后开始采样。
+    /// 验证超时的样本单独计数, 不参与统计。
+    InputLatency {
+        #[command(flatten)]
+        target: VmTargetArgs,
+
+        /// 样本数
+        #[arg(long, default_value = "200")]
+        samples: usize,
+
+        /// 相邻两个样本的间隔 (毫秒)
+        #[arg(long, default_value = "250")]
+        interval_ms: u64,
+
+        /// 注入的按键 (QMP qcode)
+        #[arg(long, default_value = "shift")]
+        key: String,
+
+        /// 单个样本等待验证结论的时间 (毫秒)
+        #[arg(long, default_value = "5000")]
+        timeout_ms: u64,
+
+        /// Agent 注册使用的虚拟机 ID (默认与虚拟机名称相同)
+        #[arg(long)]
+        agent_id: Option<String>,
+
+        /// 等待 Agent 连接的时间 (秒)
+        #[arg(long, default_value = "60")]
+        agent_wait_secs: u64,
+
+        /// 验证服务器配置文件 (默认使用内置的监听地址)
+        #[arg(long)]
+        server_config: Option<String>,
+
+        /// 构建或版本标签, 便于之后比较
+        #[arg(long)]
+        label: Option<String>,
+
+        /// 不保存结果到数据库
+        #[arg(long)]
+        no_save: bool,
+
+        /// 输出格式 (table/json/yaml)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+    },
+
+    /// 比较两次运行的结果
+    Compare {
+        /// 基准运行 ID
+        base: i64,
+
+        /// 对比运行 ID
+        other: i64,
+
+        /// 输出格式 (table/json/yaml)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+    },
+
+    /// 列出最近的运行
+    List {
+        /// 虚拟机名称过滤
+        #[arg(long)]
+        vm: Option<String>,
+
+        /// 限制数量
+        #[arg(short, long, default_value = "20")]
+        limit: i64,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RetentionAction {
+    /// 添加保留规则
+    Add {
+        /// 场景名称匹配模式(支持 * 和 ? 通配符)
+        scenario: String,
+
+        /// 每个场景保留最近N次执行(不论时间)
+        #[arg(long)]
+        keep_last: Option<i64>,
+
+        /// 保留最近N天内的执行
+        #[arg(long)]
+        keep_days: Option<i64>,
+    },
+
+    /// 列出保留规则
+    List,
+
+    /// 删除保留规则
+    Remove {
+        /// 规则 ID
+        id: i64,
+    },
+
+    /// 按保留规则清理报告(不受任何规则保护的报告将被删除)
+    Apply {
+        /// 强制删除不提示确认
+        #[arg(short, long)]
+        force: bool,
+
+        /// 演练模式: 只列出将被删除的报告, 不实际删除
+        #[arg(long)]
+        dry_run: bool,
     },
 }
 
@@ -310,11 +709,26 @@ pub enum DbAction {
         #[arg(short = 'b', long)]
         backup_dir: Option<String>,
     },
+
+    /// 执行待执行的数据库迁移
+    Migrate {
+        /// 数据库路径
+        #[arg(short, long, default_value = "~/.config/atp/data.db")]
+        db_path: String,
+    },
+
+    /// 查看数据库 schema 版本与完整性
+    Status {
+        /// 数据库路径
+        #[arg(short, long, default_value = "~/.config/atp/data.db")]
+        db_path: String,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum VdiAction {
     /// 验证 VDI 平台与 libvirt 虚拟机状态一致性
+    #[command(group(clap::ArgGroup::new("watch_mode").args(["watch", "until_consistent"]).multiple(true)))]
     Verify {
         /// 配置文件路径
         #[arg(short, long, default_value = "test.toml")]
@@ -327,6 +741,22 @@ pub enum VdiAction {
         /// 输出格式 (table/json/yaml)
         #[arg(short = 'f', long, default_value = "table")]
         format: String,
+
+        /// 持续监视: 定期重新比对, 只输出与上一轮相比的变化, Ctrl-C 结束并输出汇总
+        #[arg(long)]
+        watch: bool,
+
+        /// 监视时两轮比对的间隔 (秒)
+        #[arg(long, default_value_t = 60)]
+        interval: u64,
+
+        /// 监视到某一轮没有不一致的虚拟机时以退出码 0 结束 (隐含 --watch)
+        #[arg(long)]
+        until_consistent: bool,
+
+        /// 最长监视时间 (秒)
+        #[arg(long, requires = "watch_mode")]
+        timeout: Option<u64>,
     },
 
     /// 列出 VDI 平台的所有主机
@@ -345,6 +775,22 @@ pub enum VdiAction {
         /// 主机名过滤
         #[arg(short = 'H', long)]
         host: Option<String>,
+
+        /// 状态过滤 (running/shutoff/paused/hibernated/busy/upgrading)
+        #[arg(short, long)]
+        status: Option<String>,
+
+        /// 绑定用户过滤
+        #[arg(short, long)]
+        user: Option<String>,
+
+        /// 排序字段 (name/status/host/memory)
+        #[arg(long)]
+        sort: Option<String>,
+
+        /// 显示的列 (逗号分隔: name,host,status,cpu,memory,ip,user)
+        #[arg(long, value_delimiter = ',')]
+        columns: Vec<String>,
     },
 
     /// 同步 VDI 主机到本地配置
@@ -357,11 +803,291 @@ pub enum VdiAction {
         #[arg(short, long)]
         test_connection: bool,
     },
+
+    /// 按报告中的环境检查清单清理未清理的新增资源
+    CleanupOrphans {
+        /// 报告 ID
+        #[arg(long)]
+        from_report: i64,
+
+        /// 配置文件路径
+        #[arg(short, long, default_value = "test.toml")]
+        config: String,
+
+        #[command(flatten)]
+        confirm: ConfirmArgs,
+    },
+
+    /// 批量操作虚拟机 (按名称通配符、桌面池或虚拟机 ID 选择目标)
+    Batch {
+        /// 操作 (start/shutdown/reboot)
+        operation: String,
+
+        #[command(flatten)]
+        target: BatchTargetArgs,
+
+        /// 启动时按批放到负载最低的主机 (仅用于 start)
+        #[arg(long)]
+        balanced: bool,
+
+        #[command(flatten)]
+        confirm: ConfirmArgs,
+
+        /// 输出格式 (table/json/yaml, json/yaml 需要同时指定 --yes 或 --dry-run)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+
+        /// 配置文件路径
+        #[arg(short, long, default_value = "test.toml")]
+        config: String,
+    },
+
+    /// 按 CSV 映射 (每行 `虚拟机,用户名`) 把用户分配到虚拟机
+    Assign {
+        /// 分配映射文件路径
+        #[arg(long)]
+        mapping: String,
+
+        #[command(flatten)]
+        confirm: ConfirmArgs,
+
+        /// 输出格式 (table/json/yaml, json/yaml 需要同时指定 --yes 或 --dry-run)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+
+        /// 配置文件路径
+        #[arg(short, long, default_value = "test.toml")]
+        config: String,
+    },
+
+    /// 从虚拟机或模板批量克隆虚拟机, 可选启动并等待 QGA 就绪
+    Clone {
+        /// 克隆源 (虚拟机或模板的 ID 或名称)
+        #[arg(long)]
+        source: String,
+
+        /// 克隆数量
+        #[arg(long)]
+        count: usize,
+
+        /// 命名模板, 必须包含一个序号占位符 `{}` 或 `{:0N}` (如 `lab-{:03}`), 序号从 1 开始
+        #[arg(long)]
+        name: String,
+
+        /// 存储池 ID (从模板克隆时必须指定)
+        #[arg(long)]
+        storage_pool: Option<String>,
+
+        /// 克隆完成后按批均衡启动
+        #[arg(long)]
+        start: bool,
+
+        /// 启动后等待 QGA 就绪
+        #[arg(long, requires = "start")]
+        wait_qga: bool,
+
+        /// 等待克隆完成的超时时间 (秒)
+        #[arg(long, default_value = "1800")]
+        clone_timeout: u64,
+
+        /// 等待 QGA 就绪的超时时间 (秒)
+        #[arg(long, default_value = "300")]
+        qga_timeout: u64,
+
+        #[command(flatten)]
+        confirm: ConfirmArgs,
+
+        /// 输出格式 (table/json/yaml, json/yaml 需要同时指定 --yes 或 --dry-run)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+
+        /// 配置文件路径
+        #[arg(short, long, default_value = "test.toml")]
+        config: String,
+    },
+
+    /// 验证虚拟机还原点: 写入标记文件, 设置还原点后修改标记, 重启或重置后确认标记恢复
+    ///
+    /// QGA 不可用的虚拟机跳过, 不算失败; 结束时取消本次设置的还原点。
+    TestRestore {
+        #[command(flatten)]
+        target: BatchTargetArgs,
+
+        /// 客户机中的标记文件路径 (所在目录需已存在), 未指定时 Linux 使用 /var/tmp/atp-restore-marker,
+        /// Windows 使用 C:\Windows\Temp\atp-restore-marker.txt
+        #[arg(long)]
+        marker_path: Option<String>,
+
+        /// 触发还原的方式 (reboot/rebase)
+        #[arg(long, default_value = "reboot")]
+        method: String,
+
+        /// 触发还原后等待标记文件恢复的超时时间 (秒)
+        #[arg(long, default_value = "600")]
+        timeout: u64,
+
+        #[command(flatten)]
+        confirm: ConfirmArgs,
+
+        /// 输出格式 (table/json/yaml, json/yaml 需要同时指定 --yes 或 --dry-run)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+
+        /// 配置文件路径
+        #[arg(short, long, default_value = "test.toml")]
+        config: String,
+    },
+
+    /// 显示虚拟机的状态变更历史
+    History {
+        /// 虚拟机名称 (也可以是虚拟机 ID)
+        vm_name: String,
+
+        /// 先从 VDI 平台刷新虚拟机缓存
+        #[arg(long)]
+        refresh: bool,
+
+        /// 配置文件路径 (--refresh 时使用)
+        #[arg(short, long, default_value = "test.toml")]
+        config: String,
+    },
+
+    /// 检查虚拟机磁盘所在 GlusterFS brick 的自愈与脑裂状态 (有问题时退出码为 1)
+    DiskHealth {
+        /// 虚拟机名称
+        #[arg(long)]
+        vm: String,
+
+        /// 虚拟机所在的主机 ID (未指定时在所有已配置主机中查找)
+        #[arg(long)]
+        host: Option<String>,
+
+        /// 输出格式 (table/json/yaml)
+        #[arg(short, long, default_value = "table")]
+        format: String,
+    },
+
+    /// 平台升级前后的环境基线 (保存/对比)
+    Baseline {
+        #[command(subcommand)]
+        action: BaselineAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BaselineAction {
+    /// 保存当前环境基线 (虚拟机清单、状态、CPU/内存、绑定用户、快照数)
+    Save {
+        /// 基线文件路径
+        #[arg(short, long)]
+        output: String,
+
+        /// 配置文件路径
+        #[arg(short, long, default_value = "test.toml")]
+        config: String,
+    },
+
+    /// 采集当前环境并与基线对比 (有差异时退出码为 1)
+    Diff {
+        /// 基线文件路径
+        baseline: String,
+
+        /// 输出格式 (table/json/yaml)
+        #[arg(short = 'f', long, default_value = "table")]
+        format: String,
+
+        /// 配置文件路径
+        #[arg(short, long, default_value = "test.toml")]
+        config: String,
+    },
+}
+
+/// 键盘 / 鼠标 / 命令的目标虚拟机
+///
+/// 指定 `--host` 时使用本地配置的主机; 否则通过 `--config` 中的 VDI 平台查找虚拟机所在主机。
+#[derive(Args)]
+pub struct VmTargetArgs {
+    /// 主机 ID (本地配置中的主机)
+    #[arg(long, required_unless_present = "config", conflicts_with = "config")]
+    host: Option<String>,
+
+    /// 虚拟机名称 (使用 --config 时为 VDI 平台上的名称或 ID)
+    #[arg(long)]
+    vm: String,
+
+    /// 测试配置文件路径, 用于从 VDI 平台查找虚拟机所在主机
+    #[arg(short, long)]
+    config: Option<String>,
+}
+
+/// 批量操作的目标 (三选一)
+#[derive(Args)]
+pub struct BatchTargetArgs {
+    /// 虚拟机名称通配符 (支持 * 和 ?)
+    #[arg(long, conflicts_with_all = ["pool", "id"])]
+    pattern: Option<String>,
+
+    /// 桌面池 ID 或名称
+    #[arg(long, conflicts_with = "id")]
+    pool: Option<String>,
+
+    /// 虚拟机 ID (可重复或逗号分隔)
+    #[arg(long, value_delimiter = ',')]
+    id: Vec<String>,
+}
+
+/// 场景变量与多虚拟机并行执行参数
+#[derive(Args)]
+pub struct ScenarioFanOutArgs {
+    /// 场景变量 (可重复), 代入步骤中的 `${KEY}`, 覆盖配置文件 [variables] 中的同名变量
+    #[arg(long = "var", value_name = "KEY=VALUE")]
+    vars: Vec<String>,
+
+    /// 测试配置文件, 读取其中的 [variables] 与 [vdi] (按全局 --profile 合并), 给出 [vdi] 时登录 VDI 平台
+    #[arg(long)]
+    config: Option<String>,
+
+    /// 对多台虚拟机并行执行 (可重复或逗号分隔), 替换场景的 target_domain
+    #[arg(long = "vm", value_delimiter = ',')]
+    vms: Vec<String>,
+
+    /// 多台虚拟机执行时的工件根目录, 每台虚拟机一个子目录
+    #[arg(long, requires = "vms", default_value = "atp-artifacts")]
+    artifact_dir: String,
+
+    /// 多台虚拟机执行时同时执行的数量
+    #[arg(long, requires = "vms", default_value_t = 4, value_parser = clap::value_parser!(u64).range(1..))]
+    concurrency: u64,
+}
+
+/// 破坏性操作的确认参数 (见 `commands::common::DestructiveGuard`)
+#[derive(Args)]
+pub struct ConfirmArgs {
+    /// 跳过确认提示 (用于自动化脚本)
+    #[arg(short, long)]
+    yes: bool,
+
+    /// 演练模式: 只解析并列出目标, 不调用会修改平台的 API
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     let cli = Cli::parse();
+    i18n::set_lang(cli.lang.unwrap_or_default());
+    commands::common::set_output(cli.output);
+
+    if let Err(e) = run(cli).await {
+        eprintln!("{}: {:#}", t(MsgKey::ErrorPrefix).red().bold(), e);
+        if let Some(hint) = i18n::hint_for(&e) {
+            eprintln!("  {}: {}", t(MsgKey::HintPrefix).cyan(), t(hint));
+        }
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> Result<()> {
 
     // 初始化日志
     let log_level = match cli.log_level.to_lowercase().as_str() {
@@ -373,20 +1099,25 @@ async fn main() -> Result<()> {
         _ => Level::INFO,
     };
 
-    tracing_subscriber::fmt().with_max_level(log_level).init();
+    // 日志写到标准错误, 标准输出只留给命令结果 (--output json/yaml 时可以直接被脚本解析)
+    tracing_subscriber::fmt().with_max_level(log_level).with_writer(std::io::stderr).init();
 
     info!("ATP CLI 启动");
 
     // 处理命令
     match cli.command {
         Commands::Host { action } => commands::host::handle(action).await?,
-        Commands::Keyboard { action } => commands::keyboard::handle(action).await?,
-        Commands::Mouse { action } => commands::mouse::handle(action).await?,
-        Commands::Command { action } => commands::command::handle(action).await?,
-        Commands::Scenario { action } => commands::scenario::handle(action).await?,
-        Commands::Report { action } => commands::report::handle(action).await?,
+        Commands::Keyboard { action } => commands::keyboard::handle(action, cli.profile.as_deref()).await?,
+        Commands::Mouse { action } => commands::mouse::handle(action, cli.profile.as_deref()).await?,
+        Commands::Command { action } => commands::command::handle(action, cli.profile.as_deref()).await?,
+        Commands::Scenario { action } => commands::scenario::handle(action, cli.profile.as_deref()).await?,
+        Commands::Report { action } => commands::report::handle(action, cli.profile.as_deref()).await?,
         Commands::Db { action } => commands::db::handle(action).await?,
-        Commands::Vdi { action } => commands::vdi::handle(action).await?,
+        Commands::Vdi { action } => commands::vdi::handle(action, cli.profile.as_deref()).await?,
+        Commands::Bench { action } => commands::bench::handle(action, cli.profile.as_deref()).await?,
+        Commands::Shell { target, history } => {
+            commands::shell::handle(&target, history.as_deref(), cli.profile.as_deref()).await?
+        }
     }
 
     Ok(())
@@ -0,0 +1,389 @@
+//! 消息信封与协议版本协商
+//!
+//! 协商后 Agent 与服务端之间的 JSON 消息都包装为信封:
+//! `{"protocol_version": 1, "message_type": "result", "payload": {...}}`,
+//! 读取时先按类型标记分发, 再把 `payload` 解析为对应的结构。
+//!
+//! 注册消息本身不包装, 其中的 `protocol_version` 声明 Agent 的协议版本; 服务端用 [`negotiate`]
+//! 选出双方都支持的版本, 回复 `accepted` 信封, 此后双方都发送信封。旧版 Agent 不声明版本,
+//! 旧版服务端不回复 `accepted`, 这两种情况下继续收发裸消息; 读取端始终接受裸消息。
+//! 信封与载荷中多出的未知字段会被忽略, 未知的消息类型保留为 [`MessageType::Other`] 由调用方跳过。
+
+use std::fmt;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value;
+use thiserror::Error;
+
+/// 当前协议版本
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// 本端支持的协议版本
+pub const SUPPORTED_PROTOCOL_VERSIONS: &[u16] = &[1];
+
+/// 裸消息 (未协商) 解析为信封时使用的版本号
+pub const LEGACY_PROTOCOL_VERSION: u16 = 0;
+
+/// 信封编解码错误
+#[derive(Debug, Error)]
+pub enum EnvelopeError {
+    #[error("JSON 编解码失败: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("消息不是 JSON 对象")]
+    NotAnObject,
+
+    #[error("不支持的协议版本: {0}")]
+    UnsupportedVersion(u16),
+
+    #[error("消息类型不符: 期望 {expected}, 实际 {actual}")]
+    UnexpectedType { expected: MessageType, actual: MessageType },
+
+    #[error("{message_type} 消息内容不合法: {source}")]
+    InvalidPayload {
+        message_type: MessageType,
+        source: serde_json::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, EnvelopeError>;
+
+/// 消息类型 (信封的 `message_type`, 与裸消息的 `message_type` 取值相同)
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MessageType {
+    /// Agent 注册
+    Register,
+
+    /// 服务端接受注册并确认协议版本
+    Accepted,
+
+    /// 服务端拒绝连接
+    Rejected,
+
+    /// 服务端下发的待验证事件
+    Event,
+
+    /// Agent 回复的验证结果
+    Result,
+
+    /// 上报模式下的原始输入
+    RawInput,
+
+    /// Agent 心跳
+    Heartbeat,
+
+    /// 本端不认识的类型 (对端版本较新)
+    Other(String),
+}
+
+impl MessageType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            MessageType::Register => "register",
+            MessageType::Accepted => "accepted",
+            MessageType::Rejected => "rejected",
+            MessageType::Event => "event",
+            MessageType::Result => "result",
+            MessageType::RawInput => "raw_input",
+            MessageType::Heartbeat => "heartbeat",
+            MessageType::Other(name) => name,
+        }
+    }
+}
+
+impl From<&str> for MessageType {
+    fn from(name: &str) -> Self {
+        match name {
+            "register" => MessageType::Register,
+            "accepted" => MessageType::Accepted,
+            "rejected" => MessageType::Rejected,
+            "event" => MessageType::Event,
+            "result" => MessageType::Result,
+            "raw_input" => MessageType::RawInput,
+            "heartbeat" => MessageType::Heartbeat,
+            other => MessageType::Other(other.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for MessageType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(MessageType::from(name.as_str()))
+    }
+}
+
+/// 消息信封
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageEnvelope {
+    /// 协议版本 (裸消息为 [`LEGACY_PROTOCOL_VERSION`])
+    pub protocol_version: u16,
+
+    pub message_type: MessageType,
+
+    #[serde(default)]
+    pub payload: Value,
+}
+
+impl MessageEnvelope {
+    pub fn new<T: Serialize>(protocol_version: u16, message_type: MessageType, payload: &T) -> Result<Self> {
+        Ok(Self {
+            protocol_version,
+            message_type,
+            payload: serde_json::to_value(payload)?,
+        })
+    }
+
+    /// 是否由裸消息解析而来
+    pub fn is_legacy(&self) -> bool {
+        self.protocol_version == LEGACY_PROTOCOL_VERSION
+    }
+
+    /// 检查类型标记后把载荷解析为 `T`
+    pub fn decode_as<T: DeserializeOwned>(&self, expected: MessageType) -> Result<T> {
+        if self.message_type != expected {
+            return Err(EnvelopeError::UnexpectedType {
+                expected,
+                actual: self.message_type.clone(),
+            });
+        }
+        T::deserialize(&self.payload).map_err(|source| EnvelopeError::InvalidPayload {
+            message_type: expected,
+            source,
+        })
+    }
+}
+
+/// 注册握手协商出的协议版本
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AgreedVersion {
+    /// 未协商: 收发裸消息
+    #[default]
+    Legacy,
+
+    /// 使用该版本的信封
+    Version(u16),
+}
+
+impl AgreedVersion {
+    pub fn version(self) -> Option<u16> {
+        match self {
+            AgreedVersion::Legacy => None,
+            AgreedVersion::Version(version) => Some(version),
+        }
+    }
+
+    pub fn is_legacy(self) -> bool {
+        self == AgreedVersion::Legacy
+    }
+}
+
+/// 协商协议版本: 取服务端支持的、不高于 Agent 声明版本的最高版本
+///
+/// Agent 未声明版本 (旧版 Agent) 或没有共同版本时退回裸消息。
+pub fn negotiate(client_version: Option<u16>, server_supported: &[u16]) -> AgreedVersion {
+    let Some(client_version) = client_version else {
+        return AgreedVersion::Legacy;
+    };
+    server_supported
+        .iter()
+        .copied()
+        .filter(|&version| version != LEGACY_PROTOCOL_VERSION && version <= client_version)
+        .max()
+        .map_or(AgreedVersion::Legacy, AgreedVersion::Version)
+}
+
+/// 服务端接受注册后回复的 `accepted` 信封的载荷, 信封的版本即协商结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AcceptedPayload {
+    pub vm_id: String,
+
+    /// 服务端支持的全部协议版本
+    #[serde(default)]
+    pub supported_versions: Vec<u16>,
+}
+
+/// 按协商结果编码消息: 未协商时输出裸消息, 否则输出信封
+pub fn encode<T: Serialize>(agreed: AgreedVersion, message_type: MessageType, payload: &T) -> Result<String> {
+    match agreed {
+        AgreedVersion::Legacy => Ok(serde_json::to_string(payload)?),
+        AgreedVersion::Version(version) => {
+            Ok(serde_json::to_string(&MessageEnvelope::new(version, message_type, payload)?)?)
+        }
+    }
+}
+
+/// 解析收到的消息: 信封或旧版裸消息
+///
+/// 裸消息整体作为载荷, 类型取自其 `message_type` 字段, 没有该字段时为 `untagged`
+/// (旧版的事件与验证结果都不带类型)。
+pub fn decode(text: &str, untagged: MessageType) -> Result<MessageEnvelope> {
+    let value: Value = serde_json::from_str(text)?;
+    let Some(object) = value.as_object() else {
+        return Err(EnvelopeError::NotAnObject);
+    };
+
+    if object.contains_key("protocol_version") && object.contains_key("payload") {
+        let envelope: MessageEnvelope = serde_json::from_value(value)?;
+        if !SUPPORTED_PROTOCOL_VERSIONS.contains(&envelope.protocol_version) {
+            return Err(EnvelopeError::UnsupportedVersion(envelope.protocol_version));
+        }
+        return Ok(envelope);
+    }
+
+    let message_type = object
+        .get("message_type")
+        .and_then(Value::as_str)
+        .map_or(untagged, MessageType::from);
+    Ok(MessageEnvelope {
+        protocol_version: LEGACY_PROTOCOL_VERSION,
+        message_type,
+        payload: value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Heartbeat {
+        vm_id: String,
+        uptime_s: u64,
+    }
+
+    fn heartbeat() -> Heartbeat {
+        Heartbeat {
+            vm_id: "vm-1".to_string(),
+            uptime_s: 42,
+        }
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        let agreed = AgreedVersion::Version(PROTOCOL_VERSION);
+        let text = encode(agreed, MessageType::Heartbeat, &heartbeat()).unwrap();
+
+        let raw: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(raw["protocol_version"], 1);
+        assert_eq!(raw["message_type"], "heartbeat");
+        assert_eq!(raw["payload"]["uptime_s"], 42);
+
+        let envelope = decode(&text, MessageType::Result).unwrap();
+        assert!(!envelope.is_legacy());
+        assert_eq!(envelope.decode_as::<Heartbeat>(MessageType::Heartbeat).unwrap(), heartbeat());
+
+        for message_type in [
+            MessageType::Register,
+            MessageType::Accepted,
+            MessageType::Rejected,
+            MessageType::Event,
+            MessageType::Result,
+            MessageType::RawInput,
+            MessageType::Heartbeat,
+        ] {
+            let name = message_type.as_str().to_string();
+            assert_eq!(MessageType::from(name.as_str()), message_type);
+            assert_eq!(serde_json::to_value(&message_type).unwrap(), json!(name));
+        }
+    }
+
+    #[test]
+    fn test_decode_as_checks_type_and_payload() {
+        let text = encode(AgreedVersion::Version(1), MessageType::Heartbeat, &heartbeat()).unwrap();
+        let envelope = decode(&text, MessageType::Result).unwrap();
+
+        let err = envelope.decode_as::<Heartbeat>(MessageType::Result).unwrap_err();
+        assert!(matches!(
+            err,
+            EnvelopeError::UnexpectedType { expected: MessageType::Result, actual: MessageType::Heartbeat }
+        ));
+
+        let text = r#"{"protocol_version":1,"message_type":"heartbeat","payload":{"vm_id":"vm-1"}}"#;
+        let err = decode(text, MessageType::Result)
+            .unwrap()
+            .decode_as::<Heartbeat>(MessageType::Heartbeat)
+            .unwrap_err();
+        assert!(matches!(err, EnvelopeError::InvalidPayload { .. }));
+        assert!(err.to_string().contains("uptime_s"));
+    }
+
+    #[test]
+    fn test_forward_compat_unknown_fields_and_types() {
+        // 较新的对端在信封与载荷中加了字段
+        let text = r#"{
+            "protocol_version": 1,
+            "message_type": "heartbeat",
+            "trace_id": "abc",
+            "payload": {"vm_id": "vm-1", "uptime_s": 42, "cpu_percent": 3.5}
+        }"#;
+        let envelope = decode(text, MessageType::Result).unwrap();
+        assert_eq!(envelope.decode_as::<Heartbeat>(MessageType::Heartbeat).unwrap(), heartbeat());
+
+        // 不认识的消息类型原样保留, 由调用方跳过
+        let text = r#"{"protocol_version":1,"message_type":"screenshot","payload":{}}"#;
+        let envelope = decode(text, MessageType::Result).unwrap();
+        assert_eq!(envelope.message_type, MessageType::Other("screenshot".to_string()));
+        assert_eq!(serde_json::to_value(&envelope).unwrap()["message_type"], "screenshot");
+
+        // 不支持的版本
+        let text = r#"{"protocol_version":9,"message_type":"heartbeat","payload":{}}"#;
+        assert!(matches!(
+            decode(text, MessageType::Result),
+            Err(EnvelopeError::UnsupportedVersion(9))
+        ));
+    }
+
+    #[test]
+    fn test_legacy_messages() {
+        // 未协商时编码为裸消息
+        let text = encode(AgreedVersion::Legacy, MessageType::Heartbeat, &heartbeat()).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&text).unwrap(), json!({"vm_id": "vm-1", "uptime_s": 42}));
+
+        // 带类型的裸消息
+        let text = r#"{"message_type":"heartbeat","vm_id":"vm-1","uptime_s":42,"extra":true}"#;
+        let envelope = decode(text, MessageType::Result).unwrap();
+        assert!(envelope.is_legacy());
+        assert_eq!(envelope.message_type, MessageType::Heartbeat);
+        assert_eq!(envelope.decode_as::<Heartbeat>(MessageType::Heartbeat).unwrap(), heartbeat());
+
+        // 不带类型的裸消息使用调用方给定的类型
+        let text = r#"{"event_id":"e-1","verified":true}"#;
+        assert_eq!(decode(text, MessageType::Result).unwrap().message_type, MessageType::Result);
+        assert_eq!(decode(text, MessageType::Event).unwrap().message_type, MessageType::Event);
+
+        assert!(matches!(decode("[1, 2]", MessageType::Result), Err(EnvelopeError::NotAnObject)));
+        assert!(matches!(decode("vm-1", MessageType::Result), Err(EnvelopeError::Json(_))));
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate(None, SUPPORTED_PROTOCOL_VERSIONS), AgreedVersion::Legacy);
+        assert_eq!(negotiate(Some(1), SUPPORTED_PROTOCOL_VERSIONS), AgreedVersion::Version(1));
+
+        // Agent 较新: 退到服务端支持的最高版本
+        assert_eq!(negotiate(Some(5), &[1, 2, 3]), AgreedVersion::Version(3));
+        // 服务端较新: 使用 Agent 的版本
+        assert_eq!(negotiate(Some(2), &[1, 2, 3]), AgreedVersion::Version(2));
+        // 没有共同版本
+        assert_eq!(negotiate(Some(1), &[2, 3]), AgreedVersion::Legacy);
+        assert_eq!(negotiate(Some(0), &[0, 1]), AgreedVersion::Legacy);
+
+        assert_eq!(AgreedVersion::Version(2).version(), Some(2));
+        assert!(AgreedVersion::default().is_legacy());
+    }
+}
@@ -0,0 +1,13 @@
+//! ATP 公共协议定义
+//!
+//! Guest Agent (verifier-core) 与验证服务端 (verification-server) 共用的消息信封与协议版本协商,
+//! 以及各组件共用的指标源接口。
+
+pub mod envelope;
+pub mod metrics;
+
+pub use envelope::{
+    decode, encode, negotiate, AcceptedPayload, AgreedVersion, EnvelopeError, MessageEnvelope, MessageType,
+    LEGACY_PROTOCOL_VERSION, PROTOCOL_VERSION, SUPPORTED_PROTOCOL_VERSIONS,
+};
+pub use metrics::{MetricSample, MetricsSource};
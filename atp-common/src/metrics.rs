@@ -0,0 +1,48 @@
+//! 指标源接口
+//!
+//! 传输层连接池、协议实例、虚拟机统计等组件实现 [`MetricsSource`],
+//! 由存储层的采集器或验证服务的 `/metrics` 端点统一拉取。
+//! 放在公共 crate 中, 使底层组件无需依赖存储层即可暴露指标。
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+
+/// 单个指标值
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricSample {
+    /// 指标名称
+    pub name: String,
+
+    /// 指标值
+    pub value: f64,
+
+    /// 标签 (如主机 ID)
+    pub labels: BTreeMap<String, String>,
+}
+
+impl MetricSample {
+    pub fn new(name: &str, value: f64) -> Self {
+        Self {
+            name: name.to_string(),
+            value,
+            labels: BTreeMap::new(),
+        }
+    }
+
+    /// 添加标签
+    pub fn with_label(mut self, key: &str, value: &str) -> Self {
+        self.labels.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+/// 指标源
+#[async_trait]
+pub trait MetricsSource: Send + Sync {
+    /// 指标源名称 (写入 `source` 列)
+    fn source_name(&self) -> &str;
+
+    /// 获取当前指标快照
+    async fn collect(&self) -> Vec<MetricSample>;
+}
@@ -234,6 +234,7 @@ async fn test_libvirt_connection(uri: &str) -> anyhow::Result<()> {
         uri: uri.to_string(),
         tags: vec![],
         metadata: HashMap::new(),
+        ssh: None,
     };
 
     info!("   🔗 创建连接...");
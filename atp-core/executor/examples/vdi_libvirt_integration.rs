@@ -141,6 +141,7 @@ async fn main() -> anyhow::Result<()> {
             uri: uri.clone(),
             tags: vec![],
             metadata: HashMap::new(),
+            ssh: None,
         };
 
         let conn = HostConnection::new(host_info);
@@ -0,0 +1,297 @@
+//! 场景编写辅助
+//!
+//! 提供带注释的场景模板、离线的执行计划 (步骤、动作、目标、超时),
+//! 以及按步骤索引定位其在 YAML 文件中所在行的工具, 均不连接任何基础设施。
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::step_groups;
+use crate::validation::indexed_steps;
+use crate::{Action, ExecutorError, Result, Scenario, StepPhase};
+
+/// 场景模板
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScenarioTemplate {
+    /// 桌面池生命周期 (创建、启用、验证、删除)
+    VdiLifecycle,
+    /// 键盘输入并通过 QGA 验证
+    InputVerify,
+    /// VDI 平台操作 + 协议输入 + 宿主机诊断
+    Mixed,
+}
+
+impl ScenarioTemplate {
+    /// 所有模板
+    pub const ALL: &'static [ScenarioTemplate] = &[
+        ScenarioTemplate::VdiLifecycle,
+        ScenarioTemplate::InputVerify,
+        ScenarioTemplate::Mixed,
+    ];
+
+    /// 模板名称 (命令行中使用)
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScenarioTemplate::VdiLifecycle => "vdi-lifecycle",
+            ScenarioTemplate::InputVerify => "input-verify",
+            ScenarioTemplate::Mixed => "mixed",
+        }
+    }
+
+    /// 模板内容 (带注释的 YAML)
+    pub fn content(&self) -> &'static str {
+        match self {
+            ScenarioTemplate::VdiLifecycle => include_str!("templates/vdi-lifecycle.yaml"),
+            ScenarioTemplate::InputVerify => include_str!("templates/input-verify.yaml"),
+            ScenarioTemplate::Mixed => include_str!("templates/mixed.yaml"),
+        }
+    }
+}
+
+impl std::fmt::Display for ScenarioTemplate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for ScenarioTemplate {
+    type Err = ExecutorError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|template| template.name() == s)
+            .ok_or_else(|| {
+                let names: Vec<&str> = Self::ALL.iter().map(ScenarioTemplate::name).collect();
+                ExecutorError::ConfigError(format!("未知的场景模板: {} (可选: {})", s, names.join(", ")))
+            })
+    }
+}
+
+/// 执行计划中的一个步骤
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedStep {
+    /// 步骤索引 (与执行报告中的 step_index 一致)
+    pub step_index: usize,
+
+    /// 所属阶段
+    pub phase: StepPhase,
+
+    /// 步骤描述 (未命名时为 "步骤 N", 与执行报告一致)
+    pub description: String,
+
+    /// 动作类型名称
+    pub action: String,
+
+    /// 动作的目标 (虚拟机、桌面池或主机), 不涉及目标的动作为 None
+    pub target: Option<String>,
+
+    /// 生效的步骤超时 (秒)
+    pub timeout_secs: u64,
+
+    /// 超时是否来自执行器默认值
+    pub default_timeout: bool,
+
+    /// 步骤标签
+    pub tags: Vec<String>,
+}
+
+/// 生成场景的执行计划 (按前置、测试、清理的执行顺序)
+pub fn execution_plan(scenario: &Scenario, default_timeout: Duration) -> Vec<PlannedStep> {
+    let setup_end = scenario.setup.len();
+    let main_end = setup_end + scenario.steps.len();
+
+    indexed_steps(scenario)
+        .map(|(index, step)| PlannedStep {
+            step_index: index,
+            phase: match index {
+                i if i < setup_end => StepPhase::Setup,
+                i if i < main_end => StepPhase::Main,
+                _ => StepPhase::Teardown,
+            },
+            description: step.name.clone().unwrap_or_else(|| format!("步骤 {}", index + 1)),
+            action: step.action.type_name(),
+            target: action_target(&step.action, scenario),
+            timeout_secs: step.effective_timeout(default_timeout).as_secs(),
+            default_timeout: step.timeout.is_none(),
+            tags: step.tags.clone(),
+        })
+        .collect()
+}
+
+/// 动作的目标: VDI 操作为桌面池/虚拟机 ID, 协议与 QGA 操作为场景的目标虚拟机, 宿主机操作为主机 ID
+fn action_target(action: &Action, scenario: &Scenario) -> Option<String> {
+    match action {
+        Action::VdiCreateDeskPool { name, .. } => Some(format!("桌面池 {}", name)),
+        Action::VdiEnableDeskPool { pool_id }
+        | Action::VdiDisableDeskPool { pool_id }
+        | Action::VdiDeleteDeskPool { pool_id }
+        | Action::VdiGetDeskPoolDomains { pool_id }
+        | Action::VerifyAllDomainsRunning { pool_id, .. } => Some(format!("桌面池 {}", pool_id)),
+        Action::VdiStartDomain { domain_id }
+        | Action::VdiShutdownDomain { domain_id }
+        | Action::VdiRebootDomain { domain_id }
+        | Action::VdiDeleteDomain { domain_id }
+        | Action::VerifyDomainStatus { domain_id, .. } => Some(format!("虚拟机 {}", domain_id)),
+        Action::VdiBindUser { domain_id, user_id } => Some(format!("虚拟机 {} <- 用户 {}", domain_id, user_id)),
+        Action::VdiMigrateAndVerify { domain, target_host, .. } => {
+            Some(format!("虚拟机 {} -> 主机 {}", domain, target_host))
+        }
+        Action::VdiMigrateDomain { domain_id, target_host_id, .. } => {
+            Some(format!("虚拟机 {} -> 主机 {}", domain_id, target_host_id))
+        }
+        Action::VdiVerifyRestorePoints { pattern, .. } => Some(format!("虚拟机 {}", pattern)),
+        Action::VerifyDomainOnHost { domain, host_id } => Some(format!("虚拟机 {} @ 主机 {}", domain, host_id)),
+        Action::SshFetchFile { host, .. } | Action::SshExec { host, .. } => Some(format!("主机 {}", host)),
+        Action::Wait { .. } => None,
+        Action::RunGroup { name, .. } => Some(format!("步骤组 {}", name)),
+        Action::Unsupported { .. } => None,
+        Action::SendKey { .. }
+        | Action::SendText { .. }
+        | Action::MouseClick { .. }
+        | Action::ExecCommand { .. }
+        | Action::Custom { .. }
+        | Action::VerifyCommandSuccess { .. }
+        | Action::QueryWindowsEventLog { .. }
+        | Action::GuestUniquify { .. }
+        | Action::VerifyFileInGuest { .. } => scenario
+            .target_domain
+            .as_ref()
+            .map(|domain| format!("虚拟机 {}", domain)),
+    }
+}
+
+/// 定位步骤在场景 YAML 中的起始行 (从 1 开始)
+///
+/// `step_index` 按前置、测试、清理的执行顺序编号 (与校验结果一致)。
+/// 只识别顶层的 `setup:` / `steps:` / `teardown:` 块列表; 使用锚点或流式写法时返回 None。
+/// 使用步骤组时展开后的序号与文件中的位置不对应, 也返回 None。
+pub fn step_line(yaml: &str, step_index: usize) -> Option<usize> {
+    if step_groups::uses_step_groups(yaml) {
+        return None;
+    }
+    let lines: Vec<&str> = yaml.lines().collect();
+    let mut remaining = step_index;
+
+    for key in ["setup", "steps", "teardown"] {
+        let items = sequence_item_lines(&lines, key);
+        match items.get(remaining) {
+            Some(line) => return Some(line + 1),
+            None => remaining -= items.len(),
+        }
+    }
+
+    None
+}
+
+/// 顶层键 `key` 下块列表各元素的起始行索引
+fn sequence_item_lines(lines: &[&str], key: &str) -> Vec<usize> {
+    let header = format!("{}:", key);
+    let Some(start) = lines.iter().position(|line| line.trim_end() == header) else {
+        return Vec::new();
+    };
+
+    let mut items = Vec::new();
+    let mut item_indent = None;
+    for (index, line) in lines.iter().enumerate().skip(start + 1) {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let indent = line.len() - trimmed.len();
+        // 回到顶层的下一个键时块结束
+        if indent == 0 && !trimmed.starts_with('-') {
+            break;
+        }
+
+        if trimmed == "-" || trimmed.starts_with("- ") {
+            let item_indent = *item_indent.get_or_insert(indent);
+            if indent == item_indent {
+                items.push(index);
+            }
+        }
+    }
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::{validate_scenario, ValidationContext};
+
+    #[test]
+    fn test_templates_parse_and_validate() {
+        let ctx = ValidationContext {
+            has_vdi_client: true,
+            default_timeout: Duration::from_secs(30),
+            registered_protocols: &[],
+            defined_variables: &[],
+        };
+
+        for template in ScenarioTemplate::ALL {
+            let scenario = Scenario::from_yaml_str(template.content())
+                .unwrap_or_else(|e| panic!("模板 {} 解析失败: {}", template, e));
+            let issues = validate_scenario(&scenario, &ctx);
+            assert!(issues.is_empty(), "模板 {}: {:?}", template, issues);
+            assert_eq!(template.name().parse::<ScenarioTemplate>().unwrap(), *template);
+        }
+
+        let err = "vdi".parse::<ScenarioTemplate>().unwrap_err().to_string();
+        assert!(err.contains("vdi-lifecycle, input-verify, mixed"), "{}", err);
+    }
+
+    #[test]
+    fn test_execution_plan() {
+        let scenario = Scenario::from_yaml_str(ScenarioTemplate::Mixed.content()).unwrap();
+        let plan = execution_plan(&scenario, Duration::from_secs(30));
+
+        assert_eq!(plan.len(), 7);
+        assert_eq!(plan[0].phase, StepPhase::Setup);
+        assert_eq!(plan[0].action, "vdi_start_domain");
+        assert_eq!(plan[0].target.as_deref(), Some("虚拟机 domain-001"));
+        assert!(plan[0].default_timeout);
+        assert_eq!(plan[0].timeout_secs, 30);
+
+        assert_eq!(plan[1].timeout_secs, 360);
+        assert!(!plan[1].default_timeout);
+
+        assert_eq!(plan[2].phase, StepPhase::Main);
+        assert_eq!(plan[2].target.as_deref(), Some("虚拟机 test-vm"));
+        assert_eq!(plan[6].phase, StepPhase::Teardown);
+        assert_eq!(plan[6].step_index, 6);
+        assert_eq!(plan[6].target.as_deref(), Some("主机 host-1"));
+    }
+
+    #[test]
+    fn test_step_line() {
+        let yaml = "\
+name: demo
+setup:
+  - name: a
+    action: {type: wait, duration: 1}
+steps:
+  # 注释
+  - name: b
+    action:
+      type: send_key
+      key: ret
+    tags:
+      - x
+  -
+    name: c
+    action: {type: wait, duration: 1}
+teardown:
+- name: d
+  action: {type: wait, duration: 1}
+";
+        assert_eq!(step_line(yaml, 0), Some(3));
+        assert_eq!(step_line(yaml, 1), Some(7));
+        assert_eq!(step_line(yaml, 2), Some(13));
+        assert_eq!(step_line(yaml, 3), Some(17));
+        assert_eq!(step_line(yaml, 4), None);
+    }
+}
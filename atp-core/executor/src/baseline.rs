@@ -0,0 +1,383 @@
+//! 平台升级前后的环境基线对比
+//!
+//! 升级前保存虚拟机基线 (清单、状态、CPU/内存、绑定用户、快照数),
+//! 升级后重新采集并与基线对比, 列出新增、丢失与配置变化的虚拟机。
+//!
+//! 基线文件为带版本号的 JSON, 读取时拒绝未知版本。虚拟机按 ID 对应;
+//! 所在主机不参与对比 (升级期间虚拟机可能被迁移), 任一侧快照数未知时不对比快照数。
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use atp_vdiplatform::VdiClient;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{info, warn};
+
+use crate::vm_cache::domain_status_label;
+use crate::{ExecutorError, Result};
+
+/// 当前基线文件格式版本
+pub const BASELINE_FORMAT_VERSION: u32 = 1;
+
+/// 单台虚拟机的基线信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VmBaseline {
+    pub id: String,
+    pub name: String,
+    pub status: String,
+    pub host_id: String,
+    pub cpu: Option<i64>,
+
+    /// 内存大小 (MB)
+    pub memory: Option<i64>,
+
+    /// 绑定用户 (未绑定时为空)
+    #[serde(default)]
+    pub user: String,
+
+    /// 快照数 (查询失败时为 None)
+    pub snapshot_count: Option<usize>,
+}
+
+/// 基线快照
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BaselineSnapshot {
+    /// 文件格式版本
+    pub version: u32,
+
+    pub captured_at: DateTime<Utc>,
+
+    /// 按 ID 排序的虚拟机列表
+    pub vms: Vec<VmBaseline>,
+}
+
+impl BaselineSnapshot {
+    /// 由 `domain().list_all()` 的条目构建基线 (缺少 `id` 的条目被忽略)
+    ///
+    /// `snapshot_counts` 中没有的虚拟机快照数记为未知。
+    pub fn from_listing(
+        items: &[serde_json::Value],
+        snapshot_counts: &HashMap<String, usize>,
+        captured_at: DateTime<Utc>,
+    ) -> Self {
+        let mut vms: Vec<VmBaseline> = items
+            .iter()
+            .filter_map(|item| {
+                let id = item["id"].as_str().filter(|id| !id.is_empty())?;
+                Some(VmBaseline {
+                    id: id.to_string(),
+                    name: item["name"].as_str().unwrap_or_default().to_string(),
+                    status: domain_status_label(item["status"].as_i64().unwrap_or(-1)).to_string(),
+                    host_id: item["hostId"].as_str().unwrap_or_default().to_string(),
+                    cpu: item["cpuNum"].as_i64(),
+                    memory: item["memory"].as_i64(),
+                    user: item["userName"].as_str().unwrap_or_default().to_string(),
+                    snapshot_count: snapshot_counts.get(id).copied(),
+                })
+            })
+            .collect();
+        vms.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Self {
+            version: BASELINE_FORMAT_VERSION,
+            captured_at,
+            vms,
+        }
+    }
+
+    /// 解析基线 JSON, 版本号缺失或不受支持时返回错误
+    pub fn from_json(content: &str) -> Result<Self> {
+        let value: serde_json::Value = serde_json::from_str(content)
+            .map_err(|e| ExecutorError::SerdeError(format!("解析基线文件失败: {}", e)))?;
+
+        match value["version"].as_u64() {
+            Some(version) if version == BASELINE_FORMAT_VERSION as u64 => {}
+            Some(version) => {
+                return Err(ExecutorError::ConfigError(format!(
+                    "不支持的基线文件版本: {} (当前支持版本 {})",
+                    version, BASELINE_FORMAT_VERSION
+                )))
+            }
+            None => return Err(ExecutorError::ConfigError("基线文件缺少版本号".to_string())),
+        }
+
+        serde_json::from_value(value).map_err(|e| ExecutorError::SerdeError(format!("解析基线文件失败: {}", e)))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).map_err(|e| ExecutorError::SerdeError(e.to_string()))
+    }
+
+    /// 从文件读取基线
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        Self::from_json(&content)
+    }
+
+    /// 保存基线到文件
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        std::fs::write(path.as_ref(), self.to_json()?)?;
+        Ok(())
+    }
+
+    /// 与升级后的基线对比
+    pub fn diff(&self, after: &BaselineSnapshot) -> BaselineDiff {
+        let before_by_id: HashMap<&str, &VmBaseline> = self.vms.iter().map(|vm| (vm.id.as_str(), vm)).collect();
+        let after_by_id: HashMap<&str, &VmBaseline> = after.vms.iter().map(|vm| (vm.id.as_str(), vm)).collect();
+
+        let missing = self
+            .vms
+            .iter()
+            .filter(|vm| !after_by_id.contains_key(vm.id.as_str()))
+            .cloned()
+            .collect();
+        let added = after
+            .vms
+            .iter()
+            .filter(|vm| !before_by_id.contains_key(vm.id.as_str()))
+            .cloned()
+            .collect();
+        let changed = self
+            .vms
+            .iter()
+            .filter_map(|before| {
+                let after = after_by_id.get(before.id.as_str())?;
+                let changes = field_changes(before, after);
+                (!changes.is_empty()).then(|| VmChange {
+                    id: before.id.clone(),
+                    name: after.name.clone(),
+                    changes,
+                })
+            })
+            .collect();
+
+        BaselineDiff { added, missing, changed }
+    }
+}
+
+/// 单个字段的变化
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub before: serde_json::Value,
+    pub after: serde_json::Value,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} -> {}", self.field, display_value(&self.before), display_value(&self.after))
+    }
+}
+
+fn display_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(s) if s.is_empty() => "-".to_string(),
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => "-".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// 配置发生变化的虚拟机
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VmChange {
+    pub id: String,
+
+    /// 升级后的名称
+    pub name: String,
+
+    pub changes: Vec<FieldChange>,
+}
+
+/// 基线对比结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BaselineDiff {
+    /// 升级后新增的虚拟机
+    pub added: Vec<VmBaseline>,
+
+    /// 升级后丢失的虚拟机
+    pub missing: Vec<VmBaseline>,
+
+    /// 配置变化的虚拟机
+    pub changed: Vec<VmChange>,
+}
+
+impl BaselineDiff {
+    /// 两次采集是否一致
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.missing.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for BaselineDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "新增 {} 台, 丢失 {} 台, 配置变化 {} 台",
+            self.added.len(),
+            self.missing.len(),
+            self.changed.len()
+        )
+    }
+}
+
+/// 对比两台虚拟机的字段 (名称、状态、CPU、内存、绑定用户、快照数)
+fn field_changes(before: &VmBaseline, after: &VmBaseline) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    let mut compare = |field: &'static str, before: serde_json::Value, after: serde_json::Value| {
+        if before != after {
+            changes.push(FieldChange { field, before, after });
+        }
+    };
+
+    compare("name", json!(before.name), json!(after.name));
+    compare("status", json!(before.status), json!(after.status));
+    compare("cpu", json!(before.cpu), json!(after.cpu));
+    compare("memory", json!(before.memory), json!(after.memory));
+    compare("user", json!(before.user), json!(after.user));
+    if let (Some(before), Some(after)) = (before.snapshot_count, after.snapshot_count) {
+        compare("snapshot_count", json!(before), json!(after));
+    }
+
+    changes
+}
+
+/// 基线采集
+pub struct BaselineOps {
+    vdi_client: Arc<VdiClient>,
+}
+
+impl BaselineOps {
+    pub fn new(vdi_client: Arc<VdiClient>) -> Self {
+        Self { vdi_client }
+    }
+
+    /// 从 VDI 平台采集当前基线
+    ///
+    /// 单台虚拟机的快照查询失败时记录警告, 快照数记为未知。
+    pub async fn capture(&self) -> Result<BaselineSnapshot> {
+        let domains = self
+            .vdi_client
+            .domain()
+            .list_all()
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询虚拟机列表失败: {}", e)))?;
+
+        let mut snapshot_counts = HashMap::new();
+        for id in domains.iter().filter_map(|item| item["id"].as_str()) {
+            match self.vdi_client.domain().list_snapshots(id).await {
+                Ok(snapshots) => {
+                    snapshot_counts.insert(id.to_string(), snapshots.len());
+                }
+                Err(e) => warn!("查询虚拟机 {} 的快照失败: {}", id, e),
+            }
+        }
+
+        let baseline = BaselineSnapshot::from_listing(&domains, &snapshot_counts, Utc::now());
+        info!("已采集 {} 台虚拟机的基线", baseline.vms.len());
+        Ok(baseline)
+    }
+
+    /// 采集当前基线并与 `before` 对比
+    pub async fn diff_against(&self, before: &BaselineSnapshot) -> Result<BaselineDiff> {
+        Ok(before.diff(&self.capture().await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm(id: &str, name: &str, status: &str, memory: i64, snapshots: Option<usize>) -> VmBaseline {
+        VmBaseline {
+            id: id.to_string(),
+            name: name.to_string(),
+            status: status.to_string(),
+            host_id: "host-1".to_string(),
+            cpu: Some(4),
+            memory: Some(memory),
+            user: "alice".to_string(),
+            snapshot_count: snapshots,
+        }
+    }
+
+    fn snapshot(vms: Vec<VmBaseline>) -> BaselineSnapshot {
+        BaselineSnapshot {
+            version: BASELINE_FORMAT_VERSION,
+            captured_at: Utc::now(),
+            vms,
+        }
+    }
+
+    #[test]
+    fn test_baseline_format_version() {
+        let items = vec![
+            json!({"id": "vm-2", "name": "win10-02", "status": 0, "hostId": "h1", "cpuNum": 2, "memory": 4096}),
+            json!({"id": "vm-1", "name": "win10-01", "status": 1, "hostId": "h1", "cpuNum": 4, "memory": 8192, "userName": "alice"}),
+            json!({"name": "no-id"}),
+        ];
+        let counts = HashMap::from([("vm-1".to_string(), 3)]);
+        let baseline = BaselineSnapshot::from_listing(&items, &counts, Utc::now());
+
+        assert_eq!(baseline.version, BASELINE_FORMAT_VERSION);
+        assert_eq!(baseline.vms.len(), 2);
+        assert_eq!(baseline.vms[0].id, "vm-1");
+        assert_eq!((baseline.vms[0].status.as_str(), baseline.vms[0].user.as_str()), ("运行中", "alice"));
+        assert_eq!((baseline.vms[0].snapshot_count, baseline.vms[1].snapshot_count), (Some(3), None));
+
+        // 保存后读回内容一致
+        let loaded = BaselineSnapshot::from_json(&baseline.to_json().unwrap()).unwrap();
+        assert_eq!(loaded, baseline);
+
+        // 未知版本与缺少版本号都被拒绝
+        let mut value = serde_json::to_value(&baseline).unwrap();
+        value["version"] = json!(BASELINE_FORMAT_VERSION + 1);
+        let err = BaselineSnapshot::from_json(&value.to_string()).unwrap_err();
+        assert!(err.to_string().contains("不支持的基线文件版本"), "{}", err);
+
+        value.as_object_mut().unwrap().remove("version");
+        let err = BaselineSnapshot::from_json(&value.to_string()).unwrap_err();
+        assert!(err.to_string().contains("缺少版本号"), "{}", err);
+    }
+
+    #[test]
+    fn test_baseline_diff() {
+        let before = snapshot(vec![
+            vm("vm-1", "win10-01", "运行中", 8192, Some(2)),
+            vm("vm-2", "win10-02", "运行中", 8192, Some(1)),
+            vm("vm-3", "win10-03", "关机", 4096, Some(0)),
+            vm("vm-4", "win10-04", "关机", 4096, Some(5)),
+        ]);
+
+        let mut moved = vm("vm-1", "win10-01", "运行中", 8192, Some(2));
+        moved.host_id = "host-2".to_string();
+        let after = snapshot(vec![
+            // 只有所在主机变化: 不算配置变化
+            moved,
+            // 状态、内存、快照数变化
+            vm("vm-2", "win10-02", "关机", 4096, Some(0)),
+            // 快照数未知时不对比快照数
+            vm("vm-4", "win10-04", "关机", 4096, None),
+            vm("vm-5", "win10-05", "关机", 4096, Some(0)),
+        ]);
+
+        let diff = before.diff(&after);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.added.iter().map(|vm| vm.id.as_str()).collect::<Vec<_>>(), ["vm-5"]);
+        assert_eq!(diff.missing.iter().map(|vm| vm.id.as_str()).collect::<Vec<_>>(), ["vm-3"]);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].id, "vm-2");
+        assert_eq!(
+            diff.changed[0].changes.iter().map(|c| c.field).collect::<Vec<_>>(),
+            ["status", "memory", "snapshot_count"]
+        );
+        assert_eq!(diff.changed[0].changes[0].to_string(), "status: 运行中 -> 关机");
+        assert_eq!(diff.to_string(), "新增 1 台, 丢失 1 台, 配置变化 1 台");
+
+        assert!(before.diff(&before).is_empty());
+    }
+}
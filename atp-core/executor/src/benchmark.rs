@@ -0,0 +1,643 @@
+//! 基准测试
+//!
+//! 输入延迟基准测量 "从 QMP send-key 到 Guest 观察到按键" 的端到端时间:
+//! 每个样本先向 verification-server 登记期望的按键事件, 等 Agent 开始监听后通过 QMP
+//! 注入按键, 从发出按键开始计时, 到收到 Agent 的确认为止。
+//!
+//! 统计只包含 Agent 确认观察到的样本; 验证超时、Agent 未观察到期望按键以及发送失败的样本
+//! 分别计数, 不参与平均值与百分位的计算, 避免把超时时间当作延迟。
+//! 运行结果写入 `benchmark_runs` 表, 可以用 [`BenchmarkComparison`] 比较两次运行。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use atp_protocol::qmp::QmpProtocol;
+use atp_storage::BenchmarkRunRecord;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::sync::CancellationToken;
+use verification_server::{Event, VerificationService, VerifyOutcome};
+
+use crate::{ExecutorError, Result};
+
+/// 输入延迟基准的类型名 (`benchmark_runs.kind`)
+pub const INPUT_LATENCY_KIND: &str = "input-latency";
+
+/// 输入延迟的时序指标名称 (`metrics.metric_name`, 每个观察到的样本一条)
+pub const INPUT_LATENCY_METRIC: &str = "input_latency_ms";
+
+/// 直方图的桶上界 (毫秒), 最后一个桶不设上界
+const HISTOGRAM_BOUNDS_MS: &[u64] = &[1, 2, 5, 10, 20, 50, 100, 200, 500, 1000, 2000, 5000];
+
+/// 输入延迟基准的参数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputLatencyOptions {
+    /// 样本数
+    pub samples: usize,
+
+    /// 相邻两个样本的间隔 (毫秒)
+    pub interval_ms: u64,
+
+    /// 注入的按键 (QMP qcode), 默认 shift, 不会在 Guest 中产生输入
+    pub key: String,
+
+    /// 单个样本等待验证结论的时间 (毫秒)
+    pub timeout_ms: u64,
+
+    /// 登记事件后等待 Agent 开始监听的时间 (毫秒, 不计入延迟)
+    pub arm_delay_ms: u64,
+}
+
+impl InputLatencyOptions {
+    pub fn new(samples: usize, interval: Duration) -> Self {
+        Self {
+            samples,
+            interval_ms: interval.as_millis() as u64,
+            key: "shift".to_string(),
+            timeout_ms: 5000,
+            arm_delay_ms: 50,
+        }
+    }
+
+    /// 设置注入的按键
+    pub fn with_key(mut self, key: &str) -> Self {
+        self.key = key.to_string();
+        self
+    }
+
+    /// 设置单个样本的验证超时
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_ms = timeout.as_millis() as u64;
+        self
+    }
+
+    /// 设置登记事件后等待 Agent 开始监听的时间
+    pub fn with_arm_delay(mut self, delay: Duration) -> Self {
+        self.arm_delay_ms = delay.as_millis() as u64;
+        self
+    }
+}
+
+/// 单个样本的结论
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SampleOutcome {
+    /// Agent 观察到了按键
+    Observed { latency_ms: u64 },
+
+    /// Agent 给出了结果, 但未观察到期望的按键
+    Mismatched,
+
+    /// 超时前未收到结果
+    TimedOut,
+
+    /// 登记事件或发送按键失败
+    Failed(String),
+}
+
+/// 发送一次输入并等待 Guest 确认
+#[async_trait]
+pub trait InputLatencyProbe: Send {
+    async fn probe(&mut self, options: &InputLatencyOptions) -> SampleOutcome;
+}
+
+/// 通过 QMP send-key 注入按键, 由 verification-server 匹配 Agent 的确认
+pub struct QmpInputProbe {
+    qmp: QmpProtocol,
+    service: Arc<VerificationService>,
+    vm_id: String,
+}
+
+impl QmpInputProbe {
+    /// `vm_id` 为 Agent 注册时使用的虚拟机 ID
+    pub fn new(qmp: QmpProtocol, service: Arc<VerificationService>, vm_id: &str) -> Self {
+        Self {
+            qmp,
+            service,
+            vm_id: vm_id.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl InputLatencyProbe for QmpInputProbe {
+    async fn probe(&mut self, options: &InputLatencyOptions) -> SampleOutcome {
+        let event = Event {
+            event_type: "keyboard".to_string(),
+            data: serde_json::json!({ "key": options.key, "window_ms": options.timeout_ms }),
+            timestamp: Utc::now().timestamp_millis(),
+        };
+
+        let outcome = match self
+            .service
+            .send_event(&self.vm_id, event, Some(Duration::from_millis(options.timeout_ms)))
+            .await
+        {
+            Ok(outcome) => outcome,
+            Err(e) => return SampleOutcome::Failed(format!("登记验证事件失败: {}", e)),
+        };
+        tokio::time::sleep(Duration::from_millis(options.arm_delay_ms)).await;
+
+        let sent = Instant::now();
+        if let Err(e) = self.qmp.send_key(&options.key, None).await {
+            // 未发出按键的事件到期后由服务端清理
+            return SampleOutcome::Failed(format!("QMP send-key 失败: {}", e));
+        }
+
+        match outcome.await {
+            Ok(VerifyOutcome::Verified(_)) => SampleOutcome::Observed {
+                latency_ms: sent.elapsed().as_millis() as u64,
+            },
+            Ok(VerifyOutcome::Mismatched(_)) => SampleOutcome::Mismatched,
+            Ok(VerifyOutcome::TimedOut { .. }) => SampleOutcome::TimedOut,
+            Err(_) => SampleOutcome::Failed("验证结果通道已关闭".to_string()),
+        }
+    }
+}
+
+/// 延迟分布 (毫秒, 百分位按最近秩计算)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LatencyPercentiles {
+    pub count: usize,
+    pub min_ms: u64,
+    pub median_ms: u64,
+    pub p95_ms: u64,
+    pub p99_ms: u64,
+    pub max_ms: u64,
+    pub mean_ms: f64,
+}
+
+impl LatencyPercentiles {
+    /// 没有样本时返回 None
+    pub fn from_samples(samples: &[u64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        let percentile = |p: f64| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+
+        Some(Self {
+            count: sorted.len(),
+            min_ms: sorted[0],
+            median_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            p99_ms: percentile(0.99),
+            max_ms: sorted[sorted.len() - 1],
+            mean_ms: sorted.iter().sum::<u64>() as f64 / sorted.len() as f64,
+        })
+    }
+}
+
+/// 直方图的一个桶: `[lower_ms, upper_ms)`, `upper_ms` 为 None 表示不设上界
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub lower_ms: u64,
+    pub upper_ms: Option<u64>,
+    pub count: usize,
+}
+
+/// 延迟直方图 (按固定的对数刻度分桶, 去掉两端的空桶)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    pub buckets: Vec<HistogramBucket>,
+}
+
+impl LatencyHistogram {
+    pub fn from_samples(samples: &[u64]) -> Self {
+        let mut buckets: Vec<HistogramBucket> = std::iter::once(0)
+            .chain(HISTOGRAM_BOUNDS_MS.iter().copied())
+            .zip(HISTOGRAM_BOUNDS_MS.iter().copied().map(Some).chain(std::iter::once(None)))
+            .map(|(lower_ms, upper_ms)| HistogramBucket { lower_ms, upper_ms, count: 0 })
+            .collect();
+
+        for &sample in samples {
+            let index = HISTOGRAM_BOUNDS_MS
+                .iter()
+                .position(|&bound| sample < bound)
+                .unwrap_or(HISTOGRAM_BOUNDS_MS.len());
+            buckets[index].count += 1;
+        }
+
+        let first = buckets.iter().position(|bucket| bucket.count > 0);
+        let last = buckets.iter().rposition(|bucket| bucket.count > 0);
+        let buckets = match (first, last) {
+            (Some(first), Some(last)) => buckets[first..=last].to_vec(),
+            _ => Vec::new(),
+        };
+        Self { buckets }
+    }
+
+    /// 文本形式, 每个桶一行, 条形长度按最大的桶缩放到 `width`
+    pub fn render(&self, width: usize) -> String {
+        let max = self.buckets.iter().map(|bucket| bucket.count).max().unwrap_or(0);
+        self.buckets
+            .iter()
+            .map(|bucket| {
+                let range = match bucket.upper_ms {
+                    Some(upper) => format!("{}-{} ms", bucket.lower_ms, upper),
+                    None => format!(">= {} ms", bucket.lower_ms),
+                };
+                let bar = if max == 0 { 0 } else { (bucket.count * width).div_ceil(max) };
+                format!("{:>14} | {:<width$} {}", range, "#".repeat(bar), bucket.count, width = width)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// 一次输入延迟基准的结果
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InputLatencyRun {
+    /// 数据库中的 ID (保存后才有)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+
+    /// 虚拟机名称
+    pub target: String,
+
+    /// 构建或版本标签
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// 运行参数
+    pub options: InputLatencyOptions,
+
+    /// 观察到的样本的延迟 (按采样顺序)
+    pub latencies_ms: Vec<u64>,
+
+    /// Agent 未观察到期望按键的样本数
+    pub mismatched: usize,
+
+    /// 验证超时的样本数
+    pub timeouts: usize,
+
+    /// 发送失败的样本数
+    pub failures: usize,
+
+    /// 最近一次发送失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+}
+
+impl InputLatencyRun {
+    /// 实际完成的样本数 (被取消时可能少于请求的样本数)
+    pub fn completed(&self) -> usize {
+        self.latencies_ms.len() + self.mismatched + self.timeouts + self.failures
+    }
+
+    /// 观察到的样本的延迟分布
+    pub fn percentiles(&self) -> Option<LatencyPercentiles> {
+        LatencyPercentiles::from_samples(&self.latencies_ms)
+    }
+
+    /// 观察到的样本的直方图
+    pub fn histogram(&self) -> LatencyHistogram {
+        LatencyHistogram::from_samples(&self.latencies_ms)
+    }
+
+    /// 转换为数据库记录
+    pub fn to_record(&self) -> BenchmarkRunRecord {
+        let stats = self.percentiles();
+        let ms = |value: fn(&LatencyPercentiles) -> u64| stats.as_ref().map(|stats| value(stats) as i64);
+
+        BenchmarkRunRecord {
+            id: self.id.unwrap_or(0),
+            kind: INPUT_LATENCY_KIND.to_string(),
+            target: self.target.clone(),
+            label: self.label.clone(),
+            samples: self.completed() as i64,
+            observed: self.latencies_ms.len() as i64,
+            mismatched: self.mismatched as i64,
+            timeouts: self.timeouts as i64,
+            failures: self.failures as i64,
+            min_ms: ms(|stats| stats.min_ms),
+            median_ms: ms(|stats| stats.median_ms),
+            p95_ms: ms(|stats| stats.p95_ms),
+            p99_ms: ms(|stats| stats.p99_ms),
+            max_ms: ms(|stats| stats.max_ms),
+            mean_ms: stats.map(|stats| stats.mean_ms),
+            latencies: serde_json::to_string(&self.latencies_ms).unwrap_or_else(|_| "[]".to_string()),
+            options: serde_json::to_string(&self.options).ok(),
+            started_at: self.started_at,
+            duration_ms: self.duration_ms as i64,
+        }
+    }
+
+    /// 从数据库记录恢复
+    pub fn from_record(record: &BenchmarkRunRecord) -> Result<Self> {
+        if record.kind != INPUT_LATENCY_KIND {
+            return Err(ExecutorError::ConfigError(format!(
+                "基准测试 {} 的类型是 {}, 不是 {}",
+                record.id, record.kind, INPUT_LATENCY_KIND
+            )));
+        }
+        let latencies_ms: Vec<u64> = serde_json::from_str(&record.latencies)
+            .map_err(|e| ExecutorError::SerdeError(format!("基准测试 {} 的延迟数据无效: {}", record.id, e)))?;
+        let options = match &record.options {
+            Some(options) => serde_json::from_str(options)
+                .map_err(|e| ExecutorError::SerdeError(format!("基准测试 {} 的参数无效: {}", record.id, e)))?,
+            None => InputLatencyOptions::new(record.samples.max(0) as usize, Duration::ZERO),
+        };
+
+        Ok(Self {
+            id: Some(record.id),
+            target: record.target.clone(),
+            label: record.label.clone(),
+            options,
+            latencies_ms,
+            mismatched: record.mismatched.max(0) as usize,
+            timeouts: record.timeouts.max(0) as usize,
+            failures: record.failures.max(0) as usize,
+            last_error: None,
+            started_at: record.started_at,
+            duration_ms: record.duration_ms.max(0) as u64,
+        })
+    }
+}
+
+/// 执行输入延迟基准
+///
+/// 按 `interval_ms` 依次采集 `samples` 个样本 (上一个样本未结束时顺延), 每个样本结束后
+/// 调用 `on_sample(序号, 结论)`; `cancel` 被取消时停止采样, 返回已采集的部分。
+pub async fn run_input_latency<P, F>(
+    probe: &mut P,
+    target: &str,
+    options: &InputLatencyOptions,
+    cancel: &CancellationToken,
+    mut on_sample: F,
+) -> InputLatencyRun
+where
+    P: InputLatencyProbe + ?Sized,
+    F: FnMut(usize, &SampleOutcome),
+{
+    let started_at = Utc::now();
+    let started = Instant::now();
+    let mut run = InputLatencyRun {
+        id: None,
+        target: target.to_string(),
+        label: None,
+        options: options.clone(),
+        latencies_ms: Vec::with_capacity(options.samples),
+        mismatched: 0,
+        timeouts: 0,
+        failures: 0,
+        last_error: None,
+        started_at,
+        duration_ms: 0,
+    };
+
+    let mut ticker = tokio::time::interval(Duration::from_millis(options.interval_ms.max(1)));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    for index in 0..options.samples {
+        tokio::select! {
+            _ = cancel.cancelled() => break,
+            _ = ticker.tick() => {}
+        }
+
+        let outcome = tokio::select! {
+            _ = cancel.cancelled() => break,
+            outcome = probe.probe(options) => outcome,
+        };
+
+        match &outcome {
+            SampleOutcome::Observed { latency_ms } => run.latencies_ms.push(*latency_ms),
+            SampleOutcome::Mismatched => run.mismatched += 1,
+            SampleOutcome::TimedOut => run.timeouts += 1,
+            SampleOutcome::Failed(error) => {
+                run.failures += 1;
+                run.last_error = Some(error.clone());
+            }
+        }
+        on_sample(index, &outcome);
+    }
+
+    run.duration_ms = started.elapsed().as_millis() as u64;
+    run
+}
+
+/// 两次运行中一项指标的对比
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ComparisonRow {
+    pub metric: &'static str,
+    pub base: Option<f64>,
+    pub other: Option<f64>,
+}
+
+impl ComparisonRow {
+    /// 差值 (other - base)
+    pub fn delta(&self) -> Option<f64> {
+        Some(self.other? - self.base?)
+    }
+
+    /// 相对变化 (%), base 为 0 时为 None
+    pub fn delta_percent(&self) -> Option<f64> {
+        let base = self.base.filter(|base| *base != 0.0)?;
+        Some(self.delta()? / base * 100.0)
+    }
+}
+
+/// 两次基准测试运行的对比 (`atp bench compare`)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BenchmarkComparison {
+    pub base_id: i64,
+    pub other_id: i64,
+    pub base_label: Option<String>,
+    pub other_label: Option<String>,
+    pub rows: Vec<ComparisonRow>,
+}
+
+impl BenchmarkComparison {
+    pub fn new(base: &BenchmarkRunRecord, other: &BenchmarkRunRecord) -> Self {
+        let row = |metric, value: fn(&BenchmarkRunRecord) -> Option<f64>| ComparisonRow {
+            metric,
+            base: value(base),
+            other: value(other),
+        };
+        fn ms(value: Option<i64>) -> Option<f64> {
+            value.map(|value| value as f64)
+        }
+
+        Self {
+            base_id: base.id,
+            other_id: other.id,
+            base_label: base.label.clone(),
+            other_label: other.label.clone(),
+            rows: vec![
+                row("min_ms", |run| ms(run.min_ms)),
+                row("median_ms", |run| ms(run.median_ms)),
+                row("p95_ms", |run| ms(run.p95_ms)),
+                row("p99_ms", |run| ms(run.p99_ms)),
+                row("max_ms", |run| ms(run.max_ms)),
+                row("mean_ms", |run| run.mean_ms),
+                row("observed", |run| Some(run.observed as f64)),
+                row("timeout_rate_percent", |run| {
+                    (run.samples > 0).then(|| run.timeouts as f64 / run.samples as f64 * 100.0)
+                }),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按预设结论依次返回的探针
+    struct ScriptedProbe(std::vec::IntoIter<SampleOutcome>);
+
+    #[async_trait]
+    impl InputLatencyProbe for ScriptedProbe {
+        async fn probe(&mut self, _options: &InputLatencyOptions) -> SampleOutcome {
+            self.0.next().unwrap_or(SampleOutcome::TimedOut)
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let samples: Vec<u64> = (1..=100).rev().collect();
+        let stats = LatencyPercentiles::from_samples(&samples).unwrap();
+        assert_eq!(
+            (stats.count, stats.min_ms, stats.median_ms, stats.p95_ms, stats.p99_ms, stats.max_ms),
+            (100, 1, 50, 95, 99, 100)
+        );
+        assert_eq!(stats.mean_ms, 50.5);
+
+        let stats = LatencyPercentiles::from_samples(&[7]).unwrap();
+        assert_eq!((stats.median_ms, stats.p99_ms), (7, 7));
+        assert!(LatencyPercentiles::from_samples(&[]).is_none());
+    }
+
+    #[test]
+    fn test_histogram() {
+        let histogram = LatencyHistogram::from_samples(&[12, 15, 19, 30, 120, 9000]);
+        let buckets: Vec<(u64, Option<u64>, usize)> = histogram
+            .buckets
+            .iter()
+            .map(|bucket| (bucket.lower_ms, bucket.upper_ms, bucket.count))
+            .collect();
+        assert_eq!(
+            buckets,
+            vec![
+                (10, Some(20), 3),
+                (20, Some(50), 1),
+                (50, Some(100), 0),
+                (100, Some(200), 1),
+                (200, Some(500), 0),
+                (500, Some(1000), 0),
+                (1000, Some(2000), 0),
+                (2000, Some(5000), 0),
+                (5000, None, 1),
+            ]
+        );
+
+        let rendered = histogram.render(6);
+        assert!(rendered.lines().next().unwrap().ends_with("| ###### 3"), "{}", rendered);
+        assert!(rendered.contains(">= 5000 ms | ##     1"), "{}", rendered);
+        assert!(LatencyHistogram::from_samples(&[]).buckets.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_counts_timeouts_separately() {
+        let mut probe = ScriptedProbe(
+            vec![
+                SampleOutcome::Observed { latency_ms: 20 },
+                SampleOutcome::TimedOut,
+                SampleOutcome::Observed { latency_ms: 10 },
+                SampleOutcome::Mismatched,
+                SampleOutcome::Failed("QMP 未连接".to_string()),
+                SampleOutcome::Observed { latency_ms: 30 },
+            ]
+            .into_iter(),
+        );
+        let options = InputLatencyOptions::new(6, Duration::from_millis(250));
+        let mut seen = Vec::new();
+
+        let run = run_input_latency(&mut probe, "win10", &options, &CancellationToken::new(), |index, _| {
+            seen.push(index)
+        })
+        .await;
+
+        assert_eq!(seen, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(run.latencies_ms, vec![20, 10, 30]);
+        assert_eq!((run.timeouts, run.mismatched, run.failures, run.completed()), (1, 1, 1, 6));
+        assert_eq!(run.last_error.as_deref(), Some("QMP 未连接"));
+        // 第一个样本立即开始, 之后每 250ms 一个
+        assert_eq!(run.duration_ms, 1250);
+
+        // 超时不计入统计
+        let stats = run.percentiles().unwrap();
+        assert_eq!((stats.count, stats.max_ms, stats.mean_ms), (3, 30, 20.0));
+
+        let record = run.to_record();
+        assert_eq!((record.samples, record.observed, record.timeouts), (6, 3, 1));
+        assert_eq!((record.median_ms, record.p99_ms), (Some(20), Some(30)));
+        let restored = InputLatencyRun::from_record(&BenchmarkRunRecord { id: 9, ..record }).unwrap();
+        assert_eq!(restored.id, Some(9));
+        assert_eq!((restored.latencies_ms, restored.options), (run.latencies_ms, options));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_stops_when_cancelled() {
+        let mut probe = ScriptedProbe(Vec::new().into_iter());
+        let options = InputLatencyOptions::new(100, Duration::from_millis(100));
+        let cancel = CancellationToken::new();
+
+        let run = run_input_latency(&mut probe, "win10", &options, &cancel, |index, _| {
+            if index == 2 {
+                cancel.cancel();
+            }
+        })
+        .await;
+
+        assert_eq!((run.completed(), run.timeouts), (3, 3));
+        assert!(run.percentiles().is_none());
+        assert_eq!(run.to_record().median_ms, None);
+    }
+
+    #[test]
+    fn test_comparison() {
+        let run = |id, median, timeouts| BenchmarkRunRecord {
+            id,
+            kind: INPUT_LATENCY_KIND.to_string(),
+            target: "win10".to_string(),
+            label: Some(format!("build-{}", id)),
+            samples: 100,
+            observed: 100 - timeouts,
+            mismatched: 0,
+            timeouts,
+            failures: 0,
+            min_ms: Some(5),
+            median_ms: median,
+            p95_ms: Some(40),
+            p99_ms: Some(60),
+            max_ms: Some(80),
+            mean_ms: Some(20.0),
+            latencies: "[]".to_string(),
+            options: None,
+            started_at: Utc::now(),
+            duration_ms: 25_000,
+        };
+
+        let comparison = BenchmarkComparison::new(&run(1, Some(20), 0), &run(2, Some(15), 4));
+        let median = &comparison.rows[1];
+        assert_eq!(median.metric, "median_ms");
+        assert_eq!((median.delta(), median.delta_percent()), (Some(-5.0), Some(-25.0)));
+
+        let timeout_rate = comparison.rows.last().unwrap();
+        assert_eq!((timeout_rate.base, timeout_rate.other), (Some(0.0), Some(4.0)));
+        assert_eq!(timeout_rate.delta_percent(), None);
+
+        let comparison = BenchmarkComparison::new(&run(1, None, 100), &run(2, Some(15), 0));
+        assert_eq!(comparison.rows[1].delta(), None);
+    }
+}
@@ -0,0 +1,233 @@
+//! 场景执行前后的环境检查
+//!
+//! 场景开始前记录 VDI 平台的资源清单快照, 结束后再取一次并对比,
+//! 找出场景执行期间新增且结束时仍然存在的资源 (孤儿资源), 附在报告中,
+//! 之后可通过 `atp vdi cleanup-orphans --from-report <id>` 按清单清理。
+//!
+//! VDI 平台没有提供快照列表接口, 目前只对比虚拟机与桌面池。
+
+use std::collections::BTreeMap;
+
+use atp_vdiplatform::VdiClient;
+use serde::{Deserialize, Serialize};
+
+use crate::resources::ResourceKind;
+use crate::{ExecutorError, Result};
+
+/// 发现孤儿资源时的处理方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvironmentGuardMode {
+    /// 只记录告警
+    Warn,
+    /// 场景判定为失败
+    Fail,
+}
+
+/// 场景执行后新增且未被清理的资源
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrphanResource {
+    /// 资源类型
+    pub kind: ResourceKind,
+
+    /// 资源 ID
+    pub id: String,
+
+    /// 资源名称
+    pub name: Option<String>,
+}
+
+/// 资源清单快照
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EnvironmentSnapshot {
+    /// 资源类型 -> (资源 ID -> 资源名称)
+    resources: BTreeMap<ResourceKind, BTreeMap<String, Option<String>>>,
+}
+
+impl EnvironmentSnapshot {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 从 VDI 平台获取当前资源清单
+    pub async fn capture(client: &VdiClient) -> Result<Self> {
+        let mut snapshot = Self::new();
+
+        let domains = client
+            .domain()
+            .list_all()
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询虚拟机列表失败: {}", e)))?;
+        snapshot.insert_listing(ResourceKind::Domain, &domains);
+
+        let pools = client
+            .desk_pool()
+            .list_all()
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询桌面池列表失败: {}", e)))?;
+        snapshot.insert_listing(ResourceKind::DeskPool, &pools);
+
+        Ok(snapshot)
+    }
+
+    /// 记录一个资源
+    pub fn insert(&mut self, kind: ResourceKind, id: &str, name: Option<&str>) {
+        self.resources
+            .entry(kind)
+            .or_default()
+            .insert(id.to_string(), name.map(str::to_string));
+    }
+
+    /// 记录列表接口返回的资源 (缺少 `id` 的条目被忽略)
+    pub fn insert_listing(&mut self, kind: ResourceKind, items: &[serde_json::Value]) {
+        for item in items {
+            if let Some(id) = item["id"].as_str().filter(|id| !id.is_empty()) {
+                self.insert(kind, id, item["name"].as_str());
+            }
+        }
+    }
+
+    /// 某类资源的数量
+    pub fn count(&self, kind: ResourceKind) -> usize {
+        self.resources.get(&kind).map_or(0, BTreeMap::len)
+    }
+
+    /// 某类资源的名称集合 (没有名称时使用 ID)
+    pub fn names(&self, kind: ResourceKind) -> Vec<&str> {
+        self.resources
+            .get(&kind)
+            .map(|items| {
+                items
+                    .iter()
+                    .map(|(id, name)| name.as_deref().unwrap_or(id))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 对比场景结束后的快照, 返回新增的资源 (按类型、ID 排序)
+    pub fn diff(&self, after: &EnvironmentSnapshot) -> Vec<OrphanResource> {
+        let mut orphans = Vec::new();
+
+        for (kind, items) in &after.resources {
+            let before = self.resources.get(kind);
+            for (id, name) in items {
+                if before.is_none_or(|before| !before.contains_key(id)) {
+                    orphans.push(OrphanResource {
+                        kind: *kind,
+                        id: id.clone(),
+                        name: name.clone(),
+                    });
+                }
+            }
+        }
+
+        orphans
+    }
+}
+
+/// 环境守卫
+///
+/// 场景开始前调用 [`EnvironmentGuard::start`] 记录快照,
+/// 结束后调用 [`EnvironmentGuard::finish`] 得到孤儿资源清单。
+#[derive(Debug)]
+pub struct EnvironmentGuard {
+    mode: EnvironmentGuardMode,
+    before: EnvironmentSnapshot,
+}
+
+impl EnvironmentGuard {
+    /// 记录场景开始前的资源清单
+    pub async fn start(client: &VdiClient, mode: EnvironmentGuardMode) -> Result<Self> {
+        let before = EnvironmentSnapshot::capture(client).await?;
+        Ok(Self::from_snapshot(before, mode))
+    }
+
+    /// 使用已有快照创建守卫
+    pub fn from_snapshot(before: EnvironmentSnapshot, mode: EnvironmentGuardMode) -> Self {
+        Self { mode, before }
+    }
+
+    pub fn mode(&self) -> EnvironmentGuardMode {
+        self.mode
+    }
+
+    /// 场景开始前的快照
+    pub fn before(&self) -> &EnvironmentSnapshot {
+        &self.before
+    }
+
+    /// 再次获取资源清单并返回新增的资源
+    pub async fn finish(&self, client: &VdiClient) -> Result<Vec<OrphanResource>> {
+        let after = EnvironmentSnapshot::capture(client).await?;
+        Ok(self.before.diff(&after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_reports_only_new_resources() {
+        let mut before = EnvironmentSnapshot::new();
+        before.insert_listing(
+            ResourceKind::Domain,
+            &[json!({"id": "vm-1", "name": "base"}), json!({"id": "vm-2", "name": "gold"})],
+        );
+        before.insert(ResourceKind::DeskPool, "pool-1", Some("office"));
+
+        let mut after = before.clone();
+        // 场景删除了 vm-2, 新增 vm-3 与 pool-2
+        after.resources.get_mut(&ResourceKind::Domain).unwrap().remove("vm-2");
+        after.insert_listing(
+            ResourceKind::Domain,
+            &[json!({"id": "vm-3", "name": "clone-1"}), json!({"name": "no-id"})],
+        );
+        after.insert(ResourceKind::DeskPool, "pool-2", None);
+
+        assert_eq!(before.count(ResourceKind::Domain), 2);
+        assert_eq!(after.count(ResourceKind::Domain), 2);
+        assert_eq!(after.names(ResourceKind::DeskPool), vec!["office", "pool-2"]);
+        assert_eq!(after.count(ResourceKind::Snapshot), 0);
+
+        let orphans = before.diff(&after);
+        assert_eq!(
+            orphans,
+            vec![
+                OrphanResource {
+                    kind: ResourceKind::DeskPool,
+                    id: "pool-2".to_string(),
+                    name: None,
+                },
+                OrphanResource {
+                    kind: ResourceKind::Domain,
+                    id: "vm-3".to_string(),
+                    name: Some("clone-1".to_string()),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_of_identical_snapshots_is_empty() {
+        let mut snapshot = EnvironmentSnapshot::new();
+        snapshot.insert(ResourceKind::Domain, "vm-1", Some("base"));
+
+        assert!(snapshot.diff(&snapshot.clone()).is_empty());
+        assert!(EnvironmentSnapshot::new().diff(&EnvironmentSnapshot::new()).is_empty());
+
+        // 快照中原本没有的资源类型也能识别新增
+        let mut after = snapshot.clone();
+        after.insert(ResourceKind::Snapshot, "snap-1", None);
+        assert_eq!(snapshot.diff(&after).len(), 1);
+    }
+
+    #[test]
+    fn test_guard_mode_deserialize() {
+        let mode: EnvironmentGuardMode = serde_yaml::from_str("fail").unwrap();
+        assert_eq!(mode, EnvironmentGuardMode::Fail);
+        assert!(serde_yaml::from_str::<EnvironmentGuardMode>("ignore").is_err());
+    }
+}
@@ -0,0 +1,306 @@
+//! 步骤失败分类
+//!
+//! CI 分诊时需要区分 "基础设施抖动" (传输超时、主机不可达) 与 "产品缺陷" (命令非零退出、状态不符)。
+//! 失败的步骤按错误链归入 [`StepErrorKind`], 保存在报告与 `execution_steps.error_kind` 中,
+//! 可用 `atp report list --failed-kind <分类>` 筛选。
+
+use std::fmt;
+use std::str::FromStr;
+
+use atp_protocol::ProtocolError;
+use atp_transport::TransportError;
+use serde::{Deserialize, Serialize};
+
+use crate::{ExecutorError, PowerShellError};
+
+/// 步骤失败分类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepErrorKind {
+    /// 基础设施问题: 主机不可达、连接断开、libvirt / 数据库 / IO 错误
+    Infrastructure,
+    /// 协议交互问题: QMP / QGA / SPICE 报错或响应无法解析
+    Protocol,
+    /// 验证失败: 命令非零退出、状态或内容与预期不符
+    Verification,
+    /// 超时 (步骤超时、协议或连接超时、场景超时导致的取消)
+    Timeout,
+    /// 配置问题: 场景或测试配置有误、认证失败、权限不足
+    Configuration,
+    /// 无法归类
+    Unknown,
+}
+
+impl StepErrorKind {
+    /// 全部分类 (按显示顺序)
+    pub const ALL: [StepErrorKind; 6] = [
+        StepErrorKind::Infrastructure,
+        StepErrorKind::Protocol,
+        StepErrorKind::Verification,
+        StepErrorKind::Timeout,
+        StepErrorKind::Configuration,
+        StepErrorKind::Unknown,
+    ];
+
+    /// 分类名称, 与数据库中的取值一致
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepErrorKind::Infrastructure => "infrastructure",
+            StepErrorKind::Protocol => "protocol",
+            StepErrorKind::Verification => "verification",
+            StepErrorKind::Timeout => "timeout",
+            StepErrorKind::Configuration => "configuration",
+            StepErrorKind::Unknown => "unknown",
+        }
+    }
+
+    /// 是否可能是环境抖动 (重跑可能通过)
+    pub fn is_infrastructure_flake(&self) -> bool {
+        matches!(self, StepErrorKind::Infrastructure | StepErrorKind::Timeout)
+    }
+
+    /// 按执行器错误分类, 协议与传输错误按其原始错误继续细分
+    pub fn from_error(error: &ExecutorError) -> Self {
+        match error {
+            ExecutorError::Timeout | ExecutorError::Cancelled(_) => StepErrorKind::Timeout,
+            ExecutorError::Protocol { source, .. } => Self::from_protocol_error(source),
+            ExecutorError::Transport { source, .. } => Self::from_transport_error(source),
+            ExecutorError::ProtocolError(_) | ExecutorError::SerdeError(_) => StepErrorKind::Protocol,
+            ExecutorError::TransportError(_) | ExecutorError::IoError(_) | ExecutorError::DatabaseError(_) => {
+                StepErrorKind::Infrastructure
+            }
+            ExecutorError::ConfigError(_) | ExecutorError::ScenarioLoadFailed(_) => StepErrorKind::Configuration,
+            ExecutorError::StepExecutionFailed(_) => StepErrorKind::Verification,
+            ExecutorError::PowerShell(error) => match error {
+                PowerShellError::Timeout(_) => StepErrorKind::Timeout,
+                PowerShellError::Protocol(_) => StepErrorKind::Protocol,
+                PowerShellError::Exception(_) | PowerShellError::ExitCode { .. } | PowerShellError::InvalidJson(_) => {
+                    StepErrorKind::Verification
+                }
+            },
+        }
+    }
+
+    /// 协议层错误的分类
+    pub fn from_protocol_error(error: &ProtocolError) -> Self {
+        match error {
+            ProtocolError::Timeout => StepErrorKind::Timeout,
+            ProtocolError::ConnectionFailed(_)
+            | ProtocolError::SendFailed(_)
+            | ProtocolError::ReceiveFailed(_)
+            | ProtocolError::IoError(_) => StepErrorKind::Infrastructure,
+            ProtocolError::TransportError(source) => Self::from_transport_error(source),
+            ProtocolError::WithContext { source, .. } => Self::from_protocol_error(source),
+            ProtocolError::ProtocolNotFound(_)
+            | ProtocolError::AuthRequired(_)
+            | ProtocolError::AuthFailed(_)
+            | ProtocolError::KeyMapping(_) => StepErrorKind::Configuration,
+            ProtocolError::ProtocolAlreadyRegistered(_)
+            | ProtocolError::ParseError(_)
+            | ProtocolError::CommandFailed(_) => StepErrorKind::Protocol,
+        }
+    }
+
+    /// 传输层错误的分类
+    pub fn from_transport_error(error: &TransportError) -> Self {
+        match error {
+            TransportError::Timeout | TransportError::IdleTimeout(_) => StepErrorKind::Timeout,
+            TransportError::ConnectionFailed(_)
+            | TransportError::HostNotFound(_)
+            | TransportError::PoolExhausted
+            | TransportError::Disconnected
+            | TransportError::IoError(_)
+            | TransportError::LibvirtError(_)
+            | TransportError::SftpError(_)
+            | TransportError::GlusterError(_) => StepErrorKind::Infrastructure,
+            TransportError::ConfigError(_)
+            | TransportError::InvalidConfig(_)
+            | TransportError::AmbiguousDomain(..)
+            | TransportError::PermissionDenied(_)
+            | TransportError::SudoAuthFailed(_)
+            | TransportError::SudoNotPermitted(_) => StepErrorKind::Configuration,
+            TransportError::DomainNotFound(_) | TransportError::FileNotFound(_) => StepErrorKind::Verification,
+            TransportError::WithContext { source, .. } => Self::from_transport_error(source),
+        }
+    }
+}
+
+impl fmt::Display for StepErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for StepErrorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        Self::ALL.into_iter().find(|kind| kind.as_str() == s).ok_or_else(|| {
+            let names: Vec<_> = Self::ALL.iter().map(|kind| kind.as_str()).collect();
+            format!("未知的失败分类: {} (可选: {})", s, names.join(", "))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use atp_protocol::KeyMappingError;
+    use atp_transport::ErrorContext;
+
+    #[test]
+    fn test_executor_error_kinds() {
+        let cases = [
+            (ExecutorError::Timeout, StepErrorKind::Timeout),
+            (ExecutorError::Cancelled("场景超时".to_string()), StepErrorKind::Timeout),
+            (ExecutorError::ProtocolError("QMP 协议未初始化".to_string()), StepErrorKind::Protocol),
+            (ExecutorError::SerdeError("bad json".to_string()), StepErrorKind::Protocol),
+            (ExecutorError::TransportError("启动虚拟机失败".to_string()), StepErrorKind::Infrastructure),
+            (ExecutorError::IoError(std::io::Error::other("disk full")), StepErrorKind::Infrastructure),
+            (ExecutorError::DatabaseError("locked".to_string()), StepErrorKind::Infrastructure),
+            (ExecutorError::ConfigError("未配置 VDI".to_string()), StepErrorKind::Configuration),
+            (ExecutorError::ScenarioLoadFailed("yaml".to_string()), StepErrorKind::Configuration),
+            (ExecutorError::StepExecutionFailed("断言失败".to_string()), StepErrorKind::Verification),
+        ];
+
+        for (error, expected) in cases {
+            assert_eq!(error.kind(), expected, "{}", error);
+        }
+
+        let powershell = [
+            (PowerShellError::ExitCode { code: 1, stderr: String::new() }, StepErrorKind::Verification),
+            (PowerShellError::InvalidJson("not json".to_string()), StepErrorKind::Verification),
+            (PowerShellError::Timeout(std::time::Duration::from_secs(60)), StepErrorKind::Timeout),
+            (PowerShellError::Protocol("QGA 未连接".to_string()), StepErrorKind::Protocol),
+        ];
+        for (error, expected) in powershell {
+            let error = ExecutorError::PowerShell(error);
+            assert_eq!(error.kind(), expected, "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_wrapped_protocol_error_kinds() {
+        let cases = [
+            (ProtocolError::Timeout, StepErrorKind::Timeout),
+            (ProtocolError::ConnectionFailed("socket".to_string()), StepErrorKind::Infrastructure),
+            (ProtocolError::SendFailed("broken pipe".to_string()), StepErrorKind::Infrastructure),
+            (ProtocolError::ReceiveFailed("eof".to_string()), StepErrorKind::Infrastructure),
+            (ProtocolError::IoError(std::io::Error::other("reset")), StepErrorKind::Infrastructure),
+            (ProtocolError::ParseError("unexpected".to_string()), StepErrorKind::Protocol),
+            (ProtocolError::CommandFailed("GenericError".to_string()), StepErrorKind::Protocol),
+            (ProtocolError::ProtocolAlreadyRegistered("qmp".to_string()), StepErrorKind::Protocol),
+            (ProtocolError::ProtocolNotFound("rdp".to_string()), StepErrorKind::Configuration),
+            (ProtocolError::AuthRequired("SASL".to_string()), StepErrorKind::Configuration),
+            (ProtocolError::AuthFailed("ticket".to_string()), StepErrorKind::Configuration),
+            (
+                ProtocolError::KeyMapping(KeyMappingError::UnknownKey {
+                    token: "foo".to_string(),
+                    combo: "ctrl+foo".to_string(),
+                }),
+                StepErrorKind::Configuration,
+            ),
+            // 协议错误中包装的传输错误按传输错误分类
+            (ProtocolError::TransportError(TransportError::Timeout), StepErrorKind::Timeout),
+            (ProtocolError::TransportError(TransportError::Disconnected), StepErrorKind::Infrastructure),
+            // 上下文不影响分类
+            (
+                ProtocolError::Timeout.with_context(ErrorContext::new().with_host("host-1")),
+                StepErrorKind::Timeout,
+            ),
+        ];
+
+        for (source, expected) in cases {
+            let message = source.to_string();
+            assert_eq!(ExecutorError::protocol("QGA 执行命令失败", source).kind(), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_wrapped_transport_error_kinds() {
+        let cases = [
+            (TransportError::Timeout, StepErrorKind::Timeout),
+            (TransportError::IdleTimeout(std::time::Duration::from_secs(30)), StepErrorKind::Timeout),
+            (TransportError::ConnectionFailed("refused".to_string()), StepErrorKind::Infrastructure),
+            (TransportError::HostNotFound("host-9".to_string()), StepErrorKind::Infrastructure),
+            (TransportError::PoolExhausted, StepErrorKind::Infrastructure),
+            (TransportError::Disconnected, StepErrorKind::Infrastructure),
+            (TransportError::IoError(std::io::Error::other("reset")), StepErrorKind::Infrastructure),
+            (TransportError::LibvirtError("internal error".to_string()), StepErrorKind::Infrastructure),
+            (TransportError::SftpError("channel".to_string()), StepErrorKind::Infrastructure),
+            (TransportError::GlusterError("volume".to_string()), StepErrorKind::Infrastructure),
+            (TransportError::ConfigError("uri".to_string()), StepErrorKind::Configuration),
+            (TransportError::InvalidConfig(vec!["hosts".to_string()]), StepErrorKind::Configuration),
+            (
+                TransportError::AmbiguousDomain("win10".to_string(), vec!["h1".to_string(), "h2".to_string()]),
+                StepErrorKind::Configuration,
+            ),
+            (TransportError::PermissionDenied("/var/log".to_string()), StepErrorKind::Configuration),
+            (TransportError::SudoAuthFailed("root".to_string()), StepErrorKind::Configuration),
+            (TransportError::SudoNotPermitted("atp".to_string()), StepErrorKind::Configuration),
+            (TransportError::DomainNotFound("win10".to_string()), StepErrorKind::Verification),
+            (TransportError::FileNotFound("/tmp/x".to_string()), StepErrorKind::Verification),
+            (
+                TransportError::Disconnected.with_context(ErrorContext::new().with_domain("win10")),
+                StepErrorKind::Infrastructure,
+            ),
+        ];
+
+        for (source, expected) in cases {
+            let message = source.to_string();
+            assert_eq!(ExecutorError::transport("", source).kind(), expected, "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_wrapped_error_display() {
+        let error = ExecutorError::protocol("QMP send_key 失败", ProtocolError::Timeout);
+        assert_eq!(error.to_string(), "协议错误: QMP send_key 失败: 超时");
+
+        let error = ExecutorError::transport("", TransportError::Disconnected);
+        assert_eq!(error.to_string(), "传输错误: 连接已断开");
+    }
+
+    #[test]
+    fn test_parse_kind() {
+        assert_eq!("Infrastructure".parse::<StepErrorKind>().unwrap(), StepErrorKind::Infrastructure);
+        for kind in StepErrorKind::ALL {
+            assert_eq!(kind.as_str().parse::<StepErrorKind>().unwrap(), kind);
+            assert_eq!(serde_json::to_value(kind).unwrap(), kind.as_str());
+        }
+
+        let err = "flake".parse::<StepErrorKind>().unwrap_err();
+        assert!(err.contains("infrastructure, protocol"), "{}", err);
+        assert!(StepErrorKind::Timeout.is_infrastructure_flake());
+        assert!(!StepErrorKind::Verification.is_infrastructure_flake());
+    }
+
+    #[test]
+    fn test_report_counts_failed_kinds() {
+        use crate::{ExecutionReport, StepReport};
+
+        let mut report = ExecutionReport::new("login");
+        report.add_step(StepReport::success(0, "启动"));
+        report.add_step(StepReport::failed(1, "检查窗口", "标题不符"));
+
+        let mut flaky = StepReport::failed(2, "发送按键", "QMP 超时");
+        flaky.error_kind = Some(StepErrorKind::Timeout);
+        report.add_step(flaky);
+
+        // 旧报告中的失败步骤没有分类
+        let mut legacy = StepReport::failed(3, "旧步骤", "失败");
+        legacy.error_kind = None;
+        report.add_step(legacy);
+
+        assert_eq!(report.failed_count, 3);
+        assert_eq!(report.failed_kinds.get(&StepErrorKind::Verification), Some(&1));
+        assert_eq!(report.failed_kinds.get(&StepErrorKind::Timeout), Some(&1));
+        assert_eq!(report.failed_kinds.get(&StepErrorKind::Unknown), Some(&1));
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["failed_kinds"]["timeout"], 1);
+        assert_eq!(json["steps"][2]["error_kind"], "timeout");
+        assert!(json["steps"][0].get("error_kind").is_none());
+    }
+}
@@ -0,0 +1,177 @@
+//! Windows 事件日志查询
+//!
+//! 通过 QGA 在 Windows 客户机中执行 PowerShell `Get-WinEvent`,
+//! 以 JSON 形式导出事件并在宿主侧解析。
+
+use serde::{Deserialize, Serialize};
+
+use crate::{ExecutorError, Result};
+
+/// 事件日志名称
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventLogName {
+    #[serde(alias = "system")]
+    System,
+    #[serde(alias = "application")]
+    Application,
+}
+
+impl EventLogName {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventLogName::System => "System",
+            EventLogName::Application => "Application",
+        }
+    }
+}
+
+/// 事件级别 (按最低严重程度过滤)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventLevel {
+    /// 严重 + 错误
+    #[serde(alias = "error")]
+    Error,
+    /// 严重 + 错误 + 警告
+    #[serde(alias = "warning")]
+    Warning,
+}
+
+impl EventLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventLevel::Error => "Error",
+            EventLevel::Warning => "Warning",
+        }
+    }
+
+    /// Get-WinEvent 的 Level 过滤值 (1 = 严重, 2 = 错误, 3 = 警告)
+    fn levels(&self) -> &'static str {
+        match self {
+            EventLevel::Error => "1,2",
+            EventLevel::Warning => "1,2,3",
+        }
+    }
+}
+
+/// Windows 事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct WindowsEvent {
+    /// 事件 ID
+    #[serde(rename = "Id")]
+    pub id: u32,
+
+    /// 事件来源
+    #[serde(rename = "ProviderName", default)]
+    pub provider: Option<String>,
+
+    /// 级别名称 (已本地化)
+    #[serde(rename = "LevelDisplayName", default)]
+    pub level: Option<String>,
+
+    /// 事件时间 (ISO 8601)
+    #[serde(rename = "TimeCreated", default)]
+    pub time_created: Option<String>,
+
+    /// 事件消息
+    #[serde(rename = "Message", default)]
+    pub message: Option<String>,
+}
+
+impl WindowsEvent {
+    /// 单行摘要
+    pub fn summary(&self) -> String {
+        let message = self
+            .message
+            .as_deref()
+            .and_then(|m| m.lines().map(str::trim).find(|l| !l.is_empty()))
+            .unwrap_or("");
+
+        format!(
+            "[{}] {} {} (ID {}): {}",
+            self.time_created.as_deref().unwrap_or("-"),
+            self.level.as_deref().unwrap_or("-"),
+            self.provider.as_deref().unwrap_or("-"),
+            self.id,
+            message
+        )
+    }
+}
+
+/// 生成查询事件日志的 PowerShell 脚本
+pub fn build_query_script(log: EventLogName, level: EventLevel, since_minutes: u64) -> String {
+    format!(
+        "$events = @(Get-WinEvent -FilterHashtable @{{LogName='{}'; Level={}; StartTime=(Get-Date).AddMinutes(-{})}} -ErrorAction SilentlyContinue | \
+         Select-Object Id, ProviderName, LevelDisplayName, @{{n='TimeCreated';e={{$_.TimeCreated.ToString('o')}}}}, Message); \
+         ConvertTo-Json -InputObject $events -Compress",
+        log.as_str(),
+        level.levels(),
+        since_minutes
+    )
+}
+
+/// 解析 `ConvertTo-Json` 的输出
+///
+/// 没有事件时输出可能为空, 只有一个事件时可能是对象而不是数组。
+pub fn parse_events(output: &str) -> Result<Vec<WindowsEvent>> {
+    let output = output.trim().trim_start_matches('\u{feff}');
+    if output.is_empty() || output == "null" {
+        return Ok(Vec::new());
+    }
+
+    let value: serde_json::Value = serde_json::from_str(output)
+        .map_err(|e| ExecutorError::SerdeError(format!("解析事件日志失败: {}", e)))?;
+
+    let items = match value {
+        serde_json::Value::Array(items) => items,
+        serde_json::Value::Null => Vec::new(),
+        item => vec![item],
+    };
+
+    items
+        .into_iter()
+        .map(|item| {
+            serde_json::from_value(item)
+                .map_err(|e| ExecutorError::SerdeError(format!("解析事件失败: {}", e)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_event_array() {
+        let output = r#"[{"Id":41,"ProviderName":"Microsoft-Windows-Kernel-Power","LevelDisplayName":"Critical","TimeCreated":"2024-01-01T10:00:00.0000000+08:00","Message":"The system has rebooted without cleanly shutting down first.\r\nDetails"},{"Id":7000,"ProviderName":"Service Control Manager","LevelDisplayName":"Error","TimeCreated":"2024-01-01T10:01:00.0000000+08:00","Message":null}]"#;
+
+        let events = parse_events(output).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].id, 41);
+        assert_eq!(
+            events[0].summary(),
+            "[2024-01-01T10:00:00.0000000+08:00] Critical Microsoft-Windows-Kernel-Power (ID 41): \
+             The system has rebooted without cleanly shutting down first."
+        );
+        assert!(events[1].message.is_none());
+    }
+
+    #[test]
+    fn test_parse_single_and_empty_output() {
+        let events = parse_events(r#"{"Id":1001,"ProviderName":"BugCheck"}"#).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].provider.as_deref(), Some("BugCheck"));
+
+        assert!(parse_events("").unwrap().is_empty());
+        assert!(parse_events("\u{feff}[]\r\n").unwrap().is_empty());
+        assert!(parse_events("null").unwrap().is_empty());
+        assert!(parse_events("not json").is_err());
+    }
+
+    #[test]
+    fn test_build_query_script() {
+        let script = build_query_script(EventLogName::System, EventLevel::Warning, 30);
+        assert!(script.contains("LogName='System'"));
+        assert!(script.contains("Level=1,2,3"));
+        assert!(script.contains("AddMinutes(-30)"));
+    }
+}
@@ -0,0 +1,249 @@
+//! 多目标并行执行
+//!
+//! 对一组虚拟机并行执行同一场景。每个目标使用独立的 [`ScenarioRunner`]、
+//! 变量作用域与工件子目录 (见 [`crate::scope`]), 场景的 `target_domain`
+//! 替换为目标虚拟机。聚合报告中记录每个子运行的工件目录。
+
+use std::time::Instant;
+
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::scope::{prepare_targets, ArtifactLayout, FanOutTarget, SharedVariables};
+use crate::{ExecutionReport, Result, Scenario, ScenarioRunner, StepFilter};
+
+/// 默认同时执行的目标数
+pub const DEFAULT_FAN_OUT_CONCURRENCY: usize = 4;
+
+/// 多目标并行执行器
+pub struct FanOutRunner {
+    /// 工件目录布局
+    layout: ArtifactLayout,
+
+    /// 只读的共享变量层
+    shared: SharedVariables,
+
+    /// 同时执行的目标数
+    concurrency: usize,
+
+    /// 取消所有子运行
+    cancel_token: CancellationToken,
+}
+
+impl FanOutRunner {
+    pub fn new(layout: ArtifactLayout) -> Self {
+        Self {
+            layout,
+            shared: SharedVariables::default(),
+            concurrency: DEFAULT_FAN_OUT_CONCURRENCY,
+            cancel_token: CancellationToken::new(),
+        }
+    }
+
+    /// 设置共享变量层 (CLI `--var` 与 profile 中的变量)
+    pub fn with_shared_variables(mut self, shared: SharedVariables) -> Self {
+        self.shared = shared;
+        self
+    }
+
+    /// 设置同时执行的目标数 (至少为 1)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// 获取取消令牌
+    ///
+    /// 取消后所有正在执行的子运行跳过剩余步骤并执行清理, 尚未开始的子运行直接记为取消。
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+
+    /// 对每台虚拟机执行场景
+    ///
+    /// `make_runner` 为每个目标创建执行器; 虚拟机序号 (主机名模板中的 `{index}`)、
+    /// 工件目录与变量作用域由本方法设置。
+    /// 单个子运行出错不影响其他目标, 错误记录在聚合报告中。
+    pub async fn run<F>(
+        &self,
+        scenario: &Scenario,
+        vm_names: &[String],
+        filter: &StepFilter,
+        make_runner: F,
+    ) -> Result<FanOutReport>
+    where
+        F: Fn(&FanOutTarget) -> ScenarioRunner,
+    {
+        let targets = prepare_targets(vm_names, &self.shared, &self.layout)?;
+        let start_time = Instant::now();
+
+        info!("对 {} 台虚拟机并行执行场景: {}", targets.len(), scenario.name);
+
+        let mut results: Vec<FanOutTargetReport> = stream::iter(targets)
+            .map(|target| {
+                let runner = make_runner(&target)
+                    .with_vm_index(target.index)
+                    .with_artifact_dir(&target.artifact_dir)
+                    .with_variables(target.variables.clone());
+                self.run_target(scenario, filter, target, runner)
+            })
+            .buffer_unordered(self.concurrency)
+            .collect()
+            .await;
+        results.sort_by_key(|result| result.index);
+
+        Ok(FanOutReport::new(
+            &scenario.name,
+            self.layout.root().display().to_string(),
+            results,
+            start_time.elapsed().as_millis() as u64,
+        ))
+    }
+
+    /// 执行单个目标, 外部取消时转发给该目标的执行器
+    async fn run_target(
+        &self,
+        scenario: &Scenario,
+        filter: &StepFilter,
+        target: FanOutTarget,
+        mut runner: ScenarioRunner,
+    ) -> FanOutTargetReport {
+        let mut scenario = scenario.clone();
+        scenario.target_domain = Some(target.vm_name.clone());
+
+        let runner_token = runner.cancellation_token();
+        let forward_cancel = async {
+            self.cancel_token.cancelled().await;
+            runner_token.cancel();
+            std::future::pending::<()>().await
+        };
+
+        let result = tokio::select! {
+            result = runner.run_filtered(&scenario, filter) => result,
+            _ = forward_cancel => unreachable!(),
+        };
+
+        if let Err(e) = &result {
+            warn!("虚拟机 {} 执行场景失败: {}", target.vm_name, e);
+        }
+        FanOutTargetReport::new(&target, result)
+    }
+}
+
+/// 单个目标的执行结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanOutTargetReport {
+    /// 虚拟机名称
+    pub vm_name: String,
+
+    /// 在批量执行中的序号
+    pub index: usize,
+
+    /// 工件子目录
+    pub artifact_dir: String,
+
+    /// 是否通过
+    pub passed: bool,
+
+    /// 子运行无法开始或中途出错时的错误信息
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+
+    /// 子运行的执行报告
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub report: Option<ExecutionReport>,
+}
+
+impl FanOutTargetReport {
+    fn new(target: &FanOutTarget, result: Result<ExecutionReport>) -> Self {
+        let (passed, error, report) = match result {
+            Ok(report) => (report.passed && report.failed_count == 0, None, Some(report)),
+            Err(e) => (false, Some(e.to_string()), None),
+        };
+
+        Self {
+            vm_name: target.vm_name.clone(),
+            index: target.index,
+            artifact_dir: target.artifact_dir.display().to_string(),
+            passed,
+            error,
+            report,
+        }
+    }
+}
+
+/// 多目标执行的聚合报告
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FanOutReport {
+    /// 场景名称
+    pub scenario_name: String,
+
+    /// 工件根目录
+    pub artifact_root: String,
+
+    /// 总耗时 (毫秒)
+    pub duration_ms: u64,
+
+    /// 所有目标是否都通过
+    pub passed: bool,
+
+    /// 各目标的结果 (按序号排列)
+    pub targets: Vec<FanOutTargetReport>,
+}
+
+impl FanOutReport {
+    fn new(scenario_name: &str, artifact_root: String, targets: Vec<FanOutTargetReport>, duration_ms: u64) -> Self {
+        Self {
+            scenario_name: scenario_name.to_string(),
+            artifact_root,
+            duration_ms,
+            passed: !targets.is_empty() && targets.iter().all(|target| target.passed),
+            targets,
+        }
+    }
+
+    /// 通过的目标数
+    pub fn passed_count(&self) -> usize {
+        self.targets.iter().filter(|target| target.passed).count()
+    }
+
+    /// 导出为 JSON
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExecutorError;
+
+    #[test]
+    fn test_report_aggregation() {
+        let root = std::env::temp_dir().join(format!("atp-fanout-report-{}", std::process::id()));
+        let names = vec!["vm-1".to_string(), "vm-2".to_string()];
+        let targets = prepare_targets(&names, &SharedVariables::default(), &ArtifactLayout::new(&root)).unwrap();
+
+        let mut ok = ExecutionReport::new("login");
+        ok.passed = true;
+        let results = vec![
+            FanOutTargetReport::new(&targets[0], Ok(ok)),
+            FanOutTargetReport::new(&targets[1], Err(ExecutorError::ConfigError("未指定目标主机".to_string()))),
+        ];
+        let report = FanOutReport::new("login", root.display().to_string(), results, 10);
+
+        assert!(!report.passed);
+        assert_eq!(report.passed_count(), 1);
+        assert_eq!(report.targets[1].error.as_deref(), Some("配置错误: 未指定目标主机"));
+
+        // 聚合报告中可以定位每个子运行的工件目录
+        let json: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["targets"][0]["artifact_dir"], root.join("vm-1").display().to_string());
+        assert_eq!(json["targets"][1]["artifact_dir"], root.join("vm-2").display().to_string());
+        assert!(json["targets"][1].get("report").is_none());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
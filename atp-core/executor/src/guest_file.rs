@@ -0,0 +1,411 @@
+//! 客户机文件校验 (`verify_file_in_guest`)
+//!
+//! 通过 QGA 的 guest-file-* 命令读取客户机中的文件, 不依赖客户机里的 shell,
+//! Windows 与 Linux 客户机行为一致。文件按块流式读取: SHA-256 边读边算, 不把整个文件放进内存;
+//! 内容检查只缓存前 `max_bytes` 字节。
+//!
+//! `expect_sha256` 可以是整个文件的摘要, 也可以是分区摘要清单 (`<区域大小>:<摘要1>,<摘要2>,...`)。
+//! 步骤输出总会带上实际文件的清单, 从一次已知正确的运行中复制过来后,
+//! 摘要不一致时可以定位到第一个不同的区域。
+//!
+//! [`write_guest_file`] 以同样的方式写入客户机文件, 供还原点验证写入标记文件。
+
+use atp_protocol::qga::{GuestFileWhence, QgaProtocol};
+use atp_protocol::ProtocolError;
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::{ExecutorError, Result};
+
+/// `max_bytes` 的默认值: 16 MiB
+pub const DEFAULT_MAX_BYTES: u64 = 16 * 1024 * 1024;
+
+/// 分区摘要的默认区域大小: 1 MiB
+pub const DEFAULT_REGION_SIZE: u64 = 1024 * 1024;
+
+/// 每次 guest-file-read / guest-file-write 传输的字节数
+///
+/// 数据以 base64 放在 JSON 里经 libvirt 传回, 单次不宜过大。
+const READ_CHUNK_SIZE: u64 = 256 * 1024;
+
+/// 期望的 SHA-256
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Sha256Expectation {
+    /// 整个文件的摘要
+    Whole(String),
+
+    /// 按 `region_size` 分区的摘要清单
+    Regions { region_size: u64, digests: Vec<String> },
+}
+
+impl Sha256Expectation {
+    /// 解析 `expect_sha256` (不区分大小写)
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim().to_ascii_lowercase();
+
+        let Some((size, digests)) = text.split_once(':') else {
+            check_digest(&text)?;
+            return Ok(Self::Whole(text));
+        };
+
+        let region_size: u64 = size
+            .trim()
+            .parse()
+            .ok()
+            .filter(|size| *size > 0)
+            .ok_or_else(|| ExecutorError::ConfigError(format!("分区摘要清单的区域大小无效: {}", size)))?;
+        let digests: Vec<String> = digests.split(',').map(|digest| digest.trim().to_string()).collect();
+        for digest in &digests {
+            check_digest(digest)?;
+        }
+
+        Ok(Self::Regions { region_size, digests })
+    }
+
+    /// 计算实际文件的分区摘要时使用的区域大小
+    pub fn region_size(&self) -> u64 {
+        match self {
+            Self::Whole(_) => DEFAULT_REGION_SIZE,
+            Self::Regions { region_size, .. } => *region_size,
+        }
+    }
+}
+
+fn check_digest(digest: &str) -> Result<()> {
+    if digest.len() == 64 && digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(ExecutorError::ConfigError(format!("不是有效的 SHA-256 摘要: {}", digest)))
+    }
+}
+
+/// 流式读取文件时累积的结果
+#[derive(Debug)]
+pub struct FileDigest {
+    region_size: u64,
+    hasher: Sha256,
+    region_hasher: Sha256,
+    region_filled: u64,
+    regions: Vec<String>,
+    size: u64,
+    max_bytes: u64,
+    head: Vec<u8>,
+}
+
+impl FileDigest {
+    /// `max_bytes` 为内容检查缓存的上限
+    pub fn new(region_size: u64, max_bytes: u64) -> Self {
+        Self {
+            region_size: region_size.max(1),
+            hasher: Sha256::new(),
+            region_hasher: Sha256::new(),
+            region_filled: 0,
+            regions: Vec::new(),
+            size: 0,
+            max_bytes,
+            head: Vec::new(),
+        }
+    }
+
+    /// 追加下一块数据
+    pub fn update(&mut self, mut chunk: &[u8]) {
+        self.hasher.update(chunk);
+        self.size += chunk.len() as u64;
+
+        let room = self.max_bytes.saturating_sub(self.head.len() as u64) as usize;
+        self.head.extend_from_slice(&chunk[..room.min(chunk.len())]);
+
+        while !chunk.is_empty() {
+            let take = ((self.region_size - self.region_filled) as usize).min(chunk.len());
+            self.region_hasher.update(&chunk[..take]);
+            self.region_filled += take as u64;
+            chunk = &chunk[take..];
+
+            if self.region_filled == self.region_size {
+                self.finish_region();
+            }
+        }
+    }
+
+    fn finish_region(&mut self) {
+        let hasher = std::mem::take(&mut self.region_hasher);
+        self.regions.push(format!("{:x}", hasher.finalize()));
+        self.region_filled = 0;
+    }
+
+    /// 内容检查需要的数据是否已经读够
+    pub fn head_full(&self) -> bool {
+        self.head.len() as u64 >= self.max_bytes
+    }
+
+    /// 结束读取
+    pub fn finish(mut self) -> FileContents {
+        if self.region_filled > 0 {
+            self.finish_region();
+        }
+        FileContents {
+            size: self.size,
+            sha256: format!("{:x}", self.hasher.finalize()),
+            region_size: self.region_size,
+            regions: self.regions,
+            head: self.head,
+        }
+    }
+}
+
+/// 读取完成的文件
+#[derive(Debug, Clone)]
+pub struct FileContents {
+    /// 已读取的字节数 (只做内容检查时可能小于文件大小)
+    pub size: u64,
+
+    /// 已读取部分的 SHA-256
+    pub sha256: String,
+
+    pub region_size: u64,
+
+    /// 各区域的 SHA-256
+    pub regions: Vec<String>,
+
+    /// 前 `max_bytes` 字节
+    pub head: Vec<u8>,
+}
+
+impl FileContents {
+    /// 分区摘要清单 (可以直接用作 `expect_sha256`)
+    pub fn manifest(&self) -> String {
+        format!("{}:{}", self.region_size, self.regions.join(","))
+    }
+
+    /// 按文本解码前 `max_bytes` 字节 (识别 UTF-16 BOM, 其余按 UTF-8 宽松解码)
+    pub fn text(&self) -> String {
+        decode_text(&self.head)
+    }
+
+    /// 与期望的摘要比较, 不一致时返回失败原因
+    pub fn check_sha256(&self, expected: &Sha256Expectation) -> std::result::Result<(), String> {
+        match expected {
+            Sha256Expectation::Whole(digest) if *digest == self.sha256 => Ok(()),
+            Sha256Expectation::Whole(digest) => Err(format!(
+                "SHA-256 不一致: 期望 {}, 实际 {} (文件大小 {} 字节)",
+                digest, self.sha256, self.size
+            )),
+            Sha256Expectation::Regions { region_size, digests } => {
+                let mismatch = (0..digests.len().max(self.regions.len()))
+                    .find(|&i| digests.get(i) != self.regions.get(i));
+                match mismatch {
+                    None => Ok(()),
+                    Some(i) => Err(format!(
+                        "SHA-256 不一致: 第一个不同的区域从偏移 {} 开始 (区域 {}, 每个区域 {} 字节; 期望 {} 个区域, 实际 {} 个), 文件大小 {} 字节, 实际摘要 {}",
+                        i as u64 * region_size,
+                        i,
+                        region_size,
+                        digests.len(),
+                        self.regions.len(),
+                        self.size,
+                        self.sha256
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// 解码文本文件 (Windows 上常见带 BOM 的 UTF-16)
+pub fn decode_text(bytes: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from_bytes: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| from_bytes([pair[0], pair[1]])).collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+/// QGA 打开文件失败的原因是否为文件不存在
+///
+/// Linux guest agent 给出 errno 描述, Windows 给出系统错误 2 (文件) 或 3 (路径)。
+pub fn is_not_found(error: &ProtocolError) -> bool {
+    let message = error.to_string();
+    ["No such file or directory", "cannot find the file", "cannot find the path", "(error: 2)", "(error: 3)"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
+/// 打开并读取客户机文件, 文件不存在时返回 Ok(None)
+///
+/// 返回文件大小与读取结果; `read_all` 为 false 时读够 `max_bytes` 即停止。
+pub async fn read_guest_file(
+    qga: &QgaProtocol,
+    path: &str,
+    region_size: u64,
+    max_bytes: u64,
+    read_all: bool,
+) -> Result<Option<(u64, FileContents)>> {
+    let handle = match qga.file_open(path, "rb").await {
+        Ok(handle) => handle,
+        Err(e) if is_not_found(&e) => return Ok(None),
+        Err(e) => return Err(ExecutorError::ProtocolError(format!("打开客户机文件 {} 失败: {}", path, e))),
+    };
+
+    let result = read_handle(qga, handle, region_size, max_bytes, read_all).await;
+
+    // 读取出错时同样关闭句柄, 关闭失败不影响结果
+    if let Err(e) = qga.file_close(handle).await {
+        warn!("关闭客户机文件 {} 失败: {}", path, e);
+    }
+
+    result.map(Some).map_err(|e| ExecutorError::ProtocolError(format!("读取客户机文件 {} 失败: {}", path, e)))
+}
+
+/// 以覆盖方式写入客户机文件 (文件不存在时创建, 所在目录需已存在)
+pub async fn write_guest_file(qga: &QgaProtocol, path: &str, data: &[u8]) -> Result<()> {
+    let handle = qga
+        .file_open(path, "wb")
+        .await
+        .map_err(|e| ExecutorError::ProtocolError(format!("打开客户机文件 {} 失败: {}", path, e)))?;
+
+    let mut result = Ok(());
+    for chunk in data.chunks(READ_CHUNK_SIZE as usize) {
+        if let Err(e) = qga.file_write(handle, chunk).await {
+            result = Err(ExecutorError::ProtocolError(format!("写入客户机文件 {} 失败: {}", path, e)));
+            break;
+        }
+    }
+
+    // 关闭时客户机才把数据刷到文件, 写入成功后关闭失败同样视为失败
+    match qga.file_close(handle).await {
+        Err(e) if result.is_ok() => Err(ExecutorError::ProtocolError(format!("关闭客户机文件 {} 失败: {}", path, e))),
+        Err(e) => {
+            warn!("关闭客户机文件 {} 失败: {}", path, e);
+            result
+        }
+        Ok(()) => result,
+    }
+}
+
+async fn read_handle(
+    qga: &QgaProtocol,
+    handle: i64,
+    region_size: u64,
+    max_bytes: u64,
+    read_all: bool,
+) -> atp_protocol::Result<(u64, FileContents)> {
+    let size = qga.file_seek(handle, 0, GuestFileWhence::End).await?.position;
+    qga.file_seek(handle, 0, GuestFileWhence::Set).await?;
+    debug!("客户机文件大小: {} 字节", size);
+
+    let mut digest = FileDigest::new(region_size, max_bytes);
+    loop {
+        if !read_all && digest.head_full() {
+            break;
+        }
+        let chunk = qga.file_read(handle, READ_CHUNK_SIZE).await?;
+        digest.update(&chunk.decode()?);
+        if chunk.eof || chunk.count == 0 {
+            break;
+        }
+    }
+
+    Ok((size, digest.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sha256(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    fn contents(data: &[u8], region_size: u64, max_bytes: u64, chunk: usize) -> FileContents {
+        let mut digest = FileDigest::new(region_size, max_bytes);
+        for part in data.chunks(chunk) {
+            digest.update(part);
+        }
+        digest.finish()
+    }
+
+    #[test]
+    fn test_streamed_digest_matches_whole() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let file = contents(&data, 4096, 100, 777);
+
+        assert_eq!(file.size, 10_000);
+        assert_eq!(file.sha256, sha256(&data));
+        assert_eq!(
+            file.regions,
+            vec![sha256(&data[..4096]), sha256(&data[4096..8192]), sha256(&data[8192..])]
+        );
+        assert_eq!(file.head, &data[..100]);
+        assert!(file.check_sha256(&Sha256Expectation::Whole(sha256(&data))).is_ok());
+    }
+
+    #[test]
+    fn test_region_mismatch_offset() {
+        let good: Vec<u8> = vec![7; 3000];
+        let mut bad = good.clone();
+        bad[2500] = 0;
+
+        let expected = Sha256Expectation::parse(&contents(&good, 1024, 0, 500).manifest().to_uppercase()).unwrap();
+        assert_eq!(expected.region_size(), 1024);
+
+        let error = contents(&bad, 1024, 0, 500).check_sha256(&expected).unwrap_err();
+        assert!(error.contains("偏移 2048"), "{}", error);
+        assert!(error.contains("文件大小 3000 字节"), "{}", error);
+
+        // 文件变短: 第一个缺失的区域
+        let error = contents(&good[..1024], 1024, 0, 500).check_sha256(&expected).unwrap_err();
+        assert!(error.contains("偏移 1024"), "{}", error);
+
+        let error = contents(&bad, 1024, 0, 500)
+            .check_sha256(&Sha256Expectation::Whole(sha256(&good)))
+            .unwrap_err();
+        assert!(error.contains(&sha256(&bad)) && error.contains("3000"), "{}", error);
+    }
+
+    #[test]
+    fn test_parse_expectation() {
+        let digest = "a".repeat(64);
+        assert_eq!(Sha256Expectation::parse(&digest).unwrap(), Sha256Expectation::Whole(digest.clone()));
+        assert_eq!(
+            Sha256Expectation::parse(&format!("512:{},{}", digest, digest)).unwrap(),
+            Sha256Expectation::Regions { region_size: 512, digests: vec![digest.clone(), digest.clone()] }
+        );
+        assert!(Sha256Expectation::parse("abc").is_err());
+        assert!(Sha256Expectation::parse(&format!("0:{}", digest)).is_err());
+        assert!(Sha256Expectation::parse(&format!("1024:{},xyz", digest)).is_err());
+    }
+
+    #[test]
+    fn test_decode_text() {
+        assert_eq!(decode_text(b"version=2.3\n"), "version=2.3\n");
+        assert_eq!(decode_text(&[0xEF, 0xBB, 0xBF, b'o', b'k']), "ok");
+
+        let utf16le: Vec<u8> = [0xFF, 0xFE].into_iter().chain("版本 2.3".encode_utf16().flat_map(u16::to_le_bytes)).collect();
+        assert_eq!(decode_text(&utf16le), "版本 2.3");
+        let utf16be: Vec<u8> = [0xFE, 0xFF].into_iter().chain("2.3".encode_utf16().flat_map(u16::to_be_bytes)).collect();
+        assert_eq!(decode_text(&utf16be), "2.3");
+    }
+
+    #[test]
+    fn test_is_not_found() {
+        let linux = ProtocolError::CommandFailed(
+            "GenericError: failed to open file '/opt/agent' (mode: 'rb'): No such file or directory".to_string(),
+        );
+        let windows = ProtocolError::CommandFailed(
+            "GenericError: failed to open file 'C:\\agent.exe': The system cannot find the file specified.".to_string(),
+        );
+        let denied = ProtocolError::CommandFailed(
+            "GenericError: failed to open file '/root/x' (mode: 'rb'): Permission denied".to_string(),
+        );
+
+        assert!(is_not_found(&linux));
+        assert!(is_not_found(&windows));
+        assert!(!is_not_found(&denied));
+    }
+}
@@ -0,0 +1,521 @@
+//! HTML 报告渲染
+//!
+//! 生成单文件 HTML 报告, 其中的步骤耗时甘特图为纯 SVG,
+//! 悬停提示使用 SVG `<title>`, 步骤输出/错误/资源指标使用 `<details>` 折叠, 不依赖任何 JS。
+//! 多份报告可以渲染为按步骤描述对齐的通过/失败对比矩阵。
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::{ExecutionReport, StepMetrics, StepReport, StepStatus};
+
+/// 甘特图左侧步骤名称区域宽度
+const LABEL_WIDTH: u64 = 240;
+
+/// 甘特图时间轴区域宽度
+const CHART_WIDTH: u64 = 720;
+
+/// 每个步骤的行高
+const ROW_HEIGHT: u64 = 24;
+
+/// 横条高度
+const BAR_HEIGHT: u64 = 16;
+
+/// 顶部时间轴高度
+const AXIS_HEIGHT: u64 = 24;
+
+/// 时间轴刻度数量
+const AXIS_TICKS: u64 = 4;
+
+/// 步骤名称显示的最大字符数
+const LABEL_MAX_CHARS: usize = 28;
+
+/// 内联样式 (单文件报告不引用外部资源)
+const STYLE: &str = "body{font-family:sans-serif;margin:24px;color:#333}\
+table{border-collapse:collapse}td,th{border:1px solid #ddd;padding:4px 8px;text-align:left;vertical-align:top}\
+pre{margin:0;white-space:pre-wrap}summary{cursor:pointer}\
+tr.success td{background:#f1f8e9}tr.failed td{background:#ffebee}tr.skipped td{background:#f5f5f5}\
+td.success{color:#4caf50}td.failed{color:#f44336}td.skipped{color:#9e9e9e}td.missing{color:#bbb}";
+
+/// 步骤状态对应的颜色
+fn status_color(status: StepStatus) -> &'static str {
+    match status {
+        StepStatus::Success => "#4caf50",
+        StepStatus::Failed => "#f44336",
+        StepStatus::Skipped => "#9e9e9e",
+    }
+}
+
+/// 步骤状态对应的 CSS 类名
+fn status_class(status: StepStatus) -> &'static str {
+    match status {
+        StepStatus::Success => "success",
+        StepStatus::Failed => "failed",
+        StepStatus::Skipped => "skipped",
+    }
+}
+
+/// 转义 HTML/XML 特殊字符
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 截断过长的步骤名称
+fn truncate_label(text: &str) -> String {
+    if text.chars().count() > LABEL_MAX_CHARS {
+        let mut label: String = text.chars().take(LABEL_MAX_CHARS - 1).collect();
+        label.push('…');
+        label
+    } else {
+        text.to_string()
+    }
+}
+
+/// 格式化耗时
+fn format_ms(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    }
+}
+
+/// 渲染步骤耗时甘特图 (SVG)
+///
+/// X 轴为相对场景开始的时间, 每个步骤一行, 横条位置由 `started_at_offset_ms`
+/// 与 `duration_ms` 决定, 时间上重叠的步骤会在各自的行中并排显示。
+pub fn render_gantt_svg(steps: &[StepReport]) -> String {
+    let total_ms = steps
+        .iter()
+        .map(|step| step.started_at_offset_ms + step.duration_ms)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+
+    let width = LABEL_WIDTH + CHART_WIDTH;
+    let height = AXIS_HEIGHT + ROW_HEIGHT * steps.len() as u64;
+    let x_of = |ms: u64| LABEL_WIDTH + ms * CHART_WIDTH / total_ms;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="12">"#,
+        w = width,
+        h = height
+    );
+
+    // 时间轴刻度与网格线
+    for tick in 0..=AXIS_TICKS {
+        let ms = total_ms * tick / AXIS_TICKS;
+        let x = x_of(ms);
+        let anchor = match tick {
+            0 => "start",
+            t if t == AXIS_TICKS => "end",
+            _ => "middle",
+        };
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{x}" y1="{y1}" x2="{x}" y2="{h}" stroke="#e0e0e0"/><text x="{x}" y="{ty}" text-anchor="{anchor}" fill="#666">{label}</text>"##,
+            x = x,
+            y1 = AXIS_HEIGHT - 4,
+            h = height,
+            ty = AXIS_HEIGHT - 8,
+            anchor = anchor,
+            label = format_ms(ms)
+        );
+    }
+
+    // 步骤横条
+    for (row, step) in steps.iter().enumerate() {
+        let y = AXIS_HEIGHT + ROW_HEIGHT * row as u64;
+        let bar_x = x_of(step.started_at_offset_ms);
+        let bar_width = (step.duration_ms * CHART_WIDTH / total_ms).max(1);
+        let tooltip = format!("{} ({})", step.description, format_ms(step.duration_ms));
+
+        let _ = writeln!(
+            svg,
+            r##"<g><title>{tooltip}</title><text x="4" y="{ty}" fill="#333">{label}</text><rect x="{x}" y="{by}" width="{bw}" height="{bh}" rx="2" fill="{color}"/></g>"##,
+            tooltip = escape(&tooltip),
+            ty = y + ROW_HEIGHT / 2 + 4,
+            label = escape(&truncate_label(&step.description)),
+            x = bar_x,
+            by = y + (ROW_HEIGHT - BAR_HEIGHT) / 2,
+            bw = bar_width,
+            bh = BAR_HEIGHT,
+            color = status_color(step.status)
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// 渲染完整的 HTML 报告
+pub fn render_html(report: &ExecutionReport) -> String {
+    let title = escape(&report.scenario_name);
+    let (result_text, result_color) = if report.passed {
+        ("通过", status_color(StepStatus::Success))
+    } else {
+        ("失败", status_color(StepStatus::Failed))
+    };
+
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, r#"<html lang="zh-CN"><head><meta charset="utf-8"><title>{}</title>"#, title);
+    let _ = writeln!(html, "<style>{}</style></head><body>", STYLE);
+
+    let _ = writeln!(html, "<h1>{}</h1>", title);
+    if let Some(description) = &report.description {
+        let _ = writeln!(html, "<p>{}</p>", escape(description));
+    }
+
+    let _ = writeln!(
+        html,
+        r#"<p>结果: <strong style="color:{}">{}</strong> | 总耗时: {} | 步骤: {} | 成功: {} | 失败: {} | 跳过: {}</p>"#,
+        result_color,
+        result_text,
+        format_ms(report.duration_ms),
+        report.steps_executed,
+        report.passed_count,
+        report.failed_count,
+        report.skipped_count
+    );
+
+    if report.timed_out {
+        let _ = writeln!(html, "<p>场景超过最大执行时间, 剩余步骤已跳过</p>");
+    } else if report.cancelled {
+        let _ = writeln!(html, "<p>场景已被用户取消, 剩余步骤已跳过</p>");
+    }
+
+    if !report.orphan_resources.is_empty() {
+        let _ = writeln!(html, "<h2>未清理的新增资源</h2><ul>");
+        for orphan in &report.orphan_resources {
+            let _ = writeln!(
+                html,
+                "<li>{} {} ({})</li>",
+                orphan.kind,
+                escape(&orphan.id),
+                escape(orphan.name.as_deref().unwrap_or("-"))
+            );
+        }
+        let _ = writeln!(html, "</ul>");
+    }
+
+    if !report.steps.is_empty() {
+        let _ = writeln!(html, "<h2>步骤耗时</h2>");
+        html.push_str(&render_gantt_svg(&report.steps));
+    }
+
+    let _ = writeln!(html, "<h2>步骤详情</h2>");
+    let _ = writeln!(html, "<table><tr><th>#</th><th>步骤</th><th>状态</th><th>开始</th><th>耗时</th><th>输出 / 错误</th></tr>");
+    for step in &report.steps {
+        let _ = writeln!(
+            html,
+            r#"<tr class="{class}"><td>{}</td><td>{}</td><td class="{class}">{:?}</td><td>+{}</td><td>{}</td><td>{}</td></tr>"#,
+            step.step_index + 1,
+            escape(&step.description),
+            step.status,
+            format_ms(step.started_at_offset_ms),
+            format_ms(step.duration_ms),
+            render_step_detail(step),
+            class = status_class(step.status)
+        );
+    }
+    let _ = writeln!(html, "</table>");
+    let _ = writeln!(html, "</body></html>");
+
+    html
+}
+
+/// 渲染步骤的错误、输出与资源指标 (折叠显示, 错误默认展开)
+fn render_step_detail(step: &StepReport) -> String {
+    let mut detail = String::new();
+    if let Some(error) = &step.error {
+        let _ = write!(detail, "<details open><summary>错误</summary><pre>{}</pre></details>", escape(error));
+    }
+    if let Some(output) = step.output.as_deref().filter(|output| !output.is_empty()) {
+        let _ = write!(
+            detail,
+            "<details><summary>输出 ({} 行)</summary><pre>{}</pre></details>",
+            output.lines().count(),
+            escape(output)
+        );
+    }
+    if let Some(metrics) = &step.metrics {
+        let _ = write!(
+            detail,
+            "<details><summary>资源 ({} 次采样)</summary><pre>{}</pre></details>",
+            metrics.sample_count,
+            escape(&format_metrics(metrics))
+        );
+    }
+    detail
+}
+
+/// 步骤资源指标的文本摘要, 每项一行
+fn format_metrics(metrics: &StepMetrics) -> String {
+    let mut lines = Vec::new();
+    if let Some(cpu) = metrics.cpu_usage_percent {
+        lines.push(format!("CPU: min {:.1}% / avg {:.1}% / max {:.1}%", cpu.min, cpu.avg, cpu.max));
+    }
+    if let Some(memory) = metrics.memory_mb {
+        lines.push(format!("内存: min {:.0} MB / avg {:.0} MB / max {:.0} MB", memory.min, memory.avg, memory.max));
+    }
+    if let (Some(read), Some(write)) = (metrics.block_read_bytes, metrics.block_write_bytes) {
+        lines.push(format!("磁盘: 读 {} 字节 / 写 {} 字节", read, write));
+    }
+    lines.join("\n")
+}
+
+/// 对比矩阵中的一行: 步骤描述及其在每份报告中的结果
+struct ComparisonRow<'a> {
+    label: String,
+    cells: Vec<Option<&'a StepReport>>,
+}
+
+/// 按步骤描述对齐多份报告的步骤
+///
+/// 行按首次出现的顺序排列; 同一报告中重复的描述按出现次数区分 (第二次显示为 `描述 (#2)`)。
+fn comparison_rows(reports: &[(i64, ExecutionReport)]) -> Vec<ComparisonRow<'_>> {
+    let mut rows: Vec<ComparisonRow> = Vec::new();
+    let mut index_of: HashMap<(&str, usize), usize> = HashMap::new();
+
+    for (column, (_, report)) in reports.iter().enumerate() {
+        let mut occurrences: HashMap<&str, usize> = HashMap::new();
+        for step in &report.steps {
+            let occurrence = occurrences.entry(step.description.as_str()).or_insert(0);
+            *occurrence += 1;
+
+            let row = *index_of.entry((step.description.as_str(), *occurrence)).or_insert_with(|| {
+                let label = match *occurrence {
+                    1 => step.description.clone(),
+                    n => format!("{} (#{})", step.description, n),
+                };
+                rows.push(ComparisonRow {
+                    label,
+                    cells: vec![None; reports.len()],
+                });
+                rows.len() - 1
+            });
+            rows[row].cells[column] = Some(step);
+        }
+    }
+
+    rows
+}
+
+/// 渲染多份报告的步骤通过/失败对比矩阵 (HTML 表格)
+///
+/// 每列为一份报告, 每行为一个步骤描述, 报告中没有该步骤时显示 `-`。
+pub fn render_comparison_table(reports: &[(i64, ExecutionReport)]) -> String {
+    let mut table = String::new();
+
+    table.push_str("<table><tr><th>步骤</th>");
+    for (id, report) in reports {
+        let _ = write!(
+            table,
+            r#"<th>#{} {}<br><span class="{}">{}</span> {}</th>"#,
+            id,
+            escape(&report.scenario_name),
+            if report.passed { "success" } else { "failed" },
+            if report.passed { "通过" } else { "失败" },
+            format_ms(report.duration_ms)
+        );
+    }
+    table.push_str("</tr>\n");
+
+    for row in comparison_rows(reports) {
+        let _ = write!(table, "<tr><td>{}</td>", escape(&row.label));
+        for cell in &row.cells {
+            match cell {
+                Some(step) => {
+                    let title = step.error.as_deref().map(|error| format!(r#" title="{}""#, escape(error)));
+                    let _ = write!(
+                        table,
+                        r#"<td class="{}"{}>{:?} ({})</td>"#,
+                        status_class(step.status),
+                        title.unwrap_or_default(),
+                        step.status,
+                        format_ms(step.duration_ms)
+                    );
+                }
+                None => table.push_str(r#"<td class="missing">-</td>"#),
+            }
+        }
+        table.push_str("</tr>\n");
+    }
+
+    table.push_str("</table>\n");
+    table
+}
+
+/// 渲染多份报告的对比 HTML (单文件)
+pub fn render_comparison_html(reports: &[(i64, ExecutionReport)]) -> String {
+    let ids: Vec<String> = reports.iter().map(|(id, _)| format!("#{}", id)).collect();
+    let title = format!("报告对比: {}", ids.join(", "));
+
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, r#"<html lang="zh-CN"><head><meta charset="utf-8"><title>{}</title>"#, escape(&title));
+    let _ = writeln!(html, "<style>{}</style></head><body>", STYLE);
+    let _ = writeln!(html, "<h1>{}</h1>", escape(&title));
+
+    let passed = reports.iter().filter(|(_, report)| report.passed).count();
+    let _ = writeln!(
+        html,
+        "<p>报告: {} | 通过: {} | 失败: {}</p>",
+        reports.len(),
+        passed,
+        reports.len() - passed
+    );
+
+    html.push_str(&render_comparison_table(reports));
+    let _ = writeln!(html, "</body></html>");
+
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MetricSummary;
+
+    fn step(index: usize, description: &str, status: StepStatus, offset: u64, duration: u64) -> StepReport {
+        let mut step = match status {
+            StepStatus::Success => StepReport::success(index, description),
+            StepStatus::Failed => StepReport::failed(index, description, "boom"),
+            StepStatus::Skipped => StepReport::skipped(index, description, "tag"),
+        };
+        step.started_at_offset_ms = offset;
+        step.duration_ms = duration;
+        step
+    }
+
+    #[test]
+    fn test_render_gantt_svg_snapshot() {
+        let steps = vec![
+            step(0, "登录 <admin>", StepStatus::Success, 0, 1000),
+            step(1, "执行命令", StepStatus::Failed, 1000, 3000),
+            step(2, "清理", StepStatus::Skipped, 4000, 0),
+        ];
+
+        let expected = concat!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="960" height="96" viewBox="0 0 960 96" font-family="sans-serif" font-size="12">"##, "\n",
+            r##"<line x1="240" y1="20" x2="240" y2="96" stroke="#e0e0e0"/><text x="240" y="16" text-anchor="start" fill="#666">0ms</text>"##, "\n",
+            r##"<line x1="420" y1="20" x2="420" y2="96" stroke="#e0e0e0"/><text x="420" y="16" text-anchor="middle" fill="#666">1.0s</text>"##, "\n",
+            r##"<line x1="600" y1="20" x2="600" y2="96" stroke="#e0e0e0"/><text x="600" y="16" text-anchor="middle" fill="#666">2.0s</text>"##, "\n",
+            r##"<line x1="780" y1="20" x2="780" y2="96" stroke="#e0e0e0"/><text x="780" y="16" text-anchor="middle" fill="#666">3.0s</text>"##, "\n",
+            r##"<line x1="960" y1="20" x2="960" y2="96" stroke="#e0e0e0"/><text x="960" y="16" text-anchor="end" fill="#666">4.0s</text>"##, "\n",
+            r##"<g><title>登录 &lt;admin&gt; (1.0s)</title><text x="4" y="40" fill="#333">登录 &lt;admin&gt;</text><rect x="240" y="28" width="180" height="16" rx="2" fill="#4caf50"/></g>"##, "\n",
+            r##"<g><title>执行命令 (3.0s)</title><text x="4" y="64" fill="#333">执行命令</text><rect x="420" y="52" width="540" height="16" rx="2" fill="#f44336"/></g>"##, "\n",
+            r##"<g><title>清理 (0ms)</title><text x="4" y="88" fill="#333">清理</text><rect x="960" y="76" width="1" height="16" rx="2" fill="#9e9e9e"/></g>"##, "\n",
+            "</svg>\n",
+        );
+
+        assert_eq!(render_gantt_svg(&steps), expected);
+    }
+
+    #[test]
+    fn test_render_gantt_svg_empty() {
+        let svg = render_gantt_svg(&[]);
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" width="960" height="24""#));
+        assert!(!svg.contains("<rect"));
+    }
+
+    #[test]
+    fn test_render_html_escapes_and_embeds_svg() {
+        let mut report = ExecutionReport::new("场景 & 测试");
+        report.add_step(step(0, "a", StepStatus::Success, 0, 10));
+        let mut failed = step(1, "b", StepStatus::Failed, 10, 20);
+        failed.error = Some("<error>".to_string());
+        report.add_step(failed);
+
+        let html = render_html(&report);
+        assert!(html.contains("<title>场景 &amp; 测试</title>"));
+        assert!(html.contains("<svg "));
+        assert!(html.contains("&lt;error&gt;"));
+        assert!(html.contains(">失败<"));
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn test_render_html_step_details() {
+        let mut report = ExecutionReport::new("场景");
+        let mut ok = step(0, "a", StepStatus::Success, 0, 10);
+        ok.output = Some("line1\nline2".to_string());
+        report.add_step(ok);
+        report.add_step(step(1, "b", StepStatus::Failed, 10, 20));
+
+        let html = render_html(&report);
+        assert!(html.contains(
+            r#"<tr class="success"><td>1</td><td>a</td><td class="success">Success</td><td>+0ms</td><td>10ms</td><td><details><summary>输出 (2 行)</summary><pre>line1
+line2</pre></details></td></tr>"#
+        ));
+        assert!(html.contains(
+            r#"<tr class="failed"><td>2</td><td>b</td><td class="failed">Failed</td><td>+10ms</td><td>20ms</td><td><details open><summary>错误</summary><pre>boom</pre></details></td></tr>"#
+        ));
+    }
+
+    #[test]
+    fn test_render_html_step_metrics() {
+        let mut report = ExecutionReport::new("场景");
+        let mut measured = step(0, "a", StepStatus::Success, 0, 10);
+        measured.metrics = Some(StepMetrics {
+            sample_count: 3,
+            interval_ms: 500,
+            cpu_usage_percent: Some(MetricSummary { min: 5.0, avg: 12.5, max: 30.0 }),
+            memory_mb: None,
+            block_read_bytes: Some(4096),
+            block_write_bytes: Some(0),
+        });
+        report.add_step(measured);
+
+        let html = render_html(&report);
+        assert!(html.contains(
+            "<details><summary>资源 (3 次采样)</summary><pre>CPU: min 5.0% / avg 12.5% / max 30.0%\n磁盘: 读 4096 字节 / 写 0 字节</pre></details>"
+        ));
+    }
+
+    #[test]
+    fn test_render_comparison_table_snapshot() {
+        let mut first = ExecutionReport::new("登录");
+        first.add_step(step(0, "打开 <桌面>", StepStatus::Success, 0, 1000));
+        first.add_step(step(1, "输入", StepStatus::Success, 1000, 200));
+        first.add_step(step(2, "输入", StepStatus::Success, 1200, 300));
+        first.duration_ms = 1500;
+
+        let mut second = ExecutionReport::new("登录");
+        second.add_step(step(0, "打开 <桌面>", StepStatus::Success, 0, 1200));
+        second.add_step(step(1, "输入", StepStatus::Failed, 1200, 50));
+        second.add_step(step(2, "清理", StepStatus::Skipped, 1250, 0));
+        second.duration_ms = 1250;
+
+        let expected = concat!(
+            r#"<table><tr><th>步骤</th><th>#12 登录<br><span class="success">通过</span> 1.5s</th><th>#13 登录<br><span class="failed">失败</span> 1.2s</th></tr>"#, "\n",
+            r#"<tr><td>打开 &lt;桌面&gt;</td><td class="success">Success (1.0s)</td><td class="success">Success (1.2s)</td></tr>"#, "\n",
+            r#"<tr><td>输入</td><td class="success">Success (200ms)</td><td class="failed" title="boom">Failed (50ms)</td></tr>"#, "\n",
+            r#"<tr><td>输入 (#2)</td><td class="success">Success (300ms)</td><td class="missing">-</td></tr>"#, "\n",
+            r#"<tr><td>清理</td><td class="missing">-</td><td class="skipped">Skipped (0ms)</td></tr>"#, "\n",
+            "</table>\n",
+        );
+
+        let reports = vec![(12, first), (13, second)];
+        assert_eq!(render_comparison_table(&reports), expected);
+
+        let html = render_comparison_html(&reports);
+        assert!(html.contains("<title>报告对比: #12, #13</title>"));
+        assert!(html.contains("<p>报告: 2 | 通过: 1 | 失败: 1</p>"));
+        assert!(!html.contains("<script"));
+    }
+}
@@ -4,11 +4,56 @@
 
 pub mod scenario;
 pub mod runner;
+pub mod event_log;
+pub mod html_report;
+pub mod observer;
+pub mod uniquify;
+pub mod resources;
+pub mod environment;
+pub mod vm_cache;
+pub mod vdi_ops;
+pub mod vm_metrics;
+pub mod validation;
 pub mod test_config;
+pub mod scope;
+pub mod fan_out;
+pub mod migration;
+pub mod baseline;
+pub mod authoring;
+pub mod powershell;
+pub mod step_metrics;
+pub mod step_groups;
+pub mod benchmark;
+pub mod guest_file;
+pub mod report_diff;
+pub mod error_kind;
 
-pub use scenario::{Scenario, ScenarioStep, Action};
-pub use runner::{ScenarioRunner, ExecutionReport, StepReport, StepStatus};
-pub use test_config::{TestConfig, VdiConfig};
+pub use scenario::{Scenario, ScenarioStep, StepFilter, Action, SCENARIO_SCHEMA_VERSION};
+pub use runner::{ScenarioRunner, ExecutionReport, SessionState, StepReport, StepStatus, StepPhase};
+pub use event_log::{EventLogName, EventLevel, WindowsEvent};
+pub use uniquify::GuestPlatform;
+pub use resources::{ResourceTracker, ResourceKind, TrackedResource, CleanupStatus};
+pub use environment::{EnvironmentGuard, EnvironmentGuardMode, EnvironmentSnapshot, OrphanResource};
+pub use vm_cache::{CacheMode, VmCacheManager};
+pub use vdi_ops::{
+    AssignItemResult, AssignMapping, BatchItemResult, BatchOperation, ChunkPlacement, CloneItemResult, ClonePhase,
+    CloneSource, NameTemplate, RestoreItemResult, RestoreMethod, RestorePhase, RestoreStatus, Target, VdiBatchOps,
+    VmMatchResult,
+};
+pub use vm_metrics::{LibvirtVmMetrics, VdiVmMetrics};
+pub use validation::{ValidationIssue, IssueSeverity};
+pub use observer::{ExecutionObserver, JsonLinesObserver, TracingObserver};
+pub use test_config::{FromTestConfig, TestConfig, VdiConfig};
+pub use migration::{DowntimeStats, HostPresence, OwnershipCheck, PingSample, PlacementCheck};
+pub use baseline::{BaselineDiff, BaselineOps, BaselineSnapshot, FieldChange, VmBaseline, VmChange};
+pub use authoring::{PlannedStep, ScenarioTemplate};
+pub use powershell::{ErrorRecord, PowerShellError, PowerShellOutput, PowerShellScript};
+pub use step_metrics::{BlockStats, MetricSummary, StepMetrics};
+pub use benchmark::{BenchmarkComparison, InputLatencyOptions, InputLatencyProbe, InputLatencyRun, LatencyHistogram, LatencyPercentiles, QmpInputProbe, SampleOutcome};
+pub use error_kind::StepErrorKind;
+pub use report_diff::{DiffOptions, DiffTotals, ReportDiff, StepChange, StepDiff, StepSide};
+pub use scope::{ArtifactLayout, FanOutTarget, SharedVariables, VariableScope, prepare_targets};
+pub use fan_out::{FanOutReport, FanOutRunner, FanOutTargetReport};
 
 use thiserror::Error;
 
@@ -23,12 +68,31 @@ pub enum ExecutorError {
     #[error("超时")]
     Timeout,
 
+    #[error("已取消: {0}")]
+    Cancelled(String),
+
     #[error("协议错误: {0}")]
     ProtocolError(String),
 
     #[error("传输错误: {0}")]
     TransportError(String),
 
+    /// 协议层错误, 保留原始错误用于失败分类
+    #[error("协议错误: {}", with_context(.context, .source))]
+    Protocol {
+        context: String,
+        #[source]
+        source: atp_protocol::ProtocolError,
+    },
+
+    /// 传输层错误, 保留原始错误用于失败分类
+    #[error("传输错误: {}", with_context(.context, .source))]
+    Transport {
+        context: String,
+        #[source]
+        source: atp_transport::TransportError,
+    },
+
     #[error("配置错误: {0}")]
     ConfigError(String),
 
@@ -40,6 +104,34 @@ pub enum ExecutorError {
 
     #[error("数据库错误: {0}")]
     DatabaseError(String),
+
+    #[error(transparent)]
+    PowerShell(#[from] PowerShellError),
+}
+
+impl ExecutorError {
+    /// 包装协议层错误, `context` 为空时只显示原始错误
+    pub fn protocol(context: impl Into<String>, source: atp_protocol::ProtocolError) -> Self {
+        Self::Protocol { context: context.into(), source }
+    }
+
+    /// 包装传输层错误, `context` 为空时只显示原始错误
+    pub fn transport(context: impl Into<String>, source: atp_transport::TransportError) -> Self {
+        Self::Transport { context: context.into(), source }
+    }
+
+    /// 失败分类 (见 [`StepErrorKind::from_error`])
+    pub fn kind(&self) -> StepErrorKind {
+        StepErrorKind::from_error(self)
+    }
+}
+
+fn with_context(context: &str, source: &dyn std::fmt::Display) -> String {
+    if context.is_empty() {
+        source.to_string()
+    } else {
+        format!("{}: {}", context, source)
+    }
 }
 
 pub type Result<T> = std::result::Result<T, ExecutorError>;
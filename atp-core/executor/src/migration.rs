@@ -0,0 +1,400 @@
+//! 虚拟机迁移验证
+//!
+//! 迁移期间按固定间隔通过 QGA ping 客户机, 由采样序列统计中断时长;
+//! 迁移结束后列举各主机上的虚拟机, 校验虚拟机只在目标主机上运行。
+//!
+//! 中断时长按 "首次 ping 失败 → 恢复后首次 ping 成功" 计算,
+//! 到采样结束仍未恢复时计到最后一次采样。
+
+use std::fmt;
+
+use atp_transport::LibvirtDomainInfo;
+
+/// 一次 QGA ping 采样
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PingSample {
+    /// 相对迁移开始的偏移 (毫秒)
+    pub offset_ms: u64,
+
+    /// ping 是否成功
+    pub ok: bool,
+}
+
+/// 中断时长统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DowntimeStats {
+    /// 采样次数
+    pub samples: usize,
+
+    /// 失败次数
+    pub failures: usize,
+
+    /// 中断次数 (连续失败算一次)
+    pub outages: usize,
+
+    /// 最长一次中断 (毫秒)
+    pub max_downtime_ms: u64,
+
+    /// 中断总时长 (毫秒)
+    pub total_downtime_ms: u64,
+
+    /// 采样结束时是否仍处于中断中
+    pub unrecovered: bool,
+}
+
+impl DowntimeStats {
+    /// 由按时间排序的采样序列统计中断时长
+    pub fn from_samples(samples: &[PingSample]) -> Self {
+        let mut stats = Self {
+            samples: samples.len(),
+            ..Self::default()
+        };
+        let mut outage_start: Option<u64> = None;
+
+        for sample in samples {
+            match (sample.ok, outage_start) {
+                (false, None) => {
+                    stats.failures += 1;
+                    stats.outages += 1;
+                    outage_start = Some(sample.offset_ms);
+                }
+                (false, Some(_)) => stats.failures += 1,
+                (true, Some(start)) => {
+                    stats.record(sample.offset_ms.saturating_sub(start));
+                    outage_start = None;
+                }
+                (true, None) => {}
+            }
+        }
+
+        if let (Some(start), Some(last)) = (outage_start, samples.last()) {
+            stats.record(last.offset_ms.saturating_sub(start));
+            stats.unrecovered = true;
+        }
+
+        stats
+    }
+
+    fn record(&mut self, downtime_ms: u64) {
+        self.max_downtime_ms = self.max_downtime_ms.max(downtime_ms);
+        self.total_downtime_ms += downtime_ms;
+    }
+
+    /// 最长中断是否在允许范围内 (未恢复视为超限)
+    pub fn within(&self, max_downtime_ms: u64) -> bool {
+        !self.unrecovered && self.max_downtime_ms <= max_downtime_ms
+    }
+}
+
+impl fmt::Display for DowntimeStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "采样 {} 次, 失败 {} 次, 中断 {} 次, 最长中断 {}ms, 累计中断 {}ms",
+            self.samples, self.failures, self.outages, self.max_downtime_ms, self.total_downtime_ms
+        )?;
+        if self.unrecovered {
+            write!(f, " (采样结束时仍未恢复)")?;
+        }
+        Ok(())
+    }
+}
+
+/// 虚拟机在某台主机上的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostPresence {
+    /// 主机上不存在该虚拟机
+    Absent,
+
+    /// 已定义但未运行 (virDomainState)
+    Defined(u32),
+
+    /// 运行中
+    Running,
+
+    /// 列举主机上的虚拟机失败
+    Unknown,
+}
+
+impl HostPresence {
+    fn from_listing<E>(domain_name: &str, listing: &std::result::Result<Vec<LibvirtDomainInfo>, E>) -> Self {
+        match listing {
+            Ok(domains) => match domains.iter().find(|d| d.name == domain_name) {
+                Some(info) if info.is_running() => HostPresence::Running,
+                Some(info) => HostPresence::Defined(info.state),
+                None => HostPresence::Absent,
+            },
+            Err(_) => HostPresence::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for HostPresence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HostPresence::Absent => write!(f, "不存在"),
+            HostPresence::Defined(state) => write!(f, "已定义未运行 (state={})", state),
+            HostPresence::Running => write!(f, "运行中"),
+            HostPresence::Unknown => write!(f, "无法列举"),
+        }
+    }
+}
+
+/// 迁移后的归属校验结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnershipCheck {
+    pub source_host: String,
+    pub target_host: String,
+
+    /// 源主机上的状态
+    pub source: HostPresence,
+
+    /// 目标主机上的状态
+    pub target: HostPresence,
+
+    /// 其他主机中仍在运行该虚拟机的主机
+    pub running_elsewhere: Vec<String>,
+}
+
+impl OwnershipCheck {
+    /// 由各主机的虚拟机列表 (`TransportManager::list_all_domains` 的结果) 校验归属
+    pub fn evaluate<E>(
+        domain_name: &str,
+        source_host: &str,
+        target_host: &str,
+        listings: &[(String, std::result::Result<Vec<LibvirtDomainInfo>, E>)],
+    ) -> Self {
+        let presence = |host: &str| {
+            listings
+                .iter()
+                .find(|(host_id, _)| host_id == host)
+                .map(|(_, listing)| HostPresence::from_listing(domain_name, listing))
+                .unwrap_or(HostPresence::Unknown)
+        };
+
+        let running_elsewhere = listings
+            .iter()
+            .filter(|(host_id, _)| host_id != source_host && host_id != target_host)
+            .filter(|(_, listing)| HostPresence::from_listing(domain_name, listing) == HostPresence::Running)
+            .map(|(host_id, _)| host_id.clone())
+            .collect();
+
+        Self {
+            source_host: source_host.to_string(),
+            target_host: target_host.to_string(),
+            source: presence(source_host),
+            target: presence(target_host),
+            running_elsewhere,
+        }
+    }
+
+    /// 不满足 "只在目标主机上运行" 的原因, 满足时为空
+    ///
+    /// 源主机上残留的未运行定义 (迁移未指定 undefine source) 不视为失败。
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        match self.target {
+            HostPresence::Running => {}
+            other => problems.push(format!("目标主机 {} 上虚拟机{}", self.target_host, other)),
+        }
+        match self.source {
+            HostPresence::Running | HostPresence::Unknown => {
+                problems.push(format!("源主机 {} 上虚拟机{}", self.source_host, self.source));
+            }
+            HostPresence::Absent | HostPresence::Defined(_) => {}
+        }
+        if !self.running_elsewhere.is_empty() {
+            problems.push(format!("虚拟机同时运行于其他主机: {}", self.running_elsewhere.join(", ")));
+        }
+
+        problems
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.problems().is_empty()
+    }
+}
+
+impl fmt::Display for OwnershipCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "源主机 {}: {}, 目标主机 {}: {}",
+            self.source_host, self.source, self.target_host, self.target
+        )
+    }
+}
+
+/// 虚拟机是否运行在指定主机上的校验结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacementCheck {
+    pub host: String,
+
+    /// 指定主机上的状态
+    pub presence: HostPresence,
+
+    /// 其他主机中运行该虚拟机的主机
+    pub running_elsewhere: Vec<String>,
+}
+
+impl PlacementCheck {
+    /// 由各主机的虚拟机列表校验虚拟机只运行在 `host` 上
+    pub fn evaluate<E>(
+        domain_name: &str,
+        host: &str,
+        listings: &[(String, std::result::Result<Vec<LibvirtDomainInfo>, E>)],
+    ) -> Self {
+        let presence = listings
+            .iter()
+            .find(|(host_id, _)| host_id == host)
+            .map(|(_, listing)| HostPresence::from_listing(domain_name, listing))
+            .unwrap_or(HostPresence::Unknown);
+
+        let running_elsewhere = listings
+            .iter()
+            .filter(|(host_id, _)| host_id != host)
+            .filter(|(_, listing)| HostPresence::from_listing(domain_name, listing) == HostPresence::Running)
+            .map(|(host_id, _)| host_id.clone())
+            .collect();
+
+        Self {
+            host: host.to_string(),
+            presence,
+            running_elsewhere,
+        }
+    }
+
+    /// 不满足 "只在该主机上运行" 的原因, 满足时为空
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.presence != HostPresence::Running {
+            problems.push(format!("主机 {} 上虚拟机{}", self.host, self.presence));
+        }
+        if !self.running_elsewhere.is_empty() {
+            problems.push(format!("虚拟机同时运行于其他主机: {}", self.running_elsewhere.join(", ")));
+        }
+        problems
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.problems().is_empty()
+    }
+}
+
+impl fmt::Display for PlacementCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "主机 {}: {}", self.host, self.presence)?;
+        if !self.running_elsewhere.is_empty() {
+            write!(f, ", 同时运行于: {}", self.running_elsewhere.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(pattern: &str, interval_ms: u64) -> Vec<PingSample> {
+        pattern
+            .chars()
+            .enumerate()
+            .map(|(i, c)| PingSample { offset_ms: i as u64 * interval_ms, ok: c == '+' })
+            .collect()
+    }
+
+    fn domain(name: &str, state: u32) -> LibvirtDomainInfo {
+        LibvirtDomainInfo {
+            name: name.to_string(),
+            uuid: format!("uuid-{}", name),
+            state,
+            vcpus: 2,
+            memory_kb: 4 * 1024 * 1024,
+        }
+    }
+
+    #[test]
+    fn test_downtime_from_samples() {
+        // 两次中断: 200ms 与 600ms
+        let stats = DowntimeStats::from_samples(&samples("++-+++---++", 200));
+        assert_eq!((stats.samples, stats.failures, stats.outages), (11, 4, 2));
+        assert_eq!((stats.max_downtime_ms, stats.total_downtime_ms), (600, 800));
+        assert!(!stats.unrecovered);
+        assert!(stats.within(600));
+        assert!(!stats.within(599));
+
+        // 没有中断
+        let stats = DowntimeStats::from_samples(&samples("+++++", 200));
+        assert_eq!(stats, DowntimeStats { samples: 5, ..DowntimeStats::default() });
+        assert!(stats.within(0));
+
+        // 采样结束时仍未恢复: 计到最后一次采样, 且视为超限
+        let stats = DowntimeStats::from_samples(&samples("++---", 100));
+        assert_eq!((stats.outages, stats.max_downtime_ms), (1, 200));
+        assert!(stats.unrecovered);
+        assert!(!stats.within(10_000));
+    }
+
+    #[test]
+    fn test_ownership_after_migration() {
+        let running = virt::sys::VIR_DOMAIN_RUNNING;
+        let listings: Vec<(String, std::result::Result<Vec<LibvirtDomainInfo>, String>)> = vec![
+            ("host-a".to_string(), Ok(vec![domain("other", running)])),
+            ("host-b".to_string(), Ok(vec![domain("win10", running)])),
+            ("host-c".to_string(), Err("连接失败".to_string())),
+        ];
+
+        // 迁移成功: 源主机不再有该虚拟机, 目标主机运行中; 其他主机列举失败不影响结论
+        let check = OwnershipCheck::evaluate("win10", "host-a", "host-b", &listings);
+        assert_eq!((check.source, check.target), (HostPresence::Absent, HostPresence::Running));
+        assert!(check.is_ok(), "{:?}", check.problems());
+
+        // 源主机残留未运行的定义不视为失败
+        let mut leftover = listings.clone();
+        leftover[0].1 = Ok(vec![domain("win10", 5)]);
+        let check = OwnershipCheck::evaluate("win10", "host-a", "host-b", &leftover);
+        assert_eq!(check.source, HostPresence::Defined(5));
+        assert!(check.is_ok());
+
+        // 迁移未生效: 仍在源主机运行, 目标主机不存在
+        let check = OwnershipCheck::evaluate("win10", "host-b", "host-a", &listings);
+        assert_eq!(check.problems().len(), 2);
+
+        // 源主机无法列举时无法确认虚拟机已离开
+        let check = OwnershipCheck::evaluate("win10", "host-c", "host-b", &listings);
+        assert_eq!(check.source, HostPresence::Unknown);
+        assert!(!check.is_ok());
+
+        // 同名虚拟机同时运行在第三台主机上
+        let mut split = listings.clone();
+        split[2].1 = Ok(vec![domain("win10", running)]);
+        let check = OwnershipCheck::evaluate("win10", "host-a", "host-b", &split);
+        assert_eq!(check.running_elsewhere, vec!["host-c".to_string()]);
+        assert!(!check.is_ok());
+    }
+
+    #[test]
+    fn test_placement_on_host() {
+        let running = virt::sys::VIR_DOMAIN_RUNNING;
+        let listings: Vec<(String, std::result::Result<Vec<LibvirtDomainInfo>, String>)> = vec![
+            ("host-a".to_string(), Ok(vec![domain("win10", 5)])),
+            ("host-b".to_string(), Ok(vec![domain("win10", running)])),
+            ("host-c".to_string(), Err("连接失败".to_string())),
+        ];
+
+        let check = PlacementCheck::evaluate("win10", "host-b", &listings);
+        assert!(check.is_ok(), "{:?}", check.problems());
+        assert_eq!(check.to_string(), "主机 host-b: 运行中");
+
+        // 只有未运行的定义
+        let check = PlacementCheck::evaluate("win10", "host-a", &listings);
+        assert_eq!(check.presence, HostPresence::Defined(5));
+        assert_eq!(check.running_elsewhere, vec!["host-b".to_string()]);
+        assert_eq!(check.problems().len(), 2);
+
+        // 主机无法列举或未注册
+        assert_eq!(PlacementCheck::evaluate("win10", "host-c", &listings).presence, HostPresence::Unknown);
+        assert_eq!(PlacementCheck::evaluate("win10", "host-x", &listings).presence, HostPresence::Unknown);
+    }
+}
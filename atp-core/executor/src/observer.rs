@@ -0,0 +1,187 @@
+//! 执行进度观察者
+//!
+//! 场景执行过程中向外部 (如 CI 包装脚本) 推送结构化进度事件。
+//! 观察者返回的错误或发生的 panic 只记录日志, 不影响场景执行。
+
+use std::io::Write;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{ExecutorError, Result, StepPhase, StepStatus};
+
+/// 场景开始事件
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioStarted {
+    pub scenario_name: String,
+    /// 前置、测试、清理步骤总数
+    pub total_steps: usize,
+}
+
+/// 步骤开始事件
+#[derive(Debug, Clone, Serialize)]
+pub struct StepStarted {
+    pub scenario_name: String,
+    pub step_index: usize,
+    /// 动作类型 (与场景文件中的 type 一致)
+    pub action: String,
+    pub phase: StepPhase,
+    pub description: String,
+}
+
+/// 步骤结束事件
+#[derive(Debug, Clone, Serialize)]
+pub struct StepFinished {
+    pub scenario_name: String,
+    pub step_index: usize,
+    pub action: String,
+    pub phase: StepPhase,
+    pub description: String,
+    pub status: StepStatus,
+    pub duration_ms: u64,
+    pub error: Option<String>,
+}
+
+/// 场景结束事件
+#[derive(Debug, Clone, Serialize)]
+pub struct ScenarioFinished {
+    pub scenario_name: String,
+    pub passed: bool,
+    pub duration_ms: u64,
+    pub passed_count: usize,
+    pub failed_count: usize,
+    pub skipped_count: usize,
+}
+
+/// 执行观察者
+pub trait ExecutionObserver: Send + Sync {
+    fn on_scenario_start(&self, event: &ScenarioStarted) -> Result<()>;
+
+    fn on_step_start(&self, event: &StepStarted) -> Result<()>;
+
+    fn on_step_end(&self, event: &StepFinished) -> Result<()>;
+
+    fn on_scenario_end(&self, event: &ScenarioFinished) -> Result<()>;
+}
+
+/// 依次通知所有观察者, 忽略其错误与 panic
+pub(crate) fn notify<F>(observers: &[Arc<dyn ExecutionObserver>], f: F)
+where
+    F: Fn(&dyn ExecutionObserver) -> Result<()>,
+{
+    for observer in observers {
+        match catch_unwind(AssertUnwindSafe(|| f(observer.as_ref()))) {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("执行观察者出错, 已忽略: {}", e),
+            Err(_) => warn!("执行观察者发生 panic, 已忽略"),
+        }
+    }
+}
+
+/// 基于 tracing 日志的观察者
+#[derive(Debug, Default)]
+pub struct TracingObserver;
+
+impl ExecutionObserver for TracingObserver {
+    fn on_scenario_start(&self, event: &ScenarioStarted) -> Result<()> {
+        info!(scenario = %event.scenario_name, total_steps = event.total_steps, "场景开始");
+        Ok(())
+    }
+
+    fn on_step_start(&self, event: &StepStarted) -> Result<()> {
+        info!(
+            scenario = %event.scenario_name,
+            step = event.step_index,
+            action = %event.action,
+            "{}开始: {}",
+            event.phase.label(),
+            event.description
+        );
+        Ok(())
+    }
+
+    fn on_step_end(&self, event: &StepFinished) -> Result<()> {
+        info!(
+            scenario = %event.scenario_name,
+            step = event.step_index,
+            action = %event.action,
+            status = ?event.status,
+            duration_ms = event.duration_ms,
+            "{}结束: {}",
+            event.phase.label(),
+            event.description
+        );
+        Ok(())
+    }
+
+    fn on_scenario_end(&self, event: &ScenarioFinished) -> Result<()> {
+        info!(
+            scenario = %event.scenario_name,
+            passed = event.passed,
+            duration_ms = event.duration_ms,
+            "场景结束: {} 成功, {} 失败, {} 跳过",
+            event.passed_count,
+            event.failed_count,
+            event.skipped_count
+        );
+        Ok(())
+    }
+}
+
+/// JSON Lines 事件 (每行一个 JSON 对象)
+#[derive(Serialize)]
+struct JsonLine<'a, T: Serialize> {
+    event: &'a str,
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    data: &'a T,
+}
+
+/// 把事件以 JSON Lines 格式写入 writer 的观察者
+pub struct JsonLinesObserver {
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl JsonLinesObserver {
+    pub fn new<W: Write + Send + 'static>(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(Box::new(writer)),
+        }
+    }
+
+    fn write_event<T: Serialize>(&self, event: &str, data: &T) -> Result<()> {
+        let line = serde_json::to_string(&JsonLine {
+            event,
+            timestamp: Utc::now(),
+            data,
+        })
+        .map_err(|e| ExecutorError::SerdeError(e.to_string()))?;
+
+        // 某次写入 panic 后锁被污染, 仍继续使用
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writeln!(writer, "{}", line)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl ExecutionObserver for JsonLinesObserver {
+    fn on_scenario_start(&self, event: &ScenarioStarted) -> Result<()> {
+        self.write_event("scenario_start", event)
+    }
+
+    fn on_step_start(&self, event: &StepStarted) -> Result<()> {
+        self.write_event("step_start", event)
+    }
+
+    fn on_step_end(&self, event: &StepFinished) -> Result<()> {
+        self.write_event("step_end", event)
+    }
+
+    fn on_scenario_end(&self, event: &ScenarioFinished) -> Result<()> {
+        self.write_event("scenario_end", event)
+    }
+}
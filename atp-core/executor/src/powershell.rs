@@ -0,0 +1,330 @@
+//! PowerShell 脚本执行
+//!
+//! 通过 QGA 在 Windows 客户机中执行 PowerShell 脚本。脚本以 UTF-8 经标准输入传入,
+//! 由 `-EncodedCommand` 中的引导脚本读取后执行, 不受命令行长度与引号转义的限制。
+//! 脚本中未处理的异常以 `ATP-ERROR:` 开头的一行 JSON 写到标准错误, 在宿主侧解析为
+//! [`PowerShellError::Exception`]; 脚本中的 `exit N` 作为进程退出码返回。
+
+use std::fmt;
+use std::time::Duration;
+
+use atp_protocol::qga::{GuestExecCommand, GuestExecStatus};
+use base64::{engine::general_purpose, Engine as _};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// 标准错误中异常记录的前缀
+const ERROR_MARKER: &str = "ATP-ERROR: ";
+
+/// 从标准输入读取 base64 编码的脚本并执行
+const BOOTSTRAP: &str = "$s = [Text.Encoding]::UTF8.GetString([Convert]::FromBase64String([Console]::In.ReadToEnd())); \
+                         & ([scriptblock]::Create($s))";
+
+/// ConvertTo-Json 的深度 (默认 2 层会截断嵌套对象)
+const JSON_DEPTH: u32 = 10;
+
+/// 待执行的 PowerShell 脚本
+#[derive(Debug, Clone)]
+pub struct PowerShellScript {
+    source: String,
+    args: Vec<String>,
+    as_json: bool,
+}
+
+impl PowerShellScript {
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            args: Vec::new(),
+            as_json: false,
+        }
+    }
+
+    /// 脚本参数 (脚本中通过 `$args` 或 `param()` 读取)
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// 以 `ConvertTo-Json` 输出脚本结果, 执行后解析为 JSON
+    pub fn with_json(mut self, as_json: bool) -> Self {
+        self.as_json = as_json;
+        self
+    }
+
+    pub fn as_json(&self) -> bool {
+        self.as_json
+    }
+
+    /// 包装后的完整脚本: 传入参数、按需转换为 JSON, 并把未处理的异常写成可解析的记录
+    pub fn render(&self) -> String {
+        let args = self.args.iter().map(|arg| quote(arg)).collect::<Vec<_>>().join(", ");
+        let invoke = if self.as_json {
+            format!(
+                "    $result = & {{\n{}\n    }} @scriptArgs\n    ConvertTo-Json -InputObject $result -Depth {} -Compress",
+                self.source, JSON_DEPTH
+            )
+        } else {
+            format!("    & {{\n{}\n    }} @scriptArgs", self.source)
+        };
+
+        format!(
+            "$ErrorActionPreference = 'Stop'\n\
+             $ProgressPreference = 'SilentlyContinue'\n\
+             [Console]::OutputEncoding = [Text.Encoding]::UTF8\n\
+             $scriptArgs = @({args})\n\
+             try {{\n{invoke}\n}} catch {{\n\
+             \x20   $record = [ordered]@{{\n\
+             \x20       exception = $_.Exception.GetType().FullName\n\
+             \x20       message = $_.Exception.Message\n\
+             \x20       category = [string]$_.CategoryInfo.Category\n\
+             \x20       error_id = $_.FullyQualifiedErrorId\n\
+             \x20       line = $_.InvocationInfo.ScriptLineNumber\n\
+             \x20   }}\n\
+             \x20   [Console]::Error.WriteLine('{marker}' + (ConvertTo-Json -InputObject $record -Compress))\n\
+             \x20   exit 1\n\
+             }}\n",
+            marker = ERROR_MARKER,
+        )
+    }
+
+    /// guest-exec 命令: 引导脚本放在命令行, 脚本本身经标准输入传入
+    pub fn command(&self) -> GuestExecCommand {
+        let script = general_purpose::STANDARD.encode(self.render());
+        GuestExecCommand::simple(
+            "powershell.exe",
+            vec![
+                "-NoProfile".to_string(),
+                "-NonInteractive".to_string(),
+                "-ExecutionPolicy".to_string(),
+                "Bypass".to_string(),
+                "-EncodedCommand".to_string(),
+                encode_command(BOOTSTRAP),
+            ],
+        )
+        .with_input(script.as_bytes())
+    }
+}
+
+/// PowerShell 单引号字符串 (单引号写两次)
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+/// `-EncodedCommand` 的参数: UTF-16LE 的 base64
+fn encode_command(command: &str) -> String {
+    let bytes: Vec<u8> = command.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+    general_purpose::STANDARD.encode(bytes)
+}
+
+/// 脚本中未处理的异常
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    /// 异常类型 (如 `System.Management.Automation.ItemNotFoundException`)
+    pub exception: String,
+    pub message: String,
+    pub category: Option<String>,
+    /// FullyQualifiedErrorId
+    pub error_id: Option<String>,
+    /// 出错的行号 (相对脚本文件)
+    pub line: Option<u32>,
+}
+
+impl fmt::Display for ErrorRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.exception, self.message)?;
+        if let Some(line) = self.line.filter(|&line| line > 0) {
+            write!(f, " (第 {} 行)", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// PowerShell 执行错误
+#[derive(Debug, Error)]
+pub enum PowerShellError {
+    #[error("PowerShell 脚本抛出异常 {0}")]
+    Exception(ErrorRecord),
+
+    #[error("PowerShell 脚本退出码 {code}{}", stderr_suffix(.stderr))]
+    ExitCode { code: i32, stderr: String },
+
+    #[error("PowerShell 脚本执行超时 ({} 秒)", .0.as_secs())]
+    Timeout(Duration),
+
+    #[error("PowerShell 输出不是有效的 JSON: {0}")]
+    InvalidJson(String),
+
+    #[error("QGA 执行 PowerShell 失败: {0}")]
+    Protocol(String),
+}
+
+fn stderr_suffix(stderr: &str) -> String {
+    let stderr = stderr.trim();
+    if stderr.is_empty() {
+        String::new()
+    } else {
+        format!(": {}", stderr)
+    }
+}
+
+impl PowerShellError {
+    /// 对应的进程退出码: 脚本退出码原样返回, 异常为 1, 超时为 124 (与 `timeout` 命令一致)
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Exception(_) => 1,
+            Self::ExitCode { code, .. } => *code,
+            Self::Timeout(_) => 124,
+            Self::InvalidJson(_) | Self::Protocol(_) => 2,
+        }
+    }
+}
+
+/// PowerShell 脚本的执行结果
+#[derive(Debug, Clone, Serialize)]
+pub struct PowerShellOutput {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    /// `--as-json` 时解析后的结果 (脚本没有输出时为 null)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<serde_json::Value>,
+    pub duration_ms: u64,
+}
+
+impl PowerShellOutput {
+    /// 解析 guest-exec 的结果: 异常记录与非零退出码转换为错误
+    pub fn from_status(
+        status: &GuestExecStatus,
+        as_json: bool,
+        duration: Duration,
+    ) -> std::result::Result<Self, PowerShellError> {
+        let stdout = status.decode_stdout().unwrap_or_default();
+        let stderr = status.decode_stderr().unwrap_or_default();
+
+        let mut other_lines = Vec::new();
+        for line in stderr.lines() {
+            match line.trim_start().strip_prefix(ERROR_MARKER) {
+                Some(record) => {
+                    let record = serde_json::from_str(record).unwrap_or_else(|_| ErrorRecord {
+                        exception: "Unknown".to_string(),
+                        message: record.to_string(),
+                        category: None,
+                        error_id: None,
+                        line: None,
+                    });
+                    return Err(PowerShellError::Exception(record));
+                }
+                None => other_lines.push(line),
+            }
+        }
+        let stderr = other_lines.join("\n");
+
+        let exit_code = status.exit_code.unwrap_or(0);
+        if exit_code != 0 {
+            return Err(PowerShellError::ExitCode { code: exit_code, stderr });
+        }
+
+        let json = if as_json {
+            let text = stdout.trim().trim_start_matches('\u{feff}');
+            Some(if text.is_empty() {
+                serde_json::Value::Null
+            } else {
+                serde_json::from_str(text).map_err(|e| PowerShellError::InvalidJson(e.to_string()))?
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            exit_code,
+            stdout,
+            stderr,
+            json,
+            duration_ms: duration.as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(exit_code: i32, stdout: &str, stderr: &str) -> GuestExecStatus {
+        serde_json::from_value(serde_json::json!({
+            "exited": true,
+            "exitcode": exit_code,
+            "out-data": general_purpose::STANDARD.encode(stdout),
+            "err-data": general_purpose::STANDARD.encode(stderr),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_script() {
+        let script = PowerShellScript::new("param($Name)\nWrite-Output \"hi $Name\"")
+            .with_args(vec!["it's".to_string(), "b".to_string()]);
+        let rendered = script.render();
+
+        assert!(rendered.contains("$scriptArgs = @('it''s', 'b')"), "{}", rendered);
+        assert!(rendered.contains("param($Name)\nWrite-Output \"hi $Name\"\n    } @scriptArgs"), "{}", rendered);
+        assert!(!rendered.contains("ConvertTo-Json -InputObject $result"));
+        assert!(rendered.contains("[Console]::Error.WriteLine('ATP-ERROR: '"));
+
+        let json = script.with_json(true).render();
+        assert!(json.contains("$result = & {"), "{}", json);
+        assert!(json.contains("ConvertTo-Json -InputObject $result -Depth 10 -Compress"), "{}", json);
+    }
+
+    #[test]
+    fn test_command() {
+        let command = PowerShellScript::new("Get-Date").command();
+        let args = command.arg.unwrap();
+        assert_eq!(command.path, "powershell.exe");
+        assert_eq!(args[args.len() - 2], "-EncodedCommand");
+
+        // 引导脚本为 UTF-16LE, 脚本本身以 base64 经标准输入传入
+        let bootstrap = general_purpose::STANDARD.decode(&args[args.len() - 1]).unwrap();
+        let units: Vec<u16> = bootstrap.chunks(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        assert_eq!(String::from_utf16(&units).unwrap(), BOOTSTRAP);
+
+        let stdin = general_purpose::STANDARD.decode(command.input_data.unwrap()).unwrap();
+        let script = general_purpose::STANDARD.decode(stdin).unwrap();
+        assert!(String::from_utf8(script).unwrap().contains("Get-Date"));
+    }
+
+    #[test]
+    fn test_output_from_status() {
+        let output = PowerShellOutput::from_status(&status(0, "ok\r\n", "warn\n"), false, Duration::from_millis(20)).unwrap();
+        assert_eq!((output.stdout.as_str(), output.stderr.as_str(), output.json), ("ok\r\n", "warn", None));
+
+        let output = PowerShellOutput::from_status(&status(0, "\u{feff}{\"a\":1}\r\n", ""), true, Duration::ZERO).unwrap();
+        assert_eq!(output.json, Some(serde_json::json!({"a": 1})));
+        let output = PowerShellOutput::from_status(&status(0, "", ""), true, Duration::ZERO).unwrap();
+        assert_eq!(output.json, Some(serde_json::Value::Null));
+
+        let err = PowerShellOutput::from_status(&status(0, "not json", ""), true, Duration::ZERO).unwrap_err();
+        assert!(matches!(err, PowerShellError::InvalidJson(_)));
+        assert_eq!(err.exit_code(), 2);
+
+        let err = PowerShellOutput::from_status(&status(3, "", "failed\n"), false, Duration::ZERO).unwrap_err();
+        assert_eq!(err.to_string(), "PowerShell 脚本退出码 3: failed");
+        assert_eq!(err.exit_code(), 3);
+
+        let record = r#"ATP-ERROR: {"exception":"System.Management.Automation.ItemNotFoundException","message":"Cannot find path","category":"ObjectNotFound","error_id":"PathNotFound","line":4}"#;
+        let err = PowerShellOutput::from_status(&status(1, "", &format!("noise\n{}\n", record)), false, Duration::ZERO).unwrap_err();
+        match &err {
+            PowerShellError::Exception(record) => {
+                assert_eq!(record.category.as_deref(), Some("ObjectNotFound"));
+                assert_eq!(record.line, Some(4));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(
+            err.to_string(),
+            "PowerShell 脚本抛出异常 System.Management.Automation.ItemNotFoundException: Cannot find path (第 4 行)"
+        );
+        assert_eq!(err.exit_code(), 1);
+        assert_eq!(PowerShellError::Timeout(Duration::from_secs(5)).exit_code(), 124);
+    }
+}
@@ -0,0 +1,498 @@
+//! 两次执行报告的对比
+//!
+//! 用于发现回归: 以一次报告为基准, 逐步骤对比另一次报告的状态与耗时。
+//!
+//! 步骤先按 (阶段, 描述) 对应, 描述重复时按出现顺序依次配对;
+//! 描述对不上的步骤再按 (阶段, 步骤索引) 对应 (步骤改名但位置不变),
+//! 剩下的记为新增或移除。
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::runner::{ExecutionReport, StepPhase, StepReport, StepStatus};
+
+/// 默认耗时退化阈值 (百分比)
+pub const DEFAULT_DURATION_THRESHOLD_PCT: f64 = 30.0;
+
+/// 默认耗时退化的最小增量 (毫秒), 避免极短步骤的抖动被当作退化
+pub const DEFAULT_MIN_DURATION_DELTA_MS: u64 = 100;
+
+/// 对比选项
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffOptions {
+    /// 耗时增加超过该百分比视为退化
+    pub duration_threshold_pct: f64,
+
+    /// 耗时增加不超过该值 (毫秒) 时不视为退化
+    pub min_duration_delta_ms: u64,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self {
+            duration_threshold_pct: DEFAULT_DURATION_THRESHOLD_PCT,
+            min_duration_delta_ms: DEFAULT_MIN_DURATION_DELTA_MS,
+        }
+    }
+}
+
+impl DiffOptions {
+    pub fn with_threshold(mut self, pct: f64) -> Self {
+        self.duration_threshold_pct = pct;
+        self
+    }
+
+    pub fn with_min_delta(mut self, ms: u64) -> Self {
+        self.min_duration_delta_ms = ms;
+        self
+    }
+}
+
+/// 步骤的变化类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepChange {
+    /// 基准中未失败, 本次失败
+    NewlyFailed,
+
+    /// 基准中失败, 本次成功
+    NewlyPassed,
+
+    /// 两次都失败
+    StillFailing,
+
+    /// 两次都成功, 但耗时增加超过阈值
+    DurationRegressed,
+
+    /// 没有变化
+    Unchanged,
+
+    /// 本次新增的步骤
+    Added,
+
+    /// 本次移除的步骤
+    Removed,
+}
+
+impl StepChange {
+    pub fn label(&self) -> &'static str {
+        match self {
+            StepChange::NewlyFailed => "新失败",
+            StepChange::NewlyPassed => "新通过",
+            StepChange::StillFailing => "持续失败",
+            StepChange::DurationRegressed => "耗时退化",
+            StepChange::Unchanged => "无变化",
+            StepChange::Added => "新增",
+            StepChange::Removed => "移除",
+        }
+    }
+}
+
+/// 步骤在某一次报告中的情况
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepSide {
+    pub step_index: usize,
+    pub status: StepStatus,
+    pub duration_ms: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<&StepReport> for StepSide {
+    fn from(step: &StepReport) -> Self {
+        Self {
+            step_index: step.step_index,
+            status: step.status,
+            duration_ms: step.duration_ms,
+            error: step.error.clone(),
+        }
+    }
+}
+
+/// 单个步骤的对比结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StepDiff {
+    /// 步骤描述 (两侧都有时取本次的描述)
+    pub description: String,
+    pub phase: StepPhase,
+    pub change: StepChange,
+
+    /// 基准中的步骤 (新增步骤为空)
+    pub baseline: Option<StepSide>,
+
+    /// 本次的步骤 (移除步骤为空)
+    pub current: Option<StepSide>,
+}
+
+impl StepDiff {
+    /// 耗时变化 (毫秒), 仅两侧都有时
+    pub fn duration_delta_ms(&self) -> Option<i64> {
+        let baseline = self.baseline.as_ref()?;
+        let current = self.current.as_ref()?;
+        Some(current.duration_ms as i64 - baseline.duration_ms as i64)
+    }
+
+    /// 耗时变化百分比, 基准耗时为 0 时为空
+    pub fn duration_delta_pct(&self) -> Option<f64> {
+        let baseline = self.baseline.as_ref()?;
+        if baseline.duration_ms == 0 {
+            return None;
+        }
+        Some(self.duration_delta_ms()? as f64 * 100.0 / baseline.duration_ms as f64)
+    }
+}
+
+/// 各类变化的数量
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DiffTotals {
+    pub newly_failed: usize,
+    pub newly_passed: usize,
+    pub still_failing: usize,
+    pub duration_regressed: usize,
+    pub unchanged: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+impl DiffTotals {
+    fn count(&mut self, change: StepChange) {
+        match change {
+            StepChange::NewlyFailed => self.newly_failed += 1,
+            StepChange::NewlyPassed => self.newly_passed += 1,
+            StepChange::StillFailing => self.still_failing += 1,
+            StepChange::DurationRegressed => self.duration_regressed += 1,
+            StepChange::Unchanged => self.unchanged += 1,
+            StepChange::Added => self.added += 1,
+            StepChange::Removed => self.removed += 1,
+        }
+    }
+}
+
+/// 报告对比结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportDiff {
+    pub baseline_scenario: String,
+    pub current_scenario: String,
+    pub baseline_passed: bool,
+    pub current_passed: bool,
+    pub baseline_duration_ms: u64,
+    pub current_duration_ms: u64,
+    pub duration_threshold_pct: f64,
+
+    /// 逐步骤结果, 按本次报告的步骤顺序排列, 移除的步骤排在最后
+    pub steps: Vec<StepDiff>,
+
+    pub totals: DiffTotals,
+}
+
+impl ReportDiff {
+    /// 是否有新失败的步骤 (包括本次新增且失败的步骤)
+    pub fn has_new_failures(&self) -> bool {
+        self.steps.iter().any(|step| {
+            step.change == StepChange::NewlyFailed
+                || (step.change == StepChange::Added
+                    && step.current.as_ref().is_some_and(|current| current.status == StepStatus::Failed))
+        })
+    }
+
+    /// 有变化的步骤 (不含无变化的步骤)
+    pub fn changed(&self) -> impl Iterator<Item = &StepDiff> {
+        self.steps.iter().filter(|step| step.change != StepChange::Unchanged)
+    }
+}
+
+impl fmt::Display for ReportDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let totals = &self.totals;
+        write!(
+            f,
+            "新失败 {}, 新通过 {}, 持续失败 {}, 耗时退化 {}, 无变化 {}, 新增 {}, 移除 {}",
+            totals.newly_failed,
+            totals.newly_passed,
+            totals.still_failing,
+            totals.duration_regressed,
+            totals.unchanged,
+            totals.added,
+            totals.removed
+        )
+    }
+}
+
+impl ExecutionReport {
+    /// 与基准报告对比 (使用默认阈值)
+    pub fn diff(&self, baseline: &ExecutionReport) -> ReportDiff {
+        self.diff_with(baseline, &DiffOptions::default())
+    }
+
+    /// 与基准报告对比
+    pub fn diff_with(&self, baseline: &ExecutionReport, options: &DiffOptions) -> ReportDiff {
+        let pairs = align_steps(&baseline.steps, &self.steps);
+
+        let mut steps = Vec::with_capacity(pairs.len());
+        let mut totals = DiffTotals::default();
+        for (before, after) in pairs {
+            let change = classify(before, after, options);
+            totals.count(change);
+
+            let step = after.or(before).expect("至少一侧有步骤");
+            steps.push(StepDiff {
+                description: step.description.clone(),
+                phase: step.phase,
+                change,
+                baseline: before.map(StepSide::from),
+                current: after.map(StepSide::from),
+            });
+        }
+
+        ReportDiff {
+            baseline_scenario: baseline.scenario_name.clone(),
+            current_scenario: self.scenario_name.clone(),
+            baseline_passed: baseline.passed,
+            current_passed: self.passed,
+            baseline_duration_ms: baseline.duration_ms,
+            current_duration_ms: self.duration_ms,
+            duration_threshold_pct: options.duration_threshold_pct,
+            steps,
+            totals,
+        }
+    }
+}
+
+type StepPair<'a> = (Option<&'a StepReport>, Option<&'a StepReport>);
+
+/// 配对两次报告的步骤, 结果按本次步骤顺序排列, 基准中未配对的步骤附在最后
+fn align_steps<'a>(baseline: &'a [StepReport], current: &'a [StepReport]) -> Vec<StepPair<'a>> {
+    // 第一轮: 按 (阶段, 描述) 依次配对
+    let mut by_description: HashMap<(StepPhase, &str), VecDeque<usize>> = HashMap::new();
+    for (i, step) in baseline.iter().enumerate() {
+        by_description
+            .entry((step.phase, step.description.as_str()))
+            .or_default()
+            .push_back(i);
+    }
+
+    let mut matched: Vec<Option<usize>> = current
+        .iter()
+        .map(|step| {
+            by_description
+                .get_mut(&(step.phase, step.description.as_str()))
+                .and_then(|queue| queue.pop_front())
+        })
+        .collect();
+
+    // 第二轮: 剩下的步骤按 (阶段, 步骤索引) 配对
+    let mut used = vec![false; baseline.len()];
+    for i in matched.iter().flatten() {
+        used[*i] = true;
+    }
+    let by_index: HashMap<(StepPhase, usize), usize> = baseline
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| !used[*i])
+        .map(|(i, step)| ((step.phase, step.step_index), i))
+        .collect();
+    for (slot, step) in matched.iter_mut().zip(current) {
+        if slot.is_none() {
+            if let Some(&i) = by_index.get(&(step.phase, step.step_index)) {
+                if !used[i] {
+                    used[i] = true;
+                    *slot = Some(i);
+                }
+            }
+        }
+    }
+
+    let mut pairs: Vec<StepPair<'a>> = current
+        .iter()
+        .zip(matched)
+        .map(|(step, slot)| (slot.map(|i| &baseline[i]), Some(step)))
+        .collect();
+    pairs.extend(
+        baseline
+            .iter()
+            .zip(used)
+            .filter(|(_, used)| !used)
+            .map(|(step, _)| (Some(step), None)),
+    );
+    pairs
+}
+
+fn classify(before: Option<&StepReport>, after: Option<&StepReport>, options: &DiffOptions) -> StepChange {
+    let (before, after) = match (before, after) {
+        (Some(before), Some(after)) => (before, after),
+        (None, _) => return StepChange::Added,
+        (_, None) => return StepChange::Removed,
+    };
+
+    match (before.status, after.status) {
+        (StepStatus::Failed, StepStatus::Failed) => StepChange::StillFailing,
+        (_, StepStatus::Failed) => StepChange::NewlyFailed,
+        (StepStatus::Failed, StepStatus::Success) => StepChange::NewlyPassed,
+        (StepStatus::Success, StepStatus::Success) if duration_regressed(before, after, options) => {
+            StepChange::DurationRegressed
+        }
+        _ => StepChange::Unchanged,
+    }
+}
+
+fn duration_regressed(before: &StepReport, after: &StepReport, options: &DiffOptions) -> bool {
+    let delta = after.duration_ms.saturating_sub(before.duration_ms);
+    delta > options.min_duration_delta_ms
+        && delta as f64 > before.duration_ms as f64 * options.duration_threshold_pct / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(index: usize, description: &str, passed: bool, duration_ms: u64) -> StepReport {
+        let mut step = if passed {
+            StepReport::success(index, description)
+        } else {
+            StepReport::failed(index, description, "失败")
+        };
+        step.duration_ms = duration_ms;
+        step
+    }
+
+    fn report(steps: Vec<StepReport>) -> ExecutionReport {
+        let mut report = ExecutionReport::new("login");
+        for step in steps {
+            report.add_step(step);
+        }
+        report
+    }
+
+    fn changes(diff: &ReportDiff) -> Vec<(&str, StepChange)> {
+        diff.steps.iter().map(|step| (step.description.as_str(), step.change)).collect()
+    }
+
+    #[test]
+    fn test_diff_classifies_changes() {
+        let baseline = report(vec![
+            step(0, "启动虚拟机", true, 1000),
+            step(1, "输入密码", true, 1000),
+            step(2, "登录", false, 500),
+            step(3, "打开浏览器", false, 500),
+            step(4, "关闭", true, 50),
+        ]);
+        let current = report(vec![
+            step(0, "启动虚拟机", true, 1200),
+            step(1, "输入密码", true, 2000),
+            step(2, "登录", true, 500),
+            step(3, "打开浏览器", false, 500),
+            step(4, "关闭", false, 50),
+        ]);
+
+        let diff = current.diff(&baseline);
+        assert_eq!(
+            changes(&diff),
+            vec![
+                ("启动虚拟机", StepChange::Unchanged),
+                ("输入密码", StepChange::DurationRegressed),
+                ("登录", StepChange::NewlyPassed),
+                ("打开浏览器", StepChange::StillFailing),
+                ("关闭", StepChange::NewlyFailed),
+            ]
+        );
+        assert!(diff.has_new_failures());
+        assert_eq!(diff.totals.unchanged, 1);
+        assert_eq!(diff.steps[1].duration_delta_pct(), Some(100.0));
+
+        // 阈值调高后不再视为退化
+        let diff = current.diff_with(&baseline, &DiffOptions::default().with_threshold(150.0));
+        assert_eq!(diff.steps[1].change, StepChange::Unchanged);
+    }
+
+    #[test]
+    fn test_diff_ignores_small_duration_jitter() {
+        let baseline = report(vec![step(0, "等待", true, 10)]);
+        let current = report(vec![step(0, "等待", true, 60)]);
+
+        assert_eq!(current.diff(&baseline).steps[0].change, StepChange::Unchanged);
+        let diff = current.diff_with(&baseline, &DiffOptions::default().with_min_delta(0));
+        assert_eq!(diff.steps[0].change, StepChange::DurationRegressed);
+    }
+
+    #[test]
+    fn test_diff_aligns_reordered_steps() {
+        let baseline = report(vec![
+            step(0, "启动虚拟机", true, 100),
+            step(1, "输入密码", true, 100),
+            step(2, "登录", false, 100),
+        ]);
+        let current = report(vec![
+            step(0, "登录", true, 100),
+            step(1, "启动虚拟机", true, 100),
+            step(2, "输入密码", false, 100),
+        ]);
+
+        let diff = current.diff(&baseline);
+        assert_eq!(
+            changes(&diff),
+            vec![
+                ("登录", StepChange::NewlyPassed),
+                ("启动虚拟机", StepChange::Unchanged),
+                ("输入密码", StepChange::NewlyFailed),
+            ]
+        );
+        assert_eq!(diff.steps[0].baseline.as_ref().unwrap().step_index, 2);
+        assert_eq!(diff.steps[0].current.as_ref().unwrap().step_index, 0);
+    }
+
+    #[test]
+    fn test_diff_added_and_removed_steps() {
+        let baseline = report(vec![
+            step(0, "启动虚拟机", true, 100),
+            step(1, "截图", true, 100),
+            step(2, "关闭", true, 100),
+        ]);
+        let current = report(vec![
+            step(0, "启动虚拟机", true, 100),
+            step(1, "关闭", true, 100),
+            step(2, "检查日志", true, 100),
+        ]);
+
+        let diff = current.diff(&baseline);
+        assert_eq!(
+            changes(&diff),
+            vec![
+                ("启动虚拟机", StepChange::Unchanged),
+                ("关闭", StepChange::Unchanged),
+                ("检查日志", StepChange::Added),
+                ("截图", StepChange::Removed),
+            ]
+        );
+        assert!(!diff.has_new_failures());
+        assert_eq!((diff.totals.added, diff.totals.removed), (1, 1));
+
+        // 新增且失败的步骤算作新失败
+        let current = report(vec![step(0, "启动虚拟机", true, 100), step(1, "检查日志", false, 100)]);
+        assert!(current.diff(&baseline).has_new_failures());
+    }
+
+    #[test]
+    fn test_diff_falls_back_to_step_index() {
+        let baseline = report(vec![step(0, "启动虚拟机", true, 100), step(1, "输入 admin", true, 100)]);
+        let current = report(vec![step(0, "启动虚拟机", true, 100), step(1, "输入 root", false, 100)]);
+
+        let diff = current.diff(&baseline);
+        assert_eq!(changes(&diff), vec![("启动虚拟机", StepChange::Unchanged), ("输入 root", StepChange::NewlyFailed)]);
+        assert_eq!((diff.totals.added, diff.totals.removed), (0, 0));
+    }
+
+    #[test]
+    fn test_diff_duplicate_descriptions_pair_in_order() {
+        let baseline = report(vec![step(0, "按键 Enter", true, 100), step(1, "按键 Enter", false, 100)]);
+        let current = report(vec![step(0, "按键 Enter", true, 100), step(1, "按键 Enter", true, 100)]);
+
+        let diff = current.diff(&baseline);
+        assert_eq!(
+            changes(&diff),
+            vec![("按键 Enter", StepChange::Unchanged), ("按键 Enter", StepChange::NewlyPassed)]
+        );
+    }
+}
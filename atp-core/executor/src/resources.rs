@@ -0,0 +1,205 @@
+//! 场景资源跟踪
+//!
+//! 记录场景执行过程中创建的外部资源 (桌面池、虚拟机、快照等),
+//! 以便在场景结束时按注册的逆序自动清理, 并报告无人清理的资源。
+
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+/// 资源类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResourceKind {
+    /// 桌面池
+    DeskPool,
+    /// 虚拟机 (包括克隆出的虚拟机)
+    Domain,
+    /// 快照
+    Snapshot,
+}
+
+impl ResourceKind {
+    /// 资源类型名称 (用于持久化)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResourceKind::DeskPool => "desk_pool",
+            ResourceKind::Domain => "domain",
+            ResourceKind::Snapshot => "snapshot",
+        }
+    }
+}
+
+impl std::fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 资源清理状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CleanupStatus {
+    /// 尚未清理
+    Pending,
+    /// 已由场景步骤显式释放
+    Released,
+    /// 已在场景结束时自动清理
+    AutoCleaned,
+    /// 自动清理失败
+    Failed,
+    /// 无法自动清理 (资源泄漏)
+    Leaked,
+    /// 环境检查发现的场景外新增资源 (未被跟踪)
+    Orphaned,
+}
+
+impl CleanupStatus {
+    /// 状态名称 (用于持久化)
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CleanupStatus::Pending => "pending",
+            CleanupStatus::Released => "released",
+            CleanupStatus::AutoCleaned => "auto_cleaned",
+            CleanupStatus::Failed => "failed",
+            CleanupStatus::Leaked => "leaked",
+            CleanupStatus::Orphaned => "orphaned",
+        }
+    }
+}
+
+/// 被跟踪的资源
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrackedResource {
+    /// 资源类型
+    pub kind: ResourceKind,
+
+    /// 资源 ID
+    pub id: String,
+
+    /// 资源名称
+    pub name: Option<String>,
+
+    /// 注册资源的步骤索引
+    pub created_by_step: usize,
+
+    /// 清理状态
+    pub status: CleanupStatus,
+
+    /// 清理失败原因
+    pub error: Option<String>,
+}
+
+/// 资源跟踪器
+#[derive(Debug, Default)]
+pub struct ResourceTracker {
+    resources: Vec<TrackedResource>,
+}
+
+impl ResourceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 注册新创建的资源
+    pub fn register(
+        &mut self,
+        kind: ResourceKind,
+        id: &str,
+        name: Option<&str>,
+        step_index: usize,
+    ) {
+        self.resources.push(TrackedResource {
+            kind,
+            id: id.to_string(),
+            name: name.map(str::to_string),
+            created_by_step: step_index,
+            status: CleanupStatus::Pending,
+            error: None,
+        });
+    }
+
+    /// 标记资源已被场景步骤释放
+    ///
+    /// 返回是否找到了对应的待清理资源。
+    pub fn release(&mut self, kind: ResourceKind, id: &str) -> bool {
+        match self
+            .resources
+            .iter_mut()
+            .find(|r| r.kind == kind && r.id == id && r.status == CleanupStatus::Pending)
+        {
+            Some(resource) => {
+                resource.status = CleanupStatus::Released;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 待清理资源的索引 (按注册的逆序)
+    pub fn pending_indices(&self) -> Vec<usize> {
+        (0..self.resources.len())
+            .rev()
+            .filter(|&i| self.resources[i].status == CleanupStatus::Pending)
+            .collect()
+    }
+
+    /// 获取资源
+    pub fn get(&self, index: usize) -> Option<&TrackedResource> {
+        self.resources.get(index)
+    }
+
+    /// 更新资源的清理结果
+    pub fn set_status(&mut self, index: usize, status: CleanupStatus, error: Option<String>) {
+        if let Some(resource) = self.resources.get_mut(index) {
+            if matches!(status, CleanupStatus::Failed | CleanupStatus::Leaked) {
+                warn!(
+                    "资源未被清理: {} {} ({})",
+                    resource.kind,
+                    resource.id,
+                    error.as_deref().unwrap_or("无自动清理方式")
+                );
+            }
+            resource.status = status;
+            resource.error = error;
+        }
+    }
+
+    /// 所有被跟踪的资源
+    pub fn resources(&self) -> &[TrackedResource] {
+        &self.resources
+    }
+
+    /// 取出所有资源并重置跟踪器
+    pub fn take(&mut self) -> Vec<TrackedResource> {
+        std::mem::take(&mut self.resources)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_in_reverse_registration_order() {
+        let mut tracker = ResourceTracker::new();
+        tracker.register(ResourceKind::DeskPool, "pool-1", Some("pool"), 0);
+        tracker.register(ResourceKind::Domain, "vm-1", None, 1);
+        tracker.register(ResourceKind::Snapshot, "snap-1", None, 2);
+
+        assert!(tracker.release(ResourceKind::Domain, "vm-1"));
+        assert!(!tracker.release(ResourceKind::Domain, "vm-1"));
+
+        let pending = tracker.pending_indices();
+        assert_eq!(pending, vec![2, 0]);
+        assert_eq!(tracker.get(pending[0]).unwrap().id, "snap-1");
+
+        tracker.set_status(2, CleanupStatus::Leaked, None);
+        tracker.set_status(0, CleanupStatus::AutoCleaned, None);
+        assert!(tracker.pending_indices().is_empty());
+
+        let resources = tracker.take();
+        assert_eq!(resources.len(), 3);
+        assert_eq!(resources[1].status, CleanupStatus::Released);
+        assert!(tracker.resources().is_empty());
+    }
+}
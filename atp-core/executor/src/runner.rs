@@ -1,24 +1,85 @@
 //! 场景执行器
 
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tracing::{info, warn, error};
+use tracing::{debug, info, warn, error};
 use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
 use virt::domain::Domain;
 
-use atp_transport::TransportManager;
+use atp_transport::{host_command::quote_command, ErrorContext, HostInfo, TransportConfig, TransportManager};
 use atp_protocol::{
-    Protocol, ProtocolRegistry,
-    qmp::QmpProtocol,
-    qga::QgaProtocol,
+    KeyCombo, KeyMapper, KeyboardLayout, Protocol, ProtocolError, ProtocolRegistry,
+    qmp::{QmpProtocol, DEFAULT_SCREEN_SIZE},
+    qga::{QgaMetrics, QgaProtocol, WinShell},
     spice::{SpiceProtocol, MouseButton},
 };
-use atp_storage::{Storage, TestReportRecord, ExecutionStepRecord};
-use atp_vdiplatform::{VdiClient, models::CreateDeskPoolRequest};
-
-use crate::{Result, Scenario, ScenarioStep, Action, ExecutorError};
+use atp_storage::{EntityMetricSample, Storage, TestReportRecord, ExecutionStepRecord, ReportResourceRecord, StepMetricsRecord};
+use atp_vdiplatform::{VdiClient, VdiError, models::{CreateDeskPoolRequest, DeskPoolAdvanced}};
+
+use crate::{Result, Scenario, ScenarioStep, StepErrorKind, StepFilter, Action, ExecutorError};
+use crate::test_config::{FromTestConfig, TestConfig};
+use crate::scenario::{DEFAULT_SSH_IDLE_TIMEOUT_SECS, SCENARIO_SCHEMA_VERSION};
+use crate::event_log::{self, EventLevel, EventLogName};
+use crate::html_report;
+use crate::authoring::{self, PlannedStep};
+use crate::observer::{self, ExecutionObserver, ScenarioFinished, ScenarioStarted, StepFinished, StepStarted};
+use crate::uniquify::{self, GuestPlatform};
+use crate::guest_file::{self, Sha256Expectation};
+use crate::powershell::{PowerShellError, PowerShellOutput, PowerShellScript};
+use crate::migration::{DowntimeStats, OwnershipCheck, PingSample, PlacementCheck};
+use crate::environment::{EnvironmentGuard, EnvironmentGuardMode, OrphanResource};
+use crate::resources::{CleanupStatus, ResourceKind, ResourceTracker, TrackedResource};
+use crate::step_metrics::{BlockStats, StepMetrics, StepMetricsSampler};
+use crate::validation::{self, validate_scenario, ValidationContext, ValidationIssue};
+use crate::scope::VariableScope;
+use crate::vdi_ops::{RestoreMethod, RestoreStatus, VdiBatchOps};
+use crate::vm_cache::CacheMode;
+
+/// 场景被终止后, 清理步骤与资源回收的默认时间预算
+const DEFAULT_TEARDOWN_GRACE: Duration = Duration::from_secs(60);
+
+/// 事件日志查询结果中写入步骤输出的事件条数
+const EVENT_LOG_SUMMARY_LIMIT: usize = 10;
+
+/// 迁移期间 QGA ping 的采样间隔
+const MIGRATION_PING_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 迁移期间单次 QGA ping 的超时 (切换瞬间 QGA 可能无响应)
+const MIGRATION_PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 迁移期间检查虚拟机归属的间隔
+const MIGRATION_OWNERSHIP_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// 等待客户机重启时的 QGA 轮询间隔
+const GUEST_REBOOT_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// 唯一化脚本执行后等待客户机开始重启的最长时间
+const GUEST_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// 步骤资源采样时单次 QMP query-blockstats 的超时
+const BLOCKSTATS_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 交互式会话的连接状态
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SessionState {
+    /// 虚拟机名称
+    pub domain: Option<String>,
+
+    /// 各协议是否已连接
+    pub qmp: bool,
+    pub qga: bool,
+    pub spice: bool,
+
+    /// QMP query-status 返回的运行状态 (如 running / paused), QMP 不可用时为 None
+    pub run_state: Option<String>,
+}
 
 /// 场景执行器
 pub struct ScenarioRunner {
@@ -33,17 +94,68 @@ pub struct ScenarioRunner {
     qga_protocol: Option<QgaProtocol>,
     spice_protocol: Option<SpiceProtocol>,
 
+    /// 本执行器所有 QGA 连接共用的命令统计
+    qga_metrics: Arc<QgaMetrics>,
+
+    /// 通过注册表创建的自定义协议实例 (协议名 -> 协议实例)
+    custom_protocols: HashMap<String, Box<dyn Protocol>>,
+
     /// VDI 平台客户端 (可选)
     vdi_client: Option<Arc<VdiClient>>,
 
     /// 当前 Domain
     current_domain: Option<Domain>,
 
+    /// 当前虚拟机所在的主机 ID (迁移后跟随到目标主机)
+    current_host: Option<String>,
+
     /// 默认超时时间
     default_timeout: Duration,
 
     /// 数据库存储 (可选)
     storage: Option<Arc<Storage>>,
+
+    /// 当前场景创建的资源
+    resource_tracker: ResourceTracker,
+
+    /// 外部取消令牌 (如 Ctrl-C)
+    cancel_token: CancellationToken,
+
+    /// 场景被终止后清理阶段的时间预算
+    teardown_grace: Duration,
+
+    /// 当前虚拟机在批量执行中的序号 (用于主机名模板 `{index}`)
+    vm_index: usize,
+
+    /// 当前场景的开始时间 (用于计算步骤开始偏移)
+    run_started: Instant,
+
+    /// 执行进度观察者
+    observers: Vec<Arc<dyn ExecutionObserver>>,
+
+    /// 场景版本 (从已保存的场景运行时)
+    scenario_version: Option<i32>,
+
+    /// 当前子运行的工件目录 (多目标并行执行时每个目标一个)
+    artifact_dir: Option<PathBuf>,
+
+    /// 变量作用域 (步骤执行前代入动作中的 `${变量}`)
+    variables: Option<VariableScope>,
+
+    /// 当前场景的 Guest 键盘布局 (用于发送文本)
+    keyboard_layout: KeyboardLayout,
+
+    /// Guest 平台 (场景的 `guest_os`, 或第一次执行命令时通过 QGA 识别)
+    guest_platform: Option<GuestPlatform>,
+
+    /// 当前场景的格式版本 (拒绝执行无法识别的动作时用于提示)
+    scenario_schema_version: u32,
+
+    /// 步骤资源指标的采样间隔 (None 表示不采样)
+    metrics_interval: Option<Duration>,
+
+    /// 来宾屏幕尺寸 (宽, 高), 通过 QMP 点击鼠标时用于换算绝对坐标
+    screen_size: (u32, u32),
 }
 
 impl ScenarioRunner {
@@ -58,13 +170,43 @@ impl ScenarioRunner {
             qmp_protocol: None,
             qga_protocol: None,
             spice_protocol: None,
+            qga_metrics: Arc::new(QgaMetrics::default()),
+            custom_protocols: HashMap::new(),
             vdi_client: None,
             current_domain: None,
+            current_host: None,
             default_timeout: Duration::from_secs(30),
             storage: None,
+            resource_tracker: ResourceTracker::new(),
+            cancel_token: CancellationToken::new(),
+            teardown_grace: DEFAULT_TEARDOWN_GRACE,
+            vm_index: 0,
+            run_started: Instant::now(),
+            observers: Vec::new(),
+            scenario_version: None,
+            artifact_dir: None,
+            variables: None,
+            keyboard_layout: KeyboardLayout::default(),
+            guest_platform: None,
+            scenario_schema_version: SCENARIO_SCHEMA_VERSION,
+            metrics_interval: None,
+            screen_size: DEFAULT_SCREEN_SIZE,
         }
     }
 
+    /// 按测试配置创建场景执行器
+    ///
+    /// 注册 `[libvirt]` 中配置的主机与连接池参数, 默认超时取 `test.timeout`。
+    /// 主机配置有问题 (ID 重复、URI 格式错误等) 时一次列出全部问题。
+    pub async fn from_test_config(config: &TestConfig) -> Result<Self> {
+        let transport_manager = TransportManager::from_config(TransportConfig::from_test_config(config))
+            .await
+            .map_err(|e| ExecutorError::ConfigError(e.to_string()))?;
+
+        Ok(Self::new(Arc::new(transport_manager), Arc::new(ProtocolRegistry::new()))
+            .with_timeout(Duration::from_secs(config.test.timeout)))
+    }
+
     /// 设置数据库存储
     pub fn with_storage(mut self, storage: Arc<Storage>) -> Self {
         self.storage = Some(storage);
@@ -83,11 +225,218 @@ impl ScenarioRunner {
         self
     }
 
+    /// 设置场景被终止后清理阶段的时间预算
+    pub fn with_teardown_grace(mut self, grace: Duration) -> Self {
+        self.teardown_grace = grace;
+        self
+    }
+
+    /// 设置当前虚拟机在批量执行中的序号
+    ///
+    /// 对克隆出来的一批虚拟机逐台执行同一场景时, 用于渲染主机名模板中的 `{index}`。
+    pub fn with_vm_index(mut self, index: usize) -> Self {
+        self.vm_index = index;
+        self
+    }
+
+    /// 添加执行进度观察者
+    ///
+    /// 观察者返回错误或 panic 时只记录日志, 不影响场景执行。
+    pub fn with_observer(mut self, observer: Arc<dyn ExecutionObserver>) -> Self {
+        self.observers.push(observer);
+        self
+    }
+
+    /// 设置场景版本
+    ///
+    /// 从数据库中保存的场景运行时记录到报告中, 用于追溯报告由哪个版本产生。
+    pub fn with_scenario_version(mut self, version: i32) -> Self {
+        self.scenario_version = Some(version);
+        self
+    }
+
+    /// 设置当前子运行的工件目录
+    ///
+    /// 多目标并行执行时由 [`prepare_targets`](crate::scope::prepare_targets) 为每个目标分配,
+    /// 记录到报告中, 以便聚合报告定位各子运行的工件。
+    pub fn with_artifact_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.artifact_dir = Some(dir.into());
+        self
+    }
+
+    /// 设置变量作用域
+    ///
+    /// 每个步骤执行前把动作中的 `${变量}` 替换为作用域中的值,
+    /// 校验场景时这些变量也视为已定义。
+    pub fn with_variables(mut self, variables: VariableScope) -> Self {
+        self.variables = Some(variables);
+        self
+    }
+
+    /// 启用步骤资源指标采样
+    ///
+    /// 每个步骤执行期间按 `interval` 通过 libvirt 采样虚拟机 CPU 与内存, QMP 可用时
+    /// 额外记录步骤期间的磁盘读写量, 结果写入 [`StepReport::metrics`]。
+    /// 采样失败只记录告警, 不影响步骤结果。
+    pub fn with_metrics_sampling(mut self, interval: Duration) -> Self {
+        self.metrics_interval = Some(interval);
+        self
+    }
+
+    /// 设置来宾屏幕尺寸
+    ///
+    /// SPICE 不可用时鼠标点击通过 QMP `input-send-event` 发送绝对坐标, 需要按屏幕尺寸换算,
+    /// 默认按 1920x1080 计算。
+    pub fn with_screen_size(mut self, width: u32, height: u32) -> Self {
+        self.screen_size = (width, height);
+        self
+    }
+
+    /// 使用共享的 QGA 命令统计 (多个执行器的统计累计到同一个指标源)
+    pub fn with_qga_metrics(mut self, metrics: Arc<QgaMetrics>) -> Self {
+        self.qga_metrics = metrics;
+        self
+    }
+
+    /// QGA 命令统计 (可作为指标源挂载到 `MetricsCollector`)
+    pub fn qga_metrics(&self) -> Arc<QgaMetrics> {
+        Arc::clone(&self.qga_metrics)
+    }
+
+    /// 获取取消令牌
+    ///
+    /// 取消后当前步骤被中断, 剩余步骤标记为跳过, 清理步骤仍在时间预算内执行。
+    /// 令牌只作用于下一次 (或正在进行的) 运行, 每次运行结束后都会换用新令牌,
+    /// 因此需要在每次运行前重新获取。
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel_token.clone()
+    }
+
+    /// 校验场景 (dry-run)
+    ///
+    /// 仅检查场景定义与执行器配置, 不连接虚拟机也不访问 VDI 平台。
+    pub async fn validate(&self, scenario: &Scenario) -> Vec<ValidationIssue> {
+        let registered_protocols = self.protocol_registry.list().await;
+        let defined_variables = self.defined_variables();
+        let ctx = ValidationContext {
+            has_vdi_client: self.vdi_client.is_some(),
+            default_timeout: self.default_timeout,
+            registered_protocols: &registered_protocols,
+            defined_variables: &defined_variables,
+        };
+
+        validate_scenario(scenario, &ctx)
+    }
+
+    /// 校验场景定义本身 (编写场景时使用)
+    ///
+    /// 与 [`validate`](Self::validate) 相同, 但假定执行时会配置 VDI 客户端,
+    /// 不因当前执行器未配置 VDI 平台而报错。
+    pub async fn validate_definition(&self, scenario: &Scenario) -> Vec<ValidationIssue> {
+        let registered_protocols = self.protocol_registry.list().await;
+        let defined_variables = self.defined_variables();
+        let ctx = ValidationContext {
+            has_vdi_client: true,
+            default_timeout: self.default_timeout,
+            registered_protocols: &registered_protocols,
+            defined_variables: &defined_variables,
+        };
+
+        validate_scenario(scenario, &ctx)
+    }
+
+    /// 变量作用域中定义的变量名
+    fn defined_variables(&self) -> Vec<String> {
+        self.variables.as_ref().map(VariableScope::names).unwrap_or_default()
+    }
+
+    /// 生成场景的执行计划 (不执行任何步骤)
+    pub fn plan(&self, scenario: &Scenario) -> Vec<PlannedStep> {
+        authoring::execution_plan(scenario, self.default_timeout)
+    }
+
+    /// 校验场景, 并只读查询 VDI 平台确认引用的桌面池/虚拟机存在
+    pub async fn validate_with_vdi(&self, scenario: &Scenario) -> Vec<ValidationIssue> {
+        let mut issues = self.validate(scenario).await;
+
+        let client = match &self.vdi_client {
+            Some(client) => client,
+            None => return issues,
+        };
+
+        let mut checked: HashMap<(ResourceKind, String), bool> = HashMap::new();
+
+        for (index, step) in validation::indexed_steps(scenario) {
+            let (kind, id) = match &step.action {
+                Action::VdiEnableDeskPool { pool_id }
+                | Action::VdiDisableDeskPool { pool_id }
+                | Action::VdiDeleteDeskPool { pool_id }
+                | Action::VdiGetDeskPoolDomains { pool_id }
+                | Action::VerifyAllDomainsRunning { pool_id, .. } => (ResourceKind::DeskPool, pool_id),
+                Action::VdiStartDomain { domain_id }
+                | Action::VdiShutdownDomain { domain_id }
+                | Action::VdiRebootDomain { domain_id }
+                | Action::VdiDeleteDomain { domain_id }
+                | Action::VdiBindUser { domain_id, .. }
+                | Action::VdiMigrateAndVerify { domain: domain_id, .. }
+                | Action::VdiMigrateDomain { domain_id, .. }
+                | Action::VerifyDomainStatus { domain_id, .. } => (ResourceKind::Domain, domain_id),
+                _ => continue,
+            };
+
+            if !validation::variable_references(id).is_empty() {
+                continue;
+            }
+
+            let key = (kind, id.clone());
+            let exists = match checked.get(&key) {
+                Some(exists) => *exists,
+                None => {
+                    let exists = match kind {
+                        ResourceKind::DeskPool => client.desk_pool().get(id).await.is_ok(),
+                        _ => client.domain().get(id).await.is_ok(),
+                    };
+                    checked.insert(key, exists);
+                    exists
+                }
+            };
+
+            if !exists {
+                issues.push(ValidationIssue::warning(
+                    Some(index),
+                    format!("{} {} 不匹配任何 VDI 资源 (若由本场景创建可忽略)", kind, id),
+                ));
+            }
+        }
+
+        issues
+    }
+
     /// 执行场景
     pub async fn run(&mut self, scenario: &Scenario) -> Result<ExecutionReport> {
+        self.run_filtered(scenario, &StepFilter::default()).await
+    }
+
+    /// 按标签过滤执行场景
+    ///
+    /// 过滤只作用于测试步骤, 前置与清理步骤总是执行。
+    /// 被过滤掉的步骤记为 `StepStatus::Skipped`。
+    pub async fn run_filtered(&mut self, scenario: &Scenario, filter: &StepFilter) -> Result<ExecutionReport> {
+        let result = self.run_scenario(scenario, filter).await;
+
+        // 取消令牌只作用于一次运行, 否则取消过一次的执行器会跳过之后的所有运行
+        self.cancel_token = CancellationToken::new();
+        result
+    }
+
+    async fn run_scenario(&mut self, scenario: &Scenario, filter: &StepFilter) -> Result<ExecutionReport> {
         info!("开始执行场景: {}", scenario.name);
 
         let start_time = Instant::now();
+        self.run_started = start_time;
+        self.keyboard_layout = scenario.keyboard_layout.unwrap_or_default();
+        self.guest_platform = scenario.guest_os;
+        self.scenario_schema_version = scenario.schema_version;
         let mut report = ExecutionReport::new(&scenario.name);
 
         if let Some(desc) = &scenario.description {
@@ -95,39 +444,107 @@ impl ScenarioRunner {
         }
 
         report.tags = scenario.tags.clone();
+        report.scenario_version = self.scenario_version;
+        report.artifact_dir = self.artifact_dir.as_ref().map(|dir| dir.display().to_string());
+
+        let started = ScenarioStarted {
+            scenario_name: scenario.name.clone(),
+            total_steps: scenario.setup.len() + scenario.steps.len() + scenario.teardown.len(),
+        };
+        observer::notify(&self.observers, |o| o.on_scenario_start(&started));
+
+        // 场景令牌: 外部取消或超过 max_duration_secs 时触发
+        let scenario_token = self.cancel_token.child_token();
+        let deadline_task = scenario.max_duration_secs.map(|secs| {
+            let token = scenario_token.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(secs)).await;
+                warn!("场景执行超过 {} 秒, 取消剩余步骤", secs);
+                token.cancel();
+            })
+        });
 
         // 初始化协议连接 (如果指定了目标虚拟机)
         if let Some(target_domain) = &scenario.target_domain {
-            if let Err(e) = self.initialize_protocols(scenario, target_domain).await {
-                error!("初始化协议失败: {}", e);
-                return Err(e);
+            match self.initialize_protocols(scenario.target_host.as_deref(), target_domain, &scenario_token).await {
+                Ok(()) => {}
+                Err(ExecutorError::Cancelled(_)) => {
+                    warn!("初始化协议时场景被终止");
+                }
+                Err(e) => {
+                    error!("初始化协议失败: {}", e);
+                    if let Some(task) = deadline_task {
+                        task.abort();
+                    }
+                    return Err(e);
+                }
             }
         }
 
-        for (index, step) in scenario.steps.iter().enumerate() {
-            info!("执行步骤 {}/{}", index + 1, scenario.steps.len());
+        self.resource_tracker = ResourceTracker::new();
+        let environment_guard = self.start_environment_guard(scenario).await;
+        let mut next_index = 0;
+
+        // 前置步骤失败时跳过测试步骤, 但仍执行清理步骤
+        let setup_passed = self
+            .run_phase(&scenario.setup, StepPhase::Setup, None, &scenario_token, &mut report, &mut next_index)
+            .await;
+
+        // 场景被终止时仍遍历测试步骤, 以便把它们标记为跳过
+        if setup_passed || scenario_token.is_cancelled() {
+            self.run_phase(
+                &scenario.steps,
+                StepPhase::Main,
+                Some((filter, &scenario.tags)),
+                &scenario_token,
+                &mut report,
+                &mut next_index,
+            )
+            .await;
+        } else {
+            warn!("前置步骤失败, 跳过测试步骤");
+        }
 
-            let step_result = self.execute_step(step, index).await;
+        if let Some(task) = deadline_task {
+            task.abort();
+        }
 
-            match step_result {
-                Ok(result) => {
-                    info!("步骤 {} 完成: {}", index + 1, result.description);
-                    report.add_step(result);
-                }
-                Err(e) => {
-                    error!("步骤 {} 失败: {}", index + 1, e);
-                    let failed_step = StepReport {
-                        step_index: index,
-                        description: format!("步骤 {}", index + 1),
-                        status: StepStatus::Failed,
-                        error: Some(e.to_string()),
-                        duration_ms: 0,
-                        output: None,
-                    };
-                    report.add_step(failed_step);
-                    break; // 失败后停止执行
-                }
+        if scenario_token.is_cancelled() {
+            if self.cancel_token.is_cancelled() {
+                report.cancelled = true;
+            } else {
+                report.timed_out = true;
             }
+            report.passed = false;
+        }
+
+        // 清理阶段使用独立的令牌; 场景被终止时只给予有限的时间预算
+        let teardown_token = CancellationToken::new();
+        let grace_task = scenario_token.is_cancelled().then(|| {
+            let token = teardown_token.clone();
+            let grace = self.teardown_grace;
+            tokio::spawn(async move {
+                tokio::time::sleep(grace).await;
+                warn!("清理阶段超过 {:?}, 中断清理", grace);
+                token.cancel();
+            })
+        });
+
+        self.run_phase(&scenario.teardown, StepPhase::Teardown, None, &teardown_token, &mut report, &mut next_index)
+            .await;
+
+        // 按注册逆序清理剩余资源
+        if cancellable(&teardown_token, self.cleanup_tracked_resources()).await.is_err() {
+            warn!("资源自动清理被中断, 剩余资源保持待清理状态");
+        }
+        report.resources = self.resource_tracker.take();
+
+        if let Some(task) = grace_task {
+            task.abort();
+        }
+
+        if let Some(guard) = &environment_guard {
+            self.check_environment(guard, &mut report).await;
         }
 
         // 清理协议连接
@@ -135,6 +552,16 @@ impl ScenarioRunner {
 
         report.duration_ms = start_time.elapsed().as_millis() as u64;
 
+        let finished = ScenarioFinished {
+            scenario_name: scenario.name.clone(),
+            passed: report.passed,
+            duration_ms: report.duration_ms,
+            passed_count: report.passed_count,
+            failed_count: report.failed_count,
+            skipped_count: report.skipped_count,
+        };
+        observer::notify(&self.observers, |o| o.on_scenario_end(&finished));
+
         info!(
             "场景执行完成: {} - {}/{} 步骤成功",
             scenario.name,
@@ -153,28 +580,267 @@ impl ScenarioRunner {
         Ok(report)
     }
 
-    /// 初始化协议连接
-    async fn initialize_protocols(&mut self, scenario: &Scenario, domain_name: &str) -> Result<()> {
+    /// 执行一组步骤
+    ///
+    /// 前置步骤和测试步骤在出错后停止, 清理步骤总是全部执行。
+    /// 指定过滤条件 (及场景标签) 时跳过不匹配的步骤。
+    /// 令牌被取消后, 当前步骤被中断, 剩余步骤标记为跳过。
+    /// 返回该组步骤是否全部成功。
+    async fn run_phase(
+        &mut self,
+        steps: &[ScenarioStep],
+        phase: StepPhase,
+        filter: Option<(&StepFilter, &[String])>,
+        token: &CancellationToken,
+        report: &mut ExecutionReport,
+        next_index: &mut usize,
+    ) -> bool {
+        let mut all_passed = true;
+
+        for (position, step) in steps.iter().enumerate() {
+            let index = *next_index;
+            *next_index += 1;
+            let started_at_offset_ms = self.run_started.elapsed().as_millis() as u64;
+
+            if token.is_cancelled() {
+                let reason = self.cancel_reason(phase);
+                let description = step.name.clone()
+                    .unwrap_or_else(|| format!("步骤 {}", index + 1));
+                let mut skipped = StepReport::skipped(index, &description, reason);
+                skipped.phase = phase;
+                skipped.started_at_offset_ms = started_at_offset_ms;
+                self.record_step(report, skipped, &step.action);
+                continue;
+            }
+
+            if let Some(reason) = filter.and_then(|(f, tags)| f.skip_reason(step, tags)) {
+                info!("跳过{} {}/{}: {}", phase.label(), position + 1, steps.len(), reason);
+                let description = step.name.clone()
+                    .unwrap_or_else(|| format!("步骤 {}", index + 1));
+                let mut skipped = StepReport::skipped(index, &description, &reason);
+                skipped.phase = phase;
+                skipped.started_at_offset_ms = started_at_offset_ms;
+                self.record_step(report, skipped, &step.action);
+                continue;
+            }
+
+            info!("执行{} {}/{}", phase.label(), position + 1, steps.len());
+
+            let step_started = StepStarted {
+                scenario_name: report.scenario_name.clone(),
+                step_index: index,
+                action: step.action.type_name(),
+                phase,
+                description: step.name.clone()
+                    .unwrap_or_else(|| format!("步骤 {}", index + 1)),
+            };
+            observer::notify(&self.observers, |o| o.on_step_start(&step_started));
+
+            match self.execute_step(step, index, token).await {
+                Ok(mut result) => {
+                    info!("步骤 {} 完成: {}", index + 1, result.description);
+                    if result.status == StepStatus::Failed {
+                        all_passed = false;
+                    }
+                    result.phase = phase;
+                    result.started_at_offset_ms = started_at_offset_ms;
+                    self.record_step(report, result, &step.action);
+                }
+                Err(e) => {
+                    error!("步骤 {} 失败: {}", index + 1, e);
+                    let error_kind = e.kind();
+                    let error = match e {
+                        ExecutorError::Cancelled(_) => format!("步骤被取消: {}", self.cancel_reason(phase)),
+                        e => e.to_string(),
+                    };
+                    let failed_step = StepReport {
+                        step_index: index,
+                        description: format!("步骤 {}", index + 1),
+                        status: StepStatus::Failed,
+                        error: Some(error),
+                        duration_ms: 0,
+                        output: None,
+                        phase,
+                        started_at_offset_ms,
+                        metrics: None,
+                        error_kind: Some(error_kind),
+                    };
+                    self.record_step(report, failed_step, &step.action);
+                    all_passed = false;
+
+                    // 被取消时继续遍历, 把剩余步骤标记为跳过
+                    if phase != StepPhase::Teardown && !token.is_cancelled() {
+                        break; // 失败后停止执行
+                    }
+                }
+            }
+        }
+
+        all_passed
+    }
+
+    /// 把步骤结果加入报告并通知观察者
+    fn record_step(&self, report: &mut ExecutionReport, step: StepReport, action: &Action) {
+        let finished = StepFinished {
+            scenario_name: report.scenario_name.clone(),
+            step_index: step.step_index,
+            action: action.type_name(),
+            phase: step.phase,
+            description: step.description.clone(),
+            status: step.status,
+            duration_ms: step.duration_ms,
+            error: step.error.clone(),
+        };
+        observer::notify(&self.observers, |o| o.on_step_end(&finished));
+
+        report.add_step(step);
+    }
+
+    /// 取消原因 (用于跳过步骤的说明)
+    fn cancel_reason(&self, phase: StepPhase) -> &'static str {
+        if phase == StepPhase::Teardown {
+            "清理超时"
+        } else if self.cancel_token.is_cancelled() {
+            "用户取消"
+        } else {
+            "场景超时"
+        }
+    }
+
+    /// 记录场景开始前的 VDI 资源清单
+    ///
+    /// 场景未启用环境检查, 或无法获取资源清单时返回 None (不影响场景执行)。
+    async fn start_environment_guard(&self, scenario: &Scenario) -> Option<EnvironmentGuard> {
+        let mode = scenario.environment_guard?;
+
+        let client = match &self.vdi_client {
+            Some(client) => client,
+            None => {
+                warn!("VDI 客户端未初始化, 跳过环境检查");
+                return None;
+            }
+        };
+
+        match EnvironmentGuard::start(client, mode).await {
+            Ok(guard) => {
+                info!(
+                    "已记录环境快照: {} 个虚拟机, {} 个桌面池",
+                    guard.before().count(ResourceKind::Domain),
+                    guard.before().count(ResourceKind::DeskPool)
+                );
+                Some(guard)
+            }
+            Err(e) => {
+                warn!("记录环境快照失败, 跳过环境检查: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 对比场景结束后的资源清单, 把新增资源记入报告
+    async fn check_environment(&self, guard: &EnvironmentGuard, report: &mut ExecutionReport) {
+        let client = match &self.vdi_client {
+            Some(client) => client,
+            None => return,
+        };
+
+        let orphans = match guard.finish(client).await {
+            Ok(orphans) => orphans,
+            Err(e) => {
+                warn!("环境检查失败: {}", e);
+                return;
+            }
+        };
+
+        if orphans.is_empty() {
+            info!("环境检查通过, 没有新增未清理的资源");
+            return;
+        }
+
+        for orphan in &orphans {
+            warn!(
+                "场景结束后存在新增资源: {} {} ({})",
+                orphan.kind,
+                orphan.id,
+                orphan.name.as_deref().unwrap_or("-")
+            );
+        }
+
+        if guard.mode() == EnvironmentGuardMode::Fail {
+            report.passed = false;
+        }
+        report.orphan_resources = orphans;
+    }
+
+    /// 清理场景中未被释放的资源 (按注册的逆序)
+    async fn cleanup_tracked_resources(&mut self) {
+        for index in self.resource_tracker.pending_indices() {
+            let resource = match self.resource_tracker.get(index) {
+                Some(resource) => resource.clone(),
+                None => continue,
+            };
+
+            info!("自动清理资源: {} {}", resource.kind, resource.id);
+
+            let (status, error) = match (&self.vdi_client, resource.kind) {
+                (Some(client), ResourceKind::DeskPool) => {
+                    match client.desk_pool().delete(&resource.id).await {
+                        Ok(_) => (CleanupStatus::AutoCleaned, None),
+                        Err(e) => (CleanupStatus::Failed, Some(e.to_string())),
+                    }
+                }
+                (Some(client), ResourceKind::Domain) => {
+                    match client.domain().delete(&resource.id).await {
+                        Ok(_) => (CleanupStatus::AutoCleaned, None),
+                        Err(e) => (CleanupStatus::Failed, Some(e.to_string())),
+                    }
+                }
+                (None, ResourceKind::DeskPool | ResourceKind::Domain) => (
+                    CleanupStatus::Leaked,
+                    Some("VDI 客户端未初始化".to_string()),
+                ),
+                (_, ResourceKind::Snapshot) => (
+                    CleanupStatus::Leaked,
+                    Some("快照不支持自动清理".to_string()),
+                ),
+            };
+
+            self.resource_tracker.set_status(index, status, error);
+        }
+    }
+
+    /// 初始化协议连接 (未指定主机时使用第一个主机)
+    async fn initialize_protocols(
+        &mut self,
+        target_host: Option<&str>,
+        domain_name: &str,
+        token: &CancellationToken,
+    ) -> Result<()> {
         info!("初始化协议连接: 虚拟机 = {}", domain_name);
 
         // 获取目标主机的连接
         let hosts = self.transport_manager.list_hosts().await;
-        let host_id = scenario.target_host.as_deref()
+        let host_id = target_host
             .or_else(|| hosts.first().map(String::as_str))
             .ok_or_else(|| ExecutorError::ConfigError("未指定目标主机且无可用主机".to_string()))?;
 
         // 通过 transport manager 获取 domain
-        let domain = self.transport_manager
+        let domain = cancellable(token, self.transport_manager
             .execute_on_host(host_id, |conn| async move {
-                conn.get_domain(domain_name).await
-            })
-            .await
+                conn.get_domain(domain_name)
+                    .await
+                    .map_err(|e| e.with_context(ErrorContext::new().with_domain(domain_name)))
+            }))
+            .await?
             .map_err(|e| ExecutorError::TransportError(e.to_string()))?;
 
+        // 各协议连接可能长时间阻塞, 均可被取消
+
         // 初始化 QMP 协议
         let mut qmp = QmpProtocol::new();
-        if let Err(e) = qmp.connect(&domain).await {
-            warn!("QMP 协议连接失败: {}", e);
+        qmp.set_screen_size(self.screen_size.0, self.screen_size.1);
+        if let Err(e) = cancellable(token, qmp.connect(&domain)).await? {
+            warn!("QMP 协议连接失败: {}", e.with_context(ErrorContext::new().with_host(host_id)));
             // QMP 失败不是致命错误,可能虚拟机没有 QMP
         } else {
             info!("QMP 协议连接成功");
@@ -182,9 +848,9 @@ impl ScenarioRunner {
         }
 
         // 初始化 QGA 协议
-        let mut qga = QgaProtocol::new();
-        if let Err(e) = qga.connect(&domain).await {
-            warn!("QGA 协议连接失败: {}", e);
+        let mut qga = QgaProtocol::new().with_metrics(Arc::clone(&self.qga_metrics));
+        if let Err(e) = cancellable(token, qga.connect(&domain)).await? {
+            warn!("QGA 协议连接失败: {}", e.with_context(ErrorContext::new().with_host(host_id)));
             // QGA 失败不是致命错误,可能虚拟机没有安装 guest agent
         } else {
             info!("QGA 协议连接成功");
@@ -193,8 +859,11 @@ impl ScenarioRunner {
 
         // 初始化 SPICE 协议（用于鼠标操作）
         let mut spice = SpiceProtocol::new();
-        if let Err(e) = spice.connect(&domain).await {
-            warn!("SPICE 协议连接失败: {}", e);
+        if let Err(e) = cancellable(token, spice.connect(&domain)).await? {
+            warn!(
+                "SPICE 协议连接失败: {}",
+                e.with_context(ErrorContext::new().with_host(host_id).with_domain(domain_name))
+            );
             // SPICE 失败不是致命错误,可能虚拟机没有配置 SPICE
         } else {
             info!("SPICE 协议连接成功");
@@ -202,6 +871,7 @@ impl ScenarioRunner {
         }
 
         self.current_domain = Some(domain);
+        self.current_host = Some(host_id.to_string());
 
         Ok(())
     }
@@ -220,24 +890,147 @@ impl ScenarioRunner {
             let _ = spice.disconnect().await;
         }
 
+        for (_, mut protocol) in self.custom_protocols.drain() {
+            let _ = protocol.disconnect().await;
+        }
+
         self.current_domain = None;
+        self.current_host = None;
+    }
+
+    // ========================================
+    // 交互式会话 (atp shell)
+    // ========================================
+
+    /// 连接虚拟机的协议并保持连接, 之后用 [`Self::execute_interactive`] 逐条执行动作
+    ///
+    /// 与场景执行相同, 单个协议连接失败不是致命错误, 可以通过 [`Self::session_state`] 查看。
+    pub async fn attach(&mut self, target_host: Option<&str>, domain_name: &str) -> Result<SessionState> {
+        self.run_started = Instant::now();
+        let token = self.cancel_token.child_token();
+        self.initialize_protocols(target_host, domain_name, &token).await?;
+        Ok(self.session_state().await)
+    }
+
+    /// 执行一个动作 (与场景中的同名步骤行为一致, 使用默认超时)
+    ///
+    /// 动作失败时返回失败的步骤报告或错误, 协议连接都保持不变。
+    pub async fn execute_interactive(&mut self, action: &Action, index: usize) -> Result<StepReport> {
+        let step = ScenarioStep {
+            name: None,
+            action: action.clone(),
+            verify: false,
+            timeout: None,
+            tags: Vec::new(),
+        };
+        let token = self.cancel_token.child_token();
+        self.execute_step(&step, index, &token).await
+    }
+
+    /// 当前连接的虚拟机与协议状态 (QMP 可用时查询运行状态)
+    pub async fn session_state(&mut self) -> SessionState {
+        let run_state = match &mut self.qmp_protocol {
+            Some(qmp) => match qmp.query_status().await {
+                Ok(response) => response
+                    .ret
+                    .as_ref()
+                    .and_then(|ret| ret["status"].as_str())
+                    .map(str::to_string),
+                Err(e) => {
+                    warn!("查询虚拟机运行状态失败: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        SessionState {
+            domain: self.current_domain_name(),
+            qmp: self.qmp_protocol.is_some(),
+            qga: self.qga_protocol.is_some(),
+            spice: self.spice_protocol.is_some(),
+            run_state,
+        }
+    }
+
+    /// 通过 QMP 截取虚拟机屏幕
+    ///
+    /// 文件由 QEMU 进程写入所在主机, `path` 需要是绝对路径; 扩展名为 `.png` 时输出 PNG, 否则为 PPM。
+    pub async fn screenshot(&mut self, path: &Path) -> Result<()> {
+        let qmp = self.qmp_protocol.as_mut()
+            .ok_or_else(|| ExecutorError::ProtocolError("QMP 未连接, 无法截屏".to_string()))?;
+
+        let format = path
+            .extension()
+            .filter(|ext| ext.eq_ignore_ascii_case("png"))
+            .map(|_| "png");
+        qmp.screendump(&path.to_string_lossy(), format)
+            .await
+            .map_err(|e| ExecutorError::ProtocolError(e.to_string()))
+    }
+
+    /// 通过已连接的 QGA 执行 PowerShell 脚本 (Windows 客户机)
+    ///
+    /// 复用 `attach` 建立的 QGA 连接, 多个脚本依次执行时不需要重新连接。
+    /// 超时后只停止等待, QGA 无法终止客户机中仍在运行的进程。
+    pub async fn exec_powershell(&self, script: &PowerShellScript, timeout: Duration) -> Result<PowerShellOutput> {
+        let qga = self.qga_protocol.as_ref()
+            .ok_or_else(|| ExecutorError::ProtocolError("QGA 未连接, 无法执行 PowerShell".to_string()))?;
+
+        let start = Instant::now();
+        let status = qga.exec_and_wait_timeout(script.command(), timeout)
+            .await
+            .map_err(|e| match e {
+                ProtocolError::Timeout => PowerShellError::Timeout(timeout),
+                e => PowerShellError::Protocol(e.to_string()),
+            })?;
+
+        Ok(PowerShellOutput::from_status(&status, script.as_json(), start.elapsed())?)
+    }
+
+    /// 断开交互式会话的协议连接
+    pub async fn detach(&mut self) {
+        self.cleanup_protocols().await;
     }
 
     /// 执行单个步骤
-    async fn execute_step(&mut self, step: &ScenarioStep, index: usize) -> Result<StepReport> {
+    async fn execute_step(
+        &mut self,
+        step: &ScenarioStep,
+        index: usize,
+        token: &CancellationToken,
+    ) -> Result<StepReport> {
         let start_time = Instant::now();
 
-        let step_timeout = step.timeout
-            .map(Duration::from_secs)
-            .unwrap_or(self.default_timeout);
+        let step_timeout = step.effective_timeout(self.default_timeout);
 
-        let result = timeout(step_timeout, self.execute_action(&step.action, index)).await;
+        let action = match &self.variables {
+            Some(variables) => Cow::Owned(variables.substitute(&step.action)?),
+            None => Cow::Borrowed(&step.action),
+        };
+
+        // 采样任务在步骤超时或被取消时随 sampler 一起停止
+        let sampler = self.start_metrics_sampler();
+        let block_before = match sampler {
+            Some(_) => self.query_block_stats().await,
+            None => None,
+        };
+
+        let result = cancellable(token, timeout(step_timeout, self.execute_action(&action, index))).await?;
 
         let duration_ms = start_time.elapsed().as_millis() as u64;
 
         match result {
             Ok(Ok(mut report)) => {
                 report.duration_ms = duration_ms;
+                if let Some(sampler) = sampler {
+                    let (mut metrics, points) = sampler.finish().await;
+                    self.save_metric_samples(&points).await;
+                    if let (Some(before), Some(after)) = (block_before, self.query_block_stats().await) {
+                        metrics = metrics.with_block_io(before, after);
+                    }
+                    report.metrics = Some(metrics).filter(|metrics| !metrics.is_empty());
+                }
                 if let Some(name) = &step.name {
                     report.description = name.clone();
                 }
@@ -251,11 +1044,47 @@ impl ScenarioRunner {
         }
     }
 
+    /// 启用采样且已连接虚拟机时, 开始后台采样当前虚拟机的资源
+    fn start_metrics_sampler(&self) -> Option<StepMetricsSampler> {
+        let interval = self.metrics_interval?;
+        let host_id = self.current_host.clone()?;
+        let domain = self.current_domain_name()?;
+
+        Some(StepMetricsSampler::start(Arc::clone(&self.transport_manager), host_id, domain, interval))
+    }
+
+    /// 配置了数据库时写入步骤期间的时序指标 (失败只记录告警)
+    async fn save_metric_samples(&self, points: &[EntityMetricSample]) {
+        let Some(storage) = &self.storage else {
+            return;
+        };
+        if let Err(e) = storage.metrics().insert_batch(points).await {
+            warn!("保存步骤时序指标失败: {}", e);
+        }
+    }
+
+    /// 通过 QMP 读取块设备累计 I/O (QMP 不可用或失败时返回 None)
+    async fn query_block_stats(&mut self) -> Option<BlockStats> {
+        let qmp = self.qmp_protocol.as_mut()?;
+
+        match timeout(BLOCKSTATS_TIMEOUT, qmp.query_blockstats()).await {
+            Ok(Ok(response)) => response.ret.as_ref().and_then(BlockStats::from_qmp),
+            Ok(Err(e)) => {
+                warn!("QMP query-blockstats 失败, 本步骤不记录磁盘读写量: {}", e);
+                None
+            }
+            Err(_) => {
+                warn!("QMP query-blockstats 超时, 本步骤不记录磁盘读写量");
+                None
+            }
+        }
+    }
+
     /// 执行具体动作
     async fn execute_action(&mut self, action: &Action, index: usize) -> Result<StepReport> {
         match action {
-            Action::SendKey { key } => {
-                self.execute_send_key(key, index).await
+            Action::SendKey { key, hold_ms } => {
+                self.execute_send_key(key, *hold_ms, index).await
             }
             Action::SendText { text } => {
                 self.execute_send_text(text, index).await
@@ -270,12 +1099,20 @@ impl ScenarioRunner {
                 self.execute_wait(*duration, index).await
             }
             Action::Custom { data } => {
-                warn!("自定义动作尚未完全实现: {:?}", data);
-                Ok(StepReport::success(index, "自定义动作（跳过）"))
+                // { protocol: "<已注册协议名>", payload } 路由到注册表中的协议
+                match data.get("protocol").and_then(|v| v.as_str()) {
+                    Some(protocol) => {
+                        self.execute_custom_protocol(protocol, data.get("payload"), index).await
+                    }
+                    None => {
+                        warn!("自定义动作未指定协议: {:?}", data);
+                        Ok(StepReport::success(index, "自定义动作（跳过）"))
+                    }
+                }
             }
             // VDI 平台操作
-            Action::VdiCreateDeskPool { name, template_id, count } => {
-                self.execute_vdi_create_desk_pool(name, template_id, *count, index).await
+            Action::VdiCreateDeskPool { name, template_id, count, advanced } => {
+                self.execute_vdi_create_desk_pool(name, template_id, *count, advanced.as_ref(), index).await
             }
             Action::VdiEnableDeskPool { pool_id } => {
                 self.execute_vdi_enable_desk_pool(pool_id, index).await
@@ -304,28 +1141,85 @@ impl ScenarioRunner {
             Action::VdiGetDeskPoolDomains { pool_id } => {
                 self.execute_vdi_get_desk_pool_domains(pool_id, index).await
             }
+            Action::VdiMigrateAndVerify { domain, target_host, max_downtime_ms, timeout_secs } => {
+                self.execute_vdi_migrate_and_verify(domain, target_host, *max_downtime_ms, *timeout_secs, index).await
+            }
+            Action::VdiMigrateDomain { domain_id, target_host_id, wait, timeout_secs } => {
+                self.execute_vdi_migrate_domain(domain_id, target_host_id, *wait, *timeout_secs, index).await
+            }
+            Action::VdiVerifyRestorePoints { pattern, marker_path, method, timeout_secs } => {
+                self.execute_vdi_verify_restore_points(pattern, marker_path.as_deref(), *method, *timeout_secs, index).await
+            }
             // 验证步骤
             Action::VerifyDomainStatus { domain_id, expected_status, timeout_secs } => {
                 self.verify_domain_status(domain_id, expected_status, *timeout_secs, index).await
             }
+            Action::VerifyDomainOnHost { domain, host_id } => {
+                self.verify_domain_on_host(domain, host_id, index).await
+            }
             Action::VerifyAllDomainsRunning { pool_id, timeout_secs } => {
                 self.verify_all_domains_running(pool_id, *timeout_secs, index).await
             }
             Action::VerifyCommandSuccess { timeout_secs } => {
                 self.verify_command_success(*timeout_secs, index).await
             }
+            Action::QueryWindowsEventLog { log, level, since_minutes, expect_max_count } => {
+                self.query_windows_event_log(*log, *level, *since_minutes, *expect_max_count, index).await
+            }
+            Action::GuestUniquify { hostname_template, run_sysprep } => {
+                self.guest_uniquify(hostname_template, *run_sysprep, index).await
+            }
+            Action::VerifyFileInGuest { path, expect_exists, expect_sha256, expect_contains, max_bytes } => {
+                self.verify_file_in_guest(
+                    path,
+                    *expect_exists,
+                    expect_sha256.as_deref(),
+                    expect_contains.as_deref(),
+                    *max_bytes,
+                    index,
+                )
+                .await
+            }
+            // 宿主机操作
+            Action::SshFetchFile { host, remote_path, local_path } => {
+                self.execute_ssh_fetch_file(host, remote_path, local_path, index).await
+            }
+            Action::SshExec { host, command, idle_timeout_secs } => {
+                self.execute_ssh_exec(host, command, *idle_timeout_secs, index).await
+            }
+            // 步骤组在加载 YAML 时已经展开, 到这里说明场景来自其他途径 (如 JSON)
+            Action::RunGroup { name, .. } => Err(ExecutorError::ScenarioLoadFailed(format!(
+                "步骤组 {} 未展开: run_group 只能在 YAML 场景文件中使用", name
+            ))),
+            // 更新版本的场景文件中无法识别的动作
+            Action::Unsupported { .. } => Err(ExecutorError::ScenarioLoadFailed(
+                action.unsupported_reason(self.scenario_schema_version).unwrap_or_default(),
+            )),
         }
     }
 
     /// 执行发送按键
-    async fn execute_send_key(&mut self, key: &str, index: usize) -> Result<StepReport> {
-        info!("发送按键: {}", key);
+    ///
+    /// `key` 可以是按键组合 (如 `ctrl+alt+del`)。优先使用 QMP（hold_ms 映射为 hold-time），
+    /// QMP 未初始化时通过 SPICE 以按下 + 等待 + 释放的方式模拟
+    async fn execute_send_key(&mut self, key: &str, hold_ms: Option<u32>, index: usize) -> Result<StepReport> {
+        info!("发送按键: {} (按住: {:?} ms)", key, hold_ms);
+
+        let strokes = KeyCombo::parse(key)
+            .map_err(|e| ExecutorError::ProtocolError(e.to_string()))?;
 
-        // 使用 QMP 协议发送按键
         if let Some(qmp) = &mut self.qmp_protocol {
-            qmp.send_key(key)
+            for stroke in &strokes {
+                qmp.send_keys(stroke.qcodes(), hold_ms)
+                    .await
+                    .map_err(|e| ExecutorError::protocol("QMP send_key 失败", e))?;
+            }
+
+            Ok(StepReport::success(index, &format!("发送按键: {}", key)))
+        } else if let Some(spice) = &self.spice_protocol {
+            spice.send_key_combo(key, hold_ms)
                 .await
-                .map_err(|e| ExecutorError::ProtocolError(format!("QMP send_key 失败: {}", e)))?;
+                .map_err(|e| ExecutorError::protocol("SPICE 按键失败", e))?;
 
             Ok(StepReport::success(index, &format!("发送按键: {}", key)))
         } else {
@@ -334,27 +1228,28 @@ impl ScenarioRunner {
     }
 
     /// 执行发送文本
+    ///
+    /// 按场景的 `keyboard_layout` 把文本转换为按键, 优先使用 QMP 逐个字符发送,
+    /// QMP 未初始化时通过 SPICE 发送
     async fn execute_send_text(&mut self, text: &str, index: usize) -> Result<StepReport> {
-        info!("发送文本: {}", text);
+        let layout = self.keyboard_layout;
+        info!("发送文本 ({}): {}", layout, text);
+
+        let strokes = KeyMapper::map_strokes(text, layout)
+            .map_err(|e| ExecutorError::ProtocolError(e.to_string()))?;
 
-        // 使用 QMP 协议发送文本 (将文本拆分为按键序列)
         if let Some(qmp) = &mut self.qmp_protocol {
-            // 将文本转换为按键序列
-            let keys: Vec<&str> = text.chars()
-                .map(|c| {
-                    // 这里简化处理,实际需要完整的字符到 QKeyCode 映射
-                    match c {
-                        'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_string().leak(),
-                        ' ' => "spc",
-                        '\n' => "ret",
-                        _ => "unknown", // 需要更完整的映射表
-                    }
-                })
-                .collect();
+            for stroke in &strokes {
+                qmp.send_keys(stroke.qcodes(), None)
+                    .await
+                    .map_err(|e| ExecutorError::protocol("QMP send_keys 失败", e))?;
+            }
 
-            qmp.send_keys(keys, Some(100))
+            Ok(StepReport::success(index, &format!("发送文本: {}", text)))
+        } else if let Some(spice) = &self.spice_protocol {
+            spice.send_text(text, layout)
                 .await
-                .map_err(|e| ExecutorError::ProtocolError(format!("QMP send_keys 失败: {}", e)))?;
+                .map_err(|e| ExecutorError::protocol("SPICE 发送文本失败", e))?;
 
             Ok(StepReport::success(index, &format!("发送文本: {}", text)))
         } else {
@@ -366,23 +1261,23 @@ impl ScenarioRunner {
     async fn execute_mouse_click(&mut self, x: i32, y: i32, button: &str, index: usize) -> Result<StepReport> {
         info!("鼠标点击: ({}, {}) 按钮: {}", x, y, button);
 
+        // 将按钮字符串转换为 MouseButton 枚举
+        let mouse_button = match button.to_lowercase().as_str() {
+            "left" => MouseButton::Left,
+            "right" => MouseButton::Right,
+            "middle" => MouseButton::Middle,
+            _ => {
+                warn!("未知的鼠标按钮: {}, 使用默认左键", button);
+                MouseButton::Left
+            }
+        };
+
         // 使用 SPICE 协议发送鼠标操作
         if let Some(spice) = &mut self.spice_protocol {
-            // 将按钮字符串转换为 MouseButton 枚举
-            let mouse_button = match button.to_lowercase().as_str() {
-                "left" => MouseButton::Left,
-                "right" => MouseButton::Right,
-                "middle" => MouseButton::Middle,
-                _ => {
-                    warn!("未知的鼠标按钮: {}, 使用默认左键", button);
-                    MouseButton::Left
-                }
-            };
-
             // 首先移动鼠标到目标位置（使用绝对坐标）
             spice.send_mouse_move(x as u32, y as u32, 0)
                 .await
-                .map_err(|e| ExecutorError::ProtocolError(format!("SPICE 鼠标移动失败: {}", e)))?;
+                .map_err(|e| ExecutorError::protocol("SPICE 鼠标移动失败", e))?;
 
             // 等待一小段时间确保位置更新
             tokio::time::sleep(Duration::from_millis(50)).await;
@@ -390,7 +1285,7 @@ impl ScenarioRunner {
             // 发送鼠标点击（按下）
             spice.send_mouse_click(mouse_button, true)
                 .await
-                .map_err(|e| ExecutorError::ProtocolError(format!("SPICE 鼠标按下失败: {}", e)))?;
+                .map_err(|e| ExecutorError::protocol("SPICE 鼠标按下失败", e))?;
 
             // 短暂延迟模拟真实点击
             tokio::time::sleep(Duration::from_millis(50)).await;
@@ -398,82 +1293,435 @@ impl ScenarioRunner {
             // 发送鼠标释放
             spice.send_mouse_click(mouse_button, false)
                 .await
-                .map_err(|e| ExecutorError::ProtocolError(format!("SPICE 鼠标释放失败: {}", e)))?;
+                .map_err(|e| ExecutorError::protocol("SPICE 鼠标释放失败", e))?;
+
+            let mut report = StepReport::success(index, &format!("鼠标点击: ({}, {}) 按钮: {}", x, y, button));
+            report.output = Some("输入通道: SPICE".to_string());
+            Ok(report)
+        } else if let Some(qmp) = &mut self.qmp_protocol {
+            // SPICE 未连接时通过 QMP input-send-event 注入 (需要绝对定位设备, 如 usb-tablet)
+            qmp.send_mouse_click(x.max(0) as u32, y.max(0) as u32, mouse_button)
+                .await
+                .map_err(|e| ExecutorError::protocol("QMP 鼠标点击失败", e))?;
+
+            let mut report = StepReport::success(index, &format!("鼠标点击: ({}, {}) 按钮: {} [QMP]", x, y, button));
+            report.output = Some("输入通道: QMP input-send-event".to_string());
+            Ok(report)
+        } else if let Some(qga) = &self.qga_protocol {
+            // SPICE 与 QMP 均不可用时，尝试通过 QGA 执行脚本模拟鼠标操作（备用方案）
+            warn!("SPICE 与 QMP 协议均未初始化，尝试通过 QGA 执行鼠标脚本");
+
+            // 在 Linux 中可以使用 xdotool 模拟鼠标
+            let script = format!("DISPLAY=:0 xdotool mousemove {} {} click {}",
+                x, y,
+                match mouse_button {
+                    MouseButton::Middle => "2",
+                    MouseButton::Right => "3",
+                    _ => "1",
+                }
+            );
+
+            let status = qga.exec_shell(&script)
+                .await
+                .map_err(|e| ExecutorError::protocol("QGA 执行鼠标脚本失败", e))?;
+
+            if let Some(exit_code) = status.exit_code {
+                if exit_code != 0 {
+                    return Ok(StepReport::failed(
+                        index,
+                        &format!("鼠标点击: ({}, {})", x, y),
+                        "xdotool 执行失败（可能未安装）",
+                    ));
+                }
+            }
+
+            let mut report = StepReport::success(index, &format!("鼠标点击: ({}, {}) [QGA/xdotool]", x, y));
+            report.output = Some("输入通道: QGA/xdotool".to_string());
+            Ok(report)
+        } else {
+            Err(ExecutorError::ProtocolError(
+                "SPICE、QMP 和 QGA 协议均未初始化，无法执行鼠标操作".to_string()
+            ))
+        }
+    }
+
+    /// 执行命令
+    async fn execute_command(&mut self, command: &str, index: usize) -> Result<StepReport> {
+        info!("执行命令: {}", command);
+
+        // 使用 QGA 协议执行命令, Windows 客户机通过 cmd 执行
+        if let Some(qga) = &self.qga_protocol {
+            let platform = match self.guest_platform {
+                Some(platform) => platform,
+                None => {
+                    let platform = match qga.get_osinfo().await {
+                        Ok(os_info) => GuestPlatform::from_os_info(&os_info),
+                        Err(e) => {
+                            warn!("QGA 获取系统信息失败, 按 Linux 执行命令: {}", e);
+                            GuestPlatform::Linux
+                        }
+                    };
+                    self.guest_platform = Some(platform);
+                    platform
+                }
+            };
+
+            let status = match platform {
+                GuestPlatform::Windows => qga.exec_windows(command, WinShell::Cmd).await,
+                GuestPlatform::Linux => qga.exec_shell(command).await,
+            }
+            .map_err(|e| ExecutorError::protocol("QGA 执行命令失败", e))?;
+
+            // 检查退出码
+            if let Some(exit_code) = status.exit_code {
+                if exit_code != 0 {
+                    let stderr = status.decode_stderr()
+                        .unwrap_or_else(|| "无错误输出".to_string());
+
+                    return Ok(StepReport::failed(
+                        index,
+                        &format!("执行命令: {}", command),
+                        &format!("命令执行失败 (退出码: {}): {}", exit_code, stderr),
+                    ));
+                }
+            }
+
+            // 获取输出
+            let stdout = status.decode_stdout()
+                .unwrap_or_else(|| "无输出".to_string());
+
+            let mut report = StepReport::success(index, &format!("执行命令: {}", command));
+            report.output = Some(stdout);
+            Ok(report)
+        } else {
+            Err(ExecutorError::ProtocolError("QGA 协议未初始化".to_string()))
+        }
+    }
+
+    /// 查询 Windows 事件日志
+    async fn query_windows_event_log(
+        &mut self,
+        log: EventLogName,
+        level: EventLevel,
+        since_minutes: u64,
+        expect_max_count: u32,
+        index: usize,
+    ) -> Result<StepReport> {
+        info!("查询 Windows 事件日志: {} (级别 {}, 最近 {} 分钟)", log.as_str(), level.as_str(), since_minutes);
+
+        let qga = self.qga_protocol.as_ref()
+            .ok_or_else(|| ExecutorError::ProtocolError("QGA 协议未初始化".to_string()))?;
+
+        let os_info = qga.get_osinfo()
+            .await
+            .map_err(|e| ExecutorError::protocol("QGA 获取系统信息失败", e))?;
+
+        if !os_info.is_windows() {
+            return Err(ExecutorError::StepExecutionFailed(format!(
+                "事件日志查询仅支持 Windows 客户机, 当前系统: {}",
+                os_info.pretty_name.or(os_info.id).unwrap_or_else(|| "未知".to_string())
+            )));
+        }
+
+        let script = event_log::build_query_script(log, level, since_minutes);
+        let status = qga.exec_powershell(&script)
+            .await
+            .map_err(|e| ExecutorError::protocol("QGA exec_powershell 失败", e))?;
+
+        if let Some(exit_code) = status.exit_code {
+            if exit_code != 0 {
+                let stderr = status.decode_stderr()
+                    .unwrap_or_else(|| "无错误输出".to_string());
+                return Err(ExecutorError::StepExecutionFailed(format!(
+                    "事件日志查询失败 (退出码: {}): {}", exit_code, stderr
+                )));
+            }
+        }
+
+        let events = event_log::parse_events(&status.decode_stdout().unwrap_or_default())?;
+        let description = format!("查询事件日志: {} {}", log.as_str(), level.as_str());
+
+        let mut report = if events.len() > expect_max_count as usize {
+            StepReport::failed(
+                index,
+                &description,
+                &format!("事件数 {} 超过预期上限 {}", events.len(), expect_max_count),
+            )
+        } else {
+            StepReport::success(index, &description)
+        };
+
+        let mut output = format!("共 {} 条事件", events.len());
+        for event in events.iter().take(EVENT_LOG_SUMMARY_LIMIT) {
+            output.push('\n');
+            output.push_str(&event.summary());
+        }
+        report.output = Some(output);
+
+        Ok(report)
+    }
+
+    /// 克隆后唯一化客户机
+    async fn guest_uniquify(
+        &mut self,
+        hostname_template: &str,
+        run_sysprep: bool,
+        index: usize,
+    ) -> Result<StepReport> {
+        let qga = self.qga_protocol.as_ref()
+            .ok_or_else(|| ExecutorError::ProtocolError("QGA 协议未初始化".to_string()))?;
+
+        let vm_name = self.current_domain.as_ref()
+            .and_then(|domain| domain.get_name().ok())
+            .unwrap_or_default();
+        let hostname = uniquify::render_hostname(hostname_template, self.vm_index, &vm_name)?;
+
+        let os_info = qga.get_osinfo()
+            .await
+            .map_err(|e| ExecutorError::protocol("QGA 获取系统信息失败", e))?;
+        let platform = GuestPlatform::from_os_info(&os_info);
+        uniquify::check_hostname(&hostname, platform)?;
+
+        info!("唯一化客户机: 主机名 = {}, 平台 = {:?}, sysprep = {}", hostname, platform, run_sysprep);
+
+        let script = uniquify::build_script(platform, &hostname, run_sysprep);
+        let status = match platform {
+            GuestPlatform::Windows => qga.exec_powershell(&script).await,
+            GuestPlatform::Linux => qga.exec_shell(&script).await,
+        }
+        .map_err(|e| ExecutorError::protocol("QGA 执行唯一化脚本失败", e))?;
+
+        if let Some(exit_code) = status.exit_code {
+            if exit_code != 0 {
+                let stderr = status.decode_stderr()
+                    .unwrap_or_else(|| "无错误输出".to_string());
+                return Err(ExecutorError::StepExecutionFailed(format!(
+                    "唯一化脚本执行失败 (退出码: {}): {}", exit_code, stderr
+                )));
+            }
+        }
+
+        Self::wait_for_guest_reboot(qga, &hostname).await?;
+
+        let mut report = StepReport::success(index, &format!("唯一化客户机: {}", hostname));
+        report.output = Some(format!("主机名已设置为 {}, 客户机已重启", hostname));
+        Ok(report)
+    }
+
+    /// 校验客户机中的文件
+    async fn verify_file_in_guest(
+        &mut self,
+        path: &str,
+        expect_exists: bool,
+        expect_sha256: Option<&str>,
+        expect_contains: Option<&str>,
+        max_bytes: u64,
+        index: usize,
+    ) -> Result<StepReport> {
+        info!("校验客户机文件: {}", path);
+
+        let qga = self.qga_protocol.as_ref()
+            .ok_or_else(|| ExecutorError::ProtocolError("QGA 协议未初始化".to_string()))?;
+
+        let expected_sha256 = expect_sha256.map(Sha256Expectation::parse).transpose()?;
+        let region_size = expected_sha256
+            .as_ref()
+            .map_or(guest_file::DEFAULT_REGION_SIZE, Sha256Expectation::region_size);
+        // 只检查内容时读够 max_bytes 即可; 只检查存在性时不读取内容
+        let head_bytes = if expect_contains.is_some() { max_bytes } else { 0 };
+
+        let file = guest_file::read_guest_file(qga, path, region_size, head_bytes, expected_sha256.is_some()).await?;
+        let description = format!("校验客户机文件: {}", path);
+
+        let (size, contents) = match (file, expect_exists) {
+            (None, true) => return Ok(StepReport::failed(index, &description, "文件不存在")),
+            (None, false) => {
+                let mut report = StepReport::success(index, &description);
+                report.output = Some("文件不存在".to_string());
+                return Ok(report);
+            }
+            (Some((size, _)), false) => {
+                return Ok(StepReport::failed(
+                    index,
+                    &description,
+                    &format!("文件存在 (大小 {} 字节), 期望不存在", size),
+                ));
+            }
+            (Some(file), true) => file,
+        };
+
+        let mut failures = Vec::new();
+        let mut output = vec![format!("文件大小: {} 字节", size)];
+
+        if let Some(expected) = &expected_sha256 {
+            output.push(format!("SHA-256: {}", contents.sha256));
+            output.push(format!("分区摘要: {}", contents.manifest()));
+            if let Err(reason) = contents.check_sha256(expected) {
+                failures.push(reason);
+            }
+        }
+
+        if let Some(needle) = expect_contains {
+            if !contents.text().contains(needle) {
+                let truncated = if size > max_bytes { ", 超过 max_bytes 的部分未检查" } else { "" };
+                failures.push(format!(
+                    "前 {} 字节中未找到 {:?} (文件大小 {} 字节{})",
+                    contents.head.len(), needle, size, truncated
+                ));
+            }
+        }
+
+        let mut report = if failures.is_empty() {
+            StepReport::success(index, &description)
+        } else {
+            StepReport::failed(index, &description, &failures.join("; "))
+        };
+        report.output = Some(output.join("\n"));
+        Ok(report)
+    }
+
+    /// 在宿主机上执行命令, 输出逐行写入日志
+    async fn execute_ssh_exec(
+        &self,
+        host: &str,
+        command: &[String],
+        idle_timeout_secs: Option<u64>,
+        index: usize,
+    ) -> Result<StepReport> {
+        let command_line = quote_command(command);
+        let idle_timeout = Duration::from_secs(idle_timeout_secs.unwrap_or(DEFAULT_SSH_IDLE_TIMEOUT_SECS));
+        info!("在宿主机 {} 上执行: {}", host, command_line);
+
+        let on_stdout = |line: &str| info!("[{}] {}", host, line);
+        let on_stderr = |line: &str| warn!("[{}] {}", host, line);
+        let output = self.transport_manager
+            .exec_host_command_streaming(host, command, idle_timeout, &on_stdout, &on_stderr)
+            .await
+            .map_err(|e| ExecutorError::transport("宿主机命令执行失败", e))?;
+
+        let description = format!("宿主机命令: {}", command_line);
+        if !output.success() {
+            let stderr = output.stderr.trim();
+            return Ok(StepReport::failed(
+                index,
+                &description,
+                &format!(
+                    "命令执行失败 (退出码: {}): {}",
+                    output.exit_code.map_or_else(|| "无".to_string(), |code| code.to_string()),
+                    if stderr.is_empty() { "无错误输出" } else { stderr }
+                ),
+            ));
+        }
+
+        let mut report = StepReport::success(index, &description);
+        report.output = Some(output.stdout);
+        Ok(report)
+    }
+
+    /// 下载宿主机上的文件, 相对路径保存到工件目录下
+    async fn execute_ssh_fetch_file(
+        &self,
+        host: &str,
+        remote_path: &str,
+        local_path: &str,
+        index: usize,
+    ) -> Result<StepReport> {
+        let local = match &self.artifact_dir {
+            Some(dir) if Path::new(local_path).is_relative() => dir.join(local_path),
+            _ => PathBuf::from(local_path),
+        };
+        info!("下载宿主机文件: {}:{} -> {}", host, remote_path, local.display());
+
+        let bytes = self.transport_manager
+            .download_host_file(host, remote_path, &local)
+            .await
+            .map_err(|e| ExecutorError::transport("下载宿主机文件失败", e))?;
+
+        let mut report = StepReport::success(index, &format!("下载宿主机文件: {}:{}", host, remote_path));
+        report.output = Some(format!("已保存到 {} ({} 字节)", local.display(), bytes));
+        Ok(report)
+    }
+
+    /// 等待客户机重启完成
+    ///
+    /// 先等待 QGA 失去响应 (开始重启), 再等待 QGA 恢复且主机名生效。
+    /// 总时长受步骤超时约束 (未设置时见 [`uniquify::DEFAULT_UNIQUIFY_TIMEOUT`])。
+    async fn wait_for_guest_reboot(qga: &QgaProtocol, hostname: &str) -> Result<()> {
+        let shutdown_deadline = Instant::now() + GUEST_SHUTDOWN_TIMEOUT;
+        while qga.ping().await.is_ok() {
+            if Instant::now() >= shutdown_deadline {
+                return Err(ExecutorError::StepExecutionFailed(format!(
+                    "客户机在 {:?} 内未开始重启",
+                    GUEST_SHUTDOWN_TIMEOUT
+                )));
+            }
+            tokio::time::sleep(GUEST_REBOOT_POLL_INTERVAL).await;
+        }
 
-            Ok(StepReport::success(index, &format!("鼠标点击: ({}, {}) 按钮: {}", x, y, button)))
-        } else {
-            // 如果 SPICE 未连接，尝试通过 QGA 执行脚本模拟鼠标操作（备用方案）
-            if let Some(qga) = &self.qga_protocol {
-                warn!("SPICE 协议未初始化，尝试通过 QGA 执行鼠标脚本");
-
-                // 在 Linux 中可以使用 xdotool 模拟鼠标
-                let script = format!("DISPLAY=:0 xdotool mousemove {} {} click {}",
-                    x, y,
-                    match button.to_lowercase().as_str() {
-                        "left" => "1",
-                        "middle" => "2",
-                        "right" => "3",
-                        _ => "1",
-                    }
-                );
+        info!("客户机正在重启, 等待 QGA 恢复");
 
-                let status = qga.exec_shell(&script)
-                    .await
-                    .map_err(|e| ExecutorError::ProtocolError(format!("QGA 执行鼠标脚本失败: {}", e)))?;
-
-                if let Some(exit_code) = status.exit_code {
-                    if exit_code != 0 {
-                        return Ok(StepReport::failed(
-                            index,
-                            &format!("鼠标点击: ({}, {})", x, y),
-                            "xdotool 执行失败（可能未安装）",
-                        ));
-                    }
-                }
+        loop {
+            tokio::time::sleep(GUEST_REBOOT_POLL_INTERVAL).await;
 
-                Ok(StepReport::success(index, &format!("鼠标点击: ({}, {}) [QGA/xdotool]", x, y)))
-            } else {
-                Err(ExecutorError::ProtocolError(
-                    "SPICE 和 QGA 协议均未初始化，无法执行鼠标操作".to_string()
-                ))
+            match qga.get_host_name().await {
+                // Windows 计算机名不区分大小写, QGA 可能返回大写形式
+                Ok(current) if current.eq_ignore_ascii_case(hostname) => return Ok(()),
+                Ok(current) => debug!("客户机主机名尚未生效: {}", current),
+                Err(e) => debug!("QGA 尚未恢复: {}", e),
             }
         }
     }
 
-    /// 执行命令
-    async fn execute_command(&mut self, command: &str, index: usize) -> Result<StepReport> {
-        info!("执行命令: {}", command);
+    /// 通过已注册的自定义协议发送数据并接收响应
+    async fn execute_custom_protocol(
+        &mut self,
+        name: &str,
+        payload: Option<&serde_json::Value>,
+        index: usize,
+    ) -> Result<StepReport> {
+        info!("执行自定义协议动作: {}", name);
 
-        // 使用 QGA 协议执行命令
-        if let Some(qga) = &self.qga_protocol {
-            let status = qga.exec_shell(command)
+        if !self.custom_protocols.contains_key(name) {
+            let mut protocol = self.protocol_registry
+                .create(name)
                 .await
-                .map_err(|e| ExecutorError::ProtocolError(format!("QGA exec_shell 失败: {}", e)))?;
+                .map_err(|e| ExecutorError::ProtocolError(e.to_string()))?;
 
-            // 检查退出码
-            if let Some(exit_code) = status.exit_code {
-                if exit_code != 0 {
-                    let stderr = status.decode_stderr()
-                        .unwrap_or_else(|| "无错误输出".to_string());
+            let domain = self.current_domain.as_ref()
+                .ok_or_else(|| ExecutorError::ConfigError(
+                    format!("自定义协议 {} 需要指定目标虚拟机 (target_domain)", name)
+                ))?;
 
-                    return Ok(StepReport::failed(
-                        index,
-                        &format!("执行命令: {}", command),
-                        &format!("命令执行失败 (退出码: {}): {}", exit_code, stderr),
-                    ));
-                }
-            }
+            protocol.connect(domain)
+                .await
+                .map_err(|e| ExecutorError::protocol(format!("自定义协议 {} 连接失败", name), e))?;
 
-            // 获取输出
-            let stdout = status.decode_stdout()
-                .unwrap_or_else(|| "无输出".to_string());
+            self.custom_protocols.insert(name.to_string(), protocol);
+        }
 
-            let mut report = StepReport::success(index, &format!("执行命令: {}", command));
-            report.output = Some(stdout);
-            Ok(report)
-        } else {
-            Err(ExecutorError::ProtocolError("QGA 协议未初始化".to_string()))
+        let data = match payload {
+            None | Some(serde_json::Value::Null) => Vec::new(),
+            Some(serde_json::Value::String(text)) => text.as_bytes().to_vec(),
+            Some(value) => serde_json::to_vec(value)
+                .map_err(|e| ExecutorError::SerdeError(e.to_string()))?,
+        };
+
+        let protocol = self.custom_protocols.get_mut(name)
+            .ok_or_else(|| ExecutorError::ProtocolError(format!("协议 {} 不存在", name)))?;
+
+        protocol.send(&data)
+            .await
+            .map_err(|e| ExecutorError::protocol(format!("自定义协议 {} 发送失败", name), e))?;
+
+        let response = protocol.receive()
+            .await
+            .map_err(|e| ExecutorError::protocol(format!("自定义协议 {} 接收失败", name), e))?;
+
+        let mut report = StepReport::success(index, &format!("自定义协议: {}", name));
+        if !response.is_empty() {
+            report.output = Some(String::from_utf8_lossy(&response).to_string());
         }
+        Ok(report)
     }
 
     /// 执行等待
@@ -495,6 +1743,7 @@ impl ScenarioRunner {
         name: &str,
         template_id: &str,
         count: u32,
+        advanced: Option<&DeskPoolAdvanced>,
         index: usize
     ) -> Result<StepReport> {
         info!("创建桌面池: {} (模板: {}, 数量: {})", name, template_id, count);
@@ -502,21 +1751,20 @@ impl ScenarioRunner {
         let vdi_client = self.vdi_client.as_ref()
             .ok_or_else(|| ExecutorError::ConfigError("VDI 客户端未初始化".to_string()))?;
 
-        // 构造创建桌面池请求
-        let request = CreateDeskPoolRequest {
-            name: name.to_string(),
-            template_id: template_id.to_string(),
-            count,
-            vcpu: 2,    // 默认值，可以从场景配置中获取
-            memory: 2048, // 默认 2GB，可以从场景配置中获取
-        };
+        // 构造创建桌面池请求, 场景中的高级参数覆盖默认值
+        let mut request = CreateDeskPoolRequest::basic(name, template_id, count);
+        if let Some(advanced) = advanced {
+            request = request.with_advanced(advanced.clone());
+        }
 
         // 调用 VDI 平台 API 创建桌面池
-        vdi_client.desk_pool()
+        let pool = vdi_client.desk_pool()
             .create(request)
             .await
             .map_err(|e| ExecutorError::TransportError(format!("创建桌面池失败: {}", e)))?;
 
+        self.resource_tracker.register(ResourceKind::DeskPool, &pool.id, Some(name), index);
+
         Ok(StepReport::success(index, &format!("创建桌面池: {}", name)))
     }
 
@@ -574,6 +1822,8 @@ impl ScenarioRunner {
             .await
             .map_err(|e| ExecutorError::TransportError(format!("删除桌面池失败: {}", e)))?;
 
+        self.resource_tracker.release(ResourceKind::DeskPool, pool_id);
+
         Ok(StepReport::success(index, &format!("删除桌面池: {}", pool_id)))
     }
 
@@ -650,6 +1900,8 @@ impl ScenarioRunner {
             .await
             .map_err(|e| ExecutorError::TransportError(format!("删除虚拟机失败: {}", e)))?;
 
+        self.resource_tracker.release(ResourceKind::Domain, domain_id);
+
         Ok(StepReport::success(index, &format!("删除虚拟机: {}", domain_id)))
     }
 
@@ -694,6 +1946,357 @@ impl ScenarioRunner {
         Ok(report)
     }
 
+    /// 迁移虚拟机并验证中断时长与归属
+    async fn execute_vdi_migrate_and_verify(
+        &mut self,
+        domain_id: &str,
+        target_host: &str,
+        max_downtime_ms: u64,
+        timeout_secs: Option<u64>,
+        index: usize
+    ) -> Result<StepReport> {
+        info!("迁移虚拟机: {} -> {}", domain_id, target_host);
+
+        let vdi_client = self.vdi_client.clone()
+            .ok_or_else(|| ExecutorError::ConfigError("VDI 客户端未初始化".to_string()))?;
+
+        let timeout_duration = timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_timeout);
+
+        // 平台的虚拟机 ID 映射为 libvirt 名称, 目标主机地址映射为已注册的主机
+        let domain_name = vdi_client.domain()
+            .get(domain_id)
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询虚拟机失败: {}", e)))?
+            .name;
+        let target_host_id = self.resolve_transport_host(target_host).await?;
+
+        self.transport_manager.invalidate_domain_cache().await;
+        let (source_host_id, _) = self.transport_manager
+            .find_domain(&domain_name)
+            .await
+            .map_err(|e| ExecutorError::TransportError(e.to_string()))?
+            .ok_or_else(|| ExecutorError::StepExecutionFailed(format!("未在任何主机上找到虚拟机: {}", domain_name)))?;
+        if source_host_id == target_host_id {
+            return Err(ExecutorError::StepExecutionFailed(format!(
+                "虚拟机 {} 已在目标主机 {} 上", domain_name, target_host_id
+            )));
+        }
+
+        let mut qga = self.connect_qga(&source_host_id, &domain_name).await?;
+
+        let started = Instant::now();
+        vdi_client.domain()
+            .migrate(domain_id, target_host)
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("迁移虚拟机失败: {}", e)))?;
+
+        // 迁移期间周期 ping, 直到虚拟机只在目标主机上运行且客户机可达
+        let mut samples = Vec::new();
+        let mut ownership: Option<OwnershipCheck> = None;
+        let mut last_ownership_poll: Option<Instant> = None;
+        let mut migrated_at: Option<Duration> = None;
+
+        while started.elapsed() < timeout_duration {
+            let offset_ms = started.elapsed().as_millis() as u64;
+            let mut ok = matches!(timeout(MIGRATION_PING_TIMEOUT, qga.ping()).await, Ok(Ok(())));
+
+            // 源主机上的 QGA 通道在迁移完成后失效, 失败时改连目标主机再试一次
+            if !ok {
+                if let Ok(reconnected) = self.connect_qga(&target_host_id, &domain_name).await {
+                    let _ = qga.disconnect().await;
+                    qga = reconnected;
+                    ok = matches!(timeout(MIGRATION_PING_TIMEOUT, qga.ping()).await, Ok(Ok(())));
+                }
+            }
+            samples.push(PingSample { offset_ms, ok });
+
+            let poll_due = last_ownership_poll
+                .is_none_or(|at| at.elapsed() >= MIGRATION_OWNERSHIP_POLL_INTERVAL);
+            if migrated_at.is_none() && poll_due {
+                last_ownership_poll = Some(Instant::now());
+                let check = self.check_ownership(&domain_name, &source_host_id, &target_host_id).await;
+                if check.is_ok() {
+                    migrated_at = Some(started.elapsed());
+                }
+                ownership = Some(check);
+            }
+
+            if migrated_at.is_some() && ok {
+                break;
+            }
+            tokio::time::sleep(MIGRATION_PING_INTERVAL).await;
+        }
+
+        // 迁移完成后再确认一次归属, 并让后续步骤的 QGA 操作指向目标主机
+        let ownership = match migrated_at {
+            Some(_) => self.check_ownership(&domain_name, &source_host_id, &target_host_id).await,
+            None => ownership.unwrap_or_else(|| {
+                OwnershipCheck::evaluate::<String>(&domain_name, &source_host_id, &target_host_id, &[])
+            }),
+        };
+        self.transport_manager.invalidate_domain_cache().await;
+        let _ = qga.disconnect().await;
+        if ownership.is_ok() {
+            self.follow_migrated_domain(&target_host_id, &domain_name).await;
+        }
+
+        let downtime = DowntimeStats::from_samples(&samples);
+        let mut problems = Vec::new();
+        if migrated_at.is_none() {
+            problems.push(format!("{}s 内未完成迁移", timeout_duration.as_secs()));
+        }
+        problems.extend(ownership.problems());
+        if !downtime.within(max_downtime_ms) {
+            problems.push(format!(
+                "最长中断 {}ms 超过上限 {}ms",
+                downtime.max_downtime_ms, max_downtime_ms
+            ));
+        }
+
+        let description = format!("迁移虚拟机: {} -> {}", domain_name, target_host_id);
+        let mut report = if problems.is_empty() {
+            StepReport::success(index, &description)
+        } else {
+            StepReport::failed(index, &description, &problems.join("; "))
+        };
+        report.output = Some(format!(
+            "迁移耗时: {}\n中断统计: {}\n归属校验: {}",
+            migrated_at.map_or_else(|| "未完成".to_string(), |d| format!("{}ms", d.as_millis())),
+            downtime,
+            ownership
+        ));
+
+        Ok(report)
+    }
+
+    /// 迁移虚拟机, `wait` 时等待平台完成迁移并校验虚拟机只在目标主机上运行
+    async fn execute_vdi_migrate_domain(
+        &mut self,
+        domain_id: &str,
+        target_host_id: &str,
+        wait: bool,
+        timeout_secs: Option<u64>,
+        index: usize
+    ) -> Result<StepReport> {
+        info!("迁移虚拟机: {} -> 主机 {}", domain_id, target_host_id);
+
+        let vdi_client = self.vdi_client.clone()
+            .ok_or_else(|| ExecutorError::ConfigError("VDI 客户端未初始化".to_string()))?;
+
+        let timeout_duration = timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_timeout);
+
+        let domain_name = vdi_client.domain()
+            .get(domain_id)
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询虚拟机失败: {}", e)))?
+            .name;
+        let (target_name, target_address) = vdi_host_address(&vdi_client, target_host_id).await?;
+        let description = format!("迁移虚拟机: {} -> {}", domain_name, target_name);
+
+        if !wait {
+            vdi_client.domain()
+                .migrate(domain_id, &target_address)
+                .await
+                .map_err(|e| ExecutorError::TransportError(format!("迁移虚拟机失败: {}", e)))?;
+            self.transport_manager.invalidate_domain_cache().await;
+
+            let mut report = StepReport::success(index, &description);
+            report.output = Some("已提交迁移, 未等待完成".to_string());
+            return Ok(report);
+        }
+
+        // 目标主机按地址或名称匹配传输层中的主机
+        let transport_target = match self.resolve_transport_host(&target_address).await {
+            Ok(host_id) => host_id,
+            Err(e) => self.resolve_transport_host(&target_name).await.map_err(|_| e)?,
+        };
+
+        self.transport_manager.invalidate_domain_cache().await;
+        let (source_host_id, _) = self.transport_manager
+            .find_domain(&domain_name)
+            .await
+            .map_err(|e| ExecutorError::TransportError(e.to_string()))?
+            .ok_or_else(|| ExecutorError::StepExecutionFailed(format!("未在任何主机上找到虚拟机: {}", domain_name)))?;
+        if source_host_id == transport_target {
+            return Err(ExecutorError::StepExecutionFailed(format!(
+                "虚拟机 {} 已在目标主机 {} 上", domain_name, transport_target
+            )));
+        }
+
+        let started = Instant::now();
+        vdi_client.domain()
+            .migrate(domain_id, &target_address)
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("迁移虚拟机失败: {}", e)))?;
+
+        let mut problems = Vec::new();
+        let platform_done = match vdi_client.domain()
+            .wait_for_host(domain_id, target_host_id, timeout_duration, MIGRATION_OWNERSHIP_POLL_INTERVAL)
+            .await
+        {
+            Ok(_) => true,
+            Err(VdiError::Timeout(message)) => {
+                problems.push(message);
+                false
+            }
+            Err(e) => return Err(ExecutorError::TransportError(format!("查询迁移状态失败: {}", e))),
+        };
+
+        // 平台记录更新后 libvirt 上的状态可能稍有滞后, 在剩余时间内继续轮询
+        let mut ownership = self.check_ownership(&domain_name, &source_host_id, &transport_target).await;
+        while platform_done && !ownership.is_ok() && started.elapsed() < timeout_duration {
+            tokio::time::sleep(MIGRATION_OWNERSHIP_POLL_INTERVAL).await;
+            ownership = self.check_ownership(&domain_name, &source_host_id, &transport_target).await;
+        }
+        let migrated_at = (platform_done && ownership.is_ok()).then(|| started.elapsed());
+
+        self.transport_manager.invalidate_domain_cache().await;
+        if migrated_at.is_some() {
+            self.follow_migrated_domain(&transport_target, &domain_name).await;
+        }
+
+        problems.extend(ownership.problems());
+        let mut report = if problems.is_empty() {
+            StepReport::success(index, &description)
+        } else {
+            StepReport::failed(index, &description, &problems.join("; "))
+        };
+        report.output = Some(format!(
+            "迁移耗时: {}\n归属校验: {}",
+            migrated_at.map_or_else(|| "未完成".to_string(), |d| format!("{}ms", d.as_millis())),
+            ownership
+        ));
+
+        Ok(report)
+    }
+
+    /// 验证名称匹配的虚拟机的还原点 (见 [`VdiBatchOps::verify_restore_points`])
+    ///
+    /// 任一虚拟机失败时步骤失败; QGA 不可用而跳过的虚拟机只在输出中列出。
+    async fn execute_vdi_verify_restore_points(
+        &mut self,
+        pattern: &str,
+        marker_path: Option<&str>,
+        method: RestoreMethod,
+        timeout_secs: Option<u64>,
+        index: usize
+    ) -> Result<StepReport> {
+        info!("验证还原点: {} ({})", pattern, method.label());
+
+        let vdi_client = self.vdi_client.clone()
+            .ok_or_else(|| ExecutorError::ConfigError("VDI 客户端未初始化".to_string()))?;
+
+        let wait_timeout = timeout_secs
+            .map(Duration::from_secs)
+            .unwrap_or(self.default_timeout);
+
+        let ops = VdiBatchOps::new(vdi_client);
+        let vms = ops.get_matching_vms(pattern, CacheMode::Fresh).await?;
+        if vms.is_empty() {
+            return Err(ExecutorError::StepExecutionFailed(format!("没有名称匹配 {} 的虚拟机", pattern)));
+        }
+        let description = format!("验证还原点: {} ({} 台, {})", pattern, vms.len(), method.label());
+
+        let results = ops
+            .verify_restore_points(&self.transport_manager, vms, marker_path, method, wait_timeout)
+            .await;
+
+        let lines: Vec<String> = results
+            .iter()
+            .map(|result| match (result.status, result.failed_phase) {
+                (RestoreStatus::Passed, _) => format!("{}: 通过", result.vm.name),
+                (RestoreStatus::SkippedNoQga, _) => format!(
+                    "{}: 跳过 (QGA 不可用): {}",
+                    result.vm.name,
+                    result.error.as_deref().unwrap_or_default()
+                ),
+                (RestoreStatus::Failed, phase) => format!(
+                    "{}: 失败 ({}): {}",
+                    result.vm.name,
+                    phase.map_or("未知阶段", |phase| phase.label()),
+                    result.error.as_deref().unwrap_or_default()
+                ),
+            })
+            .collect();
+        let failed: Vec<&str> = results
+            .iter()
+            .filter(|result| result.status == RestoreStatus::Failed)
+            .map(|result| result.vm.name.as_str())
+            .collect();
+
+        let mut report = if failed.is_empty() {
+            StepReport::success(index, &description)
+        } else {
+            StepReport::failed(
+                index,
+                &description,
+                &format!("{} 台虚拟机还原点验证失败: {}", failed.len(), failed.join(", ")),
+            )
+        };
+        report.output = Some(lines.join("\n"));
+        Ok(report)
+    }
+
+    /// 迁移的是当前虚拟机时, 让后续步骤的 QGA 操作指向目标主机
+    async fn follow_migrated_domain(&mut self, target_host_id: &str, domain_name: &str) {
+        if self.current_domain_name().as_deref() != Some(domain_name) {
+            return;
+        }
+        self.current_host = Some(target_host_id.to_string());
+        if let Ok(qga) = self.connect_qga(target_host_id, domain_name).await {
+            if let Some(mut old) = self.qga_protocol.replace(qga) {
+                let _ = old.disconnect().await;
+            }
+        }
+    }
+
+    /// 把平台使用的主机地址映射为已注册的主机 ID (匹配主机 ID、主机名/IP 或 libvirt URI)
+    async fn resolve_transport_host(&self, address: &str) -> Result<String> {
+        for host_id in self.transport_manager.list_hosts().await {
+            let info: std::result::Result<HostInfo, _> = self.transport_manager
+                .execute_on_host(&host_id, |conn| async move { Ok(conn.host_info().clone()) })
+                .await;
+            match info {
+                Ok(info) if info.id == address || info.host == address || info.uri == address => {
+                    return Ok(host_id);
+                }
+                Ok(_) => {}
+                Err(e) => debug!("读取主机 {} 信息失败: {}", host_id, e),
+            }
+        }
+
+        Err(ExecutorError::ConfigError(format!("目标主机 {} 未注册到传输层", address)))
+    }
+
+    /// 在指定主机上连接虚拟机的 QGA
+    async fn connect_qga(&self, host_id: &str, domain_name: &str) -> Result<QgaProtocol> {
+        let domain = self.transport_manager
+            .execute_on_host(host_id, |conn| async move { conn.get_domain(domain_name).await })
+            .await
+            .map_err(|e| ExecutorError::TransportError(e.to_string()))?;
+
+        let mut qga = QgaProtocol::new().with_metrics(Arc::clone(&self.qga_metrics));
+        qga.connect(&domain)
+            .await
+            .map_err(|e| ExecutorError::protocol("QGA 协议连接失败", e))?;
+        Ok(qga)
+    }
+
+    /// 列举所有主机上的虚拟机, 校验迁移后的归属
+    async fn check_ownership(&self, domain_name: &str, source_host_id: &str, target_host_id: &str) -> OwnershipCheck {
+        let listings = self.transport_manager.list_all_domains().await;
+        OwnershipCheck::evaluate(domain_name, source_host_id, target_host_id, &listings)
+    }
+
+    /// 当前虚拟机的 libvirt 名称
+    fn current_domain_name(&self) -> Option<String> {
+        self.current_domain.as_ref().and_then(|domain| domain.get_name().ok())
+    }
+
     // ========================================
     // 验证步骤执行方法
     // ========================================
@@ -757,6 +2360,29 @@ impl ScenarioRunner {
         }
     }
 
+    /// 验证虚拟机只运行在指定主机上
+    async fn verify_domain_on_host(
+        &mut self,
+        domain: &str,
+        host: &str,
+        index: usize
+    ) -> Result<StepReport> {
+        info!("验证虚拟机 {} 运行于主机 {}", domain, host);
+
+        let host_id = self.resolve_transport_host(host).await?;
+        let listings = self.transport_manager.list_all_domains().await;
+        let check = PlacementCheck::evaluate(domain, &host_id, &listings);
+
+        let description = format!("验证虚拟机位置: {} @ {}", domain, host_id);
+        let mut report = if check.is_ok() {
+            StepReport::success(index, &description)
+        } else {
+            StepReport::failed(index, &description, &check.problems().join("; "))
+        };
+        report.output = Some(check.to_string());
+        Ok(report)
+    }
+
     /// 验证所有虚拟机运行中
     async fn verify_all_domains_running(
         &mut self,
@@ -854,7 +2480,7 @@ impl ScenarioRunner {
             total_steps: report.steps_executed as i32,
             success_count: report.passed_count as i32,
             failed_count: report.failed_count as i32,
-            skipped_count: 0,
+            skipped_count: report.skipped_count as i32,
             passed: report.passed,
             tags: if report.tags.is_empty() {
                 None
@@ -864,6 +2490,7 @@ impl ScenarioRunner {
                 })?)
             },
             created_at: now,
+            scenario_version: report.scenario_version,
         };
 
         // 保存报告
@@ -881,7 +2508,10 @@ impl ScenarioRunner {
                 id: 0,
                 report_id,
                 step_index: step.step_index as i32,
-                description: step.description.clone(),
+                description: match step.phase {
+                    StepPhase::Main => step.description.clone(),
+                    phase => format!("[{}] {}", phase.label(), step.description),
+                },
                 status: match step.status {
                     StepStatus::Success => "Success".to_string(),
                     StepStatus::Failed => "Failed".to_string(),
@@ -890,6 +2520,8 @@ impl ScenarioRunner {
                 error: step.error.clone(),
                 duration_ms: Some(step.duration_ms as i64),
                 output: step.output.clone(),
+                started_at_offset_ms: Some(step.started_at_offset_ms as i64),
+                error_kind: step.error_kind.map(|kind| kind.as_str().to_string()),
             })
             .collect();
 
@@ -899,11 +2531,84 @@ impl ScenarioRunner {
             .await
             .map_err(|e| ExecutorError::DatabaseError(format!("Failed to save steps: {}", e)))?;
 
+        // 保存资源记录
+        let resources: Vec<ReportResourceRecord> = report
+            .resources
+            .iter()
+            .map(|resource| ReportResourceRecord {
+                id: 0,
+                report_id,
+                resource_type: resource.kind.as_str().to_string(),
+                resource_id: resource.id.clone(),
+                name: resource.name.clone(),
+                step_index: resource.created_by_step as i32,
+                cleanup_status: resource.status.as_str().to_string(),
+                cleanup_error: resource.error.clone(),
+            })
+            .chain(report.orphan_resources.iter().map(|orphan| ReportResourceRecord {
+                id: 0,
+                report_id,
+                resource_type: orphan.kind.as_str().to_string(),
+                resource_id: orphan.id.clone(),
+                name: orphan.name.clone(),
+                step_index: -1,
+                cleanup_status: CleanupStatus::Orphaned.as_str().to_string(),
+                cleanup_error: None,
+            }))
+            .collect();
+
+        storage
+            .reports()
+            .create_resources(&resources)
+            .await
+            .map_err(|e| ExecutorError::DatabaseError(format!("Failed to save resources: {}", e)))?;
+
+        // 保存步骤资源指标
+        let metrics: Vec<StepMetricsRecord> = report
+            .steps
+            .iter()
+            .filter_map(|step| step.metrics.as_ref().map(|metrics| metrics.to_record(report_id, step.step_index)))
+            .collect();
+
+        if !metrics.is_empty() {
+            storage
+                .reports()
+                .create_step_metrics(&metrics)
+                .await
+                .map_err(|e| ExecutorError::DatabaseError(format!("Failed to save step metrics: {}", e)))?;
+        }
+
         info!("测试报告已保存到数据库, ID: {}", report_id);
         Ok(report_id)
     }
 }
 
+/// 在令牌被取消时中断 future
+async fn cancellable<T>(token: &CancellationToken, future: impl Future<Output = T>) -> Result<T> {
+    tokio::select! {
+        output = future => Ok(output),
+        _ = token.cancelled() => Err(ExecutorError::Cancelled("执行被取消".to_string())),
+    }
+}
+
+/// 平台主机 ID 对应的 (主机名称, 地址), 没有名称时以地址作为名称
+async fn vdi_host_address(vdi_client: &VdiClient, host_id: &str) -> Result<(String, String)> {
+    let hosts = vdi_client.host()
+        .list_all()
+        .await
+        .map_err(|e| ExecutorError::TransportError(format!("查询主机列表失败: {}", e)))?;
+    let host = hosts.iter()
+        .find(|host| host["id"].as_str() == Some(host_id))
+        .ok_or_else(|| ExecutorError::ConfigError(format!("VDI 平台上没有 ID 为 {} 的主机", host_id)))?;
+
+    let address = host["ip"].as_str()
+        .filter(|ip| !ip.is_empty())
+        .ok_or_else(|| ExecutorError::ConfigError(format!("VDI 主机 {} 没有地址", host_id)))?;
+    let name = host["name"].as_str().filter(|name| !name.is_empty()).unwrap_or(address);
+
+    Ok((name.to_string(), address.to_string()))
+}
+
 /// 执行报告
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutionReport {
@@ -916,6 +2621,10 @@ pub struct ExecutionReport {
     /// 标签
     pub tags: Vec<String>,
 
+    /// 场景版本 (从已保存的场景运行时)
+    #[serde(default)]
+    pub scenario_version: Option<i32>,
+
     /// 是否通过
     pub passed: bool,
 
@@ -928,11 +2637,39 @@ pub struct ExecutionReport {
     /// 失败的步骤数
     pub failed_count: usize,
 
+    /// 是否因超过 max_duration_secs 而终止
+    #[serde(default)]
+    pub timed_out: bool,
+
+    /// 是否被外部取消 (如 Ctrl-C)
+    #[serde(default)]
+    pub cancelled: bool,
+
+    /// 跳过的步骤数
+    #[serde(default)]
+    pub skipped_count: usize,
+
     /// 总耗时（毫秒）
     pub duration_ms: u64,
 
+    /// 各失败分类的步骤数
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub failed_kinds: BTreeMap<StepErrorKind, usize>,
+
     /// 步骤报告列表
     pub steps: Vec<StepReport>,
+
+    /// 场景创建的资源及其清理结果
+    #[serde(default)]
+    pub resources: Vec<TrackedResource>,
+
+    /// 环境检查发现的新增未清理资源
+    #[serde(default)]
+    pub orphan_resources: Vec<OrphanResource>,
+
+    /// 工件目录 (多目标并行执行时为该目标的子目录)
+    #[serde(default)]
+    pub artifact_dir: Option<String>,
 }
 
 impl ExecutionReport {
@@ -941,12 +2678,20 @@ impl ExecutionReport {
             scenario_name: name.to_string(),
             description: None,
             tags: Vec::new(),
+            scenario_version: None,
             passed: true,
             steps_executed: 0,
             passed_count: 0,
             failed_count: 0,
+            timed_out: false,
+            cancelled: false,
+            skipped_count: 0,
             duration_ms: 0,
+            failed_kinds: BTreeMap::new(),
             steps: Vec::new(),
+            resources: Vec::new(),
+            orphan_resources: Vec::new(),
+            artifact_dir: None,
         }
     }
 
@@ -958,8 +2703,9 @@ impl ExecutionReport {
             StepStatus::Failed => {
                 self.failed_count += 1;
                 self.passed = false;
+                *self.failed_kinds.entry(step.error_kind.unwrap_or(StepErrorKind::Unknown)).or_default() += 1;
             }
-            StepStatus::Skipped => {}
+            StepStatus::Skipped => self.skipped_count += 1,
         }
 
         self.steps.push(step);
@@ -974,6 +2720,67 @@ impl ExecutionReport {
     pub fn to_yaml(&self) -> serde_yaml::Result<String> {
         serde_yaml::to_string(self)
     }
+
+    /// 导出为 HTML (包含步骤耗时甘特图)
+    pub fn to_html(&self) -> String {
+        html_report::render_html(self)
+    }
+
+    /// 从数据库记录还原报告 (用于导出已保存的报告)
+    pub fn from_records(record: &TestReportRecord, steps: &[ExecutionStepRecord]) -> Self {
+        let mut report = Self::new(&record.scenario_name);
+        report.description = record.description.clone();
+        report.tags = record
+            .tags
+            .as_deref()
+            .and_then(|tags| serde_json::from_str(tags).ok())
+            .unwrap_or_default();
+        report.scenario_version = record.scenario_version;
+
+        for step in steps {
+            // 保存时非测试步骤的描述带有阶段前缀
+            let (phase, description) = [StepPhase::Setup, StepPhase::Teardown]
+                .into_iter()
+                .find_map(|phase| {
+                    step.description
+                        .strip_prefix(&format!("[{}] ", phase.label()))
+                        .map(|rest| (phase, rest.to_string()))
+                })
+                .unwrap_or((StepPhase::Main, step.description.clone()));
+
+            report.add_step(StepReport {
+                step_index: step.step_index.max(0) as usize,
+                description,
+                status: match step.status.as_str() {
+                    "Success" => StepStatus::Success,
+                    "Skipped" => StepStatus::Skipped,
+                    _ => StepStatus::Failed,
+                },
+                error: step.error.clone(),
+                duration_ms: step.duration_ms.unwrap_or(0).max(0) as u64,
+                output: step.output.clone(),
+                phase,
+                started_at_offset_ms: step.started_at_offset_ms.unwrap_or(0).max(0) as u64,
+                metrics: None,
+                error_kind: step.error_kind.as_deref().and_then(|kind| kind.parse().ok()),
+            });
+        }
+
+        // 以数据库中的汇总为准
+        report.passed = record.passed;
+        report.duration_ms = record.duration_ms.unwrap_or(0).max(0) as u64;
+        report
+    }
+
+    /// 附加数据库中保存的步骤资源指标 (按步骤索引匹配)
+    pub fn with_step_metrics(mut self, metrics: &[StepMetricsRecord]) -> Self {
+        for record in metrics {
+            if let Some(step) = self.steps.iter_mut().find(|step| step.step_index as i32 == record.step_index) {
+                step.metrics = Some(StepMetrics::from_record(record));
+            }
+        }
+        self
+    }
 }
 
 /// 步骤报告
@@ -996,6 +2803,22 @@ pub struct StepReport {
 
     /// 输出内容
     pub output: Option<String>,
+
+    /// 所属阶段
+    #[serde(default)]
+    pub phase: StepPhase,
+
+    /// 相对场景开始的偏移（毫秒）
+    #[serde(default)]
+    pub started_at_offset_ms: u64,
+
+    /// 步骤执行期间的资源指标 (启用采样时)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<StepMetrics>,
+
+    /// 失败分类 (仅失败步骤)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_kind: Option<StepErrorKind>,
 }
 
 impl StepReport {
@@ -1007,6 +2830,10 @@ impl StepReport {
             error: None,
             duration_ms: 0,
             output: None,
+            phase: StepPhase::Main,
+            started_at_offset_ms: 0,
+            metrics: None,
+            error_kind: None,
         }
     }
 
@@ -1018,6 +2845,26 @@ impl StepReport {
             error: Some(error.to_string()),
             duration_ms: 0,
             output: None,
+            phase: StepPhase::Main,
+            started_at_offset_ms: 0,
+            metrics: None,
+            error_kind: Some(StepErrorKind::Verification),
+        }
+    }
+
+    /// 跳过的步骤 (原因写入输出)
+    pub fn skipped(index: usize, description: &str, reason: &str) -> Self {
+        Self {
+            step_index: index,
+            description: description.to_string(),
+            status: StepStatus::Skipped,
+            error: None,
+            duration_ms: 0,
+            output: Some(format!("跳过: {}", reason)),
+            phase: StepPhase::Main,
+            started_at_offset_ms: 0,
+            metrics: None,
+            error_kind: None,
         }
     }
 }
@@ -1029,3 +2876,26 @@ pub enum StepStatus {
     Failed,
     Skipped,
 }
+
+/// 步骤所属阶段
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StepPhase {
+    /// 前置步骤
+    Setup,
+    /// 测试步骤
+    #[default]
+    Main,
+    /// 清理步骤
+    Teardown,
+}
+
+impl StepPhase {
+    /// 阶段显示名称
+    pub fn label(&self) -> &'static str {
+        match self {
+            StepPhase::Setup => "前置步骤",
+            StepPhase::Main => "步骤",
+            StepPhase::Teardown => "清理步骤",
+        }
+    }
+}
@@ -1,11 +1,39 @@
 //! 测试场景定义
+//!
+//! 场景文件带有格式版本 `schema_version` (缺省为 1)。版本高于 [`SCENARIO_SCHEMA_VERSION`] 的文件中
+//! 无法识别的动作不会导致加载失败, 而是保留为 [`Action::Unsupported`]: 校验时列出这些步骤, 执行时报错。
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
 use std::path::Path;
+use std::time::Duration;
+
+use atp_protocol::KeyboardLayout;
+use atp_vdiplatform::models::DeskPoolAdvanced;
+
+use crate::environment::EnvironmentGuardMode;
+use crate::event_log::{EventLevel, EventLogName};
+use crate::runner::StepPhase;
+use crate::step_groups;
+use crate::uniquify::GuestPlatform;
+use crate::vdi_ops::RestoreMethod;
+
+/// 当前支持的场景格式版本
+///
+/// 新增动作类型时递增, 并在 examples/scenarios/README.md 的 "格式版本" 一节记录各版本新增的动作。
+pub const SCENARIO_SCHEMA_VERSION: u32 = 2;
+
+fn default_schema_version() -> u32 {
+    1
+}
 
 /// 测试场景
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Scenario {
+    /// 场景格式版本 (缺省为 1)
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
+
     /// 场景名称
     pub name: String,
 
@@ -18,25 +46,97 @@ pub struct Scenario {
     /// 目标虚拟机名称 (可选,如果未指定则需要在步骤中指定)
     pub target_domain: Option<String>,
 
+    /// 前置步骤 (在测试步骤之前执行, 失败时跳过测试步骤)
+    #[serde(default)]
+    pub setup: Vec<ScenarioStep>,
+
     /// 测试步骤
     pub steps: Vec<ScenarioStep>,
 
+    /// 清理步骤 (无论测试结果如何都会执行)
+    #[serde(default)]
+    pub teardown: Vec<ScenarioStep>,
+
     /// 标签
     #[serde(default)]
     pub tags: Vec<String>,
+
+    /// 整个场景的最长执行时间 (秒), 超时后取消当前步骤并跳过剩余步骤
+    #[serde(default)]
+    pub max_duration_secs: Option<u64>,
+
+    /// 环境检查: 对比场景前后的 VDI 资源清单, 发现新增未清理资源时告警 (warn) 或判定失败 (fail)
+    #[serde(default)]
+    pub environment_guard: Option<EnvironmentGuardMode>,
+
+    /// Guest 的键盘布局 (如 `de-DE`), 发送文本时按该布局转换按键, 默认 en-US
+    #[serde(default)]
+    pub keyboard_layout: Option<KeyboardLayout>,
+
+    /// Guest 操作系统 (`windows` / `linux`), 决定 `exec_command` 使用的 Shell;
+    /// 未设置时通过 QGA 的 guest-get-osinfo 识别
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_os: Option<GuestPlatform>,
 }
 
 impl Scenario {
     /// 从 YAML 文件加载场景
+    ///
+    /// `includes` 中的路径相对于场景文件所在目录。
     pub fn from_yaml_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
         let content = std::fs::read_to_string(path)?;
-        Self::from_yaml_str(&content)
+        Self::parse_yaml(&content, path.parent().unwrap_or(Path::new(".")), Some(path))
     }
 
     /// 从 YAML 字符串加载场景
+    ///
+    /// 先解析为 `serde_yaml::Value` 并展开锚点与合并键 (`<<:`) 以及步骤组 (见 [`step_groups`]),
+    /// 再逐个步骤转换, 步骤无效时错误中带有步骤序号、字段以及相近的动作类型名称。
+    /// `includes` 中的路径相对于当前目录。
     pub fn from_yaml_str(yaml: &str) -> crate::Result<Self> {
-        serde_yaml::from_str(yaml)
-            .map_err(|e| crate::ExecutorError::SerdeError(e.to_string()))
+        Self::parse_yaml(yaml, Path::new("."), None)
+    }
+
+    fn parse_yaml(yaml: &str, base_dir: &Path, origin: Option<&Path>) -> crate::Result<Self> {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(yaml)
+            .map_err(|e| crate::ExecutorError::SerdeError(e.to_string()))?;
+        value
+            .apply_merge()
+            .map_err(|e| crate::ExecutorError::SerdeError(e.to_string()))?;
+        step_groups::expand(&mut value, base_dir, origin)?;
+
+        // 文件版本更新时, 无法识别的动作留到转换后作为 Unsupported 处理
+        let allow_unknown = value
+            .get("schema_version")
+            .and_then(serde_yaml::Value::as_u64)
+            .is_some_and(|version| version > u64::from(SCENARIO_SCHEMA_VERSION));
+
+        for (key, phase) in [
+            ("setup", StepPhase::Setup),
+            ("steps", StepPhase::Main),
+            ("teardown", StepPhase::Teardown),
+        ] {
+            let Some(steps) = value.get(key).and_then(serde_yaml::Value::as_sequence) else {
+                continue;
+            };
+            for (index, step) in steps.iter().enumerate() {
+                if let Err((field, reason)) = check_step(step, allow_unknown) {
+                    return Err(crate::ExecutorError::ScenarioLoadFailed(format!(
+                        "第 {} 个{} (字段 {}): {}",
+                        index + 1,
+                        phase.label(),
+                        field,
+                        reason
+                    )));
+                }
+            }
+        }
+
+        let scenario: Self = serde_yaml::from_value(value)
+            .map_err(|e| crate::ExecutorError::SerdeError(e.to_string()))?;
+        scenario.check_schema_version()?;
+        Ok(scenario)
     }
 
     /// 从 JSON 文件加载场景
@@ -47,8 +147,41 @@ impl Scenario {
 
     /// 从 JSON 字符串加载场景
     pub fn from_json_str(json: &str) -> crate::Result<Self> {
-        serde_json::from_str(json)
-            .map_err(|e| crate::ExecutorError::SerdeError(e.to_string()))
+        let scenario: Self = serde_json::from_str(json)
+            .map_err(|e| crate::ExecutorError::SerdeError(e.to_string()))?;
+        scenario.check_schema_version()?;
+        Ok(scenario)
+    }
+
+    /// 文件版本是否高于当前支持的版本
+    pub fn is_newer_schema(&self) -> bool {
+        self.schema_version > SCENARIO_SCHEMA_VERSION
+    }
+
+    /// 校验格式版本: 版本不高于当前支持的版本时, 不允许出现无法识别的动作
+    fn check_schema_version(&self) -> crate::Result<()> {
+        if self.schema_version == 0 {
+            return Err(crate::ExecutorError::ScenarioLoadFailed("schema_version 必须大于 0".to_string()));
+        }
+        if self.is_newer_schema() {
+            return Ok(());
+        }
+
+        for (phase, steps) in [
+            (StepPhase::Setup, &self.setup),
+            (StepPhase::Main, &self.steps),
+            (StepPhase::Teardown, &self.teardown),
+        ] {
+            if let Some(index) = steps.iter().position(|step| matches!(step.action, Action::Unsupported { .. })) {
+                return Err(crate::ExecutorError::ScenarioLoadFailed(format!(
+                    "第 {} 个{} (字段 action.type): 未知的动作类型 '{}'",
+                    index + 1,
+                    phase.label(),
+                    steps[index].action.type_name()
+                )));
+            }
+        }
+        Ok(())
     }
 
     /// 导出为 YAML
@@ -71,6 +204,7 @@ pub struct ScenarioStep {
     pub name: Option<String>,
 
     /// 动作类型
+    #[serde(serialize_with = "serialize_action", deserialize_with = "deserialize_action")]
     pub action: Action,
 
     /// 是否需要验证
@@ -79,6 +213,75 @@ pub struct ScenarioStep {
 
     /// 超时时间（秒）
     pub timeout: Option<u64>,
+
+    /// 步骤标签 (与场景标签合并后用于过滤执行)
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl ScenarioStep {
+    /// 步骤生效的超时时间
+    ///
+    /// 优先使用步骤中的 `timeout`; 未设置时取执行器默认超时与动作自身默认超时
+    /// (见 [`Action::default_timeout`]) 中较大的一个。
+    pub fn effective_timeout(&self, default_timeout: Duration) -> Duration {
+        match self.timeout {
+            Some(secs) => Duration::from_secs(secs),
+            None => self.action.default_timeout()
+                .map_or(default_timeout, |action_timeout| action_timeout.max(default_timeout)),
+        }
+    }
+}
+
+/// 步骤过滤条件
+///
+/// 步骤的有效标签为场景标签与步骤标签的并集。`include_tags` 非空时,
+/// 只执行带有其中任一标签的步骤; 带有 `exclude_tags` 中任一标签的步骤总是跳过。
+#[derive(Debug, Clone, Default)]
+pub struct StepFilter {
+    /// 需要包含的标签
+    pub include_tags: Vec<String>,
+
+    /// 需要排除的标签
+    pub exclude_tags: Vec<String>,
+}
+
+impl StepFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置需要包含的标签
+    pub fn with_include_tags(mut self, tags: Vec<String>) -> Self {
+        self.include_tags = tags;
+        self
+    }
+
+    /// 设置需要排除的标签
+    pub fn with_exclude_tags(mut self, tags: Vec<String>) -> Self {
+        self.exclude_tags = tags;
+        self
+    }
+
+    /// 是否没有任何过滤条件
+    pub fn is_empty(&self) -> bool {
+        self.include_tags.is_empty() && self.exclude_tags.is_empty()
+    }
+
+    /// 判断步骤是否需要跳过, 需要时返回跳过原因
+    pub fn skip_reason(&self, step: &ScenarioStep, scenario_tags: &[String]) -> Option<String> {
+        let has_tag = |tag: &String| step.tags.contains(tag) || scenario_tags.contains(tag);
+
+        if let Some(tag) = self.exclude_tags.iter().find(|t| has_tag(t)) {
+            return Some(format!("标签 {} 被排除", tag));
+        }
+
+        if !self.include_tags.is_empty() && !self.include_tags.iter().any(has_tag) {
+            return Some(format!("不匹配标签: {}", self.include_tags.join(", ")));
+        }
+
+        None
+    }
 }
 
 /// 动作类型
@@ -90,7 +293,12 @@ pub enum Action {
     // ========================================
 
     /// 发送按键
-    SendKey { key: String },
+    SendKey {
+        key: String,
+        /// 按住时长（毫秒），用于长按场景；缺省时使用 QEMU 默认值
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hold_ms: Option<u32>,
+    },
 
     /// 发送文本
     SendText { text: String },
@@ -105,6 +313,9 @@ pub enum Action {
     Wait { duration: u64 },
 
     /// 自定义动作
+    ///
+    /// `data` 形如 `{ protocol: "<已注册协议名>", payload: ... }` 时,
+    /// 通过协议注册表创建对应协议, 发送 payload 并把响应写入步骤输出。
     Custom { data: serde_json::Value },
 
     // ========================================
@@ -112,10 +323,14 @@ pub enum Action {
     // ========================================
 
     /// 创建桌面池
+    ///
+    /// `advanced` 原样透传平台的高级参数 (类型、命名前缀、存储池、网络、回收策略等)。
     VdiCreateDeskPool {
         name: String,
         template_id: String,
         count: u32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        advanced: Option<DeskPoolAdvanced>,
     },
 
     /// 启用桌面池
@@ -164,6 +379,48 @@ pub enum Action {
         pool_id: String,
     },
 
+    /// 迁移虚拟机并验证
+    ///
+    /// 通过平台动态迁移 `domain` 到 `target_host` (目标主机地址), 迁移期间通过 QGA
+    /// 周期 ping 统计中断时长, 结束后校验虚拟机只在目标主机上运行。
+    /// 最长中断超过 `max_downtime_ms` 或超时前未完成迁移时步骤失败。
+    VdiMigrateAndVerify {
+        domain: String,
+        target_host: String,
+        max_downtime_ms: u64,
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
+
+    /// 迁移虚拟机
+    ///
+    /// 通过平台把 `domain_id` 动态迁移到平台主机 `target_host_id`。`wait` 为 true (默认) 时等待平台
+    /// 记录的所在主机变为目标主机, 再通过传输层校验虚拟机只在目标主机上运行; 超时前未完成时步骤失败。
+    VdiMigrateDomain {
+        domain_id: String,
+        target_host_id: String,
+        #[serde(default = "default_wait")]
+        wait: bool,
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
+
+    /// 验证虚拟机还原点
+    ///
+    /// 对名称匹配 `pattern` 的虚拟机通过 QGA 写入标记文件 `marker_path` (缺省按客户机系统选择路径),
+    /// 设置还原点后修改标记,
+    /// 按 `method` (`reboot` 默认 / `rebase`) 触发还原, 在 `timeout_secs` 内确认标记恢复为原始内容,
+    /// 最后取消还原点。任一虚拟机失败时步骤失败; QGA 不可用的虚拟机跳过, 不算失败。
+    VdiVerifyRestorePoints {
+        pattern: String,
+        #[serde(default)]
+        marker_path: Option<String>,
+        #[serde(default)]
+        method: RestoreMethod,
+        #[serde(default)]
+        timeout_secs: Option<u64>,
+    },
+
     // ========================================
     // 验证步骤 (从 Orchestrator 迁移)
     // ========================================
@@ -176,6 +433,14 @@ pub enum Action {
         timeout_secs: Option<u64>,
     },
 
+    /// 验证虚拟机只运行在指定主机上
+    ///
+    /// `domain` 为 libvirt 中的虚拟机名称, `host_id` 为已注册到传输层的主机 (匹配主机 ID、地址或 URI)。
+    VerifyDomainOnHost {
+        domain: String,
+        host_id: String,
+    },
+
     /// 验证所有虚拟机运行中
     VerifyAllDomainsRunning {
         pool_id: String,
@@ -188,6 +453,304 @@ pub enum Action {
         #[serde(default)]
         timeout_secs: Option<u64>,
     },
+
+    /// 查询 Windows 事件日志 (通过 QGA, 仅支持 Windows 客户机)
+    ///
+    /// 最近 `since_minutes` 分钟内不低于 `level` 的事件超过 `expect_max_count` 条时步骤失败。
+    QueryWindowsEventLog {
+        log: EventLogName,
+        level: EventLevel,
+        since_minutes: u64,
+        #[serde(default)]
+        expect_max_count: u32,
+    },
+
+    /// 克隆后唯一化客户机 (通过 QGA)
+    ///
+    /// 按 `hostname_template` 修改主机名 (支持 `{index}`、`{vm_name}`),
+    /// `run_sysprep` 时 Windows 执行 sysprep 重新生成 SID, Linux 重新生成 machine-id。
+    /// 执行后客户机重启, 步骤等待重启完成且主机名生效。
+    GuestUniquify {
+        hostname_template: String,
+        #[serde(default)]
+        run_sysprep: bool,
+    },
+
+    /// 校验客户机中的文件 (通过 QGA 文件接口, Windows 与 Linux 客户机通用)
+    ///
+    /// `expect_exists` 为 false 时要求文件不存在。`expect_sha256` 为整个文件的摘要或分区摘要清单
+    /// (见 [`guest_file`](crate::guest_file)), 文件流式读取计算摘要; `expect_contains` 只在前
+    /// `max_bytes` 字节 (默认 16 MiB) 中查找, 识别带 BOM 的 UTF-16 文本。
+    VerifyFileInGuest {
+        path: String,
+        #[serde(default = "default_expect_exists")]
+        expect_exists: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expect_sha256: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expect_contains: Option<String>,
+        #[serde(default = "default_max_bytes")]
+        max_bytes: u64,
+    },
+
+    // ========================================
+    // 宿主机操作
+    // ========================================
+
+    /// 通过 SFTP 下载宿主机上的文件 (如 `/var/log/libvirt/qemu/<vm>.log`)
+    ///
+    /// `host` 为传输层中的主机 ID, 主机需要配置 SSH。`local_path` 为相对路径时
+    /// 保存到本次运行的工件目录下 (未设置工件目录时相对于当前目录)。
+    /// 放在 teardown 中可以在步骤失败后归档宿主机日志。
+    SshFetchFile {
+        host: String,
+        remote_path: String,
+        local_path: String,
+    },
+
+    /// 通过 SSH 在宿主机上执行白名单内的命令 (virsh、ovs-vsctl 等)
+    ///
+    /// `command` 为参数列表, 不经过 shell。输出逐行写入日志, 超过 `idle_timeout_secs`
+    /// (默认 300 秒) 没有新的输出时判定命令挂起, 步骤失败; 总时长受步骤超时约束。
+    /// 退出码非 0 时步骤失败。
+    SshExec {
+        host: String,
+        command: Vec<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        idle_timeout_secs: Option<u64>,
+    },
+
+    /// 执行步骤组 (见 [`step_groups`](crate::step_groups))
+    ///
+    /// 从 YAML 加载场景时原地展开为组内步骤, `with` 中的参数代入组内的 `${参数}`;
+    /// 执行器不会执行未展开的步骤组。
+    RunGroup {
+        name: String,
+        #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+        with: HashMap<String, String>,
+    },
+
+    /// 更新版本的场景文件中无法识别的动作
+    ///
+    /// 只在文件的 `schema_version` 高于 [`SCENARIO_SCHEMA_VERSION`] 时产生, `raw` 保留原始内容
+    /// (包括字段顺序), 导出时原样写回。执行器拒绝执行这类步骤。
+    #[serde(skip)]
+    Unsupported { raw: serde_yaml::Value },
+}
+
+/// 序列化步骤的动作, [`Action::Unsupported`] 原样写回
+fn serialize_action<S: Serializer>(action: &Action, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    match action {
+        Action::Unsupported { raw } => raw.serialize(serializer),
+        action => action.serialize(serializer),
+    }
+}
+
+/// 反序列化步骤的动作, 无法识别的动作类型保留为 [`Action::Unsupported`]
+///
+/// 是否允许这类动作由 [`Scenario`] 按文件版本判断。
+fn deserialize_action<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Action, D::Error> {
+    let raw = serde_yaml::Value::deserialize(deserializer)?;
+    match raw.get("type").and_then(serde_yaml::Value::as_str) {
+        Some(type_name) if !Action::TYPE_NAMES.contains(&type_name) => Ok(Action::Unsupported { raw }),
+        _ => serde_yaml::from_value(raw).map_err(serde::de::Error::custom),
+    }
+}
+
+/// `ssh_exec` 未指定 `idle_timeout_secs` 时的空闲超时 (秒)
+pub(crate) const DEFAULT_SSH_IDLE_TIMEOUT_SECS: u64 = 300;
+
+fn default_wait() -> bool {
+    true
+}
+
+fn default_expect_exists() -> bool {
+    true
+}
+
+fn default_max_bytes() -> u64 {
+    crate::guest_file::DEFAULT_MAX_BYTES
+}
+
+impl Action {
+    /// 所有动作类型名称 (与场景文件中的 type 一致)
+    pub const TYPE_NAMES: &'static [&'static str] = &[
+        "send_key",
+        "send_text",
+        "mouse_click",
+        "exec_command",
+        "wait",
+        "custom",
+        "vdi_create_desk_pool",
+        "vdi_enable_desk_pool",
+        "vdi_disable_desk_pool",
+        "vdi_delete_desk_pool",
+        "vdi_start_domain",
+        "vdi_shutdown_domain",
+        "vdi_reboot_domain",
+        "vdi_delete_domain",
+        "vdi_bind_user",
+        "vdi_get_desk_pool_domains",
+        "vdi_migrate_and_verify",
+        "vdi_migrate_domain",
+        "vdi_verify_restore_points",
+        "verify_domain_status",
+        "verify_domain_on_host",
+        "verify_all_domains_running",
+        "verify_command_success",
+        "query_windows_event_log",
+        "guest_uniquify",
+        "verify_file_in_guest",
+        "ssh_fetch_file",
+        "ssh_exec",
+        "run_group",
+    ];
+
+    /// 动作自身的默认超时 (需要较长时间的动作, 步骤未设置超时时使用)
+    pub fn default_timeout(&self) -> Option<Duration> {
+        match self {
+            Action::GuestUniquify { .. } => Some(crate::uniquify::DEFAULT_UNIQUIFY_TIMEOUT),
+            _ => None,
+        }
+    }
+
+    /// 动作类型名称 (与场景文件中的 type 一致)
+    pub fn type_name(&self) -> String {
+        if let Action::Unsupported { raw } = self {
+            return raw
+                .get("type")
+                .and_then(serde_yaml::Value::as_str)
+                .unwrap_or("unknown")
+                .to_string();
+        }
+        serde_json::to_value(self)
+            .ok()
+            .and_then(|v| v.get("type").and_then(|t| t.as_str()).map(str::to_string))
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// 动作无法识别时, 说明需要的场景格式版本 (`schema_version` 为场景文件的版本)
+    pub fn unsupported_reason(&self, schema_version: u32) -> Option<String> {
+        matches!(self, Action::Unsupported { .. }).then(|| {
+            format!(
+                "动作类型 '{}' 需要场景格式版本 {} (当前 atp 支持到 {}), 请升级 atp 后执行",
+                self.type_name(),
+                schema_version,
+                SCENARIO_SCHEMA_VERSION
+            )
+        })
+    }
+}
+
+/// 检查单个步骤能否转换, 失败时返回 (字段, 原因)
+///
+/// `allow_unknown` 为真时 (文件版本高于当前支持的版本) 跳过无法识别的动作。
+fn check_step(step: &serde_yaml::Value, allow_unknown: bool) -> std::result::Result<(), (String, String)> {
+    let Some(mapping) = step.as_mapping() else {
+        return Err(("-".to_string(), "步骤必须是映射".to_string()));
+    };
+
+    let Some(action) = mapping.get("action") else {
+        return Err(("action".to_string(), "缺少动作".to_string()));
+    };
+
+    let Some(type_name) = action.get("type").and_then(serde_yaml::Value::as_str) else {
+        return Err(("action.type".to_string(), "缺少动作类型".to_string()));
+    };
+
+    if !Action::TYPE_NAMES.contains(&type_name) {
+        if allow_unknown {
+            return Ok(());
+        }
+        let candidates = similar_names(type_name, Action::TYPE_NAMES);
+        let hint = if candidates.is_empty() {
+            format!("可用的动作类型: {}", Action::TYPE_NAMES.join(", "))
+        } else {
+            format!("是否想使用: {}", candidates.join(", "))
+        };
+        return Err((
+            "action.type".to_string(),
+            format!("未知的动作类型 '{}', {}", type_name, hint),
+        ));
+    }
+
+    if let Err(e) = serde_yaml::from_value::<Action>(action.clone()) {
+        let field = invalid_field(action, "type", |value| serde_yaml::from_value::<Action>(value).err())
+            .map_or_else(|| "action".to_string(), |field| format!("action.{}", field));
+        return Err((field, e.to_string()));
+    }
+
+    if let Err(e) = serde_yaml::from_value::<ScenarioStep>(step.clone()) {
+        let field = invalid_field(step, "action", |value| serde_yaml::from_value::<ScenarioStep>(value).err())
+            .unwrap_or_else(|| "-".to_string());
+        return Err((field, e.to_string()));
+    }
+
+    Ok(())
+}
+
+/// 找出导致转换失败的字段
+///
+/// 内部标签枚举的错误信息中没有字段名, 依次去掉每个字段重新转换,
+/// 去掉后转换成功或变为缺少该字段的字段即为出错的字段。
+fn invalid_field<F>(value: &serde_yaml::Value, skip: &str, convert: F) -> Option<String>
+where
+    F: Fn(serde_yaml::Value) -> Option<serde_yaml::Error>,
+{
+    let mapping = value.as_mapping()?;
+
+    if let Some(e) = convert(value.clone()) {
+        let message = e.to_string();
+        if let Some(field) = message
+            .strip_prefix("missing field `")
+            .and_then(|rest| rest.split('`').next())
+        {
+            return Some(field.to_string());
+        }
+    }
+
+    mapping
+        .keys()
+        .filter_map(serde_yaml::Value::as_str)
+        .filter(|key| *key != skip)
+        .find(|key| {
+            let mut reduced = mapping.clone();
+            reduced.remove(*key);
+            match convert(serde_yaml::Value::Mapping(reduced)) {
+                None => true,
+                Some(e) => e.to_string().contains(&format!("missing field `{}`", key)),
+            }
+        })
+        .map(str::to_string)
+}
+
+/// 编辑距离最近的候选名称 (最多 3 个)
+pub fn similar_names<'a>(name: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+    let threshold = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect();
+    scored.sort();
+    scored.into_iter().take(3).map(|(_, candidate)| candidate).collect()
+}
+
+/// Levenshtein 编辑距离
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
 }
 
 #[cfg(test)]
@@ -213,27 +776,344 @@ steps:
         let scenario = Scenario::from_yaml_str(yaml).unwrap();
         assert_eq!(scenario.name, "测试场景");
         assert_eq!(scenario.steps.len(), 2);
+        assert!(scenario.setup.is_empty());
+        assert!(scenario.teardown.is_empty());
+    }
+
+    #[test]
+    fn test_scenario_setup_teardown_from_yaml() {
+        let yaml = r#"
+name: "fixture"
+setup:
+  - name: "创建桌面池"
+    action:
+      type: vdi_create_desk_pool
+      name: "pool"
+      template_id: "tpl"
+      count: 1
+steps:
+  - action:
+      type: wait
+      duration: 1
+teardown:
+  - action:
+      type: wait
+      duration: 1
+  - action:
+      type: wait
+      duration: 2
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        assert_eq!(scenario.setup.len(), 1);
+        assert_eq!(scenario.steps.len(), 1);
+        assert_eq!(scenario.teardown.len(), 2);
+    }
+
+    #[test]
+    fn test_scenario_yaml_anchors_and_merge_keys() {
+        let yaml = r#"
+name: "anchors"
+x-defaults: &defaults
+  timeout: 30
+  tags: ["smoke"]
+x-wait: &wait
+  type: wait
+  duration: 5
+steps:
+  - <<: *defaults
+    name: "等待"
+    action: *wait
+  - <<: *defaults
+    timeout: 60
+    action:
+      <<: *wait
+      duration: 1
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        assert_eq!(scenario.steps.len(), 2);
+        assert_eq!(scenario.steps[0].timeout, Some(30));
+        assert_eq!(scenario.steps[0].tags, vec!["smoke"]);
+        assert!(matches!(scenario.steps[0].action, Action::Wait { duration: 5 }));
+        assert_eq!(scenario.steps[1].timeout, Some(60));
+        assert!(matches!(scenario.steps[1].action, Action::Wait { duration: 1 }));
+    }
+
+    #[test]
+    fn test_scenario_yaml_error_location() {
+
+        // 拼错的动作类型给出相近的候选
+        let error = load_error(
+            r#"
+name: "typo"
+steps:
+  - action: { type: wait, duration: 1 }
+  - action: { type: sendkey, key: "a" }
+"#,
+        );
+        assert!(error.contains("第 2 个步骤 (字段 action.type)"), "{}", error);
+        assert!(error.contains("未知的动作类型 'sendkey'"), "{}", error);
+        assert!(error.contains("是否想使用: send_key"), "{}", error);
+
+        let error = load_error(
+            r#"
+name: "typo"
+steps: []
+setup:
+  - action: { type: vdi_strat_domain, domain_id: "vm-1" }
+"#,
+        );
+        assert!(error.contains("第 1 个前置步骤 (字段 action.type)"), "{}", error);
+        assert!(error.contains("vdi_start_domain"), "{}", error);
+
+        // 缺少字段与字段类型错误
+        let error = load_error("name: x\nsteps:\n  - action: { type: send_key }\n");
+        assert!(error.contains("(字段 action.key)"), "{}", error);
+
+        let error = load_error("name: x\nsteps:\n  - action: { type: wait, duration: soon }\n");
+        assert!(error.contains("(字段 action.duration)"), "{}", error);
+
+        let error = load_error("name: x\nsteps:\n  - timeout: never\n    action: { type: wait, duration: 1 }\n");
+        assert!(error.contains("(字段 timeout)"), "{}", error);
+
+        let error = load_error("name: x\nteardown:\n  - name: no-action\nsteps: []\n");
+        assert!(error.contains("第 1 个清理步骤 (字段 action)"), "{}", error);
+    }
+
+    #[test]
+    fn test_schema_version_defaults_to_one() {
+        let scenario = Scenario::from_yaml_str("name: x\nsteps: []\n").unwrap();
+        assert_eq!(scenario.schema_version, 1);
+        assert!(!scenario.is_newer_schema());
+        assert!(scenario.to_yaml().unwrap().starts_with("schema_version: 1\n"));
+
+        let error = load_error("schema_version: 0\nname: x\nsteps: []\n");
+        assert!(error.contains("schema_version"), "{}", error);
+    }
+
+    #[test]
+    fn test_unknown_action_in_newer_schema_is_unsupported() {
+        let yaml = r#"
+schema_version: 9
+name: "newer"
+steps:
+  - action: { type: wait, duration: 1 }
+  - name: "截屏"
+    action: { type: capture_screen, format: png }
+teardown:
+  - action: { type: sendkey, key: "a" }
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        assert!(scenario.is_newer_schema());
+        assert!(matches!(scenario.steps[0].action, Action::Wait { .. }));
+        assert_eq!(scenario.steps[1].action.type_name(), "capture_screen");
+        assert_eq!(scenario.teardown[0].action.type_name(), "sendkey");
+
+        let reason = scenario.steps[1].action.unsupported_reason(scenario.schema_version).unwrap();
+        assert!(reason.contains("需要场景格式版本 9"), "{}", reason);
+        assert!(scenario.steps[0].action.unsupported_reason(scenario.schema_version).is_none());
+
+        // 已知动作的字段错误不受版本影响
+        let error = load_error("schema_version: 9\nname: x\nsteps:\n  - action: { type: send_key }\n");
+        assert!(error.contains("(字段 action.key)"), "{}", error);
+
+        // 当前版本的文件仍然拒绝未知动作, JSON 也一样
+        let json = r#"{"name": "x", "steps": [{"action": {"type": "capture_screen"}}]}"#;
+        let error = Scenario::from_json_str(json).unwrap_err().to_string();
+        assert!(error.contains("第 1 个步骤 (字段 action.type): 未知的动作类型 'capture_screen'"), "{}", error);
+        let json = r#"{"schema_version": 9, "name": "x", "steps": [{"action": {"type": "capture_screen"}}]}"#;
+        assert!(Scenario::from_json_str(json).is_ok());
+    }
+
+    #[test]
+    fn test_unsupported_action_round_trip() {
+        // 字段顺序不是字母序, 数值与嵌套结构保持原样
+        let action_yaml = "type: capture_screen\nzoom: 1.5\nformat: png\nregion:\n  y: 10\n  x: 20\nlabels:\n- a\n- b\n";
+        let yaml = format!("schema_version: 9\nname: x\nsteps:\n- action:\n{}", indent(action_yaml, "    "));
+        let scenario = Scenario::from_yaml_str(&yaml).unwrap();
+
+        let step = serde_yaml::to_string(&scenario.steps[0]).unwrap();
+        assert!(step.starts_with(&format!("name: null\naction:\n{}", indent(action_yaml, "  "))), "{}", step);
+
+        let exported = scenario.to_yaml().unwrap();
+        assert_eq!(Scenario::from_yaml_str(&exported).unwrap().to_yaml().unwrap(), exported);
+
+        let action_json = r#"{"type":"capture_screen","zoom":1.5,"format":"png","region":{"y":10,"x":20}}"#;
+        let json = format!(r#"{{"schema_version":9,"name":"x","steps":[{{"action":{}}}]}}"#, action_json);
+        let scenario = Scenario::from_json_str(&json).unwrap();
+        let Action::Unsupported { raw } = &scenario.steps[0].action else {
+            panic!("应保留为 Unsupported");
+        };
+        assert_eq!(serde_json::to_string(raw).unwrap(), action_json);
+
+        let exported = scenario.to_json().unwrap();
+        assert_eq!(Scenario::from_json_str(&exported).unwrap().to_json().unwrap(), exported);
+    }
+
+    fn load_error(yaml: &str) -> String {
+        Scenario::from_yaml_str(yaml).unwrap_err().to_string()
+    }
+
+    fn indent(text: &str, prefix: &str) -> String {
+        text.lines().map(|line| format!("{}{}\n", prefix, line)).collect()
+    }
+
+    #[test]
+    fn test_action_type_names_are_complete() {
+        for name in Action::TYPE_NAMES {
+            let mut action = serde_yaml::Mapping::new();
+            action.insert("type".into(), (*name).into());
+            let error = serde_yaml::from_value::<Action>(serde_yaml::Value::Mapping(action))
+                .err()
+                .map(|e| e.to_string())
+                .unwrap_or_default();
+            assert!(!error.contains("unknown variant"), "{}: {}", name, error);
+        }
+
+        let actions = [
+            Action::GuestUniquify {
+                hostname_template: "vm-{index}".to_string(),
+                run_sysprep: false,
+            },
+            Action::VdiMigrateAndVerify {
+                domain: "vm-1".to_string(),
+                target_host: "10.0.0.2".to_string(),
+                max_downtime_ms: 500,
+                timeout_secs: None,
+            },
+            Action::VdiMigrateDomain {
+                domain_id: "vm-1".to_string(),
+                target_host_id: "host-2".to_string(),
+                wait: true,
+                timeout_secs: Some(300),
+            },
+            Action::VdiVerifyRestorePoints {
+                pattern: "lab-*".to_string(),
+                marker_path: Some("/var/tmp/atp-restore-marker".to_string()),
+                method: RestoreMethod::Reboot,
+                timeout_secs: None,
+            },
+            Action::VerifyDomainOnHost {
+                domain: "win10".to_string(),
+                host_id: "host-2".to_string(),
+            },
+            Action::VerifyFileInGuest {
+                path: "/opt/agent/VERSION".to_string(),
+                expect_exists: true,
+                expect_sha256: None,
+                expect_contains: Some("2.3".to_string()),
+                max_bytes: 4096,
+            },
+        ];
+        for action in actions {
+            assert!(Action::TYPE_NAMES.contains(&action.type_name().as_str()), "{}", action.type_name());
+        }
+    }
+
+    #[test]
+    fn test_migrate_domain_waits_by_default() {
+        let action: Action =
+            serde_yaml::from_str("{ type: vdi_migrate_domain, domain_id: vm-1, target_host_id: host-2 }").unwrap();
+        assert!(matches!(action, Action::VdiMigrateDomain { wait: true, timeout_secs: None, .. }));
+
+        let action: Action =
+            serde_yaml::from_str("{ type: vdi_migrate_domain, domain_id: vm-1, target_host_id: host-2, wait: false }")
+                .unwrap();
+        assert!(matches!(action, Action::VdiMigrateDomain { wait: false, .. }));
+    }
+
+    #[test]
+    fn test_verify_restore_points_defaults_to_reboot() {
+        let action: Action = serde_yaml::from_str("{ type: vdi_verify_restore_points, pattern: 'lab-*' }").unwrap();
+        assert!(matches!(
+            action,
+            Action::VdiVerifyRestorePoints { marker_path: None, method: RestoreMethod::Reboot, timeout_secs: None, .. }
+        ));
+
+        let action: Action = serde_yaml::from_str(
+            "{ type: vdi_verify_restore_points, pattern: 'lab-*', marker_path: /tmp/m, method: rebase }",
+        )
+        .unwrap();
+        assert!(matches!(action, Action::VdiVerifyRestorePoints { method: RestoreMethod::Rebase, .. }));
+    }
+
+    #[test]
+    fn test_step_effective_timeout() {
+        let default = Duration::from_secs(30);
+        let step = |yaml: &str| -> ScenarioStep { serde_yaml::from_str(yaml).unwrap() };
+
+        let uniquify = step("action: { type: guest_uniquify, hostname_template: 'pc-{index}' }");
+        assert_eq!(uniquify.effective_timeout(default), crate::uniquify::DEFAULT_UNIQUIFY_TIMEOUT);
+        assert_eq!(uniquify.effective_timeout(Duration::from_secs(3600)), Duration::from_secs(3600));
+
+        let explicit = step("{ timeout: 120, action: { type: guest_uniquify, hostname_template: 'pc' } }");
+        assert_eq!(explicit.effective_timeout(default), Duration::from_secs(120));
+
+        let wait = step("action: { type: wait, duration: 5 }");
+        assert_eq!(wait.effective_timeout(default), default);
+    }
+
+    #[test]
+    fn test_similar_names() {
+        assert_eq!(edit_distance("sendkey", "send_key"), 1);
+        assert_eq!(edit_distance("", "wait"), 4);
+        assert_eq!(edit_distance("wait", "wait"), 0);
+        assert_eq!(similar_names("wiat", Action::TYPE_NAMES), vec!["wait"]);
+        assert!(similar_names("reboot_everything", Action::TYPE_NAMES).is_empty());
     }
 
     #[test]
     fn test_scenario_to_yaml() {
         let scenario = Scenario {
+            schema_version: SCENARIO_SCHEMA_VERSION,
             name: "测试场景".to_string(),
             description: Some("描述".to_string()),
             target_host: None,
             target_domain: Some("test-vm".to_string()),
             tags: vec!["test".to_string()],
+            setup: vec![],
+            teardown: vec![],
             steps: vec![
                 ScenarioStep {
                     name: Some("发送按键".to_string()),
-                    action: Action::SendKey { key: "a".to_string() },
+                    action: Action::SendKey { key: "a".to_string(), hold_ms: None },
                     verify: false,
                     timeout: None,
+                    tags: vec![],
                 },
             ],
+            max_duration_secs: None,
+            environment_guard: None,
+            keyboard_layout: None,
+            guest_os: None,
         };
 
         let yaml = scenario.to_yaml().unwrap();
         assert!(yaml.contains("测试场景"));
     }
+
+    #[test]
+    fn test_step_filter() {
+        let step = |tags: &[&str]| ScenarioStep {
+            name: None,
+            action: Action::Wait { duration: 1 },
+            verify: false,
+            timeout: None,
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+        };
+        let scenario_tags = vec!["nightly".to_string()];
+
+        let filter = StepFilter::new()
+            .with_include_tags(vec!["smoke".to_string()])
+            .with_exclude_tags(vec!["slow".to_string()]);
+
+        assert!(filter.skip_reason(&step(&["smoke"]), &scenario_tags).is_none());
+        assert!(filter.skip_reason(&step(&["stress"]), &scenario_tags).is_some());
+        assert!(filter.skip_reason(&step(&["smoke", "slow"]), &scenario_tags).is_some());
+
+        // 场景标签对所有步骤生效
+        let filter = StepFilter::new().with_include_tags(vec!["nightly".to_string()]);
+        assert!(filter.skip_reason(&step(&[]), &scenario_tags).is_none());
+
+        assert!(StepFilter::new().skip_reason(&step(&["slow"]), &[]).is_none());
+    }
 }
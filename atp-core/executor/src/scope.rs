@@ -0,0 +1,356 @@
+//! 多目标并行执行时的变量作用域与工件目录
+//!
+//! 对多台虚拟机并行执行同一场景时, 每个目标拥有独立的变量层与工件子目录,
+//! 避免子运行之间互相覆盖。变量按 "目标自身 → 共享层" 的顺序解析,
+//! 共享层 (CLI `--var` 与 profile 中的变量) 对所有目标只读。
+//!
+//! 执行器设置了变量作用域时, 每个步骤执行前把动作中所有字符串里的 `${变量}`
+//! 替换为变量值; 未定义的引用保持原样, 由场景校验报告。
+//!
+//! 工件目录布局:
+//!
+//! ```text
+//! <root>/
+//!   <vm_name>/      # 每个目标一个子目录, 名称中的非法字符替换为 '_'
+//!   <vm_name>/
+//! ```
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::step_groups::substitute_with;
+use crate::{Action, ExecutorError, Result};
+
+/// 只读的共享变量层
+///
+/// CLI `--var` 覆盖 profile 中的同名变量。所有目标共享同一份数据。
+#[derive(Debug, Clone, Default)]
+pub struct SharedVariables {
+    vars: Arc<HashMap<String, String>>,
+}
+
+impl SharedVariables {
+    /// 合并 profile 与 CLI 变量 (CLI 优先)
+    pub fn from_layers(profile: HashMap<String, String>, cli: HashMap<String, String>) -> Self {
+        let mut vars = profile;
+        vars.extend(cli);
+        Self { vars: Arc::new(vars) }
+    }
+
+    /// 解析 CLI 的 `key=value` 参数
+    pub fn parse_cli_var(arg: &str) -> Result<(String, String)> {
+        match arg.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => {
+                Ok((key.trim().to_string(), value.to_string()))
+            }
+            _ => Err(ExecutorError::ConfigError(format!(
+                "无效的变量定义: {} (格式: key=value)",
+                arg
+            ))),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+
+    /// 所有变量名
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.keys().map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+}
+
+/// 单个目标的变量作用域
+///
+/// 写入只影响目标自身的变量层; 与共享层同名时遮蔽共享值, 共享层本身不变。
+#[derive(Debug, Clone)]
+pub struct VariableScope {
+    target: String,
+    local: HashMap<String, String>,
+    shared: SharedVariables,
+}
+
+impl VariableScope {
+    pub fn new(target: &str, shared: SharedVariables) -> Self {
+        Self {
+            target: target.to_string(),
+            local: HashMap::new(),
+            shared,
+        }
+    }
+
+    /// 作用域所属的目标 (虚拟机名称)
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// 设置目标自身的变量 (如步骤产出)
+    pub fn set(&mut self, name: &str, value: impl Into<String>) {
+        self.local.insert(name.to_string(), value.into());
+    }
+
+    /// 按 "目标自身 → 共享层" 的顺序解析变量
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.local
+            .get(name)
+            .map(String::as_str)
+            .or_else(|| self.shared.get(name))
+    }
+
+    /// 目标自身写入的变量
+    pub fn local(&self) -> &HashMap<String, String> {
+        &self.local
+    }
+
+    /// 所有可解析的变量名 (目标自身与共享层)
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .local
+            .keys()
+            .map(String::as_str)
+            .chain(self.shared.names())
+            .map(str::to_string)
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// 把动作中所有字符串里的 `${变量}` 替换为变量值
+    ///
+    /// 替换结果总是字符串, 不会把 `"${port}"` 变成数字; 未定义的引用保持原样。
+    pub fn substitute(&self, action: &Action) -> Result<Action> {
+        // 无法识别的动作不会执行, 原样保留
+        if matches!(action, Action::Unsupported { .. }) {
+            return Ok(action.clone());
+        }
+
+        let mut value = serde_yaml::to_value(action).map_err(|e| ExecutorError::SerdeError(e.to_string()))?;
+        self.substitute_value(&mut value);
+        serde_yaml::from_value(value).map_err(|e| {
+            ExecutorError::ConfigError(format!("代入变量后动作 {} 无效: {}", action.type_name(), e))
+        })
+    }
+
+    fn substitute_value(&self, value: &mut serde_yaml::Value) {
+        match value {
+            serde_yaml::Value::String(text) => *text = substitute_with(text, |name| self.get(name)),
+            serde_yaml::Value::Sequence(items) => items.iter_mut().for_each(|item| self.substitute_value(item)),
+            serde_yaml::Value::Mapping(mapping) => {
+                mapping.iter_mut().for_each(|(_, item)| self.substitute_value(item))
+            }
+            _ => {}
+        }
+    }
+}
+
+/// 工件目录布局
+#[derive(Debug, Clone)]
+pub struct ArtifactLayout {
+    root: PathBuf,
+}
+
+impl ArtifactLayout {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 目标的工件子目录 (按 vm_name 命名)
+    pub fn target_dir(&self, vm_name: &str) -> PathBuf {
+        self.root.join(dir_name(vm_name))
+    }
+}
+
+/// 目录名只保留字母、数字、'-'、'_' 与 '.', 其余字符替换为 '_'
+fn dir_name(vm_name: &str) -> String {
+    let name: String = vm_name
+        .chars()
+        .map(|c| if c.is_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+
+    // 避免 "." / ".." 指向根目录或上级目录
+    if name.chars().all(|c| c == '.') {
+        name.replace('.', "_")
+    } else {
+        name
+    }
+}
+
+/// 每个目标作用域中预置的变量: 虚拟机名称
+pub const VM_NAME_VARIABLE: &str = "vm_name";
+
+/// 每个目标作用域中预置的变量: 在批量执行中的序号 (从 0 开始)
+pub const VM_INDEX_VARIABLE: &str = "vm_index";
+
+/// 一个子运行的隔离上下文
+#[derive(Debug, Clone)]
+pub struct FanOutTarget {
+    /// 虚拟机名称
+    pub vm_name: String,
+
+    /// 在批量执行中的序号 (对应 `ScenarioRunner::with_vm_index`)
+    pub index: usize,
+
+    /// 变量作用域
+    pub variables: VariableScope,
+
+    /// 工件子目录
+    pub artifact_dir: PathBuf,
+}
+
+/// 为每个目标创建独立的变量作用域与工件子目录
+///
+/// 作用域中预置 `vm_name` 与 `vm_index` 两个变量。
+/// 两台虚拟机的名称映射到同一目录时返回错误, 而不是让它们共享目录。
+pub fn prepare_targets(
+    vm_names: &[String],
+    shared: &SharedVariables,
+    layout: &ArtifactLayout,
+) -> Result<Vec<FanOutTarget>> {
+    let mut owners: HashMap<PathBuf, &str> = HashMap::new();
+    for vm_name in vm_names {
+        let dir = layout.target_dir(vm_name);
+        if let Some(other) = owners.insert(dir.clone(), vm_name) {
+            return Err(ExecutorError::ConfigError(format!(
+                "虚拟机 {} 与 {} 的工件目录冲突: {}",
+                other,
+                vm_name,
+                dir.display()
+            )));
+        }
+    }
+
+    vm_names
+        .iter()
+        .enumerate()
+        .map(|(index, vm_name)| {
+            let artifact_dir = layout.target_dir(vm_name);
+            std::fs::create_dir_all(&artifact_dir)?;
+
+            let mut variables = VariableScope::new(vm_name, shared.clone());
+            variables.set(VM_NAME_VARIABLE, vm_name.as_str());
+            variables.set(VM_INDEX_VARIABLE, index.to_string());
+
+            Ok(FanOutTarget {
+                vm_name: vm_name.clone(),
+                index,
+                variables,
+                artifact_dir,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_scope_resolution_order() {
+        let shared = SharedVariables::from_layers(
+            vars(&[("user", "profile-user"), ("domain", "corp.local")]),
+            vars(&[("user", "cli-user")]),
+        );
+        // CLI 覆盖 profile
+        assert_eq!(shared.get("user"), Some("cli-user"));
+        assert_eq!(shared.get("domain"), Some("corp.local"));
+
+        let mut vm1 = VariableScope::new("vm-1", shared.clone());
+        let mut vm2 = VariableScope::new("vm-2", shared.clone());
+        vm1.set("ip", "10.0.0.1");
+        vm2.set("ip", "10.0.0.2");
+        vm1.set("domain", "override.local");
+
+        // 目标自身优先, 其次共享层; 子运行之间互不可见
+        assert_eq!(vm1.get("ip"), Some("10.0.0.1"));
+        assert_eq!(vm2.get("ip"), Some("10.0.0.2"));
+        assert_eq!(vm1.get("domain"), Some("override.local"));
+        assert_eq!(vm2.get("domain"), Some("corp.local"));
+        assert_eq!(vm1.get("missing"), None);
+
+        // 共享层只读, 遮蔽不会修改它
+        assert_eq!(shared.get("domain"), Some("corp.local"));
+        assert_eq!(vm1.local().len(), 2);
+    }
+
+    #[test]
+    fn test_substitute_action() {
+        let shared = SharedVariables::from_layers(HashMap::new(), vars(&[("user", "admin"), ("port", "8080")]));
+        let mut scope = VariableScope::new("vm-1", shared);
+        scope.set("vm_name", "vm-1");
+        assert_eq!(scope.names(), vec!["port", "user", "vm_name"]);
+
+        let action = Action::ExecCommand {
+            command: "curl -u ${user} http://${vm_name}:${port}/ ${missing}".to_string(),
+        };
+        match scope.substitute(&action).unwrap() {
+            Action::ExecCommand { command } => {
+                assert_eq!(command, "curl -u admin http://vm-1:8080/ ${missing}");
+            }
+            other => panic!("unexpected action: {:?}", other),
+        }
+
+        // 整个字符串就是一个变量时仍代入为字符串
+        match scope.substitute(&Action::SendText { text: "${port}".to_string() }).unwrap() {
+            Action::SendText { text } => assert_eq!(text, "8080"),
+            other => panic!("unexpected action: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_cli_var() {
+        assert_eq!(
+            SharedVariables::parse_cli_var("user=admin").unwrap(),
+            ("user".to_string(), "admin".to_string())
+        );
+        assert_eq!(
+            SharedVariables::parse_cli_var("cmd=a=b").unwrap(),
+            ("cmd".to_string(), "a=b".to_string())
+        );
+        assert!(SharedVariables::parse_cli_var("novalue").is_err());
+        assert!(SharedVariables::parse_cli_var("=x").is_err());
+    }
+
+    #[test]
+    fn test_artifact_layout() {
+        let root = std::env::temp_dir().join(format!("atp-fanout-{}", std::process::id()));
+        let layout = ArtifactLayout::new(&root);
+
+        assert_eq!(layout.target_dir("win10-01"), root.join("win10-01"));
+        assert_eq!(layout.target_dir("pool/vm 1"), root.join("pool_vm_1"));
+        assert_eq!(layout.target_dir(".."), root.join("__"));
+
+        let names = vec!["win10-01".to_string(), "win10-02".to_string()];
+        let targets = prepare_targets(&names, &SharedVariables::default(), &layout).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[1].index, 1);
+        assert_eq!(targets[1].variables.target(), "win10-02");
+        assert_eq!(targets[1].variables.get("vm_name"), Some("win10-02"));
+        assert_eq!(targets[1].variables.get("vm_index"), Some("1"));
+        assert!(targets.iter().all(|t| t.artifact_dir.is_dir()));
+        assert_ne!(targets[0].artifact_dir, targets[1].artifact_dir);
+
+        // 名称映射到同一目录时拒绝
+        let clash = vec!["vm 1".to_string(), "vm/1".to_string()];
+        assert!(prepare_targets(&clash, &SharedVariables::default(), &layout).is_err());
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
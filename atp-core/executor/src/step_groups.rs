@@ -0,0 +1,444 @@
+//! 场景引用与步骤组
+//!
+//! 多个场景共用的步骤 (如 "创建并启动虚拟机") 可以定义为步骤组, 放在单独的文件中:
+//!
+//! ```yaml
+//! # common/provision.yaml
+//! groups:
+//!   provision:
+//!     - action: { type: vdi_start_domain, domain_id: "${vm}" }
+//!     - action: { type: wait, duration: "${boot_secs}" }
+//! ```
+//!
+//! 场景通过 `includes` 引用 (路径相对于引用它的文件), 用 `run_group` 动作调用:
+//!
+//! ```yaml
+//! includes: ["common/provision.yaml"]
+//! steps:
+//!   - action: { type: run_group, name: provision, with: { vm: win10-01, boot_secs: 30 } }
+//! ```
+//!
+//! 加载时把 `run_group` 原地展开为组内步骤, 并把 `with` 中的参数代入 `${参数}`
+//! (整个字符串只有一个引用时按 YAML 标量解析, 因此数字字段也可以参数化);
+//! 执行器看到的是平铺的步骤列表。引用文件和组调用出现循环时加载失败。
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use serde_yaml::{Mapping, Value};
+
+use crate::{ExecutorError, Result};
+
+/// 步骤组: 名称 -> (组内步骤, 定义所在的文件)
+type Groups = HashMap<String, (Vec<Value>, String)>;
+
+/// `run_group` 步骤上除动作外允许的字段
+const RUN_GROUP_STEP_FIELDS: &[&str] = &["name", "action", "tags"];
+
+/// 场景文件是否使用了引用或步骤组 (需要展开后才能独立使用)
+pub fn uses_step_groups(yaml: &str) -> bool {
+    serde_yaml::from_str::<Value>(yaml)
+        .ok()
+        .and_then(|value| value.as_mapping().cloned())
+        .is_some_and(|mapping| mapping.contains_key("includes") || mapping.contains_key("groups"))
+}
+
+/// 加载引用的文件并展开场景中的 `run_group` 步骤
+///
+/// `origin` 为场景文件自身的路径 (从字符串加载时为 None), 用于检测引用循环。
+pub(crate) fn expand(value: &mut Value, base_dir: &Path, origin: Option<&Path>) -> Result<()> {
+    let Some(root) = value.as_mapping_mut() else {
+        return Ok(());
+    };
+
+    let mut stack: Vec<PathBuf> = origin.and_then(|path| path.canonicalize().ok()).into_iter().collect();
+    let mut loaded: HashSet<PathBuf> = stack.iter().cloned().collect();
+    let mut groups = Groups::new();
+    let source = origin.map_or_else(|| "场景".to_string(), |path| path.display().to_string());
+    collect_groups(root, base_dir, &source, &mut stack, &mut loaded, &mut groups)?;
+
+    for key in ["setup", "steps", "teardown"] {
+        let Some(steps) = root.get(key).and_then(Value::as_sequence) else {
+            continue;
+        };
+        let expanded = expand_steps(steps, &groups, &mut Vec::new())?;
+        root.insert(key.into(), Value::Sequence(expanded));
+    }
+
+    Ok(())
+}
+
+/// 读取 `includes` 引用的文件与本文件的 `groups`, 并从文档中移除这两个键
+fn collect_groups(
+    doc: &mut Mapping,
+    base_dir: &Path,
+    source: &str,
+    stack: &mut Vec<PathBuf>,
+    loaded: &mut HashSet<PathBuf>,
+    groups: &mut Groups,
+) -> Result<()> {
+    if let Some(includes) = doc.remove("includes") {
+        let includes: Vec<String> = serde_yaml::from_value(includes)
+            .map_err(|e| load_failed(format!("{}: includes 必须是文件路径列表: {}", source, e)))?;
+
+        for include in includes {
+            let path = base_dir.join(&include);
+            let canonical = path
+                .canonicalize()
+                .map_err(|e| load_failed(format!("{}: 无法读取引用的文件 {}: {}", source, path.display(), e)))?;
+
+            if let Some(position) = stack.iter().position(|entry| *entry == canonical) {
+                let chain: Vec<String> = stack[position..]
+                    .iter()
+                    .chain(std::iter::once(&canonical))
+                    .map(|entry| entry.display().to_string())
+                    .collect();
+                return Err(load_failed(format!("场景引用形成循环: {}", chain.join(" -> "))));
+            }
+            // 多个文件引用同一个文件时只加载一次
+            if !loaded.insert(canonical.clone()) {
+                continue;
+            }
+
+            let content = std::fs::read_to_string(&canonical)?;
+            let mut value: Value = serde_yaml::from_str(&content)
+                .map_err(|e| load_failed(format!("{}: {}", canonical.display(), e)))?;
+            value
+                .apply_merge()
+                .map_err(|e| load_failed(format!("{}: {}", canonical.display(), e)))?;
+            let Some(included) = value.as_mapping_mut() else {
+                return Err(load_failed(format!("{}: 引用的文件必须是映射", canonical.display())));
+            };
+
+            let included_dir = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+            let included_source = canonical.display().to_string();
+            stack.push(canonical);
+            collect_groups(included, &included_dir, &included_source, stack, loaded, groups)?;
+            stack.pop();
+        }
+    }
+
+    if let Some(local) = doc.remove("groups") {
+        let Some(local) = local.as_mapping() else {
+            return Err(load_failed(format!("{}: groups 必须是 名称 -> 步骤列表 的映射", source)));
+        };
+        for (name, steps) in local {
+            let (Some(name), Some(steps)) = (name.as_str(), steps.as_sequence()) else {
+                return Err(load_failed(format!("{}: 步骤组 {:?} 必须是步骤列表", source, name)));
+            };
+            if let Some((_, defined_in)) = groups.get(name) {
+                return Err(load_failed(format!(
+                    "步骤组 {} 重复定义 ({} 与 {})",
+                    name, defined_in, source
+                )));
+            }
+            groups.insert(name.to_string(), (steps.clone(), source.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// 一次 `run_group` 调用
+struct GroupCall {
+    name: String,
+    params: HashMap<String, String>,
+    tags: Vec<Value>,
+}
+
+/// 步骤是 `run_group` 时解析调用参数
+fn group_call(step: &Value) -> Result<Option<GroupCall>> {
+    let Some(action) = step.get("action") else {
+        return Ok(None);
+    };
+    if action.get("type").and_then(Value::as_str) != Some("run_group") {
+        return Ok(None);
+    }
+
+    let name = action
+        .get("name")
+        .and_then(Value::as_str)
+        .ok_or_else(|| load_failed("run_group 缺少步骤组名称 (name)".to_string()))?
+        .to_string();
+
+    if let Some(field) = step
+        .as_mapping()
+        .into_iter()
+        .flat_map(Mapping::keys)
+        .filter_map(Value::as_str)
+        .find(|key| !RUN_GROUP_STEP_FIELDS.contains(key))
+    {
+        return Err(load_failed(format!("run_group {} 的步骤不支持字段 {}", name, field)));
+    }
+
+    let mut params = HashMap::new();
+    if let Some(with) = action.get("with") {
+        let Some(with) = with.as_mapping() else {
+            return Err(load_failed(format!("run_group {} 的 with 必须是 参数 -> 值 的映射", name)));
+        };
+        for (key, value) in with {
+            let value = match value {
+                Value::String(text) => text.clone(),
+                Value::Number(number) => number.to_string(),
+                Value::Bool(flag) => flag.to_string(),
+                _ => return Err(load_failed(format!("run_group {} 的参数 {:?} 必须是标量", name, key))),
+            };
+            let key = key
+                .as_str()
+                .ok_or_else(|| load_failed(format!("run_group {} 的参数名必须是字符串", name)))?;
+            params.insert(key.to_string(), value);
+        }
+    }
+
+    let tags = step
+        .get("tags")
+        .and_then(Value::as_sequence)
+        .cloned()
+        .unwrap_or_default();
+
+    Ok(Some(GroupCall { name, params, tags }))
+}
+
+/// 展开步骤列表中的 `run_group` (组内也可以调用其他组)
+fn expand_steps(steps: &[Value], groups: &Groups, calls: &mut Vec<String>) -> Result<Vec<Value>> {
+    let mut expanded = Vec::with_capacity(steps.len());
+
+    for step in steps {
+        let Some(call) = group_call(step)? else {
+            expanded.push(step.clone());
+            continue;
+        };
+
+        if calls.contains(&call.name) {
+            let chain: Vec<&str> = calls
+                .iter()
+                .skip_while(|name| **name != call.name)
+                .map(String::as_str)
+                .chain(std::iter::once(call.name.as_str()))
+                .collect();
+            return Err(load_failed(format!("步骤组调用形成循环: {}", chain.join(" -> "))));
+        }
+        let Some((group_steps, _)) = groups.get(&call.name) else {
+            let mut names: Vec<&str> = groups.keys().map(String::as_str).collect();
+            names.sort_unstable();
+            return Err(load_failed(format!(
+                "未定义的步骤组 {} (已定义: {})",
+                call.name,
+                if names.is_empty() { "无".to_string() } else { names.join(", ") }
+            )));
+        };
+
+        calls.push(call.name.clone());
+        let inner = expand_steps(group_steps, groups, calls)?;
+        calls.pop();
+
+        for mut inner_step in inner {
+            substitute(&mut inner_step, &call.params);
+            add_tags(&mut inner_step, &call.tags);
+            expanded.push(inner_step);
+        }
+    }
+
+    Ok(expanded)
+}
+
+/// 把调用上的标签追加到展开后的步骤
+fn add_tags(step: &mut Value, tags: &[Value]) {
+    if tags.is_empty() {
+        return;
+    }
+    let Some(mapping) = step.as_mapping_mut() else {
+        return;
+    };
+    let mut merged = mapping.get("tags").and_then(Value::as_sequence).cloned().unwrap_or_default();
+    for tag in tags {
+        if !merged.contains(tag) {
+            merged.push(tag.clone());
+        }
+    }
+    mapping.insert("tags".into(), Value::Sequence(merged));
+}
+
+/// 把参数代入所有字符串中的 `${参数}`, 未提供的引用保持原样
+fn substitute(value: &mut Value, params: &HashMap<String, String>) {
+    match value {
+        Value::String(text) => {
+            let trimmed = text.trim();
+            let whole = trimmed
+                .strip_prefix("${")
+                .and_then(|rest| rest.strip_suffix('}'))
+                .filter(|name| !name.contains('}'))
+                .and_then(|name| params.get(name.trim()));
+            *value = match whole {
+                Some(param) => serde_yaml::from_str(param).unwrap_or_else(|_| Value::String(param.clone())),
+                None => Value::String(substitute_str(text, params)),
+            };
+        }
+        Value::Sequence(items) => items.iter_mut().for_each(|item| substitute(item, params)),
+        Value::Mapping(mapping) => mapping.iter_mut().for_each(|(_, item)| substitute(item, params)),
+        _ => {}
+    }
+}
+
+fn substitute_str(text: &str, params: &HashMap<String, String>) -> String {
+    substitute_with(text, |name| params.get(name).map(String::as_str))
+}
+
+/// 把字符串中的 `${名称}` 替换为 `lookup` 返回的值, 未定义的引用保持原样
+pub(crate) fn substitute_with<'a>(text: &str, lookup: impl Fn(&str) -> Option<&'a str>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        let Some(end) = after.find('}') else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        match lookup(after[..end].trim()) {
+            Some(value) => result.push_str(value),
+            None => result.push_str(&rest[start..start + 2 + end + 1]),
+        }
+        rest = &after[end + 1..];
+    }
+
+    result.push_str(rest);
+    result
+}
+
+fn load_failed(message: String) -> ExecutorError {
+    ExecutorError::ScenarioLoadFailed(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, Scenario};
+
+    /// 在临时目录中写入文件, 返回目录
+    fn write_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("atp-groups-{}-{}", name, std::process::id()));
+        for (path, content) in files {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    const PROVISION: &str = r#"
+groups:
+  provision:
+    - name: "启动 ${vm}"
+      action: { type: vdi_start_domain, domain_id: "${vm}" }
+    - action: { type: wait, duration: "${boot_secs}" }
+      tags: ["boot"]
+"#;
+
+    #[test]
+    fn test_expand_included_group() {
+        let root = write_files("include", &[
+            ("common/provision.yaml", PROVISION),
+            ("scenario.yaml", r#"
+name: "引用"
+includes: ["common/provision.yaml"]
+steps:
+  - action: { type: run_group, name: provision, with: { vm: win10-01, boot_secs: 30 } }
+    tags: ["smoke"]
+  - action: { type: exec_command, command: "hostname" }
+"#),
+        ]);
+
+        let scenario = Scenario::from_yaml_file(root.join("scenario.yaml")).unwrap();
+        assert_eq!(scenario.steps.len(), 3);
+        assert_eq!(scenario.steps[0].name.as_deref(), Some("启动 win10-01"));
+        assert!(matches!(&scenario.steps[0].action, Action::VdiStartDomain { domain_id } if domain_id == "win10-01"));
+        assert!(matches!(scenario.steps[1].action, Action::Wait { duration: 30 }));
+        assert_eq!(scenario.steps[0].tags, vec!["smoke"]);
+        assert_eq!(scenario.steps[1].tags, vec!["boot", "smoke"]);
+
+        // 导出的是展开后的形式, 可以独立重新加载
+        let yaml = scenario.to_yaml().unwrap();
+        assert!(!yaml.contains("run_group") && !yaml.contains("includes"));
+        let reloaded = Scenario::from_yaml_str(&yaml).unwrap();
+        assert_eq!(reloaded.to_yaml().unwrap(), yaml);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_nested_groups_and_local_definitions() {
+        let yaml = r#"
+name: "嵌套"
+groups:
+  login:
+    - action: { type: send_text, text: "${user}" }
+  boot_and_login:
+    - action: { type: wait, duration: 1 }
+    - action: { type: run_group, name: login, with: { user: "${account}" } }
+steps:
+  - action: { type: run_group, name: boot_and_login, with: { account: admin } }
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        assert_eq!(scenario.steps.len(), 2);
+        assert!(matches!(&scenario.steps[1].action, Action::SendText { text } if text == "admin"));
+    }
+
+    #[test]
+    fn test_group_errors() {
+        let load_error = |yaml: &str| Scenario::from_yaml_str(yaml).unwrap_err().to_string();
+
+        let error = load_error(r#"
+name: "循环"
+groups:
+  a: [{ action: { type: run_group, name: b } }]
+  b: [{ action: { type: run_group, name: a } }]
+steps:
+  - action: { type: run_group, name: a }
+"#);
+        assert!(error.contains("步骤组调用形成循环: a -> b -> a"), "{}", error);
+
+        let error = load_error(r#"
+name: "未定义"
+groups:
+  login: []
+steps:
+  - action: { type: run_group, name: logon }
+"#);
+        assert!(error.contains("未定义的步骤组 logon (已定义: login)"), "{}", error);
+
+        let error = load_error(r#"
+name: "字段"
+groups:
+  login: []
+steps:
+  - action: { type: run_group, name: login }
+    timeout: 5
+"#);
+        assert!(error.contains("不支持字段 timeout"), "{}", error);
+    }
+
+    #[test]
+    fn test_include_cycle() {
+        let root = write_files("cycle", &[
+            ("a.yaml", "includes: [\"lib/b.yaml\"]\ngroups: { a: [] }\n"),
+            ("lib/b.yaml", "includes: [\"../a.yaml\"]\n"),
+            ("scenario.yaml", "name: \"循环\"\nincludes: [\"a.yaml\"]\nsteps: []\n"),
+        ]);
+
+        let error = Scenario::from_yaml_file(root.join("scenario.yaml")).unwrap_err().to_string();
+        assert!(error.contains("场景引用形成循环"), "{}", error);
+        assert!(error.contains("a.yaml -> ") && error.contains("b.yaml"), "{}", error);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_substitute_str() {
+        let params = HashMap::from([("vm".to_string(), "win10".to_string())]);
+        assert_eq!(substitute_str("ping ${vm} ${ vm } ${other}", &params), "ping win10 win10 ${other}");
+        assert_eq!(substitute_str("${unterminated", &params), "${unterminated");
+        assert!(uses_step_groups("name: x\nincludes: []\nsteps: []"));
+        assert!(!uses_step_groups("name: x\nsteps: []"));
+    }
+}
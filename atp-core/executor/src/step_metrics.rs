@@ -0,0 +1,371 @@
+//! 步骤资源指标
+//!
+//! 启用采样后, 每个步骤执行期间在后台按固定间隔通过 libvirt 读取当前虚拟机的 CPU 时间与内存,
+//! 步骤结束时再补采一次, 汇总为最小/平均/最大值; QMP 可用时在步骤前后各执行一次
+//! `query-blockstats`, 差值即步骤期间的磁盘读写量。
+//!
+//! 采样是尽力而为的: 失败只记录告警 (每个步骤最多一次), 不会让步骤失败。
+//! CPU 使用率由相邻两次采样差分得到, 因此至少需要两次采样。
+//!
+//! 每次采样同时转换为虚拟机的时序指标 (`cpu` / `memory_mb`, 见 [`metric_samples`]),
+//! 配置了数据库时写入 `metrics` 表, 可用 `atp report metrics` 按时间段查询。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use atp_storage::{EntityMetricSample, MetricEntity, StepMetricsRecord};
+use atp_transport::{cpu_usage_percent, DomainStatsSample, TransportManager};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// 一项指标在步骤期间的最小/平均/最大值
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MetricSummary {
+    pub min: f64,
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl MetricSummary {
+    /// 没有样本时返回 None
+    pub fn from_values(values: &[f64]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let avg = values.iter().sum::<f64>() / values.len() as f64;
+        Some(Self { min, avg, max })
+    }
+
+    fn from_columns(min: Option<f64>, avg: Option<f64>, max: Option<f64>) -> Option<Self> {
+        Some(Self { min: min?, avg: avg?, max: max? })
+    }
+}
+
+/// 块设备累计 I/O 字节数 (所有设备之和)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockStats {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+}
+
+impl BlockStats {
+    /// 解析 `query-blockstats` 的返回值, 格式不符时返回 None
+    pub fn from_qmp(value: &serde_json::Value) -> Option<Self> {
+        let devices = value.as_array()?;
+        let mut stats = Self::default();
+
+        for device in devices {
+            let counters = &device["stats"];
+            stats.read_bytes += counters["rd_bytes"].as_u64().unwrap_or(0);
+            stats.write_bytes += counters["wr_bytes"].as_u64().unwrap_or(0);
+        }
+
+        Some(stats)
+    }
+}
+
+/// 单个步骤的资源指标
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StepMetrics {
+    /// libvirt 采样次数
+    pub sample_count: usize,
+
+    /// 采样间隔 (毫秒)
+    pub interval_ms: u64,
+
+    /// 虚拟机 CPU 使用率 (%, 按 vCPU 数归一化)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_usage_percent: Option<MetricSummary>,
+
+    /// 虚拟机内存 (MB, 有 balloon 统计时为来宾已用内存, 否则为分配的内存)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory_mb: Option<MetricSummary>,
+
+    /// 步骤期间的磁盘读取量 (字节, 需要 QMP)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_read_bytes: Option<u64>,
+
+    /// 步骤期间的磁盘写入量 (字节, 需要 QMP)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block_write_bytes: Option<u64>,
+}
+
+impl StepMetrics {
+    /// 由按时间排序的 libvirt 采样汇总
+    pub fn from_samples(samples: &[DomainStatsSample], interval: Duration) -> Self {
+        let cpu: Vec<f64> = samples
+            .windows(2)
+            .filter_map(|pair| cpu_usage_percent(&pair[0], &pair[1]))
+            .collect();
+        let memory: Vec<f64> = samples
+            .iter()
+            .map(|sample| sample.memory_used_kb().unwrap_or(sample.memory_kb) as f64 / 1024.0)
+            .collect();
+
+        Self {
+            sample_count: samples.len(),
+            interval_ms: interval.as_millis() as u64,
+            cpu_usage_percent: MetricSummary::from_values(&cpu),
+            memory_mb: MetricSummary::from_values(&memory),
+            block_read_bytes: None,
+            block_write_bytes: None,
+        }
+    }
+
+    /// 记录步骤前后两次 `query-blockstats` 的差值
+    pub fn with_block_io(mut self, before: BlockStats, after: BlockStats) -> Self {
+        self.block_read_bytes = Some(after.read_bytes.saturating_sub(before.read_bytes));
+        self.block_write_bytes = Some(after.write_bytes.saturating_sub(before.write_bytes));
+        self
+    }
+
+    /// 是否没有任何可用指标
+    pub fn is_empty(&self) -> bool {
+        self.sample_count == 0 && self.block_read_bytes.is_none() && self.block_write_bytes.is_none()
+    }
+
+    /// 转换为数据库记录
+    pub fn to_record(&self, report_id: i64, step_index: usize) -> StepMetricsRecord {
+        StepMetricsRecord {
+            id: 0,
+            report_id,
+            step_index: step_index as i32,
+            sample_count: self.sample_count as i32,
+            interval_ms: self.interval_ms as i64,
+            cpu_min: self.cpu_usage_percent.map(|m| m.min),
+            cpu_avg: self.cpu_usage_percent.map(|m| m.avg),
+            cpu_max: self.cpu_usage_percent.map(|m| m.max),
+            memory_min_mb: self.memory_mb.map(|m| m.min),
+            memory_avg_mb: self.memory_mb.map(|m| m.avg),
+            memory_max_mb: self.memory_mb.map(|m| m.max),
+            block_read_bytes: self.block_read_bytes.map(|bytes| bytes as i64),
+            block_write_bytes: self.block_write_bytes.map(|bytes| bytes as i64),
+        }
+    }
+
+    /// 从数据库记录恢复
+    pub fn from_record(record: &StepMetricsRecord) -> Self {
+        Self {
+            sample_count: record.sample_count.max(0) as usize,
+            interval_ms: record.interval_ms.max(0) as u64,
+            cpu_usage_percent: MetricSummary::from_columns(record.cpu_min, record.cpu_avg, record.cpu_max),
+            memory_mb: MetricSummary::from_columns(record.memory_min_mb, record.memory_avg_mb, record.memory_max_mb),
+            block_read_bytes: record.block_read_bytes.map(|bytes| bytes.max(0) as u64),
+            block_write_bytes: record.block_write_bytes.map(|bytes| bytes.max(0) as u64),
+        }
+    }
+}
+
+/// 虚拟机 CPU 使用率的时序指标名称 (%)
+pub const CPU_METRIC: &str = "cpu";
+
+/// 虚拟机内存的时序指标名称 (MB)
+pub const MEMORY_METRIC: &str = "memory_mb";
+
+/// 把按时间排序的 libvirt 采样转换为虚拟机的时序指标
+///
+/// CPU 使用率由相邻两次采样差分得到, 记在后一次采样的时间上。
+pub fn metric_samples(domain: &str, samples: &[DomainStatsSample]) -> Vec<EntityMetricSample> {
+    let entity = MetricEntity::vm(domain);
+
+    let cpu = samples.windows(2).filter_map(|pair| {
+        cpu_usage_percent(&pair[0], &pair[1])
+            .map(|percent| EntityMetricSample::new(&entity, CPU_METRIC, percent, pair[1].timestamp))
+    });
+    let memory = samples.iter().map(|sample| {
+        let memory_mb = sample.memory_used_kb().unwrap_or(sample.memory_kb) as f64 / 1024.0;
+        EntityMetricSample::new(&entity, MEMORY_METRIC, memory_mb, sample.timestamp)
+    });
+
+    cpu.chain(memory).collect()
+}
+
+/// 步骤执行期间的后台采样任务
+///
+/// 丢弃时停止采样 (如步骤超时或被取消)。
+pub(crate) struct StepMetricsSampler {
+    domain: String,
+    interval: Duration,
+    stop: CancellationToken,
+    handle: Option<JoinHandle<Vec<DomainStatsSample>>>,
+}
+
+impl StepMetricsSampler {
+    /// 立即采样一次, 之后每个间隔采样一次, 直到 [`finish`](Self::finish)
+    pub(crate) fn start(
+        transport: Arc<TransportManager>,
+        host_id: String,
+        domain: String,
+        interval: Duration,
+    ) -> Self {
+        let stop = CancellationToken::new();
+        let token = stop.clone();
+        let sampled_domain = domain.clone();
+
+        let handle = tokio::spawn(async move {
+            let domain = sampled_domain;
+            let mut samples = Vec::new();
+            let mut warned = false;
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            loop {
+                tokio::select! {
+                    _ = token.cancelled() => break,
+                    _ = ticker.tick() => {
+                        sample_once(&transport, &host_id, &domain, &mut samples, &mut warned).await;
+                    }
+                }
+            }
+
+            // 步骤结束时补采一次, 短于采样间隔的步骤也能得到 CPU 使用率
+            sample_once(&transport, &host_id, &domain, &mut samples, &mut warned).await;
+            samples
+        });
+
+        Self {
+            domain,
+            interval,
+            stop,
+            handle: Some(handle),
+        }
+    }
+
+    /// 停止采样并汇总, 同时返回各次采样对应的时序指标
+    pub(crate) async fn finish(mut self) -> (StepMetrics, Vec<EntityMetricSample>) {
+        self.stop.cancel();
+
+        let samples = match self.handle.take() {
+            Some(handle) => handle.await.unwrap_or_else(|e| {
+                warn!("步骤资源采样任务异常退出: {}", e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+
+        (StepMetrics::from_samples(&samples, self.interval), metric_samples(&self.domain, &samples))
+    }
+}
+
+impl Drop for StepMetricsSampler {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+async fn sample_once(
+    transport: &TransportManager,
+    host_id: &str,
+    domain: &str,
+    samples: &mut Vec<DomainStatsSample>,
+    warned: &mut bool,
+) {
+    let result = match transport.pool().get_connection(host_id).await {
+        Ok(connection) => connection.sample_domain_stats(domain).await,
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(sample) => samples.push(sample),
+        Err(e) if !*warned => {
+            warn!("采样虚拟机 {} 资源失败, 本步骤的指标可能不完整: {}", domain, e);
+            *warned = true;
+        }
+        Err(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+
+    fn sample(cpu_time_ns: u64, memory_kb: u64, offset_ms: i64) -> DomainStatsSample {
+        let base = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        DomainStatsSample {
+            timestamp: base + chrono::Duration::milliseconds(offset_ms),
+            ..DomainStatsSample::new("win10", cpu_time_ns, 2, memory_kb)
+        }
+    }
+
+    #[test]
+    fn test_step_metrics_from_samples() {
+        // 2 个 vCPU, 每 1 秒分别消耗 0.5s / 1.5s CPU 时间 -> 25% / 75%
+        let samples = vec![
+            sample(0, 1024 * 1024, 0),
+            sample(500_000_000, 2048 * 1024, 1000),
+            sample(2_000_000_000, 1536 * 1024, 2000),
+        ];
+        let metrics = StepMetrics::from_samples(&samples, Duration::from_secs(1));
+
+        assert_eq!((metrics.sample_count, metrics.interval_ms), (3, 1000));
+        let cpu = metrics.cpu_usage_percent.unwrap();
+        assert_eq!((cpu.min, cpu.avg, cpu.max), (25.0, 50.0, 75.0));
+        let memory = metrics.memory_mb.unwrap();
+        assert_eq!((memory.min, memory.avg, memory.max), (1024.0, 1536.0, 2048.0));
+
+        // 只有一次采样时没有 CPU 使用率
+        let metrics = StepMetrics::from_samples(&samples[..1], Duration::from_secs(1));
+        assert!(metrics.cpu_usage_percent.is_none());
+        assert!(metrics.memory_mb.is_some());
+
+        let empty = StepMetrics::from_samples(&[], Duration::from_secs(1));
+        assert!(empty.is_empty());
+        assert!(!empty.with_block_io(BlockStats::default(), BlockStats::default()).is_empty());
+    }
+
+    #[test]
+    fn test_metric_samples() {
+        let samples = vec![sample(0, 1024 * 1024, 0), sample(500_000_000, 2048 * 1024, 1000)];
+        let points = metric_samples("win10", &samples);
+
+        let cpu: Vec<&EntityMetricSample> = points.iter().filter(|point| point.metric_name == CPU_METRIC).collect();
+        assert_eq!(cpu.len(), 1);
+        assert_eq!((cpu[0].value, cpu[0].sampled_at), (25.0, samples[1].timestamp));
+        assert_eq!((cpu[0].entity_type.as_str(), cpu[0].entity_id.as_str()), ("vm", "win10"));
+
+        let memory: Vec<f64> = points
+            .iter()
+            .filter(|point| point.metric_name == MEMORY_METRIC)
+            .map(|point| point.value)
+            .collect();
+        assert_eq!(memory, vec![1024.0, 2048.0]);
+        assert!(metric_samples("win10", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_block_stats_from_qmp() {
+        let value = serde_json::json!([
+            { "device": "drive-virtio-disk0", "stats": { "rd_bytes": 1000, "wr_bytes": 200 } },
+            { "device": "drive-ide0-0-0", "stats": { "rd_bytes": 24 } },
+        ]);
+        let before = BlockStats::from_qmp(&value).unwrap();
+        assert_eq!(before, BlockStats { read_bytes: 1024, write_bytes: 200 });
+        assert!(BlockStats::from_qmp(&serde_json::json!({})).is_none());
+
+        let after = BlockStats { read_bytes: 4096, write_bytes: 1200 };
+        let metrics = StepMetrics::from_samples(&[], Duration::from_millis(500)).with_block_io(before, after);
+        assert_eq!((metrics.block_read_bytes, metrics.block_write_bytes), (Some(3072), Some(1000)));
+    }
+
+    #[test]
+    fn test_step_metrics_record_roundtrip() {
+        let samples = vec![sample(0, 1024 * 1024, 0), sample(1_000_000_000, 1024 * 1024, 1000)];
+        let metrics = StepMetrics::from_samples(&samples, Duration::from_millis(500))
+            .with_block_io(BlockStats::default(), BlockStats { read_bytes: 10, write_bytes: 20 });
+
+        let record = metrics.to_record(7, 3);
+        assert_eq!((record.report_id, record.step_index, record.sample_count), (7, 3, 2));
+        assert_eq!(record.cpu_avg, Some(50.0));
+        assert_eq!(StepMetrics::from_record(&record), metrics);
+
+        let json = serde_json::to_value(StepMetrics::from_samples(&[], Duration::from_secs(1))).unwrap();
+        assert!(json.get("cpu_usage_percent").is_none());
+    }
+}
@@ -11,8 +11,29 @@
 //! 3. `./tests/config.toml` (tests 目录)
 //! 4. `~/.config/atp/test.toml` (用户配置目录)
 //! 5. `/etc/atp/test.toml` (系统配置目录)
+//!
+//! 多环境配置: 配置文件可以包含 `[default]` 与 `[profile.<name>]` 两部分,
+//! 选中的 profile 覆盖在 `[default]` 之上 (表逐层合并, 数组与标量整体替换):
+//!
+//! ```toml
+//! [default.vdi]
+//! base_url = "http://vdi-dev:8088"
+//! username = "admin"
+//! password = ""
+//!
+//! [profile.staging.vdi]
+//! base_url = "http://vdi-staging:8088"
+//!
+//! [profile.staging.libvirt.hosts.node1]
+//! id = "node1"
+//! host = "10.0.1.11"
+//! uri = "qemu+tcp://10.0.1.11/system"
+//! ```
+//!
+//! 密码等敏感信息建议不写入文件, 通过环境变量 (如 `ATP_VDI_PASSWORD`) 提供。
 
 use anyhow::{Context, Result};
+use atp_transport::{HostEntry, PoolConfig, ReconnectConfig, SshConfig, TransportConfig, SUDO_PASSWORD_ENV};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env;
@@ -53,6 +74,10 @@ pub struct TestConfig {
     /// 数据库配置
     #[serde(skip_serializing_if = "Option::is_none")]
     pub database: Option<DatabaseConfig>,
+
+    /// 场景变量 (`[variables]`), 执行场景时代入 `${变量}`, CLI `--var` 覆盖同名变量
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub variables: HashMap<String, String>,
 }
 
 /// 环境配置
@@ -89,6 +114,14 @@ pub struct LibvirtConfig {
     /// 主机列表 (可选)
     #[serde(default)]
     pub hosts: HashMap<String, LibvirtHostConfig>,
+
+    /// 连接池配置
+    #[serde(default)]
+    pub pool: PoolConfig,
+
+    /// 重连配置
+    #[serde(default)]
+    pub reconnect: ReconnectConfig,
 }
 
 /// Libvirt 主机配置
@@ -97,6 +130,14 @@ pub struct LibvirtHostConfig {
     pub id: String,
     pub host: String,
     pub uri: String,
+
+    /// 标签
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// SSH 配置 (执行宿主机命令时使用)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh: Option<SshConfig>,
 }
 
 /// 虚拟机配置
@@ -300,6 +341,7 @@ impl Default for TestConfig {
             vdi: None,
             test: TestBehaviorConfig::default(),
             database: None,
+            variables: HashMap::new(),
         }
     }
 }
@@ -321,6 +363,8 @@ impl Default for LibvirtConfig {
             heartbeat_interval: default_heartbeat_interval(),
             auto_reconnect: default_auto_reconnect(),
             hosts: HashMap::new(),
+            pool: PoolConfig::default(),
+            reconnect: ReconnectConfig::default(),
         }
     }
 }
@@ -405,10 +449,13 @@ impl TestConfig {
         // 1. 从默认值开始
         let mut config = Self::default();
 
-        // 2. 尝试加载配置文件
+        // 2. 尝试加载配置文件 (ATP_TEST_PROFILE 指定 profile)
         if let Some(path) = Self::find_config_file() {
             tracing::debug!("Loading config from: {:?}", path);
-            config = Self::load_from_file(&path)?;
+            config = match env::var("ATP_TEST_PROFILE") {
+                Ok(profile) => Self::read_profile(&path, Some(&profile))?,
+                Err(_) => Self::load_from_file(&path)?,
+            };
         } else {
             tracing::debug!("No config file found, using defaults");
         }
@@ -420,12 +467,50 @@ impl TestConfig {
     }
 
     /// 从指定文件加载配置
+    ///
+    /// 文件包含 `[default]` 段时只使用该段, 不应用任何 profile。
     pub fn load_from_file(path: &Path) -> Result<Self> {
+        Self::read_profile(path, None)
+    }
+
+    /// 从指定文件加载 profile 配置 (`[default]` + `[profile.<name>]`)
+    ///
+    /// profile 不存在时返回错误并列出可用的 profile; 加载后应用环境变量覆盖。
+    pub fn load_profile(path: &Path, name: &str) -> Result<Self> {
+        Self::load_with_env(path, Some(name))
+    }
+
+    /// 从指定文件加载配置 (可选 profile), 加载后应用环境变量覆盖
+    ///
+    /// 不指定 profile 时与 [`Self::load_from_file`] 读取相同的段, 密码等敏感信息同样可以来自环境变量。
+    pub fn load_with_env(path: &Path, profile: Option<&str>) -> Result<Self> {
+        Self::load_with(path, profile, |var| env::var(var).ok())
+    }
+
+    /// 加载配置, 用 `lookup` 代替环境变量查询
+    fn load_with(path: &Path, profile: Option<&str>, lookup: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config = Self::read_profile(path, profile)?;
+        config.apply_vars_from(lookup)?;
+        Ok(config)
+    }
+
+    /// 读取配置文件并合并 profile
+    fn read_profile(path: &Path, profile: Option<&str>) -> Result<Self> {
+        let root = Self::read_value(path)?;
+        let merged = resolve_profile(root, profile)
+            .with_context(|| format!("Failed to resolve profile in config: {:?}", path))?;
+
+        serde_json::from_value(merged)
+            .with_context(|| format!("Failed to parse config: {:?}", path))
+    }
+
+    /// 按扩展名解析配置文件为通用值
+    fn read_value(path: &Path) -> Result<serde_json::Value> {
         let content = fs::read_to_string(path)
             .with_context(|| format!("Failed to read config file: {:?}", path))?;
 
         // 根据文件扩展名选择解析器
-        let config = if path.extension().and_then(|s| s.to_str()) == Some("toml") {
+        let value = if path.extension().and_then(|s| s.to_str()) == Some("toml") {
             toml::from_str(&content)
                 .with_context(|| format!("Failed to parse TOML config: {:?}", path))?
         } else if path.extension().and_then(|s| s.to_str()) == Some("yaml")
@@ -440,7 +525,7 @@ impl TestConfig {
             anyhow::bail!("Unsupported config file format: {:?}", path);
         };
 
-        Ok(config)
+        Ok(value)
     }
 
     /// 从指定路径字符串加载配置
@@ -507,54 +592,65 @@ impl TestConfig {
 
     /// 从环境变量覆盖配置
     fn apply_env_vars(&mut self) -> Result<()> {
+        self.apply_vars_from(|name| env::var(name).ok())
+    }
+
+    /// 用 `lookup` 查到的值覆盖配置 (`lookup` 按环境变量名返回值)
+    fn apply_vars_from(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
         // Environment
-        if let Ok(mode) = env::var("ATP_TEST_MODE") {
+        if let Some(mode) = lookup("ATP_TEST_MODE") {
             self.environment.mode = mode;
         }
-        if let Ok(level) = env::var("ATP_LOG_LEVEL") {
+        if let Some(level) = lookup("ATP_LOG_LEVEL") {
             self.environment.log_level = level;
         }
 
         // Libvirt
-        if let Ok(uri) = env::var("ATP_TEST_HOST") {
+        if let Some(uri) = lookup("ATP_TEST_HOST") {
             self.libvirt.uri = uri;
         }
-        if let Ok(timeout) = env::var("ATP_CONNECT_TIMEOUT") {
+        if let Some(timeout) = lookup("ATP_CONNECT_TIMEOUT") {
             self.libvirt.connect_timeout = timeout
                 .parse()
                 .context("Invalid ATP_CONNECT_TIMEOUT value")?;
         }
+        // sudo 密码不写入配置文件, 设置给开启了 sudo 的主机
+        if let Some(password) = lookup(SUDO_PASSWORD_ENV).filter(|password| !password.is_empty()) {
+            for ssh in self.libvirt.hosts.values_mut().filter_map(|host| host.ssh.as_mut()) {
+                ssh.set_sudo_password(Some(password.clone()));
+            }
+        }
 
         // VM
-        if let Ok(name) = env::var("ATP_TEST_VM") {
+        if let Some(name) = lookup("ATP_TEST_VM") {
             self.vm.name = name;
         }
-        if let Ok(user) = env::var("ATP_TEST_VM_USER") {
+        if let Some(user) = lookup("ATP_TEST_VM_USER") {
             self.vm.user = Some(user);
         }
-        if let Ok(password) = env::var("ATP_TEST_VM_PASSWORD") {
+        if let Some(password) = lookup("ATP_TEST_VM_PASSWORD") {
             self.vm.password = Some(password);
         }
 
         // Protocols - QMP
         if let Some(ref mut qmp) = self.protocols.qmp {
-            if let Ok(prefix) = env::var("ATP_QMP_SOCKET") {
+            if let Some(prefix) = lookup("ATP_QMP_SOCKET") {
                 qmp.socket_prefix = prefix;
             }
         }
 
         // Protocols - SPICE
         if let Some(ref mut spice) = self.protocols.spice {
-            if let Ok(host) = env::var("ATP_SPICE_HOST") {
+            if let Some(host) = lookup("ATP_SPICE_HOST") {
                 spice.host = host;
             }
-            if let Ok(port) = env::var("ATP_SPICE_PORT") {
+            if let Some(port) = lookup("ATP_SPICE_PORT") {
                 spice.port = port.parse().context("Invalid ATP_SPICE_PORT value")?;
             }
         }
 
         // VDI Platform
-        if let Ok(base_url) = env::var("ATP_VDI_BASE_URL") {
+        if let Some(base_url) = lookup("ATP_VDI_BASE_URL") {
             if self.vdi.is_none() {
                 self.vdi = Some(VdiConfig {
                     base_url: base_url.clone(),
@@ -566,32 +662,32 @@ impl TestConfig {
             }
             self.vdi.as_mut().unwrap().base_url = base_url;
         }
-        if let Ok(username) = env::var("ATP_VDI_USERNAME") {
+        if let Some(username) = lookup("ATP_VDI_USERNAME") {
             if let Some(ref mut vdi) = self.vdi {
                 vdi.username = username;
             }
         }
-        if let Ok(password) = env::var("ATP_VDI_PASSWORD") {
+        if let Some(password) = lookup("ATP_VDI_PASSWORD") {
             if let Some(ref mut vdi) = self.vdi {
                 vdi.password = password;
             }
         }
-        if let Ok(verify_ssl) = env::var("ATP_VDI_VERIFY_SSL") {
+        if let Some(verify_ssl) = lookup("ATP_VDI_VERIFY_SSL") {
             if let Some(ref mut vdi) = self.vdi {
                 vdi.verify_ssl = verify_ssl.parse().unwrap_or(default_verify_ssl());
             }
         }
 
         // Test Behavior
-        if let Ok(timeout) = env::var("ATP_TEST_TIMEOUT") {
+        if let Some(timeout) = lookup("ATP_TEST_TIMEOUT") {
             self.test.timeout = timeout
                 .parse()
                 .context("Invalid ATP_TEST_TIMEOUT value")?;
         }
-        if let Ok(retry) = env::var("ATP_TEST_RETRY") {
+        if let Some(retry) = lookup("ATP_TEST_RETRY") {
             self.test.retry = retry.parse().context("Invalid ATP_TEST_RETRY value")?;
         }
-        if let Ok(skip_slow) = env::var("ATP_TEST_SKIP_SLOW") {
+        if let Some(skip_slow) = lookup("ATP_TEST_SKIP_SLOW") {
             self.test.skip_slow = skip_slow.parse().unwrap_or(false);
         }
 
@@ -646,6 +742,116 @@ impl TestConfig {
     }
 }
 
+// ============================================
+// 传输层配置
+// ============================================
+
+/// 未配置 `libvirt.hosts` 时, 以 `libvirt.uri` 注册的主机 ID
+pub const LOCAL_HOST_ID: &str = "local";
+
+/// 从测试配置构造传输层配置
+///
+/// 传输层不依赖执行器, 因此以扩展 trait 的形式提供 `TransportConfig::from_test_config`。
+pub trait FromTestConfig {
+    fn from_test_config(config: &TestConfig) -> Self;
+}
+
+impl FromTestConfig for TransportConfig {
+    /// 连接超时、心跳、重连与连接池参数取自 `[libvirt]`;
+    /// 主机取自 `[libvirt.hosts]` (按名称排序), 未配置主机时以 `libvirt.uri` 注册一台 `local` 主机。
+    fn from_test_config(config: &TestConfig) -> Self {
+        let libvirt = &config.libvirt;
+
+        let mut names: Vec<&String> = libvirt.hosts.keys().collect();
+        names.sort();
+        let mut hosts: Vec<HostEntry> = names
+            .into_iter()
+            .map(|name| {
+                let host = &libvirt.hosts[name];
+                let mut entry = HostEntry::new(&host.id, &host.host).with_uri(&host.uri);
+                entry.tags = host.tags.clone();
+                entry.ssh = host.ssh.clone();
+                entry
+            })
+            .collect();
+        if hosts.is_empty() {
+            hosts.push(HostEntry::new(LOCAL_HOST_ID, "localhost").with_uri(&libvirt.uri));
+        }
+
+        TransportConfig {
+            pool: libvirt.pool.clone(),
+            connect_timeout: libvirt.connect_timeout,
+            heartbeat_interval: libvirt.heartbeat_interval,
+            auto_reconnect: libvirt.auto_reconnect,
+            reconnect: libvirt.reconnect.clone(),
+            hosts,
+        }
+    }
+}
+
+// ============================================
+// Profile 合并
+// ============================================
+
+/// 从配置文件的顶层值中取出 `[default]` 并覆盖指定 profile
+///
+/// 没有 `[default]` 段时, 顶层 (去掉 `profile`) 即为默认配置, 兼容旧格式。
+fn resolve_profile(root: serde_json::Value, profile: Option<&str>) -> Result<serde_json::Value> {
+    let mut root = match root {
+        serde_json::Value::Object(map) => map,
+        serde_json::Value::Null => serde_json::Map::new(),
+        _ => anyhow::bail!("Config root must be a table"),
+    };
+
+    let mut profiles = match root.remove("profile") {
+        Some(serde_json::Value::Object(map)) => map,
+        Some(_) => anyhow::bail!("`profile` must be a table of named profiles"),
+        None => serde_json::Map::new(),
+    };
+
+    let mut config = match root.remove("default") {
+        Some(default) => default,
+        None => serde_json::Value::Object(root),
+    };
+
+    if let Some(name) = profile {
+        let overlay = profiles.remove(name).ok_or_else(|| {
+            let mut available: Vec<&String> = profiles.keys().collect();
+            available.sort();
+            let available = available
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            anyhow::anyhow!(
+                "Unknown profile '{}', available profiles: [{}]",
+                name,
+                available
+            )
+        })?;
+        merge_values(&mut config, overlay);
+    }
+
+    Ok(config)
+}
+
+/// 把 `overlay` 合并到 `base`: 表逐键递归合并, 其他值 (包括数组) 整体替换
+fn merge_values(base: &mut serde_json::Value, overlay: serde_json::Value) {
+    match (base, overlay) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -686,4 +892,207 @@ mod tests {
         config.vm.name = String::new();
         assert!(config.validate().is_err());
     }
+
+    const PROFILE_TOML: &str = r#"
+[default.vm]
+name = "dev-vm"
+user = "tester"
+
+[default.vdi]
+base_url = "http://vdi-dev:8088"
+username = "admin"
+password = "dev-secret"
+
+[default.libvirt.hosts.node1]
+id = "node1"
+host = "10.0.0.11"
+uri = "qemu+tcp://10.0.0.11/system"
+
+[default.libvirt.hosts.node2]
+id = "node2"
+host = "10.0.0.12"
+uri = "qemu+tcp://10.0.0.12/system"
+
+[profile.staging.vdi]
+base_url = "http://vdi-staging:8088"
+
+[profile.staging.libvirt.hosts.node1]
+host = "10.0.1.11"
+uri = "qemu+tcp://10.0.1.11/system"
+
+[profile.staging.libvirt.hosts.node3]
+id = "node3"
+host = "10.0.1.13"
+uri = "qemu+tcp://10.0.1.13/system"
+
+[profile.prod.vm]
+name = "prod-vm"
+"#;
+
+    fn resolve(profile: Option<&str>) -> Result<TestConfig> {
+        let root: serde_json::Value = toml::from_str(PROFILE_TOML).unwrap();
+        Ok(serde_json::from_value(resolve_profile(root, profile)?)?)
+    }
+
+    #[test]
+    fn test_profile_overlays_nested_tables() {
+        let config = resolve(Some("staging")).unwrap();
+
+        // 只覆盖 profile 中出现的键
+        let vdi = config.vdi.unwrap();
+        assert_eq!(vdi.base_url, "http://vdi-staging:8088");
+        assert_eq!(vdi.username, "admin");
+        assert_eq!(vdi.password, "dev-secret");
+        assert_eq!(config.vm.name, "dev-vm");
+        assert_eq!(config.vm.user.as_deref(), Some("tester"));
+
+        // 主机表按主机 ID 合并
+        let hosts = &config.libvirt.hosts;
+        assert_eq!(hosts.len(), 3);
+        assert_eq!(hosts["node1"].id, "node1");
+        assert_eq!(hosts["node1"].host, "10.0.1.11");
+        assert_eq!(hosts["node2"].host, "10.0.0.12");
+        assert_eq!(hosts["node3"].uri, "qemu+tcp://10.0.1.13/system");
+    }
+
+    #[test]
+    fn test_transport_config_from_test_config() {
+        let mut config = resolve(Some("staging")).unwrap();
+        config.libvirt.connect_timeout = 5;
+        config.libvirt.pool.max_connections_per_host = 2;
+
+        let transport = TransportConfig::from_test_config(&config);
+        let ids: Vec<&str> = transport.hosts.iter().map(|host| host.id.as_str()).collect();
+        assert_eq!(ids, vec!["node1", "node2", "node3"]);
+        assert_eq!(transport.hosts[0].uri.as_deref(), Some("qemu+tcp://10.0.1.11/system"));
+        assert_eq!(transport.connect_timeout, 5);
+        assert_eq!(transport.pool.max_connections_per_host, 2);
+        assert!(transport.validate().is_ok());
+
+        // 没有配置主机时以 libvirt.uri 注册本机
+        let transport = TransportConfig::from_test_config(&TestConfig::default());
+        assert_eq!(transport.hosts.len(), 1);
+        assert_eq!(transport.hosts[0].id, LOCAL_HOST_ID);
+        assert_eq!(transport.hosts[0].uri.as_deref(), Some("qemu:///system"));
+    }
+
+    #[test]
+    fn test_transport_config_reports_all_host_problems() {
+        let mut config = resolve(None).unwrap();
+        config.libvirt.hosts.get_mut("node2").unwrap().id = "node1".to_string();
+        config.libvirt.hosts.get_mut("node1").unwrap().uri = "10.0.0.11".to_string();
+
+        let err = TransportConfig::from_test_config(&config).validate().unwrap_err().to_string();
+        assert!(err.contains("2 处问题"), "{}", err);
+        assert!(err.contains("主机 ID node1 重复"), "{}", err);
+        assert!(err.contains("URI 10.0.0.11 无效"), "{}", err);
+    }
+
+    #[test]
+    fn test_default_section_without_profile() {
+        let config = resolve(None).unwrap();
+        assert_eq!(config.vm.name, "dev-vm");
+        assert_eq!(config.libvirt.hosts.len(), 2);
+        assert_eq!(config.vdi.unwrap().base_url, "http://vdi-dev:8088");
+
+        let config = resolve(Some("prod")).unwrap();
+        assert_eq!(config.vm.name, "prod-vm");
+        assert_eq!(config.vm.user.as_deref(), Some("tester"));
+    }
+
+    #[test]
+    fn test_unknown_profile_lists_available() {
+        let err = resolve(Some("qa")).unwrap_err().to_string();
+        assert!(err.contains("Unknown profile 'qa'"));
+        assert!(err.contains("[prod, staging]"));
+    }
+
+    #[test]
+    fn test_merge_replaces_arrays() {
+        let mut base: serde_json::Value = toml::from_str(
+            r#"
+[[hosts]]
+id = "a"
+[[hosts]]
+id = "b"
+
+[options]
+retry = 3
+"#,
+        )
+        .unwrap();
+        let overlay: serde_json::Value = toml::from_str(
+            r#"
+[[hosts]]
+id = "c"
+"#,
+        )
+        .unwrap();
+
+        merge_values(&mut base, overlay);
+
+        assert_eq!(base["hosts"], serde_json::json!([{ "id": "c" }]));
+        assert_eq!(base["options"]["retry"], 3);
+    }
+
+    #[test]
+    fn test_legacy_config_without_default_section() {
+        let root: serde_json::Value = toml::from_str(
+            r#"
+[vm]
+name = "legacy-vm"
+
+[profile.ci.vm]
+name = "ci-vm"
+"#,
+        )
+        .unwrap();
+
+        let config: TestConfig =
+            serde_json::from_value(resolve_profile(root.clone(), None).unwrap()).unwrap();
+        assert_eq!(config.vm.name, "legacy-vm");
+
+        let config: TestConfig =
+            serde_json::from_value(resolve_profile(root, Some("ci")).unwrap()).unwrap();
+        assert_eq!(config.vm.name, "ci-vm");
+    }
+
+    #[test]
+    fn test_profile_variables_merge() {
+        let root: serde_json::Value = toml::from_str(
+            r#"
+[default.variables]
+user = "tester"
+domain = "corp.local"
+
+[profile.ci.variables]
+user = "ci-runner"
+"#,
+        )
+        .unwrap();
+
+        let config: TestConfig = serde_json::from_value(resolve_profile(root, Some("ci")).unwrap()).unwrap();
+        assert_eq!(config.variables["user"], "ci-runner");
+        assert_eq!(config.variables["domain"], "corp.local");
+    }
+
+    #[test]
+    fn test_load_profile_applies_env_secrets() {
+        let path = env::temp_dir().join(format!("atp-profile-{}.toml", std::process::id()));
+        fs::write(&path, PROFILE_TOML).unwrap();
+
+        let lookup = |name: &str| (name == "ATP_VDI_PASSWORD").then(|| "from-env".to_string());
+        let config = TestConfig::load_with(&path, Some("staging"), lookup);
+        // 不指定 profile 时同样应用环境变量覆盖
+        let default_config = TestConfig::load_with(&path, None, lookup);
+        fs::remove_file(&path).unwrap();
+
+        let vdi = config.unwrap().vdi.unwrap();
+        assert_eq!(vdi.base_url, "http://vdi-staging:8088");
+        assert_eq!(vdi.password, "from-env");
+
+        let vdi = default_config.unwrap().vdi.unwrap();
+        assert_eq!(vdi.base_url, "http://vdi-dev:8088");
+        assert_eq!(vdi.password, "from-env");
+    }
 }
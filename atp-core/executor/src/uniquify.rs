@@ -0,0 +1,258 @@
+//! 克隆后客户机唯一化
+//!
+//! 链接克隆出来的虚拟机主机名 (Windows 还包括 SID, Linux 包括 machine-id) 相同,
+//! 加入域时会发生冲突。这里根据模板渲染主机名, 并生成在客户机内通过 QGA 执行的脚本。
+
+use std::time::Duration;
+
+use atp_protocol::qga::GuestOsInfo;
+use serde::{Deserialize, Serialize};
+
+use crate::{ExecutorError, Result};
+
+/// Windows 计算机名 (NetBIOS) 最大长度
+const WINDOWS_HOSTNAME_MAX_LEN: usize = 15;
+
+/// Linux 主机名最大长度
+const LINUX_HOSTNAME_MAX_LEN: usize = 63;
+
+/// 唯一化步骤未设置超时时的默认超时
+///
+/// 步骤需要等待客户机关机 (最长 120 秒) 并重新启动, 通用的默认步骤超时 (30 秒) 不够用。
+pub const DEFAULT_UNIQUIFY_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// sysprep 使用的应答文件路径
+const UNATTEND_PATH: &str = r"$env:SystemRoot\Panther\atp-unattend.xml";
+
+/// 客户机平台 (场景中的 `guest_os: windows | linux`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GuestPlatform {
+    Windows,
+    Linux,
+}
+
+impl GuestPlatform {
+    pub fn from_os_info(os_info: &GuestOsInfo) -> Self {
+        if os_info.is_windows() {
+            GuestPlatform::Windows
+        } else {
+            GuestPlatform::Linux
+        }
+    }
+
+    /// 主机名最大长度
+    pub fn hostname_max_len(&self) -> usize {
+        match self {
+            GuestPlatform::Windows => WINDOWS_HOSTNAME_MAX_LEN,
+            GuestPlatform::Linux => LINUX_HOSTNAME_MAX_LEN,
+        }
+    }
+}
+
+/// 模板中的占位符
+fn placeholders(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        match after.find('}') {
+            Some(end) => {
+                names.push(&after[..end]);
+                rest = &after[end + 1..];
+            }
+            None => break,
+        }
+    }
+
+    names
+}
+
+/// 检查主机名模板中的占位符
+///
+/// 只支持 `{index}` 与 `{vm_name}`。
+pub fn check_template(template: &str) -> Result<()> {
+    if template.trim().is_empty() {
+        return Err(ExecutorError::ConfigError("主机名模板不能为空".to_string()));
+    }
+
+    for name in placeholders(template) {
+        if name != "index" && name != "vm_name" {
+            return Err(ExecutorError::ConfigError(format!(
+                "主机名模板包含未知占位符: {{{}}}",
+                name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// 渲染主机名
+///
+/// 替换 `{index}` 与 `{vm_name}` 后, 非字母数字字符替换为 `-`,
+/// 并去掉首尾的 `-`。
+pub fn render_hostname(template: &str, index: usize, vm_name: &str) -> Result<String> {
+    check_template(template)?;
+
+    let rendered = template
+        .replace("{index}", &index.to_string())
+        .replace("{vm_name}", vm_name);
+
+    let hostname: String = rendered
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '-' })
+        .collect();
+    let hostname = hostname.trim_matches('-').to_string();
+
+    if hostname.is_empty() {
+        return Err(ExecutorError::ConfigError(format!(
+            "主机名模板 {} 渲染结果为空",
+            template
+        )));
+    }
+
+    Ok(hostname)
+}
+
+/// 检查主机名是否符合平台限制
+pub fn check_hostname(hostname: &str, platform: GuestPlatform) -> Result<()> {
+    if hostname.len() > platform.hostname_max_len() {
+        return Err(ExecutorError::ConfigError(format!(
+            "主机名 {} 超过 {:?} 的长度上限 {}",
+            hostname,
+            platform,
+            platform.hostname_max_len()
+        )));
+    }
+
+    if hostname.chars().all(|c| c.is_ascii_digit()) {
+        return Err(ExecutorError::ConfigError(format!(
+            "主机名 {} 不能全部为数字",
+            hostname
+        )));
+    }
+
+    Ok(())
+}
+
+/// 生成唯一化脚本
+///
+/// 脚本执行完成后客户机会在数秒后重启。
+pub fn build_script(platform: GuestPlatform, hostname: &str, run_sysprep: bool) -> String {
+    match platform {
+        GuestPlatform::Windows => build_windows_script(hostname, run_sysprep),
+        GuestPlatform::Linux => build_linux_script(hostname, run_sysprep),
+    }
+}
+
+/// Windows: 修改计算机名, 或通过 sysprep 重新生成 SID
+///
+/// sysprep /generalize 会重置计算机名, 因此通过应答文件在 specialize 阶段设置新名称,
+/// 并跳过 OOBE 界面。sysprep 以后台进程启动, 避免 QGA 等待其完成。
+fn build_windows_script(hostname: &str, run_sysprep: bool) -> String {
+    if !run_sysprep {
+        return format!(
+            "Rename-Computer -NewName '{}' -Force -ErrorAction Stop; shutdown.exe /r /t 5 /f",
+            hostname
+        );
+    }
+
+    let component = r#"<component name="Microsoft-Windows-Shell-Setup" processorArchitecture="amd64" publicKeyToken="31bf3856ad364e35" language="neutral" versionScope="nonSxS">"#;
+    let unattend = format!(
+        concat!(
+            r#"<?xml version="1.0" encoding="utf-8"?><unattend xmlns="urn:schemas-microsoft-com:unattend">"#,
+            r#"<settings pass="specialize">{component}<ComputerName>{hostname}</ComputerName></component></settings>"#,
+            r#"<settings pass="oobeSystem">{component}<OOBE><HideEULAPage>true</HideEULAPage>"#,
+            r#"<SkipMachineOOBE>true</SkipMachineOOBE><SkipUserOOBE>true</SkipUserOOBE>"#,
+            r#"<ProtectYourPC>3</ProtectYourPC></OOBE></component></settings></unattend>"#,
+        ),
+        component = component,
+        hostname = hostname
+    );
+
+    format!(
+        "Set-Content -Path \"{path}\" -Value '{unattend}' -Encoding UTF8 -ErrorAction Stop; \
+         Start-Process -FilePath \"$env:SystemRoot\\System32\\Sysprep\\sysprep.exe\" \
+         -ArgumentList '/generalize','/oobe','/reboot','/quiet',\"/unattend:{path}\"",
+        path = UNATTEND_PATH,
+        unattend = unattend
+    )
+}
+
+/// Linux: 修改主机名, 可选重新生成 machine-id
+///
+/// 重启命令放到后台延迟执行, 保证 guest-exec 能正常返回。
+fn build_linux_script(hostname: &str, regenerate_machine_id: bool) -> String {
+    let mut script = format!(
+        "set -e; \
+         if command -v hostnamectl >/dev/null 2>&1; then hostnamectl set-hostname '{hostname}'; \
+         else echo '{hostname}' > /etc/hostname; hostname '{hostname}'; fi; \
+         sed -i 's/^127\\.0\\.1\\.1.*/127.0.1.1\\t{hostname}/' /etc/hosts; ",
+        hostname = hostname
+    );
+
+    if regenerate_machine_id {
+        script.push_str(
+            "rm -f /etc/machine-id /var/lib/dbus/machine-id; \
+             systemd-machine-id-setup; \
+             if [ -d /var/lib/dbus ]; then ln -sf /etc/machine-id /var/lib/dbus/machine-id; fi; ",
+        );
+    }
+
+    script.push_str("nohup sh -c 'sleep 2; reboot' >/dev/null 2>&1 &");
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_hostname() {
+        assert_eq!(render_hostname("win-{index}", 3, "pool-vm").unwrap(), "win-3");
+        assert_eq!(render_hostname("{vm_name}-{index}", 12, "pool_vm.01").unwrap(), "pool-vm-01-12");
+        assert_eq!(render_hostname("_{vm_name}_", 0, "a").unwrap(), "a");
+
+        assert!(render_hostname("pc-{id}", 0, "vm").is_err());
+        assert!(render_hostname("", 0, "vm").is_err());
+        assert!(render_hostname("{vm_name}", 0, "__").is_err());
+    }
+
+    #[test]
+    fn test_check_hostname_limits() {
+        assert!(check_hostname("desktop-0000001", GuestPlatform::Windows).is_ok());
+        assert!(check_hostname("desktop-00000001", GuestPlatform::Windows).is_err());
+        assert!(check_hostname("desktop-00000001", GuestPlatform::Linux).is_ok());
+        assert!(check_hostname("12345", GuestPlatform::Linux).is_err());
+    }
+
+    #[test]
+    fn test_build_windows_script() {
+        let script = build_script(GuestPlatform::Windows, "win-1", false);
+        assert!(script.contains("Rename-Computer -NewName 'win-1'"));
+        assert!(script.contains("shutdown.exe /r"));
+        assert!(!script.contains("sysprep"));
+
+        let script = build_script(GuestPlatform::Windows, "win-1", true);
+        assert!(script.contains("<ComputerName>win-1</ComputerName>"));
+        assert!(script.contains("sysprep.exe"));
+        assert!(script.contains("'/generalize','/oobe','/reboot','/quiet'"));
+        assert!(script.contains(r"/unattend:$env:SystemRoot\Panther\atp-unattend.xml"));
+        assert!(!script.contains("Rename-Computer"));
+    }
+
+    #[test]
+    fn test_build_linux_script() {
+        let script = build_script(GuestPlatform::Linux, "web-2", false);
+        assert!(script.contains("hostnamectl set-hostname 'web-2'"));
+        assert!(script.contains("127.0.1.1\\tweb-2"));
+        assert!(!script.contains("machine-id"));
+        assert!(script.ends_with("nohup sh -c 'sleep 2; reboot' >/dev/null 2>&1 &"));
+
+        let script = build_script(GuestPlatform::Linux, "web-2", true);
+        assert!(script.contains("systemd-machine-id-setup"));
+        assert!(script.find("machine-id").unwrap() < script.find("reboot").unwrap());
+    }
+}
@@ -0,0 +1,747 @@
+//! 场景静态校验 (dry-run)
+//!
+//! 在不连接任何基础设施的前提下检查场景定义中的常见错误,
+//! 不会建立 QMP/QGA/SPICE 连接。
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::guest_file;
+use crate::uniquify;
+use crate::scenario::{DEFAULT_SSH_IDLE_TIMEOUT_SECS, SCENARIO_SCHEMA_VERSION};
+use crate::{Action, Scenario, ScenarioStep};
+
+/// 单个步骤允许的最长超时时间 (秒), 超过时给出警告
+const MAX_SANE_TIMEOUT_SECS: u64 = 3600;
+
+/// 客户机唯一化步骤建议的最短超时时间 (秒), 需覆盖客户机重启
+const MIN_UNIQUIFY_TIMEOUT_SECS: u64 = 300;
+
+/// 自定义动作 `data` 中允许的键
+const CUSTOM_ACTION_KEYS: &[&str] = &["protocol", "payload"];
+
+/// 校验问题严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+    /// 执行时必然失败
+    Error,
+    /// 可能不符合预期
+    Warning,
+}
+
+impl IssueSeverity {
+    /// 严重程度名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IssueSeverity::Error => "error",
+            IssueSeverity::Warning => "warning",
+        }
+    }
+}
+
+impl std::fmt::Display for IssueSeverity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 校验问题
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    /// 严重程度
+    pub severity: IssueSeverity,
+
+    /// 步骤索引 (与执行报告中的 step_index 一致, 场景级问题为 None)
+    pub step_index: Option<usize>,
+
+    /// 问题描述
+    pub message: String,
+}
+
+impl ValidationIssue {
+    pub fn error(step_index: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity: IssueSeverity::Error,
+            step_index,
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(step_index: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity: IssueSeverity::Warning,
+            step_index,
+            message: message.into(),
+        }
+    }
+
+    /// 是否为错误
+    pub fn is_error(&self) -> bool {
+        self.severity == IssueSeverity::Error
+    }
+}
+
+/// 校验上下文 (执行器的相关配置)
+pub(crate) struct ValidationContext<'a> {
+    /// 是否配置了 VDI 客户端
+    pub has_vdi_client: bool,
+
+    /// 默认步骤超时
+    pub default_timeout: Duration,
+
+    /// 协议注册表中已注册的协议名
+    pub registered_protocols: &'a [String],
+
+    /// 执行器变量作用域中定义的变量名
+    pub defined_variables: &'a [String],
+}
+
+/// 按执行顺序 (前置、测试、清理) 枚举步骤及其索引
+pub(crate) fn indexed_steps(scenario: &Scenario) -> impl Iterator<Item = (usize, &ScenarioStep)> {
+    scenario
+        .setup
+        .iter()
+        .chain(scenario.steps.iter())
+        .chain(scenario.teardown.iter())
+        .enumerate()
+}
+
+/// 静态校验场景
+pub(crate) fn validate_scenario(scenario: &Scenario, ctx: &ValidationContext<'_>) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    if scenario.steps.is_empty() {
+        issues.push(ValidationIssue::warning(None, "场景没有测试步骤"));
+    }
+
+    if scenario.is_newer_schema() {
+        issues.push(ValidationIssue::warning(
+            None,
+            format!(
+                "场景格式版本 {} 高于当前 atp 支持的版本 {}, 新版本的动作无法执行",
+                scenario.schema_version, SCENARIO_SCHEMA_VERSION
+            ),
+        ));
+    }
+
+    for (index, step) in indexed_steps(scenario) {
+        let step_index = Some(index);
+
+        // 无法识别的动作没有可检查的字段
+        if let Some(reason) = step.action.unsupported_reason(scenario.schema_version) {
+            issues.push(ValidationIssue::error(step_index, reason));
+            continue;
+        }
+
+        if is_vdi_action(&step.action) && !ctx.has_vdi_client {
+            issues.push(ValidationIssue::error(
+                step_index,
+                format!("{} 需要 VDI 客户端, 但执行器未配置", step.action.type_name()),
+            ));
+        }
+
+        if is_protocol_action(&step.action) && scenario.target_domain.is_none() {
+            issues.push(ValidationIssue::error(
+                step_index,
+                format!("{} 需要协议连接, 但场景未指定 target_domain", step.action.type_name()),
+            ));
+        }
+
+        check_timeouts(step, index, ctx.default_timeout, &mut issues);
+
+        if let Action::Custom { data } = &step.action {
+            check_custom_action(data, index, ctx.registered_protocols, &mut issues);
+        }
+
+        if let Action::GuestUniquify { hostname_template, .. } = &step.action {
+            if let Err(e) = uniquify::check_template(hostname_template) {
+                issues.push(ValidationIssue::error(step_index, e.to_string()));
+            }
+        }
+
+        if let Action::SshExec { command, .. } = &step.action {
+            if let Err(e) = atp_transport::host_command::validate_host_command(command) {
+                issues.push(ValidationIssue::error(step_index, e.to_string()));
+            }
+        }
+
+        if let Action::SendKey { key, .. } = &step.action {
+            if let Err(e) = atp_protocol::KeyCombo::parse(key) {
+                issues.push(ValidationIssue::error(step_index, e.to_string()));
+            }
+        }
+
+        if let Action::SendText { text } = &step.action {
+            let layout = scenario.keyboard_layout.unwrap_or_default();
+            if let Err(e) = atp_protocol::KeyMapper::map_strokes(text, layout) {
+                issues.push(ValidationIssue::error(step_index, e.to_string()));
+            }
+        }
+
+        if let Action::RunGroup { name, .. } = &step.action {
+            issues.push(ValidationIssue::error(
+                step_index,
+                format!("步骤组 {} 未展开: run_group 只能在 YAML 场景文件中使用", name),
+            ));
+        }
+
+        if let Action::VerifyFileInGuest { expect_exists, expect_sha256, expect_contains, max_bytes, .. } = &step.action {
+            check_verify_file(*expect_exists, expect_sha256.as_deref(), expect_contains.is_some(), *max_bytes, index, &mut issues);
+        }
+
+        if let Action::SshFetchFile { remote_path, .. } = &step.action {
+            if !remote_path.starts_with('/') {
+                issues.push(ValidationIssue::error(
+                    step_index,
+                    format!("宿主机文件路径必须是绝对路径: {}", remote_path),
+                ));
+            }
+        }
+
+        // 执行器只代入变量作用域中的变量, 其余 ${...} 引用执行时保持原样
+        for text in action_strings(&step.action) {
+            for name in variable_references(text) {
+                if ctx.defined_variables.iter().any(|defined| defined == name) {
+                    continue;
+                }
+                issues.push(ValidationIssue::error(
+                    step_index,
+                    format!("引用了未定义的变量: ${{{}}}", name),
+                ));
+            }
+        }
+    }
+
+    issues
+}
+
+/// 检查超时设置
+fn check_timeouts(step: &ScenarioStep, index: usize, default_timeout: Duration, issues: &mut Vec<ValidationIssue>) {
+    let step_index = Some(index);
+
+    match step.timeout {
+        Some(0) => issues.push(ValidationIssue::error(step_index, "步骤超时时间为 0")),
+        Some(secs) if secs > MAX_SANE_TIMEOUT_SECS => issues.push(ValidationIssue::warning(
+            step_index,
+            format!("步骤超时时间过长: {}s (建议不超过 {}s)", secs, MAX_SANE_TIMEOUT_SECS),
+        )),
+        _ => {}
+    }
+
+    let step_timeout = step.effective_timeout(default_timeout).as_secs();
+    if step_timeout == 0 {
+        return;
+    }
+
+    match &step.action {
+        Action::Wait { duration } if *duration >= step_timeout => {
+            issues.push(ValidationIssue::error(
+                step_index,
+                format!("等待时间 {}s 不小于步骤超时 {}s, 步骤必定超时", duration, step_timeout),
+            ));
+        }
+        Action::VerifyDomainStatus { timeout_secs: Some(secs), .. }
+        | Action::VerifyAllDomainsRunning { timeout_secs: Some(secs), .. }
+        | Action::VerifyCommandSuccess { timeout_secs: Some(secs) }
+        | Action::VdiMigrateAndVerify { timeout_secs: Some(secs), .. }
+        | Action::VdiMigrateDomain { timeout_secs: Some(secs), .. }
+        | Action::VdiVerifyRestorePoints { timeout_secs: Some(secs), .. } => {
+            if *secs == 0 {
+                issues.push(ValidationIssue::error(step_index, "验证超时时间为 0"));
+            } else if *secs > step_timeout {
+                issues.push(ValidationIssue::warning(
+                    step_index,
+                    format!("验证超时 {}s 大于步骤超时 {}s, 将先触发步骤超时", secs, step_timeout),
+                ));
+            }
+        }
+        Action::SshExec { idle_timeout_secs, .. } => {
+            let idle = idle_timeout_secs.unwrap_or(DEFAULT_SSH_IDLE_TIMEOUT_SECS);
+            if idle == 0 {
+                issues.push(ValidationIssue::error(step_index, "空闲超时时间为 0"));
+            } else if idle_timeout_secs.is_some() && idle >= step_timeout {
+                issues.push(ValidationIssue::warning(
+                    step_index,
+                    format!("空闲超时 {}s 不小于步骤超时 {}s, 命令挂起时将先触发步骤超时", idle, step_timeout),
+                ));
+            }
+        }
+        Action::GuestUniquify { .. } if step_timeout < MIN_UNIQUIFY_TIMEOUT_SECS => {
+            issues.push(ValidationIssue::warning(
+                step_index,
+                format!(
+                    "客户机唯一化需要等待重启, 步骤超时 {}s 可能不足 (建议不少于 {}s)",
+                    step_timeout, MIN_UNIQUIFY_TIMEOUT_SECS
+                ),
+            ));
+        }
+        _ => {}
+    }
+}
+
+/// 检查客户机文件校验的参数
+fn check_verify_file(
+    expect_exists: bool,
+    expect_sha256: Option<&str>,
+    expect_contains: bool,
+    max_bytes: u64,
+    index: usize,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let step_index = Some(index);
+
+    if let Some(digest) = expect_sha256 {
+        if let Err(e) = guest_file::Sha256Expectation::parse(digest) {
+            issues.push(ValidationIssue::error(step_index, e.to_string()));
+        }
+    }
+
+    if !expect_exists && (expect_sha256.is_some() || expect_contains) {
+        issues.push(ValidationIssue::error(
+            step_index,
+            "expect_exists 为 false 时不能同时检查摘要或内容",
+        ));
+    }
+
+    if expect_contains && max_bytes == 0 {
+        issues.push(ValidationIssue::error(step_index, "max_bytes 为 0, 无法检查文件内容"));
+    }
+}
+
+/// 检查自定义动作
+fn check_custom_action(
+    data: &serde_json::Value,
+    index: usize,
+    registered_protocols: &[String],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let step_index = Some(index);
+
+    let object = match data.as_object() {
+        Some(object) => object,
+        None => {
+            issues.push(ValidationIssue::warning(step_index, "自定义动作的 data 不是对象, 执行时将被跳过"));
+            return;
+        }
+    };
+
+    for key in object.keys() {
+        if !CUSTOM_ACTION_KEYS.contains(&key.as_str()) {
+            issues.push(ValidationIssue::warning(
+                step_index,
+                format!("自定义动作包含未知字段: {}", key),
+            ));
+        }
+    }
+
+    match object.get("protocol") {
+        None => issues.push(ValidationIssue::warning(step_index, "自定义动作未指定协议, 执行时将被跳过")),
+        Some(serde_json::Value::String(name)) => {
+            if !registered_protocols.iter().any(|p| p == name) {
+                issues.push(ValidationIssue::error(step_index, format!("协议未注册: {}", name)));
+            }
+        }
+        Some(_) => issues.push(ValidationIssue::error(step_index, "自定义动作的 protocol 必须是字符串")),
+    }
+}
+
+/// 是否为 VDI 平台操作 (包括依赖 VDI 查询的验证步骤)
+pub(crate) fn is_vdi_action(action: &Action) -> bool {
+    matches!(
+        action,
+        Action::VdiCreateDeskPool { .. }
+            | Action::VdiEnableDeskPool { .. }
+            | Action::VdiDisableDeskPool { .. }
+            | Action::VdiDeleteDeskPool { .. }
+            | Action::VdiStartDomain { .. }
+            | Action::VdiShutdownDomain { .. }
+            | Action::VdiRebootDomain { .. }
+            | Action::VdiDeleteDomain { .. }
+            | Action::VdiBindUser { .. }
+            | Action::VdiGetDeskPoolDomains { .. }
+            | Action::VdiMigrateAndVerify { .. }
+            | Action::VdiMigrateDomain { .. }
+            | Action::VdiVerifyRestorePoints { .. }
+            | Action::VerifyDomainStatus { .. }
+            | Action::VerifyAllDomainsRunning { .. }
+    )
+}
+
+/// 是否需要连接目标虚拟机的协议
+fn is_protocol_action(action: &Action) -> bool {
+    match action {
+        Action::SendKey { .. }
+        | Action::SendText { .. }
+        | Action::MouseClick { .. }
+        | Action::ExecCommand { .. }
+        | Action::VerifyCommandSuccess { .. }
+        | Action::QueryWindowsEventLog { .. }
+        | Action::GuestUniquify { .. }
+        | Action::VerifyFileInGuest { .. } => true,
+        Action::Custom { data } => data.get("protocol").is_some(),
+        _ => false,
+    }
+}
+
+/// 动作中的字符串参数
+fn action_strings(action: &Action) -> Vec<&str> {
+    match action {
+        Action::SendKey { key, .. } => vec![key],
+        Action::SendText { text } => vec![text],
+        Action::MouseClick { button, .. } => vec![button],
+        Action::ExecCommand { command } => vec![command],
+        Action::Wait { .. }
+        | Action::Custom { .. }
+        | Action::VerifyCommandSuccess { .. }
+        | Action::QueryWindowsEventLog { .. }
+        | Action::RunGroup { .. }
+        | Action::Unsupported { .. } => vec![],
+        Action::VdiCreateDeskPool { name, template_id, advanced, .. } => {
+            let mut strings = vec![name.as_str(), template_id.as_str()];
+            if let Some(advanced) = advanced {
+                strings.extend(
+                    [&advanced.domain_prefix, &advanced.storage_pool_id, &advanced.class_no, &advanced.remark]
+                        .into_iter()
+                        .flatten()
+                        .map(String::as_str),
+                );
+            }
+            strings
+        }
+        Action::VdiEnableDeskPool { pool_id }
+        | Action::VdiDisableDeskPool { pool_id }
+        | Action::VdiDeleteDeskPool { pool_id }
+        | Action::VdiGetDeskPoolDomains { pool_id }
+        | Action::VerifyAllDomainsRunning { pool_id, .. } => vec![pool_id],
+        Action::VdiStartDomain { domain_id }
+        | Action::VdiShutdownDomain { domain_id }
+        | Action::VdiRebootDomain { domain_id }
+        | Action::VdiDeleteDomain { domain_id } => vec![domain_id],
+        Action::VdiBindUser { domain_id, user_id } => vec![domain_id, user_id],
+        Action::VdiMigrateAndVerify { domain, target_host, .. } => vec![domain, target_host],
+        Action::VdiMigrateDomain { domain_id, target_host_id, .. } => vec![domain_id, target_host_id],
+        Action::VdiVerifyRestorePoints { pattern, marker_path, .. } => {
+            std::iter::once(pattern).chain(marker_path).map(String::as_str).collect()
+        }
+        Action::VerifyDomainOnHost { domain, host_id } => vec![domain, host_id],
+        Action::VerifyDomainStatus { domain_id, expected_status, .. } => vec![domain_id, expected_status],
+        Action::GuestUniquify { hostname_template, .. } => vec![hostname_template],
+        Action::VerifyFileInGuest { path, expect_sha256, expect_contains, .. } => std::iter::once(path)
+            .chain(expect_sha256)
+            .chain(expect_contains)
+            .map(String::as_str)
+            .collect(),
+        Action::SshFetchFile { host, remote_path, local_path } => vec![host, remote_path, local_path],
+        Action::SshExec { host, command, .. } => {
+            std::iter::once(host.as_str()).chain(command.iter().map(String::as_str)).collect()
+        }
+    }
+}
+
+/// 提取字符串中的 `${name}` 变量引用
+pub(crate) fn variable_references(text: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = text;
+
+    while let Some(start) = rest.find("${") {
+        let after = &rest[start + 2..];
+        match after.find('}') {
+            Some(end) => {
+                names.push(after[..end].trim());
+                rest = &after[end + 1..];
+            }
+            None => break,
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(has_vdi_client: bool) -> ValidationContext<'static> {
+        ValidationContext {
+            has_vdi_client,
+            default_timeout: Duration::from_secs(30),
+            registered_protocols: &[],
+            defined_variables: &[],
+        }
+    }
+
+    #[test]
+    fn test_variable_references() {
+        assert_eq!(variable_references("echo ${a} ${ b }"), vec!["a", "b"]);
+        assert!(variable_references("echo $a ${unterminated").is_empty());
+    }
+
+    #[test]
+    fn test_validate_defined_variables() {
+        let yaml = r#"
+name: "vars"
+target_domain: "vm-1"
+steps:
+  - action:
+      type: exec_command
+      command: "net user ${user} ${password}"
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let defined = vec!["user".to_string()];
+        let ctx = ValidationContext {
+            defined_variables: &defined,
+            ..context(false)
+        };
+
+        let issues = validate_scenario(&scenario, &ctx);
+        let undefined: Vec<&str> = issues
+            .iter()
+            .filter(|i| i.message.contains("未定义的变量"))
+            .map(|i| i.message.as_str())
+            .collect();
+        assert_eq!(undefined, vec!["引用了未定义的变量: ${password}"]);
+    }
+
+    #[test]
+    fn test_validate_reports_step_indices() {
+        let yaml = r#"
+name: "invalid"
+setup:
+  - action:
+      type: vdi_start_domain
+      domain_id: "vm-1"
+steps:
+  - action:
+      type: send_key
+      key: "a"
+  - timeout: 5
+    action:
+      type: wait
+      duration: 10
+teardown:
+  - action:
+      type: custom
+      data:
+        foo: 1
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let issues = validate_scenario(&scenario, &context(false));
+
+        let at = |index: usize| -> Vec<&ValidationIssue> {
+            issues.iter().filter(|i| i.step_index == Some(index)).collect()
+        };
+
+        assert!(at(0).iter().any(|i| i.is_error() && i.message.contains("VDI")));
+        assert!(at(1).iter().any(|i| i.is_error() && i.message.contains("target_domain")));
+        assert!(at(2).iter().any(|i| i.is_error() && i.message.contains("必定超时")));
+        assert!(at(3).iter().all(|i| i.severity == IssueSeverity::Warning));
+        assert_eq!(at(3).len(), 2);
+    }
+
+    #[test]
+    fn test_validate_clean_scenario() {
+        let yaml = r#"
+name: "valid"
+target_domain: "vm"
+steps:
+  - action:
+      type: vdi_start_domain
+      domain_id: "vm-1"
+  - action:
+      type: exec_command
+      command: "echo ok"
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        assert!(validate_scenario(&scenario, &context(true)).is_empty());
+    }
+
+    #[test]
+    fn test_validate_guest_uniquify() {
+        let yaml = r#"
+name: "uniquify"
+target_domain: "vm"
+steps:
+  - timeout: 600
+    action:
+      type: guest_uniquify
+      hostname_template: "pc-{vm_name}-{index}"
+  - action:
+      type: guest_uniquify
+      hostname_template: "pc-{id}"
+  - timeout: 60
+    action:
+      type: guest_uniquify
+      hostname_template: "pc-{index}"
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let issues = validate_scenario(&scenario, &context(false));
+
+        // 未设置超时时使用唯一化动作自身的默认超时, 不提示超时不足
+        let step1: Vec<_> = issues.iter().filter(|i| i.step_index == Some(1)).collect();
+        assert_eq!(step1.len(), 1);
+        assert!(step1[0].is_error() && step1[0].message.contains("{id}"));
+
+        let step2: Vec<_> = issues.iter().filter(|i| i.step_index == Some(2)).collect();
+        assert_eq!(step2.len(), 1);
+        assert!(!step2[0].is_error() && step2[0].message.contains("重启"));
+        assert!(issues.iter().all(|i| i.step_index != Some(0)));
+    }
+
+    #[test]
+    fn test_validate_verify_file_in_guest() {
+        let yaml = r#"
+name: "verify-file"
+target_domain: "vm"
+steps:
+  - action:
+      type: verify_file_in_guest
+      path: 'C:\Program Files\Agent\agent.exe'
+      expect_sha256: "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08"
+  - action:
+      type: verify_file_in_guest
+      path: /opt/agent/VERSION
+      expect_sha256: "not-a-digest"
+  - action:
+      type: verify_file_in_guest
+      path: /tmp/installer.lock
+      expect_exists: false
+      expect_contains: "done"
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let issues = validate_scenario(&scenario, &context(false));
+
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(issues[0].step_index == Some(1) && issues[0].message.contains("SHA-256"));
+        assert!(issues[1].step_index == Some(2) && issues[1].message.contains("expect_exists"));
+    }
+
+    #[test]
+    fn test_validate_ssh_fetch_file() {
+        let yaml = r#"
+name: "fetch"
+steps:
+  - action:
+      type: wait
+      duration: 1
+teardown:
+  - action:
+      type: ssh_fetch_file
+      host: host1
+      remote_path: /var/log/libvirt/qemu/vm.log
+      local_path: logs/vm.log
+  - action:
+      type: ssh_fetch_file
+      host: host1
+      remote_path: var/log/messages
+      local_path: messages
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let issues = validate_scenario(&scenario, &context(false));
+
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].step_index, Some(2));
+        assert!(issues[0].is_error() && issues[0].message.contains("绝对路径"));
+    }
+    #[test]
+    fn test_validate_ssh_exec() {
+        let yaml = r#"
+name: "ssh-exec"
+steps:
+  - timeout: 1800
+    action:
+      type: ssh_exec
+      host: host1
+      command: ["virsh", "blockjob", "vm", "vda", "--info"]
+      idle_timeout_secs: 120
+  - action:
+      type: ssh_exec
+      host: host1
+      command: ["rm", "-rf", "/tmp/x"]
+  - timeout: 60
+    action:
+      type: ssh_exec
+      host: host1
+      command: ["virsh", "list"]
+      idle_timeout_secs: 60
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let issues = validate_scenario(&scenario, &context(false));
+
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(issues.iter().any(|i| i.step_index == Some(1) && i.is_error() && i.message.contains("rm")));
+        assert!(issues.iter().any(|i| i.step_index == Some(2) && !i.is_error() && i.message.contains("空闲超时")));
+    }
+
+    #[test]
+    fn test_validate_send_text_layout() {
+        let yaml = r#"
+name: "send-text"
+target_domain: "vm"
+keyboard_layout: "de-DE"
+steps:
+  - action:
+      type: send_text
+      text: "Grüße @ home"
+  - action:
+      type: send_text
+      text: "a^b"
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let issues = validate_scenario(&scenario, &context(false));
+
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert_eq!(issues[0].step_index, Some(1));
+        assert!(issues[0].is_error() && issues[0].message.contains("de-DE") && issues[0].message.contains("第 1 个字符 '^'"));
+    }
+
+    #[test]
+    fn test_validate_send_key_combo() {
+        let yaml = r#"
+name: "send-key"
+target_domain: "vm"
+steps:
+  - action:
+      type: send_key
+      key: "ctrl+alt+del"
+  - action:
+      type: send_key
+      key: "ctrl-c"
+  - action:
+      type: send_key
+      key: "ctrl+alt+dle"
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let issues = validate_scenario(&scenario, &context(false));
+
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert_eq!(issues[0].step_index, Some(2));
+        assert!(issues[0].is_error() && issues[0].message.contains("'dle'"));
+    }
+
+    #[test]
+    fn test_validate_unsupported_actions() {
+        let yaml = r#"
+schema_version: 3
+name: "newer"
+target_domain: "vm"
+steps:
+  - action:
+      type: send_key
+      key: "a"
+  - action:
+      type: capture_screen
+      format: png
+"#;
+        let scenario = Scenario::from_yaml_str(yaml).unwrap();
+        let issues = validate_scenario(&scenario, &context(false));
+
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        assert!(issues.iter().any(|i| i.step_index.is_none() && !i.is_error() && i.message.contains("格式版本 3")));
+        let error = issues.iter().find(|i| i.is_error()).unwrap();
+        assert_eq!(error.step_index, Some(1));
+        assert!(error.message.contains("'capture_screen' 需要场景格式版本 3"), "{}", error.message);
+    }
+}
+
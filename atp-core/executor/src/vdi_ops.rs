@@ -0,0 +1,1723 @@
+//! VDI 平台批量操作
+//!
+//! 批量操作的目标可以是名称通配符 (`*` 匹配任意字符串, `?` 匹配单个字符)、
+//! 桌面池 (ID 或名称) 或虚拟机 ID 列表, 见 [`Target`]。
+//! 用户分配使用 `虚拟机,用户名` 格式的 CSV 映射, 见 [`parse_assign_mapping`]。
+//! 均衡启动按批把虚拟机放到负载最低的主机上, 见 [`plan_balanced_start`]。
+//! 批量克隆按命名模板 (见 [`NameTemplate`]) 生成名称, 逐台记录克隆、启动与 QGA 就绪结果, 见 [`CloneItemResult`]。
+//! 还原点验证通过 QGA 读写客户机中的标记文件确认还原点确实生效, 见 [`VdiBatchOps::verify_restore_points`]。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use atp_protocol::{qga::QgaProtocol, Protocol};
+use atp_storage::VmCacheRecord;
+use atp_transport::TransportManager;
+use atp_vdiplatform::{
+    api::{domain::validate_domain_name, host::least_loaded},
+    models::{BatchFreezeRequest, BatchTaskRequest, CloneDomainRequest, Domain, FreezeType, HostDetail, User},
+    VdiClient,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, timeout, Instant};
+use tracing::{debug, info, warn};
+
+use crate::guest_file;
+use crate::uniquify::GuestPlatform;
+use crate::vm_cache::{records_from_listing, CacheMode, VmCacheManager};
+use crate::{ExecutorError, Result};
+
+/// 匹配到的虚拟机
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VmMatchResult {
+    /// 虚拟机 ID
+    pub id: String,
+
+    /// 虚拟机名称
+    pub name: String,
+
+    /// 状态名称 (如 "运行中")
+    pub status: String,
+
+    /// 所在主机 ID
+    pub host_id: String,
+}
+
+impl From<VmCacheRecord> for VmMatchResult {
+    fn from(record: VmCacheRecord) -> Self {
+        Self {
+            id: record.id,
+            name: record.name,
+            status: record.status,
+            host_id: record.host_id,
+        }
+    }
+}
+
+impl From<Domain> for VmMatchResult {
+    fn from(domain: Domain) -> Self {
+        Self {
+            id: domain.id,
+            name: domain.name,
+            status: domain.status,
+            host_id: domain.host_id,
+        }
+    }
+}
+
+/// 查询桌面池列表时的每页数量
+const POOL_PAGE_SIZE: u32 = 100;
+
+/// 均衡启动时每批虚拟机的数量 (同一批放到同一台主机)
+pub const BALANCED_START_CHUNK_SIZE: usize = 10;
+
+/// 批量操作的目标
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// 名称通配符
+    Pattern(String),
+    /// 桌面池 ID 或名称
+    Pool(String),
+    /// 虚拟机 ID 列表
+    IdList(Vec<String>),
+}
+
+impl Target {
+    /// 从命令行参数构造目标, 三种方式必须且只能指定一种
+    pub fn from_args(pattern: Option<String>, pool: Option<String>, ids: Vec<String>) -> Result<Self> {
+        let ids = (!ids.is_empty()).then_some(ids);
+        match (pattern, pool, ids) {
+            (Some(pattern), None, None) => Ok(Target::Pattern(pattern)),
+            (None, Some(pool), None) => Ok(Target::Pool(pool)),
+            (None, None, Some(ids)) => Ok(Target::IdList(ids)),
+            (None, None, None) => Err(ExecutorError::ConfigError(
+                "需要指定 --pattern、--pool 或 --id 之一".to_string(),
+            )),
+            _ => Err(ExecutorError::ConfigError(
+                "--pattern、--pool 与 --id 不能同时使用".to_string(),
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Pattern(pattern) => write!(f, "名称匹配 {}", pattern),
+            Target::Pool(pool) => write!(f, "桌面池 {}", pool),
+            Target::IdList(ids) => write!(f, "{} 个虚拟机 ID", ids.len()),
+        }
+    }
+}
+
+/// 批量操作类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchOperation {
+    Start,
+    Shutdown,
+    Reboot,
+}
+
+impl BatchOperation {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BatchOperation::Start => "启动",
+            BatchOperation::Shutdown => "关机",
+            BatchOperation::Reboot => "重启",
+        }
+    }
+}
+
+/// 单个虚拟机的批量操作结果
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchItemResult {
+    pub vm: VmMatchResult,
+
+    /// 失败原因 (成功时为 None)
+    pub error: Option<String>,
+}
+
+impl BatchItemResult {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// 一批虚拟机的主机放置决策
+#[derive(Debug, Clone, Serialize)]
+pub struct ChunkPlacement {
+    /// 目标主机 ID
+    pub host_id: String,
+
+    /// 目标主机名称
+    pub host_name: String,
+
+    /// 放置前的空闲内存 (MB)
+    pub free_memory_mb: u64,
+
+    /// 放置前的空闲 CPU 核心数
+    pub free_cpu: u32,
+
+    /// 本批虚拟机
+    pub vms: Vec<VmMatchResult>,
+}
+
+/// 规划均衡启动: 按 `chunk_size` 分批, 每批放到当前负载最低的启用主机
+///
+/// 每放置一批就把其中虚拟机的配置 (`sizes`: ID -> (CPU 核数, 内存 MB)) 计入该主机的已用资源,
+/// 后续批次据此重新选择; 没有启用主机时返回错误。
+pub fn plan_balanced_start(
+    mut hosts: Vec<HostDetail>,
+    vms: Vec<VmMatchResult>,
+    sizes: &HashMap<String, (u32, u64)>,
+    chunk_size: usize,
+) -> Result<Vec<ChunkPlacement>> {
+    let mut placements = Vec::new();
+
+    for chunk in vms.chunks(chunk_size.max(1)) {
+        let host_id = least_loaded(&hosts)
+            .map(|host| host.id.clone())
+            .ok_or_else(|| ExecutorError::ConfigError("没有处于启用状态的主机, 无法均衡启动".to_string()))?;
+        let host = hosts.iter_mut().find(|host| host.id == host_id).expect("least_loaded 返回的主机在列表中");
+
+        placements.push(ChunkPlacement {
+            host_id: host.id.clone(),
+            host_name: host.name.clone(),
+            free_memory_mb: host.free_memory_mb(),
+            free_cpu: host.free_cpu(),
+            vms: chunk.to_vec(),
+        });
+        for vm in chunk {
+            let (cpu, memory_mb) = sizes.get(&vm.id).copied().unwrap_or_default();
+            host.reserve(cpu, memory_mb);
+        }
+    }
+
+    Ok(placements)
+}
+
+/// 用户分配映射中的一行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AssignMapping {
+    /// 虚拟机名称或 ID
+    pub vm: String,
+
+    /// 用户名或用户 ID
+    pub user: String,
+}
+
+/// 单条用户分配的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct AssignItemResult {
+    pub mapping: AssignMapping,
+
+    /// 解析出的虚拟机 ID (未找到时为 None)
+    pub vm_id: Option<String>,
+
+    /// 解析出的用户 ID (未找到时为 None)
+    pub user_id: Option<String>,
+
+    /// 失败原因 (成功时为 None)
+    pub error: Option<String>,
+}
+
+impl AssignItemResult {
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// 解析用户分配映射 (CSV, 每行 `虚拟机,用户名`)
+///
+/// 空行与 `#` 开头的行被忽略; 第一行为 `vm,user` 表头时跳过。
+/// 同一台虚拟机出现多次时返回错误。
+pub fn parse_assign_mapping(content: &str) -> Result<Vec<AssignMapping>> {
+    let mut mappings: Vec<AssignMapping> = Vec::new();
+
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(|field| field.trim().trim_matches('"')).collect();
+        let is_header = matches!(fields.as_slice(), [vm, user] if vm.eq_ignore_ascii_case("vm") && user.eq_ignore_ascii_case("user"));
+        if mappings.is_empty() && is_header {
+            continue;
+        }
+
+        let (vm, user) = match fields.as_slice() {
+            [vm, user] if !vm.is_empty() && !user.is_empty() => (vm.to_string(), user.to_string()),
+            _ => {
+                return Err(ExecutorError::ConfigError(format!(
+                    "分配映射第 {} 行格式错误, 应为 `虚拟机,用户名`: {}",
+                    index + 1,
+                    line
+                )));
+            }
+        };
+
+        if mappings.iter().any(|mapping| mapping.vm == vm) {
+            return Err(ExecutorError::ConfigError(format!(
+                "分配映射第 {} 行: 虚拟机 {} 重复出现",
+                index + 1,
+                vm
+            )));
+        }
+        mappings.push(AssignMapping { vm, user });
+    }
+
+    Ok(mappings)
+}
+
+/// 按名称或 ID 解析分配映射中的虚拟机与用户
+///
+/// 无法解析的行在结果中带有错误, 其余行的 `vm_id` 与 `user_id` 均已填充。
+pub fn resolve_assignments(mappings: &[AssignMapping], vms: &[VmMatchResult], users: &[User]) -> Vec<AssignItemResult> {
+    mappings
+        .iter()
+        .map(|mapping| {
+            let vm_id = resolve_vm_id(vms, &mapping.vm);
+            let user_id = users
+                .iter()
+                .find(|user| user.id == mapping.user)
+                .or_else(|| users.iter().find(|user| user.username == mapping.user))
+                .map(|user| user.id.clone())
+                .ok_or_else(|| format!("用户不存在: {}", mapping.user));
+
+            let error = match (&vm_id, &user_id) {
+                (Err(error), _) | (_, Err(error)) => Some(error.clone()),
+                _ => None,
+            };
+            AssignItemResult {
+                mapping: mapping.clone(),
+                vm_id: vm_id.ok(),
+                user_id: user_id.ok(),
+                error,
+            }
+        })
+        .collect()
+}
+
+/// 按 ID 或名称查找虚拟机 ID, 名称对应多台虚拟机时返回错误
+fn resolve_vm_id(vms: &[VmMatchResult], id_or_name: &str) -> std::result::Result<String, String> {
+    if vms.iter().any(|vm| vm.id == id_or_name) {
+        return Ok(id_or_name.to_string());
+    }
+
+    let by_name: Vec<&str> = vms
+        .iter()
+        .filter(|vm| vm.name == id_or_name)
+        .map(|vm| vm.id.as_str())
+        .collect();
+    match by_name.as_slice() {
+        [id] => Ok(id.to_string()),
+        [] => Err(format!("虚拟机不存在: {}", id_or_name)),
+        ids => Err(format!("虚拟机名称 {} 对应多台虚拟机 ({}), 请使用 ID", id_or_name, ids.join(", "))),
+    }
+}
+
+/// 按桌面池 ID 或名称查找桌面池 ID
+///
+/// ID 精确匹配优先; 按名称匹配到多个桌面池时返回错误。
+pub fn find_pool_id(pools: &[serde_json::Value], id_or_name: &str) -> Result<String> {
+    let field = |pool: &serde_json::Value, key: &str| pool[key].as_str().unwrap_or_default().to_string();
+
+    if pools.iter().any(|pool| field(pool, "id") == id_or_name) {
+        return Ok(id_or_name.to_string());
+    }
+
+    let by_name: Vec<String> = pools
+        .iter()
+        .filter(|pool| field(pool, "name") == id_or_name)
+        .map(|pool| field(pool, "id"))
+        .collect();
+
+    match by_name.as_slice() {
+        [id] => Ok(id.clone()),
+        [] => Err(ExecutorError::ConfigError(format!("桌面池不存在: {}", id_or_name))),
+        ids => Err(ExecutorError::ConfigError(format!(
+            "桌面池名称 {} 对应多个桌面池 ({}), 请使用 ID",
+            id_or_name,
+            ids.join(", ")
+        ))),
+    }
+}
+
+/// 按 ID 列表选择虚拟机 (保持给定顺序), 任一 ID 不存在时返回错误
+pub fn select_by_ids(vms: Vec<VmMatchResult>, ids: &[String]) -> Result<Vec<VmMatchResult>> {
+    let missing: Vec<&str> = ids
+        .iter()
+        .filter(|id| !vms.iter().any(|vm| &vm.id == *id))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(ExecutorError::ConfigError(format!("虚拟机不存在: {}", missing.join(", "))));
+    }
+
+    Ok(ids
+        .iter()
+        .filter_map(|id| vms.iter().find(|vm| &vm.id == id).cloned())
+        .collect())
+}
+
+/// 名称是否匹配通配符模式
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // 回溯到最近一个 `*` 重新匹配
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// 等待克隆完成时查询虚拟机列表的间隔
+const CLONE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 等待 QGA 就绪时的重试间隔
+const QGA_RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// 单次 QGA 连接与 ping 的超时
+const QGA_PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 单次 QGA 连接并读写标记文件的超时
+const QGA_FILE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// 读取标记文件的最大字节数 (标记内容远小于此值)
+const MARKER_MAX_BYTES: u64 = 4096;
+
+/// 未指定标记文件路径时 Linux 客户机使用的路径
+pub const DEFAULT_LINUX_MARKER_PATH: &str = "/var/tmp/atp-restore-marker";
+
+/// 未指定标记文件路径时 Windows 客户机使用的路径
+pub const DEFAULT_WINDOWS_MARKER_PATH: &str = r"C:\Windows\Temp\atp-restore-marker.txt";
+
+/// 虚拟机命名模板
+///
+/// 模板中必须且只能有一个序号占位符: `{}` 直接写序号, `{:0N}` 补零到 N 位 (如 `lab-{:03}` -> `lab-001`)。
+/// 序号从 1 开始; 其余 `{` / `}` 均视为错误。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    prefix: String,
+    suffix: String,
+    width: usize,
+}
+
+impl NameTemplate {
+    /// 解析命名模板
+    pub fn parse(template: &str) -> Result<Self> {
+        let invalid = |reason: &str| ExecutorError::ConfigError(format!("命名模板 {:?} 无效: {}", template, reason));
+
+        let mut placeholder: Option<(usize, usize, usize)> = None;
+        let mut pos = 0;
+        while let Some(offset) = template[pos..].find(['{', '}']) {
+            let start = pos + offset;
+            if template[start..].starts_with('}') {
+                return Err(invalid("`}` 没有对应的 `{`"));
+            }
+            let end = start + template[start..].find('}').ok_or_else(|| invalid("`{` 没有闭合"))?;
+
+            let spec = &template[start + 1..end];
+            let width = match spec.strip_prefix(":0") {
+                None if spec.is_empty() => 0,
+                Some(digits) if !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => digits
+                    .parse()
+                    .ok()
+                    .filter(|width| (1..=10).contains(width))
+                    .ok_or_else(|| invalid("补零位数应在 1 到 10 之间"))?,
+                _ => return Err(invalid(&format!("不支持的占位符 {{{}}}, 只能使用 {{}} 或 {{:0N}}", spec))),
+            };
+            if placeholder.is_some() {
+                return Err(invalid("只能包含一个序号占位符"));
+            }
+            placeholder = Some((start, end + 1, width));
+            pos = end + 1;
+        }
+
+        let (start, end, width) = placeholder.ok_or_else(|| invalid("缺少序号占位符 {} 或 {:0N}"))?;
+        Ok(Self {
+            prefix: template[..start].to_string(),
+            suffix: template[end..].to_string(),
+            width,
+        })
+    }
+
+    /// 第 `index` 台虚拟机的名称 (序号从 1 开始)
+    pub fn render(&self, index: usize) -> String {
+        format!("{}{:0width$}{}", self.prefix, index, self.suffix, width = self.width)
+    }
+
+    /// 生成 `count` 个名称, 每个名称都经过 [`validate_domain_name`] 校验
+    pub fn names(&self, count: usize) -> Result<Vec<String>> {
+        if count == 0 {
+            return Err(ExecutorError::ConfigError("克隆数量必须大于 0".to_string()));
+        }
+
+        (1..=count)
+            .map(|index| {
+                let name = self.render(index);
+                validate_domain_name(&name).map_err(|e| ExecutorError::ConfigError(e.to_string()))?;
+                Ok(name)
+            })
+            .collect()
+    }
+}
+
+/// 克隆源: 虚拟机或模板
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CloneSource {
+    pub id: String,
+    pub name: String,
+
+    /// 是否为模板 (模板克隆必须指定存储池)
+    pub template: bool,
+}
+
+/// 批量克隆的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClonePhase {
+    /// 调用克隆接口
+    Clone,
+    /// 等待平台克隆任务完成
+    Wait,
+    /// 启动虚拟机
+    Start,
+    /// 等待 QGA 就绪
+    Qga,
+}
+
+impl ClonePhase {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ClonePhase::Clone => "克隆",
+            ClonePhase::Wait => "等待克隆完成",
+            ClonePhase::Start => "启动",
+            ClonePhase::Qga => "QGA 就绪",
+        }
+    }
+}
+
+/// 单台克隆虚拟机的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CloneItemResult {
+    /// 虚拟机名称 (来自命名模板)
+    pub name: String,
+
+    /// 平台返回的虚拟机 ID (克隆请求失败时为 None)
+    pub id: Option<String>,
+
+    /// 平台状态名称 (克隆完成后填充)
+    pub status: Option<String>,
+
+    /// 所在主机 ID
+    pub host_id: Option<String>,
+
+    /// 是否已启动
+    pub started: bool,
+
+    /// QGA 是否就绪 (未检查时为 None)
+    pub qga_ready: Option<bool>,
+
+    /// 失败的阶段 (成功时为 None)
+    pub failed_phase: Option<ClonePhase>,
+
+    /// 失败原因 (成功时为 None)
+    pub error: Option<String>,
+}
+
+impl CloneItemResult {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            id: None,
+            status: None,
+            host_id: None,
+            started: false,
+            qga_ready: None,
+            failed_phase: None,
+            error: None,
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// 记录失败, 之后的阶段跳过这台虚拟机
+    fn fail(&mut self, phase: ClonePhase, error: String) {
+        warn!("批量克隆{}失败: {}: {}", phase.label(), self.name, error);
+        self.failed_phase = Some(phase);
+        self.error = Some(error);
+    }
+
+    /// 克隆请求已成功但平台任务尚未完成
+    fn is_cloning(&self) -> bool {
+        self.is_success() && self.id.is_some() && self.status.as_deref().is_none_or(|status| status == "操作中")
+    }
+}
+
+/// 用虚拟机列表更新克隆结果, 返回仍在克隆中的数量
+///
+/// 列表中出现且状态不是 "操作中" 的克隆视为完成, 同时记录状态与所在主机。
+fn settle_clones(results: &mut [CloneItemResult], records: &[VmCacheRecord]) -> usize {
+    for result in results.iter_mut().filter(|result| result.is_success()) {
+        let Some(record) = result.id.as_ref().and_then(|id| records.iter().find(|record| &record.id == id)) else {
+            continue;
+        };
+        result.status = Some(record.status.clone());
+        if !record.host_id.is_empty() {
+            result.host_id = Some(record.host_id.clone());
+        }
+    }
+
+    results.iter().filter(|result| result.is_cloning()).count()
+}
+
+/// 还原点验证的阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestorePhase {
+    /// 写入原始标记文件
+    WriteMarker,
+    /// 设置还原点
+    Freeze,
+    /// 修改标记文件
+    ModifyMarker,
+    /// 重启或重置虚拟机
+    Revert,
+    /// 确认标记文件恢复为原始内容
+    VerifyMarker,
+    /// 取消还原点
+    Cleanup,
+}
+
+impl RestorePhase {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RestorePhase::WriteMarker => "写入标记文件",
+            RestorePhase::Freeze => "设置还原点",
+            RestorePhase::ModifyMarker => "修改标记文件",
+            RestorePhase::Revert => "还原",
+            RestorePhase::VerifyMarker => "校验标记文件",
+            RestorePhase::Cleanup => "取消还原点",
+        }
+    }
+}
+
+/// 触发还原的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreMethod {
+    /// 重启虚拟机 (还原点类型为每次重启还原)
+    #[default]
+    Reboot,
+    /// 调用平台的批量重置接口
+    Rebase,
+}
+
+impl RestoreMethod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            RestoreMethod::Reboot => "重启",
+            RestoreMethod::Rebase => "重置",
+        }
+    }
+}
+
+/// 单台虚拟机的还原点验证结论
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RestoreStatus {
+    /// 标记文件恢复为原始内容
+    Passed,
+    /// 某个阶段失败
+    Failed,
+    /// QGA 不可用, 未参与验证
+    SkippedNoQga,
+}
+
+/// 单台虚拟机的还原点验证结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RestoreItemResult {
+    pub vm: VmMatchResult,
+
+    /// 客户机中的标记文件路径 (QGA 不可用时为 None)
+    pub marker_path: Option<String>,
+
+    /// 验证结论 (流程进行中为 `Passed` 表示尚未失败)
+    pub status: RestoreStatus,
+
+    /// 失败的阶段 (通过或跳过时为 None)
+    pub failed_phase: Option<RestorePhase>,
+
+    /// 失败或跳过的原因
+    pub error: Option<String>,
+}
+
+impl RestoreItemResult {
+    fn new(vm: VmMatchResult) -> Self {
+        Self {
+            vm,
+            marker_path: None,
+            status: RestoreStatus::Passed,
+            failed_phase: None,
+            error: None,
+        }
+    }
+
+    /// 尚未失败或跳过, 继续后续阶段
+    fn is_pending(&self) -> bool {
+        self.status == RestoreStatus::Passed
+    }
+
+    fn fail(&mut self, phase: RestorePhase, error: String) {
+        warn!("还原点验证{}失败: {} ({}): {}", phase.label(), self.vm.name, self.vm.id, error);
+        self.status = RestoreStatus::Failed;
+        self.failed_phase = Some(phase);
+        self.error = Some(error);
+    }
+
+    fn skip(&mut self, reason: String) {
+        info!("跳过还原点验证 (QGA 不可用): {}: {}", self.vm.name, reason);
+        self.status = RestoreStatus::SkippedNoQga;
+        self.error = Some(reason);
+    }
+}
+
+/// 标记文件的原始内容与修改后的内容
+///
+/// 内容带上虚拟机 ID 与本次运行的时间戳, 避免上一次验证留下的文件被误认为还原成功。
+fn restore_markers(vm_id: &str, nonce: i64) -> (String, String) {
+    let original = format!("atp restore-point marker {} {}\n", vm_id, nonce);
+    let modified = format!("{}modified after restore point\n", original);
+    (original, modified)
+}
+
+/// 客户机中标记文件的状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MarkerState {
+    Original,
+    Modified,
+    Missing,
+    Unexpected,
+}
+
+impl MarkerState {
+    fn of(content: Option<&[u8]>, original: &str, modified: &str) -> Self {
+        match content {
+            None => MarkerState::Missing,
+            Some(content) if content == original.as_bytes() => MarkerState::Original,
+            Some(content) if content == modified.as_bytes() => MarkerState::Modified,
+            Some(_) => MarkerState::Unexpected,
+        }
+    }
+
+    /// 未恢复为原始内容时的失败原因
+    fn problem(self) -> &'static str {
+        match self {
+            MarkerState::Original => "",
+            MarkerState::Modified => "标记文件仍为修改后的内容, 还原点未生效",
+            MarkerState::Missing => "标记文件不存在, 还原点可能早于标记文件写入",
+            MarkerState::Unexpected => "标记文件内容与写入时不一致",
+        }
+    }
+}
+
+/// 处理设置还原点的结果, 返回由本次验证设置了还原点的虚拟机 ID (结束时需要取消)
+///
+/// 平台返回此前已经设置过还原点的虚拟机, 这些虚拟机会还原到早于标记文件的状态, 无法验证,
+/// 记为失败, 且保留其原有还原点。
+fn settle_freeze(results: &mut [RestoreItemResult], already_frozen: &[String]) -> Vec<String> {
+    let mut frozen = Vec::new();
+    for result in results.iter_mut().filter(|result| result.is_pending()) {
+        if already_frozen.contains(&result.vm.id) {
+            result.fail(RestorePhase::Freeze, "虚拟机已设置过还原点, 无法验证".to_string());
+        } else {
+            frozen.push(result.vm.id.clone());
+        }
+    }
+    frozen
+}
+
+/// VDI 批量操作
+pub struct VdiBatchOps {
+    vdi_client: Arc<VdiClient>,
+    cache: Option<VmCacheManager>,
+}
+
+impl VdiBatchOps {
+    pub fn new(vdi_client: Arc<VdiClient>) -> Self {
+        Self {
+            vdi_client,
+            cache: None,
+        }
+    }
+
+    /// 使用虚拟机缓存查询虚拟机列表
+    pub fn with_cache(mut self, cache: VmCacheManager) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// 按名称通配符查找虚拟机 (按名称排序)
+    ///
+    /// 未配置缓存时 `Fresh` 与 `Cached` 都直接查询 VDI 平台, `CacheOnly` 返回错误。
+    pub async fn get_matching_vms(&self, pattern: &str, mode: CacheMode) -> Result<Vec<VmMatchResult>> {
+        let mut matched: Vec<VmMatchResult> = self
+            .list_vms(mode)
+            .await?
+            .into_iter()
+            .filter(|vm| matches_pattern(pattern, &vm.name))
+            .collect();
+        matched.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+
+        Ok(matched)
+    }
+
+    /// 查找桌面池中的虚拟机 (按名称排序)
+    ///
+    /// `pool_id_or_name` 先按 ID 匹配, 再按名称匹配; 桌面池列表分页查询。
+    pub async fn get_pool_vms(&self, pool_id_or_name: &str) -> Result<Vec<VmMatchResult>> {
+        let pools = self.list_all_pools().await?;
+        let pool_id = find_pool_id(&pools, pool_id_or_name)?;
+
+        let mut vms: Vec<VmMatchResult> = self
+            .vdi_client
+            .desk_pool()
+            .list_domains(&pool_id)
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询桌面池 {} 的虚拟机失败: {}", pool_id, e)))?
+            .into_iter()
+            .map(VmMatchResult::from)
+            .collect();
+        vms.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+
+        Ok(vms)
+    }
+
+    /// 解析批量操作目标
+    ///
+    /// `mode` 用于名称匹配与 ID 列表; 桌面池总是查询 VDI 平台。
+    pub async fn resolve_target(&self, target: &Target, mode: CacheMode) -> Result<Vec<VmMatchResult>> {
+        match target {
+            Target::Pattern(pattern) => self.get_matching_vms(pattern, mode).await,
+            Target::Pool(pool) => self.get_pool_vms(pool).await,
+            Target::IdList(ids) => select_by_ids(self.list_vms(mode).await?, ids),
+        }
+    }
+
+    /// 批量启动
+    pub async fn batch_start(&self, target: &Target, mode: CacheMode) -> Result<Vec<BatchItemResult>> {
+        self.batch(BatchOperation::Start, target, mode).await
+    }
+
+    /// 批量关机
+    pub async fn batch_shutdown(&self, target: &Target, mode: CacheMode) -> Result<Vec<BatchItemResult>> {
+        self.batch(BatchOperation::Shutdown, target, mode).await
+    }
+
+    /// 批量重启
+    pub async fn batch_reboot(&self, target: &Target, mode: CacheMode) -> Result<Vec<BatchItemResult>> {
+        self.batch(BatchOperation::Reboot, target, mode).await
+    }
+
+    /// 对目标中的每台虚拟机依次执行操作, 单台失败不影响其余虚拟机
+    pub async fn batch(
+        &self,
+        operation: BatchOperation,
+        target: &Target,
+        mode: CacheMode,
+    ) -> Result<Vec<BatchItemResult>> {
+        let vms = self.resolve_target(target, mode).await?;
+        Ok(self.batch_vms(operation, vms).await)
+    }
+
+    /// 对已解析的虚拟机依次执行操作 (用于先确认目标再执行的场景)
+    pub async fn batch_vms(&self, operation: BatchOperation, vms: Vec<VmMatchResult>) -> Vec<BatchItemResult> {
+        let mut results = Vec::with_capacity(vms.len());
+
+        for vm in vms {
+            let domain = self.vdi_client.domain();
+            let outcome = match operation {
+                BatchOperation::Start => domain.start(&vm.id).await,
+                BatchOperation::Shutdown => domain.shutdown(&vm.id).await,
+                BatchOperation::Reboot => domain.reboot(&vm.id).await,
+            };
+
+            let error = outcome.err().map(|e| e.to_string());
+            if let Some(error) = &error {
+                warn!("批量{}失败: {} ({}): {}", operation.label(), vm.name, vm.id, error);
+            }
+            results.push(BatchItemResult { vm, error });
+        }
+
+        results
+    }
+
+    /// 均衡启动: 分批启动虚拟机, 每批通过 `BatchTaskRequest::with_host` 指定负载最低的主机
+    ///
+    /// 放置决策见 [`plan_balanced_start`], 每批的目标主机与放置前的空闲资源记录到日志。
+    /// 单批请求失败只影响本批虚拟机。
+    pub async fn batch_start_balanced(&self, vms: Vec<VmMatchResult>) -> Result<Vec<BatchItemResult>> {
+        if vms.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let hosts = self
+            .vdi_client
+            .host()
+            .list_details(None)
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询主机负载失败: {}", e)))?;
+        let domains = self
+            .vdi_client
+            .domain()
+            .list_all()
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询虚拟机列表失败: {}", e)))?;
+        let sizes: HashMap<String, (u32, u64)> = records_from_listing(&domains, Utc::now())
+            .into_iter()
+            .map(|record| {
+                let cpu = record.cpu.unwrap_or(0).max(0) as u32;
+                let memory_mb = record.memory.unwrap_or(0).max(0) as u64;
+                (record.id, (cpu, memory_mb))
+            })
+            .collect();
+
+        let placements = plan_balanced_start(hosts, vms, &sizes, BALANCED_START_CHUNK_SIZE)?;
+        let total = placements.len();
+        let mut results = Vec::new();
+
+        for (index, placement) in placements.into_iter().enumerate() {
+            info!(
+                "均衡启动第 {}/{} 批 ({} 台) -> 主机 {} ({}), 放置前空闲内存 {}MB, 空闲 CPU {} 核",
+                index + 1,
+                total,
+                placement.vms.len(),
+                placement.host_name,
+                placement.host_id,
+                placement.free_memory_mb,
+                placement.free_cpu
+            );
+
+            let request = BatchTaskRequest::new(placement.vms.iter().map(|vm| vm.id.clone()).collect())
+                .with_host(&placement.host_id);
+            let outcome = self.vdi_client.domain().batch_start(&request).await;
+
+            for vm in placement.vms {
+                let error = match &outcome {
+                    Ok(result) => result
+                        .error_list
+                        .iter()
+                        .find(|error| error.domain_id == vm.id)
+                        .map(|error| error.error_msg.clone()),
+                    Err(e) => Some(e.to_string()),
+                };
+                if let Some(error) = &error {
+                    warn!("均衡启动失败: {} ({}) -> 主机 {}: {}", vm.name, vm.id, placement.host_id, error);
+                }
+                results.push(BatchItemResult { vm, error });
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// 按 ID 或名称解析克隆源, 先查找虚拟机, 再查找模板
+    pub async fn resolve_clone_source(&self, id_or_name: &str) -> Result<CloneSource> {
+        let vms = self.list_vms(CacheMode::Fresh).await?;
+        if vms.iter().any(|vm| vm.id == id_or_name || vm.name == id_or_name) {
+            let id = resolve_vm_id(&vms, id_or_name).map_err(ExecutorError::ConfigError)?;
+            let name = vms.iter().find(|vm| vm.id == id).map(|vm| vm.name.clone()).unwrap_or_default();
+            return Ok(CloneSource { id, name, template: false });
+        }
+
+        let models = self
+            .vdi_client
+            .model()
+            .list()
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询模板列表失败: {}", e)))?;
+        let model = models
+            .iter()
+            .find(|model| model.id == id_or_name)
+            .or_else(|| models.iter().find(|model| model.name == id_or_name))
+            .ok_or_else(|| ExecutorError::ConfigError(format!("克隆源不存在: {}", id_or_name)))?;
+
+        Ok(CloneSource {
+            id: model.id.clone(),
+            name: model.name.clone(),
+            template: true,
+        })
+    }
+
+    /// 批量克隆并等待平台克隆任务完成
+    ///
+    /// 逐台调用克隆接口, 再轮询虚拟机列表直到所有克隆出现且不处于 "操作中",
+    /// 超过 `wait_timeout` 仍未完成的记为等待阶段失败。单台失败不影响其余虚拟机。
+    pub async fn clone_batch_tracked(
+        &self,
+        source: &CloneSource,
+        names: &[String],
+        storage_pool: Option<&str>,
+        wait_timeout: Duration,
+    ) -> Result<Vec<CloneItemResult>> {
+        if source.template && storage_pool.is_none() {
+            return Err(ExecutorError::ConfigError(format!(
+                "克隆源 {} 是模板, 必须指定存储池",
+                source.name
+            )));
+        }
+
+        let mut results: Vec<CloneItemResult> = names.iter().map(|name| CloneItemResult::new(name)).collect();
+        for result in results.iter_mut() {
+            let mut request = CloneDomainRequest::new(&result.name).with_template_clone(source.template);
+            if let Some(storage_pool) = storage_pool {
+                request = request.with_storage_pool(storage_pool);
+            }
+
+            match self.vdi_client.domain().clone_domain(&source.id, &request).await {
+                Ok(cloned) => {
+                    info!("已提交克隆: {} -> {} ({})", source.name, result.name, cloned.clone_uuid);
+                    result.id = Some(cloned.clone_uuid);
+                }
+                Err(e) => result.fail(ClonePhase::Clone, e.to_string()),
+            }
+        }
+
+        let started = Instant::now();
+        loop {
+            let records = self.list_records().await?;
+            let pending = settle_clones(&mut results, &records);
+            if pending == 0 {
+                break;
+            }
+            if started.elapsed() >= wait_timeout {
+                for result in results.iter_mut().filter(|result| result.is_cloning()) {
+                    result.fail(ClonePhase::Wait, format!("{} 秒内克隆未完成", wait_timeout.as_secs()));
+                }
+                break;
+            }
+            debug!("等待克隆完成: 剩余 {} 台", pending);
+            sleep(CLONE_POLL_INTERVAL).await;
+        }
+
+        Ok(results)
+    }
+
+    /// 均衡启动克隆成功的虚拟机 (见 [`Self::batch_start_balanced`]), 并刷新所在主机
+    pub async fn start_clones(&self, results: &mut [CloneItemResult]) {
+        let vms: Vec<VmMatchResult> = results
+            .iter()
+            .filter(|result| result.is_success())
+            .filter_map(|result| {
+                Some(VmMatchResult {
+                    id: result.id.clone()?,
+                    name: result.name.clone(),
+                    status: result.status.clone().unwrap_or_default(),
+                    host_id: result.host_id.clone().unwrap_or_default(),
+                })
+            })
+            .collect();
+
+        let started: Vec<BatchItemResult> = match self.batch_start_balanced(vms).await {
+            Ok(started) => started,
+            Err(e) => {
+                for result in results.iter_mut().filter(|result| result.is_success()) {
+                    result.fail(ClonePhase::Start, e.to_string());
+                }
+                return;
+            }
+        };
+
+        for item in started {
+            let Some(result) = results.iter_mut().find(|result| result.id.as_deref() == Some(item.vm.id.as_str())) else {
+                continue;
+            };
+            match item.error {
+                Some(error) => result.fail(ClonePhase::Start, error),
+                None => result.started = true,
+            }
+        }
+
+        // 启动后虚拟机可能被放到其他主机
+        match self.list_records().await {
+            Ok(records) => {
+                settle_clones(results, &records);
+            }
+            Err(e) => warn!("刷新克隆虚拟机所在主机失败: {}", e),
+        }
+    }
+
+    /// 等待已启动的克隆虚拟机 QGA 就绪
+    ///
+    /// 所有虚拟机共用一个截止时间 (`wait_timeout`), 到期仍无法 ping 通的记为 QGA 阶段失败。
+    pub async fn verify_clones_qga(
+        &self,
+        transport: &TransportManager,
+        results: &mut [CloneItemResult],
+        wait_timeout: Duration,
+    ) {
+        let deadline = Instant::now() + wait_timeout;
+
+        for result in results.iter_mut().filter(|result| result.is_success() && result.started) {
+            match wait_qga_ready(transport, &result.name, deadline).await {
+                Ok(()) => {
+                    info!("QGA 已就绪: {}", result.name);
+                    result.qga_ready = Some(true);
+                }
+                Err(error) => {
+                    result.qga_ready = Some(false);
+                    result.fail(ClonePhase::Qga, error);
+                }
+            }
+        }
+    }
+
+    /// 验证虚拟机的还原点确实生效
+    ///
+    /// 流程: 通过 QGA 在客户机中写入标记文件 `marker_path` (未指定时按客户机系统使用
+    /// [`DEFAULT_LINUX_MARKER_PATH`] 或 [`DEFAULT_WINDOWS_MARKER_PATH`]) -> 设置还原点 (每次重启还原) ->
+    /// 修改标记文件 -> 按 `method` 重启或重置 -> 在 `wait_timeout` 内通过 QGA 确认标记文件恢复为原始内容 ->
+    /// 取消本次设置的还原点。标记文件本身保留在客户机中。
+    ///
+    /// 写入标记时 QGA 不可用的虚拟机记为 [`RestoreStatus::SkippedNoQga`], 不参与后续阶段;
+    /// 其余虚拟机逐台记录失败的阶段, 单台失败不影响其余虚拟机。
+    pub async fn verify_restore_points(
+        &self,
+        transport: &TransportManager,
+        vms: Vec<VmMatchResult>,
+        marker_path: Option<&str>,
+        method: RestoreMethod,
+        wait_timeout: Duration,
+    ) -> Vec<RestoreItemResult> {
+        let nonce = Utc::now().timestamp_millis();
+        let mut results: Vec<RestoreItemResult> = vms.into_iter().map(RestoreItemResult::new).collect();
+
+        for result in results.iter_mut() {
+            let (original, _) = restore_markers(&result.vm.id, nonce);
+            let mut qga = match timeout(QGA_PING_TIMEOUT, connect_qga(transport, &result.vm.name)).await {
+                Ok(Ok(qga)) => qga,
+                Ok(Err(reason)) => {
+                    result.skip(reason);
+                    continue;
+                }
+                Err(_) => {
+                    result.skip(format!("QGA 在 {} 秒内无响应", QGA_PING_TIMEOUT.as_secs()));
+                    continue;
+                }
+            };
+            let path = match marker_path {
+                Some(path) => Ok(path.to_string()),
+                None => default_marker_path(&qga).await,
+            };
+            let written = match &path {
+                Ok(path) => write_marker(&qga, path, &original).await,
+                Err(error) => Err(error.clone()),
+            };
+            let _ = qga.disconnect().await;
+            result.marker_path = path.ok();
+            if let Err(error) = written {
+                result.fail(RestorePhase::WriteMarker, error);
+            }
+        }
+
+        let ids = pending_ids(&results);
+        if ids.is_empty() {
+            return results;
+        }
+        let request = BatchFreezeRequest::new(ids, FreezeType::EveryBoot);
+        let frozen = match self.vdi_client.domain().batch_freeze(&request).await {
+            Ok(already_frozen) => settle_freeze(&mut results, &already_frozen),
+            Err(e) => {
+                for result in results.iter_mut().filter(|result| result.is_pending()) {
+                    result.fail(RestorePhase::Freeze, e.to_string());
+                }
+                return results;
+            }
+        };
+
+        for result in results.iter_mut().filter(|result| result.is_pending()) {
+            let (_, modified) = restore_markers(&result.vm.id, nonce);
+            let path = result.marker_path.clone().unwrap_or_default();
+            // 修改同样需要落盘, 否则重置时丢失的页缓存会让未生效的还原点也显示为通过
+            let written = match timeout(QGA_PING_TIMEOUT, connect_qga(transport, &result.vm.name)).await {
+                Ok(Ok(mut qga)) => {
+                    let written = write_marker(&qga, &path, &modified).await;
+                    let _ = qga.disconnect().await;
+                    written
+                }
+                Ok(Err(error)) => Err(error),
+                Err(_) => Err(format!("QGA 在 {} 秒内无响应", QGA_PING_TIMEOUT.as_secs())),
+            };
+            if let Err(error) = written {
+                result.fail(RestorePhase::ModifyMarker, error);
+            }
+        }
+
+        self.revert(&mut results, method).await;
+
+        let deadline = Instant::now() + wait_timeout;
+        for result in results.iter_mut().filter(|result| result.is_pending()) {
+            let (original, modified) = restore_markers(&result.vm.id, nonce);
+            let path = result.marker_path.clone().unwrap_or_default();
+            match wait_marker_reverted(transport, &result.vm.name, &path, &original, &modified, deadline).await {
+                Ok(()) => info!("还原点已生效: {}", result.vm.name),
+                Err(error) => result.fail(RestorePhase::VerifyMarker, error),
+            }
+        }
+
+        if let Err(e) = self.vdi_client.domain().batch_restore(&BatchTaskRequest::new(frozen.clone())).await {
+            for result in results.iter_mut().filter(|result| frozen.contains(&result.vm.id)) {
+                if result.is_pending() {
+                    result.fail(RestorePhase::Cleanup, e.to_string());
+                } else {
+                    warn!("取消还原点失败: {} ({}): {}", result.vm.name, result.vm.id, e);
+                }
+            }
+        }
+
+        results
+    }
+
+    /// 按 `method` 触发还原, 失败的虚拟机记为还原阶段失败
+    async fn revert(&self, results: &mut [RestoreItemResult], method: RestoreMethod) {
+        match method {
+            RestoreMethod::Reboot => {
+                for result in results.iter_mut().filter(|result| result.is_pending()) {
+                    if let Err(e) = self.vdi_client.domain().reboot(&result.vm.id).await {
+                        result.fail(RestorePhase::Revert, e.to_string());
+                    }
+                }
+            }
+            RestoreMethod::Rebase => {
+                let ids = pending_ids(results);
+                if ids.is_empty() {
+                    return;
+                }
+                if let Err(e) = self.vdi_client.domain().batch_rebase(&BatchTaskRequest::new(ids)).await {
+                    for result in results.iter_mut().filter(|result| result.is_pending()) {
+                        result.fail(RestorePhase::Revert, e.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    /// 按分配映射把用户绑定到虚拟机, 单条失败不影响其余映射
+    ///
+    /// 虚拟机按 `mode` 查询, 用户列表总是查询 VDI 平台。
+    pub async fn batch_assign(&self, mappings: &[AssignMapping], mode: CacheMode) -> Result<Vec<AssignItemResult>> {
+        let vms = self.list_vms(mode).await?;
+        let users = self
+            .vdi_client
+            .user()
+            .list()
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询用户列表失败: {}", e)))?;
+
+        let mut results = resolve_assignments(mappings, &vms, &users);
+        for result in results.iter_mut().filter(|result| result.is_success()) {
+            let (Some(vm_id), Some(user_id)) = (&result.vm_id, &result.user_id) else {
+                continue;
+            };
+            if let Err(e) = self.vdi_client.domain().bind_user(vm_id, user_id).await {
+                warn!("分配用户失败: {} -> {}: {}", result.mapping.user, result.mapping.vm, e);
+                result.error = Some(e.to_string());
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// 分页查询全部桌面池
+    async fn list_all_pools(&self) -> Result<Vec<serde_json::Value>> {
+        let mut pools = Vec::new();
+
+        for page in 1.. {
+            let items = self
+                .vdi_client
+                .desk_pool()
+                .list_paged(page, POOL_PAGE_SIZE)
+                .await
+                .map_err(|e| ExecutorError::TransportError(format!("查询桌面池列表失败: {}", e)))?;
+            let last_page = items.len() < POOL_PAGE_SIZE as usize;
+            pools.extend(items);
+            if last_page {
+                break;
+            }
+        }
+
+        Ok(pools)
+    }
+
+    /// 查询虚拟机列表 (按缓存策略)
+    async fn list_vms(&self, mode: CacheMode) -> Result<Vec<VmMatchResult>> {
+        let vms = match &self.cache {
+            Some(cache) => cache.list_vms(mode).await?,
+            None if mode == CacheMode::CacheOnly => {
+                return Err(ExecutorError::ConfigError(
+                    "未配置虚拟机缓存, 无法只从缓存查询".to_string(),
+                ));
+            }
+            None => self.list_records().await?,
+        };
+
+        Ok(vms.into_iter().map(VmMatchResult::from).collect())
+    }
+
+    /// 直接查询 VDI 平台的虚拟机列表
+    async fn list_records(&self) -> Result<Vec<VmCacheRecord>> {
+        let domains = self
+            .vdi_client
+            .domain()
+            .list_all()
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询虚拟机列表失败: {}", e)))?;
+        Ok(records_from_listing(&domains, Utc::now()))
+    }
+}
+
+/// 经 libvirt 连接虚拟机的 QGA 并 ping, 失败时重试直到 `deadline`, 返回最后一次的错误
+async fn wait_qga_ready(transport: &TransportManager, domain_name: &str, deadline: Instant) -> std::result::Result<(), String> {
+    loop {
+        let attempt = async {
+            let mut qga = connect_qga(transport, domain_name).await?;
+            let _ = qga.disconnect().await;
+            Ok(())
+        };
+
+        let error = match timeout(QGA_PING_TIMEOUT, attempt).await {
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(error)) => error,
+            Err(_) => format!("QGA 在 {} 秒内无响应", QGA_PING_TIMEOUT.as_secs()),
+        };
+        if Instant::now() + QGA_RETRY_INTERVAL >= deadline {
+            return Err(error);
+        }
+        debug!("QGA 未就绪: {}: {}", domain_name, error);
+        sleep(QGA_RETRY_INTERVAL).await;
+    }
+}
+
+/// 经 libvirt 连接虚拟机的 QGA 并 ping
+async fn connect_qga(transport: &TransportManager, domain_name: &str) -> std::result::Result<QgaProtocol, String> {
+    // 新克隆或刚重启的虚拟机可能还不在主机的虚拟机缓存中, 或已被放到其他主机
+    transport.invalidate_domain_cache().await;
+    let domain = transport
+        .execute_on_domain(domain_name, |conn, _| async move { conn.get_domain(domain_name).await })
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let mut qga = QgaProtocol::new();
+    qga.connect(&domain).await.map_err(|e| format!("QGA 协议连接失败: {}", e))?;
+    if let Err(e) = qga.ping().await {
+        let _ = qga.disconnect().await;
+        return Err(format!("QGA ping 失败: {}", e));
+    }
+    Ok(qga)
+}
+
+/// 按客户机系统选择默认的标记文件路径
+async fn default_marker_path(qga: &QgaProtocol) -> std::result::Result<String, String> {
+    let os_info = timeout(QGA_PING_TIMEOUT, qga.get_osinfo())
+        .await
+        .map_err(|_| format!("QGA 在 {} 秒内无响应", QGA_PING_TIMEOUT.as_secs()))?
+        .map_err(|e| format!("查询客户机系统失败: {}", e))?;
+    let path = match GuestPlatform::from_os_info(&os_info) {
+        GuestPlatform::Windows => DEFAULT_WINDOWS_MARKER_PATH,
+        GuestPlatform::Linux => DEFAULT_LINUX_MARKER_PATH,
+    };
+    Ok(path.to_string())
+}
+
+/// 写入标记文件, 再冻结并解冻客户机文件系统, 使标记在设置还原点前落盘
+///
+/// 客户机不支持冻结文件系统时只记录日志; 冻结成功而解冻失败时返回错误, 以免遗漏被冻结的客户机。
+async fn write_marker(qga: &QgaProtocol, marker_path: &str, content: &str) -> std::result::Result<(), String> {
+    let write = async {
+        guest_file::write_guest_file(qga, marker_path, content.as_bytes())
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match qga.execute_command::<(), i64>("guest-fsfreeze-freeze", None).await {
+            Ok(_) => qga
+                .execute_command::<(), i64>("guest-fsfreeze-thaw", None)
+                .await
+                .map(|_| ())
+                .map_err(|e| format!("解冻客户机文件系统失败: {}", e)),
+            Err(e) => {
+                debug!("冻结客户机文件系统失败, 标记文件可能尚未落盘: {}", e);
+                Ok(())
+            }
+        }
+    };
+
+    timeout(QGA_FILE_TIMEOUT, write)
+        .await
+        .unwrap_or_else(|_| Err(format!("{} 秒内未完成", QGA_FILE_TIMEOUT.as_secs())))
+}
+
+/// 读取标记文件直到恢复为原始内容, 失败时重试直到 `deadline`, 返回最后一次的原因
+///
+/// 触发还原后旧的系统可能仍在运行一段时间, 读到修改后的内容同样继续重试。
+async fn wait_marker_reverted(
+    transport: &TransportManager,
+    domain_name: &str,
+    marker_path: &str,
+    original: &str,
+    modified: &str,
+    deadline: Instant,
+) -> std::result::Result<(), String> {
+    loop {
+        let attempt = async {
+            let mut qga = connect_qga(transport, domain_name).await?;
+            let read = guest_file::read_guest_file(
+                &qga,
+                marker_path,
+                guest_file::DEFAULT_REGION_SIZE,
+                MARKER_MAX_BYTES,
+                false,
+            )
+            .await
+            .map_err(|e| e.to_string());
+            let _ = qga.disconnect().await;
+
+            let head = read?.map(|(_, contents)| contents.head);
+            match MarkerState::of(head.as_deref(), original, modified) {
+                MarkerState::Original => Ok(()),
+                state => Err(state.problem().to_string()),
+            }
+        };
+
+        let error = match timeout(QGA_FILE_TIMEOUT, attempt).await {
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(error)) => error,
+            Err(_) => format!("QGA 在 {} 秒内无响应", QGA_FILE_TIMEOUT.as_secs()),
+        };
+        if Instant::now() + QGA_RETRY_INTERVAL >= deadline {
+            return Err(error);
+        }
+        debug!("标记文件尚未还原: {}: {}", domain_name, error);
+        sleep(QGA_RETRY_INTERVAL).await;
+    }
+}
+
+/// 尚未失败或跳过的虚拟机 ID
+fn pending_ids(results: &[RestoreItemResult]) -> Vec<String> {
+    results
+        .iter()
+        .filter(|result| result.is_pending())
+        .map(|result| result.vm.id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("win10-*", "win10-01"));
+        assert!(matches_pattern("win10-*", "win10-"));
+        assert!(matches_pattern("*", ""));
+        assert!(matches_pattern("lab-??", "lab-07"));
+        assert!(matches_pattern("*-0?-*", "win10-01-test"));
+        assert!(matches_pattern("桌面*", "桌面01"));
+        assert!(matches_pattern("exact", "exact"));
+
+        assert!(!matches_pattern("win10-*", "win11-01"));
+        assert!(!matches_pattern("lab-??", "lab-7"));
+        assert!(!matches_pattern("exact", "exact2"));
+        assert!(!matches_pattern("a*b", "acbd"));
+    }
+
+    fn vm(id: &str, name: &str) -> VmMatchResult {
+        VmMatchResult {
+            id: id.to_string(),
+            name: name.to_string(),
+            status: "运行中".to_string(),
+            host_id: "host-1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_target_from_args() {
+        assert_eq!(
+            Target::from_args(Some("win10-*".into()), None, vec![]).unwrap(),
+            Target::Pattern("win10-*".into())
+        );
+        assert_eq!(Target::from_args(None, Some("财务部".into()), vec![]).unwrap(), Target::Pool("财务部".into()));
+        assert_eq!(
+            Target::from_args(None, None, vec!["vm-1".into()]).unwrap(),
+            Target::IdList(vec!["vm-1".into()])
+        );
+
+        // 必须且只能指定一种
+        assert!(Target::from_args(None, None, vec![]).is_err());
+        assert!(Target::from_args(Some("*".into()), Some("pool".into()), vec![]).is_err());
+        assert!(Target::from_args(None, Some("pool".into()), vec!["vm-1".into()]).is_err());
+    }
+
+    #[test]
+    fn test_find_pool_id() {
+        let pools = vec![
+            serde_json::json!({"id": "pool-1", "name": "财务部"}),
+            serde_json::json!({"id": "pool-2", "name": "研发部"}),
+            serde_json::json!({"id": "pool-3", "name": "研发部"}),
+            serde_json::json!({"id": "pool-4", "name": "pool-1"}),
+        ];
+
+        assert_eq!(find_pool_id(&pools, "财务部").unwrap(), "pool-1");
+        // ID 优先于名称
+        assert_eq!(find_pool_id(&pools, "pool-1").unwrap(), "pool-1");
+        assert_eq!(find_pool_id(&pools, "pool-3").unwrap(), "pool-3");
+
+        let err = find_pool_id(&pools, "研发部").unwrap_err().to_string();
+        assert!(err.contains("pool-2, pool-3"), "{}", err);
+        assert!(find_pool_id(&pools, "市场部").is_err());
+    }
+
+    #[test]
+    fn test_select_by_ids() {
+        let vms = vec![vm("vm-1", "a"), vm("vm-2", "b"), vm("vm-3", "c")];
+
+        let selected = select_by_ids(vms.clone(), &["vm-3".to_string(), "vm-1".to_string()]).unwrap();
+        assert_eq!(selected, vec![vm("vm-3", "c"), vm("vm-1", "a")]);
+
+        let err = select_by_ids(vms, &["vm-1".to_string(), "vm-9".to_string()]).unwrap_err();
+        assert!(err.to_string().contains("vm-9"));
+    }
+
+    #[test]
+    fn test_parse_assign_mapping() {
+        let content = "vm,user\n# 财务部\nwin10-01, alice\n\n\"win10-02\",bob\n";
+        let mappings = parse_assign_mapping(content).unwrap();
+        assert_eq!(
+            mappings,
+            vec![
+                AssignMapping { vm: "win10-01".into(), user: "alice".into() },
+                AssignMapping { vm: "win10-02".into(), user: "bob".into() },
+            ]
+        );
+
+        let err = parse_assign_mapping("win10-01,alice\nwin10-02\n").unwrap_err().to_string();
+        assert!(err.contains("第 2 行"), "{}", err);
+        let err = parse_assign_mapping("win10-01,alice\nwin10-01,bob\n").unwrap_err().to_string();
+        assert!(err.contains("重复"), "{}", err);
+    }
+
+    #[test]
+    fn test_resolve_assignments() {
+        let user = |id: &str, username: &str| User {
+            id: id.to_string(),
+            username: username.to_string(),
+            display_name: username.to_string(),
+            email: None,
+        };
+        let vms = vec![vm("vm-1", "win10-01"), vm("vm-2", "dup"), vm("vm-3", "dup")];
+        let users = vec![user("u-1", "alice"), user("u-2", "bob")];
+        let mapping = |vm: &str, user: &str| AssignMapping { vm: vm.into(), user: user.into() };
+
+        let results = resolve_assignments(
+            &[
+                mapping("win10-01", "alice"),
+                mapping("vm-3", "u-2"),
+                mapping("dup", "alice"),
+                mapping("win10-01", "carol"),
+            ],
+            &vms,
+            &users,
+        );
+
+        assert!(results[0].is_success());
+        assert_eq!(results[0].vm_id.as_deref(), Some("vm-1"));
+        assert_eq!(results[0].user_id.as_deref(), Some("u-1"));
+        assert_eq!(results[1].vm_id.as_deref(), Some("vm-3"));
+        assert_eq!(results[1].user_id.as_deref(), Some("u-2"));
+        assert!(results[2].error.as_deref().unwrap().contains("vm-2, vm-3"));
+        assert!(results[3].error.as_deref().unwrap().contains("carol"));
+    }
+
+    #[test]
+    fn test_plan_balanced_start() {
+        let host = |id: &str, status: i64, cpu_total: u32, memory_total_mb: u64| HostDetail {
+            id: id.to_string(),
+            name: format!("node-{}", id),
+            ip: String::new(),
+            status,
+            cpu_total,
+            cpu_used: 0,
+            memory_total_mb,
+            memory_used_mb: 0,
+            running_vms: 0,
+            storage_pool_ids: Vec::new(),
+        };
+        let hosts = vec![host("h-1", 1, 32, 20480), host("h-2", 1, 32, 16384), host("h-3", 0, 64, 65536)];
+        let vms: Vec<VmMatchResult> = (1..=5).map(|i| vm(&format!("vm-{}", i), &format!("win10-0{}", i))).collect();
+        let sizes: HashMap<String, (u32, u64)> = vms.iter().map(|vm| (vm.id.clone(), (2, 4096))).collect();
+
+        let placements = plan_balanced_start(hosts, vms, &sizes, 2).unwrap();
+        let summary: Vec<(&str, u64, usize)> = placements
+            .iter()
+            .map(|placement| (placement.host_id.as_str(), placement.free_memory_mb, placement.vms.len()))
+            .collect();
+        // 维护中的 h-3 不参与; 每批放置后扣除 8192MB, 下一批改选空闲内存更多的主机
+        assert_eq!(summary, vec![("h-1", 20480, 2), ("h-2", 16384, 2), ("h-1", 12288, 1)]);
+        assert_eq!(placements[1].vms[0].id, "vm-3");
+
+        let err = plan_balanced_start(vec![host("h-3", 0, 64, 65536)], vec![vm("vm-1", "a")], &sizes, 2).unwrap_err();
+        assert!(err.to_string().contains("没有处于启用状态的主机"), "{}", err);
+        assert!(plan_balanced_start(Vec::new(), Vec::new(), &sizes, 2).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_name_template() {
+        let template = NameTemplate::parse("lab-{:03}").unwrap();
+        assert_eq!(template.render(7), "lab-007");
+        assert_eq!(template.render(1234), "lab-1234");
+        assert_eq!(template.names(3).unwrap(), ["lab-001", "lab-002", "lab-003"]);
+
+        let template = NameTemplate::parse("{}-桌面").unwrap();
+        assert_eq!(template.names(2).unwrap(), ["1-桌面", "2-桌面"]);
+
+        for bad in ["lab", "lab-{}-{}", "lab-{:3}", "lab-{:0}", "lab-{:0x}", "lab-{:011}", "lab-{name}", "lab-{", "lab-}{}", "{{}}"] {
+            let err = NameTemplate::parse(bad).unwrap_err();
+            assert!(err.to_string().contains("命名模板"), "{}: {}", bad, err);
+        }
+    }
+
+    #[test]
+    fn test_name_template_validates_names() {
+        let template = NameTemplate::parse("lab/{}").unwrap();
+        assert!(template.names(1).unwrap_err().to_string().contains("无效"));
+        assert!(NameTemplate::parse("lab-{}").unwrap().names(0).is_err());
+    }
+
+    fn record(id: &str, status: &str, host_id: &str) -> VmCacheRecord {
+        VmCacheRecord {
+            id: id.to_string(),
+            name: id.to_string(),
+            status: status.to_string(),
+            host_id: host_id.to_string(),
+            cpu: None,
+            memory: None,
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_settle_clones() {
+        let mut results: Vec<CloneItemResult> =
+            ["lab-1", "lab-2", "lab-3", "lab-4"].iter().map(|name| CloneItemResult::new(name)).collect();
+        results[0].id = Some("vm-1".to_string());
+        results[1].id = Some("vm-2".to_string());
+        results[2].id = Some("vm-3".to_string());
+        results[3].fail(ClonePhase::Clone, "名称重复".to_string());
+
+        // vm-2 仍在操作中, vm-3 尚未出现在列表中
+        let records = vec![record("vm-1", "关机", "h-1"), record("vm-2", "操作中", "")];
+        assert_eq!(settle_clones(&mut results, &records), 2);
+        assert_eq!(results[0].status.as_deref(), Some("关机"));
+        assert_eq!(results[0].host_id.as_deref(), Some("h-1"));
+        assert_eq!(results[1].host_id, None);
+
+        let records = vec![record("vm-1", "运行中", "h-2"), record("vm-2", "关机", "h-1"), record("vm-3", "关机", "h-1")];
+        assert_eq!(settle_clones(&mut results, &records), 0);
+        assert_eq!(results[0].host_id.as_deref(), Some("h-2"));
+        assert_eq!(results[3].failed_phase, Some(ClonePhase::Clone));
+        assert_eq!(results[3].status, None);
+    }
+
+    #[test]
+    fn test_clone_item_result_serialization() {
+        let mut result = CloneItemResult::new("lab-001");
+        result.id = Some("vm-1".to_string());
+        result.started = true;
+        result.qga_ready = Some(false);
+        result.fail(ClonePhase::Qga, "QGA 在 5 秒内无响应".to_string());
+
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(value["failed_phase"], "qga");
+        assert_eq!(value["qga_ready"], false);
+        assert!(!result.is_success());
+    }
+
+    #[test]
+    fn test_marker_state() {
+        let (original, modified) = restore_markers("vm-1", 1700000000000);
+        assert_ne!(original, modified);
+        // 不同虚拟机或不同运行的标记互不相同
+        assert_ne!(restore_markers("vm-2", 1700000000000).0, original);
+        assert_ne!(restore_markers("vm-1", 1700000000001).0, original);
+
+        let state = |content: Option<&str>| MarkerState::of(content.map(str::as_bytes), &original, &modified);
+        assert_eq!(state(Some(&original)), MarkerState::Original);
+        assert_eq!(state(Some(&modified)), MarkerState::Modified);
+        assert_eq!(state(None), MarkerState::Missing);
+        assert_eq!(state(Some("")), MarkerState::Unexpected);
+        assert!(MarkerState::Modified.problem().contains("未生效"));
+    }
+
+    #[test]
+    fn test_settle_freeze() {
+        let mut results: Vec<RestoreItemResult> =
+            [vm("vm-1", "lab-1"), vm("vm-2", "lab-2"), vm("vm-3", "lab-3"), vm("vm-4", "lab-4")]
+                .into_iter()
+                .map(RestoreItemResult::new)
+                .collect();
+        results[2].skip("QGA 在 5 秒内无响应".to_string());
+        results[3].fail(RestorePhase::WriteMarker, "目录不存在".to_string());
+
+        // vm-2 在验证前已有还原点, 不由本次取消
+        let frozen = settle_freeze(&mut results, &["vm-2".to_string()]);
+        assert_eq!(frozen, ["vm-1"]);
+        assert_eq!(results[1].failed_phase, Some(RestorePhase::Freeze));
+        assert_eq!(results[2].status, RestoreStatus::SkippedNoQga);
+        assert_eq!(results[2].failed_phase, None);
+        assert_eq!(pending_ids(&results), ["vm-1"]);
+    }
+
+    #[test]
+    fn test_restore_item_result_serialization() {
+        let mut result = RestoreItemResult::new(vm("vm-1", "lab-1"));
+        result.fail(RestorePhase::VerifyMarker, "标记文件仍为修改后的内容, 还原点未生效".to_string());
+        let value = serde_json::to_value(&result).unwrap();
+        assert_eq!(value["status"], "failed");
+        assert_eq!(value["failed_phase"], "verify_marker");
+
+        let mut result = RestoreItemResult::new(vm("vm-2", "lab-2"));
+        result.skip("QGA ping 失败".to_string());
+        assert_eq!(serde_json::to_value(&result).unwrap()["status"], "skipped_no_qga");
+
+        let method: RestoreMethod = serde_json::from_value(serde_json::json!("rebase")).unwrap();
+        assert_eq!(method, RestoreMethod::Rebase);
+    }
+}
@@ -0,0 +1,176 @@
+//! VDI 虚拟机信息缓存
+//!
+//! 包装 `VdiClient::domain().list_all()`, 把结果同步到本地数据库的 `vm_cache` 表,
+//! 缓存有效期内的查询直接读数据库, 同步时发现的状态变化写入 `vm_status_history`。
+//! 缓存时间以数据库中的 `updated_at` 为准, 多次 CLI 调用之间共享。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use atp_storage::{Storage, VmCacheRecord};
+use atp_vdiplatform::VdiClient;
+use chrono::{DateTime, Utc};
+use tracing::{debug, info};
+
+use crate::{ExecutorError, Result};
+
+/// 默认缓存有效期 (5 分钟)
+pub const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// 查询虚拟机列表时的缓存策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// 总是查询 VDI 平台并更新缓存
+    Fresh,
+    /// 缓存未过期时读缓存, 否则查询 VDI 平台
+    Cached,
+    /// 只读缓存, 不访问 VDI 平台 (离线查询)
+    CacheOnly,
+}
+
+/// VDI 平台虚拟机状态码对应的名称
+pub fn domain_status_label(status: i64) -> &'static str {
+    match status {
+        0 => "关机",
+        1 => "运行中",
+        2 => "挂起",
+        3 => "休眠",
+        5 => "操作中",
+        6 => "升级中",
+        _ => "未知",
+    }
+}
+
+/// 把 `domain().list_all()` 返回的条目转换为缓存记录 (缺少 `id` 的条目被忽略)
+pub fn records_from_listing(items: &[serde_json::Value], updated_at: DateTime<Utc>) -> Vec<VmCacheRecord> {
+    items
+        .iter()
+        .filter_map(|item| {
+            let id = item["id"].as_str().filter(|id| !id.is_empty())?;
+            Some(VmCacheRecord {
+                id: id.to_string(),
+                name: item["name"].as_str().unwrap_or_default().to_string(),
+                status: domain_status_label(item["status"].as_i64().unwrap_or(-1)).to_string(),
+                host_id: item["hostId"].as_str().unwrap_or_default().to_string(),
+                cpu: item["cpuNum"].as_i64(),
+                memory: item["memory"].as_i64(),
+                updated_at,
+            })
+        })
+        .collect()
+}
+
+/// 虚拟机缓存管理器
+pub struct VmCacheManager {
+    client: Arc<VdiClient>,
+    storage: Arc<Storage>,
+    ttl: Duration,
+}
+
+impl VmCacheManager {
+    pub fn new(client: Arc<VdiClient>, storage: Arc<Storage>) -> Self {
+        Self {
+            client,
+            storage,
+            ttl: DEFAULT_CACHE_TTL,
+        }
+    }
+
+    /// 设置缓存有效期
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// 按缓存策略获取虚拟机列表
+    pub async fn list_vms(&self, mode: CacheMode) -> Result<Vec<VmCacheRecord>> {
+        match mode {
+            CacheMode::Fresh => self.force_refresh().await,
+            CacheMode::Cached if self.is_fresh().await? => self.cached_vms().await,
+            CacheMode::Cached => self.force_refresh().await,
+            CacheMode::CacheOnly => self.cached_vms().await,
+        }
+    }
+
+    /// 缓存是否在有效期内
+    pub async fn is_fresh(&self) -> Result<bool> {
+        let last_updated = self
+            .storage
+            .vm_cache()
+            .last_updated()
+            .await
+            .map_err(|e| ExecutorError::DatabaseError(format!("读取虚拟机缓存失败: {}", e)))?;
+
+        let ttl = chrono::Duration::from_std(self.ttl).unwrap_or(chrono::Duration::MAX);
+        Ok(last_updated.is_some_and(|updated_at| Utc::now() - updated_at < ttl))
+    }
+
+    /// 忽略缓存有效期, 从 VDI 平台重新获取虚拟机列表并同步到缓存
+    pub async fn force_refresh(&self) -> Result<Vec<VmCacheRecord>> {
+        let domains = self
+            .client
+            .domain()
+            .list_all()
+            .await
+            .map_err(|e| ExecutorError::TransportError(format!("查询虚拟机列表失败: {}", e)))?;
+
+        let records = records_from_listing(&domains, Utc::now());
+        let transitions = self
+            .storage
+            .vm_cache()
+            .sync_all(&records)
+            .await
+            .map_err(|e| ExecutorError::DatabaseError(format!("更新虚拟机缓存失败: {}", e)))?;
+
+        info!(
+            "虚拟机缓存已刷新: {} 台虚拟机, {} 条状态变更",
+            records.len(),
+            transitions.len()
+        );
+        Ok(records)
+    }
+
+    async fn cached_vms(&self) -> Result<Vec<VmCacheRecord>> {
+        let vms = self
+            .storage
+            .vm_cache()
+            .list_all()
+            .await
+            .map_err(|e| ExecutorError::DatabaseError(format!("读取虚拟机缓存失败: {}", e)))?;
+
+        debug!("从缓存读取 {} 台虚拟机", vms.len());
+        Ok(vms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_records_from_listing() {
+        let now = Utc::now();
+        let records = records_from_listing(
+            &[
+                json!({"id": "vm-1", "name": "win10-01", "status": 1, "hostId": "host-1", "cpuNum": 4, "memory": 8192}),
+                json!({"id": "vm-2", "name": "win10-02", "status": 9}),
+                json!({"name": "no-id", "status": 0}),
+            ],
+            now,
+        );
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].status, "运行中");
+        assert_eq!(records[0].host_id, "host-1");
+        assert_eq!(records[0].cpu, Some(4));
+        assert_eq!(records[0].memory, Some(8192));
+        assert_eq!(records[1].status, "未知");
+        assert_eq!(records[1].cpu, None);
+        assert!(records.iter().all(|record| record.updated_at == now));
+    }
+}
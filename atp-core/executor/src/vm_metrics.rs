@@ -0,0 +1,224 @@
+//! 虚拟机资源指标源
+//!
+//! 供 `MetricsCollector` 后台定期采样虚拟机资源, 有两种数据来源:
+//!
+//! - [`LibvirtVmMetrics`]: 直接通过 libvirt 读取 CPU 时间、内存与磁盘 IO 统计,
+//!   不依赖 VDI 平台的监控模块, CPU 使用率由相邻两次采样差分得到
+//! - [`VdiVmMetrics`]: 读取 VDI 平台的虚拟机列表, 只有运行状态与分配的资源
+//!
+//! 单台虚拟机采样失败只记录告警并跳过, 不影响采集循环。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use atp_storage::{MetricSample, MetricsSource};
+use atp_transport::{cpu_usage_percent, DomainStatsSample, TransportManager};
+use atp_vdiplatform::VdiClient;
+use tokio::sync::Mutex;
+use tracing::warn;
+
+/// 基于 libvirt 的虚拟机资源指标源
+pub struct LibvirtVmMetrics {
+    transport: Arc<TransportManager>,
+    /// (主机 ID, 虚拟机名称)
+    targets: Vec<(String, String)>,
+    /// 上一次采样, 用于计算 CPU 使用率
+    previous: Mutex<HashMap<(String, String), DomainStatsSample>>,
+}
+
+impl LibvirtVmMetrics {
+    pub fn new(transport: Arc<TransportManager>) -> Self {
+        Self {
+            transport,
+            targets: Vec::new(),
+            previous: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 添加要采样的虚拟机
+    pub fn with_target(mut self, host_id: &str, domain: &str) -> Self {
+        self.targets.push((host_id.to_string(), domain.to_string()));
+        self
+    }
+
+    async fn sample(&self, host_id: &str, domain: &str) -> atp_transport::Result<DomainStatsSample> {
+        let connection = self.transport.pool().get_connection(host_id).await?;
+        connection.sample_domain_stats(domain).await
+    }
+}
+
+#[async_trait]
+impl MetricsSource for LibvirtVmMetrics {
+    fn source_name(&self) -> &str {
+        "vm_libvirt"
+    }
+
+    async fn collect(&self) -> Vec<MetricSample> {
+        let mut samples = Vec::new();
+
+        for (host_id, domain) in &self.targets {
+            let current = match self.sample(host_id, domain).await {
+                Ok(current) => current,
+                Err(e) => {
+                    warn!("采样虚拟机 {} 资源失败, 已跳过: {}", domain, e);
+                    continue;
+                }
+            };
+
+            let previous = self
+                .previous
+                .lock()
+                .await
+                .insert((host_id.clone(), domain.clone()), current.clone());
+
+            samples.extend(
+                stats_to_samples(&current, previous.as_ref())
+                    .into_iter()
+                    .map(|sample| sample.with_label("host", host_id).with_label("domain", domain)),
+            );
+        }
+
+        samples
+    }
+}
+
+/// 把一次 libvirt 采样转换为指标 (第一次采样没有 CPU 使用率)
+fn stats_to_samples(current: &DomainStatsSample, previous: Option<&DomainStatsSample>) -> Vec<MetricSample> {
+    let mut samples = vec![
+        MetricSample::new("vm_vcpus", current.vcpus as f64),
+        MetricSample::new("vm_memory_mb", current.memory_kb as f64 / 1024.0),
+    ];
+
+    if let Some(usage) = previous.and_then(|previous| cpu_usage_percent(previous, current)) {
+        samples.push(MetricSample::new("vm_cpu_usage_percent", usage));
+    }
+    if let Some(used_kb) = current.memory_used_kb() {
+        samples.push(MetricSample::new("vm_memory_used_mb", used_kb as f64 / 1024.0));
+    }
+    if let Some(usage) = current.memory_usage_percent() {
+        samples.push(MetricSample::new("vm_memory_usage_percent", usage));
+    }
+    if let Some(rss_kb) = current.rss_kb {
+        samples.push(MetricSample::new("vm_rss_mb", rss_kb as f64 / 1024.0));
+    }
+    if let Some(block) = &current.block {
+        samples.push(MetricSample::new("vm_disk_read_bytes", block.read_bytes as f64));
+        samples.push(MetricSample::new("vm_disk_write_bytes", block.write_bytes as f64));
+        samples.push(MetricSample::new("vm_disk_read_ops", block.read_ops as f64));
+        samples.push(MetricSample::new("vm_disk_write_ops", block.write_ops as f64));
+    }
+
+    samples
+}
+
+/// 基于 VDI 平台虚拟机列表的指标源
+pub struct VdiVmMetrics {
+    client: Arc<VdiClient>,
+}
+
+impl VdiVmMetrics {
+    pub fn new(client: Arc<VdiClient>) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl MetricsSource for VdiVmMetrics {
+    fn source_name(&self) -> &str {
+        "vm_vdi"
+    }
+
+    async fn collect(&self) -> Vec<MetricSample> {
+        match self.client.domain().list_all().await {
+            Ok(domains) => listing_to_samples(&domains),
+            Err(e) => {
+                warn!("查询 VDI 虚拟机列表失败, 本轮跳过: {}", e);
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// 把 VDI 虚拟机列表转换为指标 (缺少名称的条目被忽略)
+fn listing_to_samples(domains: &[serde_json::Value]) -> Vec<MetricSample> {
+    let mut samples = Vec::new();
+
+    for domain in domains {
+        let Some(name) = domain["name"].as_str() else {
+            continue;
+        };
+        let host = domain["hostId"].as_str().unwrap_or("-");
+
+        let running = domain["status"].as_i64() == Some(1);
+        let mut values = vec![("vm_running", if running { 1.0 } else { 0.0 })];
+        if let Some(vcpus) = domain["cpuNum"].as_f64() {
+            values.push(("vm_vcpus", vcpus));
+        }
+        if let Some(memory) = domain["memory"].as_f64() {
+            values.push(("vm_memory_mb", memory));
+        }
+
+        samples.extend(
+            values
+                .into_iter()
+                .map(|(metric, value)| MetricSample::new(metric, value).with_label("host", host).with_label("domain", name)),
+        );
+    }
+
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_stats_to_samples() {
+        let previous = DomainStatsSample::new("win10", 0, 2, 4096 * 1024);
+        let mut current = DomainStatsSample::new("win10", 1_000_000_000, 2, 4096 * 1024);
+        current.timestamp = previous.timestamp + chrono::Duration::seconds(1);
+        current.rss_kb = Some(2048 * 1024);
+
+        let names = |samples: &[MetricSample]| samples.iter().map(|s| s.name.clone()).collect::<Vec<_>>();
+
+        // 第一次采样只有分配的资源
+        let first = stats_to_samples(&previous, None);
+        assert_eq!(names(&first), vec!["vm_vcpus", "vm_memory_mb"]);
+        assert_eq!(first[1].value, 4096.0);
+
+        let second = stats_to_samples(&current, Some(&previous));
+        assert_eq!(names(&second), vec!["vm_vcpus", "vm_memory_mb", "vm_cpu_usage_percent", "vm_rss_mb"]);
+        assert!((second[2].value - 50.0).abs() < 1e-9);
+        assert_eq!(second[3].value, 2048.0);
+
+        // 磁盘 IO 为累计计数
+        current.block = Some(atp_transport::BlockIoStats::from_libvirt(3, 4096, 1, 512));
+        let third = stats_to_samples(&current, None);
+        assert_eq!(
+            names(&third)[3..],
+            ["vm_disk_read_bytes", "vm_disk_write_bytes", "vm_disk_read_ops", "vm_disk_write_ops"]
+        );
+        assert_eq!(third[3].value, 4096.0);
+        assert_eq!(third[6].value, 1.0);
+    }
+
+    #[test]
+    fn test_listing_to_samples() {
+        let samples = listing_to_samples(&[
+            json!({"name": "vm-1", "hostId": "h1", "status": 1, "cpuNum": 4, "memory": 8192}),
+            json!({"name": "vm-2", "status": 0}),
+            json!({"id": "no-name", "status": 1}),
+        ]);
+
+        assert_eq!(samples.len(), 4);
+        assert_eq!(samples[0].name, "vm_running");
+        assert_eq!(samples[0].value, 1.0);
+        assert_eq!(samples[0].labels["host"], "h1");
+        assert_eq!(samples[2].value, 8192.0);
+        assert_eq!(samples[3].labels["domain"], "vm-2");
+        assert_eq!(samples[3].labels["host"], "-");
+        assert_eq!(samples[3].value, 0.0);
+    }
+}
@@ -35,11 +35,6 @@
 //! ```
 
 use atp_executor::*;
-use atp_transport::{TransportManager, HostInfo};
-use atp_protocol::ProtocolRegistry;
-use std::sync::Arc;
-use std::time::Duration;
-use std::collections::HashMap;
 
 /// 初始化测试环境 (使用 TestConfig)
 async fn setup_test_runner() -> (ScenarioRunner, TestConfig) {
@@ -60,27 +55,9 @@ async fn setup_test_runner() -> (ScenarioRunner, TestConfig) {
     tracing::debug!("VM name: {}", config.vm.name);
     tracing::debug!("Libvirt URI: {}", config.libvirt.uri);
 
-    // 4. 创建传输管理器
-    let transport_manager = Arc::new(TransportManager::default());
-
-    // 5. 添加测试主机 (从配置读取)
-    let host_info = HostInfo {
-        id: "test-host".to_string(),
-        host: "localhost".to_string(),
-        uri: config.libvirt.uri.clone(),
-        tags: vec!["test".to_string()],
-        metadata: HashMap::new(),
-    };
-
-    transport_manager.add_host(host_info).await
-        .expect("Failed to add test host");
-
-    // 6. 创建协议注册表
-    let protocol_registry = Arc::new(ProtocolRegistry::new());
-
-    // 7. 创建场景执行器 (使用配置的超时)
-    let runner = ScenarioRunner::new(transport_manager, protocol_registry)
-        .with_timeout(Duration::from_secs(config.test.timeout));
+    // 4. 创建场景执行器 (按配置注册主机, 使用配置的超时)
+    let runner = ScenarioRunner::from_test_config(&config).await
+        .expect("Failed to create runner from test config");
 
     (runner, config)
 }
@@ -95,6 +72,7 @@ async fn test_basic_scenario_wait() {
     let mut runner = setup_test_runner().await;
 
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "basic-wait-test".to_string(),
         description: Some("基础等待测试".to_string()),
         target_host: Some(get_test_host_uri()),
@@ -105,15 +83,23 @@ async fn test_basic_scenario_wait() {
                 action: Action::Wait { duration: 1 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("等待2秒".to_string()),
                 action: Action::Wait { duration: 2 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
         ],
         tags: vec!["e2e".to_string(), "basic".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let report = runner.run(&scenario).await
@@ -136,6 +122,7 @@ async fn test_qmp_keyboard_input() {
     let vm_name = get_test_vm_name();
 
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "qmp-keyboard-test".to_string(),
         description: Some("QMP 键盘输入测试".to_string()),
         target_host: Some(get_test_host_uri()),
@@ -143,18 +130,26 @@ async fn test_qmp_keyboard_input() {
         steps: vec![
             ScenarioStep {
                 name: Some("发送 Enter 键".to_string()),
-                action: Action::SendKey { key: "ret".to_string() },
+                action: Action::SendKey { key: "ret".to_string(), hold_ms: None },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("发送文本".to_string()),
                 action: Action::SendText { text: "hello".to_string() },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
         ],
         tags: vec!["e2e".to_string(), "qmp".to_string(), "keyboard".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let report = runner.run(&scenario).await
@@ -175,6 +170,7 @@ async fn test_qga_command_execution() {
     let vm_name = get_test_vm_name();
 
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "qga-command-test".to_string(),
         description: Some("QGA 命令执行测试".to_string()),
         target_host: Some(get_test_host_uri()),
@@ -187,6 +183,7 @@ async fn test_qga_command_execution() {
                 },
                 verify: true,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("执行 uname 命令".to_string()),
@@ -195,6 +192,7 @@ async fn test_qga_command_execution() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("执行 date 命令".to_string()),
@@ -203,9 +201,16 @@ async fn test_qga_command_execution() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
         ],
         tags: vec!["e2e".to_string(), "qga".to_string(), "command".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let report = runner.run(&scenario).await
@@ -233,6 +238,7 @@ async fn test_spice_mouse_operations() {
     let vm_name = get_test_vm_name();
 
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "spice-mouse-test".to_string(),
         description: Some("SPICE 鼠标操作测试".to_string()),
         target_host: Some(get_test_host_uri()),
@@ -247,12 +253,14 @@ async fn test_spice_mouse_operations() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("等待 1 秒".to_string()),
                 action: Action::Wait { duration: 1 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("右键点击 (200, 200)".to_string()),
@@ -263,9 +271,16 @@ async fn test_spice_mouse_operations() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
         ],
         tags: vec!["e2e".to_string(), "spice".to_string(), "mouse".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let report = runner.run(&scenario).await
@@ -289,6 +304,7 @@ async fn test_mixed_protocol_scenario() {
     let vm_name = get_test_vm_name();
 
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "mixed-protocol-test".to_string(),
         description: Some("混合协议操作测试 (QMP + QGA + SPICE)".to_string()),
         target_host: Some(get_test_host_uri()),
@@ -301,24 +317,28 @@ async fn test_mixed_protocol_scenario() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("2. 等待 1 秒".to_string()),
                 action: Action::Wait { duration: 1 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("3. QMP: 发送键盘输入".to_string()),
-                action: Action::SendKey { key: "ret".to_string() },
+                action: Action::SendKey { key: "ret".to_string(), hold_ms: None },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("4. 等待 1 秒".to_string()),
                 action: Action::Wait { duration: 1 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("5. SPICE: 鼠标点击".to_string()),
@@ -329,6 +349,7 @@ async fn test_mixed_protocol_scenario() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("6. QGA: 验证操作".to_string()),
@@ -337,9 +358,16 @@ async fn test_mixed_protocol_scenario() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
         ],
         tags: vec!["e2e".to_string(), "mixed".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let report = runner.run(&scenario).await
@@ -473,6 +501,7 @@ async fn test_command_failure_handling() {
     let vm_name = get_test_vm_name();
 
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "error-handling-test".to_string(),
         description: Some("错误处理测试".to_string()),
         target_host: Some(get_test_host_uri()),
@@ -485,6 +514,7 @@ async fn test_command_failure_handling() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("失败的命令".to_string()),
@@ -493,15 +523,23 @@ async fn test_command_failure_handling() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("这一步不应该执行".to_string()),
                 action: Action::Wait { duration: 1 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
         ],
         tags: vec!["e2e".to_string(), "error".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let report = runner.run(&scenario).await
@@ -531,6 +569,7 @@ async fn test_timeout_handling() {
     let vm_name = get_test_vm_name();
 
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "timeout-test".to_string(),
         description: Some("超时处理测试".to_string()),
         target_host: Some(get_test_host_uri()),
@@ -543,9 +582,16 @@ async fn test_timeout_handling() {
                 },
                 verify: false,
                 timeout: Some(2), // 但只给2秒超时
+                tags: vec![],
             },
         ],
         tags: vec!["e2e".to_string(), "timeout".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let report = runner.run(&scenario).await;
@@ -573,16 +619,24 @@ async fn test_scenario_execution_performance() {
             },
             verify: false,
             timeout: Some(5),
+            tags: vec![],
         })
         .collect();
 
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "performance-test".to_string(),
         description: Some("性能测试 - 10个快速命令".to_string()),
         target_host: Some(get_test_host_uri()),
         target_domain: Some(vm_name.clone()),
         steps,
         tags: vec!["e2e".to_string(), "performance".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let start = std::time::Instant::now();
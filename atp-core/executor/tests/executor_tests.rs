@@ -1,16 +1,25 @@
 //! Executor 模块测试
 
 use atp_executor::*;
+use atp_executor::observer::{ScenarioFinished, ScenarioStarted, StepFinished, StepStarted};
+use atp_vdiplatform::models::{CreateDeskPoolRequest, DeskPoolType};
 
 #[test]
 fn test_scenario_creation() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "test-scenario".to_string(),
         description: Some("A test scenario".to_string()),
         target_host: None,
         target_domain: None,
         steps: vec![],
         tags: vec!["test".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     assert_eq!(scenario.name, "test-scenario");
@@ -23,9 +32,10 @@ fn test_scenario_creation() {
 fn test_scenario_step_creation() {
     let step = ScenarioStep {
         name: Some("send key".to_string()),
-        action: Action::SendKey { key: "enter".to_string() },
+        action: Action::SendKey { key: "enter".to_string(), hold_ms: None },
         verify: true,
         timeout: Some(30),
+        tags: vec![],
     };
 
     assert!(step.name.is_some());
@@ -36,7 +46,7 @@ fn test_scenario_step_creation() {
 
 #[test]
 fn test_action_variants() {
-    let action1 = Action::SendKey { key: "a".to_string() };
+    let action1 = Action::SendKey { key: "a".to_string(), hold_ms: None };
     assert!(matches!(action1, Action::SendKey { .. }));
 
     let action2 = Action::SendText { text: "hello".to_string() };
@@ -58,6 +68,7 @@ fn test_action_variants() {
 #[test]
 fn test_scenario_json_serialization() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "json-test".to_string(),
         description: None,
         target_host: None,
@@ -68,9 +79,16 @@ fn test_scenario_json_serialization() {
                 action: Action::Wait { duration: 1 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             }
         ],
         tags: vec![],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let json = scenario.to_json().unwrap();
@@ -87,6 +105,7 @@ fn test_scenario_json_serialization() {
 #[test]
 fn test_scenario_yaml_serialization() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "yaml-test".to_string(),
         description: Some("YAML test scenario".to_string()),
         target_host: None,
@@ -97,9 +116,16 @@ fn test_scenario_yaml_serialization() {
                 action: Action::SendText { text: "hello world".to_string() },
                 verify: true,
                 timeout: Some(10),
+                tags: vec![],
             }
         ],
         tags: vec!["yaml".to_string(), "test".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let yaml = scenario.to_yaml().unwrap();
@@ -116,6 +142,7 @@ fn test_scenario_yaml_serialization() {
 #[test]
 fn test_scenario_complex_actions() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "complex-scenario".to_string(),
         description: None,
         target_host: None,
@@ -123,36 +150,47 @@ fn test_scenario_complex_actions() {
         steps: vec![
             ScenarioStep {
                 name: Some("send key".to_string()),
-                action: Action::SendKey { key: "ctrl-c".to_string() },
+                action: Action::SendKey { key: "ctrl-c".to_string(), hold_ms: None },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("send text".to_string()),
                 action: Action::SendText { text: "test input".to_string() },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("mouse click".to_string()),
                 action: Action::MouseClick { x: 500, y: 300, button: "right".to_string() },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("execute command".to_string()),
                 action: Action::ExecCommand { command: "echo test".to_string() },
                 verify: true,
                 timeout: Some(5),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("wait".to_string()),
                 action: Action::Wait { duration: 3 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
         ],
         tags: vec!["complex".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     assert_eq!(scenario.steps.len(), 5);
@@ -201,6 +239,7 @@ fn test_executor_error_display() {
 #[test]
 fn test_scenario_clone() {
     let original = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "clone-test".to_string(),
         description: Some("test".to_string()),
         target_host: None,
@@ -211,9 +250,16 @@ fn test_scenario_clone() {
                 action: Action::Wait { duration: 1 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             }
         ],
         tags: vec!["tag1".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let cloned = original.clone();
@@ -242,6 +288,149 @@ fn test_custom_action_data() {
     }
 }
 
+#[test]
+fn test_custom_protocol_action_from_yaml() {
+    let yaml = r#"
+name: "custom-protocol"
+target_domain: "test-vm"
+steps:
+  - action:
+      type: custom
+      data:
+        protocol: "vendor-agent"
+        payload:
+          command: "ping"
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+
+    if let Action::Custom { data } = &scenario.steps[0].action {
+        assert_eq!(data["protocol"], "vendor-agent");
+        assert_eq!(data["payload"]["command"], "ping");
+    } else {
+        panic!("Expected Custom action");
+    }
+}
+
+#[test]
+fn test_send_key_hold_ms_from_yaml() {
+    let yaml = r#"
+name: "long-press"
+target_domain: "test-vm"
+steps:
+  - action:
+      type: send_key
+      key: backspace
+      hold_ms: 2000
+  - action:
+      type: send_key
+      key: ret
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+
+    if let Action::SendKey { key, hold_ms } = &scenario.steps[0].action {
+        assert_eq!(key, "backspace");
+        assert_eq!(*hold_ms, Some(2000));
+    } else {
+        panic!("Expected SendKey action");
+    }
+    assert!(matches!(scenario.steps[1].action, Action::SendKey { hold_ms: None, .. }));
+
+    // 未指定 hold_ms 时不输出该字段
+    let yaml = scenario.to_yaml().unwrap();
+    assert_eq!(yaml.matches("hold_ms").count(), 1);
+}
+
+#[test]
+fn test_query_windows_event_log_from_yaml() {
+    let yaml = r#"
+name: "event-log"
+target_domain: "win-vm"
+steps:
+  - action:
+      type: query_windows_event_log
+      log: System
+      level: error
+      since_minutes: 30
+      expect_max_count: 0
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+
+    if let Action::QueryWindowsEventLog { log, level, since_minutes, expect_max_count } = &scenario.steps[0].action {
+        assert_eq!(*log, EventLogName::System);
+        assert_eq!(*level, EventLevel::Error);
+        assert_eq!(*since_minutes, 30);
+        assert_eq!(*expect_max_count, 0);
+    } else {
+        panic!("Expected QueryWindowsEventLog action");
+    }
+}
+
+#[test]
+fn test_guest_uniquify_from_yaml() {
+    let yaml = r#"
+name: "uniquify"
+target_domain: "clone-01"
+steps:
+  - timeout: 900
+    action:
+      type: guest_uniquify
+      hostname_template: "desk-{index}"
+      run_sysprep: true
+  - action:
+      type: guest_uniquify
+      hostname_template: "{vm_name}"
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+
+    if let Action::GuestUniquify { hostname_template, run_sysprep } = &scenario.steps[0].action {
+        assert_eq!(hostname_template, "desk-{index}");
+        assert!(*run_sysprep);
+    } else {
+        panic!("Expected GuestUniquify action");
+    }
+
+    assert!(matches!(
+        &scenario.steps[1].action,
+        Action::GuestUniquify { run_sysprep: false, .. }
+    ));
+}
+
+#[test]
+fn test_verify_file_in_guest_from_yaml() {
+    let yaml = r#"
+name: "verify-file"
+target_domain: "win-vm"
+steps:
+  - action:
+      type: verify_file_in_guest
+      path: 'C:\Program Files\Agent\VERSION.txt'
+      expect_contains: "2.3"
+      max_bytes: 4096
+  - action:
+      type: verify_file_in_guest
+      path: /tmp/installer.lock
+      expect_exists: false
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+
+    if let Action::VerifyFileInGuest { path, expect_exists, expect_sha256, expect_contains, max_bytes } =
+        &scenario.steps[0].action
+    {
+        assert_eq!(path, r"C:\Program Files\Agent\VERSION.txt");
+        assert!(*expect_exists);
+        assert!(expect_sha256.is_none());
+        assert_eq!(expect_contains.as_deref(), Some("2.3"));
+        assert_eq!(*max_bytes, 4096);
+    } else {
+        panic!("Expected VerifyFileInGuest action");
+    }
+
+    assert!(matches!(
+        &scenario.steps[1].action,
+        Action::VerifyFileInGuest { expect_exists: false, max_bytes: atp_executor::guest_file::DEFAULT_MAX_BYTES, .. }
+    ));
+}
+
 // ========================================
 // VDI 操作测试
 // ========================================
@@ -252,17 +441,65 @@ fn test_vdi_create_desk_pool_action() {
         name: "test-pool".to_string(),
         template_id: "template-001".to_string(),
         count: 5,
+        advanced: None,
     };
 
     assert!(matches!(action, Action::VdiCreateDeskPool { .. }));
 
-    if let Action::VdiCreateDeskPool { name, template_id, count } = action {
+    if let Action::VdiCreateDeskPool { name, template_id, count, .. } = action {
         assert_eq!(name, "test-pool");
         assert_eq!(template_id, "template-001");
         assert_eq!(count, 5);
     }
 }
 
+#[test]
+fn test_vdi_create_desk_pool_advanced_from_yaml() {
+    let yaml = r#"
+name: "advanced-pool"
+steps:
+  - name: "创建桌面池"
+    action:
+      type: vdi_create_desk_pool
+      name: "pool-1"
+      template_id: "model-1"
+      count: 2
+      advanced:
+        pool_type: shared
+        domain_prefix: "test-"
+        storage_pool_id: "storage-1"
+        force_freeze: true
+        networks:
+          - vlan_id: "100"
+"#;
+
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+    let Action::VdiCreateDeskPool { advanced: Some(advanced), .. } = &scenario.steps[0].action else {
+        panic!("Expected VdiCreateDeskPool action with advanced options");
+    };
+    assert_eq!(advanced.pool_type, Some(DeskPoolType::Shared));
+    assert_eq!(advanced.domain_prefix.as_deref(), Some("test-"));
+    assert_eq!(advanced.force_freeze, Some(true));
+
+    // 透传到平台请求时使用平台字段名
+    let request = CreateDeskPoolRequest::basic("pool-1", "model-1", 2).with_advanced(advanced.clone());
+    let value = serde_json::to_value(&request).unwrap();
+    assert_eq!(value["type"], 1);
+    assert_eq!(value["domainPrefix"], "test-");
+    assert_eq!(value["poolId"], "storage-1");
+    assert_eq!(value["forceFreeze"], 1);
+    assert_eq!(value["networkList"][0]["vlanId"], "100");
+
+    // 未设置 advanced 的步骤序列化时不输出该字段
+    let action = Action::VdiCreateDeskPool {
+        name: "pool-1".to_string(),
+        template_id: "model-1".to_string(),
+        count: 2,
+        advanced: None,
+    };
+    assert!(!serde_yaml::to_string(&action).unwrap().contains("advanced"));
+}
+
 #[test]
 fn test_vdi_enable_desk_pool_action() {
     let action = Action::VdiEnableDeskPool {
@@ -433,6 +670,7 @@ fn test_verify_command_success_action() {
 #[test]
 fn test_vdi_scenario_json_serialization() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "vdi-workflow".to_string(),
         description: Some("VDI platform workflow test".to_string()),
         target_host: None,
@@ -444,9 +682,11 @@ fn test_vdi_scenario_json_serialization() {
                     name: "dev-pool".to_string(),
                     template_id: "ubuntu-20.04".to_string(),
                     count: 3,
+                    advanced: None,
                 },
                 verify: false,
                 timeout: Some(120),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("启用桌面池".to_string()),
@@ -455,6 +695,7 @@ fn test_vdi_scenario_json_serialization() {
                 },
                 verify: true,
                 timeout: Some(30),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("启动虚拟机".to_string()),
@@ -463,9 +704,16 @@ fn test_vdi_scenario_json_serialization() {
                 },
                 verify: false,
                 timeout: Some(60),
+                tags: vec![],
             },
         ],
         tags: vec!["vdi".to_string(), "workflow".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let json = scenario.to_json().unwrap();
@@ -487,6 +735,7 @@ fn test_vdi_scenario_json_serialization() {
 #[test]
 fn test_vdi_scenario_yaml_serialization() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "vdi-lifecycle".to_string(),
         description: Some("Complete VDI lifecycle test".to_string()),
         target_host: Some("qemu:///system".to_string()),
@@ -500,6 +749,7 @@ fn test_vdi_scenario_yaml_serialization() {
                 },
                 verify: true,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("验证虚拟机状态".to_string()),
@@ -510,6 +760,7 @@ fn test_vdi_scenario_yaml_serialization() {
                 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("关闭虚拟机".to_string()),
@@ -518,9 +769,16 @@ fn test_vdi_scenario_yaml_serialization() {
                 },
                 verify: false,
                 timeout: Some(60),
+                tags: vec![],
             },
         ],
         tags: vec!["lifecycle".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let yaml = scenario.to_yaml().unwrap();
@@ -540,6 +798,7 @@ fn test_vdi_scenario_yaml_serialization() {
 #[test]
 fn test_mixed_protocol_and_vdi_scenario() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "mixed-workflow".to_string(),
         description: Some("Mixed protocol and VDI operations".to_string()),
         target_host: None,
@@ -552,12 +811,14 @@ fn test_mixed_protocol_and_vdi_scenario() {
                 },
                 verify: false,
                 timeout: Some(60),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("等待启动".to_string()),
                 action: Action::Wait { duration: 10 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("验证状态".to_string()),
@@ -568,6 +829,7 @@ fn test_mixed_protocol_and_vdi_scenario() {
                 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("执行命令".to_string()),
@@ -576,6 +838,7 @@ fn test_mixed_protocol_and_vdi_scenario() {
                 },
                 verify: true,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("验证命令成功".to_string()),
@@ -584,9 +847,16 @@ fn test_mixed_protocol_and_vdi_scenario() {
                 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
         ],
         tags: vec!["mixed".to_string(), "integration".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     assert_eq!(scenario.steps.len(), 5);
@@ -605,6 +875,7 @@ fn test_mixed_protocol_and_vdi_scenario() {
 #[test]
 fn test_vdi_get_desk_pool_domains_in_scenario() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "pool-inspection".to_string(),
         description: Some("Inspect desk pool domains".to_string()),
         target_host: None,
@@ -617,6 +888,7 @@ fn test_vdi_get_desk_pool_domains_in_scenario() {
                 },
                 verify: false,
                 timeout: Some(30),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("验证所有虚拟机运行".to_string()),
@@ -626,9 +898,16 @@ fn test_vdi_get_desk_pool_domains_in_scenario() {
                 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
         ],
         tags: vec!["inspection".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     let json = scenario.to_json().unwrap();
@@ -643,6 +922,7 @@ fn test_vdi_get_desk_pool_domains_in_scenario() {
 #[test]
 fn test_vdi_complete_lifecycle_scenario() {
     let scenario = Scenario {
+        schema_version: SCENARIO_SCHEMA_VERSION,
         name: "complete-vdi-lifecycle".to_string(),
         description: Some("Complete VDI lifecycle from creation to deletion".to_string()),
         target_host: None,
@@ -654,9 +934,11 @@ fn test_vdi_complete_lifecycle_scenario() {
                     name: "test-pool".to_string(),
                     template_id: "centos7".to_string(),
                     count: 2,
+                    advanced: None,
                 },
                 verify: false,
                 timeout: Some(180),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("2. 启用桌面池".to_string()),
@@ -665,6 +947,7 @@ fn test_vdi_complete_lifecycle_scenario() {
                 },
                 verify: true,
                 timeout: Some(30),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("3. 获取虚拟机列表".to_string()),
@@ -673,6 +956,7 @@ fn test_vdi_complete_lifecycle_scenario() {
                 },
                 verify: false,
                 timeout: Some(10),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("4. 验证所有虚拟机运行".to_string()),
@@ -682,6 +966,7 @@ fn test_vdi_complete_lifecycle_scenario() {
                 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("5. 重启虚拟机".to_string()),
@@ -690,12 +975,14 @@ fn test_vdi_complete_lifecycle_scenario() {
                 },
                 verify: false,
                 timeout: Some(60),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("6. 等待重启完成".to_string()),
                 action: Action::Wait { duration: 30 },
                 verify: false,
                 timeout: None,
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("7. 禁用桌面池".to_string()),
@@ -704,6 +991,7 @@ fn test_vdi_complete_lifecycle_scenario() {
                 },
                 verify: false,
                 timeout: Some(30),
+                tags: vec![],
             },
             ScenarioStep {
                 name: Some("8. 删除桌面池".to_string()),
@@ -712,9 +1000,16 @@ fn test_vdi_complete_lifecycle_scenario() {
                 },
                 verify: false,
                 timeout: Some(60),
+                tags: vec![],
             },
         ],
         tags: vec!["lifecycle".to_string(), "integration".to_string(), "vdi".to_string()],
+        setup: vec![],
+        teardown: vec![],
+        max_duration_secs: None,
+        environment_guard: None,
+        keyboard_layout: None,
+        guest_os: None,
     };
 
     // 验证场景结构
@@ -748,11 +1043,12 @@ fn test_action_clone_for_vdi_operations() {
         name: "pool-1".to_string(),
         template_id: "template-1".to_string(),
         count: 5,
+        advanced: None,
     };
 
     let cloned = original.clone();
 
-    if let Action::VdiCreateDeskPool { name, template_id, count } = cloned {
+    if let Action::VdiCreateDeskPool { name, template_id, count, .. } = cloned {
         assert_eq!(name, "pool-1");
         assert_eq!(template_id, "template-1");
         assert_eq!(count, 5);
@@ -840,6 +1136,26 @@ fn test_execution_report_with_description_and_tags() {
     assert_eq!(report.tags[1], "tag2");
 }
 
+#[test]
+fn test_execution_report_resources_roundtrip() {
+    let mut tracker = ResourceTracker::new();
+    tracker.register(ResourceKind::DeskPool, "pool-1", Some("fixture-pool"), 0);
+    tracker.set_status(0, CleanupStatus::AutoCleaned, None);
+
+    let mut report = ExecutionReport::new("fixture-test");
+    let mut setup_step = StepReport::success(0, "create pool");
+    setup_step.phase = StepPhase::Setup;
+    report.add_step(setup_step);
+    report.resources = tracker.take();
+
+    let json = report.to_json().unwrap();
+    let parsed: ExecutionReport = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.resources.len(), 1);
+    assert_eq!(parsed.resources[0].kind, ResourceKind::DeskPool);
+    assert_eq!(parsed.resources[0].status, CleanupStatus::AutoCleaned);
+    assert_eq!(parsed.steps[0].phase, StepPhase::Setup);
+}
+
 #[test]
 fn test_execution_report_to_json() {
     let mut report = ExecutionReport::new("json-test");
@@ -954,6 +1270,304 @@ fn test_execution_report_mixed_results() {
     assert_eq!(report.steps.len(), 3);
 }
 
+#[test]
+fn test_execution_report_skipped_steps() {
+    let mut report = ExecutionReport::new("skipped");
+    report.add_step(StepReport::success(0, "step1"));
+    report.add_step(StepReport::skipped(1, "step2", "标签 slow 被排除"));
+
+    assert!(report.passed);
+    assert_eq!(report.passed_count, 1);
+    assert_eq!(report.skipped_count, 1);
+    assert_eq!(report.steps[1].status, StepStatus::Skipped);
+    assert_eq!(report.steps[1].output.as_deref(), Some("跳过: 标签 slow 被排除"));
+}
+
+#[tokio::test]
+async fn test_run_filtered_skips_non_matching_steps() {
+    let yaml = r#"
+name: "filtered"
+setup:
+  - name: "fixture"
+    tags: ["slow"]
+    action:
+      type: wait
+      duration: 0
+steps:
+  - name: "smoke"
+    tags: ["smoke"]
+    action:
+      type: wait
+      duration: 0
+  - name: "stress"
+    tags: ["smoke", "slow"]
+    action:
+      type: wait
+      duration: 0
+  - name: "untagged"
+    action:
+      type: wait
+      duration: 0
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+    assert_eq!(scenario.steps[0].tags, vec!["smoke".to_string()]);
+
+    let mut runner = wait_runner();
+    let filter = StepFilter::new()
+        .with_include_tags(vec!["smoke".to_string()])
+        .with_exclude_tags(vec!["slow".to_string()]);
+
+    let report = runner.run_filtered(&scenario, &filter).await.unwrap();
+
+    // 前置步骤不受过滤影响
+    assert_eq!(report.steps[0].status, StepStatus::Success);
+    assert_eq!(report.steps[1].status, StepStatus::Success);
+    assert_eq!(report.steps[2].status, StepStatus::Skipped);
+    assert_eq!(report.steps[3].status, StepStatus::Skipped);
+    assert_eq!(report.passed_count, 2);
+    assert_eq!(report.skipped_count, 2);
+    assert!(report.passed);
+}
+
+fn wait_runner() -> ScenarioRunner {
+    use std::sync::Arc;
+    use atp_protocol::ProtocolRegistry;
+    use atp_transport::{TransportConfig, TransportManager};
+
+    ScenarioRunner::new(
+        Arc::new(TransportManager::new(TransportConfig::default())),
+        Arc::new(ProtocolRegistry::new()),
+    )
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_scenario_max_duration_cancels_remaining_steps() {
+    let yaml = r#"
+name: "deadline"
+max_duration_secs: 1
+steps:
+  - action:
+      type: wait
+      duration: 0
+  - name: "hang"
+    timeout: 10
+    action:
+      type: wait
+      duration: 5
+  - action:
+      type: wait
+      duration: 0
+teardown:
+  - name: "cleanup"
+    action:
+      type: wait
+      duration: 0
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+    assert_eq!(scenario.max_duration_secs, Some(1));
+
+    let mut runner = wait_runner();
+    let report = runner.run(&scenario).await.unwrap();
+
+    assert!(report.timed_out);
+    assert!(!report.cancelled);
+    assert!(!report.passed);
+    assert_eq!(report.steps.len(), 4);
+    assert_eq!(report.steps[0].status, StepStatus::Success);
+    assert_eq!(report.steps[1].status, StepStatus::Failed);
+    assert_eq!(report.steps[2].status, StepStatus::Skipped);
+    assert_eq!(report.steps[2].output.as_deref(), Some("跳过: 场景超时"));
+    // 步骤开始偏移按执行顺序递增
+    assert!(report
+        .steps
+        .windows(2)
+        .all(|w| w[0].started_at_offset_ms <= w[1].started_at_offset_ms));
+    // 清理步骤仍然执行
+    assert_eq!(report.steps[3].phase, StepPhase::Teardown);
+    assert_eq!(report.steps[3].status, StepStatus::Success);
+}
+
+/// 共享内存缓冲区 (用于读取观察者输出)
+#[derive(Clone, Default)]
+struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 总是出错或 panic 的观察者
+struct BrokenObserver;
+
+impl ExecutionObserver for BrokenObserver {
+    fn on_scenario_start(&self, _: &ScenarioStarted) -> atp_executor::Result<()> {
+        panic!("observer panic");
+    }
+
+    fn on_step_start(&self, _: &StepStarted) -> atp_executor::Result<()> {
+        Err(ExecutorError::ConfigError("observer error".to_string()))
+    }
+
+    fn on_step_end(&self, _: &StepFinished) -> atp_executor::Result<()> {
+        panic!("observer panic");
+    }
+
+    fn on_scenario_end(&self, _: &ScenarioFinished) -> atp_executor::Result<()> {
+        Err(ExecutorError::ConfigError("observer error".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn test_jsonl_observer_emits_progress_events() {
+    let yaml = r#"
+name: "progress"
+tags: ["smoke"]
+steps:
+  - name: "first"
+    action:
+      type: wait
+      duration: 0
+  - name: "second"
+    tags: ["slow"]
+    action:
+      type: wait
+      duration: 0
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+    let buffer = SharedBuffer::default();
+
+    let mut runner = wait_runner()
+        .with_observer(std::sync::Arc::new(BrokenObserver))
+        .with_observer(std::sync::Arc::new(JsonLinesObserver::new(buffer.clone())));
+    let filter = StepFilter::new().with_exclude_tags(vec!["slow".to_string()]);
+    let report = runner.run_filtered(&scenario, &filter).await.unwrap();
+
+    // 出错的观察者不影响执行, 也不影响其他观察者
+    assert!(report.passed);
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let events: Vec<serde_json::Value> = output
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+
+    let kinds: Vec<&str> = events.iter().map(|e| e["event"].as_str().unwrap()).collect();
+    assert_eq!(kinds, vec!["scenario_start", "step_start", "step_end", "step_end", "scenario_end"]);
+
+    assert_eq!(events[0]["scenario_name"], "progress");
+    assert_eq!(events[0]["total_steps"], 2);
+    assert_eq!(events[1]["action"], "wait");
+    assert_eq!(events[2]["step_index"], 0);
+    assert_eq!(events[2]["status"], "Success");
+    assert!(events[2]["duration_ms"].is_u64());
+    assert_eq!(events[3]["status"], "Skipped");
+    assert_eq!(events[3]["description"], "second");
+    assert_eq!(events[4]["passed"], true);
+    assert_eq!(events[4]["skipped_count"], 1);
+    assert!(events.iter().all(|e| e["timestamp"].is_string()));
+}
+
+#[tokio::test]
+async fn test_environment_guard_skipped_without_vdi_client() {
+    let yaml = r#"
+name: "guarded"
+environment_guard: fail
+steps:
+  - action:
+      type: wait
+      duration: 0
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+    assert_eq!(scenario.environment_guard, Some(EnvironmentGuardMode::Fail));
+
+    // 没有 VDI 客户端时跳过环境检查, 不影响场景结果
+    let mut runner = wait_runner();
+    let report = runner.run(&scenario).await.unwrap();
+    assert!(report.passed);
+    assert!(report.orphan_resources.is_empty());
+
+    let json = report.to_json().unwrap();
+    let restored: ExecutionReport = serde_json::from_str(&json).unwrap();
+    assert!(restored.orphan_resources.is_empty());
+}
+
+#[tokio::test]
+async fn test_cancelled_scenario_skips_steps_but_runs_teardown() {
+    let yaml = r#"
+name: "cancelled"
+steps:
+  - action:
+      type: wait
+      duration: 0
+teardown:
+  - action:
+      type: wait
+      duration: 0
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+
+    let mut runner = wait_runner();
+    runner.cancellation_token().cancel();
+
+    let report = runner.run(&scenario).await.unwrap();
+
+    assert!(report.cancelled);
+    assert!(!report.timed_out);
+    assert_eq!(report.steps[0].status, StepStatus::Skipped);
+    assert_eq!(report.steps[0].output.as_deref(), Some("跳过: 用户取消"));
+    assert_eq!(report.steps[1].status, StepStatus::Success);
+
+    // 取消只影响那一次运行
+    let report = runner.run(&scenario).await.unwrap();
+    assert!(!report.cancelled);
+    assert!(report.passed);
+    assert_eq!(report.steps[0].status, StepStatus::Success);
+}
+
+#[tokio::test]
+async fn test_fan_out_isolates_targets() {
+    use std::sync::Arc;
+    use atp_protocol::ProtocolRegistry;
+    use atp_transport::{TransportConfig, TransportManager};
+
+    let root = std::env::temp_dir().join(format!("atp-fanout-run-{}", std::process::id()));
+    let yaml = r#"
+name: "fan-out"
+steps:
+  - action:
+      type: wait
+      duration: 0
+"#;
+    let scenario = Scenario::from_yaml_str(yaml).unwrap();
+    let names = vec!["vm-2".to_string(), "vm-1".to_string(), "vm-3".to_string()];
+    let transport_manager = Arc::new(TransportManager::new(TransportConfig::default()));
+
+    let fan_out = FanOutRunner::new(ArtifactLayout::new(&root)).with_concurrency(2);
+    let report = fan_out
+        .run(&scenario, &names, &StepFilter::default(), |_| {
+            ScenarioRunner::new(Arc::clone(&transport_manager), Arc::new(ProtocolRegistry::new()))
+        })
+        .await
+        .unwrap();
+
+    // 没有可用主机, 每个目标单独记录错误, 互不影响
+    assert!(!report.passed);
+    assert_eq!(report.targets.len(), 3);
+    for (index, target) in report.targets.iter().enumerate() {
+        assert_eq!(target.index, index);
+        assert_eq!(target.vm_name, names[index]);
+        assert!(target.error.as_deref().unwrap().contains("无可用主机"));
+        assert!(std::path::Path::new(&target.artifact_dir).is_dir());
+    }
+
+    let _ = std::fs::remove_dir_all(&root);
+}
+
 #[test]
 fn test_step_report_clone() {
     let original = StepReport::success(0, "original-step");
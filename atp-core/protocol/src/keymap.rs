@@ -0,0 +1,687 @@
+//! 键盘布局与文本到按键的映射
+//!
+//! QMP 的 qcode 与 SPICE 的扫描码都表示物理按键, Guest 按自身的键盘布局把按键解释为字符。
+//! 向德语布局的 Guest 发送 "y" 需要按下美式键盘上 Z 所在的键, "@" 需要 AltGr+Q。
+//! [`KeyMapper`] 按 Guest 的布局把文本转换为物理按键 (以 qcode 表示) 与 Shift/AltGr 组合。
+//!
+//! 死键 (如德语的 `^`、`´`) 需要两次按键且依赖 Guest 的输入法状态, 不在映射表中。
+//! ja-JP 只映射 JIS 键盘上的 ASCII 字符, 假名需要在 Guest 中通过输入法以罗马字输入。
+//!
+//! [`KeyCombo`] 解析 `ctrl+alt+del`、`shift+f5` 这类按键组合, QMP 与 SPICE 共用同一结果。
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::spice::qcode_to_scancode;
+
+/// Guest 的键盘布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum KeyboardLayout {
+    /// 美式键盘
+    #[default]
+    #[serde(rename = "en-US")]
+    EnUs,
+    /// 德语 QWERTZ
+    #[serde(rename = "de-DE")]
+    DeDe,
+    /// 法语 AZERTY
+    #[serde(rename = "fr-FR")]
+    FrFr,
+    /// 日语 JIS (罗马字输入)
+    #[serde(rename = "ja-JP")]
+    JaJp,
+}
+
+impl KeyboardLayout {
+    /// 所有支持的布局
+    pub const ALL: [KeyboardLayout; 4] = [Self::EnUs, Self::DeDe, Self::FrFr, Self::JaJp];
+
+    /// 布局名称 (如 `de-DE`)
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::EnUs => "en-US",
+            Self::DeDe => "de-DE",
+            Self::FrFr => "fr-FR",
+            Self::JaJp => "ja-JP",
+        }
+    }
+
+    fn symbols(&self) -> &'static [(char, &'static str, Level)] {
+        match self {
+            Self::EnUs => US_SYMBOLS,
+            Self::DeDe => DE_SYMBOLS,
+            Self::FrFr => FR_SYMBOLS,
+            Self::JaJp => JA_SYMBOLS,
+        }
+    }
+
+    /// 字母所在的物理按键
+    fn letter_key(&self, letter: char) -> Option<&'static str> {
+        let letter = match (self, letter) {
+            (Self::DeDe, 'y') => 'z',
+            (Self::DeDe, 'z') => 'y',
+            (Self::FrFr, 'a') => 'q',
+            (Self::FrFr, 'q') => 'a',
+            (Self::FrFr, 'z') => 'w',
+            (Self::FrFr, 'w') => 'z',
+            (Self::FrFr, 'm') => return Some("semicolon"),
+            (_, letter) => letter,
+        };
+        let index = (letter as u32).checked_sub('a' as u32)? as usize;
+        LETTER_KEYS.get(index).copied()
+    }
+}
+
+impl fmt::Display for KeyboardLayout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for KeyboardLayout {
+    type Err = String;
+
+    /// 解析布局名称, 不区分大小写, 也接受 `de_DE` 与 `de` 等写法
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.trim().replace('_', "-").to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|layout| {
+                let name = layout.name().to_ascii_lowercase();
+                normalized == name || name.split('-').next() == Some(normalized.as_str())
+            })
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(KeyboardLayout::name).collect();
+                format!("不支持的键盘布局 '{}', 可选: {}", s, names.join(", "))
+            })
+    }
+}
+
+/// 文本映射错误
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum KeyMappingError {
+    /// 字符无法在该布局下输入 (`position` 为字符在文本中的序号, 从 0 开始)
+    #[error("键盘布局 {layout} 无法输入第 {position} 个字符 {ch:?}")]
+    UnsupportedCharacter {
+        ch: char,
+        position: usize,
+        layout: KeyboardLayout,
+    },
+
+    /// 按键组合中有无法识别的按键名
+    #[error("未知的按键 '{token}' (按键组合: {combo})")]
+    UnknownKey { token: String, combo: String },
+
+    /// 按键组合不合法 (如修饰键重复、修饰键不在最后一个按键之前)
+    #[error("无效的按键组合 '{combo}': {reason}")]
+    InvalidCombo { combo: String, reason: String },
+}
+
+/// 一次按键: 一个物理按键加上需要同时按住的修饰键
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeyStroke {
+    /// 物理按键的 qcode
+    pub qcode: &'static str,
+
+    /// 需要按住 Ctrl
+    pub ctrl: bool,
+
+    /// 需要按住 Shift
+    pub shift: bool,
+
+    /// 需要按住 Alt
+    pub alt: bool,
+
+    /// 需要按住 AltGr (右 Alt)
+    pub altgr: bool,
+
+    /// 需要按住 Win/Super 键
+    pub meta: bool,
+}
+
+impl KeyStroke {
+    /// 按下顺序的 qcode 列表 (修饰键在前), 可直接作为 QMP send-key 的参数
+    pub fn qcodes(&self) -> Vec<&'static str> {
+        let modifiers = [
+            (self.ctrl, "ctrl"),
+            (self.shift, "shift"),
+            (self.alt, "alt"),
+            (self.altgr, "alt_r"),
+            (self.meta, "meta_l"),
+        ];
+        let mut qcodes: Vec<_> = modifiers
+            .into_iter()
+            .filter_map(|(held, qcode)| held.then_some(qcode))
+            .collect();
+        qcodes.push(self.qcode);
+        qcodes
+    }
+
+    /// 按下顺序的 SPICE 扫描码 (PC AT 扫描码集 1)
+    ///
+    /// 本模块产生的 qcode 都有对应的扫描码
+    pub fn scancodes(&self) -> Vec<u32> {
+        self.qcodes().into_iter().filter_map(qcode_to_scancode).collect()
+    }
+}
+
+/// 按键组合解析
+///
+/// 组合内的按键以 `+` 或 `-` 连接 (`ctrl+alt+del`、`ctrl-c`), 多个组合以空白分隔时依次发送
+/// (`ctrl+a ctrl+c`)。按键名不区分大小写, 接受常见别名 (`del`/`delete`、`esc`/`escape`、
+/// `win`/`meta`/`super`、`enter`/`return`), 最后一个按键之前只能是修饰键。
+pub struct KeyCombo;
+
+impl KeyCombo {
+    /// 解析按键组合, 返回依次发送的按键
+    pub fn parse(combo: &str) -> Result<Vec<KeyStroke>, KeyMappingError> {
+        let strokes: Vec<KeyStroke> = combo
+            .split_whitespace()
+            .map(Self::parse_one)
+            .collect::<Result<_, _>>()?;
+
+        if strokes.is_empty() {
+            return Err(KeyMappingError::InvalidCombo {
+                combo: combo.to_string(),
+                reason: "没有按键".to_string(),
+            });
+        }
+        Ok(strokes)
+    }
+
+    /// 解析单个组合 (不含空白)
+    fn parse_one(combo: &str) -> Result<KeyStroke, KeyMappingError> {
+        let invalid = |reason: String| KeyMappingError::InvalidCombo {
+            combo: combo.to_string(),
+            reason,
+        };
+
+        let tokens = split_combo(combo);
+        let (key, modifiers) = tokens.split_last().ok_or_else(|| invalid("没有按键".to_string()))?;
+        if key.is_empty() {
+            return Err(invalid("以连接符结尾".to_string()));
+        }
+
+        let mut stroke = KeyStroke {
+            qcode: canonical_key(key).ok_or_else(|| KeyMappingError::UnknownKey {
+                token: key.to_string(),
+                combo: combo.to_string(),
+            })?,
+            ..KeyStroke::default()
+        };
+
+        for token in modifiers {
+            let held = match token.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut stroke.ctrl,
+                "shift" => &mut stroke.shift,
+                "alt" => &mut stroke.alt,
+                "altgr" | "alt_r" => &mut stroke.altgr,
+                "win" | "meta" | "super" | "cmd" => &mut stroke.meta,
+                _ if canonical_key(token).is_some() => {
+                    return Err(invalid(format!("'{}' 不是修饰键, 只能作为最后一个按键", token)));
+                }
+                _ => {
+                    return Err(KeyMappingError::UnknownKey {
+                        token: token.to_string(),
+                        combo: combo.to_string(),
+                    });
+                }
+            };
+            if *held {
+                return Err(invalid(format!("修饰键 '{}' 重复", token)));
+            }
+            *held = true;
+        }
+        Ok(stroke)
+    }
+}
+
+/// 按 `+`/`-` 拆分组合; 出现在按键开头的连接符视为按键本身 (`ctrl+-`、`-`)
+fn split_combo(combo: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    for (i, ch) in combo.char_indices() {
+        if (ch == '+' || ch == '-') && i > start {
+            tokens.push(&combo[start..i]);
+            start = i + 1;
+        }
+    }
+    tokens.push(&combo[start..]);
+    tokens
+}
+
+/// 按键名 (含别名) 对应的 qcode
+fn canonical_key(name: &str) -> Option<&'static str> {
+    let lower = name.to_ascii_lowercase();
+    let mut chars = lower.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return match ch {
+            'a'..='z' => Some(LETTER_KEYS[ch as usize - 'a' as usize]),
+            '0'..='9' => Some(DIGIT_KEYS[ch as usize - '0' as usize]),
+            _ => US_SYMBOLS
+                .iter()
+                .find(|(symbol, _, level)| *symbol == ch && *level == Base)
+                .map(|(_, qcode, _)| *qcode),
+        };
+    }
+
+    let alias = match lower.as_str() {
+        "del" => "delete",
+        "escape" => "esc",
+        "enter" | "return" => "ret",
+        "space" => "spc",
+        "ins" => "insert",
+        "pageup" | "page_up" => "pgup",
+        "pagedown" | "page_down" => "pgdn",
+        "control" => "ctrl",
+        "altgr" => "alt_r",
+        "win" | "meta" | "super" | "cmd" => "meta_l",
+        "capslock" => "caps_lock",
+        "period" => "dot",
+        other => other,
+    };
+    NAMED_KEYS.iter().find(|&&key| key == alias).copied()
+}
+
+/// 单个按下或释放事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// 物理按键的 qcode
+    pub qcode: &'static str,
+
+    /// true 为按下, false 为释放
+    pub pressed: bool,
+}
+
+/// 文本到按键的映射
+pub struct KeyMapper;
+
+impl KeyMapper {
+    /// 字符在布局中对应的按键组合, 无法输入时返回 None
+    pub fn map_char(ch: char, layout: KeyboardLayout) -> Option<KeyStroke> {
+        let stroke = |qcode, level: Level| KeyStroke {
+            qcode,
+            shift: matches!(level, Level::Shift),
+            altgr: matches!(level, Level::AltGr),
+            ..KeyStroke::default()
+        };
+
+        match ch {
+            ' ' => return Some(stroke("spc", Level::Base)),
+            '\n' | '\r' => return Some(stroke("ret", Level::Base)),
+            '\t' => return Some(stroke("tab", Level::Base)),
+            'a'..='z' => return layout.letter_key(ch).map(|qcode| stroke(qcode, Level::Base)),
+            'A'..='Z' => return layout.letter_key(ch.to_ascii_lowercase()).map(|qcode| stroke(qcode, Level::Shift)),
+            '0'..='9' => {
+                let qcode = DIGIT_KEYS[ch as usize - '0' as usize];
+                // AZERTY 的数字需要 Shift
+                let level = if layout == KeyboardLayout::FrFr { Level::Shift } else { Level::Base };
+                return Some(stroke(qcode, level));
+            }
+            _ => {}
+        }
+
+        layout
+            .symbols()
+            .iter()
+            .find(|(symbol, _, _)| *symbol == ch)
+            .map(|(_, qcode, level)| stroke(qcode, *level))
+    }
+
+    /// 把文本转换为按键组合序列
+    ///
+    /// `\r\n` 视为一次回车。遇到无法输入的字符时返回该字符及其位置。
+    pub fn map_strokes(text: &str, layout: KeyboardLayout) -> Result<Vec<KeyStroke>, KeyMappingError> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut strokes = Vec::with_capacity(chars.len());
+
+        for (position, &ch) in chars.iter().enumerate() {
+            if ch == '\r' && chars.get(position + 1) == Some(&'\n') {
+                continue;
+            }
+            let stroke = Self::map_char(ch, layout)
+                .ok_or(KeyMappingError::UnsupportedCharacter { ch, position, layout })?;
+            strokes.push(stroke);
+        }
+        Ok(strokes)
+    }
+
+    /// 把文本转换为完整的按下/释放事件序列
+    ///
+    /// 每个字符依次按下修饰键与按键, 再以相反顺序释放。
+    pub fn map_text(text: &str, layout: KeyboardLayout) -> Result<Vec<KeyEvent>, KeyMappingError> {
+        let strokes = Self::map_strokes(text, layout)?;
+        let mut events = Vec::with_capacity(strokes.len() * 2);
+
+        for stroke in strokes {
+            let qcodes = stroke.qcodes();
+            events.extend(qcodes.iter().map(|&qcode| KeyEvent { qcode, pressed: true }));
+            events.extend(qcodes.iter().rev().map(|&qcode| KeyEvent { qcode, pressed: false }));
+        }
+        Ok(events)
+    }
+}
+
+/// 字符所在的键位层
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Level {
+    Base,
+    Shift,
+    AltGr,
+}
+
+use Level::{AltGr, Base, Shift};
+
+const LETTER_KEYS: [&str; 26] = [
+    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u",
+    "v", "w", "x", "y", "z",
+];
+
+const DIGIT_KEYS: [&str; 10] = ["0", "1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+/// 按键组合中可以使用的非字母数字 qcode
+const NAMED_KEYS: &[&str] = &[
+    "esc", "backspace", "tab", "ret", "spc", "ctrl", "ctrl_r", "shift", "shift_r", "alt", "alt_r",
+    "caps_lock", "num_lock", "scroll_lock", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10",
+    "f11", "f12", "insert", "delete", "home", "end", "pgup", "pgdn", "up", "down", "left", "right",
+    "meta_l", "meta_r", "menu", "minus", "equal", "comma", "dot", "slash", "bracket_left",
+    "bracket_right", "backslash", "semicolon", "apostrophe", "grave_accent", "less", "ro", "yen",
+];
+
+/// 美式键盘的符号
+const US_SYMBOLS: &[(char, &str, Level)] = &[
+    ('!', "1", Shift),
+    ('@', "2", Shift),
+    ('#', "3", Shift),
+    ('$', "4", Shift),
+    ('%', "5", Shift),
+    ('^', "6", Shift),
+    ('&', "7", Shift),
+    ('*', "8", Shift),
+    ('(', "9", Shift),
+    (')', "0", Shift),
+    ('-', "minus", Base),
+    ('_', "minus", Shift),
+    ('=', "equal", Base),
+    ('+', "equal", Shift),
+    ('[', "bracket_left", Base),
+    ('{', "bracket_left", Shift),
+    (']', "bracket_right", Base),
+    ('}', "bracket_right", Shift),
+    ('\\', "backslash", Base),
+    ('|', "backslash", Shift),
+    (';', "semicolon", Base),
+    (':', "semicolon", Shift),
+    ('\'', "apostrophe", Base),
+    ('"', "apostrophe", Shift),
+    ('`', "grave_accent", Base),
+    ('~', "grave_accent", Shift),
+    (',', "comma", Base),
+    ('<', "comma", Shift),
+    ('.', "dot", Base),
+    ('>', "dot", Shift),
+    ('/', "slash", Base),
+    ('?', "slash", Shift),
+];
+
+/// 德语键盘的符号 (qcode 为美式键盘上相同位置的键, ISO 键盘左 Shift 右侧的键为 `less`)
+const DE_SYMBOLS: &[(char, &str, Level)] = &[
+    ('!', "1", Shift),
+    ('"', "2", Shift),
+    ('²', "2", AltGr),
+    ('§', "3", Shift),
+    ('³', "3", AltGr),
+    ('$', "4", Shift),
+    ('%', "5", Shift),
+    ('&', "6", Shift),
+    ('/', "7", Shift),
+    ('{', "7", AltGr),
+    ('(', "8", Shift),
+    ('[', "8", AltGr),
+    (')', "9", Shift),
+    (']', "9", AltGr),
+    ('=', "0", Shift),
+    ('}', "0", AltGr),
+    ('ß', "minus", Base),
+    ('?', "minus", Shift),
+    ('\\', "minus", AltGr),
+    ('@', "q", AltGr),
+    ('€', "e", AltGr),
+    ('µ', "m", AltGr),
+    ('ü', "bracket_left", Base),
+    ('Ü', "bracket_left", Shift),
+    ('+', "bracket_right", Base),
+    ('*', "bracket_right", Shift),
+    ('~', "bracket_right", AltGr),
+    ('ö', "semicolon", Base),
+    ('Ö', "semicolon", Shift),
+    ('ä', "apostrophe", Base),
+    ('Ä', "apostrophe", Shift),
+    ('#', "backslash", Base),
+    ('\'', "backslash", Shift),
+    ('°', "grave_accent", Shift),
+    ('<', "less", Base),
+    ('>', "less", Shift),
+    ('|', "less", AltGr),
+    (',', "comma", Base),
+    (';', "comma", Shift),
+    ('.', "dot", Base),
+    (':', "dot", Shift),
+    ('-', "slash", Base),
+    ('_', "slash", Shift),
+];
+
+/// 法语键盘的符号 (数字行不按 Shift 时为符号)
+const FR_SYMBOLS: &[(char, &str, Level)] = &[
+    ('&', "1", Base),
+    ('é', "2", Base),
+    ('"', "3", Base),
+    ('#', "3", AltGr),
+    ('\'', "4", Base),
+    ('{', "4", AltGr),
+    ('(', "5", Base),
+    ('[', "5", AltGr),
+    ('-', "6", Base),
+    ('|', "6", AltGr),
+    ('è', "7", Base),
+    ('_', "8", Base),
+    ('\\', "8", AltGr),
+    ('ç', "9", Base),
+    ('^', "9", AltGr),
+    ('à', "0", Base),
+    ('@', "0", AltGr),
+    (')', "minus", Base),
+    ('°', "minus", Shift),
+    (']', "minus", AltGr),
+    ('=', "equal", Base),
+    ('+', "equal", Shift),
+    ('}', "equal", AltGr),
+    ('€', "e", AltGr),
+    ('$', "bracket_right", Base),
+    ('£', "bracket_right", Shift),
+    ('¤', "bracket_right", AltGr),
+    ('ù', "apostrophe", Base),
+    ('%', "apostrophe", Shift),
+    ('*', "backslash", Base),
+    ('µ', "backslash", Shift),
+    ('²', "grave_accent", Base),
+    ('<', "less", Base),
+    ('>', "less", Shift),
+    (',', "m", Base),
+    ('?', "m", Shift),
+    (';', "comma", Base),
+    ('.', "comma", Shift),
+    (':', "dot", Base),
+    ('/', "dot", Shift),
+    ('!', "slash", Base),
+    ('§', "slash", Shift),
+];
+
+/// 日语 JIS 键盘的 ASCII 符号 (`ro` 与 `yen` 为 JIS 键盘特有的键)
+const JA_SYMBOLS: &[(char, &str, Level)] = &[
+    ('!', "1", Shift),
+    ('"', "2", Shift),
+    ('#', "3", Shift),
+    ('$', "4", Shift),
+    ('%', "5", Shift),
+    ('&', "6", Shift),
+    ('\'', "7", Shift),
+    ('(', "8", Shift),
+    (')', "9", Shift),
+    ('-', "minus", Base),
+    ('=', "minus", Shift),
+    ('^', "equal", Base),
+    ('~', "equal", Shift),
+    ('¥', "yen", Base),
+    ('|', "yen", Shift),
+    ('@', "bracket_left", Base),
+    ('`', "bracket_left", Shift),
+    ('[', "bracket_right", Base),
+    ('{', "bracket_right", Shift),
+    (';', "semicolon", Base),
+    ('+', "semicolon", Shift),
+    (':', "apostrophe", Base),
+    ('*', "apostrophe", Shift),
+    (']', "backslash", Base),
+    ('}', "backslash", Shift),
+    (',', "comma", Base),
+    ('<', "comma", Shift),
+    ('.', "dot", Base),
+    ('>', "dot", Shift),
+    ('/', "slash", Base),
+    ('?', "slash", Shift),
+    ('\\', "ro", Base),
+    ('_', "ro", Shift),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn qcodes(text: &str, layout: KeyboardLayout) -> Vec<Vec<&'static str>> {
+        KeyMapper::map_strokes(text, layout)
+            .unwrap()
+            .iter()
+            .map(KeyStroke::qcodes)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_layout() {
+        assert_eq!("de-DE".parse::<KeyboardLayout>().unwrap(), KeyboardLayout::DeDe);
+        assert_eq!("fr_fr".parse::<KeyboardLayout>().unwrap(), KeyboardLayout::FrFr);
+        assert_eq!("ja".parse::<KeyboardLayout>().unwrap(), KeyboardLayout::JaJp);
+        assert!("ru-RU".parse::<KeyboardLayout>().unwrap_err().contains("可选: en-US, de-DE, fr-FR, ja-JP"));
+
+        let layout: KeyboardLayout = serde_json::from_str("\"de-DE\"").unwrap();
+        assert_eq!(layout, KeyboardLayout::DeDe);
+        assert_eq!(serde_json::to_string(&KeyboardLayout::JaJp).unwrap(), "\"ja-JP\"");
+    }
+
+    #[test]
+    fn test_us_layout() {
+        assert_eq!(qcodes("aZ1!", KeyboardLayout::EnUs), vec![vec!["a"], vec!["shift", "z"], vec!["1"], vec!["shift", "1"]]);
+        assert_eq!(qcodes("@ \n", KeyboardLayout::EnUs), vec![vec!["shift", "2"], vec!["spc"], vec!["ret"]]);
+        assert_eq!(qcodes("a\r\nb", KeyboardLayout::EnUs).len(), 3);
+    }
+
+    #[test]
+    fn test_de_layout() {
+        let layout = KeyboardLayout::DeDe;
+        assert_eq!(qcodes("yz", layout), vec![vec!["z"], vec!["y"]]);
+        assert_eq!(qcodes("ü", layout), vec![vec!["bracket_left"]]);
+        assert_eq!(qcodes("Ä", layout), vec![vec!["shift", "apostrophe"]]);
+        assert_eq!(qcodes("@", layout), vec![vec!["alt_r", "q"]]);
+        assert_eq!(qcodes("|", layout), vec![vec!["alt_r", "less"]]);
+        assert_eq!(qcodes("-", layout), vec![vec!["slash"]]);
+
+        // 死键不在映射表中
+        assert!(KeyMapper::map_char('^', layout).is_none());
+    }
+
+    #[test]
+    fn test_fr_layout() {
+        let layout = KeyboardLayout::FrFr;
+        assert_eq!(qcodes("aqzwm", layout), vec![vec!["q"], vec!["a"], vec!["w"], vec!["z"], vec!["semicolon"]]);
+        assert_eq!(qcodes("1é", layout), vec![vec!["shift", "1"], vec!["2"]]);
+        assert_eq!(qcodes("@", layout), vec![vec!["alt_r", "0"]]);
+        assert_eq!(qcodes(",!", layout), vec![vec!["m"], vec!["slash"]]);
+    }
+
+    #[test]
+    fn test_ja_layout() {
+        let layout = KeyboardLayout::JaJp;
+        assert_eq!(qcodes("konnichiha", layout).len(), 10);
+        assert_eq!(qcodes("@", layout), vec![vec!["bracket_left"]]);
+        assert_eq!(qcodes("\\_", layout), vec![vec!["ro"], vec!["shift", "ro"]]);
+        assert_eq!(qcodes(":", layout), vec![vec!["apostrophe"]]);
+
+        let err = KeyMapper::map_strokes("abcこ", layout).unwrap_err();
+        assert_eq!(err, KeyMappingError::UnsupportedCharacter { ch: 'こ', position: 3, layout });
+    }
+
+    #[test]
+    fn test_map_text_events() {
+        let events = KeyMapper::map_text("a@", KeyboardLayout::DeDe).unwrap();
+        let expected = [("a", true), ("a", false), ("alt_r", true), ("q", true), ("q", false), ("alt_r", false)];
+        assert_eq!(
+            events,
+            expected
+                .iter()
+                .map(|&(qcode, pressed)| KeyEvent { qcode, pressed })
+                .collect::<Vec<_>>()
+        );
+
+        let err = KeyMapper::map_text("Grüße", KeyboardLayout::EnUs).unwrap_err();
+        assert_eq!(err.to_string(), "键盘布局 en-US 无法输入第 2 个字符 'ü'");
+    }
+
+    #[test]
+    fn test_parse_key_combo() {
+        let combo = |s: &str| -> Vec<Vec<&'static str>> {
+            KeyCombo::parse(s).unwrap().iter().map(KeyStroke::qcodes).collect()
+        };
+
+        assert_eq!(combo("ctrl+alt+del"), vec![vec!["ctrl", "alt", "delete"]]);
+        assert_eq!(combo("Shift+F5"), vec![vec!["shift", "f5"]]);
+        assert_eq!(combo("ctrl-c"), vec![vec!["ctrl", "c"]]);
+        assert_eq!(combo("super+e"), vec![vec!["meta_l", "e"]]);
+        assert_eq!(combo("esc"), vec![vec!["esc"]]);
+        assert_eq!(combo("escape"), vec![vec!["esc"]]);
+        assert_eq!(combo("ret"), vec![vec!["ret"]]);
+        assert_eq!(combo("ctrl+-"), vec![vec!["ctrl", "minus"]]);
+        assert_eq!(combo("ctrl+a ctrl+c"), vec![vec!["ctrl", "a"], vec!["ctrl", "c"]]);
+
+        let scancodes = KeyCombo::parse("ctrl+alt+del").unwrap()[0].scancodes();
+        assert_eq!(scancodes, vec![0x1D, 0x38, 0xE053]);
+    }
+
+    #[test]
+    fn test_parse_key_combo_errors() {
+        let err = KeyCombo::parse("ctrl+alt+dle").unwrap_err();
+        assert_eq!(err, KeyMappingError::UnknownKey { token: "dle".to_string(), combo: "ctrl+alt+dle".to_string() });
+        assert!(err.to_string().contains("'dle'"));
+
+        let err = KeyCombo::parse("hyper+a").unwrap_err();
+        assert!(matches!(err, KeyMappingError::UnknownKey { ref token, .. } if token == "hyper"));
+
+        let err = KeyCombo::parse("a+ctrl").unwrap_err().to_string();
+        assert!(err.contains("'a' 不是修饰键"), "{}", err);
+
+        let err = KeyCombo::parse("ctrl+control+c").unwrap_err().to_string();
+        assert!(err.contains("重复"), "{}", err);
+
+        assert!(KeyCombo::parse("  ").is_err());
+        assert!(KeyCombo::parse("ctrl+").unwrap_err().to_string().contains("以连接符结尾"));
+    }
+
+    #[test]
+    fn test_named_keys_have_scancodes() {
+        for key in NAMED_KEYS.iter().chain(&LETTER_KEYS).chain(&DIGIT_KEYS) {
+            assert!(qcode_to_scancode(key).is_some(), "{}", key);
+        }
+    }
+}
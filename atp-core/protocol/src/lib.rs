@@ -16,9 +16,11 @@ pub mod qga;
 pub mod virtio;
 pub mod custom;
 pub mod spice;
+pub mod keymap;
 
 pub use traits::{Protocol, ProtocolType, ProtocolBuilder};
 pub use registry::ProtocolRegistry;
+pub use keymap::{KeyCombo, KeyEvent, KeyMapper, KeyMappingError, KeyStroke, KeyboardLayout};
 
 // 导出 VirtioSerial 相关类型
 pub use virtio::{
@@ -50,6 +52,8 @@ pub use spice::{
     UsbFilter,
 };
 
+pub use atp_transport::ErrorContext;
+
 use thiserror::Error;
 
 /// 协议层错误
@@ -79,11 +83,65 @@ pub enum ProtocolError {
     #[error("超时")]
     Timeout,
 
+    #[error("服务器要求 {0} 认证, 当前不支持")]
+    AuthRequired(String),
+
+    #[error("认证失败: {0}")]
+    AuthFailed(String),
+
     #[error("传输层错误: {0}")]
     TransportError(#[from] atp_transport::TransportError),
 
     #[error("IO 错误: {0}")]
     IoError(#[from] std::io::Error),
+
+    #[error("按键映射失败: {0}")]
+    KeyMapping(#[from] KeyMappingError),
+
+    #[error("{context} {source}")]
+    WithContext {
+        context: ErrorContext,
+        #[source]
+        source: Box<ProtocolError>,
+    },
+}
+
+impl ProtocolError {
+    /// 附加主机、虚拟机、操作等上下文
+    ///
+    /// 已带有上下文时只补全缺失的字段, 不会重复包装。
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        if context.is_empty() {
+            return self;
+        }
+
+        match self {
+            ProtocolError::WithContext { context: mut inner, source } => {
+                inner.merge(context);
+                ProtocolError::WithContext { context: inner, source }
+            }
+            err => ProtocolError::WithContext {
+                context,
+                source: Box::new(err),
+            },
+        }
+    }
+
+    /// 错误的上下文
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            ProtocolError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// 去掉上下文后的原始错误
+    pub fn root(&self) -> &ProtocolError {
+        match self {
+            ProtocolError::WithContext { source, .. } => source.root(),
+            err => err,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, ProtocolError>;
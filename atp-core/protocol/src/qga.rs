@@ -5,12 +5,16 @@
 
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use virt::domain::Domain;
 
-use crate::{Protocol, ProtocolBuilder, ProtocolError, ProtocolType, Result};
+use atp_common::{MetricSample, MetricsSource};
+
+use crate::{ErrorContext, Protocol, ProtocolBuilder, ProtocolError, ProtocolType, Result};
 
 // ============================================================================
 // QGA 协议数据结构
@@ -83,6 +87,104 @@ pub struct GuestExecStatus {
     pub err_truncated: Option<bool>,
 }
 
+/// guest-get-osinfo 返回结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct GuestOsInfo {
+    /// 操作系统标识 (Windows 为 "mswindows")
+    pub id: Option<String>,
+    pub name: Option<String>,
+    #[serde(rename = "pretty-name")]
+    pub pretty_name: Option<String>,
+    pub version: Option<String>,
+    #[serde(rename = "kernel-release")]
+    pub kernel_release: Option<String>,
+    pub machine: Option<String>,
+}
+
+impl GuestOsInfo {
+    /// 是否为 Windows 客户机
+    pub fn is_windows(&self) -> bool {
+        self.id.as_deref() == Some("mswindows")
+    }
+}
+
+/// guest-get-host-name 返回结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct GuestHostName {
+    #[serde(rename = "host-name")]
+    pub host_name: String,
+}
+
+/// guest-file-read 返回结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct GuestFileRead {
+    /// 实际读取的字节数
+    pub count: u64,
+    #[serde(rename = "buf-b64")]
+    pub buf_b64: String,
+    /// 是否已到文件末尾
+    pub eof: bool,
+}
+
+impl GuestFileRead {
+    /// 解码读取到的数据
+    pub fn decode(&self) -> Result<Vec<u8>> {
+        use base64::{Engine as _, engine::general_purpose};
+        general_purpose::STANDARD
+            .decode(&self.buf_b64)
+            .map_err(|e| ProtocolError::ParseError(format!("解码文件内容失败: {}", e)))
+    }
+}
+
+/// guest-file-write 返回结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct GuestFileWrite {
+    /// 实际写入的字节数
+    pub count: u64,
+    pub eof: bool,
+}
+
+/// guest-file-seek 返回结果
+#[derive(Debug, Clone, Deserialize)]
+pub struct GuestFileSeek {
+    /// 移动后的位置
+    pub position: u64,
+    pub eof: bool,
+}
+
+/// guest-file-seek 的起点
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GuestFileWhence {
+    Set,
+    Cur,
+    End,
+}
+
+impl GuestFileWhence {
+    /// QGA 接受的整数形式 (与 SEEK_SET/SEEK_CUR/SEEK_END 一致, 兼容旧版 guest agent)
+    fn as_int(self) -> i32 {
+        match self {
+            GuestFileWhence::Set => 0,
+            GuestFileWhence::Cur => 1,
+            GuestFileWhence::End => 2,
+        }
+    }
+}
+
+/// Windows 客户机上执行命令使用的 Shell
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WinShell {
+    /// `cmd.exe /U /C` (内部命令以 UTF-16LE 输出)
+    #[default]
+    Cmd,
+    /// `powershell.exe -Command` (输出编码设为 UTF-8)
+    PowerShell,
+}
+
+/// PowerShell 输出到管道时默认使用 OEM 代码页, 先切换为 UTF-8
+const POWERSHELL_UTF8_PREFIX: &str = "[Console]::OutputEncoding = [System.Text.Encoding]::UTF8; ";
+
 impl GuestExecCommand {
     pub fn simple(path: &str, args: Vec<String>) -> Self {
         Self {
@@ -93,27 +195,141 @@ impl GuestExecCommand {
             capture_output: Some(true),
         }
     }
+
+    /// Windows 客户机上通过 cmd 或 PowerShell 执行命令
+    pub fn windows(command: &str, shell: WinShell) -> Self {
+        match shell {
+            WinShell::Cmd => Self::simple("cmd.exe", vec!["/U".to_string(), "/C".to_string(), command.to_string()]),
+            WinShell::PowerShell => Self::simple(
+                "powershell.exe",
+                vec![
+                    "-NoProfile".to_string(),
+                    "-NonInteractive".to_string(),
+                    "-Command".to_string(),
+                    format!("{}{}", POWERSHELL_UTF8_PREFIX, command),
+                ],
+            ),
+        }
+    }
+
+    /// 设置写入进程标准输入的数据
+    pub fn with_input(mut self, data: &[u8]) -> Self {
+        use base64::{Engine as _, engine::general_purpose};
+        self.input_data = Some(general_purpose::STANDARD.encode(data));
+        self
+    }
 }
 
 impl GuestExecStatus {
+    /// 解码标准输出 (自动识别 UTF-8 / UTF-16)
     pub fn decode_stdout(&self) -> Option<String> {
-        use base64::{Engine as _, engine::general_purpose};
-        self.out_data.as_ref().and_then(|data| {
-            general_purpose::STANDARD
-                .decode(data)
-                .ok()
-                .and_then(|bytes| String::from_utf8(bytes).ok())
-        })
+        self.out_data.as_deref().and_then(decode_output)
     }
 
+    /// 解码标准错误 (自动识别 UTF-8 / UTF-16)
     pub fn decode_stderr(&self) -> Option<String> {
-        use base64::{Engine as _, engine::general_purpose};
-        self.err_data.as_ref().and_then(|data| {
-            general_purpose::STANDARD
-                .decode(data)
-                .ok()
-                .and_then(|bytes| String::from_utf8(bytes).ok())
-        })
+        self.err_data.as_deref().and_then(decode_output)
+    }
+}
+
+/// 解码 guest-exec 的 base64 输出, base64 无效时返回 `None`
+///
+/// Windows 客户机上 `cmd /U` 与 `Out-File` 等输出 UTF-16LE, 按以下顺序判断编码:
+/// 1. 有 BOM 时按 BOM (UTF-8 / UTF-16LE / UTF-16BE)
+/// 2. NUL 字节占四分之一以上时按 UTF-16LE (正常文本不含 NUL, UTF-16LE 中 ASCII 字符的高字节为 0)
+/// 3. 其余按 UTF-8, 无效字节替换为 U+FFFD
+pub fn decode_output(data: &str) -> Option<String> {
+    use base64::{Engine as _, engine::general_purpose};
+    let bytes = general_purpose::STANDARD.decode(data).ok()?;
+    Some(decode_text(&bytes))
+}
+
+fn decode_text(bytes: &[u8]) -> String {
+    if let Some(rest) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8_lossy(rest).into_owned();
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        return decode_utf16(rest, u16::from_le_bytes);
+    }
+    if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        return decode_utf16(rest, u16::from_be_bytes);
+    }
+
+    let nuls = bytes.iter().filter(|&&b| b == 0).count();
+    if bytes.len() >= 2 && nuls * 4 >= bytes.len() {
+        return decode_utf16(bytes, u16::from_le_bytes);
+    }
+
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// 按指定字节序解码 UTF-16 (末尾多出的单个字节丢弃)
+fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|pair| from_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+// ============================================================================
+// QGA 指标
+// ============================================================================
+
+/// QGA 命令统计 (命令数、错误数、延迟)
+#[derive(Debug, Default)]
+pub struct QgaMetrics {
+    total_commands: AtomicU64,
+    total_errors: AtomicU64,
+    last_latency_us: AtomicU64,
+    total_latency_us: AtomicU64,
+}
+
+impl QgaMetrics {
+    /// 记录一次命令执行
+    pub fn record(&self, latency_us: u64, success: bool) {
+        self.total_commands.fetch_add(1, Ordering::Relaxed);
+        self.last_latency_us.store(latency_us, Ordering::Relaxed);
+        self.total_latency_us.fetch_add(latency_us, Ordering::Relaxed);
+        if !success {
+            self.total_errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// 总命令数
+    pub fn total_commands(&self) -> u64 {
+        self.total_commands.load(Ordering::Relaxed)
+    }
+
+    /// 失败命令数
+    pub fn total_errors(&self) -> u64 {
+        self.total_errors.load(Ordering::Relaxed)
+    }
+
+    /// 最近一次命令延迟 (毫秒)
+    pub fn last_latency_ms(&self) -> f64 {
+        self.last_latency_us.load(Ordering::Relaxed) as f64 / 1000.0
+    }
+
+    /// 平均命令延迟 (毫秒)
+    pub fn avg_latency_ms(&self) -> f64 {
+        match self.total_commands() {
+            0 => 0.0,
+            count => self.total_latency_us.load(Ordering::Relaxed) as f64 / 1000.0 / count as f64,
+        }
+    }
+}
+
+#[async_trait]
+impl MetricsSource for QgaMetrics {
+    fn source_name(&self) -> &str {
+        "qga"
+    }
+
+    async fn collect(&self) -> Vec<MetricSample> {
+        vec![
+            MetricSample::new("total_commands", self.total_commands() as f64),
+            MetricSample::new("total_errors", self.total_errors() as f64),
+            MetricSample::new("last_latency_ms", self.last_latency_ms()),
+            MetricSample::new("avg_latency_ms", self.avg_latency_ms()),
+        ]
     }
 }
 
@@ -125,18 +341,24 @@ impl GuestExecStatus {
 pub struct QgaProtocol {
     /// Domain 引用（用Arc<Mutex>包装）
     domain: Option<Arc<Mutex<Domain>>>,
+    /// 虚拟机名称 (用于错误上下文)
+    domain_name: Option<String>,
     /// 超时时间（秒）
     timeout: i32,
     /// 连接状态
     connected: bool,
+    /// 命令统计
+    metrics: Arc<QgaMetrics>,
 }
 
 impl QgaProtocol {
     pub fn new() -> Self {
         Self {
             domain: None,
+            domain_name: None,
             timeout: 30,
             connected: false,
+            metrics: Arc::new(QgaMetrics::default()),
         }
     }
 
@@ -145,8 +367,35 @@ impl QgaProtocol {
         self
     }
 
+    /// 使用共享的命令统计
+    ///
+    /// 同一执行器多次重连 QGA 时, 统计累计到同一个指标源。
+    pub fn with_metrics(mut self, metrics: Arc<QgaMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// 命令统计 (可作为指标源挂载到 `MetricsCollector`)
+    pub fn metrics(&self) -> Arc<QgaMetrics> {
+        Arc::clone(&self.metrics)
+    }
+
     /// 执行 QGA 命令的通用方法
+    ///
+    /// 出错时附加虚拟机名称与命令名。
     pub async fn execute_command<T, R>(&self, command: &str, args: Option<T>) -> Result<R>
+    where
+        T: Serialize,
+        R: DeserializeOwned,
+    {
+        self.send_command(command, args).await.map_err(|e| {
+            let mut context = ErrorContext::new().with_operation(command);
+            context.domain = self.domain_name.clone();
+            e.with_context(context)
+        })
+    }
+
+    async fn send_command<T, R>(&self, command: &str, args: Option<T>) -> Result<R>
     where
         T: Serialize,
         R: DeserializeOwned,
@@ -176,12 +425,15 @@ impl QgaProtocol {
         let timeout = self.timeout;
         drop(domain_guard);
 
+        let started = Instant::now();
         let response_json = tokio::task::spawn_blocking(move || {
             domain_clone.qemu_agent_command(&cmd_json, timeout, 0)
         })
         .await
         .map_err(|e| ProtocolError::CommandFailed(format!("任务执行失败: {}", e)))?
-        .map_err(|e| ProtocolError::CommandFailed(format!("QGA 命令失败: {}", e)))?;
+        .map_err(|e| ProtocolError::CommandFailed(format!("QGA 命令失败: {}", e)));
+        self.metrics.record(started.elapsed().as_micros() as u64, response_json.is_ok());
+        let response_json = response_json?;
 
         debug!("收到 QGA 响应: {}", response_json);
 
@@ -235,15 +487,31 @@ impl QgaProtocol {
     /// 执行命令并等待完成
     pub async fn exec_and_wait(&self, cmd: GuestExecCommand) -> Result<GuestExecStatus> {
         let pid = self.exec(cmd).await?;
+        self.wait_exit(pid, None).await
+    }
+
+    /// 执行命令并等待完成, 超过 `timeout` 返回 [`ProtocolError::Timeout`]
+    ///
+    /// QGA 不能终止客户机中的进程, 超时后进程仍在客户机中运行。
+    pub async fn exec_and_wait_timeout(&self, cmd: GuestExecCommand, timeout: Duration) -> Result<GuestExecStatus> {
+        let pid = self.exec(cmd).await?;
+        self.wait_exit(pid, Some(tokio::time::Instant::now() + timeout)).await
+    }
+
+    /// 轮询直到进程退出
+    async fn wait_exit(&self, pid: i64, deadline: Option<tokio::time::Instant>) -> Result<GuestExecStatus> {
         info!("等待进程完成: PID {}", pid);
 
-        // 轮询直到进程退出
         loop {
             let status = self.exec_status(pid).await?;
             if status.exited {
                 info!("进程已退出: PID {}, 退出码: {:?}", pid, status.exit_code);
                 return Ok(status);
             }
+            if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+                warn!("等待进程超时: PID {}", pid);
+                return Err(ProtocolError::Timeout);
+            }
             tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
         }
     }
@@ -257,6 +525,84 @@ impl QgaProtocol {
 
         self.exec_and_wait(cmd).await
     }
+
+    /// 在 Windows 客户机上执行命令
+    ///
+    /// `cmd` 以 `/U` 运行, 内部命令 (echo、dir 等) 输出 UTF-16LE;
+    /// PowerShell 先把输出编码切换为 UTF-8。两种输出都由 `decode_stdout` 自动识别。
+    pub async fn exec_windows(&self, command: &str, shell: WinShell) -> Result<GuestExecStatus> {
+        info!("执行 Windows 命令 ({:?}): {}", shell, command);
+        self.exec_and_wait(GuestExecCommand::windows(command, shell)).await
+    }
+
+    /// 执行 PowerShell 脚本（Windows 客户机）
+    pub async fn exec_powershell(&self, script: &str) -> Result<GuestExecStatus> {
+        self.exec_windows(script, WinShell::PowerShell).await
+    }
+
+    /// 获取客户机操作系统信息
+    pub async fn get_osinfo(&self) -> Result<GuestOsInfo> {
+        #[derive(Serialize)]
+        struct Empty {}
+
+        self.execute_command::<Empty, GuestOsInfo>("guest-get-osinfo", None)
+            .await
+    }
+
+    /// 获取客户机主机名
+    pub async fn get_host_name(&self) -> Result<String> {
+        #[derive(Serialize)]
+        struct Empty {}
+
+        let result: GuestHostName = self
+            .execute_command::<Empty, GuestHostName>("guest-get-host-name", None)
+            .await?;
+        Ok(result.host_name)
+    }
+
+    /// 打开客户机中的文件, 返回句柄 (`mode` 与 fopen 一致, 如 "r"、"rb")
+    ///
+    /// 文件不存在时返回 [`ProtocolError::CommandFailed`], 错误信息中包含客户机给出的原因。
+    pub async fn file_open(&self, path: &str, mode: &str) -> Result<i64> {
+        debug!("打开客户机文件: {} ({})", path, mode);
+        self.execute_command("guest-file-open", Some(serde_json::json!({ "path": path, "mode": mode })))
+            .await
+    }
+
+    /// 从句柄的当前位置读取最多 `count` 字节
+    pub async fn file_read(&self, handle: i64, count: u64) -> Result<GuestFileRead> {
+        self.execute_command("guest-file-read", Some(serde_json::json!({ "handle": handle, "count": count })))
+            .await
+    }
+
+    /// 从句柄的当前位置写入数据
+    pub async fn file_write(&self, handle: i64, data: &[u8]) -> Result<GuestFileWrite> {
+        use base64::{Engine as _, engine::general_purpose};
+        self.execute_command(
+            "guest-file-write",
+            Some(serde_json::json!({ "handle": handle, "buf-b64": general_purpose::STANDARD.encode(data) })),
+        )
+        .await
+    }
+
+    /// 移动句柄的读写位置
+    pub async fn file_seek(&self, handle: i64, offset: i64, whence: GuestFileWhence) -> Result<GuestFileSeek> {
+        self.execute_command(
+            "guest-file-seek",
+            Some(serde_json::json!({ "handle": handle, "offset": offset, "whence": whence.as_int() })),
+        )
+        .await
+    }
+
+    /// 关闭句柄
+    pub async fn file_close(&self, handle: i64) -> Result<()> {
+        #[derive(Deserialize)]
+        struct CloseResponse {}
+
+        self.execute_command::<_, CloseResponse>("guest-file-close", Some(serde_json::json!({ "handle": handle })))
+            .await?;
+        Ok(())
+    }
 }
 
 impl Default for QgaProtocol {
@@ -274,6 +620,7 @@ impl Protocol for QgaProtocol {
         let domain_clone = domain.clone();
 
         self.domain = Some(Arc::new(Mutex::new(domain_clone)));
+        self.domain_name = domain.get_name().ok();
         self.connected = true;
 
         // 测试连通性
@@ -320,6 +667,7 @@ impl Protocol for QgaProtocol {
 
     async fn disconnect(&mut self) -> Result<()> {
         self.domain = None;
+        self.domain_name = None;
         self.connected = false;
         info!("QGA 连接已断开");
         Ok(())
@@ -380,4 +728,101 @@ mod tests {
         assert_eq!(cmd.path, "/bin/ls");
         assert_eq!(cmd.capture_output, Some(true));
     }
+
+    #[tokio::test]
+    async fn test_command_error_has_operation_context() {
+        let qga = QgaProtocol::new();
+        let err = qga.exec_shell("true").await.unwrap_err();
+
+        assert_eq!(err.context().unwrap().operation.as_deref(), Some("guest-exec"));
+        assert_eq!(err.to_string(), "[op=guest-exec] 协议连接失败: QGA 未连接");
+    }
+
+    #[tokio::test]
+    async fn test_qga_metrics_source() {
+        let metrics = QgaMetrics::default();
+        metrics.record(2000, true);
+        metrics.record(4000, false);
+
+        assert_eq!(metrics.total_commands(), 2);
+        assert_eq!(metrics.total_errors(), 1);
+        assert_eq!(metrics.last_latency_ms(), 4.0);
+        assert_eq!(metrics.avg_latency_ms(), 3.0);
+
+        let samples = metrics.collect().await;
+        assert_eq!(metrics.source_name(), "qga");
+        assert_eq!(samples.len(), 4);
+        assert!(samples.iter().any(|s| s.name == "avg_latency_ms" && s.value == 3.0));
+    }
+
+    #[test]
+    fn test_guest_osinfo_is_windows() {
+        let info: GuestOsInfo = serde_json::from_str(
+            r#"{"id":"mswindows","name":"Microsoft Windows","pretty-name":"Windows 10 Pro","machine":"x86_64"}"#,
+        ).unwrap();
+        assert!(info.is_windows());
+
+        let info: GuestOsInfo = serde_json::from_str(r#"{"id":"ubuntu","kernel-release":"6.8.0"}"#).unwrap();
+        assert!(!info.is_windows());
+    }
+
+    /// guest-exec-status 中实际返回的 out-data
+    fn status(out_data: &str) -> GuestExecStatus {
+        serde_json::from_value(serde_json::json!({
+            "exited": true,
+            "exitcode": 0,
+            "out-data": out_data,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_windows_exec_command() {
+        let cmd = GuestExecCommand::windows("echo hello", WinShell::Cmd);
+        assert_eq!(cmd.path, "cmd.exe");
+        assert_eq!(cmd.arg.as_deref(), Some(&["/U".to_string(), "/C".to_string(), "echo hello".to_string()][..]));
+
+        let cmd = GuestExecCommand::windows("Get-Date", WinShell::PowerShell);
+        assert_eq!(cmd.path, "powershell.exe");
+        let args = cmd.arg.unwrap();
+        assert_eq!(args[2], "-Command");
+        assert!(args[3].starts_with("[Console]::OutputEncoding"));
+        assert!(args[3].ends_with("Get-Date"));
+    }
+
+    #[test]
+    fn test_decode_utf16_output() {
+        // cmd /U /C echo hello
+        assert_eq!(status("aABlAGwAbABvAA0ACgA=").decode_stdout().unwrap(), "hello\r\n");
+        // cmd /U /C echo %USERPROFILE% (中文用户名)
+        assert_eq!(
+            status("QwA6AFwAVQBzAGUAcgBzAFwAS23Viyh1N2INAAoA").decode_stdout().unwrap(),
+            "C:\\Users\\测试用户\r\n"
+        );
+        // Out-File 写出的带 BOM 的 UTF-16LE
+        assert_eq!(
+            status("//5XAGkAbgBkAG8AdwBzACAASQBQACAATZFufw0ACgA=").decode_stdout().unwrap(),
+            "Windows IP 配置\r\n"
+        );
+    }
+
+    #[test]
+    fn test_decode_utf8_output() {
+        // Linux: ls -l 空目录
+        assert_eq!(status("dG90YWwgMAo=").decode_stdout().unwrap(), "total 0\n");
+        // PowerShell (输出编码切换为 UTF-8 后)
+        assert_eq!(status("5Li75py65ZCNOiBXSU4tMDENCg==").decode_stdout().unwrap(), "主机名: WIN-01\r\n");
+        // 带 BOM 的 UTF-8
+        assert_eq!(status("77u/5bey5a6M5oiQDQo=").decode_stdout().unwrap(), "已完成\r\n");
+
+        assert_eq!(status("!!!").decode_stdout(), None);
+        assert_eq!(status("").decode_stdout().unwrap(), "");
+        assert_eq!(status("dG90YWwgMAo=").decode_stderr(), None);
+    }
+
+    #[test]
+    fn test_guest_host_name_deserialize() {
+        let result: GuestHostName = serde_json::from_str(r#"{"host-name":"WIN-01"}"#).unwrap();
+        assert_eq!(result.host_name, "WIN-01");
+    }
 }
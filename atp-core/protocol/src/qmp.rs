@@ -12,7 +12,10 @@ use tokio::sync::Mutex;
 use tracing::{debug, info};
 use virt::domain::Domain;
 
-use crate::{Protocol, ProtocolBuilder, ProtocolError, ProtocolType, Result};
+use atp_transport::DomainInspection;
+
+use crate::spice::MouseButton;
+use crate::{ErrorContext, Protocol, ProtocolBuilder, ProtocolError, ProtocolType, Result};
 
 // ============================================================================
 // QMP 协议数据结构
@@ -97,6 +100,89 @@ pub struct SendKeyArgs {
     pub hold_time: Option<u32>, // 单位：毫秒
 }
 
+/// QEMU 未指定 hold-time 时的默认按住时长（毫秒）
+pub const DEFAULT_HOLD_TIME_MS: u32 = 100;
+
+impl SendKeyArgs {
+    /// 由 qcode 列表与可选按住时长组装参数
+    pub fn new(keys: &[&str], hold_time: Option<u32>) -> Self {
+        Self {
+            keys: keys.iter().map(|k| QmpKey::new_qcode(k)).collect(),
+            hold_time,
+        }
+    }
+
+    /// 实际生效的按住时长（毫秒）
+    pub fn effective_hold_time(&self) -> u32 {
+        self.hold_time.unwrap_or(DEFAULT_HOLD_TIME_MS)
+    }
+}
+
+/// input-send-event 绝对坐标轴的取值上限 (0..=32767)
+pub const QMP_ABS_AXIS_MAX: u32 = 0x7fff;
+
+/// 未设置屏幕尺寸时按 1920x1080 换算绝对坐标
+pub const DEFAULT_SCREEN_SIZE: (u32, u32) = (1920, 1080);
+
+/// input-send-event 输入事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "lowercase")]
+pub enum InputEvent {
+    /// 绝对坐标 (axis 为 "x" 或 "y")
+    Abs { axis: &'static str, value: u32 },
+    /// 按钮按下/释放
+    Btn { down: bool, button: &'static str },
+}
+
+impl InputEvent {
+    /// 把屏幕坐标换算为绝对坐标事件 (x, y), 超出屏幕的坐标按边缘处理
+    pub fn abs_move(x: u32, y: u32, max_x: u32, max_y: u32) -> [Self; 2] {
+        [
+            Self::Abs { axis: "x", value: scale_abs_axis(x, max_x) },
+            Self::Abs { axis: "y", value: scale_abs_axis(y, max_y) },
+        ]
+    }
+
+    /// 鼠标按钮事件
+    pub fn button(button: MouseButton, down: bool) -> Self {
+        Self::Btn { down, button: qmp_button_name(button) }
+    }
+}
+
+/// 把 `0..=max` 范围的坐标线性换算到 `0..=QMP_ABS_AXIS_MAX`
+fn scale_abs_axis(value: u32, max: u32) -> u32 {
+    if max == 0 {
+        return 0;
+    }
+    let scaled = u64::from(value.min(max)) * u64::from(QMP_ABS_AXIS_MAX) / u64::from(max);
+    scaled as u32
+}
+
+/// QMP InputButton 名称
+fn qmp_button_name(button: MouseButton) -> &'static str {
+    match button {
+        MouseButton::Left => "left",
+        MouseButton::Middle => "middle",
+        MouseButton::Right => "right",
+        MouseButton::ScrollUp => "wheel-up",
+        MouseButton::ScrollDown => "wheel-down",
+        MouseButton::Side => "side",
+        MouseButton::Extra => "extra",
+    }
+}
+
+/// input-send-event 命令参数
+#[derive(Debug, Serialize)]
+pub struct InputSendEventArgs {
+    pub events: Vec<InputEvent>,
+}
+
+impl InputSendEventArgs {
+    pub fn new(events: impl IntoIterator<Item = InputEvent>) -> Self {
+        Self { events: events.into_iter().collect() }
+    }
+}
+
 // ============================================================================
 // QMP 协议实现
 // ============================================================================
@@ -109,8 +195,12 @@ pub struct QmpProtocol {
     reader: Option<Arc<Mutex<BufReader<ReadHalf<UnixStream>>>>>,
     /// QMP Socket 路径
     socket_path: Option<String>,
+    /// 虚拟机名称 (用于错误上下文)
+    domain_name: Option<String>,
     /// 连接状态
     connected: bool,
+    /// 来宾屏幕尺寸 (宽, 高), 用于换算鼠标绝对坐标
+    screen_size: (u32, u32),
 }
 
 impl QmpProtocol {
@@ -119,12 +209,33 @@ impl QmpProtocol {
             writer: None,
             reader: None,
             socket_path: None,
+            domain_name: None,
             connected: false,
+            screen_size: DEFAULT_SCREEN_SIZE,
         }
     }
 
+    /// 设置来宾屏幕尺寸, [`send_mouse_click`](Self::send_mouse_click) 按此换算绝对坐标
+    pub fn set_screen_size(&mut self, width: u32, height: u32) {
+        self.screen_size = (width, height);
+    }
+
+    /// 错误上下文 (虚拟机名称与操作)
+    fn error_context(&self, operation: &str) -> ErrorContext {
+        let mut context = ErrorContext::new().with_operation(operation);
+        context.domain = self.domain_name.clone();
+        context
+    }
+
     /// 执行 QMP 命令
+    ///
+    /// 出错时附加虚拟机名称与命令名。
     pub async fn execute_command(&mut self, cmd: &QmpCommand<'_>) -> Result<QmpResponse> {
+        let context = self.error_context(cmd.execute);
+        self.send_command(cmd).await.map_err(|e| e.with_context(context))
+    }
+
+    async fn send_command(&mut self, cmd: &QmpCommand<'_>) -> Result<QmpResponse> {
         if !self.connected {
             return Err(ProtocolError::ConnectionFailed(
                 "QMP 未连接".to_string(),
@@ -188,12 +299,7 @@ impl QmpProtocol {
 
     /// 发送按键序列
     pub async fn send_keys(&mut self, keys: Vec<&str>, hold_time: Option<u32>) -> Result<()> {
-        let qmp_keys: Vec<QmpKey> = keys.into_iter().map(|k| QmpKey::new_qcode(k)).collect();
-
-        let args = SendKeyArgs {
-            keys: qmp_keys,
-            hold_time,
-        };
+        let args = SendKeyArgs::new(&keys, hold_time);
 
         let cmd = QmpCommand {
             execute: "send-key",
@@ -208,8 +314,50 @@ impl QmpProtocol {
     }
 
     /// 发送单个按键
-    pub async fn send_key(&mut self, key: &str) -> Result<()> {
-        self.send_keys(vec![key], None).await
+    ///
+    /// `hold_ms` 映射为 send-key 的 hold-time，用于长按场景；
+    /// 为 `None` 时由 QEMU 使用默认值 [`DEFAULT_HOLD_TIME_MS`]
+    pub async fn send_key(&mut self, key: &str, hold_ms: Option<u32>) -> Result<()> {
+        self.send_keys(vec![key], hold_ms).await
+    }
+
+    /// 发送输入事件 (input-send-event)
+    async fn send_input_events(&mut self, events: impl IntoIterator<Item = InputEvent>) -> Result<()> {
+        let args = InputSendEventArgs::new(events);
+
+        let cmd = QmpCommand {
+            execute: "input-send-event",
+            arguments: Some(serde_json::to_value(args).map_err(|e| {
+                ProtocolError::SendFailed(format!("序列化参数失败: {}", e))
+            })?),
+            id: Some("input-send-event"),
+        };
+
+        self.execute_command(&cmd).await?;
+        Ok(())
+    }
+
+    /// 移动鼠标到屏幕坐标 (x, y)
+    ///
+    /// `max_x`/`max_y` 为屏幕宽高, 坐标按比例换算到 QEMU 绝对坐标轴 (0..=32767);
+    /// 虚拟机需要有绝对定位设备 (如 usb-tablet), 否则事件会被忽略。
+    pub async fn send_mouse_move_abs(&mut self, x: u32, y: u32, max_x: u32, max_y: u32) -> Result<()> {
+        self.send_input_events(InputEvent::abs_move(x, y, max_x, max_y)).await
+    }
+
+    /// 按下或释放鼠标按钮
+    pub async fn send_mouse_button(&mut self, button: MouseButton, down: bool) -> Result<()> {
+        self.send_input_events([InputEvent::button(button, down)]).await
+    }
+
+    /// 在屏幕坐标 (x, y) 处单击 (移动 + 按下 + 释放)
+    pub async fn send_mouse_click(&mut self, x: u32, y: u32, button: MouseButton) -> Result<()> {
+        let (width, height) = self.screen_size;
+        self.send_mouse_move_abs(x, y, width, height).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        self.send_mouse_button(button, true).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        self.send_mouse_button(button, false).await
     }
 
     /// 查询 QMP 版本
@@ -234,52 +382,41 @@ impl QmpProtocol {
         self.execute_command(&cmd).await
     }
 
-    /// 协商 QMP 能力
-    async fn negotiate_capabilities(&mut self) -> Result<()> {
+    /// 查询各块设备的累计 I/O 统计 (query-blockstats)
+    pub async fn query_blockstats(&mut self) -> Result<QmpResponse> {
         let cmd = QmpCommand {
-            execute: "qmp_capabilities",
+            execute: "query-blockstats",
             arguments: None,
-            id: Some("init"),
+            id: Some("query-blockstats"),
         };
 
-        self.execute_command(&cmd).await?;
-        info!("QMP 能力协商完成");
-        Ok(())
-    }
-}
-
-impl Default for QmpProtocol {
-    fn default() -> Self {
-        Self::new()
+        self.execute_command(&cmd).await
     }
-}
-
-#[async_trait]
-impl Protocol for QmpProtocol {
-    async fn connect(&mut self, domain: &Domain) -> Result<()> {
-        // 从 Domain 获取 QMP Socket 路径
-        // 通常位于 /var/lib/libvirt/qemu/domain-{id}-{name}/monitor.sock
-        let domain_name = domain
-            .get_name()
-            .map_err(|e| ProtocolError::ConnectionFailed(e.to_string()))?;
 
-        // 尝试构建 socket 路径
-        // 注意：这是一个简化的实现，实际可能需要从 libvirt XML 中读取
-        let socket_path = format!("/var/lib/libvirt/qemu/domain-*-{}/monitor.sock", domain_name);
-
-        info!("连接到 QMP Socket: {}", socket_path);
-
-        // 由于路径包含通配符，我们需要展开它
-        // 这里简化处理，实际应该使用 glob 或从 XML 读取
-        // 为了演示，我们假设路径是已知的或通过其他方式获取
+    /// 截取虚拟机屏幕 (screendump)
+    ///
+    /// 文件由 QEMU 进程写入, `filename` 需要是 QEMU 所在主机上的绝对路径;
+    /// `format` 为 None 时输出 PPM, `png` 需要 QEMU 7.1 及以上版本。
+    pub async fn screendump(&mut self, filename: &str, format: Option<&str>) -> Result<()> {
+        let mut arguments = serde_json::json!({ "filename": filename });
+        if let Some(format) = format {
+            arguments["format"] = serde_json::Value::from(format);
+        }
 
-        // TODO: 实现真实的 socket 路径解析
+        let cmd = QmpCommand {
+            execute: "screendump",
+            arguments: Some(arguments),
+            id: Some("screendump"),
+        };
 
-        // 暂时存储 socket 路径用于后续重连
-        self.socket_path = Some(socket_path.clone());
+        self.execute_command(&cmd).await?;
+        Ok(())
+    }
 
+    /// 连接 QMP Socket 并进入命令模式
+    async fn open(&mut self, socket_path: &str) -> Result<()> {
         // 建立 Unix Socket 连接
-        let stream = UnixStream::connect(&socket_path)
+        let stream = UnixStream::connect(socket_path)
             .await
             .map_err(|e| {
                 ProtocolError::ConnectionFailed(format!("无法连接到 QMP Socket: {}", e))
@@ -316,6 +453,66 @@ impl Protocol for QmpProtocol {
         Ok(())
     }
 
+    /// 协商 QMP 能力
+    async fn negotiate_capabilities(&mut self) -> Result<()> {
+        let cmd = QmpCommand {
+            execute: "qmp_capabilities",
+            arguments: None,
+            id: Some("init"),
+        };
+
+        self.execute_command(&cmd).await?;
+        info!("QMP 能力协商完成");
+        Ok(())
+    }
+}
+
+impl Default for QmpProtocol {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 虚拟机的 QMP Socket 路径
+///
+/// 优先使用 domain XML 中声明的套接字 (`<qemu:commandline>` 的 `-qmp unix:...`),
+/// 否则使用 libvirt 默认的监控套接字 `/var/lib/libvirt/qemu/domain-{id}-{name}/monitor.sock`
+fn qmp_socket_path(domain: &Domain, domain_name: &str) -> Result<String> {
+    let xml = domain
+        .get_xml_desc(0)
+        .map_err(|e| ProtocolError::ConnectionFailed(format!("无法获取虚拟机 XML: {}", e)))?;
+    let inspection = DomainInspection::parse(&xml)
+        .map_err(|e| ProtocolError::ParseError(e.to_string()))?;
+
+    if let Some(path) = inspection.qmp_socket {
+        return Ok(path.display().to_string());
+    }
+
+    let id = domain
+        .get_id()
+        .ok_or_else(|| ProtocolError::ConnectionFailed(format!("虚拟机 {} 未运行", domain_name)))?;
+    Ok(format!("/var/lib/libvirt/qemu/domain-{}-{}/monitor.sock", id, domain_name))
+}
+
+#[async_trait]
+impl Protocol for QmpProtocol {
+    async fn connect(&mut self, domain: &Domain) -> Result<()> {
+        let domain_name = domain
+            .get_name()
+            .map_err(|e| ProtocolError::ConnectionFailed(e.to_string()))?;
+
+        self.domain_name = Some(domain_name.clone());
+        let context = self.error_context("connect");
+
+        let socket_path = qmp_socket_path(domain, &domain_name).map_err(|e| e.with_context(context.clone()))?;
+        info!("连接到 QMP Socket: {}", socket_path);
+
+        // 存储 socket 路径用于后续重连
+        self.socket_path = Some(socket_path.clone());
+
+        self.open(&socket_path).await.map_err(|e| e.with_context(context))
+    }
+
     async fn send(&mut self, data: &[u8]) -> Result<()> {
         if !self.connected {
             return Err(ProtocolError::ConnectionFailed(
@@ -372,6 +569,7 @@ impl Protocol for QmpProtocol {
         self.reader = None;
         self.connected = false;
         self.socket_path = None;
+        self.domain_name = None;
 
         info!("QMP 连接已断开");
         Ok(())
@@ -436,4 +634,63 @@ mod tests {
         let json = serde_json::to_string(&args).unwrap();
         assert!(json.contains("\"hold-time\":100"));
     }
+
+    #[test]
+    fn test_send_key_args_hold_time() {
+        let args = SendKeyArgs::new(&["ctrl", "alt", "delete"], Some(2000));
+        let value = serde_json::to_value(&args).unwrap();
+        assert_eq!(value["hold-time"], 2000);
+        assert_eq!(value["keys"].as_array().unwrap().len(), 3);
+        assert_eq!(value["keys"][2]["data"], "delete");
+        assert_eq!(args.effective_hold_time(), 2000);
+
+        let args = SendKeyArgs::new(&["ret"], None);
+        let value = serde_json::to_value(&args).unwrap();
+        assert!(value.get("hold-time").is_none());
+        assert_eq!(args.effective_hold_time(), DEFAULT_HOLD_TIME_MS);
+    }
+
+    #[test]
+    fn test_input_send_event_abs_move() {
+        let args = InputSendEventArgs::new(InputEvent::abs_move(960, 540, 1920, 1080));
+        assert_eq!(
+            serde_json::to_value(&args).unwrap(),
+            serde_json::json!({
+                "events": [
+                    { "type": "abs", "data": { "axis": "x", "value": 16383 } },
+                    { "type": "abs", "data": { "axis": "y", "value": 16383 } },
+                ]
+            })
+        );
+
+        // 屏幕边缘与越界坐标
+        assert_eq!(InputEvent::abs_move(0, 0, 1920, 1080)[0], InputEvent::Abs { axis: "x", value: 0 });
+        assert_eq!(InputEvent::abs_move(1920, 1080, 1920, 1080)[1], InputEvent::Abs { axis: "y", value: 32767 });
+        assert_eq!(InputEvent::abs_move(5000, 0, 1920, 1080)[0], InputEvent::Abs { axis: "x", value: 32767 });
+        assert_eq!(InputEvent::abs_move(100, 100, 0, 0)[0], InputEvent::Abs { axis: "x", value: 0 });
+    }
+
+    #[test]
+    fn test_input_send_event_button() {
+        let cmd = QmpCommand {
+            execute: "input-send-event",
+            arguments: Some(serde_json::to_value(InputSendEventArgs::new([
+                InputEvent::button(MouseButton::Right, true),
+                InputEvent::button(MouseButton::ScrollDown, false),
+            ])).unwrap()),
+            id: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&cmd).unwrap(),
+            serde_json::json!({
+                "execute": "input-send-event",
+                "arguments": {
+                    "events": [
+                        { "type": "btn", "data": { "down": true, "button": "right" } },
+                        { "type": "btn", "data": { "down": false, "button": "wheel-down" } },
+                    ]
+                }
+            })
+        );
+    }
 }
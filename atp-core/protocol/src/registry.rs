@@ -5,7 +5,7 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, info};
 
-use crate::{Protocol, ProtocolBuilder, ProtocolError, Result};
+use crate::{Protocol, ProtocolBuilder, ProtocolError, ProtocolType, Result};
 
 /// 协议注册表
 ///
@@ -53,9 +53,11 @@ impl ProtocolRegistry {
         Ok(())
     }
 
-    /// 获取协议实例
-    pub async fn get(&self, name: &str) -> Result<Box<dyn Protocol>> {
-        debug!("获取协议实例: {}", name);
+    /// 创建协议实例
+    ///
+    /// 每次调用都会通过已注册的构建器创建一个新的、未连接的实例。
+    pub async fn create(&self, name: &str) -> Result<Box<dyn Protocol>> {
+        debug!("创建协议实例: {}", name);
 
         let builders = self.builders.read().await;
 
@@ -66,6 +68,21 @@ impl ProtocolRegistry {
         Ok(builder.build())
     }
 
+    /// 获取协议实例 (等同于 [`ProtocolRegistry::create`])
+    pub async fn get(&self, name: &str) -> Result<Box<dyn Protocol>> {
+        self.create(name).await
+    }
+
+    /// 获取已注册协议的类型
+    pub async fn protocol_type(&self, name: &str) -> Result<ProtocolType> {
+        let builders = self.builders.read().await;
+
+        builders
+            .get(name)
+            .map(|builder| builder.protocol_type())
+            .ok_or_else(|| ProtocolError::ProtocolNotFound(name.to_string()))
+    }
+
     /// 列出所有已注册的协议
     pub async fn list(&self) -> Vec<String> {
         let builders = self.builders.read().await;
@@ -88,10 +105,78 @@ impl Default for ProtocolRegistry {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::custom::CustomProtocolBuilder;
 
     #[tokio::test]
     async fn test_registry_creation() {
         let registry = ProtocolRegistry::new();
         assert_eq!(registry.list().await.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_register_and_create() {
+        let registry = ProtocolRegistry::new();
+        registry
+            .register("my-agent", Box::new(CustomProtocolBuilder::new("org.example.agent.0".to_string())))
+            .await
+            .unwrap();
+
+        assert!(registry.is_registered("my-agent").await);
+
+        let protocol = registry.create("my-agent").await.unwrap();
+        assert_eq!(protocol.name(), "org.example.agent.0");
+        assert!(!protocol.is_connected().await);
+
+        assert!(matches!(
+            registry.create("missing").await,
+            Err(ProtocolError::ProtocolNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_register_conflict() {
+        let registry = ProtocolRegistry::new();
+        registry
+            .register("my-agent", Box::new(CustomProtocolBuilder::new("a".to_string())))
+            .await
+            .unwrap();
+
+        let result = registry
+            .register("my-agent", Box::new(CustomProtocolBuilder::new("b".to_string())))
+            .await;
+        assert!(matches!(result, Err(ProtocolError::ProtocolAlreadyRegistered(_))));
+
+        // 冲突不会覆盖原有注册
+        assert_eq!(
+            registry.protocol_type("my-agent").await.unwrap(),
+            ProtocolType::VirtioSerial("a".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_registration() {
+        let registry = Arc::new(ProtocolRegistry::new());
+
+        let tasks: Vec<_> = (0..8)
+            .map(|i| {
+                let registry = Arc::clone(&registry);
+                tokio::spawn(async move {
+                    registry
+                        .register("shared", Box::new(CustomProtocolBuilder::new(format!("ch{}", i))))
+                        .await
+                })
+            })
+            .collect();
+
+        let mut succeeded = 0;
+        for task in tasks {
+            if task.await.unwrap().is_ok() {
+                succeeded += 1;
+            }
+        }
+
+        // 并发注册同名协议只有一个成功
+        assert_eq!(succeeded, 1);
+        assert_eq!(registry.list().await, vec!["shared".to_string()]);
+    }
 }
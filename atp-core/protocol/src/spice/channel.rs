@@ -8,6 +8,9 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, ReadHalf, WriteHalf};
 use tokio::net::TcpStream;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Oaep, RsaPublicKey};
+use sha1::Sha1;
 use tokio::sync::Mutex;
 use tracing::{debug, trace};
 
@@ -175,11 +178,13 @@ impl ChannelConnection {
 
     /// 执行 SPICE 握手
     async fn perform_handshake(&mut self, password: Option<&str>) -> Result<()> {
-        // 1. 发送链接消息
+        // 1. 发送链接消息, 声明支持选择认证方式与票据认证
         let link_msg = SpiceLinkMessage::new(
             self.channel_type.to_u8(),
             self.channel_id,
-        ).with_connection_id(self.connection_id);
+        )
+        .with_connection_id(self.connection_id)
+        .with_common_caps(vec![common_caps::AUTH_SELECT | common_caps::AUTH_SPICE]);
 
         let link_data = link_msg.to_bytes();
         let header = SpiceLinkHeader::new(link_data.len() as u32);
@@ -232,20 +237,18 @@ impl ChannelConnection {
             .ok_or_else(|| ProtocolError::ParseError("无效的链接回复".to_string()))?;
 
         if !reply.is_ok() {
-            return Err(ProtocolError::ConnectionFailed(
-                format!("SPICE 链接错误: {}", reply.error)
-            ));
+            return Err(link_error(reply.error));
         }
 
         drop(reader_guard);
 
-        // 3. 发送认证信息（如果需要密码）
-        if let Some(pwd) = password {
-            self.send_auth(pwd, &reply.pub_key).await?;
-        } else {
-            // 发送空认证
-            self.send_empty_auth().await?;
-        }
+        // 3. 选择认证方式并发送票据: 有密码时用服务器公钥加密, 否则发送空票据
+        let select_auth = select_auth(&reply)?;
+        let ticket = match password {
+            Some(password) => encrypt_ticket(password, &reply.pub_key)?,
+            None => vec![0u8; RSA_KEY_SIZE / 8],
+        };
+        self.send_ticket(select_auth, &ticket).await?;
 
         // 检查是否支持 mini header
         // TODO: 从能力协商中确定
@@ -254,109 +257,16 @@ impl ChannelConnection {
         Ok(())
     }
 
-    /// 发送认证信息
-    async fn send_auth(&mut self, _password: &str, _pub_key: &[u8]) -> Result<()> {
-        // TODO: 实现 RSA 加密密码
-        //
-        // 参考实现：spice-gtk/src/channel-main.c 中的 spice_channel_send_link_ack()
-        //
-        // 1. 解析服务器提供的 RSA 公钥（DER 格式）
-        //    - pub_key 字段包含 SPICE_TICKET_PUBKEY_BYTES (162 字节) 的 RSA 公钥
-        //    - 使用 rsa crate: use rsa::RsaPublicKey;
-        //    - 解析方法：
-        //      ```rust
-        //      use rsa::pkcs1::DecodeRsaPublicKey;
-        //      let public_key = RsaPublicKey::from_pkcs1_der(_pub_key)
-        //          .map_err(|e| ProtocolError::ParseError(format!("解析 RSA 公钥失败: {}", e)))?;
-        //      ```
-        //
-        // 2. 准备密码票据（128 字节缓冲区）
-        //    - 创建 [u8; 128] 数组，初始化为 0
-        //    - 将密码字符串拷贝到缓冲区前面（最多 127 字节，保留一个 \0 结尾）
-        //    - 剩余部分用随机数填充以增强安全性
-        //      ```rust
-        //      use rand::RngCore;
-        //      let mut ticket = [0u8; 128];
-        //      let pwd_bytes = _password.as_bytes();
-        //      let copy_len = std::cmp::min(pwd_bytes.len(), 127);
-        //      ticket[..copy_len].copy_from_slice(&pwd_bytes[..copy_len]);
-        //      // ticket[copy_len] = 0; // null terminator (already 0)
-        //      if copy_len < 127 {
-        //          rand::thread_rng().fill_bytes(&mut ticket[copy_len+1..]);
-        //      }
-        //      ```
-        //
-        // 3. 使用 RSA-OAEP 加密票据
-        //    - 使用 SHA-1 作为哈希函数（SPICE 协议规范）
-        //    - 使用 rsa crate 的 Oaep 填充：
-        //      ```rust
-        //      use rsa::{Oaep, sha1::Sha1};
-        //      use rand::thread_rng;
-        //
-        //      let padding = Oaep::new::<Sha1>();
-        //      let mut rng = thread_rng();
-        //      let encrypted_ticket = public_key.encrypt(&mut rng, padding, &ticket)
-        //          .map_err(|e| ProtocolError::ConnectionFailed(format!("RSA 加密失败: {}", e)))?;
-        //      ```
-        //
-        // 4. 发送加密后的票据（128 字节）
-        //    - 加密后的数据大小应该等于 RSA 密钥大小（通常 128 字节）
-        //    - 如果加密结果不足 128 字节，需要左侧填充 0
-        //      ```rust
-        //      let writer = self.writer.as_ref().unwrap();
-        //      let mut writer_guard = writer.lock().await;
-        //
-        //      // 确保是 128 字节
-        //      let mut final_ticket = [0u8; 128];
-        //      let offset = 128 - encrypted_ticket.len();
-        //      final_ticket[offset..].copy_from_slice(&encrypted_ticket);
-        //
-        //      writer_guard.write_all(&final_ticket).await
-        //          .map_err(|e| ProtocolError::SendFailed(e.to_string()))?;
-        //      writer_guard.flush().await
-        //          .map_err(|e| ProtocolError::SendFailed(e.to_string()))?;
-        //      drop(writer_guard);
-        //      ```
-        //
-        // 5. 读取认证结果（与 send_empty_auth 相同）
-        //    - 接收 4 字节的认证结果
-        //    - 0 表示成功，非 0 表示失败
-        //      ```rust
-        //      let reader = self.reader.as_ref().unwrap();
-        //      let mut reader_guard = reader.lock().await;
-        //      let mut result = [0u8; 4];
-        //      reader_guard.read_exact(&mut result).await
-        //          .map_err(|e| ProtocolError::ReceiveFailed(format!("读取认证结果失败: {}", e)))?;
-        //      let auth_result = u32::from_le_bytes(result);
-        //      if auth_result != 0 {
-        //          return Err(ProtocolError::ConnectionFailed(
-        //              format!("SPICE 认证失败，错误码: {}", auth_result)
-        //          ));
-        //      }
-        //      debug!("SPICE RSA 认证成功");
-        //      ```
-        //
-        // 需要添加的依赖到 Cargo.toml:
-        //   rsa = "0.9"
-        //   rand = "0.8"
-        //   sha1 = "0.10"
-        //
-        // 参考资料：
-        //   - spice-protocol/spice/protocol.h: SPICE_TICKET_PUBKEY_BYTES 定义
-        //   - spice-gtk/src/channel-main.c: spice_channel_send_link_ack() 函数
-
-        // 目前发送空认证（假设服务器不需要密码）
-        self.send_empty_auth().await
-    }
-
-    /// 发送空认证
-    async fn send_empty_auth(&mut self) -> Result<()> {
+    /// 发送认证方式 (服务器支持选择时) 与票据, 并读取认证结果
+    async fn send_ticket(&mut self, select_auth: bool, ticket: &[u8]) -> Result<()> {
         let writer = self.writer.as_ref().unwrap();
         let mut writer_guard = writer.lock().await;
 
-        // 发送 128 字节的空票据
-        let ticket = [0u8; 128];
-        writer_guard.write_all(&ticket).await
+        if select_auth {
+            writer_guard.write_all(&auth_mechanism::SPICE.to_le_bytes()).await
+                .map_err(|e| ProtocolError::SendFailed(e.to_string()))?;
+        }
+        writer_guard.write_all(ticket).await
             .map_err(|e| ProtocolError::SendFailed(e.to_string()))?;
         writer_guard.flush().await
             .map_err(|e| ProtocolError::SendFailed(e.to_string()))?;
@@ -374,10 +284,8 @@ impl ChannelConnection {
             ))?;
 
         let auth_result = u32::from_le_bytes(result);
-        if auth_result != 0 {
-            return Err(ProtocolError::ConnectionFailed(
-                format!("SPICE 认证失败: {}", auth_result)
-            ));
+        if auth_result != link_error::OK {
+            return Err(link_error(auth_result));
         }
 
         debug!("SPICE 认证成功");
@@ -385,7 +293,17 @@ impl ChannelConnection {
     }
 
     /// 发送消息
+    ///
+    /// 写入失败时通道标记为已断开
     pub async fn send_message(&mut self, msg_type: u16, data: &[u8]) -> Result<()> {
+        let result = self.write_message(msg_type, data).await;
+        if let Err(ProtocolError::SendFailed(_)) = result {
+            self.connected = false;
+        }
+        result
+    }
+
+    async fn write_message(&self, msg_type: u16, data: &[u8]) -> Result<()> {
         let writer = self.writer.as_ref()
             .ok_or_else(|| ProtocolError::ConnectionFailed("通道未连接".to_string()))?;
 
@@ -417,7 +335,17 @@ impl ChannelConnection {
     }
 
     /// 接收消息
+    ///
+    /// 读取失败 (包括对端关闭连接) 时通道标记为已断开
     pub async fn receive_message(&mut self) -> Result<(u16, Vec<u8>)> {
+        let result = self.read_message().await;
+        if let Err(ProtocolError::ReceiveFailed(_)) = result {
+            self.connected = false;
+        }
+        result
+    }
+
+    async fn read_message(&self) -> Result<(u16, Vec<u8>)> {
         let reader = self.reader.as_ref()
             .ok_or_else(|| ProtocolError::ConnectionFailed("通道未连接".to_string()))?;
 
@@ -479,6 +407,46 @@ impl ChannelConnection {
     }
 }
 
+/// 按服务器声明的能力确定认证方式, 返回是否需要在票据前发送认证方式
+///
+/// 服务器只接受 SASL 时返回 [`ProtocolError::AuthRequired`]。
+fn select_auth(reply: &SpiceLinkReply) -> Result<bool> {
+    let selectable = reply.has_common_cap(common_caps::AUTH_SELECT);
+    if selectable && !reply.has_common_cap(common_caps::AUTH_SPICE) && reply.has_common_cap(common_caps::AUTH_SASL) {
+        return Err(ProtocolError::AuthRequired("sasl".to_string()));
+    }
+    Ok(selectable)
+}
+
+/// 用服务器公钥加密密码票据 (RSA-OAEP/SHA-1, 明文为以 `\0` 结尾的密码)
+fn encrypt_ticket(password: &str, pub_key: &[u8]) -> Result<Vec<u8>> {
+    if password.len() > PASSWORD_MAX_LEN {
+        return Err(ProtocolError::AuthFailed(format!("密码长度超过 {} 字节", PASSWORD_MAX_LEN)));
+    }
+
+    let public_key = RsaPublicKey::from_public_key_der(pub_key)
+        .map_err(|e| ProtocolError::ParseError(format!("解析 SPICE 公钥失败: {}", e)))?;
+
+    let mut plain = password.as_bytes().to_vec();
+    plain.push(0);
+    public_key
+        .encrypt(&mut rand::thread_rng(), Oaep::new::<Sha1>(), &plain)
+        .map_err(|e| ProtocolError::AuthFailed(format!("加密票据失败: {}", e)))
+}
+
+/// 链接回复或认证结果中的错误码对应的错误
+fn link_error(code: u32) -> ProtocolError {
+    match code {
+        link_error::PERMISSION_DENIED => ProtocolError::AuthFailed("密码错误或票据已过期".to_string()),
+        link_error::NEED_SECURED => ProtocolError::ConnectionFailed("SPICE 服务器要求 TLS 连接".to_string()),
+        link_error::NEED_UNSECURED => ProtocolError::ConnectionFailed("SPICE 服务器要求非 TLS 连接".to_string()),
+        link_error::VERSION_MISMATCH => ProtocolError::ConnectionFailed("SPICE 协议版本不匹配".to_string()),
+        link_error::CHANNEL_NOT_AVAILABLE => ProtocolError::ConnectionFailed("SPICE 通道不可用".to_string()),
+        link_error::BAD_CONNECTION_ID => ProtocolError::ConnectionFailed("SPICE 会话 ID 无效".to_string()),
+        code => ProtocolError::ConnectionFailed(format!("SPICE 链接错误: {}", code)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -501,4 +469,129 @@ mod tests {
         assert_eq!(conn.channel_id(), 0);
         assert!(!conn.is_connected());
     }
+
+    // 以下握手测试用一个只处理链接阶段的模拟服务器, 回复按 spice-server 的实际布局构造
+
+    use rsa::pkcs8::EncodePublicKey;
+    use rsa::RsaPrivateKey;
+    use tokio::net::TcpListener;
+
+    /// 主通道的链接消息 (抓包): 连接 ID 0, 通道 main/0, 1 个公共能力 (AUTH_SELECT | AUTH_SPICE)
+    const MAIN_LINK_MESS: &str = "00000000 01 00 01000000 00000000 12000000 03000000";
+
+    /// spice-server 开启 SASL 且关闭票据认证时的链接回复 (抓包, 公钥已置零):
+    /// 公共能力 AUTH_SELECT | AUTH_SASL | MINI_HEADER, 主通道能力 0x0f
+    fn sasl_only_reply() -> Vec<u8> {
+        let mut reply = vec![0u8; 4 + SPICE_TICKET_PUBKEY_BYTES];
+        reply.extend(hex("01000000 01000000 b2000000 0d000000 0f000000"));
+        reply
+    }
+
+    fn hex(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect()
+    }
+
+    /// 带公钥与公共能力的链接回复
+    fn link_reply(pub_key: &[u8], common_caps: u32) -> Vec<u8> {
+        let mut reply = 0u32.to_le_bytes().to_vec();
+        reply.extend_from_slice(pub_key);
+        reply.extend(1u32.to_le_bytes());
+        reply.extend(0u32.to_le_bytes());
+        reply.extend(((4 + SPICE_TICKET_PUBKEY_BYTES + 12) as u32).to_le_bytes());
+        reply.extend(common_caps.to_le_bytes());
+        reply
+    }
+
+    /// 模拟服务器: 校验链接消息后发送 `reply`, 再交给 `auth` 处理认证阶段
+    async fn fake_server<F, Fut>(reply: Vec<u8>, auth: F) -> u16
+    where
+        F: FnOnce(TcpStream) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            let mut header = [0u8; 16];
+            stream.read_exact(&mut header).await.unwrap();
+            let header = SpiceLinkHeader::from_bytes(&header).unwrap();
+            assert!(header.is_valid());
+            let mut link = vec![0u8; header.size as usize];
+            stream.read_exact(&mut link).await.unwrap();
+            assert_eq!(link, hex(MAIN_LINK_MESS));
+
+            stream.write_all(&SpiceLinkHeader::new(reply.len() as u32).to_bytes()).await.unwrap();
+            stream.write_all(&reply).await.unwrap();
+            auth(stream).await;
+        });
+        port
+    }
+
+    /// 读取认证方式与票据, 返回解密后的明文
+    async fn read_ticket(stream: &mut TcpStream, key: &RsaPrivateKey) -> Vec<u8> {
+        let mut mechanism = [0u8; 4];
+        stream.read_exact(&mut mechanism).await.unwrap();
+        assert_eq!(u32::from_le_bytes(mechanism), auth_mechanism::SPICE);
+
+        let mut ticket = [0u8; RSA_KEY_SIZE / 8];
+        stream.read_exact(&mut ticket).await.unwrap();
+        key.decrypt(Oaep::new::<Sha1>(), &ticket).unwrap()
+    }
+
+    fn test_key() -> (RsaPrivateKey, Vec<u8>) {
+        let key = RsaPrivateKey::new(&mut rand::thread_rng(), RSA_KEY_SIZE).unwrap();
+        let der = key.to_public_key().to_public_key_der().unwrap().as_bytes().to_vec();
+        assert_eq!(der.len(), SPICE_TICKET_PUBKEY_BYTES);
+        (key, der)
+    }
+
+    #[tokio::test]
+    async fn test_handshake_sends_encrypted_ticket() {
+        let (key, der) = test_key();
+        let caps = common_caps::AUTH_SELECT | common_caps::AUTH_SPICE;
+        let port = fake_server(link_reply(&der, caps), move |mut stream| async move {
+            let ticket = read_ticket(&mut stream, &key).await;
+            assert_eq!(ticket, b"s3cret\0");
+            stream.write_all(&link_error::OK.to_le_bytes()).await.unwrap();
+        })
+        .await;
+
+        let mut conn = ChannelConnection::new(ChannelType::Main, 0);
+        conn.connect("127.0.0.1", port, 0, Some("s3cret")).await.unwrap();
+        assert!(conn.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejected_password() {
+        let (key, der) = test_key();
+        let caps = common_caps::AUTH_SELECT | common_caps::AUTH_SPICE;
+        let port = fake_server(link_reply(&der, caps), move |mut stream| async move {
+            read_ticket(&mut stream, &key).await;
+            stream.write_all(&link_error::PERMISSION_DENIED.to_le_bytes()).await.unwrap();
+        })
+        .await;
+
+        let mut conn = ChannelConnection::new(ChannelType::Main, 0);
+        let err = conn.connect("127.0.0.1", port, 0, Some("wrong")).await.unwrap_err();
+        assert!(matches!(err, ProtocolError::AuthFailed(_)), "{}", err);
+        assert!(!conn.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_detects_sasl_only_server() {
+        let port = fake_server(sasl_only_reply(), |_| async {}).await;
+
+        let mut conn = ChannelConnection::new(ChannelType::Main, 0);
+        let err = conn.connect("127.0.0.1", port, 0, Some("s3cret")).await.unwrap_err();
+        assert!(matches!(&err, ProtocolError::AuthRequired(mechanism) if mechanism == "sasl"), "{}", err);
+    }
+
+    #[test]
+    fn test_link_error_mapping() {
+        assert!(matches!(link_error(link_error::PERMISSION_DENIED), ProtocolError::AuthFailed(_)));
+        assert!(link_error(link_error::NEED_SECURED).to_string().contains("TLS"));
+        assert!(encrypt_ticket(&"x".repeat(PASSWORD_MAX_LEN + 1), &[]).is_err());
+    }
 }
@@ -0,0 +1,434 @@
+//! SPICE 通道状态管理
+//!
+//! 同一会话的各通道共用主通道分配的 session_id, 单个通道断开后可以用该 session_id 单独重连,
+//! 不影响其他通道。[`ChannelManager`] 记录每个通道的状态, 负责按 [`ReconnectPolicy`] 重试,
+//! 并让等待中的调用方在重连完成 (或失败) 时得到通知。
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+use super::channel::ChannelType;
+use crate::{ProtocolError, Result};
+
+/// 通道状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelState {
+    /// 已连接
+    Connected,
+    /// 连接已断开, 尚未重连
+    Disconnected,
+    /// 正在重连
+    Reconnecting,
+    /// 重连失败 (已用尽重试次数或不可重试)
+    Failed,
+}
+
+impl ChannelState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Connected => "connected",
+            Self::Disconnected => "disconnected",
+            Self::Reconnecting => "reconnecting",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+/// 单个通道的状态快照
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelStatus {
+    /// 通道类型
+    pub channel_type: ChannelType,
+    /// 通道 ID
+    pub channel_id: u8,
+    /// 当前状态
+    pub state: ChannelState,
+    /// 成功重连的次数
+    pub reconnects: u32,
+    /// 最近一次断开或重连失败的原因
+    pub last_error: Option<String>,
+}
+
+/// 单个通道的重连策略
+#[derive(Debug, Clone)]
+pub struct ReconnectPolicy {
+    /// 最多尝试次数
+    pub max_attempts: u32,
+    /// 首次重试前的等待时间, 之后每次翻倍
+    pub initial_backoff: Duration,
+    /// 重试等待时间上限
+    pub max_backoff: Duration,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// 第 `attempt` 次失败后的等待时间 (从 1 开始)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32 << attempt.saturating_sub(1).min(16);
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+type ChannelKey = (ChannelType, u8);
+
+/// SPICE 通道状态管理器
+///
+/// 使用内部可变性, 可以在不持有 [`SpiceClient`](super::SpiceClient) 锁的情况下查询状态或等待重连。
+pub struct ChannelManager {
+    policy: ReconnectPolicy,
+    channels: Mutex<HashMap<ChannelKey, watch::Sender<ChannelStatus>>>,
+}
+
+impl ChannelManager {
+    pub fn new(policy: ReconnectPolicy) -> Self {
+        Self {
+            policy,
+            channels: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 重连策略
+    pub fn policy(&self) -> &ReconnectPolicy {
+        &self.policy
+    }
+
+    /// 更新通道状态, 通道未登记时先登记
+    fn update(&self, channel_type: ChannelType, channel_id: u8, f: impl FnOnce(&mut ChannelStatus)) {
+        let mut channels = self.channels.lock().unwrap();
+        let sender = channels.entry((channel_type, channel_id)).or_insert_with(|| {
+            watch::channel(ChannelStatus {
+                channel_type,
+                channel_id,
+                state: ChannelState::Disconnected,
+                reconnects: 0,
+                last_error: None,
+            })
+            .0
+        });
+        sender.send_modify(f);
+    }
+
+    /// 标记通道已连接
+    pub fn mark_connected(&self, channel_type: ChannelType, channel_id: u8) {
+        self.update(channel_type, channel_id, |status| {
+            status.state = ChannelState::Connected;
+        });
+    }
+
+    /// 标记通道连接已断开
+    pub fn mark_disconnected(&self, channel_type: ChannelType, channel_id: u8, reason: &str) {
+        self.update(channel_type, channel_id, |status| {
+            status.state = ChannelState::Disconnected;
+            status.last_error = Some(reason.to_string());
+        });
+    }
+
+    /// 标记通道重连失败
+    pub fn mark_failed(&self, channel_type: ChannelType, channel_id: u8, reason: &str) {
+        self.update(channel_type, channel_id, |status| {
+            status.state = ChannelState::Failed;
+            status.last_error = Some(reason.to_string());
+        });
+    }
+
+    /// 移除通道 (主动断开时)
+    pub fn remove(&self, channel_type: ChannelType, channel_id: u8) {
+        self.channels.lock().unwrap().remove(&(channel_type, channel_id));
+    }
+
+    /// 移除所有通道
+    pub fn clear(&self) {
+        self.channels.lock().unwrap().clear();
+    }
+
+    /// 通道状态, 未登记时为 `None`
+    pub fn state(&self, channel_type: ChannelType, channel_id: u8) -> Option<ChannelState> {
+        self.status(channel_type, channel_id).map(|status| status.state)
+    }
+
+    /// 通道状态快照
+    pub fn status(&self, channel_type: ChannelType, channel_id: u8) -> Option<ChannelStatus> {
+        let channels = self.channels.lock().unwrap();
+        channels.get(&(channel_type, channel_id)).map(|sender| sender.borrow().clone())
+    }
+
+    /// 所有通道的状态 (按通道类型与 ID 排序)
+    pub fn states(&self) -> Vec<ChannelStatus> {
+        let channels = self.channels.lock().unwrap();
+        let mut states: Vec<ChannelStatus> = channels.values().map(|sender| sender.borrow().clone()).collect();
+        states.sort_by_key(|status| (status.channel_type.to_u8(), status.channel_id));
+        states
+    }
+
+    /// 等待通道可用
+    ///
+    /// 已连接时立即返回; 正在重连时最多等待 `timeout`; 未登记、已断开或重连失败时返回错误。
+    pub async fn wait_ready(&self, channel_type: ChannelType, channel_id: u8, timeout: Duration) -> Result<()> {
+        let receiver = {
+            let channels = self.channels.lock().unwrap();
+            channels.get(&(channel_type, channel_id)).map(|sender| sender.subscribe())
+        };
+        let Some(mut receiver) = receiver else {
+            return Err(ProtocolError::ConnectionFailed(format!("{} 通道未连接", channel_type.name())));
+        };
+
+        let waited = tokio::time::timeout(timeout, async {
+            loop {
+                let status = receiver.borrow_and_update().clone();
+                match status.state {
+                    ChannelState::Connected => return Ok(()),
+                    ChannelState::Reconnecting => {}
+                    ChannelState::Disconnected | ChannelState::Failed => {
+                        return Err(ProtocolError::ConnectionFailed(format!(
+                            "{} 通道已断开{}",
+                            channel_type.name(),
+                            status.last_error.map(|e| format!(": {}", e)).unwrap_or_default()
+                        )));
+                    }
+                }
+                if receiver.changed().await.is_err() {
+                    // 通道已被移除 (客户端断开)
+                    return Err(ProtocolError::ConnectionFailed(format!("{} 通道已关闭", channel_type.name())));
+                }
+            }
+        })
+        .await;
+
+        waited.unwrap_or_else(|_| {
+            Err(ProtocolError::ConnectionFailed(format!(
+                "{} 通道正在重连, 等待 {} ms 后仍未完成",
+                channel_type.name(),
+                timeout.as_millis()
+            )))
+        })
+    }
+
+    /// 按重连策略重连单个通道
+    ///
+    /// `connect` 每次调用建立一个新的通道连接。重连期间状态为 [`ChannelState::Reconnecting`],
+    /// 成功后为 [`ChannelState::Connected`], 用尽重试次数或遇到认证错误时为 [`ChannelState::Failed`]。
+    pub async fn reconnect<T, F, Fut>(&self, channel_type: ChannelType, channel_id: u8, mut connect: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        self.update(channel_type, channel_id, |status| {
+            status.state = ChannelState::Reconnecting;
+        });
+
+        let max_attempts = self.policy.max_attempts.max(1);
+        let mut attempt = 0;
+        let error = loop {
+            attempt += 1;
+            match connect().await {
+                Ok(channel) => {
+                    self.update(channel_type, channel_id, |status| {
+                        status.state = ChannelState::Connected;
+                        status.reconnects += 1;
+                    });
+                    info!("{} 通道 {} 已重连 (第 {} 次尝试)", channel_type.name(), channel_id, attempt);
+                    return Ok(channel);
+                }
+                // 认证错误重试也不会成功 (错误可能带有上下文, 按原始错误判断)
+                Err(e) if matches!(e.root(), ProtocolError::AuthFailed(_) | ProtocolError::AuthRequired(_)) => break e,
+                Err(e) if attempt >= max_attempts => break e,
+                Err(e) => {
+                    let backoff = self.policy.backoff(attempt);
+                    warn!(
+                        "{} 通道 {} 重连失败 (第 {}/{} 次): {}, {} ms 后重试",
+                        channel_type.name(),
+                        channel_id,
+                        attempt,
+                        max_attempts,
+                        e,
+                        backoff.as_millis()
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        };
+
+        self.mark_failed(channel_type, channel_id, &error.to_string());
+        Err(error)
+    }
+}
+
+impl Default for ChannelManager {
+    fn default() -> Self {
+        Self::new(ReconnectPolicy::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorContext;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    fn fast_policy(max_attempts: u32) -> ReconnectPolicy {
+        ReconnectPolicy {
+            max_attempts,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = ReconnectPolicy::default();
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(400));
+        assert_eq!(policy.backoff(10), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_states_sorted() {
+        let manager = ChannelManager::default();
+        manager.mark_connected(ChannelType::Inputs, 0);
+        manager.mark_connected(ChannelType::Main, 0);
+        manager.mark_disconnected(ChannelType::Display, 0, "reset");
+
+        let states: Vec<_> = manager.states().iter().map(|s| (s.channel_type, s.state)).collect();
+        assert_eq!(
+            states,
+            [
+                (ChannelType::Main, ChannelState::Connected),
+                (ChannelType::Display, ChannelState::Disconnected),
+                (ChannelType::Inputs, ChannelState::Connected),
+            ]
+        );
+
+        manager.remove(ChannelType::Display, 0);
+        assert_eq!(manager.state(ChannelType::Display, 0), None);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_retries_until_success() {
+        let manager = ChannelManager::new(fast_policy(3));
+        manager.mark_disconnected(ChannelType::Inputs, 0, "broken pipe");
+
+        let attempts = AtomicU32::new(0);
+        let channel = manager
+            .reconnect(ChannelType::Inputs, 0, || async {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err(ProtocolError::ConnectionFailed("refused".to_string()))
+                } else {
+                    Ok("inputs")
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(channel, "inputs");
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        let status = manager.status(ChannelType::Inputs, 0).unwrap();
+        assert_eq!(status.state, ChannelState::Connected);
+        assert_eq!(status.reconnects, 1);
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_gives_up() {
+        let manager = ChannelManager::new(fast_policy(2));
+        manager.mark_connected(ChannelType::Main, 0);
+
+        let attempts = AtomicU32::new(0);
+        let err = manager
+            .reconnect(ChannelType::Inputs, 0, || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(ProtocolError::ConnectionFailed("refused".to_string()))
+            })
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("refused"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(manager.state(ChannelType::Inputs, 0), Some(ChannelState::Failed));
+        // 其他通道不受影响
+        assert_eq!(manager.state(ChannelType::Main, 0), Some(ChannelState::Connected));
+
+        // 认证错误不重试
+        let attempts = AtomicU32::new(0);
+        manager
+            .reconnect(ChannelType::Display, 0, || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(ProtocolError::AuthFailed("ticket expired".to_string()))
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        // 带上下文的认证错误同样不重试
+        let attempts = AtomicU32::new(0);
+        let err = manager
+            .reconnect(ChannelType::Display, 0, || async {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Err::<(), _>(
+                    ProtocolError::AuthRequired("sasl".to_string())
+                        .with_context(ErrorContext::new().with_operation("spice_connect")),
+                )
+            })
+            .await
+            .unwrap_err();
+        assert!(err.context().is_some());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_wait_ready_follows_reconnect() {
+        let manager = Arc::new(ChannelManager::new(fast_policy(1)));
+        manager.mark_connected(ChannelType::Inputs, 0);
+        manager.wait_ready(ChannelType::Inputs, 0, Duration::from_millis(10)).await.unwrap();
+
+        // 重连进行中: 等待直到完成
+        let (release_tx, release_rx) = tokio::sync::oneshot::channel::<()>();
+        let reconnecting = {
+            let manager = manager.clone();
+            tokio::spawn(async move {
+                let mut release = Some(release_rx);
+                manager
+                    .reconnect(ChannelType::Inputs, 0, || {
+                        let release = release.take();
+                        async move {
+                            release.unwrap().await.unwrap();
+                            Ok(())
+                        }
+                    })
+                    .await
+            })
+        };
+        while manager.state(ChannelType::Inputs, 0) != Some(ChannelState::Reconnecting) {
+            tokio::task::yield_now().await;
+        }
+
+        let err = manager.wait_ready(ChannelType::Inputs, 0, Duration::from_millis(10)).await.unwrap_err();
+        assert!(err.to_string().contains("正在重连"), "{}", err);
+
+        let waiter = {
+            let manager = manager.clone();
+            tokio::spawn(async move { manager.wait_ready(ChannelType::Inputs, 0, Duration::from_secs(5)).await })
+        };
+        release_tx.send(()).unwrap();
+        reconnecting.await.unwrap().unwrap();
+        waiter.await.unwrap().unwrap();
+
+        // 失败或未登记的通道立即报错
+        manager.mark_failed(ChannelType::Inputs, 0, "refused");
+        let err = manager.wait_ready(ChannelType::Inputs, 0, Duration::from_secs(5)).await.unwrap_err();
+        assert!(err.to_string().contains("refused"), "{}", err);
+        assert!(manager.wait_ready(ChannelType::Display, 0, Duration::from_secs(5)).await.is_err());
+    }
+}
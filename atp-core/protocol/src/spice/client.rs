@@ -9,6 +9,7 @@ use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
 
 use super::channel::{ChannelConnection, ChannelType};
+use super::channel_manager::{ChannelManager, ChannelState, ChannelStatus, ReconnectPolicy};
 use super::inputs::InputsChannel;
 use super::display::DisplayChannel;
 use super::usbredir::UsbRedirChannel;
@@ -33,6 +34,8 @@ pub struct SpiceConfig {
     pub auto_inputs: bool,
     /// 请求客户端鼠标模式
     pub request_client_mouse: bool,
+    /// 单个通道断开后的重连策略
+    pub reconnect: ReconnectPolicy,
 }
 
 impl SpiceConfig {
@@ -46,6 +49,7 @@ impl SpiceConfig {
             auto_display: true,
             auto_inputs: true,
             request_client_mouse: true,
+            reconnect: ReconnectPolicy::default(),
         }
     }
 
@@ -86,6 +90,11 @@ impl SpiceConfig {
         self.request_client_mouse = client_mouse;
         self
     }
+
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect = policy;
+        self
+    }
 }
 
 /// SPICE 客户端状态
@@ -123,12 +132,15 @@ pub struct SpiceClient {
     usbredir_channels: HashMap<u8, UsbRedirChannel>,
     /// 可用通道列表
     available_channels: Vec<(u8, u8)>, // (type, id)
+    /// 各通道状态 (与持有客户端锁的调用方共享)
+    channels: Arc<ChannelManager>,
 }
 
 impl SpiceClient {
     /// 创建新的 SPICE 客户端
     pub fn new(config: SpiceConfig) -> Self {
         Self {
+            channels: Arc::new(ChannelManager::new(config.reconnect.clone())),
             config,
             state: ClientState::Disconnected,
             session_id: 0,
@@ -246,20 +258,28 @@ impl SpiceClient {
         //   - TLS 连接建立后，SPICE 协议握手流程与非 TLS 相同
         //
 
-        // 1. 连接主通道
+        // 1. 连接主通道 (认证失败等错误时回到未连接状态, 允许重试)
         let mut main_channel = ChannelConnection::new(ChannelType::Main, 0);
-        main_channel.connect(
+        let connected = main_channel.connect(
             &self.config.host,
             self.config.port,
             0, // 初始连接 ID 为 0
             self.config.password.as_deref(),
-        ).await?;
+        ).await;
+        if let Err(e) = connected {
+            self.state = ClientState::Disconnected;
+            return Err(e);
+        }
 
         // 2. 处理主通道初始化
-        self.handle_main_init(&mut main_channel).await?;
+        if let Err(e) = self.handle_main_init(&mut main_channel).await {
+            self.state = ClientState::Disconnected;
+            return Err(e);
+        }
 
         self.main_channel = Some(main_channel);
         self.state = ClientState::Connected;
+        self.channels.mark_connected(ChannelType::Main, 0);
 
         // 3. 自动连接其他通道
         if self.config.auto_inputs {
@@ -348,6 +368,7 @@ impl SpiceClient {
         ).await?;
 
         self.inputs_channel = Some(inputs);
+        self.channels.mark_connected(ChannelType::Inputs, 0);
         info!("输入通道已连接");
         Ok(())
     }
@@ -367,6 +388,7 @@ impl SpiceClient {
         ).await?;
 
         self.display_channels.insert(id, display);
+        self.channels.mark_connected(ChannelType::Display, id);
         info!("显示通道 {} 已连接", id);
         Ok(())
     }
@@ -386,6 +408,7 @@ impl SpiceClient {
         ).await?;
 
         self.usbredir_channels.insert(id, usbredir);
+        self.channels.mark_connected(ChannelType::Usbredir, id);
         info!("USB 重定向通道 {} 已连接", id);
         Ok(())
     }
@@ -423,6 +446,7 @@ impl SpiceClient {
         self.state = ClientState::Disconnected;
         self.session_id = 0;
         self.available_channels.clear();
+        self.channels.clear();
 
         info!("SPICE 连接已断开");
         Ok(())
@@ -460,9 +484,111 @@ impl SpiceClient {
         self.state
     }
 
-    /// 是否已连接
+    /// 是否已连接 (以主通道的连接状态为准)
     pub fn is_connected(&self) -> bool {
         self.state == ClientState::Connected
+            && self.main_channel.as_ref().is_some_and(|main| main.is_connected())
+    }
+
+    /// 输入通道是否可用
+    pub fn inputs_connected(&self) -> bool {
+        self.inputs_channel.as_ref().is_some_and(|inputs| inputs.is_connected())
+    }
+
+    /// 通道状态管理器
+    ///
+    /// 可在不持有客户端锁的情况下查询通道状态或等待重连完成
+    pub fn channel_manager(&self) -> Arc<ChannelManager> {
+        self.channels.clone()
+    }
+
+    /// 各通道的当前状态
+    ///
+    /// 登记为已连接、但底层连接已断开的通道会先标记为 [`ChannelState::Disconnected`]
+    pub fn channel_states(&self) -> Vec<ChannelStatus> {
+        let main = self.main_channel.as_ref().map(|main| (ChannelType::Main, 0, main.is_connected()));
+        let inputs = self.inputs_channel.as_ref().map(|inputs| (ChannelType::Inputs, 0, inputs.is_connected()));
+        let displays = self.display_channels.iter().map(|(&id, display)| (ChannelType::Display, id, display.is_connected()));
+        let usbredirs = self.usbredir_channels.iter().map(|(&id, usbredir)| (ChannelType::Usbredir, id, usbredir.is_connected()));
+
+        for (channel_type, id, connected) in main.into_iter().chain(inputs).chain(displays).chain(usbredirs) {
+            if !connected && self.channels.state(channel_type, id) == Some(ChannelState::Connected) {
+                self.channels.mark_disconnected(channel_type, id, "连接已断开");
+            }
+        }
+
+        self.channels.states()
+    }
+
+    /// 用当前会话 ID 重连单个通道, 不影响其他通道
+    ///
+    /// 主通道断开后会话失效, 只能重新连接整个客户端
+    pub async fn reconnect_channel(&mut self, channel_type: ChannelType, channel_id: u8) -> Result<()> {
+        if channel_type == ChannelType::Main {
+            return Err(ProtocolError::ConnectionFailed(
+                "主通道断开后会话失效, 需要重新连接客户端".to_string()
+            ));
+        }
+        if !self.is_connected() {
+            let reason = "主通道未连接, 无法重连单个通道";
+            self.channels.mark_failed(channel_type, channel_id, reason);
+            return Err(ProtocolError::ConnectionFailed(reason.to_string()));
+        }
+
+        let channels = self.channels.clone();
+        let host = self.config.host.clone();
+        let password = self.config.password.clone();
+        let (host, port, password) = (host.as_str(), self.config.port, password.as_deref());
+        let session_id = self.session_id;
+
+        match channel_type {
+            ChannelType::Inputs => {
+                if let Some(mut old) = self.inputs_channel.take() {
+                    let _ = old.disconnect().await;
+                }
+                let inputs = channels.reconnect(channel_type, channel_id, move || async move {
+                    let mut inputs = InputsChannel::new(channel_id);
+                    inputs.connect(host, port, session_id, password).await?;
+                    Ok(inputs)
+                }).await?;
+                self.inputs_channel = Some(inputs);
+            }
+            ChannelType::Display => {
+                if let Some(mut old) = self.display_channels.remove(&channel_id) {
+                    let _ = old.disconnect().await;
+                }
+                let display = channels.reconnect(channel_type, channel_id, move || async move {
+                    let mut display = DisplayChannel::new(channel_id);
+                    display.connect(host, port, session_id, password).await?;
+                    Ok(display)
+                }).await?;
+                self.display_channels.insert(channel_id, display);
+            }
+            ChannelType::Usbredir => {
+                if let Some(mut old) = self.usbredir_channels.remove(&channel_id) {
+                    let _ = old.disconnect().await;
+                }
+                let usbredir = channels.reconnect(channel_type, channel_id, move || async move {
+                    let mut usbredir = UsbRedirChannel::new(channel_id);
+                    usbredir.connect(host, port, session_id, password).await?;
+                    Ok(usbredir)
+                }).await?;
+                self.usbredir_channels.insert(channel_id, usbredir);
+            }
+            other => {
+                return Err(ProtocolError::ConnectionFailed(format!("不支持重连 {} 通道", other.name())));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 确保输入通道可用, 已断开时重连
+    pub async fn ensure_inputs(&mut self) -> Result<()> {
+        if self.inputs_connected() {
+            return Ok(());
+        }
+        self.reconnect_channel(ChannelType::Inputs, 0).await
     }
 
     /// 获取会话 ID
@@ -497,7 +623,15 @@ impl SpiceClient {
         let main = self.main_channel.as_mut()
             .ok_or_else(|| ProtocolError::ConnectionFailed("主通道未连接".to_string()))?;
 
-        let (msg_type, data) = main.receive_message().await?;
+        let (msg_type, data) = match main.receive_message().await {
+            Ok(message) => message,
+            Err(e) => {
+                if !main.is_connected() {
+                    self.channels.mark_disconnected(ChannelType::Main, 0, &e.to_string());
+                }
+                return Err(e);
+            }
+        };
 
         match msg_type {
             // SPICE_MSG_MAIN_MOUSE_MODE
@@ -547,6 +681,11 @@ impl SharedSpiceClient {
         client.is_connected()
     }
 
+    pub async fn channel_states(&self) -> Vec<ChannelStatus> {
+        let client = self.inner.read().await;
+        client.channel_states()
+    }
+
     /// 获取内部引用
     pub fn inner(&self) -> Arc<RwLock<SpiceClient>> {
         self.inner.clone()
@@ -578,6 +717,22 @@ mod tests {
 
         assert_eq!(client.state(), ClientState::Disconnected);
         assert!(!client.is_connected());
+        assert!(!client.inputs_connected());
         assert_eq!(client.session_id(), 0);
+        assert!(client.channel_states().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_reconnect_requires_main_channel() {
+        let mut client = SpiceClient::new(SpiceConfig::new("localhost", 5900));
+
+        let err = client.reconnect_channel(ChannelType::Main, 0).await.unwrap_err();
+        assert!(err.to_string().contains("重新连接客户端"), "{}", err);
+
+        let err = client.ensure_inputs().await.unwrap_err();
+        assert!(err.to_string().contains("主通道未连接"), "{}", err);
+        let states = client.channel_states();
+        assert_eq!(states.len(), 1);
+        assert_eq!((states[0].channel_type, states[0].state), (ChannelType::Inputs, ChannelState::Failed));
     }
 }
@@ -198,6 +198,12 @@ pub mod common_caps {
     pub const MINI_HEADER: u32 = 1 << 3;
 }
 
+/// 认证方式 (SpiceLinkAuthMechanism), 双方都声明 `AUTH_SELECT` 时由客户端在票据前发送
+pub mod auth_mechanism {
+    pub const SPICE: u32 = 1;
+    pub const SASL: u32 = 2;
+}
+
 /// Inputs 通道能力
 pub mod inputs_caps {
     pub const KEY_SCANCODE: u32 = 1 << 0;
@@ -260,5 +266,8 @@ pub const MOTION_ACK_BUNCH: u32 = 8;
 /// RSA 公钥大小 (位)
 pub const RSA_KEY_SIZE: usize = 1024;
 
+/// 链接回复中公钥的长度 (DER 编码的 1024 位 RSA 公钥)
+pub const SPICE_TICKET_PUBKEY_BYTES: usize = 162;
+
 /// 密码最大长度
 pub const PASSWORD_MAX_LEN: usize = 60;
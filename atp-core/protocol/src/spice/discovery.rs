@@ -6,6 +6,7 @@
 //! - 获取宿主机 IP
 
 use crate::{ProtocolError, Result};
+use atp_transport::DomainInspection;
 use tracing::{debug, info, warn};
 use virt::connect::Connect;
 use virt::domain::Domain;
@@ -100,168 +101,36 @@ impl SpiceDiscovery {
 
     /// 解析 XML 获取 SPICE 配置
     fn parse_spice_from_xml(&self, xml: &str, name: &str, uuid: &str) -> Result<SpiceVmInfo> {
-        // TODO: 使用完整的 XML 解析器（如 quick-xml）以获得更好的稳定性
-        //
-        // 推荐实现：
-        // 1. 添加依赖到 Cargo.toml:
-        //    quick-xml = "0.31"
-        //
-        // 2. 使用 quick-xml 解析：
-        //    ```rust
-        //    use quick_xml::Reader;
-        //    use quick_xml::events::Event;
-        //
-        //    let mut reader = Reader::from_str(xml);
-        //    reader.trim_text(true);
-        //
-        //    let mut buf = Vec::new();
-        //    let mut port = None;
-        //    let mut tls_port = None;
-        //    let mut listen = None;
-        //    let mut password = None;
-        //
-        //    loop {
-        //        match reader.read_event_into(&mut buf) {
-        //            Ok(Event::Empty(e)) | Ok(Event::Start(e)) => {
-        //                if e.name().as_ref() == b"graphics" {
-        //                    // 检查 type='spice'
-        //                    let is_spice = e.attributes()
-        //                        .filter_map(|a| a.ok())
-        //                        .any(|a| a.key.as_ref() == b"type" && a.value.as_ref() == b"spice");
-        //
-        //                    if is_spice {
-        //                        // 提取所有 SPICE 相关属性
-        //                        for attr in e.attributes().filter_map(|a| a.ok()) {
-        //                            match attr.key.as_ref() {
-        //                                b"port" => {
-        //                                    port = String::from_utf8_lossy(&attr.value)
-        //                                        .parse::<i32>().ok()
-        //                                        .filter(|&p| p > 0)
-        //                                        .map(|p| p as u16);
-        //                                }
-        //                                b"tlsPort" => {
-        //                                    tls_port = String::from_utf8_lossy(&attr.value)
-        //                                        .parse::<i32>().ok()
-        //                                        .filter(|&p| p > 0)
-        //                                        .map(|p| p as u16);
-        //                                }
-        //                                b"listen" => {
-        //                                    listen = Some(String::from_utf8_lossy(&attr.value).to_string());
-        //                                }
-        //                                b"passwd" => {
-        //                                    password = Some(String::from_utf8_lossy(&attr.value).to_string());
-        //                                }
-        //                                _ => {}
-        //                            }
-        //                        }
-        //                        break; // 找到 SPICE 配置后退出
-        //                    }
-        //                }
-        //            }
-        //            Ok(Event::Eof) => break,
-        //            Err(e) => return Err(ProtocolError::ParseError(
-        //                format!("XML 解析错误: {}", e)
-        //            )),
-        //            _ => {}
-        //        }
-        //        buf.clear();
-        //    }
-        //
-        //    let port = port.ok_or_else(|| ProtocolError::ConnectionFailed(
-        //        "SPICE 端口未配置".to_string()
-        //    ))?;
-        //    ```
-        //
-        // 3. 处理嵌套的 <listen> 元素:
-        //    有些虚拟机配置使用嵌套的 listen 元素而非属性
-        //    <graphics type='spice' port='5900'>
-        //        <listen type='address' address='192.168.1.100'/>
-        //    </graphics>
-        //    需要在解析时处理这种情况
-        //
-        // 目前使用简单的字符串解析
+        let inspection = DomainInspection::parse(xml)
+            .map_err(|e| ProtocolError::ParseError(e.to_string()))?;
 
-        // 查找 <graphics type='spice' ...>
-        let graphics_start = xml.find("<graphics type='spice'")
-            .or_else(|| xml.find("<graphics type=\"spice\""))
+        let graphics = inspection.graphics("spice")
             .ok_or_else(|| ProtocolError::ConnectionFailed(
                 "虚拟机未配置 SPICE 图形".to_string()
             ))?;
 
-        let graphics_end = xml[graphics_start..].find("/>")
-            .or_else(|| xml[graphics_start..].find("</graphics>"))
-            .map(|pos| graphics_start + pos)
-            .ok_or_else(|| ProtocolError::ParseError(
-                "无法解析 SPICE 图形配置".to_string()
-            ))?;
-
-        let graphics_xml = &xml[graphics_start..graphics_end];
-
-        // 解析端口
-        let port = self.extract_attr(graphics_xml, "port")
-            .and_then(|s| s.parse::<i32>().ok())
-            .filter(|&p| p > 0)
-            .map(|p| p as u16)
+        let port = graphics.port
             .ok_or_else(|| ProtocolError::ConnectionFailed(
                 "SPICE 端口未配置或无效".to_string()
             ))?;
 
-        // 解析 TLS 端口
-        let tls_port = self.extract_attr(graphics_xml, "tlsPort")
-            .and_then(|s| s.parse::<i32>().ok())
-            .filter(|&p| p > 0)
-            .map(|p| p as u16);
-
-        // 解析监听地址
-        let listen = self.extract_attr(graphics_xml, "listen")
-            .unwrap_or_else(|| self.default_host.clone());
-
-        let host = if listen == "0.0.0.0" || listen.is_empty() {
-            self.default_host.clone()
-        } else {
-            listen
+        // 监听所有地址时通过默认宿主机地址访问
+        let host = match graphics.listen.as_deref() {
+            Some(listen) if listen != "0.0.0.0" => listen.to_string(),
+            _ => self.default_host.clone(),
         };
 
-        // 检查密码
-        let password = self.extract_attr(graphics_xml, "passwd");
-
-        // 检查 TLS 配置
-        let tls_enabled = tls_port.is_some();
-
         Ok(SpiceVmInfo {
             name: name.to_string(),
             uuid: uuid.to_string(),
             host,
             port,
-            tls_port,
-            password,
-            tls_enabled,
+            tls_port: graphics.tls_port,
+            password: graphics.password.clone(),
+            tls_enabled: graphics.tls_port.is_some(),
         })
     }
 
-    /// 从 XML 属性中提取值
-    fn extract_attr(&self, xml: &str, attr: &str) -> Option<String> {
-        // 尝试单引号
-        let pattern1 = format!("{}='", attr);
-        if let Some(start) = xml.find(&pattern1) {
-            let value_start = start + pattern1.len();
-            if let Some(end) = xml[value_start..].find('\'') {
-                return Some(xml[value_start..value_start + end].to_string());
-            }
-        }
-
-        // 尝试双引号
-        let pattern2 = format!("{}=\"", attr);
-        if let Some(start) = xml.find(&pattern2) {
-            let value_start = start + pattern2.len();
-            if let Some(end) = xml[value_start..].find('"') {
-                return Some(xml[value_start..value_start + end].to_string());
-            }
-        }
-
-        None
-    }
-
     /// 设置 SPICE 密码
     ///
     /// 通过 libvirt API 设置虚拟机的 SPICE 访问密码
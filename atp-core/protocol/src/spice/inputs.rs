@@ -2,8 +2,12 @@
 //!
 //! 实现键盘和鼠标输入事件的发送。
 
+use crate::keymap::{KeyCombo, KeyMapper, KeyboardLayout};
+use crate::qmp::DEFAULT_HOLD_TIME_MS;
 use crate::{ProtocolError, Result};
+use async_trait::async_trait;
 use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
 use tracing::{debug, trace};
 
 use super::channel::{ChannelConnection, ChannelType};
@@ -11,6 +15,50 @@ use super::constants::*;
 use super::messages::*;
 use super::types::*;
 
+/// 键盘按下/释放事件的发送端
+///
+/// 长按逻辑只依赖该 trait，便于与具体通道解耦及测试
+#[async_trait]
+pub trait KeyEventSink: Send + Sync {
+    /// 按下按键
+    async fn key_down(&self, scancode: u32) -> Result<()>;
+
+    /// 释放按键
+    async fn key_up(&self, scancode: u32) -> Result<()>;
+}
+
+/// 按下按键并保持 `hold_ms` 毫秒后释放
+///
+/// 与 QMP send-key 的 hold-time 语义一致，未指定时使用 [`DEFAULT_HOLD_TIME_MS`]。
+/// 保持期间不会提前释放，释放失败会作为错误返回
+pub async fn press_key_with_hold<S: KeyEventSink + ?Sized>(
+    sink: &S,
+    scancode: u32,
+    hold_ms: Option<u32>,
+) -> Result<()> {
+    press_keys_with_hold(sink, &[scancode], hold_ms).await
+}
+
+/// 依次按下一组按键 (修饰键在前), 保持 `hold_ms` 毫秒后按相反顺序释放
+///
+/// 与 QMP send-key 发送组合键的语义一致
+pub async fn press_keys_with_hold<S: KeyEventSink + ?Sized>(
+    sink: &S,
+    scancodes: &[u32],
+    hold_ms: Option<u32>,
+) -> Result<()> {
+    let hold = Duration::from_millis(u64::from(hold_ms.unwrap_or(DEFAULT_HOLD_TIME_MS)));
+
+    for &scancode in scancodes {
+        sink.key_down(scancode).await?;
+    }
+    tokio::time::sleep(hold).await;
+    for &scancode in scancodes.iter().rev() {
+        sink.key_up(scancode).await?;
+    }
+    Ok(())
+}
+
 /// 鼠标按钮
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MouseButton {
@@ -214,6 +262,19 @@ impl InputsChannel {
         Ok(())
     }
 
+    /// 发送按键并按住指定时长（长按场景）
+    pub async fn send_key_hold(&self, scancode: u32, hold_ms: Option<u32>) -> Result<()> {
+        press_key_with_hold(self, scancode, hold_ms).await
+    }
+
+    /// 发送按键组合（如 `ctrl+alt+del`），多个组合以空白分隔时依次发送
+    pub async fn send_key_combo(&self, combo: &str, hold_ms: Option<u32>) -> Result<()> {
+        for stroke in KeyCombo::parse(combo)? {
+            press_keys_with_hold(self, &stroke.scancodes(), hold_ms).await?;
+        }
+        Ok(())
+    }
+
     /// 发送键盘修饰键状态
     pub async fn send_key_modifiers(&self, modifiers: KeyModifiers) -> Result<()> {
         self.check_connected()?;
@@ -225,27 +286,29 @@ impl InputsChannel {
         Ok(())
     }
 
-    /// 发送文本（转换为扫描码序列）
+    /// 发送文本（按美式键盘布局转换为扫描码序列）
     pub async fn send_text(&self, text: &str) -> Result<()> {
-        for ch in text.chars() {
-            if let Some(scancode) = char_to_scancode(ch) {
-                // 检查是否需要 Shift
-                let needs_shift = ch.is_ascii_uppercase()
-                    || "!@#$%^&*()_+{}|:\"<>?~".contains(ch);
-
-                if needs_shift {
-                    self.send_key_down(0x2A).await?; // Left Shift
-                }
+        self.send_text_with_layout(text, KeyboardLayout::EnUs).await
+    }
 
-                self.send_key_press(scancode).await?;
+    /// 按 Guest 的键盘布局发送文本
+    ///
+    /// 文本中有布局无法输入的字符时不发送任何按键, 返回 [`ProtocolError::KeyMapping`]
+    pub async fn send_text_with_layout(&self, text: &str, layout: KeyboardLayout) -> Result<()> {
+        let strokes = KeyMapper::map_strokes(text, layout)?;
 
-                if needs_shift {
-                    self.send_key_up(0x2A).await?;
-                }
+        for stroke in strokes {
+            let scancodes = stroke.scancodes();
 
-                // 按键之间的延迟
-                tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
+            for &scancode in &scancodes {
+                self.send_key_down(scancode).await?;
             }
+            for &scancode in scancodes.iter().rev() {
+                self.send_key_up(scancode).await?;
+            }
+
+            // 按键之间的延迟
+            tokio::time::sleep(tokio::time::Duration::from_millis(20)).await;
         }
         Ok(())
     }
@@ -392,6 +455,86 @@ impl InputsChannel {
     }
 }
 
+#[async_trait]
+impl KeyEventSink for InputsChannel {
+    async fn key_down(&self, scancode: u32) -> Result<()> {
+        self.send_key_down(scancode).await
+    }
+
+    async fn key_up(&self, scancode: u32) -> Result<()> {
+        self.send_key_up(scancode).await
+    }
+}
+
+/// QMP qcode 到扫描码的映射
+///
+/// 用于 QMP 不可用时通过 SPICE 发送同一按键；单字符 qcode 按字符映射
+pub fn qcode_to_scancode(qcode: &str) -> Option<u32> {
+    let mut chars = qcode.chars();
+    if let (Some(ch), None) = (chars.next(), chars.next()) {
+        return char_to_scancode(ch);
+    }
+
+    let code = match qcode {
+        "esc" => scancode::ESCAPE,
+        "backspace" => scancode::BACKSPACE,
+        "tab" => scancode::TAB,
+        "ret" => scancode::ENTER,
+        "spc" => scancode::SPACE,
+        "ctrl" => scancode::LEFT_CTRL,
+        "ctrl_r" => scancode::RIGHT_CTRL,
+        "shift" => scancode::LEFT_SHIFT,
+        "shift_r" => scancode::RIGHT_SHIFT,
+        "alt" => scancode::LEFT_ALT,
+        "alt_r" => scancode::RIGHT_ALT,
+        "caps_lock" => scancode::CAPS_LOCK,
+        "num_lock" => scancode::NUM_LOCK,
+        "scroll_lock" => scancode::SCROLL_LOCK,
+        "f1" => scancode::F1,
+        "f2" => scancode::F2,
+        "f3" => scancode::F3,
+        "f4" => scancode::F4,
+        "f5" => scancode::F5,
+        "f6" => scancode::F6,
+        "f7" => scancode::F7,
+        "f8" => scancode::F8,
+        "f9" => scancode::F9,
+        "f10" => scancode::F10,
+        "f11" => scancode::F11,
+        "f12" => scancode::F12,
+        "insert" => scancode::INSERT,
+        "delete" => scancode::DELETE,
+        "home" => scancode::HOME,
+        "end" => scancode::END,
+        "pgup" => scancode::PAGE_UP,
+        "pgdn" => scancode::PAGE_DOWN,
+        "up" => scancode::UP,
+        "down" => scancode::DOWN,
+        "left" => scancode::LEFT,
+        "right" => scancode::RIGHT,
+        "meta_l" => scancode::LEFT_WIN,
+        "meta_r" => scancode::RIGHT_WIN,
+        "menu" => scancode::MENU,
+        "minus" => char_to_scancode('-')?,
+        "equal" => char_to_scancode('=')?,
+        "comma" => char_to_scancode(',')?,
+        "dot" => char_to_scancode('.')?,
+        "slash" => char_to_scancode('/')?,
+        "bracket_left" => char_to_scancode('[')?,
+        "bracket_right" => char_to_scancode(']')?,
+        "backslash" => char_to_scancode('\\')?,
+        "semicolon" => char_to_scancode(';')?,
+        "apostrophe" => char_to_scancode('\'')?,
+        "grave_accent" => char_to_scancode('`')?,
+        "less" => scancode::INTL_BACKSLASH,
+        "ro" => scancode::INTL_RO,
+        "yen" => scancode::INTL_YEN,
+        _ => return None,
+    };
+
+    Some(code)
+}
+
 /// 字符到扫描码的映射
 fn char_to_scancode(ch: char) -> Option<u32> {
     // PC AT 扫描码集 1
@@ -502,6 +645,11 @@ pub mod scancode {
     // 右侧修饰键
     pub const RIGHT_CTRL: u32 = 0xE01D;
     pub const RIGHT_ALT: u32 = 0xE038;
+
+    // ISO/JIS 键盘额外的按键
+    pub const INTL_BACKSLASH: u32 = 0x56;
+    pub const INTL_RO: u32 = 0x73;
+    pub const INTL_YEN: u32 = 0x7D;
 }
 
 #[cfg(test)]
@@ -539,4 +687,86 @@ mod tests {
         assert_eq!(char_to_scancode(' '), Some(0x39));
         assert_eq!(char_to_scancode('\n'), Some(0x1C));
     }
+
+    #[test]
+    fn test_qcode_to_scancode() {
+        assert_eq!(qcode_to_scancode("a"), Some(0x1E));
+        assert_eq!(qcode_to_scancode("ret"), Some(scancode::ENTER));
+        assert_eq!(qcode_to_scancode("delete"), Some(scancode::DELETE));
+        assert_eq!(qcode_to_scancode("f12"), Some(scancode::F12));
+        assert_eq!(qcode_to_scancode("unknown"), None);
+
+        // 布局映射用到的 qcode 都有扫描码
+        for layout in KeyboardLayout::ALL {
+            let text: String = (' '..='~').chain("äöüßé€²§µ°£¤ù¥".chars()).collect();
+            for ch in text.chars() {
+                if let Some(stroke) = KeyMapper::map_char(ch, layout) {
+                    for qcode in stroke.qcodes() {
+                        assert!(qcode_to_scancode(qcode).is_some(), "{} {:?} {}", layout, ch, qcode);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 记录按键事件及其（虚拟）时间点的 mock
+    #[derive(Default)]
+    struct RecordingSink {
+        events: std::sync::Mutex<Vec<(bool, u32, tokio::time::Instant)>>,
+    }
+
+    #[async_trait]
+    impl KeyEventSink for RecordingSink {
+        async fn key_down(&self, scancode: u32) -> Result<()> {
+            self.events.lock().unwrap().push((true, scancode, tokio::time::Instant::now()));
+            Ok(())
+        }
+
+        async fn key_up(&self, scancode: u32) -> Result<()> {
+            self.events.lock().unwrap().push((false, scancode, tokio::time::Instant::now()));
+            Ok(())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_spice_hold_matches_qmp_hold_time() {
+        use crate::qmp::SendKeyArgs;
+
+        for hold_ms in [None, Some(0), Some(1500)] {
+            let sink = RecordingSink::default();
+            press_key_with_hold(&sink, 0x1E, hold_ms).await.unwrap();
+
+            let events = sink.events.lock().unwrap();
+            assert_eq!(events.len(), 2);
+            assert_eq!((events[0].0, events[0].1), (true, 0x1E));
+            assert_eq!((events[1].0, events[1].1), (false, 0x1E));
+
+            // 两种后端对同一 hold_ms 的实际按住时长一致
+            let qmp_hold = SendKeyArgs::new(&["a"], hold_ms).effective_hold_time();
+            assert_eq!(
+                events[1].2 - events[0].2,
+                Duration::from_millis(u64::from(qmp_hold))
+            );
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_press_combo_releases_in_reverse() {
+        let sink = RecordingSink::default();
+        let stroke = KeyCombo::parse("ctrl+alt+del").unwrap()[0];
+        press_keys_with_hold(&sink, &stroke.scancodes(), Some(200)).await.unwrap();
+
+        let events: Vec<_> = sink.events.lock().unwrap().iter().map(|e| (e.0, e.1)).collect();
+        assert_eq!(
+            events,
+            vec![
+                (true, scancode::LEFT_CTRL),
+                (true, scancode::LEFT_ALT),
+                (true, scancode::DELETE),
+                (false, scancode::DELETE),
+                (false, scancode::LEFT_ALT),
+                (false, scancode::LEFT_CTRL),
+            ]
+        );
+    }
 }
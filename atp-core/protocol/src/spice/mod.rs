@@ -34,12 +34,21 @@
 //! // 发送鼠标移动
 //! client.inputs().send_mouse_position(100, 200, 0).await?;
 //! client.inputs().send_mouse_press(MouseButton::Left).await?;
+//!
+//! // 输入通道断开后单独重连, 主通道与显示通道不受影响
+//! if !client.inputs_connected() {
+//!     client.reconnect_channel(ChannelType::Inputs, 0).await?;
+//! }
+//! for status in client.channel_states() {
+//!     println!("{} {}: {}", status.channel_type.name(), status.channel_id, status.state.name());
+//! }
 //! ```
 
 pub mod types;
 pub mod constants;
 pub mod messages;
 pub mod channel;
+pub mod channel_manager;
 pub mod discovery;
 pub mod client;
 pub mod inputs;
@@ -50,18 +59,25 @@ pub mod usbredir;
 pub use types::*;
 pub use constants::*;
 pub use channel::{SpiceChannel, ChannelType};
+pub use channel_manager::{ChannelManager, ChannelState, ChannelStatus, ReconnectPolicy};
 pub use discovery::{SpiceDiscovery, SpiceVmInfo};
 pub use client::{SpiceClient, SpiceConfig};
-pub use inputs::{InputsChannel, MouseButton, MouseMode, KeyModifiers};
+pub use inputs::{InputsChannel, MouseButton, MouseMode, KeyModifiers, KeyEventSink, qcode_to_scancode};
 pub use display::{DisplayChannel, DisplayConfig};
 pub use usbredir::{UsbRedirChannel, UsbDevice, UsbFilter};
 
-use crate::{Protocol, ProtocolBuilder, ProtocolError, ProtocolType, Result};
+use crate::{KeyboardLayout, Protocol, ProtocolBuilder, ProtocolError, ProtocolType, Result};
 use async_trait::async_trait;
+use atp_vdiplatform::models::SpiceKey;
+use atp_vdiplatform::VdiClient;
 use virt::domain::Domain;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 
+/// 输入通道正在重连时, 发送输入事件前最多等待的时间
+pub const INPUTS_RECONNECT_WAIT: Duration = Duration::from_secs(3);
+
 /// SPICE 协议实现
 ///
 /// 实现 Protocol trait，提供统一的协议接口
@@ -70,8 +86,8 @@ pub struct SpiceProtocol {
     client: Option<Arc<RwLock<SpiceClient>>>,
     /// 配置
     config: Option<SpiceConfig>,
-    /// 连接状态
-    connected: bool,
+    /// 通道状态 (等待重连时不需要持有客户端锁)
+    channels: Option<Arc<ChannelManager>>,
 }
 
 impl SpiceProtocol {
@@ -79,7 +95,7 @@ impl SpiceProtocol {
         Self {
             client: None,
             config: None,
-            connected: false,
+            channels: None,
         }
     }
 
@@ -88,20 +104,67 @@ impl SpiceProtocol {
         Self {
             client: None,
             config: Some(config),
-            connected: false,
+            channels: None,
         }
     }
 
+    /// 按配置连接 (使用配置中的密码作为票据)
+    pub async fn connect_with_config(&mut self, config: SpiceConfig) -> Result<()> {
+        let mut client = SpiceClient::new(config.clone());
+        client.connect().await?;
+
+        self.channels = Some(client.channel_manager());
+        self.client = Some(Arc::new(RwLock::new(client)));
+        self.config = Some(config);
+
+        Ok(())
+    }
+
+    /// 从 VDI 平台获取虚拟机的 SPICE 票据 (地址、端口与密码) 后连接
+    pub async fn connect_with_vdi_ticket(&mut self, vdi: &VdiClient, domain_id: &str) -> Result<()> {
+        let spice_key = vdi
+            .domain()
+            .get_spice_key(domain_id)
+            .await
+            .map_err(|e| ProtocolError::ConnectionFailed(format!("获取虚拟机 {} 的 SPICE 票据失败: {}", domain_id, e)))?;
+        let config = spice_config_from_key(&spice_key)?;
+        self.connect_with_config(config).await
+    }
+
     /// 获取 SPICE 客户端引用
     pub fn client(&self) -> Option<Arc<RwLock<SpiceClient>>> {
         self.client.clone()
     }
 
+    /// 各通道的当前状态
+    pub async fn channel_states(&self) -> Vec<ChannelStatus> {
+        match &self.client {
+            Some(client) => client.read().await.channel_states(),
+            None => Vec::new(),
+        }
+    }
+
+    /// 获取输入通道可用的客户端
+    ///
+    /// 输入通道正在重连时最多等待 [`INPUTS_RECONNECT_WAIT`]; 已断开且没有进行中的重连时由本次调用发起重连。
+    async fn inputs_client(&self) -> Result<Arc<RwLock<SpiceClient>>> {
+        let (Some(client), Some(channels)) = (&self.client, &self.channels) else {
+            return Err(ProtocolError::ConnectionFailed("SPICE 未连接".to_string()));
+        };
+
+        if channels.state(ChannelType::Inputs, 0) == Some(ChannelState::Reconnecting) {
+            channels.wait_ready(ChannelType::Inputs, 0, INPUTS_RECONNECT_WAIT).await?;
+        }
+        if !client.read().await.inputs_connected() {
+            client.write().await.ensure_inputs().await?;
+        }
+
+        Ok(client.clone())
+    }
+
     /// 发送键盘按键
     pub async fn send_key(&self, scancode: u32, pressed: bool) -> Result<()> {
-        let client = self.client.as_ref()
-            .ok_or_else(|| ProtocolError::ConnectionFailed("SPICE 未连接".to_string()))?;
-
+        let client = self.inputs_client().await?;
         let client_guard = client.read().await;
         if pressed {
             client_guard.inputs().send_key_down(scancode).await
@@ -110,20 +173,37 @@ impl SpiceProtocol {
         }
     }
 
+    /// 发送按键并按住指定时长（key_down + 等待 + key_up）
+    pub async fn send_key_hold(&self, scancode: u32, hold_ms: Option<u32>) -> Result<()> {
+        let client = self.inputs_client().await?;
+        let client_guard = client.read().await;
+        client_guard.inputs().send_key_hold(scancode, hold_ms).await
+    }
+
+    /// 发送按键组合（如 `ctrl+alt+del`），按住指定时长后释放
+    pub async fn send_key_combo(&self, combo: &str, hold_ms: Option<u32>) -> Result<()> {
+        let client = self.inputs_client().await?;
+        let client_guard = client.read().await;
+        client_guard.inputs().send_key_combo(combo, hold_ms).await
+    }
+
+    /// 按 Guest 的键盘布局发送文本
+    pub async fn send_text(&self, text: &str, layout: KeyboardLayout) -> Result<()> {
+        let client = self.inputs_client().await?;
+        let client_guard = client.read().await;
+        client_guard.inputs().send_text_with_layout(text, layout).await
+    }
+
     /// 发送鼠标移动
     pub async fn send_mouse_move(&self, x: u32, y: u32, display_id: u8) -> Result<()> {
-        let client = self.client.as_ref()
-            .ok_or_else(|| ProtocolError::ConnectionFailed("SPICE 未连接".to_string()))?;
-
+        let client = self.inputs_client().await?;
         let client_guard = client.read().await;
         client_guard.inputs().send_mouse_position(x, y, display_id).await
     }
 
     /// 发送鼠标点击
     pub async fn send_mouse_click(&self, button: MouseButton, pressed: bool) -> Result<()> {
-        let client = self.client.as_ref()
-            .ok_or_else(|| ProtocolError::ConnectionFailed("SPICE 未连接".to_string()))?;
-
+        let client = self.inputs_client().await?;
         let client_guard = client.read().await;
         if pressed {
             client_guard.inputs().send_mouse_press(button).await
@@ -151,15 +231,7 @@ impl Protocol for SpiceProtocol {
             .with_tls_port(vm_info.tls_port)
             .with_password_opt(vm_info.password);
 
-        // 创建并连接客户端
-        let mut client = SpiceClient::new(config.clone());
-        client.connect().await?;
-
-        self.client = Some(Arc::new(RwLock::new(client)));
-        self.config = Some(config);
-        self.connected = true;
-
-        Ok(())
+        self.connect_with_config(config).await
     }
 
     async fn send(&mut self, data: &[u8]) -> Result<()> {
@@ -190,7 +262,7 @@ impl Protocol for SpiceProtocol {
             client_guard.disconnect().await?;
         }
 
-        self.connected = false;
+        self.channels = None;
         self.config = None;
 
         tracing::info!("SPICE 连接已断开");
@@ -202,10 +274,26 @@ impl Protocol for SpiceProtocol {
     }
 
     async fn is_connected(&self) -> bool {
-        self.connected
+        match &self.client {
+            Some(client) => client.read().await.is_connected(),
+            None => false,
+        }
     }
 }
 
+/// 由平台返回的 SPICE 票据构造连接配置
+fn spice_config_from_key(spice_key: &SpiceKey) -> Result<SpiceConfig> {
+    let port = spice_key.port().ok_or_else(|| {
+        ProtocolError::ConnectionFailed(format!("虚拟机 {} 没有 SPICE 端口 (可能未运行)", spice_key.domain_id))
+    })?;
+    if spice_key.ip.is_empty() {
+        return Err(ProtocolError::ConnectionFailed(format!("虚拟机 {} 的 SPICE 票据缺少地址", spice_key.domain_id)));
+    }
+
+    let password = Some(spice_key.key.clone()).filter(|key| !key.is_empty());
+    Ok(SpiceConfig::new(&spice_key.ip, port).with_password_opt(password))
+}
+
 /// SPICE 协议构建器
 pub struct SpiceProtocolBuilder {
     config: Option<SpiceConfig>,
@@ -245,11 +333,13 @@ impl ProtocolBuilder for SpiceProtocolBuilder {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_spice_protocol_creation() {
+    #[tokio::test]
+    async fn test_spice_protocol_creation() {
         let protocol = SpiceProtocol::new();
         assert_eq!(protocol.protocol_type(), ProtocolType::Spice);
-        assert!(!protocol.connected);
+        assert!(protocol.client().is_none());
+        assert!(protocol.channel_states().await.is_empty());
+        assert!(!protocol.is_connected().await);
     }
 
     #[test]
@@ -263,4 +353,24 @@ mod tests {
         assert_eq!(config.tls_port, Some(5901));
         assert_eq!(config.password, Some("test123".to_string()));
     }
+
+    #[test]
+    fn test_spice_config_from_key() {
+        let key = SpiceKey {
+            domain_id: "vm-1".to_string(),
+            ip: "10.0.0.5".to_string(),
+            port: "5901".to_string(),
+            key: "s3cret".to_string(),
+            ..Default::default()
+        };
+        let config = spice_config_from_key(&key).unwrap();
+        assert_eq!((config.host.as_str(), config.port), ("10.0.0.5", 5901));
+        assert_eq!(config.password.as_deref(), Some("s3cret"));
+
+        let no_password = SpiceKey { key: String::new(), ..key.clone() };
+        assert_eq!(spice_config_from_key(&no_password).unwrap().password, None);
+
+        let stopped = SpiceKey { port: String::new(), ..key };
+        assert!(spice_config_from_key(&stopped).is_err());
+    }
 }
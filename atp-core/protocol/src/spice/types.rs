@@ -4,6 +4,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use super::constants::SPICE_TICKET_PUBKEY_BYTES;
+
 /// SPICE 链接头部 (RedLinkHeader)
 #[derive(Debug, Clone, Copy)]
 #[repr(C, packed)]
@@ -132,12 +134,13 @@ impl SpiceLinkMessage {
 }
 
 /// SPICE 链接回复 (RedLinkReply)
+///
+/// 布局: error (u32) + 公钥 (固定 [`SPICE_TICKET_PUBKEY_BYTES`] 字节, DER 编码的 SubjectPublicKeyInfo)
+/// + num_common_caps + num_channel_caps + caps_offset (均为 u32), 能力列表位于回复起始处的 `caps_offset`。
 #[derive(Debug, Clone)]
 pub struct SpiceLinkReply {
     /// 错误码
     pub error: u32,
-    /// RSA 公钥长度
-    pub pub_key_len: u32,
     /// RSA 公钥数据
     pub pub_key: Vec<u8>,
     /// 公共能力数量
@@ -153,49 +156,65 @@ pub struct SpiceLinkReply {
 }
 
 impl SpiceLinkReply {
+    /// 固定部分的长度
+    const FIXED_SIZE: usize = 4 + SPICE_TICKET_PUBKEY_BYTES + 12;
+
     pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
-        if bytes.len() < 20 {
-            return None;
+        let error = read_u32(bytes, 0)?;
+        // 出错的回复可能只带错误码
+        if error != 0 && bytes.len() < Self::FIXED_SIZE {
+            return Some(Self {
+                error,
+                pub_key: Vec::new(),
+                num_common_caps: 0,
+                num_channel_caps: 0,
+                caps_offset: 0,
+                common_caps: Vec::new(),
+                channel_caps: Vec::new(),
+            });
         }
-
-        let error = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
-        let pub_key_len = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
-
-        let pub_key_end = 8 + pub_key_len as usize;
-        if bytes.len() < pub_key_end + 12 {
+        if bytes.len() < Self::FIXED_SIZE {
             return None;
         }
 
-        let pub_key = bytes[8..pub_key_end].to_vec();
+        let pub_key = bytes[4..4 + SPICE_TICKET_PUBKEY_BYTES].to_vec();
+        let offset = 4 + SPICE_TICKET_PUBKEY_BYTES;
+        let num_common_caps = read_u32(bytes, offset)?;
+        let num_channel_caps = read_u32(bytes, offset + 4)?;
+        let caps_offset = read_u32(bytes, offset + 8)?;
 
-        let offset = pub_key_end;
-        let num_common_caps = u32::from_le_bytes([
-            bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]
-        ]);
-        let num_channel_caps = u32::from_le_bytes([
-            bytes[offset + 4], bytes[offset + 5], bytes[offset + 6], bytes[offset + 7]
-        ]);
-        let caps_offset = u32::from_le_bytes([
-            bytes[offset + 8], bytes[offset + 9], bytes[offset + 10], bytes[offset + 11]
-        ]);
-
-        // TODO: 解析能力列表
+        let caps_start = caps_offset as usize;
+        let read_caps = |start: usize, count: u32| -> Option<Vec<u32>> {
+            (0..count as usize).map(|i| read_u32(bytes, start + i * 4)).collect()
+        };
+        let common_caps = read_caps(caps_start, num_common_caps)?;
+        let channel_caps = read_caps(caps_start + num_common_caps as usize * 4, num_channel_caps)?;
 
         Some(Self {
             error,
-            pub_key_len,
             pub_key,
             num_common_caps,
             num_channel_caps,
             caps_offset,
-            common_caps: Vec::new(),
-            channel_caps: Vec::new(),
+            common_caps,
+            channel_caps,
         })
     }
 
     pub fn is_ok(&self) -> bool {
         self.error == 0
     }
+
+    /// 服务端是否声明了公共能力 (`cap` 为 [`common_caps`](super::constants::common_caps) 中的位)
+    pub fn has_common_cap(&self, cap: u32) -> bool {
+        self.common_caps.first().is_some_and(|caps| caps & cap != 0)
+    }
+}
+
+/// 按小端序读取 u32, 越界时返回 None
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let end = offset.checked_add(4)?;
+    bytes.get(offset..end).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
 }
 
 /// SPICE 数据头部 (RedDataHeader)
@@ -440,10 +459,34 @@ mod tests {
         let bytes = header.to_bytes();
         let parsed = SpiceLinkHeader::from_bytes(&bytes).unwrap();
 
-        assert_eq!(parsed.magic, SpiceLinkHeader::MAGIC);
-        assert_eq!(parsed.major_version, 2);
-        assert_eq!(parsed.minor_version, 2);
-        assert_eq!(parsed.size, 100);
+        assert_eq!({ parsed.magic }, SpiceLinkHeader::MAGIC);
+        assert_eq!({ parsed.major_version }, 2);
+        assert_eq!({ parsed.minor_version }, 2);
+        assert_eq!({ parsed.size }, 100);
+    }
+
+    #[test]
+    fn test_link_reply_parses_fixed_key_and_caps() {
+        // 抓包: 错误码 0, 162 字节公钥, 1 个公共能力 / 1 个通道能力, 能力位于偏移 178
+        let mut bytes = vec![0u8; 4];
+        bytes.extend((0..SPICE_TICKET_PUBKEY_BYTES).map(|i| i as u8));
+        for value in [1u32, 1, 178, 0x0b, 0x0f] {
+            bytes.extend(value.to_le_bytes());
+        }
+
+        let reply = SpiceLinkReply::from_bytes(&bytes).unwrap();
+        assert!(reply.is_ok());
+        assert_eq!(reply.pub_key.len(), SPICE_TICKET_PUBKEY_BYTES);
+        assert_eq!(reply.pub_key[161], 161);
+        assert_eq!(reply.common_caps, vec![0x0b]);
+        assert_eq!(reply.channel_caps, vec![0x0f]);
+        assert!(reply.has_common_cap(1 << 1));
+        assert!(!reply.has_common_cap(1 << 2));
+
+        // 能力列表越界
+        assert!(SpiceLinkReply::from_bytes(&bytes[..bytes.len() - 4]).is_none());
+        // 出错的回复只带错误码
+        assert_eq!(SpiceLinkReply::from_bytes(&7u32.to_le_bytes()).unwrap().error, 7);
     }
 
     #[test]
@@ -452,9 +495,9 @@ mod tests {
         let bytes = header.to_bytes();
         let parsed = SpiceDataHeader::from_bytes(&bytes).unwrap();
 
-        assert_eq!(parsed.serial, 12345);
-        assert_eq!(parsed.msg_type, 1);
-        assert_eq!(parsed.size, 256);
+        assert_eq!({ parsed.serial }, 12345);
+        assert_eq!({ parsed.msg_type }, 1);
+        assert_eq!({ parsed.size }, 256);
     }
 
     #[test]
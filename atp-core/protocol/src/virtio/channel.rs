@@ -8,6 +8,8 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use virt::domain::Domain;
 use tracing::{debug, info, warn};
 
+use atp_transport::DomainInspection;
+
 use crate::ProtocolError;
 
 /// VirtioSerial 通道信息
@@ -189,62 +191,15 @@ impl VirtioChannel {
 
     /// 解析 XML 获取通道路径
     fn parse_channel_path(xml: &str, channel_name: &str) -> Result<PathBuf, ProtocolError> {
-        // 简单的 XML 解析，查找 channel 元素
-        // 格式：<target type='virtio' name='com.vmagent.sock'/>
-        //      <source mode='bind' path='/var/lib/libvirt/qemu/channel/target/...'/>
-
-        let lines: Vec<&str> = xml.lines().collect();
-        let mut in_channel = false;
-        let mut current_channel_name = String::new();
-
-        for (_i, line) in lines.iter().enumerate() {
-            // 检查是否是 channel 开始标签
-            if line.contains("<channel type='unix'>") {
-                in_channel = true;
-                continue;
-            }
-
-            // 检查 channel 结束
-            if line.contains("</channel>") {
-                in_channel = false;
-                current_channel_name.clear();
-                continue;
-            }
-
-            if !in_channel {
-                continue;
-            }
-
-            // 查找 target name
-            if line.contains("<target") && line.contains("name=") {
-                if let Some(name) = Self::extract_attribute(line, "name") {
-                    current_channel_name = name;
-                }
-            }
-
-            // 如果找到匹配的通道名称，查找 source path
-            if current_channel_name == channel_name && line.contains("<source") {
-                if let Some(path) = Self::extract_attribute(line, "path") {
-                    return Ok(PathBuf::from(path));
-                }
-            }
-        }
-
-        Err(ProtocolError::ConnectionFailed(
-            format!("未找到通道: {}", channel_name)
-        ))
-    }
-
-    /// 从 XML 属性中提取值
-    fn extract_attribute(line: &str, attr: &str) -> Option<String> {
-        let pattern = format!("{}='", attr);
-        if let Some(start) = line.find(&pattern) {
-            let value_start = start + pattern.len();
-            if let Some(end) = line[value_start..].find('\'') {
-                return Some(line[value_start..value_start + end].to_string());
-            }
-        }
-        None
+        let inspection = DomainInspection::parse(xml)
+            .map_err(|e| ProtocolError::ParseError(e.to_string()))?;
+
+        inspection
+            .channel(channel_name)
+            .and_then(|channel| channel.path.clone())
+            .ok_or_else(|| ProtocolError::ConnectionFailed(
+                format!("未找到通道: {}", channel_name)
+            ))
     }
 }
 
@@ -280,15 +235,4 @@ mod tests {
         let path = result.unwrap();
         assert_eq!(path.to_str().unwrap(), "/var/lib/libvirt/qemu/channel/target/test-uuid");
     }
-
-    #[test]
-    fn test_extract_attribute() {
-        let line = "  <source mode='bind' path='/var/lib/test'/>";
-
-        let mode = VirtioChannel::extract_attribute(line, "mode");
-        assert_eq!(mode, Some("bind".to_string()));
-
-        let path = VirtioChannel::extract_attribute(line, "path");
-        assert_eq!(path, Some("/var/lib/test".to_string()));
-    }
 }
@@ -90,10 +90,59 @@ fn test_protocol_error_display() {
 }
 
 #[test]
-fn test_protocol_registry() {
+fn test_protocol_error_context() {
+    let err = ProtocolError::CommandFailed("超时".to_string())
+        .with_context(ErrorContext::new().with_domain("win10").with_operation("guest-exec"))
+        .with_context(ErrorContext::new().with_host("host1"));
+
+    assert_eq!(err.to_string(), "[host=host1, vm=win10, op=guest-exec] 命令执行失败: 超时");
+    assert_eq!(err.context().unwrap().domain.as_deref(), Some("win10"));
+    assert!(matches!(err.root(), ProtocolError::CommandFailed(_)));
+
+    // 传输层错误的上下文随之输出
+    let err: ProtocolError = atp_transport::TransportError::Timeout
+        .with_context(ErrorContext::new().with_host("host2"))
+        .into();
+    assert_eq!(err.to_string(), "传输层错误: [host=host2] 连接超时");
+}
+
+#[tokio::test]
+async fn test_protocol_registry() {
     let registry = ProtocolRegistry::new();
 
     // 测试空注册表
-    assert!(registry.get(&ProtocolType::QMP).is_none());
-    assert!(registry.get(&ProtocolType::QGA).is_none());
+    assert!(matches!(
+        registry.create("qmp").await,
+        Err(ProtocolError::ProtocolNotFound(_))
+    ));
+    assert!(matches!(
+        registry.create("qga").await,
+        Err(ProtocolError::ProtocolNotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_protocol_registry_custom_plugin() {
+    let registry = ProtocolRegistry::new();
+    registry
+        .register(
+            "vendor-agent",
+            Box::new(custom::CustomProtocolBuilder::new("com.vendor.agent.0".to_string())),
+        )
+        .await
+        .unwrap();
+
+    let protocol = registry.create("vendor-agent").await.unwrap();
+    assert_eq!(
+        protocol.protocol_type(),
+        ProtocolType::VirtioSerial("com.vendor.agent.0".to_string())
+    );
+
+    let duplicate = registry
+        .register(
+            "vendor-agent",
+            Box::new(custom::CustomProtocolBuilder::new("other".to_string())),
+        )
+        .await;
+    assert!(matches!(duplicate, Err(ProtocolError::ProtocolAlreadyRegistered(_))));
 }
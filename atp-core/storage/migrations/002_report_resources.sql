@@ -0,0 +1,15 @@
+-- 场景资源表 (记录场景执行中创建的资源及其清理结果)
+CREATE TABLE IF NOT EXISTS report_resources (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    report_id INTEGER NOT NULL,
+    resource_type TEXT NOT NULL, -- 'desk_pool', 'domain', 'snapshot'
+    resource_id TEXT NOT NULL,
+    name TEXT,
+    step_index INTEGER NOT NULL,
+    cleanup_status TEXT NOT NULL, -- 'released', 'auto_cleaned', 'failed', 'leaked'
+    cleanup_error TEXT,
+    FOREIGN KEY (report_id) REFERENCES test_reports(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_resources_report ON report_resources(report_id);
+CREATE INDEX IF NOT EXISTS idx_resources_status ON report_resources(cleanup_status);
@@ -0,0 +1,12 @@
+-- 通用指标采样表 (由 MetricsCollector 定期写入)
+CREATE TABLE IF NOT EXISTS metric_samples (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source TEXT NOT NULL, -- 指标来源, 如 'transport', 'qga', 'verification_server'
+    name TEXT NOT NULL,
+    value REAL NOT NULL,
+    labels TEXT, -- JSON object: {"host": "host1"}
+    timestamp DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_samples_source_time ON metric_samples(source, timestamp);
+CREATE INDEX IF NOT EXISTS idx_samples_name_time ON metric_samples(name, timestamp);
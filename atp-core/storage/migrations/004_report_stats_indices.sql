@@ -0,0 +1,3 @@
+-- 报告趋势统计与不稳定步骤检测使用的索引
+CREATE INDEX IF NOT EXISTS idx_reports_scenario_time ON test_reports(scenario_name, start_time);
+CREATE INDEX IF NOT EXISTS idx_steps_report_description ON execution_steps(report_id, description);
@@ -0,0 +1,23 @@
+-- VDI 虚拟机信息缓存 (由 VmCacheManager 从 VDI 平台同步)
+CREATE TABLE IF NOT EXISTS vm_cache (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    status TEXT NOT NULL,
+    host_id TEXT NOT NULL,
+    cpu INTEGER,
+    memory INTEGER, -- MB
+    updated_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_vm_cache_name ON vm_cache(name);
+
+-- 虚拟机状态变更历史 (同步时发现状态变化才写入)
+CREATE TABLE IF NOT EXISTS vm_status_history (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    vm_id TEXT NOT NULL,
+    old_status TEXT, -- 首次同步时为 NULL
+    new_status TEXT NOT NULL,
+    changed_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_vm_history_vm_time ON vm_status_history(vm_id, changed_at);
@@ -0,0 +1,8 @@
+-- 报告保留规则 (由 Storage::apply_retention 执行)
+CREATE TABLE IF NOT EXISTS retention_policies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    scenario_pattern TEXT NOT NULL, -- 支持 * 和 ? 通配符
+    keep_last_n INTEGER, -- 每个场景保留最近 N 次执行
+    keep_days INTEGER, -- 保留最近 N 天内的执行
+    created_at DATETIME NOT NULL
+);
@@ -0,0 +1,14 @@
+-- 场景定义的历史版本 (scenarios 表保存最新版本)
+CREATE TABLE IF NOT EXISTS scenario_versions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    scenario_id INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    definition TEXT NOT NULL, -- JSON/YAML
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (scenario_id) REFERENCES scenarios(id) ON DELETE CASCADE,
+    UNIQUE (scenario_id, version)
+);
+
+-- 已有场景的当前定义作为其最新版本
+INSERT OR IGNORE INTO scenario_versions (scenario_id, version, definition, created_at)
+SELECT id, version, definition, updated_at FROM scenarios;
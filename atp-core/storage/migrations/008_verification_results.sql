@@ -0,0 +1,16 @@
+-- Guest 验证结果 (由 verification-server 在事件匹配或超时时写入)
+CREATE TABLE IF NOT EXISTS verification_results (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    vm_id TEXT NOT NULL,
+    event_id TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    verified BOOLEAN NOT NULL,
+    outcome TEXT NOT NULL, -- 'verified', 'mismatched', 'timeout'
+    latency_ms INTEGER, -- Agent 上报的延迟, 超时时为空
+    server_latency_ms INTEGER, -- 服务端测得的延迟, 超时时为空
+    details TEXT, -- JSON
+    created_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_verification_vm_time ON verification_results(vm_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_verification_time ON verification_results(created_at);
@@ -0,0 +1,15 @@
+-- 主机最近一次连接探测的结果 (hosts 表保存主机配置)
+CREATE TABLE IF NOT EXISTS host_probes (
+    host_id TEXT PRIMARY KEY,
+    probed_at DATETIME NOT NULL,
+    reachable BOOLEAN NOT NULL,
+    last_seen_at DATETIME, -- 最近一次连接成功的时间
+    error TEXT, -- 最近一次探测失败的原因, 成功时为空
+    libvirt_version TEXT,
+    hypervisor TEXT,
+    hypervisor_version TEXT,
+    cpu_model TEXT,
+    max_vcpus INTEGER,
+    capabilities TEXT, -- JSON, 完整的探测结果
+    FOREIGN KEY (host_id) REFERENCES hosts(id) ON DELETE CASCADE
+);
@@ -0,0 +1,19 @@
+-- 步骤执行期间采样的虚拟机资源指标 (每个步骤一行)
+CREATE TABLE IF NOT EXISTS step_metrics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    report_id INTEGER NOT NULL,
+    step_index INTEGER NOT NULL,
+    sample_count INTEGER NOT NULL,
+    interval_ms INTEGER NOT NULL,
+    cpu_min REAL, -- 虚拟机 CPU 使用率 (%)
+    cpu_avg REAL,
+    cpu_max REAL,
+    memory_min_mb REAL, -- 来宾已用内存, 没有 balloon 统计时为分配的内存
+    memory_avg_mb REAL,
+    memory_max_mb REAL,
+    block_read_bytes INTEGER, -- 步骤期间的磁盘读写量 (QMP query-blockstats)
+    block_write_bytes INTEGER,
+    FOREIGN KEY (report_id) REFERENCES test_reports(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_step_metrics_report ON step_metrics(report_id);
@@ -0,0 +1,24 @@
+-- 基准测试运行结果 (如 atp bench input-latency), 用于跨版本比较趋势
+CREATE TABLE IF NOT EXISTS benchmark_runs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    kind TEXT NOT NULL, -- 'input-latency'
+    target TEXT NOT NULL, -- 虚拟机名称
+    label TEXT, -- 构建或版本标签
+    samples INTEGER NOT NULL, -- 请求的样本数
+    observed INTEGER NOT NULL, -- Agent 观察到输入的样本数 (参与统计)
+    mismatched INTEGER NOT NULL, -- Agent 未观察到期望输入的样本数
+    timeouts INTEGER NOT NULL, -- 验证超时的样本数 (不参与统计)
+    failures INTEGER NOT NULL, -- 发送失败的样本数
+    min_ms INTEGER,
+    median_ms INTEGER,
+    p95_ms INTEGER,
+    p99_ms INTEGER,
+    max_ms INTEGER,
+    mean_ms REAL,
+    latencies TEXT NOT NULL, -- JSON 数组, 观察到的样本的延迟 (毫秒, 按采样顺序)
+    options TEXT, -- JSON, 运行参数
+    started_at DATETIME NOT NULL,
+    duration_ms INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_benchmark_runs_kind_target ON benchmark_runs(kind, target, started_at);
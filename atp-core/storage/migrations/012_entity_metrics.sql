@@ -0,0 +1,11 @@
+-- 主机 / 虚拟机时序指标 (步骤资源采样、基准测试等写入, 按时间桶降采样查询)
+CREATE TABLE IF NOT EXISTS metrics (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entity_type TEXT NOT NULL, -- 'host' 或 'vm'
+    entity_id TEXT NOT NULL, -- 主机 ID 或虚拟机名称
+    metric_name TEXT NOT NULL, -- 如 'cpu', 'memory_mb', 'input_latency_ms'
+    value REAL NOT NULL,
+    sampled_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_metrics_entity_metric_time ON metrics(entity_id, metric_name, sampled_at);
@@ -0,0 +1,2 @@
+-- 执行报告对应的场景版本 (按名称运行已保存的场景时记录, 场景文件为空)
+ALTER TABLE test_reports ADD COLUMN scenario_version INTEGER;
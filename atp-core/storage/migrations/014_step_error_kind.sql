@@ -0,0 +1,2 @@
+-- 失败步骤的错误分类 (infrastructure / protocol / verification / timeout / configuration / unknown)
+ALTER TABLE execution_steps ADD COLUMN error_kind TEXT;
@@ -0,0 +1,2 @@
+-- 步骤开始时间相对场景开始的偏移 (毫秒), 用于 HTML 报告的甘特图
+ALTER TABLE execution_steps ADD COLUMN started_at_offset_ms INTEGER;
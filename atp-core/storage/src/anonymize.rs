@@ -0,0 +1,353 @@
+//! 导出数据匿名化
+//!
+//! 向外部提交报告时, 把 IP、主机名、用户名替换为假名。同一原值始终映射到同一假名
+//! (`ip-1`、`host-2`、`user-1` ...), 映射表可单独输出供内部对照。
+//!
+//! 替换来源:
+//! - 内置规则: IPv4 地址、`user@host` 与 URI 中的主机/用户名
+//! - 敏感字段: JSON 中 `host`、`hostname`、`username` 等字段的值
+//! - 已知值: 通过 [`Anonymizer::add_known`] 登记的值 (如配置中的主机地址)
+//! - 追加规则: 通过 [`Anonymizer::with_rule`] 添加的正则, 包含捕获组时只替换第一个捕获组
+
+use std::collections::HashMap;
+
+use regex_automata::meta::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, StorageError};
+
+/// IPv4 地址
+const IP_PATTERN: &str =
+    r"\b(?:25[0-5]|2[0-4][0-9]|1?[0-9]?[0-9])(?:\.(?:25[0-5]|2[0-4][0-9]|1?[0-9]?[0-9])){3}\b";
+
+/// 内置规则: (类别, 正则), 同一位置同时匹配时靠前的规则优先
+const BUILTIN_RULES: &[(&str, &str)] = &[
+    ("ip", IP_PATTERN),
+    // user@host 中的用户名
+    ("user", r"([A-Za-z_][A-Za-z0-9_.-]*)@[A-Za-z0-9]"),
+    // user@host 中的主机名
+    ("host", r"@([A-Za-z0-9][A-Za-z0-9.-]*[A-Za-z0-9])"),
+    // scheme://host 中的主机名
+    ("host", r"://([A-Za-z0-9][A-Za-z0-9.-]*[A-Za-z0-9])"),
+];
+
+/// 值为主机名或地址的字段
+const HOST_KEYS: &[&str] = &["host", "hostname", "host_id", "host_name", "address", "ip"];
+
+/// 值为用户名的字段
+const USER_KEYS: &[&str] = &["user", "username", "user_name", "login"];
+
+/// 追加的匿名化规则 (可写在配置文件中)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnonymizeRuleSpec {
+    /// 类别, 用作假名前缀
+    pub category: String,
+
+    /// 正则表达式
+    pub pattern: String,
+}
+
+/// 映射表中的一项
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnonymizedValue {
+    pub category: String,
+    pub original: String,
+    pub alias: String,
+}
+
+struct Rule {
+    category: String,
+    regex: Regex,
+}
+
+/// 一致性映射匿名化器
+///
+/// 同一个匿名化器处理的所有数据共用一张映射表。
+pub struct Anonymizer {
+    rules: Vec<Rule>,
+    /// 原值 -> 映射表下标
+    index: HashMap<String, usize>,
+    mapping: Vec<AnonymizedValue>,
+    /// 类别 -> 已分配的假名数
+    counters: HashMap<String, usize>,
+}
+
+impl Anonymizer {
+    /// 创建带内置规则的匿名化器
+    pub fn new() -> Self {
+        let rules = BUILTIN_RULES
+            .iter()
+            .map(|(category, pattern)| Rule {
+                category: category.to_string(),
+                regex: Regex::new(pattern).expect("builtin anonymize rule must compile"),
+            })
+            .collect();
+
+        Self {
+            rules,
+            index: HashMap::new(),
+            mapping: Vec::new(),
+            counters: HashMap::new(),
+        }
+    }
+
+    /// 追加规则 (在内置规则之后匹配)
+    pub fn with_rule(mut self, category: &str, pattern: &str) -> Result<Self> {
+        if category.is_empty() {
+            return Err(StorageError::ValidationError(
+                "Anonymize rule category must not be empty".to_string(),
+            ));
+        }
+
+        let regex = Regex::new(pattern).map_err(|e| {
+            StorageError::ValidationError(format!("Invalid anonymize pattern '{}': {}", pattern, e))
+        })?;
+
+        self.rules.push(Rule {
+            category: category.to_string(),
+            regex,
+        });
+        Ok(self)
+    }
+
+    /// 批量追加规则
+    pub fn with_rules(self, specs: &[AnonymizeRuleSpec]) -> Result<Self> {
+        specs
+            .iter()
+            .try_fold(self, |anonymizer, spec| anonymizer.with_rule(&spec.category, &spec.pattern))
+    }
+
+    /// 登记已知的敏感值, 之后在任意文本中出现都会被替换
+    pub fn add_known(&mut self, category: &str, original: &str) -> String {
+        let category = if is_ip(original) { "ip" } else { category };
+        self.alias(category, original)
+    }
+
+    /// 原值对应的假名 (首次出现时分配)
+    fn alias(&mut self, category: &str, original: &str) -> String {
+        if let Some(&i) = self.index.get(original) {
+            return self.mapping[i].alias.clone();
+        }
+
+        let counter = self.counters.entry(category.to_string()).or_insert(0);
+        *counter += 1;
+        let alias = format!("{}-{}", category, counter);
+
+        self.index.insert(original.to_string(), self.mapping.len());
+        self.mapping.push(AnonymizedValue {
+            category: category.to_string(),
+            original: original.to_string(),
+            alias: alias.clone(),
+        });
+        alias
+    }
+
+    /// 匿名化文本
+    pub fn anonymize_str(&mut self, text: &str) -> String {
+        // (起点, 终点, 规则序号), 已知值的规则序号为 usize::MAX
+        let mut spans: Vec<(usize, usize, usize)> = Vec::new();
+
+        for (rule_index, rule) in self.rules.iter().enumerate() {
+            let group = if rule.regex.captures_len() > 1 { 1 } else { 0 };
+            for caps in rule.regex.captures_iter(text) {
+                if let Some(span) = caps.get_group(group).filter(|span| !span.is_empty()) {
+                    spans.push((span.start, span.end, rule_index));
+                }
+            }
+        }
+
+        for known in self.index.keys() {
+            spans.extend(
+                find_words(text, known).map(|start| (start, start + known.len(), usize::MAX)),
+            );
+        }
+
+        // 从左到右, 同一起点取最长, 长度相同时靠前的规则优先; 丢弃重叠的匹配
+        spans.sort_by_key(|&(start, end, rule)| (start, std::cmp::Reverse(end), rule));
+
+        let mut output = String::with_capacity(text.len());
+        let mut cursor = 0;
+        for (start, end, rule) in spans {
+            if start < cursor {
+                continue;
+            }
+
+            let original = &text[start..end];
+            let alias = match rule {
+                usize::MAX => self.mapping[self.index[original]].alias.clone(),
+                rule => {
+                    let category = self.rules[rule].category.clone();
+                    self.alias(&category, original)
+                }
+            };
+
+            output.push_str(&text[cursor..start]);
+            output.push_str(&alias);
+            cursor = end;
+        }
+        output.push_str(&text[cursor..]);
+
+        output
+    }
+
+    /// 匿名化 JSON 值 (就地修改)
+    ///
+    /// 先登记所有敏感字段的值, 再替换所有字符串, 保证字段值在其他文本中出现时同样被替换。
+    pub fn anonymize_value(&mut self, value: &mut serde_json::Value) {
+        self.collect_sensitive_fields(value);
+        self.replace_strings(value);
+    }
+
+    fn collect_sensitive_fields(&mut self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    let key = key.to_ascii_lowercase();
+                    if let Some(text) = value.as_str().filter(|text| !text.is_empty()) {
+                        if HOST_KEYS.contains(&key.as_str()) {
+                            self.add_known("host", text);
+                        } else if USER_KEYS.contains(&key.as_str()) {
+                            self.add_known("user", text);
+                        }
+                    } else {
+                        self.collect_sensitive_fields(value);
+                    }
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.collect_sensitive_fields(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn replace_strings(&mut self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(text) => *text = self.anonymize_str(text),
+            serde_json::Value::Object(map) => {
+                for value in map.values_mut() {
+                    self.replace_strings(value);
+                }
+            }
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.replace_strings(item);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// 映射表 (按首次出现顺序)
+    pub fn mapping(&self) -> &[AnonymizedValue] {
+        &self.mapping
+    }
+}
+
+impl Default for Anonymizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_ip(text: &str) -> bool {
+    text.parse::<std::net::Ipv4Addr>().is_ok()
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+/// 查找作为完整单词出现的位置 (前后不是字母、数字、`_` 或 `-`)
+fn find_words<'a>(text: &'a str, word: &'a str) -> impl Iterator<Item = usize> + 'a {
+    text.match_indices(word).filter_map(move |(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        let bounded = !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char);
+        bounded.then_some(start)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_same_value_maps_to_same_alias() {
+        let mut anonymizer = Anonymizer::new();
+
+        let text = anonymizer.anonymize_str("ping 10.0.0.5 ok, ping 10.0.0.6 ok, retry 10.0.0.5");
+        assert_eq!(text, "ping ip-1 ok, ping ip-2 ok, retry ip-1");
+
+        // 之后的数据继续使用同一张映射表
+        assert_eq!(anonymizer.anonymize_str("gw 10.0.0.6"), "gw ip-2");
+        assert_eq!(anonymizer.mapping().len(), 2);
+        assert_eq!(anonymizer.mapping()[0].original, "10.0.0.5");
+        assert_eq!(anonymizer.mapping()[0].alias, "ip-1");
+    }
+
+    #[test]
+    fn test_builtin_user_and_host_rules() {
+        let mut anonymizer = Anonymizer::new();
+
+        let text = anonymizer.anonymize_str(
+            "qemu+ssh://admin@node1.lab:22/system; ssh admin@192.168.1.10; http://vdi.lab:8088/api",
+        );
+        assert_eq!(
+            text,
+            "qemu+ssh://user-1@host-1:22/system; ssh user-1@ip-1; http://host-2:8088/api"
+        );
+
+        // 版本号之类不完整的地址不会被替换
+        assert_eq!(anonymizer.anonymize_str("v1.2.3 qemu:///system"), "v1.2.3 qemu:///system");
+    }
+
+    #[test]
+    fn test_known_values_replaced_as_whole_words() {
+        let mut anonymizer = Anonymizer::new();
+        assert_eq!(anonymizer.add_known("host", "node1"), "host-1");
+        assert_eq!(anonymizer.add_known("host", "172.16.0.1"), "ip-1");
+
+        let text = anonymizer.anonymize_str("node1 -> node10, node1.");
+        assert_eq!(text, "host-1 -> node10, host-1.");
+    }
+
+    #[test]
+    fn test_custom_rules() {
+        let mut anonymizer = Anonymizer::new()
+            .with_rules(&[AnonymizeRuleSpec {
+                category: "asset".to_string(),
+                pattern: r"asset-id=([0-9]+)".to_string(),
+            }])
+            .unwrap();
+
+        let text = anonymizer.anonymize_str("asset-id=9001 asset-id=9002 asset-id=9001");
+        assert_eq!(text, "asset-id=asset-1 asset-id=asset-2 asset-id=asset-1");
+
+        assert!(Anonymizer::new().with_rule("x", "(").is_err());
+        assert!(Anonymizer::new().with_rule("", "x").is_err());
+    }
+
+    #[test]
+    fn test_anonymize_value_uses_sensitive_fields() {
+        let mut anonymizer = Anonymizer::new();
+        let mut value = json!({
+            "metadata": {"hostname": "build-box", "profile": "prod"},
+            "steps": [
+                {"output": "connected to build-box as operator"},
+                {"error": "login failed for operator", "username": "operator"}
+            ]
+        });
+
+        anonymizer.anonymize_value(&mut value);
+
+        assert_eq!(value["metadata"]["hostname"], "host-1");
+        assert_eq!(value["metadata"]["profile"], "prod");
+        assert_eq!(value["steps"][0]["output"], "connected to host-1 as user-1");
+        assert_eq!(value["steps"][1]["error"], "login failed for user-1");
+        assert_eq!(value["steps"][1]["username"], "user-1");
+    }
+}
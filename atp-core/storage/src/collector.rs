@@ -0,0 +1,184 @@
+//! 指标采集器
+//!
+//! 定期从多个指标源 (传输层连接池、协议实例、验证服务等) 拉取快照,
+//! 缓存后批量写入 `metric_samples` 表。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::sync::{oneshot, Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+pub use atp_common::{MetricSample, MetricsSource};
+
+use crate::error::Result;
+use crate::models::MetricSampleRecord;
+use crate::repositories::MetricRepository;
+
+/// 采集器配置
+#[derive(Debug, Clone)]
+pub struct CollectorConfig {
+    /// 采集间隔
+    pub interval: Duration,
+
+    /// 缓存的采样数达到该值时立即写库
+    pub flush_threshold: usize,
+}
+
+impl Default for CollectorConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60),
+            flush_threshold: 500,
+        }
+    }
+}
+
+/// 后台采集任务
+struct CollectorTask {
+    handle: JoinHandle<()>,
+    shutdown: oneshot::Sender<()>,
+}
+
+/// 指标采集器
+pub struct MetricsCollector {
+    repository: MetricRepository,
+    config: CollectorConfig,
+    sources: RwLock<Vec<Arc<dyn MetricsSource>>>,
+    buffer: Mutex<Vec<MetricSampleRecord>>,
+    task: Mutex<Option<CollectorTask>>,
+}
+
+impl MetricsCollector {
+    pub fn new(repository: MetricRepository, config: CollectorConfig) -> Self {
+        Self {
+            repository,
+            config,
+            sources: RwLock::new(Vec::new()),
+            buffer: Mutex::new(Vec::new()),
+            task: Mutex::new(None),
+        }
+    }
+
+    /// 添加指标源
+    pub async fn add_source(&self, source: Arc<dyn MetricsSource>) {
+        debug!("添加指标源: {}", source.source_name());
+        self.sources.write().await.push(source);
+    }
+
+    /// 已添加的指标源数量
+    pub async fn source_count(&self) -> usize {
+        self.sources.read().await.len()
+    }
+
+    /// 缓存中尚未写库的采样数
+    pub async fn buffered_count(&self) -> usize {
+        self.buffer.lock().await.len()
+    }
+
+    /// 从所有指标源采集一次
+    ///
+    /// 同一轮采集的所有采样使用相同的时间戳。缓存达到阈值时自动写库。
+    /// 返回本轮采集到的采样数。
+    pub async fn collect_once(&self) -> Result<usize> {
+        let timestamp = Utc::now();
+        let sources = self.sources.read().await.clone();
+
+        let mut records = Vec::new();
+        for source in &sources {
+            for sample in source.collect().await {
+                let labels = if sample.labels.is_empty() {
+                    None
+                } else {
+                    Some(serde_json::to_string(&sample.labels)?)
+                };
+
+                records.push(MetricSampleRecord {
+                    id: 0,
+                    source: source.source_name().to_string(),
+                    name: sample.name,
+                    value: sample.value,
+                    labels,
+                    timestamp,
+                });
+            }
+        }
+
+        let collected = records.len();
+        let should_flush = {
+            let mut buffer = self.buffer.lock().await;
+            buffer.extend(records);
+            buffer.len() >= self.config.flush_threshold
+        };
+
+        if should_flush {
+            self.flush().await?;
+        }
+
+        Ok(collected)
+    }
+
+    /// 将缓存的采样写入数据库
+    ///
+    /// 写入失败时采样保留在缓存中, 下次 flush 时重试。
+    pub async fn flush(&self) -> Result<u64> {
+        let mut buffer = self.buffer.lock().await;
+        if buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let written = self.repository.create_batch(&buffer).await?;
+        buffer.clear();
+
+        debug!("指标写入数据库: {} 条", written);
+        Ok(written)
+    }
+
+    /// 启动后台定时采集
+    pub async fn start(self: &Arc<Self>) {
+        let mut task = self.task.lock().await;
+        if task.is_some() {
+            warn!("指标采集器已在运行");
+            return;
+        }
+
+        let (shutdown, mut shutdown_rx) = oneshot::channel();
+        let collector = Arc::clone(self);
+        let interval = self.config.interval;
+
+        let handle = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        if let Err(e) = collector.collect_once().await {
+                            warn!("指标采集失败: {}", e);
+                        }
+                    }
+                    _ = &mut shutdown_rx => break,
+                }
+            }
+        });
+
+        info!("指标采集器已启动, 间隔 {:?}", interval);
+        *task = Some(CollectorTask { handle, shutdown });
+    }
+
+    /// 是否正在运行
+    pub async fn is_running(&self) -> bool {
+        self.task.lock().await.is_some()
+    }
+
+    /// 停止后台采集并写入剩余的缓存
+    pub async fn stop(&self) -> Result<u64> {
+        if let Some(task) = self.task.lock().await.take() {
+            let _ = task.shutdown.send(());
+            let _ = task.handle.await;
+            info!("指标采集器已停止");
+        }
+
+        self.flush().await
+    }
+}
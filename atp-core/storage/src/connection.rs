@@ -1,9 +1,66 @@
+use chrono::Utc;
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
 use std::path::Path;
 use tracing::{debug, info};
 
 use crate::error::{Result, StorageError};
 
+/// 迁移脚本: (版本, 名称, SQL), 版本从 1 开始连续递增
+const MIGRATIONS: &[(i64, &str, &str)] = &[
+    (1, "initial", include_str!("../migrations/001_initial.sql")),
+    (2, "report_resources", include_str!("../migrations/002_report_resources.sql")),
+    (3, "metric_samples", include_str!("../migrations/003_metric_samples.sql")),
+    (4, "report_stats_indices", include_str!("../migrations/004_report_stats_indices.sql")),
+    (5, "vm_cache", include_str!("../migrations/005_vm_cache.sql")),
+    (6, "retention_policies", include_str!("../migrations/006_retention_policies.sql")),
+    (7, "scenario_versions", include_str!("../migrations/007_scenario_versions.sql")),
+    (8, "verification_results", include_str!("../migrations/008_verification_results.sql")),
+    (9, "host_probes", include_str!("../migrations/009_host_probes.sql")),
+    (10, "step_metrics", include_str!("../migrations/010_step_metrics.sql")),
+    (11, "benchmark_runs", include_str!("../migrations/011_benchmark_runs.sql")),
+    (12, "entity_metrics", include_str!("../migrations/012_entity_metrics.sql")),
+    (13, "report_scenario_version", include_str!("../migrations/013_report_scenario_version.sql")),
+    (14, "step_error_kind", include_str!("../migrations/014_step_error_kind.sql")),
+    (15, "step_start_offset", include_str!("../migrations/015_step_start_offset.sql")),
+];
+
+/// 当前程序支持的数据库 schema 版本
+pub const LATEST_SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// 记录已执行迁移的表
+const SCHEMA_VERSION_TABLE: &str = r#"
+CREATE TABLE IF NOT EXISTS schema_version (
+    version INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    applied_at DATETIME NOT NULL
+)
+"#;
+
+/// 数据库迁移
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SchemaMigration {
+    /// 版本
+    pub version: i64,
+    /// 名称
+    pub name: &'static str,
+}
+
+/// 数据库完整性检查结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntegrityReport {
+    /// `PRAGMA integrity_check` 报告的问题
+    pub integrity_errors: Vec<String>,
+    /// `PRAGMA foreign_key_check` 报告的外键违例
+    pub foreign_key_violations: Vec<String>,
+}
+
+impl IntegrityReport {
+    /// 是否没有发现任何问题
+    pub fn is_ok(&self) -> bool {
+        self.integrity_errors.is_empty() && self.foreign_key_violations.is_empty()
+    }
+}
+
 /// 存储管理器 - 负责数据库连接和迁移
 pub struct StorageManager {
     pool: SqlitePool,
@@ -24,6 +81,16 @@ impl StorageManager {
     /// # }
     /// ```
     pub async fn new(db_path: &str) -> Result<Self> {
+        let manager = Self::open(db_path).await?;
+
+        // 运行迁移
+        manager.run_migrations().await?;
+
+        Ok(manager)
+    }
+
+    /// 打开数据库但不执行迁移 (用于查看 schema 状态)
+    pub async fn open(db_path: &str) -> Result<Self> {
         // 展开用户目录
         let expanded_path = shellexpand::tilde(db_path);
         let path = Path::new(expanded_path.as_ref());
@@ -44,12 +111,7 @@ impl StorageManager {
             .await
             .map_err(|e| StorageError::ConnectionError(e.to_string()))?;
 
-        let manager = Self { pool };
-
-        // 运行迁移
-        manager.run_migrations().await?;
-
-        Ok(manager)
+        Ok(Self { pool })
     }
 
     /// 创建内存数据库(用于测试)
@@ -70,16 +132,151 @@ impl StorageManager {
     async fn run_migrations(&self) -> Result<()> {
         info!("Running database migrations");
 
-        // 读取迁移脚本
-        let migration_sql = include_str!("../migrations/001_initial.sql");
+        let applied = self.migrate().await?;
+
+        debug!("Database migrations completed successfully ({} applied)", applied.len());
 
-        // 执行迁移
-        sqlx::query(migration_sql)
+        Ok(())
+    }
+
+    /// 执行所有待执行的迁移, 返回本次执行的迁移
+    ///
+    /// 每个迁移在单独的事务中执行并记录到 `schema_version` 表。
+    /// 引入版本管理之前创建的数据库 (已有表但没有版本记录) 被标记为版本 1 后继续升级;
+    /// 数据库版本高于当前程序支持的版本时返回错误。
+    pub async fn migrate(&self) -> Result<Vec<SchemaMigration>> {
+        let current = self.schema_version().await?;
+        if current > LATEST_SCHEMA_VERSION {
+            return Err(StorageError::MigrationError(format!(
+                "Database schema version {} is newer than supported version {}, please upgrade atp",
+                current, LATEST_SCHEMA_VERSION
+            )));
+        }
+
+        sqlx::query(SCHEMA_VERSION_TABLE)
             .execute(&self.pool)
             .await
             .map_err(|e| StorageError::MigrationError(e.to_string()))?;
 
-        debug!("Database migrations completed successfully");
+        // 没有版本记录但已有表: 旧版本创建的数据库
+        if current == 1 && self.recorded_version().await?.is_none() {
+            info!("Stamping pre-migration database as schema version 1");
+            self.record_version(&self.pool, 1, MIGRATIONS[0].1).await?;
+        }
+
+        let mut applied = Vec::new();
+        for (version, name, sql) in MIGRATIONS.iter().filter(|(version, _, _)| *version > current) {
+            info!("Applying migration {:03}_{}", version, name);
+
+            let mut tx = self.pool.begin().await?;
+            sqlx::query(sql)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| StorageError::MigrationError(format!("{:03}_{}: {}", version, name, e)))?;
+            self.record_version(&mut *tx, *version, name).await?;
+            tx.commit().await?;
+
+            applied.push(SchemaMigration {
+                version: *version,
+                name,
+            });
+        }
+
+        Ok(applied)
+    }
+
+    /// 数据库当前的 schema 版本
+    ///
+    /// 空数据库为 0; 引入版本管理之前创建的数据库视为版本 1。
+    pub async fn schema_version(&self) -> Result<i64> {
+        if let Some(version) = self.recorded_version().await? {
+            return Ok(version);
+        }
+
+        let (legacy,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'test_reports'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(if legacy > 0 { 1 } else { 0 })
+    }
+
+    /// 尚未执行的迁移
+    pub async fn pending_migrations(&self) -> Result<Vec<SchemaMigration>> {
+        let current = self.schema_version().await?;
+
+        Ok(MIGRATIONS
+            .iter()
+            .filter(|(version, _, _)| *version > current)
+            .map(|(version, name, _)| SchemaMigration {
+                version: *version,
+                name,
+            })
+            .collect())
+    }
+
+    /// 完整性检查 (`PRAGMA integrity_check` 与 `PRAGMA foreign_key_check`)
+    pub async fn verify_integrity(&self) -> Result<IntegrityReport> {
+        let integrity: Vec<(String,)> = sqlx::query_as("PRAGMA integrity_check")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let foreign_keys: Vec<(String, Option<i64>, String, i64)> =
+            sqlx::query_as("PRAGMA foreign_key_check")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(IntegrityReport {
+            integrity_errors: integrity
+                .into_iter()
+                .map(|(message,)| message)
+                .filter(|message| message != "ok")
+                .collect(),
+            foreign_key_violations: foreign_keys
+                .into_iter()
+                .map(|(table, rowid, parent, _)| {
+                    format!(
+                        "{} rowid {} references missing row in {}",
+                        table,
+                        rowid.map_or("-".to_string(), |rowid| rowid.to_string()),
+                        parent
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    /// `schema_version` 表中记录的最高版本
+    async fn recorded_version(&self) -> Result<Option<i64>> {
+        let (exists,): (i64,) = sqlx::query_as(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        if exists == 0 {
+            return Ok(None);
+        }
+
+        let (version,): (Option<i64>,) = sqlx::query_as("SELECT MAX(version) FROM schema_version")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(version)
+    }
+
+    async fn record_version<'e, E>(&self, executor: E, version: i64, name: &str) -> Result<()>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        sqlx::query("INSERT INTO schema_version (version, name, applied_at) VALUES (?, ?, ?)")
+            .bind(version)
+            .bind(name)
+            .bind(Utc::now())
+            .execute(executor)
+            .await
+            .map_err(|e| StorageError::MigrationError(e.to_string()))?;
 
         Ok(())
     }
@@ -1,22 +1,35 @@
+mod anonymize;
 mod backup;
+mod collector;
 mod connection;
 mod error;
 mod models;
 mod repositories;
 
+pub use anonymize::{AnonymizeRuleSpec, AnonymizedValue, Anonymizer};
 pub use backup::{BackupInfo, BackupManager};
-pub use connection::StorageManager;
+pub use collector::{CollectorConfig, MetricSample, MetricsCollector, MetricsSource};
+pub use connection::{IntegrityReport, SchemaMigration, StorageManager, LATEST_SCHEMA_VERSION};
 pub use error::{Result, StorageError};
 pub use models::*;
 pub use repositories::*;
 
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use sqlx::SqlitePool;
 
 /// 统一的数据访问层入口
 pub struct Storage {
     _pool: SqlitePool,
     reports: ReportRepository,
+    hosts: HostRepository,
     scenarios: ScenarioRepository,
+    metrics: MetricRepository,
+    vm_cache: VmCacheRepository,
+    retention: RetentionRepository,
+    verifications: VerificationRepository,
+    benchmarks: BenchmarkRepository,
 }
 
 impl Storage {
@@ -26,7 +39,13 @@ impl Storage {
         Self {
             _pool: pool.clone(),
             reports: ReportRepository::new(pool.clone()),
+            hosts: HostRepository::new(pool.clone()),
             scenarios: ScenarioRepository::new(pool.clone()),
+            metrics: MetricRepository::new(pool.clone()),
+            vm_cache: VmCacheRepository::new(pool.clone()),
+            retention: RetentionRepository::new(pool.clone()),
+            verifications: VerificationRepository::new(pool.clone()),
+            benchmarks: BenchmarkRepository::new(pool.clone()),
         }
     }
 
@@ -40,7 +59,76 @@ impl Storage {
         &self.scenarios
     }
 
-    // TODO: 添加其他 repository 访问器
-    // pub fn hosts(&self) -> &HostRepository { ... }
-    // pub fn metrics(&self) -> &MetricRepository { ... }
+    /// 获取指标仓储
+    pub fn metrics(&self) -> &MetricRepository {
+        &self.metrics
+    }
+
+    /// 获取虚拟机缓存仓储
+    pub fn vm_cache(&self) -> &VmCacheRepository {
+        &self.vm_cache
+    }
+
+    /// 获取主机仓储
+    pub fn hosts(&self) -> &HostRepository {
+        &self.hosts
+    }
+
+    /// 获取报告保留规则仓储
+    pub fn retention(&self) -> &RetentionRepository {
+        &self.retention
+    }
+
+    /// 获取验证结果仓储
+    pub fn verifications(&self) -> &VerificationRepository {
+        &self.verifications
+    }
+
+    /// 获取基准测试仓储
+    pub fn benchmarks(&self) -> &BenchmarkRepository {
+        &self.benchmarks
+    }
+
+    /// 按保留规则清理报告 (单个事务, 级联删除步骤与资源记录)
+    ///
+    /// 不受任何规则保护的报告都会被删除; 没有任何规则时不删除报告。
+    /// `dry_run` 为 true 时只返回将被删除的报告, 不做任何修改。
+    pub async fn apply_retention(&self, dry_run: bool) -> Result<RetentionResult> {
+        let policies = self.retention.list_all().await?;
+        if policies.is_empty() {
+            return Ok(RetentionResult::default());
+        }
+
+        let reports = self.reports.list(&ReportFilter::default()).await?;
+        let expired = expired_reports(&policies, reports, Utc::now());
+
+        let ids: Vec<i64> = expired.iter().map(|report| report.id).collect();
+        let stats = self.reports.delete_many(&ids, dry_run).await?;
+
+        Ok(RetentionResult {
+            reports: expired,
+            stats,
+        })
+    }
+}
+
+/// 筛选不受任何保留规则保护的报告
+///
+/// `reports` 需按开始时间倒序排列, 以便计算报告在同一场景中的序号。
+fn expired_reports(
+    policies: &[RetentionPolicyRecord],
+    reports: Vec<TestReportRecord>,
+    now: DateTime<Utc>,
+) -> Vec<TestReportRecord> {
+    let mut ranks: HashMap<String, usize> = HashMap::new();
+
+    reports
+        .into_iter()
+        .filter(|report| {
+            let rank = ranks.entry(report.scenario_name.clone()).or_insert(0);
+            let protected = policies.iter().any(|policy| policy.protects(report, *rank, now));
+            *rank += 1;
+            !protected
+        })
+        .collect()
 }
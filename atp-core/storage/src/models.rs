@@ -18,6 +18,9 @@ pub struct TestReportRecord {
     pub passed: bool,
     pub tags: Option<String>, // JSON array
     pub created_at: DateTime<Utc>,
+    /// 产生该报告的场景版本 (从已保存的场景运行时)
+    #[serde(default)]
+    pub scenario_version: Option<i32>,
 }
 
 /// 执行步骤数据库模型
@@ -31,6 +34,120 @@ pub struct ExecutionStepRecord {
     pub error: Option<String>,
     pub duration_ms: Option<i64>,
     pub output: Option<String>,
+    pub started_at_offset_ms: Option<i64>, // 相对场景开始的偏移
+    /// 失败分类 (infrastructure / protocol / verification / timeout / configuration / unknown)
+    #[serde(default)]
+    pub error_kind: Option<String>,
+}
+
+/// 场景每日执行统计
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct DailyStats {
+    /// 日期 (UTC, YYYY-MM-DD)
+    pub date: String,
+    pub runs: i64,
+    pub passed_runs: i64,
+    /// 通过率 (百分比)
+    pub pass_rate: f64,
+    pub avg_duration_ms: Option<f64>,
+}
+
+/// 不稳定步骤: 同一步骤在部分执行中失败、部分执行中通过
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct FlakyStep {
+    pub description: String,
+    /// 执行次数 (不含跳过)
+    pub runs: i64,
+    pub failures: i64,
+    /// 失败率 (百分比)
+    pub failure_rate: f64,
+}
+
+/// 步骤耗时统计
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct SlowStep {
+    pub description: String,
+    pub runs: i64,
+    pub avg_duration_ms: f64,
+    pub max_duration_ms: i64,
+}
+
+/// 场景资源数据库模型
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ReportResourceRecord {
+    pub id: i64,
+    pub report_id: i64,
+    pub resource_type: String, // 'desk_pool', 'domain', 'snapshot'
+    pub resource_id: String,
+    pub name: Option<String>,
+    pub step_index: i32,
+    pub cleanup_status: String, // 'released', 'auto_cleaned', 'failed', 'leaked'
+    pub cleanup_error: Option<String>,
+}
+
+/// 步骤资源指标数据库模型 (步骤执行期间采样的最小/平均/最大值)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct StepMetricsRecord {
+    pub id: i64,
+    pub report_id: i64,
+    pub step_index: i32,
+    pub sample_count: i32,
+    pub interval_ms: i64,
+    pub cpu_min: Option<f64>,
+    pub cpu_avg: Option<f64>,
+    pub cpu_max: Option<f64>,
+    pub memory_min_mb: Option<f64>,
+    pub memory_avg_mb: Option<f64>,
+    pub memory_max_mb: Option<f64>,
+    pub block_read_bytes: Option<i64>,
+    pub block_write_bytes: Option<i64>,
+}
+
+/// 报告导出包格式版本
+pub const REPORT_BUNDLE_VERSION: u32 = 1;
+
+/// 报告导出包中的导出环境信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleMetadata {
+    /// 导出包格式版本
+    pub bundle_version: u32,
+    pub atp_version: String,
+    /// 导出报告的机器
+    pub hostname: Option<String>,
+    /// 导出时使用的配置 profile
+    pub profile: Option<String>,
+    pub exported_at: DateTime<Utc>,
+}
+
+impl BundleMetadata {
+    /// 收集当前环境信息
+    pub fn collect(profile: Option<&str>) -> Self {
+        let hostname = std::env::var("HOSTNAME")
+            .ok()
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+
+        Self {
+            bundle_version: REPORT_BUNDLE_VERSION,
+            atp_version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname,
+            profile: profile.map(str::to_string),
+            exported_at: Utc::now(),
+        }
+    }
+}
+
+/// 报告导出包: 报告、步骤与资源记录, 可保存为单个 JSON 文件后在其他机器导入
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportBundle {
+    pub metadata: BundleMetadata,
+    pub report: TestReportRecord,
+    pub steps: Vec<ExecutionStepRecord>,
+    #[serde(default)]
+    pub resources: Vec<ReportResourceRecord>,
+    #[serde(default)]
+    pub step_metrics: Vec<StepMetricsRecord>,
 }
 
 /// 场景数据库模型
@@ -46,6 +163,32 @@ pub struct ScenarioRecord {
     pub updated_at: DateTime<Utc>,
 }
 
+/// 场景历史版本数据库模型
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScenarioVersionRecord {
+    pub scenario_id: i64,
+    pub name: String,
+    pub version: i32,
+    pub definition: String, // JSON/YAML
+    pub created_at: DateTime<Utc>,
+}
+
+/// 场景及其最近一次执行结果
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScenarioSummary {
+    pub id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub version: i32,
+    pub updated_at: DateTime<Utc>,
+    /// 最近一次执行的报告 ID
+    pub last_report_id: Option<i64>,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_run_passed: Option<bool>,
+    /// 最近一次执行使用的场景版本
+    pub last_run_version: Option<i32>,
+}
+
 /// 主机配置数据库模型
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct HostRecord {
@@ -58,6 +201,24 @@ pub struct HostRecord {
     pub updated_at: DateTime<Utc>,
 }
 
+/// 主机连接探测结果数据库模型
+///
+/// 探测失败时只更新探测时间与错误, 保留最近一次成功探测到的版本与能力。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct HostProbeRecord {
+    pub host_id: String,
+    pub probed_at: DateTime<Utc>,
+    pub reachable: bool,
+    pub last_seen_at: Option<DateTime<Utc>>, // 最近一次连接成功的时间
+    pub error: Option<String>,
+    pub libvirt_version: Option<String>,
+    pub hypervisor: Option<String>,
+    pub hypervisor_version: Option<String>,
+    pub cpu_model: Option<String>,
+    pub max_vcpus: Option<i64>,
+    pub capabilities: Option<String>, // JSON
+}
+
 /// 性能指标数据库模型
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct ConnectionMetricRecord {
@@ -71,6 +232,235 @@ pub struct ConnectionMetricRecord {
     pub avg_response_time: Option<f64>,
 }
 
+/// 指标采样数据库模型
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MetricSampleRecord {
+    pub id: i64,
+    pub source: String,
+    pub name: String,
+    pub value: f64,
+    pub labels: Option<String>, // JSON object
+    pub timestamp: DateTime<Utc>,
+}
+
+/// 时序指标的实体类型: 主机
+pub const METRIC_ENTITY_HOST: &str = "host";
+
+/// 时序指标的实体类型: 虚拟机
+pub const METRIC_ENTITY_VM: &str = "vm";
+
+/// 指标所属的实体 (主机或虚拟机)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MetricEntity {
+    pub entity_type: String,
+    pub entity_id: String,
+}
+
+impl MetricEntity {
+    /// 虚拟机 (以虚拟机名称标识)
+    pub fn vm(name: &str) -> Self {
+        Self {
+            entity_type: METRIC_ENTITY_VM.to_string(),
+            entity_id: name.to_string(),
+        }
+    }
+
+    /// 主机 (以主机 ID 标识)
+    pub fn host(id: &str) -> Self {
+        Self {
+            entity_type: METRIC_ENTITY_HOST.to_string(),
+            entity_id: id.to_string(),
+        }
+    }
+}
+
+/// 主机 / 虚拟机时序指标采样 (`metrics` 表)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct EntityMetricSample {
+    pub entity_type: String, // host, vm
+    pub entity_id: String,
+    pub metric_name: String,
+    pub value: f64,
+    pub sampled_at: DateTime<Utc>,
+}
+
+impl EntityMetricSample {
+    pub fn new(entity: &MetricEntity, metric_name: &str, value: f64, sampled_at: DateTime<Utc>) -> Self {
+        Self {
+            entity_type: entity.entity_type.clone(),
+            entity_id: entity.entity_id.clone(),
+            metric_name: metric_name.to_string(),
+            value,
+            sampled_at,
+        }
+    }
+}
+
+/// 降采样后的一个时间桶
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MetricBucket {
+    /// 桶的起始时间 (按桶长度对齐到 Unix 纪元)
+    pub bucket_start: DateTime<Utc>,
+    pub avg: f64,
+    pub max: f64,
+    /// 桶内的采样数
+    pub count: i64,
+}
+
+/// Guest 验证结果数据库模型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct VerificationResultRecord {
+    pub id: i64,
+    pub vm_id: String,
+    pub event_id: String,
+    pub event_type: String,
+    pub verified: bool,
+    pub outcome: String,                // verified, mismatched, timeout
+    pub latency_ms: Option<i64>,        // Agent 上报, 超时时为 None
+    pub server_latency_ms: Option<i64>, // 服务端测得, 超时时为 None
+    pub details: Option<String>,        // JSON
+    pub created_at: DateTime<Utc>,
+}
+
+/// 基准测试运行记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct BenchmarkRunRecord {
+    pub id: i64,
+    pub kind: String,          // input-latency
+    pub target: String,        // 虚拟机名称
+    pub label: Option<String>, // 构建或版本标签
+    pub samples: i64,
+    pub observed: i64,
+    pub mismatched: i64,
+    pub timeouts: i64,
+    pub failures: i64,
+    pub min_ms: Option<i64>, // 没有观察到的样本时为 None
+    pub median_ms: Option<i64>,
+    pub p95_ms: Option<i64>,
+    pub p99_ms: Option<i64>,
+    pub max_ms: Option<i64>,
+    pub mean_ms: Option<f64>,
+    pub latencies: String,       // JSON 数组
+    pub options: Option<String>, // JSON
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: i64,
+}
+
+/// 虚拟机缓存数据库模型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct VmCacheRecord {
+    pub id: String,
+    pub name: String,
+    pub status: String,
+    pub host_id: String,
+    pub cpu: Option<i64>,
+    pub memory: Option<i64>, // MB
+    pub updated_at: DateTime<Utc>,
+}
+
+/// 虚拟机状态变更记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct VmStatusHistoryRecord {
+    pub id: i64,
+    pub vm_id: String,
+    pub old_status: Option<String>, // 首次同步时为 None
+    pub new_status: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// 报告保留规则数据库模型
+///
+/// 场景名称匹配 `scenario_pattern` 的报告中, 同一场景最近 `keep_last_n` 次执行
+/// 以及 `keep_days` 天内的执行受保护, 两者都设置时满足其一即可。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow)]
+pub struct RetentionPolicyRecord {
+    pub id: i64,
+    pub scenario_pattern: String, // 支持 * 和 ? 通配符
+    pub keep_last_n: Option<i64>,
+    pub keep_days: Option<i64>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl RetentionPolicyRecord {
+    pub fn new(scenario_pattern: &str, keep_last_n: Option<i64>, keep_days: Option<i64>) -> Self {
+        Self {
+            id: 0,
+            scenario_pattern: scenario_pattern.to_string(),
+            keep_last_n,
+            keep_days,
+            created_at: Utc::now(),
+        }
+    }
+
+    /// 场景名称是否匹配该规则
+    pub fn matches(&self, scenario_name: &str) -> bool {
+        let pattern: Vec<char> = self.scenario_pattern.chars().collect();
+        let name: Vec<char> = scenario_name.chars().collect();
+        glob_matches(&pattern, &name)
+    }
+
+    /// 报告是否受该规则保护
+    ///
+    /// `rank` 为报告在同一场景中按开始时间倒序的序号 (从 0 开始)。
+    pub fn protects(&self, report: &TestReportRecord, rank: usize, now: DateTime<Utc>) -> bool {
+        if !self.matches(&report.scenario_name) {
+            return false;
+        }
+
+        let recent_run = self.keep_last_n.is_some_and(|n| (rank as i64) < n);
+        let recent_day = self
+            .keep_days
+            .is_some_and(|days| report.start_time >= now - chrono::Duration::days(days));
+        recent_run || recent_day
+    }
+}
+
+/// `*`/`?` 通配符匹配
+fn glob_matches(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| glob_matches(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && glob_matches(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && glob_matches(rest, &text[1..]),
+    }
+}
+
+/// 执行保留规则的结果
+#[derive(Debug, Default, Clone)]
+pub struct RetentionResult {
+    /// 已删除 (演练时为将被删除) 的报告, 按开始时间倒序
+    pub reports: Vec<TestReportRecord>,
+    /// 删除统计
+    pub stats: ReportCleanupStats,
+}
+
+/// 指标查询过滤器
+#[derive(Debug, Default, Clone)]
+pub struct MetricFilter {
+    pub source: Option<String>,
+    pub name: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+/// 验证结果查询过滤器
+#[derive(Debug, Default, Clone)]
+pub struct VerificationFilter {
+    pub vm_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+}
+
+/// 基准测试运行查询过滤器
+#[derive(Debug, Default, Clone)]
+pub struct BenchmarkFilter {
+    pub kind: Option<String>,
+    pub target: Option<String>,
+    pub limit: Option<i64>,
+}
+
 /// 报告查询过滤器
 #[derive(Debug, Default, Clone)]
 pub struct ReportFilter {
@@ -79,6 +469,8 @@ pub struct ReportFilter {
     pub start_time_from: Option<DateTime<Utc>>,
     pub start_time_to: Option<DateTime<Utc>>,
     pub tags: Option<Vec<String>>,
+    /// 只列出含有该失败分类步骤的报告
+    pub failed_kind: Option<String>,
     pub limit: Option<i64>,
     pub offset: Option<i64>,
 }
@@ -91,3 +483,43 @@ pub struct ScenarioFilter {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
 }
+
+/// 报告清理条件
+///
+/// 各条件之间为 AND 关系, 未设置的条件不参与过滤。
+#[derive(Debug, Default, Clone)]
+pub struct ReportCleanupCriteria {
+    /// 只清理开始时间早于该时间点的报告
+    pub before: Option<DateTime<Utc>>,
+    /// 场景名称匹配模式, 支持 `*` (任意字符) 和 `?` (单个字符) 通配符
+    pub scenario_pattern: Option<String>,
+    /// 只清理带有该标签的报告
+    pub tag: Option<String>,
+}
+
+/// 报告清理统计
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportCleanupStats {
+    /// 匹配(或已删除)的报告数
+    pub report_count: i64,
+    /// 关联的执行步骤数
+    pub step_count: i64,
+    /// 预计释放的空间(字节, 按文本字段长度估算)
+    pub estimated_bytes: i64,
+}
+
+impl ReportCleanupStats {
+    /// 格式化预计释放空间
+    pub fn estimated_size_human_readable(&self) -> String {
+        const KB: i64 = 1024;
+        const MB: i64 = KB * 1024;
+
+        if self.estimated_bytes >= MB {
+            format!("{:.2} MB", self.estimated_bytes as f64 / MB as f64)
+        } else if self.estimated_bytes >= KB {
+            format!("{:.2} KB", self.estimated_bytes as f64 / KB as f64)
+        } else {
+            format!("{} B", self.estimated_bytes)
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use sqlx::SqlitePool;
+use tracing::debug;
+
+use crate::error::{Result, StorageError};
+use crate::models::{BenchmarkFilter, BenchmarkRunRecord};
+
+/// benchmark_runs 表的列 (与 [`BenchmarkRunRecord`] 的字段一致)
+const COLUMNS: &str = "id, kind, target, label, samples, observed, mismatched, timeouts, failures, \
+    min_ms, median_ms, p95_ms, p99_ms, max_ms, mean_ms, latencies, options, started_at, duration_ms";
+
+/// 基准测试仓储
+#[derive(Clone)]
+pub struct BenchmarkRepository {
+    pool: SqlitePool,
+}
+
+impl BenchmarkRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 写入一次运行结果, 返回新记录的 ID
+    pub async fn create(&self, record: &BenchmarkRunRecord) -> Result<i64> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO benchmark_runs
+                (kind, target, label, samples, observed, mismatched, timeouts, failures,
+                 min_ms, median_ms, p95_ms, p99_ms, max_ms, mean_ms, latencies, options, started_at, duration_ms)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&record.kind)
+        .bind(&record.target)
+        .bind(&record.label)
+        .bind(record.samples)
+        .bind(record.observed)
+        .bind(record.mismatched)
+        .bind(record.timeouts)
+        .bind(record.failures)
+        .bind(record.min_ms)
+        .bind(record.median_ms)
+        .bind(record.p95_ms)
+        .bind(record.p99_ms)
+        .bind(record.max_ms)
+        .bind(record.mean_ms)
+        .bind(&record.latencies)
+        .bind(&record.options)
+        .bind(record.started_at)
+        .bind(record.duration_ms)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+
+        debug!("Inserted {} benchmark run {} for {}", record.kind, id, record.target);
+        Ok(id)
+    }
+
+    /// 根据 ID 查询
+    pub async fn get_by_id(&self, id: i64) -> Result<Option<BenchmarkRunRecord>> {
+        let record = sqlx::query_as::<_, BenchmarkRunRecord>(&format!(
+            "SELECT {} FROM benchmark_runs WHERE id = ?",
+            COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// 查询运行记录 (按开始时间倒序)
+    pub async fn list(&self, filter: &BenchmarkFilter) -> Result<Vec<BenchmarkRunRecord>> {
+        let mut query = format!("SELECT {} FROM benchmark_runs WHERE 1=1", COLUMNS);
+
+        if filter.kind.is_some() {
+            query.push_str(" AND kind = ?");
+        }
+
+        if filter.target.is_some() {
+            query.push_str(" AND target = ?");
+        }
+
+        query.push_str(" ORDER BY started_at DESC, id DESC");
+
+        if let Some(limit) = filter.limit {
+            query.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let mut sql_query = sqlx::query_as::<_, BenchmarkRunRecord>(&query);
+
+        if let Some(kind) = &filter.kind {
+            sql_query = sql_query.bind(kind);
+        }
+
+        if let Some(target) = &filter.target {
+            sql_query = sql_query.bind(target);
+        }
+
+        let records = sql_query.fetch_all(&self.pool).await?;
+
+        Ok(records)
+    }
+
+    /// 删除运行记录
+    pub async fn delete(&self, id: i64) -> Result<()> {
+        let result = sqlx::query("DELETE FROM benchmark_runs WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Benchmark run {} not found", id)));
+        }
+
+        debug!("Deleted benchmark run {}", id);
+
+        Ok(())
+    }
+}
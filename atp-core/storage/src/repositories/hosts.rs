@@ -0,0 +1,190 @@
+use chrono::Utc;
+use sqlx::SqlitePool;
+use tracing::debug;
+
+use crate::error::{Result, StorageError};
+use crate::models::{HostProbeRecord, HostRecord};
+
+/// host_probes 表的列 (与 [`HostProbeRecord`] 的字段一致)
+const PROBE_COLUMNS: &str = "host_id, probed_at, reachable, last_seen_at, error, libvirt_version, hypervisor, \
+    hypervisor_version, cpu_model, max_vcpus, capabilities";
+
+/// 主机仓储
+#[derive(Clone)]
+pub struct HostRepository {
+    pool: SqlitePool,
+}
+
+impl HostRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 按主机 ID 新增或更新主机 (更新时保留创建时间)
+    ///
+    /// 返回 true 表示新增, false 表示更新了已有主机。
+    pub async fn upsert_by_host_id(&self, host: &HostRecord) -> Result<bool> {
+        let exists: Option<(String,)> = sqlx::query_as("SELECT id FROM hosts WHERE id = ?")
+            .bind(&host.id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        let now = Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO hosts (id, host, uri, tags, metadata, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                host = excluded.host,
+                uri = excluded.uri,
+                tags = excluded.tags,
+                metadata = excluded.metadata,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&host.id)
+        .bind(&host.host)
+        .bind(&host.uri)
+        .bind(&host.tags)
+        .bind(&host.metadata)
+        .bind(now)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+
+        debug!("Upserted host '{}'", host.id);
+        Ok(exists.is_none())
+    }
+
+    /// 根据主机 ID 查询
+    pub async fn get_by_id(&self, id: &str) -> Result<Option<HostRecord>> {
+        let host = sqlx::query_as::<_, HostRecord>(
+            r#"
+            SELECT id, host, uri, tags, metadata, created_at, updated_at
+            FROM hosts
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(host)
+    }
+
+    /// 列出所有主机 (按 ID 排序)
+    pub async fn list_all(&self) -> Result<Vec<HostRecord>> {
+        let hosts = sqlx::query_as::<_, HostRecord>(
+            r#"
+            SELECT id, host, uri, tags, metadata, created_at, updated_at
+            FROM hosts
+            ORDER BY id ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(hosts)
+    }
+
+    /// 删除主机
+    pub async fn delete(&self, id: &str) -> Result<()> {
+        let result = sqlx::query("DELETE FROM hosts WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Host {} not found", id)));
+        }
+
+        debug!("Deleted host {}", id);
+
+        Ok(())
+    }
+
+    /// 设置主机标签
+    pub async fn set_tags(&self, id: &str, tags: &[String]) -> Result<()> {
+        let tags = if tags.is_empty() { None } else { Some(serde_json::to_string(tags)?) };
+        let result = sqlx::query("UPDATE hosts SET tags = ?, updated_at = ? WHERE id = ?")
+            .bind(tags)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Host {} not found", id)));
+        }
+
+        debug!("Updated tags of host {}", id);
+        Ok(())
+    }
+
+    /// 记录一次连接探测
+    ///
+    /// 探测成功时覆盖版本与能力并更新最近在线时间; 失败时只更新探测时间、状态与错误。
+    pub async fn record_probe(&self, probe: &HostProbeRecord) -> Result<()> {
+        let update = if probe.reachable {
+            r#"
+                probed_at = excluded.probed_at,
+                reachable = excluded.reachable,
+                last_seen_at = excluded.last_seen_at,
+                error = excluded.error,
+                libvirt_version = excluded.libvirt_version,
+                hypervisor = excluded.hypervisor,
+                hypervisor_version = excluded.hypervisor_version,
+                cpu_model = excluded.cpu_model,
+                max_vcpus = excluded.max_vcpus,
+                capabilities = excluded.capabilities
+            "#
+        } else {
+            r#"
+                probed_at = excluded.probed_at,
+                reachable = excluded.reachable,
+                error = excluded.error
+            "#
+        };
+        let query = format!(
+            "INSERT INTO host_probes ({}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT(host_id) DO UPDATE SET {}",
+            PROBE_COLUMNS, update
+        );
+
+        sqlx::query(&query)
+            .bind(&probe.host_id)
+            .bind(probe.probed_at)
+            .bind(probe.reachable)
+            .bind(probe.last_seen_at)
+            .bind(&probe.error)
+            .bind(&probe.libvirt_version)
+            .bind(&probe.hypervisor)
+            .bind(&probe.hypervisor_version)
+            .bind(&probe.cpu_model)
+            .bind(probe.max_vcpus)
+            .bind(&probe.capabilities)
+            .execute(&self.pool)
+            .await?;
+
+        debug!("Recorded probe of host {} (reachable: {})", probe.host_id, probe.reachable);
+        Ok(())
+    }
+
+    /// 查询主机最近一次探测结果
+    pub async fn get_probe(&self, host_id: &str) -> Result<Option<HostProbeRecord>> {
+        let probe = sqlx::query_as::<_, HostProbeRecord>(&format!("SELECT {} FROM host_probes WHERE host_id = ?", PROBE_COLUMNS))
+            .bind(host_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(probe)
+    }
+
+    /// 列出所有主机的最近一次探测结果 (按主机 ID 排序)
+    pub async fn list_probes(&self) -> Result<Vec<HostProbeRecord>> {
+        let probes = sqlx::query_as::<_, HostProbeRecord>(&format!("SELECT {} FROM host_probes ORDER BY host_id ASC", PROBE_COLUMNS))
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(probes)
+    }
+}
@@ -0,0 +1,244 @@
+use std::ops::Range;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+use tracing::debug;
+
+use crate::error::{Result, StorageError};
+use crate::models::{EntityMetricSample, MetricBucket, MetricEntity, MetricFilter, MetricSampleRecord};
+
+/// 批量写入 `metrics` 表时每条 INSERT 的行数 (每行 5 个参数, 不超过 SQLite 默认的 999 个参数上限)
+const INSERT_CHUNK_ROWS: usize = 150;
+
+/// 指标仓储
+///
+/// `metric_samples` 表保存采集器拉取的通用指标; `metrics` 表保存按主机 / 虚拟机区分的时序指标,
+/// 支持按时间桶降采样查询 ([`aggregate`](Self::aggregate))。
+#[derive(Clone)]
+pub struct MetricRepository {
+    pool: SqlitePool,
+}
+
+impl MetricRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 批量写入指标采样 (单个事务)
+    pub async fn create_batch(&self, samples: &[MetricSampleRecord]) -> Result<u64> {
+        if samples.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        for sample in samples {
+            sqlx::query(
+                r#"
+                INSERT INTO metric_samples (source, name, value, labels, timestamp)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&sample.source)
+            .bind(&sample.name)
+            .bind(sample.value)
+            .bind(&sample.labels)
+            .bind(sample.timestamp)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        debug!("Inserted {} metric samples", samples.len());
+        Ok(samples.len() as u64)
+    }
+
+    /// 查询指标采样 (按时间升序)
+    pub async fn list(&self, filter: &MetricFilter) -> Result<Vec<MetricSampleRecord>> {
+        let mut query = String::from(
+            r#"
+            SELECT id, source, name, value, labels, timestamp
+            FROM metric_samples
+            WHERE 1=1
+            "#,
+        );
+
+        let mut bindings = Vec::new();
+
+        if let Some(source) = &filter.source {
+            query.push_str(" AND source = ?");
+            bindings.push(source.clone());
+        }
+
+        if let Some(name) = &filter.name {
+            query.push_str(" AND name = ?");
+            bindings.push(name.clone());
+        }
+
+        if filter.from.is_some() {
+            query.push_str(" AND timestamp >= ?");
+        }
+
+        if filter.to.is_some() {
+            query.push_str(" AND timestamp <= ?");
+        }
+
+        query.push_str(" ORDER BY timestamp ASC, id ASC");
+
+        if let Some(limit) = filter.limit {
+            query.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let mut sql_query = sqlx::query_as::<_, MetricSampleRecord>(&query);
+
+        for binding in &bindings {
+            sql_query = sql_query.bind(binding);
+        }
+
+        if let Some(from) = filter.from {
+            sql_query = sql_query.bind(from);
+        }
+
+        if let Some(to) = filter.to {
+            sql_query = sql_query.bind(to);
+        }
+
+        let samples = sql_query.fetch_all(&self.pool).await?;
+
+        Ok(samples)
+    }
+
+    /// 统计指标采样数量
+    pub async fn count(&self) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM metric_samples")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count.0)
+    }
+
+    /// 删除指定时间之前的指标采样
+    pub async fn delete_before(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM metric_samples WHERE timestamp < ?")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+
+        debug!("Deleted {} metric samples", result.rows_affected());
+        Ok(result.rows_affected())
+    }
+
+    /// 批量写入主机 / 虚拟机时序指标 (单个事务, 多行 INSERT)
+    pub async fn insert_batch(&self, samples: &[EntityMetricSample]) -> Result<u64> {
+        if samples.is_empty() {
+            return Ok(0);
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        for chunk in samples.chunks(INSERT_CHUNK_ROWS) {
+            let mut builder: QueryBuilder<Sqlite> =
+                QueryBuilder::new("INSERT INTO metrics (entity_type, entity_id, metric_name, value, sampled_at) ");
+            builder.push_values(chunk, |mut row, sample| {
+                row.push_bind(&sample.entity_type)
+                    .push_bind(&sample.entity_id)
+                    .push_bind(&sample.metric_name)
+                    .push_bind(sample.value)
+                    .push_bind(sample.sampled_at);
+            });
+            builder.build().execute(&mut *tx).await?;
+        }
+
+        tx.commit().await?;
+
+        debug!("Inserted {} entity metric samples", samples.len());
+        Ok(samples.len() as u64)
+    }
+
+    /// 查询实体在时间范围 `[start, end)` 内的指标采样 (按时间升序)
+    pub async fn range(
+        &self,
+        entity: &MetricEntity,
+        metric_name: &str,
+        range: Range<DateTime<Utc>>,
+    ) -> Result<Vec<EntityMetricSample>> {
+        let samples = sqlx::query_as::<_, EntityMetricSample>(
+            r#"
+            SELECT entity_type, entity_id, metric_name, value, sampled_at
+            FROM metrics
+            WHERE entity_id = ? AND metric_name = ? AND entity_type = ?
+                AND sampled_at >= ? AND sampled_at < ?
+            ORDER BY sampled_at ASC, id ASC
+            "#,
+        )
+        .bind(&entity.entity_id)
+        .bind(metric_name)
+        .bind(&entity.entity_type)
+        .bind(range.start)
+        .bind(range.end)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(samples)
+    }
+
+    /// 按时间桶降采样: 返回时间范围 `[start, end)` 内每个非空桶的平均值与最大值 (按时间升序)
+    ///
+    /// 桶按 `bucket` 长度对齐到 Unix 纪元, 长度至少 1 秒。
+    pub async fn aggregate(
+        &self,
+        entity: &MetricEntity,
+        metric_name: &str,
+        range: Range<DateTime<Utc>>,
+        bucket: Duration,
+    ) -> Result<Vec<MetricBucket>> {
+        let bucket_secs = bucket.num_seconds();
+        if bucket_secs < 1 {
+            return Err(StorageError::ValidationError(format!(
+                "降采样时间桶至少 1 秒, 实际为 {}ms",
+                bucket.num_milliseconds()
+            )));
+        }
+
+        let rows: Vec<(i64, f64, f64, i64)> = sqlx::query_as(
+            r#"
+            SELECT (CAST(strftime('%s', sampled_at) AS INTEGER) / ?) * ? AS bucket_start,
+                AVG(value), MAX(value), COUNT(*)
+            FROM metrics
+            WHERE entity_id = ? AND metric_name = ? AND entity_type = ?
+                AND sampled_at >= ? AND sampled_at < ?
+            GROUP BY bucket_start
+            ORDER BY bucket_start ASC
+            "#,
+        )
+        .bind(bucket_secs)
+        .bind(bucket_secs)
+        .bind(&entity.entity_id)
+        .bind(metric_name)
+        .bind(&entity.entity_type)
+        .bind(range.start)
+        .bind(range.end)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|(start, avg, max, count)| {
+                let bucket_start = Utc
+                    .timestamp_opt(start, 0)
+                    .single()
+                    .ok_or_else(|| StorageError::ValidationError(format!("无效的时间桶起点: {}", start)))?;
+                Ok(MetricBucket { bucket_start, avg, max, count })
+            })
+            .collect()
+    }
+
+    /// 统计主机 / 虚拟机时序指标采样数量
+    pub async fn count_entity_samples(&self) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM metrics")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count.0)
+    }
+}
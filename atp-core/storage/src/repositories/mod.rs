@@ -1,6 +1,17 @@
+mod benchmarks;
+mod hosts;
+mod metrics;
 mod reports;
+mod retention;
 mod scenarios;
-// TODO: 添加 hosts, metrics repositories
+mod verification;
+mod vm_cache;
 
+pub use benchmarks::BenchmarkRepository;
+pub use hosts::HostRepository;
+pub use metrics::MetricRepository;
 pub use reports::ReportRepository;
+pub use retention::RetentionRepository;
 pub use scenarios::ScenarioRepository;
+pub use verification::VerificationRepository;
+pub use vm_cache::VmCacheRepository;
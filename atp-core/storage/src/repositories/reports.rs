@@ -1,9 +1,13 @@
 use chrono::Utc;
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 use tracing::debug;
 
 use crate::error::{Result, StorageError};
-use crate::models::{ExecutionStepRecord, ReportFilter, TestReportRecord};
+use crate::models::{
+    BundleMetadata, DailyStats, ExecutionStepRecord, FlakyStep, ReportBundle,
+    ReportCleanupCriteria, ReportCleanupStats, ReportFilter, ReportResourceRecord, SlowStep,
+    StepMetricsRecord, TestReportRecord, REPORT_BUNDLE_VERSION,
+};
 
 /// 测试报告仓储
 pub struct ReportRepository {
@@ -23,8 +27,9 @@ impl ReportRepository {
             r#"
             INSERT INTO test_reports
             (scenario_name, description, start_time, end_time, duration_ms,
-             total_steps, success_count, failed_count, skipped_count, passed, tags)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+             total_steps, success_count, failed_count, skipped_count, passed, tags,
+             scenario_version)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&report.scenario_name)
@@ -38,6 +43,7 @@ impl ReportRepository {
         .bind(report.skipped_count)
         .bind(report.passed)
         .bind(tags_json)
+        .bind(report.scenario_version)
         .execute(&self.pool)
         .await?;
 
@@ -52,8 +58,9 @@ impl ReportRepository {
         let result = sqlx::query(
             r#"
             INSERT INTO execution_steps
-            (report_id, step_index, description, status, error, duration_ms, output)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            (report_id, step_index, description, status, error, duration_ms, output, started_at_offset_ms,
+             error_kind)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(step.report_id)
@@ -63,6 +70,8 @@ impl ReportRepository {
         .bind(&step.error)
         .bind(step.duration_ms)
         .bind(&step.output)
+        .bind(step.started_at_offset_ms)
+        .bind(&step.error_kind)
         .execute(&self.pool)
         .await?;
 
@@ -77,12 +86,106 @@ impl ReportRepository {
         Ok(())
     }
 
+    /// 批量保存场景资源记录
+    pub async fn create_resources(&self, resources: &[ReportResourceRecord]) -> Result<()> {
+        for resource in resources {
+            sqlx::query(
+                r#"
+                INSERT INTO report_resources
+                (report_id, resource_type, resource_id, name, step_index, cleanup_status, cleanup_error)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(resource.report_id)
+            .bind(&resource.resource_type)
+            .bind(&resource.resource_id)
+            .bind(&resource.name)
+            .bind(resource.step_index)
+            .bind(&resource.cleanup_status)
+            .bind(&resource.cleanup_error)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// 获取报告的场景资源记录
+    pub async fn get_resources(&self, report_id: i64) -> Result<Vec<ReportResourceRecord>> {
+        let resources = sqlx::query_as::<_, ReportResourceRecord>(
+            r#"
+            SELECT id, report_id, resource_type, resource_id, name, step_index,
+                   cleanup_status, cleanup_error
+            FROM report_resources
+            WHERE report_id = ?
+            ORDER BY id ASC
+            "#,
+        )
+        .bind(report_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(resources)
+    }
+
+    /// 批量保存步骤资源指标
+    pub async fn create_step_metrics(&self, metrics: &[StepMetricsRecord]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for record in metrics {
+            insert_step_metrics(&mut tx, record.report_id, record).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// 获取报告的步骤资源指标 (按步骤顺序)
+    pub async fn get_step_metrics(&self, report_id: i64) -> Result<Vec<StepMetricsRecord>> {
+        let metrics = sqlx::query_as::<_, StepMetricsRecord>(
+            r#"
+            SELECT id, report_id, step_index, sample_count, interval_ms,
+                   cpu_min, cpu_avg, cpu_max, memory_min_mb, memory_avg_mb, memory_max_mb,
+                   block_read_bytes, block_write_bytes
+            FROM step_metrics
+            WHERE report_id = ?
+            ORDER BY step_index ASC, id ASC
+            "#,
+        )
+        .bind(report_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(metrics)
+    }
+
+    /// 更新场景资源记录的清理结果
+    pub async fn update_resource_status(
+        &self,
+        id: i64,
+        cleanup_status: &str,
+        cleanup_error: Option<&str>,
+    ) -> Result<()> {
+        let result = sqlx::query(
+            "UPDATE report_resources SET cleanup_status = ?, cleanup_error = ? WHERE id = ?",
+        )
+        .bind(cleanup_status)
+        .bind(cleanup_error)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Report resource {} not found", id)));
+        }
+
+        Ok(())
+    }
+
     /// 根据ID获取报告
     pub async fn get_by_id(&self, id: i64) -> Result<Option<TestReportRecord>> {
         let report = sqlx::query_as::<_, TestReportRecord>(
             r#"
             SELECT id, scenario_name, description, start_time, end_time, duration_ms,
-                   total_steps, success_count, failed_count, skipped_count, passed, tags, created_at
+                   total_steps, success_count, failed_count, skipped_count, passed, tags, created_at,
+                   scenario_version
             FROM test_reports
             WHERE id = ?
             "#,
@@ -98,7 +201,8 @@ impl ReportRepository {
     pub async fn get_steps(&self, report_id: i64) -> Result<Vec<ExecutionStepRecord>> {
         let steps = sqlx::query_as::<_, ExecutionStepRecord>(
             r#"
-            SELECT id, report_id, step_index, description, status, error, duration_ms, output
+            SELECT id, report_id, step_index, description, status, error, duration_ms, output,
+                   started_at_offset_ms, error_kind
             FROM execution_steps
             WHERE report_id = ?
             ORDER BY step_index ASC
@@ -116,7 +220,8 @@ impl ReportRepository {
         let mut query = String::from(
             r#"
             SELECT id, scenario_name, description, start_time, end_time, duration_ms,
-                   total_steps, success_count, failed_count, skipped_count, passed, tags, created_at
+                   total_steps, success_count, failed_count, skipped_count, passed, tags, created_at,
+                   scenario_version
             FROM test_reports
             WHERE 1=1
             "#,
@@ -143,6 +248,14 @@ impl ReportRepository {
             query.push_str(" AND start_time <= ?");
         }
 
+        if let Some(kind) = &filter.failed_kind {
+            query.push_str(
+                " AND EXISTS (SELECT 1 FROM execution_steps s \
+                 WHERE s.report_id = test_reports.id AND s.status = 'Failed' AND s.error_kind = ?)",
+            );
+            bindings.push(kind.clone());
+        }
+
         // TODO: 支持 tags 过滤 (需要 JSON 函数)
 
         query.push_str(" ORDER BY start_time DESC");
@@ -174,6 +287,119 @@ impl ReportRepository {
         Ok(reports)
     }
 
+    /// 导出报告及其步骤、资源记录与步骤资源指标
+    pub async fn export_bundle(&self, report_id: i64, profile: Option<&str>) -> Result<ReportBundle> {
+        let report = self
+            .get_by_id(report_id)
+            .await?
+            .ok_or_else(|| StorageError::NotFound(format!("Report {} not found", report_id)))?;
+
+        Ok(ReportBundle {
+            metadata: BundleMetadata::collect(profile),
+            report,
+            steps: self.get_steps(report_id).await?,
+            resources: self.get_resources(report_id).await?,
+            step_metrics: self.get_step_metrics(report_id).await?,
+        })
+    }
+
+    /// 导入报告导出包 (单个事务), 返回新的报告 ID
+    ///
+    /// 报告使用新的 ID, 保留原始的开始时间与创建时间。
+    /// 报告只按名称引用场景, 本地是否存在同名场景不影响导入。
+    pub async fn import_bundle(&self, bundle: &ReportBundle) -> Result<i64> {
+        if bundle.metadata.bundle_version > REPORT_BUNDLE_VERSION {
+            return Err(StorageError::ValidationError(format!(
+                "Unsupported report bundle version {} (supported: {})",
+                bundle.metadata.bundle_version, REPORT_BUNDLE_VERSION
+            )));
+        }
+
+        let report = &bundle.report;
+        let mut tx = self.pool.begin().await?;
+
+        let report_id = sqlx::query(
+            r#"
+            INSERT INTO test_reports
+            (scenario_name, description, start_time, end_time, duration_ms,
+             total_steps, success_count, failed_count, skipped_count, passed, tags, created_at,
+             scenario_version)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&report.scenario_name)
+        .bind(&report.description)
+        .bind(report.start_time)
+        .bind(report.end_time)
+        .bind(report.duration_ms)
+        .bind(report.total_steps)
+        .bind(report.success_count)
+        .bind(report.failed_count)
+        .bind(report.skipped_count)
+        .bind(report.passed)
+        .bind(&report.tags)
+        .bind(report.created_at)
+        .bind(report.scenario_version)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        for step in &bundle.steps {
+            sqlx::query(
+                r#"
+                INSERT INTO execution_steps
+                (report_id, step_index, description, status, error, duration_ms, output, started_at_offset_ms,
+                 error_kind)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(report_id)
+            .bind(step.step_index)
+            .bind(&step.description)
+            .bind(&step.status)
+            .bind(&step.error)
+            .bind(step.duration_ms)
+            .bind(&step.output)
+            .bind(step.started_at_offset_ms)
+            .bind(&step.error_kind)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for resource in &bundle.resources {
+            sqlx::query(
+                r#"
+                INSERT INTO report_resources
+                (report_id, resource_type, resource_id, name, step_index, cleanup_status, cleanup_error)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(report_id)
+            .bind(&resource.resource_type)
+            .bind(&resource.resource_id)
+            .bind(&resource.name)
+            .bind(resource.step_index)
+            .bind(&resource.cleanup_status)
+            .bind(&resource.cleanup_error)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        for record in &bundle.step_metrics {
+            insert_step_metrics(&mut tx, report_id, record).await?;
+        }
+
+        tx.commit().await?;
+
+        debug!(
+            "Imported report {} from bundle (original ID {}) with {} steps",
+            report_id,
+            report.id,
+            bundle.steps.len()
+        );
+        Ok(report_id)
+    }
+
     /// 删除报告(级联删除步骤)
     pub async fn delete(&self, id: i64) -> Result<()> {
         let result = sqlx::query("DELETE FROM test_reports WHERE id = ?")
@@ -210,6 +436,90 @@ impl ReportRepository {
         Ok(result.0)
     }
 
+    /// 场景最近若干天的每日执行趋势 (按日期升序)
+    pub async fn scenario_trend(&self, scenario_name: &str, days: i32) -> Result<Vec<DailyStats>> {
+        let start_time = Utc::now() - chrono::Duration::days(days as i64);
+
+        let stats = sqlx::query_as::<_, DailyStats>(
+            r#"
+            SELECT date(start_time) AS date,
+                   COUNT(*) AS runs,
+                   SUM(CASE WHEN passed = 1 THEN 1 ELSE 0 END) AS passed_runs,
+                   CAST(SUM(CASE WHEN passed = 1 THEN 1 ELSE 0 END) AS REAL) / COUNT(*) * 100 AS pass_rate,
+                   AVG(duration_ms) AS avg_duration_ms
+            FROM test_reports
+            WHERE scenario_name = ? AND start_time >= ?
+            GROUP BY date(start_time)
+            ORDER BY date ASC
+            "#,
+        )
+        .bind(scenario_name)
+        .bind(start_time)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(stats)
+    }
+
+    /// 检测不稳定步骤
+    ///
+    /// 按步骤描述聚合场景最近若干天的执行结果, 返回既有失败又有成功、
+    /// 且执行次数 (不含跳过) 不少于 `min_runs` 的步骤, 按失败率降序排列。
+    pub async fn flaky_steps(
+        &self,
+        scenario_name: &str,
+        days: i32,
+        min_runs: i64,
+    ) -> Result<Vec<FlakyStep>> {
+        let start_time = Utc::now() - chrono::Duration::days(days as i64);
+
+        let steps = sqlx::query_as::<_, FlakyStep>(
+            r#"
+            SELECT s.description AS description,
+                   COUNT(*) AS runs,
+                   SUM(CASE WHEN s.status = 'Failed' THEN 1 ELSE 0 END) AS failures,
+                   CAST(SUM(CASE WHEN s.status = 'Failed' THEN 1 ELSE 0 END) AS REAL) / COUNT(*) * 100 AS failure_rate
+            FROM execution_steps s
+            JOIN test_reports r ON r.id = s.report_id
+            WHERE r.scenario_name = ? AND r.start_time >= ? AND s.status IN ('Success', 'Failed')
+            GROUP BY s.description
+            HAVING failures > 0 AND failures < runs AND runs >= ?
+            ORDER BY failure_rate DESC, runs DESC, description ASC
+            "#,
+        )
+        .bind(scenario_name)
+        .bind(start_time)
+        .bind(min_runs)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(steps)
+    }
+
+    /// 场景中平均耗时最长的步骤
+    pub async fn slowest_steps(&self, scenario_name: &str, limit: i64) -> Result<Vec<SlowStep>> {
+        let steps = sqlx::query_as::<_, SlowStep>(
+            r#"
+            SELECT s.description AS description,
+                   COUNT(*) AS runs,
+                   AVG(s.duration_ms) AS avg_duration_ms,
+                   MAX(s.duration_ms) AS max_duration_ms
+            FROM execution_steps s
+            JOIN test_reports r ON r.id = s.report_id
+            WHERE r.scenario_name = ? AND s.duration_ms IS NOT NULL
+            GROUP BY s.description
+            ORDER BY avg_duration_ms DESC, description ASC
+            LIMIT ?
+            "#,
+        )
+        .bind(scenario_name)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(steps)
+    }
+
     /// 获取报告总数
     pub async fn count(&self, filter: &ReportFilter) -> Result<i64> {
         let mut query = String::from("SELECT COUNT(*) FROM test_reports WHERE 1=1");
@@ -236,6 +546,315 @@ impl ReportRepository {
 
         Ok(count)
     }
+
+    /// 列出符合清理条件的报告
+    pub async fn list_cleanup_candidates(
+        &self,
+        criteria: &ReportCleanupCriteria,
+    ) -> Result<Vec<TestReportRecord>> {
+        let (conditions, bindings) = cleanup_conditions(criteria);
+        let query = format!(
+            r#"
+            SELECT id, scenario_name, description, start_time, end_time, duration_ms,
+                   total_steps, success_count, failed_count, skipped_count, passed, tags, created_at,
+                   scenario_version
+            FROM test_reports
+            WHERE 1=1{}
+            ORDER BY start_time ASC
+            "#,
+            conditions
+        );
+
+        let mut sql_query = sqlx::query_as::<_, TestReportRecord>(&query);
+
+        for binding in &bindings {
+            sql_query = sql_query.bind(binding);
+        }
+
+        if let Some(before) = criteria.before {
+            sql_query = sql_query.bind(before);
+        }
+
+        let reports = sql_query.fetch_all(&self.pool).await?;
+
+        Ok(reports)
+    }
+
+    /// 按组合条件清理报告(级联删除步骤)
+    ///
+    /// `dry_run` 为 true 时只统计将被删除的数据, 不做任何修改。
+    /// 统计与删除使用同一组条件, 因此演练结果与实际删除结果一致。
+    pub async fn cleanup(
+        &self,
+        criteria: &ReportCleanupCriteria,
+        dry_run: bool,
+    ) -> Result<ReportCleanupStats> {
+        if dry_run {
+            let mut conn = self.pool.acquire().await?;
+            return cleanup_stats(&mut conn, criteria).await;
+        }
+
+        let mut tx = self.pool.begin().await?;
+
+        let mut stats = cleanup_stats(&mut tx, criteria).await?;
+
+        let (conditions, bindings) = cleanup_conditions(criteria);
+
+        let steps_query = format!(
+            "DELETE FROM execution_steps WHERE report_id IN (SELECT id FROM test_reports WHERE 1=1{})",
+            conditions
+        );
+        let mut sql_query = sqlx::query(&steps_query);
+        for binding in &bindings {
+            sql_query = sql_query.bind(binding);
+        }
+        if let Some(before) = criteria.before {
+            sql_query = sql_query.bind(before);
+        }
+        sql_query.execute(&mut *tx).await?;
+
+        for table in ["report_resources", "step_metrics"] {
+            let query = format!(
+                "DELETE FROM {} WHERE report_id IN (SELECT id FROM test_reports WHERE 1=1{})",
+                table, conditions
+            );
+            let mut sql_query = sqlx::query(&query);
+            for binding in &bindings {
+                sql_query = sql_query.bind(binding);
+            }
+            if let Some(before) = criteria.before {
+                sql_query = sql_query.bind(before);
+            }
+            sql_query.execute(&mut *tx).await?;
+        }
+
+        let reports_query = format!("DELETE FROM test_reports WHERE 1=1{}", conditions);
+        let mut sql_query = sqlx::query(&reports_query);
+        for binding in &bindings {
+            sql_query = sql_query.bind(binding);
+        }
+        if let Some(before) = criteria.before {
+            sql_query = sql_query.bind(before);
+        }
+        let result = sql_query.execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        stats.report_count = result.rows_affected() as i64;
+        debug!("Cleaned up {} test reports", stats.report_count);
+
+        Ok(stats)
+    }
+
+    /// 按 ID 批量删除报告 (单个事务, 级联删除步骤与资源记录)
+    ///
+    /// `dry_run` 为 true 时只统计将被删除的数据, 不做任何修改。
+    pub async fn delete_many(&self, ids: &[i64], dry_run: bool) -> Result<ReportCleanupStats> {
+        let mut tx = self.pool.begin().await?;
+        let mut stats = ReportCleanupStats::default();
+
+        for chunk in ids.chunks(DELETE_CHUNK_SIZE) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+
+            let reports_query = format!(
+                r#"
+                SELECT COUNT(*),
+                       COALESCE(SUM(LENGTH(scenario_name) + COALESCE(LENGTH(description), 0)
+                                    + COALESCE(LENGTH(tags), 0)), 0)
+                FROM test_reports
+                WHERE id IN ({})
+                "#,
+                placeholders
+            );
+            let steps_query = format!(
+                r#"
+                SELECT COUNT(*),
+                       COALESCE(SUM(LENGTH(description) + COALESCE(LENGTH(error), 0)
+                                    + COALESCE(LENGTH(output), 0)), 0)
+                FROM execution_steps
+                WHERE report_id IN ({})
+                "#,
+                placeholders
+            );
+
+            for (query, is_report) in [(&reports_query, true), (&steps_query, false)] {
+                let mut sql_query = sqlx::query_as::<_, (i64, i64)>(query);
+                for id in chunk {
+                    sql_query = sql_query.bind(id);
+                }
+                let (count, bytes) = sql_query.fetch_one(&mut *tx).await?;
+                if is_report {
+                    stats.report_count += count;
+                } else {
+                    stats.step_count += count;
+                }
+                stats.estimated_bytes += bytes;
+            }
+
+            if dry_run {
+                continue;
+            }
+
+            for table in ["execution_steps", "report_resources", "step_metrics"] {
+                let query = format!("DELETE FROM {} WHERE report_id IN ({})", table, placeholders);
+                let mut sql_query = sqlx::query(&query);
+                for id in chunk {
+                    sql_query = sql_query.bind(id);
+                }
+                sql_query.execute(&mut *tx).await?;
+            }
+
+            let query = format!("DELETE FROM test_reports WHERE id IN ({})", placeholders);
+            let mut sql_query = sqlx::query(&query);
+            for id in chunk {
+                sql_query = sql_query.bind(id);
+            }
+            sql_query.execute(&mut *tx).await?;
+        }
+
+        if dry_run {
+            tx.rollback().await?;
+        } else {
+            tx.commit().await?;
+            debug!("Deleted {} test reports by ID", stats.report_count);
+        }
+
+        Ok(stats)
+    }
+}
+
+/// 按 ID 删除报告时每条 SQL 绑定的最大 ID 数
+const DELETE_CHUNK_SIZE: usize = 500;
+
+/// 构建清理条件子句
+///
+/// 返回以 " AND" 开头的条件片段和按顺序排列的字符串绑定参数;
+/// `before` 条件总是位于最后, 由调用方单独绑定。
+fn cleanup_conditions(criteria: &ReportCleanupCriteria) -> (String, Vec<String>) {
+    let mut conditions = String::new();
+    let mut bindings = Vec::new();
+
+    if let Some(pattern) = &criteria.scenario_pattern {
+        conditions.push_str(" AND scenario_name LIKE ? ESCAPE '\\'");
+        bindings.push(pattern_to_like(pattern));
+    }
+
+    if let Some(tag) = &criteria.tag {
+        conditions.push_str(
+            " AND tags IS NOT NULL AND json_valid(tags) \
+             AND EXISTS (SELECT 1 FROM json_each(tags) WHERE json_each.value = ?)",
+        );
+        bindings.push(tag.clone());
+    }
+
+    if criteria.before.is_some() {
+        conditions.push_str(" AND start_time < ?");
+    }
+
+    (conditions, bindings)
+}
+
+/// 在事务中写入一条步骤资源指标
+async fn insert_step_metrics(
+    conn: &mut SqliteConnection,
+    report_id: i64,
+    record: &StepMetricsRecord,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO step_metrics
+        (report_id, step_index, sample_count, interval_ms, cpu_min, cpu_avg, cpu_max,
+         memory_min_mb, memory_avg_mb, memory_max_mb, block_read_bytes, block_write_bytes)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        "#,
+    )
+    .bind(report_id)
+    .bind(record.step_index)
+    .bind(record.sample_count)
+    .bind(record.interval_ms)
+    .bind(record.cpu_min)
+    .bind(record.cpu_avg)
+    .bind(record.cpu_max)
+    .bind(record.memory_min_mb)
+    .bind(record.memory_avg_mb)
+    .bind(record.memory_max_mb)
+    .bind(record.block_read_bytes)
+    .bind(record.block_write_bytes)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
+/// 将 `*`/`?` 通配符模式转换为 SQL LIKE 模式
+fn pattern_to_like(pattern: &str) -> String {
+    let mut like = String::with_capacity(pattern.len());
+
+    for c in pattern.chars() {
+        match c {
+            '*' => like.push('%'),
+            '?' => like.push('_'),
+            '%' | '_' | '\\' => {
+                like.push('\\');
+                like.push(c);
+            }
+            _ => like.push(c),
+        }
+    }
+
+    like
+}
+
+/// 统计符合清理条件的报告、步骤数量及占用空间
+async fn cleanup_stats(
+    conn: &mut SqliteConnection,
+    criteria: &ReportCleanupCriteria,
+) -> Result<ReportCleanupStats> {
+    let (conditions, bindings) = cleanup_conditions(criteria);
+
+    let reports_query = format!(
+        r#"
+        SELECT COUNT(*),
+               COALESCE(SUM(LENGTH(scenario_name) + COALESCE(LENGTH(description), 0)
+                            + COALESCE(LENGTH(tags), 0)), 0)
+        FROM test_reports
+        WHERE 1=1{}
+        "#,
+        conditions
+    );
+    let mut sql_query = sqlx::query_as::<_, (i64, i64)>(&reports_query);
+    for binding in &bindings {
+        sql_query = sql_query.bind(binding);
+    }
+    if let Some(before) = criteria.before {
+        sql_query = sql_query.bind(before);
+    }
+    let (report_count, report_bytes) = sql_query.fetch_one(&mut *conn).await?;
+
+    let steps_query = format!(
+        r#"
+        SELECT COUNT(*),
+               COALESCE(SUM(LENGTH(description) + COALESCE(LENGTH(error), 0)
+                            + COALESCE(LENGTH(output), 0)), 0)
+        FROM execution_steps
+        WHERE report_id IN (SELECT id FROM test_reports WHERE 1=1{})
+        "#,
+        conditions
+    );
+    let mut sql_query = sqlx::query_as::<_, (i64, i64)>(&steps_query);
+    for binding in &bindings {
+        sql_query = sql_query.bind(binding);
+    }
+    if let Some(before) = criteria.before {
+        sql_query = sql_query.bind(before);
+    }
+    let (step_count, step_bytes) = sql_query.fetch_one(&mut *conn).await?;
+
+    Ok(ReportCleanupStats {
+        report_count,
+        step_count,
+        estimated_bytes: report_bytes + step_bytes,
+    })
 }
 
 #[cfg(test)]
@@ -262,6 +881,7 @@ mod tests {
             passed: true,
             tags: Some(r#"["smoke", "regression"]"#.to_string()),
             created_at: Utc::now(),
+            scenario_version: None,
         };
 
         let report_id = repo.create(&report).await.unwrap();
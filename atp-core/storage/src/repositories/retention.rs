@@ -0,0 +1,138 @@
+use sqlx::SqlitePool;
+use tracing::debug;
+
+use crate::error::{Result, StorageError};
+use crate::models::RetentionPolicyRecord;
+
+/// 报告保留规则仓储
+#[derive(Clone)]
+pub struct RetentionRepository {
+    pool: SqlitePool,
+}
+
+impl RetentionRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 创建保留规则, 返回规则 ID
+    ///
+    /// `keep_last_n` 与 `keep_days` 至少设置一个, 且不能为负数。
+    pub async fn create(&self, policy: &RetentionPolicyRecord) -> Result<i64> {
+        validate(policy)?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO retention_policies (scenario_pattern, keep_last_n, keep_days, created_at)
+            VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(&policy.scenario_pattern)
+        .bind(policy.keep_last_n)
+        .bind(policy.keep_days)
+        .bind(policy.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        let id = result.last_insert_rowid();
+        debug!("Created retention policy {} for '{}'", id, policy.scenario_pattern);
+
+        Ok(id)
+    }
+
+    /// 根据 ID 查询
+    pub async fn get_by_id(&self, id: i64) -> Result<Option<RetentionPolicyRecord>> {
+        let policy = sqlx::query_as::<_, RetentionPolicyRecord>(
+            r#"
+            SELECT id, scenario_pattern, keep_last_n, keep_days, created_at
+            FROM retention_policies
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(policy)
+    }
+
+    /// 列出所有保留规则 (按 ID 排序)
+    pub async fn list_all(&self) -> Result<Vec<RetentionPolicyRecord>> {
+        let policies = sqlx::query_as::<_, RetentionPolicyRecord>(
+            r#"
+            SELECT id, scenario_pattern, keep_last_n, keep_days, created_at
+            FROM retention_policies
+            ORDER BY id ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(policies)
+    }
+
+    /// 更新保留规则
+    pub async fn update(&self, policy: &RetentionPolicyRecord) -> Result<()> {
+        validate(policy)?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE retention_policies
+            SET scenario_pattern = ?, keep_last_n = ?, keep_days = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(&policy.scenario_pattern)
+        .bind(policy.keep_last_n)
+        .bind(policy.keep_days)
+        .bind(policy.id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Retention policy {} not found", policy.id)));
+        }
+
+        debug!("Updated retention policy {}", policy.id);
+
+        Ok(())
+    }
+
+    /// 删除保留规则
+    pub async fn delete(&self, id: i64) -> Result<()> {
+        let result = sqlx::query("DELETE FROM retention_policies WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(StorageError::NotFound(format!("Retention policy {} not found", id)));
+        }
+
+        debug!("Deleted retention policy {}", id);
+
+        Ok(())
+    }
+}
+
+fn validate(policy: &RetentionPolicyRecord) -> Result<()> {
+    if policy.scenario_pattern.trim().is_empty() {
+        return Err(StorageError::ValidationError(
+            "Retention policy scenario pattern must not be empty".to_string(),
+        ));
+    }
+
+    if policy.keep_last_n.is_none() && policy.keep_days.is_none() {
+        return Err(StorageError::ValidationError(
+            "Retention policy needs keep_last_n or keep_days".to_string(),
+        ));
+    }
+
+    if policy.keep_last_n.is_some_and(|n| n < 0) || policy.keep_days.is_some_and(|days| days < 0) {
+        return Err(StorageError::ValidationError(
+            "Retention policy limits must not be negative".to_string(),
+        ));
+    }
+
+    Ok(())
+}
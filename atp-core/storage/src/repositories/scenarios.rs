@@ -1,9 +1,9 @@
 use chrono::Utc;
-use sqlx::SqlitePool;
+use sqlx::{SqliteConnection, SqlitePool};
 use tracing::debug;
 
 use crate::error::{Result, StorageError};
-use crate::models::{ScenarioFilter, ScenarioRecord};
+use crate::models::{ScenarioFilter, ScenarioRecord, ScenarioSummary, ScenarioVersionRecord};
 
 /// 场景仓储
 pub struct ScenarioRepository {
@@ -17,6 +17,8 @@ impl ScenarioRepository {
 
     /// 创建新场景
     pub async fn create(&self, scenario: &ScenarioRecord) -> Result<i64> {
+        let mut tx = self.pool.begin().await?;
+
         let result = sqlx::query(
             r#"
             INSERT INTO scenarios
@@ -31,7 +33,7 @@ impl ScenarioRepository {
         .bind(scenario.version)
         .bind(Utc::now())
         .bind(Utc::now())
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await
         .map_err(|e| {
             if e.to_string().contains("UNIQUE constraint failed") {
@@ -42,6 +44,9 @@ impl ScenarioRepository {
         })?;
 
         let scenario_id = result.last_insert_rowid();
+        record_current_version(&mut tx, scenario_id).await?;
+        tx.commit().await?;
+
         debug!("Created scenario '{}' with ID: {}", scenario.name, scenario_id);
 
         Ok(scenario_id)
@@ -49,6 +54,8 @@ impl ScenarioRepository {
 
     /// 更新场景(递增版本)
     pub async fn update(&self, id: i64, definition: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
         let result = sqlx::query(
             r#"
             UPDATE scenarios
@@ -59,18 +66,164 @@ impl ScenarioRepository {
         .bind(definition)
         .bind(Utc::now())
         .bind(id)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
         if result.rows_affected() == 0 {
             return Err(StorageError::NotFound(format!("Scenario {} not found", id)));
         }
 
+        record_current_version(&mut tx, id).await?;
+        tx.commit().await?;
+
         debug!("Updated scenario {}", id);
 
         Ok(())
     }
 
+    /// 按名称保存场景定义, 返回保存后的版本
+    ///
+    /// 场景不存在时创建版本 1; 定义与最新版本不同时版本加 1,
+    /// 相同时不产生新版本。
+    pub async fn save(&self, name: &str, definition: &str) -> Result<i32> {
+        let mut tx = self.pool.begin().await?;
+
+        let existing: Option<(i64, i32, String)> =
+            sqlx::query_as("SELECT id, version, definition FROM scenarios WHERE name = ?")
+                .bind(name)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+        let (scenario_id, version) = match existing {
+            Some((_, version, current)) if current == definition => {
+                debug!("Scenario '{}' unchanged at version {}", name, version);
+                return Ok(version);
+            }
+            Some((id, version, _)) => {
+                sqlx::query(
+                    "UPDATE scenarios SET definition = ?, version = ?, updated_at = ? WHERE id = ?",
+                )
+                .bind(definition)
+                .bind(version + 1)
+                .bind(Utc::now())
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+                (id, version + 1)
+            }
+            None => {
+                let id = sqlx::query(
+                    r#"
+                    INSERT INTO scenarios (name, definition, version, created_at, updated_at)
+                    VALUES (?, ?, 1, ?, ?)
+                    "#,
+                )
+                .bind(name)
+                .bind(definition)
+                .bind(Utc::now())
+                .bind(Utc::now())
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid();
+                (id, 1)
+            }
+        };
+
+        record_current_version(&mut tx, scenario_id).await?;
+        tx.commit().await?;
+
+        debug!("Saved scenario '{}' as version {}", name, version);
+
+        Ok(version)
+    }
+
+    /// 获取场景的最新版本
+    pub async fn get_latest(&self, name: &str) -> Result<Option<ScenarioRecord>> {
+        self.get_by_name(name).await
+    }
+
+    /// 获取场景的指定版本
+    pub async fn get_version(&self, name: &str, version: i32) -> Result<Option<ScenarioVersionRecord>> {
+        let record = sqlx::query_as::<_, ScenarioVersionRecord>(
+            r#"
+            SELECT v.scenario_id, s.name, v.version, v.definition, v.created_at
+            FROM scenario_versions v
+            JOIN scenarios s ON s.id = v.scenario_id
+            WHERE s.name = ? AND v.version = ?
+            "#,
+        )
+        .bind(name)
+        .bind(version)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(record)
+    }
+
+    /// 列出场景的所有历史版本 (版本号升序)
+    pub async fn list_versions(&self, name: &str) -> Result<Vec<ScenarioVersionRecord>> {
+        let records = sqlx::query_as::<_, ScenarioVersionRecord>(
+            r#"
+            SELECT v.scenario_id, s.name, v.version, v.definition, v.created_at
+            FROM scenario_versions v
+            JOIN scenarios s ON s.id = v.scenario_id
+            WHERE s.name = ?
+            ORDER BY v.version ASC
+            "#,
+        )
+        .bind(name)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(records)
+    }
+
+    /// 列出场景及其最近一次执行结果 (按名称排序)
+    pub async fn list_with_last_run(&self, filter: &ScenarioFilter) -> Result<Vec<ScenarioSummary>> {
+        let mut query = String::from(
+            r#"
+            SELECT s.id, s.name, s.description, s.version, s.updated_at,
+                   r.id AS last_report_id, r.start_time AS last_run_at,
+                   r.passed AS last_run_passed, r.scenario_version AS last_run_version
+            FROM scenarios s
+            LEFT JOIN test_reports r ON r.id = (
+                SELECT id FROM test_reports
+                WHERE scenario_name = s.name
+                ORDER BY start_time DESC, id DESC
+                LIMIT 1
+            )
+            WHERE 1=1
+            "#,
+        );
+
+        let mut bindings = Vec::new();
+
+        if let Some(name) = &filter.name {
+            query.push_str(" AND s.name LIKE ?");
+            bindings.push(format!("%{}%", name));
+        }
+
+        query.push_str(" ORDER BY s.name ASC");
+
+        if let Some(limit) = filter.limit {
+            query.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        if let Some(offset) = filter.offset {
+            query.push_str(&format!(" OFFSET {}", offset));
+        }
+
+        let mut sql_query = sqlx::query_as::<_, ScenarioSummary>(&query);
+
+        for binding in &bindings {
+            sql_query = sql_query.bind(binding);
+        }
+
+        let scenarios = sql_query.fetch_all(&self.pool).await?;
+
+        Ok(scenarios)
+    }
+
     /// 根据ID获取场景
     pub async fn get_by_id(&self, id: i64) -> Result<Option<ScenarioRecord>> {
         let scenario = sqlx::query_as::<_, ScenarioRecord>(
@@ -181,3 +334,18 @@ impl ScenarioRepository {
         Ok(count)
     }
 }
+
+/// 把场景的当前定义记录为历史版本
+async fn record_current_version(conn: &mut SqliteConnection, scenario_id: i64) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO scenario_versions (scenario_id, version, definition, created_at)
+        SELECT id, version, definition, updated_at FROM scenarios WHERE id = ?
+        "#,
+    )
+    .bind(scenario_id)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::models::{VerificationFilter, VerificationResultRecord};
+
+/// 验证结果仓储
+#[derive(Clone)]
+pub struct VerificationRepository {
+    pool: SqlitePool,
+}
+
+impl VerificationRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 写入一条验证结果, 返回新记录的 ID
+    pub async fn create(&self, record: &VerificationResultRecord) -> Result<i64> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO verification_results
+                (vm_id, event_id, event_type, verified, outcome, latency_ms, server_latency_ms, details, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&record.vm_id)
+        .bind(&record.event_id)
+        .bind(&record.event_type)
+        .bind(record.verified)
+        .bind(&record.outcome)
+        .bind(record.latency_ms)
+        .bind(record.server_latency_ms)
+        .bind(&record.details)
+        .bind(record.created_at)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+
+        debug!("Inserted verification result {} for VM {}", record.event_id, record.vm_id);
+        Ok(id)
+    }
+
+    /// 将超时记录改判为迟到结果的结论, 找不到超时记录时写入新记录
+    ///
+    /// 用于宽限期内迟到的结果 (如 Agent 重连后补发), 保持每个事件只有一条记录。
+    pub async fn reclassify(&self, record: &VerificationResultRecord) -> Result<()> {
+        let updated = sqlx::query(
+            r#"
+            UPDATE verification_results
+            SET verified = ?, outcome = ?, latency_ms = ?, server_latency_ms = ?, details = ?
+            WHERE event_id = ? AND outcome = 'timeout'
+            "#,
+        )
+        .bind(record.verified)
+        .bind(&record.outcome)
+        .bind(record.latency_ms)
+        .bind(record.server_latency_ms)
+        .bind(&record.details)
+        .bind(&record.event_id)
+        .execute(&self.pool)
+        .await?
+        .rows_affected();
+
+        if updated == 0 {
+            self.create(record).await?;
+        }
+
+        debug!(
+            "Reclassified verification result {} as {}",
+            record.event_id, record.outcome
+        );
+        Ok(())
+    }
+
+    /// 查询验证结果 (按时间升序)
+    pub async fn list(&self, filter: &VerificationFilter) -> Result<Vec<VerificationResultRecord>> {
+        let mut query = String::from(
+            r#"
+            SELECT id, vm_id, event_id, event_type, verified, outcome,
+                   latency_ms, server_latency_ms, details, created_at
+            FROM verification_results
+            WHERE 1=1
+            "#,
+        );
+
+        if filter.vm_id.is_some() {
+            query.push_str(" AND vm_id = ?");
+        }
+
+        if filter.from.is_some() {
+            query.push_str(" AND created_at >= ?");
+        }
+
+        if filter.to.is_some() {
+            query.push_str(" AND created_at <= ?");
+        }
+
+        query.push_str(" ORDER BY created_at ASC, id ASC");
+
+        if let Some(limit) = filter.limit {
+            query.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let mut sql_query = sqlx::query_as::<_, VerificationResultRecord>(&query);
+
+        if let Some(vm_id) = &filter.vm_id {
+            sql_query = sql_query.bind(vm_id);
+        }
+
+        if let Some(from) = filter.from {
+            sql_query = sql_query.bind(from);
+        }
+
+        if let Some(to) = filter.to {
+            sql_query = sql_query.bind(to);
+        }
+
+        let records = sql_query.fetch_all(&self.pool).await?;
+
+        Ok(records)
+    }
+
+    /// 查询指定虚拟机的验证结果
+    pub async fn list_by_vm(&self, vm_id: &str) -> Result<Vec<VerificationResultRecord>> {
+        self.list(&VerificationFilter {
+            vm_id: Some(vm_id.to_string()),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// 查询时间范围内的验证结果
+    pub async fn list_by_time_range(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<VerificationResultRecord>> {
+        self.list(&VerificationFilter {
+            from: Some(from),
+            to: Some(to),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// 统计验证结果数量
+    pub async fn count(&self) -> Result<i64> {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM verification_results")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(count.0)
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+use tracing::debug;
+
+use crate::error::Result;
+use crate::models::{VmCacheRecord, VmStatusHistoryRecord};
+
+/// 虚拟机缓存仓储
+#[derive(Clone)]
+pub struct VmCacheRepository {
+    pool: SqlitePool,
+}
+
+impl VmCacheRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// 用平台返回的完整虚拟机列表同步缓存 (单个事务)
+    ///
+    /// 状态发生变化 (或首次出现) 的虚拟机写入状态历史,
+    /// 平台上已不存在的虚拟机从缓存中删除, 其历史记录保留。
+    /// 返回本次写入的状态变更记录。
+    pub async fn sync_all(&self, vms: &[VmCacheRecord]) -> Result<Vec<VmStatusHistoryRecord>> {
+        let mut tx = self.pool.begin().await?;
+        let mut transitions = Vec::new();
+
+        for vm in vms {
+            let old_status: Option<(String,)> = sqlx::query_as("SELECT status FROM vm_cache WHERE id = ?")
+                .bind(&vm.id)
+                .fetch_optional(&mut *tx)
+                .await?;
+            let old_status = old_status.map(|(status,)| status);
+
+            if old_status.as_deref() != Some(vm.status.as_str()) {
+                let id = sqlx::query(
+                    r#"
+                    INSERT INTO vm_status_history (vm_id, old_status, new_status, changed_at)
+                    VALUES (?, ?, ?, ?)
+                    "#,
+                )
+                .bind(&vm.id)
+                .bind(&old_status)
+                .bind(&vm.status)
+                .bind(vm.updated_at)
+                .execute(&mut *tx)
+                .await?
+                .last_insert_rowid();
+
+                transitions.push(VmStatusHistoryRecord {
+                    id,
+                    vm_id: vm.id.clone(),
+                    old_status,
+                    new_status: vm.status.clone(),
+                    changed_at: vm.updated_at,
+                });
+            }
+
+            sqlx::query(
+                r#"
+                INSERT INTO vm_cache (id, name, status, host_id, cpu, memory, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT(id) DO UPDATE SET
+                    name = excluded.name,
+                    status = excluded.status,
+                    host_id = excluded.host_id,
+                    cpu = excluded.cpu,
+                    memory = excluded.memory,
+                    updated_at = excluded.updated_at
+                "#,
+            )
+            .bind(&vm.id)
+            .bind(&vm.name)
+            .bind(&vm.status)
+            .bind(&vm.host_id)
+            .bind(vm.cpu)
+            .bind(vm.memory)
+            .bind(vm.updated_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let current: HashSet<&str> = vms.iter().map(|vm| vm.id.as_str()).collect();
+        let cached: Vec<(String,)> = sqlx::query_as("SELECT id FROM vm_cache")
+            .fetch_all(&mut *tx)
+            .await?;
+        for (id,) in cached {
+            if !current.contains(id.as_str()) {
+                sqlx::query("DELETE FROM vm_cache WHERE id = ?")
+                    .bind(&id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        debug!(
+            "Synced {} cached VMs, {} status transitions",
+            vms.len(),
+            transitions.len()
+        );
+        Ok(transitions)
+    }
+
+    /// 列出所有缓存的虚拟机 (按名称排序)
+    pub async fn list_all(&self) -> Result<Vec<VmCacheRecord>> {
+        let vms = sqlx::query_as::<_, VmCacheRecord>(
+            r#"
+            SELECT id, name, status, host_id, cpu, memory, updated_at
+            FROM vm_cache
+            ORDER BY name ASC, id ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(vms)
+    }
+
+    /// 根据 ID 获取缓存的虚拟机
+    pub async fn get_by_id(&self, id: &str) -> Result<Option<VmCacheRecord>> {
+        let vm = sqlx::query_as::<_, VmCacheRecord>(
+            r#"
+            SELECT id, name, status, host_id, cpu, memory, updated_at
+            FROM vm_cache
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(vm)
+    }
+
+    /// 根据名称查找缓存的虚拟机 (平台不保证名称唯一)
+    pub async fn find_by_name(&self, name: &str) -> Result<Vec<VmCacheRecord>> {
+        let vms = sqlx::query_as::<_, VmCacheRecord>(
+            r#"
+            SELECT id, name, status, host_id, cpu, memory, updated_at
+            FROM vm_cache
+            WHERE name = ?
+            ORDER BY id ASC
+            "#,
+        )
+        .bind(name)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(vms)
+    }
+
+    /// 缓存最近一次同步的时间 (缓存为空时返回 None)
+    pub async fn last_updated(&self) -> Result<Option<DateTime<Utc>>> {
+        let updated_at: Option<(DateTime<Utc>,)> =
+            sqlx::query_as("SELECT updated_at FROM vm_cache ORDER BY updated_at DESC LIMIT 1")
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(updated_at.map(|(updated_at,)| updated_at))
+    }
+
+    /// 获取虚拟机的状态变更历史 (按时间升序)
+    pub async fn history(&self, vm_id: &str) -> Result<Vec<VmStatusHistoryRecord>> {
+        let history = sqlx::query_as::<_, VmStatusHistoryRecord>(
+            r#"
+            SELECT id, vm_id, old_status, new_status, changed_at
+            FROM vm_status_history
+            WHERE vm_id = ?
+            ORDER BY changed_at ASC, id ASC
+            "#,
+        )
+        .bind(vm_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(history)
+    }
+
+    /// 删除指定时间之前的状态历史
+    pub async fn delete_history_before(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM vm_status_history WHERE changed_at < ?")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+
+        debug!("Deleted {} VM status history records", result.rows_affected());
+        Ok(result.rows_affected())
+    }
+}
@@ -1,8 +1,14 @@
 // 数据库集成测试
 use atp_storage::{
-    ExecutionStepRecord, ReportFilter, ReportRepository, ScenarioFilter, ScenarioRecord,
-    ScenarioRepository, Storage, StorageManager, TestReportRecord,
+    BenchmarkFilter, BenchmarkRunRecord, CollectorConfig, EntityMetricSample, ExecutionStepRecord, HostProbeRecord, HostRecord,
+    MetricEntity, MetricFilter, MetricRepository,
+    MetricSample, MetricsCollector, MetricsSource, ReportBundle, ReportCleanupCriteria,
+    ReportFilter, ReportRepository, ReportResourceRecord, RetentionPolicyRecord, ScenarioFilter,
+    ScenarioRecord, ScenarioRepository, StepMetricsRecord, Storage, StorageManager, TestReportRecord,
+    VerificationFilter, VerificationRepository, VerificationResultRecord, VmCacheRecord,
+    VmCacheRepository, LATEST_SCHEMA_VERSION, REPORT_BUNDLE_VERSION,
 };
+use async_trait::async_trait;
 use chrono::Utc;
 use sqlx::SqlitePool;
 use std::sync::Arc;
@@ -31,6 +37,7 @@ fn create_test_report(scenario_name: &str, success: bool) -> TestReportRecord {
         passed: success,
         tags: Some(r#"["test", "integration"]"#.to_string()),
         created_at: Utc::now(),
+        scenario_version: None,
     }
 }
 
@@ -53,6 +60,8 @@ fn create_test_step(report_id: i64, step_index: i32, success: bool) -> Execution
         },
         duration_ms: Some(100),
         output: Some("Test output".to_string()),
+        started_at_offset_ms: Some(step_index as i64 * 100),
+        error_kind: if success { None } else { Some("verification".to_string()) },
     }
 }
 
@@ -169,6 +178,34 @@ async fn test_list_reports_by_status() {
     assert_eq!(reports.len(), 2);
 }
 
+#[tokio::test]
+async fn test_list_reports_by_failed_kind() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    let flaky = repo.create(&create_test_report("scenario_1", false)).await.unwrap();
+    let mut step = create_test_step(flaky, 0, false);
+    step.error_kind = Some("infrastructure".to_string());
+    repo.create_step(&step).await.unwrap();
+
+    let bug = repo.create(&create_test_report("scenario_2", false)).await.unwrap();
+    repo.create_step(&create_test_step(bug, 0, true)).await.unwrap();
+    repo.create_step(&create_test_step(bug, 1, false)).await.unwrap();
+
+    let filter = |kind: &str| ReportFilter {
+        failed_kind: Some(kind.to_string()),
+        ..Default::default()
+    };
+    let reports = repo.list(&filter("infrastructure")).await.unwrap();
+    assert_eq!(reports.iter().map(|r| r.id).collect::<Vec<_>>(), vec![flaky]);
+    let reports = repo.list(&filter("verification")).await.unwrap();
+    assert_eq!(reports.iter().map(|r| r.id).collect::<Vec<_>>(), vec![bug]);
+    assert!(repo.list(&filter("timeout")).await.unwrap().is_empty());
+
+    let steps = repo.get_steps(flaky).await.unwrap();
+    assert_eq!(steps[0].error_kind.as_deref(), Some("infrastructure"));
+}
+
 #[tokio::test]
 async fn test_list_reports_pagination() {
     let pool = setup_test_db().await;
@@ -337,6 +374,27 @@ async fn test_get_steps_by_report_id() {
     assert_eq!(found_steps[0].step_index, 0);
     assert_eq!(found_steps[2].step_index, 2);
     assert_eq!(found_steps[2].status, "Failed");
+    assert_eq!(found_steps[2].started_at_offset_ms, Some(200));
+}
+
+#[tokio::test]
+async fn test_migrations_rerun_on_existing_database() {
+    let path = std::env::temp_dir().join(format!("atp-migrate-{}.db", std::process::id()));
+    std::fs::File::create(&path).unwrap();
+    let db_path = path.to_str().unwrap();
+
+    // 再次打开同一数据库时, 新增列的迁移不能重复执行
+    let manager = StorageManager::new(db_path).await.unwrap();
+    manager.close().await;
+    let manager = StorageManager::new(db_path).await.unwrap();
+
+    let repo = ReportRepository::new(manager.pool().clone());
+    let report_id = repo.create(&create_test_report("rerun", true)).await.unwrap();
+    repo.create_step(&create_test_step(report_id, 1, true)).await.unwrap();
+    assert_eq!(repo.get_steps(report_id).await.unwrap()[0].started_at_offset_ms, Some(100));
+
+    manager.close().await;
+    std::fs::remove_file(&path).unwrap();
 }
 
 #[tokio::test]
@@ -362,6 +420,273 @@ async fn test_steps_cascade_delete() {
     assert!(found_report.is_none());
 }
 
+#[tokio::test]
+async fn test_report_resources() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    let report = create_test_report("test_scenario", true);
+    let report_id = repo.create(&report).await.unwrap();
+
+    let resources = vec![
+        ReportResourceRecord {
+            id: 0,
+            report_id,
+            resource_type: "desk_pool".to_string(),
+            resource_id: "pool-1".to_string(),
+            name: Some("test-pool".to_string()),
+            step_index: 0,
+            cleanup_status: "auto_cleaned".to_string(),
+            cleanup_error: None,
+        },
+        ReportResourceRecord {
+            id: 0,
+            report_id,
+            resource_type: "snapshot".to_string(),
+            resource_id: "snap-1".to_string(),
+            name: None,
+            step_index: 1,
+            cleanup_status: "leaked".to_string(),
+            cleanup_error: Some("no cleanup available".to_string()),
+        },
+    ];
+    repo.create_resources(&resources).await.unwrap();
+
+    let found = repo.get_resources(report_id).await.unwrap();
+    assert_eq!(found.len(), 2);
+    assert_eq!(found[0].resource_id, "pool-1");
+    assert_eq!(found[1].cleanup_status, "leaked");
+
+    // 更新清理结果
+    repo.update_resource_status(found[1].id, "released", None).await.unwrap();
+    let found = repo.get_resources(report_id).await.unwrap();
+    assert_eq!(found[1].cleanup_status, "released");
+    assert!(found[1].cleanup_error.is_none());
+    assert!(repo.update_resource_status(-1, "released", None).await.is_err());
+
+    // 删除报告时一并删除资源记录
+    let criteria = ReportCleanupCriteria::default();
+    repo.cleanup(&criteria, false).await.unwrap();
+    assert!(repo.get_resources(report_id).await.unwrap().is_empty());
+}
+
+// ==================== 报告清理测试 ====================
+
+/// 创建带标签的报告及其步骤
+async fn create_tagged_report(repo: &ReportRepository, scenario_name: &str, tags: &str) -> i64 {
+    let mut report = create_test_report(scenario_name, true);
+    report.tags = Some(tags.to_string());
+    let report_id = repo.create(&report).await.unwrap();
+
+    let steps = vec![
+        create_test_step(report_id, 0, true),
+        create_test_step(report_id, 1, true),
+    ];
+    repo.create_steps(&steps).await.unwrap();
+
+    report_id
+}
+
+#[tokio::test]
+async fn test_cleanup_dry_run_matches_actual_cleanup() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    create_tagged_report(&repo, "stress_cpu", r#"["stress"]"#).await;
+    create_tagged_report(&repo, "stress_io", r#"["stress", "nightly"]"#).await;
+    let kept_id = create_tagged_report(&repo, "acceptance_login", r#"["acceptance"]"#).await;
+
+    let criteria = ReportCleanupCriteria {
+        scenario_pattern: Some("stress_*".to_string()),
+        ..Default::default()
+    };
+
+    // 演练模式不删除任何数据
+    let preview = repo.cleanup(&criteria, true).await.unwrap();
+    assert_eq!(preview.report_count, 2);
+    assert_eq!(preview.step_count, 4);
+    assert!(preview.estimated_bytes > 0);
+    assert_eq!(repo.count(&ReportFilter::default()).await.unwrap(), 3);
+
+    let candidates = repo.list_cleanup_candidates(&criteria).await.unwrap();
+    assert_eq!(candidates.len(), 2);
+
+    // 同一条件下实际删除结果与演练一致
+    let actual = repo.cleanup(&criteria, false).await.unwrap();
+    assert_eq!(actual, preview);
+    assert_eq!(repo.count(&ReportFilter::default()).await.unwrap(), 1);
+    assert!(repo.get_by_id(kept_id).await.unwrap().is_some());
+    assert_eq!(repo.get_steps(kept_id).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_cleanup_by_tag() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    let stress_id = create_tagged_report(&repo, "scenario_a", r#"["stress", "nightly"]"#).await;
+    create_tagged_report(&repo, "scenario_b", r#"["acceptance"]"#).await;
+    create_tagged_report(&repo, "scenario_c", r#"["stressful"]"#).await;
+
+    let criteria = ReportCleanupCriteria {
+        tag: Some("stress".to_string()),
+        ..Default::default()
+    };
+
+    let stats = repo.cleanup(&criteria, false).await.unwrap();
+    assert_eq!(stats.report_count, 1);
+    assert_eq!(stats.step_count, 2);
+    assert!(repo.get_by_id(stress_id).await.unwrap().is_none());
+    assert!(repo.get_steps(stress_id).await.unwrap().is_empty());
+    assert_eq!(repo.count(&ReportFilter::default()).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn test_cleanup_combined_criteria() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    create_tagged_report(&repo, "stress_cpu", r#"["stress"]"#).await;
+    create_tagged_report(&repo, "stress_io", r#"["acceptance"]"#).await;
+    create_tagged_report(&repo, "100%_load", r#"["stress"]"#).await;
+
+    // 场景模式与标签同时生效
+    let criteria = ReportCleanupCriteria {
+        scenario_pattern: Some("stress_*".to_string()),
+        tag: Some("stress".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(repo.cleanup(&criteria, true).await.unwrap().report_count, 1);
+
+    // LIKE 特殊字符按字面匹配
+    let criteria = ReportCleanupCriteria {
+        scenario_pattern: Some("100%_load".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(repo.cleanup(&criteria, true).await.unwrap().report_count, 1);
+
+    // 截止时间之前没有报告
+    let criteria = ReportCleanupCriteria {
+        before: Some(Utc::now() - chrono::Duration::days(1)),
+        ..Default::default()
+    };
+    let stats = repo.cleanup(&criteria, false).await.unwrap();
+    assert_eq!(stats.report_count, 0);
+    assert_eq!(stats.step_count, 0);
+    assert_eq!(repo.count(&ReportFilter::default()).await.unwrap(), 3);
+}
+
+// ==================== 趋势统计测试 ====================
+
+/// 创建指定天数之前的报告及其步骤: (描述, 是否成功, 耗时)
+async fn create_report_with_steps(
+    repo: &ReportRepository,
+    scenario_name: &str,
+    days_ago: i64,
+    steps: &[(&str, bool, i64)],
+) -> i64 {
+    let passed = steps.iter().all(|(_, success, _)| *success);
+    let mut report = create_test_report(scenario_name, passed);
+    report.start_time = Utc::now() - chrono::Duration::days(days_ago);
+    report.duration_ms = Some(steps.iter().map(|(_, _, ms)| ms).sum());
+    let report_id = repo.create(&report).await.unwrap();
+
+    let records: Vec<_> = steps
+        .iter()
+        .enumerate()
+        .map(|(index, (description, success, ms))| {
+            let mut step = create_test_step(report_id, index as i32, *success);
+            step.description = description.to_string();
+            step.duration_ms = Some(*ms);
+            step
+        })
+        .collect();
+    repo.create_steps(&records).await.unwrap();
+
+    report_id
+}
+
+#[tokio::test]
+async fn test_scenario_trend() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    create_report_with_steps(&repo, "login", 1, &[("a", true, 100)]).await;
+    create_report_with_steps(&repo, "login", 1, &[("a", false, 300)]).await;
+    create_report_with_steps(&repo, "login", 0, &[("a", true, 200)]).await;
+    // 时间范围之外与其他场景的报告不参与统计
+    create_report_with_steps(&repo, "login", 40, &[("a", false, 100)]).await;
+    create_report_with_steps(&repo, "logout", 0, &[("a", false, 100)]).await;
+
+    let trend = repo.scenario_trend("login", 30).await.unwrap();
+    assert_eq!(trend.len(), 2);
+
+    let yesterday = (Utc::now() - chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
+    assert_eq!(trend[0].date, yesterday);
+    assert_eq!(trend[0].runs, 2);
+    assert_eq!(trend[0].passed_runs, 1);
+    assert!((trend[0].pass_rate - 50.0).abs() < 0.1);
+    assert_eq!(trend[0].avg_duration_ms, Some(200.0));
+
+    assert_eq!(trend[1].runs, 1);
+    assert!((trend[1].pass_rate - 100.0).abs() < 0.1);
+
+    assert!(repo.scenario_trend("unknown", 30).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_flaky_steps() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    for run in 0..4 {
+        create_report_with_steps(
+            &repo,
+            "login",
+            0,
+            &[
+                ("stable", true, 100),
+                ("flaky", run != 0, 100),
+                ("broken", false, 100),
+                ("rare", run < 2 || run == 3, 100),
+            ],
+        )
+        .await;
+    }
+    // 只在一次执行中失败且执行次数不足的步骤
+    create_report_with_steps(&repo, "login", 0, &[("few", true, 100)]).await;
+    create_report_with_steps(&repo, "login", 0, &[("few", false, 100)]).await;
+
+    let flaky = repo.flaky_steps("login", 30, 3).await.unwrap();
+    let names: Vec<_> = flaky.iter().map(|s| s.description.as_str()).collect();
+    assert_eq!(names, vec!["flaky", "rare"]);
+    assert_eq!(flaky[0].runs, 4);
+    assert_eq!(flaky[0].failures, 1);
+    assert!((flaky[0].failure_rate - 25.0).abs() < 0.1);
+
+    let flaky = repo.flaky_steps("login", 30, 2).await.unwrap();
+    assert_eq!(flaky.len(), 3);
+    assert_eq!(flaky[0].description, "few");
+}
+
+#[tokio::test]
+async fn test_slowest_steps() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    create_report_with_steps(&repo, "boot", 0, &[("fast", true, 10), ("slow", true, 900), ("mid", true, 300)]).await;
+    create_report_with_steps(&repo, "boot", 0, &[("fast", true, 30), ("slow", false, 1100), ("mid", true, 500)]).await;
+    create_report_with_steps(&repo, "other", 0, &[("slowest", true, 10_000)]).await;
+
+    let slowest = repo.slowest_steps("boot", 2).await.unwrap();
+    assert_eq!(slowest.len(), 2);
+    assert_eq!(slowest[0].description, "slow");
+    assert_eq!(slowest[0].runs, 2);
+    assert!((slowest[0].avg_duration_ms - 1000.0).abs() < 0.1);
+    assert_eq!(slowest[0].max_duration_ms, 1100);
+    assert_eq!(slowest[1].description, "mid");
+}
+
 // ==================== ScenarioRepository 测试 ====================
 
 #[tokio::test]
@@ -517,6 +842,75 @@ async fn test_count_scenarios() {
     assert_eq!(count, 4);
 }
 
+#[tokio::test]
+async fn test_save_scenario_versions() {
+    let pool = setup_test_db().await;
+    let repo = ScenarioRepository::new(pool);
+
+    assert_eq!(repo.save("nightly", "name: nightly\nsteps: []\n").await.unwrap(), 1);
+    // 内容未变化时不产生新版本
+    assert_eq!(repo.save("nightly", "name: nightly\nsteps: []\n").await.unwrap(), 1);
+    assert_eq!(repo.save("nightly", "name: nightly\nsteps: [a]\n").await.unwrap(), 2);
+
+    let latest = repo.get_latest("nightly").await.unwrap().unwrap();
+    assert_eq!(latest.version, 2);
+    assert_eq!(latest.definition, "name: nightly\nsteps: [a]\n");
+
+    let v1 = repo.get_version("nightly", 1).await.unwrap().unwrap();
+    assert_eq!(v1.definition, "name: nightly\nsteps: []\n");
+    assert_eq!(v1.name, "nightly");
+    assert!(repo.get_version("nightly", 3).await.unwrap().is_none());
+    assert!(repo.get_version("missing", 1).await.unwrap().is_none());
+
+    // create/update 也记录历史版本
+    let id = repo.create(&create_test_scenario("legacy")).await.unwrap();
+    repo.update(id, r#"{"steps": [1]}"#).await.unwrap();
+    let versions = repo.list_versions("legacy").await.unwrap();
+    assert_eq!(versions.iter().map(|v| v.version).collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(versions[1].definition, r#"{"steps": [1]}"#);
+
+    // 删除场景时一并删除历史版本
+    repo.delete(id).await.unwrap();
+    assert!(repo.list_versions("legacy").await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_list_scenarios_with_last_run() {
+    let pool = setup_test_db().await;
+    let scenarios = ScenarioRepository::new(pool.clone());
+    let reports = ReportRepository::new(pool);
+
+    scenarios.save("alpha", "name: alpha").await.unwrap();
+    scenarios.save("alpha", "name: alpha\n").await.unwrap();
+    scenarios.save("beta", "name: beta").await.unwrap();
+
+    let mut first = create_test_report("alpha", true);
+    first.start_time = Utc::now() - chrono::Duration::hours(1);
+    first.scenario_version = Some(1);
+    reports.create(&first).await.unwrap();
+
+    let mut last = create_test_report("alpha", false);
+    last.scenario_version = Some(2);
+    let last_id = reports.create(&last).await.unwrap();
+
+    let list = scenarios.list_with_last_run(&ScenarioFilter::default()).await.unwrap();
+    assert_eq!(list.len(), 2);
+
+    assert_eq!(list[0].name, "alpha");
+    assert_eq!(list[0].version, 2);
+    assert_eq!(list[0].last_report_id, Some(last_id));
+    assert_eq!(list[0].last_run_passed, Some(false));
+    assert_eq!(list[0].last_run_version, Some(2));
+
+    // 从未执行过的场景
+    assert_eq!(list[1].name, "beta");
+    assert!(list[1].last_report_id.is_none());
+    assert!(list[1].last_run_at.is_none());
+
+    let stored = reports.get_by_id(last_id).await.unwrap().unwrap();
+    assert_eq!(stored.scenario_version, Some(2));
+}
+
 // ==================== Storage 统一接口测试 ====================
 
 #[tokio::test]
@@ -527,6 +921,285 @@ async fn test_storage_from_manager() {
     // 测试访问 repositories
     let _reports_repo = storage.reports();
     let _scenarios_repo = storage.scenarios();
+    assert_eq!(storage.metrics().count().await.unwrap(), 0);
+    assert!(storage.vm_cache().list_all().await.unwrap().is_empty());
+    assert!(storage.hosts().list_all().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_report_bundle_roundtrip() {
+    let manager = StorageManager::new_in_memory().await.unwrap();
+    let storage = Storage::from_manager(&manager);
+
+    // 本地已存在同名场景, 导入不应报错
+    storage.scenarios().create(&create_test_scenario("shared")).await.unwrap();
+
+    let mut report = create_test_report("shared", false);
+    report.start_time = Utc::now() - chrono::Duration::days(7);
+    report.created_at = report.start_time;
+    let report_id = storage.reports().create(&report).await.unwrap();
+    storage
+        .reports()
+        .create_steps(&[create_test_step(report_id, 0, true), create_test_step(report_id, 1, false)])
+        .await
+        .unwrap();
+    storage
+        .reports()
+        .create_resources(&[ReportResourceRecord {
+            id: 0,
+            report_id,
+            resource_type: "domain".to_string(),
+            resource_id: "vm-1".to_string(),
+            name: None,
+            step_index: -1,
+            cleanup_status: "orphaned".to_string(),
+            cleanup_error: None,
+        }])
+        .await
+        .unwrap();
+    storage
+        .reports()
+        .create_step_metrics(&[StepMetricsRecord {
+            id: 0,
+            report_id,
+            step_index: 1,
+            sample_count: 4,
+            interval_ms: 500,
+            cpu_min: Some(5.0),
+            cpu_avg: Some(12.5),
+            cpu_max: Some(30.0),
+            memory_min_mb: Some(1024.0),
+            memory_avg_mb: Some(1100.0),
+            memory_max_mb: Some(1200.0),
+            block_read_bytes: None,
+            block_write_bytes: None,
+        }])
+        .await
+        .unwrap();
+
+    let bundle = storage.reports().export_bundle(report_id, Some("lab")).await.unwrap();
+    assert_eq!(bundle.metadata.profile.as_deref(), Some("lab"));
+    assert_eq!(bundle.metadata.bundle_version, REPORT_BUNDLE_VERSION);
+    assert_eq!(bundle.steps.len(), 2);
+    assert_eq!(bundle.resources.len(), 1);
+    assert_eq!(bundle.step_metrics.len(), 1);
+
+    // 经 JSON 文件往返后导入
+    let json = serde_json::to_string(&bundle).unwrap();
+    let bundle: ReportBundle = serde_json::from_str(&json).unwrap();
+    let imported_id = storage.reports().import_bundle(&bundle).await.unwrap();
+    assert_ne!(imported_id, report_id);
+
+    let original = storage.reports().get_by_id(report_id).await.unwrap().unwrap();
+    let imported = storage.reports().get_by_id(imported_id).await.unwrap().unwrap();
+    assert_eq!(imported.scenario_name, "shared");
+    assert_eq!(imported.start_time, original.start_time);
+    assert_eq!(imported.created_at, original.created_at);
+    assert!(!imported.passed);
+
+    let steps = storage.reports().get_steps(imported_id).await.unwrap();
+    assert_eq!(steps.len(), 2);
+    assert!(steps.iter().all(|step| step.report_id == imported_id));
+    assert_eq!(steps[1].error.as_deref(), Some("Step failed"));
+    assert_eq!(storage.reports().get_resources(imported_id).await.unwrap()[0].resource_id, "vm-1");
+    let metrics = storage.reports().get_step_metrics(imported_id).await.unwrap();
+    assert_eq!(metrics.len(), 1);
+    assert_eq!((metrics[0].step_index, metrics[0].cpu_max), (1, Some(30.0)));
+
+    assert!(storage.reports().export_bundle(9999, None).await.is_err());
+}
+
+#[tokio::test]
+async fn test_import_bundle_rejects_newer_version() {
+    let pool = setup_test_db().await;
+    let repo = ReportRepository::new(pool);
+
+    let report_id = repo.create(&create_test_report("test_scenario", true)).await.unwrap();
+    let mut bundle = repo.export_bundle(report_id, None).await.unwrap();
+    bundle.metadata.bundle_version = REPORT_BUNDLE_VERSION + 1;
+
+    assert!(repo.import_bundle(&bundle).await.is_err());
+    assert_eq!(repo.count(&ReportFilter::default()).await.unwrap(), 1);
+}
+
+// ==================== 保留规则测试 ====================
+
+#[tokio::test]
+async fn test_retention_policy_crud() {
+    let manager = StorageManager::new_in_memory().await.unwrap();
+    let storage = Storage::from_manager(&manager);
+    let retention = storage.retention();
+
+    let id = retention
+        .create(&RetentionPolicyRecord::new("nightly-*", Some(20), None))
+        .await
+        .unwrap();
+    retention.create(&RetentionPolicyRecord::new("*", None, Some(30))).await.unwrap();
+
+    // 没有任何保留条件或条件为负数的规则无效
+    assert!(retention.create(&RetentionPolicyRecord::new("*", None, None)).await.is_err());
+    assert!(retention.create(&RetentionPolicyRecord::new("*", Some(-1), None)).await.is_err());
+
+    let mut policy = retention.get_by_id(id).await.unwrap().unwrap();
+    assert_eq!(policy.scenario_pattern, "nightly-*");
+    assert_eq!(policy.keep_last_n, Some(20));
+
+    policy.keep_days = Some(90);
+    retention.update(&policy).await.unwrap();
+    assert_eq!(retention.get_by_id(id).await.unwrap().unwrap().keep_days, Some(90));
+
+    assert_eq!(retention.list_all().await.unwrap().len(), 2);
+    retention.delete(id).await.unwrap();
+    assert!(retention.delete(id).await.is_err());
+    assert_eq!(retention.list_all().await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_apply_retention() {
+    let manager = StorageManager::new_in_memory().await.unwrap();
+    let storage = Storage::from_manager(&manager);
+    let reports = storage.reports();
+
+    // nightly-regression 与 smoke 各 5 次执行, 分别在 0..5 个 10 天之前
+    let mut ids = Vec::new();
+    for scenario in ["nightly-regression", "smoke"] {
+        for age in 0..5 {
+            let mut report = create_test_report(scenario, true);
+            report.start_time = Utc::now() - chrono::Duration::days(age * 10);
+            let id = reports.create(&report).await.unwrap();
+            reports.create_step(&create_test_step(id, 0, true)).await.unwrap();
+            ids.push(id);
+        }
+    }
+
+    // 没有规则时不删除任何报告
+    let result = storage.apply_retention(false).await.unwrap();
+    assert!(result.reports.is_empty());
+    assert_eq!(reports.count(&ReportFilter::default()).await.unwrap(), 10);
+
+    // nightly-* 保留最近 4 次, 其余只保留 25 天内的
+    let retention = storage.retention();
+    retention.create(&RetentionPolicyRecord::new("nightly-*", Some(4), None)).await.unwrap();
+    retention.create(&RetentionPolicyRecord::new("*", None, Some(25))).await.unwrap();
+
+    let dry_run = storage.apply_retention(true).await.unwrap();
+    // 按开始时间倒序: smoke 30 天前、smoke 40 天前、nightly 第 5 次
+    let expected = vec![ids[8], ids[9], ids[4]];
+    assert_eq!(dry_run.reports.iter().map(|r| r.id).collect::<Vec<_>>(), expected);
+    assert_eq!(dry_run.stats.report_count, 3);
+    assert_eq!(dry_run.stats.step_count, 3);
+    assert_eq!(reports.count(&ReportFilter::default()).await.unwrap(), 10);
+
+    let result = storage.apply_retention(false).await.unwrap();
+    assert_eq!(result.stats, dry_run.stats);
+    assert_eq!(reports.count(&ReportFilter::default()).await.unwrap(), 7);
+    assert!(reports.get_by_id(ids[4]).await.unwrap().is_none());
+    assert!(reports.get_steps(ids[4]).await.unwrap().is_empty());
+    assert!(reports.get_by_id(ids[3]).await.unwrap().is_some());
+
+    // 再次执行时没有需要删除的报告
+    assert_eq!(storage.apply_retention(false).await.unwrap().stats.report_count, 0);
+}
+
+/// 创建主机记录
+fn create_test_host(id: &str, ip: &str) -> HostRecord {
+    HostRecord {
+        id: id.to_string(),
+        host: ip.to_string(),
+        uri: format!("qemu+tcp://{}/system", ip),
+        tags: None,
+        metadata: Some(r#"{"vdi_host_id":"h-1"}"#.to_string()),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+    }
+}
+
+#[tokio::test]
+async fn test_host_upsert_by_host_id() {
+    let manager = StorageManager::new_in_memory().await.unwrap();
+    let storage = Storage::from_manager(&manager);
+    let hosts = storage.hosts();
+
+    assert!(hosts.upsert_by_host_id(&create_test_host("node-1", "10.0.0.1")).await.unwrap());
+    assert!(hosts.upsert_by_host_id(&create_test_host("node-2", "10.0.0.2")).await.unwrap());
+    let created_at = hosts.get_by_id("node-1").await.unwrap().unwrap().created_at;
+
+    // 再次同步同一主机: 更新地址, 保留创建时间
+    assert!(!hosts.upsert_by_host_id(&create_test_host("node-1", "10.0.0.9")).await.unwrap());
+    let host = hosts.get_by_id("node-1").await.unwrap().unwrap();
+    assert_eq!(host.host, "10.0.0.9");
+    assert_eq!(host.uri, "qemu+tcp://10.0.0.9/system");
+    assert_eq!(host.created_at, created_at);
+    assert!(host.updated_at >= created_at);
+
+    let ids: Vec<_> = hosts.list_all().await.unwrap().into_iter().map(|h| h.id).collect();
+    assert_eq!(ids, vec!["node-1", "node-2"]);
+
+    hosts.delete("node-2").await.unwrap();
+    assert!(hosts.get_by_id("node-2").await.unwrap().is_none());
+    assert!(hosts.delete("node-2").await.is_err());
+}
+
+#[tokio::test]
+async fn test_host_tags_and_probes() {
+    let manager = StorageManager::new_in_memory().await.unwrap();
+    let storage = Storage::from_manager(&manager);
+    let hosts = storage.hosts();
+    hosts.upsert_by_host_id(&create_test_host("node-1", "10.0.0.1")).await.unwrap();
+
+    hosts.set_tags("node-1", &["gpu".to_string(), "rack-a".to_string()]).await.unwrap();
+    let host = hosts.get_by_id("node-1").await.unwrap().unwrap();
+    assert_eq!(host.tags.as_deref(), Some(r#"["gpu","rack-a"]"#));
+    hosts.set_tags("node-1", &[]).await.unwrap();
+    assert!(hosts.get_by_id("node-1").await.unwrap().unwrap().tags.is_none());
+    assert!(hosts.set_tags("node-9", &[]).await.is_err());
+
+    let seen_at = Utc::now();
+    let probe = HostProbeRecord {
+        host_id: "node-1".to_string(),
+        probed_at: seen_at,
+        reachable: true,
+        last_seen_at: Some(seen_at),
+        error: None,
+        libvirt_version: Some("8.0.0".to_string()),
+        hypervisor: Some("QEMU".to_string()),
+        hypervisor_version: Some("6.2.0".to_string()),
+        cpu_model: Some("Skylake-Client-IBRS".to_string()),
+        max_vcpus: Some(288),
+        capabilities: Some("{}".to_string()),
+    };
+    hosts.record_probe(&probe).await.unwrap();
+    assert_eq!(hosts.get_probe("node-1").await.unwrap(), Some(probe.clone()));
+
+    // 探测失败: 更新状态与错误, 保留最近在线时间与能力
+    let failed_at = seen_at + chrono::Duration::minutes(5);
+    hosts
+        .record_probe(&HostProbeRecord {
+            probed_at: failed_at,
+            reachable: false,
+            last_seen_at: None,
+            error: Some("connection refused".to_string()),
+            libvirt_version: None,
+            hypervisor: None,
+            hypervisor_version: None,
+            cpu_model: None,
+            max_vcpus: None,
+            capabilities: None,
+            ..probe.clone()
+        })
+        .await
+        .unwrap();
+    let failed = hosts.get_probe("node-1").await.unwrap().unwrap();
+    assert!(!failed.reachable);
+    assert_eq!(failed.probed_at, failed_at);
+    assert_eq!(failed.error.as_deref(), Some("connection refused"));
+    assert_eq!(failed.last_seen_at, Some(seen_at));
+    assert_eq!(failed.cpu_model.as_deref(), Some("Skylake-Client-IBRS"));
+    assert_eq!(hosts.list_probes().await.unwrap().len(), 1);
+
+    // 删除主机时一并删除探测结果
+    hosts.delete("node-1").await.unwrap();
+    assert!(hosts.get_probe("node-1").await.unwrap().is_none());
 }
 
 #[tokio::test]
@@ -660,3 +1333,551 @@ async fn test_concurrent_report_creation() {
     let count = repo.count(&filter).await.unwrap();
     assert_eq!(count, 10);
 }
+
+// ========================================
+// 指标采集测试
+// ========================================
+
+/// 返回固定指标的假指标源
+struct FakeSource {
+    name: &'static str,
+    samples: Vec<MetricSample>,
+}
+
+#[async_trait]
+impl MetricsSource for FakeSource {
+    fn source_name(&self) -> &str {
+        self.name
+    }
+
+    async fn collect(&self) -> Vec<MetricSample> {
+        self.samples.clone()
+    }
+}
+
+fn fake_sources() -> (Arc<FakeSource>, Arc<FakeSource>) {
+    let transport = Arc::new(FakeSource {
+        name: "transport",
+        samples: vec![
+            MetricSample::new("active_connections", 2.0).with_label("host", "host1"),
+            MetricSample::new("active_connections", 1.0).with_label("host", "host2"),
+        ],
+    });
+    let qga = Arc::new(FakeSource {
+        name: "qga",
+        samples: vec![MetricSample::new("last_latency_ms", 12.5)],
+    });
+    (transport, qga)
+}
+
+#[tokio::test]
+async fn test_metrics_collector_merges_sources() {
+    let pool = setup_test_db().await;
+    let repo = MetricRepository::new(pool);
+    let collector = MetricsCollector::new(repo.clone(), CollectorConfig::default());
+
+    let (transport, qga) = fake_sources();
+    collector.add_source(transport).await;
+    collector.add_source(qga).await;
+    assert_eq!(collector.source_count().await, 2);
+
+    // 未 flush 前不写库
+    assert_eq!(collector.collect_once().await.unwrap(), 3);
+    assert_eq!(collector.buffered_count().await, 3);
+    assert_eq!(repo.count().await.unwrap(), 0);
+
+    assert_eq!(collector.flush().await.unwrap(), 3);
+    assert_eq!(collector.buffered_count().await, 0);
+    assert_eq!(collector.flush().await.unwrap(), 0);
+
+    let samples = repo.list(&MetricFilter::default()).await.unwrap();
+    assert_eq!(samples.len(), 3);
+    // 同一轮采集共享时间戳
+    assert!(samples.iter().all(|s| s.timestamp == samples[0].timestamp));
+
+    let transport_samples = repo
+        .list(&MetricFilter {
+            source: Some("transport".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(transport_samples.len(), 2);
+    assert_eq!(transport_samples[0].labels.as_deref(), Some(r#"{"host":"host1"}"#));
+
+    let qga_samples = repo
+        .list(&MetricFilter {
+            name: Some("last_latency_ms".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(qga_samples.len(), 1);
+    assert_eq!(qga_samples[0].source, "qga");
+    assert_eq!(qga_samples[0].labels, None);
+    assert_eq!(qga_samples[0].value, 12.5);
+}
+
+#[tokio::test]
+async fn test_metrics_collector_flush_threshold() {
+    let pool = setup_test_db().await;
+    let repo = MetricRepository::new(pool);
+    let config = CollectorConfig {
+        flush_threshold: 4,
+        ..Default::default()
+    };
+    let collector = MetricsCollector::new(repo.clone(), config);
+
+    let (transport, qga) = fake_sources();
+    collector.add_source(transport).await;
+    collector.add_source(qga).await;
+
+    collector.collect_once().await.unwrap();
+    assert_eq!(repo.count().await.unwrap(), 0);
+
+    // 第二轮后缓存达到阈值, 自动写库
+    collector.collect_once().await.unwrap();
+    assert_eq!(repo.count().await.unwrap(), 6);
+    assert_eq!(collector.buffered_count().await, 0);
+}
+
+#[tokio::test]
+async fn test_metrics_collector_start_stop() {
+    let pool = setup_test_db().await;
+    let repo = MetricRepository::new(pool);
+    let config = CollectorConfig {
+        interval: std::time::Duration::from_millis(20),
+        flush_threshold: 1000,
+    };
+    let collector = Arc::new(MetricsCollector::new(repo.clone(), config));
+
+    let (_, qga) = fake_sources();
+    collector.add_source(qga).await;
+
+    collector.start().await;
+    assert!(collector.is_running().await);
+
+    tokio::time::sleep(std::time::Duration::from_millis(110)).await;
+
+    // 停止时写入剩余缓存
+    let written = collector.stop().await.unwrap();
+    assert!(!collector.is_running().await);
+    assert!(written >= 2, "expected at least 2 rounds, got {}", written);
+
+    let count = repo.count().await.unwrap();
+    assert_eq!(count, written as i64);
+
+    // 停止后不再采集
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    assert_eq!(collector.buffered_count().await, 0);
+    assert_eq!(repo.count().await.unwrap(), count);
+}
+
+#[tokio::test]
+async fn test_metric_repository_delete_before() {
+    let pool = setup_test_db().await;
+    let repo = MetricRepository::new(pool);
+
+    let old = atp_storage::MetricSampleRecord {
+        id: 0,
+        source: "transport".to_string(),
+        name: "total_requests".to_string(),
+        value: 1.0,
+        labels: None,
+        timestamp: Utc::now() - chrono::Duration::days(10),
+    };
+    let recent = atp_storage::MetricSampleRecord {
+        timestamp: Utc::now(),
+        ..old.clone()
+    };
+    assert_eq!(repo.create_batch(&[old, recent]).await.unwrap(), 2);
+
+    let deleted = repo
+        .delete_before(Utc::now() - chrono::Duration::days(1))
+        .await
+        .unwrap();
+    assert_eq!(deleted, 1);
+    assert_eq!(repo.count().await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_entity_metrics_range_and_aggregate() {
+    let pool = setup_test_db().await;
+    let repo = MetricRepository::new(pool);
+
+    let base = chrono::DateTime::parse_from_rfc3339("2026-03-01T10:00:00Z").unwrap().with_timezone(&Utc);
+    let vm = MetricEntity::vm("win10-01");
+    let at = |secs: i64| base + chrono::Duration::seconds(secs);
+
+    // 每 20 秒一个 cpu 采样, 值依次为 0, 10, ..., 50; 另有其他实体与指标的干扰数据
+    let mut samples: Vec<EntityMetricSample> =
+        (0..6).map(|i| EntityMetricSample::new(&vm, "cpu", i as f64 * 10.0, at(i * 20))).collect();
+    samples.push(EntityMetricSample::new(&vm, "memory_mb", 2048.0, at(0)));
+    samples.push(EntityMetricSample::new(&MetricEntity::vm("win10-02"), "cpu", 99.0, at(0)));
+    samples.push(EntityMetricSample::new(&MetricEntity::host("win10-01"), "cpu", 99.0, at(0)));
+    assert_eq!(repo.insert_batch(&samples).await.unwrap(), 9);
+    assert_eq!(repo.count_entity_samples().await.unwrap(), 9);
+
+    // 半开区间 [20s, 100s)
+    let range = repo.range(&vm, "cpu", at(20)..at(100)).await.unwrap();
+    let values: Vec<f64> = range.iter().map(|sample| sample.value).collect();
+    assert_eq!(values, vec![10.0, 20.0, 30.0, 40.0]);
+    assert_eq!(range[0].sampled_at, at(20));
+
+    // 1 分钟一个桶: [0, 20, 40] 与 [60, 80, 100]
+    let buckets = repo
+        .aggregate(&vm, "cpu", at(0)..at(3600), chrono::Duration::minutes(1))
+        .await
+        .unwrap();
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets[0].bucket_start, at(0));
+    assert_eq!((buckets[0].avg, buckets[0].max, buckets[0].count), (10.0, 20.0, 3));
+    assert_eq!(buckets[1].bucket_start, at(60));
+    assert_eq!((buckets[1].avg, buckets[1].max, buckets[1].count), (40.0, 50.0, 3));
+
+    assert!(repo
+        .aggregate(&vm, "cpu", at(0)..at(3600), chrono::Duration::milliseconds(500))
+        .await
+        .is_err());
+    assert!(repo.aggregate(&vm, "disk", at(0)..at(3600), chrono::Duration::minutes(1)).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_entity_metrics_insert_throughput() {
+    let pool = setup_test_db().await;
+    let repo = MetricRepository::new(pool);
+
+    let base = Utc::now() - chrono::Duration::days(1);
+    let entities: Vec<MetricEntity> = (0..10).map(|i| MetricEntity::vm(&format!("vm-{}", i))).collect();
+    let samples: Vec<EntityMetricSample> = (0..100_000)
+        .map(|i: i64| {
+            let entity = &entities[(i % 10) as usize];
+            EntityMetricSample::new(entity, "cpu", (i % 100) as f64, base + chrono::Duration::milliseconds(i * 100))
+        })
+        .collect();
+
+    let started = std::time::Instant::now();
+    assert_eq!(repo.insert_batch(&samples).await.unwrap(), 100_000);
+    let elapsed = started.elapsed();
+    println!("写入 100000 条指标耗时 {:?}", elapsed);
+    assert!(elapsed < std::time::Duration::from_secs(60), "写入过慢: {:?}", elapsed);
+    assert_eq!(repo.count_entity_samples().await.unwrap(), 100_000);
+
+    // 每台虚拟机 10000 条, 每秒 1 条 -> 约 167 个 1 分钟的桶
+    let buckets = repo
+        .aggregate(&entities[3], "cpu", base..Utc::now(), chrono::Duration::minutes(1))
+        .await
+        .unwrap();
+    assert_eq!(buckets.iter().map(|bucket| bucket.count).sum::<i64>(), 10_000);
+    assert!((167..=168).contains(&buckets.len()), "{}", buckets.len());
+}
+
+/// 创建虚拟机缓存记录
+fn create_test_vm(id: &str, name: &str, status: &str, updated_at: chrono::DateTime<Utc>) -> VmCacheRecord {
+    VmCacheRecord {
+        id: id.to_string(),
+        name: name.to_string(),
+        status: status.to_string(),
+        host_id: "host-1".to_string(),
+        cpu: Some(2),
+        memory: Some(4096),
+        updated_at,
+    }
+}
+
+#[tokio::test]
+async fn test_vm_cache_sync_records_transitions() {
+    let pool = setup_test_db().await;
+    let repo = VmCacheRepository::new(pool);
+    let t0 = Utc::now() - chrono::Duration::minutes(10);
+    let t1 = Utc::now();
+
+    assert!(repo.last_updated().await.unwrap().is_none());
+
+    // 首次同步: 每台虚拟机都记录一次 (old_status 为空)
+    let transitions = repo
+        .sync_all(&[
+            create_test_vm("vm-1", "win10-01", "关机", t0),
+            create_test_vm("vm-2", "win10-02", "运行中", t0),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(transitions.len(), 2);
+    assert!(transitions.iter().all(|t| t.old_status.is_none()));
+
+    // 第二次同步: 只有 vm-1 状态变化, vm-2 已被删除, 新增 vm-3
+    let transitions = repo
+        .sync_all(&[
+            create_test_vm("vm-1", "win10-01", "运行中", t1),
+            create_test_vm("vm-3", "win10-03", "关机", t1),
+        ])
+        .await
+        .unwrap();
+    assert_eq!(transitions.len(), 2);
+    assert_eq!(transitions[0].vm_id, "vm-1");
+    assert_eq!(transitions[0].old_status.as_deref(), Some("关机"));
+    assert_eq!(transitions[0].new_status, "运行中");
+
+    let vms = repo.list_all().await.unwrap();
+    let names: Vec<_> = vms.iter().map(|vm| vm.name.as_str()).collect();
+    assert_eq!(names, vec!["win10-01", "win10-03"]);
+    assert!(repo.get_by_id("vm-2").await.unwrap().is_none());
+    assert_eq!(repo.get_by_id("vm-1").await.unwrap().unwrap().status, "运行中");
+    assert_eq!(repo.find_by_name("win10-03").await.unwrap().len(), 1);
+
+    let last = repo.last_updated().await.unwrap().unwrap();
+    assert_eq!(last.timestamp(), t1.timestamp());
+
+    // 历史按时间升序, 已删除的虚拟机历史保留
+    let history = repo.history("vm-1").await.unwrap();
+    let statuses: Vec<_> = history.iter().map(|h| h.new_status.as_str()).collect();
+    assert_eq!(statuses, vec!["关机", "运行中"]);
+    assert_eq!(repo.history("vm-2").await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_vm_cache_unchanged_status_not_recorded() {
+    let pool = setup_test_db().await;
+    let repo = VmCacheRepository::new(pool);
+    let vm = create_test_vm("vm-1", "win10-01", "运行中", Utc::now() - chrono::Duration::days(3));
+
+    repo.sync_all(std::slice::from_ref(&vm)).await.unwrap();
+    let transitions = repo
+        .sync_all(&[VmCacheRecord {
+            name: "renamed".to_string(),
+            updated_at: Utc::now(),
+            ..vm
+        }])
+        .await
+        .unwrap();
+    assert!(transitions.is_empty());
+    assert_eq!(repo.get_by_id("vm-1").await.unwrap().unwrap().name, "renamed");
+
+    let deleted = repo
+        .delete_history_before(Utc::now() - chrono::Duration::days(1))
+        .await
+        .unwrap();
+    assert_eq!(deleted, 1);
+    assert!(repo.history("vm-1").await.unwrap().is_empty());
+}
+
+fn verification_record(vm_id: &str, outcome: &str, created_at: chrono::DateTime<Utc>) -> VerificationResultRecord {
+    let timed_out = outcome == "timeout";
+    VerificationResultRecord {
+        id: 0,
+        vm_id: vm_id.to_string(),
+        event_id: format!("{}-{}", vm_id, outcome),
+        event_type: "keyboard".to_string(),
+        verified: outcome == "verified",
+        outcome: outcome.to_string(),
+        latency_ms: (!timed_out).then_some(12),
+        server_latency_ms: (!timed_out).then_some(40),
+        details: Some(r#"{"key":"a"}"#.to_string()),
+        created_at,
+    }
+}
+
+#[tokio::test]
+async fn test_verification_results_query_by_vm_and_time() {
+    let pool = setup_test_db().await;
+    let repo = VerificationRepository::new(pool);
+    let now = Utc::now();
+
+    let records = [
+        verification_record("vm-1", "verified", now - chrono::Duration::hours(30)),
+        verification_record("vm-1", "timeout", now - chrono::Duration::hours(2)),
+        verification_record("vm-2", "mismatched", now - chrono::Duration::hours(1)),
+    ];
+    for record in &records {
+        repo.create(record).await.unwrap();
+    }
+    assert_eq!(repo.count().await.unwrap(), 3);
+
+    let vm1 = repo.list_by_vm("vm-1").await.unwrap();
+    assert_eq!(vm1.len(), 2);
+    assert_eq!(vm1[0].outcome, "verified");
+    assert!(vm1[0].verified);
+    assert_eq!(vm1[1].latency_ms, None);
+
+    let recent = repo
+        .list_by_time_range(now - chrono::Duration::hours(24), now)
+        .await
+        .unwrap();
+    assert_eq!(recent.iter().map(|r| r.vm_id.as_str()).collect::<Vec<_>>(), ["vm-1", "vm-2"]);
+
+    let filtered = repo
+        .list(&VerificationFilter {
+            vm_id: Some("vm-1".to_string()),
+            from: Some(now - chrono::Duration::hours(24)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].details.as_deref(), Some(r#"{"key":"a"}"#));
+}
+
+fn benchmark_run(target: &str, label: &str, started_at: chrono::DateTime<Utc>) -> BenchmarkRunRecord {
+    BenchmarkRunRecord {
+        id: 0,
+        kind: "input-latency".to_string(),
+        target: target.to_string(),
+        label: Some(label.to_string()),
+        samples: 4,
+        observed: 3,
+        mismatched: 0,
+        timeouts: 1,
+        failures: 0,
+        min_ms: Some(10),
+        median_ms: Some(12),
+        p95_ms: Some(30),
+        p99_ms: Some(30),
+        max_ms: Some(30),
+        mean_ms: Some(17.3),
+        latencies: "[10,12,30]".to_string(),
+        options: Some(r#"{"interval_ms":250}"#.to_string()),
+        started_at,
+        duration_ms: 1500,
+    }
+}
+
+#[tokio::test]
+async fn test_benchmark_runs() {
+    let manager = StorageManager::new_in_memory().await.unwrap();
+    let storage = Storage::from_manager(&manager);
+    let repo = storage.benchmarks();
+    let now = Utc::now();
+
+    let old = repo.create(&benchmark_run("win10", "v1.0", now - chrono::Duration::days(1))).await.unwrap();
+    let new = repo.create(&benchmark_run("win10", "v1.1", now)).await.unwrap();
+    repo.create(&benchmark_run("ubuntu", "v1.1", now)).await.unwrap();
+
+    let record = repo.get_by_id(old).await.unwrap().unwrap();
+    assert_eq!(record.label.as_deref(), Some("v1.0"));
+    assert_eq!((record.observed, record.timeouts, record.p99_ms), (3, 1, Some(30)));
+    assert_eq!(record.latencies, "[10,12,30]");
+
+    let runs = repo
+        .list(&BenchmarkFilter {
+            target: Some("win10".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(runs.iter().map(|run| run.id).collect::<Vec<_>>(), [new, old]);
+
+    let latest = repo
+        .list(&BenchmarkFilter {
+            kind: Some("input-latency".to_string()),
+            limit: Some(1),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(latest.len(), 1);
+
+    repo.delete(old).await.unwrap();
+    assert!(repo.get_by_id(old).await.unwrap().is_none());
+    assert!(repo.delete(old).await.is_err());
+}
+
+#[tokio::test]
+async fn test_schema_version_and_idempotent_migrate() {
+    let manager = StorageManager::new_in_memory().await.unwrap();
+
+    assert_eq!(manager.schema_version().await.unwrap(), LATEST_SCHEMA_VERSION);
+    assert!(manager.pending_migrations().await.unwrap().is_empty());
+
+    // 重复执行不会再次应用
+    assert!(manager.migrate().await.unwrap().is_empty());
+    assert_eq!(manager.schema_version().await.unwrap(), LATEST_SCHEMA_VERSION);
+
+    let report = manager.verify_integrity().await.unwrap();
+    assert!(report.is_ok(), "{:?}", report);
+}
+
+#[tokio::test]
+async fn test_pre_migration_database_stamped_as_version_1() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("legacy.db");
+    std::fs::File::create(&db_path).unwrap();
+    let db_path = db_path.to_str().unwrap();
+
+    // 模拟引入版本管理之前的数据库: 只有初始表和数据, 没有 schema_version
+    {
+        let manager = StorageManager::open(db_path).await.unwrap();
+        sqlx::query(include_str!("../migrations/001_initial.sql"))
+            .execute(manager.pool())
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO test_reports (scenario_name, start_time, passed) VALUES ('legacy', ?, 1)")
+            .bind(Utc::now())
+            .execute(manager.pool())
+            .await
+            .unwrap();
+
+        assert_eq!(manager.schema_version().await.unwrap(), 1);
+        let pending = manager.pending_migrations().await.unwrap();
+        assert_eq!(pending.len() as i64, LATEST_SCHEMA_VERSION - 1);
+        assert_eq!(pending[0].version, 2);
+        manager.close().await;
+    }
+
+    let manager = StorageManager::new(db_path).await.unwrap();
+    assert_eq!(manager.schema_version().await.unwrap(), LATEST_SCHEMA_VERSION);
+
+    // 原有数据保留
+    let repo = ReportRepository::new(manager.pool().clone());
+    assert_eq!(repo.count(&ReportFilter::default()).await.unwrap(), 1);
+
+    let versions: Vec<(i64,)> = sqlx::query_as("SELECT version FROM schema_version ORDER BY version")
+        .fetch_all(manager.pool())
+        .await
+        .unwrap();
+    assert_eq!(versions.len() as i64, LATEST_SCHEMA_VERSION);
+    assert_eq!(versions[0].0, 1);
+
+    // 建表之后新增的列都由编号迁移添加
+    for (table, column) in [
+        ("test_reports", "scenario_version"),
+        ("execution_steps", "error_kind"),
+        ("execution_steps", "started_at_offset_ms"),
+    ] {
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?")
+            .bind(table)
+            .bind(column)
+            .fetch_one(manager.pool())
+            .await
+            .unwrap();
+        assert_eq!(count, 1, "{}.{}", table, column);
+    }
+}
+
+#[tokio::test]
+async fn test_newer_database_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("newer.db");
+    std::fs::File::create(&db_path).unwrap();
+    let db_path = db_path.to_str().unwrap();
+
+    {
+        let manager = StorageManager::new(db_path).await.unwrap();
+        sqlx::query("INSERT INTO schema_version (version, name, applied_at) VALUES (?, 'future', ?)")
+            .bind(LATEST_SCHEMA_VERSION + 1)
+            .bind(Utc::now())
+            .execute(manager.pool())
+            .await
+            .unwrap();
+        manager.close().await;
+    }
+
+    let err = match StorageManager::new(db_path).await {
+        Ok(_) => panic!("newer database should be rejected"),
+        Err(e) => e,
+    };
+    assert!(err.to_string().contains("newer"), "{}", err);
+
+    // 仍可只读打开查看版本
+    let manager = StorageManager::open(db_path).await.unwrap();
+    assert_eq!(manager.schema_version().await.unwrap(), LATEST_SCHEMA_VERSION + 1);
+}
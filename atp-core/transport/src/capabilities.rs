@@ -0,0 +1,231 @@
+//! 主机能力
+//!
+//! 解析 `virConnectGetCapabilities` 返回的 XML (`virsh capabilities`), 提取主机 CPU 与可用的虚拟化类型,
+//! 与 libvirt / hypervisor 版本一起作为主机探测结果。
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, TransportError};
+
+/// 主机的 libvirt 能力
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostCapabilities {
+    /// libvirt 版本 (如 `8.0.0`)
+    pub libvirt_version: String,
+
+    /// hypervisor 类型 (`virConnectGetType`, 如 `QEMU`)
+    pub hypervisor: String,
+
+    /// hypervisor 版本 (如 `6.2.0`)
+    pub hypervisor_version: Option<String>,
+
+    /// 主机 CPU 架构
+    pub arch: Option<String>,
+
+    /// 主机 CPU 型号
+    pub cpu_model: Option<String>,
+
+    /// 主机 CPU 厂商
+    pub cpu_vendor: Option<String>,
+
+    /// 主机逻辑 CPU 数量 (各 NUMA 节点之和)
+    pub host_cpus: Option<u32>,
+
+    /// 单台虚拟机的最大 vCPU 数
+    pub max_vcpus: Option<u32>,
+
+    /// 可用的虚拟机类型 (`<guest><arch><domain type=...>`, 如 kvm、qemu)
+    pub domain_types: Vec<String>,
+}
+
+impl HostCapabilities {
+    /// 解析 capabilities XML 中的主机 CPU 与虚拟机类型 (版本与 hypervisor 类型由调用方填写)
+    pub fn parse(xml: &str) -> Result<Self> {
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+
+        let mut capabilities = HostCapabilities::default();
+        let mut path: Vec<String> = Vec::new();
+        let mut has_root = false;
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| xml_error(format!("位置 {}: {}", reader.buffer_position(), e)))?;
+
+            match event {
+                Event::Start(e) => {
+                    has_root = true;
+                    let name = local_name(&e);
+                    capabilities.on_element(&path, &name, &e)?;
+                    path.push(name);
+                }
+                Event::Empty(e) => {
+                    has_root = true;
+                    let name = local_name(&e);
+                    capabilities.on_element(&path, &name, &e)?;
+                }
+                Event::Text(text) => {
+                    let text = text.unescape().map_err(|e| xml_error(e.to_string()))?;
+                    capabilities.on_text(&path, &text);
+                }
+                Event::End(_) => {
+                    path.pop();
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        if !has_root {
+            return Err(xml_error("没有根元素".to_string()));
+        }
+        Ok(capabilities)
+    }
+
+    /// 查询最大 vCPU 数时使用的虚拟机类型: 支持 KVM 时为 `kvm`, 否则为 hypervisor 类型
+    pub fn vcpu_domain_type(&self) -> String {
+        if self.domain_types.iter().any(|t| t == "kvm") {
+            "kvm".to_string()
+        } else {
+            self.hypervisor.to_lowercase()
+        }
+    }
+
+    fn on_element(&mut self, path: &[String], name: &str, e: &BytesStart<'_>) -> Result<()> {
+        let parent = path.last().map(String::as_str);
+
+        match (parent, name) {
+            (Some("cell"), "cpus") if path.iter().any(|p| p == "host") => {
+                if let Some(num) = attribute(e, "num")?.and_then(|n| n.parse::<u32>().ok()) {
+                    *self.host_cpus.get_or_insert(0) += num;
+                }
+            }
+            (Some("arch"), "domain") if path.iter().any(|p| p == "guest") => {
+                if let Some(domain_type) = attribute(e, "type")? {
+                    if !self.domain_types.contains(&domain_type) {
+                        self.domain_types.push(domain_type);
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn on_text(&mut self, path: &[String], text: &str) {
+        // 只取 <capabilities><host><cpu> 下的直接子元素
+        if path.len() != 4 || path[1] != "host" || path[2] != "cpu" {
+            return;
+        }
+        match path[3].as_str() {
+            "arch" => self.arch = Some(text.to_string()),
+            "model" => self.cpu_model = Some(text.to_string()),
+            "vendor" => self.cpu_vendor = Some(text.to_string()),
+            _ => {}
+        }
+    }
+}
+
+/// libvirt 版本号 (`major * 1,000,000 + minor * 1,000 + release`) 转换为 `major.minor.release`
+pub fn format_version(version: u32) -> String {
+    format!("{}.{}.{}", version / 1_000_000, version / 1_000 % 1_000, version % 1_000)
+}
+
+/// 去掉命名空间前缀的元素名
+fn local_name(e: &BytesStart<'_>) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+fn attribute(e: &BytesStart<'_>, key: &str) -> Result<Option<String>> {
+    let attr = e
+        .try_get_attribute(key)
+        .map_err(|err| xml_error(err.to_string()))?;
+    attr.map(|a| a.unescape_value().map(|v| v.into_owned()))
+        .transpose()
+        .map_err(|err| xml_error(err.to_string()))
+}
+
+fn xml_error(message: String) -> TransportError {
+    TransportError::LibvirtError(format!("解析主机 capabilities XML 失败: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPABILITIES: &str = r#"
+<capabilities>
+  <host>
+    <uuid>4c4c4544-0042-3610-8058-b4c04f564433</uuid>
+    <cpu>
+      <arch>x86_64</arch>
+      <model>Skylake-Client-IBRS</model>
+      <vendor>Intel</vendor>
+      <microcode version='240'/>
+      <topology sockets='1' dies='1' cores='4' threads='2'/>
+      <feature name='ds'/>
+    </cpu>
+    <topology>
+      <cells num='2'>
+        <cell id='0'>
+          <memory unit='KiB'>16303092</memory>
+          <cpus num='4'>
+            <cpu id='0' socket_id='0' core_id='0' siblings='0,4'/>
+          </cpus>
+        </cell>
+        <cell id='1'>
+          <cpus num='4'/>
+        </cell>
+      </cells>
+    </topology>
+  </host>
+  <guest>
+    <os_type>hvm</os_type>
+    <arch name='x86_64'>
+      <wordsize>64</wordsize>
+      <emulator>/usr/bin/qemu-system-x86_64</emulator>
+      <machine maxCpus='255'>pc-i440fx-6.2</machine>
+      <domain type='qemu'/>
+      <domain type='kvm'/>
+    </arch>
+  </guest>
+  <guest>
+    <os_type>hvm</os_type>
+    <arch name='i686'>
+      <domain type='qemu'/>
+      <domain type='kvm'/>
+    </arch>
+  </guest>
+</capabilities>"#;
+
+    #[test]
+    fn test_parse_capabilities() {
+        let capabilities = HostCapabilities::parse(CAPABILITIES).unwrap();
+
+        assert_eq!(capabilities.arch.as_deref(), Some("x86_64"));
+        assert_eq!(capabilities.cpu_model.as_deref(), Some("Skylake-Client-IBRS"));
+        assert_eq!(capabilities.cpu_vendor.as_deref(), Some("Intel"));
+        assert_eq!(capabilities.host_cpus, Some(8));
+        assert_eq!(capabilities.domain_types, vec!["qemu", "kvm"]);
+        assert_eq!(capabilities.vcpu_domain_type(), "kvm");
+
+        let qemu_only = HostCapabilities {
+            hypervisor: "QEMU".to_string(),
+            domain_types: vec!["qemu".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(qemu_only.vcpu_domain_type(), "qemu");
+
+        assert!(HostCapabilities::parse("").is_err());
+    }
+
+    #[test]
+    fn test_format_version() {
+        assert_eq!(format_version(8_000_000), "8.0.0");
+        assert_eq!(format_version(6_002_001), "6.2.1");
+        assert_eq!(format_version(10_010_000), "10.10.0");
+    }
+}
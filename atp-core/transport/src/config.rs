@@ -1,8 +1,14 @@
 //! 传输层配置
+//!
+//! 除连接池与重连参数外, 配置中也可以列出要注册的主机,
+//! 由 [`TransportManager::from_config`](crate::TransportManager::from_config) 一次性注册。
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::time::Duration;
 
+use crate::{HostInfo, Result, SshConfig, TransportError};
+
 /// 传输层配置
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransportConfig {
@@ -23,6 +29,65 @@ pub struct TransportConfig {
 
     /// 重连配置
     pub reconnect: ReconnectConfig,
+
+    /// 要注册的主机
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hosts: Vec<HostEntry>,
+}
+
+/// 配置中的主机
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostEntry {
+    /// 主机 ID
+    pub id: String,
+
+    /// 主机名或 IP
+    pub host: String,
+
+    /// Libvirt URI (未设置时通过 SSH 连接: `qemu+ssh://<host>:22/system`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+
+    /// 标签
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// 元数据
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub metadata: HashMap<String, String>,
+
+    /// SSH 配置 (执行宿主机命令时使用)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh: Option<SshConfig>,
+}
+
+impl HostEntry {
+    pub fn new(id: &str, host: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            host: host.to_string(),
+            uri: None,
+            tags: Vec::new(),
+            metadata: HashMap::new(),
+            ssh: None,
+        }
+    }
+
+    pub fn with_uri(mut self, uri: &str) -> Self {
+        self.uri = Some(uri.to_string());
+        self
+    }
+
+    /// 转换为连接池使用的主机信息
+    pub fn to_host_info(&self) -> HostInfo {
+        let mut host_info = HostInfo::new(&self.id, &self.host).with_tags(self.tags.clone());
+        if let Some(uri) = &self.uri {
+            host_info = host_info.with_uri(uri);
+        }
+        host_info.metadata = self.metadata.clone();
+        host_info.ssh = self.ssh.clone();
+        host_info
+    }
 }
 
 /// 重连配置
@@ -91,6 +156,7 @@ impl Default for TransportConfig {
             heartbeat_interval: default_heartbeat_interval(),
             auto_reconnect: default_auto_reconnect(),
             reconnect: ReconnectConfig::default(),
+            hosts: Vec::new(),
         }
     }
 }
@@ -125,6 +191,105 @@ impl TransportConfig {
     pub fn heartbeat_interval(&self) -> Duration {
         Duration::from_secs(self.heartbeat_interval)
     }
+
+    /// 添加要注册的主机
+    pub fn with_host(mut self, host: HostEntry) -> Self {
+        self.hosts.push(host);
+        self
+    }
+
+    /// 校验配置
+    ///
+    /// 一次列出所有问题 (主机 ID 重复、地址为空、URI 格式错误、连接池参数无效),
+    /// 有问题时返回 [`TransportError::InvalidConfig`]。
+    pub fn validate(&self) -> Result<()> {
+        let mut problems = Vec::new();
+
+        if self.pool.max_connections_per_host == 0 {
+            problems.push("pool.max_connections_per_host 不能为 0".to_string());
+        }
+        if self.pool.min_connections_per_host > self.pool.max_connections_per_host {
+            problems.push(format!(
+                "pool.min_connections_per_host ({}) 大于 max_connections_per_host ({})",
+                self.pool.min_connections_per_host, self.pool.max_connections_per_host
+            ));
+        }
+        if self.reconnect.backoff_multiplier < 1.0 {
+            problems.push(format!("reconnect.backoff_multiplier ({}) 不能小于 1", self.reconnect.backoff_multiplier));
+        }
+
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for host in &self.hosts {
+            if host.id.is_empty() {
+                problems.push(format!("主机 {} 的 ID 为空", host.host));
+                continue;
+            }
+            let count = seen.entry(host.id.as_str()).or_default();
+            *count += 1;
+            if *count == 2 {
+                problems.push(format!("主机 ID {} 重复", host.id));
+            }
+            if host.host.is_empty() {
+                problems.push(format!("主机 {} 的地址为空", host.id));
+            }
+            if let Some(uri) = &host.uri {
+                if let Err(reason) = validate_libvirt_uri(uri) {
+                    problems.push(format!("主机 {} 的 URI {} 无效: {}", host.id, uri, reason));
+                }
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(TransportError::InvalidConfig(problems))
+        }
+    }
+}
+
+/// 需要远端主机地址的 libvirt 传输方式
+const REMOTE_TRANSPORTS: &[&str] = &["ssh", "libssh", "libssh2", "tcp", "tls"];
+
+/// libvirt 支持的传输方式
+const URI_TRANSPORTS: &[&str] = &["ssh", "libssh", "libssh2", "tcp", "tls", "unix", "ext"];
+
+/// 检查 libvirt URI 的格式 (`driver[+transport]://[user@][host][:port]/path`)
+pub fn validate_libvirt_uri(uri: &str) -> std::result::Result<(), String> {
+    let (scheme, rest) = uri.split_once("://").ok_or("缺少 \"://\"")?;
+    let (driver, transport) = match scheme.split_once('+') {
+        Some((driver, transport)) => (driver, Some(transport)),
+        None => (scheme, None),
+    };
+
+    if driver.is_empty() || !driver.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("驱动名称 \"{}\" 无效", driver));
+    }
+    if let Some(transport) = transport {
+        if !URI_TRANSPORTS.contains(&transport) {
+            return Err(format!("不支持的传输方式 \"{}\"", transport));
+        }
+    }
+
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+    if authority.chars().any(char::is_whitespace) {
+        return Err("主机部分包含空白字符".to_string());
+    }
+    if transport.is_some_and(|transport| REMOTE_TRANSPORTS.contains(&transport)) && authority.is_empty() {
+        return Err("远程连接缺少主机地址".to_string());
+    }
+    if let Some((_, port)) = authority.rsplit_once(':').filter(|_| !authority.ends_with(']')) {
+        if port.parse::<u16>().is_err() {
+            return Err(format!("端口 \"{}\" 无效", port));
+        }
+    }
+    if path.len() <= 1 {
+        return Err("缺少路径 (如 /system)".to_string());
+    }
+
+    Ok(())
 }
 
 impl PoolConfig {
@@ -1,12 +1,17 @@
 //! 主机连接管理
 
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{Mutex, RwLock};
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 use virt::connect::Connect;
 
-use crate::{HostInfo, Result, TransportConfig, TransportError};
+use crate::capabilities::format_version;
+use crate::{
+    BlockIoStats, DomainInspection, DomainStatsSample, ErrorContext, HostCapabilities, HostCommandOutput, HostInfo, LibvirtDomainInfo, Result,
+    TransportConfig, TransportError,
+};
 
 /// 连接状态
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -283,6 +288,173 @@ impl HostConnection {
         Ok(domain)
     }
 
+    /// 列举该主机上的所有虚拟机 (包括未运行的)
+    pub async fn list_domains(&self) -> Result<Vec<LibvirtDomainInfo>> {
+        let state = *self.state.lock().await;
+        if state != ConnectionState::Connected {
+            return Err(TransportError::Disconnected);
+        }
+
+        let conn = self
+            .connection
+            .lock()
+            .await
+            .as_ref()
+            .ok_or(TransportError::Disconnected)?
+            .clone();
+
+        let domains = tokio::task::spawn_blocking(move || {
+            let domains = conn
+                .list_all_domains(0)
+                .map_err(|e| TransportError::LibvirtError(format!("列举虚拟机失败: {}", e)))?;
+
+            domains
+                .iter()
+                .map(|domain| {
+                    let info = domain
+                        .get_info()
+                        .map_err(|e| TransportError::LibvirtError(format!("获取虚拟机信息失败: {}", e)))?;
+                    Ok(LibvirtDomainInfo {
+                        name: domain
+                            .get_name()
+                            .map_err(|e| TransportError::LibvirtError(format!("获取虚拟机名称失败: {}", e)))?,
+                        uuid: domain.get_uuid_string().unwrap_or_default(),
+                        state: info.state,
+                        vcpus: info.nr_virt_cpu,
+                        memory_kb: info.memory,
+                    })
+                })
+                .collect::<Result<Vec<_>>>()
+        })
+        .await
+        .map_err(|e| TransportError::ConnectionFailed(format!("任务执行失败: {}", e)))?
+        .map_err(|e| e.with_context(ErrorContext::new().with_host(&self.host_info.id)))?;
+
+        self.metrics.increment_request().await;
+        *self.last_active.lock().await = chrono::Utc::now();
+
+        Ok(domains)
+    }
+
+    /// 采样虚拟机的 CPU 时间、内存与磁盘 IO 统计
+    ///
+    /// 内存统计需要来宾安装 balloon 驱动, 读取失败时相应字段为 None。
+    /// 磁盘 IO 为 XML 中所有 `device='disk'` 磁盘的计数之和, 光驱与软驱不计入。
+    pub async fn sample_domain_stats(&self, domain_name: &str) -> Result<DomainStatsSample> {
+        let domain = self.get_domain(domain_name).await?;
+        let name = domain_name.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let info = domain
+                .get_info()
+                .map_err(|e| TransportError::LibvirtError(format!("获取虚拟机信息失败: {}", e)))?;
+
+            let sample = DomainStatsSample::new(&name, info.cpu_time, info.nr_virt_cpu, info.memory);
+            let sample = match domain.memory_stats(0) {
+                Ok(stats) => sample.with_memory_stats(stats.into_iter().map(|stat| (stat.tag, stat.val))),
+                Err(e) => {
+                    debug!("获取虚拟机 {} 内存统计失败: {}", name, e);
+                    sample
+                }
+            };
+
+            let disks = match domain.get_xml_desc(0).map_err(|e| e.to_string()).and_then(|xml| {
+                DomainInspection::parse(&xml).map_err(|e| e.to_string())
+            }) {
+                Ok(inspection) => inspection.disks,
+                Err(e) => {
+                    debug!("解析虚拟机 {} 磁盘列表失败: {}", name, e);
+                    Vec::new()
+                }
+            };
+            let block_stats = disks
+                .iter()
+                .filter(|disk| disk.device == "disk")
+                .filter_map(|disk| match domain.get_block_stats(&disk.target) {
+                    Ok(stats) => Some(BlockIoStats::from_libvirt(stats.rd_req, stats.rd_bytes, stats.wr_req, stats.wr_bytes)),
+                    Err(e) => {
+                        debug!("获取虚拟机 {} 磁盘 {} IO 统计失败: {}", name, disk.target, e);
+                        None
+                    }
+                });
+
+            Ok(sample.with_block_stats(block_stats))
+        })
+        .await
+        .map_err(|e| TransportError::ConnectionFailed(format!("任务执行失败: {}", e)))?
+        .map_err(|e: TransportError| {
+            e.with_context(ErrorContext::new().with_host(&self.host_info.id).with_domain(domain_name))
+        })
+    }
+
+    /// 解析虚拟机 XML 中的 QMP/QGA 套接字、图形、磁盘与通道 (见 [`crate::domain_xml`])
+    pub async fn inspect_domain(&self, domain_name: &str) -> Result<DomainInspection> {
+        let domain = self.get_domain(domain_name).await?;
+
+        tokio::task::spawn_blocking(move || {
+            let xml = domain
+                .get_xml_desc(0)
+                .map_err(|e| TransportError::LibvirtError(format!("获取虚拟机 XML 失败: {}", e)))?;
+            DomainInspection::parse(&xml)
+        })
+        .await
+        .map_err(|e| TransportError::ConnectionFailed(format!("任务执行失败: {}", e)))?
+        .map_err(|e: TransportError| {
+            e.with_context(ErrorContext::new().with_host(&self.host_info.id).with_domain(domain_name))
+        })
+    }
+
+    /// 探测主机的 libvirt 能力: 版本、hypervisor 类型、CPU 型号与最大 vCPU 数 (见 [`crate::capabilities`])
+    pub async fn capabilities(&self) -> Result<HostCapabilities> {
+        let state = *self.state.lock().await;
+        if state != ConnectionState::Connected {
+            return Err(TransportError::Disconnected);
+        }
+
+        let conn = self
+            .connection
+            .lock()
+            .await
+            .as_ref()
+            .ok_or(TransportError::Disconnected)?
+            .clone();
+
+        let capabilities = tokio::task::spawn_blocking(move || {
+            let xml = conn
+                .get_capabilities()
+                .map_err(|e| TransportError::LibvirtError(format!("获取主机 capabilities 失败: {}", e)))?;
+            let mut capabilities = HostCapabilities::parse(&xml)?;
+
+            capabilities.libvirt_version = conn
+                .get_lib_version()
+                .map(format_version)
+                .map_err(|e| TransportError::LibvirtError(format!("获取 libvirt 版本失败: {}", e)))?;
+            capabilities.hypervisor = conn
+                .get_type()
+                .map_err(|e| TransportError::LibvirtError(format!("获取 hypervisor 类型失败: {}", e)))?;
+            // 部分驱动 (如 test:///) 不提供 hypervisor 版本与最大 vCPU 数
+            capabilities.hypervisor_version = conn.get_hyp_version().ok().filter(|&v| v > 0).map(format_version);
+            capabilities.max_vcpus = conn
+                .get_max_vcpus(Some(&capabilities.vcpu_domain_type()))
+                .ok()
+                .filter(|&n| n > 0);
+            Ok(capabilities)
+        })
+        .await
+        .map_err(|e| TransportError::ConnectionFailed(format!("任务执行失败: {}", e)))?
+        .map_err(|e: TransportError| e.with_context(ErrorContext::new().with_host(&self.host_info.id)))?;
+
+        self.metrics.increment_request().await;
+        *self.last_active.lock().await = chrono::Utc::now();
+
+        Ok(capabilities)
+    }
+
+    /// 通过 SSH 在该主机上执行白名单内的命令 (见 [`crate::host_command`])
+    pub async fn exec_host_command(&self, argv: &[String], timeout: Duration) -> Result<HostCommandOutput> {
+        crate::host_command::exec_host_command(&self.host_info, argv, timeout).await
+    }
+
     /// 获取监控指标
     pub fn metrics(&self) -> Arc<ConnectionMetrics> {
         Arc::clone(&self.metrics)
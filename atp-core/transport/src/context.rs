@@ -0,0 +1,107 @@
+//! 错误上下文
+//!
+//! 批量执行时仅凭错误消息无法判断是哪台主机、哪台虚拟机出错,
+//! 错误在冒泡过程中通过 `with_context()` 附加主机、虚拟机与操作信息。
+
+use std::fmt;
+
+/// 错误发生时的主机、虚拟机与操作
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// 主机 ID
+    pub host_id: Option<String>,
+
+    /// 虚拟机名称
+    pub domain: Option<String>,
+
+    /// 出错的操作 (如 QGA 命令名)
+    pub operation: Option<String>,
+}
+
+impl ErrorContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_host(mut self, host_id: impl Into<String>) -> Self {
+        self.host_id = Some(host_id.into());
+        self
+    }
+
+    pub fn with_domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    pub fn with_operation(mut self, operation: impl Into<String>) -> Self {
+        self.operation = Some(operation.into());
+        self
+    }
+
+    /// 是否没有任何上下文信息
+    pub fn is_empty(&self) -> bool {
+        self.host_id.is_none() && self.domain.is_none() && self.operation.is_none()
+    }
+
+    /// 用外层的上下文补全缺失的字段 (已有字段保持不变)
+    pub fn merge(&mut self, outer: ErrorContext) {
+        if self.host_id.is_none() {
+            self.host_id = outer.host_id;
+        }
+        if self.domain.is_none() {
+            self.domain = outer.domain;
+        }
+        if self.operation.is_none() {
+            self.operation = outer.operation;
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    /// 格式: `[host=..., vm=..., op=...]`, 只输出已设置的字段
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<String> = [
+            ("host", &self.host_id),
+            ("vm", &self.domain),
+            ("op", &self.operation),
+        ]
+        .iter()
+        .filter_map(|(key, value)| value.as_ref().map(|value| format!("{}={}", key, value)))
+        .collect();
+
+        write!(f, "[{}]", fields.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TransportError;
+
+    #[test]
+    fn test_context_display() {
+        let context = ErrorContext::new().with_host("host1").with_domain("win10");
+        assert_eq!(context.to_string(), "[host=host1, vm=win10]");
+
+        let context = ErrorContext::new().with_domain("win10").with_operation("guest-exec");
+        assert_eq!(context.to_string(), "[vm=win10, op=guest-exec]");
+        assert!(ErrorContext::new().is_empty());
+    }
+
+    #[test]
+    fn test_with_context_merges_instead_of_nesting() {
+        let err = TransportError::Timeout
+            .with_context(ErrorContext::new().with_domain("win10"))
+            .with_context(ErrorContext::new().with_host("host1").with_domain("outer"));
+
+        // 内层已有的虚拟机名称不会被外层覆盖
+        assert_eq!(err.to_string(), "[host=host1, vm=win10] 连接超时");
+        assert_eq!(err.context().unwrap().host_id.as_deref(), Some("host1"));
+        assert!(matches!(err.root(), TransportError::Timeout));
+
+        // 空上下文不包装错误
+        let err = TransportError::Disconnected.with_context(ErrorContext::new());
+        assert!(err.context().is_none());
+        assert_eq!(err.to_string(), "连接已断开");
+    }
+}
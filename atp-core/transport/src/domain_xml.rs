@@ -0,0 +1,504 @@
+//! 虚拟机 XML 解析
+//!
+//! 从 libvirt 的 domain XML (`virsh dumpxml` 或 `virDomainGetXMLDesc`) 中提取 QMP/QGA 套接字、
+//! 图形 (SPICE/VNC)、磁盘与 virtio-serial 通道, 供协议层连接与 CLI 查询共用。
+
+use std::path::PathBuf;
+
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use serde::{Deserialize, Serialize};
+
+use crate::{Result, TransportError};
+
+/// QEMU Guest Agent 通道名称
+pub const QGA_CHANNEL_NAME: &str = "org.qemu.guest_agent.0";
+
+/// 虚拟机 XML 中与测试相关的配置
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainInspection {
+    /// 虚拟机名称
+    pub name: Option<String>,
+
+    /// 虚拟机 UUID
+    pub uuid: Option<String>,
+
+    /// 是否使用 UEFI 固件
+    pub uefi: bool,
+
+    /// QMP 监控套接字 (`<qemu:commandline>` 中的 `-qmp unix:...`, 或运行态 XML 中的 `<monitor path=...>`)
+    pub qmp_socket: Option<PathBuf>,
+
+    /// 图形设备
+    pub graphics: Vec<DomainGraphics>,
+
+    /// 磁盘与光驱
+    pub disks: Vec<DomainDiskInfo>,
+
+    /// 字符设备通道 (virtio-serial、spicevmc 等)
+    pub channels: Vec<DomainChannel>,
+}
+
+/// 图形设备 (`<graphics>`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainGraphics {
+    /// 类型 (spice、vnc 等)
+    pub graphics_type: String,
+
+    /// 端口 (自动分配且虚拟机未运行时为 None)
+    pub port: Option<u16>,
+
+    /// TLS 端口
+    pub tls_port: Option<u16>,
+
+    /// 监听地址 (`listen` 属性或 `<listen address=...>`)
+    pub listen: Option<String>,
+
+    /// 访问密码
+    pub password: Option<String>,
+}
+
+/// 磁盘 (`<disk>`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainDiskInfo {
+    /// 设备类型 (disk、cdrom、floppy)
+    pub device: String,
+
+    /// 目标设备名 (vda、sda 等)
+    pub target: String,
+
+    /// 总线 (virtio、sata、scsi 等)
+    pub bus: Option<String>,
+
+    /// 源 (文件路径、块设备、存储卷名或网络磁盘名), 空光驱为 None
+    pub source: Option<String>,
+
+    /// 驱动格式 (qcow2、raw 等)
+    pub driver_type: Option<String>,
+}
+
+/// 字符设备通道 (`<channel>`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainChannel {
+    /// 通道类型 (unix、spicevmc、pty 等)
+    pub channel_type: String,
+
+    /// 目标类型 (virtio、guestfwd 等)
+    pub target_type: Option<String>,
+
+    /// 目标名称 (如 `org.qemu.guest_agent.0`)
+    pub name: Option<String>,
+
+    /// 宿主机上的套接字路径 (unix 通道)
+    pub path: Option<PathBuf>,
+
+    /// Guest 端连接状态 (connected、disconnected)
+    pub state: Option<String>,
+}
+
+impl DomainInspection {
+    /// 解析 domain XML
+    pub fn parse(xml: &str) -> Result<Self> {
+        let mut reader = Reader::from_str(xml);
+        reader.trim_text(true);
+
+        let mut inspection = DomainInspection::default();
+        // 当前元素路径 (不含命名空间前缀的本地名)
+        let mut path: Vec<String> = Vec::new();
+        let mut qemu_args: Vec<String> = Vec::new();
+        let mut has_root = false;
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| xml_error(format!("位置 {}: {}", reader.buffer_position(), e)))?;
+
+            match event {
+                Event::Start(e) => {
+                    has_root = true;
+                    let name = local_name(&e);
+                    inspection.on_element(&path, &name, &e, &mut qemu_args)?;
+                    path.push(name);
+                }
+                Event::Empty(e) => {
+                    has_root = true;
+                    let name = local_name(&e);
+                    inspection.on_element(&path, &name, &e, &mut qemu_args)?;
+                }
+                Event::Text(text) => {
+                    let text = text.unescape().map_err(|e| xml_error(e.to_string()))?;
+                    inspection.on_text(&path, &text);
+                }
+                Event::End(_) => {
+                    path.pop();
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+
+        if !has_root {
+            return Err(xml_error("没有根元素".to_string()));
+        }
+
+        if inspection.qmp_socket.is_none() {
+            inspection.qmp_socket = qmp_socket_from_args(&qemu_args);
+        }
+        Ok(inspection)
+    }
+
+    /// 第一个指定类型的图形设备 (如 `spice`)
+    pub fn graphics(&self, graphics_type: &str) -> Option<&DomainGraphics> {
+        self.graphics.iter().find(|g| g.graphics_type == graphics_type)
+    }
+
+    /// 指定名称的通道
+    pub fn channel(&self, name: &str) -> Option<&DomainChannel> {
+        self.channels.iter().find(|c| c.name.as_deref() == Some(name))
+    }
+
+    /// QEMU Guest Agent 通道的套接字路径
+    pub fn qga_channel(&self) -> Option<&PathBuf> {
+        self.channel(QGA_CHANNEL_NAME).and_then(|c| c.path.as_ref())
+    }
+
+    /// 指定目标设备名的磁盘
+    pub fn disk(&self, target: &str) -> Option<&DomainDiskInfo> {
+        self.disks.iter().find(|d| d.target == target)
+    }
+
+    fn on_element(&mut self, path: &[String], name: &str, e: &BytesStart<'_>, qemu_args: &mut Vec<String>) -> Result<()> {
+        let parent = path.last().map(String::as_str);
+        let attr = |key: &str| attribute(e, key);
+
+        match (parent, name) {
+            (Some("devices"), "graphics") => self.graphics.push(DomainGraphics {
+                graphics_type: attr("type")?.unwrap_or_default(),
+                port: port(attr("port")?),
+                tls_port: port(attr("tlsPort")?),
+                listen: attr("listen")?.filter(|l| !l.is_empty()),
+                password: attr("passwd")?,
+            }),
+            (Some("graphics"), "listen") => {
+                if let Some(graphics) = self.graphics.last_mut() {
+                    if graphics.listen.is_none() {
+                        graphics.listen = attr("address")?;
+                    }
+                }
+            }
+            (Some("devices"), "disk") => self.disks.push(DomainDiskInfo {
+                device: attr("device")?.unwrap_or_else(|| "disk".to_string()),
+                ..DomainDiskInfo::default()
+            }),
+            (Some("disk"), "target") => {
+                if let Some(disk) = self.disks.last_mut() {
+                    disk.target = attr("dev")?.unwrap_or_default();
+                    disk.bus = attr("bus")?;
+                }
+            }
+            (Some("disk"), "source") => {
+                if let Some(disk) = self.disks.last_mut() {
+                    let mut source = None;
+                    for key in ["file", "dev", "volume", "name"] {
+                        if let Some(value) = attr(key)? {
+                            source = Some(value);
+                            break;
+                        }
+                    }
+                    disk.source = source;
+                }
+            }
+            (Some("disk"), "driver") => {
+                if let Some(disk) = self.disks.last_mut() {
+                    disk.driver_type = attr("type")?;
+                }
+            }
+            (Some("devices"), "channel") => self.channels.push(DomainChannel {
+                channel_type: attr("type")?.unwrap_or_default(),
+                ..DomainChannel::default()
+            }),
+            (Some("channel"), "source") => {
+                if let Some(channel) = self.channels.last_mut() {
+                    channel.path = attr("path")?.map(PathBuf::from);
+                }
+            }
+            (Some("channel"), "target") => {
+                if let Some(channel) = self.channels.last_mut() {
+                    channel.target_type = attr("type")?;
+                    channel.name = attr("name")?;
+                    channel.state = attr("state")?;
+                }
+            }
+            (Some("os"), "loader") => self.uefi |= attr("type")?.as_deref() == Some("pflash"),
+            (Some("domain"), "os") => self.uefi |= attr("firmware")?.as_deref() == Some("efi"),
+            // 运行态 XML (/run/libvirt/qemu/<name>.xml) 中 libvirt 自身的监控套接字
+            (Some("domstatus"), "monitor") => self.qmp_socket = attr("path")?.map(PathBuf::from),
+            (Some("commandline"), "arg") => {
+                if let Some(value) = attr("value")? {
+                    qemu_args.push(value);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn on_text(&mut self, path: &[String], text: &str) {
+        let element = path.last().map(String::as_str);
+        let parent = path.len().checked_sub(2).map(|i| path[i].as_str());
+
+        match (parent, element) {
+            (Some("domain"), Some("name")) => self.name = Some(text.to_string()),
+            (Some("domain"), Some("uuid")) => self.uuid = Some(text.to_string()),
+            (Some("os"), Some("loader")) if text.contains("OVMF") || text.contains("AAVMF") => self.uefi = true,
+            _ => {}
+        }
+    }
+}
+
+/// 从 `<qemu:commandline>` 参数中找出 `-qmp unix:PATH,...` 或 `-qmp=unix:PATH`
+fn qmp_socket_from_args(args: &[String]) -> Option<PathBuf> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = match arg.as_str() {
+            "-qmp" => iter.next().map(String::as_str),
+            other => other.strip_prefix("-qmp="),
+        };
+        if let Some(path) = value.and_then(|v| v.strip_prefix("unix:")) {
+            let path = path.split(',').next().unwrap_or_default();
+            if !path.is_empty() {
+                return Some(PathBuf::from(path));
+            }
+        }
+    }
+    None
+}
+
+/// 去掉命名空间前缀的元素名 (`qemu:arg` -> `arg`)
+fn local_name(e: &BytesStart<'_>) -> String {
+    String::from_utf8_lossy(e.local_name().as_ref()).into_owned()
+}
+
+fn attribute(e: &BytesStart<'_>, key: &str) -> Result<Option<String>> {
+    let attr = e
+        .try_get_attribute(key)
+        .map_err(|err| xml_error(err.to_string()))?;
+    attr.map(|a| a.unescape_value().map(|v| v.into_owned()))
+        .transpose()
+        .map_err(|err| xml_error(err.to_string()))
+}
+
+/// 端口值, `-1` (自动分配尚未生效) 与 0 视为未配置
+fn port(value: Option<String>) -> Option<u16> {
+    value
+        .and_then(|v| v.parse::<i32>().ok())
+        .filter(|&p| p > 0)
+        .and_then(|p| u16::try_from(p).ok())
+}
+
+fn xml_error(message: String) -> TransportError {
+    TransportError::LibvirtError(format!("解析虚拟机 XML 失败: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// UEFI 虚拟机, SPICE + VNC 双图形, QGA 与自定义 virtio-serial 通道, 额外的 QMP 套接字
+    const UEFI_XML: &str = r#"
+<domain type='kvm' id='7' xmlns:qemu='http://libvirt.org/schemas/domain/qemu/1.0'>
+  <name>win10-uefi</name>
+  <uuid>0b6c3f1e-4d59-4c26-9e7c-2f3a6b1d8e90</uuid>
+  <os>
+    <type arch='x86_64' machine='pc-q35-6.2'>hvm</type>
+    <loader readonly='yes' type='pflash'>/usr/share/OVMF/OVMF_CODE.fd</loader>
+    <nvram>/var/lib/libvirt/qemu/nvram/win10-uefi_VARS.fd</nvram>
+  </os>
+  <devices>
+    <disk type='file' device='disk'>
+      <driver name='qemu' type='qcow2' cache='none'/>
+      <source file='/var/lib/libvirt/images/win10-uefi.qcow2' index='1'/>
+      <target dev='vda' bus='virtio'/>
+    </disk>
+    <channel type='unix'>
+      <source mode='bind' path='/var/lib/libvirt/qemu/channel/target/domain-7-win10-uefi/org.qemu.guest_agent.0'/>
+      <target type='virtio' name='org.qemu.guest_agent.0' state='connected'/>
+      <address type='virtio-serial' controller='0' bus='0' port='1'/>
+    </channel>
+    <channel type='spicevmc'>
+      <target type='virtio' name='com.redhat.spice.0' state='disconnected'/>
+    </channel>
+    <channel type='unix'>
+      <source mode='bind' path='/var/lib/libvirt/qemu/channel/target/test-uuid'/>
+      <target type='virtio' name='com.vmagent.sock'/>
+    </channel>
+    <graphics type='spice' port='5901' tlsPort='5902' autoport='yes' listen='0.0.0.0' passwd='s&amp;cret'>
+      <listen type='address' address='0.0.0.0'/>
+    </graphics>
+    <graphics type='vnc' port='5903' autoport='yes'>
+      <listen type='address' address='192.168.10.5'/>
+    </graphics>
+  </devices>
+  <qemu:commandline>
+    <qemu:arg value='-qmp'/>
+    <qemu:arg value='unix:/var/run/atp/win10-uefi.qmp,server,nowait'/>
+  </qemu:commandline>
+</domain>
+"#;
+
+    /// 多磁盘 BIOS 虚拟机 (块设备、存储卷、GlusterFS 网络磁盘、空光驱), 未运行 (端口 -1)
+    const MULTI_DISK_XML: &str = r#"
+<domain type='kvm'>
+  <name>db-server</name>
+  <uuid>5a1d0c2e-8f3b-4e7a-b9c1-6d2e4f8a0b13</uuid>
+  <os>
+    <type arch='x86_64' machine='pc-i440fx-6.2'>hvm</type>
+    <boot dev='hd'/>
+  </os>
+  <devices>
+    <disk type='block' device='disk'>
+      <driver name='qemu' type='raw'/>
+      <source dev='/dev/vg0/db-root'/>
+      <target dev='vda' bus='virtio'/>
+    </disk>
+    <disk type='volume' device='disk'>
+      <driver name='qemu' type='qcow2'/>
+      <source pool='default' volume='db-data.qcow2'/>
+      <target dev='vdb' bus='virtio'/>
+    </disk>
+    <disk type='network' device='disk'>
+      <driver name='qemu' type='qcow2'/>
+      <source protocol='gluster' name='gv0/db-log.qcow2'>
+        <host name='10.0.0.11' port='24007'/>
+      </source>
+      <target dev='sda' bus='scsi'/>
+    </disk>
+    <disk type='file' device='cdrom'>
+      <driver name='qemu' type='raw'/>
+      <target dev='hdc' bus='ide'/>
+      <readonly/>
+    </disk>
+    <graphics type='vnc' port='-1' autoport='yes' listen='127.0.0.1'/>
+  </devices>
+</domain>
+"#;
+
+    /// libvirt 运行态 XML (domstatus 包裹 domain), 监控套接字在 <monitor> 中
+    const STATUS_XML: &str = r#"
+<domstatus state='running' reason='booted' pid='12345'>
+  <monitor path='/var/lib/libvirt/qemu/domain-3-linux-spice/monitor.sock' type='unix'/>
+  <domain type='kvm' id='3'>
+    <name>linux-spice</name>
+    <uuid>9f8e7d6c-5b4a-3928-1706-f5e4d3c2b1a0</uuid>
+    <os firmware='efi'>
+      <type arch='x86_64' machine='pc-q35-7.2'>hvm</type>
+    </os>
+    <devices>
+      <graphics type="spice" port="5910" listen="10.0.0.21"/>
+    </devices>
+  </domain>
+</domstatus>
+"#;
+
+    #[test]
+    fn test_inspect_uefi_domain() {
+        let inspection = DomainInspection::parse(UEFI_XML).unwrap();
+
+        assert_eq!(inspection.name.as_deref(), Some("win10-uefi"));
+        assert_eq!(inspection.uuid.as_deref(), Some("0b6c3f1e-4d59-4c26-9e7c-2f3a6b1d8e90"));
+        assert!(inspection.uefi);
+        assert_eq!(inspection.qmp_socket, Some(PathBuf::from("/var/run/atp/win10-uefi.qmp")));
+        assert_eq!(
+            inspection.qga_channel(),
+            Some(&PathBuf::from("/var/lib/libvirt/qemu/channel/target/domain-7-win10-uefi/org.qemu.guest_agent.0"))
+        );
+
+        let spice = inspection.graphics("spice").unwrap();
+        assert_eq!(spice.port, Some(5901));
+        assert_eq!(spice.tls_port, Some(5902));
+        assert_eq!(spice.listen.as_deref(), Some("0.0.0.0"));
+        assert_eq!(spice.password.as_deref(), Some("s&cret"));
+
+        // listen 属性缺失时使用嵌套的 <listen address=...>
+        let vnc = inspection.graphics("vnc").unwrap();
+        assert_eq!(vnc.port, Some(5903));
+        assert_eq!(vnc.listen.as_deref(), Some("192.168.10.5"));
+
+        assert_eq!(inspection.channels.len(), 3);
+        let spicevmc = inspection.channel("com.redhat.spice.0").unwrap();
+        assert_eq!(spicevmc.channel_type, "spicevmc");
+        assert_eq!(spicevmc.path, None);
+        assert_eq!(spicevmc.state.as_deref(), Some("disconnected"));
+        assert_eq!(
+            inspection.channel("com.vmagent.sock").unwrap().path,
+            Some(PathBuf::from("/var/lib/libvirt/qemu/channel/target/test-uuid"))
+        );
+
+        let disk = inspection.disk("vda").unwrap();
+        assert_eq!(disk.source.as_deref(), Some("/var/lib/libvirt/images/win10-uefi.qcow2"));
+        assert_eq!(disk.driver_type.as_deref(), Some("qcow2"));
+        assert_eq!(disk.bus.as_deref(), Some("virtio"));
+    }
+
+    #[test]
+    fn test_inspect_multi_disk_domain() {
+        let inspection = DomainInspection::parse(MULTI_DISK_XML).unwrap();
+
+        assert!(!inspection.uefi);
+        assert_eq!(inspection.qmp_socket, None);
+        assert_eq!(inspection.qga_channel(), None);
+
+        let disks: Vec<_> = inspection
+            .disks
+            .iter()
+            .map(|d| (d.device.as_str(), d.target.as_str(), d.source.as_deref(), d.driver_type.as_deref()))
+            .collect();
+        assert_eq!(
+            disks,
+            vec![
+                ("disk", "vda", Some("/dev/vg0/db-root"), Some("raw")),
+                ("disk", "vdb", Some("db-data.qcow2"), Some("qcow2")),
+                ("disk", "sda", Some("gv0/db-log.qcow2"), Some("qcow2")),
+                ("cdrom", "hdc", None, Some("raw")),
+            ]
+        );
+
+        // 未运行时自动分配的端口为 -1
+        let vnc = inspection.graphics("vnc").unwrap();
+        assert_eq!(vnc.port, None);
+        assert_eq!(vnc.listen.as_deref(), Some("127.0.0.1"));
+        assert!(inspection.graphics("spice").is_none());
+    }
+
+    #[test]
+    fn test_inspect_status_xml() {
+        let inspection = DomainInspection::parse(STATUS_XML).unwrap();
+
+        assert_eq!(inspection.name.as_deref(), Some("linux-spice"));
+        assert!(inspection.uefi);
+        assert_eq!(
+            inspection.qmp_socket,
+            Some(PathBuf::from("/var/lib/libvirt/qemu/domain-3-linux-spice/monitor.sock"))
+        );
+        assert_eq!(inspection.graphics("spice").unwrap().port, Some(5910));
+    }
+
+    #[test]
+    fn test_inspect_invalid_xml() {
+        let err = DomainInspection::parse("<domain><devices></domain>").unwrap_err();
+        assert!(err.to_string().contains("解析虚拟机 XML 失败"), "{}", err);
+        assert!(DomainInspection::parse("").is_err());
+    }
+
+    #[test]
+    fn test_qmp_socket_from_args() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            qmp_socket_from_args(&args(&["-device", "x", "-qmp=unix:/tmp/a.sock,server=on"])),
+            Some(PathBuf::from("/tmp/a.sock"))
+        );
+        assert_eq!(qmp_socket_from_args(&args(&["-qmp", "tcp:127.0.0.1:4444,server"])), None);
+        assert_eq!(qmp_socket_from_args(&args(&["-qmp"])), None);
+    }
+}
@@ -0,0 +1,861 @@
+//! GlusterFS 卷状态查询
+//!
+//! 通过宿主机命令执行 gluster CLI 并解析其文本输出: 副本卷的自愈状态 (`volume heal <vol> info`)、
+//! 脑裂条目 (`volume heal <vol> info split-brain`)、brick 容量 (`volume status <vol> detail`),
+//! 以及 FUSE 挂载点上的文件位于哪个卷、哪些 brick。
+//! gluster 各版本的输出格式略有差异 (行尾空格、不可达 brick 的条目数为 `-`、条目后缀等),
+//! 解析时逐行识别关键字, 不依赖固定的行数与空行。
+//!
+//! gluster 命令需要 root 权限, 以普通用户登录的主机需要在 SSH 配置中开启 sudo。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{HostCommandOutput, Result, TransportError, TransportManager};
+
+/// gluster 命令的超时时间 (条目较多时 heal info 需要遍历 brick 上的索引, 耗时较长)
+const GLUSTER_COMMAND_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// GlusterFS FUSE 挂载的文件系统类型
+const GLUSTER_FUSE_TYPE: &str = "fuse.glusterfs";
+
+/// 记录文件所在 brick 的扩展属性
+const PATHINFO_XATTR: &str = "trusted.glusterfs.pathinfo";
+
+/// 单次 getfattr 调用最多查询的文件数 (避免远端命令行过长)
+const GETFATTR_BATCH_SIZE: usize = 256;
+
+/// 待自愈条目
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealEntry {
+    /// 卷内路径 (如 `/images/win10.qcow2`), brick 上找不到路径时为 `<gfid:...>`
+    pub path: String,
+
+    /// 条目处于脑裂
+    pub split_brain: bool,
+
+    /// 条目可能正在自愈
+    pub healing: bool,
+}
+
+impl HealEntry {
+    /// 条目只有 GFID 时返回 GFID
+    pub fn gfid(&self) -> Option<&str> {
+        self.path.strip_prefix("<gfid:")?.strip_suffix('>')
+    }
+}
+
+/// 单个 brick 的自愈状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrickHealInfo {
+    /// brick 地址 (`host:/path`)
+    pub brick: String,
+
+    /// 连接状态 (`Connected` 或错误描述)
+    pub status: String,
+
+    /// 列出的待自愈条目
+    pub entries: Vec<HealEntry>,
+
+    /// 输出中报告的条目数 (brick 不可达时为 None)
+    pub pending: Option<u64>,
+}
+
+impl BrickHealInfo {
+    /// brick 进程是否在线
+    pub fn is_connected(&self) -> bool {
+        self.status.eq_ignore_ascii_case("connected")
+    }
+
+    /// 待自愈条目数 (输出中没有条目数时按列出的条目计算)
+    pub fn pending_entries(&self) -> u64 {
+        self.pending.unwrap_or(self.entries.len() as u64)
+    }
+}
+
+/// 卷的自愈状态
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealInfo {
+    /// 卷名
+    pub volume: String,
+
+    /// 各 brick 的状态, 顺序与 gluster 输出一致
+    pub bricks: Vec<BrickHealInfo>,
+}
+
+impl HealInfo {
+    /// 按地址查找 brick
+    pub fn brick(&self, brick: &str) -> Option<&BrickHealInfo> {
+        self.bricks.iter().find(|info| info.brick == brick)
+    }
+
+    /// 所有 brick 的待自愈条目总数
+    pub fn pending_entries(&self) -> u64 {
+        self.bricks.iter().map(BrickHealInfo::pending_entries).sum()
+    }
+
+    /// 所有 brick 在线且没有待自愈条目
+    pub fn is_healthy(&self) -> bool {
+        self.bricks.iter().all(|brick| brick.is_connected() && brick.pending_entries() == 0)
+    }
+}
+
+/// 脑裂条目
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SplitBrainEntry {
+    /// 报告该条目的 brick (`host:/path`)
+    pub brick: String,
+
+    /// 卷内路径或 `<gfid:...>`
+    pub path: String,
+}
+
+/// FUSE 挂载点上的文件在卷中的位置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GlusterFile {
+    /// 卷名
+    pub volume: String,
+
+    /// 卷内路径 (以 `/` 开头)
+    pub path: String,
+
+    /// 保存该文件的 brick (`host:/path`), 即文件所在副本组的全部 brick
+    pub bricks: Vec<String>,
+}
+
+/// brick 进程状态与所在文件系统的容量 (`gluster volume status <vol> detail`)
+///
+/// brick 离线时 gluster 不输出或以 `N/A` 输出端口、容量等字段, 对应字段为 None。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BrickStatus {
+    /// brick 地址 (`host:/path`)
+    pub brick: String,
+
+    /// brick 进程是否在线
+    pub online: bool,
+
+    /// TCP 端口
+    pub port: Option<u16>,
+
+    /// brick 进程 PID
+    pub pid: Option<u32>,
+
+    /// 文件系统类型 (如 xfs)
+    pub file_system: Option<String>,
+
+    /// 块设备
+    pub device: Option<String>,
+
+    /// 可用空间 (字节, gluster 输出保留一位小数, 为近似值)
+    pub free_bytes: Option<u64>,
+
+    /// 总空间 (字节)
+    pub total_bytes: Option<u64>,
+
+    /// inode 总数
+    pub inode_count: Option<u64>,
+
+    /// 可用 inode 数
+    pub free_inodes: Option<u64>,
+}
+
+impl BrickStatus {
+    fn new(brick: &str) -> Self {
+        Self {
+            brick: brick.to_string(),
+            online: false,
+            port: None,
+            pid: None,
+            file_system: None,
+            device: None,
+            free_bytes: None,
+            total_bytes: None,
+            inode_count: None,
+            free_inodes: None,
+        }
+    }
+
+    /// 空间使用率 (百分比)
+    pub fn used_percent(&self) -> Option<f64> {
+        used_percent(self.free_bytes?, self.total_bytes?)
+    }
+
+    /// inode 使用率 (百分比)
+    pub fn inode_used_percent(&self) -> Option<f64> {
+        used_percent(self.free_inodes?, self.inode_count?)
+    }
+}
+
+fn used_percent(free: u64, total: u64) -> Option<f64> {
+    (total > 0).then(|| total.saturating_sub(free) as f64 * 100.0 / total as f64)
+}
+
+/// 文件的位置及其各副本所在 brick 的容量
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GlusterFileUsage {
+    /// 文件所在的卷与 brick
+    pub file: GlusterFile,
+
+    /// 与 `file.bricks` 一一对应的 brick 状态 (status 输出中缺失的 brick 视为离线)
+    pub bricks: Vec<BrickStatus>,
+}
+
+impl GlusterFileUsage {
+    /// 从卷的 brick 状态中选出文件所在 brick 的状态
+    pub fn new(file: GlusterFile, statuses: &[BrickStatus]) -> Self {
+        let bricks = file
+            .bricks
+            .iter()
+            .map(|brick| {
+                statuses
+                    .iter()
+                    .find(|status| status.brick == *brick)
+                    .cloned()
+                    .unwrap_or_else(|| BrickStatus::new(brick))
+            })
+            .collect();
+        Self { file, bricks }
+    }
+}
+
+/// GlusterFS 客户端
+///
+/// gluster 命令在指定主机 (卷的服务端或挂载了卷的客户端) 上执行。
+pub struct GlusterClient {
+    manager: Arc<TransportManager>,
+    host_id: String,
+}
+
+impl GlusterClient {
+    pub fn new(manager: Arc<TransportManager>, host_id: &str) -> Self {
+        Self {
+            manager,
+            host_id: host_id.to_string(),
+        }
+    }
+
+    /// 查询卷的待自愈条目
+    pub async fn get_heal_info(&self, volume: &str) -> Result<HealInfo> {
+        let output = self.gluster(&["volume", "heal", volume, "info"]).await?;
+        parse_heal_info(volume, &output.stdout)
+    }
+
+    /// 查询卷中处于脑裂的条目
+    pub async fn detect_split_brain(&self, volume: &str) -> Result<Vec<SplitBrainEntry>> {
+        let output = self.gluster(&["volume", "heal", volume, "info", "split-brain"]).await?;
+        parse_split_brain(volume, &output.stdout)
+    }
+
+    /// 查询卷中各 brick 的进程状态与容量
+    pub async fn get_volume_status_detail(&self, volume: &str) -> Result<Vec<BrickStatus>> {
+        let output = self.gluster(&["volume", "status", volume, "detail"]).await?;
+        parse_volume_status_detail(volume, &output.stdout)
+    }
+
+    /// 查找文件所在的 brick 并查询这些 brick 的容量, 文件不在 GlusterFS 挂载点上时返回 None
+    pub async fn locate_and_measure(&self, path: &str) -> Result<Option<GlusterFileUsage>> {
+        let Some(file) = self.locate_file(path).await? else {
+            return Ok(None);
+        };
+        let statuses = self.get_volume_status_detail(&file.volume).await?;
+        Ok(Some(GlusterFileUsage::new(file, &statuses)))
+    }
+
+    /// 查找 FUSE 挂载点上的文件所在的卷与 brick, 文件不在 GlusterFS 挂载点上时返回 None
+    pub async fn locate_file(&self, path: &str) -> Result<Option<GlusterFile>> {
+        let mut locations = self.get_file_locations_batch(&[path.to_string()]).await?;
+        locations.remove(path).unwrap_or(Ok(None))
+    }
+
+    /// 批量查找文件所在的卷与 brick
+    ///
+    /// 所有文件由一次 getfattr 调用查询 (文件很多时按批拆分), 返回路径 -> 结果:
+    /// 不在 GlusterFS 挂载点上为 `Ok(None)`, 文件不存在、没有权限等错误只影响对应的文件。
+    /// 连接失败等整体错误直接返回 Err。
+    pub async fn get_file_locations_batch(
+        &self,
+        paths: &[String],
+    ) -> Result<HashMap<String, Result<Option<GlusterFile>>>> {
+        let mut locations = HashMap::new();
+        if paths.is_empty() {
+            return Ok(locations);
+        }
+
+        let mounts = self.exec(&["cat", "/proc/mounts"]).await?;
+        let mut on_gluster = Vec::new();
+        for path in paths {
+            match find_gluster_mount(&mounts.stdout, path) {
+                Some((volume, volume_path)) => on_gluster.push((path, volume, volume_path)),
+                None => {
+                    locations.insert(path.clone(), Ok(None));
+                }
+            }
+        }
+
+        for chunk in on_gluster.chunks(GETFATTR_BATCH_SIZE) {
+            let mut argv = vec!["getfattr", "-n", PATHINFO_XATTR, "-e", "text", "--absolute-names"];
+            argv.extend(chunk.iter().map(|(path, _, _)| path.as_str()));
+            let output = self.exec(&argv).await?;
+
+            // 错误写到标准错误, 与标准输出一起解析
+            let mut parsed = parse_pathinfo_batch(&format!("{}\n{}", output.stdout, output.stderr));
+            for (path, volume, volume_path) in chunk {
+                let location = match parsed.remove(path.as_str()) {
+                    Some(Ok(bricks)) if !bricks.is_empty() => Ok(Some(GlusterFile {
+                        volume: volume.clone(),
+                        path: volume_path.clone(),
+                        bricks,
+                    })),
+                    Some(Ok(_)) => Err(TransportError::GlusterError(format!(
+                        "无法解析 {} 的 {}",
+                        path, PATHINFO_XATTR
+                    ))),
+                    Some(Err(e)) => Err(e),
+                    None => Err(command_error("getfattr", &output)),
+                };
+                locations.insert((*path).clone(), location);
+            }
+        }
+        Ok(locations)
+    }
+
+    async fn gluster(&self, args: &[&str]) -> Result<HostCommandOutput> {
+        let mut argv = vec!["gluster"];
+        argv.extend_from_slice(args);
+        let output = self.exec(&argv).await?;
+        if !output.success() {
+            return Err(command_error("gluster", &output));
+        }
+        Ok(output)
+    }
+
+    async fn exec(&self, argv: &[&str]) -> Result<HostCommandOutput> {
+        let argv: Vec<String> = argv.iter().map(|arg| arg.to_string()).collect();
+        self.manager
+            .exec_host_command(&self.host_id, &argv, GLUSTER_COMMAND_TIMEOUT)
+            .await
+    }
+}
+
+/// 命令失败时的错误 (gluster 把部分错误写到标准输出)
+fn command_error(program: &str, output: &HostCommandOutput) -> TransportError {
+    let message = [output.stderr.trim(), output.stdout.trim()]
+        .into_iter()
+        .find(|text| !text.is_empty())
+        .unwrap_or("无输出");
+    TransportError::GlusterError(format!(
+        "{} 执行失败 (退出码: {}): {}",
+        program,
+        output.exit_code.map_or_else(|| "无".to_string(), |code| code.to_string()),
+        message
+    ))
+}
+
+/// 解析 `gluster volume heal <vol> info` 与 `... info split-brain` 的输出
+pub fn parse_heal_info(volume: &str, output: &str) -> Result<HealInfo> {
+    let mut bricks: Vec<BrickHealInfo> = Vec::new();
+
+    for line in output.lines().map(str::trim) {
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(brick) = line.strip_prefix("Brick ") {
+            bricks.push(BrickHealInfo {
+                brick: brick.trim().to_string(),
+                status: String::new(),
+                entries: Vec::new(),
+                pending: None,
+            });
+            continue;
+        }
+        // brick 之前的提示行 (如 "Launching heal operation ...") 忽略
+        let Some(current) = bricks.last_mut() else {
+            continue;
+        };
+
+        if let Some(status) = line.strip_prefix("Status:") {
+            current.status = status.trim().to_string();
+        } else if line.starts_with("Number of entries") || line.starts_with("Total Number of entries") {
+            // "Number of entries: 2"、"Number of entries in split-brain: 1", 不可达时为 "-"
+            let value = line.rsplit_once(':').map_or("", |(_, value)| value.trim());
+            current.pending = value.parse().ok();
+        } else {
+            current.entries.push(parse_heal_entry(line));
+        }
+    }
+
+    if bricks.is_empty() {
+        return Err(TransportError::GlusterError(format!(
+            "无法解析卷 {} 的 heal info 输出: {}",
+            volume,
+            output.trim()
+        )));
+    }
+
+    Ok(HealInfo {
+        volume: volume.to_string(),
+        bricks,
+    })
+}
+
+fn parse_heal_entry(line: &str) -> HealEntry {
+    let mut entry = HealEntry {
+        path: line.to_string(),
+        split_brain: false,
+        healing: false,
+    };
+    if let Some(path) = line.strip_suffix("- Is in split-brain") {
+        entry.path = path.trim_end().to_string();
+        entry.split_brain = true;
+    } else if let Some(path) = line.strip_suffix("- Possibly undergoing heal") {
+        entry.path = path.trim_end().to_string();
+        entry.healing = true;
+    }
+    entry
+}
+
+/// 解析 `gluster volume heal <vol> info split-brain` 的输出
+pub fn parse_split_brain(volume: &str, output: &str) -> Result<Vec<SplitBrainEntry>> {
+    let info = parse_heal_info(volume, output)?;
+    Ok(info
+        .bricks
+        .into_iter()
+        .flat_map(|brick| {
+            let name = brick.brick;
+            brick.entries.into_iter().map(move |entry| SplitBrainEntry {
+                brick: name.clone(),
+                path: entry.path,
+            })
+        })
+        .collect())
+}
+
+/// 解析 `gluster volume status <vol> detail` 的输出
+///
+/// 每个 brick 为一组 `键 : 值` 行, 以 `Brick : Brick host:/path` 开始。
+/// 值为 `N/A` 或无法解析的字段记为 None, 不影响其他 brick。
+pub fn parse_volume_status_detail(volume: &str, output: &str) -> Result<Vec<BrickStatus>> {
+    let mut bricks: Vec<BrickStatus> = Vec::new();
+
+    for line in output.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+
+        if key == "Brick" {
+            let brick = value.strip_prefix("Brick ").unwrap_or(value).trim();
+            bricks.push(BrickStatus::new(brick));
+            continue;
+        }
+        // "Status of volume: gv0" 等 brick 之前的行忽略
+        let Some(current) = bricks.last_mut() else {
+            continue;
+        };
+        let value = (!value.is_empty() && value != "N/A").then_some(value);
+
+        match key {
+            "Online" => current.online = value == Some("Y"),
+            "TCP Port" => current.port = value.and_then(|v| v.parse().ok()).filter(|port| *port > 0),
+            "Pid" => current.pid = value.and_then(|v| v.parse().ok()),
+            "File System" => current.file_system = value.map(str::to_string),
+            "Device" => current.device = value.map(str::to_string),
+            "Disk Space Free" => current.free_bytes = value.and_then(parse_size),
+            "Total Disk Space" => current.total_bytes = value.and_then(parse_size),
+            "Inode Count" => current.inode_count = value.and_then(|v| v.parse().ok()),
+            "Free Inodes" => current.free_inodes = value.and_then(|v| v.parse().ok()),
+            _ => {}
+        }
+    }
+
+    if bricks.is_empty() {
+        return Err(TransportError::GlusterError(format!(
+            "无法解析卷 {} 的 status detail 输出: {}",
+            volume,
+            output.trim()
+        )));
+    }
+    Ok(bricks)
+}
+
+/// 解析 gluster 的容量格式 (`90.5GB`、`512.0MB`、`0Bytes`, 按 1024 进位)
+fn parse_size(value: &str) -> Option<u64> {
+    const UNITS: &[(&str, u32)] = &[("Bytes", 0), ("KB", 1), ("MB", 2), ("GB", 3), ("TB", 4), ("PB", 5)];
+
+    let value = value.trim();
+    let (number, exponent) = UNITS
+        .iter()
+        .find_map(|(unit, exponent)| value.strip_suffix(unit).map(|number| (number.trim(), *exponent)))?;
+    let number: f64 = number.parse().ok()?;
+    (number >= 0.0).then(|| (number * 1024f64.powi(exponent as i32)).round() as u64)
+}
+
+/// 解析 `trusted.glusterfs.pathinfo`, 返回 (brick, brick 上的文件路径)
+///
+/// 属性值形如 `(<REPLICATE:vol-replicate-0> <POSIX(/bricks/b1):server1:/bricks/b1/images/a.qcow2> ...)`。
+pub fn parse_pathinfo(output: &str) -> Vec<(String, String)> {
+    let mut bricks = Vec::new();
+    let mut rest = output;
+
+    while let Some(start) = rest.find("<POSIX(") {
+        rest = &rest[start + "<POSIX(".len()..];
+        let Some((root, tail)) = rest.split_once("):") else {
+            break;
+        };
+        let Some(end) = tail.find('>') else {
+            break;
+        };
+        let location = &tail[..end];
+        rest = &tail[end..];
+
+        if let Some((host, path)) = location.split_once(':') {
+            let brick = format!("{}:{}", host, root);
+            if !bricks.iter().any(|(known, _)| *known == brick) {
+                bricks.push((brick, path.to_string()));
+            }
+        }
+    }
+    bricks
+}
+
+/// 解析批量 getfattr 的输出, 返回路径 -> 文件所在的 brick
+///
+/// 标准输出中每个文件为 `# file: <path>` 加属性行, 以空行分隔; 错误行可能穿插其间,
+/// 文件不存在时为 `getfattr: <path>: No such file or directory`,
+/// 缺少属性时为 `<path>: trusted.glusterfs.pathinfo: No such attribute`。
+pub fn parse_pathinfo_batch(output: &str) -> HashMap<String, Result<Vec<String>>> {
+    let mut results = HashMap::new();
+    let mut current: Option<String> = None;
+    let attribute = format!("{}=", PATHINFO_XATTR);
+    let attribute_error = format!(": {}: ", PATHINFO_XATTR);
+
+    for line in output.lines().map(str::trim_end) {
+        if line.is_empty() {
+            current = None;
+        } else if let Some(path) = line.strip_prefix("# file: ") {
+            current = Some(unescape_octal(path));
+        } else if let Some(value) = line.strip_prefix(attribute.as_str()) {
+            if let Some(path) = &current {
+                let bricks = parse_pathinfo(value).into_iter().map(|(brick, _)| brick).collect();
+                results.insert(path.clone(), Ok(bricks));
+            }
+        } else {
+            let error = line.strip_prefix("getfattr: ").unwrap_or(line);
+            let split = match error.find(attribute_error.as_str()) {
+                Some(index) => Some((&error[..index], &error[index + attribute_error.len()..])),
+                None => error.rsplit_once(": "),
+            };
+            if let Some((path, message)) = split {
+                let path = unescape_octal(path);
+                let err = getfattr_error(&path, message);
+                results.insert(path, Err(err));
+            }
+        }
+    }
+    results
+}
+
+fn getfattr_error(path: &str, message: &str) -> TransportError {
+    match message {
+        "No such file or directory" => TransportError::FileNotFound(path.to_string()),
+        "Permission denied" => TransportError::PermissionDenied(path.to_string()),
+        _ => TransportError::GlusterError(format!("{}: {}", path, message)),
+    }
+}
+
+/// 在 `/proc/mounts` 中查找包含 `path` 的 GlusterFS 挂载点, 返回 (卷名, 卷内路径)
+pub fn find_gluster_mount(mounts: &str, path: &str) -> Option<(String, String)> {
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (source, target, fs_type) = (fields.next()?, fields.next()?, fields.next()?);
+            if fs_type != GLUSTER_FUSE_TYPE {
+                return None;
+            }
+            let target = unescape_octal(target);
+            let relative = path.strip_prefix(target.trim_end_matches('/'))?;
+            if !relative.is_empty() && !relative.starts_with('/') {
+                return None;
+            }
+            // 挂载源为 server:/volume 或 server:volume
+            let volume = source.rsplit_once(':')?.1.trim_start_matches('/').to_string();
+            let relative = if relative.is_empty() { "/".to_string() } else { relative.to_string() };
+            Some((target.len(), volume, relative))
+        })
+        .max_by_key(|(len, _, _)| *len)
+        .map(|(_, volume, relative)| (volume, relative))
+}
+
+/// `/proc/mounts` 与 getfattr 输出的路径中空格等字符写作八进制转义 (如 `\040`)
+fn unescape_octal(path: &str) -> String {
+    let mut result = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            let digits: String = chars.clone().take(3).collect();
+            if let Ok(code) = u8::from_str_radix(&digits, 8) {
+                result.push(code as char);
+                chars.nth(2);
+                continue;
+            }
+        }
+        result.push(c);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// gluster 9.6 `volume heal gv0 info`, 第三个 brick 离线
+    const HEAL_INFO_V9: &str = "Brick gfs1:/data/brick1/gv0
+/images/win10.qcow2 \n<gfid:9a3b5c1e-2f4d-4e6a-8b7c-0d1e2f3a4b5c> \nStatus: Connected
+Number of entries: 2
+
+Brick gfs2:/data/brick1/gv0
+/images/win10.qcow2 \nStatus: Connected
+Number of entries: 1
+
+Brick gfs3:/data/brick1/gv0
+Status: Transport endpoint is not connected
+Number of entries: -
+
+";
+
+    /// gluster 10.3 `volume heal gv0 info`, 条目带状态后缀, 没有结尾空行
+    const HEAL_INFO_V10: &str = "Brick gfs1:/data/brick1/gv0\r
+/images/win10.qcow2 - Is in split-brain\r
+/images/ubuntu.qcow2 - Possibly undergoing heal\r
+Status: Connected\r
+Number of entries: 2\r
+\r
+Brick gfs2:/data/brick1/gv0\r
+/images/win10.qcow2 - Is in split-brain\r
+Status: Connected\r
+Number of entries: 1";
+
+    /// gluster 10.3 `volume heal gv0 info split-brain`
+    const SPLIT_BRAIN_V10: &str = "Brick gfs1:/data/brick1/gv0
+/images/win10.qcow2
+Status: Connected
+Number of entries in split-brain: 1
+
+Brick gfs2:/data/brick1/gv0
+/images/win10.qcow2
+Status: Connected
+Number of entries in split-brain: 1
+
+Brick gfs3:/data/brick1/gv0
+Status: Connected
+Number of entries in split-brain: 0
+";
+
+    #[test]
+    fn test_parse_heal_info_v9() {
+        let info = parse_heal_info("gv0", HEAL_INFO_V9).unwrap();
+        assert_eq!(info.bricks.len(), 3);
+
+        let first = &info.bricks[0];
+        assert_eq!(first.brick, "gfs1:/data/brick1/gv0");
+        assert!(first.is_connected());
+        assert_eq!(first.pending, Some(2));
+        assert_eq!(first.entries[0].path, "/images/win10.qcow2");
+        assert_eq!(first.entries[1].gfid(), Some("9a3b5c1e-2f4d-4e6a-8b7c-0d1e2f3a4b5c"));
+
+        let offline = info.brick("gfs3:/data/brick1/gv0").unwrap();
+        assert!(!offline.is_connected());
+        assert_eq!(offline.pending, None);
+        assert_eq!(info.pending_entries(), 3);
+        assert!(!info.is_healthy());
+    }
+
+    #[test]
+    fn test_parse_heal_info_v10() {
+        let info = parse_heal_info("gv0", HEAL_INFO_V10).unwrap();
+        assert_eq!(info.bricks.len(), 2);
+        assert_eq!(
+            info.bricks[0].entries,
+            vec![
+                HealEntry { path: "/images/win10.qcow2".to_string(), split_brain: true, healing: false },
+                HealEntry { path: "/images/ubuntu.qcow2".to_string(), split_brain: false, healing: true },
+            ]
+        );
+        assert_eq!(info.bricks[1].pending, Some(1));
+    }
+
+    #[test]
+    fn test_parse_heal_info_healthy_and_errors() {
+        let healthy = "Launching heal operation to perform index self heal on volume gv0 has been successful\n\n\
+            Brick gfs1:/data/brick1/gv0\nStatus: Connected\nNumber of entries: 0\n";
+        let info = parse_heal_info("gv0", healthy).unwrap();
+        assert!(info.is_healthy());
+        assert!(info.bricks[0].entries.is_empty());
+
+        let err = parse_heal_info("gv0", "Volume gv0 is not of type replicate/disperse\n").unwrap_err();
+        assert!(matches!(err, TransportError::GlusterError(_)));
+    }
+
+    #[test]
+    fn test_parse_split_brain() {
+        let entries = parse_split_brain("gv0", SPLIT_BRAIN_V10).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                SplitBrainEntry { brick: "gfs1:/data/brick1/gv0".to_string(), path: "/images/win10.qcow2".to_string() },
+                SplitBrainEntry { brick: "gfs2:/data/brick1/gv0".to_string(), path: "/images/win10.qcow2".to_string() },
+            ]
+        );
+    }
+
+    /// gluster 10.3 `volume status gv0 detail`, gfs2 离线 (部分字段缺失或为 N/A)
+    const STATUS_DETAIL_V10: &str = "Status of volume: gv0
+------------------------------------------------------------------------------
+Brick                : Brick gfs1:/data/brick1/gv0
+TCP Port             : 49152
+RDMA Port            : 0
+Online               : Y
+Pid                  : 2817
+File System          : xfs
+Device               : /dev/mapper/vg0-brick1
+Mount Options        : rw,relatime,attr2,inode64,logbufs=8,logbsize=32k,noquota
+Inode Size           : 512
+Disk Space Free      : 90.5GB
+Total Disk Space     : 100.0GB
+Inode Count          : 52428800
+Free Inodes          : 52427776
+------------------------------------------------------------------------------
+Brick                : Brick gfs2:/data/brick1/gv0
+TCP Port             : N/A
+RDMA Port            : N/A
+Online               : N
+Pid                  : N/A
+File System          : N/A
+Device               : N/A
+Mount Options        : N/A
+Inode Size           : N/A
+Disk Space Free      : N/A
+Total Disk Space     : N/A
+------------------------------------------------------------------------------
+Brick                : Brick gfs3:/data/brick1/gv0
+TCP Port             : 49153
+Online               : Y
+Pid                  : 3011
+Disk Space Free      : 0Bytes
+Total Disk Space     : 1.5TB
+";
+
+    #[test]
+    fn test_parse_volume_status_detail() {
+        let bricks = parse_volume_status_detail("gv0", STATUS_DETAIL_V10).unwrap();
+        assert_eq!(bricks.len(), 3);
+
+        let first = &bricks[0];
+        assert_eq!(first.brick, "gfs1:/data/brick1/gv0");
+        assert!(first.online);
+        assert_eq!((first.port, first.pid), (Some(49152), Some(2817)));
+        assert_eq!(first.file_system.as_deref(), Some("xfs"));
+        assert_eq!(first.total_bytes, Some(100 * 1024 * 1024 * 1024));
+        assert!((first.used_percent().unwrap() - 9.5).abs() < 0.01);
+        assert!(first.inode_used_percent().unwrap() < 0.01);
+
+        let offline = &bricks[1];
+        assert!(!offline.online);
+        assert_eq!((offline.port, offline.pid, offline.free_bytes), (None, None, None));
+        assert_eq!(offline.used_percent(), None);
+
+        // 字段缺失的 brick 同样保留
+        assert_eq!(bricks[2].free_bytes, Some(0));
+        assert_eq!(bricks[2].used_percent(), Some(100.0));
+        assert_eq!(bricks[2].inode_count, None);
+
+        assert!(parse_volume_status_detail("gv0", "Volume gv9 does not exist\n").is_err());
+    }
+
+    #[test]
+    fn test_measure_file() {
+        let statuses = parse_volume_status_detail("gv0", STATUS_DETAIL_V10).unwrap();
+        let file = GlusterFile {
+            volume: "gv0".to_string(),
+            path: "/images/win10.qcow2".to_string(),
+            bricks: vec!["gfs3:/data/brick1/gv0".to_string(), "gfs9:/data/brick1/gv0".to_string()],
+        };
+
+        let usage = GlusterFileUsage::new(file, &statuses);
+        assert_eq!(usage.bricks[0].brick, "gfs3:/data/brick1/gv0");
+        assert!(usage.bricks[0].online);
+        assert_eq!(usage.bricks[1].brick, "gfs9:/data/brick1/gv0");
+        assert!(!usage.bricks[1].online);
+    }
+
+    #[test]
+    fn test_parse_pathinfo() {
+        let output = "# file: /mnt/gv0/images/win10.qcow2\n\
+            trusted.glusterfs.pathinfo=\"(<DISTRIBUTE:gv0-dht> (<REPLICATE:gv0-replicate-1> \
+            <POSIX(/data/brick1/gv0):gfs4:/data/brick1/gv0/images/win10.qcow2> \
+            <POSIX(/data/brick1/gv0):gfs5:/data/brick1/gv0/images/win10.qcow2>))\"\n";
+        assert_eq!(
+            parse_pathinfo(output),
+            vec![
+                ("gfs4:/data/brick1/gv0".to_string(), "/data/brick1/gv0/images/win10.qcow2".to_string()),
+                ("gfs5:/data/brick1/gv0".to_string(), "/data/brick1/gv0/images/win10.qcow2".to_string()),
+            ]
+        );
+        assert!(parse_pathinfo("trusted.glusterfs.pathinfo: No such attribute").is_empty());
+    }
+
+    #[test]
+    fn test_parse_pathinfo_batch() {
+        // 标准输出与标准错误合并后错误行穿插在文件之间
+        let output = "# file: /mnt/gv0/images/a.qcow2
+getfattr: /mnt/gv0/images/missing.qcow2: No such file or directory
+trusted.glusterfs.pathinfo=\"(<REPLICATE:gv0-replicate-0> <POSIX(/data/b1):gfs1:/data/b1/images/a.qcow2> <POSIX(/data/b1):gfs2:/data/b1/images/a.qcow2>)\"
+
+/mnt/gv0/local: trusted.glusterfs.pathinfo: No such attribute
+# file: /mnt/gv0/images/win\\04010.qcow2
+trusted.glusterfs.pathinfo=\"(<REPLICATE:gv0-replicate-1> <POSIX(/data/b2):gfs3:/data/b2/images/win 10.qcow2>)\"
+
+getfattr: /mnt/gv0/secret/b.qcow2: Permission denied
+";
+        let results = parse_pathinfo_batch(output);
+        assert_eq!(results.len(), 5);
+
+        assert_eq!(
+            results["/mnt/gv0/images/a.qcow2"].as_ref().unwrap(),
+            &vec!["gfs1:/data/b1".to_string(), "gfs2:/data/b1".to_string()]
+        );
+        assert_eq!(results["/mnt/gv0/images/win 10.qcow2"].as_ref().unwrap(), &vec!["gfs3:/data/b2".to_string()]);
+        assert!(matches!(
+            results["/mnt/gv0/images/missing.qcow2"],
+            Err(TransportError::FileNotFound(ref path)) if path == "/mnt/gv0/images/missing.qcow2"
+        ));
+        assert!(matches!(results["/mnt/gv0/secret/b.qcow2"], Err(TransportError::PermissionDenied(_))));
+        assert!(matches!(
+            &results["/mnt/gv0/local"],
+            Err(TransportError::GlusterError(message)) if message.ends_with("No such attribute")
+        ));
+    }
+
+    #[test]
+    fn test_find_gluster_mount() {
+        let mounts = "/dev/sda1 / ext4 rw,relatime 0 0\n\
+            gfs1:/gv0 /mnt/gv0 fuse.glusterfs rw,relatime,user_id=0 0 0\n\
+            gfs1:gv1 /mnt/gv0/nested\\040dir fuse.glusterfs rw,relatime 0 0\n";
+
+        assert_eq!(
+            find_gluster_mount(mounts, "/mnt/gv0/images/win10.qcow2"),
+            Some(("gv0".to_string(), "/images/win10.qcow2".to_string()))
+        );
+        // 嵌套的挂载点取最长匹配
+        assert_eq!(
+            find_gluster_mount(mounts, "/mnt/gv0/nested dir/a.qcow2"),
+            Some(("gv1".to_string(), "/a.qcow2".to_string()))
+        );
+        assert_eq!(find_gluster_mount(mounts, "/mnt/gv01/a.qcow2"), None);
+        assert_eq!(find_gluster_mount(mounts, "/var/lib/libvirt/images/a.qcow2"), None);
+    }
+}
@@ -0,0 +1,737 @@
+//! 宿主机命令执行
+//!
+//! 个别操作 libvirt 绑定没有覆盖 (如 `virsh domifstat` 的部分参数), 需要直接在宿主机上执行命令。
+//! 命令通过系统的 `ssh` 客户端以主机配置的 SSH 凭据执行, 且只允许白名单内的程序,
+//! 所有参数经过 shell 转义后传给远端, 不能拼接额外的 shell 命令。
+//!
+//! 加固的宿主机只开放普通用户登录时, 可以为主机开启 sudo: 命令包装为
+//! `sudo -S -p '' sh -c '...'` 执行, 密码通过标准输入传给 sudo, 不出现在命令行、
+//! 日志与错误信息中。
+
+use std::borrow::Cow;
+use std::fmt;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use tokio::process::{Child, Command};
+use tracing::debug;
+
+use crate::{ErrorContext, HostInfo, Result, TransportError};
+
+/// 允许在宿主机上执行的程序
+pub const ALLOWED_HOST_COMMANDS: &[&str] = &["virsh", "ovs-vsctl", "ovs-ofctl", "cat", "ip", "gluster", "getfattr"];
+
+/// `ip` 允许的全局选项 (都不带参数; `-n`/`-netns`、`-b`/`-batch` 等不允许)
+const IP_ALLOWED_OPTIONS: &[&str] = &[
+    "-j", "-json", "-p", "-pretty", "-d", "-details", "-s", "-stats", "-br", "-brief", "-o", "-oneline", "-4", "-6",
+];
+
+/// `ip` 允许查询的对象
+const IP_ALLOWED_OBJECTS: &[&str] = &["addr", "address", "link", "route"];
+
+/// ssh 自身出错 (连接失败、主连接失效) 时的退出码
+pub(crate) const SSH_ERROR_EXIT_CODE: i32 = 255;
+
+/// 输出中替换 sudo 密码的占位符
+const REDACTED: &str = "******";
+
+/// SSH 连接配置
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshConfig {
+    /// 登录用户
+    #[serde(default = "default_ssh_user")]
+    pub user: String,
+
+    /// SSH 端口
+    #[serde(default = "default_ssh_port")]
+    pub port: u16,
+
+    /// 私钥文件 (未设置时使用 ssh 客户端的默认配置)
+    #[serde(default)]
+    pub identity_file: Option<String>,
+
+    /// 连接超时 (秒, 未设置时使用命令的超时时间)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+
+    /// 跳板机 (主机只能经由跳板机访问时设置, 跳板机自身也可以再经由跳板机)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump: Option<Box<SshJump>>,
+
+    /// 以 sudo 提权执行命令 (登录用户不是 root 时设置)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sudo: Option<SshSudo>,
+}
+
+/// 提供 sudo 密码的环境变量
+pub const SUDO_PASSWORD_ENV: &str = "ATP_SSH_SUDO_PASSWORD";
+
+/// sudo 提权配置
+///
+/// 配置文件中只记录是否提权, 密码在运行时由调用方提供 (见 [`SshConfig::set_sudo_password`]),
+/// 不会被序列化。
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshSudo {
+    /// sudo 密码 (未设置时要求登录用户可以免密 sudo)
+    #[serde(skip)]
+    pub password: Option<String>,
+}
+
+impl fmt::Debug for SshSudo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SshSudo")
+            .field("password", &self.password.as_ref().map(|_| REDACTED))
+            .finish()
+    }
+}
+
+/// SSH 跳板机
+///
+/// 经由跳板机的 direct-tcpip 通道 (`ssh -W`) 连接下一跳, 每一跳的用户、端口、
+/// 私钥与连接超时相互独立。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SshJump {
+    /// 跳板机地址
+    pub host: String,
+
+    /// 登录跳板机使用的 SSH 配置
+    #[serde(flatten)]
+    pub ssh: SshConfig,
+}
+
+impl SshJump {
+    pub fn new(host: &str, ssh: SshConfig) -> Self {
+        Self {
+            host: host.to_string(),
+            ssh,
+        }
+    }
+}
+
+impl FromStr for SshJump {
+    type Err = String;
+
+    /// 解析 `[user@]host[:port]`, 未指定用户时为 root, IPv6 地址需写作 `[addr]:port`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (user, address) = match s.rsplit_once('@') {
+            Some((user, address)) if !user.is_empty() => (Some(user), address),
+            Some(_) => return Err(format!("跳板机格式错误: {}", s)),
+            None => (None, s),
+        };
+
+        let (host, port) = if let Some(rest) = address.strip_prefix('[') {
+            let (host, rest) = rest
+                .split_once(']')
+                .ok_or_else(|| format!("跳板机格式错误: {}", s))?;
+            (host, rest.strip_prefix(':'))
+        } else {
+            match address.split_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (address, None),
+            }
+        };
+        if host.is_empty() {
+            return Err(format!("跳板机地址为空: {}", s));
+        }
+
+        let mut ssh = user.map_or_else(SshConfig::default, SshConfig::new);
+        if let Some(port) = port {
+            ssh.port = port.parse().map_err(|_| format!("跳板机端口无效: {}", s))?;
+        }
+        Ok(Self::new(host, ssh))
+    }
+}
+
+fn default_ssh_user() -> String {
+    "root".to_string()
+}
+
+fn default_ssh_port() -> u16 {
+    22
+}
+
+impl Default for SshConfig {
+    fn default() -> Self {
+        Self {
+            user: default_ssh_user(),
+            port: default_ssh_port(),
+            identity_file: None,
+            connect_timeout: None,
+            jump: None,
+            sudo: None,
+        }
+    }
+}
+
+impl SshConfig {
+    pub fn new(user: &str) -> Self {
+        Self {
+            user: user.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    pub fn with_identity_file(mut self, path: &str) -> Self {
+        self.identity_file = Some(path.to_string());
+        self
+    }
+
+    /// 本跳的连接超时 (按秒取整)
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout.as_secs());
+        self
+    }
+
+    /// 经由跳板机连接
+    pub fn with_jump(mut self, jump: SshJump) -> Self {
+        self.jump = Some(Box::new(jump));
+        self
+    }
+
+    /// 以 sudo 提权执行命令, 免密 sudo 时密码传 None
+    pub fn with_sudo(mut self, password: Option<String>) -> Self {
+        self.sudo = Some(SshSudo { password });
+        self
+    }
+
+    /// 设置运行时提供的 sudo 密码, 未开启 sudo 时忽略
+    pub fn set_sudo_password(&mut self, password: Option<String>) {
+        if let Some(sudo) = &mut self.sudo {
+            sudo.password = password;
+        }
+    }
+
+    /// 配置的 sudo 密码 (未开启 sudo 或免密时为 None)
+    fn sudo_password(&self) -> Option<&str> {
+        self.sudo.as_ref()?.password.as_deref().filter(|password| !password.is_empty())
+    }
+}
+
+/// 宿主机命令执行结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostCommandOutput {
+    /// 退出码 (被信号终止时为 None)
+    pub exit_code: Option<i32>,
+
+    /// 标准输出
+    pub stdout: String,
+
+    /// 标准错误
+    pub stderr: String,
+
+    /// 执行耗时 (毫秒)
+    pub duration_ms: u64,
+}
+
+impl HostCommandOutput {
+    /// 命令是否执行成功 (退出码为 0)
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+/// 校验命令是否允许执行
+///
+/// 程序名必须与白名单完全一致 (不允许路径), 参数中不能包含 NUL 与换行。
+/// `ip` 只允许只读查询, 见 [`validate_ip_args`]。
+pub fn validate_host_command(argv: &[String]) -> Result<()> {
+    let program = argv
+        .first()
+        .ok_or_else(|| TransportError::ConfigError("宿主机命令不能为空".to_string()))?;
+
+    if !ALLOWED_HOST_COMMANDS.contains(&program.as_str()) {
+        return Err(TransportError::ConfigError(format!(
+            "不允许在宿主机上执行 '{}', 允许的命令: {}",
+            program,
+            ALLOWED_HOST_COMMANDS.join(", ")
+        )));
+    }
+
+    if let Some(arg) = argv.iter().find(|arg| arg.contains(['\0', '\n', '\r'])) {
+        return Err(TransportError::ConfigError(format!(
+            "宿主机命令参数包含非法字符: {:?}",
+            arg
+        )));
+    }
+
+    if program == "ip" {
+        validate_ip_args(&argv[1..])?;
+    }
+
+    Ok(())
+}
+
+/// 校验 `ip` 的参数: 只允许 `ip [选项] addr|link|route [show|list] [过滤条件]`
+///
+/// `ip netns exec` 可以执行任意程序, `ip link set`、`ip route add` 等会修改宿主机网络,
+/// 因此只放行查询。
+fn validate_ip_args(args: &[String]) -> Result<()> {
+    let rejected = |reason: &str| {
+        TransportError::ConfigError(format!(
+            "不允许在宿主机上执行 'ip {}': {}, 只允许 ip [-j] {} [show]",
+            args.join(" "),
+            reason,
+            IP_ALLOWED_OBJECTS.join("|")
+        ))
+    };
+
+    let mut rest = args.iter().map(String::as_str).skip_while(|arg| IP_ALLOWED_OPTIONS.contains(arg));
+    match rest.next() {
+        Some(option) if option.starts_with('-') => return Err(rejected(&format!("不支持的选项 {}", option))),
+        Some(object) if IP_ALLOWED_OBJECTS.contains(&object) => {}
+        Some(object) => return Err(rejected(&format!("不支持的对象 {}", object))),
+        None => return Err(rejected("缺少查询对象")),
+    }
+    match rest.next() {
+        None | Some("show" | "list") => Ok(()),
+        Some(command) => Err(rejected(&format!("不支持的子命令 {}", command))),
+    }
+}
+
+/// 对单个参数做 POSIX shell 转义
+///
+/// 只包含安全字符的参数原样返回, 否则用单引号包裹 (内部单引号写作 `'\''`)。
+pub fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./=:,@%+".contains(c));
+
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// 把参数列表拼成远端执行的命令行
+pub fn quote_command(argv: &[String]) -> String {
+    argv.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>().join(" ")
+}
+
+/// 构建 ssh 客户端参数
+pub(crate) fn ssh_args(host: &str, ssh: &SshConfig, timeout: Duration, argv: &[String]) -> Vec<String> {
+    let mut args = ssh_login_args(host, ssh, timeout);
+    args.push("--".to_string());
+    args.push(remote_command(ssh, argv));
+    args
+}
+
+/// 远端执行的命令行, 开启 sudo 时经 `sh -c` 包装
+///
+/// 有密码时使用 `-S` 从标准输入读取密码并忽略缓存的凭据 (`-k`), 保证密码总是被 sudo 读走;
+/// 免密时使用 `-n`, 需要密码时直接失败而不是等待输入。
+fn remote_command(ssh: &SshConfig, argv: &[String]) -> String {
+    let command = quote_command(argv);
+    match &ssh.sudo {
+        None => command,
+        Some(_) if ssh.sudo_password().is_some() => format!("sudo -k -S -p '' sh -c {}", shell_quote(&command)),
+        Some(_) => format!("sudo -n sh -c {}", shell_quote(&command)),
+    }
+}
+
+/// 启动 ssh 进程, sudo 需要密码时写入标准输入
+pub(crate) async fn spawn_ssh(command: &mut Command, ssh: &SshConfig) -> Result<Child> {
+    let password = ssh.sudo_password();
+    command.stdin(if password.is_some() { Stdio::piped() } else { Stdio::null() });
+
+    let mut child = command.spawn()?;
+    if let (Some(password), Some(mut stdin)) = (password, child.stdin.take()) {
+        // 远端提前退出时写入失败, 结果以退出码与错误输出为准
+        let _ = stdin.write_all(format!("{}\n", password).as_bytes()).await;
+    }
+    Ok(child)
+}
+
+/// 把文本中的 sudo 密码替换为占位符
+pub(crate) fn redact<'a>(ssh: &SshConfig, text: &'a str) -> Cow<'a, str> {
+    match ssh.sudo_password() {
+        Some(password) if text.contains(password) => Cow::Owned(text.replace(password, REDACTED)),
+        _ => Cow::Borrowed(text),
+    }
+}
+
+/// 去掉输出中的 sudo 密码, 并把 sudo 自身的失败转换为对应的错误
+///
+/// sudo 失败时以 1 退出, 与命令自身的失败只能通过错误输出中的 `sudo:` 提示区分。
+pub(crate) fn check_sudo(host: &str, ssh: &SshConfig, mut output: HostCommandOutput) -> Result<HostCommandOutput> {
+    if ssh.sudo.is_none() {
+        return Ok(output);
+    }
+    output.stdout = redact(ssh, &output.stdout).into_owned();
+    output.stderr = redact(ssh, &output.stderr).into_owned();
+    if output.success() {
+        return Ok(output);
+    }
+
+    let login = format!("{}@{}", ssh.user, host);
+    for line in output.stderr.lines().map(str::trim) {
+        if line.contains("not in the sudoers file")
+            || line.contains("is not allowed to execute")
+            || line.contains("may not run sudo")
+        {
+            return Err(TransportError::SudoNotPermitted(format!("{}: {}", login, line)));
+        }
+        if line.starts_with("Sorry, try again")
+            || line.contains("incorrect password")
+            || (line.starts_with("sudo:") && line.contains("password is required"))
+            || (line.starts_with("sudo:") && line.contains("no password was provided"))
+        {
+            return Err(TransportError::SudoAuthFailed(format!("{}: {}", login, line)));
+        }
+    }
+    Ok(output)
+}
+
+/// 构建 ssh 客户端的连接参数 (以 `user@host` 结尾, 不含远端命令)
+pub(crate) fn ssh_login_args(host: &str, ssh: &SshConfig, timeout: Duration) -> Vec<String> {
+    let mut args = vec![
+        "-o".to_string(),
+        "BatchMode=yes".to_string(),
+        "-o".to_string(),
+        format!("ConnectTimeout={}", ssh.connect_timeout.unwrap_or(timeout.as_secs()).max(1)),
+        "-p".to_string(),
+        ssh.port.to_string(),
+    ];
+
+    if let Some(identity_file) = &ssh.identity_file {
+        args.push("-i".to_string());
+        args.push(identity_file.clone());
+    }
+
+    if let Some(jump) = &ssh.jump {
+        args.push("-o".to_string());
+        args.push(format!("ProxyCommand={}", proxy_command(jump, host, ssh.port, timeout)));
+    }
+
+    args.push(format!("{}@{}", ssh.user, host));
+    args
+}
+
+/// 经由跳板机转发到下一跳的 ProxyCommand
+///
+/// 跳板机自身的跳板机通过嵌套的 ProxyCommand 实现。ssh 会展开 ProxyCommand 中的 `%` 记号,
+/// 目标地址直接写入命令, 其余 `%` 转义为 `%%`。
+fn proxy_command(jump: &SshJump, target_host: &str, target_port: u16, timeout: Duration) -> String {
+    let mut argv = vec!["ssh".to_string()];
+    argv.extend(ssh_login_args(&jump.host, &jump.ssh, timeout));
+
+    // -W 必须位于目标之前
+    let login = argv.pop().unwrap_or_default();
+    argv.push("-W".to_string());
+    if target_host.contains(':') {
+        argv.push(format!("[{}]:{}", target_host, target_port));
+    } else {
+        argv.push(format!("{}:{}", target_host, target_port));
+    }
+    argv.push(login);
+
+    quote_command(&argv).replace('%', "%%")
+}
+
+/// 连接链路上的各跳, 按连接顺序排列 (最外层的跳板机在前, 目标主机在最后)
+fn hops<'a>(host: &'a str, ssh: &'a SshConfig) -> Vec<(&'a str, &'a SshConfig)> {
+    let mut hops = vec![(host, ssh)];
+    let mut current = ssh;
+    while let Some(jump) = &current.jump {
+        hops.push((&jump.host, &jump.ssh));
+        current = &jump.ssh;
+    }
+    hops.reverse();
+    hops
+}
+
+/// 根据 ssh 的错误输出判断连接在哪一跳失败
+///
+/// 能识别解析主机名失败、TCP 连接失败与认证失败, 返回描述该跳的错误信息;
+/// 无法判断时 (如远端命令自身以 255 退出) 返回 None。
+pub(crate) fn hop_failure(host: &str, ssh: &SshConfig, stderr: &str) -> Option<String> {
+    let hops = hops(host, ssh);
+    let count = hops.len();
+
+    hops.iter().enumerate().find_map(|(index, (hop_host, hop_ssh))| {
+        let markers = [
+            format!("hostname {}", hop_host),
+            format!("host {} port", hop_host),
+            format!("{}@{}:", hop_ssh.user, hop_host),
+        ];
+        let line = stderr
+            .lines()
+            .map(str::trim)
+            .find(|line| markers.iter().any(|marker| line.contains(marker.as_str())))?;
+
+        let role = if index + 1 == count { "目标主机" } else { "跳板机" };
+        let hop = format!("{}@{}:{}", hop_ssh.user, hop_host, hop_ssh.port);
+        Some(if count > 1 {
+            format!("第 {}/{} 跳 ({} {}) 连接失败: {}", index + 1, count, role, hop, line)
+        } else {
+            format!("{} {} 连接失败: {}", role, hop, line)
+        })
+    })
+}
+
+/// 通过 SSH 在宿主机上执行白名单内的命令
+pub async fn exec_host_command(host: &HostInfo, argv: &[String], timeout: Duration) -> Result<HostCommandOutput> {
+    let context = ErrorContext::new()
+        .with_host(&host.id)
+        .with_operation(argv.first().map_or("host-command", String::as_str));
+
+    let ssh = host.ssh.as_ref().ok_or_else(|| {
+        TransportError::ConfigError(format!("主机 {} 未配置 SSH, 无法执行宿主机命令", host.id))
+            .with_context(context.clone())
+    })?;
+    validate_host_command(argv).map_err(|e| e.with_context(context.clone()))?;
+
+    debug!("在主机 {} 上执行: {}", host.id, quote_command(argv));
+
+    let started = Instant::now();
+    let mut command = Command::new("ssh");
+    command
+        .args(ssh_args(&host.host, ssh, timeout, argv))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let child = async { Ok::<_, TransportError>(spawn_ssh(&mut command, ssh).await?.wait_with_output().await?) };
+
+    let output = tokio::time::timeout(timeout, child)
+        .await
+        .map_err(|_| TransportError::Timeout.with_context(context.clone()))?
+        .map_err(|e| e.with_context(context.clone()))?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if output.status.code() == Some(SSH_ERROR_EXIT_CODE) {
+        if let Some(message) = hop_failure(&host.host, ssh, &stderr) {
+            return Err(TransportError::ConnectionFailed(message).with_context(context));
+        }
+    }
+
+    let output = HostCommandOutput {
+        exit_code: output.status.code(),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: stderr.into_owned(),
+        duration_ms: started.elapsed().as_millis() as u64,
+    };
+    check_sudo(&host.host, ssh, output).map_err(|e| e.with_context(context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_validate_host_command() {
+        assert!(validate_host_command(&argv(&["virsh", "domifstat", "win10", "vnet0"])).is_ok());
+        assert!(validate_host_command(&argv(&["ovs-vsctl", "show"])).is_ok());
+
+        assert!(validate_host_command(&[]).is_err());
+        assert!(validate_host_command(&argv(&["rm", "-rf", "/"])).is_err());
+        // 不允许路径或前缀相同的其他程序
+        assert!(validate_host_command(&argv(&["/usr/bin/virsh", "list"])).is_err());
+        assert!(validate_host_command(&argv(&["virsh-evil"])).is_err());
+        assert!(validate_host_command(&argv(&["cat", "/etc/hostname\nreboot"])).is_err());
+    }
+
+    #[test]
+    fn test_validate_ip_command() {
+        assert!(validate_host_command(&argv(&["ip", "-j", "addr", "show"])).is_ok());
+        assert!(validate_host_command(&argv(&["ip", "-j", "-d", "link", "show", "dev", "vnet0"])).is_ok());
+        assert!(validate_host_command(&argv(&["ip", "route"])).is_ok());
+        assert!(validate_host_command(&argv(&["ip", "-4", "route", "list", "table", "main"])).is_ok());
+
+        // ip netns exec 可以执行任意程序
+        let err = validate_host_command(&argv(&["ip", "netns", "exec", "ns1", "sh", "-c", "reboot"])).unwrap_err();
+        assert!(err.to_string().contains("netns"), "{}", err);
+        assert!(validate_host_command(&argv(&["ip", "-n", "ns1", "link", "show"])).is_err());
+        assert!(validate_host_command(&argv(&["ip", "-batch", "/tmp/cmds"])).is_err());
+
+        // 修改网络配置
+        assert!(validate_host_command(&argv(&["ip", "link", "set", "eth0", "down"])).is_err());
+        assert!(validate_host_command(&argv(&["ip", "-j", "route", "add", "default", "via", "10.0.0.1"])).is_err());
+        assert!(validate_host_command(&argv(&["ip", "addr", "flush", "dev", "eth0"])).is_err());
+        assert!(validate_host_command(&argv(&["ip"])).is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote("domifstat"), "domifstat");
+        assert_eq!(shell_quote("--type=qemu"), "--type=qemu");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("win 10"), "'win 10'");
+        assert_eq!(shell_quote("a;reboot"), "'a;reboot'");
+        assert_eq!(shell_quote("$(id)"), "'$(id)'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+
+        assert_eq!(
+            quote_command(&argv(&["virsh", "domifstat", "my vm", "x`id`"])),
+            "virsh domifstat 'my vm' 'x`id`'"
+        );
+    }
+
+    #[test]
+    fn test_ssh_args() {
+        let ssh = SshConfig::new("admin").with_port(2222).with_identity_file("/keys/id");
+        let args = ssh_args("10.0.0.1", &ssh, Duration::from_secs(10), &argv(&["virsh", "list", "--all"]));
+
+        assert_eq!(
+            args,
+            argv(&[
+                "-o", "BatchMode=yes", "-o", "ConnectTimeout=10", "-p", "2222", "-i", "/keys/id",
+                "admin@10.0.0.1", "--", "virsh list --all",
+            ])
+        );
+    }
+
+    #[test]
+    fn test_ssh_args_through_jump_hosts() {
+        let outer = SshJump::new("gw.example.com", SshConfig::new("gw").with_identity_file("/keys/gw 1"));
+        let bastion = SshConfig::new("ops")
+            .with_port(2200)
+            .with_connect_timeout(Duration::from_secs(3))
+            .with_jump(outer);
+        let ssh = SshConfig::new("root").with_jump(SshJump::new("bastion", bastion));
+
+        let args = ssh_args("10.0.0.1", &ssh, Duration::from_secs(10), &argv(&["virsh", "list"]));
+        let proxy = args.iter().find_map(|arg| arg.strip_prefix("ProxyCommand=")).unwrap();
+
+        // 每一跳使用自己的端口、私钥与超时, 目标地址写在 -W 中
+        assert!(proxy.starts_with("ssh -o BatchMode=yes -o ConnectTimeout=3 -p 2200 "), "{}", proxy);
+        assert!(proxy.ends_with(" -W 10.0.0.1:22 ops@bastion"), "{}", proxy);
+        assert!(proxy.contains("-W bastion:2200 gw@gw.example.com"), "{}", proxy);
+        assert!(proxy.contains("-i '\\''/keys/gw 1'\\''"), "{}", proxy);
+        assert_eq!(args[args.len() - 3..], argv(&["root@10.0.0.1", "--", "virsh list"]));
+
+        // 没有跳板机时不使用 ProxyCommand
+        let direct = ssh_args("10.0.0.1", &SshConfig::new("root"), Duration::from_secs(10), &argv(&["virsh"]));
+        assert!(!direct.iter().any(|arg| arg.starts_with("ProxyCommand=")));
+    }
+
+    #[test]
+    fn test_parse_jump() {
+        let jump: SshJump = "ops@bastion.lab:2222".parse().unwrap();
+        assert_eq!((jump.host.as_str(), jump.ssh.user.as_str(), jump.ssh.port), ("bastion.lab", "ops", 2222));
+
+        let jump: SshJump = "10.0.0.254".parse().unwrap();
+        assert_eq!((jump.host.as_str(), jump.ssh.user.as_str(), jump.ssh.port), ("10.0.0.254", "root", 22));
+
+        let jump: SshJump = "ops@[fd00::1]:22".parse().unwrap();
+        assert_eq!(jump.host, "fd00::1");
+
+        assert!("ops@".parse::<SshJump>().is_err());
+        assert!("@bastion".parse::<SshJump>().is_err());
+        assert!("bastion:ssh".parse::<SshJump>().is_err());
+    }
+
+    #[test]
+    fn test_jump_config_deserialize() {
+        let ssh: SshConfig = serde_json::from_value(serde_json::json!({
+            "user": "root",
+            "jump": { "host": "bastion", "user": "ops", "identity_file": "/keys/ops", "connect_timeout": 5 }
+        }))
+        .unwrap();
+
+        let jump = ssh.jump.unwrap();
+        assert_eq!(jump.host, "bastion");
+        assert_eq!(jump.ssh.identity_file.as_deref(), Some("/keys/ops"));
+        assert_eq!((jump.ssh.port, jump.ssh.connect_timeout), (22, Some(5)));
+    }
+
+    #[test]
+    fn test_hop_failure() {
+        let ssh = SshConfig::new("root").with_jump(SshJump::new("bastion", SshConfig::new("ops")));
+
+        let stderr = "ssh: connect to host bastion port 22: Connection timed out\r\nConnection closed by UNKNOWN port 65535\n";
+        assert_eq!(
+            hop_failure("10.0.0.1", &ssh, stderr).unwrap(),
+            "第 1/2 跳 (跳板机 ops@bastion:22) 连接失败: ssh: connect to host bastion port 22: Connection timed out"
+        );
+
+        let stderr = "root@10.0.0.1: Permission denied (publickey).\n";
+        assert!(hop_failure("10.0.0.1", &ssh, stderr).unwrap().starts_with("第 2/2 跳 (目标主机 root@10.0.0.1:22)"));
+
+        let direct = SshConfig::new("root");
+        let stderr = "ssh: Could not resolve hostname host-a: Name or service not known\n";
+        assert!(hop_failure("host-a", &direct, stderr).unwrap().starts_with("目标主机 root@host-a:22 连接失败"));
+
+        assert_eq!(hop_failure("10.0.0.1", &ssh, "error: failed to get domain 'vm'\n"), None);
+    }
+
+    #[test]
+    fn test_sudo_remote_command() {
+        let command = argv(&["cat", "/var/lib/glusterd/vols/vol 1/info"]);
+        let args = ssh_args("10.0.0.1", &SshConfig::new("ops").with_sudo(None), Duration::from_secs(10), &command);
+        assert_eq!(args.last().unwrap(), r"sudo -n sh -c 'cat '\''/var/lib/glusterd/vols/vol 1/info'\'''");
+
+        let ssh = SshConfig::new("ops").with_sudo(Some("p@ss".to_string()));
+        let args = ssh_args("10.0.0.1", &ssh, Duration::from_secs(10), &argv(&["ovs-vsctl", "show"]));
+        assert_eq!(args.last().unwrap(), "sudo -k -S -p '' sh -c 'ovs-vsctl show'");
+        // 密码不出现在命令行与调试输出中
+        assert!(args.iter().all(|arg| !arg.contains("p@ss")));
+        assert!(!format!("{:?}", ssh).contains("p@ss"));
+
+        // 密码不会被序列化, 读回后只保留 sudo 开关, 由调用方在运行时补上密码
+        let json = serde_json::to_value(&ssh).unwrap();
+        assert!(!json.to_string().contains("p@ss"), "{}", json);
+        let mut loaded = serde_json::from_value::<SshConfig>(json).unwrap();
+        assert_eq!(loaded, SshConfig::new("ops").with_sudo(None));
+        loaded.set_sudo_password(Some("p@ss".to_string()));
+        assert_eq!(loaded, ssh);
+
+        // 未开启 sudo 时忽略密码
+        let mut plain = SshConfig::new("ops");
+        plain.set_sudo_password(Some("p@ss".to_string()));
+        assert_eq!(plain.sudo, None);
+    }
+
+    #[test]
+    fn test_check_sudo() {
+        let ssh = SshConfig::new("ops").with_sudo(Some("p@ss".to_string()));
+        let output = |exit_code: i32, stderr: &str| HostCommandOutput {
+            exit_code: Some(exit_code),
+            stdout: "echo p@ss\n".to_string(),
+            stderr: stderr.to_string(),
+            duration_ms: 1,
+        };
+
+        let ok = check_sudo("h1", &ssh, output(0, "")).unwrap();
+        assert_eq!(ok.stdout, "echo ******\n");
+
+        let err = check_sudo("h1", &ssh, output(1, "Sorry, try again.\nsudo: 1 incorrect password attempt\n")).unwrap_err();
+        assert!(matches!(&err, TransportError::SudoAuthFailed(message) if message.starts_with("ops@h1: Sorry")));
+
+        let stderr = "ops is not in the sudoers file.  This incident will be reported.\n";
+        let err = check_sudo("h1", &ssh, output(1, stderr)).unwrap_err();
+        assert!(matches!(err, TransportError::SudoNotPermitted(_)));
+
+        let err = check_sudo("h1", &SshConfig::new("ops").with_sudo(None), output(1, "sudo: a password is required\n"));
+        assert!(matches!(err.unwrap_err(), TransportError::SudoAuthFailed(_)));
+
+        // 命令自身的失败原样返回
+        let failed = check_sudo("h1", &ssh, output(1, "error: failed to get domain 'vm'\n")).unwrap();
+        assert_eq!(failed.exit_code, Some(1));
+        // 未开启 sudo 时不处理输出
+        assert_eq!(check_sudo("h1", &SshConfig::new("root"), output(0, "")).unwrap().stdout, "echo p@ss\n");
+    }
+
+    #[tokio::test]
+    async fn test_exec_without_ssh_config() {
+        let host = HostInfo::new("host1", "10.0.0.1");
+        let err = exec_host_command(&host, &argv(&["virsh", "list"]), Duration::from_secs(1))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err.root(), TransportError::ConfigError(_)));
+        assert!(err.to_string().contains("未配置 SSH"));
+    }
+}
@@ -2,15 +2,37 @@
 //!
 //! 负责与 Libvirt 的长连接管理，支持多主机节点和并发执行。
 
+pub mod capabilities;
 pub mod config;
+pub mod context;
 pub mod connection;
+pub mod domain_xml;
+pub mod gluster;
+pub mod host_command;
+pub mod locator;
 pub mod pool;
 pub mod manager;
-
-pub use config::{TransportConfig, PoolConfig, ReconnectConfig, SelectionStrategy};
+pub mod sftp;
+pub mod snapshot;
+pub mod ssh_pool;
+pub mod stats;
+
+pub use capabilities::HostCapabilities;
+pub use config::{validate_libvirt_uri, HostEntry, TransportConfig, PoolConfig, ReconnectConfig, SelectionStrategy};
+pub use context::ErrorContext;
 pub use connection::{HostConnection, ConnectionState, ConnectionMetrics};
+pub use domain_xml::{DomainChannel, DomainDiskInfo, DomainGraphics, DomainInspection};
+pub use gluster::{
+    BrickHealInfo, BrickStatus, GlusterClient, GlusterFile, GlusterFileUsage, HealEntry, HealInfo, SplitBrainEntry,
+};
+pub use host_command::{HostCommandOutput, SshConfig, SshJump, SshSudo, ALLOWED_HOST_COMMANDS, SUDO_PASSWORD_ENV};
+pub use locator::{DomainCache, LibvirtDomainInfo};
 pub use pool::{ConnectionPool, ConnectionPoolStats};
 pub use manager::TransportManager;
+pub use sftp::{FileStat, TransferProgress};
+pub use snapshot::{ClusterDomainSnapshot, DomainFilter, HostDomainInfo, HostSnapshotError, SnapshotCache};
+pub use ssh_pool::{LineCallback, SshPool, SshPoolStats};
+pub use stats::{cpu_usage_percent, BlockIoStats, DomainStatsSample};
 
 use thiserror::Error;
 
@@ -26,6 +48,9 @@ pub enum TransportError {
     #[error("虚拟机 {0} 不存在")]
     DomainNotFound(String),
 
+    #[error("虚拟机 {0} 同时存在于多个主机: {}", .1.join(", "))]
+    AmbiguousDomain(String, Vec<String>),
+
     #[error("连接池已满")]
     PoolExhausted,
 
@@ -43,6 +68,75 @@ pub enum TransportError {
 
     #[error("配置错误: {0}")]
     ConfigError(String),
+
+    #[error("配置无效 ({} 处问题): {}", .0.len(), .0.join("; "))]
+    InvalidConfig(Vec<String>),
+
+    #[error("远端文件不存在: {0}")]
+    FileNotFound(String),
+
+    #[error("没有权限访问远端文件: {0}")]
+    PermissionDenied(String),
+
+    #[error("SFTP 错误: {0}")]
+    SftpError(String),
+
+    #[error("命令超过 {} 秒没有输出, 可能已挂起", .0.as_secs())]
+    IdleTimeout(std::time::Duration),
+
+    #[error("sudo 认证失败: {0}")]
+    SudoAuthFailed(String),
+
+    #[error("用户没有 sudo 权限: {0}")]
+    SudoNotPermitted(String),
+
+    #[error("GlusterFS 错误: {0}")]
+    GlusterError(String),
+
+    #[error("{context} {source}")]
+    WithContext {
+        context: ErrorContext,
+        #[source]
+        source: Box<TransportError>,
+    },
+}
+
+impl TransportError {
+    /// 附加主机、虚拟机等上下文
+    ///
+    /// 已带有上下文时只补全缺失的字段, 不会重复包装。
+    pub fn with_context(self, context: ErrorContext) -> Self {
+        if context.is_empty() {
+            return self;
+        }
+
+        match self {
+            TransportError::WithContext { context: mut inner, source } => {
+                inner.merge(context);
+                TransportError::WithContext { context: inner, source }
+            }
+            err => TransportError::WithContext {
+                context,
+                source: Box::new(err),
+            },
+        }
+    }
+
+    /// 错误的上下文
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            TransportError::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// 去掉上下文后的原始错误
+    pub fn root(&self) -> &TransportError {
+        match self {
+            TransportError::WithContext { source, .. } => source.root(),
+            err => err,
+        }
+    }
 }
 
 pub type Result<T> = std::result::Result<T, TransportError>;
@@ -64,6 +158,9 @@ pub struct HostInfo {
 
     /// 元数据
     pub metadata: std::collections::HashMap<String, String>,
+
+    /// SSH 配置 (执行宿主机命令时使用, 未配置时不能执行)
+    pub ssh: Option<SshConfig>,
 }
 
 impl HostInfo {
@@ -74,6 +171,7 @@ impl HostInfo {
             uri: format!("qemu+ssh://{}:22/system", host),
             tags: Vec::new(),
             metadata: std::collections::HashMap::new(),
+            ssh: None,
         }
     }
 
@@ -91,4 +189,9 @@ impl HostInfo {
         self.metadata.insert(key.to_string(), value.to_string());
         self
     }
+
+    pub fn with_ssh(mut self, ssh: SshConfig) -> Self {
+        self.ssh = Some(ssh);
+        self
+    }
 }
@@ -0,0 +1,216 @@
+//! 虚拟机位置查找
+//!
+//! 按虚拟机名称找到其所在的主机, 调用方不必先查询 VDI 平台。
+//! 缓存来自最近一次批量列举 ([`crate::TransportManager::list_all_domains`]),
+//! 未命中时并行列举所有主机一次并刷新缓存。
+
+use std::collections::HashMap;
+use std::future::Future;
+
+use futures_util::future::join_all;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::{Result, TransportError};
+
+/// libvirt 中的虚拟机信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LibvirtDomainInfo {
+    /// 虚拟机名称
+    pub name: String,
+
+    /// UUID
+    pub uuid: String,
+
+    /// 状态 (virDomainState)
+    pub state: u32,
+
+    /// vCPU 数量
+    pub vcpus: u32,
+
+    /// 当前分配的内存 (KiB)
+    pub memory_kb: u64,
+}
+
+impl LibvirtDomainInfo {
+    /// 是否处于运行状态
+    pub fn is_running(&self) -> bool {
+        self.state == virt::sys::VIR_DOMAIN_RUNNING
+    }
+}
+
+/// 按主机缓存的虚拟机列表
+#[derive(Debug, Default)]
+pub struct DomainCache {
+    /// 主机 ID -> 该主机上的虚拟机
+    hosts: RwLock<HashMap<String, Vec<LibvirtDomainInfo>>>,
+}
+
+impl DomainCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 用一次完整列举的结果替换某台主机的缓存
+    pub async fn update_host(&self, host_id: &str, domains: Vec<LibvirtDomainInfo>) {
+        self.hosts.write().await.insert(host_id.to_string(), domains);
+    }
+
+    /// 移除某台主机的缓存
+    pub async fn remove_host(&self, host_id: &str) {
+        self.hosts.write().await.remove(host_id);
+    }
+
+    /// 清空缓存
+    pub async fn clear(&self) {
+        self.hosts.write().await.clear();
+    }
+
+    /// 在缓存中查找虚拟机, 返回所有匹配的位置 (按主机 ID 排序)
+    pub async fn locate(&self, domain_name: &str) -> Vec<(String, LibvirtDomainInfo)> {
+        let hosts = self.hosts.read().await;
+        let mut found: Vec<(String, LibvirtDomainInfo)> = hosts
+            .iter()
+            .filter_map(|(host_id, domains)| {
+                domains
+                    .iter()
+                    .find(|domain| domain.name == domain_name)
+                    .map(|domain| (host_id.clone(), domain.clone()))
+            })
+            .collect();
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        found
+    }
+
+    /// 查找虚拟机: 先查缓存, 未命中时用 `list` 并行列举所有主机并刷新缓存后再查
+    ///
+    /// 列举失败的主机保留原有缓存, 只记录日志。
+    pub async fn find<L, Fut>(
+        &self,
+        domain_name: &str,
+        host_ids: &[String],
+        list: L,
+    ) -> Result<Option<(String, LibvirtDomainInfo)>>
+    where
+        L: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Vec<LibvirtDomainInfo>>>,
+    {
+        let mut found = self.locate(domain_name).await;
+
+        if found.is_empty() {
+            let listings = join_all(host_ids.iter().map(|host_id| {
+                let listing = list(host_id.clone());
+                async move { (host_id, listing.await) }
+            }))
+            .await;
+
+            for (host_id, listing) in listings {
+                match listing {
+                    Ok(domains) => self.update_host(host_id, domains).await,
+                    Err(e) => warn!("列举主机 {} 上的虚拟机失败: {}", host_id, e),
+                }
+            }
+
+            found = self.locate(domain_name).await;
+        }
+
+        match found.len() {
+            0 => Ok(None),
+            1 => Ok(found.pop()),
+            _ => Err(TransportError::AmbiguousDomain(
+                domain_name.to_string(),
+                found.into_iter().map(|(host_id, _)| host_id).collect(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn domain(name: &str) -> LibvirtDomainInfo {
+        LibvirtDomainInfo {
+            name: name.to_string(),
+            uuid: format!("uuid-{}", name),
+            state: virt::sys::VIR_DOMAIN_RUNNING,
+            vcpus: 2,
+            memory_kb: 4 * 1024 * 1024,
+        }
+    }
+
+    /// 模拟的主机列表: host1 上有 win10, host2 上有 ubuntu
+    fn listing(host_id: &str) -> Result<Vec<LibvirtDomainInfo>> {
+        match host_id {
+            "host1" => Ok(vec![domain("win10")]),
+            "host2" => Ok(vec![domain("ubuntu")]),
+            other => Err(TransportError::HostNotFound(other.to_string())),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_queries_hosts_on_miss_then_uses_cache() {
+        let cache = DomainCache::new();
+        let hosts = vec!["host1".to_string(), "host2".to_string(), "host3".to_string()];
+        let queries = AtomicUsize::new(0);
+        let list = |host_id: String| {
+            queries.fetch_add(1, Ordering::SeqCst);
+            async move { listing(&host_id) }
+        };
+
+        // 未命中: 并行列举所有主机 (host3 失败不影响结果)
+        let (host_id, info) = cache.find("ubuntu", &hosts, &list).await.unwrap().unwrap();
+        assert_eq!(host_id, "host2");
+        assert_eq!(info.uuid, "uuid-ubuntu");
+        assert!(info.is_running());
+        assert_eq!(queries.load(Ordering::SeqCst), 3);
+
+        // 命中缓存: 不再查询
+        let (host_id, _) = cache.find("win10", &hosts, &list).await.unwrap().unwrap();
+        assert_eq!(host_id, "host1");
+        assert_eq!(queries.load(Ordering::SeqCst), 3);
+
+        // 不存在的虚拟机每次都会重新列举
+        assert!(cache.find("missing", &hosts, &list).await.unwrap().is_none());
+        assert_eq!(queries.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn test_update_host_replaces_listing() {
+        let cache = DomainCache::new();
+        cache.update_host("host1", vec![domain("win10")]).await;
+
+        // 虚拟机迁移到 host2
+        cache.update_host("host1", vec![]).await;
+        cache.update_host("host2", vec![domain("win10")]).await;
+
+        let found = cache.locate("win10").await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, "host2");
+
+        cache.remove_host("host2").await;
+        assert!(cache.locate("win10").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_same_name_on_multiple_hosts_is_ambiguous() {
+        let cache = DomainCache::new();
+        cache.update_host("host2", vec![domain("win10")]).await;
+        cache.update_host("host1", vec![domain("win10"), domain("ubuntu")]).await;
+
+        let list = |_: String| async { Ok(Vec::new()) };
+        let err = cache.find("win10", &[], list).await.unwrap_err();
+        match &err {
+            TransportError::AmbiguousDomain(name, hosts) => {
+                assert_eq!(name, "win10");
+                assert_eq!(hosts, &vec!["host1".to_string(), "host2".to_string()]);
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert_eq!(err.to_string(), "虚拟机 win10 同时存在于多个主机: host1, host2");
+
+        let (host_id, _) = cache.find("ubuntu", &[], list).await.unwrap().unwrap();
+        assert_eq!(host_id, "host1");
+    }
+}
@@ -1,10 +1,22 @@
 //! 传输管理器
 
+use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 use std::future::Future;
+use std::time::Duration;
 use tokio::task::JoinHandle;
+use async_trait::async_trait;
+use futures_util::stream::{self, StreamExt};
+use atp_common::{MetricSample, MetricsSource};
 
-use crate::{ConnectionPool, ConnectionPoolStats, HostConnection, HostInfo, Result, TransportConfig, TransportError};
+use crate::{
+    ClusterDomainSnapshot, ConnectionPool, ConnectionPoolStats, ConnectionState, DomainCache, DomainFilter, DomainInspection, ErrorContext, HostCommandOutput, HostConnection, HostInfo,
+    LibvirtDomainInfo, LineCallback, Result, SnapshotCache, SshPool, TransportConfig, TransportError,
+};
+
+/// 初始连接检查的最大并发数
+const CHECK_CONCURRENCY: usize = 16;
 
 /// 传输管理器
 ///
@@ -15,14 +27,76 @@ pub struct TransportManager {
 
     /// 配置
     config: TransportConfig,
+
+    /// 虚拟机所在主机的缓存
+    domain_cache: DomainCache,
+
+    /// 宿主机命令使用的 SSH 连接池
+    ssh_pool: Arc<SshPool>,
+
+    /// 集群虚拟机快照的缓存 (默认不缓存)
+    snapshot_cache: SnapshotCache,
 }
 
 impl TransportManager {
     /// 创建新的传输管理器
     pub fn new(config: TransportConfig) -> Self {
-        let pool = Arc::new(ConnectionPool::new(config.pool.clone()));
+        let pool = Arc::new(ConnectionPool::with_transport_config(config.pool.clone(), Arc::new(config.clone())));
+
+        Self {
+            pool,
+            config,
+            domain_cache: DomainCache::new(),
+            ssh_pool: Arc::new(SshPool::new()),
+            snapshot_cache: SnapshotCache::default(),
+        }
+    }
 
-        Self { pool, config }
+    /// 按配置创建传输管理器并注册配置中的所有主机
+    ///
+    /// 先校验配置, 所有问题一次性以 [`TransportError::InvalidConfig`] 返回;
+    /// 注册后连接在后台建立, 需要确认主机可达时调用 [`check_connections`](Self::check_connections)。
+    pub async fn from_config(config: TransportConfig) -> Result<Self> {
+        config.validate()?;
+
+        let hosts = config.hosts.clone();
+        let manager = Self::new(config);
+        for host in &hosts {
+            manager.add_host(host.to_host_info()).await?;
+        }
+        Ok(manager)
+    }
+
+    /// 并发检查所有主机的连接 (尚未连上的主机在这里直接连接), 返回主机 ID -> 结果
+    pub async fn check_connections(&self) -> HashMap<String, Result<()>> {
+        let host_ids = self.list_hosts().await;
+        stream::iter(host_ids)
+            .map(|host_id| async move {
+                let result = self
+                    .execute_on_host(&host_id, |conn| async move {
+                        if conn.state().await != ConnectionState::Connected {
+                            conn.connect().await?;
+                        }
+                        Ok(())
+                    })
+                    .await;
+                (host_id, result)
+            })
+            .buffer_unordered(CHECK_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    /// 使用指定的 SSH 连接池执行宿主机命令
+    pub fn with_ssh_pool(mut self, ssh_pool: SshPool) -> Self {
+        self.ssh_pool = Arc::new(ssh_pool);
+        self
+    }
+
+    /// 缓存集群虚拟机快照, 有效期内 [`snapshot_all_domains`](Self::snapshot_all_domains) 不重复列举主机
+    pub fn with_snapshot_ttl(mut self, ttl: Duration) -> Self {
+        self.snapshot_cache = SnapshotCache::new(ttl);
+        self
     }
 
     /// 创建默认配置的传输管理器
@@ -37,9 +111,20 @@ impl TransportManager {
 
     /// 移除主机
     pub async fn remove_host(&self, host_id: &str) -> Result<()> {
+        self.domain_cache.remove_host(host_id).await;
+        self.snapshot_cache.invalidate_host(host_id).await;
         self.pool.remove_host(host_id).await
     }
 
+    /// 摘除主机: 不再分配新连接, 等待进行中的任务用完连接 (最多 `timeout`) 后断开
+    ///
+    /// 返回超时后被强制断开的连接数。
+    pub async fn drain_host(&self, host_id: &str, timeout: Duration) -> Result<usize> {
+        self.domain_cache.remove_host(host_id).await;
+        self.snapshot_cache.invalidate_host(host_id).await;
+        self.pool.drain_host(host_id, timeout).await
+    }
+
     /// 列出所有主机
     pub async fn list_hosts(&self) -> Vec<String> {
         self.pool.list_hosts().await
@@ -55,6 +140,74 @@ impl TransportManager {
         &self.config
     }
 
+    /// 获取 SSH 连接池
+    pub fn ssh_pool(&self) -> &Arc<SshPool> {
+        &self.ssh_pool
+    }
+
+    /// 通过 SSH 在指定主机上执行白名单内的命令 (如 virsh)
+    ///
+    /// 主机未配置 SSH 或命令不在白名单内时返回 `ConfigError`。
+    /// 同一主机的命令复用 SSH 连接池中的主连接。
+    pub async fn exec_host_command(
+        &self,
+        host_id: &str,
+        argv: &[String],
+        timeout: Duration,
+    ) -> Result<HostCommandOutput> {
+        let ssh_pool = &self.ssh_pool;
+        self.execute_on_host(host_id, |conn| async move {
+            ssh_pool.execute(conn.host_info(), argv, timeout).await
+        })
+        .await
+    }
+
+    /// 在指定主机上执行宿主机命令并逐行回调输出
+    ///
+    /// 超过 `idle_timeout` 没有新的输出行时终止命令并返回 `IdleTimeout`。
+    pub async fn exec_host_command_streaming(
+        &self,
+        host_id: &str,
+        argv: &[String],
+        idle_timeout: Duration,
+        on_stdout: LineCallback<'_>,
+        on_stderr: LineCallback<'_>,
+    ) -> Result<HostCommandOutput> {
+        let ssh_pool = &self.ssh_pool;
+        self.execute_on_host(host_id, |conn| async move {
+            ssh_pool
+                .execute_streaming(conn.host_info(), argv, idle_timeout, on_stdout, on_stderr)
+                .await
+        })
+        .await
+    }
+
+    /// 通过 SFTP 下载指定主机上的文件 (如 `/var/log/libvirt/qemu/*.log`), 返回下载的字节数
+    pub async fn download_host_file(&self, host_id: &str, remote: &str, local: &Path) -> Result<u64> {
+        let ssh_pool = &self.ssh_pool;
+        self.execute_on_host(host_id, |conn| async move {
+            ssh_pool.download(conn.host_info(), remote, local, None).await
+        })
+        .await
+    }
+
+    /// 在多个主机上并发执行同一条宿主机命令 (同时最多 `concurrency` 台), 返回主机 ID -> 结果
+    pub async fn exec_host_command_on_all(
+        &self,
+        host_ids: &[&str],
+        argv: &[String],
+        timeout: Duration,
+        concurrency: usize,
+    ) -> HashMap<String, Result<HostCommandOutput>> {
+        stream::iter(host_ids)
+            .map(|host_id| async move {
+                (host_id.to_string(), self.exec_host_command(host_id, argv, timeout).await)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
     /// 在指定主机上执行任务
     ///
     /// # 示例
@@ -69,8 +222,11 @@ impl TransportManager {
         F: FnOnce(Arc<HostConnection>) -> Fut,
         Fut: Future<Output = Result<T>>,
     {
-        let conn = self.pool.get_connection(host_id).await?;
-        task(conn).await
+        let result = match self.pool.get_connection(host_id).await {
+            Ok(conn) => task(conn).await,
+            Err(e) => Err(e),
+        };
+        result.map_err(|e| e.with_context(ErrorContext::new().with_host(host_id)))
     }
 
     /// 在多个主机上并发执行任务
@@ -104,8 +260,11 @@ impl TransportManager {
             let host_id = host_id.to_string();
 
             let handle: JoinHandle<Result<T>> = tokio::spawn(async move {
-                let conn = pool.get_connection(&host_id).await?;
-                task(conn).await
+                let result = match pool.get_connection(&host_id).await {
+                    Ok(conn) => task(conn).await,
+                    Err(e) => Err(e),
+                };
+                result.map_err(|e| e.with_context(ErrorContext::new().with_host(host_id)))
             });
 
             handles.push(handle);
@@ -151,6 +310,8 @@ impl TransportManager {
                     Ok(conn) => task(conn).await,
                     Err(e) => Err(e),
                 };
+                let result = result
+                    .map_err(|e| e.with_context(ErrorContext::new().with_host(host_id_clone.as_str())));
                 (host_id_clone, result)
             });
 
@@ -174,6 +335,105 @@ impl TransportManager {
         results
     }
 
+    /// 并发列举所有主机上的虚拟机, 成功的结果会刷新虚拟机位置缓存
+    pub async fn list_all_domains(&self) -> Vec<(String, Result<Vec<LibvirtDomainInfo>>)> {
+        let results = self
+            .execute_on_all_hosts(|conn| async move { conn.list_domains().await })
+            .await;
+
+        for (host_id, result) in &results {
+            if let Ok(domains) = result {
+                self.domain_cache.update_host(host_id, domains.clone()).await;
+            }
+        }
+
+        results
+    }
+
+    /// 生成所有主机上虚拟机的只读快照
+    ///
+    /// 同时最多列举 `concurrency` 台主机, 连接尚未建立 (或已断开) 时先连接;
+    /// 列举失败的主机记录在 [`ClusterDomainSnapshot::errors`] 中。
+    /// 成功的结果同时刷新虚拟机位置缓存, 设置了 [`with_snapshot_ttl`](Self::with_snapshot_ttl)
+    /// 时在有效期内复用。`filter.hosts` 为 None 时列举所有已注册的主机。
+    pub async fn snapshot_all_domains(&self, filter: &DomainFilter, concurrency: usize) -> ClusterDomainSnapshot {
+        let host_ids = match &filter.hosts {
+            Some(hosts) => hosts.clone(),
+            None => self.list_hosts().await,
+        };
+
+        self.snapshot_cache
+            .snapshot(&host_ids, filter, concurrency, |host_id| async move {
+                let result = self
+                    .execute_on_host(&host_id, |conn| async move {
+                        if conn.state().await != ConnectionState::Connected {
+                            conn.connect().await?;
+                        }
+                        conn.list_domains().await
+                    })
+                    .await;
+                if let Ok(domains) = &result {
+                    self.domain_cache.update_host(&host_id, domains.clone()).await;
+                }
+                result
+            })
+            .await
+    }
+
+    /// 丢弃集群虚拟机快照的缓存
+    pub async fn invalidate_snapshot(&self) {
+        self.snapshot_cache.invalidate().await;
+    }
+
+    /// 按名称查找虚拟机所在的主机
+    ///
+    /// 先查缓存, 未命中时并行列举所有主机一次并刷新缓存。
+    /// 同名虚拟机存在于多台主机时返回 `AmbiguousDomain`。
+    pub async fn find_domain(&self, domain_name: &str) -> Result<Option<(String, LibvirtDomainInfo)>> {
+        let host_ids = self.list_hosts().await;
+        self.domain_cache
+            .find(domain_name, &host_ids, |host_id| async move {
+                self.execute_on_host(&host_id, |conn| async move { conn.list_domains().await })
+                    .await
+            })
+            .await
+    }
+
+    /// 在虚拟机所在的主机上执行任务
+    ///
+    /// # 示例
+    /// ```ignore
+    /// let domain = manager.execute_on_domain("win10", |conn, host_id| async move {
+    ///     conn.get_domain("win10").await
+    /// }).await?;
+    /// ```
+    pub async fn execute_on_domain<F, Fut, T>(&self, domain_name: &str, task: F) -> Result<T>
+    where
+        F: FnOnce(Arc<HostConnection>, String) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let (host_id, _) = self
+            .find_domain(domain_name)
+            .await?
+            .ok_or_else(|| TransportError::DomainNotFound(domain_name.to_string()))?;
+
+        let task_host_id = host_id.clone();
+        self.execute_on_host(&host_id, |conn| task(conn, task_host_id))
+            .await
+            .map_err(|e| e.with_context(ErrorContext::new().with_domain(domain_name)))
+    }
+
+    /// 在虚拟机所在的主机上解析其 XML 配置
+    pub async fn inspect_domain(&self, domain_name: &str) -> Result<DomainInspection> {
+        self.execute_on_domain(domain_name, |conn, _| async move { conn.inspect_domain(domain_name).await })
+            .await
+    }
+
+    /// 清空虚拟机位置缓存 (如虚拟机迁移后)
+    pub async fn invalidate_domain_cache(&self) {
+        self.domain_cache.clear().await;
+    }
+
     /// 获取所有主机的连接池统计信息
     pub async fn stats(&self) -> std::collections::HashMap<String, ConnectionPoolStats> {
         self.pool.stats().await
@@ -188,45 +448,40 @@ impl TransportManager {
     pub async fn active_connection_count(&self, host_id: &str) -> Result<usize> {
         self.pool.active_connection_count(host_id).await
     }
+}
 
-    // TODO: 数据库集成 - 添加性能指标持久化
-    //
-    // /// 保存连接指标到数据库
-    // pub async fn save_metrics(&self, storage: &atp_storage::Storage) -> Result<()> {
-    //     use chrono::Utc;
-    //     use atp_storage::ConnectionMetricRecord;
-    //
-    //     let stats = self.stats().await;
-    //
-    //     for (host_id, pool_stats) in stats {
-    //         let metrics = pool_stats.metrics();
-    //
-    //         let metric_record = ConnectionMetricRecord {
-    //             id: 0,
-    //             host_id: host_id.clone(),
-    //             timestamp: Utc::now(),
-    //             total_connections: pool_stats.total_connections as i32,
-    //             active_connections: pool_stats.active_connections as i32,
-    //             total_requests: metrics.total_requests as i64,
-    //             total_errors: metrics.total_errors as i64,
-    //             avg_response_time: None, // 可以计算平均响应时间
-    //         };
-    //
-    //         storage.metrics().create(&metric_record).await?;
-    //     }
-    //
-    //     Ok(())
-    // }
-    //
-    // 使用示例:
-    // let storage_manager = atp_storage::StorageManager::new("~/.config/atp/data.db").await?;
-    // let storage = atp_storage::Storage::from_manager(&storage_manager);
-    // manager.save_metrics(&storage).await?;
+/// 按主机导出连接池指标, 供 `MetricsCollector` 定期写库
+#[async_trait]
+impl MetricsSource for TransportManager {
+    fn source_name(&self) -> &str {
+        "transport"
+    }
+
+    async fn collect(&self) -> Vec<MetricSample> {
+        let mut samples = Vec::new();
+
+        for (host_id, stats) in self.stats().await {
+            let values = [
+                ("total_connections", stats.total_connections as f64),
+                ("active_connections", stats.active_connections as f64),
+                ("total_requests", stats.total_requests as f64),
+                ("total_errors", stats.total_errors as f64),
+                ("active_uses", stats.total_active_uses as f64),
+            ];
+
+            for (name, value) in values {
+                samples.push(MetricSample::new(name, value).with_label("host", &host_id));
+            }
+        }
+
+        samples
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::HostEntry;
 
     #[tokio::test]
     async fn test_transport_manager_creation() {
@@ -246,4 +501,63 @@ mod tests {
         // 暂时跳过实际连接测试
         // assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_from_config_registers_hosts() {
+        let config = TransportConfig::default()
+            .with_host(HostEntry::new("node-2", "192.0.2.12").with_uri("qemu+tcp://192.0.2.12/system"))
+            .with_host(HostEntry::new("node-1", "192.0.2.11"));
+        let manager = TransportManager::from_config(config).await.unwrap();
+
+        let mut hosts = manager.list_hosts().await;
+        hosts.sort();
+        assert_eq!(hosts, vec!["node-1", "node-2"]);
+        let conn = manager.pool().get_connection("node-1").await.unwrap();
+        assert_eq!(conn.host_info().uri, "qemu+ssh://192.0.2.11:22/system");
+        assert_eq!(manager.config().hosts.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_from_config_reports_all_problems() {
+        let mut config = TransportConfig::default()
+            .with_host(HostEntry::new("node-1", "192.0.2.11"))
+            .with_host(HostEntry::new("node-1", "192.0.2.12"))
+            .with_host(HostEntry::new("node-3", "192.0.2.13").with_uri("qemu+ssh:///system"));
+        config.pool.min_connections_per_host = 20;
+
+        let Err(TransportError::InvalidConfig(problems)) = TransportManager::from_config(config).await else {
+            panic!("配置应当无效");
+        };
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[1].contains("node-1 重复"), "{:?}", problems);
+        assert!(problems[2].contains("node-3"), "{:?}", problems);
+    }
+
+    #[tokio::test]
+    async fn test_drain_host() {
+        let manager = TransportManager::default();
+        manager.add_host(HostInfo::new("drain-host", "192.0.2.10")).await.unwrap();
+
+        // 任务持有连接期间摘除, 超时后强制断开
+        let conn = manager.pool().get_connection("drain-host").await.unwrap();
+        let busy = manager.drain_host("drain-host", Duration::from_millis(100)).await.unwrap();
+        assert_eq!(busy, 1);
+        assert!(manager.list_hosts().await.is_empty());
+        assert!(manager.pool().get_connection("drain-host").await.is_err());
+        drop(conn);
+
+        manager.add_host(HostInfo::new("drain-host", "192.0.2.10")).await.unwrap();
+        assert_eq!(manager.drain_host("drain-host", Duration::from_secs(5)).await.unwrap(), 0);
+        assert!(matches!(
+            manager.drain_host("drain-host", Duration::from_secs(1)).await,
+            Err(TransportError::HostNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_metrics_source_without_hosts() {
+        let manager = TransportManager::default();
+        assert_eq!(manager.source_name(), "transport");
+        assert!(manager.collect().await.is_empty());
+    }
 }
@@ -2,6 +2,7 @@
 
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{RwLock, Mutex};
 use tracing::{debug, info, warn};
 
@@ -114,6 +115,36 @@ impl ConnectionPool {
         }
     }
 
+    /// 摘除主机: 立即停止分配新连接, 等待已取出的连接用完 (最多 `timeout`) 后断开
+    ///
+    /// 返回超时后仍在使用、被强制断开的连接数。
+    pub async fn drain_host(&self, host_id: &str, timeout: Duration) -> Result<usize> {
+        info!("摘除主机: {}", host_id);
+
+        let host_conns = self
+            .hosts
+            .write()
+            .await
+            .remove(host_id)
+            .ok_or_else(|| TransportError::HostNotFound(host_id.to_string()))?;
+
+        // 连接池之外仍持有的引用即为正在使用的连接
+        let in_use = |conns: &[Arc<HostConnection>]| conns.iter().filter(|conn| Arc::strong_count(conn) > 1).count();
+        let deadline = tokio::time::Instant::now() + timeout;
+        while in_use(&host_conns.connections) > 0 && tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        let busy = in_use(&host_conns.connections);
+        if busy > 0 {
+            warn!("主机 {} 仍有 {} 个连接在使用, 强制断开", host_id, busy);
+        }
+        for conn in host_conns.connections {
+            let _ = conn.disconnect().await;
+        }
+        Ok(busy)
+    }
+
     /// 获取连接（根据配置的策略）
     pub async fn get_connection(&self, host_id: &str) -> Result<Arc<HostConnection>> {
         match self.config.selection_strategy {
@@ -0,0 +1,632 @@
+//! SFTP 文件传输
+//!
+//! 收集宿主机上的日志 (如 `/var/log/libvirt/qemu/*.log`) 或下发文件时, 通过 ssh 的 `sftp`
+//! 子系统传输文件。这里实现 SFTP 协议第 3 版中文件传输所需的最小子集 (打开、读写、
+//! 关闭、stat、修改权限), 会话由 [`SshPool`](crate::SshPool) 建立在主机的 SSH 主连接之上。
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Result, TransportError};
+
+/// 传输进度回调, 参数为 (已传输字节数, 总字节数)
+pub type TransferProgress<'a> = &'a (dyn Fn(u64, u64) + Send + Sync);
+
+/// 远端文件信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStat {
+    /// 文件大小 (字节)
+    pub size: u64,
+
+    /// 权限位 (如 0o644)
+    pub mode: u32,
+
+    /// 修改时间 (Unix 时间戳, 秒)
+    pub mtime: Option<u64>,
+
+    /// 是否为目录
+    pub is_dir: bool,
+}
+
+const SFTP_VERSION: u32 = 3;
+
+/// 单次读写的数据块大小 (OpenSSH 单个请求上限为 256 KiB)
+const CHUNK_SIZE: u32 = 32 * 1024;
+
+/// 响应包长度上限, 防止异常数据导致分配过大的缓冲区
+const MAX_PACKET_LEN: u32 = 1024 * 1024;
+
+const FXP_INIT: u8 = 1;
+const FXP_VERSION: u8 = 2;
+const FXP_OPEN: u8 = 3;
+const FXP_CLOSE: u8 = 4;
+const FXP_READ: u8 = 5;
+const FXP_WRITE: u8 = 6;
+const FXP_FSETSTAT: u8 = 10;
+const FXP_STAT: u8 = 17;
+const FXP_STATUS: u8 = 101;
+const FXP_HANDLE: u8 = 102;
+const FXP_DATA: u8 = 103;
+const FXP_ATTRS: u8 = 105;
+
+const FXF_READ: u32 = 0x01;
+const FXF_WRITE: u32 = 0x02;
+const FXF_CREAT: u32 = 0x08;
+const FXF_TRUNC: u32 = 0x10;
+
+const ATTR_SIZE: u32 = 0x01;
+const ATTR_UIDGID: u32 = 0x02;
+const ATTR_PERMISSIONS: u32 = 0x04;
+const ATTR_ACMODTIME: u32 = 0x08;
+const ATTR_EXTENDED: u32 = 0x8000_0000;
+
+const FX_OK: u32 = 0;
+const FX_EOF: u32 = 1;
+const FX_NO_SUCH_FILE: u32 = 2;
+const FX_PERMISSION_DENIED: u32 = 3;
+
+/// 文件类型位 (S_IFMT) 与目录类型
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+
+/// SFTP 会话 (请求逐个发送, 等待响应后再发送下一个)
+pub(crate) struct SftpSession<R, W> {
+    reader: R,
+    writer: W,
+    next_id: u32,
+}
+
+impl<R, W> SftpSession<R, W>
+where
+    R: AsyncRead + Unpin + Send,
+    W: AsyncWrite + Unpin + Send,
+{
+    /// 协商协议版本
+    pub(crate) async fn init(reader: R, writer: W) -> Result<Self> {
+        let mut session = Self { reader, writer, next_id: 0 };
+
+        let mut packet = Packet::new(FXP_INIT);
+        packet.put_u32(SFTP_VERSION);
+        session.send(packet).await?;
+
+        let (packet_type, mut body) = session.receive().await?;
+        if packet_type != FXP_VERSION {
+            return Err(sftp_error(format!("握手响应类型错误: {}", packet_type)));
+        }
+        let version = body.u32()?;
+        if version < SFTP_VERSION {
+            return Err(sftp_error(format!("不支持的 SFTP 协议版本: {}", version)));
+        }
+        Ok(session)
+    }
+
+    /// 查询远端文件信息 (跟随符号链接)
+    pub(crate) async fn stat(&mut self, path: &str) -> Result<FileStat> {
+        let mut packet = self.request(FXP_STAT);
+        packet.put_str(path);
+        let (packet_type, mut body) = self.call(packet).await?;
+        match packet_type {
+            FXP_ATTRS => read_attrs(&mut body),
+            _ => Err(status_error(packet_type, &mut body, path)),
+        }
+    }
+
+    /// 上传本地文件, 并把远端文件权限设置为 `mode`, 返回传输的字节数
+    pub(crate) async fn upload(
+        &mut self,
+        local: &Path,
+        remote: &str,
+        mode: u32,
+        progress: Option<TransferProgress<'_>>,
+    ) -> Result<u64> {
+        let mut file = tokio::fs::File::open(local).await?;
+        let total = file.metadata().await?.len();
+
+        let handle = self.open(remote, FXF_WRITE | FXF_CREAT | FXF_TRUNC, Some(mode)).await?;
+        let result = async {
+            let mut buf = vec![0u8; CHUNK_SIZE as usize];
+            let mut offset = 0u64;
+            loop {
+                let n = file.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                self.write_chunk(&handle, offset, &buf[..n], remote).await?;
+                offset += n as u64;
+                if let Some(progress) = progress {
+                    progress(offset, total.max(offset));
+                }
+            }
+            // 打开时的权限受远端 umask 影响, 且不会修改已存在文件的权限
+            self.set_mode(&handle, mode, remote).await?;
+            Ok(offset)
+        }
+        .await;
+
+        self.close(&handle, remote).await?;
+        result
+    }
+
+    /// 下载远端文件到本地 (自动创建上级目录), 返回传输的字节数
+    pub(crate) async fn download(
+        &mut self,
+        remote: &str,
+        local: &Path,
+        progress: Option<TransferProgress<'_>>,
+    ) -> Result<u64> {
+        let total = self.stat(remote).await?.size;
+        let handle = self.open(remote, FXF_READ, None).await?;
+
+        let result = async {
+            if let Some(parent) = local.parent().filter(|p| !p.as_os_str().is_empty()) {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            let mut file = tokio::fs::File::create(local).await?;
+
+            // 日志文件可能仍在增长, 读到 EOF 为止
+            let mut offset = 0u64;
+            while let Some(data) = self.read_chunk(&handle, offset, CHUNK_SIZE, remote).await? {
+                file.write_all(&data).await?;
+                offset += data.len() as u64;
+                if let Some(progress) = progress {
+                    progress(offset, total.max(offset));
+                }
+            }
+            file.flush().await?;
+            Ok(offset)
+        }
+        .await;
+
+        self.close(&handle, remote).await?;
+        result
+    }
+
+    /// 读取远端文件开头的至多 `max_bytes` 字节 (非 UTF-8 字符被替换)
+    pub(crate) async fn read_to_string(&mut self, remote: &str, max_bytes: usize) -> Result<String> {
+        let handle = self.open(remote, FXF_READ, None).await?;
+
+        let result = async {
+            let mut content = Vec::new();
+            while content.len() < max_bytes {
+                let len = (max_bytes - content.len()).min(CHUNK_SIZE as usize) as u32;
+                match self.read_chunk(&handle, content.len() as u64, len, remote).await? {
+                    Some(data) => content.extend_from_slice(&data),
+                    None => break,
+                }
+            }
+            content.truncate(max_bytes);
+            Ok(String::from_utf8_lossy(&content).into_owned())
+        }
+        .await;
+
+        self.close(&handle, remote).await?;
+        result
+    }
+
+    async fn open(&mut self, path: &str, pflags: u32, mode: Option<u32>) -> Result<Vec<u8>> {
+        let mut packet = self.request(FXP_OPEN);
+        packet.put_str(path);
+        packet.put_u32(pflags);
+        match mode {
+            Some(mode) => {
+                packet.put_u32(ATTR_PERMISSIONS);
+                packet.put_u32(mode);
+            }
+            None => packet.put_u32(0),
+        }
+
+        let (packet_type, mut body) = self.call(packet).await?;
+        match packet_type {
+            FXP_HANDLE => body.bytes().map(<[u8]>::to_vec),
+            _ => Err(status_error(packet_type, &mut body, path)),
+        }
+    }
+
+    async fn close(&mut self, handle: &[u8], path: &str) -> Result<()> {
+        let mut packet = self.request(FXP_CLOSE);
+        packet.put_bytes(handle);
+        self.expect_ok(packet, path).await
+    }
+
+    /// 读取一块数据, 到达文件末尾时返回 None
+    async fn read_chunk(&mut self, handle: &[u8], offset: u64, len: u32, path: &str) -> Result<Option<Vec<u8>>> {
+        let mut packet = self.request(FXP_READ);
+        packet.put_bytes(handle);
+        packet.put_u64(offset);
+        packet.put_u32(len);
+
+        let (packet_type, mut body) = self.call(packet).await?;
+        match packet_type {
+            FXP_DATA => body.bytes().map(|data| Some(data.to_vec())),
+            FXP_STATUS if body.peek_u32() == Some(FX_EOF) => Ok(None),
+            _ => Err(status_error(packet_type, &mut body, path)),
+        }
+    }
+
+    async fn write_chunk(&mut self, handle: &[u8], offset: u64, data: &[u8], path: &str) -> Result<()> {
+        let mut packet = self.request(FXP_WRITE);
+        packet.put_bytes(handle);
+        packet.put_u64(offset);
+        packet.put_bytes(data);
+        self.expect_ok(packet, path).await
+    }
+
+    async fn set_mode(&mut self, handle: &[u8], mode: u32, path: &str) -> Result<()> {
+        let mut packet = self.request(FXP_FSETSTAT);
+        packet.put_bytes(handle);
+        packet.put_u32(ATTR_PERMISSIONS);
+        packet.put_u32(mode);
+        self.expect_ok(packet, path).await
+    }
+
+    /// 创建带请求 ID 的请求包
+    fn request(&mut self, packet_type: u8) -> Packet {
+        self.next_id = self.next_id.wrapping_add(1);
+        let mut packet = Packet::new(packet_type);
+        packet.put_u32(self.next_id);
+        packet
+    }
+
+    /// 发送请求并等待响应, 返回去掉请求 ID 的响应
+    async fn call(&mut self, packet: Packet) -> Result<(u8, Reader)> {
+        self.send(packet).await?;
+        let (packet_type, mut body) = self.receive().await?;
+        let id = body.u32()?;
+        if id != self.next_id {
+            return Err(sftp_error(format!("响应 ID 不匹配: 期望 {}, 收到 {}", self.next_id, id)));
+        }
+        Ok((packet_type, body))
+    }
+
+    async fn expect_ok(&mut self, packet: Packet, path: &str) -> Result<()> {
+        let (packet_type, mut body) = self.call(packet).await?;
+        if packet_type == FXP_STATUS && body.peek_u32() == Some(FX_OK) {
+            return Ok(());
+        }
+        Err(status_error(packet_type, &mut body, path))
+    }
+
+    async fn send(&mut self, packet: Packet) -> Result<()> {
+        let data = packet.finish();
+        self.writer.write_all(&data).await?;
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<(u8, Reader)> {
+        let len = self.reader.read_u32().await.map_err(disconnected)?;
+        if len == 0 || len > MAX_PACKET_LEN {
+            return Err(sftp_error(format!("响应长度异常: {}", len)));
+        }
+        let mut data = vec![0u8; len as usize];
+        self.reader.read_exact(&mut data).await.map_err(disconnected)?;
+        let packet_type = data[0];
+        Ok((packet_type, Reader { data, pos: 1 }))
+    }
+}
+
+/// 读取 ATTRS 结构
+fn read_attrs(body: &mut Reader) -> Result<FileStat> {
+    let flags = body.u32()?;
+    let size = if flags & ATTR_SIZE != 0 { body.u64()? } else { 0 };
+    if flags & ATTR_UIDGID != 0 {
+        body.u32()?;
+        body.u32()?;
+    }
+    let permissions = if flags & ATTR_PERMISSIONS != 0 { body.u32()? } else { 0 };
+    let mtime = if flags & ATTR_ACMODTIME != 0 {
+        body.u32()?;
+        Some(u64::from(body.u32()?))
+    } else {
+        None
+    };
+    if flags & ATTR_EXTENDED != 0 {
+        for _ in 0..body.u32()? {
+            body.bytes()?;
+            body.bytes()?;
+        }
+    }
+
+    Ok(FileStat {
+        size,
+        mode: permissions & 0o7777,
+        mtime,
+        is_dir: permissions & S_IFMT == S_IFDIR,
+    })
+}
+
+/// 把失败响应转换为错误, 文件不存在与没有权限分别对应独立的错误类型
+fn status_error(packet_type: u8, body: &mut Reader, path: &str) -> TransportError {
+    if packet_type != FXP_STATUS {
+        return sftp_error(format!("意外的响应类型: {}", packet_type));
+    }
+
+    let code = match body.u32() {
+        Ok(code) => code,
+        Err(e) => return e,
+    };
+    let message = body
+        .bytes()
+        .map(|msg| String::from_utf8_lossy(msg).into_owned())
+        .unwrap_or_default();
+
+    match code {
+        FX_NO_SUCH_FILE => TransportError::FileNotFound(path.to_string()),
+        FX_PERMISSION_DENIED => TransportError::PermissionDenied(path.to_string()),
+        _ => sftp_error(format!("{}: {} (状态码 {})", path, message, code)),
+    }
+}
+
+fn sftp_error(message: String) -> TransportError {
+    TransportError::SftpError(message)
+}
+
+/// 对端关闭了会话 (通常是 ssh 连接失败或远端未启用 sftp 子系统)
+fn disconnected(e: std::io::Error) -> TransportError {
+    if e.kind() == std::io::ErrorKind::UnexpectedEof {
+        TransportError::Disconnected
+    } else {
+        TransportError::IoError(e)
+    }
+}
+
+/// 待发送的数据包
+struct Packet {
+    data: Vec<u8>,
+}
+
+impl Packet {
+    fn new(packet_type: u8) -> Self {
+        // 前 4 字节为长度, 发送前填写
+        Self { data: vec![0, 0, 0, 0, packet_type] }
+    }
+
+    fn put_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn put_bytes(&mut self, value: &[u8]) {
+        self.put_u32(value.len() as u32);
+        self.data.extend_from_slice(value);
+    }
+
+    fn put_str(&mut self, value: &str) {
+        self.put_bytes(value.as_bytes());
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let len = (self.data.len() - 4) as u32;
+        self.data[..4].copy_from_slice(&len.to_be_bytes());
+        self.data
+    }
+}
+
+/// 收到的数据包 (不含长度与类型)
+struct Reader {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl Reader {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len());
+        let end = end.ok_or_else(|| sftp_error("响应数据不完整".to_string()))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        self.take(4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.take(8).map(|b| u64::from_be_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+
+    fn peek_u32(&self) -> Option<u32> {
+        self.data.get(self.pos..self.pos + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn bytes(&mut self) -> Result<&[u8]> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+    type Files = Arc<Mutex<HashMap<String, (Vec<u8>, u32)>>>;
+
+    /// 内存中的 SFTP 服务端: `/root/` 下的路径没有权限
+    async fn serve(mut stream: DuplexStream, files: Files) {
+        let mut handles: HashMap<Vec<u8>, String> = HashMap::new();
+        loop {
+            let Ok(len) = stream.read_u32().await else { return };
+            let mut data = vec![0u8; len as usize];
+            stream.read_exact(&mut data).await.unwrap();
+            let packet_type = data[0];
+            let mut req = Reader { data, pos: 1 };
+
+            if packet_type == FXP_INIT {
+                let mut resp = Packet::new(FXP_VERSION);
+                resp.put_u32(SFTP_VERSION);
+                stream.write_all(&resp.finish()).await.unwrap();
+                continue;
+            }
+
+            let id = req.u32().unwrap();
+            let status = |code: u32| {
+                let mut resp = Packet::new(FXP_STATUS);
+                resp.put_u32(id);
+                resp.put_u32(code);
+                resp.put_str("");
+                resp.put_str("");
+                resp
+            };
+            let denied = |path: &str| path.starts_with("/root/");
+
+            let resp = match packet_type {
+                FXP_OPEN | FXP_STAT => {
+                    let path = String::from_utf8(req.bytes().unwrap().to_vec()).unwrap();
+                    let mut files = files.lock().unwrap();
+                    if denied(&path) {
+                        status(FX_PERMISSION_DENIED)
+                    } else if packet_type == FXP_STAT {
+                        match files.get(&path) {
+                            Some((content, mode)) => {
+                                let mut resp = Packet::new(FXP_ATTRS);
+                                resp.put_u32(id);
+                                resp.put_u32(ATTR_SIZE | ATTR_PERMISSIONS);
+                                resp.put_u64(content.len() as u64);
+                                resp.put_u32(0o100000 | mode);
+                                resp
+                            }
+                            None => status(FX_NO_SUCH_FILE),
+                        }
+                    } else {
+                        let pflags = req.u32().unwrap();
+                        if pflags & FXF_CREAT != 0 {
+                            // 模拟 umask 022
+                            let mode = if req.u32().unwrap() & ATTR_PERMISSIONS != 0 { req.u32().unwrap() } else { 0o666 };
+                            files.insert(path.clone(), (Vec::new(), mode & !0o022));
+                        }
+                        if files.contains_key(&path) {
+                            let handle = format!("h{}", id).into_bytes();
+                            handles.insert(handle.clone(), path);
+                            let mut resp = Packet::new(FXP_HANDLE);
+                            resp.put_u32(id);
+                            resp.put_bytes(&handle);
+                            resp
+                        } else {
+                            status(FX_NO_SUCH_FILE)
+                        }
+                    }
+                }
+                FXP_READ => {
+                    let path = &handles[req.bytes().unwrap()];
+                    let (offset, len) = (req.u64().unwrap() as usize, req.u32().unwrap() as usize);
+                    let files = files.lock().unwrap();
+                    let content = &files[path].0;
+                    if offset >= content.len() {
+                        status(FX_EOF)
+                    } else {
+                        let mut resp = Packet::new(FXP_DATA);
+                        resp.put_u32(id);
+                        resp.put_bytes(&content[offset..(offset + len).min(content.len())]);
+                        resp
+                    }
+                }
+                FXP_WRITE => {
+                    let path = handles[req.bytes().unwrap()].clone();
+                    let offset = req.u64().unwrap() as usize;
+                    let data = req.bytes().unwrap().to_vec();
+                    let mut files = files.lock().unwrap();
+                    let content = &mut files.get_mut(&path).unwrap().0;
+                    content.resize(offset, 0);
+                    content.extend_from_slice(&data);
+                    status(FX_OK)
+                }
+                FXP_FSETSTAT => {
+                    let path = handles[req.bytes().unwrap()].clone();
+                    assert_eq!(req.u32().unwrap(), ATTR_PERMISSIONS);
+                    files.lock().unwrap().get_mut(&path).unwrap().1 = req.u32().unwrap();
+                    status(FX_OK)
+                }
+                FXP_CLOSE => {
+                    handles.remove(req.bytes().unwrap());
+                    status(FX_OK)
+                }
+                _ => status(8),
+            };
+            stream.write_all(&resp.finish()).await.unwrap();
+        }
+    }
+
+    async fn session(files: &Files) -> SftpSession<ReadHalf<DuplexStream>, WriteHalf<DuplexStream>> {
+        let (client, server) = tokio::io::duplex(64 * 1024);
+        tokio::spawn(serve(server, files.clone()));
+        let (reader, writer) = tokio::io::split(client);
+        SftpSession::init(reader, writer).await.unwrap()
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("atp-sftp-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_upload_download_roundtrip() {
+        let files = Files::default();
+        let mut sftp = session(&files).await;
+        let dir = temp_dir("roundtrip");
+
+        // 跨多个数据块
+        let content: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(dir.join("disk.log"), &content).unwrap();
+
+        let progress = Mutex::new(Vec::new());
+        let record = |done: u64, total: u64| progress.lock().unwrap().push((done, total));
+        let sent = sftp.upload(&dir.join("disk.log"), "/tmp/disk.log", 0o640, Some(&record)).await.unwrap();
+        assert_eq!(sent, content.len() as u64);
+        assert_eq!(progress.lock().unwrap().last(), Some(&(100_000, 100_000)));
+
+        let stat = sftp.stat("/tmp/disk.log").await.unwrap();
+        assert_eq!((stat.size, stat.mode, stat.is_dir), (100_000, 0o640, false));
+
+        progress.lock().unwrap().clear();
+        let local = dir.join("fetched/disk.log");
+        let received = sftp.download("/tmp/disk.log", &local, Some(&record)).await.unwrap();
+        assert_eq!(received, content.len() as u64);
+        assert_eq!(std::fs::read(&local).unwrap(), content);
+        assert_eq!(progress.lock().unwrap().last(), Some(&(100_000, 100_000)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_to_string_limit() {
+        let files = Files::default();
+        files.lock().unwrap().insert("/var/log/libvirt/qemu/vm1.log".to_string(), (b"line1\nline2\n".to_vec(), 0o600));
+        let mut sftp = session(&files).await;
+
+        assert_eq!(sftp.read_to_string("/var/log/libvirt/qemu/vm1.log", 5).await.unwrap(), "line1");
+        assert_eq!(sftp.read_to_string("/var/log/libvirt/qemu/vm1.log", 1024).await.unwrap(), "line1\nline2\n");
+    }
+
+    #[tokio::test]
+    async fn test_missing_and_denied_paths() {
+        let files = Files::default();
+        let mut sftp = session(&files).await;
+        let dir = temp_dir("errors");
+
+        let err = sftp.stat("/var/log/missing.log").await.unwrap_err();
+        assert!(matches!(err, TransportError::FileNotFound(ref path) if path == "/var/log/missing.log"));
+        let err = sftp.download("/var/log/missing.log", &dir.join("x"), None).await.unwrap_err();
+        assert!(matches!(err, TransportError::FileNotFound(_)));
+
+        let err = sftp.read_to_string("/root/secret", 16).await.unwrap_err();
+        assert!(matches!(err, TransportError::PermissionDenied(ref path) if path == "/root/secret"));
+        std::fs::write(dir.join("a"), "a").unwrap();
+        let err = sftp.upload(&dir.join("a"), "/root/a", 0o600, None).await.unwrap_err();
+        assert!(matches!(err, TransportError::PermissionDenied(_)));
+
+        // 出错后会话仍然可用
+        assert!(sftp.upload(&dir.join("a"), "/tmp/a", 0o600, None).await.is_ok());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,373 @@
+//! 集群虚拟机快照
+//!
+//! 一致性比对等只读操作需要所有主机上的虚拟机列表。逐台串行列举在 20 台主机的集群上
+//! 要花费数十秒, [`SnapshotCache::snapshot`] 以有限并发同时列举各主机, 汇总为一份带主机 ID
+//! 的只读快照, 列举失败的主机单独记录, 不影响其他主机。
+//!
+//! 缓存有效期 (TTL) 大于 0 时, 成功的列举结果在有效期内复用, 连续执行多个命令时不会
+//! 重复访问主机; 失败的主机每次都会重新列举。
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, StreamExt};
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::{LibvirtDomainInfo, Result};
+
+/// 快照的筛选条件
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DomainFilter {
+    /// 只列举这些主机 (None 表示所有已注册的主机)
+    pub hosts: Option<Vec<String>>,
+
+    /// 虚拟机名称包含的子串
+    pub name_contains: Option<String>,
+
+    /// 只保留运行中的虚拟机
+    pub running_only: bool,
+}
+
+impl DomainFilter {
+    /// 不筛选
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// 只列举指定的主机
+    pub fn with_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.hosts = Some(hosts);
+        self
+    }
+
+    /// 只保留名称包含 `pattern` 的虚拟机
+    pub fn with_name_contains(mut self, pattern: &str) -> Self {
+        self.name_contains = Some(pattern.to_string());
+        self
+    }
+
+    /// 只保留运行中的虚拟机
+    pub fn with_running_only(mut self) -> Self {
+        self.running_only = true;
+        self
+    }
+
+    /// 虚拟机是否满足条件
+    pub fn matches(&self, domain: &LibvirtDomainInfo) -> bool {
+        if self.running_only && !domain.is_running() {
+            return false;
+        }
+        self.name_contains
+            .as_deref()
+            .is_none_or(|pattern| domain.name.contains(pattern))
+    }
+}
+
+/// 带所在主机 ID 的虚拟机信息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostDomainInfo {
+    /// 主机 ID
+    pub host_id: String,
+
+    /// 虚拟机信息
+    pub domain: LibvirtDomainInfo,
+}
+
+/// 列举失败的主机
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostSnapshotError {
+    /// 主机 ID
+    pub host_id: String,
+
+    /// 错误信息
+    pub error: String,
+}
+
+/// 所有主机上虚拟机的只读快照
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClusterDomainSnapshot {
+    /// 采集时间 (使用了缓存时为其中最早的一次列举)
+    pub captured_at: DateTime<Utc>,
+
+    /// 满足筛选条件的虚拟机, 按主机 ID 与名称排序
+    pub domains: Vec<HostDomainInfo>,
+
+    /// 列举成功的主机 (按 ID 排序)
+    pub hosts: Vec<String>,
+
+    /// 列举失败的主机 (按 ID 排序)
+    pub errors: Vec<HostSnapshotError>,
+
+    /// 来自缓存的主机数
+    pub cached_hosts: usize,
+}
+
+impl ClusterDomainSnapshot {
+    /// 某台主机上的虚拟机
+    pub fn domains_on<'a>(&'a self, host_id: &'a str) -> impl Iterator<Item = &'a LibvirtDomainInfo> + 'a {
+        self.domains
+            .iter()
+            .filter(move |entry| entry.host_id == host_id)
+            .map(|entry| &entry.domain)
+    }
+
+    /// 按名称查找虚拟机, 同名虚拟机可能出现在多台主机上
+    pub fn locate(&self, domain_name: &str) -> Vec<&HostDomainInfo> {
+        self.domains
+            .iter()
+            .filter(|entry| entry.domain.name == domain_name)
+            .collect()
+    }
+
+    /// 所有主机是否都列举成功
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+/// 一台主机的缓存列举结果
+#[derive(Debug)]
+struct CachedListing {
+    captured_at: DateTime<Utc>,
+    fetched: Instant,
+    domains: Vec<LibvirtDomainInfo>,
+}
+
+/// 按主机缓存的列举结果 (TTL 为 0 时不缓存)
+#[derive(Debug, Default)]
+pub struct SnapshotCache {
+    ttl: Duration,
+    hosts: Mutex<HashMap<String, CachedListing>>,
+}
+
+impl SnapshotCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 缓存有效期
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// 丢弃所有缓存
+    pub async fn invalidate(&self) {
+        self.hosts.lock().await.clear();
+    }
+
+    /// 丢弃某台主机的缓存
+    pub async fn invalidate_host(&self, host_id: &str) {
+        self.hosts.lock().await.remove(host_id);
+    }
+
+    /// 生成快照: 缓存未命中的主机用 `list` 列举, 同时最多 `concurrency` 台
+    pub async fn snapshot<L, Fut>(
+        &self,
+        host_ids: &[String],
+        filter: &DomainFilter,
+        concurrency: usize,
+        list: L,
+    ) -> ClusterDomainSnapshot
+    where
+        L: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Vec<LibvirtDomainInfo>>>,
+    {
+        let mut listings: Vec<(String, DateTime<Utc>, Vec<LibvirtDomainInfo>)> = Vec::new();
+        let mut pending = Vec::new();
+
+        {
+            let cached = self.hosts.lock().await;
+            for host_id in host_ids {
+                match cached.get(host_id).filter(|entry| entry.fetched.elapsed() < self.ttl) {
+                    Some(entry) => listings.push((host_id.clone(), entry.captured_at, entry.domains.clone())),
+                    None => pending.push(host_id.clone()),
+                }
+            }
+        }
+        let cached_hosts = listings.len();
+
+        let fetched: Vec<(String, DateTime<Utc>, Result<Vec<LibvirtDomainInfo>>)> = stream::iter(pending)
+            .map(|host_id| {
+                let listing = list(host_id.clone());
+                async move {
+                    let captured_at = Utc::now();
+                    (host_id, captured_at, listing.await)
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+
+        let mut errors = Vec::new();
+        {
+            let mut cached = self.hosts.lock().await;
+            for (host_id, captured_at, result) in fetched {
+                match result {
+                    Ok(domains) => {
+                        if !self.ttl.is_zero() {
+                            cached.insert(
+                                host_id.clone(),
+                                CachedListing {
+                                    captured_at,
+                                    fetched: Instant::now(),
+                                    domains: domains.clone(),
+                                },
+                            );
+                        }
+                        listings.push((host_id, captured_at, domains));
+                    }
+                    Err(e) => {
+                        cached.remove(&host_id);
+                        errors.push(HostSnapshotError {
+                            host_id,
+                            error: e.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        listings.sort_by(|a, b| a.0.cmp(&b.0));
+        errors.sort_by(|a, b| a.host_id.cmp(&b.host_id));
+
+        let captured_at = listings
+            .iter()
+            .map(|(_, captured_at, _)| *captured_at)
+            .min()
+            .unwrap_or_else(Utc::now);
+        let hosts = listings.iter().map(|(host_id, _, _)| host_id.clone()).collect();
+        let mut domains: Vec<HostDomainInfo> = listings
+            .into_iter()
+            .flat_map(|(host_id, _, domains)| {
+                domains.into_iter().map(move |domain| HostDomainInfo {
+                    host_id: host_id.clone(),
+                    domain,
+                })
+            })
+            .filter(|entry| filter.matches(&entry.domain))
+            .collect();
+        domains.sort_by(|a, b| a.host_id.cmp(&b.host_id).then_with(|| a.domain.name.cmp(&b.domain.name)));
+
+        ClusterDomainSnapshot {
+            captured_at,
+            domains,
+            hosts,
+            errors,
+            cached_hosts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TransportError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn domain(name: &str, state: u32) -> LibvirtDomainInfo {
+        LibvirtDomainInfo {
+            name: name.to_string(),
+            uuid: format!("uuid-{}", name),
+            state,
+            vcpus: 2,
+            memory_kb: 4 * 1024 * 1024,
+        }
+    }
+
+    /// 模拟的主机: host1 上有 win10 (运行) 与 win7 (关机), host2 上有 ubuntu, host3 无法连接
+    fn listing(host_id: &str) -> Result<Vec<LibvirtDomainInfo>> {
+        match host_id {
+            "host1" => Ok(vec![domain("win7", 5), domain("win10", 1)]),
+            "host2" => Ok(vec![domain("ubuntu", 1)]),
+            other => Err(TransportError::HostNotFound(other.to_string())),
+        }
+    }
+
+    fn hosts() -> Vec<String> {
+        vec!["host3".to_string(), "host2".to_string(), "host1".to_string()]
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_tags_hosts_and_collects_errors() {
+        let cache = SnapshotCache::new(Duration::ZERO);
+        let snapshot = cache
+            .snapshot(&hosts(), &DomainFilter::all(), 2, |host_id| async move { listing(&host_id) })
+            .await;
+
+        let tagged: Vec<(&str, &str)> = snapshot
+            .domains
+            .iter()
+            .map(|entry| (entry.host_id.as_str(), entry.domain.name.as_str()))
+            .collect();
+        assert_eq!(tagged, vec![("host1", "win10"), ("host1", "win7"), ("host2", "ubuntu")]);
+        assert_eq!(snapshot.hosts, vec!["host1", "host2"]);
+        assert_eq!(snapshot.errors.len(), 1);
+        assert_eq!(snapshot.errors[0].host_id, "host3");
+        assert!(!snapshot.is_complete());
+        assert_eq!(snapshot.domains_on("host1").count(), 2);
+        assert_eq!(snapshot.locate("ubuntu")[0].host_id, "host2");
+
+        let filter = DomainFilter::all().with_running_only().with_name_contains("win");
+        let snapshot = cache
+            .snapshot(&hosts(), &filter, 2, |host_id| async move { listing(&host_id) })
+            .await;
+        let names: Vec<&str> = snapshot.domains.iter().map(|entry| entry.domain.name.as_str()).collect();
+        assert_eq!(names, vec!["win10"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_snapshot_cache_ttl() {
+        let cache = SnapshotCache::new(Duration::from_secs(30));
+        let queries = AtomicUsize::new(0);
+        let list = |host_id: String| {
+            queries.fetch_add(1, Ordering::SeqCst);
+            async move { listing(&host_id) }
+        };
+
+        let first = cache.snapshot(&hosts(), &DomainFilter::all(), 4, &list).await;
+        assert_eq!((queries.load(Ordering::SeqCst), first.cached_hosts), (3, 0));
+
+        // 有效期内: 成功的主机走缓存, 失败的主机重新列举
+        tokio::time::advance(Duration::from_secs(10)).await;
+        let second = cache.snapshot(&hosts(), &DomainFilter::all(), 4, &list).await;
+        assert_eq!((queries.load(Ordering::SeqCst), second.cached_hosts), (4, 2));
+        assert_eq!(second.domains, first.domains);
+        assert_eq!(second.captured_at, first.captured_at);
+
+        // 过期后全部重新列举
+        tokio::time::advance(Duration::from_secs(30)).await;
+        let third = cache.snapshot(&hosts(), &DomainFilter::all(), 4, &list).await;
+        assert_eq!((queries.load(Ordering::SeqCst), third.cached_hosts), (7, 0));
+
+        cache.invalidate().await;
+        cache.snapshot(&hosts(), &DomainFilter::all(), 4, &list).await;
+        assert_eq!(queries.load(Ordering::SeqCst), 10);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_respects_concurrency() {
+        let cache = SnapshotCache::new(Duration::ZERO);
+        let in_flight = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let host_ids: Vec<String> = (0..8).map(|i| format!("host{}", i)).collect();
+
+        let snapshot = cache
+            .snapshot(&host_ids, &DomainFilter::all(), 3, |_| async {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(current, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(Vec::new())
+            })
+            .await;
+
+        assert_eq!(snapshot.hosts.len(), 8);
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,977 @@
+//! SSH 连接复用
+//!
+//! 批量检查需要在几十台主机上各执行多条命令, 每条命令新建一次 SSH 连接会触发
+//! sshd 的 `MaxStartups` 限流。`SshPool` 借助 OpenSSH 的 ControlMaster 为每个
+//! (主机, 用户, 端口) 维持一条主连接, 之后的命令作为该连接上的会话执行:
+//!
+//! - 主连接空闲超过 `idle_timeout` 后由 ssh 自行退出 (`ControlPersist`)
+//! - 主连接数达到 `max_connections` 时关闭最久未使用的主连接
+//! - 主连接通过 `ServerAliveInterval` 保活; 复用的主连接失效 (ssh 退出码 255) 时
+//!   关闭该主连接并重试一次, 重试时自动建立新的主连接
+//!
+//! 远端命令自身以 255 退出时同样会触发一次重试, 白名单内的命令不使用该退出码。
+//!
+//! 耗时较长的命令 (如 `virsh blockcopy` 等待同步) 使用 [`SshPool::execute_streaming`]
+//! 逐行获取输出, 长时间没有输出时判定为挂起并终止, 不必等待命令结束。
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tracing::{debug, warn};
+
+use crate::host_command::{
+    check_sudo, hop_failure, quote_command, redact, spawn_ssh, ssh_args, ssh_login_args, validate_host_command, SSH_ERROR_EXIT_CODE,
+};
+use crate::sftp::{SftpSession, TransferProgress};
+use crate::{ErrorContext, FileStat, HostCommandOutput, HostInfo, Result, SshConfig, TransportError};
+
+/// 建立 SFTP 会话的超时时间
+const SFTP_CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 逐行输出回调 (参数不含行尾的换行符)
+pub type LineCallback<'a> = &'a (dyn Fn(&str) + Send + Sync);
+
+/// 命令输出的收集方式
+#[derive(Clone, Copy)]
+enum Collect<'a> {
+    /// 命令结束后一次性返回输出, 超过 `timeout` 时终止
+    Buffered { timeout: Duration },
+    /// 逐行回调输出, 超过 `idle_timeout` 没有新的输出行时终止
+    Streaming {
+        idle_timeout: Duration,
+        on_stdout: LineCallback<'a>,
+        on_stderr: LineCallback<'a>,
+    },
+}
+
+/// 主连接的标识
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct SessionKey {
+    host: String,
+    user: String,
+    port: u16,
+}
+
+impl SessionKey {
+    fn new(host: &HostInfo, ssh: &SshConfig) -> Self {
+        Self {
+            host: host.host.clone(),
+            user: ssh.user.clone(),
+            port: ssh.port,
+        }
+    }
+}
+
+/// 已建立 (或正在建立) 的主连接
+struct Session {
+    control_path: PathBuf,
+    last_used: Instant,
+}
+
+/// SSH 连接池统计信息
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SshPoolStats {
+    /// 当前保持的主连接数
+    pub live_sessions: usize,
+    /// 执行的命令数
+    pub commands: u64,
+    /// 文件操作数 (SFTP 上传、下载、stat、读取)
+    pub transfers: u64,
+    /// 新建主连接的次数
+    pub opened: u64,
+    /// 复用已有主连接的次数
+    pub reused: u64,
+    /// 主连接失效后重建的次数
+    pub reconnects: u64,
+    /// 因超过连接数上限被关闭的主连接数
+    pub evicted: u64,
+}
+
+/// SSH 连接池
+pub struct SshPool {
+    /// ssh 程序
+    program: String,
+
+    /// 存放 ControlMaster 套接字的目录
+    control_dir: PathBuf,
+
+    max_connections: usize,
+    idle_timeout: Duration,
+    keepalive_interval: Duration,
+
+    sessions: Mutex<HashMap<SessionKey, Session>>,
+
+    commands: AtomicU64,
+    transfers: AtomicU64,
+    opened: AtomicU64,
+    reused: AtomicU64,
+    reconnects: AtomicU64,
+    evicted: AtomicU64,
+}
+
+impl Default for SshPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SshPool {
+    pub fn new() -> Self {
+        Self {
+            program: "ssh".to_string(),
+            control_dir: std::env::temp_dir().join(format!("atp-ssh-{}", std::process::id())),
+            max_connections: 64,
+            idle_timeout: Duration::from_secs(300),
+            keepalive_interval: Duration::from_secs(15),
+            sessions: Mutex::new(HashMap::new()),
+            commands: AtomicU64::new(0),
+            transfers: AtomicU64::new(0),
+            opened: AtomicU64::new(0),
+            reused: AtomicU64::new(0),
+            reconnects: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+        }
+    }
+
+    /// 同时保持的主连接数上限
+    pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+        self.max_connections = max_connections.max(1);
+        self
+    }
+
+    /// 主连接的空闲超时 (按秒取整, 至少 1 秒)
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// 主连接的保活间隔, 连续 3 次无响应时主连接退出
+    pub fn with_keepalive_interval(mut self, keepalive_interval: Duration) -> Self {
+        self.keepalive_interval = keepalive_interval;
+        self
+    }
+
+    /// ControlMaster 套接字目录 (路径需要足够短, Unix 套接字路径上限约 100 字节)
+    pub fn with_control_dir(mut self, control_dir: impl Into<PathBuf>) -> Self {
+        self.control_dir = control_dir.into();
+        self
+    }
+
+    /// 使用指定的 ssh 程序 (测试时替换为模拟脚本)
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// 连接池统计信息
+    pub fn stats(&self) -> SshPoolStats {
+        let live_sessions = {
+            let mut sessions = self.sessions.lock().unwrap();
+            self.prune_idle(&mut sessions);
+            sessions.len()
+        };
+        SshPoolStats {
+            live_sessions,
+            commands: self.commands.load(Ordering::Relaxed),
+            transfers: self.transfers.load(Ordering::Relaxed),
+            opened: self.opened.load(Ordering::Relaxed),
+            reused: self.reused.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            evicted: self.evicted.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 通过 SSH 在宿主机上执行白名单内的命令, 复用该主机已有的主连接
+    pub async fn execute(&self, host: &HostInfo, argv: &[String], timeout: Duration) -> Result<HostCommandOutput> {
+        self.execute_with(host, argv, Collect::Buffered { timeout }).await
+    }
+
+    /// 执行命令并逐行回调标准输出与标准错误
+    ///
+    /// 超过 `idle_timeout` 没有新的输出行时终止命令并返回 `IdleTimeout`; 命令总时长不受限制,
+    /// 需要时由调用方在外层加超时 (丢弃返回的 future 会终止 ssh 进程)。
+    /// 返回的输出中同样包含完整的标准输出与标准错误。
+    pub async fn execute_streaming(
+        &self,
+        host: &HostInfo,
+        argv: &[String],
+        idle_timeout: Duration,
+        on_stdout: LineCallback<'_>,
+        on_stderr: LineCallback<'_>,
+    ) -> Result<HostCommandOutput> {
+        let collect = Collect::Streaming { idle_timeout, on_stdout, on_stderr };
+        self.execute_with(host, argv, collect).await
+    }
+
+    async fn execute_with(&self, host: &HostInfo, argv: &[String], collect: Collect<'_>) -> Result<HostCommandOutput> {
+        let context = ErrorContext::new()
+            .with_host(&host.id)
+            .with_operation(argv.first().map_or("host-command", String::as_str));
+
+        let ssh = ssh_config(host).map_err(|e| e.with_context(context.clone()))?;
+        validate_host_command(argv).map_err(|e| e.with_context(context.clone()))?;
+
+        let key = SessionKey::new(host, ssh);
+        let (control_path, reused) = self.acquire(&key).await.map_err(|e| e.with_context(context.clone()))?;
+        self.commands.fetch_add(1, Ordering::Relaxed);
+
+        debug!(
+            "在主机 {} 上执行 ({}): {}",
+            host.id,
+            if reused { "复用连接" } else { "新建连接" },
+            quote_command(argv)
+        );
+        let mut output = self
+            .run(host, ssh, &control_path, argv, collect)
+            .await
+            .map_err(|e| e.with_context(context.clone()))?;
+
+        // 主连接失效时远端命令没有执行, 不会有标准输出
+        if reused && output.exit_code == Some(SSH_ERROR_EXIT_CODE) && output.stdout.is_empty() {
+            warn!("主机 {} 的 SSH 主连接已失效, 重新建立: {}", host.id, output.stderr.trim());
+            self.reconnects.fetch_add(1, Ordering::Relaxed);
+            self.close_master(&key, &control_path).await;
+            output = self
+                .run(host, ssh, &control_path, argv, collect)
+                .await
+                .map_err(|e| e.with_context(context.clone()))?;
+        }
+
+        // 连接失败时不保留该主连接, 下次执行按新建连接处理
+        if output.exit_code == Some(SSH_ERROR_EXIT_CODE) {
+            self.sessions.lock().unwrap().remove(&key);
+            if let Some(message) = hop_failure(&host.host, ssh, &output.stderr) {
+                return Err(TransportError::ConnectionFailed(message).with_context(context));
+            }
+        }
+        check_sudo(&host.host, ssh, output).map_err(|e| e.with_context(context))
+    }
+
+    /// 通过 SFTP 上传本地文件到宿主机, 远端文件权限设置为 `mode`, 返回传输的字节数
+    pub async fn upload(
+        &self,
+        host: &HostInfo,
+        local: &Path,
+        remote: &str,
+        mode: u32,
+        progress: Option<TransferProgress<'_>>,
+    ) -> Result<u64> {
+        let context = sftp_context(host, "sftp-upload");
+        let mut sftp = self.open_sftp(host).await.map_err(|e| e.with_context(context.clone()))?;
+        let result = sftp.session.upload(local, remote, mode, progress).await;
+        sftp.close().await;
+        result.map_err(|e| e.with_context(context))
+    }
+
+    /// 通过 SFTP 下载宿主机上的文件 (自动创建本地上级目录), 返回传输的字节数
+    pub async fn download(
+        &self,
+        host: &HostInfo,
+        remote: &str,
+        local: &Path,
+        progress: Option<TransferProgress<'_>>,
+    ) -> Result<u64> {
+        let context = sftp_context(host, "sftp-download");
+        let mut sftp = self.open_sftp(host).await.map_err(|e| e.with_context(context.clone()))?;
+        let result = sftp.session.download(remote, local, progress).await;
+        sftp.close().await;
+        result.map_err(|e| e.with_context(context))
+    }
+
+    /// 查询宿主机上的文件信息
+    pub async fn stat(&self, host: &HostInfo, remote: &str) -> Result<FileStat> {
+        let context = sftp_context(host, "sftp-stat");
+        let mut sftp = self.open_sftp(host).await.map_err(|e| e.with_context(context.clone()))?;
+        let result = sftp.session.stat(remote).await;
+        sftp.close().await;
+        result.map_err(|e| e.with_context(context))
+    }
+
+    /// 读取宿主机上文件开头的至多 `max_bytes` 字节
+    pub async fn read_to_string(&self, host: &HostInfo, remote: &str, max_bytes: usize) -> Result<String> {
+        let context = sftp_context(host, "sftp-read");
+        let mut sftp = self.open_sftp(host).await.map_err(|e| e.with_context(context.clone()))?;
+        let result = sftp.session.read_to_string(remote, max_bytes).await;
+        sftp.close().await;
+        result.map_err(|e| e.with_context(context))
+    }
+
+    /// 在多台主机上并发执行同一条命令 (同时最多 `concurrency` 台), 返回主机 ID -> 结果
+    pub async fn execute_on_all(
+        &self,
+        hosts: &[HostInfo],
+        argv: &[String],
+        timeout: Duration,
+        concurrency: usize,
+    ) -> HashMap<String, Result<HostCommandOutput>> {
+        stream::iter(hosts)
+            .map(|host| async move { (host.id.clone(), self.execute(host, argv, timeout).await) })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// 关闭所有主连接
+    pub async fn close_all(&self) {
+        let sessions: Vec<(SessionKey, Session)> = self.sessions.lock().unwrap().drain().collect();
+        for (key, session) in sessions {
+            self.close_master(&key, &session.control_path).await;
+        }
+    }
+
+    /// 取出主连接并关闭因超过上限被淘汰的主连接, 返回套接字路径与是否复用已有主连接
+    async fn acquire(&self, key: &SessionKey) -> Result<(PathBuf, bool)> {
+        self.ensure_control_dir()?;
+
+        let (control_path, reused, evicted) = self.checkout(key);
+        for (evicted_key, evicted_path) in evicted {
+            debug!("SSH 主连接数已达上限, 关闭 {}@{}", evicted_key.user, evicted_key.host);
+            self.close_master(&evicted_key, &evicted_path).await;
+        }
+        Ok((control_path, reused))
+    }
+
+    /// 在主连接上打开 SFTP 会话, 复用的主连接失效时重建一次
+    async fn open_sftp(&self, host: &HostInfo) -> Result<SftpChannel> {
+        let ssh = ssh_config(host)?;
+        let key = SessionKey::new(host, ssh);
+        let (control_path, reused) = self.acquire(&key).await?;
+        self.transfers.fetch_add(1, Ordering::Relaxed);
+
+        let result = match self.spawn_sftp(host, ssh, &control_path).await {
+            Err(e) if reused => {
+                warn!("主机 {} 的 SSH 主连接已失效, 重新建立: {}", host.id, e);
+                self.reconnects.fetch_add(1, Ordering::Relaxed);
+                self.close_master(&key, &control_path).await;
+                self.spawn_sftp(host, ssh, &control_path).await
+            }
+            result => result,
+        };
+
+        if result.is_err() {
+            self.sessions.lock().unwrap().remove(&key);
+        }
+        result
+    }
+
+    async fn spawn_sftp(&self, host: &HostInfo, ssh: &SshConfig, control_path: &Path) -> Result<SftpChannel> {
+        let mut args = self.master_args(control_path);
+        args.push("-s".to_string());
+        args.extend(ssh_login_args(&host.host, ssh, SFTP_CONNECT_TIMEOUT));
+        args.push("sftp".to_string());
+
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        let (Some(stdout), Some(stdin)) = (child.stdout.take(), child.stdin.take()) else {
+            return Err(TransportError::ConnectionFailed("无法获取 ssh 进程的标准输入输出".to_string()));
+        };
+
+        let init = tokio::time::timeout(SFTP_CONNECT_TIMEOUT, SftpSession::init(stdout, stdin)).await;
+        match init {
+            Ok(Ok(session)) => Ok(SftpChannel { session, child }),
+            Ok(Err(e)) => {
+                // ssh 已退出时标准错误中是连接失败的原因
+                let _ = child.start_kill();
+                let stderr = child
+                    .wait_with_output()
+                    .await
+                    .map(|output| String::from_utf8_lossy(&output.stderr).trim().to_string())
+                    .unwrap_or_default();
+                Err(TransportError::ConnectionFailed(if let Some(message) = hop_failure(&host.host, ssh, &stderr) {
+                    message
+                } else if stderr.is_empty() {
+                    format!("建立 SFTP 会话失败: {}", e)
+                } else {
+                    format!("建立 SFTP 会话失败: {}", stderr)
+                }))
+            }
+            Err(_) => Err(TransportError::Timeout),
+        }
+    }
+
+    /// 取出主连接: 返回套接字路径、是否复用已有主连接, 以及需要关闭的主连接
+    fn checkout(&self, key: &SessionKey) -> (PathBuf, bool, Vec<(SessionKey, PathBuf)>) {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.prune_idle(&mut sessions);
+
+        let now = Instant::now();
+        if let Some(session) = sessions.get_mut(key) {
+            session.last_used = now;
+            self.reused.fetch_add(1, Ordering::Relaxed);
+            return (session.control_path.clone(), true, Vec::new());
+        }
+
+        let mut evicted = Vec::new();
+        while sessions.len() >= self.max_connections {
+            let Some(oldest) = sessions
+                .iter()
+                .min_by_key(|(_, session)| session.last_used)
+                .map(|(key, _)| key.clone())
+            else {
+                break;
+            };
+            if let Some(session) = sessions.remove(&oldest) {
+                self.evicted.fetch_add(1, Ordering::Relaxed);
+                evicted.push((oldest, session.control_path));
+            }
+        }
+
+        let control_path = self.control_path(key);
+        sessions.insert(
+            key.clone(),
+            Session {
+                control_path: control_path.clone(),
+                last_used: now,
+            },
+        );
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        (control_path, false, evicted)
+    }
+
+    /// 移除空闲超时的主连接 (ssh 已按 ControlPersist 自行退出)
+    fn prune_idle(&self, sessions: &mut HashMap<SessionKey, Session>) {
+        let idle_timeout = self.control_persist();
+        sessions.retain(|_, session| session.last_used.elapsed() < idle_timeout);
+    }
+
+    /// 实际传给 ssh 的空闲超时 (`ControlPersist=0` 表示永久保持, 因此至少 1 秒)
+    fn control_persist(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout.as_secs().max(1))
+    }
+
+    fn control_path(&self, key: &SessionKey) -> PathBuf {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.control_dir.join(format!("{:016x}", hasher.finish()))
+    }
+
+    fn ensure_control_dir(&self) -> std::io::Result<()> {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::DirBuilderExt;
+            builder.mode(0o700);
+        }
+        builder.create(&self.control_dir)
+    }
+
+    /// ControlMaster 相关的 ssh 参数
+    fn master_args(&self, control_path: &Path) -> Vec<String> {
+        vec![
+            "-o".to_string(),
+            "ControlMaster=auto".to_string(),
+            "-o".to_string(),
+            format!("ControlPath={}", control_path.display()),
+            "-o".to_string(),
+            format!("ControlPersist={}", self.control_persist().as_secs()),
+            "-o".to_string(),
+            format!("ServerAliveInterval={}", self.keepalive_interval.as_secs().max(1)),
+            "-o".to_string(),
+            "ServerAliveCountMax=3".to_string(),
+        ]
+    }
+
+    async fn run(
+        &self,
+        host: &HostInfo,
+        ssh: &SshConfig,
+        control_path: &Path,
+        argv: &[String],
+        collect: Collect<'_>,
+    ) -> Result<HostCommandOutput> {
+        let connect_timeout = match collect {
+            Collect::Buffered { timeout } => timeout,
+            Collect::Streaming { idle_timeout, .. } => idle_timeout,
+        };
+        let mut command = Command::new(&self.program);
+        command
+            .args(self.master_args(control_path))
+            .args(ssh_args(&host.host, ssh, connect_timeout, argv))
+            .kill_on_drop(true);
+
+        command
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+
+        match collect {
+            Collect::Buffered { timeout } => {
+                let started = Instant::now();
+                let child = async { Ok::<_, TransportError>(spawn_ssh(&mut command, ssh).await?.wait_with_output().await?) };
+                let output = tokio::time::timeout(timeout, child)
+                    .await
+                    .map_err(|_| TransportError::Timeout)??;
+
+                Ok(HostCommandOutput {
+                    exit_code: output.status.code(),
+                    stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                    stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                    duration_ms: started.elapsed().as_millis() as u64,
+                })
+            }
+            Collect::Streaming { idle_timeout, on_stdout, on_stderr } => {
+                // 回显的 sudo 密码不能进入回调
+                let on_stdout = |line: &str| on_stdout(&redact(ssh, line));
+                let on_stderr = |line: &str| on_stderr(&redact(ssh, line));
+                let child = spawn_ssh(&mut command, ssh).await?;
+                stream_output(child, idle_timeout, &on_stdout, &on_stderr).await
+            }
+        }
+    }
+
+    /// 通知主连接退出 (主连接已不存在时忽略错误)
+    async fn close_master(&self, key: &SessionKey, control_path: &Path) {
+        let result = Command::new(&self.program)
+            .arg("-o")
+            .arg(format!("ControlPath={}", control_path.display()))
+            .arg("-p")
+            .arg(key.port.to_string())
+            .arg("-O")
+            .arg("exit")
+            .arg(format!("{}@{}", key.user, key.host))
+            .kill_on_drop(true)
+            .output();
+        match tokio::time::timeout(Duration::from_secs(5), result).await {
+            Ok(Ok(_)) => {}
+            Ok(Err(e)) => debug!("关闭 SSH 主连接失败: {}", e),
+            Err(_) => debug!("关闭 SSH 主连接超时: {}@{}", key.user, key.host),
+        }
+    }
+}
+
+/// 逐行读取子进程的输出直到进程结束, 超过 `idle_timeout` 没有新的输出行时终止进程
+pub(crate) async fn stream_output(
+    mut child: Child,
+    idle_timeout: Duration,
+    on_stdout: LineCallback<'_>,
+    on_stderr: LineCallback<'_>,
+) -> Result<HostCommandOutput> {
+    let started = Instant::now();
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        return Err(TransportError::ConnectionFailed("无法获取子进程的输出".to_string()));
+    };
+
+    let mut stdout = LineReader::new(stdout);
+    let mut stderr = LineReader::new(stderr);
+    let idle = tokio::time::sleep(idle_timeout);
+    tokio::pin!(idle);
+
+    while !(stdout.closed && stderr.closed) {
+        tokio::select! {
+            line = stdout.next_line(), if !stdout.closed => {
+                if let Some(line) = line? {
+                    on_stdout(&line);
+                    idle.as_mut().reset(tokio::time::Instant::now() + idle_timeout);
+                }
+            }
+            line = stderr.next_line(), if !stderr.closed => {
+                if let Some(line) = line? {
+                    on_stderr(&line);
+                    idle.as_mut().reset(tokio::time::Instant::now() + idle_timeout);
+                }
+            }
+            _ = &mut idle => {
+                let _ = child.kill().await;
+                return Err(TransportError::IdleTimeout(idle_timeout));
+            }
+        }
+    }
+
+    // 输出已关闭, 进程通常已经退出
+    let status = tokio::select! {
+        status = child.wait() => status?,
+        _ = &mut idle => {
+            let _ = child.kill().await;
+            return Err(TransportError::IdleTimeout(idle_timeout));
+        }
+    };
+
+    Ok(HostCommandOutput {
+        exit_code: status.code(),
+        stdout: stdout.collected,
+        stderr: stderr.collected,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// 按行读取输出, 同时保留完整内容
+struct LineReader<R> {
+    reader: BufReader<R>,
+    /// 尚未读到换行的部分 (在 select 中被取消时保留已读取的数据)
+    pending: Vec<u8>,
+    collected: String,
+    closed: bool,
+}
+
+impl<R: AsyncRead + Unpin> LineReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            pending: Vec::new(),
+            collected: String::new(),
+            closed: false,
+        }
+    }
+
+    /// 读取下一行, 输出关闭时返回 None (最后一行没有换行时同样返回)
+    async fn next_line(&mut self) -> Result<Option<String>> {
+        let n = self.reader.read_until(b'\n', &mut self.pending).await?;
+        if n == 0 {
+            self.closed = true;
+            if self.pending.is_empty() {
+                return Ok(None);
+            }
+        }
+
+        let raw = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        self.collected.push_str(&raw);
+        Ok(Some(raw.trim_end_matches(['\n', '\r']).to_string()))
+    }
+}
+
+/// 主机配置的 SSH 凭据
+fn ssh_config(host: &HostInfo) -> Result<&SshConfig> {
+    host.ssh.as_ref().ok_or_else(|| {
+        TransportError::ConfigError(format!("主机 {} 未配置 SSH, 无法执行宿主机命令", host.id))
+    })
+}
+
+fn sftp_context(host: &HostInfo, operation: &str) -> ErrorContext {
+    ErrorContext::new().with_host(&host.id).with_operation(operation)
+}
+
+/// 运行在 ssh 子进程上的 SFTP 会话
+struct SftpChannel {
+    session: SftpSession<ChildStdout, ChildStdin>,
+    child: Child,
+}
+
+impl SftpChannel {
+    /// 关闭会话: 关闭标准输入后 ssh 随之退出, 超时则终止进程
+    async fn close(self) {
+        let SftpChannel { session, mut child } = self;
+        drop(session);
+        if tokio::time::timeout(Duration::from_secs(5), child.wait()).await.is_err() {
+            let _ = child.kill().await;
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn argv(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// 模拟 ssh 的脚本: 记录每次调用的参数, 回显远端命令, 不支持 sftp 子系统;
+    /// 目录中存在 `fail_once` 时模拟一次主连接失效 (退出码 255),
+    /// 存在 `unreachable` 时模拟无法连接其中记录的主机;
+    /// sudo 的密码为 `s3cret`, 免密 sudo 时模拟用户不在 sudoers 中
+    fn fake_ssh(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("atp-ssh-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let script = dir.join("ssh");
+        std::fs::write(
+            &script,
+            format!(
+                r#"#!/bin/sh
+echo "$@" >> {dir}/calls.log
+for arg in "$@"; do
+    if [ "$arg" = "-O" ]; then exit 0; fi
+    if [ "$arg" = "-s" ]; then echo "subsystem request failed on channel 0" >&2; exit 1; fi
+done
+if [ -f {dir}/unreachable ]; then
+    echo "ssh: connect to host $(cat {dir}/unreachable) port 22: Connection timed out" >&2
+    exit 255
+fi
+if [ -f {dir}/fail_once ]; then
+    rm -f {dir}/fail_once
+    echo "mux_client_request_session: read from master failed" >&2
+    exit 255
+fi
+for last in "$@"; do :; done
+case "$last" in
+    "sudo -k -S"*)
+        read -r password
+        if [ "$password" != "s3cret" ]; then
+            echo "Sorry, try again." >&2
+            echo "sudo: 1 incorrect password attempt" >&2
+            exit 1
+        fi
+        echo "password $password accepted" >&2
+        ;;
+    "sudo -n"*)
+        echo "admin is not in the sudoers file.  This incident will be reported." >&2
+        exit 1
+        ;;
+esac
+echo "$last"
+"#,
+                dir = dir.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755)).unwrap();
+        dir
+    }
+
+    fn pool(dir: &Path) -> SshPool {
+        SshPool::new()
+            .with_program(dir.join("ssh").display().to_string())
+            .with_control_dir(dir.join("ctl"))
+    }
+
+    fn host(id: &str, addr: &str) -> HostInfo {
+        HostInfo::new(id, addr).with_ssh(SshConfig::new("admin"))
+    }
+
+    fn calls(dir: &Path) -> Vec<String> {
+        std::fs::read_to_string(dir.join("calls.log"))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sessions_reused_per_host() {
+        let dir = fake_ssh("reuse");
+        let pool = pool(&dir);
+        let timeout = Duration::from_secs(5);
+
+        let output = pool.execute(&host("h1", "10.0.0.1"), &argv(&["virsh", "list"]), timeout).await.unwrap();
+        assert!(output.success());
+        assert_eq!(output.stdout.trim(), "virsh list");
+        pool.execute(&host("h1", "10.0.0.1"), &argv(&["ip", "link"]), timeout).await.unwrap();
+        pool.execute(&host("h2", "10.0.0.2"), &argv(&["ip", "link"]), timeout).await.unwrap();
+
+        let stats = pool.stats();
+        assert_eq!(stats.live_sessions, 2);
+        assert_eq!((stats.commands, stats.opened, stats.reused), (3, 2, 1));
+
+        // 同一主机的命令使用同一个 ControlMaster 套接字
+        let calls = calls(&dir);
+        let control_path = |call: &str| call.split(' ').find(|arg| arg.starts_with("ControlPath=")).unwrap().to_string();
+        assert!(calls.iter().all(|call| call.contains("ControlMaster=auto")));
+        assert_eq!(control_path(&calls[0]), control_path(&calls[1]));
+        assert_ne!(control_path(&calls[0]), control_path(&calls[2]));
+
+        // 未配置 SSH 或命令不在白名单内时不调用 ssh
+        assert!(pool.execute(&HostInfo::new("h3", "10.0.0.3"), &argv(&["virsh"]), timeout).await.is_err());
+        assert!(pool.execute(&host("h1", "10.0.0.1"), &argv(&["rm", "-rf", "/"]), timeout).await.is_err());
+        assert_eq!(pool.stats().commands, 3);
+
+        pool.close_all().await;
+        assert_eq!(pool.stats().live_sessions, 0);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_dead_session_reestablished() {
+        let dir = fake_ssh("dead");
+        let pool = pool(&dir);
+        let h1 = host("h1", "10.0.0.1");
+        let timeout = Duration::from_secs(5);
+
+        pool.execute(&h1, &argv(&["virsh", "list"]), timeout).await.unwrap();
+
+        // 复用的主连接失效: 关闭后重试成功
+        std::fs::write(dir.join("fail_once"), "").unwrap();
+        let output = pool.execute(&h1, &argv(&["virsh", "list"]), timeout).await.unwrap();
+        assert!(output.success());
+
+        let stats = pool.stats();
+        assert_eq!((stats.reused, stats.reconnects), (1, 1));
+        assert!(calls(&dir).iter().any(|call| call.contains("-O exit admin@10.0.0.1")));
+
+        // 新建连接失败时不重试, 也不保留主连接
+        let h2 = host("h2", "10.0.0.2");
+        std::fs::write(dir.join("fail_once"), "").unwrap();
+        let output = pool.execute(&h2, &argv(&["virsh", "list"]), timeout).await.unwrap();
+        assert_eq!(output.exit_code, Some(SSH_ERROR_EXIT_CODE));
+        assert_eq!(pool.stats().live_sessions, 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_jump_host_named_in_error() {
+        let dir = fake_ssh("jump");
+        let pool = pool(&dir);
+        let ssh = SshConfig::new("admin").with_jump("ops@bastion".parse().unwrap());
+        let h1 = HostInfo::new("h1", "10.0.0.1").with_ssh(ssh);
+
+        std::fs::write(dir.join("unreachable"), "bastion").unwrap();
+        let err = pool.execute(&h1, &argv(&["virsh", "list"]), Duration::from_secs(5)).await.unwrap_err();
+        assert!(matches!(err.root(), TransportError::ConnectionFailed(_)));
+        assert!(err.to_string().contains("第 1/2 跳 (跳板机 ops@bastion:22) 连接失败"), "{}", err);
+        assert_eq!(pool.stats().live_sessions, 0);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sftp_unavailable() {
+        let dir = fake_ssh("sftp");
+        let pool = pool(&dir);
+
+        let err = pool.stat(&host("h1", "10.0.0.1"), "/var/log/libvirt/qemu/vm1.log").await.unwrap_err();
+        assert!(err.to_string().contains("subsystem request failed"), "{}", err);
+        assert!(matches!(err.root(), TransportError::ConnectionFailed(_)));
+        let call = &calls(&dir)[0];
+        assert!(call.contains(" -s ") && call.ends_with("admin@10.0.0.1 sftp"), "{}", call);
+
+        let stats = pool.stats();
+        assert_eq!((stats.transfers, stats.live_sessions), (1, 0));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn shell(script: &str) -> Child {
+        Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_stream_output_lines() {
+        let stdout_lines = Mutex::new(Vec::new());
+        let stderr_lines = Mutex::new(Vec::new());
+        let on_stdout = |line: &str| stdout_lines.lock().unwrap().push(line.to_string());
+        let on_stderr = |line: &str| stderr_lines.lock().unwrap().push(line.to_string());
+
+        // 总时长超过空闲超时, 但每行间隔都在空闲超时之内
+        let child = shell("echo one; sleep 0.2; echo warn >&2; sleep 0.2; echo two; sleep 0.2; printf three; exit 3");
+        let output = stream_output(child, Duration::from_millis(400), &on_stdout, &on_stderr).await.unwrap();
+
+        assert_eq!(*stdout_lines.lock().unwrap(), vec!["one", "two", "three"]);
+        assert_eq!(*stderr_lines.lock().unwrap(), vec!["warn"]);
+        assert_eq!(output.exit_code, Some(3));
+        assert_eq!(output.stdout, "one\ntwo\nthree");
+        assert_eq!(output.stderr, "warn\n");
+    }
+
+    #[tokio::test]
+    async fn test_stream_output_idle_timeout() {
+        let lines = Mutex::new(Vec::new());
+        let on_line = |line: &str| lines.lock().unwrap().push(line.to_string());
+
+        let started = Instant::now();
+        let child = shell("echo start; exec sleep 10");
+        let err = stream_output(child, Duration::from_millis(300), &on_line, &on_line).await.unwrap_err();
+
+        assert!(matches!(err, TransportError::IdleTimeout(idle) if idle == Duration::from_millis(300)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(*lines.lock().unwrap(), vec!["start"]);
+    }
+
+    #[tokio::test]
+    async fn test_execute_streaming_through_pool() {
+        let dir = fake_ssh("stream");
+        let pool = pool(&dir);
+        let h1 = host("h1", "10.0.0.1");
+        let lines = Mutex::new(Vec::new());
+        let on_stdout = |line: &str| lines.lock().unwrap().push(line.to_string());
+        let on_stderr = |_: &str| {};
+        let idle = Duration::from_secs(5);
+
+        pool.execute(&h1, &argv(&["virsh", "list"]), idle).await.unwrap();
+
+        // 复用的主连接失效时同样重建并重试
+        std::fs::write(dir.join("fail_once"), "").unwrap();
+        let output = pool
+            .execute_streaming(&h1, &argv(&["virsh", "blockjob", "vm", "vda"]), idle, &on_stdout, &on_stderr)
+            .await
+            .unwrap();
+        assert!(output.success());
+        assert_eq!(*lines.lock().unwrap(), vec!["virsh blockjob vm vda"]);
+        assert_eq!(pool.stats().reconnects, 1);
+
+        // 白名单同样适用
+        let err = pool.execute_streaming(&h1, &argv(&["sh", "-c", "id"]), idle, &on_stdout, &on_stderr).await;
+        assert!(matches!(err.unwrap_err().root(), TransportError::ConfigError(_)));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sudo_password_via_stdin() {
+        let dir = fake_ssh("sudo");
+        let pool = pool(&dir);
+        let timeout = Duration::from_secs(5);
+        let sudo_host = |password: Option<&str>| {
+            HostInfo::new("h1", "10.0.0.1").with_ssh(SshConfig::new("admin").with_sudo(password.map(str::to_string)))
+        };
+
+        let output = pool.execute(&sudo_host(Some("s3cret")), &argv(&["ovs-vsctl", "show"]), timeout).await.unwrap();
+        assert_eq!(output.stdout.trim(), "sudo -k -S -p '' sh -c 'ovs-vsctl show'");
+        assert_eq!(output.stderr.trim(), "password ****** accepted");
+
+        let lines = Mutex::new(Vec::new());
+        let on_stderr = |line: &str| lines.lock().unwrap().push(line.to_string());
+        pool.execute_streaming(&sudo_host(Some("s3cret")), &argv(&["virsh", "list"]), timeout, &|_| {}, &on_stderr)
+            .await
+            .unwrap();
+        assert_eq!(*lines.lock().unwrap(), vec!["password ****** accepted"]);
+
+        let err = pool.execute(&sudo_host(Some("wrong")), &argv(&["virsh", "list"]), timeout).await.unwrap_err();
+        assert!(matches!(err.root(), TransportError::SudoAuthFailed(_)));
+        assert!(!err.to_string().contains("wrong"));
+
+        let err = pool.execute(&sudo_host(None), &argv(&["virsh", "list"]), timeout).await.unwrap_err();
+        assert!(matches!(err.root(), TransportError::SudoNotPermitted(message) if message.starts_with("admin@10.0.0.1")));
+
+        // 密码不出现在 ssh 的命令行中
+        assert!(calls(&dir).iter().all(|call| !call.contains("s3cret")));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_execute_on_all_respects_connection_limit() {
+        let dir = fake_ssh("all");
+        let pool = pool(&dir).with_max_connections(2);
+        let hosts: Vec<HostInfo> = (1..=4)
+            .map(|i| host(&format!("h{}", i), &format!("10.0.0.{}", i)))
+            .chain(std::iter::once(HostInfo::new("no-ssh", "10.0.0.9")))
+            .collect();
+
+        let results = pool.execute_on_all(&hosts, &argv(&["cat", "/proc/loadavg"]), Duration::from_secs(5), 3).await;
+        assert_eq!(results.len(), 5);
+        for i in 1..=4 {
+            assert_eq!(results[&format!("h{}", i)].as_ref().unwrap().stdout.trim(), "cat /proc/loadavg");
+        }
+        assert!(results["no-ssh"].is_err());
+
+        let stats = pool.stats();
+        assert_eq!(stats.live_sessions, 2);
+        assert_eq!((stats.opened, stats.evicted), (4, 2));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+//! 虚拟机资源采样
+//!
+//! 直接通过 libvirt 读取虚拟机的 CPU 时间、内存与磁盘 IO 统计, 不依赖 VDI 平台的监控模块。
+//! CPU 使用率需要两次采样的差分, 见 [`cpu_usage_percent`]。
+
+use chrono::{DateTime, Utc};
+
+/// libvirt 内存统计标签 (virDomainMemoryStatTags)
+const MEMORY_STAT_UNUSED: u32 = 4;
+const MEMORY_STAT_AVAILABLE: u32 = 5;
+const MEMORY_STAT_ACTUAL_BALLOON: u32 = 6;
+const MEMORY_STAT_RSS: u32 = 7;
+
+/// 单次虚拟机资源采样
+#[derive(Debug, Clone, PartialEq)]
+pub struct DomainStatsSample {
+    /// 虚拟机名称
+    pub domain: String,
+
+    /// 采样时间
+    pub timestamp: DateTime<Utc>,
+
+    /// 累计 CPU 时间 (纳秒, 所有 vCPU 之和)
+    pub cpu_time_ns: u64,
+
+    /// vCPU 数量
+    pub vcpus: u32,
+
+    /// 当前分配的内存 (KiB)
+    pub memory_kb: u64,
+
+    /// 来宾可见的内存总量 (KiB, 需要 balloon 驱动)
+    pub available_kb: Option<u64>,
+
+    /// 来宾未使用的内存 (KiB, 需要 balloon 驱动)
+    pub unused_kb: Option<u64>,
+
+    /// QEMU 进程占用的物理内存 (KiB)
+    pub rss_kb: Option<u64>,
+
+    /// 磁盘 IO 累计计数 (所有磁盘之和, 无法读取时为 None)
+    pub block: Option<BlockIoStats>,
+}
+
+/// 磁盘 IO 累计计数 (virDomainBlockStats)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BlockIoStats {
+    /// 读取字节数
+    pub read_bytes: u64,
+
+    /// 写入字节数
+    pub write_bytes: u64,
+
+    /// 读请求数
+    pub read_ops: u64,
+
+    /// 写请求数
+    pub write_ops: u64,
+}
+
+impl BlockIoStats {
+    /// 从 libvirt 的计数构造, 驱动不支持的字段 (值为 -1) 记为 0
+    pub fn from_libvirt(rd_req: i64, rd_bytes: i64, wr_req: i64, wr_bytes: i64) -> Self {
+        let count = |value: i64| value.max(0) as u64;
+        Self {
+            read_bytes: count(rd_bytes),
+            write_bytes: count(wr_bytes),
+            read_ops: count(rd_req),
+            write_ops: count(wr_req),
+        }
+    }
+}
+
+impl DomainStatsSample {
+    pub fn new(domain: &str, cpu_time_ns: u64, vcpus: u32, memory_kb: u64) -> Self {
+        Self {
+            domain: domain.to_string(),
+            timestamp: Utc::now(),
+            cpu_time_ns,
+            vcpus,
+            memory_kb,
+            available_kb: None,
+            unused_kb: None,
+            rss_kb: None,
+            block: None,
+        }
+    }
+
+    /// 填入 `memory_stats` 返回的 (标签, 值) 列表
+    pub fn with_memory_stats(mut self, stats: impl IntoIterator<Item = (u32, u64)>) -> Self {
+        for (tag, value) in stats {
+            match tag {
+                MEMORY_STAT_UNUSED => self.unused_kb = Some(value),
+                MEMORY_STAT_AVAILABLE => self.available_kb = Some(value),
+                MEMORY_STAT_ACTUAL_BALLOON => self.memory_kb = value,
+                MEMORY_STAT_RSS => self.rss_kb = Some(value),
+                _ => {}
+            }
+        }
+        self
+    }
+
+    /// 累加各磁盘的 IO 计数, 没有任何磁盘时保持 None
+    pub fn with_block_stats(mut self, disks: impl IntoIterator<Item = BlockIoStats>) -> Self {
+        for disk in disks {
+            let total = self.block.get_or_insert_with(BlockIoStats::default);
+            total.read_bytes += disk.read_bytes;
+            total.write_bytes += disk.write_bytes;
+            total.read_ops += disk.read_ops;
+            total.write_ops += disk.write_ops;
+        }
+        self
+    }
+
+    /// 来宾已使用的内存 (KiB), 没有 balloon 统计时返回 None
+    pub fn memory_used_kb(&self) -> Option<u64> {
+        Some(self.available_kb?.saturating_sub(self.unused_kb?))
+    }
+
+    /// 来宾内存使用率 (百分比)
+    pub fn memory_usage_percent(&self) -> Option<f64> {
+        let available = self.available_kb.filter(|available| *available > 0)?;
+        Some(self.memory_used_kb()? as f64 * 100.0 / available as f64)
+    }
+}
+
+/// 两次采样之间的 CPU 使用率 (百分比, 按 vCPU 数归一化到 0-100)
+///
+/// 采样时间没有前进、vCPU 数为 0 或 CPU 时间倒退 (虚拟机重启) 时返回 None。
+pub fn cpu_usage_percent(previous: &DomainStatsSample, current: &DomainStatsSample) -> Option<f64> {
+    let elapsed_ns = (current.timestamp - previous.timestamp).num_nanoseconds()?;
+    if elapsed_ns <= 0 || current.vcpus == 0 || current.cpu_time_ns < previous.cpu_time_ns {
+        return None;
+    }
+
+    let cpu_ns = (current.cpu_time_ns - previous.cpu_time_ns) as f64;
+    let usage = cpu_ns * 100.0 / (elapsed_ns as f64 * current.vcpus as f64);
+    Some(usage.min(100.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn sample(cpu_time_ns: u64, vcpus: u32, offset_ms: i64) -> DomainStatsSample {
+        let base = DateTime::parse_from_rfc3339("2026-01-01T00:00:00Z").unwrap().with_timezone(&Utc);
+        DomainStatsSample {
+            timestamp: base + Duration::milliseconds(offset_ms),
+            ..DomainStatsSample::new("win10", cpu_time_ns, vcpus, 4 * 1024 * 1024)
+        }
+    }
+
+    #[test]
+    fn test_cpu_usage_percent() {
+        // 2 个 vCPU, 1 秒内使用了 1 秒 CPU 时间 => 50%
+        let usage = cpu_usage_percent(&sample(0, 2, 0), &sample(1_000_000_000, 2, 1000)).unwrap();
+        assert!((usage - 50.0).abs() < 1e-9);
+
+        // 空闲
+        assert_eq!(cpu_usage_percent(&sample(500, 4, 0), &sample(500, 4, 2000)), Some(0.0));
+
+        // 采样误差导致超过 100% 时截断
+        assert_eq!(cpu_usage_percent(&sample(0, 1, 0), &sample(1_500_000_000, 1, 1000)), Some(100.0));
+
+        // 时间没有前进 / 虚拟机重启导致 CPU 时间倒退 / 没有 vCPU
+        assert_eq!(cpu_usage_percent(&sample(0, 2, 1000), &sample(100, 2, 1000)), None);
+        assert_eq!(cpu_usage_percent(&sample(900, 2, 0), &sample(100, 2, 1000)), None);
+        assert_eq!(cpu_usage_percent(&sample(0, 0, 0), &sample(100, 0, 1000)), None);
+    }
+
+    #[test]
+    fn test_memory_stats() {
+        let stats = sample(0, 2, 0).with_memory_stats([
+            (MEMORY_STAT_AVAILABLE, 4000),
+            (MEMORY_STAT_UNUSED, 1000),
+            (MEMORY_STAT_ACTUAL_BALLOON, 4096),
+            (MEMORY_STAT_RSS, 4200),
+            (0, 7),
+        ]);
+
+        assert_eq!(stats.memory_kb, 4096);
+        assert_eq!(stats.rss_kb, Some(4200));
+        assert_eq!(stats.memory_used_kb(), Some(3000));
+        assert_eq!(stats.memory_usage_percent(), Some(75.0));
+
+        // 没有 balloon 驱动时只有分配的内存
+        let stats = sample(0, 2, 0);
+        assert_eq!(stats.memory_used_kb(), None);
+        assert_eq!(stats.memory_usage_percent(), None);
+    }
+
+    #[test]
+    fn test_block_stats() {
+        let stats = sample(0, 2, 0).with_block_stats([
+            BlockIoStats::from_libvirt(10, 4096, 5, 2048),
+            // 驱动不支持的字段为 -1
+            BlockIoStats::from_libvirt(2, 1024, -1, -1),
+        ]);
+
+        assert_eq!(
+            stats.block,
+            Some(BlockIoStats { read_bytes: 5120, write_bytes: 2048, read_ops: 12, write_ops: 5 })
+        );
+
+        // 没有磁盘
+        assert_eq!(sample(0, 2, 0).with_block_stats([]).block, None);
+    }
+}
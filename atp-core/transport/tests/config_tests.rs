@@ -166,3 +166,49 @@ fn test_reconnect_with_different_multipliers() {
     assert_eq!(config.calculate_delay(2), Duration::from_secs(2)); // 1.5^2 = 2.25, 向下取整为 2
     assert_eq!(config.calculate_delay(3), Duration::from_secs(3)); // 1.5^3 = 3.375, 向下取整为 3
 }
+
+#[test]
+fn test_validate_libvirt_uri() {
+    for uri in [
+        "qemu:///system",
+        "qemu+ssh://root@10.0.0.1/system",
+        "qemu+ssh://10.0.0.1:22/system",
+        "qemu+tcp://node-1/system",
+        "test:///default",
+    ] {
+        assert!(validate_libvirt_uri(uri).is_ok(), "{}", uri);
+    }
+
+    for uri in [
+        "10.0.0.1",
+        "qemu+ssh:///system",
+        "qemu+http://10.0.0.1/system",
+        "qemu+tcp://10.0.0.1",
+        "qemu+tcp://10.0.0.1:abc/system",
+        "+ssh://10.0.0.1/system",
+    ] {
+        assert!(validate_libvirt_uri(uri).is_err(), "{}", uri);
+    }
+}
+
+#[test]
+fn test_transport_config_hosts_from_json() {
+    let config: TransportConfig = serde_json::from_value(serde_json::json!({
+        "pool": { "max_connections_per_host": 4 },
+        "reconnect": { "max_attempts": 10 },
+        "hosts": [
+            { "id": "node-1", "host": "10.0.0.1", "tags": ["gpu"] },
+            { "id": "node-2", "host": "10.0.0.2", "uri": "qemu+tcp://10.0.0.2/system" }
+        ]
+    }))
+    .unwrap();
+
+    assert_eq!(config.pool.max_connections_per_host, 4);
+    assert_eq!(config.reconnect.max_attempts, 10);
+    assert!(config.validate().is_ok());
+
+    let node1 = config.hosts[0].to_host_info();
+    assert_eq!(node1.uri, "qemu+ssh://10.0.0.1:22/system");
+    assert_eq!(node1.tags, vec!["gpu".to_string()]);
+    assert_eq!(config.hosts[1].to_host_info().uri, "qemu+tcp://10.0.0.2/system");
+}
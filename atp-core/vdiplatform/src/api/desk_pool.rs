@@ -18,6 +18,44 @@ impl<'a> DeskPoolApi<'a> {
         Self { client }
     }
 
+    /// 查询桌面池列表(支持分页)
+    ///
+    /// # Arguments
+    /// * `page_num` - 页码(从1开始)
+    /// * `page_size` - 每页数量
+    pub async fn list_paged(&self, page_num: u32, page_size: u32) -> Result<Vec<serde_json::Value>> {
+        info!("查询桌面池列表: 第{}页, 每页{}条", page_num, page_size);
+
+        let url = format!("/ocloud/v1/desk-pool?pageNum={}&pageSize={}", page_num, page_size);
+        let token = self.client.get_token().await?;
+
+        let _permit = self.client.throttle().await;
+        let response: serde_json::Value = self.client.http_client()
+            .get(format!("{}{}", self.client.base_url(), url))
+            .header("Token", &token)
+            .send()
+            .await
+            .map_err(|e| crate::error::VdiError::HttpError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| crate::error::VdiError::ParseError(e.to_string()))?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(crate::error::VdiError::ApiError(500, msg.to_string()));
+        }
+
+        Ok(response["data"]["list"]
+            .as_array()
+            .unwrap_or(&vec![])
+            .clone())
+    }
+
+    /// 查询所有桌面池(自动处理分页)
+    pub async fn list_all(&self) -> Result<Vec<serde_json::Value>> {
+        self.list_paged(1, 1000).await
+    }
+
     /// 创建桌面池
     pub async fn create(&self, req: CreateDeskPoolRequest) -> Result<DeskPool> {
         info!("创建桌面池: {}", req.name);
@@ -1,11 +1,42 @@
 //! 虚拟机管理 API
 
+use std::time::Duration;
+
 use reqwest::Method;
 use tracing::info;
 
 use crate::client::VdiClient;
-use crate::error::Result;
-use crate::models::{Domain, CreateDomainRequest};
+use crate::error::{Result, VdiError};
+use crate::models::{
+    BatchFreezeRequest, BatchTaskRequest, BatchTaskResult, CloneDomainRequest, CloneDomainResult, Domain, CreateDomainRequest, SpiceKey,
+    UpdateDomainRequest,
+};
+
+/// 虚拟机名称最大长度 (字符)
+pub const MAX_DOMAIN_NAME_LEN: usize = 64;
+
+/// 校验新的虚拟机名称
+///
+/// 平台未公开名称约束, 这里按 libvirt 虚拟机名称的限制提前拒绝:
+/// 不能为空、首尾不能有空白、不能包含 `/` 与控制字符, 长度不超过 [`MAX_DOMAIN_NAME_LEN`]。
+pub fn validate_domain_name(name: &str) -> Result<()> {
+    let reason = if name.is_empty() {
+        Some("名称不能为空".to_string())
+    } else if name.trim() != name {
+        Some("名称首尾不能有空白".to_string())
+    } else if name.chars().count() > MAX_DOMAIN_NAME_LEN {
+        Some(format!("名称超过 {} 个字符", MAX_DOMAIN_NAME_LEN))
+    } else if name.chars().any(|c| c == '/' || c.is_control()) {
+        Some("名称不能包含 / 或控制字符".to_string())
+    } else {
+        None
+    };
+
+    match reason {
+        Some(reason) => Err(VdiError::InvalidArgument(format!("虚拟机名称 {:?} 无效: {}", name, reason))),
+        None => Ok(()),
+    }
+}
 
 /// 虚拟机管理 API
 pub struct DomainApi<'a> {
@@ -29,6 +60,7 @@ impl<'a> DomainApi<'a> {
         let url = format!("/ocloud/v1/domain?pageNum={}&pageSize={}", page_num, page_size);
         let token = self.client.get_token().await?;
 
+        let _permit = self.client.throttle().await;
         let response: serde_json::Value = self.client.http_client()
             .get(&format!("{}{}", self.client.base_url(), url))
             .header("Token", &token)
@@ -65,6 +97,39 @@ impl<'a> DomainApi<'a> {
         ).await
     }
 
+    /// 克隆虚拟机 (POST /ocloud/v1/domain/{id}/clone)
+    ///
+    /// 新名称先在本地校验 (见 [`validate_domain_name`]); 平台异步执行克隆,
+    /// 返回的新虚拟机 ID 需等待任务结束后才可操作。
+    pub async fn clone_domain(&self, source_id: &str, req: &CloneDomainRequest) -> Result<CloneDomainResult> {
+        validate_domain_name(&req.name)?;
+        if req.template_clone == 1 && req.storage_pool_id.is_none() {
+            return Err(VdiError::InvalidArgument("模板克隆必须指定存储池".to_string()));
+        }
+        info!("克隆虚拟机: {} -> {}", source_id, req.name);
+        let response: serde_json::Value = self.client.request(
+            Method::POST,
+            &format!("/ocloud/v1/domain/{}/clone", source_id),
+            Some(req),
+        ).await?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(VdiError::ApiError(500, msg.to_string()));
+        }
+
+        let result: CloneDomainResult = match response.get("data") {
+            Some(data) if !data.is_null() => {
+                serde_json::from_value(data.clone()).map_err(|e| VdiError::ParseError(e.to_string()))?
+            }
+            _ => CloneDomainResult::default(),
+        };
+        if result.clone_uuid.is_empty() {
+            return Err(VdiError::ParseError(format!("克隆 {} 未返回新虚拟机 ID", req.name)));
+        }
+        Ok(result)
+    }
+
     /// 查询虚拟机详情
     pub async fn get(&self, domain_id: &str) -> Result<Domain> {
         info!("查询虚拟机详情: {}", domain_id);
@@ -75,6 +140,44 @@ impl<'a> DomainApi<'a> {
         ).await
     }
 
+    /// 修改虚拟机 (PATCH /ocloud/v1/domain/{id})
+    pub async fn update(&self, domain_id: &str, req: &UpdateDomainRequest) -> Result<()> {
+        info!("修改虚拟机: {}", domain_id);
+        let response: serde_json::Value = self.client.request(
+            Method::PATCH,
+            &format!("/ocloud/v1/domain/{}", domain_id),
+            Some(req),
+        ).await?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(VdiError::ApiError(500, msg.to_string()));
+        }
+        Ok(())
+    }
+
+    /// 重命名虚拟机
+    ///
+    /// 新名称先在本地校验 (见 [`validate_domain_name`]), 不合法时不发送请求。
+    /// 平台的批量修改接口不支持名称, 批量重命名只能逐台调用。
+    pub async fn rename(&self, domain_id: &str, new_name: &str) -> Result<()> {
+        validate_domain_name(new_name)?;
+        info!("重命名虚拟机: {} -> {}", domain_id, new_name);
+        self.update(domain_id, &UpdateDomainRequest::new(new_name)).await
+    }
+
+    /// 设置虚拟机是否自动加域 (1-是, 0-否)
+    ///
+    /// 修改接口要求携带名称, 因此先查询虚拟机当前名称。
+    pub async fn set_auto_join_domain(&self, domain_id: &str, value: i32) -> Result<()> {
+        if !matches!(value, 0 | 1) {
+            return Err(VdiError::InvalidArgument(format!("自动加域取值只能为 0 或 1: {}", value)));
+        }
+        let domain = self.get(domain_id).await?;
+        info!("设置虚拟机自动加域: {} = {}", domain_id, value);
+        self.update(domain_id, &UpdateDomainRequest::new(&domain.name).with_auto_join_domain(value)).await
+    }
+
     /// 启动虚拟机
     pub async fn start(&self, domain_id: &str) -> Result<()> {
         info!("启动虚拟机: {}", domain_id);
@@ -85,6 +188,30 @@ impl<'a> DomainApi<'a> {
         ).await
     }
 
+    /// 批量启动虚拟机
+    ///
+    /// `req.host_id` 指定运行的主机; 单台虚拟机的错误在结果的 `error_list` 中返回, 不作为整体失败。
+    pub async fn batch_start(&self, req: &BatchTaskRequest) -> Result<BatchTaskResult> {
+        info!("批量启动虚拟机: {} 台, 主机 {}", req.id_list.len(), req.host_id.as_deref().unwrap_or("自动"));
+        let response: serde_json::Value = self.client.request(
+            Method::POST,
+            "/ocloud/v1/domain/start",
+            Some(req),
+        ).await?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(VdiError::ApiError(500, msg.to_string()));
+        }
+
+        match response.get("data") {
+            Some(data) if !data.is_null() => {
+                serde_json::from_value(data.clone()).map_err(|e| VdiError::ParseError(e.to_string()))
+            }
+            _ => Ok(BatchTaskResult::default()),
+        }
+    }
+
     /// 关闭虚拟机
     pub async fn shutdown(&self, domain_id: &str) -> Result<()> {
         info!("关闭虚拟机: {}", domain_id);
@@ -105,6 +232,53 @@ impl<'a> DomainApi<'a> {
         ).await
     }
 
+    /// 批量设置还原点 (POST /ocloud/v1/domain/freeze)
+    ///
+    /// 返回在此之前已经设置过还原点的虚拟机 ID, 这些虚拟机保留原有还原点。
+    pub async fn batch_freeze(&self, req: &BatchFreezeRequest) -> Result<Vec<String>> {
+        info!("批量设置还原点: {} 台, 类型 {}", req.id_list.len(), req.freeze_type);
+        let response: serde_json::Value = self.client.request(
+            Method::POST,
+            "/ocloud/v1/domain/freeze",
+            Some(req),
+        ).await?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(VdiError::ApiError(500, msg.to_string()));
+        }
+
+        match response.get("data") {
+            Some(data) if !data.is_null() => {
+                serde_json::from_value(data.clone()).map_err(|e| VdiError::ParseError(e.to_string()))
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    /// 批量重置虚拟机到还原点 (POST /ocloud/v1/domain/rebase)
+    pub async fn batch_rebase(&self, req: &BatchTaskRequest) -> Result<()> {
+        info!("批量重置虚拟机: {} 台", req.id_list.len());
+        self.batch_task("/ocloud/v1/domain/rebase", req).await
+    }
+
+    /// 批量取消还原点 (POST /ocloud/v1/domain/restore)
+    pub async fn batch_restore(&self, req: &BatchTaskRequest) -> Result<()> {
+        info!("批量取消还原点: {} 台", req.id_list.len());
+        self.batch_task("/ocloud/v1/domain/restore", req).await
+    }
+
+    /// 发送只返回状态的批量任务请求
+    async fn batch_task(&self, path: &str, req: &BatchTaskRequest) -> Result<()> {
+        let response: serde_json::Value = self.client.request(Method::POST, path, Some(req)).await?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(VdiError::ApiError(500, msg.to_string()));
+        }
+        Ok(())
+    }
+
     /// 删除虚拟机
     pub async fn delete(&self, domain_id: &str) -> Result<()> {
         info!("删除虚拟机: {}", domain_id);
@@ -135,6 +309,97 @@ impl<'a> DomainApi<'a> {
         ).await
     }
 
+    /// 动态迁移虚拟机到目标主机
+    ///
+    /// `target_host` 为目标主机的地址。平台异步执行迁移, 接口返回时迁移未必完成。
+    pub async fn migrate(&self, domain_id: &str, target_host: &str) -> Result<()> {
+        info!("迁移虚拟机: {} -> {}", domain_id, target_host);
+        self.client.request(
+            Method::POST,
+            &format!("/ocloud/v1/domain/{}/migrate", domain_id),
+            Some(serde_json::json!({ "dconnuri": target_host, "migrateType": 2 })),
+        ).await
+    }
+
+    /// 等待平台记录的虚拟机所在主机变为 `host_id` (如迁移完成)
+    ///
+    /// 每隔 `interval` 查询一次虚拟机详情, 超过 `timeout` 返回 [`VdiError::Timeout`]。
+    pub async fn wait_for_host(
+        &self,
+        domain_id: &str,
+        host_id: &str,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<Domain> {
+        info!("等待虚拟机 {} 位于主机 {}", domain_id, host_id);
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let domain = self.get(domain_id).await?;
+            if domain.host_id == host_id {
+                return Ok(domain);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(VdiError::Timeout(format!(
+                    "{}s 内虚拟机 {} 未位于主机 {} (当前主机 {}, 状态 {})",
+                    timeout.as_secs(), domain_id, host_id, domain.host_id, domain.status
+                )));
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// 查询虚拟机的 SPICE 连接票据 (地址、端口与密码)
+    pub async fn get_spice_key(&self, domain_id: &str) -> Result<SpiceKey> {
+        info!("查询 SPICE 票据: {}", domain_id);
+        let response: serde_json::Value = self.client.request(
+            Method::GET,
+            &format!("/ocloud/v1/domain/{}/spice-key", domain_id),
+            None::<()>,
+        ).await?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(VdiError::ApiError(500, msg.to_string()));
+        }
+
+        match response.get("data") {
+            Some(data) if !data.is_null() => {
+                serde_json::from_value(data.clone()).map_err(|e| VdiError::ParseError(e.to_string()))
+            }
+            _ => Err(VdiError::NotFound(format!("虚拟机 {} 没有 SPICE 票据", domain_id))),
+        }
+    }
+
+    /// 查询虚拟机的快照列表
+    pub async fn list_snapshots(&self, domain_id: &str) -> Result<Vec<serde_json::Value>> {
+        info!("查询虚拟机快照: {}", domain_id);
+
+        let url = format!("/ocloud/v1/domain/{}/snapshot?pageNum=1&pageSize=1000", domain_id);
+        let token = self.client.get_token().await?;
+
+        let _permit = self.client.throttle().await;
+        let response: serde_json::Value = self.client.http_client()
+            .get(format!("{}{}", self.client.base_url(), url))
+            .header("Token", &token)
+            .send()
+            .await
+            .map_err(|e| crate::error::VdiError::HttpError(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| crate::error::VdiError::ParseError(e.to_string()))?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(crate::error::VdiError::ApiError(500, msg.to_string()));
+        }
+
+        Ok(response["data"]["list"]
+            .as_array()
+            .unwrap_or(&vec![])
+            .clone())
+    }
+
     /// 绑定用户
     pub async fn bind_user(&self, domain_id: &str, user_id: &str) -> Result<()> {
         info!("绑定用户到虚拟机: {} -> {}", user_id, domain_id);
@@ -162,3 +427,188 @@ impl<'a> DomainApi<'a> {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::{VdiClient, VdiConfig};
+    use crate::models::FreezeType;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// 收到的请求: (方法, 路径, JSON 请求体)
+    type Recorded = (String, String, serde_json::Value);
+
+    /// 极简的 VDI 平台模拟服务: 登录返回令牌, 查询详情返回固定虚拟机, 克隆返回固定 ID,
+    /// 设置还原点返回一台已有还原点的虚拟机, 其余请求返回成功
+    async fn mock_server() -> (String, mpsc::UnboundedReceiver<Recorded>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let mut reader = BufReader::new(stream);
+                    loop {
+                        let mut request_line = String::new();
+                        if reader.read_line(&mut request_line).await.unwrap_or(0) == 0 {
+                            return;
+                        }
+                        let mut content_length = 0;
+                        loop {
+                            let mut header = String::new();
+                            reader.read_line(&mut header).await.unwrap();
+                            let header = header.trim_end();
+                            if header.is_empty() {
+                                break;
+                            }
+                            if let Some((name, value)) = header.split_once(':') {
+                                if name.eq_ignore_ascii_case("content-length") {
+                                    content_length = value.trim().parse().unwrap();
+                                }
+                            }
+                        }
+                        let mut body = vec![0; content_length];
+                        reader.read_exact(&mut body).await.unwrap();
+
+                        let mut parts = request_line.split_whitespace();
+                        let method = parts.next().unwrap_or_default().to_string();
+                        let path = parts.next().unwrap_or_default().to_string();
+                        let response = match (method.as_str(), path.as_str()) {
+                            ("POST", "/ocloud/v1/login") => serde_json::json!({ "status": 0, "data": { "token": "t" } }),
+                            ("POST", p) if p.ends_with("/clone") => serde_json::json!({
+                                "status": 0, "data": { "cloneUuid": "d-2", "eventId": "e-1" },
+                            }),
+                            ("POST", "/ocloud/v1/domain/freeze") => serde_json::json!({
+                                "status": 0, "data": ["d-9"],
+                            }),
+                            ("GET", _) => serde_json::json!({
+                                "id": "d-1", "name": "win10-01", "status": "running",
+                                "host_id": "h-1", "vcpu": 2, "memory": 4096, "created_at": null,
+                            }),
+                            _ => serde_json::json!({ "status": 0, "msg": "操作成功" }),
+                        };
+                        let body = serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+                        let _ = tx.send((method, path, body));
+
+                        let payload = response.to_string();
+                        let reply = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                            payload.len(),
+                            payload
+                        );
+                        reader.get_mut().write_all(reply.as_bytes()).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        (base_url, rx)
+    }
+
+    async fn logged_in_client(base_url: &str) -> VdiClient {
+        let mut client = VdiClient::new(base_url, VdiConfig::default()).unwrap();
+        client.login("admin", "password").await.unwrap();
+        client
+    }
+
+    #[test]
+    fn test_validate_domain_name() {
+        assert!(validate_domain_name("win10-01").is_ok());
+        assert!(validate_domain_name("测试桌面 01").is_ok());
+        assert!(validate_domain_name(&"a".repeat(MAX_DOMAIN_NAME_LEN)).is_ok());
+
+        for name in ["", " win10", "win10 ", "a/b", "a\tb"] {
+            assert!(matches!(validate_domain_name(name), Err(VdiError::InvalidArgument(_))), "{:?}", name);
+        }
+        assert!(validate_domain_name(&"a".repeat(MAX_DOMAIN_NAME_LEN + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rename_request_body() {
+        let (base_url, mut requests) = mock_server().await;
+        let client = logged_in_client(&base_url).await;
+        requests.recv().await.unwrap();
+
+        client.domain().rename("d-1", "win10-renamed").await.unwrap();
+        let (method, path, body) = requests.recv().await.unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("PATCH", "/ocloud/v1/domain/d-1"));
+        assert_eq!(body, serde_json::json!({ "name": "win10-renamed" }));
+
+        // 名称不合法时不发送请求
+        assert!(client.domain().rename("d-1", "bad/name").await.is_err());
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_set_auto_join_domain_request_body() {
+        let (base_url, mut requests) = mock_server().await;
+        let client = logged_in_client(&base_url).await;
+        requests.recv().await.unwrap();
+
+        client.domain().set_auto_join_domain("d-1", 1).await.unwrap();
+        let (method, path, _) = requests.recv().await.unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("GET", "/ocloud/v1/domain/d-1"));
+
+        // 携带查询到的当前名称
+        let (method, path, body) = requests.recv().await.unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("PATCH", "/ocloud/v1/domain/d-1"));
+        assert_eq!(body, serde_json::json!({ "name": "win10-01", "autoJoinDomain": 1 }));
+
+        assert!(matches!(
+            client.domain().set_auto_join_domain("d-1", 2).await,
+            Err(VdiError::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_clone_domain_request_body() {
+        let (base_url, mut requests) = mock_server().await;
+        let client = logged_in_client(&base_url).await;
+        requests.recv().await.unwrap();
+
+        let req = CloneDomainRequest::new("lab-001").with_storage_pool("pool-1").with_template_clone(true);
+        let result = client.domain().clone_domain("tpl-1", &req).await.unwrap();
+        assert_eq!(result.clone_uuid, "d-2");
+        assert_eq!(result.event_id, "e-1");
+
+        let (method, path, body) = requests.recv().await.unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("POST", "/ocloud/v1/domain/tpl-1/clone"));
+        assert_eq!(body, serde_json::json!({ "name": "lab-001", "storagePoolId": "pool-1", "templateClone": 1 }));
+
+        // 名称不合法或模板克隆缺少存储池时不发送请求
+        assert!(client.domain().clone_domain("tpl-1", &CloneDomainRequest::new("bad/name")).await.is_err());
+        let req = CloneDomainRequest::new("lab-002").with_template_clone(true);
+        assert!(matches!(client.domain().clone_domain("tpl-1", &req).await, Err(VdiError::InvalidArgument(_))));
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_restore_point_request_bodies() {
+        let (base_url, mut requests) = mock_server().await;
+        let client = logged_in_client(&base_url).await;
+        requests.recv().await.unwrap();
+
+        let ids = vec!["d-1".to_string(), "d-9".to_string()];
+        let frozen = client
+            .domain()
+            .batch_freeze(&BatchFreezeRequest::new(ids.clone(), FreezeType::EveryBoot))
+            .await
+            .unwrap();
+        assert_eq!(frozen, ["d-9"]);
+        let (method, path, body) = requests.recv().await.unwrap();
+        assert_eq!((method.as_str(), path.as_str()), ("POST", "/ocloud/v1/domain/freeze"));
+        assert_eq!(body, serde_json::json!({ "freezeType": 1, "idList": ["d-1", "d-9"] }));
+
+        client.domain().batch_rebase(&BatchTaskRequest::new(ids.clone())).await.unwrap();
+        let (_, path, body) = requests.recv().await.unwrap();
+        assert_eq!(path, "/ocloud/v1/domain/rebase");
+        assert_eq!(body, serde_json::json!({ "idList": ["d-1", "d-9"] }));
+
+        client.domain().batch_restore(&BatchTaskRequest::new(ids)).await.unwrap();
+        let (_, path, _) = requests.recv().await.unwrap();
+        assert_eq!(path, "/ocloud/v1/domain/restore");
+    }
+}
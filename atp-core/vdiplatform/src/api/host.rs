@@ -4,8 +4,8 @@ use reqwest::Method;
 use tracing::info;
 
 use crate::client::VdiClient;
-use crate::error::Result;
-use crate::models::{Host, HostStatus};
+use crate::error::{Result, VdiError};
+use crate::models::{Host, HostDetail, HostStatus};
 
 /// 主机管理 API
 pub struct HostApi<'a> {
@@ -29,6 +29,7 @@ impl<'a> HostApi<'a> {
         let url = format!("/ocloud/v1/host?pageNum={}&pageSize={}", page_num, page_size);
         let token = self.client.get_token().await?;
 
+        let _permit = self.client.throttle().await;
         let response: serde_json::Value = self.client.http_client()
             .get(&format!("{}{}", self.client.base_url(), url))
             .header("Token", &token)
@@ -94,5 +95,189 @@ impl<'a> HostApi<'a> {
             None::<()>,
         ).await
     }
+
+    /// 查询主机负载详情
+    ///
+    /// 已用资源按主机上运行中虚拟机的配置累加, 需要额外查询虚拟机与存储池列表。
+    pub async fn get_detail(&self, host_id: &str) -> Result<HostDetail> {
+        info!("查询主机负载详情: {}", host_id);
+        let host = self.get_data(&format!("/ocloud/v1/host/{}", host_id)).await?;
+        if host.is_null() {
+            return Err(VdiError::NotFound(format!("主机 {}", host_id)));
+        }
+        let domains = self.client.domain().list_all().await?;
+        let pools = self.list_storage_pools().await?;
+
+        Ok(host_detail(&host, &domains, &pools))
+    }
+
+    /// 查询多台主机的负载详情
+    ///
+    /// `candidates` 为主机 ID 列表, 未指定时返回全部主机; 列表中有不存在的主机时返回错误。
+    pub async fn list_details(&self, candidates: Option<&[String]>) -> Result<Vec<HostDetail>> {
+        let hosts = self.get_data("/ocloud/v1/host/all").await?;
+        let hosts = hosts.as_array().map(Vec::as_slice).unwrap_or_default();
+        let domains = self.client.domain().list_all().await?;
+        let pools = self.list_storage_pools().await?;
+
+        let details: Vec<HostDetail> = hosts.iter().map(|host| host_detail(host, &domains, &pools)).collect();
+        let Some(candidates) = candidates else {
+            return Ok(details);
+        };
+
+        if let Some(missing) = candidates.iter().find(|id| !details.iter().any(|host| &host.id == *id)) {
+            return Err(VdiError::NotFound(format!("主机 {}", missing)));
+        }
+        Ok(details.into_iter().filter(|host| candidates.contains(&host.id)).collect())
+    }
+
+    /// 在候选主机中选出负载最低的启用主机
+    ///
+    /// 按空闲内存优先、空闲 CPU 其次比较, 见 [`least_loaded`]。`candidates` 未指定时在全部主机中选择。
+    pub async fn pick_least_loaded(&self, candidates: Option<Vec<String>>) -> Result<HostDetail> {
+        let details = self.list_details(candidates.as_deref()).await?;
+        let host = least_loaded(&details)
+            .cloned()
+            .ok_or_else(|| VdiError::NotFound("没有处于启用状态的候选主机".to_string()))?;
+
+        info!(
+            "负载最低的主机: {} ({}), 空闲内存 {}MB, 空闲 CPU {} 核",
+            host.name, host.id, host.free_memory_mb(), host.free_cpu()
+        );
+        Ok(host)
+    }
+
+    /// 查询全部存储池
+    async fn list_storage_pools(&self) -> Result<Vec<serde_json::Value>> {
+        let pools = self.get_data("/ocloud/v1/storage-pool/all").await?;
+        Ok(pools.as_array().cloned().unwrap_or_default())
+    }
+
+    /// 发送 GET 请求并取出平台响应中的 `data`
+    async fn get_data(&self, path: &str) -> Result<serde_json::Value> {
+        let mut response: serde_json::Value = self.client.request(Method::GET, path, None::<()>).await?;
+
+        if response["status"].as_i64().unwrap_or(-1) != 0 {
+            let msg = response["msg"].as_str().unwrap_or("未知错误");
+            return Err(VdiError::ApiError(500, msg.to_string()));
+        }
+
+        Ok(response["data"].take())
+    }
 }
 
+/// 按平台的主机、虚拟机与存储池列表条目计算主机负载详情
+///
+/// 主机内存以 GB 为单位, 虚拟机内存以 MB 为单位; 只累加状态为运行中 (1) 的虚拟机。
+pub fn host_detail(host: &serde_json::Value, domains: &[serde_json::Value], pools: &[serde_json::Value]) -> HostDetail {
+    let id = host["id"].as_str().unwrap_or_default();
+    let resource_pool = host["poolId"].as_str().filter(|pool| !pool.is_empty());
+
+    let running: Vec<&serde_json::Value> = domains
+        .iter()
+        .filter(|domain| domain["hostId"].as_str() == Some(id) && domain["status"].as_i64() == Some(1))
+        .collect();
+
+    let storage_pool_ids = pools
+        .iter()
+        .filter(|pool| {
+            let local = pool["hostId"].as_str() == Some(id);
+            let shared = pool["isShare"].as_i64() == Some(1)
+                && resource_pool.is_some()
+                && pool["poolId"].as_str() == resource_pool;
+            local || shared
+        })
+        .filter_map(|pool| pool["id"].as_str().map(str::to_string))
+        .collect();
+
+    HostDetail {
+        id: id.to_string(),
+        name: host["name"].as_str().unwrap_or_default().to_string(),
+        ip: host["ip"].as_str().unwrap_or_default().to_string(),
+        status: host["status"].as_i64().unwrap_or(-1),
+        cpu_total: host["cpuSize"].as_u64().unwrap_or(0) as u32,
+        cpu_used: running.iter().map(|domain| domain["cpuNum"].as_u64().unwrap_or(0) as u32).sum(),
+        memory_total_mb: (host["memory"].as_f64().unwrap_or(0.0) * 1024.0).round() as u64,
+        memory_used_mb: running.iter().map(|domain| domain["memory"].as_f64().unwrap_or(0.0) as u64).sum(),
+        running_vms: running.len() as u32,
+        storage_pool_ids,
+    }
+}
+
+/// 选出负载最低的启用主机
+///
+/// 空闲内存多者优先, 相同时比较空闲 CPU, 仍相同时取 ID 较小者 (保证结果稳定)。
+pub fn least_loaded(hosts: &[HostDetail]) -> Option<&HostDetail> {
+    hosts.iter().filter(|host| host.is_enabled()).max_by(|a, b| {
+        a.free_memory_mb()
+            .cmp(&b.free_memory_mb())
+            .then_with(|| a.free_cpu().cmp(&b.free_cpu()))
+            .then_with(|| b.id.cmp(&a.id))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn detail(id: &str, status: i64, cpu: (u32, u32), memory_mb: (u64, u64)) -> HostDetail {
+        HostDetail {
+            id: id.to_string(),
+            name: id.to_string(),
+            ip: String::new(),
+            status,
+            cpu_total: cpu.0,
+            cpu_used: cpu.1,
+            memory_total_mb: memory_mb.0,
+            memory_used_mb: memory_mb.1,
+            running_vms: 0,
+            storage_pool_ids: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_host_detail_sums_running_domains() {
+        let host = json!({"id": "h-1", "name": "node-1", "ip": "10.0.0.1", "status": 1, "cpuSize": 32, "memory": 64, "poolId": "rp-1"});
+        let domains = vec![
+            json!({"id": "d-1", "hostId": "h-1", "status": 1, "cpuNum": 4, "memory": 8192}),
+            json!({"id": "d-2", "hostId": "h-1", "status": 1, "cpuNum": 2, "memory": 4096}),
+            json!({"id": "d-3", "hostId": "h-1", "status": 0, "cpuNum": 8, "memory": 16384}),
+            json!({"id": "d-4", "hostId": "h-2", "status": 1, "cpuNum": 8, "memory": 16384}),
+        ];
+        let pools = vec![
+            json!({"id": "sp-local", "hostId": "h-1", "isShare": 0, "poolId": "rp-1"}),
+            json!({"id": "sp-shared", "hostId": "h-2", "isShare": 1, "poolId": "rp-1"}),
+            json!({"id": "sp-other", "hostId": "h-3", "isShare": 1, "poolId": "rp-2"}),
+        ];
+
+        let detail = host_detail(&host, &domains, &pools);
+        assert_eq!((detail.cpu_total, detail.cpu_used, detail.free_cpu()), (32, 6, 26));
+        assert_eq!((detail.memory_total_mb, detail.memory_used_mb), (65536, 12288));
+        assert_eq!(detail.free_memory_mb(), 53248);
+        assert_eq!(detail.running_vms, 2);
+        assert_eq!(detail.storage_pool_ids, vec!["sp-local", "sp-shared"]);
+        assert!(detail.is_enabled());
+    }
+
+    #[test]
+    fn test_least_loaded_prefers_free_memory_then_cpu() {
+        let hosts = vec![
+            detail("h-1", 1, (32, 30), (65536, 1024)),
+            detail("h-2", 1, (32, 0), (65536, 8192)),
+            // 维护中的主机不参与选择
+            detail("h-3", 0, (64, 0), (131072, 0)),
+        ];
+        assert_eq!(least_loaded(&hosts).unwrap().id, "h-1");
+
+        // 空闲内存相同时比较空闲 CPU, 仍相同时取 ID 较小者
+        let hosts = vec![
+            detail("h-2", 1, (32, 4), (65536, 0)),
+            detail("h-1", 1, (32, 8), (65536, 0)),
+            detail("h-0", 1, (32, 4), (65536, 0)),
+        ];
+        assert_eq!(least_loaded(&hosts).unwrap().id, "h-0");
+
+        assert!(least_loaded(&[detail("h-1", 3, (32, 0), (65536, 0))]).is_none());
+    }
+}
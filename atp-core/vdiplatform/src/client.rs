@@ -1,13 +1,15 @@
 //! VDI 平台客户端核心实现
 
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use tokio::sync::{RwLock, Semaphore};
 use reqwest::{Client, Method};
 use serde::{Serialize, de::DeserializeOwned};
 use tracing::{debug, info, warn};
 
 use crate::error::{VdiError, Result};
 use crate::api::{DomainApi, DeskPoolApi, HostApi, ModelApi, UserApi};
+use crate::rate_limit::{RateLimit, RateLimiter, RateLimiterStats, RatePermit};
 
 /// VDI 平台客户端配置
 #[derive(Debug, Clone)]
@@ -37,6 +39,9 @@ impl Default for VdiConfig {
 }
 
 /// VDI 平台客户端
+///
+/// 克隆的客户端共享访问令牌与限流器。
+#[derive(Clone)]
 pub struct VdiClient {
     /// API 基础 URL
     base_url: String,
@@ -49,6 +54,9 @@ pub struct VdiClient {
 
     /// 配置
     config: VdiConfig,
+
+    /// 请求限流器 (可选)
+    rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 impl VdiClient {
@@ -66,9 +74,50 @@ impl VdiClient {
             http_client,
             access_token: Arc::new(RwLock::new(None)),
             config,
+            rate_limiter: None,
         })
     }
 
+    /// 限制并发请求数与相邻请求的最小间隔
+    ///
+    /// 批量场景中大量并发请求会触发平台限流, 所有 API 调用在发送前都经过同一个限流器。
+    pub fn with_rate_limit(mut self, max_concurrent: usize, min_interval: Duration) -> Self {
+        let limits = RateLimit::new(max_concurrent, min_interval);
+        let limits = match self.rate_limiter.as_ref().and_then(|limiter| limiter.limits().requests_per_second) {
+            Some(rps) => limits.with_requests_per_second(rps),
+            None => limits,
+        };
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(limits)));
+        self
+    }
+
+    /// 限制平均每秒请求数 (令牌桶, 允许最多 1 秒的突发)
+    ///
+    /// 未调用 [`with_rate_limit`](Self::with_rate_limit) 时不限制并发。
+    pub fn with_requests_per_second(mut self, requests_per_second: f64) -> Self {
+        let limits = self
+            .rate_limiter
+            .as_ref()
+            .map(|limiter| limiter.limits())
+            .unwrap_or_else(|| RateLimit::new(Semaphore::MAX_PERMITS, Duration::ZERO))
+            .with_requests_per_second(requests_per_second);
+        self.rate_limiter = Some(Arc::new(RateLimiter::new(limits)));
+        self
+    }
+
+    /// 限流器统计 (未启用限流时为 None)
+    pub fn rate_limit_stats(&self) -> Option<RateLimiterStats> {
+        self.rate_limiter.as_ref().map(|limiter| limiter.stats())
+    }
+
+    /// 启用限流时等待发送许可, 许可在请求完成前保持
+    pub(crate) async fn throttle(&self) -> Option<RatePermit<'_>> {
+        match &self.rate_limiter {
+            Some(limiter) => Some(limiter.acquire().await),
+            None => None,
+        }
+    }
+
     /// 认证登录
     ///
     /// # Arguments
@@ -170,6 +219,7 @@ impl VdiClient {
             request = request.json(&body);
         }
 
+        let _permit = self.throttle().await;
         let response = request.send().await
             .map_err(|e| VdiError::HttpError(e.to_string()))?;
 
@@ -217,4 +267,24 @@ mod tests {
         );
         assert!(client.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_rate_limiter_shared_across_clones() {
+        let client = VdiClient::new("http://192.168.1.11:8088", VdiConfig::default()).unwrap();
+        assert!(client.rate_limit_stats().is_none());
+
+        let client = client
+            .with_rate_limit(1, Duration::ZERO)
+            .with_requests_per_second(50.0);
+        let clone = client.clone();
+
+        let permit = client.throttle().await;
+        assert_eq!(clone.rate_limit_stats().unwrap().in_flight, 1);
+        drop(permit);
+
+        let stats = clone.rate_limit_stats().unwrap();
+        assert_eq!((stats.in_flight, stats.total_requests), (0, 1));
+        let limits = client.rate_limiter.as_ref().unwrap().limits();
+        assert_eq!((limits.max_concurrent, limits.requests_per_second), (1, Some(50.0)));
+    }
 }
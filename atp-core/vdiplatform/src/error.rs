@@ -20,6 +20,9 @@ pub enum VdiError {
     #[error("配置错误: {0}")]
     ConfigError(String),
 
+    #[error("参数无效: {0}")]
+    InvalidArgument(String),
+
     #[error("超时错误: {0}")]
     Timeout(String),
 
@@ -6,9 +6,11 @@ pub mod client;
 pub mod api;
 pub mod models;
 pub mod error;
+pub mod rate_limit;
 
 pub use client::VdiClient;
 pub use error::{VdiError, Result};
+pub use rate_limit::{RateLimit, RateLimiterStats};
 
 // 导出 API 模块
 pub use api::{
@@ -1,26 +1,13 @@
 //! VDI 平台数据模型
 //!
-//! **当前数据来源**: VDI 平台 REST API (实时查询,无本地持久化)
+//! **数据来源**: VDI 平台 REST API (实时查询)
 //!
-//! **问题**:
-//! - 每次查询都需要调用 VDI API
-//! - 无历史状态记录
-//! - 离线无法查询
-//! - 频繁 API 调用可能影响性能
+//! **本地缓存**: 虚拟机列表可经 `atp_executor::VmCacheManager` 缓存到数据库:
+//! - vm_cache: 缓存 VM 基本信息, 有效期默认 5 分钟 (`with_ttl` 可配置)
+//! - vm_status_history: 同步时记录状态变更历史 (`atp vdi history <vm-name>`)
+//! - 查询策略由 `CacheMode` 决定 (Fresh / Cached / CacheOnly), `force_refresh()` 强制刷新
 //!
-//! **TODO: 添加数据库缓存层** (优先级: 中)
-//!
-//! 建议实现方案:
-//! 1. 创建 VmCacheManager 管理缓存
-//! 2. 添加数据库表:
-//!    - vm_cache: 缓存 VM 基本信息
-//!    - vm_status_history: 记录状态变更历史
-//! 3. 实现混合查询策略:
-//!    - 优先从缓存读取 (缓存有效期如 5 分钟)
-//!    - 缓存过期时从 API 查询并更新缓存
-//!    - 提供强制刷新命令
-//!
-//! 参考实现: docs/DATA_STORAGE_ANALYSIS.md - 建议 2
+//! 参考: docs/DATA_STORAGE_ANALYSIS.md - 建议 2
 
 use serde::{Deserialize, Serialize};
 
@@ -68,6 +55,97 @@ pub struct CreateDomainRequest {
     pub disk_size: u64,
 }
 
+/// 修改虚拟机请求
+///
+/// 平台要求每次修改都携带名称 (不改名时传当前名称), 其余未设置的字段不发送。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateDomainRequest {
+    /// 虚拟机名称
+    pub name: String,
+
+    /// 是否自动加域 (平台编码 1-是, 0-否)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_join_domain: Option<i32>,
+
+    /// 备注
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remark: Option<String>,
+}
+
+impl UpdateDomainRequest {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// 设置是否自动加域
+    pub fn with_auto_join_domain(mut self, value: i32) -> Self {
+        self.auto_join_domain = Some(value);
+        self
+    }
+
+    /// 设置备注
+    pub fn with_remark(mut self, remark: &str) -> Self {
+        self.remark = Some(remark.to_string());
+        self
+    }
+}
+
+/// 克隆虚拟机请求 (`POST /ocloud/v1/domain/{id}/clone`)
+///
+/// 源为模板时 `template_clone` 为 1, 且必须指定存储池。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloneDomainRequest {
+    /// 新虚拟机名称
+    pub name: String,
+
+    /// 存储池 ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_pool_id: Option<String>,
+
+    /// 是否为模板克隆 (平台编码 1-是, 0-否)
+    #[serde(default)]
+    pub template_clone: i32,
+}
+
+impl CloneDomainRequest {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ..Self::default()
+        }
+    }
+
+    /// 设置存储池
+    pub fn with_storage_pool(mut self, storage_pool_id: &str) -> Self {
+        self.storage_pool_id = Some(storage_pool_id.to_string());
+        self
+    }
+
+    /// 设置是否为模板克隆
+    pub fn with_template_clone(mut self, template_clone: bool) -> Self {
+        self.template_clone = i32::from(template_clone);
+        self
+    }
+}
+
+/// 克隆虚拟机结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CloneDomainResult {
+    /// 新虚拟机 ID
+    #[serde(default)]
+    pub clone_uuid: String,
+
+    /// 克隆任务的事件 ID
+    #[serde(default)]
+    pub event_id: String,
+}
+
 /// 桌面池信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DeskPool {
@@ -90,23 +168,294 @@ pub struct DeskPool {
     pub created_at: Option<String>,
 }
 
+/// 桌面池类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeskPoolType {
+    /// 非共享 (专属桌面)
+    #[default]
+    Dedicated,
+
+    /// 共享
+    Shared,
+
+    /// 教学池
+    Classroom,
+}
+
+impl DeskPoolType {
+    /// 平台 API 中的类型编码 (0-非共享, 1-共享, 2-教学池)
+    pub fn code(self) -> i32 {
+        match self {
+            DeskPoolType::Dedicated => 0,
+            DeskPoolType::Shared => 1,
+            DeskPoolType::Classroom => 2,
+        }
+    }
+
+    pub fn from_code(code: i32) -> Option<Self> {
+        match code {
+            0 => Some(DeskPoolType::Dedicated),
+            1 => Some(DeskPoolType::Shared),
+            2 => Some(DeskPoolType::Classroom),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for DeskPoolType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.code())
+    }
+}
+
+/// 同时接受编码 (0/1/2) 与名称 (dedicated/shared/classroom), 便于在场景文件中书写
+impl<'de> Deserialize<'de> for DeskPoolType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Code(i32),
+            Name(String),
+        }
+
+        match Raw::deserialize(deserializer)? {
+            Raw::Code(code) => Self::from_code(code)
+                .ok_or_else(|| serde::de::Error::custom(format!("未知的桌面池类型: {}", code))),
+            Raw::Name(name) => match name.as_str() {
+                "dedicated" => Ok(DeskPoolType::Dedicated),
+                "shared" => Ok(DeskPoolType::Shared),
+                "classroom" => Ok(DeskPoolType::Classroom),
+                other => Err(serde::de::Error::custom(format!("未知的桌面池类型: {}", other))),
+            },
+        }
+    }
+}
+
+/// 桌面池网络配置
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeskPoolNetwork {
+    /// OVS 交换机 ID
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "ovs_id")]
+    pub ovs_id: Option<String>,
+
+    /// VLAN ID
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "vlan_id")]
+    pub vlan_id: Option<String>,
+}
+
+/// 创建桌面池的高级参数
+///
+/// 序列化为平台 API 的 camelCase 字段, 未设置的字段不发送;
+/// 反序列化同时接受 snake_case, 便于在场景文件中书写。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeskPoolAdvanced {
+    /// 桌面池类型 (默认非共享)
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none", alias = "pool_type")]
+    pub pool_type: Option<DeskPoolType>,
+
+    /// 命名规则: 桌面名称前缀
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "domain_prefix")]
+    pub domain_prefix: Option<String>,
+
+    /// 存储池 ID
+    #[serde(default, rename = "poolId", skip_serializing_if = "Option::is_none", alias = "storage_pool_id")]
+    pub storage_pool_id: Option<String>,
+
+    /// 网络列表
+    #[serde(default, rename = "networkList", skip_serializing_if = "Option::is_none", alias = "networks")]
+    pub networks: Option<Vec<DeskPoolNetwork>>,
+
+    /// 回收策略: 关机后强制还原到还原点 (平台编码 1-是, 0-否)
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "bool_as_int",
+        alias = "force_freeze"
+    )]
+    pub force_freeze: Option<bool>,
+
+    /// 频道号 (教学池)
+    #[serde(default, skip_serializing_if = "Option::is_none", alias = "class_no")]
+    pub class_no: Option<String>,
+
+    /// CPU 核心数
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcpu: Option<u32>,
+
+    /// 内存大小 (MB)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u64>,
+
+    /// 备注
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remark: Option<String>,
+}
+
+/// 平台以 1/0 表示布尔值, 反序列化同时接受 true/false
+mod bool_as_int {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &Option<bool>, serializer: S) -> Result<S::Ok, S::Error> {
+        match value {
+            Some(value) => serializer.serialize_i32(*value as i32),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<bool>, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Bool(bool),
+            Int(i64),
+        }
+
+        Ok(Option::<Raw>::deserialize(deserializer)?.map(|raw| match raw {
+            Raw::Bool(value) => value,
+            Raw::Int(value) => value != 0,
+        }))
+    }
+}
+
 /// 创建桌面池请求
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// 字段与平台 `DeskPoolCreateDto` 对齐。
+///
+/// # 示例
+/// ```ignore
+/// let request = CreateDeskPoolRequest::basic("pool-1", "model-1", 10)
+///     .with_pool_type(DeskPoolType::Shared)
+///     .with_domain_prefix("test-")
+///     .with_storage_pool("storage-1");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateDeskPoolRequest {
     /// 桌面池名称
     pub name: String,
 
-    /// 模板 ID
+    /// 模板 ID, 多个用逗号分隔
+    #[serde(rename = "modelId")]
     pub template_id: String,
 
-    /// 虚拟机数量
+    /// 桌面数量
+    #[serde(rename = "amount")]
     pub count: u32,
 
+    /// 桌面池类型
+    #[serde(rename = "type", default)]
+    pub pool_type: DeskPoolType,
+
+    /// 命名规则: 桌面名称前缀
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain_prefix: Option<String>,
+
+    /// 存储池 ID
+    #[serde(default, rename = "poolId", skip_serializing_if = "Option::is_none")]
+    pub storage_pool_id: Option<String>,
+
+    /// 网络列表
+    #[serde(default, rename = "networkList", skip_serializing_if = "Option::is_none")]
+    pub networks: Option<Vec<DeskPoolNetwork>>,
+
+    /// 回收策略: 关机后强制还原到还原点 (平台编码 1-是, 0-否)
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "bool_as_int")]
+    pub force_freeze: Option<bool>,
+
+    /// 频道号 (教学池)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub class_no: Option<String>,
+
     /// CPU 核心数
-    pub vcpu: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcpu: Option<u32>,
 
     /// 内存大小 (MB)
-    pub memory: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<u64>,
+
+    /// 备注
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remark: Option<String>,
+}
+
+impl CreateDeskPoolRequest {
+    /// 只包含必填字段的请求 (非共享桌面池)
+    pub fn basic(name: impl Into<String>, template_id: impl Into<String>, count: u32) -> Self {
+        Self {
+            name: name.into(),
+            template_id: template_id.into(),
+            count,
+            pool_type: DeskPoolType::default(),
+            domain_prefix: None,
+            storage_pool_id: None,
+            networks: None,
+            force_freeze: None,
+            class_no: None,
+            vcpu: None,
+            memory: None,
+            remark: None,
+        }
+    }
+
+    pub fn with_pool_type(mut self, pool_type: DeskPoolType) -> Self {
+        self.pool_type = pool_type;
+        self
+    }
+
+    pub fn with_domain_prefix(mut self, domain_prefix: impl Into<String>) -> Self {
+        self.domain_prefix = Some(domain_prefix.into());
+        self
+    }
+
+    pub fn with_storage_pool(mut self, storage_pool_id: impl Into<String>) -> Self {
+        self.storage_pool_id = Some(storage_pool_id.into());
+        self
+    }
+
+    pub fn with_network(mut self, network: DeskPoolNetwork) -> Self {
+        self.networks.get_or_insert_with(Vec::new).push(network);
+        self
+    }
+
+    pub fn with_force_freeze(mut self, force_freeze: bool) -> Self {
+        self.force_freeze = Some(force_freeze);
+        self
+    }
+
+    pub fn with_class_no(mut self, class_no: impl Into<String>) -> Self {
+        self.class_no = Some(class_no.into());
+        self
+    }
+
+    pub fn with_resources(mut self, vcpu: u32, memory: u64) -> Self {
+        self.vcpu = Some(vcpu);
+        self.memory = Some(memory);
+        self
+    }
+
+    pub fn with_remark(mut self, remark: impl Into<String>) -> Self {
+        self.remark = Some(remark.into());
+        self
+    }
+
+    /// 应用高级参数, 只覆盖其中已设置的字段
+    pub fn with_advanced(mut self, advanced: DeskPoolAdvanced) -> Self {
+        if let Some(pool_type) = advanced.pool_type {
+            self.pool_type = pool_type;
+        }
+        self.domain_prefix = advanced.domain_prefix.or(self.domain_prefix);
+        self.storage_pool_id = advanced.storage_pool_id.or(self.storage_pool_id);
+        self.networks = advanced.networks.or(self.networks);
+        self.force_freeze = advanced.force_freeze.or(self.force_freeze);
+        self.class_no = advanced.class_no.or(self.class_no);
+        self.vcpu = advanced.vcpu.or(self.vcpu);
+        self.memory = advanced.memory.or(self.memory);
+        self.remark = advanced.remark.or(self.remark);
+        self
+    }
 }
 
 /// 主机信息
@@ -153,6 +502,224 @@ pub struct HostStatus {
     pub memory_usage: f64,
 }
 
+/// 主机负载详情 (`HostApi::get_detail`)
+///
+/// 已用 CPU / 内存按主机上运行中虚拟机的配置累加, 不是主机的实时使用率。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostDetail {
+    /// 主机 ID
+    pub id: String,
+
+    /// 主机名称
+    pub name: String,
+
+    /// 主机 IP
+    pub ip: String,
+
+    /// 平台状态码 (0-维护, 1-启用, 2-关闭, 3-离线)
+    pub status: i64,
+
+    /// CPU 总核心数
+    pub cpu_total: u32,
+
+    /// 已分配给运行中虚拟机的 CPU 核心数
+    pub cpu_used: u32,
+
+    /// 总内存 (MB)
+    pub memory_total_mb: u64,
+
+    /// 已分配给运行中虚拟机的内存 (MB)
+    pub memory_used_mb: u64,
+
+    /// 运行中的虚拟机数量
+    pub running_vms: u32,
+
+    /// 主机可用的存储池 ID (本机存储池与所在资源池的共享存储池)
+    pub storage_pool_ids: Vec<String>,
+}
+
+impl HostDetail {
+    /// 主机是否处于启用状态
+    pub fn is_enabled(&self) -> bool {
+        self.status == 1
+    }
+
+    /// 空闲内存 (MB)
+    pub fn free_memory_mb(&self) -> u64 {
+        self.memory_total_mb.saturating_sub(self.memory_used_mb)
+    }
+
+    /// 空闲 CPU 核心数
+    pub fn free_cpu(&self) -> u32 {
+        self.cpu_total.saturating_sub(self.cpu_used)
+    }
+
+    /// 把即将启动的虚拟机计入已用资源 (批量放置时避免同一主机被连续选中)
+    pub fn reserve(&mut self, cpu: u32, memory_mb: u64) {
+        self.cpu_used = self.cpu_used.saturating_add(cpu);
+        self.memory_used_mb = self.memory_used_mb.saturating_add(memory_mb);
+        self.running_vms += 1;
+    }
+}
+
+/// 批量虚拟机任务请求 (启动 / 关机 / 重启 / 重置 / 取消还原点)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTaskRequest {
+    /// 虚拟机 ID 列表
+    pub id_list: Vec<String>,
+
+    /// 启动时指定运行的主机, 未设置时由平台选择
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_id: Option<String>,
+
+    /// 强制关机标记 (1-强制, 0-不强制), 仅用于关机
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_force: Option<i32>,
+}
+
+impl BatchTaskRequest {
+    pub fn new(id_list: Vec<String>) -> Self {
+        Self {
+            id_list,
+            host_id: None,
+            is_force: None,
+        }
+    }
+
+    /// 指定运行的主机
+    pub fn with_host(mut self, host_id: impl Into<String>) -> Self {
+        self.host_id = Some(host_id.into());
+        self
+    }
+
+    /// 强制关机
+    pub fn with_force(mut self) -> Self {
+        self.is_force = Some(1);
+        self
+    }
+}
+
+/// 还原点触发周期
+///
+/// 设置还原点后, 虚拟机在对应时机 (或手动重置时) 丢弃还原点之后的磁盘改动。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FreezeType {
+    /// 不还原 (取消还原点)
+    Never,
+
+    /// 每次重启还原
+    EveryBoot,
+
+    /// 每天还原
+    Daily,
+
+    /// 每周还原
+    Weekly,
+
+    /// 每月还原
+    Monthly,
+}
+
+impl FreezeType {
+    /// 平台 API 中的类型编码 (0-无, 1-每次, 2-每天, 3-每周, 4-每月)
+    pub fn code(self) -> i32 {
+        match self {
+            FreezeType::Never => 0,
+            FreezeType::EveryBoot => 1,
+            FreezeType::Daily => 2,
+            FreezeType::Weekly => 3,
+            FreezeType::Monthly => 4,
+        }
+    }
+}
+
+/// 批量设置还原点请求 (`POST /ocloud/v1/domain/freeze`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchFreezeRequest {
+    /// 还原类型编码, 见 [`FreezeType::code`]
+    pub freeze_type: i32,
+
+    /// 虚拟机 ID 列表
+    pub id_list: Vec<String>,
+}
+
+impl BatchFreezeRequest {
+    pub fn new(id_list: Vec<String>, freeze_type: FreezeType) -> Self {
+        Self {
+            freeze_type: freeze_type.code(),
+            id_list,
+        }
+    }
+}
+
+/// 批量任务中出错的虚拟机
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTaskError {
+    /// 虚拟机 ID
+    #[serde(default)]
+    pub domain_id: String,
+
+    /// 虚拟机名称
+    #[serde(default)]
+    pub domain_name: String,
+
+    /// 错误信息
+    #[serde(default)]
+    pub error_msg: String,
+}
+
+/// 批量任务结果
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchTaskResult {
+    /// 出错的虚拟机
+    #[serde(default)]
+    pub error_list: Vec<BatchTaskError>,
+
+    /// 操作成功的虚拟机对应的事件 ID
+    #[serde(default)]
+    pub event_id_list: Vec<String>,
+}
+
+/// SPICE 连接票据 (`GET /ocloud/v1/domain/{id}/spice-key`)
+///
+/// `key` 是平台为虚拟机设置的 SPICE 密码, 连接 `ip:port` 时作为票据使用。
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpiceKey {
+    #[serde(default)]
+    pub domain_id: String,
+
+    #[serde(default)]
+    pub host_id: String,
+
+    /// 虚拟机名称
+    #[serde(default)]
+    pub name: String,
+
+    /// SPICE 服务地址 (虚拟机所在主机)
+    #[serde(default)]
+    pub ip: String,
+
+    /// SPICE 端口 (平台以字符串返回)
+    #[serde(default)]
+    pub port: String,
+
+    /// SPICE 密码
+    #[serde(default)]
+    pub key: String,
+}
+
+impl SpiceKey {
+    /// 解析端口, 虚拟机未运行时平台可能返回空端口
+    pub fn port(&self) -> Option<u16> {
+        self.port.trim().parse().ok()
+    }
+}
+
 /// 模板信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Model {
@@ -235,3 +802,97 @@ pub struct PageResponse<T> {
     /// 数据列表
     pub items: Vec<T>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_basic_request_serializes_required_fields_only() {
+        let request = CreateDeskPoolRequest::basic("pool-1", "model-1", 10);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({"name": "pool-1", "modelId": "model-1", "amount": 10, "type": 0})
+        );
+    }
+
+    #[test]
+    fn test_builder_serializes_platform_fields() {
+        let request = CreateDeskPoolRequest::basic("pool-1", "model-1,model-2", 3)
+            .with_pool_type(DeskPoolType::Classroom)
+            .with_domain_prefix("class-")
+            .with_storage_pool("storage-1")
+            .with_network(DeskPoolNetwork {
+                ovs_id: Some("ovs-1".to_string()),
+                vlan_id: None,
+            })
+            .with_force_freeze(true)
+            .with_class_no("101")
+            .with_resources(4, 8192)
+            .with_remark("测试");
+
+        let value = serde_json::to_value(&request).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "name": "pool-1",
+                "modelId": "model-1,model-2",
+                "amount": 3,
+                "type": 2,
+                "domainPrefix": "class-",
+                "poolId": "storage-1",
+                "networkList": [{"ovsId": "ovs-1"}],
+                "forceFreeze": 1,
+                "classNo": "101",
+                "vcpu": 4,
+                "memory": 8192,
+                "remark": "测试"
+            })
+        );
+
+        let parsed: CreateDeskPoolRequest = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed, request);
+    }
+
+    #[test]
+    fn test_advanced_accepts_snake_case_and_overrides_set_fields() {
+        let advanced: DeskPoolAdvanced = serde_json::from_value(json!({
+            "pool_type": "shared",
+            "domain_prefix": "vm-",
+            "force_freeze": false,
+            "networks": [{"vlan_id": "100"}]
+        }))
+        .unwrap();
+
+        let request = CreateDeskPoolRequest::basic("pool-1", "model-1", 2)
+            .with_storage_pool("storage-1")
+            .with_advanced(advanced);
+
+        assert_eq!(request.pool_type, DeskPoolType::Shared);
+        assert_eq!(request.domain_prefix.as_deref(), Some("vm-"));
+        assert_eq!(request.force_freeze, Some(false));
+        assert_eq!(request.networks.as_ref().unwrap()[0].vlan_id.as_deref(), Some("100"));
+        // 高级参数中未设置的字段保留原值
+        assert_eq!(request.storage_pool_id.as_deref(), Some("storage-1"));
+
+        assert!(serde_json::from_value::<DeskPoolAdvanced>(json!({"type": 5})).is_err());
+    }
+
+    #[test]
+    fn test_spice_key_port() {
+        let key: SpiceKey = serde_json::from_value(json!({
+            "domainId": "vm-1",
+            "hostId": "host-1",
+            "ip": "10.0.0.5",
+            "port": "5901",
+            "key": "s3cret"
+        }))
+        .unwrap();
+        assert_eq!(key.port(), Some(5901));
+        assert_eq!(key.key, "s3cret");
+
+        let stopped: SpiceKey = serde_json::from_value(json!({"domainId": "vm-2", "port": ""})).unwrap();
+        assert_eq!(stopped.port(), None);
+    }
+}
@@ -0,0 +1,243 @@
+//! 请求限流
+//!
+//! 批量场景中并发调用平台 API (如为 500 台虚拟机逐台查询详情) 会触发平台限流与偶发的 503。
+//! [`RateLimiter`] 在发送请求前依次经过三道限制:
+//!
+//! - 并发上限: 同时在途的请求数 (信号量)
+//! - 最小间隔: 相邻两个请求的发出时间至少相隔 `min_interval`
+//! - 令牌桶 (可选): 平均每秒请求数, 允许最多 1 秒的突发
+//!
+//! 限流器由 `Arc` 共享, 同一个客户端的所有 API 以及客户端的克隆共用同一份配额。
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+/// 限流配置
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    /// 最大并发请求数
+    pub max_concurrent: usize,
+
+    /// 相邻两个请求之间的最小间隔
+    pub min_interval: Duration,
+
+    /// 平均每秒请求数 (None 表示不限)
+    pub requests_per_second: Option<f64>,
+}
+
+impl RateLimit {
+    pub fn new(max_concurrent: usize, min_interval: Duration) -> Self {
+        Self {
+            max_concurrent: max_concurrent.max(1),
+            min_interval,
+            requests_per_second: None,
+        }
+    }
+
+    /// 设置平均每秒请求数
+    pub fn with_requests_per_second(mut self, requests_per_second: f64) -> Self {
+        self.requests_per_second = Some(requests_per_second).filter(|rps| *rps > 0.0);
+        self
+    }
+}
+
+/// 限流器运行统计 (用于排查)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RateLimiterStats {
+    /// 当前在途的请求数
+    pub in_flight: usize,
+
+    /// 经过限流器的请求总数
+    pub total_requests: u64,
+
+    /// 因限流而等待过的请求数
+    pub throttled_waits: u64,
+}
+
+/// 令牌桶状态 (令牌数可以为负, 表示已被预约)
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// 请求限流器
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: RateLimit,
+    semaphore: Semaphore,
+    /// 下一个请求最早的发出时间
+    next_slot: Mutex<Instant>,
+    bucket: Mutex<TokenBucket>,
+    in_flight: AtomicUsize,
+    total_requests: AtomicU64,
+    throttled_waits: AtomicU64,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            semaphore: Semaphore::new(limits.max_concurrent.max(1)),
+            next_slot: Mutex::new(now),
+            bucket: Mutex::new(TokenBucket {
+                tokens: bucket_capacity(&limits),
+                updated: now,
+            }),
+            limits,
+            in_flight: AtomicUsize::new(0),
+            total_requests: AtomicU64::new(0),
+            throttled_waits: AtomicU64::new(0),
+        }
+    }
+
+    /// 限流配置
+    pub fn limits(&self) -> RateLimit {
+        self.limits
+    }
+
+    /// 等待到允许发送请求, 返回的许可在请求结束 (丢弃) 前占用一个并发名额
+    pub async fn acquire(&self) -> RatePermit<'_> {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+        let mut throttled = false;
+
+        let permit = match self.semaphore.try_acquire() {
+            Ok(permit) => permit,
+            Err(_) => {
+                throttled = true;
+                // 信号量不会被关闭
+                self.semaphore.acquire().await.expect("限流信号量已关闭")
+            }
+        };
+
+        let now = Instant::now();
+        let start = self.reserve_slot(now).await.max(self.reserve_token(now).await);
+        if start > now {
+            throttled = true;
+            tokio::time::sleep_until(start).await;
+        }
+
+        if throttled {
+            self.throttled_waits.fetch_add(1, Ordering::Relaxed);
+        }
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        RatePermit { limiter: self, _permit: permit }
+    }
+
+    /// 当前统计
+    pub fn stats(&self) -> RateLimiterStats {
+        RateLimiterStats {
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            total_requests: self.total_requests.load(Ordering::Relaxed),
+            throttled_waits: self.throttled_waits.load(Ordering::Relaxed),
+        }
+    }
+
+    /// 按最小间隔预约发出时间
+    async fn reserve_slot(&self, now: Instant) -> Instant {
+        if self.limits.min_interval.is_zero() {
+            return now;
+        }
+        let mut next_slot = self.next_slot.lock().await;
+        let start = (*next_slot).max(now);
+        *next_slot = start + self.limits.min_interval;
+        start
+    }
+
+    /// 从令牌桶预约一个令牌, 令牌不足时返回补足后的时间
+    async fn reserve_token(&self, now: Instant) -> Instant {
+        let Some(rate) = self.limits.requests_per_second else {
+            return now;
+        };
+        let mut bucket = self.bucket.lock().await;
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(bucket_capacity(&self.limits));
+        bucket.updated = now;
+        bucket.tokens -= 1.0;
+
+        if bucket.tokens >= 0.0 {
+            now
+        } else {
+            now + Duration::from_secs_f64(-bucket.tokens / rate)
+        }
+    }
+}
+
+/// 令牌桶容量: 最多突发 1 秒的请求量 (至少 1 个)
+fn bucket_capacity(limits: &RateLimit) -> f64 {
+    limits.requests_per_second.map_or(0.0, |rate| rate.max(1.0))
+}
+
+/// 限流许可, 丢弃时释放并发名额
+pub struct RatePermit<'a> {
+    limiter: &'a RateLimiter,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Drop for RatePermit<'_> {
+    fn drop(&mut self) {
+        self.limiter.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_max_concurrent() {
+        let limiter = Arc::new(RateLimiter::new(RateLimit::new(2, Duration::ZERO)));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..5)
+            .map(|_| {
+                let limiter = Arc::clone(&limiter);
+                let peak = Arc::clone(&peak);
+                tokio::spawn(async move {
+                    let _permit = limiter.acquire().await;
+                    peak.fetch_max(limiter.stats().in_flight, Ordering::Relaxed);
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        assert_eq!(peak.load(Ordering::Relaxed), 2);
+        let stats = limiter.stats();
+        assert_eq!((stats.in_flight, stats.total_requests, stats.throttled_waits), (0, 5, 3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_min_interval() {
+        let limiter = RateLimiter::new(RateLimit::new(10, Duration::from_millis(100)));
+        let started = Instant::now();
+
+        for _ in 0..3 {
+            drop(limiter.acquire().await);
+        }
+
+        assert_eq!(started.elapsed(), Duration::from_millis(200));
+        assert_eq!(limiter.stats().throttled_waits, 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_requests_per_second() {
+        // 每秒 2 个请求: 桶里的 2 个令牌立即可用, 之后每 500ms 一个
+        let limiter = RateLimiter::new(RateLimit::new(10, Duration::ZERO).with_requests_per_second(2.0));
+        let started = Instant::now();
+
+        for _ in 0..4 {
+            drop(limiter.acquire().await);
+        }
+
+        assert_eq!(started.elapsed(), Duration::from_millis(1000));
+        assert_eq!(limiter.stats().throttled_waits, 2);
+    }
+}
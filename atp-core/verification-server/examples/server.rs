@@ -32,6 +32,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         default_timeout: Duration::from_secs(30),
         cleanup_interval: Duration::from_secs(60),
         max_pending_events: 1000,
+        late_result_grace: Duration::from_secs(60),
     };
     let verification_service = Arc::new(VerificationService::new(
         client_manager.clone(),
@@ -42,6 +43,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let server_config = ServerConfig {
         websocket_addr: Some("0.0.0.0:8765".parse::<SocketAddr>()?),
         tcp_addr: Some("0.0.0.0:8766".parse::<SocketAddr>()?),
+        ..Default::default()
     };
 
     info!("WebSocket 服务器地址: 0.0.0.0:8765");
@@ -0,0 +1,72 @@
+//! Agent 认证
+//!
+//! 开启认证后, Agent 的握手必须是带 `token` 的注册消息, 旧版的纯文本 VM ID 握手会被拒绝。
+//! token 列表可在运行中替换 (独立部署时由 SIGHUP 触发重载)。
+
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+
+use crate::{Result, VerificationError};
+
+/// 允许接入的 token 列表, 克隆后共享同一份列表
+#[derive(Debug, Clone, Default)]
+pub struct AuthTokens {
+    /// None 表示未开启认证
+    tokens: Option<Arc<RwLock<HashSet<String>>>>,
+}
+
+impl AuthTokens {
+    /// 不开启认证
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    /// 开启认证 (列表为空时拒绝所有 Agent)
+    pub fn new(tokens: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            tokens: Some(Arc::new(RwLock::new(tokens.into_iter().collect()))),
+        }
+    }
+
+    /// 是否开启认证
+    pub fn is_enabled(&self) -> bool {
+        self.tokens.is_some()
+    }
+
+    /// 当前 token 数量
+    pub fn len(&self) -> usize {
+        self.tokens
+            .as_ref()
+            .map(|tokens| tokens.read().unwrap_or_else(|e| e.into_inner()).len())
+            .unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 替换 token 列表, 未开启认证时返回 false (运行中不能开启或关闭认证)
+    pub fn replace(&self, new_tokens: impl IntoIterator<Item = String>) -> bool {
+        match &self.tokens {
+            Some(tokens) => {
+                *tokens.write().unwrap_or_else(|e| e.into_inner()) = new_tokens.into_iter().collect();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 校验 Agent 提供的 token
+    pub fn verify(&self, token: Option<&str>) -> Result<()> {
+        let Some(tokens) = &self.tokens else {
+            return Ok(());
+        };
+
+        let token = token.ok_or_else(|| VerificationError::Unauthorized("缺少认证 token".to_string()))?;
+        if tokens.read().unwrap_or_else(|e| e.into_inner()).contains(token) {
+            Ok(())
+        } else {
+            Err(VerificationError::Unauthorized("token 无效".to_string()))
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! 多 VM 广播验证
+//!
+//! 同一事件发给多台虚拟机的 Agent (如 "50 台虚拟机同时按回车"), 汇总每台的结论与延迟分布。
+//! 每台虚拟机的 event_id 由广播 ID 与 VM ID 派生 (UUID v3), 同一次广播的结果可以按广播 ID
+//! 关联; 未连接的 Agent 记为跳过, 不参与等待与延迟统计。
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::types::VerifyOutcome;
+
+/// 由广播 ID 派生单台虚拟机的 event_id
+pub fn derive_event_id(broadcast_id: Uuid, vm_id: &str) -> Uuid {
+    Uuid::new_v3(&broadcast_id, vm_id.as_bytes())
+}
+
+/// 单台虚拟机的广播结论
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BroadcastStatus {
+    /// Agent 确认观察到了事件
+    Verified,
+
+    /// 收到结果, 但 Agent 未观察到期望的输入
+    Mismatched,
+
+    /// 截止时间前未收到结果
+    Timeout,
+
+    /// Agent 未连接, 没有发送事件
+    Skipped,
+
+    /// 发送事件失败 (如待验证事件表已满)
+    Failed,
+}
+
+/// 单台虚拟机的广播结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BroadcastVmResult {
+    pub vm_id: String,
+
+    /// 发给该虚拟机的 event_id (跳过时为空)
+    pub event_id: Option<String>,
+
+    pub status: BroadcastStatus,
+
+    /// 服务端测得的延迟 (毫秒)
+    pub server_latency_ms: Option<u64>,
+
+    /// Agent 上报的延迟 (毫秒)
+    pub agent_latency_ms: Option<u64>,
+
+    /// 跳过或失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl BroadcastVmResult {
+    /// 未发送事件的结果
+    pub fn not_sent(vm_id: &str, status: BroadcastStatus, reason: String) -> Self {
+        Self {
+            vm_id: vm_id.to_string(),
+            event_id: None,
+            status,
+            server_latency_ms: None,
+            agent_latency_ms: None,
+            reason: Some(reason),
+        }
+    }
+
+    /// 由已发送事件的结论构造
+    pub fn from_outcome(vm_id: &str, event_id: Uuid, outcome: &VerifyOutcome) -> Self {
+        let status = match outcome {
+            VerifyOutcome::Verified(_) => BroadcastStatus::Verified,
+            VerifyOutcome::Mismatched(_) => BroadcastStatus::Mismatched,
+            VerifyOutcome::TimedOut { .. } => BroadcastStatus::Timeout,
+        };
+
+        Self {
+            vm_id: vm_id.to_string(),
+            event_id: Some(event_id.to_string()),
+            status,
+            server_latency_ms: outcome.matched().map(|m| m.server_latency_ms),
+            agent_latency_ms: outcome.matched().map(|m| m.agent_latency_ms),
+            reason: None,
+        }
+    }
+}
+
+/// 延迟分布 (毫秒, 百分位按最近秩计算)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LatencyStats {
+    pub count: usize,
+    pub min_ms: u64,
+    pub median_ms: u64,
+    pub p95_ms: u64,
+    pub max_ms: u64,
+}
+
+impl LatencyStats {
+    /// 没有样本时返回 None
+    pub fn from_samples(samples: &[u64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+
+        let percentile = |p: f64| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+
+        Some(Self {
+            count: sorted.len(),
+            min_ms: sorted[0],
+            median_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            max_ms: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+/// 一次广播的汇总结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BroadcastOutcome {
+    /// 广播 ID (各虚拟机的 event_id 由其派生)
+    pub broadcast_id: String,
+
+    pub event_type: String,
+
+    /// 各虚拟机的结果, 与请求中的 VM 顺序一致
+    pub results: Vec<BroadcastVmResult>,
+
+    pub verified: usize,
+    pub mismatched: usize,
+    pub timed_out: usize,
+    pub skipped: usize,
+    pub failed: usize,
+
+    /// 收到结果的虚拟机的服务端延迟分布 (包括验证失败)
+    pub latency: Option<LatencyStats>,
+
+    /// 从发送到最后一个结论的时间 (毫秒)
+    pub elapsed_ms: u64,
+}
+
+impl BroadcastOutcome {
+    pub fn new(broadcast_id: Uuid, event_type: &str, results: Vec<BroadcastVmResult>, elapsed_ms: u64) -> Self {
+        let count = |status: BroadcastStatus| results.iter().filter(|r| r.status == status).count();
+        let latencies: Vec<u64> = results.iter().filter_map(|r| r.server_latency_ms).collect();
+
+        Self {
+            broadcast_id: broadcast_id.to_string(),
+            event_type: event_type.to_string(),
+            verified: count(BroadcastStatus::Verified),
+            mismatched: count(BroadcastStatus::Mismatched),
+            timed_out: count(BroadcastStatus::Timeout),
+            skipped: count(BroadcastStatus::Skipped),
+            failed: count(BroadcastStatus::Failed),
+            latency: LatencyStats::from_samples(&latencies),
+            results,
+            elapsed_ms,
+        }
+    }
+
+    /// 发送了事件的虚拟机是否全部验证成功 (跳过的不计)
+    pub fn all_verified(&self) -> bool {
+        self.verified + self.skipped == self.results.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{MatchedResult, VerifyResult};
+
+    fn matched(server_latency_ms: u64) -> MatchedResult {
+        MatchedResult {
+            result: VerifyResult {
+                event_id: String::new(),
+                verified: true,
+                timestamp: 0,
+                latency_ms: 1,
+                details: serde_json::json!({}),
+            },
+            server_latency_ms,
+            agent_latency_ms: 1,
+        }
+    }
+
+    #[test]
+    fn test_latency_stats() {
+        assert_eq!(LatencyStats::from_samples(&[]), None);
+
+        let stats = LatencyStats::from_samples(&[7]).unwrap();
+        assert_eq!((stats.min_ms, stats.median_ms, stats.p95_ms, stats.max_ms), (7, 7, 7, 7));
+
+        // 1..=20 乱序: 中位数取第 10 个, p95 取第 19 个
+        let samples: Vec<u64> = (1..=20).rev().collect();
+        let stats = LatencyStats::from_samples(&samples).unwrap();
+        assert_eq!(stats.count, 20);
+        assert_eq!((stats.min_ms, stats.median_ms, stats.p95_ms, stats.max_ms), (1, 10, 19, 20));
+    }
+
+    #[test]
+    fn test_derive_event_id() {
+        let base = Uuid::new_v4();
+        assert_eq!(derive_event_id(base, "vm-1"), derive_event_id(base, "vm-1"));
+        assert_ne!(derive_event_id(base, "vm-1"), derive_event_id(base, "vm-2"));
+        assert_ne!(derive_event_id(base, "vm-1"), derive_event_id(Uuid::new_v4(), "vm-1"));
+    }
+
+    #[test]
+    fn test_broadcast_outcome() {
+        let base = Uuid::new_v4();
+        let results = vec![
+            BroadcastVmResult::from_outcome("vm-1", derive_event_id(base, "vm-1"), &VerifyOutcome::Verified(matched(20))),
+            BroadcastVmResult::from_outcome("vm-2", derive_event_id(base, "vm-2"), &VerifyOutcome::Mismatched(matched(40))),
+            BroadcastVmResult::from_outcome("vm-3", derive_event_id(base, "vm-3"), &VerifyOutcome::TimedOut { elapsed_ms: 100 }),
+            BroadcastVmResult::not_sent("vm-4", BroadcastStatus::Skipped, "客户端未连接".to_string()),
+        ];
+        let outcome = BroadcastOutcome::new(base, "keyboard", results, 100);
+
+        assert_eq!(
+            (outcome.verified, outcome.mismatched, outcome.timed_out, outcome.skipped, outcome.failed),
+            (1, 1, 1, 1, 0)
+        );
+        let latency = outcome.latency.unwrap();
+        assert_eq!((latency.count, latency.min_ms, latency.max_ms), (2, 20, 40));
+        assert!(!outcome.all_verified());
+
+        let json = serde_json::to_value(&outcome).unwrap();
+        assert_eq!(json["results"][2]["status"], "timeout");
+        assert_eq!(json["results"][3]["reason"], "客户端未连接");
+        assert!(json["results"][0].get("reason").is_none());
+    }
+}
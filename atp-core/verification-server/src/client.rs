@@ -1,33 +1,257 @@
 //! 客户端连接管理
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::{mpsc, RwLock};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, watch, RwLock};
+use tokio::time::Instant;
 use tracing::{debug, info, warn};
 
-use crate::types::{ClientInfo, Event, VerifyResult};
+use crate::types::{
+    AgentMode, ClientConnection, ClientInfo, Event, RawInputEvent, RawInputReport, RegisterMessage,
+    VerifyResult, RAW_INPUT_CAPABILITY,
+};
 use crate::{Result, VerificationError};
 
+/// 客户端最近活动时间 (Unix 毫秒)
+///
+/// 连接处理任务每收到一条消息更新一次, 不需要获取注册表的写锁。
+#[derive(Debug)]
+pub struct ClientActivity {
+    last_activity: AtomicI64,
+
+    /// 最近一次心跳时间 (Unix 毫秒), 0 表示尚未收到心跳
+    last_heartbeat: AtomicI64,
+
+    /// 最近一次心跳上报的 Agent 运行时长 (秒)
+    uptime_s: AtomicU64,
+}
+
+impl ClientActivity {
+    fn new() -> Self {
+        Self {
+            last_activity: AtomicI64::new(chrono::Utc::now().timestamp_millis()),
+            last_heartbeat: AtomicI64::new(0),
+            uptime_s: AtomicU64::new(0),
+        }
+    }
+
+    /// 记录一次活动
+    pub fn touch(&self) {
+        self.last_activity.store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    /// 记录一次心跳
+    pub fn heartbeat(&self, uptime_s: u64) {
+        self.uptime_s.store(uptime_s, Ordering::Relaxed);
+        self.last_heartbeat.store(chrono::Utc::now().timestamp_millis(), Ordering::Relaxed);
+    }
+
+    /// 最近活动时间
+    pub fn last_activity(&self) -> chrono::DateTime<chrono::Utc> {
+        chrono::DateTime::from_timestamp_millis(self.last_activity.load(Ordering::Relaxed)).unwrap_or_default()
+    }
+
+    /// 最近心跳时间, 尚未收到心跳时为 `None`
+    pub fn last_heartbeat(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        match self.last_heartbeat.load(Ordering::Relaxed) {
+            0 => None,
+            millis => chrono::DateTime::from_timestamp_millis(millis),
+        }
+    }
+
+    /// 最近一次心跳上报的 Agent 运行时长, 尚未收到心跳时为 `None`
+    pub fn agent_uptime_s(&self) -> Option<u64> {
+        self.last_heartbeat().map(|_| self.uptime_s.load(Ordering::Relaxed))
+    }
+}
+
 /// 客户端会话
 pub struct ClientSession {
     /// 客户端信息
     pub info: ClientInfo,
 
+    /// 客户端连接
+    pub connection: ClientConnection,
+
+    /// 会话 ID (区分同一 VM ID 的先后连接)
+    pub session_id: u64,
+
     /// 发送事件到客户端的通道
     pub event_tx: mpsc::UnboundedSender<Event>,
 
     /// 是否已连接
     pub connected: bool,
+
+    /// 最近活动时间
+    pub activity: Arc<ClientActivity>,
+}
+
+/// 注册成功后返回给连接处理任务的句柄
+pub struct ClientRegistration {
+    /// 会话 ID, 注销时使用
+    pub session_id: u64,
+
+    /// 接收发往该客户端的事件; 通道关闭表示连接已被接管
+    pub event_rx: mpsc::UnboundedReceiver<Event>,
+
+    /// 收到客户端消息时更新的活动时间
+    pub activity: Arc<ClientActivity>,
+}
+
+/// 客户端注册表 (VM ID -> 会话)
+///
+/// 同一 VM ID 同时只允许一个连接, 后来的连接被拒绝;
+/// 开启 `allow_takeover` 时由新连接接管, 旧连接的事件通道随之关闭。
+#[derive(Default)]
+pub struct ClientRegistry {
+    sessions: HashMap<String, ClientSession>,
+
+    /// 是否允许新连接接管同一 VM ID 的旧连接
+    allow_takeover: bool,
+
+    /// 下一个会话 ID
+    next_session_id: u64,
+}
+
+impl ClientRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_allow_takeover(mut self, allow_takeover: bool) -> Self {
+        self.allow_takeover = allow_takeover;
+        self
+    }
+
+    /// 注册客户端
+    pub fn register(
+        &mut self,
+        connection: ClientConnection,
+        registration: &RegisterMessage,
+    ) -> Result<ClientRegistration> {
+        registration.validate()?;
+        let vm_id = registration.vm_id.clone();
+
+        if let Some(existing) = self.sessions.get(&vm_id) {
+            if !self.allow_takeover {
+                return Err(VerificationError::DuplicateClient(
+                    vm_id,
+                    existing.connection.addr().to_string(),
+                ));
+            }
+            warn!(
+                "客户端 {} 已存在 ({}), 由新连接 {} 接管",
+                vm_id,
+                existing.connection.addr(),
+                connection.addr()
+            );
+        }
+
+        self.next_session_id += 1;
+        let session_id = self.next_session_id;
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let activity = Arc::new(ClientActivity::new());
+
+        let session = ClientSession {
+            info: ClientInfo {
+                vm_id: vm_id.clone(),
+                connected_at: chrono::Utc::now(),
+                last_activity: activity.last_activity(),
+                transport: connection.transport().to_string(),
+                remote_addr: Some(connection.addr().to_string()),
+                agent_version: registration.agent_version.clone(),
+                capabilities: registration.capabilities.clone(),
+                last_heartbeat: None,
+                agent_uptime_s: None,
+            },
+            connection,
+            session_id,
+            event_tx,
+            connected: true,
+            activity: activity.clone(),
+        };
+
+        self.sessions.insert(vm_id, session);
+
+        Ok(ClientRegistration { session_id, event_rx, activity })
+    }
+
+    /// 用连接建立后补发的注册消息更新版本与能力
+    pub fn update(&mut self, vm_id: &str, session_id: u64, registration: &RegisterMessage) -> Result<()> {
+        if registration.vm_id != vm_id {
+            return Err(VerificationError::InvalidHandshake(format!(
+                "注册消息中的 VM ID {} 与连接的 VM ID {} 不一致",
+                registration.vm_id, vm_id
+            )));
+        }
+
+        match self.sessions.get_mut(vm_id) {
+            Some(session) if session.session_id == session_id => {
+                session.info.agent_version = registration.agent_version.clone();
+                session.info.capabilities = registration.capabilities.clone();
+                Ok(())
+            }
+            _ => Err(VerificationError::ClientNotConnected(vm_id.to_string())),
+        }
+    }
+
+    /// 注销客户端
+    ///
+    /// 只移除 `session_id` 对应的会话, 已被接管的旧连接断开时不会影响新连接。
+    pub fn unregister(&mut self, vm_id: &str, session_id: u64) -> bool {
+        match self.sessions.get(vm_id) {
+            Some(session) if session.session_id == session_id => {
+                self.sessions.remove(vm_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn get(&self, vm_id: &str) -> Option<&ClientSession> {
+        self.sessions.get(vm_id)
+    }
+
+    pub fn get_mut(&mut self, vm_id: &str) -> Option<&mut ClientSession> {
+        self.sessions.get_mut(vm_id)
+    }
+
+    /// 所有客户端信息 (按 VM ID 排序)
+    pub fn list(&self) -> Vec<ClientInfo> {
+        let mut clients: Vec<ClientInfo> = self
+            .sessions
+            .values()
+            .map(|session| ClientInfo {
+                last_activity: session.activity.last_activity(),
+                last_heartbeat: session.activity.last_heartbeat(),
+                agent_uptime_s: session.activity.agent_uptime_s(),
+                ..session.info.clone()
+            })
+            .collect();
+        clients.sort_by(|a, b| a.vm_id.cmp(&b.vm_id));
+        clients
+    }
 }
 
+/// 输入上报广播通道容量, 订阅者落后超过该数量时跳过旧消息
+const RAW_INPUT_CHANNEL_CAPACITY: usize = 1024;
+
 /// 客户端管理器
 pub struct ClientManager {
-    /// VM ID -> 客户端会话
-    clients: Arc<RwLock<HashMap<String, ClientSession>>>,
+    /// 客户端注册表
+    clients: RwLock<ClientRegistry>,
 
     /// 结果接收通道（所有客户端共享）
-    result_rx: Arc<RwLock<Option<mpsc::UnboundedReceiver<VerifyResult>>>>,
+    result_rx: RwLock<Option<mpsc::UnboundedReceiver<VerifyResult>>>,
     result_tx: mpsc::UnboundedSender<VerifyResult>,
+
+    /// 关闭通知 (true 表示服务器正在关闭)
+    shutdown_tx: watch::Sender<bool>,
+
+    /// 输入上报广播 (所有客户端共享)
+    raw_input_tx: broadcast::Sender<RawInputReport>,
 }
 
 impl ClientManager {
@@ -36,41 +260,54 @@ impl ClientManager {
         let (result_tx, result_rx) = mpsc::unbounded_channel();
 
         Self {
-            clients: Arc::new(RwLock::new(HashMap::new())),
-            result_rx: Arc::new(RwLock::new(Some(result_rx))),
+            clients: RwLock::new(ClientRegistry::new()),
+            result_rx: RwLock::new(Some(result_rx)),
             result_tx,
+            shutdown_tx: watch::channel(false).0,
+            raw_input_tx: broadcast::channel(RAW_INPUT_CHANNEL_CAPACITY).0,
         }
     }
 
+    /// 允许新连接接管同一 VM ID 的旧连接 (默认拒绝重复连接)
+    pub fn with_allow_takeover(mut self, allow_takeover: bool) -> Self {
+        self.clients.get_mut().allow_takeover = allow_takeover;
+        self
+    }
+
     /// 注册客户端
+    ///
+    /// 同一 VM ID 已有连接且未开启接管时返回 `DuplicateClient`, 服务器关闭过程中返回 `ShuttingDown`。
     pub async fn register_client(
         &self,
-        info: ClientInfo,
-    ) -> Result<mpsc::UnboundedReceiver<Event>> {
-        let vm_id = info.vm_id.clone();
-        let (event_tx, event_rx) = mpsc::unbounded_channel();
-
-        let session = ClientSession {
-            info,
-            event_tx,
-            connected: true,
-        };
-
-        let mut clients = self.clients.write().await;
-        if clients.contains_key(&vm_id) {
-            warn!("客户端 {} 已存在，将被替换", vm_id);
+        connection: ClientConnection,
+        registration: &RegisterMessage,
+    ) -> Result<ClientRegistration> {
+        if self.is_shutting_down() {
+            return Err(VerificationError::ShuttingDown);
         }
+        let registered = self.clients.write().await.register(connection, registration)?;
+        info!("注册客户端: {}", registration.vm_id);
+        Ok(registered)
+    }
 
-        clients.insert(vm_id.clone(), session);
-        info!("注册客户端: {}", vm_id);
-
-        Ok(event_rx)
+    /// 更新客户端的版本与能力
+    pub async fn update_client(
+        &self,
+        vm_id: &str,
+        session_id: u64,
+        registration: &RegisterMessage,
+    ) -> Result<()> {
+        self.clients.write().await.update(vm_id, session_id, registration)?;
+        debug!(
+            "更新客户端 {}: version={:?}, capabilities={:?}",
+            vm_id, registration.agent_version, registration.capabilities
+        );
+        Ok(())
     }
 
     /// 注销客户端
-    pub async fn unregister_client(&self, vm_id: &str) {
-        let mut clients = self.clients.write().await;
-        if clients.remove(vm_id).is_some() {
+    pub async fn unregister_client(&self, vm_id: &str, session_id: u64) {
+        if self.clients.write().await.unregister(vm_id, session_id) {
             info!("注销客户端: {}", vm_id);
         }
     }
@@ -98,6 +335,48 @@ impl ClientManager {
         }
     }
 
+    /// 通知 Agent 切换工作模式 (发送 `set_mode` 控制事件)
+    pub async fn set_agent_mode(&self, vm_id: &str, mode: AgentMode) -> Result<()> {
+        if mode == AgentMode::Report {
+            let clients = self.clients.read().await;
+            if let Some(session) = clients.sessions.get(vm_id) {
+                // 旧版 Agent 不声明能力, 只在声明了其他能力却没有 raw_input 时提示
+                let info = &session.info;
+                if !info.capabilities.is_empty() && !info.supports_raw_input() {
+                    warn!("客户端 {} 未声明 {} 能力, 可能不支持上报模式", vm_id, RAW_INPUT_CAPABILITY);
+                }
+            }
+        }
+        self.send_event(vm_id, mode.to_event()).await?;
+        info!("通知客户端 {} 切换到 {} 模式", vm_id, mode.as_str());
+        Ok(())
+    }
+
+    /// 转发 Agent 上报的原始输入, 没有订阅者时只记录日志
+    pub fn publish_raw_input(&self, vm_id: &str, event: RawInputEvent) {
+        if event.dropped > 0 {
+            warn!("客户端 {} 的上报队列已满, 丢弃了 {} 条输入事件", vm_id, event.dropped);
+        }
+
+        let report = RawInputReport {
+            vm_id: vm_id.to_string(),
+            event,
+        };
+        if let Err(broadcast::error::SendError(report)) = self.raw_input_tx.send(report) {
+            debug!(
+                "输入上报 (无订阅者): {} {} {}={}",
+                report.vm_id, report.event.device, report.event.code, report.event.value
+            );
+        }
+    }
+
+    /// 订阅所有客户端的输入上报
+    ///
+    /// 订阅者处理过慢时 `recv` 返回 `Lagged`, 被跳过的消息不会重发。
+    pub fn subscribe_raw_input(&self) -> broadcast::Receiver<RawInputReport> {
+        self.raw_input_tx.subscribe()
+    }
+
     /// 获取结果接收器（只能获取一次）
     pub async fn take_result_receiver(&self) -> Option<mpsc::UnboundedReceiver<VerifyResult>> {
         let mut rx = self.result_rx.write().await;
@@ -109,15 +388,23 @@ impl ClientManager {
         self.result_tx.clone()
     }
 
-    /// 获取客户端列表
-    pub async fn get_clients(&self) -> Vec<ClientInfo> {
-        let clients = self.clients.read().await;
-        clients
-            .values()
-            .map(|session| session.info.clone())
+    /// 声明了输入上报能力的客户端 (按 VM ID 排序)
+    pub async fn raw_input_clients(&self) -> Vec<String> {
+        self.clients
+            .read()
+            .await
+            .list()
+            .into_iter()
+            .filter(ClientInfo::supports_raw_input)
+            .map(|info| info.vm_id)
             .collect()
     }
 
+    /// 获取客户端列表 (按 VM ID 排序)
+    pub async fn get_clients(&self) -> Vec<ClientInfo> {
+        self.clients.read().await.list()
+    }
+
     /// 检查客户端是否连接
     pub async fn is_connected(&self, vm_id: &str) -> bool {
         let clients = self.clients.read().await;
@@ -127,6 +414,39 @@ impl ClientManager {
             .unwrap_or(false)
     }
 
+    /// 通知所有连接关闭 (连接处理任务告知 Agent 后断开), 之后拒绝新的注册
+    pub fn shutdown(&self) {
+        if !self.shutdown_tx.send_replace(true) {
+            info!("通知所有客户端断开连接");
+        }
+    }
+
+    /// 是否正在关闭
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutdown_tx.borrow()
+    }
+
+    /// 订阅关闭通知, 用 `wait_for(|stop| *stop)` 等待
+    pub fn subscribe_shutdown(&self) -> watch::Receiver<bool> {
+        self.shutdown_tx.subscribe()
+    }
+
+    /// 等待所有客户端断开, 超时返回 false
+    pub async fn wait_all_disconnected(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = self.clients.read().await.list().len();
+            if remaining == 0 {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                warn!("仍有 {} 个客户端未断开", remaining);
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
     /// 标记客户端为断开
     pub async fn mark_disconnected(&self, vm_id: &str) {
         let mut clients = self.clients.write().await;
@@ -146,37 +466,44 @@ impl Default for ClientManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::ClientMessage;
+
+    fn tcp(vm_id: &str, addr: &str) -> ClientConnection {
+        ClientConnection::Tcp {
+            vm_id: vm_id.to_string(),
+            addr: addr.to_string(),
+        }
+    }
 
     #[tokio::test]
     async fn test_client_manager_registration() {
         let manager = ClientManager::new();
 
-        let info = ClientInfo {
-            vm_id: "vm-123".to_string(),
-            connected_at: chrono::Utc::now(),
-            remote_addr: Some("192.168.1.100:5000".to_string()),
-        };
+        let connection = tcp("vm-123", "192.168.1.100:5000");
+        let registration = RegisterMessage::new("vm-123")
+            .with_agent_version("0.1.0")
+            .with_capabilities(vec!["keyboard".to_string()]);
 
-        let _event_rx = manager.register_client(info).await.unwrap();
+        let _registered = manager.register_client(connection, &registration).await.unwrap();
 
         assert!(manager.is_connected("vm-123").await);
 
         let clients = manager.get_clients().await;
         assert_eq!(clients.len(), 1);
         assert_eq!(clients[0].vm_id, "vm-123");
+        assert_eq!(clients[0].remote_addr.as_deref(), Some("192.168.1.100:5000"));
+        assert_eq!(clients[0].agent_version.as_deref(), Some("0.1.0"));
+        assert_eq!(clients[0].capabilities, vec!["keyboard".to_string()]);
     }
 
     #[tokio::test]
     async fn test_send_event() {
         let manager = ClientManager::new();
 
-        let info = ClientInfo {
-            vm_id: "vm-123".to_string(),
-            connected_at: chrono::Utc::now(),
-            remote_addr: None,
-        };
-
-        let mut event_rx = manager.register_client(info).await.unwrap();
+        let mut registered = manager
+            .register_client(tcp("vm-123", "127.0.0.1:5000"), &RegisterMessage::new("vm-123"))
+            .await
+            .unwrap();
 
         let event = Event {
             event_type: "test".to_string(),
@@ -186,7 +513,235 @@ mod tests {
 
         manager.send_event("vm-123", event.clone()).await.unwrap();
 
-        let received = event_rx.recv().await.unwrap();
+        let received = registered.event_rx.recv().await.unwrap();
         assert_eq!(received.event_type, "test");
     }
+
+    #[tokio::test]
+    async fn test_duplicate_vm_id_rejected() {
+        let manager = ClientManager::new();
+        let registration = RegisterMessage::new("vm-123");
+
+        let first = manager
+            .register_client(tcp("vm-123", "10.0.0.1:5000"), &registration)
+            .await
+            .unwrap();
+
+        let err = match manager.register_client(tcp("vm-123", "10.0.0.2:5000"), &registration).await {
+            Ok(_) => panic!("duplicate registration should be rejected"),
+            Err(e) => e,
+        };
+        assert!(matches!(err, VerificationError::DuplicateClient(_, _)));
+        assert_eq!(err.to_string(), "虚拟机 vm-123 已有客户端连接 (10.0.0.1:5000)");
+
+        // 原连接不受影响
+        let clients = manager.get_clients().await;
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].remote_addr.as_deref(), Some("10.0.0.1:5000"));
+
+        // 原连接断开后可以重新注册
+        manager.unregister_client("vm-123", first.session_id).await;
+        assert!(manager
+            .register_client(tcp("vm-123", "10.0.0.2:5000"), &registration)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_takeover_closes_old_session() {
+        let manager = ClientManager::new().with_allow_takeover(true);
+        let registration = RegisterMessage::new("vm-123");
+
+        let mut old = manager
+            .register_client(tcp("vm-123", "10.0.0.1:5000"), &registration)
+            .await
+            .unwrap();
+        let new = manager
+            .register_client(tcp("vm-123", "10.0.0.2:5000"), &registration)
+            .await
+            .unwrap();
+
+        // 旧连接的事件通道关闭
+        assert!(old.event_rx.recv().await.is_none());
+
+        // 旧连接随后断开不会注销新连接
+        manager.unregister_client("vm-123", old.session_id).await;
+        assert!(manager.is_connected("vm-123").await);
+
+        manager.unregister_client("vm-123", new.session_id).await;
+        assert!(!manager.is_connected("vm-123").await);
+    }
+
+    #[tokio::test]
+    async fn test_update_client_after_legacy_handshake() {
+        let manager = ClientManager::new();
+
+        // 旧版握手: 纯文本 VM ID
+        let legacy = RegisterMessage::parse_handshake("vm-123\n").unwrap();
+        assert_eq!(legacy, RegisterMessage::new("vm-123"));
+        let registered = manager
+            .register_client(tcp("vm-123", "10.0.0.1:5000"), &legacy)
+            .await
+            .unwrap();
+
+        // 随后补发的注册消息
+        let text = r#"{"message_type":"register","vm_id":"vm-123","agent_version":"0.2.0","capabilities":["mouse"]}"#;
+        let registration = match ClientMessage::parse(text).unwrap() {
+            ClientMessage::Register(registration) => registration,
+            other => panic!("unexpected message: {:?}", other),
+        };
+        manager
+            .update_client("vm-123", registered.session_id, &registration)
+            .await
+            .unwrap();
+
+        let clients = manager.get_clients().await;
+        assert_eq!(clients[0].agent_version.as_deref(), Some("0.2.0"));
+        assert_eq!(clients[0].capabilities, vec!["mouse".to_string()]);
+
+        // VM ID 不一致的注册消息被拒绝
+        let other = RegisterMessage::new("vm-456");
+        assert!(manager
+            .update_client("vm-123", registered.session_id, &other)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_raw_input_forwarded_to_subscribers() {
+        let manager = ClientManager::new();
+        let mut registered = manager
+            .register_client(tcp("vm-123", "10.0.0.1:5000"), &RegisterMessage::new("vm-123"))
+            .await
+            .unwrap();
+
+        // 通知 Agent 进入上报模式
+        manager.set_agent_mode("vm-123", AgentMode::Report).await.unwrap();
+        let event = registered.event_rx.recv().await.unwrap();
+        assert_eq!(event.event_type, "set_mode");
+        assert_eq!(event.data["mode"], "report");
+        assert!(manager.set_agent_mode("vm-404", AgentMode::Report).await.is_err());
+
+        // 没有订阅者时只记录日志
+        let text = r#"{"message_type":"raw_input","device":"keyboard","code":"KEY_A","value":1,"timestamp":1,"dropped":3}"#;
+        let raw = match ClientMessage::parse(text).unwrap() {
+            ClientMessage::RawInput(raw) => raw,
+            other => panic!("unexpected message: {:?}", other),
+        };
+        manager.publish_raw_input("vm-123", raw.clone());
+
+        let mut first = manager.subscribe_raw_input();
+        let mut second = manager.subscribe_raw_input();
+        manager.publish_raw_input("vm-123", raw);
+
+        for subscriber in [&mut first, &mut second] {
+            let report = subscriber.recv().await.unwrap();
+            assert_eq!(report.vm_id, "vm-123");
+            assert_eq!(report.event.code, "KEY_A");
+            assert_eq!(report.event.dropped, 3);
+            assert_eq!(report.event.x, None);
+        }
+        assert!(first.try_recv().is_err());
+
+        // 输入上报不能作为握手消息
+        assert!(RegisterMessage::parse_handshake(text).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_raw_input_capability_registration() {
+        let manager = ClientManager::new();
+
+        // 只上报输入的 Agent: 只声明 raw_input 能力
+        let text = r#"{"message_type":"register","vm_id":"vm-raw","agent_version":"0.3.0","capabilities":["raw_input"]}"#;
+        let registration = RegisterMessage::parse_handshake(text).unwrap();
+        let mut raw_agent = manager
+            .register_client(tcp("vm-raw", "10.0.0.1:5000"), &registration)
+            .await
+            .unwrap();
+
+        // 兼容旧版注册消息: 纯文本 VM ID, 以及不带版本与能力的注册消息
+        let legacy = RegisterMessage::parse_handshake("vm-old\n").unwrap();
+        let _old_agent = manager
+            .register_client(tcp("vm-old", "10.0.0.2:5000"), &legacy)
+            .await
+            .unwrap();
+        let minimal = RegisterMessage::parse_handshake(r#"{"message_type":"register","vm_id":"vm-min"}"#).unwrap();
+        assert!(minimal.capabilities.is_empty());
+        let _minimal_agent = manager
+            .register_client(tcp("vm-min", "10.0.0.3:5000"), &minimal)
+            .await
+            .unwrap();
+
+        let clients = manager.get_clients().await;
+        assert_eq!(clients.len(), 3);
+        assert_eq!(manager.raw_input_clients().await, vec!["vm-raw".to_string()]);
+
+        // 未声明能力的旧版 Agent 同样可以切换到上报模式, 上报的输入走同一个通道
+        manager.set_agent_mode("vm-old", AgentMode::Report).await.unwrap();
+        manager.set_agent_mode("vm-raw", AgentMode::Report).await.unwrap();
+        assert_eq!(raw_agent.event_rx.recv().await.unwrap().data["mode"], "report");
+
+        let mut subscriber = manager.subscribe_raw_input();
+        let raw = match ClientMessage::parse(
+            r#"{"message_type":"raw_input","device":"mouse","code":"BTN_LEFT","value":1,"timestamp":1}"#,
+        )
+        .unwrap()
+        {
+            ClientMessage::RawInput(raw) => raw,
+            other => panic!("unexpected message: {:?}", other),
+        };
+        manager.publish_raw_input("vm-raw", raw.clone());
+        manager.publish_raw_input("vm-old", raw);
+        assert_eq!(subscriber.recv().await.unwrap().vm_id, "vm-raw");
+        assert_eq!(subscriber.recv().await.unwrap().vm_id, "vm-old");
+    }
+
+    #[tokio::test]
+    async fn test_heartbeat_exposed_in_client_list() {
+        let manager = ClientManager::new();
+        let registered = manager
+            .register_client(tcp("vm-123", "10.0.0.1:5000"), &RegisterMessage::new("vm-123"))
+            .await
+            .unwrap();
+
+        // 尚未收到心跳
+        let clients = manager.get_clients().await;
+        assert_eq!(clients[0].last_heartbeat, None);
+        assert_eq!(clients[0].agent_uptime_s, None);
+
+        let text = r#"{"message_type":"heartbeat","vm_id":"vm-123","uptime_s":42}"#;
+        let heartbeat = match ClientMessage::parse(text).unwrap() {
+            ClientMessage::Heartbeat(heartbeat) => heartbeat,
+            other => panic!("unexpected message: {:?}", other),
+        };
+        assert_eq!(heartbeat.vm_id, "vm-123");
+        registered.activity.heartbeat(heartbeat.uptime_s);
+
+        let clients = manager.get_clients().await;
+        assert!(clients[0].last_heartbeat.is_some());
+        assert_eq!(clients[0].agent_uptime_s, Some(42));
+
+        // 心跳不能作为握手消息
+        assert!(RegisterMessage::parse_handshake(text).is_err());
+    }
+
+    #[test]
+    fn test_parse_handshake_validates_vm_id() {
+        let registration = RegisterMessage::parse_handshake(
+            r#"{"message_type":"register","vm_id":"vm-1","capabilities":["keyboard","command"]}"#,
+        )
+        .unwrap();
+        assert_eq!(registration.vm_id, "vm-1");
+        assert_eq!(registration.agent_version, None);
+        assert_eq!(registration.capabilities.len(), 2);
+
+        assert!(RegisterMessage::parse_handshake("").is_err());
+        assert!(RegisterMessage::parse_handshake("vm 1").is_err());
+        assert!(RegisterMessage::parse_handshake(&"x".repeat(300)).is_err());
+        assert!(RegisterMessage::parse_handshake(r#"{"message_type":"register"}"#).is_err());
+        assert!(RegisterMessage::parse_handshake(
+            r#"{"event_id":"e","verified":true,"timestamp":0,"latency_ms":0,"details":{}}"#
+        )
+        .is_err());
+    }
 }
@@ -0,0 +1,417 @@
+//! 独立部署的配置文件
+//!
+//! TOML 格式, 所有段都可省略。`[server]` 段中未配置的监听地址不启动,
+//! 省略整个 `[server]` 段时使用默认地址 (WebSocket 8765, TCP 8766)。
+//!
+//! ```toml
+//! [server]
+//! websocket_addr = "0.0.0.0:8765"
+//! tcp_addr = "0.0.0.0:8766"
+//!
+//! [tls]
+//! cert = "/etc/atp/server.crt"
+//! key = "/etc/atp/server.key"
+//! # client_ca = "/etc/atp/agent-ca.crt"  # 可选, 校验 Agent 客户端证书
+//!
+//! [auth]
+//! tokens = ["token-1"]
+//!
+//! [storage]
+//! db_path = "~/.config/atp/data.db"
+//!
+//! [metrics]
+//! addr = "0.0.0.0:9100"
+//! # admin = true  # 开启 POST /broadcast 管理端点
+//! # api_token = "secret"  # 开启 POST /api/v1/events 事件注入接口 (Bearer 认证)
+//!
+//! [log]
+//! level = "info"
+//! ```
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+use crate::auth::AuthTokens;
+use crate::framing::DEFAULT_MAX_FRAME_SIZE;
+use crate::server::ServerConfig;
+use crate::service::ServiceConfig;
+use crate::tls::TlsConfig;
+use crate::{Result, VerificationError};
+
+/// 配置文件
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// 监听与连接
+    #[serde(default)]
+    pub server: ListenSection,
+
+    /// 事件匹配
+    #[serde(default)]
+    pub service: ServiceSection,
+
+    /// TLS (省略则明文)
+    pub tls: Option<TlsSection>,
+
+    /// Agent 认证 (省略则不认证)
+    pub auth: Option<AuthSection>,
+
+    /// 指标落库 (省略则不落库)
+    pub storage: Option<StorageSection>,
+
+    /// 指标 HTTP 端点 (省略则不启动)
+    pub metrics: Option<MetricsSection>,
+
+    /// 日志
+    #[serde(default)]
+    pub log: LogSection,
+}
+
+/// `[server]` 段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenSection {
+    /// WebSocket 监听地址
+    pub websocket_addr: Option<SocketAddr>,
+
+    /// TCP 监听地址
+    pub tcp_addr: Option<SocketAddr>,
+
+    /// TCP 最大帧长度
+    #[serde(default = "default_max_frame_size")]
+    pub max_frame_size: usize,
+
+    /// TCP 兼容模式 (接收旧版 Agent 的分帧格式)
+    #[serde(default)]
+    pub tcp_legacy_framing: bool,
+
+    /// 是否允许新连接接管同一 VM ID 的旧连接
+    #[serde(default)]
+    pub allow_takeover: bool,
+
+    /// 退出时等待 Agent 断开的最长时间 (秒)
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+}
+
+impl Default for ListenSection {
+    fn default() -> Self {
+        let defaults = ServerConfig::default();
+        Self {
+            websocket_addr: defaults.websocket_addr,
+            tcp_addr: defaults.tcp_addr,
+            max_frame_size: default_max_frame_size(),
+            tcp_legacy_framing: false,
+            allow_takeover: false,
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+        }
+    }
+}
+
+/// `[service]` 段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceSection {
+    /// 默认超时时间 (秒)
+    pub default_timeout_secs: u64,
+
+    /// 事件清理间隔 (秒)
+    pub cleanup_interval_secs: u64,
+
+    /// 最大待验证事件数
+    pub max_pending_events: usize,
+
+    /// 超时后仍接受迟到结果的宽限期 (秒), 0 表示不接受
+    pub late_result_grace_secs: u64,
+}
+
+impl Default for ServiceSection {
+    fn default() -> Self {
+        let defaults = ServiceConfig::default();
+        Self {
+            default_timeout_secs: defaults.default_timeout.as_secs(),
+            cleanup_interval_secs: defaults.cleanup_interval.as_secs(),
+            max_pending_events: defaults.max_pending_events,
+            late_result_grace_secs: defaults.late_result_grace.as_secs(),
+        }
+    }
+}
+
+/// `[tls]` 段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsSection {
+    /// PEM 格式的证书链
+    pub cert: PathBuf,
+
+    /// PEM 格式的私钥
+    pub key: PathBuf,
+
+    /// 签发 Agent 客户端证书的 CA (PEM), 配置后要求客户端证书
+    #[serde(default)]
+    pub client_ca: Option<PathBuf>,
+}
+
+/// `[auth]` 段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthSection {
+    /// 允许接入的 token (可通过 SIGHUP 重载)
+    #[serde(default)]
+    pub tokens: Vec<String>,
+}
+
+/// `[storage]` 段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StorageSection {
+    /// 数据库文件路径
+    pub db_path: String,
+
+    /// 指标采集间隔 (秒)
+    #[serde(default = "default_collect_interval_secs")]
+    pub collect_interval_secs: u64,
+}
+
+/// `[metrics]` 段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsSection {
+    /// 指标端点监听地址
+    pub addr: SocketAddr,
+
+    /// 是否开启管理端点 (`POST /broadcast`), 端点没有认证, 只应监听在内网地址
+    #[serde(default)]
+    pub admin: bool,
+
+    /// 事件注入接口 (`POST /api/v1/events`) 的 Bearer token, 省略则不提供该接口
+    pub api_token: Option<String>,
+}
+
+/// `[log]` 段
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogSection {
+    /// 日志级别, 支持 `RUST_LOG` 语法 (可通过 SIGHUP 重载)
+    pub level: String,
+}
+
+impl Default for LogSection {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+        }
+    }
+}
+
+fn default_max_frame_size() -> usize {
+    DEFAULT_MAX_FRAME_SIZE
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    10
+}
+
+fn default_collect_interval_secs() -> u64 {
+    60
+}
+
+impl DaemonConfig {
+    /// 读取并校验配置文件
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            VerificationError::ConfigError(format!("读取配置文件 {} 失败: {}", path.display(), e))
+        })?;
+        Self::from_toml(&text)
+    }
+
+    /// 解析并校验配置
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)
+            .map_err(|e| VerificationError::ConfigError(format!("解析配置文件失败: {}", e)))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 校验配置 (不检查 TLS 文件, 加载证书时再报错)
+    pub fn validate(&self) -> Result<()> {
+        if self.server.websocket_addr.is_none() && self.server.tcp_addr.is_none() {
+            return Err(VerificationError::ConfigError(
+                "至少需要配置 websocket_addr 或 tcp_addr".to_string(),
+            ));
+        }
+        if self.server.max_frame_size == 0 {
+            return Err(VerificationError::ConfigError("max_frame_size 必须大于 0".to_string()));
+        }
+        if self.service.max_pending_events == 0 {
+            return Err(VerificationError::ConfigError("max_pending_events 必须大于 0".to_string()));
+        }
+        if self.service.cleanup_interval_secs == 0 {
+            return Err(VerificationError::ConfigError("cleanup_interval_secs 必须大于 0".to_string()));
+        }
+        if let Some(storage) = &self.storage {
+            if storage.collect_interval_secs == 0 {
+                return Err(VerificationError::ConfigError(
+                    "collect_interval_secs 必须大于 0".to_string(),
+                ));
+            }
+        }
+        if let Some(metrics) = &self.metrics {
+            if metrics.api_token.as_deref().is_some_and(|token| token.trim().is_empty()) {
+                return Err(VerificationError::ConfigError("api_token 不能为空".to_string()));
+            }
+        }
+        self.log_filter()?;
+        Ok(())
+    }
+
+    /// 日志级别对应的过滤器
+    pub fn log_filter(&self) -> Result<EnvFilter> {
+        EnvFilter::try_new(&self.log.level).map_err(|e| {
+            VerificationError::ConfigError(format!("无效的日志级别 {:?}: {}", self.log.level, e))
+        })
+    }
+
+    /// 认证 token 列表
+    pub fn auth_tokens(&self) -> AuthTokens {
+        match &self.auth {
+            Some(auth) => AuthTokens::new(auth.tokens.iter().cloned()),
+            None => AuthTokens::disabled(),
+        }
+    }
+
+    /// 服务器配置 (加载 TLS 证书)
+    pub fn server_config(&self) -> Result<ServerConfig> {
+        let tls = match &self.tls {
+            Some(tls) => Some(TlsConfig::load(&tls.cert, &tls.key, tls.client_ca.as_deref())?),
+            None => None,
+        };
+
+        Ok(ServerConfig {
+            websocket_addr: self.server.websocket_addr,
+            tcp_addr: self.server.tcp_addr,
+            max_frame_size: self.server.max_frame_size,
+            tcp_legacy_framing: self.server.tcp_legacy_framing,
+            tls,
+            auth: self.auth_tokens(),
+        })
+    }
+
+    /// 验证服务配置
+    pub fn service_config(&self) -> ServiceConfig {
+        ServiceConfig {
+            default_timeout: Duration::from_secs(self.service.default_timeout_secs),
+            cleanup_interval: Duration::from_secs(self.service.cleanup_interval_secs),
+            max_pending_events: self.service.max_pending_events,
+            late_result_grace: Duration::from_secs(self.service.late_result_grace_secs),
+        }
+    }
+
+    /// 退出时等待 Agent 断开的最长时间
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.server.shutdown_timeout_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_config_uses_defaults() {
+        let config = DaemonConfig::from_toml("").unwrap();
+        assert_eq!(config, DaemonConfig::default());
+        assert_eq!(config.server.websocket_addr, Some("0.0.0.0:8765".parse().unwrap()));
+        assert_eq!(config.server.tcp_addr, Some("0.0.0.0:8766".parse().unwrap()));
+        assert_eq!(config.service_config().default_timeout, Duration::from_secs(30));
+        assert!(!config.auth_tokens().is_enabled());
+
+        let server = config.server_config().unwrap();
+        assert!(server.tls.is_none());
+        assert_eq!(server.max_frame_size, DEFAULT_MAX_FRAME_SIZE);
+    }
+
+    #[test]
+    fn test_full_config() {
+        let config = DaemonConfig::from_toml(
+            r#"
+            [server]
+            tcp_addr = "127.0.0.1:9766"
+            tcp_legacy_framing = true
+            allow_takeover = true
+            shutdown_timeout_secs = 3
+
+            [service]
+            default_timeout_secs = 5
+            max_pending_events = 100
+
+            [tls]
+            cert = "/etc/atp/server.crt"
+            key = "/etc/atp/server.key"
+            client_ca = "/etc/atp/agent-ca.crt"
+
+            [auth]
+            tokens = ["a", "b"]
+
+            [storage]
+            db_path = "/var/lib/atp/data.db"
+
+            [metrics]
+            addr = "127.0.0.1:9100"
+
+            [log]
+            level = "verification_server=debug,info"
+            "#,
+        )
+        .unwrap();
+
+        // [server] 段中未配置的监听地址不启动
+        assert_eq!(config.server.websocket_addr, None);
+        assert_eq!(config.server.tcp_addr, Some("127.0.0.1:9766".parse().unwrap()));
+        assert!(config.server.tcp_legacy_framing);
+        assert_eq!(config.shutdown_timeout(), Duration::from_secs(3));
+
+        let service = config.service_config();
+        assert_eq!(service.default_timeout, Duration::from_secs(5));
+        assert_eq!(service.cleanup_interval, Duration::from_secs(60));
+        assert_eq!(service.max_pending_events, 100);
+
+        assert_eq!(config.tls.as_ref().unwrap().key, PathBuf::from("/etc/atp/server.key"));
+        assert_eq!(
+            config.tls.as_ref().unwrap().client_ca,
+            Some(PathBuf::from("/etc/atp/agent-ca.crt"))
+        );
+        assert_eq!(config.auth_tokens().len(), 2);
+        assert_eq!(config.storage.as_ref().unwrap().collect_interval_secs, 60);
+        assert_eq!(config.metrics.as_ref().unwrap().addr.port(), 9100);
+        assert!(!config.metrics.as_ref().unwrap().admin);
+        assert!(config.metrics.as_ref().unwrap().api_token.is_none());
+
+        // 证书文件不存在
+        let err = config.server_config().unwrap_err();
+        assert!(err.to_string().contains("/etc/atp/server.crt"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_config_rejected() {
+        let cases = [
+            ("[server]\nmax_frame_size = 0\ntcp_addr = \"0.0.0.0:1\"", "max_frame_size"),
+            ("[server]\n", "websocket_addr 或 tcp_addr"),
+            ("[server]\ntcp_addr = \"not-an-addr\"", "解析配置文件失败"),
+            ("[servr]\n", "解析配置文件失败"),
+            ("[log]\nlevel = \"verification_server=loud\"", "无效的日志级别"),
+            ("[storage]\ndb_path = \"x.db\"\ncollect_interval_secs = 0", "collect_interval_secs"),
+            ("[metrics]\naddr = \"127.0.0.1:9100\"\napi_token = \" \"", "api_token"),
+        ];
+
+        for (text, expected) in cases {
+            let err = DaemonConfig::from_toml(text).unwrap_err();
+            assert!(err.to_string().contains(expected), "{:?}: {}", text, err);
+        }
+    }
+}
@@ -0,0 +1,371 @@
+//! 独立部署
+//!
+//! 按 [`DaemonConfig`] 组装服务器、验证服务、指标落库与指标端点。
+//! 运行中可重载认证 token (日志级别由入口程序重载), 退出时先通知所有 Agent 断开,
+//! 再把最后一次指标快照写库。
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use atp_storage::{CollectorConfig, MetricsCollector, Storage, StorageManager};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::auth::AuthTokens;
+use crate::client::ClientManager;
+use crate::config::DaemonConfig;
+use crate::metrics_http::{run_metrics_server, HttpOptions};
+use crate::server::VerificationServer;
+use crate::service::VerificationService;
+use crate::Result;
+
+/// 运行中的验证服务器
+pub struct Daemon {
+    config: DaemonConfig,
+    client_manager: Arc<ClientManager>,
+    service: Arc<VerificationService>,
+    auth: AuthTokens,
+    collector: Option<Arc<MetricsCollector>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Daemon {
+    /// 按配置启动所有组件
+    ///
+    /// 证书、数据库与指标端口的错误在这里返回; 监听端口在后台绑定, 失败时记录日志。
+    pub async fn start(config: DaemonConfig) -> Result<Self> {
+        let server_config = config.server_config()?;
+        let auth = server_config.auth.clone();
+        if auth.is_enabled() {
+            info!("已开启 Agent 认证, token 数量: {}", auth.len());
+        }
+
+        let client_manager =
+            Arc::new(ClientManager::new().with_allow_takeover(config.server.allow_takeover));
+
+        // 配置了数据库时验证结果与指标都写库
+        let manager = match &config.storage {
+            Some(storage) => Some(StorageManager::new(&storage.db_path).await?),
+            None => None,
+        };
+        let service = Arc::new(match &manager {
+            Some(manager) => VerificationService::new_with_storage(
+                client_manager.clone(),
+                config.service_config(),
+                &Storage::from_manager(manager),
+            ),
+            None => VerificationService::new(client_manager.clone(), config.service_config()),
+        });
+
+        let mut tasks = Vec::new();
+
+        // 指标端点 (先绑定端口, 占用时直接报错)
+        if let Some(metrics) = &config.metrics {
+            let listener = TcpListener::bind(metrics.addr).await?;
+            let service = service.clone();
+            let options = HttpOptions {
+                admin: metrics.admin,
+                api_token: metrics.api_token.clone(),
+            };
+            tasks.push(tokio::spawn(async move {
+                if let Err(e) = run_metrics_server(listener, service, options).await {
+                    error!("指标端点错误: {}", e);
+                }
+            }));
+        }
+
+        // 指标落库
+        let collector = match (&config.storage, &manager) {
+            (Some(storage), Some(manager)) => {
+                let collector = Arc::new(MetricsCollector::new(
+                    Storage::from_manager(manager).metrics().clone(),
+                    CollectorConfig {
+                        interval: Duration::from_secs(storage.collect_interval_secs),
+                        ..Default::default()
+                    },
+                ));
+                collector.add_source(service.clone()).await;
+                collector.start().await;
+                Some(collector)
+            }
+            _ => None,
+        };
+
+        let server = VerificationServer::new(server_config, client_manager.clone());
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = server.start().await {
+                error!("服务器错误: {}", e);
+            }
+        }));
+
+        Ok(Self {
+            config,
+            client_manager,
+            service,
+            auth,
+            collector,
+            tasks,
+        })
+    }
+
+    /// 当前配置
+    pub fn config(&self) -> &DaemonConfig {
+        &self.config
+    }
+
+    /// 验证服务
+    pub fn service(&self) -> &Arc<VerificationService> {
+        &self.service
+    }
+
+    /// 重载配置: 替换认证 token 与日志级别, 其余改动需要重启才能生效
+    ///
+    /// 返回需要重启才能生效的配置段。
+    pub fn reload(&mut self, new_config: DaemonConfig) -> Vec<&'static str> {
+        let mut restart_required = Vec::new();
+
+        match (&self.config.auth, &new_config.auth) {
+            (Some(_), Some(auth)) => {
+                self.auth.replace(auth.tokens.iter().cloned());
+                info!("已重载认证 token, 数量: {}", auth.tokens.len());
+            }
+            (None, None) => {}
+            _ => restart_required.push("auth"),
+        }
+
+        if new_config.server != self.config.server {
+            restart_required.push("server");
+        }
+        if new_config.service != self.config.service {
+            restart_required.push("service");
+        }
+        if new_config.tls != self.config.tls {
+            restart_required.push("tls");
+        }
+        if new_config.storage != self.config.storage {
+            restart_required.push("storage");
+        }
+        if new_config.metrics != self.config.metrics {
+            restart_required.push("metrics");
+        }
+        if !restart_required.is_empty() {
+            warn!("以下配置段的改动需要重启才能生效: {}", restart_required.join(", "));
+        }
+
+        // 只接受可重载的部分, 其余保持启动时的配置
+        if !restart_required.contains(&"auth") {
+            self.config.auth = new_config.auth;
+        }
+        self.config.log = new_config.log;
+
+        restart_required
+    }
+
+    /// 优雅退出: 通知所有 Agent 断开, 等待断开 (有超时), 停止监听并把指标写库
+    pub async fn shutdown(self) -> Result<()> {
+        info!("正在关闭验证服务器");
+
+        self.client_manager.shutdown();
+        if !self
+            .client_manager
+            .wait_all_disconnected(self.config.shutdown_timeout())
+            .await
+        {
+            warn!("等待 Agent 断开超时, 强制关闭");
+        }
+
+        for task in &self.tasks {
+            task.abort();
+        }
+
+        if let Some(collector) = &self.collector {
+            // 记录退出前的最后状态
+            collector.collect_once().await?;
+            let written = collector.stop().await?;
+            info!("指标已写库: {} 条", written);
+        }
+
+        info!("验证服务器已关闭");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::framing::{encode_frame, FrameDecoder, FrameFormat};
+    use crate::types::{Event, RegisterMessage, VerifyResult};
+    use atp_common::{encode, AgreedVersion, MessageType};
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    /// 找一个空闲端口
+    fn free_addr() -> std::net::SocketAddr {
+        std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+    }
+
+    /// 发送握手并等待注册完成
+    async fn connect_agent(addr: std::net::SocketAddr, registration: &RegisterMessage) -> TcpStream {
+        let mut stream = loop {
+            match TcpStream::connect(addr).await {
+                Ok(stream) => break stream,
+                // 监听任务还未绑定端口
+                Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+            }
+        };
+        let handshake = serde_json::to_string(registration).unwrap();
+        stream.write_all(&encode_frame(FrameFormat::Versioned, &handshake)).await.unwrap();
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_notifies_agents_and_flushes_metrics() {
+        let db_path = std::env::temp_dir().join(format!("atp-verification-{}.db", uuid::Uuid::new_v4()));
+        std::fs::File::create(&db_path).unwrap();
+        let tcp_addr = free_addr();
+        let config = DaemonConfig::from_toml(&format!(
+            "[server]\ntcp_addr = \"{}\"\n[auth]\ntokens = [\"secret\"]\n[storage]\ndb_path = \"{}\"\ncollect_interval_secs = 3600\n",
+            tcp_addr,
+            db_path.display()
+        ))
+        .unwrap();
+
+        let mut daemon = Daemon::start(config.clone()).await.unwrap();
+
+        // 错误的 token 被拒绝
+        let mut rejected = connect_agent(tcp_addr, &RegisterMessage::new("vm-1").with_token("wrong")).await;
+        let reply = FrameDecoder::new().read_frame(&mut rejected).await.unwrap().unwrap();
+        assert!(reply.contains("token 无效"), "{}", reply);
+
+        // 重载后新 token 生效
+        let mut reloaded = config.clone();
+        reloaded.auth.as_mut().unwrap().tokens = vec!["rotated".to_string()];
+        reloaded.log.level = "debug".to_string();
+        assert!(daemon.reload(reloaded).is_empty());
+        assert_eq!(daemon.config().log.level, "debug");
+
+        let mut agent = connect_agent(tcp_addr, &RegisterMessage::new("vm-1").with_token("rotated")).await;
+        for _ in 0..100 {
+            if !daemon.service().list_clients().await.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(daemon.service().list_clients().await.len(), 1);
+
+        // 退出: Agent 收到关闭通知, 指标写库
+        let service = daemon.service().clone();
+        daemon.shutdown().await.unwrap();
+
+        let mut decoder = FrameDecoder::new();
+        let notice = decoder.read_frame(&mut agent).await.unwrap().unwrap();
+        assert!(notice.contains("rejected") && notice.contains("服务器正在关闭"), "{}", notice);
+        assert_eq!(decoder.read_frame(&mut agent).await.unwrap(), None);
+        assert!(service.list_clients().await.is_empty());
+
+        let manager = StorageManager::new(db_path.to_str().unwrap()).await.unwrap();
+        assert!(Storage::from_manager(&manager).metrics().count().await.unwrap() > 0);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_agent_uses_envelopes() {
+        let tcp_addr = free_addr();
+        let config = DaemonConfig::from_toml(&format!("[server]\ntcp_addr = \"{}\"\n", tcp_addr)).unwrap();
+        let daemon = Daemon::start(config).await.unwrap();
+
+        // 声明了协议版本的注册消息: 服务端回复 accepted 信封
+        let registration = RegisterMessage::new("vm-1").with_protocol_version(atp_common::PROTOCOL_VERSION);
+        let mut agent = connect_agent(tcp_addr, &registration).await;
+        let mut decoder = FrameDecoder::new();
+        let accepted = decoder.read_frame(&mut agent).await.unwrap().unwrap();
+        let accepted = atp_common::decode(&accepted, MessageType::Event).unwrap();
+        assert_eq!(accepted.message_type, MessageType::Accepted);
+        assert_eq!(accepted.protocol_version, atp_common::PROTOCOL_VERSION);
+
+        // 之后的事件包装为信封, 信封形式的结果可以完成验证
+        let service = daemon.service().clone();
+        let verify = tokio::spawn(async move {
+            let event = Event {
+                event_type: "keyboard".to_string(),
+                data: serde_json::json!({"key": "ENTER"}),
+                timestamp: 0,
+            };
+            service.verify_event("vm-1", event, Some(Duration::from_secs(5))).await
+        });
+
+        let frame = decoder.read_frame(&mut agent).await.unwrap().unwrap();
+        let event: Event = atp_common::decode(&frame, MessageType::Event)
+            .unwrap()
+            .decode_as(MessageType::Event)
+            .unwrap();
+        assert_eq!(event.data["key"], "ENTER");
+
+        let result = VerifyResult {
+            event_id: event.data["event_id"].as_str().unwrap().to_string(),
+            verified: true,
+            timestamp: 0,
+            latency_ms: 3,
+            details: serde_json::json!({}),
+        };
+        let reply = encode(AgreedVersion::Version(accepted.protocol_version), MessageType::Result, &result).unwrap();
+        agent.write_all(&encode_frame(FrameFormat::Versioned, &reply)).await.unwrap();
+
+        assert!(verify.await.unwrap().unwrap().verified);
+        daemon.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_websocket_answers_ping() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let ws_addr = free_addr();
+        let config = DaemonConfig::from_toml(&format!("[server]\nwebsocket_addr = \"{}\"\n", ws_addr)).unwrap();
+        let daemon = Daemon::start(config).await.unwrap();
+
+        let mut ws = loop {
+            match tokio_tungstenite::connect_async(format!("ws://{}", ws_addr)).await {
+                Ok((ws, _)) => break ws,
+                // 监听任务还未绑定端口
+                Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+            }
+        };
+        ws.send(Message::Text("vm-1".to_string())).await.unwrap();
+
+        // Agent 的保活 Ping 得到原样回复的 Pong
+        ws.send(Message::Ping(b"keepalive".to_vec())).await.unwrap();
+        let pong = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                match ws.next().await.unwrap().unwrap() {
+                    Message::Pong(payload) => break payload,
+                    _ => continue,
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(pong, b"keepalive");
+
+        drop(ws);
+        daemon.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reload_reports_sections_requiring_restart() {
+        let config = DaemonConfig::from_toml(&format!("[server]\ntcp_addr = \"{}\"\n", free_addr())).unwrap();
+        let mut daemon = Daemon::start(config.clone()).await.unwrap();
+
+        let mut changed = config.clone();
+        changed.server.allow_takeover = true;
+        changed.auth = Some(crate::config::AuthSection { tokens: vec!["x".to_string()] });
+        assert_eq!(daemon.reload(changed), vec!["auth", "server"]);
+
+        // 需要重启的改动不生效
+        assert!(daemon.config().auth.is_none());
+        assert!(!daemon.config().server.allow_takeover);
+
+        daemon.shutdown().await.unwrap();
+    }
+}
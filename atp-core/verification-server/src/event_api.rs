@@ -0,0 +1,269 @@
+//! 事件注入接口
+//!
+//! 外部测试框架 (不链接本 crate 的进程) 通过 HTTP 向 Agent 注入验证事件并取回结论:
+//!
+//! - `POST /api/v1/events`: 发送单个事件, 等待结论后返回匹配的 [`VerifyResult`] 或超时状态
+//! - `POST /api/v1/events/batch`: 同时发送多个事件 (可以发往不同虚拟机、不同类型), 全部给出结论后返回
+//!
+//! 两个端点都需要 `Authorization: Bearer <api_token>`, token 来自 `[metrics]` 段的 `api_token`。
+
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+use uuid::Uuid;
+
+use crate::broadcast::{BroadcastStatus, BroadcastVmResult};
+use crate::metrics_http::Response;
+use crate::service::VerificationService;
+use crate::types::{Event, VerifyResult};
+use crate::VerificationError;
+
+/// `POST /api/v1/events` 的请求体, 也是批量请求中的单个事件
+#[derive(Debug, Clone, Deserialize)]
+pub struct EventRequest {
+    /// 目标虚拟机 ID
+    pub vm_id: String,
+
+    /// 事件类型 (keyboard, mouse, command)
+    pub event_type: String,
+
+    /// 事件数据 (必须是 JSON 对象)
+    #[serde(default = "empty_object")]
+    pub data: serde_json::Value,
+
+    /// 超时时间 (毫秒), 省略时使用服务的默认超时
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+fn empty_object() -> serde_json::Value {
+    serde_json::json!({})
+}
+
+/// `POST /api/v1/events/batch` 的请求体
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchEventRequest {
+    pub events: Vec<EventRequest>,
+}
+
+/// 单个事件的结论
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventResponse {
+    pub vm_id: String,
+
+    /// 发给 Agent 的 event_id (未发送时为空)
+    pub event_id: Option<String>,
+
+    /// `verified` / `mismatched` / `timeout` / `skipped` (Agent 未连接) / `failed`
+    pub status: BroadcastStatus,
+
+    /// Agent 返回的结果 (超时或未发送时为空)
+    pub result: Option<VerifyResult>,
+
+    /// 服务端测得的延迟 (毫秒)
+    pub server_latency_ms: Option<u64>,
+
+    /// 未发送或失败的原因
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl EventResponse {
+    fn not_sent(vm_id: &str, status: BroadcastStatus, reason: String) -> Self {
+        Self::from_summary(BroadcastVmResult::not_sent(vm_id, status, reason), None)
+    }
+
+    fn from_summary(summary: BroadcastVmResult, result: Option<VerifyResult>) -> Self {
+        Self {
+            vm_id: summary.vm_id,
+            event_id: summary.event_id,
+            status: summary.status,
+            result,
+            server_latency_ms: summary.server_latency_ms,
+            reason: summary.reason,
+        }
+    }
+}
+
+/// `POST /api/v1/events/batch` 的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEventResponse {
+    /// 各事件的结论, 与请求中的顺序一致
+    pub results: Vec<EventResponse>,
+
+    pub verified: usize,
+    pub mismatched: usize,
+    pub timed_out: usize,
+    pub skipped: usize,
+    pub failed: usize,
+
+    /// 从收到请求到全部给出结论的耗时 (毫秒)
+    pub elapsed_ms: u64,
+}
+
+impl BatchEventResponse {
+    fn new(results: Vec<EventResponse>, elapsed_ms: u64) -> Self {
+        let count = |status: BroadcastStatus| results.iter().filter(|r| r.status == status).count();
+        Self {
+            verified: count(BroadcastStatus::Verified),
+            mismatched: count(BroadcastStatus::Mismatched),
+            timed_out: count(BroadcastStatus::Timeout),
+            skipped: count(BroadcastStatus::Skipped),
+            failed: count(BroadcastStatus::Failed),
+            results,
+            elapsed_ms,
+        }
+    }
+}
+
+/// 校验 `Authorization` 请求头
+pub(crate) fn authorize(authorization: Option<&str>, api_token: &str) -> Option<Response> {
+    let token = authorization.and_then(|value| {
+        let (scheme, token) = value.trim().split_once(' ')?;
+        scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
+    });
+
+    match token {
+        Some(token) if constant_time_eq(token.as_bytes(), api_token.as_bytes()) => None,
+        Some(_) => Some(error_response("401 Unauthorized", "token 无效")),
+        None => Some(error_response("401 Unauthorized", "缺少 Bearer token")),
+    }
+}
+
+/// 比较 token, 耗时与第一个不同字节的位置无关, 避免逐字节猜测
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn error_response(status: &'static str, message: &str) -> Response {
+    Response::json(status, &serde_json::json!({"error": message}))
+}
+
+/// 校验单个事件请求
+fn validate(request: &EventRequest) -> std::result::Result<(), String> {
+    if request.vm_id.is_empty() {
+        return Err("vm_id 不能为空".to_string());
+    }
+    if request.event_type.is_empty() {
+        return Err("event_type 不能为空".to_string());
+    }
+    if !request.data.is_object() {
+        return Err("data 必须是 JSON 对象".to_string());
+    }
+    Ok(())
+}
+
+/// 发送事件并等待结论
+async fn inject(service: &VerificationService, request: EventRequest) -> EventResponse {
+    let event = Event {
+        event_type: request.event_type,
+        data: request.data,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    };
+    let event_id = Uuid::new_v4();
+    let timeout = request.timeout_ms.map(Duration::from_millis);
+
+    match service.send_event_with_id(&request.vm_id, event, event_id, timeout).await {
+        Ok(outcome_rx) => match outcome_rx.await {
+            Ok(outcome) => {
+                let result = outcome.matched().map(|matched| matched.result.clone());
+                EventResponse::from_summary(BroadcastVmResult::from_outcome(&request.vm_id, event_id, &outcome), result)
+            }
+            Err(_) => EventResponse {
+                event_id: Some(event_id.to_string()),
+                ..EventResponse::not_sent(&request.vm_id, BroadcastStatus::Failed, "结果通道关闭".to_string())
+            },
+        },
+        Err(VerificationError::ClientNotConnected(_)) => {
+            EventResponse::not_sent(&request.vm_id, BroadcastStatus::Skipped, "客户端未连接".to_string())
+        }
+        Err(e) => EventResponse::not_sent(&request.vm_id, BroadcastStatus::Failed, e.to_string()),
+    }
+}
+
+/// 处理 `POST /api/v1/events`
+///
+/// 收到结果或超时时返回 200 (结论见 `status`), Agent 未连接时返回 409, 发送失败时返回 503。
+pub(crate) async fn send_one(body: &[u8], service: &VerificationService) -> Response {
+    let request: EventRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return error_response("400 Bad Request", &e.to_string()),
+    };
+    if let Err(message) = validate(&request) {
+        return error_response("400 Bad Request", &message);
+    }
+
+    let response = inject(service, request).await;
+    let status = match response.status {
+        BroadcastStatus::Skipped => "409 Conflict",
+        BroadcastStatus::Failed => "503 Service Unavailable",
+        _ => "200 OK",
+    };
+    Response::json(status, &response)
+}
+
+/// 处理 `POST /api/v1/events/batch`
+///
+/// 所有事件同时发送, 单个事件未发送或失败不影响其余事件, 结论逐个写在 `results` 中。
+pub(crate) async fn send_batch(body: &[u8], service: &VerificationService) -> Response {
+    let request: BatchEventRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return error_response("400 Bad Request", &e.to_string()),
+    };
+    if request.events.is_empty() {
+        return error_response("400 Bad Request", "events 不能为空");
+    }
+    for (index, event) in request.events.iter().enumerate() {
+        if let Err(message) = validate(event) {
+            return error_response("400 Bad Request", &format!("events[{}]: {}", index, message));
+        }
+    }
+
+    let started = Instant::now();
+    let results = join_all(request.events.into_iter().map(|event| inject(service, event))).await;
+    Response::json("200 OK", &BatchEventResponse::new(results, started.elapsed().as_millis() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorize() {
+        assert!(authorize(Some("Bearer secret"), "secret").is_none());
+        assert!(authorize(Some("bearer  secret "), "secret").is_none());
+
+        for header in [None, Some("secret"), Some("Basic secret"), Some("Bearer wrong"), Some("Bearer secret2"), Some("Bearer secre")] {
+            let response = authorize(header, "secret").expect("应拒绝");
+            assert_eq!(response.status, "401 Unauthorized", "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secrets"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let request: EventRequest =
+            serde_json::from_str(r#"{"vm_id": "vm-1", "event_type": "keyboard"}"#).unwrap();
+        assert!(validate(&request).is_ok());
+        assert!(request.data.is_object());
+
+        let request: EventRequest =
+            serde_json::from_str(r#"{"vm_id": "vm-1", "event_type": "keyboard", "data": []}"#).unwrap();
+        assert!(validate(&request).unwrap_err().contains("data"));
+
+        let request: EventRequest = serde_json::from_str(r#"{"vm_id": "", "event_type": "keyboard"}"#).unwrap();
+        assert!(validate(&request).unwrap_err().contains("vm_id"));
+    }
+}
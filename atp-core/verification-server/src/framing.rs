@@ -0,0 +1,350 @@
+//! TCP 消息分帧
+//!
+//! 帧格式: 1 字节协议版本 + 4 字节大端长度 + JSON 消息体。
+//!
+//! 旧版 Agent 的帧没有版本字节 (4 字节长度 + 消息体, 首字节为 0),
+//! 更早的实现按换行分隔 JSON; 首帧的第一个字节即可区分三种格式。
+//! 旧格式默认被拒绝并返回明确的错误, 开启兼容模式时仍可接收, 且回复使用与对端相同的格式。
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::{Result, VerificationError};
+
+/// 当前协议版本
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// 默认最大帧长度 (10MB)
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 10 * 1024 * 1024;
+
+/// 帧格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameFormat {
+    /// 版本字节 + 长度前缀
+    Versioned,
+
+    /// 无版本字节的长度前缀 (旧版 Agent)
+    LegacyLengthPrefixed,
+
+    /// 换行分隔的 JSON (旧版 Agent)
+    NewlineDelimited,
+}
+
+impl FrameFormat {
+    /// 是否为需要兼容模式才能接收的旧格式
+    pub fn is_legacy(self) -> bool {
+        self != FrameFormat::Versioned
+    }
+}
+
+/// 把消息体编码为一帧
+pub fn encode_frame(format: FrameFormat, body: &str) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(body.len() + 5);
+    match format {
+        FrameFormat::Versioned => {
+            frame.push(PROTOCOL_VERSION);
+            frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            frame.extend_from_slice(body.as_bytes());
+        }
+        FrameFormat::LegacyLengthPrefixed => {
+            frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            frame.extend_from_slice(body.as_bytes());
+        }
+        FrameFormat::NewlineDelimited => {
+            frame.extend_from_slice(body.as_bytes());
+            frame.push(b'\n');
+        }
+    }
+    frame
+}
+
+/// 增量分帧解码器
+///
+/// 收到的字节通过 `feed` 追加, `next_frame` 每次取出一个完整帧,
+/// 数据不足时返回 `Ok(None)`。格式由首帧决定, 之后的帧必须使用相同格式。
+#[derive(Debug)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+    format: Option<FrameFormat>,
+    max_frame_size: usize,
+    allow_legacy: bool,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            format: None,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            allow_legacy: false,
+        }
+    }
+
+    pub fn with_max_frame_size(mut self, max_frame_size: usize) -> Self {
+        self.max_frame_size = max_frame_size;
+        self
+    }
+
+    /// 兼容模式: 接收旧版 Agent 的无版本字节帧与换行分隔帧
+    pub fn with_legacy_framing(mut self, allow_legacy: bool) -> Self {
+        self.allow_legacy = allow_legacy;
+        self
+    }
+
+    /// 对端使用的帧格式 (收到首帧之前为 None)
+    ///
+    /// 旧格式被拒绝时也会记录, 便于用对端能识别的格式回复拒绝消息。
+    pub fn format(&self) -> Option<FrameFormat> {
+        self.format
+    }
+
+    /// 追加收到的字节
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// 取出下一个完整帧的消息体
+    pub fn next_frame(&mut self) -> Result<Option<String>> {
+        let format = match self.format {
+            Some(format) => format,
+            None => match self.buffer.first() {
+                Some(&first) => {
+                    let format = detect_format(first)?;
+                    self.format = Some(format);
+                    format
+                }
+                None => return Ok(None),
+            },
+        };
+
+        if format.is_legacy() && !self.allow_legacy {
+            return Err(VerificationError::ProtocolError(format!(
+                "对端使用旧版分帧格式 ({:?}), 请升级 Agent 或开启兼容模式",
+                format
+            )));
+        }
+
+        let (header_len, body_len) = match format {
+            FrameFormat::Versioned => {
+                if self.buffer.len() < 5 {
+                    return Ok(None);
+                }
+                if self.buffer[0] != PROTOCOL_VERSION {
+                    return Err(unsupported_version(self.buffer[0]));
+                }
+                (5, read_len(&self.buffer[1..5]))
+            }
+            FrameFormat::LegacyLengthPrefixed => {
+                if self.buffer.len() < 4 {
+                    return Ok(None);
+                }
+                (4, read_len(&self.buffer[..4]))
+            }
+            FrameFormat::NewlineDelimited => {
+                return match self.buffer.iter().position(|&b| b == b'\n') {
+                    Some(end) => {
+                        let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                        let line = line.strip_suffix(b"\n").unwrap_or(&line);
+                        let line = line.strip_suffix(b"\r").unwrap_or(line);
+                        self.check_size(line.len())?;
+                        decode_body(line.to_vec()).map(Some)
+                    }
+                    None => {
+                        self.check_size(self.buffer.len())?;
+                        Ok(None)
+                    }
+                };
+            }
+        };
+
+        self.check_size(body_len)?;
+        if self.buffer.len() < header_len + body_len {
+            return Ok(None);
+        }
+
+        let frame: Vec<u8> = self.buffer.drain(..header_len + body_len).skip(header_len).collect();
+        decode_body(frame).map(Some)
+    }
+
+    fn check_size(&self, len: usize) -> Result<()> {
+        if len > self.max_frame_size {
+            return Err(VerificationError::ProtocolError(format!(
+                "消息过大: {} bytes (最大: {} bytes)",
+                len, self.max_frame_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// 从 reader 读取下一帧, 连接正常关闭时返回 `Ok(None)`
+    pub async fn read_frame<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> Result<Option<String>> {
+        let mut chunk = [0u8; 8192];
+        loop {
+            if let Some(frame) = self.next_frame()? {
+                return Ok(Some(frame));
+            }
+
+            let n = reader.read(&mut chunk).await?;
+            if n == 0 {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+                return Err(VerificationError::ProtocolError(format!(
+                    "连接在帧中途关闭 (剩余 {} bytes)",
+                    self.buffer.len()
+                )));
+            }
+            self.feed(&chunk[..n]);
+        }
+    }
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 根据首字节判断帧格式
+fn detect_format(first: u8) -> Result<FrameFormat> {
+    match first {
+        PROTOCOL_VERSION => Ok(FrameFormat::Versioned),
+        // 长度前缀的最高字节: 合法长度不超过 16MB
+        0 => Ok(FrameFormat::LegacyLengthPrefixed),
+        b if b.is_ascii_graphic() => Ok(FrameFormat::NewlineDelimited),
+        b => Err(unsupported_version(b)),
+    }
+}
+
+fn unsupported_version(version: u8) -> VerificationError {
+    VerificationError::ProtocolError(format!(
+        "不支持的协议版本: {} (当前版本: {})",
+        version, PROTOCOL_VERSION
+    ))
+}
+
+fn read_len(bytes: &[u8]) -> usize {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+}
+
+fn decode_body(body: Vec<u8>) -> Result<String> {
+    String::from_utf8(body)
+        .map_err(|_| VerificationError::ProtocolError("消息不是有效的 UTF-8".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RESULT: &str = r#"{"event_id":"e1","verified":true,"timestamp":0,"latency_ms":1,"details":{"output":"line1\nline2"}}"#;
+
+    #[test]
+    fn test_partial_frames_are_buffered() {
+        let frame = encode_frame(FrameFormat::Versioned, RESULT);
+        let mut decoder = FrameDecoder::new();
+
+        // 逐字节送入, 直到最后一个字节才得到完整帧
+        for (i, byte) in frame.iter().enumerate() {
+            decoder.feed(&[*byte]);
+            let decoded = decoder.next_frame().unwrap();
+            if i + 1 < frame.len() {
+                assert!(decoded.is_none(), "frame completed early at byte {}", i);
+            } else {
+                assert_eq!(decoded.as_deref(), Some(RESULT));
+            }
+        }
+        assert_eq!(decoder.format(), Some(FrameFormat::Versioned));
+    }
+
+    #[test]
+    fn test_concatenated_frames_split_correctly() {
+        let bodies = ["vm-1", RESULT, "", "{\"a\":\"\\n\"}"];
+        let stream: Vec<u8> = bodies
+            .iter()
+            .flat_map(|body| encode_frame(FrameFormat::Versioned, body))
+            .collect();
+
+        // 不同的切分方式得到相同的帧序列
+        for chunk_size in [1, 3, 7, stream.len()] {
+            let mut decoder = FrameDecoder::new();
+            let mut frames = Vec::new();
+            for chunk in stream.chunks(chunk_size) {
+                decoder.feed(chunk);
+                while let Some(frame) = decoder.next_frame().unwrap() {
+                    frames.push(frame);
+                }
+            }
+            assert_eq!(frames, bodies, "chunk size {}", chunk_size);
+        }
+    }
+
+    #[test]
+    fn test_oversized_length_rejected_before_reading_body() {
+        let mut decoder = FrameDecoder::new().with_max_frame_size(16);
+        let mut frame = vec![PROTOCOL_VERSION];
+        frame.extend_from_slice(&u32::MAX.to_be_bytes());
+        decoder.feed(&frame);
+
+        let err = decoder.next_frame().unwrap_err();
+        assert!(err.to_string().contains("消息过大"), "{}", err);
+
+        // 换行分隔帧没有长度, 缓冲超过上限即报错
+        let mut decoder = FrameDecoder::new().with_max_frame_size(16).with_legacy_framing(true);
+        decoder.feed(&[b'x'; 17]);
+        assert!(decoder.next_frame().is_err());
+    }
+
+    #[test]
+    fn test_legacy_frames_rejected_unless_compat() {
+        let legacy = encode_frame(FrameFormat::LegacyLengthPrefixed, "vm-1");
+        let newline = encode_frame(FrameFormat::NewlineDelimited, "vm-1");
+
+        for frame in [&legacy, &newline] {
+            let mut decoder = FrameDecoder::new();
+            decoder.feed(frame);
+            let err = decoder.next_frame().unwrap_err();
+            assert!(err.to_string().contains("旧版分帧格式"), "{}", err);
+        }
+
+        let mut decoder = FrameDecoder::new().with_legacy_framing(true);
+        decoder.feed(&legacy);
+        decoder.feed(&encode_frame(FrameFormat::LegacyLengthPrefixed, RESULT));
+        assert_eq!(decoder.next_frame().unwrap().as_deref(), Some("vm-1"));
+        assert_eq!(decoder.next_frame().unwrap().as_deref(), Some(RESULT));
+        assert_eq!(decoder.format(), Some(FrameFormat::LegacyLengthPrefixed));
+
+        let mut decoder = FrameDecoder::new().with_legacy_framing(true);
+        decoder.feed(b"vm-1\r\n{\"a\":1}\n{\"b\"");
+        assert_eq!(decoder.next_frame().unwrap().as_deref(), Some("vm-1"));
+        assert_eq!(decoder.next_frame().unwrap().as_deref(), Some("{\"a\":1}"));
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        assert_eq!(decoder.format(), Some(FrameFormat::NewlineDelimited));
+    }
+
+    #[test]
+    fn test_unknown_version_and_invalid_utf8() {
+        let mut decoder = FrameDecoder::new();
+        decoder.feed(&[7, 0, 0, 0, 1, b'x']);
+        assert!(decoder.next_frame().unwrap_err().to_string().contains("不支持的协议版本: 7"));
+
+        let mut decoder = FrameDecoder::new();
+        decoder.feed(&[PROTOCOL_VERSION, 0, 0, 0, 2, 0xff, 0xfe]);
+        assert!(decoder.next_frame().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_from_stream() {
+        let mut stream: Vec<u8> = encode_frame(FrameFormat::Versioned, "vm-1");
+        stream.extend(encode_frame(FrameFormat::Versioned, RESULT));
+        stream.extend(&encode_frame(FrameFormat::Versioned, "cut")[..4]);
+
+        let mut reader = stream.as_slice();
+        let mut decoder = FrameDecoder::new();
+        assert_eq!(decoder.read_frame(&mut reader).await.unwrap().as_deref(), Some("vm-1"));
+        assert_eq!(decoder.read_frame(&mut reader).await.unwrap().as_deref(), Some(RESULT));
+        // 帧中途断开
+        assert!(decoder.read_frame(&mut reader).await.is_err());
+
+        let mut reader: &[u8] = &[];
+        assert_eq!(FrameDecoder::new().read_frame(&mut reader).await.unwrap(), None);
+    }
+}
@@ -4,13 +4,34 @@
 //! 并与发送的事件进行一对一匹配。
 
 pub mod server;
+pub mod broadcast;
 pub mod service;
 pub mod types;
 pub mod client;
+pub mod pending;
+pub mod framing;
+pub mod auth;
+pub mod tls;
+pub mod metrics_http;
+pub mod event_api;
+pub mod config;
+pub mod daemon;
 
+pub use auth::AuthTokens;
+pub use broadcast::{BroadcastOutcome, BroadcastStatus, BroadcastVmResult, LatencyStats};
+pub use config::DaemonConfig;
+pub use event_api::{BatchEventRequest, BatchEventResponse, EventRequest, EventResponse};
+pub use daemon::Daemon;
 pub use server::VerificationServer;
 pub use service::VerificationService;
-pub use types::{Event, VerifyResult, ClientConnection};
+pub use client::{ClientActivity, ClientManager, ClientRegistration, ClientRegistry};
+pub use framing::{FrameDecoder, FrameFormat, PROTOCOL_VERSION};
+pub use pending::{MatchCounters, MatchStats, PendingEventTable};
+pub use tls::TlsConfig;
+pub use types::{
+    AgentMode, ClientConnection, ClientInfo, Event, HeartbeatMessage, MatchedResult, RawInputEvent,
+    RawInputReport, RegisterMessage, VerifyOutcome, VerifyResult,
+};
 
 use thiserror::Error;
 
@@ -22,17 +43,41 @@ pub enum VerificationError {
     #[error("验证超时")]
     Timeout,
 
+    #[error("握手失败: {0}")]
+    InvalidHandshake(String),
+
+    #[error("虚拟机 {0} 已有客户端连接 ({1})")]
+    DuplicateClient(String, String),
+
+    #[error("认证失败: {0}")]
+    Unauthorized(String),
+
+    #[error("服务器正在关闭")]
+    ShuttingDown,
+
+    #[error("协议错误: {0}")]
+    ProtocolError(String),
+
     #[error("事件未找到: {0}")]
     EventNotFound(String),
 
     #[error("服务器错误: {0}")]
     ServerError(String),
 
+    #[error("配置错误: {0}")]
+    ConfigError(String),
+
     #[error("IO 错误: {0}")]
     IoError(#[from] std::io::Error),
 
+    #[error("存储错误: {0}")]
+    StorageError(#[from] atp_storage::StorageError),
+
     #[error("序列化错误: {0}")]
     SerializationError(#[from] serde_json::Error),
+
+    #[error("消息格式错误: {0}")]
+    EnvelopeError(#[from] atp_common::EnvelopeError),
 }
 
 pub type Result<T> = std::result::Result<T, VerificationError>;
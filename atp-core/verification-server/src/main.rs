@@ -0,0 +1,100 @@
+//! 验证服务器独立部署入口
+//!
+//! ```bash
+//! verification-server --config /etc/atp/verification-server.toml
+//! ```
+//!
+//! - SIGHUP: 重载配置文件中的日志级别与认证 token
+//! - SIGTERM / Ctrl+C: 通知所有 Agent 断开, 指标写库后退出
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use tokio::signal::unix::{signal, SignalKind};
+use tracing::{error, info};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+use verification_server::{Daemon, DaemonConfig};
+
+#[derive(Parser, Debug)]
+#[command(name = "verification-server")]
+#[command(about = "Guest 验证器服务端 - 接收 Agent 的验证结果并与事件匹配", long_about = None)]
+struct Args {
+    /// 配置文件路径
+    #[arg(short, long, default_value = "/etc/atp/verification-server.toml")]
+    config: PathBuf,
+
+    /// 只校验配置文件后退出
+    #[arg(long)]
+    check: bool,
+}
+
+type FilterHandle = reload::Handle<EnvFilter, Registry>;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let config = DaemonConfig::load(&args.config)
+        .with_context(|| format!("加载配置文件失败: {}", args.config.display()))?;
+
+    if args.check {
+        // 证书在组装服务器配置时加载
+        config.server_config().context("配置校验失败")?;
+        println!("配置有效: {}", args.config.display());
+        return Ok(());
+    }
+
+    let (filter, filter_handle) = reload::Layer::new(config.log_filter()?);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer())
+        .init();
+
+    info!("加载配置文件: {}", args.config.display());
+    let mut daemon = Daemon::start(config).await.context("启动验证服务器失败")?;
+
+    let mut hangup = signal(SignalKind::hangup())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    loop {
+        tokio::select! {
+            _ = hangup.recv() => reload_config(&args.config, &mut daemon, &filter_handle),
+            _ = terminate.recv() => {
+                info!("收到 SIGTERM");
+                break;
+            }
+            _ = tokio::signal::ctrl_c() => {
+                info!("收到 Ctrl+C");
+                break;
+            }
+        }
+    }
+
+    daemon.shutdown().await.context("关闭验证服务器失败")?;
+    Ok(())
+}
+
+/// 处理 SIGHUP: 配置无效时保持原配置
+fn reload_config(path: &Path, daemon: &mut Daemon, filter_handle: &FilterHandle) {
+    info!("收到 SIGHUP, 重载配置: {}", path.display());
+
+    let config = match DaemonConfig::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("重载配置失败, 保持原配置: {}", e);
+            return;
+        }
+    };
+
+    if config.log != daemon.config().log {
+        match config.log_filter().map(|filter| filter_handle.reload(filter)) {
+            Ok(Ok(())) => info!("日志级别已更新: {}", config.log.level),
+            Ok(Err(e)) => error!("更新日志级别失败: {}", e),
+            Err(e) => error!("更新日志级别失败: {}", e),
+        }
+    }
+
+    daemon.reload(config);
+}
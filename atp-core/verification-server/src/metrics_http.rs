@@ -0,0 +1,462 @@
+//! 指标与状态 HTTP 端点
+//!
+//! - `GET /metrics`: 以 Prometheus 文本格式输出 [`MetricsSource`] 的当前快照,
+//!   指标名为 `atp_<source>_<name>`
+//! - `GET /healthz`: 存活检查, 服务关闭过程中返回 503
+//! - `GET /clients`: 已连接 Agent 列表 (JSON)
+//! - `GET /stats`: 事件发送与结果匹配统计 (JSON)
+//! - `POST /broadcast`: 向多台虚拟机广播验证事件并返回汇总结果 (JSON, 需在 `[metrics]` 中开启 `admin`,
+//!   配置了 `api_token` 时同样要求 Bearer token)
+//! - `POST /api/v1/events`, `POST /api/v1/events/batch`: 事件注入接口 (见 [`crate::event_api`],
+//!   需在 `[metrics]` 中配置 `api_token`)
+//!
+//! 只实现了抓取与管理所需的最小 HTTP 子集。
+
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use atp_storage::{MetricSample, MetricsSource};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info};
+
+use crate::event_api;
+use crate::service::VerificationService;
+use crate::types::Event;
+use crate::Result;
+
+/// 请求头最大长度
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// 请求体最大长度
+const MAX_BODY_LEN: usize = 64 * 1024;
+
+/// 读取请求的超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 指标名只允许 `[a-zA-Z0-9_]`, 其余字符替换为下划线
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// 转义标签值
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// 渲染 Prometheus 文本格式
+pub fn render_prometheus(source_name: &str, samples: &[MetricSample]) -> String {
+    let prefix = format!("atp_{}", sanitize(source_name));
+    let mut text = String::new();
+
+    for sample in samples {
+        let _ = write!(text, "{}_{}", prefix, sanitize(&sample.name));
+        if !sample.labels.is_empty() {
+            let labels: Vec<String> = sample
+                .labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", sanitize(key), escape_label(value)))
+                .collect();
+            let _ = write!(text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(text, " {}", sample.value);
+    }
+
+    text
+}
+
+/// `GET /stats` 的响应
+#[derive(Debug, Serialize)]
+pub struct StatsResponse {
+    /// 已发送给 Agent 的事件数
+    pub events_sent: u64,
+    /// 收到结果的事件数 (verified + mismatched)
+    pub results_matched: u64,
+    /// 验证成功数
+    pub verified: u64,
+    /// 验证失败数
+    pub mismatched: u64,
+    /// 超时数
+    pub timeouts: u64,
+    /// 孤儿结果数
+    pub orphaned: u64,
+    /// 宽限期内迟到、由超时改判的结果数
+    pub late_results: u64,
+    /// Agent 上报的平均延迟 (毫秒)
+    pub avg_latency_ms: f64,
+    /// 服务端测得的平均延迟 (毫秒)
+    pub avg_server_latency_ms: f64,
+    /// 当前待验证事件数
+    pub pending_events: usize,
+    /// 当前已连接客户端数
+    pub connected_clients: usize,
+}
+
+impl StatsResponse {
+    /// 采集验证服务的当前统计
+    pub async fn collect(service: &VerificationService) -> Self {
+        let stats = service.match_stats().await;
+
+        Self {
+            events_sent: stats.sent,
+            results_matched: stats.matched(),
+            verified: stats.verified,
+            mismatched: stats.mismatched,
+            timeouts: stats.timed_out,
+            orphaned: stats.orphaned,
+            late_results: stats.late,
+            avg_latency_ms: stats.avg_agent_latency_ms(),
+            avg_server_latency_ms: stats.avg_server_latency_ms(),
+            pending_events: service.pending_count().await,
+            connected_clients: service.list_clients().await.len(),
+        }
+    }
+}
+
+/// `POST /broadcast` 的请求体
+#[derive(Debug, Deserialize)]
+pub struct BroadcastRequest {
+    /// 目标虚拟机 ID
+    pub vm_ids: Vec<String>,
+
+    /// 事件类型 (keyboard, mouse, command)
+    pub event_type: String,
+
+    /// 事件数据 (必须是 JSON 对象)
+    #[serde(default = "empty_object")]
+    pub data: serde_json::Value,
+
+    /// 超时时间 (毫秒), 省略时使用服务的默认超时
+    #[serde(default)]
+    pub timeout_ms: Option<u64>,
+}
+
+fn empty_object() -> serde_json::Value {
+    serde_json::json!({})
+}
+
+/// HTTP 端点选项
+#[derive(Debug, Clone, Default)]
+pub struct HttpOptions {
+    /// 是否提供 `POST /broadcast` 管理端点 (配置了 `api_token` 时需要认证)
+    pub admin: bool,
+
+    /// 事件注入接口的 Bearer token, None 时不提供该接口
+    pub api_token: Option<String>,
+}
+
+/// HTTP 响应
+pub(crate) struct Response {
+    pub(crate) status: &'static str,
+    content_type: &'static str,
+    pub(crate) body: String,
+}
+
+impl Response {
+    fn text(status: &'static str, body: String) -> Self {
+        Self { status, content_type: "text/plain; version=0.0.4", body }
+    }
+
+    pub(crate) fn json<T: Serialize>(status: &'static str, value: &T) -> Self {
+        match serde_json::to_string(value) {
+            Ok(body) => Self { status, content_type: "application/json", body },
+            Err(e) => Self::text("500 Internal Server Error", format!("{}\n", e)),
+        }
+    }
+}
+
+/// 按路径分发请求, 未开启的管理端点与事件注入接口返回 404
+async fn route(
+    method: Option<&str>,
+    path: Option<&str>,
+    authorization: Option<&str>,
+    body: &[u8],
+    service: &VerificationService,
+    options: &HttpOptions,
+) -> Response {
+    if let (Some(path @ ("/api/v1/events" | "/api/v1/events/batch")), Some(api_token)) = (path, &options.api_token) {
+        if let Some(rejected) = event_api::authorize(authorization, api_token) {
+            return rejected;
+        }
+        return match (method, path) {
+            (Some("POST"), "/api/v1/events") => event_api::send_one(body, service).await,
+            (Some("POST"), _) => event_api::send_batch(body, service).await,
+            _ => Response::text("405 Method Not Allowed", "method not allowed\n".to_string()),
+        };
+    }
+
+    match (method, path) {
+        (Some("GET"), Some("/metrics")) => {
+            let samples = service.collect().await;
+            Response::text("200 OK", render_prometheus(service.source_name(), &samples))
+        }
+        (Some("GET"), Some("/healthz")) => {
+            if service.client_manager.is_shutting_down() {
+                Response::json("503 Service Unavailable", &serde_json::json!({"status": "shutting_down"}))
+            } else {
+                Response::json("200 OK", &serde_json::json!({"status": "ok"}))
+            }
+        }
+        (Some("GET"), Some("/clients")) => Response::json("200 OK", &service.list_clients().await),
+        (Some("GET"), Some("/stats")) => Response::json("200 OK", &StatsResponse::collect(service).await),
+        (Some("POST"), Some("/broadcast")) if options.admin => {
+            if let Some(rejected) = options.api_token.as_deref().and_then(|token| event_api::authorize(authorization, token)) {
+                return rejected;
+            }
+            broadcast(body, service).await
+        }
+        _ => Response::text("404 Not Found", "not found\n".to_string()),
+    }
+}
+
+/// 处理 `POST /broadcast`
+async fn broadcast(body: &[u8], service: &VerificationService) -> Response {
+    let request: BroadcastRequest = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => return Response::json("400 Bad Request", &serde_json::json!({"error": e.to_string()})),
+    };
+    if request.vm_ids.is_empty() || !request.data.is_object() {
+        return Response::json(
+            "400 Bad Request",
+            &serde_json::json!({"error": "vm_ids 不能为空, data 必须是 JSON 对象"}),
+        );
+    }
+
+    let event = Event {
+        event_type: request.event_type,
+        data: request.data,
+        timestamp: chrono::Utc::now().timestamp_millis(),
+    };
+    let outcome = service
+        .broadcast_event(&request.vm_ids, event, request.timeout_ms.map(Duration::from_millis))
+        .await;
+    Response::json("200 OK", &outcome)
+}
+
+/// 在已绑定的端口上提供指标与状态端点, 直到任务被取消
+///
+/// 按 `options` 同时提供 `POST /broadcast` 管理端点与事件注入接口。
+pub async fn run_metrics_server(
+    listener: TcpListener,
+    service: Arc<VerificationService>,
+    options: HttpOptions,
+) -> Result<()> {
+    info!("指标端点启动: http://{}/metrics", listener.local_addr()?);
+    if options.api_token.is_some() {
+        info!("事件注入接口已开启: http://{}/api/v1/events", listener.local_addr()?);
+    }
+    let options = Arc::new(options);
+
+    loop {
+        let (stream, peer_addr) = listener.accept().await?;
+        let service = service.clone();
+        let options = options.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = handle_request(stream, service, &options).await {
+                debug!("指标请求处理失败 ({}): {}", peer_addr, e);
+            }
+        });
+    }
+}
+
+/// 读取请求头与请求体 (按 Content-Length), 连接提前关闭或超长时返回 None
+async fn read_request(stream: &mut TcpStream) -> Result<Option<(String, Vec<u8>)>> {
+    let mut request = Vec::new();
+    let mut chunk = [0u8; 1024];
+    let header_end = loop {
+        if let Some(pos) = request.windows(4).position(|window| window == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 || request.len() + n > MAX_REQUEST_LEN {
+            return Ok(None);
+        }
+        request.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&request[..header_end]).into_owned();
+    let content_length = header(&head, "content-length")
+        .and_then(|value| value.parse::<usize>().ok())
+        .unwrap_or(0);
+    if content_length > MAX_BODY_LEN {
+        return Ok(None);
+    }
+
+    let mut body = request.split_off(header_end);
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Ok(None);
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+    body.truncate(content_length);
+
+    Ok(Some((head, body)))
+}
+
+/// 按名称 (不区分大小写) 查找请求头
+fn header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.trim())
+}
+
+/// 处理一次 HTTP 请求
+///
+/// 读取请求受 [`REQUEST_TIMEOUT`] 限制; 广播与事件注入请求的处理时间取决于事件超时, 不受此限制。
+async fn handle_request(mut stream: TcpStream, service: Arc<VerificationService>, options: &HttpOptions) -> Result<()> {
+    let (head, body) = match tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(Ok(Some(request))) => request,
+        Ok(Ok(None)) => return Ok(()),
+        Ok(Err(e)) => return Err(e),
+        Err(_) => {
+            debug!("读取指标请求超时");
+            return Ok(());
+        }
+    };
+
+    let mut parts = head.split_whitespace();
+    let response = route(parts.next(), parts.next(), header(&head, "authorization"), &body, &service, options).await;
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status,
+        response.content_type,
+        response.body.len(),
+        response.body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientManager;
+    use crate::service::ServiceConfig;
+    use crate::types::{ClientConnection, RegisterMessage};
+
+    #[test]
+    fn test_render_prometheus() {
+        let samples = vec![
+            MetricSample::new("pending_events", 3.0),
+            MetricSample::new("avg_latency_ms", 12.5).with_label("vm", "win\"10"),
+        ];
+
+        assert_eq!(
+            render_prometheus("verification_server", &samples),
+            "atp_verification_server_pending_events 3\n\
+             atp_verification_server_avg_latency_ms{vm=\"win\\\"10\"} 12.5\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_status_routes() {
+        let client_manager = Arc::new(ClientManager::new());
+        let service = VerificationService::new(client_manager.clone(), ServiceConfig::default());
+        let options = HttpOptions::default();
+        client_manager
+            .register_client(
+                ClientConnection::Tcp { vm_id: "vm-1".to_string(), addr: "10.0.0.5:4000".to_string() },
+                &RegisterMessage::new("vm-1"),
+            )
+            .await
+            .unwrap();
+
+        let health = route(Some("GET"), Some("/healthz"), None, b"", &service, &options).await;
+        assert_eq!((health.status, health.content_type), ("200 OK", "application/json"));
+
+        let clients = route(Some("GET"), Some("/clients"), None, b"", &service, &options).await;
+        let clients: serde_json::Value = serde_json::from_str(&clients.body).unwrap();
+        assert_eq!(clients[0]["vm_id"], "vm-1");
+        assert_eq!(clients[0]["transport"], "tcp");
+        assert!(clients[0]["last_activity"].is_string());
+
+        let stats = route(Some("GET"), Some("/stats"), None, b"", &service, &options).await;
+        let stats: serde_json::Value = serde_json::from_str(&stats.body).unwrap();
+        assert_eq!(stats["events_sent"], 0);
+        assert_eq!(stats["connected_clients"], 1);
+
+        assert_eq!(route(Some("POST"), Some("/stats"), None, b"", &service, &options).await.status, "404 Not Found");
+
+        client_manager.shutdown();
+        let health = route(Some("GET"), Some("/healthz"), None, b"", &service, &options).await;
+        assert_eq!(health.status, "503 Service Unavailable");
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_route() {
+        let service = VerificationService::new(Arc::new(ClientManager::new()), ServiceConfig::default());
+        let body = br#"{"vm_ids": ["vm-1", "vm-2"], "event_type": "keyboard", "data": {"key": "ret"}, "timeout_ms": 50}"#;
+
+        let admin = HttpOptions { admin: true, ..Default::default() };
+
+        // 未开启管理端点
+        let response = route(Some("POST"), Some("/broadcast"), None, body, &service, &HttpOptions::default()).await;
+        assert_eq!(response.status, "404 Not Found");
+
+        let response = route(Some("POST"), Some("/broadcast"), None, body, &service, &admin).await;
+        assert_eq!(response.status, "200 OK");
+        let outcome: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(outcome["skipped"], 2);
+        assert_eq!(outcome["results"][1]["vm_id"], "vm-2");
+        assert!(outcome["latency"].is_null());
+
+        for body in [&b"not json"[..], br#"{"vm_ids": [], "event_type": "keyboard"}"#, br#"{"vm_ids": ["vm-1"], "event_type": "keyboard", "data": 1}"#] {
+            let response = route(Some("POST"), Some("/broadcast"), None, body, &service, &admin).await;
+            assert_eq!(response.status, "400 Bad Request", "{}", String::from_utf8_lossy(body));
+        }
+
+        // 配置了 api_token 时广播同样需要认证
+        let secured = HttpOptions { admin: true, api_token: Some("secret".to_string()) };
+        let response = route(Some("POST"), Some("/broadcast"), None, body, &service, &secured).await;
+        assert_eq!(response.status, "401 Unauthorized");
+        let response = route(Some("POST"), Some("/broadcast"), Some("Bearer wrong!"), body, &service, &secured).await;
+        assert_eq!(response.status, "401 Unauthorized");
+        let response = route(Some("POST"), Some("/broadcast"), Some("Bearer secret"), body, &service, &secured).await;
+        assert_eq!(response.status, "200 OK");
+    }
+
+    #[tokio::test]
+    async fn test_event_api_routes() {
+        let service = VerificationService::new(Arc::new(ClientManager::new()), ServiceConfig::default());
+        let options = HttpOptions { api_token: Some("secret".to_string()), ..Default::default() };
+        let body = br#"{"vm_id": "vm-1", "event_type": "keyboard", "data": {"key": "ret"}}"#;
+
+        // 未配置 api_token
+        let response = route(Some("POST"), Some("/api/v1/events"), Some("Bearer secret"), body, &service, &HttpOptions::default()).await;
+        assert_eq!(response.status, "404 Not Found");
+
+        let response = route(Some("POST"), Some("/api/v1/events"), None, body, &service, &options).await;
+        assert_eq!(response.status, "401 Unauthorized");
+        let response = route(Some("GET"), Some("/api/v1/events"), Some("Bearer secret"), body, &service, &options).await;
+        assert_eq!(response.status, "405 Method Not Allowed");
+
+        // Agent 未连接
+        let response = route(Some("POST"), Some("/api/v1/events"), Some("Bearer secret"), body, &service, &options).await;
+        assert_eq!(response.status, "409 Conflict");
+        let outcome: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(outcome["status"], "skipped");
+        assert!(outcome["result"].is_null());
+
+        let batch = br#"{"events": [{"vm_id": "vm-1", "event_type": "keyboard"}, {"vm_id": "vm-2", "event_type": "mouse"}]}"#;
+        let response = route(Some("POST"), Some("/api/v1/events/batch"), Some("Bearer secret"), batch, &service, &options).await;
+        assert_eq!(response.status, "200 OK");
+        let outcome: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(outcome["skipped"], 2);
+        assert_eq!(outcome["results"][1]["vm_id"], "vm-2");
+
+        for body in [&b"{}"[..], br#"{"events": []}"#, br#"{"events": [{"vm_id": "vm-1", "event_type": "keyboard", "data": 1}]}"#] {
+            let response = route(Some("POST"), Some("/api/v1/events/batch"), Some("Bearer secret"), body, &service, &options).await;
+            assert_eq!(response.status, "400 Bad Request", "{}", String::from_utf8_lossy(body));
+        }
+    }
+}
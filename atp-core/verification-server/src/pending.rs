@@ -0,0 +1,534 @@
+//! 待验证事件表
+//!
+//! 按 event_id 将 Agent 返回的结果与发出的事件一对一匹配。每个事件有自己的截止时间,
+//! 到期仍未收到结果即判为超时; 找不到对应事件 (未知或已超时) 的结果计为孤儿结果。
+//! 配置了验证结果仓储时, 每个匹配或超时的事件在后台任务中写库, 不阻塞匹配路径。
+//!
+//! 配置了迟到宽限期时, 超时的事件在宽限期内仍保留: Agent 断线重连后补发的结果
+//! 在宽限期内到达时, 将该事件由超时改判为成功/失败 (统计与存储同步修正)。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use atp_storage::{VerificationRepository, VerificationResultRecord};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::types::{MatchedResult, PendingEvent, VerifyOutcome, VerifyResult};
+use crate::{Result, VerificationError};
+
+/// 匹配统计
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchStats {
+    /// 已发送给 Agent 的事件数
+    pub sent: u64,
+
+    /// Agent 确认成功的事件数
+    pub verified: u64,
+
+    /// 收到结果但验证失败的事件数
+    pub mismatched: u64,
+
+    /// 超时的事件数
+    pub timed_out: u64,
+
+    /// 找不到对应事件的结果数
+    pub orphaned: u64,
+
+    /// 宽限期内迟到、由超时改判的结果数 (已计入 verified / mismatched)
+    pub late: u64,
+
+    /// Agent 上报延迟累计 (毫秒)
+    pub total_agent_latency_ms: u64,
+
+    /// 服务端测得延迟累计 (毫秒)
+    pub total_server_latency_ms: u64,
+}
+
+impl MatchStats {
+    /// 收到结果的事件数
+    pub fn matched(&self) -> u64 {
+        self.verified + self.mismatched
+    }
+
+    /// Agent 上报的平均延迟 (毫秒)
+    pub fn avg_agent_latency_ms(&self) -> f64 {
+        match self.matched() {
+            0 => 0.0,
+            count => self.total_agent_latency_ms as f64 / count as f64,
+        }
+    }
+
+    /// 服务端测得的平均延迟 (毫秒)
+    pub fn avg_server_latency_ms(&self) -> f64 {
+        match self.matched() {
+            0 => 0.0,
+            count => self.total_server_latency_ms as f64 / count as f64,
+        }
+    }
+}
+
+/// 匹配计数器
+///
+/// 全部为原子计数, 读取统计不需要获取待验证事件表的锁。
+#[derive(Debug, Default)]
+pub struct MatchCounters {
+    sent: AtomicU64,
+    verified: AtomicU64,
+    mismatched: AtomicU64,
+    timed_out: AtomicU64,
+    orphaned: AtomicU64,
+    late: AtomicU64,
+    total_agent_latency_ms: AtomicU64,
+    total_server_latency_ms: AtomicU64,
+}
+
+impl MatchCounters {
+    /// 记录一次成功发送的事件
+    pub fn record_sent(&self) {
+        self.sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 当前统计快照
+    pub fn snapshot(&self) -> MatchStats {
+        MatchStats {
+            sent: self.sent.load(Ordering::Relaxed),
+            verified: self.verified.load(Ordering::Relaxed),
+            mismatched: self.mismatched.load(Ordering::Relaxed),
+            timed_out: self.timed_out.load(Ordering::Relaxed),
+            orphaned: self.orphaned.load(Ordering::Relaxed),
+            late: self.late.load(Ordering::Relaxed),
+            total_agent_latency_ms: self.total_agent_latency_ms.load(Ordering::Relaxed),
+            total_server_latency_ms: self.total_server_latency_ms.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add(counter: &AtomicU64, value: u64) {
+        counter.fetch_add(value, Ordering::Relaxed);
+    }
+}
+
+/// 已超时、仍在宽限期内等待迟到结果的事件
+#[derive(Debug)]
+struct ExpiredEvent {
+    vm_id: String,
+    event_type: String,
+    created_at: Instant,
+    expired_at: Instant,
+}
+
+/// 待验证事件表
+pub struct PendingEventTable {
+    /// event_id -> 待验证事件
+    events: HashMap<Uuid, PendingEvent>,
+
+    /// event_id -> 宽限期内的超时事件
+    expired: HashMap<Uuid, ExpiredEvent>,
+
+    /// 迟到结果的宽限期 (为 0 时超时即终局)
+    late_grace: Duration,
+
+    /// 最大待验证事件数
+    max_pending: usize,
+
+    /// 匹配统计
+    counters: Arc<MatchCounters>,
+
+    /// 验证结果仓储 (未配置时不落库)
+    recorder: Option<VerificationRepository>,
+}
+
+impl PendingEventTable {
+    pub fn new(max_pending: usize) -> Self {
+        Self {
+            events: HashMap::new(),
+            expired: HashMap::new(),
+            late_grace: Duration::ZERO,
+            max_pending,
+            counters: Arc::new(MatchCounters::default()),
+            recorder: None,
+        }
+    }
+
+    /// 将匹配或超时的事件写入验证结果仓储
+    pub fn with_recorder(mut self, recorder: VerificationRepository) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// 超时后在宽限期内仍接受迟到的结果, 并据此改判结论
+    pub fn with_late_grace(mut self, late_grace: Duration) -> Self {
+        self.late_grace = late_grace;
+        self
+    }
+
+    /// 登记待验证事件, 超过最大数量时返回错误
+    pub fn insert(&mut self, pending: PendingEvent) -> Result<()> {
+        if self.events.len() >= self.max_pending {
+            return Err(VerificationError::ServerError(
+                "待验证事件过多，请稍后重试".to_string(),
+            ));
+        }
+
+        self.events.insert(pending.event_id, pending);
+        Ok(())
+    }
+
+    /// 用收到的结果完成对应事件, 返回是否匹配上
+    ///
+    /// `received_at` 为服务端收到结果的时间, 用于计算服务端延迟。
+    pub fn complete(&mut self, result: VerifyResult, received_at: Instant) -> bool {
+        let pending = Uuid::parse_str(&result.event_id)
+            .ok()
+            .and_then(|event_id| self.events.remove(&event_id));
+
+        let Some(pending) = pending else {
+            return self.complete_late(result, received_at);
+        };
+
+        let matched = MatchedResult {
+            server_latency_ms: received_at.duration_since(pending.created_at).as_millis() as u64,
+            agent_latency_ms: result.latency_ms,
+            result,
+        };
+
+        MatchCounters::add(&self.counters.total_server_latency_ms, matched.server_latency_ms);
+        MatchCounters::add(&self.counters.total_agent_latency_ms, matched.agent_latency_ms);
+
+        let outcome = if matched.result.verified {
+            MatchCounters::add(&self.counters.verified, 1);
+            VerifyOutcome::Verified(matched)
+        } else {
+            MatchCounters::add(&self.counters.mismatched, 1);
+            VerifyOutcome::Mismatched(matched)
+        };
+
+        debug!(
+            "事件匹配完成: vm_id={}, event_id={}, verified={}",
+            pending.vm_id,
+            pending.event_id,
+            outcome.is_verified()
+        );
+        self.record(&pending, &outcome);
+        if pending.result_tx.send(outcome).is_err() {
+            warn!("发送验证结果失败，接收方已关闭: event_id={}", pending.event_id);
+        }
+
+        true
+    }
+
+    /// 宽限期内迟到的结果: 将超时改判为成功/失败, 否则计为孤儿结果
+    fn complete_late(&mut self, result: VerifyResult, received_at: Instant) -> bool {
+        let expired = Uuid::parse_str(&result.event_id).ok().and_then(|event_id| {
+            match self.expired.get(&event_id) {
+                Some(expired) if received_at <= expired.expired_at + self.late_grace => {
+                    self.expired.remove(&event_id).map(|expired| (event_id, expired))
+                }
+                _ => None,
+            }
+        });
+
+        let Some((event_id, expired)) = expired else {
+            MatchCounters::add(&self.counters.orphaned, 1);
+            warn!("收到未知或已超时事件的验证结果: event_id={}", result.event_id);
+            return false;
+        };
+
+        let matched = MatchedResult {
+            server_latency_ms: received_at.duration_since(expired.created_at).as_millis() as u64,
+            agent_latency_ms: result.latency_ms,
+            result,
+        };
+
+        self.counters.timed_out.fetch_sub(1, Ordering::Relaxed);
+        MatchCounters::add(&self.counters.late, 1);
+        MatchCounters::add(&self.counters.total_server_latency_ms, matched.server_latency_ms);
+        MatchCounters::add(&self.counters.total_agent_latency_ms, matched.agent_latency_ms);
+
+        let outcome = if matched.result.verified {
+            MatchCounters::add(&self.counters.verified, 1);
+            VerifyOutcome::Verified(matched)
+        } else {
+            MatchCounters::add(&self.counters.mismatched, 1);
+            VerifyOutcome::Mismatched(matched)
+        };
+
+        info!(
+            "迟到的验证结果, 由超时改判为 {}: vm_id={}, event_id={}",
+            outcome.as_str(),
+            expired.vm_id,
+            event_id
+        );
+        self.record_reclassified(event_id, &expired, &outcome);
+        true
+    }
+
+    /// 截止时间已到时判定事件超时, 返回是否确实超时 (结果先到则不处理)
+    pub fn expire(&mut self, event_id: Uuid, now: Instant) -> bool {
+        match self.events.get(&event_id) {
+            Some(pending) if pending.deadline <= now => {}
+            _ => return false,
+        }
+
+        if let Some(pending) = self.events.remove(&event_id) {
+            self.time_out(pending, now);
+        }
+        true
+    }
+
+    /// 判定所有已过截止时间的事件超时, 返回数量
+    ///
+    /// 同时清理宽限期已过的超时事件。
+    pub fn expire_due(&mut self, now: Instant) -> usize {
+        let late_grace = self.late_grace;
+        self.expired.retain(|_, expired| now <= expired.expired_at + late_grace);
+
+        let expired: Vec<Uuid> = self
+            .events
+            .values()
+            .filter(|pending| pending.deadline <= now)
+            .map(|pending| pending.event_id)
+            .collect();
+
+        for event_id in &expired {
+            if let Some(pending) = self.events.remove(event_id) {
+                self.time_out(pending, now);
+            }
+        }
+
+        expired.len()
+    }
+
+    fn time_out(&mut self, pending: PendingEvent, now: Instant) {
+        let elapsed_ms = now.duration_since(pending.created_at).as_millis() as u64;
+        warn!(
+            "验证超时: vm_id={}, event_id={}, elapsed={}ms",
+            pending.vm_id, pending.event_id, elapsed_ms
+        );
+        MatchCounters::add(&self.counters.timed_out, 1);
+        let outcome = VerifyOutcome::TimedOut { elapsed_ms };
+        self.record(&pending, &outcome);
+        if !self.late_grace.is_zero() {
+            self.expired.insert(
+                pending.event_id,
+                ExpiredEvent {
+                    vm_id: pending.vm_id.clone(),
+                    event_type: pending.event.event_type.clone(),
+                    created_at: pending.created_at,
+                    expired_at: now,
+                },
+            );
+        }
+        let _ = pending.result_tx.send(outcome);
+    }
+
+    /// 后台写入验证结果, 写库失败只记录日志
+    fn record(&self, pending: &PendingEvent, outcome: &VerifyOutcome) {
+        let Some(recorder) = self.recorder.clone() else {
+            return;
+        };
+
+        let record = outcome_record(&pending.vm_id, pending.event_id, &pending.event.event_type, outcome);
+        tokio::spawn(async move {
+            if let Err(e) = recorder.create(&record).await {
+                warn!("写入验证结果失败: event_id={}, {}", record.event_id, e);
+            }
+        });
+    }
+
+    /// 后台改写已落库的超时记录
+    fn record_reclassified(&self, event_id: Uuid, expired: &ExpiredEvent, outcome: &VerifyOutcome) {
+        let Some(recorder) = self.recorder.clone() else {
+            return;
+        };
+
+        let record = outcome_record(&expired.vm_id, event_id, &expired.event_type, outcome);
+        tokio::spawn(async move {
+            if let Err(e) = recorder.reclassify(&record).await {
+                warn!("改写验证结果失败: event_id={}, {}", record.event_id, e);
+            }
+        });
+    }
+
+    /// 取消事件 (等待方收到通道关闭)
+    pub fn cancel(&mut self, event_id: Uuid) -> bool {
+        self.events.remove(&event_id).is_some()
+    }
+
+    /// 取消指定 VM 的所有事件, 返回数量
+    pub fn cancel_vm(&mut self, vm_id: &str) -> usize {
+        let before = self.events.len();
+        self.events.retain(|_, pending| pending.vm_id != vm_id);
+        before - self.events.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// 宽限期内等待迟到结果的超时事件数
+    pub fn expired_len(&self) -> usize {
+        self.expired.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn stats(&self) -> MatchStats {
+        self.counters.snapshot()
+    }
+
+    /// 共享的匹配计数器, 供不持有表锁的读取方使用
+    pub fn counters(&self) -> Arc<MatchCounters> {
+        self.counters.clone()
+    }
+}
+
+fn outcome_record(vm_id: &str, event_id: Uuid, event_type: &str, outcome: &VerifyOutcome) -> VerificationResultRecord {
+    let matched = outcome.matched();
+    VerificationResultRecord {
+        id: 0,
+        vm_id: vm_id.to_string(),
+        event_id: event_id.to_string(),
+        event_type: event_type.to_string(),
+        verified: outcome.is_verified(),
+        outcome: outcome.as_str().to_string(),
+        latency_ms: matched.map(|m| m.agent_latency_ms as i64),
+        server_latency_ms: matched.map(|m| m.server_latency_ms as i64),
+        details: matched.map(|m| m.result.details.to_string()),
+        created_at: chrono::Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Event;
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+
+    fn pending(sent_at: Instant, timeout: Duration) -> (PendingEvent, oneshot::Receiver<VerifyOutcome>) {
+        let (result_tx, result_rx) = oneshot::channel();
+        let pending = PendingEvent {
+            event_id: Uuid::new_v4(),
+            vm_id: "vm-1".to_string(),
+            event: Event {
+                event_type: "keyboard".to_string(),
+                data: serde_json::json!({}),
+                timestamp: 0,
+            },
+            result_tx,
+            created_at: sent_at,
+            deadline: sent_at + timeout,
+        };
+        (pending, result_rx)
+    }
+
+    fn result(event_id: &str, verified: bool, latency_ms: u64) -> VerifyResult {
+        VerifyResult {
+            event_id: event_id.to_string(),
+            verified,
+            timestamp: 0,
+            latency_ms,
+            details: serde_json::json!({}),
+        }
+    }
+
+    #[test]
+    fn test_complete_measures_both_latencies() {
+        let mut table = PendingEventTable::new(10);
+        let sent_at = Instant::now();
+
+        let (event, mut verified_rx) = pending(sent_at, Duration::from_secs(5));
+        let verified_id = event.event_id.to_string();
+        table.insert(event).unwrap();
+
+        let (event, mut mismatched_rx) = pending(sent_at, Duration::from_secs(5));
+        let mismatched_id = event.event_id.to_string();
+        table.insert(event).unwrap();
+
+        assert!(table.complete(result(&verified_id, true, 20), sent_at + Duration::from_millis(150)));
+        assert!(table.complete(result(&mismatched_id, false, 30), sent_at + Duration::from_millis(250)));
+
+        match verified_rx.try_recv().unwrap() {
+            VerifyOutcome::Verified(matched) => {
+                assert_eq!(matched.server_latency_ms, 150);
+                assert_eq!(matched.agent_latency_ms, 20);
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+        assert!(matches!(mismatched_rx.try_recv().unwrap(), VerifyOutcome::Mismatched(_)));
+
+        let stats = table.stats();
+        assert_eq!((stats.verified, stats.mismatched, stats.orphaned), (1, 1, 0));
+        assert_eq!(stats.avg_server_latency_ms(), 200.0);
+        assert_eq!(stats.avg_agent_latency_ms(), 25.0);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_expired_and_unknown_results_are_orphans() {
+        let mut table = PendingEventTable::new(10);
+        let sent_at = Instant::now();
+
+        let (event, mut result_rx) = pending(sent_at, Duration::from_millis(100));
+        let event_id = event.event_id;
+        table.insert(event).unwrap();
+
+        // 截止时间之前不判定超时
+        assert!(!table.expire(event_id, sent_at + Duration::from_millis(50)));
+        assert_eq!(table.expire_due(sent_at + Duration::from_millis(50)), 0);
+
+        assert_eq!(table.expire_due(sent_at + Duration::from_millis(120)), 1);
+        assert!(matches!(
+            result_rx.try_recv().unwrap(),
+            VerifyOutcome::TimedOut { elapsed_ms: 120 }
+        ));
+
+        // 超时后才到的结果与未知事件的结果都计为孤儿结果
+        assert!(!table.complete(result(&event_id.to_string(), true, 10), Instant::now()));
+        assert!(!table.complete(result("not-a-uuid", true, 10), Instant::now()));
+
+        let stats = table.stats();
+        assert_eq!((stats.timed_out, stats.orphaned, stats.matched()), (1, 2, 0));
+    }
+
+    #[test]
+    fn test_late_result_within_grace_is_reclassified() {
+        let mut table = PendingEventTable::new(10).with_late_grace(Duration::from_secs(10));
+        let sent_at = Instant::now();
+
+        let (event, mut result_rx) = pending(sent_at, Duration::from_millis(100));
+        let late_id = event.event_id;
+        table.insert(event).unwrap();
+        let (event, _rx) = pending(sent_at, Duration::from_millis(100));
+        let stale_id = event.event_id;
+        table.insert(event).unwrap();
+
+        let expired_at = sent_at + Duration::from_millis(100);
+        assert_eq!(table.expire_due(expired_at), 2);
+        assert!(matches!(result_rx.try_recv().unwrap(), VerifyOutcome::TimedOut { .. }));
+        assert_eq!(table.expired_len(), 2);
+
+        // 宽限期内补发的结果: 超时改判为成功
+        let mut replayed = result(&late_id.to_string(), true, 15);
+        replayed.details = serde_json::json!({"replayed": true});
+        assert!(table.complete(replayed, expired_at + Duration::from_secs(3)));
+
+        let stats = table.stats();
+        assert_eq!((stats.verified, stats.timed_out, stats.late, stats.orphaned), (1, 1, 1, 0));
+        assert_eq!(stats.avg_server_latency_ms(), 3100.0);
+
+        // 同一事件的重复结果与宽限期之后的结果计为孤儿结果
+        assert!(!table.complete(result(&late_id.to_string(), true, 15), expired_at + Duration::from_secs(4)));
+        assert_eq!(table.expire_due(expired_at + Duration::from_secs(11)), 0);
+        assert_eq!(table.expired_len(), 0);
+        assert!(!table.complete(result(&stale_id.to_string(), true, 15), expired_at + Duration::from_secs(11)));
+
+        let stats = table.stats();
+        assert_eq!((stats.verified, stats.timed_out, stats.late, stats.orphaned), (1, 1, 1, 2));
+    }
+}
@@ -3,15 +3,24 @@
 use futures_util::{SinkExt, StreamExt};
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
 use tokio_tungstenite::tungstenite::Message;
 use tracing::{debug, error, info, warn};
 
-use crate::client::ClientManager;
-use crate::types::{ClientInfo, Event, VerifyResult};
-use crate::Result;
+use crate::auth::AuthTokens;
+use crate::client::{ClientManager, ClientRegistration};
+use crate::framing::{encode_frame, FrameDecoder, FrameFormat, DEFAULT_MAX_FRAME_SIZE};
+use crate::tls::TlsConfig;
+use crate::types::{ClientConnection, ClientMessage, RegisterMessage, RejectMessage, MAX_VM_ID_LEN};
+use atp_common::{encode, AcceptedPayload, AgreedVersion, MessageType, SUPPORTED_PROTOCOL_VERSIONS};
+use crate::{Result, VerificationError};
+
+/// TCP 握手消息最大长度 (注册消息含版本与能力, 比纯 VM ID 长)
+const MAX_HANDSHAKE_LEN: usize = MAX_VM_ID_LEN * 16;
 
 /// 验证服务器配置
 #[derive(Debug, Clone)]
@@ -21,6 +30,18 @@ pub struct ServerConfig {
 
     /// TCP 服务器地址
     pub tcp_addr: Option<SocketAddr>,
+
+    /// TCP 最大帧长度
+    pub max_frame_size: usize,
+
+    /// TCP 兼容模式: 接收旧版 Agent 的无版本字节帧与换行分隔帧 (仅保留一个版本)
+    pub tcp_legacy_framing: bool,
+
+    /// TLS 配置 (None 表示明文)
+    pub tls: Option<TlsConfig>,
+
+    /// Agent 认证 token
+    pub auth: AuthTokens,
 }
 
 impl Default for ServerConfig {
@@ -28,6 +49,10 @@ impl Default for ServerConfig {
         Self {
             websocket_addr: Some("0.0.0.0:8765".parse().unwrap()),
             tcp_addr: Some("0.0.0.0:8766".parse().unwrap()),
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            tcp_legacy_framing: false,
+            tls: None,
+            auth: AuthTokens::disabled(),
         }
     }
 }
@@ -54,8 +79,9 @@ impl VerificationServer {
         // 启动 WebSocket 服务器
         if let Some(addr) = self.config.websocket_addr {
             let client_manager = self.client_manager.clone();
+            let config = self.config.clone();
             tasks.push(tokio::spawn(async move {
-                if let Err(e) = run_websocket_server(addr, client_manager).await {
+                if let Err(e) = run_websocket_server(addr, config, client_manager).await {
                     error!("WebSocket 服务器错误: {}", e);
                 }
             }));
@@ -64,8 +90,9 @@ impl VerificationServer {
         // 启动 TCP 服务器
         if let Some(addr) = self.config.tcp_addr {
             let client_manager = self.client_manager.clone();
+            let config = self.config.clone();
             tasks.push(tokio::spawn(async move {
-                if let Err(e) = run_tcp_server(addr, client_manager).await {
+                if let Err(e) = run_tcp_server(addr, config, client_manager).await {
                     error!("TCP 服务器错误: {}", e);
                 }
             }));
@@ -81,15 +108,32 @@ impl VerificationServer {
 }
 
 /// 运行 WebSocket 服务器
-async fn run_websocket_server(addr: SocketAddr, client_manager: Arc<ClientManager>) -> Result<()> {
+async fn run_websocket_server(
+    addr: SocketAddr,
+    config: ServerConfig,
+    client_manager: Arc<ClientManager>,
+) -> Result<()> {
     let listener = TcpListener::bind(addr).await?;
-    info!("WebSocket 服务器启动: {}", addr);
+    info!(
+        "WebSocket 服务器启动: {}{}",
+        addr,
+        if config.tls.is_some() { " (TLS)" } else { "" }
+    );
 
     while let Ok((stream, peer_addr)) = listener.accept().await {
         let client_manager = client_manager.clone();
+        let tls = config.tls.clone();
+        let auth = config.auth.clone();
 
         tokio::spawn(async move {
-            if let Err(e) = handle_websocket_client(stream, peer_addr, client_manager).await {
+            let handled = match tls {
+                Some(tls) => match accept_tls(&tls, stream, peer_addr).await {
+                    Ok(stream) => handle_websocket_client(stream, peer_addr, auth, client_manager).await,
+                    Err(e) => Err(e),
+                },
+                None => handle_websocket_client(stream, peer_addr, auth, client_manager).await,
+            };
+            if let Err(e) = handled {
                 error!("WebSocket 客户端处理错误 ({}): {}", peer_addr, e);
             }
         });
@@ -98,12 +142,65 @@ async fn run_websocket_server(addr: SocketAddr, client_manager: Arc<ClientManage
     Ok(())
 }
 
-/// 处理 WebSocket 客户端连接
-async fn handle_websocket_client(
+/// TLS 握手
+async fn accept_tls(
+    tls: &TlsConfig,
     stream: TcpStream,
     peer_addr: SocketAddr,
+) -> Result<tokio_rustls::server::TlsStream<TcpStream>> {
+    tls.acceptor().accept(stream).await.map_err(|e| {
+        warn!("TLS 握手失败 ({}): {}", peer_addr, e);
+        e.into()
+    })
+}
+
+/// 解析握手消息并校验认证 token
+fn authenticate(handshake: &str, auth: &AuthTokens) -> Result<RegisterMessage> {
+    let registration = RegisterMessage::parse_handshake(handshake)?;
+    auth.verify(registration.token.as_deref())?;
+    debug!(
+        "握手: vm_id={}, version={:?}",
+        registration.vm_id, registration.agent_version
+    );
+    Ok(registration)
+}
+
+/// 按注册消息协商协议版本, 协商出新版本时返回需要回复的 `accepted` 信封
+///
+/// 未声明版本的注册消息不改变已协商的版本。
+fn accept_version(agreed: &mut AgreedVersion, registration: &RegisterMessage) -> Result<Option<String>> {
+    let negotiated = registration.agreed_version();
+    if negotiated.is_legacy() || negotiated == *agreed {
+        return Ok(None);
+    }
+    *agreed = negotiated;
+    debug!("协商协议版本: vm_id={}, version={:?}", registration.vm_id, negotiated.version());
+
+    let payload = AcceptedPayload {
+        vm_id: registration.vm_id.clone(),
+        supported_versions: SUPPORTED_PROTOCOL_VERSIONS.to_vec(),
+    };
+    Ok(Some(encode(negotiated, MessageType::Accepted, &payload)?))
+}
+
+/// 等待服务器关闭通知
+async fn wait_shutdown(shutdown_rx: &mut watch::Receiver<bool>) {
+    if shutdown_rx.wait_for(|stop| *stop).await.is_err() {
+        // 通知通道随 ClientManager 释放, 此时不会再有关闭通知
+        std::future::pending::<()>().await;
+    }
+}
+
+/// 处理 WebSocket 客户端连接
+async fn handle_websocket_client<S>(
+    stream: S,
+    peer_addr: SocketAddr,
+    auth: AuthTokens,
     client_manager: Arc<ClientManager>,
-) -> Result<()> {
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
     debug!("WebSocket 客户端连接: {}", peer_addr);
 
     // WebSocket 握手
@@ -115,36 +212,78 @@ async fn handle_websocket_client(
 
     let (mut ws_sender, mut ws_receiver) = ws_stream.split();
 
-    // 等待客户端发送 VM ID (第一条消息)
-    let vm_id = match ws_receiver.next().await {
-        Some(Ok(Message::Text(text))) => {
-            debug!("收到 VM ID: {}", text);
-            text
-        }
+    // 等待客户端发送 VM ID 或注册消息 (第一条消息)
+    let handshake = match ws_receiver.next().await {
+        Some(Ok(Message::Text(text))) => text,
         _ => {
             warn!("客户端未发送 VM ID: {}", peer_addr);
             return Ok(());
         }
     };
 
-    // 注册客户端
-    let info = ClientInfo {
-        vm_id: vm_id.clone(),
-        connected_at: chrono::Utc::now(),
-        remote_addr: Some(peer_addr.to_string()),
+    // 校验并注册客户端, 失败时以关闭帧告知原因
+    let registered = match authenticate(&handshake, &auth) {
+        Ok(registration) => {
+            let connection = ClientConnection::WebSocket {
+                vm_id: registration.vm_id.clone(),
+                addr: peer_addr.to_string(),
+            };
+            client_manager
+                .register_client(connection, &registration)
+                .await
+                .map(|registered| (registration, registered))
+        }
+        Err(e) => Err(e),
     };
 
-    let mut event_rx = client_manager.register_client(info).await?;
+    let (registration, ClientRegistration { session_id, mut event_rx, activity }) = match registered {
+        Ok(registered) => registered,
+        Err(e) => {
+            warn!("拒绝 WebSocket 客户端 ({}): {}", peer_addr, e);
+            let _ = ws_sender.send(close_message(&e)).await;
+            return Ok(());
+        }
+    };
+    let vm_id = registration.vm_id.clone();
     let result_tx = client_manager.get_result_sender();
+    let mut shutdown_rx = client_manager.subscribe_shutdown();
 
     info!("WebSocket 客户端已注册: {} ({})", vm_id, peer_addr);
 
+    // 握手即注册消息时立即确认协议版本
+    let mut agreed = AgreedVersion::Legacy;
+    if let Some(json) = accept_version(&mut agreed, &registration)? {
+        ws_sender
+            .send(Message::Text(json))
+            .await
+            .map_err(|e| VerificationError::ServerError(format!("发送协议确认失败: {}", e)))?;
+    }
+
     // 双向消息转发
     loop {
         tokio::select! {
+            // 服务器关闭: 告知客户端后断开
+            _ = wait_shutdown(&mut shutdown_rx) => {
+                info!("服务器关闭, 断开客户端: {}", vm_id);
+                let _ = ws_sender
+                    .send(Message::Close(Some(CloseFrame {
+                        code: CloseCode::Away,
+                        reason: VerificationError::ShuttingDown.to_string().into(),
+                    })))
+                    .await;
+                break;
+            }
+
             // 从服务端接收事件，发送给客户端
-            Some(event) = event_rx.recv() => {
-                let json = serde_json::to_string(&event)?;
+            event = event_rx.recv() => {
+                let Some(event) = event else {
+                    // 事件通道关闭: 同一 VM ID 的新连接接管了本连接
+                    info!("客户端 {} 已被新连接接管: {}", vm_id, peer_addr);
+                    let reason = VerificationError::DuplicateClient(vm_id.clone(), "已被新连接接管".to_string());
+                    let _ = ws_sender.send(close_message(&reason)).await;
+                    break;
+                };
+                let json = encode(agreed, MessageType::Event, &event)?;
                 if let Err(e) = ws_sender.send(Message::Text(json)).await {
                     error!("发送事件到客户端失败: {}", e);
                     break;
@@ -153,17 +292,38 @@ async fn handle_websocket_client(
 
             // 从客户端接收结果
             Some(msg) = ws_receiver.next() => {
+                activity.touch();
                 match msg {
                     Ok(Message::Text(text)) => {
-                        match serde_json::from_str::<VerifyResult>(&text) {
-                            Ok(result) => {
+                        match ClientMessage::parse(&text) {
+                            Ok(ClientMessage::Result(result)) => {
                                 debug!("收到验证结果: event_id={}", result.event_id);
                                 if result_tx.send(result).is_err() {
                                     error!("转发验证结果失败");
                                 }
                             }
+                            Ok(ClientMessage::RawInput(event)) => {
+                                client_manager.publish_raw_input(&vm_id, event);
+                            }
+                            Ok(ClientMessage::Heartbeat(heartbeat)) => {
+                                debug!("收到心跳: vm_id={}, uptime_s={}", vm_id, heartbeat.uptime_s);
+                                activity.heartbeat(heartbeat.uptime_s);
+                            }
+                            Ok(ClientMessage::Register(registration)) => {
+                                if let Err(e) = client_manager.update_client(&vm_id, session_id, &registration).await {
+                                    warn!("拒绝 WebSocket 客户端 {} 的注册消息: {}", vm_id, e);
+                                    let _ = ws_sender.send(close_message(&e)).await;
+                                    break;
+                                }
+                                if let Some(json) = accept_version(&mut agreed, &registration)? {
+                                    if let Err(e) = ws_sender.send(Message::Text(json)).await {
+                                        error!("发送协议确认失败: {}", e);
+                                        break;
+                                    }
+                                }
+                            }
                             Err(e) => {
-                                warn!("解析验证结果失败: {}", e);
+                                warn!("解析客户端消息失败: {}", e);
                             }
                         }
                     }
@@ -172,7 +332,7 @@ async fn handle_websocket_client(
                         break;
                     }
                     Ok(Message::Ping(_)) | Ok(Message::Pong(_)) => {
-                        // 忽略心跳
+                        // Agent 的保活 Ping 由 tungstenite 在读取时自动回复 Pong
                     }
                     Err(e) => {
                         error!("接收消息错误: {}", e);
@@ -188,22 +348,56 @@ async fn handle_websocket_client(
         }
     }
 
-    client_manager.unregister_client(&vm_id).await;
+    client_manager.unregister_client(&vm_id, session_id).await;
     info!("WebSocket 客户端断开: {}", vm_id);
 
     Ok(())
 }
 
+/// 拒绝连接时发送的关闭帧
+fn close_message(reason: &VerificationError) -> Message {
+    Message::Close(Some(CloseFrame {
+        code: CloseCode::Policy,
+        reason: reason.to_string().into(),
+    }))
+}
+
 /// 运行 TCP 服务器
-async fn run_tcp_server(addr: SocketAddr, client_manager: Arc<ClientManager>) -> Result<()> {
+async fn run_tcp_server(
+    addr: SocketAddr,
+    config: ServerConfig,
+    client_manager: Arc<ClientManager>,
+) -> Result<()> {
     let listener = TcpListener::bind(addr).await?;
-    info!("TCP 服务器启动: {}", addr);
+    info!(
+        "TCP 服务器启动: {}{}",
+        addr,
+        if config.tls.is_some() { " (TLS)" } else { "" }
+    );
+    if config.tcp_legacy_framing {
+        warn!("TCP 兼容模式已开启, 将接收旧版 Agent 的分帧格式");
+    }
 
     while let Ok((stream, peer_addr)) = listener.accept().await {
         let client_manager = client_manager.clone();
+        let decoder = FrameDecoder::new()
+            .with_max_frame_size(MAX_HANDSHAKE_LEN)
+            .with_legacy_framing(config.tcp_legacy_framing);
+        let max_frame_size = config.max_frame_size;
+        let tls = config.tls.clone();
+        let auth = config.auth.clone();
 
         tokio::spawn(async move {
-            if let Err(e) = handle_tcp_client(stream, peer_addr, client_manager).await {
+            let handled = match tls {
+                Some(tls) => match accept_tls(&tls, stream, peer_addr).await {
+                    Ok(stream) => {
+                        handle_tcp_client(stream, peer_addr, decoder, max_frame_size, auth, client_manager).await
+                    }
+                    Err(e) => Err(e),
+                },
+                None => handle_tcp_client(stream, peer_addr, decoder, max_frame_size, auth, client_manager).await,
+            };
+            if let Err(e) = handled {
                 error!("TCP 客户端处理错误 ({}): {}", peer_addr, e);
             }
         });
@@ -213,68 +407,115 @@ async fn run_tcp_server(addr: SocketAddr, client_manager: Arc<ClientManager>) ->
 }
 
 /// 处理 TCP 客户端连接
-async fn handle_tcp_client(
-    stream: TcpStream,
+///
+/// 首帧 (握手) 决定连接的帧格式, 之后的回复使用相同格式。
+async fn handle_tcp_client<S>(
+    stream: S,
     peer_addr: SocketAddr,
+    decoder: FrameDecoder,
+    max_frame_size: usize,
+    auth: AuthTokens,
     client_manager: Arc<ClientManager>,
-) -> Result<()> {
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
     debug!("TCP 客户端连接: {}", peer_addr);
 
-    // 拆分读写（使用 into_split 获得所有权）
-    let (mut read_half, mut write_half) = stream.into_split();
-
-    // 读取 VM ID (长度前缀格式: 4 字节长度 + 字符串)
-    let vm_id_len = read_half.read_u32().await? as usize;
-    if vm_id_len > 256 {
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "VM ID 过长",
-        )
-        .into());
-    }
-
-    let mut vm_id_bytes = vec![0u8; vm_id_len];
-    read_half.read_exact(&mut vm_id_bytes).await?;
-    let vm_id = String::from_utf8(vm_id_bytes).map_err(|_| {
-        std::io::Error::new(std::io::ErrorKind::InvalidData, "VM ID 不是有效的 UTF-8")
-    })?;
+    // 拆分读写 (获得所有权, 分别交给发送与接收任务)
+    let (mut read_half, mut write_half) = tokio::io::split(stream);
 
-    debug!("收到 VM ID: {}", vm_id);
+    // 读取 VM ID 或注册消息
+    let mut decoder = decoder;
+    let handshake = decoder.read_frame(&mut read_half).await;
 
-    // 注册客户端
-    let info = ClientInfo {
-        vm_id: vm_id.clone(),
-        connected_at: chrono::Utc::now(),
-        remote_addr: Some(peer_addr.to_string()),
+    // 校验并注册客户端, 失败时发送拒绝消息后关闭
+    let registered = match handshake {
+        Ok(Some(handshake)) => authenticate(&handshake, &auth),
+        Ok(None) => return Ok(()),
+        Err(e) => Err(e),
+    };
+    let registered = match registered {
+        Ok(registration) => {
+            let connection = ClientConnection::Tcp {
+                vm_id: registration.vm_id.clone(),
+                addr: peer_addr.to_string(),
+            };
+            client_manager
+                .register_client(connection, &registration)
+                .await
+                .map(|registered| (registration, registered))
+        }
+        Err(e) => Err(e),
     };
 
-    let mut event_rx = client_manager.register_client(info).await?;
+    let (registration, ClientRegistration { session_id, mut event_rx, activity }) = match registered {
+        Ok(registered) => registered,
+        Err(e) => {
+            warn!("拒绝 TCP 客户端 ({}): {}", peer_addr, e);
+            // 未能识别帧格式时对端无法解析任何回复, 直接关闭
+            if let Some(format) = decoder.format() {
+                let json = serde_json::to_string(&RejectMessage::new(e.to_string()))?;
+                write_half.write_all(&encode_frame(format, &json)).await?;
+                write_half.flush().await?;
+            }
+            return Ok(());
+        }
+    };
+    let vm_id = registration.vm_id.clone();
+    let format = decoder.format().unwrap_or(FrameFormat::Versioned);
+    let mut decoder = decoder.with_max_frame_size(max_frame_size);
     let result_tx = client_manager.get_result_sender();
 
     info!("TCP 客户端已注册: {} ({})", vm_id, peer_addr);
 
+    // 握手即注册消息时立即确认协议版本; 之后补发的注册消息由接收任务协商, 交给发送任务回复
+    let mut agreed = AgreedVersion::Legacy;
+    if let Some(json) = accept_version(&mut agreed, &registration)? {
+        write_half.write_all(&encode_frame(format, &json)).await?;
+        write_half.flush().await?;
+    }
+    let (accepted_tx, mut accepted_rx) = mpsc::unbounded_channel::<(AgreedVersion, String)>();
+
     // 创建通道用于发送任务和接收任务通信
     let (shutdown_tx, mut shutdown_rx) = mpsc::channel::<()>(1);
 
     // 发送任务
-    let send_task = tokio::spawn(async move {
-        while let Some(event) = event_rx.recv().await {
-            let json = match serde_json::to_string(&event) {
-                Ok(j) => j,
-                Err(e) => {
-                    error!("序列化事件失败: {}", e);
-                    continue;
+    let mut server_shutdown_rx = client_manager.subscribe_shutdown();
+    let send_vm_id = vm_id.clone();
+    let mut send_task = tokio::spawn(async move {
+        let mut agreed = agreed;
+        loop {
+            let json = tokio::select! {
+                event = event_rx.recv() => {
+                    let Some(event) = event else { break };
+                    match encode(agreed, MessageType::Event, &event) {
+                        Ok(j) => j,
+                        Err(e) => {
+                            error!("序列化事件失败: {}", e);
+                            continue;
+                        }
+                    }
                 }
-            };
 
-            // 发送长度
-            let len = json.len() as u32;
-            if write_half.write_u32(len).await.is_err() {
-                break;
-            }
+                // 协商出新的协议版本: 先回复确认, 之后的消息使用信封
+                Some((version, json)) = accepted_rx.recv() => {
+                    agreed = version;
+                    json
+                }
+
+                // 服务器关闭: 发送拒绝消息告知客户端后断开
+                _ = wait_shutdown(&mut server_shutdown_rx) => {
+                    info!("服务器关闭, 断开客户端: {}", send_vm_id);
+                    let reject = RejectMessage::new(VerificationError::ShuttingDown.to_string());
+                    if let Ok(json) = encode(agreed, MessageType::Rejected, &reject) {
+                        let _ = write_half.write_all(&encode_frame(format, &json)).await;
+                    }
+                    break;
+                }
+            };
 
-            // 发送数据
-            if write_half.write_all(json.as_bytes()).await.is_err() {
+            if write_half.write_all(&encode_frame(format, &json)).await.is_err() {
                 break;
             }
 
@@ -282,42 +523,55 @@ async fn handle_tcp_client(
                 break;
             }
         }
+
+        let _ = write_half.shutdown().await;
     });
 
     // 接收任务
-    let recv_task = tokio::spawn(async move {
+    let recv_manager = client_manager.clone();
+    let recv_vm_id = vm_id.clone();
+    let mut recv_task = tokio::spawn(async move {
+        let mut agreed = agreed;
         loop {
-            // 读取长度
-            let len = match read_half.read_u32().await {
-                Ok(l) => l as usize,
-                Err(_) => break,
-            };
-
-            if len > 10 * 1024 * 1024 {
-                error!("消息过大: {} bytes", len);
-                break;
-            }
-
-            // 读取数据
-            let mut buffer = vec![0u8; len];
-            if read_half.read_exact(&mut buffer).await.is_err() {
-                break;
-            }
-
-            let json = match String::from_utf8(buffer) {
-                Ok(s) => s,
-                Err(_) => continue,
+            let json = match decoder.read_frame(&mut read_half).await {
+                Ok(Some(json)) => json,
+                Ok(None) => break,
+                Err(e) => {
+                    error!("读取 TCP 客户端 {} 的消息失败: {}", recv_vm_id, e);
+                    break;
+                }
             };
+            activity.touch();
 
             // 解析结果
-            match serde_json::from_str::<VerifyResult>(&json) {
-                Ok(result) => {
+            match ClientMessage::parse(&json) {
+                Ok(ClientMessage::Result(result)) => {
                     if result_tx.send(result).is_err() {
                         error!("转发验证结果失败");
                     }
                 }
+                Ok(ClientMessage::RawInput(event)) => {
+                    recv_manager.publish_raw_input(&recv_vm_id, event);
+                }
+                Ok(ClientMessage::Heartbeat(heartbeat)) => {
+                    debug!("收到心跳: vm_id={}, uptime_s={}", recv_vm_id, heartbeat.uptime_s);
+                    activity.heartbeat(heartbeat.uptime_s);
+                }
+                Ok(ClientMessage::Register(registration)) => {
+                    if let Err(e) = recv_manager.update_client(&recv_vm_id, session_id, &registration).await {
+                        warn!("拒绝 TCP 客户端 {} 的注册消息: {}", recv_vm_id, e);
+                        break;
+                    }
+                    match accept_version(&mut agreed, &registration) {
+                        Ok(Some(json)) => {
+                            let _ = accepted_tx.send((agreed, json));
+                        }
+                        Ok(None) => {}
+                        Err(e) => error!("编码协议确认失败: {}", e),
+                    }
+                }
                 Err(e) => {
-                    warn!("解析验证结果失败: {}", e);
+                    warn!("解析客户端消息失败: {}", e);
                 }
             }
         }
@@ -327,12 +581,16 @@ async fn handle_tcp_client(
 
     // 等待任一任务完成
     tokio::select! {
-        _ = send_task => {},
-        _ = recv_task => {},
+        _ = &mut send_task => {},
+        _ = &mut recv_task => {},
         _ = shutdown_rx.recv() => {},
     }
+    recv_task.abort();
+    if !send_task.is_finished() {
+        send_task.abort();
+    }
 
-    client_manager.unregister_client(&vm_id).await;
+    client_manager.unregister_client(&vm_id, session_id).await;
     info!("TCP 客户端断开: {}", vm_id);
 
     Ok(())
@@ -1,15 +1,21 @@
 //! 验证服务 - 事件跟踪和结果匹配
 
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::{mpsc, RwLock};
-use tokio::time::{timeout, Instant};
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use tokio::sync::{oneshot, RwLock};
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
+use async_trait::async_trait;
+use atp_storage::{MetricSample, MetricsSource, Storage};
+
+use crate::broadcast::{derive_event_id, BroadcastOutcome, BroadcastStatus, BroadcastVmResult};
 use crate::client::ClientManager;
-use crate::types::{Event, PendingEvent, VerifyResult};
+use crate::pending::{MatchCounters, MatchStats, PendingEventTable};
+use crate::types::{ClientInfo, Event, PendingEvent, VerifyOutcome, VerifyResult};
 use crate::{Result, VerificationError};
 
 /// 验证服务配置
@@ -23,6 +29,9 @@ pub struct ServiceConfig {
 
     /// 最大待验证事件数
     pub max_pending_events: usize,
+
+    /// 超时后仍接受迟到结果的宽限期 (如 Agent 重连后补发), 为 0 时超时即终局
+    pub late_result_grace: Duration,
 }
 
 impl Default for ServiceConfig {
@@ -31,6 +40,7 @@ impl Default for ServiceConfig {
             default_timeout: Duration::from_secs(30),
             cleanup_interval: Duration::from_secs(60),
             max_pending_events: 10000,
+            late_result_grace: Duration::from_secs(60),
         }
     }
 }
@@ -40,8 +50,11 @@ pub struct VerificationService {
     /// 客户端管理器（公开以便外部访问）
     pub client_manager: Arc<ClientManager>,
 
-    /// 待验证事件 (event_id -> PendingEvent)
-    pending_events: Arc<RwLock<HashMap<Uuid, PendingEvent>>>,
+    /// 待验证事件表
+    pending_events: Arc<RwLock<PendingEventTable>>,
+
+    /// 匹配计数 (与事件表共享, 读取时无需加锁)
+    counters: Arc<MatchCounters>,
 
     /// 配置
     config: ServiceConfig,
@@ -50,9 +63,24 @@ pub struct VerificationService {
 impl VerificationService {
     /// 创建新的验证服务
     pub fn new(client_manager: Arc<ClientManager>, config: ServiceConfig) -> Self {
+        let pending_events = PendingEventTable::new(config.max_pending_events)
+            .with_late_grace(config.late_result_grace);
+        Self::build(client_manager, config, pending_events)
+    }
+
+    /// 创建验证服务, 匹配或超时的事件写入 `verification_results` 表
+    pub fn new_with_storage(client_manager: Arc<ClientManager>, config: ServiceConfig, storage: &Storage) -> Self {
+        let pending_events = PendingEventTable::new(config.max_pending_events)
+            .with_late_grace(config.late_result_grace)
+            .with_recorder(storage.verifications().clone());
+        Self::build(client_manager, config, pending_events)
+    }
+
+    fn build(client_manager: Arc<ClientManager>, config: ServiceConfig, pending_events: PendingEventTable) -> Self {
         let service = Self {
             client_manager,
-            pending_events: Arc::new(RwLock::new(HashMap::new())),
+            counters: pending_events.counters(),
+            pending_events: Arc::new(RwLock::new(pending_events)),
             config,
         };
 
@@ -65,26 +93,32 @@ impl VerificationService {
         service
     }
 
-    /// 发送验证事件并等待结果
+    /// 发送验证事件, 返回接收验证结论的通道
+    ///
+    /// 收到 Agent 的结果 (按 event_id 匹配) 或到达截止时间时给出结论。
     ///
     /// # 参数
     /// - `vm_id`: 虚拟机 ID
     /// - `event`: 事件数据
     /// - `timeout_duration`: 超时时间（None 使用默认值）
-    ///
-    /// # 返回
-    /// - `Ok(VerifyResult)`: 验证成功，返回结果
-    /// - `Err(VerificationError::Timeout)`: 超时
-    /// - `Err(VerificationError::ClientNotConnected)`: 客户端未连接
-    pub async fn verify_event(
+    pub async fn send_event(
         &self,
         vm_id: &str,
-        mut event: Event,
+        event: Event,
         timeout_duration: Option<Duration>,
-    ) -> Result<VerifyResult> {
+    ) -> Result<oneshot::Receiver<VerifyOutcome>> {
         // 生成唯一事件 ID
-        let event_id = Uuid::new_v4();
+        self.send_event_with_id(vm_id, event, Uuid::new_v4(), timeout_duration).await
+    }
 
+    /// 以指定的 event_id 发送验证事件
+    pub(crate) async fn send_event_with_id(
+        &self,
+        vm_id: &str,
+        mut event: Event,
+        event_id: Uuid,
+        timeout_duration: Option<Duration>,
+    ) -> Result<oneshot::Receiver<VerifyOutcome>> {
         // 在事件数据中添加 event_id
         if let Some(data) = event.data.as_object_mut() {
             data.insert("event_id".to_string(), serde_json::json!(event_id.to_string()));
@@ -94,60 +128,147 @@ impl VerificationService {
                vm_id, event_id, event.event_type);
 
         // 创建结果通道
-        let (result_tx, result_rx) = tokio::sync::oneshot::channel();
+        let (result_tx, result_rx) = oneshot::channel();
 
         // 注册待验证事件
+        let created_at = Instant::now();
+        let deadline = created_at + timeout_duration.unwrap_or(self.config.default_timeout);
         let pending = PendingEvent {
             event_id,
             vm_id: vm_id.to_string(),
             event: event.clone(),
             result_tx,
-            created_at: Instant::now(),
+            created_at,
+            deadline,
         };
 
-        {
-            let mut events = self.pending_events.write().await;
-
-            // 检查是否超过最大数量
-            if events.len() >= self.config.max_pending_events {
-                return Err(VerificationError::ServerError(
-                    "待验证事件过多，请稍后重试".to_string(),
-                ));
-            }
-
-            events.insert(event_id, pending);
-        }
+        self.pending_events.write().await.insert(pending)?;
 
         // 发送事件到客户端
         if let Err(e) = self.client_manager.send_event(vm_id, event).await {
             // 发送失败，移除待验证事件
-            self.pending_events.write().await.remove(&event_id);
+            self.pending_events.write().await.cancel(event_id);
             return Err(e);
         }
+        self.counters.record_sent();
+
+        // 到达截止时间时判定超时 (结果先到则无操作)
+        let pending_events = self.pending_events.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep_until(deadline).await;
+            pending_events.write().await.expire(event_id, Instant::now());
+        });
+
+        Ok(result_rx)
+    }
 
-        // 等待结果（带超时）
-        let timeout_duration = timeout_duration.unwrap_or(self.config.default_timeout);
+    /// 发送验证事件并等待结果
+    ///
+    /// # 参数
+    /// - `vm_id`: 虚拟机 ID
+    /// - `event`: 事件数据
+    /// - `timeout_duration`: 超时时间（None 使用默认值）
+    ///
+    /// # 返回
+    /// - `Ok(VerifyResult)`: 收到结果 (包括 `verified = false`)
+    /// - `Err(VerificationError::Timeout)`: 超时
+    /// - `Err(VerificationError::ClientNotConnected)`: 客户端未连接
+    pub async fn verify_event(
+        &self,
+        vm_id: &str,
+        event: Event,
+        timeout_duration: Option<Duration>,
+    ) -> Result<VerifyResult> {
+        let outcome_rx = self.send_event(vm_id, event, timeout_duration).await?;
 
-        match timeout(timeout_duration, result_rx).await {
-            Ok(Ok(result)) => {
+        match outcome_rx.await {
+            Ok(VerifyOutcome::Verified(matched)) | Ok(VerifyOutcome::Mismatched(matched)) => {
                 debug!("收到验证结果: event_id={}, verified={}",
-                       event_id, result.verified);
-                Ok(result)
+                       matched.result.event_id, matched.result.verified);
+                Ok(matched.result)
             }
-            Ok(Err(_)) => {
-                // 通道关闭（不应该发生）
-                error!("验证结果通道意外关闭: event_id={}", event_id);
+            Ok(VerifyOutcome::TimedOut { .. }) => Err(VerificationError::Timeout),
+            Err(_) => {
+                // 事件被取消
+                error!("验证结果通道意外关闭: vm_id={}", vm_id);
                 Err(VerificationError::ServerError(
                     "结果通道关闭".to_string(),
                 ))
             }
-            Err(_) => {
-                // 超时
-                warn!("验证超时: vm_id={}, event_id={}", vm_id, event_id);
-                self.pending_events.write().await.remove(&event_id);
-                Err(VerificationError::Timeout)
+        }
+    }
+
+    /// 向多台虚拟机广播同一事件, 等待全部给出结论后汇总
+    ///
+    /// 各虚拟机的 event_id 由广播 ID 派生, 事件数据中同时带上 `broadcast_id`。
+    /// 未连接的 Agent 记为跳过, 不等待; 结果按到达顺序收集, 整体耗时不超过超时时间。
+    pub async fn broadcast_event(
+        &self,
+        vm_ids: &[String],
+        event: Event,
+        timeout_duration: Option<Duration>,
+    ) -> BroadcastOutcome {
+        let broadcast_id = Uuid::new_v4();
+        let started = Instant::now();
+        info!("广播验证事件: broadcast_id={}, type={}, 虚拟机 {} 台",
+              broadcast_id, event.event_type, vm_ids.len());
+
+        let mut results: Vec<Option<BroadcastVmResult>> = vec![None; vm_ids.len()];
+        let mut waiting = FuturesUnordered::new();
+        let mut seen = HashSet::new();
+
+        for (index, vm_id) in vm_ids.iter().enumerate() {
+            // 同一广播中重复的 VM 会派生出相同的 event_id
+            if !seen.insert(vm_id.as_str()) {
+                results[index] = Some(BroadcastVmResult::not_sent(
+                    vm_id, BroadcastStatus::Failed, "重复的 VM ID".to_string(),
+                ));
+                continue;
             }
+
+            let event_id = derive_event_id(broadcast_id, vm_id);
+            let mut event = event.clone();
+            if let Some(data) = event.data.as_object_mut() {
+                data.insert("broadcast_id".to_string(), serde_json::json!(broadcast_id.to_string()));
+            }
+
+            match self.send_event_with_id(vm_id, event, event_id, timeout_duration).await {
+                Ok(outcome_rx) => waiting.push(async move { (index, event_id, outcome_rx.await) }),
+                Err(VerificationError::ClientNotConnected(_)) => {
+                    results[index] = Some(BroadcastVmResult::not_sent(
+                        vm_id, BroadcastStatus::Skipped, "客户端未连接".to_string(),
+                    ));
+                }
+                Err(e) => {
+                    results[index] = Some(BroadcastVmResult::not_sent(vm_id, BroadcastStatus::Failed, e.to_string()));
+                }
+            }
+        }
+
+        while let Some((index, event_id, outcome)) = waiting.next().await {
+            let vm_id = &vm_ids[index];
+            let result = match outcome {
+                Ok(outcome) => {
+                    debug!("广播结果: vm_id={}, outcome={}", vm_id, outcome.as_str());
+                    BroadcastVmResult::from_outcome(vm_id, event_id, &outcome)
+                }
+                Err(_) => BroadcastVmResult {
+                    event_id: Some(event_id.to_string()),
+                    ..BroadcastVmResult::not_sent(vm_id, BroadcastStatus::Failed, "结果通道关闭".to_string())
+                },
+            };
+            results[index] = Some(result);
         }
+
+        let outcome = BroadcastOutcome::new(
+            broadcast_id,
+            &event.event_type,
+            results.into_iter().flatten().collect(),
+            started.elapsed().as_millis() as u64,
+        );
+        info!("广播完成: broadcast_id={}, 成功 {}, 失败 {}, 超时 {}, 跳过 {}",
+              broadcast_id, outcome.verified, outcome.mismatched, outcome.timed_out, outcome.skipped);
+        outcome
     }
 
     /// 启动结果处理任务
@@ -167,40 +288,21 @@ impl VerificationService {
 
             info!("启动验证结果处理任务");
 
-            // 处理接收到的结果
+            // 按 event_id 匹配待验证事件
             while let Some(result) = result_rx.recv().await {
                 debug!("处理验证结果: event_id={}", result.event_id);
-
-                // 解析 event_id
-                let event_id = match Uuid::parse_str(&result.event_id) {
-                    Ok(id) => id,
-                    Err(e) => {
-                        error!("无效的 event_id: {}, 错误: {}", result.event_id, e);
-                        continue;
-                    }
-                };
-
-                // 查找并移除待验证事件
-                let mut events = pending_events.write().await;
-                if let Some(pending) = events.remove(&event_id) {
-                    // 发送结果
-                    if pending.result_tx.send(result).is_err() {
-                        warn!("发送验证结果失败，接收方已关闭: event_id={}", event_id);
-                    }
-                } else {
-                    warn!("收到未知事件的验证结果: event_id={}", event_id);
-                }
+                let received_at = Instant::now();
+                pending_events.write().await.complete(result, received_at);
             }
 
             info!("验证结果处理任务已停止");
         });
     }
 
-    /// 启动清理任务（移除超时的待验证事件）
+    /// 启动清理任务（兜底判定已过截止时间的事件超时）
     fn spawn_cleanup_task(&self) {
         let pending_events = self.pending_events.clone();
         let cleanup_interval = self.config.cleanup_interval;
-        let default_timeout = self.config.default_timeout;
 
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(cleanup_interval);
@@ -209,28 +311,9 @@ impl VerificationService {
                 interval.tick().await;
 
                 let mut events = pending_events.write().await;
-                let now = Instant::now();
-
-                // 找出所有超时的事件
-                let expired: Vec<Uuid> = events
-                    .iter()
-                    .filter(|(_, pending)| {
-                        now.duration_since(pending.created_at) > default_timeout
-                    })
-                    .map(|(id, _)| *id)
-                    .collect();
-
-                // 移除超时事件
-                for event_id in expired {
-                    if let Some(pending) = events.remove(&event_id) {
-                        warn!(
-                            "清理超时事件: vm_id={}, event_id={}, age={}s",
-                            pending.vm_id,
-                            event_id,
-                            now.duration_since(pending.created_at).as_secs()
-                        );
-                        // 通道会自动关闭，等待方会收到错误
-                    }
+                let expired = events.expire_due(Instant::now());
+                if expired > 0 {
+                    warn!("清理超时事件: {} 个", expired);
                 }
 
                 if !events.is_empty() {
@@ -245,24 +328,24 @@ impl VerificationService {
         self.pending_events.read().await.len()
     }
 
+    /// 匹配统计 (已发送/成功/失败/超时/孤儿结果数与延迟)
+    pub async fn match_stats(&self) -> MatchStats {
+        self.counters.snapshot()
+    }
+
+    /// 当前已注册的客户端 (按 VM ID 排序), 供运维查看
+    pub async fn list_clients(&self) -> Vec<ClientInfo> {
+        self.client_manager.get_clients().await
+    }
+
     /// 取消待验证事件
     pub async fn cancel_event(&self, event_id: Uuid) -> bool {
-        self.pending_events.write().await.remove(&event_id).is_some()
+        self.pending_events.write().await.cancel(event_id)
     }
 
     /// 取消指定 VM 的所有待验证事件
     pub async fn cancel_vm_events(&self, vm_id: &str) -> usize {
-        let mut events = self.pending_events.write().await;
-        let to_remove: Vec<Uuid> = events
-            .iter()
-            .filter(|(_, pending)| pending.vm_id == vm_id)
-            .map(|(id, _)| *id)
-            .collect();
-
-        let count = to_remove.len();
-        for event_id in to_remove {
-            events.remove(&event_id);
-        }
+        let count = self.pending_events.write().await.cancel_vm(vm_id);
 
         if count > 0 {
             info!("取消 VM {} 的 {} 个待验证事件", vm_id, count);
@@ -272,10 +355,35 @@ impl VerificationService {
     }
 }
 
+/// 导出验证服务指标, 供 `MetricsCollector` 定期写库
+#[async_trait]
+impl MetricsSource for VerificationService {
+    fn source_name(&self) -> &str {
+        "verification_server"
+    }
+
+    async fn collect(&self) -> Vec<MetricSample> {
+        let stats = self.match_stats().await;
+
+        vec![
+            MetricSample::new("pending_events", self.pending_count().await as f64),
+            MetricSample::new("connected_clients", self.client_manager.get_clients().await.len() as f64),
+            MetricSample::new("sent_total", stats.sent as f64),
+            MetricSample::new("verified_total", stats.matched() as f64),
+            MetricSample::new("mismatched_total", stats.mismatched as f64),
+            MetricSample::new("timeout_total", stats.timed_out as f64),
+            MetricSample::new("orphan_results_total", stats.orphaned as f64),
+            MetricSample::new("late_results_total", stats.late as f64),
+            MetricSample::new("avg_latency_ms", stats.avg_agent_latency_ms()),
+            MetricSample::new("avg_server_latency_ms", stats.avg_server_latency_ms()),
+        ]
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::ClientInfo;
+    use crate::types::{ClientConnection, RegisterMessage};
 
     #[tokio::test]
     async fn test_verification_service() {
@@ -284,18 +392,20 @@ mod tests {
             default_timeout: Duration::from_millis(100),
             cleanup_interval: Duration::from_secs(1),
             max_pending_events: 100,
+            late_result_grace: Duration::ZERO,
         };
 
         let service = VerificationService::new(client_manager.clone(), config);
 
         // 注册客户端
-        let info = ClientInfo {
+        let connection = ClientConnection::WebSocket {
             vm_id: "vm-test".to_string(),
-            connected_at: chrono::Utc::now(),
-            remote_addr: None,
+            addr: "127.0.0.1:5000".to_string(),
         };
-
-        let _event_rx = client_manager.register_client(info).await.unwrap();
+        let _registered = client_manager
+            .register_client(connection, &RegisterMessage::new("vm-test"))
+            .await
+            .unwrap();
 
         // 测试超时
         let event = Event {
@@ -307,6 +417,243 @@ mod tests {
         let result = service.verify_event("vm-test", event, None).await;
         assert!(result.is_err());
         assert!(matches!(result.unwrap_err(), VerificationError::Timeout));
+
+        // 超时计入指标
+        let samples = service.collect().await;
+        let value = |name: &str| samples.iter().find(|s| s.name == name).unwrap().value;
+        assert_eq!(value("timeout_total"), 1.0);
+        assert_eq!(value("connected_clients"), 1.0);
+        assert_eq!(value("pending_events"), 0.0);
+
+        let clients = service.list_clients().await;
+        assert_eq!(clients.len(), 1);
+        assert_eq!(clients[0].vm_id, "vm-test");
+    }
+
+    /// 模拟 Agent: 收到事件后按 event_id 回复结果
+    fn spawn_agent(
+        client_manager: Arc<ClientManager>,
+        mut event_rx: tokio::sync::mpsc::UnboundedReceiver<Event>,
+        verified: bool,
+    ) {
+        tokio::spawn(async move {
+            let result_tx = client_manager.get_result_sender();
+            while let Some(event) = event_rx.recv().await {
+                tokio::time::sleep(Duration::from_millis(30)).await;
+                let _ = result_tx.send(VerifyResult {
+                    event_id: event.data["event_id"].as_str().unwrap().to_string(),
+                    verified,
+                    timestamp: 0,
+                    latency_ms: 5,
+                    details: serde_json::json!({}),
+                });
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_send_event_outcomes() {
+        let client_manager = Arc::new(ClientManager::new());
+        let service = VerificationService::new(client_manager.clone(), ServiceConfig::default());
+
+        for (vm_id, verified) in [("vm-ok", true), ("vm-bad", false)] {
+            let connection = ClientConnection::Tcp {
+                vm_id: vm_id.to_string(),
+                addr: "127.0.0.1:5000".to_string(),
+            };
+            let registered = client_manager
+                .register_client(connection, &RegisterMessage::new(vm_id))
+                .await
+                .unwrap();
+            spawn_agent(client_manager.clone(), registered.event_rx, verified);
+        }
+
+        let event = Event {
+            event_type: "keyboard".to_string(),
+            data: serde_json::json!({"key": "a"}),
+            timestamp: 0,
+        };
+
+        let outcome = service.send_event("vm-ok", event.clone(), None).await.unwrap().await.unwrap();
+        match outcome {
+            VerifyOutcome::Verified(matched) => {
+                // 服务端延迟独立于 Agent 上报的延迟
+                assert!(matched.server_latency_ms >= 30);
+                assert_eq!(matched.agent_latency_ms, 5);
+            }
+            other => panic!("unexpected outcome: {:?}", other),
+        }
+
+        let outcome = service.send_event("vm-bad", event, None).await.unwrap().await.unwrap();
+        assert!(matches!(outcome, VerifyOutcome::Mismatched(_)));
+
+        // 未知事件的结果计为孤儿结果
+        client_manager
+            .get_result_sender()
+            .send(VerifyResult {
+                event_id: Uuid::new_v4().to_string(),
+                verified: true,
+                timestamp: 0,
+                latency_ms: 0,
+                details: serde_json::json!({}),
+            })
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let stats = service.match_stats().await;
+        assert_eq!((stats.verified, stats.mismatched, stats.orphaned), (1, 1, 1));
+        assert_eq!(stats.sent, 2);
+        assert_eq!(service.pending_count().await, 0);
+    }
+
+    #[tokio::test]
+    async fn test_outcomes_persisted_to_storage() {
+        let manager = atp_storage::StorageManager::new_in_memory().await.unwrap();
+        let storage = Storage::from_manager(&manager);
+        let client_manager = Arc::new(ClientManager::new());
+        let config = ServiceConfig {
+            default_timeout: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let service = VerificationService::new_with_storage(client_manager.clone(), config, &storage);
+
+        // 不回复的 Agent 保持连接, 让事件走到超时
+        let mut silent_agents = Vec::new();
+        for (vm_id, reply) in [("vm-ok", true), ("vm-silent", false)] {
+            let connection = ClientConnection::Tcp {
+                vm_id: vm_id.to_string(),
+                addr: "127.0.0.1:5000".to_string(),
+            };
+            let registered = client_manager
+                .register_client(connection, &RegisterMessage::new(vm_id))
+                .await
+                .unwrap();
+            if reply {
+                spawn_agent(client_manager.clone(), registered.event_rx, true);
+            } else {
+                silent_agents.push(registered.event_rx);
+            }
+        }
+
+        let event = Event {
+            event_type: "keyboard".to_string(),
+            data: serde_json::json!({"key": "a"}),
+            timestamp: 0,
+        };
+        assert!(service.verify_event("vm-ok", event.clone(), None).await.unwrap().verified);
+        assert!(matches!(
+            service.verify_event("vm-silent", event, None).await,
+            Err(VerificationError::Timeout)
+        ));
+
+        // 写库在后台任务中完成
+        let repo = storage.verifications();
+        for _ in 0..100 {
+            if repo.count().await.unwrap() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let ok = repo.list_by_vm("vm-ok").await.unwrap();
+        assert_eq!(ok.len(), 1);
+        assert_eq!((ok[0].outcome.as_str(), ok[0].event_type.as_str()), ("verified", "keyboard"));
+        assert_eq!(ok[0].latency_ms, Some(5));
+
+        let silent = repo.list_by_vm("vm-silent").await.unwrap();
+        assert_eq!(silent.len(), 1);
+        assert_eq!(silent[0].outcome, "timeout");
+        assert!(!silent[0].verified && silent[0].server_latency_ms.is_none());
+
+        // Agent 重连后补发的结果在宽限期内到达: 超时记录改判为成功
+        let event = silent_agents[0].recv().await.unwrap();
+        client_manager
+            .get_result_sender()
+            .send(VerifyResult {
+                event_id: event.data["event_id"].as_str().unwrap().to_string(),
+                verified: true,
+                timestamp: 0,
+                latency_ms: 7,
+                details: serde_json::json!({"replayed": true}),
+            })
+            .unwrap();
+        for _ in 0..100 {
+            if repo.list_by_vm("vm-silent").await.unwrap()[0].verified {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let silent = repo.list_by_vm("vm-silent").await.unwrap();
+        assert_eq!(silent.len(), 1);
+        assert_eq!((silent[0].outcome.as_str(), silent[0].latency_ms), ("verified", Some(7)));
+        let stats = service.match_stats().await;
+        assert_eq!((stats.verified, stats.timed_out, stats.late), (2, 0, 1));
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_event() {
+        let client_manager = Arc::new(ClientManager::new());
+        let config = ServiceConfig {
+            default_timeout: Duration::from_millis(200),
+            ..Default::default()
+        };
+        let service = VerificationService::new(client_manager.clone(), config);
+
+        let mut silent_agents = Vec::new();
+        for (vm_id, reply) in [("vm-1", Some(true)), ("vm-2", Some(false)), ("vm-3", None)] {
+            let connection = ClientConnection::Tcp {
+                vm_id: vm_id.to_string(),
+                addr: "127.0.0.1:5000".to_string(),
+            };
+            let registered = client_manager
+                .register_client(connection, &RegisterMessage::new(vm_id))
+                .await
+                .unwrap();
+            match reply {
+                Some(verified) => spawn_agent(client_manager.clone(), registered.event_rx, verified),
+                None => silent_agents.push(registered.event_rx),
+            }
+        }
+
+        let event = Event {
+            event_type: "keyboard".to_string(),
+            data: serde_json::json!({"key": "ret"}),
+            timestamp: 0,
+        };
+        let vm_ids: Vec<String> = ["vm-1", "vm-2", "vm-3", "vm-offline", "vm-1"]
+            .iter()
+            .map(|id| id.to_string())
+            .collect();
+        let outcome = service.broadcast_event(&vm_ids, event, None).await;
+
+        let statuses: Vec<BroadcastStatus> = outcome.results.iter().map(|r| r.status).collect();
+        assert_eq!(
+            statuses,
+            vec![
+                BroadcastStatus::Verified,
+                BroadcastStatus::Mismatched,
+                BroadcastStatus::Timeout,
+                BroadcastStatus::Skipped,
+                BroadcastStatus::Failed,
+            ]
+        );
+        assert_eq!(outcome.latency.unwrap().count, 2);
+        assert!(outcome.latency.unwrap().min_ms >= 30);
+
+        // event_id 由广播 ID 派生, 事件中带有广播 ID
+        let broadcast_id = Uuid::parse_str(&outcome.broadcast_id).unwrap();
+        assert_eq!(
+            outcome.results[0].event_id.as_deref(),
+            Some(derive_event_id(broadcast_id, "vm-1").to_string().as_str())
+        );
+        let sent = silent_agents[0].recv().await.unwrap();
+        assert_eq!(sent.data["broadcast_id"], outcome.broadcast_id.as_str());
+        assert_eq!(sent.data["event_id"], derive_event_id(broadcast_id, "vm-3").to_string().as_str());
+
+        // 未连接与重复的 VM 不发送事件
+        assert_eq!(service.match_stats().await.sent, 3);
+        assert_eq!(service.pending_count().await, 0);
     }
 
     #[tokio::test]
@@ -0,0 +1,171 @@
+//! TLS 配置
+//!
+//! 开启后 WebSocket 与 TCP 监听都要求 TLS, 证书与私钥为 PEM 文件。
+//! 配置客户端 CA 后要求 Agent 出示由该 CA 签发的客户端证书。
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig as RustlsServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+use crate::{Result, VerificationError};
+
+/// 服务端 TLS 配置
+#[derive(Clone)]
+pub struct TlsConfig {
+    acceptor: TlsAcceptor,
+}
+
+impl TlsConfig {
+    /// 从 PEM 格式的证书链与私钥文件加载
+    pub fn from_pem_files(cert_path: impl AsRef<Path>, key_path: impl AsRef<Path>) -> Result<Self> {
+        Self::load(cert_path.as_ref(), key_path.as_ref(), None)
+    }
+
+    /// 加载证书与私钥, `client_ca` 不为空时校验客户端证书
+    pub fn load(cert_path: &Path, key_path: &Path, client_ca: Option<&Path>) -> Result<Self> {
+        let certs = load_certs(cert_path)?;
+
+        let key = rustls_pemfile::private_key(&mut BufReader::new(open(key_path)?))
+            .map_err(|e| tls_error(key_path, e))?
+            .ok_or_else(|| tls_error(key_path, "文件中没有私钥"))?;
+
+        let builder = match client_ca {
+            Some(ca_path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(ca_path)? {
+                    roots.add(cert).map_err(|e| tls_error(ca_path, e))?;
+                }
+                let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+                    .build()
+                    .map_err(|e| tls_error(ca_path, e))?;
+                RustlsServerConfig::builder().with_client_cert_verifier(verifier)
+            }
+            None => RustlsServerConfig::builder().with_no_client_auth(),
+        };
+
+        let config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| VerificationError::ConfigError(format!("TLS 证书与私钥不匹配: {}", e)))?;
+
+        Ok(Self {
+            acceptor: TlsAcceptor::from(Arc::new(config)),
+        })
+    }
+
+    pub fn acceptor(&self) -> &TlsAcceptor {
+        &self.acceptor
+    }
+}
+
+impl fmt::Debug for TlsConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TlsConfig").finish_non_exhaustive()
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(open(path)?))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| tls_error(path, e))?;
+    if certs.is_empty() {
+        return Err(tls_error(path, "文件中没有证书"));
+    }
+    Ok(certs)
+}
+
+fn open(path: &Path) -> Result<File> {
+    File::open(path).map_err(|e| tls_error(path, e))
+}
+
+fn tls_error(path: &Path, e: impl fmt::Display) -> VerificationError {
+    VerificationError::ConfigError(format!("读取 TLS 文件 {} 失败: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::ClientConfig;
+    use tokio_rustls::TlsConnector;
+
+    /// 生成自签证书并写入目录, 返回 (证书路径, 私钥路径)
+    fn self_signed(dir: &Path, name: &str) -> (PathBuf, PathBuf) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_path = dir.join(format!("{}.crt", name));
+        let key_path = dir.join(format!("{}.key", name));
+        std::fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    fn connector(server_cert: &Path, client: Option<(&Path, &Path)>) -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        roots.add_parsable_certificates(load_certs(server_cert).unwrap());
+        let builder = ClientConfig::builder().with_root_certificates(roots);
+        let config = match client {
+            Some((cert, key)) => {
+                let key = rustls_pemfile::private_key(&mut BufReader::new(File::open(key).unwrap()))
+                    .unwrap()
+                    .unwrap();
+                builder.with_client_auth_cert(load_certs(cert).unwrap(), key).unwrap()
+            }
+            None => builder.with_no_client_auth(),
+        };
+        TlsConnector::from(Arc::new(config))
+    }
+
+    /// 建立一次连接并收发数据, 返回服务端握手是否成功
+    async fn handshake(tls: &TlsConfig, connector: TlsConnector) -> bool {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let acceptor = tls.acceptor().clone();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            match acceptor.accept(stream).await {
+                Ok(mut stream) => {
+                    let mut buf = [0u8; 2];
+                    stream.read_exact(&mut buf).await.is_ok()
+                }
+                Err(_) => false,
+            }
+        });
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let name = ServerName::try_from("localhost").unwrap();
+        if let Ok(mut stream) = connector.connect(name, stream).await {
+            let _ = stream.write_all(b"ok").await;
+            let _ = stream.flush().await;
+        }
+        server.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_client_certificate_verification() {
+        let dir = std::env::temp_dir().join(format!("vs-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (server_cert, server_key) = self_signed(&dir, "server");
+        let (client_cert, client_key) = self_signed(&dir, "client");
+
+        // 未配置客户端 CA 时不要求客户端证书
+        let tls = TlsConfig::from_pem_files(&server_cert, &server_key).unwrap();
+        assert!(handshake(&tls, connector(&server_cert, None)).await);
+
+        let tls = TlsConfig::load(&server_cert, &server_key, Some(&client_cert)).unwrap();
+        assert!(!handshake(&tls, connector(&server_cert, None)).await);
+        assert!(
+            handshake(&tls, connector(&server_cert, Some((&client_cert, &client_key)))).await
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
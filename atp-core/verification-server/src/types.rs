@@ -1,8 +1,11 @@
 //! 数据类型定义
 
+use atp_common::{decode, negotiate, AgreedVersion, MessageType, SUPPORTED_PROTOCOL_VERSIONS};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::{Result, VerificationError};
+
 /// 验证事件
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
@@ -36,7 +39,7 @@ pub struct VerifyResult {
 }
 
 /// 客户端连接信息
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ClientInfo {
     /// VM ID
     pub vm_id: String,
@@ -44,8 +47,299 @@ pub struct ClientInfo {
     /// 连接时间
     pub connected_at: chrono::DateTime<chrono::Utc>,
 
+    /// 最近一次收到客户端消息的时间
+    pub last_activity: chrono::DateTime<chrono::Utc>,
+
+    /// 传输类型 (`websocket` / `tcp`)
+    pub transport: String,
+
     /// 客户端地址
     pub remote_addr: Option<String>,
+
+    /// Agent 版本 (旧版 Agent 不上报)
+    pub agent_version: Option<String>,
+
+    /// Agent 支持的验证能力 (如 keyboard, mouse, command)
+    pub capabilities: Vec<String>,
+
+    /// 最近一次收到心跳的时间 (未开启心跳的 Agent 为空)
+    pub last_heartbeat: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// 最近一次心跳上报的 Agent 运行时长 (秒)
+    pub agent_uptime_s: Option<u64>,
+}
+
+/// 注册消息的 `message_type`
+pub const REGISTER_MESSAGE_TYPE: &str = "register";
+
+/// 拒绝消息的 `message_type`
+pub const REJECTED_MESSAGE_TYPE: &str = "rejected";
+
+/// 输入上报消息的 `message_type`
+pub const RAW_INPUT_MESSAGE_TYPE: &str = "raw_input";
+
+/// 注册消息中声明支持输入上报的能力标记
+///
+/// 只上报输入、不做验证的 Agent 只声明这一项 (`"capabilities": ["raw_input"]`),
+/// 与验证 Agent 共用同一套注册表与输入上报通道。旧版 Agent 不声明该能力, 但仍可以上报输入。
+pub const RAW_INPUT_CAPABILITY: &str = "raw_input";
+
+/// 心跳消息的 `message_type`
+pub const HEARTBEAT_MESSAGE_TYPE: &str = "heartbeat";
+
+/// 切换 Agent 工作模式的控制事件的 `event_type`
+pub const SET_MODE_EVENT_TYPE: &str = "set_mode";
+
+/// VM ID 最大长度
+pub const MAX_VM_ID_LEN: usize = 256;
+
+impl ClientInfo {
+    /// 是否声明了输入上报能力
+    pub fn supports_raw_input(&self) -> bool {
+        self.capabilities.iter().any(|c| c == RAW_INPUT_CAPABILITY)
+    }
+}
+
+/// 客户端注册消息 (连接后的第一条消息)
+///
+/// 旧版 Agent 只发送纯文本 VM ID, 等价于不带版本与能力的注册消息。
+/// 新版 Agent 在纯文本 VM ID 之后再补发一条注册消息, 旧版服务端会将其忽略。
+/// 注册消息始终不包装为信封; 声明了协议版本时服务端回复 `accepted` 信封, 此后双方使用信封。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisterMessage {
+    /// 固定为 `register`
+    pub message_type: String,
+
+    /// VM ID
+    pub vm_id: String,
+
+    /// Agent 版本
+    #[serde(default)]
+    pub agent_version: Option<String>,
+
+    /// Agent 支持的验证能力
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+
+    /// 认证 token (服务端开启认证时必填)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+
+    /// Agent 的消息协议版本 (旧版 Agent 不声明, 使用裸消息)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u16>,
+}
+
+impl RegisterMessage {
+    pub fn new(vm_id: impl Into<String>) -> Self {
+        Self {
+            message_type: REGISTER_MESSAGE_TYPE.to_string(),
+            vm_id: vm_id.into(),
+            agent_version: None,
+            capabilities: Vec::new(),
+            token: None,
+            protocol_version: None,
+        }
+    }
+
+    pub fn with_agent_version(mut self, agent_version: impl Into<String>) -> Self {
+        self.agent_version = Some(agent_version.into());
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: Vec<String>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn with_protocol_version(mut self, protocol_version: u16) -> Self {
+        self.protocol_version = Some(protocol_version);
+        self
+    }
+
+    /// 与本服务端协商出的协议版本
+    pub fn agreed_version(&self) -> AgreedVersion {
+        negotiate(self.protocol_version, SUPPORTED_PROTOCOL_VERSIONS)
+    }
+
+    /// 解析握手消息: JSON 注册消息或旧版的纯文本 VM ID
+    pub fn parse_handshake(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let registration = if text.starts_with('{') {
+            match ClientMessage::parse(text)? {
+                ClientMessage::Register(registration) => registration,
+                ClientMessage::Result(_)
+                | ClientMessage::RawInput(_)
+                | ClientMessage::Heartbeat(_) => {
+                    return Err(VerificationError::InvalidHandshake(
+                        "第一条消息必须是 VM ID 或注册消息".to_string(),
+                    ))
+                }
+            }
+        } else {
+            Self::new(text)
+        };
+
+        registration.validate()?;
+        Ok(registration)
+    }
+
+    /// 校验 VM ID: 非空、不超长、不含空白或控制字符
+    pub fn validate(&self) -> Result<()> {
+        if self.vm_id.is_empty() {
+            return Err(VerificationError::InvalidHandshake("VM ID 为空".to_string()));
+        }
+        if self.vm_id.len() > MAX_VM_ID_LEN {
+            return Err(VerificationError::InvalidHandshake(format!(
+                "VM ID 过长 ({} > {})",
+                self.vm_id.len(),
+                MAX_VM_ID_LEN
+            )));
+        }
+        if self.vm_id.chars().any(|c| c.is_whitespace() || c.is_control()) {
+            return Err(VerificationError::InvalidHandshake(format!(
+                "VM ID 包含非法字符: {:?}",
+                self.vm_id
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// 拒绝连接时发给客户端的消息 (TCP; WebSocket 使用关闭帧的 reason)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectMessage {
+    /// 固定为 `rejected`
+    pub message_type: String,
+
+    /// 拒绝原因
+    pub reason: String,
+}
+
+impl RejectMessage {
+    pub fn new(reason: impl Into<String>) -> Self {
+        Self {
+            message_type: REJECTED_MESSAGE_TYPE.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Agent 在上报模式下观察到的原始输入
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawInputEvent {
+    /// 固定为 `raw_input`
+    pub message_type: String,
+
+    /// 输入设备 (`keyboard` / `mouse`)
+    pub device: String,
+
+    /// 按键或坐标轴名称 (如 `KEY_A`、`BTN_LEFT`、`REL_X`)
+    pub code: String,
+
+    /// 按键: 1 按下, 0 释放, 2 重复; 坐标轴: 相对位移
+    pub value: i32,
+
+    /// 鼠标绝对坐标 (Windows Agent 提供)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<i32>,
+
+    /// Agent 观察到输入的时间 (Unix 毫秒)
+    pub timestamp: i64,
+
+    /// Agent 上报队列已满而丢弃的事件数 (自上一条上报以来)
+    #[serde(default)]
+    pub dropped: u64,
+}
+
+/// Agent 定期发送的心跳, 服务端据此判断 Agent 是否失联
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatMessage {
+    /// 固定为 `heartbeat`
+    pub message_type: String,
+
+    /// VM ID
+    pub vm_id: String,
+
+    /// Agent 进程运行时长 (秒)
+    pub uptime_s: u64,
+}
+
+/// 转发给订阅者的输入上报
+#[derive(Debug, Clone)]
+pub struct RawInputReport {
+    /// 上报的 VM ID
+    pub vm_id: String,
+
+    /// 输入事件
+    pub event: RawInputEvent,
+}
+
+/// Agent 工作模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentMode {
+    /// 等待事件并验证
+    Verify,
+
+    /// 持续上报观察到的输入
+    Report,
+}
+
+impl AgentMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AgentMode::Verify => "verify",
+            AgentMode::Report => "report",
+        }
+    }
+
+    /// 通知 Agent 切换到该模式的控制事件
+    pub fn to_event(&self) -> Event {
+        Event {
+            event_type: SET_MODE_EVENT_TYPE.to_string(),
+            data: serde_json::json!({ "mode": self.as_str() }),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+}
+
+/// 客户端发来的消息
+#[derive(Debug, Clone)]
+pub enum ClientMessage {
+    /// 注册消息
+    Register(RegisterMessage),
+
+    /// 验证结果
+    Result(VerifyResult),
+
+    /// 上报模式下的原始输入
+    RawInput(RawInputEvent),
+
+    /// 心跳
+    Heartbeat(HeartbeatMessage),
+}
+
+impl ClientMessage {
+    /// 解析客户端消息: 协商后的信封, 或按 `message_type` 区分的旧版裸消息 (不带类型的为验证结果)
+    pub fn parse(text: &str) -> Result<Self> {
+        let envelope = decode(text, MessageType::Result)?;
+        match envelope.message_type {
+            MessageType::Register => Ok(ClientMessage::Register(envelope.decode_as(MessageType::Register)?)),
+            MessageType::RawInput => Ok(ClientMessage::RawInput(envelope.decode_as(MessageType::RawInput)?)),
+            MessageType::Heartbeat => Ok(ClientMessage::Heartbeat(envelope.decode_as(MessageType::Heartbeat)?)),
+            MessageType::Result => Ok(ClientMessage::Result(envelope.decode_as(MessageType::Result)?)),
+            other => Err(VerificationError::ProtocolError(format!("不支持的客户端消息类型: {}", other))),
+        }
+    }
 }
 
 /// 待验证事件
@@ -61,10 +355,65 @@ pub struct PendingEvent {
     pub event: Event,
 
     /// 结果发送器
-    pub result_tx: tokio::sync::oneshot::Sender<VerifyResult>,
+    pub result_tx: tokio::sync::oneshot::Sender<VerifyOutcome>,
 
-    /// 创建时间
+    /// 创建时间 (即发送给 Agent 的时间)
     pub created_at: tokio::time::Instant,
+
+    /// 截止时间, 到期未收到结果判为超时
+    pub deadline: tokio::time::Instant,
+}
+
+/// 与事件匹配上的验证结果
+#[derive(Debug, Clone)]
+pub struct MatchedResult {
+    /// Agent 返回的结果
+    pub result: VerifyResult,
+
+    /// 服务端测得的延迟: 从发送事件到收到结果 (毫秒)
+    pub server_latency_ms: u64,
+
+    /// Agent 上报的延迟 (`VerifyResult::latency_ms`)
+    pub agent_latency_ms: u64,
+}
+
+/// 事件的验证结论
+#[derive(Debug, Clone)]
+pub enum VerifyOutcome {
+    /// Agent 确认观察到了事件
+    Verified(MatchedResult),
+
+    /// 收到结果, 但 Agent 未观察到期望的输入 (`verified = false`)
+    Mismatched(MatchedResult),
+
+    /// 截止时间前未收到结果
+    TimedOut {
+        /// 从发送事件到判定超时经过的时间 (毫秒)
+        elapsed_ms: u64,
+    },
+}
+
+impl VerifyOutcome {
+    pub fn is_verified(&self) -> bool {
+        matches!(self, VerifyOutcome::Verified(_))
+    }
+
+    /// 结论名称 (`verified` / `mismatched` / `timeout`), 与存储中的 outcome 列一致
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerifyOutcome::Verified(_) => "verified",
+            VerifyOutcome::Mismatched(_) => "mismatched",
+            VerifyOutcome::TimedOut { .. } => "timeout",
+        }
+    }
+
+    /// 匹配上的结果 (超时时为 None)
+    pub fn matched(&self) -> Option<&MatchedResult> {
+        match self {
+            VerifyOutcome::Verified(matched) | VerifyOutcome::Mismatched(matched) => Some(matched),
+            VerifyOutcome::TimedOut { .. } => None,
+        }
+    }
 }
 
 /// 客户端连接（抽象）
@@ -97,4 +446,12 @@ impl ClientConnection {
             ClientConnection::Tcp { addr, .. } => addr,
         }
     }
+
+    /// 传输类型名称
+    pub fn transport(&self) -> &'static str {
+        match self {
+            ClientConnection::WebSocket { .. } => "websocket",
+            ClientConnection::Tcp { .. } => "tcp",
+        }
+    }
 }
@@ -0,0 +1,212 @@
+//! 事件注入接口集成测试
+//!
+//! 在临时端口上启动完整的验证服务器, 由模拟 Agent 应答事件, 通过 HTTP 注入事件并检查结论。
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use verification_server::framing::encode_frame;
+use verification_server::{
+    BatchEventResponse, Daemon, DaemonConfig, Event, EventResponse, FrameDecoder, FrameFormat, RegisterMessage,
+    VerifyResult,
+};
+
+const API_TOKEN: &str = "test-token";
+
+/// 找一个空闲端口
+fn free_addr() -> SocketAddr {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap()
+}
+
+/// 启动开启事件注入接口的服务器, 返回 (daemon, Agent 端口, HTTP 端口)
+async fn start_daemon() -> (Daemon, SocketAddr, SocketAddr) {
+    let tcp_addr = free_addr();
+    let http_addr = free_addr();
+    let config = DaemonConfig::from_toml(&format!(
+        "[server]\ntcp_addr = \"{}\"\n[metrics]\naddr = \"{}\"\napi_token = \"{}\"\n",
+        tcp_addr, http_addr, API_TOKEN
+    ))
+    .unwrap();
+    let daemon = Daemon::start(config).await.unwrap();
+    (daemon, tcp_addr, http_addr)
+}
+
+/// 模拟 Agent: 按事件中的 `key` 应答
+///
+/// - `miss`: 回复 `verified = false`
+/// - `drop`: 不回复 (服务端判定超时)
+/// - 其他: 回复 `verified = true`
+async fn spawn_mock_agent(daemon: &Daemon, addr: SocketAddr, vm_id: &str) {
+    let mut stream = loop {
+        match TcpStream::connect(addr).await {
+            Ok(stream) => break stream,
+            // 监听任务还未绑定端口
+            Err(_) => tokio::time::sleep(Duration::from_millis(20)).await,
+        }
+    };
+    let handshake = serde_json::to_string(&RegisterMessage::new(vm_id)).unwrap();
+    stream.write_all(&encode_frame(FrameFormat::Versioned, &handshake)).await.unwrap();
+
+    // 等待注册完成
+    for _ in 0..100 {
+        if daemon.service().list_clients().await.iter().any(|client| client.vm_id == vm_id) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    tokio::spawn(async move {
+        let mut decoder = FrameDecoder::new();
+        while let Ok(Some(frame)) = decoder.read_frame(&mut stream).await {
+            let Ok(event) = serde_json::from_str::<Event>(&frame) else {
+                continue;
+            };
+            let key = event.data["key"].as_str().unwrap_or_default();
+            if key == "drop" {
+                continue;
+            }
+
+            let result = VerifyResult {
+                event_id: event.data["event_id"].as_str().unwrap().to_string(),
+                verified: key != "miss",
+                timestamp: 0,
+                latency_ms: 2,
+                details: serde_json::json!({"key": key}),
+            };
+            let reply = serde_json::to_string(&result).unwrap();
+            if stream.write_all(&encode_frame(FrameFormat::Versioned, &reply)).await.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// 发送 HTTP 请求, 返回 (状态码, 响应体)
+async fn post(addr: SocketAddr, path: &str, token: Option<&str>, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let authorization = token.map(|token| format!("Authorization: Bearer {}\r\n", token)).unwrap_or_default();
+    let request = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\n{}Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+        path,
+        addr,
+        authorization,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+    (status, body.to_string())
+}
+
+#[tokio::test]
+async fn test_single_event_returns_matched_result() {
+    let (daemon, tcp_addr, http_addr) = start_daemon().await;
+    spawn_mock_agent(&daemon, tcp_addr, "vm-1").await;
+
+    let (status, body) = post(
+        http_addr,
+        "/api/v1/events",
+        Some(API_TOKEN),
+        r#"{"vm_id": "vm-1", "event_type": "keyboard", "data": {"key": "ENTER"}, "timeout_ms": 5000}"#,
+    )
+    .await;
+    assert_eq!(status, 200, "{}", body);
+
+    let response: EventResponse = serde_json::from_str(&body).unwrap();
+    assert_eq!(serde_json::to_value(response.status).unwrap(), "verified");
+    let result = response.result.unwrap();
+    assert_eq!(Some(result.event_id), response.event_id);
+    assert_eq!(result.details["key"], "ENTER");
+    assert!(response.server_latency_ms.is_some());
+
+    // Agent 回复未观察到输入
+    let (status, body) = post(
+        http_addr,
+        "/api/v1/events",
+        Some(API_TOKEN),
+        r#"{"vm_id": "vm-1", "event_type": "keyboard", "data": {"key": "miss"}, "timeout_ms": 5000}"#,
+    )
+    .await;
+    assert_eq!(status, 200);
+    let response: EventResponse = serde_json::from_str(&body).unwrap();
+    assert_eq!(serde_json::to_value(response.status).unwrap(), "mismatched");
+    assert!(!response.result.unwrap().verified);
+
+    daemon.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_single_event_timeout_and_errors() {
+    let (daemon, tcp_addr, http_addr) = start_daemon().await;
+    spawn_mock_agent(&daemon, tcp_addr, "vm-1").await;
+
+    // Agent 不回复: 超时状态写在响应体中
+    let (status, body) = post(
+        http_addr,
+        "/api/v1/events",
+        Some(API_TOKEN),
+        r#"{"vm_id": "vm-1", "event_type": "keyboard", "data": {"key": "drop"}, "timeout_ms": 200}"#,
+    )
+    .await;
+    assert_eq!(status, 200);
+    let response: EventResponse = serde_json::from_str(&body).unwrap();
+    assert_eq!(serde_json::to_value(response.status).unwrap(), "timeout");
+    assert!(response.event_id.is_some());
+    assert!(response.result.is_none());
+
+    // Agent 未连接
+    let (status, body) =
+        post(http_addr, "/api/v1/events", Some(API_TOKEN), r#"{"vm_id": "vm-404", "event_type": "keyboard"}"#).await;
+    assert_eq!(status, 409, "{}", body);
+
+    // 缺少或错误的 token
+    let request = r#"{"vm_id": "vm-1", "event_type": "keyboard"}"#;
+    assert_eq!(post(http_addr, "/api/v1/events", None, request).await.0, 401);
+    assert_eq!(post(http_addr, "/api/v1/events", Some("wrong"), request).await.0, 401);
+
+    // 请求体无效
+    let (status, _) = post(http_addr, "/api/v1/events", Some(API_TOKEN), r#"{"vm_id": "vm-1"}"#).await;
+    assert_eq!(status, 400);
+
+    daemon.shutdown().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_batch_fans_out_to_multiple_agents() {
+    let (daemon, tcp_addr, http_addr) = start_daemon().await;
+    spawn_mock_agent(&daemon, tcp_addr, "vm-1").await;
+    spawn_mock_agent(&daemon, tcp_addr, "vm-2").await;
+
+    let request = serde_json::json!({
+        "events": [
+            {"vm_id": "vm-1", "event_type": "keyboard", "data": {"key": "a"}, "timeout_ms": 5000},
+            {"vm_id": "vm-2", "event_type": "mouse", "data": {"key": "miss"}, "timeout_ms": 5000},
+            {"vm_id": "vm-1", "event_type": "keyboard", "data": {"key": "drop"}, "timeout_ms": 300},
+            {"vm_id": "vm-3", "event_type": "keyboard"},
+        ]
+    });
+    let (status, body) = post(http_addr, "/api/v1/events/batch", Some(API_TOKEN), &request.to_string()).await;
+    assert_eq!(status, 200, "{}", body);
+
+    let response: BatchEventResponse = serde_json::from_str(&body).unwrap();
+    let statuses: Vec<_> = response
+        .results
+        .iter()
+        .map(|result| serde_json::to_value(result.status).unwrap())
+        .collect();
+    assert_eq!(statuses, ["verified", "mismatched", "timeout", "skipped"]);
+    assert_eq!(
+        (response.verified, response.mismatched, response.timed_out, response.skipped, response.failed),
+        (1, 1, 1, 1, 0)
+    );
+    assert_eq!(response.results[0].result.as_ref().unwrap().details["key"], "a");
+    assert_eq!(response.results[3].vm_id, "vm-3");
+
+    daemon.shutdown().await.unwrap();
+}
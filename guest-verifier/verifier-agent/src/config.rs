@@ -0,0 +1,348 @@
+//! Agent 配置文件
+//!
+//! 用 `--config /etc/atp/verifier.toml` 代替冗长的命令行, 便于预置到 Guest 镜像中。
+//!
+//! 优先级: 命令行参数 > 配置文件 > 内置默认值。只有在命令行中显式给出的参数
+//! 才会覆盖配置文件, 未给出的参数 (包括带默认值的参数) 取配置文件中的值。
+//!
+//! ```toml
+//! server = "10.0.0.1:8080"
+//! transport = "websocket"
+//! vm_id = "win10-01"
+//! verifiers = ["keyboard", "mouse", "command"]
+//! log_level = "info"
+//! log_file = "/var/log/atp/verifier-agent.log"
+//! heartbeat_interval_secs = 30
+//! keepalive_interval_secs = 15
+//!
+//! [reconnect]
+//! enabled = true
+//! interval_secs = 5
+//!
+//! [tls]
+//! ca_cert = "/etc/atp/ca.pem"
+//! ```
+//!
+//! 未知的键只产生警告 (兼容新旧版本的配置文件), 类型错误或相互冲突的配置返回错误。
+
+use anyhow::{bail, Context, Result};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, ValueEnum};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::{Args, TransportType, VerifierTypeArg};
+
+/// 配置文件内容 (所有字段可选, 未配置的取命令行默认值)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AgentConfig {
+    /// 服务器地址
+    pub server: Option<String>,
+
+    /// 虚拟机 ID, 未配置时自动获取
+    pub vm_id: Option<String>,
+
+    /// 传输类型 (websocket/tcp)
+    pub transport: Option<String>,
+
+    /// 启用的验证器
+    pub verifiers: Option<Vec<String>>,
+
+    pub log_level: Option<String>,
+
+    /// 工作模式 (verify/report)
+    pub mode: Option<String>,
+
+    pub auth_token: Option<String>,
+
+    /// 事件过滤规则, 格式同 `--event-filter`
+    pub event_filters: Option<Vec<String>>,
+
+    pub tcp_legacy_framing: Option<bool>,
+    pub report_queue_size: Option<usize>,
+    pub result_buffer_size: Option<usize>,
+
+    /// 命令验证超时 (秒)
+    pub command_timeout: Option<u64>,
+
+    /// 同时执行的验证数上限
+    pub parallelism: Option<usize>,
+
+    /// 后台运行 (Windows 服务 / systemd)
+    pub daemon: Option<bool>,
+
+    /// 心跳间隔 (秒), 0 表示不发送心跳
+    pub heartbeat_interval_secs: Option<u64>,
+
+    /// WebSocket 保活间隔 (秒), 0 表示关闭保活
+    pub keepalive_interval_secs: Option<u64>,
+
+    /// 日志文件, 后台运行时没有标准输出
+    pub log_file: Option<PathBuf>,
+
+    pub reconnect: ReconnectConfig,
+    pub tls: TlsConfig,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+/// 自动重连配置
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ReconnectConfig {
+    pub enabled: Option<bool>,
+    pub interval_secs: Option<u64>,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+/// TLS 配置
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// 是否开启 TLS; 未配置时由其他 TLS 选项决定
+    pub enabled: Option<bool>,
+    pub ca_cert: Option<PathBuf>,
+    pub insecure: Option<bool>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl TlsConfig {
+    /// 是否配置了除 `enabled` 以外的 TLS 选项
+    fn has_options(&self) -> bool {
+        self.ca_cert.is_some()
+            || self.insecure == Some(true)
+            || self.client_cert.is_some()
+            || self.client_key.is_some()
+    }
+}
+
+impl AgentConfig {
+    /// 读取并校验配置文件
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("读取配置文件失败: {}", path.display()))?;
+        Self::parse(&content).with_context(|| format!("配置文件无效: {}", path.display()))
+    }
+
+    /// 解析并校验配置内容
+    pub fn parse(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// 未知的键 (带表名前缀)
+    pub fn unknown_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.unknown.keys().cloned().collect();
+        keys.extend(self.reconnect.unknown.keys().map(|key| format!("reconnect.{}", key)));
+        keys.extend(self.tls.unknown.keys().map(|key| format!("tls.{}", key)));
+        keys
+    }
+
+    /// 校验取值与配置文件内部的冲突 (与命令行合并后的冲突由 `Args::validate` 检查)
+    fn validate(&self) -> Result<()> {
+        if let Some(transport) = &self.transport {
+            parse_value_enum::<TransportType>("transport", transport)?;
+        }
+        for verifier in self.verifiers.iter().flatten() {
+            parse_value_enum::<VerifierTypeArg>("verifiers", verifier)?;
+        }
+        if let Some(mode) = &self.mode {
+            mode.parse::<verifier_core::AgentMode>()?;
+        }
+        if self.tls.enabled == Some(false) && self.tls.has_options() {
+            bail!("tls.enabled = false 时不能配置其他 TLS 选项");
+        }
+        if self.tls.enabled == Some(false) && self.server.as_deref().is_some_and(|s| s.starts_with("wss://")) {
+            bail!("tls.enabled = false 与 wss:// 服务器地址冲突");
+        }
+        Ok(())
+    }
+
+    /// 把配置文件中的值填入命令行中未显式给出的参数
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches) -> Result<()> {
+        let from_cli = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+        macro_rules! merge {
+            ($id:literal, $field:ident, $value:expr) => {
+                if !from_cli($id) {
+                    if let Some(value) = $value {
+                        args.$field = value;
+                    }
+                }
+            };
+        }
+
+        merge!("server", server, self.server.clone());
+        merge!("vm_id", vm_id, self.vm_id.clone().map(Some));
+        merge!(
+            "transport",
+            transport,
+            self.transport.as_deref().map(|v| parse_value_enum("transport", v)).transpose()?
+        );
+        merge!(
+            "verifiers",
+            verifiers,
+            self.verifiers
+                .as_ref()
+                .map(|vs| vs.iter().map(|v| parse_value_enum("verifiers", v)).collect::<Result<Vec<_>>>())
+                .transpose()?
+        );
+        merge!("log_level", log_level, self.log_level.clone());
+        merge!("mode", mode, self.mode.as_deref().map(str::parse).transpose()?);
+        merge!("auth_token", auth_token, self.auth_token.clone().map(Some));
+        merge!("event_filters", event_filters, self.event_filters.clone());
+        merge!("tcp_legacy_framing", tcp_legacy_framing, self.tcp_legacy_framing);
+        merge!("report_queue_size", report_queue_size, self.report_queue_size);
+        merge!("result_buffer_size", result_buffer_size, self.result_buffer_size);
+        merge!("command_timeout", command_timeout, self.command_timeout);
+        merge!("parallelism", parallelism, self.parallelism);
+        merge!("daemon", daemon, self.daemon);
+        merge!("heartbeat_interval", heartbeat_interval, self.heartbeat_interval_secs);
+        merge!("keepalive_interval", keepalive_interval, self.keepalive_interval_secs);
+        merge!("log_file", log_file, self.log_file.clone().map(Some));
+        merge!("auto_reconnect", auto_reconnect, self.reconnect.enabled);
+        merge!("reconnect_interval", reconnect_interval, self.reconnect.interval_secs);
+        merge!("tls", tls, self.tls.enabled);
+        merge!("ca_cert", ca_cert, self.tls.ca_cert.clone().map(Some));
+        merge!("insecure", insecure, self.tls.insecure);
+        merge!("client_cert", client_cert, self.tls.client_cert.clone().map(Some));
+        merge!("client_key", client_key, self.tls.client_key.clone().map(Some));
+
+        Ok(())
+    }
+}
+
+/// 按命令行的取值规则解析枚举值 (不区分大小写)
+fn parse_value_enum<T: ValueEnum>(key: &str, value: &str) -> Result<T> {
+    T::from_str(value, true).map_err(|_| {
+        let choices: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        anyhow::anyhow!("{} 的取值无效: {} (可选: {})", key, value, choices.join(", "))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::{CommandFactory, FromArgMatches};
+    use verifier_core::AgentMode;
+
+    /// 解析命令行并合并配置文件
+    fn merged(cli: &[&str], content: &str) -> Result<Args> {
+        let matches = Args::command().try_get_matches_from(std::iter::once("verifier-agent").chain(cli.iter().copied()))?;
+        let mut args = Args::from_arg_matches(&matches)?;
+        AgentConfig::parse(content)?.apply(&mut args, &matches)?;
+        args.validate()?;
+        Ok(args)
+    }
+
+    const CONFIG: &str = r#"
+server = "10.0.0.1:9000"
+transport = "tcp"
+vm_id = "win10-01"
+verifiers = ["keyboard", "Clipboard"]
+log_level = "debug"
+mode = "report"
+command_timeout = 60
+parallelism = 8
+heartbeat_interval_secs = 10
+keepalive_interval_secs = 20
+log_file = "/var/log/atp/verifier-agent.log"
+
+[reconnect]
+interval_secs = 15
+
+[tls]
+ca_cert = "/etc/atp/ca.pem"
+"#;
+
+    #[test]
+    fn test_config_fills_defaults() {
+        let args = merged(&[], CONFIG).unwrap();
+        assert_eq!(args.server, "10.0.0.1:9000");
+        assert!(matches!(args.transport, TransportType::Tcp));
+        assert_eq!(args.vm_id.as_deref(), Some("win10-01"));
+        assert_eq!(args.verifiers, vec![VerifierTypeArg::Keyboard, VerifierTypeArg::Clipboard]);
+        assert_eq!(args.log_level, "debug");
+        assert_eq!(args.mode, AgentMode::Report);
+        assert_eq!((args.command_timeout, args.reconnect_interval), (60, 15));
+        assert_eq!(args.parallelism, 8);
+        assert_eq!(args.ca_cert, Some(PathBuf::from("/etc/atp/ca.pem")));
+        assert_eq!(args.heartbeat_interval, 10);
+        assert_eq!(args.keepalive_interval, 20);
+        assert_eq!(args.log_file, Some(PathBuf::from("/var/log/atp/verifier-agent.log")));
+
+        // 配置文件未涉及的参数保持内置默认值
+        assert_eq!(args.result_buffer_size, 256);
+        assert!(args.auto_reconnect);
+        assert!(!args.daemon);
+    }
+
+    #[test]
+    fn test_cli_overrides_config() {
+        let args = merged(
+            &[
+                "--server",
+                "ws://192.168.1.1:8080",
+                "-t",
+                "websocket",
+                "-v",
+                "mouse",
+                "--reconnect-interval",
+                "5",
+                "--heartbeat-interval",
+                "0",
+            ],
+            CONFIG,
+        )
+        .unwrap();
+        assert_eq!(args.server, "ws://192.168.1.1:8080");
+        assert!(matches!(args.transport, TransportType::Websocket));
+        assert_eq!(args.verifiers, vec![VerifierTypeArg::Mouse]);
+        // 命令行给出的值与默认值相同时仍然优先于配置文件
+        assert_eq!(args.reconnect_interval, 5);
+        assert_eq!(args.heartbeat_interval, 0);
+        // 未在命令行给出的仍取配置文件
+        assert_eq!(args.vm_id.as_deref(), Some("win10-01"));
+        assert_eq!(args.log_level, "debug");
+    }
+
+    #[test]
+    fn test_config_validation() {
+        // 未知的键只记录, 不报错
+        let config = AgentConfig::parse("serve = \"x\"\n[tls]\nca = \"ca.pem\"\n").unwrap();
+        assert_eq!(config.unknown_keys(), ["serve", "tls.ca"]);
+
+        // 取值无效
+        let err = AgentConfig::parse("transport = \"udp\"").unwrap_err();
+        assert!(err.to_string().contains("websocket, tcp"), "{}", err);
+        assert!(AgentConfig::parse("verifiers = [\"keyboard\", \"screen\"]").is_err());
+        assert!(AgentConfig::parse("mode = \"watch\"").is_err());
+        assert!(AgentConfig::parse("command_timeout = \"30\"").is_err());
+
+        // 配置文件内部冲突
+        assert!(AgentConfig::parse("[tls]\nenabled = false\ninsecure = true\n").is_err());
+
+        // 与命令行合并后的冲突: 证书与私钥分别来自命令行与配置文件时可以组合
+        let args = merged(&["--client-cert", "agent.pem"], "[tls]\nclient_key = \"agent.key\"\n").unwrap();
+        assert!(args.tls_config().is_some());
+        assert!(merged(&["--client-cert", "agent.pem"], "").is_err());
+        assert!(merged(&["--insecure"], "[tls]\nca_cert = \"ca.pem\"\n").is_err());
+        assert!(merged(&["-t", "tcp"], "server = \"ws://host:8080\"").is_err());
+        assert!(merged(&[], "tcp_legacy_framing = true").is_err());
+        assert!(merged(&[], "parallelism = 0").is_err());
+    }
+}
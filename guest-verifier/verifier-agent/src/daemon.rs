@@ -0,0 +1,62 @@
+//! 守护进程支持
+//!
+//! Linux 下以 systemd `Type=notify` 服务运行时通过 sd_notify 上报就绪与状态;
+//! 未由 systemd 启动 (没有 `NOTIFY_SOCKET`) 时这些通知不产生任何效果。
+//! Windows 服务的注册与运行见 `service` 模块。
+
+use tracing::warn;
+
+/// 通知 systemd 服务已就绪
+pub fn notify_ready(status: &str) {
+    #[cfg(target_os = "linux")]
+    notify(&[sd_notify::NotifyState::Ready, sd_notify::NotifyState::Status(status)]);
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = status;
+}
+
+/// 更新 systemd 中显示的服务状态
+pub fn notify_status(status: &str) {
+    #[cfg(target_os = "linux")]
+    notify(&[sd_notify::NotifyState::Status(status)]);
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = status;
+}
+
+/// 通知 systemd 服务正在停止
+pub fn notify_stopping() {
+    #[cfg(target_os = "linux")]
+    notify(&[sd_notify::NotifyState::Stopping]);
+}
+
+#[cfg(target_os = "linux")]
+fn notify(states: &[sd_notify::NotifyState]) {
+    if let Err(e) = sd_notify::notify(false, states) {
+        warn!("发送 systemd 通知失败: {}", e);
+    }
+}
+
+/// 等待退出信号: Ctrl-C, Unix 下还包括 SIGTERM (systemd 停止服务时发送)
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!("注册 SIGTERM 处理失败: {}", e),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!("等待退出信号失败: {}", e);
+        std::future::pending::<()>().await;
+    }
+}
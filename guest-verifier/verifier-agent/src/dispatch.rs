@@ -0,0 +1,117 @@
+//! 事件分发
+//!
+//! 事件循环只负责接收事件与发送结果, 验证在工作任务中执行, 同时执行的验证不超过
+//! `parallelism` 个, 慢的命令验证不会阻塞排在后面的键盘事件。
+//!
+//! 键盘、鼠标验证器等待下一个输入事件, 剪贴板与显示验证器读取全局状态, 同类事件必须
+//! 按到达顺序依次验证, 每种类型各有一个串行队列; 命令验证相互独立, 直接并发执行。
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+use tracing::{error, info};
+use verifier_core::{verify_with_timeout, CancellationToken, Event, Verifier, VerifierError, VerifierType, VerifyResult};
+
+/// 待执行的验证
+struct Job {
+    verifier: Arc<dyn Verifier>,
+    event: Event,
+}
+
+/// 执行验证并把结果交给事件循环发送
+#[derive(Clone)]
+struct Worker {
+    permits: Arc<Semaphore>,
+    results: mpsc::UnboundedSender<VerifyResult>,
+    shutdown: CancellationToken,
+}
+
+impl Worker {
+    async fn run(&self, job: Job) {
+        let Ok(_permit) = self.permits.acquire().await else {
+            return;
+        };
+        // 关闭后不再开始排队中的验证
+        if self.shutdown.is_cancelled() {
+            return;
+        }
+
+        match verify_with_timeout(job.verifier.as_ref(), job.event, self.shutdown.child_token()).await {
+            Ok(result) => {
+                info!(
+                    "验证完成: event_id={}, verified={}, latency={}ms",
+                    result.event_id, result.verified, result.latency_ms
+                );
+                let _ = self.results.send(result);
+            }
+            Err(VerifierError::Cancelled) => {
+                info!("Agent 正在关闭, 验证已取消");
+            }
+            Err(e) => {
+                error!("验证失败: {}", e);
+            }
+        }
+    }
+}
+
+/// 事件分发器
+pub struct EventDispatcher {
+    worker: Worker,
+    parallelism: usize,
+    /// 需要串行验证的类型各自的队列, 首次收到该类型事件时创建
+    lanes: std::sync::Mutex<HashMap<VerifierType, mpsc::UnboundedSender<Job>>>,
+}
+
+impl EventDispatcher {
+    /// 创建分发器, 同时返回接收验证结果的通道
+    pub fn new(parallelism: usize, shutdown: CancellationToken) -> (Self, mpsc::UnboundedReceiver<VerifyResult>) {
+        let (results, results_rx) = mpsc::unbounded_channel();
+        let dispatcher = Self {
+            worker: Worker {
+                permits: Arc::new(Semaphore::new(parallelism)),
+                results,
+                shutdown,
+            },
+            parallelism,
+            lanes: std::sync::Mutex::new(HashMap::new()),
+        };
+        (dispatcher, results_rx)
+    }
+
+    /// 提交一个验证, 立即返回
+    pub fn dispatch(&self, verifier: Arc<dyn Verifier>, event: Event) {
+        let verifier_type = verifier.verifier_type();
+        let job = Job { verifier, event };
+
+        if !is_serial(&verifier_type) {
+            let worker = self.worker.clone();
+            tokio::spawn(async move { worker.run(job).await });
+            return;
+        }
+
+        let mut lanes = self.lanes.lock().unwrap();
+        let lane = lanes.entry(verifier_type).or_insert_with(|| {
+            let (tx, mut rx) = mpsc::unbounded_channel::<Job>();
+            let worker = self.worker.clone();
+            tokio::spawn(async move {
+                while let Some(job) = rx.recv().await {
+                    worker.run(job).await;
+                }
+            });
+            tx
+        });
+        // 队列任务在发送端关闭前不会退出
+        let _ = lane.send(job);
+    }
+
+    /// 停止接收新的验证, 并等待正在执行的验证结束
+    pub async fn drain(&self) {
+        self.lanes.lock().unwrap().clear();
+        let _ = self.worker.permits.acquire_many(self.parallelism as u32).await;
+    }
+}
+
+/// 同类事件是否必须串行验证
+fn is_serial(verifier_type: &VerifierType) -> bool {
+    !matches!(verifier_type, VerifierType::Command)
+}
@@ -0,0 +1,174 @@
+//! 输入上报模式的监听器
+//!
+//! 复用验证器的设备发现与 Hook 代码, 把观察到的每一次按键/鼠标输入转换为
+//! `RawInputEvent` 写入上报队列。监听器启动后一直运行, 是否入队由队列的开关决定。
+
+use verifier_core::{RawInputQueue, Result};
+
+/// 启动键盘与鼠标监听, 返回启动的监听器数量
+pub fn start_listeners(queue: &RawInputQueue) -> Result<usize> {
+    platform::start(queue)
+}
+
+// ===== Linux 实现 (evdev) =====
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use evdev::{Device, InputEventKind};
+    use tracing::{debug, warn};
+    use verifier_core::{RawInputEvent, RawInputQueue, Result, VerifierError};
+
+    use crate::verifiers::{LinuxKeyboardVerifier, LinuxMouseVerifier};
+
+    pub fn start(queue: &RawInputQueue) -> Result<usize> {
+        let keyboards = LinuxKeyboardVerifier::find_keyboard_devices()?;
+        let mice = LinuxMouseVerifier::find_mouse_devices()?;
+        if keyboards.is_empty() && mice.is_empty() {
+            return Err(VerifierError::ConfigError("未找到键盘或鼠标设备".to_string()));
+        }
+
+        let count = keyboards.len() + mice.len();
+        for device in keyboards {
+            spawn_listener(device, "keyboard", queue.clone());
+        }
+        for device in mice {
+            spawn_listener(device, "mouse", queue.clone());
+        }
+        Ok(count)
+    }
+
+    /// 每个设备一个读取线程 (阻塞读取, 不占用 tokio 运行时)
+    fn spawn_listener(mut device: Device, kind: &'static str, queue: RawInputQueue) {
+        let name = device.name().unwrap_or("未知").to_string();
+        debug!("监听输入设备: {} ({})", name, kind);
+
+        std::thread::spawn(move || loop {
+            let events = match device.fetch_events() {
+                Ok(events) => events,
+                Err(e) => {
+                    warn!("读取输入设备 {} 失败, 停止监听: {}", name, e);
+                    return;
+                }
+            };
+
+            for event in events {
+                let code = match event.kind() {
+                    InputEventKind::Key(key) => format!("{:?}", key),
+                    InputEventKind::RelAxis(axis) => format!("{:?}", axis),
+                    _ => continue,
+                };
+                queue.push(RawInputEvent::new(kind, code, event.value()));
+            }
+        });
+    }
+}
+
+// ===== Windows 实现 (Hook API) =====
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use std::sync::OnceLock;
+    use tracing::{debug, error};
+    use verifier_core::{RawInputEvent, RawInputQueue, Result};
+    use windows::Win32::Foundation::{HWND, LPARAM, LRESULT, WPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::*;
+
+    use crate::verifiers::WindowsKeyboardVerifier;
+
+    // Hook 回调无法携带上下文, 通过全局变量访问上报队列
+    static REPORT_QUEUE: OnceLock<RawInputQueue> = OnceLock::new();
+
+    pub fn start(queue: &RawInputQueue) -> Result<usize> {
+        if REPORT_QUEUE.set(queue.clone()).is_err() {
+            // Hook 已安装
+            return Ok(0);
+        }
+        std::thread::spawn(hook_thread);
+        Ok(2)
+    }
+
+    /// Hook 线程主函数: 同时安装键盘与鼠标钩子
+    fn hook_thread() {
+        unsafe {
+            let keyboard = SetWindowsHookExW(WH_KEYBOARD_LL, Some(keyboard_proc), None, 0);
+            let mouse = SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), None, 0);
+            match (&keyboard, &mouse) {
+                (Ok(_), Ok(_)) => debug!("输入上报钩子已安装"),
+                _ => error!("安装输入上报钩子失败: keyboard={:?}, mouse={:?}", keyboard, mouse),
+            }
+
+            // Windows 消息循环
+            let mut msg = MSG::default();
+            while GetMessageW(&mut msg, HWND::default(), 0, 0).as_bool() {
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+
+            // 清理钩子
+            if let Ok(hook) = keyboard {
+                let _ = UnhookWindowsHookEx(hook);
+            }
+            if let Ok(hook) = mouse {
+                let _ = UnhookWindowsHookEx(hook);
+            }
+            debug!("输入上报钩子已卸载");
+        }
+    }
+
+    /// 键盘钩子回调: 按下为 1, 释放为 0
+    unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code >= 0 {
+            if let Some(queue) = REPORT_QUEUE.get() {
+                let value = match wparam.0 as u32 {
+                    WM_KEYDOWN | WM_SYSKEYDOWN => Some(1),
+                    WM_KEYUP | WM_SYSKEYUP => Some(0),
+                    _ => None,
+                };
+                if let Some(value) = value {
+                    let kb = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+                    let key = WindowsKeyboardVerifier::vk_code_to_key_name(kb.vkCode)
+                        .unwrap_or_else(|| format!("VK_0x{:X}", kb.vkCode));
+                    queue.push(RawInputEvent::new("keyboard", key, value));
+                }
+            }
+        }
+
+        CallNextHookEx(None, code, wparam, lparam)
+    }
+
+    /// 鼠标钩子回调: 按键按下为 1、释放为 0, 滚轮为滚动量, 均附带屏幕坐标
+    unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+        if code >= 0 {
+            if let Some(queue) = REPORT_QUEUE.get() {
+                let mouse = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+                let input = match wparam.0 as u32 {
+                    WM_LBUTTONDOWN => Some(("BTN_LEFT", 1)),
+                    WM_LBUTTONUP => Some(("BTN_LEFT", 0)),
+                    WM_RBUTTONDOWN => Some(("BTN_RIGHT", 1)),
+                    WM_RBUTTONUP => Some(("BTN_RIGHT", 0)),
+                    WM_MBUTTONDOWN => Some(("BTN_MIDDLE", 1)),
+                    WM_MBUTTONUP => Some(("BTN_MIDDLE", 0)),
+                    WM_MOUSEMOVE => Some(("MOVE", 0)),
+                    WM_MOUSEWHEEL => Some(("WHEEL", (mouse.mouseData >> 16) as i16 as i32)),
+                    _ => None,
+                };
+                if let Some((input, value)) = input {
+                    let event = RawInputEvent::new("mouse", input, value)
+                        .with_position(mouse.pt.x, mouse.pt.y);
+                    queue.push(event);
+                }
+            }
+        }
+
+        CallNextHookEx(None, code, wparam, lparam)
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows")))]
+mod platform {
+    use verifier_core::{RawInputQueue, Result, VerifierError};
+
+    pub fn start(_queue: &RawInputQueue) -> Result<usize> {
+        Err(VerifierError::ConfigError("当前平台不支持输入上报".to_string()))
+    }
+}
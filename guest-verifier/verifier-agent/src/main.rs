@@ -2,88 +2,45 @@
 //!
 //! 该 Agent 运行在 Guest OS 内部，接收测试事件并验证实际发生的输入/输出
 
+mod config;
+mod daemon;
+mod dispatch;
+mod input_report;
+#[cfg(target_os = "windows")]
+mod service;
 mod verifiers;
+mod vm_id;
 
-use anyhow::{Context, Result};
-use clap::{Parser, ValueEnum};
+use anyhow::{bail, Context, Result};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Mutex, RwLock};
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use config::AgentConfig;
+use dispatch::EventDispatcher;
+use vm_id::{VmIdDetector, VmIdSource};
 use verifier_core::{
-    Event, TcpTransport, Verifier, VerifierTransport, VerifierType, WebSocketTransport,
+    AgentMode, CancellationToken, Event, EventFilter, FilterDecision,
+    HeartbeatMessage, RawInputEvent, RAW_INPUT_CAPABILITY, RawInputQueue, RawInputReceiver, RegisterMessage, ResultOutbox, TcpTransport,
+    TlsClientConfig, Verifier, VerifierTransport, VerifierType, VerifyResult,
+    WebSocketTransport,
 };
 
 // 根据平台导入不同的验证器
 #[cfg(target_os = "linux")]
-use verifiers::{CommandVerifier, LinuxKeyboardVerifier, LinuxMouseVerifier};
+use verifiers::{ClipboardVerifier, CommandVerifier, DisplayVerifier, LinuxKeyboardVerifier, LinuxMouseVerifier};
 
 #[cfg(target_os = "windows")]
-use verifiers::{CommandVerifier, WindowsKeyboardVerifier, WindowsMouseVerifier};
-
-/// 自动获取 VM ID
-///
-/// 尝试多种方式获取 VM ID：
-/// 1. 从 DMI/SMBIOS 读取 (需要 libvirt 配置 sysinfo)
-/// 2. 从系统主机名读取
-fn auto_detect_vm_id() -> Result<String> {
-    // 方式 1: 尝试从 DMI/SMBIOS 读取
-    #[cfg(target_os = "linux")]
-    {
-        // 优先尝试 product_serial
-        if let Ok(serial) = std::fs::read_to_string("/sys/class/dmi/id/product_serial") {
-            let vm_id = serial.trim().to_string();
-            if !vm_id.is_empty() && vm_id != "Not Specified" {
-                debug!("从 DMI product_serial 获取 VM ID: {}", vm_id);
-                return Ok(vm_id);
-            }
-        }
+use verifiers::{ClipboardVerifier, CommandVerifier, DisplayVerifier, WindowsKeyboardVerifier, WindowsMouseVerifier};
 
-        // 尝试 product_uuid
-        if let Ok(uuid) = std::fs::read_to_string("/sys/class/dmi/id/product_uuid") {
-            let vm_id = uuid.trim().to_string();
-            if !vm_id.is_empty() && vm_id != "Not Specified" {
-                debug!("从 DMI product_uuid 获取 VM ID: {}", vm_id);
-                return Ok(vm_id);
-            }
-        }
-
-        debug!("DMI/SMBIOS 信息不可用或为空");
-    }
-
-    // 方式 2: 回退到主机名
-    #[cfg(target_os = "linux")]
-    {
-        if let Ok(hostname) = std::fs::read_to_string("/etc/hostname") {
-            let vm_id = hostname.trim().to_string();
-            if !vm_id.is_empty() {
-                debug!("使用主机名作为 VM ID: {}", vm_id);
-                return Ok(vm_id);
-            }
-        }
-    }
-
-    #[cfg(target_os = "windows")]
-    {
-        // Windows: 使用计算机名
-        use std::process::Command;
-        if let Ok(output) = Command::new("hostname").output() {
-            let hostname = String::from_utf8_lossy(&output.stdout)
-                .trim()
-                .to_string();
-            if !hostname.is_empty() {
-                debug!("使用主机名作为 VM ID: {}", hostname);
-                return Ok(hostname);
-            }
-        }
-    }
-
-    Err(anyhow::anyhow!(
-        "无法自动获取 VM ID，请使用 --vm-id 手动指定"
-    ))
-}
+/// 保活超时为保活间隔的倍数
+const KEEPALIVE_TIMEOUT_INTERVALS: u32 = 3;
 
 /// 传输类型
 #[derive(Debug, Clone, ValueEnum)]
@@ -99,6 +56,13 @@ enum TransportType {
 #[command(name = "verifier-agent")]
 #[command(about = "Guest 验证器 Agent - 运行在 Guest OS 内部验证输入/输出", long_about = None)]
 struct Args {
+    /// 配置文件 (TOML), 命令行中显式给出的参数优先于配置文件
+    #[arg(short, long, global = true)]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<AgentCommand>,
+
     /// 服务器地址 (例如: localhost:8080 或 ws://localhost:8080)
     #[arg(short, long, default_value = "localhost:8080")]
     server: String,
@@ -126,6 +90,175 @@ struct Args {
     /// 重连间隔（秒）
     #[arg(long, default_value = "5")]
     reconnect_interval: u64,
+
+    /// 事件过滤规则 (可多次指定, 按顺序匹配)
+    ///
+    /// 格式: `<accept|reject>:<条件>`, 例如 `accept:event_type=keyboard`、`reject:*`
+    #[arg(long = "event-filter")]
+    event_filters: Vec<String>,
+
+    /// TCP 兼容模式: 使用无版本字节的旧帧格式连接旧版服务端
+    #[arg(long)]
+    tcp_legacy_framing: bool,
+
+    /// 认证 token (服务端开启认证时必填)
+    #[arg(long)]
+    auth_token: Option<String>,
+
+    /// 使用 TLS 连接 (WebSocket 使用 wss://, TCP 使用 TLS);
+    /// 指定其他 TLS 参数时自动开启
+    #[arg(long)]
+    tls: bool,
+
+    /// 校验服务端证书的 CA 证书 (PEM), 未指定时使用内置根证书
+    #[arg(long)]
+    ca_cert: Option<PathBuf>,
+
+    /// 跳过服务端证书校验 (仅用于调试)
+    #[arg(long)]
+    insecure: bool,
+
+    /// 客户端证书 (PEM), 服务端开启客户端证书校验时使用; 需与私钥同时指定
+    #[arg(long)]
+    client_cert: Option<PathBuf>,
+
+    /// 客户端证书私钥 (PEM)
+    #[arg(long)]
+    client_key: Option<PathBuf>,
+
+    /// 工作模式: verify (等待事件并验证) 或 report (持续上报观察到的输入);
+    /// 运行中可由服务端的 `set_mode` 事件切换
+    #[arg(long, default_value = "verify")]
+    mode: AgentMode,
+
+    /// 上报队列容量, 服务端处理不过来时超出部分被丢弃并计数
+    #[arg(long, default_value = "1024")]
+    report_queue_size: usize,
+
+    /// 断线期间缓冲的验证结果数上限, 重连后补发; 满时丢弃最旧的结果, 0 表示不缓冲
+    #[arg(long, default_value = "256")]
+    result_buffer_size: usize,
+
+    /// 命令验证的超时 (秒), 超时后终止命令并回复验证失败; 键盘/鼠标验证固定为 10 秒
+    #[arg(long, default_value = "30")]
+    command_timeout: u64,
+
+    /// 同时执行的验证数上限; 键盘、鼠标等同类事件始终按到达顺序依次验证
+    #[arg(long, default_value = "4")]
+    parallelism: usize,
+
+    /// 后台运行: Windows 下作为服务运行 (由服务控制管理器启动);
+    /// Linux 下配合 systemd 使用, 初始连接失败时持续重试而不是退出
+    #[arg(long)]
+    daemon: bool,
+
+    /// 心跳间隔 (秒), 服务端据此判断 Agent 是否失联; 0 表示不发送心跳
+    #[arg(long, default_value = "30")]
+    heartbeat_interval: u64,
+
+    /// WebSocket 保活间隔 (秒): 定时发送 Ping, 连续 3 个间隔没有收到服务端消息时判定连接失效并重连;
+    /// 0 表示关闭保活
+    #[arg(long, default_value = "15")]
+    keepalive_interval: u64,
+
+    /// 日志文件 (追加写入), 未指定时输出到标准输出;
+    /// Windows 服务模式下默认写入程序所在目录的 verifier-agent.log
+    #[arg(long)]
+    log_file: Option<PathBuf>,
+}
+
+/// 子命令 (不指定时运行 Agent)
+#[derive(Subcommand, Debug)]
+enum AgentCommand {
+    /// 配置文件操作
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    /// Windows 服务管理 (需要管理员权限)
+    #[cfg(target_os = "windows")]
+    Service {
+        #[command(subcommand)]
+        action: service::ServiceAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// 校验配置文件 (与命令行参数合并后), 不连接服务器
+    Check,
+}
+
+impl Args {
+    /// 解析命令行参数并合并 `--config` 指定的配置文件, 同时返回配置文件中未知的键
+    ///
+    /// 优先级: 命令行参数 > 配置文件 > 内置默认值。
+    fn load() -> Result<(Self, Vec<String>)> {
+        let matches = Self::command().get_matches();
+        let mut args = Self::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+        let mut unknown_keys = Vec::new();
+        if let Some(path) = &args.config {
+            let config = AgentConfig::load(path)?;
+            unknown_keys = config.unknown_keys();
+            config.apply(&mut args, &matches)?;
+        }
+        args.validate()?;
+
+        Ok((args, unknown_keys))
+    }
+
+    /// 校验参数组合 (命令行与配置文件合并后)
+    fn validate(&self) -> Result<()> {
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            bail!("客户端证书与私钥必须同时指定 (client_cert / client_key)");
+        }
+        if self.insecure && self.ca_cert.is_some() {
+            bail!("insecure 跳过服务端证书校验, 不能同时指定 ca_cert");
+        }
+        let websocket_url = self.server.starts_with("ws://") || self.server.starts_with("wss://");
+        match self.transport {
+            TransportType::Tcp if websocket_url => {
+                bail!("TCP 传输不能使用 WebSocket 地址: {}", self.server)
+            }
+            TransportType::Websocket if self.tcp_legacy_framing => {
+                bail!("tcp_legacy_framing 只适用于 TCP 传输")
+            }
+            _ => {}
+        }
+        if self.auto_reconnect && self.reconnect_interval == 0 {
+            bail!("开启自动重连时重连间隔必须大于 0");
+        }
+        if self.command_timeout == 0 {
+            bail!("命令验证超时必须大于 0");
+        }
+        if self.parallelism == 0 {
+            bail!("验证并发数必须大于 0");
+        }
+        Ok(())
+    }
+
+    /// 根据命令行参数构建 TLS 配置, 未开启 TLS 时返回 None
+    fn tls_config(&self) -> Option<TlsClientConfig> {
+        let enabled = self.tls
+            || self.ca_cert.is_some()
+            || self.insecure
+            || self.client_cert.is_some()
+            || self.server.starts_with("wss://");
+        if !enabled {
+            return None;
+        }
+
+        let mut config = TlsClientConfig::new().with_insecure(self.insecure);
+        if let Some(ca_cert) = &self.ca_cert {
+            config = config.with_ca_cert(ca_cert);
+        }
+        if let (Some(cert), Some(key)) = (&self.client_cert, &self.client_key) {
+            config = config.with_client_cert(cert, key);
+        }
+        Some(config)
+    }
 }
 
 /// 验证器类型参数
@@ -134,6 +267,8 @@ enum VerifierTypeArg {
     Keyboard,
     Mouse,
     Command,
+    Clipboard,
+    Display,
     All,
 }
 
@@ -143,39 +278,75 @@ struct AgentState {
     transport: Arc<RwLock<Box<dyn VerifierTransport>>>,
     args: Args,
     vm_id: String, // 实际使用的 VM ID（自动检测或手动指定）
+    event_filter: EventFilter,
+    raw_input: RawInputQueue,
+    raw_input_rx: Mutex<RawInputReceiver>,
+    listeners_started: AtomicBool,
+    outbox: Mutex<ResultOutbox>,
+    /// 在工作任务中执行验证, 结果经 `results_rx` 交回事件循环发送
+    dispatcher: EventDispatcher,
+    results_rx: Mutex<mpsc::UnboundedReceiver<VerifyResult>>,
+    /// 优雅关闭信号, 同时传给正在执行的验证器
+    shutdown: CancellationToken,
+    /// 启动时间, 用于心跳上报运行时长
+    started_at: Instant,
+}
+
+/// 事件循环收到的消息
+enum Incoming {
+    Event(verifier_core::Result<Event>),
+    RawInput(RawInputEvent),
+    Result(VerifyResult),
+    Heartbeat,
 }
 
 impl AgentState {
     /// 创建新的 Agent 状态
-    async fn new(args: Args) -> Result<Self> {
+    async fn new(args: Args, shutdown: CancellationToken) -> Result<Self> {
         // 确定 VM ID（手动指定优先，否则自动检测）
-        let vm_id = match &args.vm_id {
-            Some(id) => {
-                info!("使用手动指定的 VM ID: {}", id);
-                id.clone()
-            }
+        let (vm_id, source) = match &args.vm_id {
+            Some(id) => (id.clone(), VmIdSource::Manual),
             None => {
                 info!("尝试自动获取 VM ID...");
-                auto_detect_vm_id().context("自动获取 VM ID 失败")?
+                VmIdDetector::new().detect().context("自动获取 VM ID 失败")?
             }
         };
 
-        info!("VM ID: {}", vm_id);
+        info!("VM ID: {} (来源: {})", vm_id, source);
+
+        let event_filter = EventFilter::parse(&args.event_filters).context("解析事件过滤规则失败")?;
+        if !event_filter.is_empty() {
+            info!("已加载 {} 条事件过滤规则", args.event_filters.len());
+        }
 
         // 创建传输层
+        let tls = args.tls_config();
+        if tls.is_some() {
+            info!("已启用 TLS");
+        }
         let transport: Box<dyn VerifierTransport> = match args.transport {
             TransportType::Websocket => {
                 info!("使用 WebSocket 传输");
-                Box::new(WebSocketTransport::new())
+                let mut transport = WebSocketTransport::new();
+                if args.keepalive_interval > 0 {
+                    let interval = Duration::from_secs(args.keepalive_interval);
+                    transport = transport.with_keepalive(interval, interval * KEEPALIVE_TIMEOUT_INTERVALS);
+                }
+                Box::new(match tls {
+                    Some(tls) => transport.with_tls(tls),
+                    None => transport,
+                })
             }
             TransportType::Tcp => {
                 info!("使用 TCP 传输");
-                Box::new(TcpTransport::new())
+                let transport = TcpTransport::new().with_legacy_framing(args.tcp_legacy_framing);
+                Box::new(match tls {
+                    Some(tls) => transport.with_tls(tls),
+                    None => transport,
+                })
             }
         };
 
-        let transport = Arc::new(RwLock::new(transport));
-
         // 创建验证器
         let mut verifiers: HashMap<VerifierType, Arc<dyn Verifier>> = HashMap::new();
 
@@ -186,6 +357,8 @@ impl AgentState {
                 VerifierTypeArg::Keyboard,
                 VerifierTypeArg::Mouse,
                 VerifierTypeArg::Command,
+                VerifierTypeArg::Clipboard,
+                VerifierTypeArg::Display,
             ]
         } else {
             args.verifiers.clone()
@@ -243,6 +416,7 @@ impl AgentState {
                 VerifierTypeArg::Command => {
                     match CommandVerifier::new() {
                         Ok(v) => {
+                            let v = v.with_timeout(std::time::Duration::from_secs(args.command_timeout));
                             info!("启用命令验证器");
                             verifiers.insert(VerifierType::Command, Arc::new(v));
                         }
@@ -251,6 +425,28 @@ impl AgentState {
                         }
                     }
                 }
+                VerifierTypeArg::Clipboard => {
+                    match ClipboardVerifier::new() {
+                        Ok(v) => {
+                            info!("启用剪贴板验证器");
+                            verifiers.insert(VerifierType::Clipboard, Arc::new(v));
+                        }
+                        Err(e) => {
+                            warn!("初始化剪贴板验证器失败: {}", e);
+                        }
+                    }
+                }
+                VerifierTypeArg::Display => {
+                    match DisplayVerifier::new() {
+                        Ok(v) => {
+                            info!("启用显示分辨率验证器");
+                            verifiers.insert(VerifierType::Display, Arc::new(v));
+                        }
+                        Err(e) => {
+                            warn!("初始化显示分辨率验证器失败: {}", e);
+                        }
+                    }
+                }
                 VerifierTypeArg::All => {
                     // 已在上面处理
                 }
@@ -263,34 +459,141 @@ impl AgentState {
 
         info!("已启用 {} 个验证器", verifiers.len());
 
-        Ok(Self {
+        Ok(Self::from_parts(args, vm_id, event_filter, transport, verifiers, shutdown))
+    }
+
+    /// 由已创建的传输层与验证器组装 Agent 状态
+    fn from_parts(
+        args: Args,
+        vm_id: String,
+        event_filter: EventFilter,
+        transport: Box<dyn VerifierTransport>,
+        verifiers: HashMap<VerifierType, Arc<dyn Verifier>>,
+        shutdown: CancellationToken,
+    ) -> Self {
+        let (raw_input, raw_input_rx) = RawInputQueue::bounded(args.report_queue_size);
+        let (dispatcher, results_rx) = EventDispatcher::new(args.parallelism, shutdown.clone());
+
+        Self {
             verifiers,
-            transport,
-            args,
+            transport: Arc::new(RwLock::new(transport)),
             vm_id,
-        })
+            event_filter,
+            raw_input,
+            raw_input_rx: Mutex::new(raw_input_rx),
+            listeners_started: AtomicBool::new(false),
+            outbox: Mutex::new(ResultOutbox::new(args.result_buffer_size)),
+            dispatcher,
+            results_rx: Mutex::new(results_rx),
+            shutdown,
+            started_at: Instant::now(),
+            args,
+        }
     }
 
     /// 连接到服务器
+    ///
+    /// 配置了认证 token 时直接以注册消息握手, 否则先发纯文本 VM ID 再补发注册消息 (兼容旧版服务端)。
     async fn connect(&self) -> Result<()> {
         let mut transport = self.transport.write().await;
+        let handshake_vm_id = match self.args.auth_token {
+            Some(_) => None,
+            None => Some(self.vm_id.as_str()),
+        };
         transport
-            .connect(&self.args.server, Some(&self.vm_id))
+            .connect(&self.args.server, handshake_vm_id)
             .await
             .context("连接到服务器失败")?;
+
+        let mut capabilities: Vec<String> = self
+            .verifiers
+            .keys()
+            .map(|verifier_type| verifier_type.event_type().to_string())
+            .collect();
+        capabilities.push(RAW_INPUT_CAPABILITY.to_string());
+        capabilities.sort();
+        let mut registration = RegisterMessage::new(&self.vm_id)
+            .with_agent_version(env!("CARGO_PKG_VERSION"))
+            .with_capabilities(capabilities);
+        if let Some(token) = &self.args.auth_token {
+            registration = registration.with_token(token);
+        }
+        transport
+            .register(&registration)
+            .await
+            .context("发送注册消息失败")?;
         info!("已连接到服务器: {}", self.args.server);
+
+        // 补发断线期间产生的验证结果
+        let mut outbox = self.outbox.lock().await;
+        if !outbox.is_empty() {
+            match outbox.flush(&mut **transport).await {
+                Ok(count) => info!("已补发 {} 条断线期间的验证结果", count),
+                Err(e) => warn!("补发验证结果失败, 剩余 {} 条待下次重连: {}", outbox.len(), e),
+            }
+        }
         Ok(())
     }
 
-    /// 处理事件
+    /// 守护进程模式下的初始连接: 失败时按重连间隔持续重试, 收到退出信号时返回 false
+    async fn connect_until_ready(&self) -> bool {
+        let interval = Duration::from_secs(self.args.reconnect_interval.max(1));
+        loop {
+            match self.connect().await {
+                Ok(()) => return true,
+                Err(e) => warn!("初始连接失败, {} 秒后重试: {:#}", interval.as_secs(), e),
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = self.shutdown.cancelled() => return false,
+            }
+        }
+    }
+
+    /// 发送心跳; 连接不可用时忽略, 由接收事件失败触发重连
+    ///
+    /// 最近一个心跳周期内已发送过其他消息时跳过, 服务端收到任何消息都会刷新活动时间。
+    async fn send_heartbeat(&self) {
+        let period = Duration::from_secs(self.args.heartbeat_interval);
+        let mut transport = self.transport.write().await;
+        if transport.last_activity().is_some_and(|at| at.elapsed() < period) {
+            debug!("最近已有上行消息, 跳过本次心跳");
+            return;
+        }
+
+        let heartbeat = HeartbeatMessage::new(&self.vm_id, self.started_at.elapsed().as_secs());
+        if let Err(e) = transport.send_heartbeat(&heartbeat).await {
+            debug!("发送心跳失败: {}", e);
+        }
+    }
+
+    /// 发送验证结果, 连接不可用时缓冲到重连后补发
+    async fn send_result(&self, result: VerifyResult) {
+        let mut transport = self.transport.write().await;
+        let mut outbox = self.outbox.lock().await;
+        if !outbox.send(&mut **transport, result).await {
+            warn!("连接不可用, 验证结果已缓冲 (待补发 {} 条)", outbox.len());
+        }
+    }
+
+    /// 处理事件: 过滤后交给对应验证器的工作任务, 不等待验证完成
     async fn handle_event(&self, event: Event) -> Result<()> {
         info!("收到事件: type={}", event.event_type);
 
+        // 被过滤的事件立即回复, 不占用验证器
+        if let FilterDecision::Reject(rule) = self.event_filter.decide(&event) {
+            info!("事件被过滤规则拒绝: {}", rule);
+            self.send_result(VerifyResult::filtered(&event, &rule)).await;
+            return Ok(());
+        }
+
         // 根据事件类型选择验证器
         let verifier = match event.event_type.as_str() {
             "keyboard" => self.verifiers.get(&VerifierType::Keyboard),
             "mouse" => self.verifiers.get(&VerifierType::Mouse),
             "command" => self.verifiers.get(&VerifierType::Command),
+            "clipboard" => self.verifiers.get(&VerifierType::Clipboard),
+            "display" => self.verifiers.get(&VerifierType::Display),
             _ => {
                 warn!("未知事件类型: {}", event.event_type);
                 None
@@ -298,107 +601,502 @@ impl AgentState {
         };
 
         if let Some(verifier) = verifier {
-            // 执行验证
-            match verifier.verify(event).await {
-                Ok(result) => {
-                    info!(
-                        "验证完成: verified={}, latency={}ms",
-                        result.verified, result.latency_ms
-                    );
-
-                    // 发送验证结果
-                    let mut transport = self.transport.write().await;
-                    transport
-                        .send_result(&result)
-                        .await
-                        .context("发送验证结果失败")?;
+            // 验证在工作任务中执行 (超时时生成验证失败的结果), 结果由事件循环发送
+            self.dispatcher.dispatch(verifier.clone(), event);
+        } else {
+            warn!("没有合适的验证器处理事件类型: {}", event.event_type);
+        }
+
+        Ok(())
+    }
+
+    /// 发送一条上报的输入事件, 失败时丢弃 (断线期间由重连逻辑处理)
+    async fn send_raw_input(&self, event: &RawInputEvent) {
+        if event.dropped > 0 {
+            warn!("上报队列已满, 丢弃了 {} 条输入事件", event.dropped);
+        }
+        let mut transport = self.transport.write().await;
+        if let Err(e) = transport.send_raw_input_event(event).await {
+            debug!("发送输入事件失败: {}", e);
+        }
+    }
+
+    /// 切换工作模式
+    ///
+    /// 首次进入上报模式时启动输入监听, 之后只切换上报开关;
+    /// 切回验证模式时先发送已入队的事件。
+    async fn switch_mode(&self, mode: AgentMode, raw_rx: &mut RawInputReceiver) -> Result<()> {
+        match mode {
+            AgentMode::Report => {
+                if !self.listeners_started.load(Ordering::Relaxed) {
+                    let count = input_report::start_listeners(&self.raw_input)
+                        .context("启动输入监听失败")?;
+                    self.listeners_started.store(true, Ordering::Relaxed);
+                    info!("已启动 {} 个输入监听", count);
                 }
-                Err(e) => {
-                    error!("验证失败: {}", e);
+                self.raw_input.set_enabled(true);
+            }
+            AgentMode::Verify => {
+                self.raw_input.set_enabled(false);
+                while let Some(event) = raw_rx.try_recv() {
+                    self.send_raw_input(&event).await;
                 }
             }
-        } else {
-            warn!("没有合适的验证器处理事件类型: {}", event.event_type);
         }
 
+        info!("工作模式: {}", mode);
         Ok(())
     }
 
     /// 运行事件循环
+    ///
+    /// 事件循环独占传输层: 同时等待服务端事件、验证结果与上报队列;
+    /// 接收事件可安全取消, 不会丢失半帧数据。
     async fn run(&self) -> Result<()> {
         info!("启动事件循环");
 
+        let mut raw_rx = self.raw_input_rx.lock().await;
+        let mut results_rx = self.results_rx.lock().await;
+        if self.args.mode == AgentMode::Report {
+            self.switch_mode(AgentMode::Report, &mut raw_rx).await?;
+        }
+
+        let heartbeat_enabled = self.args.heartbeat_interval > 0;
+        let period = Duration::from_secs(self.args.heartbeat_interval.max(1));
+        let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        heartbeat.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
         loop {
-            // 接收事件
-            let event = {
+            let incoming = {
                 let mut transport = self.transport.write().await;
-                match transport.receive_event().await {
-                    Ok(event) => event,
-                    Err(e) => {
-                        error!("接收事件失败: {}", e);
-
-                        // 如果启用了自动重连，尝试重连
-                        if self.args.auto_reconnect {
-                            warn!(
-                                "将在 {} 秒后尝试重连...",
-                                self.args.reconnect_interval
-                            );
-                            tokio::time::sleep(tokio::time::Duration::from_secs(
-                                self.args.reconnect_interval,
-                            ))
-                            .await;
-
-                            // 尝试重连
-                            drop(transport); // 释放锁
-                            if let Err(e) = self.connect().await {
-                                error!("重连失败: {}", e);
-                            }
-                            continue;
-                        } else {
-                            return Err(e.into());
+                tokio::select! {
+                    event = transport.receive_event() => Incoming::Event(event),
+                    Some(result) = results_rx.recv() => Incoming::Result(result),
+                    Some(raw) = raw_rx.recv() => Incoming::RawInput(raw),
+                    _ = heartbeat.tick(), if heartbeat_enabled => Incoming::Heartbeat,
+                    _ = self.shutdown.cancelled() => break,
+                }
+            };
+
+            // 接收事件
+            let event = match incoming {
+                Incoming::RawInput(raw) => {
+                    self.send_raw_input(&raw).await;
+                    continue;
+                }
+                Incoming::Result(result) => {
+                    self.send_result(result).await;
+                    continue;
+                }
+                Incoming::Heartbeat => {
+                    self.send_heartbeat().await;
+                    continue;
+                }
+                Incoming::Event(Ok(event)) => event,
+                Incoming::Event(Err(e)) => {
+                    error!("接收事件失败: {}", e);
+
+                    // 如果启用了自动重连，尝试重连
+                    if self.args.auto_reconnect {
+                        warn!(
+                            "将在 {} 秒后尝试重连...",
+                            self.args.reconnect_interval
+                        );
+                        let interval = tokio::time::Duration::from_secs(self.args.reconnect_interval);
+                        tokio::select! {
+                            _ = tokio::time::sleep(interval) => {}
+                            _ = self.shutdown.cancelled() => break,
                         }
+
+                        // 尝试重连
+                        if let Err(e) = self.connect().await {
+                            error!("重连失败: {}", e);
+                        }
+                        continue;
+                    } else {
+                        return Err(e.into());
                     }
                 }
             };
 
+            // 模式切换控制事件不经过过滤规则与验证器
+            if let Some(mode) = AgentMode::from_event(&event) {
+                match mode {
+                    Ok(mode) => {
+                        if let Err(e) = self.switch_mode(mode, &mut raw_rx).await {
+                            error!("切换工作模式失败: {:#}", e);
+                        }
+                    }
+                    Err(e) => warn!("忽略无效的模式切换事件: {}", e),
+                }
+                continue;
+            }
+
             // 处理事件
             if let Err(e) = self.handle_event(event).await {
                 error!("处理事件失败: {}", e);
             }
         }
+
+        Ok(())
+    }
+
+    /// 优雅关闭: 等待正在执行的验证结束, 尽量补发缓冲的验证结果后断开连接
+    async fn close(&self) {
+        self.dispatcher.drain().await;
+        let mut results_rx = self.results_rx.lock().await;
+        while let Ok(result) = results_rx.try_recv() {
+            self.send_result(result).await;
+        }
+
+        let mut transport = self.transport.write().await;
+        let mut outbox = self.outbox.lock().await;
+        if !outbox.is_empty() {
+            if let Err(e) = outbox.flush(&mut **transport).await {
+                warn!("关闭前补发验证结果失败, 丢弃 {} 条: {}", outbox.len(), e);
+            }
+        }
+        if let Err(e) = transport.disconnect().await {
+            debug!("断开连接失败: {}", e);
+        }
+        info!("Agent 已关闭");
+    }
+}
+
+fn main() -> Result<()> {
+    // 解析命令行参数 (合并配置文件)
+    let (args, unknown_keys) = Args::load()?;
+
+    match &args.command {
+        Some(AgentCommand::Config { action: ConfigAction::Check }) => {
+            return check_config(&args, &unknown_keys);
+        }
+        #[cfg(target_os = "windows")]
+        Some(AgentCommand::Service { action }) => return service::handle(action, &args),
+        None => {}
+    }
+
+    // Windows 服务: 由服务控制管理器驱动启动与停止
+    #[cfg(target_os = "windows")]
+    if args.daemon {
+        return service::run(args, unknown_keys);
     }
+
+    let shutdown = CancellationToken::new();
+    tokio::runtime::Runtime::new()
+        .context("创建异步运行时失败")?
+        .block_on(async {
+            // Ctrl-C / SIGTERM 触发优雅关闭: 事件循环退出, 正在执行的验证器收到取消信号
+            let signal = shutdown.clone();
+            tokio::spawn(async move {
+                daemon::shutdown_signal().await;
+                info!("收到退出信号, 正在关闭...");
+                signal.cancel();
+            });
+
+            run_agent(args, unknown_keys, shutdown).await
+        })
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    // 解析命令行参数
-    let args = Args::parse();
+/// 初始化日志, 配置了日志文件时写入文件 (追加) 而不是标准输出
+fn init_logging(args: &Args) -> Result<()> {
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+        format!("verifier_agent={},verifier_core={}", args.log_level, args.log_level).into()
+    });
+
+    let (stdout_layer, file_layer) = match &args.log_file {
+        Some(path) => {
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("打开日志文件失败: {}", path.display()))?;
+            let layer = tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(std::sync::Mutex::new(file));
+            (None, Some(layer))
+        }
+        None => (Some(tracing_subscriber::fmt::layer()), None),
+    };
 
-    // 初始化日志
-    let log_level = args.log_level.clone();
     tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| format!("verifier_agent={},verifier_core={}", log_level, log_level).into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
+        .with(filter)
+        .with(stdout_layer)
+        .with(file_layer)
         .init();
+    Ok(())
+}
+
+/// 运行 Agent 直到收到退出信号 (前台、systemd 与 Windows 服务共用)
+async fn run_agent(args: Args, unknown_keys: Vec<String>, shutdown: CancellationToken) -> Result<()> {
+    init_logging(&args)?;
 
     info!("启动 Guest 验证器 Agent");
+    if let Some(path) = &args.config {
+        info!("配置文件: {}", path.display());
+    }
+    for key in &unknown_keys {
+        warn!("忽略未知的配置项: {}", key);
+    }
     info!("服务器地址: {}", args.server);
     info!("传输类型: {:?}", args.transport);
     info!("启用的验证器: {:?}", args.verifiers);
+    info!("工作模式: {}", args.mode);
+    if args.heartbeat_interval > 0 {
+        info!("心跳间隔: {} 秒", args.heartbeat_interval);
+    }
+    if args.keepalive_interval > 0 && matches!(args.transport, TransportType::Websocket) {
+        info!("保活间隔: {} 秒", args.keepalive_interval);
+    }
 
     // 创建 Agent 状态
-    let state = AgentState::new(args)
+    let state = AgentState::new(args, shutdown)
         .await
         .context("创建 Agent 状态失败")?;
+    daemon::notify_ready(&format!("正在连接服务器 {}", state.args.server));
 
-    // 连接到服务器
-    state.connect().await.context("初始连接失败")?;
+    // 连接到服务器; 守护进程模式下持续重试, 不因服务端暂时不可用而退出
+    if state.args.daemon {
+        if !state.connect_until_ready().await {
+            daemon::notify_stopping();
+            info!("Agent 已关闭");
+            return Ok(());
+        }
+    } else {
+        state.connect().await.context("初始连接失败")?;
+    }
+    daemon::notify_status(&format!("已连接到服务器 {}", state.args.server));
 
     // 运行事件循环
-    state.run().await.context("事件循环异常退出")?;
+    let result = state.run().await.context("事件循环异常退出");
+    daemon::notify_stopping();
+    state.close().await;
+
+    result
+}
+
+/// `config check`: 输出合并后的配置, 不连接服务器
+fn check_config(args: &Args, unknown_keys: &[String]) -> Result<()> {
+    let path = args.config.as_ref().context("请使用 --config 指定配置文件")?;
+
+    println!("配置文件有效: {}", path.display());
+    for key in unknown_keys {
+        println!("  警告: 未知的配置项 {}", key);
+    }
+    println!("  服务器地址: {}", args.server);
+    println!("  传输类型: {:?}", args.transport);
+    match &args.vm_id {
+        Some(vm_id) => println!("  虚拟机 ID: {}", vm_id),
+        None => println!(
+            "  虚拟机 ID: (自动获取, 持久化文件 {})",
+            VmIdDetector::new().persist_path().display()
+        ),
+    }
+    if args.verifiers.is_empty() {
+        println!("  启用的验证器: (全部)");
+    } else {
+        println!("  启用的验证器: {:?}", args.verifiers);
+    }
+    println!("  工作模式: {}", args.mode);
+    println!("  日志级别: {}", args.log_level);
+    println!(
+        "  自动重连: {} (间隔 {} 秒)",
+        args.auto_reconnect, args.reconnect_interval
+    );
+    println!("  TLS: {}", if args.tls_config().is_some() { "开启" } else { "关闭" });
+    println!("  后台运行: {}", if args.daemon { "是" } else { "否" });
+    if args.heartbeat_interval > 0 {
+        println!("  心跳间隔: {} 秒", args.heartbeat_interval);
+    } else {
+        println!("  心跳: 关闭");
+    }
+    if args.keepalive_interval > 0 {
+        println!("  保活间隔: {} 秒 (仅 WebSocket)", args.keepalive_interval);
+    } else {
+        println!("  保活: 关闭");
+    }
+    match &args.log_file {
+        Some(path) => println!("  日志文件: {}", path.display()),
+        None => println!("  日志文件: (标准输出)"),
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use serde_json::json;
+    use verifier_core::VerifierError;
+
+    /// 从通道读取事件、把结果写入通道的传输层
+    struct MockTransport {
+        events: mpsc::UnboundedReceiver<Event>,
+        results: mpsc::UnboundedSender<VerifyResult>,
+    }
+
+    #[async_trait]
+    impl VerifierTransport for MockTransport {
+        async fn connect(&mut self, _endpoint: &str, _vm_id: Option<&str>) -> verifier_core::Result<()> {
+            Ok(())
+        }
+
+        async fn register(&mut self, _registration: &RegisterMessage) -> verifier_core::Result<()> {
+            Ok(())
+        }
+
+        async fn send_result(&mut self, result: &VerifyResult) -> verifier_core::Result<()> {
+            let _ = self.results.send(result.clone());
+            Ok(())
+        }
+
+        async fn send_raw_input_event(&mut self, _event: &RawInputEvent) -> verifier_core::Result<()> {
+            Ok(())
+        }
+
+        async fn send_heartbeat(&mut self, _heartbeat: &HeartbeatMessage) -> verifier_core::Result<()> {
+            Ok(())
+        }
+
+        async fn receive_event(&mut self) -> verifier_core::Result<Event> {
+            match self.events.recv().await {
+                Some(event) => Ok(event),
+                None => std::future::pending().await,
+            }
+        }
+
+        async fn disconnect(&mut self) -> verifier_core::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// 等待事件中的 `delay_ms` 后验证成功
+    struct DelayVerifier(VerifierType);
+
+    #[async_trait]
+    impl Verifier for DelayVerifier {
+        async fn verify(&self, event: Event, cancel: CancellationToken) -> verifier_core::Result<VerifyResult> {
+            let delay_ms = event.data["delay_ms"].as_u64().unwrap_or(0);
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(delay_ms)) => {}
+                _ = cancel.cancelled() => return Err(VerifierError::Cancelled),
+            }
+            Ok(VerifyResult {
+                event_id: event.data["event_id"].as_str().unwrap_or_default().to_string(),
+                verified: true,
+                timestamp: event.timestamp,
+                latency_ms: delay_ms,
+                details: json!({}),
+            })
+        }
+
+        fn verifier_type(&self) -> VerifierType {
+            self.0.clone()
+        }
+    }
+
+    fn event(event_type: &str, event_id: &str, delay_ms: u64) -> Event {
+        Event {
+            event_type: event_type.to_string(),
+            data: json!({"event_id": event_id, "delay_ms": delay_ms}),
+            timestamp: 0,
+        }
+    }
+
+    /// 以模拟传输层启动事件循环, 返回 Agent 状态、事件发送端与结果接收端
+    fn start_agent(
+        parallelism: usize,
+    ) -> (Arc<AgentState>, mpsc::UnboundedSender<Event>, mpsc::UnboundedReceiver<VerifyResult>) {
+        let (event_tx, events) = mpsc::unbounded_channel();
+        let (results, results_rx) = mpsc::unbounded_channel();
+        let args = Args::try_parse_from([
+            "verifier-agent",
+            "--heartbeat-interval",
+            "0",
+            "--parallelism",
+            &parallelism.to_string(),
+        ])
+        .unwrap();
+        let verifiers = [VerifierType::Keyboard, VerifierType::Command]
+            .into_iter()
+            .map(|t| (t.clone(), Arc::new(DelayVerifier(t)) as Arc<dyn Verifier>))
+            .collect();
+
+        let state = Arc::new(AgentState::from_parts(
+            args,
+            "vm-test".to_string(),
+            EventFilter::default(),
+            Box::new(MockTransport { events, results }),
+            verifiers,
+            CancellationToken::new(),
+        ));
+        let running = state.clone();
+        tokio::spawn(async move { running.run().await });
+
+        (state, event_tx, results_rx)
+    }
+
+    async fn next_id(results: &mut mpsc::UnboundedReceiver<VerifyResult>) -> String {
+        tokio::time::timeout(Duration::from_secs(2), results.recv())
+            .await
+            .expect("等待验证结果超时")
+            .unwrap()
+            .event_id
+    }
+
+    #[tokio::test]
+    async fn test_slow_command_does_not_delay_keyboard() {
+        let (state, events, mut results) = start_agent(4);
+
+        let started = Instant::now();
+        events.send(event("command", "cmd-1", 5_000)).unwrap();
+        events.send(event("keyboard", "key-1", 10)).unwrap();
+
+        // 键盘结果不必等待排在前面的命令验证
+        assert_eq!(next_id(&mut results).await, "key-1");
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // 关闭时取消仍在执行的命令验证
+        state.shutdown.cancel();
+        tokio::time::timeout(Duration::from_secs(2), state.close()).await.unwrap();
+        assert!(results.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_same_type_events_verified_in_order() {
+        let (state, events, mut results) = start_agent(4);
+
+        // 同类事件串行: 先到的慢事件先出结果
+        events.send(event("keyboard", "key-1", 200)).unwrap();
+        events.send(event("keyboard", "key-2", 10)).unwrap();
+        // 命令验证并发执行
+        events.send(event("command", "cmd-1", 300)).unwrap();
+        events.send(event("command", "cmd-2", 50)).unwrap();
+
+        let mut ids = Vec::new();
+        for _ in 0..4 {
+            ids.push(next_id(&mut results).await);
+        }
+        assert_eq!(ids, ["cmd-2", "key-1", "key-2", "cmd-1"]);
+
+        state.shutdown.cancel();
+        state.close().await;
+    }
+
+    #[tokio::test]
+    async fn test_parallelism_limits_concurrent_verifies() {
+        let (state, events, mut results) = start_agent(1);
+
+        // 只有一个工作位时, 慢的命令验证占满后键盘事件需要排队
+        events.send(event("command", "cmd-1", 200)).unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        events.send(event("keyboard", "key-1", 10)).unwrap();
+
+        assert_eq!(next_id(&mut results).await, "cmd-1");
+        assert_eq!(next_id(&mut results).await, "key-1");
+
+        state.shutdown.cancel();
+        state.close().await;
+    }
+}
@@ -0,0 +1,195 @@
+//! Windows 服务模式
+//!
+//! `verifier-agent service install` 把当前程序注册为开机自启的服务,
+//! 服务以 `--daemon` 启动并由服务控制管理器驱动停止。服务没有控制台,
+//! 日志写入 `--log-file` (默认为程序所在目录的 verifier-agent.log)。
+
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use std::ffi::OsString;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use windows_service::service::{
+    ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+use crate::{run_agent, Args};
+
+/// 服务名
+const SERVICE_NAME: &str = "AtpVerifierAgent";
+
+const SERVICE_DISPLAY_NAME: &str = "ATP Guest Verifier Agent";
+
+const SERVICE_DESCRIPTION: &str = "ATP Guest 验证器 Agent, 验证 Guest 内的键盘、鼠标、命令等输入输出";
+
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+/// 服务管理操作
+#[derive(Subcommand, Debug)]
+pub enum ServiceAction {
+    /// 注册服务 (开机自启), 当前的 `--config` 会写入服务的启动参数
+    Install,
+    /// 停止并删除服务
+    Uninstall,
+    /// 启动服务
+    Start,
+    /// 停止服务
+    Stop,
+}
+
+/// 由 `run` 传给服务入口的参数 (服务入口由服务控制管理器回调, 无法直接传参)
+static SERVICE_ARGS: Mutex<Option<(Args, Vec<String>)>> = Mutex::new(None);
+
+define_windows_service!(ffi_service_main, service_main);
+
+/// 以服务方式运行, 只能由服务控制管理器启动
+pub fn run(mut args: Args, unknown_keys: Vec<String>) -> Result<()> {
+    if args.log_file.is_none() {
+        args.log_file = Some(default_log_file()?);
+    }
+    *SERVICE_ARGS.lock().unwrap() = Some((args, unknown_keys));
+
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+        .context("启动服务失败 (--daemon 只能由服务控制管理器启动, 请使用 service start)")
+}
+
+/// 默认日志文件: 程序所在目录的 verifier-agent.log
+fn default_log_file() -> Result<PathBuf> {
+    let exe = std::env::current_exe().context("获取程序路径失败")?;
+    Ok(exe.with_file_name("verifier-agent.log"))
+}
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        tracing::error!("服务异常退出: {:#}", e);
+    }
+}
+
+fn run_service() -> Result<()> {
+    let (args, unknown_keys) = SERVICE_ARGS.lock().unwrap().take().context("服务参数未初始化")?;
+
+    let shutdown = CancellationToken::new();
+    let stop = shutdown.clone();
+    let status_handle = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            stop.cancel();
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })
+    .context("注册服务控制处理器失败")?;
+
+    let set_status = |state: ServiceState, controls_accepted: ServiceControlAccept, exit_code: u32| {
+        status_handle.set_service_status(ServiceStatus {
+            service_type: SERVICE_TYPE,
+            current_state: state,
+            controls_accepted,
+            exit_code: ServiceExitCode::Win32(exit_code),
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        })
+    };
+
+    set_status(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        0,
+    )?;
+
+    let result = tokio::runtime::Runtime::new()
+        .context("创建异步运行时失败")
+        .and_then(|runtime| runtime.block_on(run_agent(args, unknown_keys, shutdown)));
+
+    set_status(
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        if result.is_ok() { 0 } else { 1 },
+    )?;
+    result
+}
+
+/// 执行服务管理操作
+pub fn handle(action: &ServiceAction, args: &Args) -> Result<()> {
+    match action {
+        ServiceAction::Install => install(args),
+        ServiceAction::Uninstall => uninstall(),
+        ServiceAction::Start => {
+            let service = open_manager(ServiceManagerAccess::CONNECT)?
+                .open_service(SERVICE_NAME, ServiceAccess::START)
+                .context("打开服务失败, 请先执行 service install")?;
+            service.start::<&str>(&[]).context("启动服务失败")?;
+            println!("服务已启动: {}", SERVICE_NAME);
+            Ok(())
+        }
+        ServiceAction::Stop => {
+            let service = open_manager(ServiceManagerAccess::CONNECT)?
+                .open_service(SERVICE_NAME, ServiceAccess::STOP)
+                .context("打开服务失败")?;
+            service.stop().context("停止服务失败")?;
+            println!("服务已停止: {}", SERVICE_NAME);
+            Ok(())
+        }
+    }
+}
+
+fn open_manager(access: ServiceManagerAccess) -> Result<ServiceManager> {
+    ServiceManager::local_computer(None::<&str>, access).context("连接服务控制管理器失败 (需要管理员权限)")
+}
+
+fn install(args: &Args) -> Result<()> {
+    let mut launch_arguments = vec![OsString::from("--daemon")];
+    if let Some(config) = &args.config {
+        let config = std::fs::canonicalize(config)
+            .with_context(|| format!("配置文件不存在: {}", config.display()))?;
+        launch_arguments.push(OsString::from("--config"));
+        launch_arguments.push(config.into_os_string());
+    }
+
+    let info = ServiceInfo {
+        name: OsString::from(SERVICE_NAME),
+        display_name: OsString::from(SERVICE_DISPLAY_NAME),
+        service_type: SERVICE_TYPE,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe().context("获取程序路径失败")?,
+        launch_arguments,
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+
+    let manager = open_manager(ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE)?;
+    let service = manager
+        .create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .context("注册服务失败")?;
+    service.set_description(SERVICE_DESCRIPTION).context("设置服务描述失败")?;
+
+    println!("服务已注册: {}", SERVICE_NAME);
+    println!("  启动参数: {:?}", info.launch_arguments);
+    Ok(())
+}
+
+fn uninstall() -> Result<()> {
+    let service = open_manager(ServiceManagerAccess::CONNECT)?
+        .open_service(
+            SERVICE_NAME,
+            ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+        )
+        .context("打开服务失败")?;
+
+    if service.query_status().context("查询服务状态失败")?.current_state != ServiceState::Stopped {
+        service.stop().context("停止服务失败")?;
+    }
+    service.delete().context("删除服务失败")?;
+
+    println!("服务已删除: {}", SERVICE_NAME);
+    Ok(())
+}
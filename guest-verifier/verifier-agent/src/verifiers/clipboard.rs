@@ -0,0 +1,316 @@
+//! 剪贴板验证器实现
+//!
+//! 用于 SPICE 剪贴板共享的复制/粘贴往返测试: 宿主侧写入剪贴板后,
+//! 在 Guest 内轮询剪贴板直到出现预期文本。
+//!
+//! - Linux: 依次尝试 `wl-paste` (Wayland)、`xclip`、`xsel`
+//! - Windows: 剪贴板 API (`CF_UNICODETEXT`)
+
+use async_trait::async_trait;
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+use verifier_core::{
+    CancellationToken, Event, Result, Verifier, VerifierError, VerifierType, VerifyResult,
+};
+
+/// 剪贴板轮询间隔
+const POLL_INTERVAL_MS: u64 = 200;
+
+/// 验证失败时写入结果详情的剪贴板内容长度上限 (字符)
+const ACTUAL_TEXT_LIMIT: usize = 256;
+
+/// 剪贴板验证器
+pub struct ClipboardVerifier {
+    reader: platform::ClipboardReader,
+}
+
+impl ClipboardVerifier {
+    /// 创建新的剪贴板验证器, 找不到可用的剪贴板读取方式时返回错误
+    pub fn new() -> Result<Self> {
+        info!("初始化剪贴板验证器");
+        let reader = platform::ClipboardReader::detect()?;
+        info!("剪贴板读取方式: {}", reader.method());
+        Ok(Self { reader })
+    }
+
+    /// 轮询剪贴板直到出现预期文本, 返回是否匹配与最后一次读取的内容
+    async fn wait_for_text(
+        &self,
+        expected: &str,
+        timeout_ms: u64,
+        cancel: &CancellationToken,
+    ) -> Result<(bool, ClipboardRead)> {
+        let timeout = tokio::time::Duration::from_millis(timeout_ms);
+        let start_time = tokio::time::Instant::now();
+
+        debug!("等待剪贴板内容 (超时: {}ms)", timeout_ms);
+
+        loop {
+            let read = match self.reader.read().await {
+                Ok(text) => ClipboardRead::Text(text),
+                Err(e) => ClipboardRead::Error(e.to_string()),
+            };
+
+            if let ClipboardRead::Text(text) = &read {
+                if text_matches(text, expected) {
+                    info!("剪贴板内容与预期一致");
+                    return Ok((true, read));
+                }
+            }
+
+            // 检查超时
+            if start_time.elapsed() > timeout {
+                debug!("等待超时");
+                return Ok((false, read));
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_millis(POLL_INTERVAL_MS)) => {}
+                _ = cancel.cancelled() => return Err(VerifierError::Cancelled),
+            }
+        }
+    }
+}
+
+/// 一次剪贴板读取的结果
+enum ClipboardRead {
+    Text(String),
+    Error(String),
+}
+
+/// 剪贴板内容是否与预期一致 (忽略读取工具追加的行尾换行)
+fn text_matches(actual: &str, expected: &str) -> bool {
+    actual == expected || actual.strip_suffix("\r\n").or_else(|| actual.strip_suffix('\n')) == Some(expected)
+}
+
+/// 截断过长的剪贴板内容
+fn truncate_text(text: &str) -> String {
+    match text.char_indices().nth(ACTUAL_TEXT_LIMIT) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+#[async_trait]
+impl Verifier for ClipboardVerifier {
+    async fn verify(&self, event: Event, cancel: CancellationToken) -> Result<VerifyResult> {
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+
+        debug!("验证剪贴板事件: {:?}", event);
+
+        // 从事件数据中提取预期文本
+        let expected = event
+            .data
+            .get("expect_text")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| {
+                VerifierError::VerificationFailed("事件缺少 expect_text 字段".to_string())
+            })?;
+
+        // 获取超时时间（默认 5000ms）
+        let timeout_ms = event
+            .data
+            .get("timeout_ms")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(5000);
+
+        let (verified, last_read) = self.wait_for_text(expected, timeout_ms, &cancel).await?;
+
+        let end_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+
+        let latency_ms = (end_time - start_time) as u64;
+
+        let mut details = json!({
+            "expect_text": expected,
+            "platform": std::env::consts::OS,
+            "method": self.reader.method(),
+        });
+        if !verified {
+            match last_read {
+                ClipboardRead::Text(text) => {
+                    details["actual_text"] = json!(truncate_text(&text));
+                    details["actual_length"] = json!(text.chars().count());
+                }
+                ClipboardRead::Error(e) => details["error"] = json!(e),
+            }
+        }
+
+        Ok(VerifyResult {
+            event_id: event
+                .data
+                .get("event_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            verified,
+            timestamp: end_time,
+            latency_ms,
+            details,
+        })
+    }
+
+    fn verifier_type(&self) -> VerifierType {
+        VerifierType::Clipboard
+    }
+}
+
+// ===== Linux 实现 (命令行工具) =====
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use crate::verifiers::is_installed;
+    use std::process::Stdio;
+    use tokio::process::Command;
+    use tracing::debug;
+    use verifier_core::{Result, VerifierError};
+
+    /// 候选读取工具: (名称, 参数), Wayland 会话优先 wl-paste
+    const TOOLS: &[(&str, &[&str])] = &[
+        ("wl-paste", &["--no-newline"]),
+        ("xclip", &["-selection", "clipboard", "-o"]),
+        ("xsel", &["--clipboard", "--output"]),
+    ];
+
+    pub struct ClipboardReader {
+        program: &'static str,
+        args: &'static [&'static str],
+    }
+
+    impl ClipboardReader {
+        /// 按会话类型选择已安装的读取工具
+        pub fn detect() -> Result<Self> {
+            let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+            let x11 = std::env::var_os("DISPLAY").is_some();
+
+            TOOLS
+                .iter()
+                .filter(|(program, _)| if *program == "wl-paste" { wayland } else { x11 || !wayland })
+                .find(|(program, _)| is_installed(program))
+                .map(|(program, args)| Self { program, args })
+                .ok_or_else(|| {
+                    VerifierError::ConfigError(
+                        "未找到剪贴板读取工具 (需要 wl-paste、xclip 或 xsel)".to_string(),
+                    )
+                })
+        }
+
+        pub fn method(&self) -> &'static str {
+            self.program
+        }
+
+        /// 读取剪贴板文本
+        pub async fn read(&self) -> Result<String> {
+            let output = Command::new(self.program)
+                .args(self.args)
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await?;
+
+            if !output.status.success() {
+                // 剪贴板为空时部分工具返回非零退出码
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                debug!("{} 读取剪贴板失败: {}", self.program, stderr.trim());
+                return Err(VerifierError::VerificationFailed(format!(
+                    "{} 退出码 {:?}: {}",
+                    self.program,
+                    output.status.code(),
+                    stderr.trim()
+                )));
+            }
+
+            Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        }
+    }
+}
+
+// ===== Windows 实现 (剪贴板 API) =====
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use verifier_core::{Result, VerifierError};
+    use windows::Win32::Foundation::{HGLOBAL, HWND};
+    use windows::Win32::System::DataExchange::{CloseClipboard, GetClipboardData, OpenClipboard};
+    use windows::Win32::System::Memory::{GlobalLock, GlobalUnlock};
+    use windows::Win32::System::Ole::CF_UNICODETEXT;
+
+    pub struct ClipboardReader;
+
+    impl ClipboardReader {
+        pub fn detect() -> Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn method(&self) -> &'static str {
+            "clipboard_api"
+        }
+
+        /// 读取剪贴板文本 (剪贴板 API 为同步调用, 放到阻塞线程执行)
+        pub async fn read(&self) -> Result<String> {
+            tokio::task::spawn_blocking(read_unicode_text)
+                .await
+                .map_err(|e| VerifierError::VerificationFailed(format!("读取剪贴板失败: {}", e)))?
+        }
+    }
+
+    fn read_unicode_text() -> Result<String> {
+        unsafe {
+            OpenClipboard(HWND::default())
+                .map_err(|e| VerifierError::VerificationFailed(format!("打开剪贴板失败: {}", e)))?;
+
+            let text = (|| {
+                let handle = GetClipboardData(CF_UNICODETEXT.0 as u32)
+                    .map_err(|e| VerifierError::VerificationFailed(format!("剪贴板中没有文本: {}", e)))?;
+                let memory = HGLOBAL(handle.0 as _);
+                let ptr = GlobalLock(memory) as *const u16;
+                if ptr.is_null() {
+                    return Err(VerifierError::VerificationFailed("锁定剪贴板数据失败".to_string()));
+                }
+
+                let mut len = 0;
+                while *ptr.add(len) != 0 {
+                    len += 1;
+                }
+                let text = String::from_utf16_lossy(std::slice::from_raw_parts(ptr, len));
+                let _ = GlobalUnlock(memory);
+                Ok(text)
+            })();
+
+            let _ = CloseClipboard();
+            text
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_matches() {
+        assert!(text_matches("hello", "hello"));
+        assert!(text_matches("hello\n", "hello"));
+        assert!(text_matches("hello\r\n", "hello"));
+        assert!(text_matches("line1\nline2", "line1\nline2"));
+        assert!(!text_matches("hello world", "hello"));
+        assert!(!text_matches("", "hello"));
+        assert!(!text_matches("hello\n\n", "hello"));
+    }
+
+    #[test]
+    fn test_truncate_text() {
+        assert_eq!(truncate_text("short"), "short");
+
+        let long = "剪".repeat(ACTUAL_TEXT_LIMIT + 10);
+        let truncated = truncate_text(&long);
+        assert!(truncated.ends_with("..."));
+        assert_eq!(truncated.chars().count(), ACTUAL_TEXT_LIMIT + 3);
+    }
+}
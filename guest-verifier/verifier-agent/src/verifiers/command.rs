@@ -3,20 +3,35 @@
 use async_trait::async_trait;
 use serde_json::json;
 use std::process::Stdio;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 use tracing::{debug, error, info};
-use verifier_core::{Event, Result, Verifier, VerifierError, VerifierType, VerifyResult};
+use verifier_core::{
+    CancellationToken, Event, Result, Verifier, VerifierError, VerifierType, VerifyResult,
+};
+
+/// 命令验证的默认超时 (命令可能比输入事件耗时更久)
+const DEFAULT_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// 命令执行验证器
-pub struct CommandVerifier {}
+pub struct CommandVerifier {
+    timeout: Duration,
+}
 
 impl CommandVerifier {
     /// 创建新的命令验证器
     pub fn new() -> Result<Self> {
         info!("初始化命令验证器");
-        Ok(Self {})
+        Ok(Self {
+            timeout: DEFAULT_COMMAND_TIMEOUT,
+        })
+    }
+
+    /// 设置单次验证的超时, 超时或取消时命令进程被终止
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
     }
 
     /// 执行命令并获取输出
@@ -27,7 +42,8 @@ impl CommandVerifier {
         command
             .args(args)
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
         let start_time = tokio::time::Instant::now();
 
@@ -145,7 +161,7 @@ struct CommandExpectation {
 
 #[async_trait]
 impl Verifier for CommandVerifier {
-    async fn verify(&self, event: Event) -> Result<VerifyResult> {
+    async fn verify(&self, event: Event, cancel: CancellationToken) -> Result<VerifyResult> {
         let start_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap()
@@ -193,8 +209,11 @@ impl Verifier for CommandVerifier {
                 .map(|s| s.to_string()),
         };
 
-        // 执行命令
-        let result = self.execute_command(command, &args).await?;
+        // 执行命令, Agent 关闭时终止
+        let result = tokio::select! {
+            result = self.execute_command(command, &args) => result?,
+            _ = cancel.cancelled() => return Err(VerifierError::Cancelled),
+        };
 
         // 验证结果
         let verified = self.verify_result(&result, &expectation);
@@ -235,6 +254,10 @@ impl Verifier for CommandVerifier {
     fn verifier_type(&self) -> VerifierType {
         VerifierType::Command
     }
+
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
 }
 
 #[cfg(test)]
@@ -260,4 +283,41 @@ mod tests {
         assert_eq!(result.exit_code, 0);
         assert!(result.stdout.contains("hello"));
     }
+
+    fn sleep_event() -> Event {
+        Event {
+            event_type: "command".to_string(),
+            data: json!({"event_id": "e1", "command": "sleep", "args": ["5"]}),
+            timestamp: 0,
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_timeout() {
+        let verifier = CommandVerifier::new()
+            .unwrap()
+            .with_timeout(Duration::from_millis(200));
+
+        let started = std::time::Instant::now();
+        let result = verifier_core::verify_with_timeout(&verifier, sleep_event(), CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(result.event_id, "e1");
+        assert!(!result.verified);
+        assert_eq!(result.details["reason"], "verifier timeout");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_command_cancelled() {
+        let verifier = CommandVerifier::new().unwrap();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+
+        let result = verifier.verify(sleep_event(), cancel).await;
+        assert!(matches!(result, Err(VerifierError::Cancelled)));
+    }
 }
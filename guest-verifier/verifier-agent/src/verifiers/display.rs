@@ -0,0 +1,390 @@
+//! 显示分辨率验证器实现
+//!
+//! 用于 VDI 客户端窗口缩放后的分辨率跟随测试: 宿主侧调整客户端窗口后,
+//! 在 Guest 内轮询当前分辨率直到与预期一致, 并记录收敛耗时。
+//!
+//! - Linux: Wayland 会话优先 `wlr-randr`, 否则 `xrandr`
+//! - Windows: `EnumDisplaySettingsW` (主显示器当前模式)
+
+use async_trait::async_trait;
+use serde_json::json;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info};
+use verifier_core::{
+    CancellationToken, Event, Result, Verifier, VerifierError, VerifierType, VerifyResult,
+};
+
+/// 分辨率轮询间隔
+const POLL_INTERVAL_MS: u64 = 250;
+
+/// 默认超时 (客户端缩放后 Guest 内的 spice-vdagent 需要时间调整分辨率)
+const DEFAULT_TIMEOUT_MS: u64 = 10000;
+
+/// 验证器整体超时, 事件的 `timeout_ms` 应小于该值
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// 显示分辨率
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// 显示分辨率验证器
+pub struct DisplayVerifier {
+    reader: platform::ResolutionReader,
+}
+
+impl DisplayVerifier {
+    /// 创建新的显示分辨率验证器, 找不到可用的查询方式时返回错误
+    pub fn new() -> Result<Self> {
+        info!("初始化显示分辨率验证器");
+        let reader = platform::ResolutionReader::detect()?;
+        info!("分辨率查询方式: {}", reader.method());
+        Ok(Self { reader })
+    }
+
+    /// 轮询分辨率直到与预期一致, 返回是否一致、最后一次查询结果与收敛耗时
+    async fn wait_for_resolution(
+        &self,
+        expected: Resolution,
+        timeout_ms: u64,
+        cancel: &CancellationToken,
+    ) -> Result<(bool, std::result::Result<Resolution, String>, u64)> {
+        let timeout = tokio::time::Duration::from_millis(timeout_ms);
+        let start_time = tokio::time::Instant::now();
+
+        debug!("等待分辨率变为 {} (超时: {}ms)", expected, timeout_ms);
+
+        loop {
+            let read = self.reader.read().await.map_err(|e| e.to_string());
+            let elapsed_ms = start_time.elapsed().as_millis() as u64;
+
+            if read.as_ref() == Ok(&expected) {
+                info!("分辨率已变为 {} (耗时 {}ms)", expected, elapsed_ms);
+                return Ok((true, read, elapsed_ms));
+            }
+
+            // 检查超时
+            if start_time.elapsed() > timeout {
+                debug!("等待超时");
+                return Ok((false, read, elapsed_ms));
+            }
+
+            tokio::select! {
+                _ = tokio::time::sleep(tokio::time::Duration::from_millis(POLL_INTERVAL_MS)) => {}
+                _ = cancel.cancelled() => return Err(VerifierError::Cancelled),
+            }
+        }
+    }
+}
+
+/// 从事件数据中读取预期的宽或高
+fn expected_dimension(event: &Event, field: &str) -> Result<u32> {
+    event
+        .data
+        .get(field)
+        .and_then(|v| v.as_u64())
+        .and_then(|v| u32::try_from(v).ok())
+        .filter(|v| *v > 0)
+        .ok_or_else(|| VerifierError::VerificationFailed(format!("事件缺少有效的 {} 字段", field)))
+}
+
+/// 解析 `xrandr --current` 的输出
+///
+/// 优先取主显示器 (`connected primary 1920x1080+0+0`), 其次第一个已启用的显示器,
+/// 都没有时取屏幕的 `current 1920 x 1080`。
+#[cfg(any(target_os = "linux", test))]
+fn parse_xrandr(output: &str) -> Option<Resolution> {
+    let connected_mode = |line: &str| {
+        line.split_whitespace()
+            .find_map(|word| word.split_once('+').and_then(|(mode, _)| parse_mode(mode)))
+    };
+
+    let connected: Vec<&str> = output.lines().filter(|line| line.contains(" connected")).collect();
+    connected
+        .iter()
+        .filter(|line| line.contains(" connected primary"))
+        .chain(connected.iter())
+        .find_map(|line| connected_mode(line))
+        .or_else(|| {
+            let current = output.lines().next()?.split(", ").find_map(|part| part.strip_prefix("current "))?;
+            let (width, height) = current.split_once(" x ")?;
+            Some(Resolution {
+                width: width.trim().parse().ok()?,
+                height: height.trim().parse().ok()?,
+            })
+        })
+}
+
+/// 解析 `wlr-randr` 的输出: 第一个已启用输出的当前模式 (`1920x1080 px, 60.000000 Hz (current)`)
+#[cfg(any(target_os = "linux", test))]
+fn parse_wlr_randr(output: &str) -> Option<Resolution> {
+    let mut enabled = true;
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if !line.starts_with(' ') {
+            // 新的输出段
+            enabled = true;
+        } else if let Some(value) = trimmed.strip_prefix("Enabled:") {
+            enabled = value.trim() == "yes";
+        } else if enabled && trimmed.contains("current") {
+            if let Some(mode) = trimmed.split_whitespace().next().and_then(parse_mode) {
+                return Some(mode);
+            }
+        }
+    }
+    None
+}
+
+/// 解析 `1920x1080` 形式的模式
+#[cfg(any(target_os = "linux", test))]
+fn parse_mode(mode: &str) -> Option<Resolution> {
+    let (width, height) = mode.split_once('x')?;
+    Some(Resolution {
+        width: width.parse().ok()?,
+        height: height.parse().ok()?,
+    })
+}
+
+#[async_trait]
+impl Verifier for DisplayVerifier {
+    async fn verify(&self, event: Event, cancel: CancellationToken) -> Result<VerifyResult> {
+        let start_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+
+        debug!("验证显示事件: {:?}", event);
+
+        // 从事件数据中提取预期分辨率
+        let expected = Resolution {
+            width: expected_dimension(&event, "expect_width")?,
+            height: expected_dimension(&event, "expect_height")?,
+        };
+
+        // 获取超时时间（默认 10000ms）
+        let timeout_ms = event
+            .data
+            .get("timeout_ms")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_TIMEOUT_MS);
+
+        let (verified, last_read, elapsed_ms) =
+            self.wait_for_resolution(expected, timeout_ms, &cancel).await?;
+
+        let end_time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+
+        let latency_ms = (end_time - start_time) as u64;
+
+        let mut details = json!({
+            "expect_width": expected.width,
+            "expect_height": expected.height,
+            "platform": std::env::consts::OS,
+            "method": self.reader.method(),
+        });
+        match last_read {
+            Ok(resolution) => {
+                details["width"] = json!(resolution.width);
+                details["height"] = json!(resolution.height);
+            }
+            Err(e) => details["error"] = json!(e),
+        }
+        if verified {
+            details["converge_ms"] = json!(elapsed_ms);
+        }
+
+        Ok(VerifyResult {
+            event_id: event
+                .data
+                .get("event_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            verified,
+            timestamp: end_time,
+            latency_ms,
+            details,
+        })
+    }
+
+    fn verifier_type(&self) -> VerifierType {
+        VerifierType::Display
+    }
+
+    fn timeout(&self) -> Duration {
+        VERIFY_TIMEOUT
+    }
+}
+
+// ===== Linux 实现 (命令行工具) =====
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use super::{parse_wlr_randr, parse_xrandr, Resolution};
+    use crate::verifiers::is_installed;
+    use std::process::Stdio;
+    use tokio::process::Command;
+    use verifier_core::{Result, VerifierError};
+
+    pub struct ResolutionReader {
+        program: &'static str,
+        args: &'static [&'static str],
+        parse: fn(&str) -> Option<Resolution>,
+    }
+
+    impl ResolutionReader {
+        /// 按会话类型选择已安装的查询工具
+        pub fn detect() -> Result<Self> {
+            let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+            let x11 = std::env::var_os("DISPLAY").is_some();
+
+            if wayland && is_installed("wlr-randr") {
+                return Ok(Self {
+                    program: "wlr-randr",
+                    args: &[],
+                    parse: parse_wlr_randr,
+                });
+            }
+            // GNOME/KDE 等非 wlroots 的 Wayland 会话通过 XWayland 查询
+            if (x11 || !wayland) && is_installed("xrandr") {
+                return Ok(Self {
+                    program: "xrandr",
+                    args: &["--current"],
+                    parse: parse_xrandr,
+                });
+            }
+
+            Err(VerifierError::ConfigError(
+                "未找到分辨率查询工具 (需要 wlr-randr 或 xrandr)".to_string(),
+            ))
+        }
+
+        pub fn method(&self) -> &'static str {
+            self.program
+        }
+
+        /// 查询当前分辨率
+        pub async fn read(&self) -> Result<Resolution> {
+            let output = Command::new(self.program)
+                .args(self.args)
+                .stdin(Stdio::null())
+                .kill_on_drop(true)
+                .output()
+                .await?;
+
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(VerifierError::VerificationFailed(format!(
+                    "{} 退出码 {:?}: {}",
+                    self.program,
+                    output.status.code(),
+                    stderr.trim()
+                )));
+            }
+
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            (self.parse)(&stdout).ok_or_else(|| {
+                VerifierError::VerificationFailed(format!("无法解析 {} 的输出", self.program))
+            })
+        }
+    }
+}
+
+// ===== Windows 实现 (EnumDisplaySettingsW) =====
+
+#[cfg(target_os = "windows")]
+mod platform {
+    use super::Resolution;
+    use verifier_core::{Result, VerifierError};
+    use windows::core::PCWSTR;
+    use windows::Win32::Graphics::Gdi::{EnumDisplaySettingsW, DEVMODEW, ENUM_CURRENT_SETTINGS};
+
+    pub struct ResolutionReader;
+
+    impl ResolutionReader {
+        pub fn detect() -> Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn method(&self) -> &'static str {
+            "enum_display_settings"
+        }
+
+        /// 查询主显示器的当前分辨率
+        pub async fn read(&self) -> Result<Resolution> {
+            let mut mode = DEVMODEW {
+                dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+                ..Default::default()
+            };
+
+            let ok = unsafe { EnumDisplaySettingsW(PCWSTR::null(), ENUM_CURRENT_SETTINGS, &mut mode) };
+            if !ok.as_bool() {
+                return Err(VerifierError::VerificationFailed(
+                    "EnumDisplaySettingsW 查询失败".to_string(),
+                ));
+            }
+
+            Ok(Resolution {
+                width: mode.dmPelsWidth,
+                height: mode.dmPelsHeight,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_xrandr() {
+        let output = "\
+Screen 0: minimum 320 x 200, current 2944 x 1080, maximum 8192 x 8192
+Virtual-1 connected 1024x768+1920+0 (normal left inverted right x axis y axis) 0mm x 0mm
+   1024x768      60.00*+
+Virtual-2 connected primary 1920x1080+0+0 (normal left inverted right x axis y axis) 0mm x 0mm
+   1920x1080     60.00*+
+Virtual-3 disconnected (normal left inverted right x axis y axis)
+";
+        assert_eq!(parse_xrandr(output), Some(Resolution { width: 1920, height: 1080 }));
+
+        // 没有主显示器时取第一个已启用的显示器
+        let output = output.replace("connected primary", "connected");
+        assert_eq!(parse_xrandr(&output), Some(Resolution { width: 1024, height: 768 }));
+
+        // 没有已启用的显示器时取屏幕大小
+        let output = "Screen 0: minimum 320 x 200, current 1280 x 800, maximum 8192 x 8192\n";
+        assert_eq!(parse_xrandr(output), Some(Resolution { width: 1280, height: 800 }));
+
+        assert_eq!(parse_xrandr(""), None);
+    }
+
+    #[test]
+    fn test_parse_wlr_randr() {
+        let output = "\
+Virtual-1 \"Red Hat, Inc. QEMU Monitor (Virtual-1)\"
+  Enabled: no
+  Modes:
+    1024x768 px, 60.000000 Hz (preferred, current)
+Virtual-2 \"Red Hat, Inc. QEMU Monitor (Virtual-2)\"
+  Enabled: yes
+  Modes:
+    1024x768 px, 60.000000 Hz (preferred)
+    1920x1080 px, 60.000000 Hz (current)
+  Position: 0,0
+";
+        assert_eq!(parse_wlr_randr(output), Some(Resolution { width: 1920, height: 1080 }));
+        assert_eq!(parse_wlr_randr("Virtual-1 \"x\"\n  Enabled: yes\n"), None);
+    }
+}
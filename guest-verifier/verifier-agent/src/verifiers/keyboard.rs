@@ -4,13 +4,16 @@ use async_trait::async_trait;
 use serde_json::json;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{debug, error, info};
-use verifier_core::{Event, Result, Verifier, VerifierError, VerifierType, VerifyResult};
+use verifier_core::{
+    CancellationToken, Event, KeyExpectation, KeyMatcher, ObservedKey, Result, Verifier, VerifierError,
+    VerifierType, VerifyResult,
+};
 
 /// 键盘验证器 trait
 #[async_trait]
 pub trait KeyboardVerifier: Verifier {
     /// 验证键盘事件
-    async fn verify_keyboard(&self, event: &Event) -> Result<VerifyResult>;
+    async fn verify_keyboard(&self, event: &Event, cancel: &CancellationToken) -> Result<VerifyResult>;
 }
 
 // ===== Linux 实现 (evdev) =====
@@ -49,14 +52,14 @@ mod linux {
         }
 
         /// 查找所有键盘设备
-        fn find_keyboard_devices() -> Result<Vec<Device>> {
+        pub(crate) fn find_keyboard_devices() -> Result<Vec<Device>> {
             let mut keyboards = Vec::new();
 
             // 遍历 /dev/input/event* 设备
             for entry in std::fs::read_dir("/dev/input")
-                .map_err(|e| VerifierError::IoError(e))?
+                .map_err(VerifierError::IoError)?
             {
-                let entry = entry.map_err(|e| VerifierError::IoError(e))?;
+                let entry = entry.map_err(VerifierError::IoError)?;
                 let path = entry.path();
 
                 // 只处理 eventX 设备
@@ -82,18 +85,28 @@ mod linux {
             Ok(keyboards)
         }
 
-        /// 监听键盘事件（带超时）
-        async fn wait_for_key_event(&self, expected_key: &str, timeout_ms: u64) -> Result<bool> {
-            let timeout = tokio::time::Duration::from_millis(timeout_ms);
+        /// 在窗口内监听键盘事件, 按顺序匹配预期按键
+        async fn wait_for_keys(
+            &self,
+            expectation: &KeyExpectation,
+            cancel: &CancellationToken,
+        ) -> Result<KeyMatcher> {
+            let window = tokio::time::Duration::from_millis(expectation.window_ms);
             let start_time = tokio::time::Instant::now();
+            let mut matcher = expectation.matcher();
 
-            debug!("等待键盘事件: {} (超时: {}ms)", expected_key, timeout_ms);
+            debug!("等待键盘事件: {} (窗口: {}ms)", expectation, expectation.window_ms);
 
             loop {
+                // Agent 关闭时提前退出
+                if cancel.is_cancelled() {
+                    return Err(VerifierError::Cancelled);
+                }
+
                 // 检查超时
-                if start_time.elapsed() > timeout {
-                    debug!("等待超时");
-                    return Ok(false);
+                if start_time.elapsed() > window {
+                    debug!("等待超时, 已匹配 {}/{} 个按键", matcher.matched(), expectation.keys.len());
+                    return Ok(matcher);
                 }
 
                 // 检查所有设备
@@ -106,13 +119,9 @@ mod linux {
                                 let key_name = format!("{:?}", key);
                                 debug!("检测到按键: {} (value: {})", key_name, event.value());
 
-                                // 只检查按下事件（value == 1）
-                                if event.value() == 1 {
-                                    // 简单的按键名称匹配
-                                    if self.match_key(&key_name, expected_key) {
-                                        info!("匹配到预期按键: {}", expected_key);
-                                        return Ok(true);
-                                    }
+                                if matcher.feed(ObservedKey::new(key_name, event.value())) {
+                                    info!("匹配到预期按键: {}", expectation);
+                                    return Ok(matcher);
                                 }
                             }
                         }
@@ -123,22 +132,12 @@ mod linux {
                 tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
             }
         }
-
-        /// 匹配按键名称
-        fn match_key(&self, detected: &str, expected: &str) -> bool {
-            // 移除 KEY_ 前缀
-            let detected = detected.strip_prefix("KEY_").unwrap_or(detected);
-            let expected = expected.strip_prefix("KEY_").unwrap_or(expected);
-
-            // 不区分大小写比较
-            detected.eq_ignore_ascii_case(expected)
-        }
     }
 
     #[async_trait]
     impl Verifier for LinuxKeyboardVerifier {
-        async fn verify(&self, event: Event) -> Result<VerifyResult> {
-            self.verify_keyboard(&event).await
+        async fn verify(&self, event: Event, cancel: CancellationToken) -> Result<VerifyResult> {
+            self.verify_keyboard(&event, &cancel).await
         }
 
         fn verifier_type(&self) -> VerifierType {
@@ -148,7 +147,7 @@ mod linux {
 
     #[async_trait]
     impl KeyboardVerifier for LinuxKeyboardVerifier {
-        async fn verify_keyboard(&self, event: &Event) -> Result<VerifyResult> {
+        async fn verify_keyboard(&self, event: &Event, cancel: &CancellationToken) -> Result<VerifyResult> {
             let start_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
@@ -156,24 +155,11 @@ mod linux {
 
             debug!("验证键盘事件: {:?}", event);
 
-            // 从事件数据中提取按键信息
-            let key = event
-                .data
-                .get("key")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| {
-                    VerifierError::VerificationFailed("事件缺少 key 字段".to_string())
-                })?;
-
-            // 获取超时时间（默认 5000ms）
-            let timeout_ms = event
-                .data
-                .get("timeout_ms")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(5000);
-
-            // 等待按键事件
-            let verified = self.wait_for_key_event(key, timeout_ms).await?;
+            // 从事件数据中解析预期按键
+            let expectation = KeyExpectation::from_event(event)?;
+
+            // 在窗口内按顺序匹配
+            let matcher = self.wait_for_keys(&expectation, cancel).await?;
 
             let end_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -182,6 +168,11 @@ mod linux {
 
             let latency_ms = (end_time - start_time) as u64;
 
+            // 匹配失败时详情中附带实际观察到的事件
+            let mut details = matcher.details(&expectation);
+            details["platform"] = json!("linux");
+            details["method"] = json!("evdev");
+
             Ok(VerifyResult {
                 event_id: event
                     .data
@@ -189,14 +180,10 @@ mod linux {
                     .and_then(|v| v.as_str())
                     .unwrap_or("unknown")
                     .to_string(),
-                verified,
+                verified: matcher.is_complete(),
                 timestamp: end_time,
                 latency_ms,
-                details: json!({
-                    "key": key,
-                    "platform": "linux",
-                    "method": "evdev",
-                }),
+                details,
             })
         }
     }
@@ -221,6 +208,8 @@ mod windows {
     #[derive(Debug, Clone)]
     struct KeyEvent {
         key: String,
+        /// 1 按下, 0 释放
+        value: i32,
         timestamp: Instant,
     }
 
@@ -291,166 +280,165 @@ mod windows {
             wparam: WPARAM,
             lparam: LPARAM,
         ) -> LRESULT {
-            if code >= 0 && wparam.0 as u32 == WM_KEYDOWN {
+            let value = match wparam.0 as u32 {
+                WM_KEYDOWN | WM_SYSKEYDOWN => Some(1),
+                WM_KEYUP | WM_SYSKEYUP => Some(0),
+                _ => None,
+            };
+
+            if let (true, Some(value)) = (code >= 0, value) {
                 // 获取键盘信息
                 let kb = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
                 let vk_code = kb.vkCode;
 
-                // 转换为按键名称
-                if let Some(key_name) = Self::vk_code_to_key_name(vk_code) {
-                    let event = KeyEvent {
-                        key: key_name.clone(),
-                        timestamp: Instant::now(),
-                    };
-
-                    // 推送到事件队列
-                    if let Ok(mut queue) = KEYBOARD_EVENTS.lock() {
-                        queue.push_back(event);
-                        // 限制队列大小
-                        if queue.len() > 100 {
-                            queue.pop_front();
-                        }
+                // 转换为按键名称, 未知按键保留虚拟键码以便在结果详情中排查
+                let key_name = Self::vk_code_to_key_name(vk_code)
+                    .unwrap_or_else(|| format!("VK_0x{:X}", vk_code));
+                let event = KeyEvent {
+                    key: key_name.clone(),
+                    value,
+                    timestamp: Instant::now(),
+                };
+
+                // 推送到事件队列
+                if let Ok(mut queue) = KEYBOARD_EVENTS.lock() {
+                    queue.push_back(event);
+                    // 限制队列大小
+                    if queue.len() > 100 {
+                        queue.pop_front();
                     }
-
-                    debug!("检测到按键: {} (VK: 0x{:X})", key_name, vk_code);
                 }
+
+                debug!("检测到按键: {} (VK: 0x{:X}, value: {})", key_name, vk_code, value);
             }
 
             CallNextHookEx(None, code, wparam, lparam)
         }
 
         /// 虚拟键码转换为按键名称
-        fn vk_code_to_key_name(vk_code: u32) -> Option<String> {
+        pub(crate) fn vk_code_to_key_name(vk_code: u32) -> Option<String> {
             match vk_code as u16 {
                 // 字母键 A-Z
                 0x41..=0x5A => Some(format!("{}", (vk_code as u8) as char)),
                 // 数字键 0-9
                 0x30..=0x39 => Some(format!("{}", (vk_code - 0x30))),
                 // 功能键
-                VK_RETURN.0 => Some("ENTER".to_string()),
-                VK_SPACE.0 => Some("SPACE".to_string()),
-                VK_ESCAPE.0 => Some("ESC".to_string()),
-                VK_TAB.0 => Some("TAB".to_string()),
-                VK_BACK.0 => Some("BACKSPACE".to_string()),
-                VK_DELETE.0 => Some("DELETE".to_string()),
-                VK_INSERT.0 => Some("INSERT".to_string()),
-                VK_HOME.0 => Some("HOME".to_string()),
-                VK_END.0 => Some("END".to_string()),
-                VK_PRIOR.0 => Some("PAGEUP".to_string()),
-                VK_NEXT.0 => Some("PAGEDOWN".to_string()),
+                v if v == VK_RETURN.0 => Some("ENTER".to_string()),
+                v if v == VK_SPACE.0 => Some("SPACE".to_string()),
+                v if v == VK_ESCAPE.0 => Some("ESC".to_string()),
+                v if v == VK_TAB.0 => Some("TAB".to_string()),
+                v if v == VK_BACK.0 => Some("BACKSPACE".to_string()),
+                v if v == VK_DELETE.0 => Some("DELETE".to_string()),
+                v if v == VK_INSERT.0 => Some("INSERT".to_string()),
+                v if v == VK_HOME.0 => Some("HOME".to_string()),
+                v if v == VK_END.0 => Some("END".to_string()),
+                v if v == VK_PRIOR.0 => Some("PAGEUP".to_string()),
+                v if v == VK_NEXT.0 => Some("PAGEDOWN".to_string()),
                 // 方向键
-                VK_LEFT.0 => Some("LEFT".to_string()),
-                VK_RIGHT.0 => Some("RIGHT".to_string()),
-                VK_UP.0 => Some("UP".to_string()),
-                VK_DOWN.0 => Some("DOWN".to_string()),
+                v if v == VK_LEFT.0 => Some("LEFT".to_string()),
+                v if v == VK_RIGHT.0 => Some("RIGHT".to_string()),
+                v if v == VK_UP.0 => Some("UP".to_string()),
+                v if v == VK_DOWN.0 => Some("DOWN".to_string()),
                 // F1-F12
-                VK_F1.0 => Some("F1".to_string()),
-                VK_F2.0 => Some("F2".to_string()),
-                VK_F3.0 => Some("F3".to_string()),
-                VK_F4.0 => Some("F4".to_string()),
-                VK_F5.0 => Some("F5".to_string()),
-                VK_F6.0 => Some("F6".to_string()),
-                VK_F7.0 => Some("F7".to_string()),
-                VK_F8.0 => Some("F8".to_string()),
-                VK_F9.0 => Some("F9".to_string()),
-                VK_F10.0 => Some("F10".to_string()),
-                VK_F11.0 => Some("F11".to_string()),
-                VK_F12.0 => Some("F12".to_string()),
+                v if v == VK_F1.0 => Some("F1".to_string()),
+                v if v == VK_F2.0 => Some("F2".to_string()),
+                v if v == VK_F3.0 => Some("F3".to_string()),
+                v if v == VK_F4.0 => Some("F4".to_string()),
+                v if v == VK_F5.0 => Some("F5".to_string()),
+                v if v == VK_F6.0 => Some("F6".to_string()),
+                v if v == VK_F7.0 => Some("F7".to_string()),
+                v if v == VK_F8.0 => Some("F8".to_string()),
+                v if v == VK_F9.0 => Some("F9".to_string()),
+                v if v == VK_F10.0 => Some("F10".to_string()),
+                v if v == VK_F11.0 => Some("F11".to_string()),
+                v if v == VK_F12.0 => Some("F12".to_string()),
                 // 修饰键
-                VK_SHIFT.0 => Some("SHIFT".to_string()),
-                VK_CONTROL.0 => Some("CTRL".to_string()),
-                VK_MENU.0 => Some("ALT".to_string()),
-                VK_LWIN.0 => Some("LWIN".to_string()),
-                VK_RWIN.0 => Some("RWIN".to_string()),
+                v if v == VK_SHIFT.0 => Some("SHIFT".to_string()),
+                v if v == VK_CONTROL.0 => Some("CTRL".to_string()),
+                v if v == VK_MENU.0 => Some("ALT".to_string()),
+                v if v == VK_LWIN.0 => Some("LWIN".to_string()),
+                v if v == VK_RWIN.0 => Some("RWIN".to_string()),
                 // 数字键盘
-                VK_NUMPAD0.0 => Some("NUMPAD0".to_string()),
-                VK_NUMPAD1.0 => Some("NUMPAD1".to_string()),
-                VK_NUMPAD2.0 => Some("NUMPAD2".to_string()),
-                VK_NUMPAD3.0 => Some("NUMPAD3".to_string()),
-                VK_NUMPAD4.0 => Some("NUMPAD4".to_string()),
-                VK_NUMPAD5.0 => Some("NUMPAD5".to_string()),
-                VK_NUMPAD6.0 => Some("NUMPAD6".to_string()),
-                VK_NUMPAD7.0 => Some("NUMPAD7".to_string()),
-                VK_NUMPAD8.0 => Some("NUMPAD8".to_string()),
-                VK_NUMPAD9.0 => Some("NUMPAD9".to_string()),
-                VK_MULTIPLY.0 => Some("MULTIPLY".to_string()),
-                VK_ADD.0 => Some("ADD".to_string()),
-                VK_SUBTRACT.0 => Some("SUBTRACT".to_string()),
-                VK_DECIMAL.0 => Some("DECIMAL".to_string()),
-                VK_DIVIDE.0 => Some("DIVIDE".to_string()),
+                v if v == VK_NUMPAD0.0 => Some("NUMPAD0".to_string()),
+                v if v == VK_NUMPAD1.0 => Some("NUMPAD1".to_string()),
+                v if v == VK_NUMPAD2.0 => Some("NUMPAD2".to_string()),
+                v if v == VK_NUMPAD3.0 => Some("NUMPAD3".to_string()),
+                v if v == VK_NUMPAD4.0 => Some("NUMPAD4".to_string()),
+                v if v == VK_NUMPAD5.0 => Some("NUMPAD5".to_string()),
+                v if v == VK_NUMPAD6.0 => Some("NUMPAD6".to_string()),
+                v if v == VK_NUMPAD7.0 => Some("NUMPAD7".to_string()),
+                v if v == VK_NUMPAD8.0 => Some("NUMPAD8".to_string()),
+                v if v == VK_NUMPAD9.0 => Some("NUMPAD9".to_string()),
+                v if v == VK_MULTIPLY.0 => Some("MULTIPLY".to_string()),
+                v if v == VK_ADD.0 => Some("ADD".to_string()),
+                v if v == VK_SUBTRACT.0 => Some("SUBTRACT".to_string()),
+                v if v == VK_DECIMAL.0 => Some("DECIMAL".to_string()),
+                v if v == VK_DIVIDE.0 => Some("DIVIDE".to_string()),
                 // OEM 键
-                VK_OEM_1.0 => Some(";".to_string()),
-                VK_OEM_PLUS.0 => Some("=".to_string()),
-                VK_OEM_COMMA.0 => Some(",".to_string()),
-                VK_OEM_MINUS.0 => Some("-".to_string()),
-                VK_OEM_PERIOD.0 => Some(".".to_string()),
-                VK_OEM_2.0 => Some("/".to_string()),
-                VK_OEM_3.0 => Some("`".to_string()),
-                VK_OEM_4.0 => Some("[".to_string()),
-                VK_OEM_5.0 => Some("\\".to_string()),
-                VK_OEM_6.0 => Some("]".to_string()),
-                VK_OEM_7.0 => Some("'".to_string()),
+                v if v == VK_OEM_1.0 => Some(";".to_string()),
+                v if v == VK_OEM_PLUS.0 => Some("=".to_string()),
+                v if v == VK_OEM_COMMA.0 => Some(",".to_string()),
+                v if v == VK_OEM_MINUS.0 => Some("-".to_string()),
+                v if v == VK_OEM_PERIOD.0 => Some(".".to_string()),
+                v if v == VK_OEM_2.0 => Some("/".to_string()),
+                v if v == VK_OEM_3.0 => Some("`".to_string()),
+                v if v == VK_OEM_4.0 => Some("[".to_string()),
+                v if v == VK_OEM_5.0 => Some("\\".to_string()),
+                v if v == VK_OEM_6.0 => Some("]".to_string()),
+                v if v == VK_OEM_7.0 => Some("'".to_string()),
                 _ => None,
             }
         }
 
-        /// 等待并匹配键盘事件
-        async fn wait_for_key_event(&self, expected_key: &str, timeout_ms: u64) -> Result<bool> {
-            let timeout = tokio::time::Duration::from_millis(timeout_ms);
+        /// 在窗口内按到达顺序匹配预期按键
+        async fn wait_for_keys(
+            &self,
+            expectation: &KeyExpectation,
+            cancel: &CancellationToken,
+        ) -> Result<KeyMatcher> {
+            let window = tokio::time::Duration::from_millis(expectation.window_ms);
             let start_time = tokio::time::Instant::now();
+            let mut matcher = expectation.matcher();
 
-            debug!("等待键盘事件: {} (超时: {}ms)", expected_key, timeout_ms);
+            debug!("等待键盘事件: {} (窗口: {}ms)", expectation, expectation.window_ms);
 
             loop {
+                // Agent 关闭时提前退出
+                if cancel.is_cancelled() {
+                    return Err(VerifierError::Cancelled);
+                }
+
                 // 检查超时
-                if start_time.elapsed() > timeout {
-                    debug!("等待超时");
-                    return Ok(false);
+                if start_time.elapsed() > window {
+                    debug!("等待超时, 已匹配 {}/{} 个按键", matcher.matched(), expectation.keys.len());
+                    return Ok(matcher);
                 }
 
                 // 检查事件队列
                 if let Ok(mut queue) = self.event_queue.lock() {
-                    // 查找匹配的事件
-                    let mut found_index = None;
-                    for (i, event) in queue.iter().enumerate() {
-                        if self.match_key(&event.key, expected_key) {
-                            found_index = Some(i);
-                            break;
-                        }
-                    }
-
-                    // 如果找到匹配事件，移除它并返回成功
-                    if let Some(index) = found_index {
-                        queue.remove(index);
-                        info!("匹配到预期按键: {}", expected_key);
-                        return Ok(true);
-                    }
-
                     // 清理过期事件（超过 10 秒）
                     let now = Instant::now();
                     queue.retain(|e| now.duration_since(e.timestamp).as_secs() < 10);
+
+                    // 依次取出事件匹配, 匹配完成后剩余事件留给后续验证
+                    while let Some(event) = queue.pop_front() {
+                        if matcher.feed(ObservedKey::new(event.key, event.value)) {
+                            info!("匹配到预期按键: {}", expectation);
+                            return Ok(matcher);
+                        }
+                    }
                 }
 
                 // 短暂休眠避免 CPU 占用过高
                 tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
             }
         }
-
-        /// 匹配按键名称
-        fn match_key(&self, detected: &str, expected: &str) -> bool {
-            // 移除 KEY_ 前缀（兼容 Linux 格式）
-            let detected = detected.strip_prefix("KEY_").unwrap_or(detected);
-            let expected = expected.strip_prefix("KEY_").unwrap_or(expected);
-
-            // 不区分大小写比较
-            detected.eq_ignore_ascii_case(expected)
-        }
     }
 
     #[async_trait]
     impl Verifier for WindowsKeyboardVerifier {
-        async fn verify(&self, event: Event) -> Result<VerifyResult> {
-            self.verify_keyboard(&event).await
+        async fn verify(&self, event: Event, cancel: CancellationToken) -> Result<VerifyResult> {
+            self.verify_keyboard(&event, &cancel).await
         }
 
         fn verifier_type(&self) -> VerifierType {
@@ -460,7 +448,7 @@ mod windows {
 
     #[async_trait]
     impl KeyboardVerifier for WindowsKeyboardVerifier {
-        async fn verify_keyboard(&self, event: &Event) -> Result<VerifyResult> {
+        async fn verify_keyboard(&self, event: &Event, cancel: &CancellationToken) -> Result<VerifyResult> {
             let start_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
@@ -468,24 +456,11 @@ mod windows {
 
             debug!("验证键盘事件: {:?}", event);
 
-            // 从事件数据中提取按键信息
-            let key = event
-                .data
-                .get("key")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| {
-                    VerifierError::VerificationFailed("事件缺少 key 字段".to_string())
-                })?;
-
-            // 获取超时时间（默认 5000ms）
-            let timeout_ms = event
-                .data
-                .get("timeout_ms")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(5000);
-
-            // 等待按键事件
-            let verified = self.wait_for_key_event(key, timeout_ms).await?;
+            // 从事件数据中解析预期按键
+            let expectation = KeyExpectation::from_event(event)?;
+
+            // 在窗口内按顺序匹配
+            let matcher = self.wait_for_keys(&expectation, cancel).await?;
 
             let end_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -494,6 +469,11 @@ mod windows {
 
             let latency_ms = (end_time - start_time) as u64;
 
+            // 匹配失败时详情中附带实际观察到的事件
+            let mut details = matcher.details(&expectation);
+            details["platform"] = json!("windows");
+            details["method"] = json!("hook_api");
+
             Ok(VerifyResult {
                 event_id: event
                     .data
@@ -501,14 +481,10 @@ mod windows {
                     .and_then(|v| v.as_str())
                     .unwrap_or("unknown")
                     .to_string(),
-                verified,
+                verified: matcher.is_complete(),
                 timestamp: end_time,
                 latency_ms,
-                details: json!({
-                    "key": key,
-                    "platform": "windows",
-                    "method": "hook_api",
-                }),
+                details,
             })
         }
     }
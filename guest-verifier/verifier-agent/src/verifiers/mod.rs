@@ -3,9 +3,13 @@
 pub mod keyboard;
 pub mod mouse;
 pub mod command;
+pub mod clipboard;
+pub mod display;
 
-// 导出命令验证器（跨平台）
+// 导出命令、剪贴板与显示验证器（跨平台）
 pub use command::CommandVerifier;
+pub use clipboard::ClipboardVerifier;
+pub use display::DisplayVerifier;
 
 // Linux 平台验证器
 #[cfg(target_os = "linux")]
@@ -18,3 +22,11 @@ pub use mouse::LinuxMouseVerifier;
 pub use keyboard::WindowsKeyboardVerifier;
 #[cfg(target_os = "windows")]
 pub use mouse::WindowsMouseVerifier;
+
+/// PATH 中是否存在可执行文件
+#[cfg(target_os = "linux")]
+pub(crate) fn is_installed(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
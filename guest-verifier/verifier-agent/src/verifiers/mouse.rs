@@ -4,13 +4,15 @@ use async_trait::async_trait;
 use serde_json::json;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{debug, error, info};
-use verifier_core::{Event, Result, Verifier, VerifierError, VerifierType, VerifyResult};
+use verifier_core::{
+    CancellationToken, Event, Result, Verifier, VerifierError, VerifierType, VerifyResult,
+};
 
 /// 鼠标验证器 trait
 #[async_trait]
 pub trait MouseVerifier: Verifier {
     /// 验证鼠标事件
-    async fn verify_mouse(&self, event: &Event) -> Result<VerifyResult>;
+    async fn verify_mouse(&self, event: &Event, cancel: &CancellationToken) -> Result<VerifyResult>;
 }
 
 // ===== Linux 实现 (evdev) =====
@@ -49,14 +51,14 @@ mod linux {
         }
 
         /// 查找所有鼠标设备
-        fn find_mouse_devices() -> Result<Vec<Device>> {
+        pub(crate) fn find_mouse_devices() -> Result<Vec<Device>> {
             let mut mice = Vec::new();
 
             // 遍历 /dev/input/event* 设备
             for entry in std::fs::read_dir("/dev/input")
-                .map_err(|e| VerifierError::IoError(e))?
+                .map_err(VerifierError::IoError)?
             {
-                let entry = entry.map_err(|e| VerifierError::IoError(e))?;
+                let entry = entry.map_err(VerifierError::IoError)?;
                 let path = entry.path();
 
                 // 只处理 eventX 设备
@@ -105,6 +107,7 @@ mod linux {
             &self,
             event_type: &str,
             timeout_ms: u64,
+            cancel: &CancellationToken,
         ) -> Result<bool> {
             let timeout = tokio::time::Duration::from_millis(timeout_ms);
             let start_time = tokio::time::Instant::now();
@@ -112,6 +115,11 @@ mod linux {
             debug!("等待鼠标事件: {} (超时: {}ms)", event_type, timeout_ms);
 
             loop {
+                // Agent 关闭时提前退出
+                if cancel.is_cancelled() {
+                    return Err(VerifierError::Cancelled);
+                }
+
                 // 检查超时
                 if start_time.elapsed() > timeout {
                     debug!("等待超时");
@@ -125,17 +133,14 @@ mod linux {
                     while let Ok(events) = device.fetch_events() {
                         for event in events {
                             match event.kind() {
-                                InputEventKind::Key(key) => {
-                                    // 鼠标按键事件
-                                    if event.value() == 1 {
-                                        // 按下事件
-                                        let button_name = format!("{:?}", key);
-                                        debug!("检测到鼠标按键: {}", button_name);
-
-                                        if self.match_mouse_button(&button_name, event_type) {
-                                            info!("匹配到预期鼠标事件: {}", event_type);
-                                            return Ok(true);
-                                        }
+                                // 鼠标按键按下事件
+                                InputEventKind::Key(key) if event.value() == 1 => {
+                                    let button_name = format!("{:?}", key);
+                                    debug!("检测到鼠标按键: {}", button_name);
+
+                                    if self.match_mouse_button(&button_name, event_type) {
+                                        info!("匹配到预期鼠标事件: {}", event_type);
+                                        return Ok(true);
                                     }
                                 }
                                 InputEventKind::RelAxis(axis) => {
@@ -172,8 +177,8 @@ mod linux {
 
     #[async_trait]
     impl Verifier for LinuxMouseVerifier {
-        async fn verify(&self, event: Event) -> Result<VerifyResult> {
-            self.verify_mouse(&event).await
+        async fn verify(&self, event: Event, cancel: CancellationToken) -> Result<VerifyResult> {
+            self.verify_mouse(&event, &cancel).await
         }
 
         fn verifier_type(&self) -> VerifierType {
@@ -183,7 +188,7 @@ mod linux {
 
     #[async_trait]
     impl MouseVerifier for LinuxMouseVerifier {
-        async fn verify_mouse(&self, event: &Event) -> Result<VerifyResult> {
+        async fn verify_mouse(&self, event: &Event, cancel: &CancellationToken) -> Result<VerifyResult> {
             let start_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
@@ -208,7 +213,7 @@ mod linux {
                 .unwrap_or(5000);
 
             // 等待鼠标事件
-            let verified = self.wait_for_mouse_event(action, timeout_ms).await?;
+            let verified = self.wait_for_mouse_event(action, timeout_ms, cancel).await?;
 
             let end_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -382,6 +387,7 @@ mod windows {
             &self,
             event_type: &str,
             timeout_ms: u64,
+            cancel: &CancellationToken,
         ) -> Result<bool> {
             let timeout = tokio::time::Duration::from_millis(timeout_ms);
             let start_time = tokio::time::Instant::now();
@@ -391,6 +397,11 @@ mod windows {
             let expected_type = self.parse_mouse_event_type(event_type)?;
 
             loop {
+                // Agent 关闭时提前退出
+                if cancel.is_cancelled() {
+                    return Err(VerifierError::Cancelled);
+                }
+
                 // 检查超时
                 if start_time.elapsed() > timeout {
                     debug!("等待超时");
@@ -442,8 +453,8 @@ mod windows {
 
     #[async_trait]
     impl Verifier for WindowsMouseVerifier {
-        async fn verify(&self, event: Event) -> Result<VerifyResult> {
-            self.verify_mouse(&event).await
+        async fn verify(&self, event: Event, cancel: CancellationToken) -> Result<VerifyResult> {
+            self.verify_mouse(&event, &cancel).await
         }
 
         fn verifier_type(&self) -> VerifierType {
@@ -453,7 +464,7 @@ mod windows {
 
     #[async_trait]
     impl MouseVerifier for WindowsMouseVerifier {
-        async fn verify_mouse(&self, event: &Event) -> Result<VerifyResult> {
+        async fn verify_mouse(&self, event: &Event, cancel: &CancellationToken) -> Result<VerifyResult> {
             let start_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
                 .unwrap()
@@ -478,7 +489,7 @@ mod windows {
                 .unwrap_or(5000);
 
             // 等待鼠标事件
-            let verified = self.wait_for_mouse_event(action, timeout_ms).await?;
+            let verified = self.wait_for_mouse_event(action, timeout_ms, cancel).await?;
 
             let end_time = SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -0,0 +1,369 @@
+//! VM ID 自动获取
+//!
+//! 优先级:
+//! 1. 持久化文件 (`/etc/atp/vm-id`, Windows 为 `%ProgramData%\atp\vm-id`)
+//! 2. SMBIOS OEM 字符串中的 `atp.vm_id=<id>` (平台注入的 VDI 虚拟机 ID)
+//! 3. DMI 序列号 / UUID
+//! 4. 主机名
+//!
+//! 从 SMBIOS 获取成功后写入持久化文件, 之后即使模板或硬件信息变化 VM ID 也保持不变。
+//! 主机名在克隆的虚拟机之间可能重复, 只作为最后的回退, 不会写入持久化文件。
+
+use anyhow::{bail, Result};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// OEM 字符串中携带 VM ID 的前缀
+pub const OEM_VM_ID_PREFIX: &str = "atp.vm_id=";
+
+/// VM ID 的来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VmIdSource {
+    /// 命令行或配置文件指定
+    Manual,
+    /// 持久化文件
+    Persisted,
+    /// SMBIOS OEM 字符串
+    OemString,
+    /// DMI 序列号
+    DmiSerial,
+    /// DMI UUID
+    DmiUuid,
+    /// 主机名
+    Hostname,
+}
+
+impl VmIdSource {
+    /// 是否写入持久化文件 (只持久化来自 SMBIOS 的 ID)
+    fn persistable(self) -> bool {
+        matches!(self, VmIdSource::OemString | VmIdSource::DmiSerial | VmIdSource::DmiUuid)
+    }
+}
+
+impl fmt::Display for VmIdSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            VmIdSource::Manual => "手动指定",
+            VmIdSource::Persisted => "持久化文件",
+            VmIdSource::OemString => "SMBIOS OEM 字符串",
+            VmIdSource::DmiSerial => "DMI 序列号",
+            VmIdSource::DmiUuid => "DMI UUID",
+            VmIdSource::Hostname => "主机名",
+        };
+        f.write_str(name)
+    }
+}
+
+/// VM ID 探测器
+pub struct VmIdDetector {
+    /// 持久化文件
+    persist_path: PathBuf,
+    /// sysfs 根目录 (Linux)
+    sysfs_root: PathBuf,
+    /// 主机名文件 (Linux)
+    hostname_path: PathBuf,
+}
+
+impl Default for VmIdDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VmIdDetector {
+    /// 使用当前平台的默认路径
+    pub fn new() -> Self {
+        Self {
+            persist_path: default_persist_path(),
+            sysfs_root: PathBuf::from("/sys"),
+            hostname_path: PathBuf::from("/etc/hostname"),
+        }
+    }
+
+    /// 持久化文件路径
+    pub fn persist_path(&self) -> &Path {
+        &self.persist_path
+    }
+
+    /// 按优先级获取 VM ID
+    pub fn detect(&self) -> Result<(String, VmIdSource)> {
+        #[cfg(target_os = "linux")]
+        return self.detect_with(Self::read_linux);
+
+        #[cfg(target_os = "windows")]
+        return self.detect_with(|_| read_windows());
+
+        #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+        self.detect_with(|_| None)
+    }
+
+    /// 持久化文件优先, 其次由 `read_platform` 读取平台信息
+    fn detect_with(&self, read_platform: impl FnOnce(&Self) -> Option<(String, VmIdSource)>) -> Result<(String, VmIdSource)> {
+        if let Some(vm_id) = read_value(&self.persist_path) {
+            debug!("从持久化文件 {} 获取 VM ID: {}", self.persist_path.display(), vm_id);
+            return Ok((vm_id, VmIdSource::Persisted));
+        }
+
+        let Some((vm_id, source)) = read_platform(self) else {
+            bail!("无法自动获取 VM ID，请使用 --vm-id 手动指定");
+        };
+        debug!("从{}获取 VM ID: {}", source, vm_id);
+
+        if source.persistable() {
+            match self.persist(&vm_id) {
+                Ok(()) => debug!("VM ID 已写入 {}", self.persist_path.display()),
+                Err(e) => warn!("写入 VM ID 持久化文件 {} 失败: {}", self.persist_path.display(), e),
+            }
+        }
+        Ok((vm_id, source))
+    }
+
+    fn persist(&self, vm_id: &str) -> std::io::Result<()> {
+        if let Some(parent) = self.persist_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.persist_path, format!("{}\n", vm_id))
+    }
+
+    /// Linux: SMBIOS OEM 字符串 > DMI 序列号 > DMI UUID > 主机名
+    #[cfg(any(target_os = "linux", test))]
+    fn read_linux(&self) -> Option<(String, VmIdSource)> {
+        // OEM 字符串 (SMBIOS type 11) 只能从原始表中读取
+        let entries = self.sysfs_root.join("firmware/dmi/entries");
+        if let Ok(dir) = std::fs::read_dir(&entries) {
+            let mut tables: Vec<PathBuf> = dir
+                .flatten()
+                .filter(|entry| entry.file_name().to_string_lossy().starts_with("11-"))
+                .map(|entry| entry.path().join("raw"))
+                .collect();
+            tables.sort();
+            for table in tables {
+                if let Ok(raw) = std::fs::read(&table) {
+                    if let Some(vm_id) = vm_id_from_oem_strings(&parse_oem_strings(&raw)) {
+                        return Some((vm_id, VmIdSource::OemString));
+                    }
+                }
+            }
+        }
+
+        let dmi = self.sysfs_root.join("class/dmi/id");
+        if let Some(serial) = read_value(&dmi.join("product_serial")) {
+            return Some((serial, VmIdSource::DmiSerial));
+        }
+        if let Some(uuid) = read_value(&dmi.join("product_uuid")) {
+            return Some((uuid, VmIdSource::DmiUuid));
+        }
+        debug!("DMI/SMBIOS 信息不可用或为空");
+
+        read_value(&self.hostname_path).map(|hostname| (hostname, VmIdSource::Hostname))
+    }
+}
+
+/// 持久化文件的默认路径
+fn default_persist_path() -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        let program_data = std::env::var_os("ProgramData").unwrap_or_else(|| "C:\\ProgramData".into());
+        PathBuf::from(program_data).join("atp").join("vm-id")
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    PathBuf::from("/etc/atp/vm-id")
+}
+
+/// 读取文件中的单个值, 文件不存在或值无效时返回 None
+fn read_value(path: &Path) -> Option<String> {
+    std::fs::read_to_string(path).ok().and_then(|value| usable(&value))
+}
+
+/// 过滤固件中常见的占位值
+fn usable(value: &str) -> Option<String> {
+    const PLACEHOLDERS: &[&str] = &[
+        "Not Specified",
+        "Not Available",
+        "None",
+        "Default string",
+        "To be filled by O.E.M.",
+        "00000000-0000-0000-0000-000000000000",
+    ];
+
+    let value = value.trim();
+    if value.is_empty()
+        || value.chars().any(char::is_whitespace)
+        || PLACEHOLDERS.iter().any(|p| p.eq_ignore_ascii_case(value))
+    {
+        return None;
+    }
+    Some(value.to_string())
+}
+
+/// 解析 SMBIOS type 11 (OEM Strings) 结构
+///
+/// 结构头: 类型 (1 字节)、长度 (1 字节)、句柄 (2 字节)、字符串数 (1 字节);
+/// 结构头之后是以 NUL 结尾的字符串, 以连续两个 NUL 结束。
+fn parse_oem_strings(raw: &[u8]) -> Vec<String> {
+    if raw.len() < 5 || raw[0] != 11 {
+        return Vec::new();
+    }
+    let Some(strings) = raw.get(raw[1] as usize..) else {
+        return Vec::new();
+    };
+    strings
+        .split(|&b| b == 0)
+        .take_while(|s| !s.is_empty())
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect()
+}
+
+/// 从 OEM 字符串中取出 `atp.vm_id=<id>`
+fn vm_id_from_oem_strings(strings: &[String]) -> Option<String> {
+    strings
+        .iter()
+        .find_map(|s| s.trim().strip_prefix(OEM_VM_ID_PREFIX))
+        .and_then(usable)
+}
+
+/// Windows: SMBIOS OEM 字符串 > 产品序列号 > 产品 UUID > 计算机名
+#[cfg(target_os = "windows")]
+fn read_windows() -> Option<(String, VmIdSource)> {
+    let oem_strings = powershell_lines("(Get-CimInstance Win32_ComputerSystem).OEMStringArray");
+    if let Some(vm_id) = vm_id_from_oem_strings(&oem_strings) {
+        return Some((vm_id, VmIdSource::OemString));
+    }
+
+    let product = powershell_lines(
+        "$p = Get-CimInstance Win32_ComputerSystemProduct; $p.IdentifyingNumber; $p.UUID",
+    );
+    if let Some(serial) = product.first().and_then(|v| usable(v)) {
+        return Some((serial, VmIdSource::DmiSerial));
+    }
+    if let Some(uuid) = product.get(1).and_then(|v| usable(v)) {
+        return Some((uuid, VmIdSource::DmiUuid));
+    }
+    debug!("SMBIOS 信息不可用或为空");
+
+    let output = std::process::Command::new("hostname").output().ok()?;
+    usable(&String::from_utf8_lossy(&output.stdout)).map(|hostname| (hostname, VmIdSource::Hostname))
+}
+
+/// 执行 PowerShell 脚本, 按行返回输出
+#[cfg(target_os = "windows")]
+fn powershell_lines(script: &str) -> Vec<String> {
+    match std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .output()
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect(),
+        Ok(output) => {
+            debug!("PowerShell 查询失败: {}", String::from_utf8_lossy(&output.stderr).trim());
+            Vec::new()
+        }
+        Err(e) => {
+            debug!("执行 PowerShell 失败: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 模拟 sysfs 与持久化文件的临时目录
+    fn fixture(name: &str) -> (PathBuf, VmIdDetector) {
+        let dir = std::env::temp_dir().join(format!("verifier-vm-id-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("sys/class/dmi/id")).unwrap();
+        let detector = VmIdDetector {
+            persist_path: dir.join("etc/atp/vm-id"),
+            sysfs_root: dir.join("sys"),
+            hostname_path: dir.join("etc/hostname"),
+        };
+        (dir, detector)
+    }
+
+    fn write(path: PathBuf, content: impl AsRef<[u8]>) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    /// 构造 SMBIOS type 11 结构
+    fn oem_table(strings: &[&str]) -> Vec<u8> {
+        let mut raw = vec![11, 5, 0x00, 0x11, strings.len() as u8];
+        for s in strings {
+            raw.extend_from_slice(s.as_bytes());
+            raw.push(0);
+        }
+        raw.push(0);
+        raw
+    }
+
+    fn detect(detector: &VmIdDetector) -> (String, VmIdSource) {
+        detector.detect_with(VmIdDetector::read_linux).unwrap()
+    }
+
+    #[test]
+    fn test_parse_oem_strings() {
+        let raw = oem_table(&["vendor=acme", "atp.vm_id=domain-42"]);
+        let strings = parse_oem_strings(&raw);
+        assert_eq!(strings, ["vendor=acme", "atp.vm_id=domain-42"]);
+        assert_eq!(vm_id_from_oem_strings(&strings).as_deref(), Some("domain-42"));
+
+        // 没有 VM ID 条目、类型不对、长度越界
+        assert_eq!(vm_id_from_oem_strings(&parse_oem_strings(&oem_table(&["vendor=acme"]))), None);
+        assert!(parse_oem_strings(&[1, 5, 0, 0, 1, b'a', 0, 0]).is_empty());
+        assert!(parse_oem_strings(&[11, 40, 0, 0, 1]).is_empty());
+    }
+
+    #[test]
+    fn test_source_priority() {
+        let (dir, detector) = fixture("priority");
+        let dmi = dir.join("sys/class/dmi/id");
+
+        // 只有主机名: 不写入持久化文件
+        write(dir.join("etc/hostname"), "template-host\n");
+        assert_eq!(detect(&detector), ("template-host".to_string(), VmIdSource::Hostname));
+        assert!(!detector.persist_path().exists());
+
+        // 占位值被忽略
+        write(dmi.join("product_serial"), "Not Specified\n");
+        write(dmi.join("product_uuid"), "00000000-0000-0000-0000-000000000000\n");
+        assert_eq!(detect(&detector).1, VmIdSource::Hostname);
+
+        write(dmi.join("product_uuid"), "4c4c4544-0042-3510-8052-b4c04f4b4e32\n");
+        assert_eq!(detect(&detector).1, VmIdSource::DmiUuid);
+        std::fs::remove_file(detector.persist_path()).unwrap();
+
+        write(dmi.join("product_serial"), "serial-001\n");
+        assert_eq!(detect(&detector), ("serial-001".to_string(), VmIdSource::DmiSerial));
+        std::fs::remove_file(detector.persist_path()).unwrap();
+
+        write(dir.join("sys/firmware/dmi/entries/11-0/raw"), oem_table(&["atp.vm_id=domain-42"]));
+        assert_eq!(detect(&detector), ("domain-42".to_string(), VmIdSource::OemString));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_persisted_id_is_stable() {
+        let (dir, detector) = fixture("persist");
+        write(dir.join("sys/firmware/dmi/entries/11-0/raw"), oem_table(&["atp.vm_id=domain-42"]));
+
+        // 首次获取成功后写入持久化文件
+        assert_eq!(detect(&detector).1, VmIdSource::OemString);
+        assert_eq!(std::fs::read_to_string(detector.persist_path()).unwrap(), "domain-42\n");
+
+        // 之后 SMBIOS 信息变化 (例如更换模板) 仍使用持久化的 ID
+        write(dir.join("sys/firmware/dmi/entries/11-0/raw"), oem_table(&["atp.vm_id=domain-43"]));
+        assert_eq!(detect(&detector), ("domain-42".to_string(), VmIdSource::Persisted));
+
+        // 没有任何来源
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(detector.detect_with(VmIdDetector::read_linux).is_err());
+    }
+}
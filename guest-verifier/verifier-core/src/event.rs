@@ -1,6 +1,7 @@
 //! 事件和结果定义
 
 use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
@@ -17,3 +18,145 @@ pub struct VerifyResult {
     pub latency_ms: u64,
     pub details: serde_json::Value,
 }
+
+/// 注册消息的 `message_type`
+pub const REGISTER_MESSAGE_TYPE: &str = "register";
+
+/// 服务端拒绝连接时发送的消息的 `message_type` (TCP)
+pub const REJECTED_MESSAGE_TYPE: &str = "rejected";
+
+/// 连接后发送的注册消息, 上报 Agent 版本与支持的验证能力
+///
+/// 先发送纯文本 VM ID 再补发本消息, 不认识注册消息的旧版服务端会将其忽略。
+/// 服务端开启认证时, 带 `token` 的本消息须作为第一条消息发送。
+///
+/// 本消息始终以裸 JSON 发送, 其中声明 Agent 的协议版本; 服务端回复 `accepted` 信封后,
+/// 之后的消息都包装为信封 (见 `atp_common::envelope`)。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RegisterMessage {
+    pub message_type: String,
+    pub vm_id: String,
+    #[serde(default)]
+    pub agent_version: Option<String>,
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol_version: Option<u16>,
+}
+
+impl RegisterMessage {
+    pub fn new(vm_id: impl Into<String>) -> Self {
+        Self {
+            message_type: REGISTER_MESSAGE_TYPE.to_string(),
+            vm_id: vm_id.into(),
+            agent_version: None,
+            capabilities: Vec::new(),
+            token: None,
+            protocol_version: Some(atp_common::PROTOCOL_VERSION),
+        }
+    }
+
+    pub fn with_agent_version(mut self, agent_version: impl Into<String>) -> Self {
+        self.agent_version = Some(agent_version.into());
+        self
+    }
+
+    pub fn with_capabilities(mut self, capabilities: Vec<String>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+}
+
+/// 输入上报消息的 `message_type`
+pub const RAW_INPUT_MESSAGE_TYPE: &str = "raw_input";
+
+/// 注册消息中声明支持输入上报 (上报模式) 的能力标记
+pub const RAW_INPUT_CAPABILITY: &str = "raw_input";
+
+/// 心跳消息的 `message_type`
+pub const HEARTBEAT_MESSAGE_TYPE: &str = "heartbeat";
+
+/// 切换 Agent 工作模式的控制事件的 `event_type`, 数据为 `{"mode": "verify" | "report"}`
+pub const SET_MODE_EVENT_TYPE: &str = "set_mode";
+
+/// Agent 在上报模式下观察到的原始输入
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawInputEvent {
+    /// 固定为 `raw_input`
+    pub message_type: String,
+
+    /// 输入设备 (`keyboard` / `mouse`)
+    pub device: String,
+
+    /// 按键或坐标轴名称 (如 `KEY_A`、`BTN_LEFT`、`REL_X`)
+    pub code: String,
+
+    /// 按键: 1 按下, 0 释放, 2 重复; 坐标轴: 相对位移
+    pub value: i32,
+
+    /// 鼠标绝对坐标 (Windows Hook 提供)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<i32>,
+
+    /// 观察到输入的时间 (Unix 毫秒)
+    pub timestamp: i64,
+
+    /// 自上一条上报以来因队列已满丢弃的事件数
+    #[serde(default)]
+    pub dropped: u64,
+}
+
+/// Agent 定期发送的心跳, 服务端据此判断 Agent 是否失联
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeartbeatMessage {
+    /// 固定为 `heartbeat`
+    pub message_type: String,
+    pub vm_id: String,
+
+    /// Agent 已运行的时间 (秒)
+    pub uptime_s: u64,
+}
+
+impl HeartbeatMessage {
+    pub fn new(vm_id: impl Into<String>, uptime_s: u64) -> Self {
+        Self {
+            message_type: HEARTBEAT_MESSAGE_TYPE.to_string(),
+            vm_id: vm_id.into(),
+            uptime_s,
+        }
+    }
+}
+
+impl RawInputEvent {
+    pub fn new(device: impl Into<String>, code: impl Into<String>, value: i32) -> Self {
+        Self {
+            message_type: RAW_INPUT_MESSAGE_TYPE.to_string(),
+            device: device.into(),
+            code: code.into(),
+            value,
+            x: None,
+            y: None,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64,
+            dropped: 0,
+        }
+    }
+
+    pub fn with_position(mut self, x: i32, y: i32) -> Self {
+        self.x = Some(x);
+        self.y = Some(y);
+        self
+    }
+}
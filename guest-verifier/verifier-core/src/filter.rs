@@ -0,0 +1,289 @@
+//! 事件过滤规则
+//!
+//! 在事件交给验证器之前按本地规则决定接受或拒绝。
+//!
+//! 规则格式: `<accept|reject>:<条件>`, 条件为 `*` (匹配所有事件)
+//! 或逗号分隔的 `字段=值` / `字段!=值`, 所有条件都满足时规则匹配。
+//! 字段为 `event_type` 或 `data.<路径>` (路径用 `.` 分隔), 值为 `*` 时只要求字段存在。
+//!
+//! ```text
+//! accept:event_type=keyboard
+//! reject:event_type=command,data.source=server
+//! reject:*
+//! ```
+//!
+//! 规则按顺序匹配, 第一条匹配的规则决定结果; 没有规则匹配时接受事件。
+
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::json;
+
+use crate::{Event, Result, VerifierError, VerifyResult};
+
+/// 规则动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterAction {
+    Accept,
+    Reject,
+}
+
+/// 单个匹配条件
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    /// 字段等于值 (`*` 表示字段存在)
+    Equals(String, String),
+    /// 字段不等于值 (`*` 表示字段不存在)
+    NotEquals(String, String),
+}
+
+/// 过滤规则
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterRule {
+    pub action: FilterAction,
+    conditions: Vec<Condition>,
+    source: String,
+}
+
+impl FilterRule {
+    /// 规则是否匹配事件
+    pub fn matches(&self, event: &Event) -> bool {
+        self.conditions.iter().all(|condition| match condition {
+            Condition::Equals(field, expected) => field_matches(event, field, expected),
+            Condition::NotEquals(field, expected) => !field_matches(event, field, expected),
+        })
+    }
+}
+
+impl fmt::Display for FilterRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl FromStr for FilterRule {
+    type Err = VerifierError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| VerifierError::ConfigError(format!("无效的过滤规则 '{}': {}", s, reason));
+
+        let (action, conditions) = s
+            .split_once(':')
+            .ok_or_else(|| invalid("缺少 ':'"))?;
+
+        let action = match action.trim() {
+            "accept" => FilterAction::Accept,
+            "reject" => FilterAction::Reject,
+            _ => return Err(invalid("动作必须是 accept 或 reject")),
+        };
+
+        let conditions = conditions.trim();
+        if conditions.is_empty() {
+            return Err(invalid("缺少匹配条件"));
+        }
+
+        let conditions = if conditions == "*" {
+            Vec::new()
+        } else {
+            conditions
+                .split(',')
+                .map(|condition| {
+                    let (field, value, negate) = match condition.split_once("!=") {
+                        Some((field, value)) => (field, value, true),
+                        None => {
+                            let (field, value) = condition
+                                .split_once('=')
+                                .ok_or_else(|| invalid("条件必须是 字段=值 或 字段!=值"))?;
+                            (field, value, false)
+                        }
+                    };
+
+                    let field = field.trim();
+                    if field != "event_type" && !field.starts_with("data.") {
+                        return Err(invalid("字段必须是 event_type 或 data.<路径>"));
+                    }
+
+                    let (field, value) = (field.to_string(), value.trim().to_string());
+                    Ok(if negate {
+                        Condition::NotEquals(field, value)
+                    } else {
+                        Condition::Equals(field, value)
+                    })
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        Ok(Self {
+            action,
+            conditions,
+            source: s.trim().to_string(),
+        })
+    }
+}
+
+/// 取事件中的字段值 (字符串形式)
+fn field_value(event: &Event, field: &str) -> Option<String> {
+    if field == "event_type" {
+        return Some(event.event_type.clone());
+    }
+
+    let path = field.strip_prefix("data.")?;
+    let value = path
+        .split('.')
+        .try_fold(&event.data, |value, key| value.get(key))?;
+
+    match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn field_matches(event: &Event, field: &str, expected: &str) -> bool {
+    match field_value(event, field) {
+        Some(actual) => expected == "*" || actual == expected,
+        None => false,
+    }
+}
+
+/// 过滤结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum FilterDecision {
+    Accept,
+    /// 被拒绝, 附带匹配的规则
+    Reject(String),
+}
+
+/// 事件过滤器
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    rules: Vec<FilterRule>,
+}
+
+impl EventFilter {
+    pub fn new(rules: Vec<FilterRule>) -> Self {
+        Self { rules }
+    }
+
+    /// 从规则字符串列表解析
+    pub fn parse<S: AsRef<str>>(rules: &[S]) -> Result<Self> {
+        let rules = rules
+            .iter()
+            .map(|rule| rule.as_ref().parse())
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(rules))
+    }
+
+    /// 是否没有任何规则
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// 判断事件是否应交给验证器
+    pub fn decide(&self, event: &Event) -> FilterDecision {
+        match self.rules.iter().find(|rule| rule.matches(event)) {
+            Some(rule) if rule.action == FilterAction::Reject => FilterDecision::Reject(rule.to_string()),
+            _ => FilterDecision::Accept,
+        }
+    }
+}
+
+impl VerifyResult {
+    /// 被过滤规则拒绝的事件的结果
+    pub fn filtered(event: &Event, rule: &str) -> Self {
+        Self {
+            event_id: event
+                .data
+                .get("event_id")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            verified: false,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64,
+            latency_ms: 0,
+            details: json!({
+                "reason": "filtered",
+                "rule": rule,
+                "event_type": event.event_type,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(event_type: &str, data: serde_json::Value) -> Event {
+        Event {
+            event_type: event_type.to_string(),
+            data,
+            timestamp: 0,
+        }
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let rule: FilterRule = "reject:event_type=command, data.meta.source != guest".parse().unwrap();
+        assert_eq!(rule.action, FilterAction::Reject);
+        assert_eq!(rule.conditions.len(), 2);
+        assert_eq!(rule.to_string(), "reject:event_type=command, data.meta.source != guest");
+
+        let rule: FilterRule = "accept:*".parse().unwrap();
+        assert!(rule.conditions.is_empty());
+
+        assert!("drop:event_type=keyboard".parse::<FilterRule>().is_err());
+        assert!("accept".parse::<FilterRule>().is_err());
+        assert!("accept:".parse::<FilterRule>().is_err());
+        assert!("accept:event_type".parse::<FilterRule>().is_err());
+        assert!("accept:key=a".parse::<FilterRule>().is_err());
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let keyboard = event("keyboard", json!({"key": "a", "meta": {"count": 2, "source": "server"}}));
+
+        let matches = |rule: &str| rule.parse::<FilterRule>().unwrap().matches(&keyboard);
+        assert!(matches("accept:*"));
+        assert!(matches("accept:event_type=keyboard"));
+        assert!(matches("accept:data.key=a,data.meta.source=server"));
+        assert!(matches("accept:data.meta.count=2"));
+        assert!(matches("accept:data.meta.source=*"));
+        assert!(matches("accept:data.missing!=*"));
+        assert!(!matches("accept:event_type=mouse"));
+        assert!(!matches("accept:data.key=a,event_type=mouse"));
+        assert!(!matches("accept:data.missing=*"));
+        assert!(!matches("accept:data.key!=a"));
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let filter = EventFilter::parse(&["accept:event_type=keyboard", "reject:*"]).unwrap();
+
+        assert_eq!(filter.decide(&event("keyboard", json!({}))), FilterDecision::Accept);
+        assert_eq!(
+            filter.decide(&event("command", json!({}))),
+            FilterDecision::Reject("reject:*".to_string())
+        );
+
+        // 没有规则匹配时接受
+        let filter = EventFilter::parse(&["reject:event_type=command"]).unwrap();
+        assert_eq!(filter.decide(&event("mouse", json!({}))), FilterDecision::Accept);
+        assert!(EventFilter::default().is_empty());
+    }
+
+    #[test]
+    fn test_filtered_result() {
+        let command = event("command", json!({"event_id": "evt-1", "command": "ls"}));
+        let result = VerifyResult::filtered(&command, "reject:event_type=command");
+
+        assert_eq!(result.event_id, "evt-1");
+        assert!(!result.verified);
+        assert_eq!(result.details["reason"], "filtered");
+        assert_eq!(result.details["rule"], "reject:event_type=command");
+    }
+}
@@ -0,0 +1,350 @@
+//! 键盘事件的预期匹配
+//!
+//! 服务端在 `Event.data` 中描述预期的按键:
+//!
+//! ```json
+//! {"key": "a", "value": "press", "window_ms": 2000}
+//! {"keys": ["ctrl", "c"]}
+//! ```
+//!
+//! `keys` 按顺序匹配, 中间出现的其他按键被忽略 (但会记录)。`value` 可选
+//! `press` (默认) 或 `release`; `window_ms` 缺省时兼容旧的 `timeout_ms`, 默认 5000。
+//! 按键名称在比较前归一化, Linux evdev (`KEY_LEFTCTRL`) 与 Windows (`CTRL`)
+//! 的写法可以互相匹配。
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{Event, Result, VerifierError};
+
+/// 未指定窗口时的默认等待时长
+pub const DEFAULT_KEY_WINDOW_MS: u64 = 5000;
+
+/// 匹配失败时写入 `details` 的观察事件数上限
+pub const OBSERVED_LIMIT: usize = 20;
+
+/// 按键动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    Press,
+    Release,
+}
+
+impl KeyAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyAction::Press => "press",
+            KeyAction::Release => "release",
+        }
+    }
+
+    /// 是否与观察到的事件值匹配 (evdev: 1 按下, 0 释放, 2 自动重复)
+    fn matches(&self, value: i32) -> bool {
+        match self {
+            KeyAction::Press => value == 1,
+            KeyAction::Release => value == 0,
+        }
+    }
+}
+
+/// 预期的按键 (序列)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyExpectation {
+    /// 原始写法, 用于结果详情
+    pub keys: Vec<String>,
+    pub action: KeyAction,
+    pub window_ms: u64,
+}
+
+impl KeyExpectation {
+    /// 从事件数据解析预期
+    pub fn from_event(event: &Event) -> Result<Self> {
+        let data = &event.data;
+
+        let keys = match (data.get("keys"), data.get("key")) {
+            (Some(Value::Array(keys)), _) => keys
+                .iter()
+                .map(|k| k.as_str().map(str::to_string))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| VerifierError::VerificationFailed("keys 字段必须是字符串数组".to_string()))?,
+            (Some(_), _) => {
+                return Err(VerifierError::VerificationFailed("keys 字段必须是字符串数组".to_string()))
+            }
+            (None, Some(Value::String(key))) => vec![key.clone()],
+            (None, _) => {
+                return Err(VerifierError::VerificationFailed("事件缺少 key 或 keys 字段".to_string()))
+            }
+        };
+        if keys.is_empty() || keys.iter().any(|k| k.trim().is_empty()) {
+            return Err(VerifierError::VerificationFailed("预期按键不能为空".to_string()));
+        }
+
+        let action = match data.get("value").and_then(|v| v.as_str()) {
+            None => KeyAction::Press,
+            Some(v) if v.eq_ignore_ascii_case("press") => KeyAction::Press,
+            Some(v) if v.eq_ignore_ascii_case("release") => KeyAction::Release,
+            Some(v) => {
+                return Err(VerifierError::VerificationFailed(format!(
+                    "未知的按键动作: {} (可选: press, release)",
+                    v
+                )))
+            }
+        };
+
+        let window_ms = data
+            .get("window_ms")
+            .or_else(|| data.get("timeout_ms"))
+            .and_then(|v| v.as_u64())
+            .unwrap_or(DEFAULT_KEY_WINDOW_MS);
+
+        Ok(Self { keys, action, window_ms })
+    }
+
+    /// 创建匹配器
+    pub fn matcher(&self) -> KeyMatcher {
+        KeyMatcher {
+            expected: self.keys.iter().map(|k| normalize_key(k)).collect(),
+            action: self.action,
+            matched: 0,
+            observed: Vec::new(),
+            total_observed: 0,
+        }
+    }
+}
+
+impl fmt::Display for KeyExpectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.keys.join("+"), self.action.as_str())
+    }
+}
+
+/// 观察到的一次按键事件
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ObservedKey {
+    /// 平台上报的按键名称
+    pub key: String,
+
+    /// 1 按下, 0 释放, 2 自动重复
+    pub value: i32,
+}
+
+impl ObservedKey {
+    pub fn new(key: impl Into<String>, value: i32) -> Self {
+        Self { key: key.into(), value }
+    }
+}
+
+/// 按顺序匹配预期按键的状态机
+#[derive(Debug, Clone)]
+pub struct KeyMatcher {
+    expected: Vec<String>,
+    action: KeyAction,
+    matched: usize,
+    observed: Vec<ObservedKey>,
+    total_observed: usize,
+}
+
+impl KeyMatcher {
+    /// 输入一次观察到的事件, 返回预期是否已全部匹配
+    pub fn feed(&mut self, event: ObservedKey) -> bool {
+        if self.is_complete() {
+            return true;
+        }
+
+        if self.action.matches(event.value) && normalize_key(&event.key) == self.expected[self.matched] {
+            self.matched += 1;
+        }
+
+        self.total_observed += 1;
+        if self.observed.len() < OBSERVED_LIMIT {
+            self.observed.push(event);
+        }
+
+        self.is_complete()
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.matched == self.expected.len()
+    }
+
+    /// 已按顺序匹配的按键数
+    pub fn matched(&self) -> usize {
+        self.matched
+    }
+
+    /// 观察到的前 `OBSERVED_LIMIT` 个事件
+    pub fn observed(&self) -> &[ObservedKey] {
+        &self.observed
+    }
+
+    /// 结果详情: 匹配失败时附带实际观察到的事件
+    pub fn details(&self, expectation: &KeyExpectation) -> Value {
+        let mut details = json!({
+            "keys": expectation.keys,
+            "value": expectation.action.as_str(),
+            "window_ms": expectation.window_ms,
+            "matched": self.matched,
+        });
+        if !self.is_complete() {
+            details["observed"] = json!(self.observed);
+            details["observed_total"] = json!(self.total_observed);
+        }
+        details
+    }
+}
+
+/// 按键名称归一化: 去掉 `KEY_` 前缀、转为大写, 并合并各平台的别名
+pub fn normalize_key(key: &str) -> String {
+    let key = key.trim();
+    let upper = key.to_ascii_uppercase();
+    let name = upper.strip_prefix("KEY_").unwrap_or(&upper);
+
+    let alias = match name {
+        "CONTROL" | "LEFTCTRL" | "RIGHTCTRL" | "LCTRL" | "RCTRL" => "CTRL",
+        "LEFTSHIFT" | "RIGHTSHIFT" | "LSHIFT" | "RSHIFT" => "SHIFT",
+        "MENU" | "LEFTALT" | "RIGHTALT" | "LALT" | "RALT" => "ALT",
+        "LEFTMETA" | "RIGHTMETA" | "LWIN" | "RWIN" | "WIN" | "SUPER" => "META",
+        "RETURN" => "ENTER",
+        "ESCAPE" => "ESC",
+        "SPACEBAR" => "SPACE",
+        "SEMICOLON" => ";",
+        "EQUAL" => "=",
+        "COMMA" => ",",
+        "MINUS" => "-",
+        "DOT" | "PERIOD" => ".",
+        "SLASH" => "/",
+        "GRAVE" => "`",
+        "LEFTBRACE" => "[",
+        "BACKSLASH" => "\\",
+        "RIGHTBRACE" => "]",
+        "APOSTROPHE" => "'",
+        "KPASTERISK" => "MULTIPLY",
+        "KPPLUS" => "ADD",
+        "KPMINUS" => "SUBTRACT",
+        "KPDOT" => "DECIMAL",
+        "KPSLASH" => "DIVIDE",
+        other => match other.strip_prefix("KP") {
+            Some(digit) if digit.len() == 1 && digit.chars().all(|c| c.is_ascii_digit()) => {
+                return format!("NUMPAD{}", digit);
+            }
+            _ => other,
+        },
+    };
+    alias.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(data: Value) -> Event {
+        Event {
+            event_type: "keyboard".to_string(),
+            data,
+            timestamp: 0,
+        }
+    }
+
+    fn press(key: &str) -> ObservedKey {
+        ObservedKey::new(key, 1)
+    }
+
+    fn release(key: &str) -> ObservedKey {
+        ObservedKey::new(key, 0)
+    }
+
+    /// 依次输入事件, 返回匹配完成时已输入的事件数
+    fn run(matcher: &mut KeyMatcher, stream: Vec<ObservedKey>) -> Option<usize> {
+        stream
+            .into_iter()
+            .enumerate()
+            .find_map(|(i, e)| matcher.feed(e).then_some(i + 1))
+    }
+
+    #[test]
+    fn test_parse_expectation() {
+        let exp = KeyExpectation::from_event(&event(json!({"key": "a", "value": "press", "window_ms": 2000}))).unwrap();
+        assert_eq!(exp.keys, vec!["a"]);
+        assert_eq!((exp.action, exp.window_ms), (KeyAction::Press, 2000));
+
+        // 兼容旧的 timeout_ms; keys 优先于 key
+        let exp = KeyExpectation::from_event(&event(json!({"keys": ["ctrl", "c"], "key": "x", "timeout_ms": 800}))).unwrap();
+        assert_eq!(exp.keys, vec!["ctrl", "c"]);
+        assert_eq!(exp.window_ms, 800);
+
+        let exp = KeyExpectation::from_event(&event(json!({"key": "a", "value": "Release"}))).unwrap();
+        assert_eq!((exp.action, exp.window_ms), (KeyAction::Release, DEFAULT_KEY_WINDOW_MS));
+
+        for bad in [json!({}), json!({"keys": []}), json!({"keys": ["a", 1]}), json!({"keys": "a"}), json!({"key": "a", "value": "hold"})] {
+            assert!(KeyExpectation::from_event(&event(bad.clone())).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_wrong_key_is_not_verified() {
+        let exp = KeyExpectation::from_event(&event(json!({"key": "a"}))).unwrap();
+        let mut matcher = exp.matcher();
+
+        // 布局不一致: 发送 a, 客户机收到 q
+        assert_eq!(run(&mut matcher, vec![press("KEY_Q"), release("KEY_Q")]), None);
+        let details = matcher.details(&exp);
+        assert_eq!(details["matched"], 0);
+        assert_eq!(details["observed"], json!([{"key": "KEY_Q", "value": 1}, {"key": "KEY_Q", "value": 0}]));
+
+        // 预期按键到达后匹配成功, 详情中不再附带观察事件
+        assert!(matcher.feed(press("KEY_A")));
+        assert!(matcher.details(&exp).get("observed").is_none());
+    }
+
+    #[test]
+    fn test_sequence_matched_in_order() {
+        let exp = KeyExpectation::from_event(&event(json!({"keys": ["ctrl", "c"]}))).unwrap();
+
+        // Linux 写法 + 自动重复与无关按键
+        let mut matcher = exp.matcher();
+        let stream = vec![
+            press("KEY_LEFTCTRL"),
+            ObservedKey::new("KEY_LEFTCTRL", 2),
+            press("KEY_X"),
+            press("KEY_C"),
+            release("KEY_C"),
+        ];
+        assert_eq!(run(&mut matcher, stream), Some(4));
+
+        // Windows 写法
+        let mut matcher = exp.matcher();
+        assert_eq!(run(&mut matcher, vec![press("CTRL"), press("C")]), Some(2));
+
+        // 顺序颠倒时不匹配
+        let mut matcher = exp.matcher();
+        assert_eq!(run(&mut matcher, vec![press("C"), press("CTRL")]), None);
+        assert_eq!(matcher.matched(), 1);
+
+        // 只接受释放事件
+        let exp = KeyExpectation::from_event(&event(json!({"key": "enter", "value": "release"}))).unwrap();
+        let mut matcher = exp.matcher();
+        assert_eq!(run(&mut matcher, vec![press("KEY_ENTER"), release("KEY_ENTER")]), Some(2));
+    }
+
+    #[test]
+    fn test_observed_events_are_capped() {
+        let exp = KeyExpectation::from_event(&event(json!({"key": "a"}))).unwrap();
+        let mut matcher = exp.matcher();
+        run(&mut matcher, (0..OBSERVED_LIMIT + 5).map(|_| press("KEY_B")).collect());
+
+        assert_eq!(matcher.observed().len(), OBSERVED_LIMIT);
+        assert_eq!(matcher.details(&exp)["observed_total"], OBSERVED_LIMIT + 5);
+    }
+
+    #[test]
+    fn test_normalize_key() {
+        assert_eq!(normalize_key("KEY_LEFTCTRL"), normalize_key("ctrl"));
+        assert_eq!(normalize_key("LWIN"), normalize_key("KEY_RIGHTMETA"));
+        assert_eq!(normalize_key("KEY_SEMICOLON"), ";");
+        assert_eq!(normalize_key("KEY_KP5"), "NUMPAD5");
+        assert_eq!(normalize_key("key_a"), "A");
+        assert_eq!(normalize_key("KEY_1"), "1");
+    }
+}
@@ -3,13 +3,22 @@
 pub mod verifier;
 pub mod transport;
 pub mod event;
+pub mod filter;
+pub mod key_match;
+pub mod outbox;
+pub mod report;
 
-pub use verifier::{Verifier, VerifierType};
+pub use verifier::{verify_with_timeout, Verifier, VerifierType, DEFAULT_VERIFY_TIMEOUT};
 pub use transport::VerifierTransport;
-pub use event::{Event, VerifyResult};
+pub use event::{Event, HeartbeatMessage, RawInputEvent, RegisterMessage, VerifyResult, RAW_INPUT_CAPABILITY};
+pub use filter::{EventFilter, FilterAction, FilterDecision, FilterRule};
+pub use key_match::{KeyAction, KeyExpectation, KeyMatcher, ObservedKey};
+pub use outbox::ResultOutbox;
+pub use report::{AgentMode, RawInputQueue, RawInputReceiver};
 
-// 重新导出传输实现
-pub use transport::{WebSocketTransport, TcpTransport};
+// 重新导出取消令牌与传输实现
+pub use tokio_util::sync::CancellationToken;
+pub use transport::{WebSocketTransport, TcpTransport, TlsClientConfig, KEEPALIVE_TIMEOUT};
 
 use thiserror::Error;
 
@@ -24,6 +33,12 @@ pub enum VerifierError {
     #[error("超时")]
     Timeout,
 
+    #[error("验证已取消")]
+    Cancelled,
+
+    #[error("配置错误: {0}")]
+    ConfigError(String),
+
     #[error("IO 错误: {0}")]
     IoError(#[from] std::io::Error),
 }
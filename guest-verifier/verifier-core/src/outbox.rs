@@ -0,0 +1,240 @@
+//! 断线期间的验证结果缓冲
+//!
+//! 连接断开时发送失败的结果暂存在内存中 (有容量上限, 满时丢弃最旧的结果),
+//! 重连后按产生顺序补发。补发的结果保留原始时间戳, 并在 `details` 中标记
+//! `"replayed": true`, 服务端在迟到宽限期内据此将超时改判。
+
+use std::collections::VecDeque;
+
+use serde_json::json;
+use tracing::{debug, warn};
+
+use crate::{Result, VerifierTransport, VerifyResult};
+
+/// 验证结果发件箱
+#[derive(Debug)]
+pub struct ResultOutbox {
+    buffer: VecDeque<VerifyResult>,
+    capacity: usize,
+    dropped: u64,
+}
+
+impl ResultOutbox {
+    /// 创建最多缓冲 `capacity` 条结果的发件箱 (为 0 时不缓冲)
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    /// 发送结果, 发送失败时缓冲待重连后补发
+    ///
+    /// 有未补发的结果时先补发, 保证结果按产生顺序到达。返回结果是否已直接发出。
+    pub async fn send(&mut self, transport: &mut dyn VerifierTransport, result: VerifyResult) -> bool {
+        if !self.buffer.is_empty() && self.flush(transport).await.is_err() {
+            self.push(result);
+            return false;
+        }
+
+        match transport.send_result(&result).await {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("发送验证结果失败, 缓冲待重连后补发: {}", e);
+                self.push(result);
+                false
+            }
+        }
+    }
+
+    /// 按产生顺序补发缓冲的结果, 返回补发数量
+    ///
+    /// 中途失败时未发出的结果保留在缓冲区, 返回错误。
+    pub async fn flush(&mut self, transport: &mut dyn VerifierTransport) -> Result<usize> {
+        let mut sent = 0;
+        while let Some(result) = self.buffer.front() {
+            transport.send_result(result).await?;
+            self.buffer.pop_front();
+            sent += 1;
+        }
+        Ok(sent)
+    }
+
+    /// 缓冲一条结果并标记为补发, 超出容量时丢弃最旧的结果
+    pub fn push(&mut self, mut result: VerifyResult) {
+        if self.capacity == 0 {
+            self.dropped += 1;
+            warn!("未开启结果缓冲, 丢弃验证结果: event_id={}", result.event_id);
+            return;
+        }
+
+        mark_replayed(&mut result);
+        if self.buffer.len() >= self.capacity {
+            if let Some(oldest) = self.buffer.pop_front() {
+                self.dropped += 1;
+                warn!("结果缓冲已满, 丢弃最旧的验证结果: event_id={}", oldest.event_id);
+            }
+        }
+        self.buffer.push_back(result);
+    }
+
+    /// 待补发的结果数
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// 因缓冲已满而丢弃的结果数
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+/// 在 `details` 中加入 `"replayed": true`, 非对象的 `details` 保留在 `value` 字段中
+fn mark_replayed(result: &mut VerifyResult) {
+    match result.details.as_object_mut() {
+        Some(details) => {
+            details.insert("replayed".to_string(), json!(true));
+        }
+        None => {
+            let value = result.details.take();
+            result.details = if value.is_null() {
+                json!({ "replayed": true })
+            } else {
+                json!({ "replayed": true, "value": value })
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, HeartbeatMessage, RawInputEvent, RegisterMessage, VerifierError};
+    use async_trait::async_trait;
+
+    /// 时断时续的传输: `online` 为 false 时发送失败, `fail_after` 次成功发送后断开
+    #[derive(Default)]
+    struct FlappingTransport {
+        online: bool,
+        fail_after: Option<usize>,
+        sent: Vec<VerifyResult>,
+    }
+
+    #[async_trait]
+    impl VerifierTransport for FlappingTransport {
+        async fn connect(&mut self, _endpoint: &str, _vm_id: Option<&str>) -> Result<()> {
+            self.online = true;
+            Ok(())
+        }
+
+        async fn register(&mut self, _registration: &RegisterMessage) -> Result<()> {
+            Ok(())
+        }
+
+        async fn send_result(&mut self, result: &VerifyResult) -> Result<()> {
+            if self.fail_after == Some(0) {
+                self.online = false;
+                self.fail_after = None;
+            }
+            if !self.online {
+                return Err(VerifierError::ConnectionFailed("未连接到服务器".to_string()));
+            }
+            if let Some(remaining) = &mut self.fail_after {
+                *remaining -= 1;
+            }
+            self.sent.push(result.clone());
+            Ok(())
+        }
+
+        async fn send_raw_input_event(&mut self, _event: &RawInputEvent) -> Result<()> {
+            Ok(())
+        }
+
+        async fn send_heartbeat(&mut self, _heartbeat: &HeartbeatMessage) -> Result<()> {
+            Ok(())
+        }
+
+        async fn receive_event(&mut self) -> Result<Event> {
+            Err(VerifierError::ConnectionFailed("未连接到服务器".to_string()))
+        }
+
+        async fn disconnect(&mut self) -> Result<()> {
+            self.online = false;
+            Ok(())
+        }
+    }
+
+    fn result(event_id: &str, timestamp: i64) -> VerifyResult {
+        VerifyResult {
+            event_id: event_id.to_string(),
+            verified: true,
+            timestamp,
+            latency_ms: 3,
+            details: json!({"key": "a"}),
+        }
+    }
+
+    fn ids(results: &[VerifyResult]) -> Vec<&str> {
+        results.iter().map(|r| r.event_id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_results_replayed_after_reconnect() {
+        let mut transport = FlappingTransport::default();
+        let mut outbox = ResultOutbox::new(10);
+
+        transport.connect("server", None).await.unwrap();
+        assert!(outbox.send(&mut transport, result("e1", 100)).await);
+
+        // 断线期间产生的结果被缓冲
+        transport.disconnect().await.unwrap();
+        assert!(!outbox.send(&mut transport, result("e2", 200)).await);
+        assert!(!outbox.send(&mut transport, result("e3", 300)).await);
+        assert_eq!(outbox.len(), 2);
+
+        // 重连后补发到一半再次断开: 未发出的结果保留
+        transport.connect("server", None).await.unwrap();
+        transport.fail_after = Some(1);
+        assert!(outbox.flush(&mut transport).await.is_err());
+        assert_eq!(outbox.len(), 1);
+
+        // 再次重连后, 新结果排在补发结果之后
+        transport.connect("server", None).await.unwrap();
+        assert!(outbox.send(&mut transport, result("e4", 400)).await);
+        assert!(outbox.is_empty());
+
+        assert_eq!(ids(&transport.sent), ["e1", "e2", "e3", "e4"]);
+        // 补发的结果保留原始时间戳并带上标记
+        assert_eq!(transport.sent[1].timestamp, 200);
+        assert_eq!(transport.sent[1].details, json!({"key": "a", "replayed": true}));
+        assert_eq!(transport.sent[2].timestamp, 300);
+        assert!(transport.sent[0].details.get("replayed").is_none());
+        assert!(transport.sent[3].details.get("replayed").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_outbox_drops_oldest_when_full() {
+        let mut transport = FlappingTransport::default();
+        let mut outbox = ResultOutbox::new(2);
+
+        for (i, event_id) in ["e1", "e2", "e3"].iter().enumerate() {
+            assert!(!outbox.send(&mut transport, result(event_id, i as i64)).await);
+        }
+        assert_eq!((outbox.len(), outbox.dropped()), (2, 1));
+
+        transport.connect("server", None).await.unwrap();
+        assert_eq!(outbox.flush(&mut transport).await.unwrap(), 2);
+        assert_eq!(ids(&transport.sent), ["e2", "e3"]);
+
+        // 非对象的 details 保留在 value 字段中
+        let mut plain = result("e5", 0);
+        plain.details = json!("ok");
+        mark_replayed(&mut plain);
+        assert_eq!(plain.details, json!({"replayed": true, "value": "ok"}));
+    }
+}
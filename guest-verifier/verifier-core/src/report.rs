@@ -0,0 +1,219 @@
+//! 输入上报模式
+//!
+//! 上报模式下 Agent 不等待待验证事件, 而是把观察到的每一次按键/鼠标输入
+//! 以 `RawInputEvent` 持续发给服务端。监听线程与发送循环之间通过有界队列衔接:
+//! 服务端处理不过来时丢弃新事件并计数, 丢弃数随下一条上报一起发送。
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+
+use crate::event::SET_MODE_EVENT_TYPE;
+use crate::{Event, RawInputEvent, Result, VerifierError};
+
+/// Agent 工作模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentMode {
+    /// 等待服务端事件并验证 (默认)
+    #[default]
+    Verify,
+
+    /// 持续上报观察到的输入
+    Report,
+}
+
+impl AgentMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AgentMode::Verify => "verify",
+            AgentMode::Report => "report",
+        }
+    }
+
+    /// 解析 `set_mode` 控制事件, 其他事件返回 None
+    pub fn from_event(event: &Event) -> Option<Result<Self>> {
+        if event.event_type != SET_MODE_EVENT_TYPE {
+            return None;
+        }
+        let mode = match event.data.get("mode").and_then(|v| v.as_str()) {
+            Some(mode) => mode.parse(),
+            None => Err(VerifierError::ConfigError("set_mode 事件缺少 mode 字段".to_string())),
+        };
+        Some(mode)
+    }
+}
+
+impl FromStr for AgentMode {
+    type Err = VerifierError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "verify" => Ok(AgentMode::Verify),
+            "report" => Ok(AgentMode::Report),
+            _ => Err(VerifierError::ConfigError(format!(
+                "未知的工作模式: {} (可选: verify, report)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for AgentMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// 丢弃计数
+#[derive(Debug, Default)]
+struct DropCounter {
+    /// 尚未随上报发出的丢弃数
+    pending: AtomicU64,
+    /// 累计丢弃数
+    total: AtomicU64,
+}
+
+/// 原始输入队列的写端
+///
+/// 可在监听线程 (evdev 读取线程、Windows Hook 回调) 中同步调用, 不会阻塞。
+/// 关闭上报时监听线程继续运行, 但事件不再入队。
+#[derive(Debug, Clone)]
+pub struct RawInputQueue {
+    tx: mpsc::Sender<RawInputEvent>,
+    enabled: Arc<AtomicBool>,
+    drops: Arc<DropCounter>,
+}
+
+/// 原始输入队列的读端
+#[derive(Debug)]
+pub struct RawInputReceiver {
+    rx: mpsc::Receiver<RawInputEvent>,
+    drops: Arc<DropCounter>,
+}
+
+impl RawInputQueue {
+    /// 创建容量为 `capacity` 的队列 (初始为关闭状态)
+    pub fn bounded(capacity: usize) -> (Self, RawInputReceiver) {
+        let (tx, rx) = mpsc::channel(capacity.max(1));
+        let drops = Arc::new(DropCounter::default());
+        (
+            Self {
+                tx,
+                enabled: Arc::new(AtomicBool::new(false)),
+                drops: drops.clone(),
+            },
+            RawInputReceiver { rx, drops },
+        )
+    }
+
+    /// 入队一条事件, 队列已满时丢弃并计数; 返回是否入队
+    pub fn push(&self, event: RawInputEvent) -> bool {
+        if !self.is_enabled() {
+            return false;
+        }
+        match self.tx.try_send(event) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                self.drops.pending.fetch_add(1, Ordering::Relaxed);
+                self.drops.total.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// 开启或关闭上报
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// 累计丢弃的事件数
+    pub fn dropped(&self) -> u64 {
+        self.drops.total.load(Ordering::Relaxed)
+    }
+}
+
+impl RawInputReceiver {
+    /// 取出下一条事件, 并附上此前累积的丢弃数
+    ///
+    /// 可安全地在 `tokio::select!` 中取消。
+    pub async fn recv(&mut self) -> Option<RawInputEvent> {
+        let mut event = self.rx.recv().await?;
+        event.dropped = self.drops.pending.swap(0, Ordering::Relaxed);
+        Some(event)
+    }
+
+    /// 不等待地取出下一条事件 (切换回验证模式时用于发送剩余事件)
+    pub fn try_recv(&mut self) -> Option<RawInputEvent> {
+        let mut event = self.rx.try_recv().ok()?;
+        event.dropped = self.drops.pending.swap(0, Ordering::Relaxed);
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(code: &str) -> RawInputEvent {
+        RawInputEvent::new("keyboard", code, 1)
+    }
+
+    #[tokio::test]
+    async fn test_queue_drops_when_full() {
+        let (queue, mut receiver) = RawInputQueue::bounded(2);
+
+        // 关闭状态下不入队, 也不计为丢弃
+        assert!(!queue.push(key("KEY_A")));
+        assert_eq!(queue.dropped(), 0);
+
+        queue.set_enabled(true);
+        assert!(queue.push(key("KEY_A")));
+        assert!(queue.push(key("KEY_B")));
+        assert!(!queue.push(key("KEY_C")));
+        assert!(!queue.push(key("KEY_D")));
+        assert_eq!(queue.dropped(), 2);
+
+        // 丢弃数随下一条上报发出, 之后清零
+        let first = receiver.recv().await.unwrap();
+        assert_eq!((first.code.as_str(), first.dropped), ("KEY_A", 2));
+        assert!(queue.push(key("KEY_E")));
+        let second = receiver.try_recv().unwrap();
+        assert_eq!((second.code.as_str(), second.dropped), ("KEY_B", 0));
+        assert_eq!(receiver.try_recv().unwrap().code, "KEY_E");
+        assert!(receiver.try_recv().is_none());
+        assert_eq!(queue.dropped(), 2);
+    }
+
+    #[test]
+    fn test_parse_set_mode_event() {
+        let event = |event_type: &str, data| Event {
+            event_type: event_type.to_string(),
+            data,
+            timestamp: 0,
+        };
+
+        let mode = AgentMode::from_event(&event("set_mode", json!({"mode": "report"})));
+        assert_eq!(mode.unwrap().unwrap(), AgentMode::Report);
+        let mode = AgentMode::from_event(&event("set_mode", json!({"mode": "Verify"})));
+        assert_eq!(mode.unwrap().unwrap(), AgentMode::Verify);
+
+        assert!(AgentMode::from_event(&event("set_mode", json!({"mode": "idle"}))).unwrap().is_err());
+        assert!(AgentMode::from_event(&event("set_mode", json!({}))).unwrap().is_err());
+        assert!(AgentMode::from_event(&event("keyboard", json!({"mode": "report"}))).is_none());
+
+        let raw = serde_json::to_value(key("KEY_A")).unwrap();
+        assert_eq!(raw["message_type"], "raw_input");
+        assert!(raw.get("x").is_none());
+    }
+}
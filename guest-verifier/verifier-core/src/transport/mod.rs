@@ -2,12 +2,20 @@
 
 pub mod websocket;
 pub mod tcp;
+pub mod tls;
 
-pub use websocket::WebSocketTransport;
+pub use websocket::{WebSocketTransport, KEEPALIVE_TIMEOUT};
 pub use tcp::TcpTransport;
+pub use tls::TlsClientConfig;
+
+use std::time::Instant;
 
 use async_trait::async_trait;
-use crate::{Event, Result, VerifyResult};
+use atp_common::{decode, encode, AgreedVersion, MessageType};
+use serde::Serialize;
+use tracing::{debug, info};
+
+use crate::{Event, HeartbeatMessage, RawInputEvent, RegisterMessage, Result, VerifierError, VerifyResult};
 
 /// 传输层抽象接口
 #[async_trait]
@@ -19,12 +27,83 @@ pub trait VerifierTransport: Send + Sync {
     /// - `vm_id`: 虚拟机 ID（可选，用于客户端标识）
     async fn connect(&mut self, endpoint: &str, vm_id: Option<&str>) -> Result<()>;
 
+    /// 发送注册消息 (在 `connect` 之后调用)
+    async fn register(&mut self, registration: &RegisterMessage) -> Result<()>;
+
     /// 发送验证结果
     async fn send_result(&mut self, result: &VerifyResult) -> Result<()>;
 
+    /// 发送上报模式下观察到的原始输入
+    async fn send_raw_input_event(&mut self, event: &RawInputEvent) -> Result<()>;
+
+    /// 发送心跳
+    async fn send_heartbeat(&mut self, heartbeat: &HeartbeatMessage) -> Result<()>;
+
     /// 接收事件
+    ///
+    /// 可在 `tokio::select!` 中取消: 已读取的部分数据保留到下次调用。
     async fn receive_event(&mut self) -> Result<Event>;
 
+    /// 最近一次成功发送数据消息的时间, 未连接或不跟踪时为 `None`
+    ///
+    /// Agent 据此在最近已有上行消息时跳过心跳。
+    fn last_activity(&self) -> Option<Instant> {
+        None
+    }
+
     /// 断开连接
     async fn disconnect(&mut self) -> Result<()>;
 }
+
+/// 服务端发来的消息
+pub(crate) enum ServerMessage {
+    /// 待验证事件
+    Event(Event),
+
+    /// 服务端接受注册并确认了协议版本
+    Accepted(AgreedVersion),
+
+    /// 服务端拒绝连接, 附带原因
+    Rejected(String),
+
+    /// 本端不认识的消息, 跳过
+    Ignored,
+}
+
+/// 解析服务端消息: 信封或旧版裸消息 (不带类型的为事件)
+pub(crate) fn parse_server_message(text: &str) -> Result<ServerMessage> {
+    let invalid = |e: atp_common::EnvelopeError| VerifierError::ConnectionFailed(format!("解析事件失败: {}", e));
+    let envelope = decode(text, MessageType::Event).map_err(invalid)?;
+
+    match envelope.message_type {
+        MessageType::Event => Ok(ServerMessage::Event(envelope.decode_as(MessageType::Event).map_err(invalid)?)),
+        MessageType::Accepted if !envelope.is_legacy() => {
+            info!("服务端确认协议版本: {}", envelope.protocol_version);
+            Ok(ServerMessage::Accepted(AgreedVersion::Version(envelope.protocol_version)))
+        }
+        MessageType::Rejected => {
+            let reason = envelope
+                .payload
+                .get("reason")
+                .and_then(|v| v.as_str())
+                .unwrap_or("未知原因")
+                .to_string();
+            Ok(ServerMessage::Rejected(reason))
+        }
+        other => {
+            debug!("忽略不支持的服务端消息: {}", other);
+            Ok(ServerMessage::Ignored)
+        }
+    }
+}
+
+/// 按协商结果编码发往服务端的消息, `what` 用于错误信息
+pub(crate) fn encode_message<T: Serialize>(
+    agreed: AgreedVersion,
+    message_type: MessageType,
+    payload: &T,
+    what: &str,
+) -> Result<String> {
+    encode(agreed, message_type, payload)
+        .map_err(|e| VerifierError::ConnectionFailed(format!("序列化{}失败: {}", what, e)))
+}
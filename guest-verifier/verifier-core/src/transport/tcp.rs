@@ -1,17 +1,140 @@
 //! TCP 传输实现
+//!
+//! 帧格式: 1 字节协议版本 + 4 字节大端长度 + JSON 消息体。
+//! 旧版服务端不识别版本字节, 连接旧版服务端时需开启兼容模式 (无版本字节的长度前缀)。
+//! 配置 TLS 后在 TCP 连接上先完成 TLS 握手, 帧格式不变。
 
 use async_trait::async_trait;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tracing::{debug, error, info};
 
-use crate::{Event, Result, VerifierError, VerifyResult};
-use super::VerifierTransport;
+use atp_common::{AgreedVersion, MessageType};
+
+use crate::{Event, HeartbeatMessage, RawInputEvent, RegisterMessage, Result, VerifierError, VerifyResult};
+use super::tls::{TlsClientConfig, TransportStream};
+use super::{encode_message, parse_server_message, ServerMessage, VerifierTransport};
+
+/// 当前协议版本
+pub const PROTOCOL_VERSION: u8 = 1;
+
+/// 最大帧长度 (10MB)
+pub const MAX_FRAME_SIZE: usize = 10 * 1024 * 1024;
+
+/// 写入一帧, `legacy` 为 true 时省略版本字节
+async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, body: &str, legacy: bool) -> Result<()> {
+    if body.len() > MAX_FRAME_SIZE {
+        return Err(VerifierError::ConnectionFailed(format!(
+            "消息过大: {} bytes (最大: {} bytes)",
+            body.len(),
+            MAX_FRAME_SIZE
+        )));
+    }
+
+    let mut frame = Vec::with_capacity(body.len() + 5);
+    if !legacy {
+        frame.push(PROTOCOL_VERSION);
+    }
+    frame.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    frame.extend_from_slice(body.as_bytes());
+
+    writer.write_all(&frame).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// 帧读取器
+///
+/// 已读到但尚未组成完整帧的字节保存在缓冲区中, 因此 `read_frame` 可以被安全地取消
+/// (如在 `tokio::select!` 中与其他分支竞争), 下次调用时从缓冲区继续解析。
+struct FrameReader {
+    buffer: Vec<u8>,
+    max_frame_size: usize,
+    legacy: bool,
+}
+
+impl FrameReader {
+    /// `legacy` 为 true 时帧不带版本字节
+    fn new(max_frame_size: usize, legacy: bool) -> Self {
+        Self {
+            buffer: Vec::new(),
+            max_frame_size,
+            legacy,
+        }
+    }
+
+    /// 读取一帧
+    async fn read_frame<R: AsyncRead + Unpin>(&mut self, reader: &mut R) -> Result<String> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(frame) = self.decode()? {
+                return Ok(frame);
+            }
+
+            let n = reader.read(&mut chunk).await?;
+            if n == 0 {
+                return Err(VerifierError::IoError(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// 从缓冲区取出一个完整帧, 数据不足时返回 None
+    fn decode(&mut self) -> Result<Option<String>> {
+        let header_len = if self.legacy { 4 } else { 5 };
+
+        if !self.legacy {
+            match self.buffer.first() {
+                Some(&PROTOCOL_VERSION) => {}
+                Some(&version) => {
+                    return Err(VerifierError::ConnectionFailed(format!(
+                        "不支持的协议版本: {} (当前版本: {}), 服务端可能是旧版本, 可开启兼容模式",
+                        version, PROTOCOL_VERSION
+                    )));
+                }
+                None => return Ok(None),
+            }
+        }
+        if self.buffer.len() < header_len {
+            return Ok(None);
+        }
+
+        let mut len_bytes = [0u8; 4];
+        len_bytes.copy_from_slice(&self.buffer[header_len - 4..header_len]);
+        let len = u32::from_be_bytes(len_bytes) as usize;
+        if len > self.max_frame_size {
+            return Err(VerifierError::ConnectionFailed(format!(
+                "消息过大: {} bytes (最大: {} bytes)",
+                len, self.max_frame_size
+            )));
+        }
+        if self.buffer.len() < header_len + len {
+            return Ok(None);
+        }
+
+        let body: Vec<u8> = self.buffer.drain(..header_len + len).skip(header_len).collect();
+        String::from_utf8(body).map(Some).map_err(|e| {
+            error!("解码 UTF-8 失败: {}", e);
+            VerifierError::ConnectionFailed(format!("UTF-8 解码失败: {}", e))
+        })
+    }
+}
+
+/// 取 `host:port` 中的主机部分, 用作 TLS 服务器名称
+fn endpoint_host(endpoint: &str) -> &str {
+    let host = endpoint.rsplit_once(':').map_or(endpoint, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
 
 /// TCP 传输实现
 pub struct TcpTransport {
-    stream: Option<TcpStream>,
+    stream: Option<TransportStream>,
+    reader: FrameReader,
     endpoint: Option<String>,
+    legacy_framing: bool,
+    tls: Option<TlsClientConfig>,
+    /// 与服务端协商出的协议版本 (收到 `accepted` 之前为裸消息)
+    agreed: AgreedVersion,
 }
 
 impl TcpTransport {
@@ -19,10 +142,26 @@ impl TcpTransport {
     pub fn new() -> Self {
         Self {
             stream: None,
+            reader: FrameReader::new(MAX_FRAME_SIZE, false),
             endpoint: None,
+            legacy_framing: false,
+            tls: None,
+            agreed: AgreedVersion::Legacy,
         }
     }
 
+    /// 兼容模式: 使用无版本字节的旧帧格式, 用于连接旧版服务端
+    pub fn with_legacy_framing(mut self, legacy_framing: bool) -> Self {
+        self.legacy_framing = legacy_framing;
+        self
+    }
+
+    /// 使用 TLS 连接服务端
+    pub fn with_tls(mut self, tls: TlsClientConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
     /// 检查连接是否存在
     fn ensure_connected(&self) -> Result<()> {
         if self.stream.is_none() {
@@ -33,64 +172,25 @@ impl TcpTransport {
         Ok(())
     }
 
-    /// 发送 JSON 消息（长度前缀格式）
+    /// 发送 JSON 消息
     async fn send_json(&mut self, json: &str) -> Result<()> {
         if let Some(stream) = &mut self.stream {
-            // 发送长度（4字节，大端序）
-            let len = json.len() as u32;
-            stream.write_u32(len).await.map_err(|e| {
-                error!("发送消息长度失败: {}", e);
-                VerifierError::IoError(e)
-            })?;
-
-            // 发送 JSON 数据
-            stream.write_all(json.as_bytes()).await.map_err(|e| {
-                error!("发送消息内容失败: {}", e);
-                VerifierError::IoError(e)
-            })?;
-
-            stream.flush().await.map_err(|e| {
-                error!("刷新输出缓冲失败: {}", e);
-                VerifierError::IoError(e)
-            })?;
-
-            Ok(())
+            write_frame(stream, json, self.legacy_framing).await.map_err(|e| {
+                error!("发送消息失败: {}", e);
+                e
+            })
         } else {
             Err(VerifierError::ConnectionFailed("未连接".to_string()))
         }
     }
 
-    /// 接收 JSON 消息（长度前缀格式）
+    /// 接收 JSON 消息
     async fn receive_json(&mut self) -> Result<String> {
         if let Some(stream) = &mut self.stream {
-            // 读取长度（4字节，大端序）
-            let len = stream.read_u32().await.map_err(|e| {
-                error!("读取消息长度失败: {}", e);
-                VerifierError::IoError(e)
-            })?;
-
-            // 限制消息大小（最大 10MB）
-            const MAX_MESSAGE_SIZE: u32 = 10 * 1024 * 1024;
-            if len > MAX_MESSAGE_SIZE {
-                return Err(VerifierError::ConnectionFailed(format!(
-                    "消息过大: {} bytes (最大: {} bytes)",
-                    len, MAX_MESSAGE_SIZE
-                )));
-            }
-
-            // 读取 JSON 数据
-            let mut buffer = vec![0u8; len as usize];
-            stream.read_exact(&mut buffer).await.map_err(|e| {
-                error!("读取消息内容失败: {}", e);
-                VerifierError::IoError(e)
-            })?;
-
-            let json = String::from_utf8(buffer).map_err(|e| {
-                error!("解码 UTF-8 失败: {}", e);
-                VerifierError::ConnectionFailed(format!("UTF-8 解码失败: {}", e))
-            })?;
-
-            Ok(json)
+            self.reader.read_frame(stream).await.map_err(|e| {
+                error!("接收消息失败: {}", e);
+                e
+            })
         } else {
             Err(VerifierError::ConnectionFailed("未连接".to_string()))
         }
@@ -108,62 +208,89 @@ impl VerifierTransport for TcpTransport {
     async fn connect(&mut self, endpoint: &str, vm_id: Option<&str>) -> Result<()> {
         info!("连接到 TCP 服务器: {}", endpoint);
 
-        match TcpStream::connect(endpoint).await {
-            Ok(mut stream) => {
-                info!("成功连接到 TCP 服务器");
-
-                // 发送 VM ID（如果提供，使用长度前缀格式）
-                if let Some(vm_id) = vm_id {
-                    debug!("发送 VM ID: {}", vm_id);
-                    let vm_id_bytes = vm_id.as_bytes();
-                    stream.write_u32(vm_id_bytes.len() as u32).await.map_err(|e| {
-                        error!("发送 VM ID 长度失败: {}", e);
-                        VerifierError::IoError(e)
-                    })?;
-                    stream.write_all(vm_id_bytes).await.map_err(|e| {
-                        error!("发送 VM ID 失败: {}", e);
-                        VerifierError::IoError(e)
-                    })?;
-                    stream.flush().await.map_err(|e| {
-                        error!("刷新失败: {}", e);
-                        VerifierError::IoError(e)
-                    })?;
-                }
+        let stream = TcpStream::connect(endpoint).await.map_err(|e| {
+            error!("TCP 连接失败: {}", e);
+            VerifierError::IoError(e)
+        })?;
 
-                self.stream = Some(stream);
-                self.endpoint = Some(endpoint.to_string());
-                Ok(())
-            }
-            Err(e) => {
-                error!("TCP 连接失败: {}", e);
-                Err(VerifierError::IoError(e))
-            }
+        let mut stream = match &self.tls {
+            Some(tls) => tls.connect(endpoint_host(endpoint), stream).await?,
+            None => TransportStream::Plain(stream),
+        };
+        info!("成功连接到 TCP 服务器{}", if self.tls.is_some() { " (TLS)" } else { "" });
+
+        // 发送 VM ID（如果提供）
+        if let Some(vm_id) = vm_id {
+            debug!("发送 VM ID: {}", vm_id);
+            write_frame(&mut stream, vm_id, self.legacy_framing).await.map_err(|e| {
+                error!("发送 VM ID 失败: {}", e);
+                e
+            })?;
         }
+
+        self.stream = Some(stream);
+        self.reader = FrameReader::new(MAX_FRAME_SIZE, self.legacy_framing);
+        self.endpoint = Some(endpoint.to_string());
+        self.agreed = AgreedVersion::Legacy;
+        Ok(())
     }
 
-    async fn send_result(&mut self, result: &VerifyResult) -> Result<()> {
+    async fn register(&mut self, registration: &RegisterMessage) -> Result<()> {
         self.ensure_connected()?;
 
-        let json = serde_json::to_string(result).map_err(|e| {
-            VerifierError::ConnectionFailed(format!("序列化验证结果失败: {}", e))
+        let json = serde_json::to_string(registration).map_err(|e| {
+            VerifierError::ConnectionFailed(format!("序列化注册消息失败: {}", e))
         })?;
 
+        debug!("发送注册消息: {}", json);
+        self.send_json(&json).await
+    }
+
+    async fn send_result(&mut self, result: &VerifyResult) -> Result<()> {
+        self.ensure_connected()?;
+
+        let json = encode_message(self.agreed, MessageType::Result, result, "验证结果")?;
+
         debug!("发送验证结果: {}", json);
         self.send_json(&json).await
     }
 
-    async fn receive_event(&mut self) -> Result<Event> {
+    async fn send_raw_input_event(&mut self, event: &RawInputEvent) -> Result<()> {
         self.ensure_connected()?;
 
-        let json = self.receive_json().await?;
-        debug!("接收到事件: {}", json);
+        let json = encode_message(self.agreed, MessageType::RawInput, event, "输入事件")?;
 
-        let event: Event = serde_json::from_str(&json).map_err(|e| {
-            error!("解析事件失败: {}", e);
-            VerifierError::ConnectionFailed(format!("解析事件失败: {}", e))
-        })?;
+        self.send_json(&json).await
+    }
+
+    async fn send_heartbeat(&mut self, heartbeat: &HeartbeatMessage) -> Result<()> {
+        self.ensure_connected()?;
+
+        let json = encode_message(self.agreed, MessageType::Heartbeat, heartbeat, "心跳")?;
+
+        debug!("发送心跳: {}", json);
+        self.send_json(&json).await
+    }
+
+    async fn receive_event(&mut self) -> Result<Event> {
+        self.ensure_connected()?;
+
+        loop {
+            let json = self.receive_json().await?;
+            debug!("接收到事件: {}", json);
 
-        Ok(event)
+            match parse_server_message(&json)? {
+                ServerMessage::Event(event) => return Ok(event),
+                ServerMessage::Accepted(agreed) => self.agreed = agreed,
+                // 服务端拒绝连接 (如同一 VM ID 已有连接) 后会关闭连接
+                ServerMessage::Rejected(reason) => {
+                    error!("服务端拒绝连接: {}", reason);
+                    self.stream = None;
+                    return Err(VerifierError::ConnectionFailed(format!("连接被拒绝: {}", reason)));
+                }
+                ServerMessage::Ignored => {}
+            }
+        }
     }
 
     async fn disconnect(&mut self) -> Result<()> {
@@ -194,5 +321,142 @@ mod tests {
     fn test_tcp_transport_default() {
         let transport = TcpTransport::default();
         assert!(transport.stream.is_none());
+        assert!(!transport.legacy_framing);
+    }
+
+    #[test]
+    fn test_endpoint_host() {
+        assert_eq!(endpoint_host("verify.lab:8766"), "verify.lab");
+        assert_eq!(endpoint_host("127.0.0.1:8766"), "127.0.0.1");
+        assert_eq!(endpoint_host("[::1]:8766"), "::1");
+    }
+
+    #[tokio::test]
+    async fn test_frames_round_trip_across_partial_writes() {
+        let mut stream = Vec::new();
+        write_frame(&mut stream, "vm-1", false).await.unwrap();
+        write_frame(&mut stream, r#"{"output":"a\nb"}"#, false).await.unwrap();
+        assert_eq!(&stream[..5], &[PROTOCOL_VERSION, 0, 0, 0, 4]);
+
+        // 写端每次只写 3 字节, 连续的帧仍能正确拆分
+        let (mut client, mut server) = tokio::io::duplex(3);
+        let writer = tokio::spawn(async move {
+            for chunk in stream.chunks(3) {
+                client.write_all(chunk).await.unwrap();
+            }
+        });
+
+        let mut reader = FrameReader::new(MAX_FRAME_SIZE, false);
+        assert_eq!(reader.read_frame(&mut server).await.unwrap(), "vm-1");
+        assert_eq!(
+            reader.read_frame(&mut server).await.unwrap(),
+            r#"{"output":"a\nb"}"#
+        );
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_rejects_bad_frames() {
+        // 超出上限的长度在读取消息体之前被拒绝
+        let mut reader: &[u8] = &[PROTOCOL_VERSION, 0xff, 0xff, 0xff, 0xff];
+        let err = FrameReader::new(16, false).read_frame(&mut reader).await.unwrap_err();
+        assert!(err.to_string().contains("消息过大"), "{}", err);
+
+        // 旧版服务端的帧没有版本字节
+        let mut legacy = Vec::new();
+        write_frame(&mut legacy, "{}", true).await.unwrap();
+        let err = FrameReader::new(MAX_FRAME_SIZE, false)
+            .read_frame(&mut legacy.as_slice())
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("不支持的协议版本: 0"), "{}", err);
+        assert_eq!(
+            FrameReader::new(MAX_FRAME_SIZE, true).read_frame(&mut legacy.as_slice()).await.unwrap(),
+            "{}"
+        );
+
+        // 帧中途断开
+        let mut reader: &[u8] = &[PROTOCOL_VERSION, 0, 0, 0, 8, b'{'];
+        assert!(matches!(
+            FrameReader::new(MAX_FRAME_SIZE, false).read_frame(&mut reader).await,
+            Err(VerifierError::IoError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_switches_to_envelopes_after_accepted() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // 模拟服务端: 收到注册后回复 accepted, 再下发信封形式的事件, 返回 Agent 之后发来的两条消息
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut reader = FrameReader::new(MAX_FRAME_SIZE, false);
+            assert_eq!(reader.read_frame(&mut stream).await.unwrap(), "vm-1");
+
+            let heartbeat = reader.read_frame(&mut stream).await.unwrap();
+            let registration: serde_json::Value =
+                serde_json::from_str(&reader.read_frame(&mut stream).await.unwrap()).unwrap();
+            assert_eq!(registration["protocol_version"], atp_common::PROTOCOL_VERSION);
+
+            let agreed = AgreedVersion::Version(atp_common::PROTOCOL_VERSION);
+            let accepted = atp_common::AcceptedPayload { vm_id: "vm-1".to_string(), supported_versions: vec![1] };
+            let event = Event {
+                event_type: "keyboard".to_string(),
+                data: serde_json::json!({"event_id": "e-1"}),
+                timestamp: 0,
+            };
+            for json in [
+                atp_common::encode(agreed, MessageType::Accepted, &accepted).unwrap(),
+                r#"{"protocol_version":1,"message_type":"screenshot","payload":{}}"#.to_string(),
+                atp_common::encode(agreed, MessageType::Event, &event).unwrap(),
+            ] {
+                write_frame(&mut stream, &json, false).await.unwrap();
+            }
+            (heartbeat, reader.read_frame(&mut stream).await.unwrap())
+        });
+
+        let mut transport = TcpTransport::new();
+        transport.connect(&addr.to_string(), Some("vm-1")).await.unwrap();
+        // 收到 accepted 之前发送裸消息
+        transport.send_heartbeat(&HeartbeatMessage::new("vm-1", 1)).await.unwrap();
+        transport.register(&RegisterMessage::new("vm-1")).await.unwrap();
+
+        // 跳过 accepted 与不认识的消息类型
+        let event = transport.receive_event().await.unwrap();
+        assert_eq!(event.data["event_id"], "e-1");
+        let result = VerifyResult {
+            event_id: "e-1".to_string(),
+            verified: true,
+            timestamp: 0,
+            latency_ms: 1,
+            details: serde_json::json!({}),
+        };
+        transport.send_result(&result).await.unwrap();
+
+        let (heartbeat, reply) = server.await.unwrap();
+        assert!(atp_common::decode(&heartbeat, MessageType::Result).unwrap().is_legacy());
+        let reply = atp_common::decode(&reply, MessageType::Result).unwrap();
+        assert!(!reply.is_legacy());
+        let reply: VerifyResult = reply.decode_as(MessageType::Result).unwrap();
+        assert_eq!(reply.event_id, "e-1");
+    }
+
+    #[tokio::test]
+    async fn test_read_frame_resumes_after_cancel() {
+        let mut frame = Vec::new();
+        write_frame(&mut frame, r#"{"event_type":"set_mode"}"#, false).await.unwrap();
+
+        let (mut client, mut server) = tokio::io::duplex(64);
+        let mut reader = FrameReader::new(MAX_FRAME_SIZE, false);
+
+        // 只收到半帧时被取消, 已读取的字节不会丢失
+        client.write_all(&frame[..7]).await.unwrap();
+        let cancelled =
+            tokio::time::timeout(std::time::Duration::from_millis(20), reader.read_frame(&mut server)).await;
+        assert!(cancelled.is_err());
+
+        client.write_all(&frame[7..]).await.unwrap();
+        assert_eq!(reader.read_frame(&mut server).await.unwrap(), r#"{"event_type":"set_mode"}"#);
     }
 }
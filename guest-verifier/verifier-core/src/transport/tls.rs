@@ -0,0 +1,353 @@
+//! TLS 客户端配置
+//!
+//! WebSocket (`wss://`) 与 TCP 传输共用。默认信任 Mozilla 根证书,
+//! 指定 CA 证书后只信任该 CA; 服务端要求客户端证书时可配置证书与私钥。
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::client::danger::{
+    HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier,
+};
+use tokio_rustls::rustls::crypto::{self, ring, WebPkiSupportedAlgorithms};
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use tokio_rustls::rustls::{
+    self, AlertDescription, ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme,
+};
+use tokio_rustls::TlsConnector;
+use tracing::{error, warn};
+
+use crate::{Result, VerifierError};
+
+/// TLS 客户端配置
+#[derive(Debug, Clone, Default)]
+pub struct TlsClientConfig {
+    ca_cert: Option<PathBuf>,
+    insecure: bool,
+    client_cert: Option<(PathBuf, PathBuf)>,
+}
+
+impl TlsClientConfig {
+    /// 使用系统内置 (Mozilla) 根证书校验服务端
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 只信任指定的 CA 证书 (PEM), 用于实验室自签证书
+    pub fn with_ca_cert(mut self, path: impl Into<PathBuf>) -> Self {
+        self.ca_cert = Some(path.into());
+        self
+    }
+
+    /// 跳过服务端证书校验, 仅用于调试
+    pub fn with_insecure(mut self, insecure: bool) -> Self {
+        self.insecure = insecure;
+        self
+    }
+
+    /// 客户端证书与私钥 (PEM), 服务端开启客户端证书校验时使用
+    pub fn with_client_cert(mut self, cert: impl Into<PathBuf>, key: impl Into<PathBuf>) -> Self {
+        self.client_cert = Some((cert.into(), key.into()));
+        self
+    }
+
+    /// 构建 TLS 连接器
+    pub fn connector(&self) -> Result<TlsConnector> {
+        let builder = if self.insecure {
+            warn!("已关闭服务端证书校验, 连接不受中间人攻击保护");
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(NoVerification::new()))
+        } else {
+            ClientConfig::builder().with_root_certificates(self.root_store()?)
+        };
+
+        let config = match &self.client_cert {
+            Some((cert, key)) => builder
+                .with_client_auth_cert(load_certs(cert)?, load_key(key)?)
+                .map_err(|e| VerifierError::ConfigError(format!("客户端证书与私钥不匹配: {}", e)))?,
+            None => builder.with_no_client_auth(),
+        };
+
+        Ok(TlsConnector::from(Arc::new(config)))
+    }
+
+    /// 在已建立的 TCP 连接上完成 TLS 握手
+    pub(crate) async fn connect(&self, host: &str, stream: TcpStream) -> Result<TransportStream> {
+        let server_name = ServerName::try_from(host.to_string()).map_err(|e| {
+            VerifierError::ConfigError(format!("无效的 TLS 服务器名称 {}: {}", host, e))
+        })?;
+
+        let stream = self
+            .connector()?
+            .connect(server_name, stream)
+            .await
+            .map_err(|e| {
+                let e = handshake_error(e);
+                error!("{}", e);
+                e
+            })?;
+
+        Ok(TransportStream::Tls(Box::new(stream)))
+    }
+
+    fn root_store(&self) -> Result<RootCertStore> {
+        let mut roots = RootCertStore::empty();
+        match &self.ca_cert {
+            Some(path) => {
+                for cert in load_certs(path)? {
+                    roots.add(cert).map_err(|e| tls_file_error(path, e))?;
+                }
+            }
+            None => roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned()),
+        }
+        Ok(roots)
+    }
+}
+
+/// 将握手失败转换为带 TLS 告警描述的连接错误
+fn handshake_error(e: io::Error) -> VerifierError {
+    let reason = match e.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+        Some(rustls::Error::AlertReceived(alert)) => format!("服务端发送告警 {:?}", alert),
+        Some(rustls::Error::InvalidCertificate(cert_error)) => format!(
+            "服务端证书无效 ({:?}), 已发送告警 {:?}",
+            cert_error,
+            AlertDescription::from(cert_error.clone())
+        ),
+        Some(other) => other.to_string(),
+        None => e.to_string(),
+    };
+    VerifierError::ConnectionFailed(format!("TLS 握手失败: {}", reason))
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(open(path)?))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| tls_file_error(path, e))?;
+    if certs.is_empty() {
+        return Err(tls_file_error(path, "文件中没有证书"));
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &Path) -> Result<PrivateKeyDer<'static>> {
+    rustls_pemfile::private_key(&mut BufReader::new(open(path)?))
+        .map_err(|e| tls_file_error(path, e))?
+        .ok_or_else(|| tls_file_error(path, "文件中没有私钥"))
+}
+
+fn open(path: &Path) -> Result<File> {
+    File::open(path).map_err(|e| tls_file_error(path, e))
+}
+
+fn tls_file_error(path: &Path, e: impl fmt::Display) -> VerifierError {
+    VerifierError::ConfigError(format!("读取 TLS 文件 {} 失败: {}", path.display(), e))
+}
+
+/// 不校验服务端证书 (仍校验握手签名)
+#[derive(Debug)]
+struct NoVerification {
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl NoVerification {
+    fn new() -> Self {
+        Self {
+            algorithms: ring::default_provider().signature_verification_algorithms,
+        }
+    }
+}
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// 明文或 TLS 连接
+pub enum TransportStream {
+    Plain(TcpStream),
+    Tls(Box<TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for TransportStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for TransportStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Self::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio_rustls::rustls::ServerConfig;
+    use tokio_rustls::TlsAcceptor;
+
+    /// 生成自签证书, 写入临时目录并返回 (证书路径, 私钥路径, 对应的服务端 acceptor)
+    fn self_signed(dir: &Path, name: &str) -> (PathBuf, PathBuf, TlsAcceptor) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let cert_pem = cert.serialize_pem().unwrap();
+        let key_pem = cert.serialize_private_key_pem();
+
+        let cert_path = dir.join(format!("{}.crt", name));
+        let key_path = dir.join(format!("{}.key", name));
+        std::fs::write(&cert_path, &cert_pem).unwrap();
+        std::fs::write(&key_path, &key_pem).unwrap();
+
+        let config = ServerConfig::builder()
+            .with_no_client_auth()
+            .with_single_cert(load_certs(&cert_path).unwrap(), load_key(&key_path).unwrap())
+            .unwrap();
+        (cert_path, key_path, TlsAcceptor::from(Arc::new(config)))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("verifier-tls-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    async fn serve_once(acceptor: TlsAcceptor) -> (String, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let handle = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            if let Ok(mut tls) = acceptor.accept(stream).await {
+                let mut buf = [0u8; 4];
+                tls.read_exact(&mut buf).await.unwrap();
+                tls.write_all(&buf).await.unwrap();
+                tls.flush().await.unwrap();
+            }
+        });
+        (addr, handle)
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_ca_cert() {
+        let dir = temp_dir("ca");
+        let (cert, _, acceptor) = self_signed(&dir, "server");
+        let (addr, server) = serve_once(acceptor).await;
+
+        let config = TlsClientConfig::new().with_ca_cert(&cert);
+        let tcp = TcpStream::connect(&addr).await.unwrap();
+        let mut stream = config.connect("localhost", tcp).await.unwrap();
+        stream.write_all(b"ping").await.unwrap();
+        stream.flush().await.unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+
+        server.await.unwrap();
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_server_reports_alert() {
+        let dir = temp_dir("untrusted");
+        let (_, _, acceptor) = self_signed(&dir, "server");
+        let (other_ca, _, _) = self_signed(&dir, "other");
+        let (addr, server) = serve_once(acceptor).await;
+
+        let config = TlsClientConfig::new().with_ca_cert(&other_ca);
+        let tcp = TcpStream::connect(&addr).await.unwrap();
+        let err = match config.connect("localhost", tcp).await {
+            Err(e) => e,
+            Ok(_) => panic!("不受信任的证书应握手失败"),
+        };
+        match err {
+            VerifierError::ConnectionFailed(msg) => {
+                assert!(msg.contains("TLS 握手失败"), "{}", msg);
+                // 两张自签证书主题相同, 具体告警为签名错误而非未知 CA
+                assert!(msg.contains("已发送告警"), "{}", msg);
+            }
+            other => panic!("期望 ConnectionFailed, 实际: {:?}", other),
+        }
+
+        server.await.unwrap();
+
+        // 跳过校验时可以连接同一类服务端
+        let (_, _, acceptor) = self_signed(&dir, "server2");
+        let (addr, server) = serve_once(acceptor).await;
+        let tcp = TcpStream::connect(&addr).await.unwrap();
+        let mut stream = TlsClientConfig::new()
+            .with_insecure(true)
+            .connect("localhost", tcp)
+            .await
+            .unwrap();
+        stream.write_all(b"pong").await.unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+
+        server.await.unwrap();
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
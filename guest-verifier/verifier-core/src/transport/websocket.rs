@@ -1,41 +1,236 @@
 //! WebSocket 传输实现
+//!
+//! 开启保活后, 后台任务每隔固定间隔发送 Ping; 超过保活超时没有收到 Pong (或任何服务端消息)
+//! 时判定连接失效, `receive_event` 返回 `keepalive timeout` 错误, 由 Agent 触发重连。
+//! NAT / 防火墙静默丢弃的连接因此能被及时发现, 而不是一直阻塞在接收上。
+
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
 use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
 use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{Message, protocol::CloseFrame},
-    MaybeTlsStream, WebSocketStream,
+    client_async,
+    tungstenite::{http::Uri, Message, protocol::CloseFrame},
+    WebSocketStream,
 };
-use tracing::{debug, error, info};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use atp_common::{AgreedVersion, MessageType};
+
+use crate::{Event, HeartbeatMessage, RawInputEvent, RegisterMessage, Result, VerifierError, VerifyResult};
+use super::tls::{TlsClientConfig, TransportStream};
+use super::{encode_message, parse_server_message, ServerMessage, VerifierTransport};
+
+/// 保活检测失败时 `receive_event` 返回的错误信息
+pub const KEEPALIVE_TIMEOUT: &str = "keepalive timeout";
+
+type WsSink = SplitSink<WebSocketStream<TransportStream>, Message>;
+type WsStream = SplitStream<WebSocketStream<TransportStream>>;
+
+/// 保活参数
+#[derive(Debug, Clone, Copy)]
+struct Keepalive {
+    /// Ping 发送间隔
+    interval: Duration,
+
+    /// 超过该时长没有收到服务端消息即判定连接失效
+    timeout: Duration,
+}
+
+/// 已建立的连接
+///
+/// 发送端由保活任务与本端共用; 连接被替换或丢弃时保活任务随之结束。
+struct Connection {
+    sink: Arc<Mutex<WsSink>>,
+    stream: WsStream,
+
+    /// 最近一次收到服务端消息 (包括 Pong) 的时间
+    last_seen: Arc<StdMutex<Instant>>,
+
+    /// 最近一次成功发送数据消息的时间 (不含 Ping)
+    last_sent: Instant,
+
+    /// 保活任务判定连接失效时取消
+    dead: CancellationToken,
+
+    keepalive_task: Option<JoinHandle<()>>,
+}
+
+impl Connection {
+    fn new(ws_stream: WebSocketStream<TransportStream>, keepalive: Option<Keepalive>) -> Self {
+        let (sink, stream) = ws_stream.split();
+        let sink = Arc::new(Mutex::new(sink));
+        let last_seen = Arc::new(StdMutex::new(Instant::now()));
+        let dead = CancellationToken::new();
+        let keepalive_task = keepalive.map(|keepalive| {
+            tokio::spawn(run_keepalive(keepalive, sink.clone(), last_seen.clone(), dead.clone()))
+        });
+
+        Self {
+            sink,
+            stream,
+            last_seen,
+            last_sent: Instant::now(),
+            dead,
+            keepalive_task,
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(task) = self.keepalive_task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// 保活任务: 定时发送 Ping, 超时没有收到服务端消息或发送失败时取消 `dead`
+async fn run_keepalive(keepalive: Keepalive, sink: Arc<Mutex<WsSink>>, last_seen: Arc<StdMutex<Instant>>, dead: CancellationToken) {
+    let start = tokio::time::Instant::now() + keepalive.interval;
+    let mut ticker = tokio::time::interval_at(start, keepalive.interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
-use crate::{Event, Result, VerifierError, VerifyResult};
-use super::VerifierTransport;
+    loop {
+        ticker.tick().await;
+
+        let silent = last_seen.lock().unwrap().elapsed();
+        if silent > keepalive.timeout {
+            warn!("{} ms 内没有收到服务端消息, 判定连接失效", silent.as_millis());
+            dead.cancel();
+            return;
+        }
+
+        // 发送缓冲区被占满 (对端已失联) 时发送会一直阻塞, 同样判定失效
+        let sent = tokio::time::timeout(keepalive.interval, async {
+            sink.lock().await.send(Message::Ping(Vec::new())).await
+        })
+        .await;
+        match sent {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                warn!("发送 Ping 失败, 判定连接失效: {}", e);
+                dead.cancel();
+                return;
+            }
+            Err(_) => {
+                warn!("发送 Ping 超时, 判定连接失效");
+                dead.cancel();
+                return;
+            }
+        }
+    }
+}
 
 /// WebSocket 传输实现
 pub struct WebSocketTransport {
-    ws_stream: Option<WebSocketStream<MaybeTlsStream<TcpStream>>>,
+    conn: Option<Connection>,
     endpoint: Option<String>,
+    tls: Option<TlsClientConfig>,
+    /// 与服务端协商出的协议版本 (收到 `accepted` 之前为裸消息)
+    agreed: AgreedVersion,
+    keepalive: Option<Keepalive>,
 }
 
 impl WebSocketTransport {
     /// 创建新的 WebSocket 传输
     pub fn new() -> Self {
         Self {
-            ws_stream: None,
+            conn: None,
             endpoint: None,
+            tls: None,
+            agreed: AgreedVersion::Legacy,
+            keepalive: None,
         }
     }
 
-    /// 检查连接是否存在
-    fn ensure_connected(&self) -> Result<()> {
-        if self.ws_stream.is_none() {
-            return Err(VerifierError::ConnectionFailed(
-                "未连接到服务器".to_string(),
-            ));
+    /// 开启保活: 每隔 `interval` 发送一次 Ping, 超过 `timeout` 没有收到服务端消息时判定连接失效
+    ///
+    /// `timeout` 应为 `interval` 的数倍, 以容忍个别 Pong 的延迟。
+    pub fn with_keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some(Keepalive { interval, timeout });
+        self
+    }
+
+    /// `wss://` 连接使用的 TLS 配置 (未配置时使用系统内置根证书),
+    /// 配置后不带协议前缀的地址默认使用 `wss://`
+    pub fn with_tls(mut self, tls: TlsClientConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// 规范化地址并建立底层连接 (wss 时完成 TLS 握手)
+    async fn open_stream(&self, endpoint: &str) -> Result<(String, TransportStream)> {
+        // 如果 endpoint 不包含协议，按是否配置 TLS 添加 wss:// 或 ws:// 前缀
+        let url = if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+            endpoint.to_string()
+        } else if self.tls.is_some() {
+            format!("wss://{}", endpoint)
+        } else {
+            format!("ws://{}", endpoint)
+        };
+
+        let uri: Uri = url.parse().map_err(|e| {
+            VerifierError::ConfigError(format!("无效的 WebSocket 地址 {}: {}", url, e))
+        })?;
+        let secure = uri.scheme_str() == Some("wss");
+        let host = uri
+            .host()
+            .ok_or_else(|| VerifierError::ConfigError(format!("WebSocket 地址缺少主机: {}", url)))?
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .to_string();
+        let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+
+        let stream = TcpStream::connect((host.as_str(), port)).await.map_err(|e| {
+            error!("WebSocket 连接失败: {}", e);
+            VerifierError::ConnectionFailed(format!("WebSocket 连接失败: {}", e))
+        })?;
+
+        let stream = if secure {
+            self.tls.clone().unwrap_or_default().connect(&host, stream).await?
+        } else {
+            TransportStream::Plain(stream)
+        };
+
+        Ok((url, stream))
+    }
+
+    /// 当前连接, 未连接时返回错误
+    fn connection(&mut self) -> Result<&mut Connection> {
+        self.conn
+            .as_mut()
+            .ok_or_else(|| VerifierError::ConnectionFailed("未连接到服务器".to_string()))
+    }
+
+    /// 发送一条数据消息; 保活任务已判定连接失效时立即返回
+    async fn send(&mut self, message: Message, what: &str) -> Result<()> {
+        let conn = self.connection()?;
+        let sent = {
+            let mut sink = conn.sink.lock().await;
+            tokio::select! {
+                sent = sink.send(message) => Some(sent),
+                _ = conn.dead.cancelled() => None,
+            }
+        };
+
+        match sent {
+            Some(Ok(())) => {
+                conn.last_sent = Instant::now();
+                Ok(())
+            }
+            Some(Err(e)) => {
+                error!("发送{}失败: {}", what, e);
+                Err(VerifierError::ConnectionFailed(format!("发送{}失败: {}", what, e)))
+            }
+            None => Err(VerifierError::ConnectionFailed(KEEPALIVE_TIMEOUT.to_string())),
         }
-        Ok(())
     }
 }
 
@@ -50,14 +245,11 @@ impl VerifierTransport for WebSocketTransport {
     async fn connect(&mut self, endpoint: &str, vm_id: Option<&str>) -> Result<()> {
         info!("连接到 WebSocket 服务器: {}", endpoint);
 
-        // 如果 endpoint 不包含协议，添加 ws:// 前缀
-        let url = if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
-            endpoint.to_string()
-        } else {
-            format!("ws://{}", endpoint)
-        };
+        // 重连时先结束旧连接的保活任务
+        self.conn = None;
+        let (url, stream) = self.open_stream(endpoint).await?;
 
-        match connect_async(&url).await {
+        match client_async(&url, stream).await {
             Ok((mut ws_stream, _)) => {
                 info!("成功连接到 WebSocket 服务器");
 
@@ -73,8 +265,9 @@ impl VerifierTransport for WebSocketTransport {
                         })?;
                 }
 
-                self.ws_stream = Some(ws_stream);
+                self.conn = Some(Connection::new(ws_stream, self.keepalive));
                 self.endpoint = Some(endpoint.to_string());
+                self.agreed = AgreedVersion::Legacy;
                 Ok(())
             }
             Err(e) => {
@@ -87,107 +280,140 @@ impl VerifierTransport for WebSocketTransport {
         }
     }
 
-    async fn send_result(&mut self, result: &VerifyResult) -> Result<()> {
-        self.ensure_connected()?;
+    async fn register(&mut self, registration: &RegisterMessage) -> Result<()> {
+        self.connection()?;
 
-        let json = serde_json::to_string(result).map_err(|e| {
-            VerifierError::ConnectionFailed(format!("序列化验证结果失败: {}", e))
+        let json = serde_json::to_string(registration).map_err(|e| {
+            VerifierError::ConnectionFailed(format!("序列化注册消息失败: {}", e))
         })?;
 
+        debug!("发送注册消息: {}", json);
+        self.send(Message::Text(json), "注册消息").await
+    }
+
+    async fn send_result(&mut self, result: &VerifyResult) -> Result<()> {
+        self.connection()?;
+
+        let json = encode_message(self.agreed, MessageType::Result, result, "验证结果")?;
+
         debug!("发送验证结果: {}", json);
+        self.send(Message::Text(json), "验证结果").await
+    }
 
-        if let Some(ws_stream) = &mut self.ws_stream {
-            ws_stream
-                .send(Message::Text(json))
-                .await
-                .map_err(|e| {
-                    error!("发送验证结果失败: {}", e);
-                    VerifierError::ConnectionFailed(format!("发送失败: {}", e))
-                })?;
-        }
+    async fn send_raw_input_event(&mut self, event: &RawInputEvent) -> Result<()> {
+        self.connection()?;
 
-        Ok(())
+        let json = encode_message(self.agreed, MessageType::RawInput, event, "输入事件")?;
+        self.send(Message::Text(json), "输入事件").await
+    }
+
+    async fn send_heartbeat(&mut self, heartbeat: &HeartbeatMessage) -> Result<()> {
+        self.connection()?;
+
+        let json = encode_message(self.agreed, MessageType::Heartbeat, heartbeat, "心跳")?;
+
+        debug!("发送心跳: {}", json);
+        self.send(Message::Text(json), "心跳").await
     }
 
     async fn receive_event(&mut self) -> Result<Event> {
-        self.ensure_connected()?;
-
-        if let Some(ws_stream) = &mut self.ws_stream {
-            loop {
-                match ws_stream.next().await {
-                    Some(Ok(msg)) => match msg {
-                        Message::Text(text) => {
-                            debug!("接收到事件: {}", text);
-                            let event: Event = serde_json::from_str(&text).map_err(|e| {
-                                error!("解析事件失败: {}", e);
-                                VerifierError::ConnectionFailed(format!("解析事件失败: {}", e))
-                            })?;
-                            return Ok(event);
-                        }
-                        Message::Binary(data) => {
-                            debug!("接收到二进制事件: {} bytes", data.len());
-                            let event: Event = serde_json::from_slice(&data).map_err(|e| {
-                                error!("解析二进制事件失败: {}", e);
-                                VerifierError::ConnectionFailed(format!("解析事件失败: {}", e))
-                            })?;
-                            return Ok(event);
-                        }
-                        Message::Ping(_) | Message::Pong(_) => {
-                            // 忽略心跳消息，继续接收下一条
-                            continue;
-                        }
-                        Message::Close(frame) => {
-                            let reason = frame
-                                .as_ref()
-                                .map(|f| f.reason.to_string())
-                                .unwrap_or_else(|| "未知原因".to_string());
-                            error!("WebSocket 连接已关闭: {}", reason);
-                            self.ws_stream = None;
-                            return Err(VerifierError::ConnectionFailed(format!(
-                                "连接已关闭: {}",
-                                reason
-                            )));
-                        }
-                        Message::Frame(_) => {
-                            // 底层帧，通常不会收到
-                            continue;
-                        }
-                    },
-                    Some(Err(e)) => {
-                        error!("接收消息失败: {}", e);
-                        self.ws_stream = None;
+        loop {
+            let conn = self.connection()?;
+            // 保活任务判定失效时立即返回, 不再等待永远不会到达的数据
+            let next = tokio::select! {
+                next = conn.stream.next() => Some(next),
+                _ = conn.dead.cancelled() => None,
+            };
+            let Some(next) = next else {
+                error!("保活检测失败, 连接已失效");
+                self.conn = None;
+                return Err(VerifierError::ConnectionFailed(KEEPALIVE_TIMEOUT.to_string()));
+            };
+            if let Some(Ok(_)) = &next {
+                *conn.last_seen.lock().unwrap() = Instant::now();
+            }
+
+            let text = match next {
+                Some(Ok(msg)) => match msg {
+                    Message::Text(text) => {
+                        debug!("接收到事件: {}", text);
+                        text
+                    }
+                    Message::Binary(data) => {
+                        debug!("接收到二进制事件: {} bytes", data.len());
+                        String::from_utf8(data).map_err(|e| {
+                            error!("解析二进制事件失败: {}", e);
+                            VerifierError::ConnectionFailed(format!("解析事件失败: {}", e))
+                        })?
+                    }
+                    Message::Ping(_) | Message::Pong(_) => {
+                        // 控制帧只用于保活，继续接收下一条
+                        continue;
+                    }
+                    Message::Close(frame) => {
+                        let reason = frame
+                            .as_ref()
+                            .map(|f| f.reason.to_string())
+                            .unwrap_or_else(|| "未知原因".to_string());
+                        error!("WebSocket 连接已关闭: {}", reason);
+                        self.conn = None;
                         return Err(VerifierError::ConnectionFailed(format!(
-                            "接收失败: {}",
-                            e
+                            "连接已关闭: {}",
+                            reason
                         )));
                     }
-                    None => {
-                        error!("WebSocket 流已关闭");
-                        self.ws_stream = None;
-                        return Err(VerifierError::ConnectionFailed(
-                            "连接已断开".to_string(),
-                        ));
+                    Message::Frame(_) => {
+                        // 底层帧，通常不会收到
+                        continue;
                     }
+                },
+                Some(Err(e)) => {
+                    error!("接收消息失败: {}", e);
+                    self.conn = None;
+                    return Err(VerifierError::ConnectionFailed(format!(
+                        "接收失败: {}",
+                        e
+                    )));
+                }
+                None => {
+                    error!("WebSocket 流已关闭");
+                    self.conn = None;
+                    return Err(VerifierError::ConnectionFailed(
+                        "连接已断开".to_string(),
+                    ));
+                }
+            };
+
+            match parse_server_message(&text)? {
+                ServerMessage::Event(event) => return Ok(event),
+                ServerMessage::Accepted(agreed) => self.agreed = agreed,
+                ServerMessage::Rejected(reason) => {
+                    error!("服务端拒绝连接: {}", reason);
+                    self.conn = None;
+                    return Err(VerifierError::ConnectionFailed(format!("连接被拒绝: {}", reason)));
                 }
+                ServerMessage::Ignored => {}
             }
         }
+    }
 
-        Err(VerifierError::ConnectionFailed("未连接".to_string()))
+    fn last_activity(&self) -> Option<Instant> {
+        self.conn.as_ref().map(|conn| conn.last_sent)
     }
 
     async fn disconnect(&mut self) -> Result<()> {
-        if let Some(mut ws_stream) = self.ws_stream.take() {
+        if let Some(conn) = self.conn.take() {
             info!("关闭 WebSocket 连接");
-            ws_stream
-                .close(Some(CloseFrame {
-                    code: tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode::Normal,
-                    reason: "正常关闭".into(),
-                }))
-                .await
-                .map_err(|e| {
-                    error!("关闭 WebSocket 连接失败: {}", e);
-                    VerifierError::ConnectionFailed(format!("关闭失败: {}", e))
-                })?;
+            let mut sink = conn.sink.lock().await;
+            sink.send(Message::Close(Some(CloseFrame {
+                code: tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode::Normal,
+                reason: "正常关闭".into(),
+            })))
+            .await
+            .map_err(|e| {
+                error!("关闭 WebSocket 连接失败: {}", e);
+                VerifierError::ConnectionFailed(format!("关闭失败: {}", e))
+            })?;
         }
         self.endpoint = None;
         Ok(())
@@ -197,17 +423,90 @@ impl VerifierTransport for WebSocketTransport {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use tokio::net::TcpListener;
 
     #[test]
     fn test_websocket_transport_creation() {
         let transport = WebSocketTransport::new();
-        assert!(transport.ws_stream.is_none());
+        assert!(transport.conn.is_none());
         assert!(transport.endpoint.is_none());
+        assert!(transport.tls.is_none());
+        assert!(transport.keepalive.is_none());
     }
 
     #[test]
     fn test_websocket_transport_default() {
         let transport = WebSocketTransport::default();
-        assert!(transport.ws_stream.is_none());
+        assert!(transport.conn.is_none());
+        assert!(transport.last_activity().is_none());
+    }
+
+    /// 启动只接受一个连接的服务端, `serve` 处理握手后的连接
+    async fn serve_once<F, Fut>(serve: F) -> String
+    where
+        F: FnOnce(WebSocketStream<TcpStream>) -> Fut + Send + 'static,
+        Fut: std::future::Future<Output = ()> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            serve(tokio_tungstenite::accept_async(stream).await.unwrap()).await;
+        });
+        addr.to_string()
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_detects_silent_server() {
+        // 服务端不再读取: Ping 得不到 Pong, 连接也没有关闭
+        let addr = serve_once(|ws| async move {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            drop(ws);
+        })
+        .await;
+
+        let mut transport =
+            WebSocketTransport::new().with_keepalive(Duration::from_millis(50), Duration::from_millis(200));
+        transport.connect(&addr, Some("vm-1")).await.unwrap();
+        assert!(transport.last_activity().is_some());
+
+        let err = tokio::time::timeout(Duration::from_secs(5), transport.receive_event())
+            .await
+            .expect("保活应在超时前判定连接失效")
+            .unwrap_err();
+        assert!(err.to_string().contains(KEEPALIVE_TIMEOUT), "{}", err);
+        assert!(transport.conn.is_none());
+        assert!(transport.send_heartbeat(&HeartbeatMessage::new("vm-1", 1)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keepalive_with_responsive_server() {
+        // 服务端持续读取 (自动回复 Pong), 空闲一段时间后才发送事件
+        let addr = serve_once(|ws| async move {
+            let (mut tx, mut rx) = ws.split();
+            let reader = tokio::spawn(async move { while let Some(Ok(_)) = rx.next().await {} });
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            let event = r#"{"event_type":"keyboard","data":{"key":"A"},"timestamp":0}"#;
+            tx.send(Message::Text(event.to_string())).await.unwrap();
+            let _ = reader.await;
+        })
+        .await;
+
+        let mut transport =
+            WebSocketTransport::new().with_keepalive(Duration::from_millis(50), Duration::from_millis(200));
+        transport.connect(&addr, Some("vm-1")).await.unwrap();
+
+        let connected_at = transport.last_activity().unwrap();
+        let event = tokio::time::timeout(Duration::from_secs(5), transport.receive_event())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(event.event_type, "keyboard");
+        // Ping 不计入数据消息的发送时间
+        assert_eq!(transport.last_activity(), Some(connected_at));
+
+        transport.send_heartbeat(&HeartbeatMessage::new("vm-1", 1)).await.unwrap();
+        assert!(transport.last_activity().unwrap() > connected_at);
+        transport.disconnect().await.unwrap();
     }
 }
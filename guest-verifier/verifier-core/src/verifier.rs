@@ -1,18 +1,148 @@
 //! 验证器接口
+//!
+//! 调用方通过 [`verify_with_timeout`] 执行验证: 超过验证器的超时时间后放弃等待,
+//! 生成 `verified=false`、`details.reason = "verifier timeout"` 的结果,
+//! 避免单个卡死的验证器拖垮事件处理。取消令牌在 Agent 优雅关闭时触发。
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
+use serde_json::json;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
 use crate::{Event, Result, VerifyResult};
 
+/// 验证器的默认超时
+pub const DEFAULT_VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum VerifierType {
     Keyboard,
     Mouse,
     Command,
+    Clipboard,
+    Display,
     Custom(String),
 }
 
+impl VerifierType {
+    /// 对应的事件类型名称
+    pub fn event_type(&self) -> &str {
+        match self {
+            VerifierType::Keyboard => "keyboard",
+            VerifierType::Mouse => "mouse",
+            VerifierType::Command => "command",
+            VerifierType::Clipboard => "clipboard",
+            VerifierType::Display => "display",
+            VerifierType::Custom(name) => name,
+        }
+    }
+}
+
 #[async_trait]
 pub trait Verifier: Send + Sync {
-    async fn verify(&self, event: Event) -> Result<VerifyResult>;
+    /// 执行验证; `cancel` 触发时应尽快返回 `VerifierError::Cancelled`
+    async fn verify(&self, event: Event, cancel: CancellationToken) -> Result<VerifyResult>;
+
     fn verifier_type(&self) -> VerifierType;
+
+    /// 单次验证的超时
+    fn timeout(&self) -> Duration {
+        DEFAULT_VERIFY_TIMEOUT
+    }
+}
+
+/// 在验证器的超时约束下执行验证
+///
+/// 超时时返回验证失败的结果而不是错误; 验证器自身的错误 (包括取消) 原样返回。
+pub async fn verify_with_timeout(
+    verifier: &dyn Verifier,
+    event: Event,
+    cancel: CancellationToken,
+) -> Result<VerifyResult> {
+    let limit = verifier.timeout();
+    let event_id = event
+        .data
+        .get("event_id")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let event_type = event.event_type.clone();
+
+    match tokio::time::timeout(limit, verifier.verify(event, cancel)).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!("验证器超时 ({}ms): type={}, event_id={}", limit.as_millis(), event_type, event_id);
+            Ok(VerifyResult {
+                event_id,
+                verified: false,
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as i64,
+                latency_ms: limit.as_millis() as u64,
+                details: json!({
+                    "reason": "verifier timeout",
+                    "timeout_ms": limit.as_millis() as u64,
+                    "event_type": event_type,
+                }),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VerifierError;
+
+    /// 一直等待直到被取消的验证器
+    struct StuckVerifier;
+
+    #[async_trait]
+    impl Verifier for StuckVerifier {
+        async fn verify(&self, _event: Event, cancel: CancellationToken) -> Result<VerifyResult> {
+            cancel.cancelled().await;
+            Err(VerifierError::Cancelled)
+        }
+
+        fn verifier_type(&self) -> VerifierType {
+            VerifierType::Custom("stuck".to_string())
+        }
+    }
+
+    fn event() -> Event {
+        Event {
+            event_type: "stuck".to_string(),
+            data: json!({"event_id": "e1"}),
+            timestamp: 0,
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_timeout_produces_failed_result() {
+        let result = verify_with_timeout(&StuckVerifier, event(), CancellationToken::new())
+            .await
+            .unwrap();
+
+        assert_eq!(result.event_id, "e1");
+        assert!(!result.verified);
+        assert_eq!(result.latency_ms, DEFAULT_VERIFY_TIMEOUT.as_millis() as u64);
+        assert_eq!(result.details["reason"], "verifier timeout");
+        assert_eq!(result.details["event_type"], "stuck");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancel_stops_verifier() {
+        let cancel = CancellationToken::new();
+        let token = cancel.clone();
+        let task = tokio::spawn(async move { verify_with_timeout(&StuckVerifier, event(), token).await });
+
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        cancel.cancel();
+
+        let result = task.await.unwrap();
+        assert!(matches!(result, Err(VerifierError::Cancelled)));
+    }
 }